@@ -1,13 +1,29 @@
 #[cfg(test)]
 use crate::ProcessEventWire;
+use crate::anomaly::{Ewma, SpikeConfig, SpikeDetector, SpikeOverride, SpikeSignal};
+use crate::collectors::filesystems::FilesystemStatus;
+use crate::config::AlertContextConfig;
+use crate::context::ContextStore;
+use crate::evidence::{Evidence, RecentEvents};
+use crate::exec_risk::{self, ExecPattern, ExecRiskMatcher};
+use crate::expr::{self, Expr, PROCESS_VARIABLES};
+use crate::fd_usage::FdUsage;
 use crate::handler::Handler;
+use crate::k8s::K8sMetadata;
+use crate::lineage::{self, AncestryCache, LineageEntry, LineagePredicate};
+use crate::maintenance::{MaintenanceManager, MaintenanceSubject, glob_match};
 use crate::metrics::Metrics;
+use crate::patterns::PatternSet;
+use crate::runtime::bpf_control::parse_event_type;
+use crate::script;
+use crate::signals::SignalRecord;
 use crate::{ProcessEvent, types::SystemSnapshot};
 use anyhow::{Context, anyhow};
 use async_trait::async_trait;
+use dashmap::DashMap;
 use serde::de::Deserializer;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::convert::TryFrom;
 use std::fs::OpenOptions;
 use std::io::Write;
@@ -61,9 +77,130 @@ pub struct Alert {
     pub severity: Severity,
     pub message: String,
     pub host: String,
+    /// `threshold` for fixed-limit rules, `anomaly` for learned baselines.
+    pub detection: &'static str,
+    /// Name of the maintenance window that suppressed this alert. Suppressed
+    /// alerts are still recorded but not sent to notifiers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suppressed_by: Option<String>,
+    /// Ancestry of the offending process, self first, captured when the
+    /// alert fired. Only set for rule alerts raised by a process event.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub lineage: Vec<LineageEntry>,
+    /// Youngest live children of the offending process.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<LineageEntry>,
+    /// Pod of the offending process.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pod: Option<K8sMetadata>,
+    /// Inputs the rule evaluated when it fired. Only set by the rules engine.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub evidence: Option<Box<Evidence>>,
+    /// Runbook, owner and labels copied from the rule that fired, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<Box<Annotations>>,
+}
+
+/// Operator-supplied rule metadata passed through to notifiers and the
+/// timeline. None of it affects evaluation.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Annotations {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runbook_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Team or rota responsible for the alert.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+}
+
+impl Annotations {
+    pub fn is_empty(&self) -> bool {
+        self.runbook_url.is_none()
+            && self.description.is_none()
+            && self.owner.is_none()
+            && self.labels.is_empty()
+    }
+}
+
+impl From<&Severity> for linnix_events::Severity {
+    fn from(severity: &Severity) -> Self {
+        match severity {
+            Severity::Info => Self::Info,
+            Severity::Low => Self::Low,
+            Severity::Medium => Self::Medium,
+            Severity::High => Self::High,
+        }
+    }
+}
+
+impl From<&Annotations> for linnix_events::Annotations {
+    fn from(annotations: &Annotations) -> Self {
+        Self {
+            runbook_url: annotations.runbook_url.clone(),
+            description: annotations.description.clone(),
+            owner: annotations.owner.clone(),
+            labels: annotations.labels.clone(),
+        }
+    }
+}
+
+impl From<&Alert> for linnix_events::Alert {
+    fn from(alert: &Alert) -> Self {
+        Self {
+            rule: alert.rule.clone(),
+            severity: (&alert.severity).into(),
+            message: alert.message.clone(),
+            host: alert.host.clone(),
+            detection: alert.detection.to_string(),
+            suppressed_by: alert.suppressed_by.clone(),
+            lineage: alert.lineage.iter().map(Into::into).collect(),
+            children: alert.children.iter().map(Into::into).collect(),
+            pod: alert.pod.as_ref().map(Into::into),
+            evidence: alert
+                .evidence
+                .as_ref()
+                .and_then(|e| serde_json::to_value(e).ok()),
+            annotations: alert.annotations.as_deref().map(Into::into),
+        }
+    }
 }
 
 impl Alert {
+    /// Labelled one-line summaries of the process context captured with the
+    /// alert (`Process`, `Children`, `Pod`), for plain-text notifiers.
+    pub fn process_context_lines(&self) -> Vec<(&'static str, String)> {
+        let mut lines = Vec::new();
+        let describe = |entries: &[LineageEntry], sep: &str| {
+            entries
+                .iter()
+                .map(lineage::describe_entry)
+                .collect::<Vec<_>>()
+                .join(sep)
+        };
+        if !self.lineage.is_empty() {
+            lines.push(("Process", describe(&self.lineage, " <- ")));
+        }
+        if !self.children.is_empty() {
+            lines.push(("Children", describe(&self.children, ", ")));
+        }
+        if let Some(pod) = &self.pod {
+            lines.push((
+                "Pod",
+                format!(
+                    "{}/{} ({})",
+                    pod.namespace, pod.pod_name, pod.container_name
+                ),
+            ));
+        }
+        if let Some(signal) = self.evidence.as_ref().and_then(|e| e.killed_by.as_ref()) {
+            lines.push(("Killed by", signal.describe()));
+        }
+        lines
+    }
+
     pub fn incident_context_line(&self) -> String {
         let mut message = self.message.replace(['\n', '\r'], " ");
         if message.len() > 256 {
@@ -79,6 +216,130 @@ impl Alert {
     }
 }
 
+/// Bound on the rule and subject keys `AlertSender` remembers for its cooldown.
+const MAX_COOLDOWN_KEYS: usize = 4096;
+
+/// Sending half of the alert channel, shared by the rules engine and every
+/// other alert source. Sending through it tags alerts covered by an active
+/// maintenance window, holds back repeats within the cooldown, if one is
+/// set, and counts them, whatever raised the alert.
+#[derive(Clone)]
+pub struct AlertSender {
+    tx: broadcast::Sender<Alert>,
+    metrics: Arc<Metrics>,
+    maintenance: Option<Arc<MaintenanceManager>>,
+    cooldown: Duration,
+    /// Rule, severity and subject to the end of their cooldown
+    recent: Arc<DashMap<String, Instant>>,
+}
+
+impl AlertSender {
+    pub fn new(capacity: usize, metrics: Arc<Metrics>) -> Self {
+        let (tx, _rx) = broadcast::channel(capacity);
+        Self {
+            tx,
+            metrics,
+            maintenance: None,
+            cooldown: Duration::ZERO,
+            recent: Arc::default(),
+        }
+    }
+
+    /// Tag alerts matching an active maintenance window as suppressed.
+    pub fn with_maintenance(mut self, maintenance: Arc<MaintenanceManager>) -> Self {
+        self.maintenance = Some(maintenance);
+        self
+    }
+
+    /// Hold back an alert sent within `cooldown` of another with the same
+    /// rule, severity and subject; zero sends them all. Rule alerts keep
+    /// the cooldown of their rule instead.
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Alert> {
+        self.tx.subscribe()
+    }
+
+    /// Send an alert raised outside the rules engine. Its subject is the
+    /// process it names first, else its pod, else its message. Returns
+    /// `false` when the cooldown held it back; such alerts are counted in
+    /// `alerts_held_back_total`.
+    pub fn send(&self, mut alert: Alert) -> bool {
+        if !self.cooldown.is_zero() {
+            let subject = match (alert.lineage.first(), &alert.pod) {
+                (Some(entry), _) => entry.comm.clone(),
+                (None, Some(pod)) => format!("{}/{}", pod.namespace, pod.pod_name),
+                (None, None) => alert.message.clone(),
+            };
+            let key = format!("{}:{}:{subject}", alert.rule, alert.severity.as_str());
+            let now = Instant::now();
+            if self.recent.len() >= MAX_COOLDOWN_KEYS {
+                self.recent.retain(|_, until| now < *until);
+            }
+            let mut until = self.recent.entry(key).or_insert(now);
+            if now < *until {
+                self.metrics.inc_alerts_held_back();
+                return false;
+            }
+            *until = now + self.cooldown;
+        }
+        let process = alert
+            .lineage
+            .first()
+            .map(|entry| (entry.pid, entry.comm.clone()));
+        self.tag(
+            &mut alert,
+            process.as_ref().map(|(pid, comm)| (*pid, comm.as_str())),
+        );
+        self.deliver(alert);
+        true
+    }
+
+    /// Set `suppressed_by` when an active maintenance window covers the
+    /// alert's rule, or the comm or namespace of `process`, the pid and
+    /// comm it was raised for.
+    fn tag(&self, alert: &mut Alert, process: Option<(u32, &str)>) {
+        let Some(mgr) = &self.maintenance else {
+            return;
+        };
+        let comm = process.map(|(_, comm)| comm);
+        let namespace = alert
+            .pod
+            .as_ref()
+            .map(|pod| pod.namespace.clone())
+            .or_else(|| process.and_then(|(pid, _)| mgr.namespace_for_pid(pid)));
+        let Some(window) = mgr.matching_window(&MaintenanceSubject {
+            rule: Some(&alert.rule),
+            comm,
+            namespace: namespace.as_deref(),
+        }) else {
+            return;
+        };
+        mgr.record_suppression(
+            &window,
+            "alert_suppressed",
+            format!(
+                "rule={} comm={} namespace={}",
+                alert.rule,
+                comm.unwrap_or("-"),
+                namespace.as_deref().unwrap_or("-")
+            ),
+        );
+        alert.suppressed_by = Some(window);
+    }
+
+    fn deliver(&self, alert: Alert) {
+        if alert.suppressed_by.is_some() {
+            self.metrics.inc_alerts_suppressed();
+        }
+        let _ = self.tx.send(alert);
+        self.metrics.inc_alerts_emitted();
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Detector {
     ForksPerSec {
@@ -122,6 +383,110 @@ pub enum Detector {
         #[allow(dead_code)]
         duration: u64,
     },
+    /// Per-pid EWMA baseline; fires on deviations beyond `k`·σ.
+    EwmaSpike {
+        signal: SpikeSignal,
+        k: f64,
+        alpha: f64,
+        warmup_samples: u32,
+        interval_secs: u64,
+        overrides: Vec<SpikeOverride>,
+    },
+    /// Matches exec command lines against high-risk signatures.
+    ExecRisk {
+        matcher: ExecRiskMatcher,
+    },
+    /// Fires on each exec whose comm matches one of `comms` and whose
+    /// interpreter script name matches one of `scripts` (globs; empty matches
+    /// any). Usually paired with a lineage condition.
+    ProcessExec {
+        comms: PatternSet,
+        scripts: PatternSet,
+    },
+    /// Exec-to-first-listen latency of services matching `comms` and
+    /// `scripts` as for `ProcessExec`. Fires above `threshold_ms`, or once
+    /// `min_samples` startups are known, when a startup takes more than
+    /// `regression_factor` times the service's EWMA baseline (keyed by script
+    /// name for interpreters, comm otherwise).
+    StartupLatency {
+        comms: PatternSet,
+        scripts: PatternSet,
+        threshold_ms: Option<u64>,
+        regression_factor: f64,
+        min_samples: u32,
+    },
+    /// Filesystems whose mount point matches one of `mounts` (globs; empty
+    /// matches all) at or above `used_pct`, or predicted to fill within
+    /// `hours_to_full`. Evaluated after each filesystem sample.
+    FilesystemUsage {
+        mounts: Vec<String>,
+        used_pct: Option<f64>,
+        hours_to_full: Option<f64>,
+    },
+    /// Dead-man switch: fires when no event of a type in `events` (names as
+    /// in `/events?type=`) from a process matching `comms` and `scripts` (as
+    /// for `ProcessExec`) has been seen for `within_secs`, and once more, at
+    /// info severity, when that activity resumes. Checked by the timer from
+    /// [`RuleEngine::spawn_absence_timer`].
+    Absence {
+        events: Vec<String>,
+        comms: PatternSet,
+        scripts: PatternSet,
+        within_secs: u64,
+    },
+    /// Fires on each event of a type in `events` (any when empty) whose
+    /// process satisfies the rule's `condition`.
+    ProcessCondition {
+        events: Vec<String>,
+    },
+    /// Fires on `fd_pressure` events at or above `used_pct` of the process's
+    /// `RLIMIT_NOFILE` (any report when unset), and always when an fd
+    /// allocation failed.
+    FdPressure {
+        used_pct: Option<f64>,
+    },
+    /// Fires on `signal` events for the signal numbers in `signals` (any
+    /// reported signal when empty). The event's process is the sender.
+    Signal {
+        signals: Vec<u32>,
+    },
+}
+
+impl Detector {
+    /// Anomaly detectors learn a baseline instead of using fixed thresholds.
+    pub fn detection(&self) -> &'static str {
+        match self {
+            Detector::EwmaSpike { .. } => "anomaly",
+            Detector::ExecRisk { .. } => "signature",
+            _ => "threshold",
+        }
+    }
+
+    /// These bypass the per-rule cooldown that collapses repeated alerts.
+    /// Exec risk matches are distinct security events, each alerted.
+    /// Absence rules fire once per silence and once on resuming, so the
+    /// cooldown would only hide the resume.
+    pub fn dedup_exempt(&self) -> bool {
+        matches!(self, Detector::ExecRisk { .. } | Detector::Absence { .. })
+    }
+
+    /// Detectors that evaluate one process at a time and so can be gated on
+    /// that process's lineage. Aggregate rate detectors cannot.
+    pub fn per_process(&self) -> bool {
+        matches!(
+            self,
+            Detector::RunawayTree { .. }
+                | Detector::SubtreeCpuPct { .. }
+                | Detector::SubtreeRssMb { .. }
+                | Detector::EwmaSpike { .. }
+                | Detector::ExecRisk { .. }
+                | Detector::ProcessExec { .. }
+                | Detector::StartupLatency { .. }
+                | Detector::ProcessCondition { .. }
+                | Detector::FdPressure { .. }
+                | Detector::Signal { .. }
+        )
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -130,6 +495,12 @@ pub struct RuleConfig {
     pub severity: Severity,
     pub cooldown: u64,
     pub detector: Detector,
+    /// Only evaluate events whose ancestors match.
+    pub lineage: Option<LineagePredicate>,
+    /// Only evaluate events whose process satisfies this boolean expression
+    /// over [`PROCESS_VARIABLES`].
+    pub condition: Option<Expr>,
+    pub annotations: Annotations,
 }
 
 struct Rule {
@@ -138,6 +509,16 @@ struct Rule {
 
 const DEFAULT_COOLDOWN_SECS: u64 = 60;
 const DEFAULT_SHORT_JOB_DURATION_MS: u64 = 1000;
+const DEFAULT_SPIKE_K: f64 = 4.0;
+const DEFAULT_SPIKE_ALPHA: f64 = 0.1;
+const DEFAULT_SPIKE_WARMUP_SAMPLES: u32 = 30;
+const DEFAULT_SPIKE_INTERVAL_SECS: u64 = 1;
+const DEFAULT_STARTUP_REGRESSION_FACTOR: f64 = 2.0;
+const DEFAULT_STARTUP_MIN_SAMPLES: u32 = 5;
+const STARTUP_BASELINE_ALPHA: f64 = 0.3;
+const MAX_STARTUP_BASELINES: usize = 4096;
+const MIN_ABSENCE_CHECK_SECS: u64 = 1;
+const MAX_ABSENCE_CHECK_SECS: u64 = 60;
 
 #[derive(Debug, Deserialize)]
 struct RawRule {
@@ -146,6 +527,18 @@ struct RawRule {
     severity: Option<String>,
     #[serde(default)]
     cooldown: Option<u64>,
+    #[serde(default)]
+    lineage: Option<LineagePredicate>,
+    #[serde(default)]
+    condition: Option<String>,
+    #[serde(default)]
+    runbook_url: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    owner: Option<String>,
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
     #[serde(flatten)]
     detector: RawDetector,
 }
@@ -188,21 +581,163 @@ enum RawDetector {
         threshold: u64,
         duration: u64,
     },
+    EwmaSpike {
+        signal: SpikeSignal,
+        #[serde(default = "default_spike_k")]
+        k: f64,
+        #[serde(default = "default_spike_alpha")]
+        alpha: f64,
+        #[serde(default = "default_spike_warmup_samples")]
+        warmup_samples: u32,
+        #[serde(default = "default_spike_interval_secs")]
+        interval_secs: u64,
+        #[serde(default)]
+        overrides: Vec<SpikeOverride>,
+    },
+    ExecRisk {
+        #[serde(default = "default_true")]
+        builtin: bool,
+        #[serde(default)]
+        patterns: Vec<ExecPattern>,
+    },
+    ProcessExec {
+        #[serde(default)]
+        comms: Vec<String>,
+        #[serde(default)]
+        scripts: Vec<String>,
+    },
+    StartupLatency {
+        #[serde(default)]
+        comms: Vec<String>,
+        #[serde(default)]
+        scripts: Vec<String>,
+        #[serde(default)]
+        threshold_ms: Option<u64>,
+        #[serde(default = "default_startup_regression_factor")]
+        regression_factor: f64,
+        #[serde(default = "default_startup_min_samples")]
+        min_samples: u32,
+    },
+    FilesystemUsage {
+        #[serde(default)]
+        mounts: Vec<String>,
+        #[serde(default)]
+        used_pct: Option<f64>,
+        #[serde(default)]
+        hours_to_full: Option<f64>,
+    },
+    Absence {
+        #[serde(default = "default_absence_events")]
+        events: Vec<String>,
+        #[serde(default)]
+        comms: Vec<String>,
+        #[serde(default)]
+        scripts: Vec<String>,
+        within_secs: u64,
+    },
+    ProcessCondition {
+        #[serde(default)]
+        events: Vec<String>,
+    },
+    FdPressure {
+        #[serde(default)]
+        used_pct: Option<f64>,
+    },
+    Signal {
+        #[serde(default)]
+        signals: Vec<String>,
+    },
+}
+
+fn default_true() -> bool {
+    true
 }
 
 fn default_short_job_duration_ms() -> u64 {
     DEFAULT_SHORT_JOB_DURATION_MS
 }
 
+fn default_spike_k() -> f64 {
+    DEFAULT_SPIKE_K
+}
+
+fn default_spike_alpha() -> f64 {
+    DEFAULT_SPIKE_ALPHA
+}
+
+fn default_spike_warmup_samples() -> u32 {
+    DEFAULT_SPIKE_WARMUP_SAMPLES
+}
+
+fn default_spike_interval_secs() -> u64 {
+    DEFAULT_SPIKE_INTERVAL_SECS
+}
+
+fn default_startup_regression_factor() -> f64 {
+    DEFAULT_STARTUP_REGRESSION_FACTOR
+}
+
+fn default_startup_min_samples() -> u32 {
+    DEFAULT_STARTUP_MIN_SAMPLES
+}
+
+fn default_absence_events() -> Vec<String> {
+    vec!["exec".into()]
+}
+
+/// `comms` globs match the task name, `scripts` globs the interpreter script
+/// name; an empty list matches anything, but `scripts` needs a resolved script.
+fn matches_process(
+    comms: &PatternSet,
+    scripts: &PatternSet,
+    comm: &str,
+    event: &ProcessEvent,
+) -> bool {
+    if !comms.is_empty() && !comms.is_match(comm) {
+        return false;
+    }
+    scripts.is_empty()
+        || event
+            .script_path
+            .as_deref()
+            .is_some_and(|path| scripts.is_match(script::script_name(path)))
+}
+
+/// `comms` and `scripts` of a rule for messages.
+fn describe_process(comms: &PatternSet, scripts: &PatternSet) -> String {
+    let (comms, scripts) = (comms.patterns(), scripts.patterns());
+    match (comms.is_empty(), scripts.is_empty()) {
+        (true, true) => "any process".into(),
+        (false, true) => comms.join("/"),
+        (true, false) => scripts.join("/"),
+        (false, false) => format!("{} running {}", comms.join("/"), scripts.join("/")),
+    }
+}
+
+/// Compile a rule's `comms` and `scripts` globs.
+fn process_globs(
+    rule: &str,
+    comms: &[String],
+    scripts: &[String],
+) -> anyhow::Result<(PatternSet, PatternSet)> {
+    let comms = PatternSet::globs(comms).with_context(|| format!("rule {rule}: comms"))?;
+    let scripts = PatternSet::globs(scripts).with_context(|| format!("rule {rule}: scripts"))?;
+    Ok((comms, scripts))
+}
+
 impl TryFrom<RawRule> for RuleConfig {
     type Error = anyhow::Error;
 
     fn try_from(value: RawRule) -> Result<Self, Self::Error> {
+        let default_severity = match value.detector {
+            RawDetector::ExecRisk { .. } => Severity::High,
+            _ => Severity::Info,
+        };
         let severity = value
             .severity
             .as_deref()
             .map(Severity::from_str)
-            .unwrap_or(Severity::Info);
+            .unwrap_or(default_severity);
         let cooldown = value.cooldown.unwrap_or(DEFAULT_COOLDOWN_SECS);
 
         let detector = match value.detector {
@@ -266,13 +801,180 @@ impl TryFrom<RawRule> for RuleConfig {
                 threshold,
                 duration,
             },
+            RawDetector::EwmaSpike {
+                signal,
+                k,
+                alpha,
+                warmup_samples,
+                interval_secs,
+                overrides,
+            } => {
+                if !(alpha > 0.0 && alpha <= 1.0) {
+                    return Err(anyhow!(
+                        "rule {}: alpha must be in (0, 1], got {alpha}",
+                        value.name
+                    ));
+                }
+                if k <= 0.0 || interval_secs == 0 {
+                    return Err(anyhow!(
+                        "rule {}: k and interval_secs must be positive",
+                        value.name
+                    ));
+                }
+                Detector::EwmaSpike {
+                    signal,
+                    k,
+                    alpha,
+                    warmup_samples,
+                    interval_secs,
+                    overrides,
+                }
+            }
+            RawDetector::ExecRisk { builtin, patterns } => {
+                let matcher = ExecRiskMatcher::new(builtin, &patterns)
+                    .with_context(|| format!("rule {}", value.name))?;
+                if matcher.is_empty() {
+                    return Err(anyhow!(
+                        "rule {}: builtin signatures disabled and no patterns given",
+                        value.name
+                    ));
+                }
+                Detector::ExecRisk { matcher }
+            }
+            RawDetector::ProcessExec { comms, scripts } => {
+                let (comms, scripts) = process_globs(&value.name, &comms, &scripts)?;
+                Detector::ProcessExec { comms, scripts }
+            }
+            RawDetector::StartupLatency {
+                comms,
+                scripts,
+                threshold_ms,
+                regression_factor,
+                min_samples,
+            } => {
+                if regression_factor <= 1.0 {
+                    return Err(anyhow!(
+                        "rule {}: regression_factor must be greater than 1",
+                        value.name
+                    ));
+                }
+                let (comms, scripts) = process_globs(&value.name, &comms, &scripts)?;
+                Detector::StartupLatency {
+                    comms,
+                    scripts,
+                    threshold_ms,
+                    regression_factor,
+                    min_samples: min_samples.max(1),
+                }
+            }
+            RawDetector::FilesystemUsage {
+                mounts,
+                used_pct,
+                hours_to_full,
+            } => {
+                if used_pct.is_none() && hours_to_full.is_none() {
+                    return Err(anyhow!(
+                        "rule {}: set used_pct, hours_to_full or both",
+                        value.name
+                    ));
+                }
+                Detector::FilesystemUsage {
+                    mounts,
+                    used_pct,
+                    hours_to_full,
+                }
+            }
+            RawDetector::Absence {
+                events,
+                comms,
+                scripts,
+                within_secs,
+            } => {
+                if within_secs == 0 {
+                    return Err(anyhow!("rule {}: within_secs must be positive", value.name));
+                }
+                if events.is_empty() {
+                    return Err(anyhow!("rule {}: events must not be empty", value.name));
+                }
+                if let Some(unknown) = events.iter().find(|e| parse_event_type(e).is_none()) {
+                    return Err(anyhow!(
+                        "rule {}: unknown event type '{unknown}'",
+                        value.name
+                    ));
+                }
+                let (comms, scripts) = process_globs(&value.name, &comms, &scripts)?;
+                Detector::Absence {
+                    events,
+                    comms,
+                    scripts,
+                    within_secs,
+                }
+            }
+            RawDetector::ProcessCondition { events } => {
+                if value.condition.is_none() {
+                    return Err(anyhow!(
+                        "rule {}: process_condition needs a condition",
+                        value.name
+                    ));
+                }
+                if let Some(unknown) = events.iter().find(|e| parse_event_type(e).is_none()) {
+                    return Err(anyhow!(
+                        "rule {}: unknown event type '{unknown}'",
+                        value.name
+                    ));
+                }
+                Detector::ProcessCondition { events }
+            }
+            RawDetector::FdPressure { used_pct } => {
+                if used_pct.is_some_and(|pct| !(pct > 0.0 && pct <= 100.0)) {
+                    return Err(anyhow!("rule {}: used_pct must be in (0, 100]", value.name));
+                }
+                Detector::FdPressure { used_pct }
+            }
+            RawDetector::Signal { signals } => {
+                let signals = signals
+                    .iter()
+                    .map(|name| {
+                        linnix_ai_ebpf_common::signal_event::number(name)
+                            .ok_or_else(|| anyhow!("rule {}: unknown signal '{name}'", value.name))
+                    })
+                    .collect::<anyhow::Result<_>>()?;
+                Detector::Signal { signals }
+            }
         };
 
+        if value.lineage.is_some() && !detector.per_process() {
+            return Err(anyhow!(
+                "rule {}: lineage conditions need a per-process detector",
+                value.name
+            ));
+        }
+        if value.condition.is_some() && !detector.per_process() {
+            return Err(anyhow!(
+                "rule {}: conditions need a per-process detector",
+                value.name
+            ));
+        }
+        let condition = value
+            .condition
+            .as_deref()
+            .map(|source| expr::compile(source, PROCESS_VARIABLES, Some(expr::Type::Bool)))
+            .transpose()
+            .map_err(|e| anyhow!("rule {}: invalid condition: {e:#}", value.name))?;
+
         Ok(RuleConfig {
             name: value.name,
             severity,
             cooldown,
             detector,
+            lineage: value.lineage,
+            condition,
+            annotations: Annotations {
+                runbook_url: value.runbook_url,
+                description: value.description,
+                owner: value.owner,
+                labels: value.labels,
+            },
         })
     }
 }
@@ -286,34 +988,43 @@ struct RuleState {
     cpu_exceed: HashMap<String, Instant>,
     rss_exceed: HashMap<String, Instant>,
     active: HashMap<String, Instant>,
+    spikes: HashMap<String, SpikeDetector>,
+    ancestry: AncestryCache,
+    /// Startup latency baselines in ms, keyed by rule and comm.
+    startup_baselines: HashMap<(String, String), Ewma>,
+    /// Recent fork/exec/exit events for alert evidence.
+    recent: RecentEvents,
+    /// Per absence rule, started on first check or match.
+    absence: HashMap<String, AbsenceTimer>,
 }
 
-pub struct RuleEngine {
+/// When an absence rule last saw its activity and whether it has fired.
+struct AbsenceTimer {
+    last_seen: Instant,
+    raised: bool,
+}
+
+impl AbsenceTimer {
+    fn new(now: Instant) -> Self {
+        Self {
+            last_seen: now,
+            raised: false,
+        }
+    }
+}
+
+/// Loaded rules and the event history they need, replaced as a whole by
+/// [`RuleEngine::reload`].
+struct RuleSet {
     rules: Vec<Rule>,
-    state: Mutex<RuleState>,
-    tx: broadcast::Sender<Alert>,
-    alerts_file: String,
-    journald: bool,
-    host: String,
     fork_window_secs: u64,
     exec_window_secs: u64,
     completion_window_secs: u64,
     runaway_window_secs: u64,
-    metrics: Arc<Metrics>,
-    total_memory_bytes: Option<u64>,
 }
 
-impl RuleEngine {
-    pub fn from_path(
-        path: &str,
-        alerts_file: String,
-        journald: bool,
-        metrics: Arc<Metrics>,
-    ) -> anyhow::Result<Self> {
-        let text = std::fs::read_to_string(path)?;
-        let hint = Path::new(path).extension().and_then(|ext| ext.to_str());
-        let cfgs = parse_rules(&text, hint)?;
-
+impl RuleSet {
+    fn new(cfgs: Vec<RuleConfig>) -> Self {
         let mut fork_window_secs = 0u64;
         let exec_window_secs = 60u64;
         let mut completion_window_secs = 60u64;
@@ -351,8 +1062,54 @@ impl RuleEngine {
             completion_window_secs = 60;
         }
 
-        let rules = cfgs.into_iter().map(|cfg| Rule { cfg }).collect();
-        let (tx, _rx) = broadcast::channel(128);
+        Self {
+            rules: cfgs.into_iter().map(|cfg| Rule { cfg }).collect(),
+            fork_window_secs,
+            exec_window_secs,
+            completion_window_secs,
+            runaway_window_secs,
+        }
+    }
+}
+
+pub struct RuleEngine {
+    rules: std::sync::RwLock<Arc<RuleSet>>,
+    state: Mutex<RuleState>,
+    tx: AlertSender,
+    alerts_file: String,
+    journald: bool,
+    host: String,
+    metrics: Arc<Metrics>,
+    total_memory_bytes: Option<u64>,
+    context: Option<Arc<ContextStore>>,
+    alert_context: AlertContextConfig,
+}
+
+impl RuleEngine {
+    pub fn from_path(
+        path: &str,
+        alerts_file: String,
+        journald: bool,
+        metrics: Arc<Metrics>,
+    ) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let hint = Path::new(path).extension().and_then(|ext| ext.to_str());
+        Self::from_text(&text, hint, alerts_file, journald, metrics)
+    }
+
+    /// Rules from a YAML or TOML document, e.g. one synced from a ConfigMap.
+    /// `hint` is the file extension it came with, if any.
+    pub fn from_text(
+        text: &str,
+        hint: Option<&str>,
+        alerts_file: String,
+        journald: bool,
+        metrics: Arc<Metrics>,
+    ) -> anyhow::Result<Self> {
+        let cfgs = parse_rules(text, hint)?;
+        let spikes = spike_detectors(&cfgs);
+        let rules = RuleSet::new(cfgs);
+        let tx = AlertSender::new(128, Arc::clone(&metrics));
         let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown".into());
         let mut sys = System::new_all();
         sys.refresh_memory();
@@ -361,7 +1118,7 @@ impl RuleEngine {
             kb => Some(kb.saturating_mul(1024)),
         };
         Ok(Self {
-            rules,
+            rules: std::sync::RwLock::new(Arc::new(rules)),
             state: Mutex::new(RuleState {
                 fork_events: VecDeque::new(),
                 exec_events: VecDeque::new(),
@@ -371,51 +1128,359 @@ impl RuleEngine {
                 cpu_exceed: HashMap::new(),
                 rss_exceed: HashMap::new(),
                 active: HashMap::new(),
+                spikes,
+                ancestry: AncestryCache::default(),
+                startup_baselines: HashMap::new(),
+                recent: RecentEvents::default(),
+                absence: HashMap::new(),
             }),
             tx,
             alerts_file,
             journald,
             host,
-            fork_window_secs,
-            exec_window_secs,
-            completion_window_secs,
-            runaway_window_secs,
             metrics,
             total_memory_bytes,
+            context: None,
+            alert_context: AlertContextConfig::default(),
         })
     }
 
-    pub fn broadcaster(&self) -> broadcast::Sender<Alert> {
+    /// Tag alerts matching an active maintenance window as suppressed.
+    pub fn with_maintenance(mut self, maintenance: Arc<MaintenanceManager>) -> Self {
+        self.tx = self.tx.with_maintenance(maintenance);
+        self
+    }
+
+    /// Resolve process lineage from the live process table before falling
+    /// back to procfs.
+    pub fn with_context_store(mut self, context: Arc<ContextStore>) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// How much process context alerts capture when they fire.
+    pub fn with_alert_context(mut self, config: AlertContextConfig) -> Self {
+        self.alert_context = config;
+        self
+    }
+
+    pub fn broadcaster(&self) -> AlertSender {
         self.tx.clone()
     }
 
     pub fn rule_count(&self) -> usize {
-        self.rules.len()
+        self.rules().rules.len()
+    }
+
+    fn rules(&self) -> Arc<RuleSet> {
+        Arc::clone(&self.rules.read().unwrap())
     }
 
-    async fn emit_alert(&self, rule: &RuleConfig, message: String) {
-        let key = format!("{}:{}", self.host, rule.name);
+    /// Replace every rule with those in `text`. The current rules stay
+    /// loaded when it does not parse. Cooldowns carry over by rule name;
+    /// spike baselines and absence windows start over. Returns the new rule
+    /// count.
+    pub async fn reload(&self, text: &str, hint: Option<&str>) -> anyhow::Result<usize> {
+        let cfgs = parse_rules(text, hint)?;
+        let spikes = spike_detectors(&cfgs);
+        let rules = Arc::new(RuleSet::new(cfgs));
+        let count = rules.rules.len();
+        let mut state = self.state.lock().await;
+        state.spikes = spikes;
+        state.absence.clear();
+        let previous = std::mem::replace(&mut *self.rules.write().unwrap(), rules);
+        drop(state);
+        self.metrics.remove_active_rules(previous.rules.len());
+        self.metrics.add_active_rules(count);
+        Ok(count)
+    }
+
+    /// Evaluate `filesystem_usage` rules against the latest sample of every
+    /// mount. One alert per rule lists all matching filesystems, fullest first.
+    pub async fn on_filesystems(&self, filesystems: &[FilesystemStatus]) {
+        for rule in &self.rules().rules {
+            let Detector::FilesystemUsage {
+                mounts,
+                used_pct,
+                hours_to_full,
+            } = &rule.cfg.detector
+            else {
+                continue;
+            };
+            let limit_secs = hours_to_full.map(|hours| hours * 3600.0);
+            let mut hits: Vec<&FilesystemStatus> = filesystems
+                .iter()
+                .filter(|fs| {
+                    mounts.is_empty()
+                        || mounts
+                            .iter()
+                            .any(|pattern| glob_match(pattern, &fs.mount_point))
+                })
+                .filter(|fs| {
+                    used_pct.is_some_and(|threshold| fs.used_pct >= threshold)
+                        || limit_secs
+                            .zip(fs.secs_to_full)
+                            .is_some_and(|(limit, secs)| secs as f64 <= limit)
+                })
+                .collect();
+            hits.sort_by(|a, b| b.used_pct.total_cmp(&a.used_pct));
+            let Some(fullest) = hits.first() else {
+                continue;
+            };
+            let mut evidence = Evidence::default()
+                .observed("used_pct", fullest.used_pct)
+                .observed("filesystems", hits.len() as f64);
+            if let Some(secs) = fullest.secs_to_full {
+                evidence = evidence.observed("hours_to_full", secs as f64 / 3600.0);
+            }
+            if let Some(threshold) = used_pct {
+                evidence = evidence.threshold("used_pct", *threshold);
+            }
+            if let Some(hours) = hours_to_full {
+                evidence = evidence.threshold("hours_to_full", *hours);
+            }
+            let message = hits
+                .iter()
+                .map(|fs| fs.describe())
+                .collect::<Vec<_>>()
+                .join("; ");
+            self.emit_alert(&rule.cfg, None, message, evidence).await;
+        }
+    }
+
+    /// Fire `absence` rules whose activity has been missing for longer than
+    /// `within_secs`. Once per silence; a matching event re-arms the rule.
+    pub async fn check_absence(&self) {
+        let now = Instant::now();
+        for rule in &self.rules().rules {
+            let Detector::Absence {
+                events,
+                comms,
+                scripts,
+                within_secs,
+            } = &rule.cfg.detector
+            else {
+                continue;
+            };
+            let silent = {
+                let mut state = self.state.lock().await;
+                let timer = state
+                    .absence
+                    .entry(rule.cfg.name.clone())
+                    .or_insert_with(|| AbsenceTimer::new(now));
+                let silent = now.saturating_duration_since(timer.last_seen);
+                if timer.raised || silent < Duration::from_secs(*within_secs) {
+                    continue;
+                }
+                timer.raised = true;
+                silent
+            };
+            let evidence = Evidence::default()
+                .threshold("within_secs", *within_secs as f64)
+                .observed("silent_secs", silent.as_secs() as f64);
+            let message = format!(
+                "no {} from {} for {}s (expected within {}s)",
+                events.join("/"),
+                describe_process(comms, scripts),
+                silent.as_secs(),
+                within_secs
+            );
+            self.emit_alert(&rule.cfg, None, message, evidence).await;
+        }
+    }
+
+    /// Interval between absence checks: a tenth of the shortest window,
+    /// within 1-60s. `None` without absence rules.
+    fn absence_check_interval(&self) -> Option<Duration> {
+        self.rules()
+            .rules
+            .iter()
+            .filter_map(|rule| match &rule.cfg.detector {
+                Detector::Absence { within_secs, .. } => Some(*within_secs / 10),
+                _ => None,
+            })
+            .min()
+            .map(|secs| {
+                Duration::from_secs(secs.clamp(MIN_ABSENCE_CHECK_SECS, MAX_ABSENCE_CHECK_SECS))
+            })
+    }
+
+    /// Start the timer that evaluates absence rules. The first check starts
+    /// every rule's window. The interval follows [`Self::reload`]; without
+    /// absence rules the timer idles at the longest interval.
+    pub fn spawn_absence_timer(self: &Arc<Self>) {
+        let engine = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                engine.check_absence().await;
+                let interval = engine
+                    .absence_check_interval()
+                    .unwrap_or(Duration::from_secs(MAX_ABSENCE_CHECK_SECS));
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    /// Expression variable `name` for the process of `event`, preferring
+    /// the live process table, which carries the latest CPU and memory.
+    fn process_variable(&self, name: &str, event: &ProcessEvent) -> Option<f64> {
+        let context = self.context.as_deref();
+        match context.and_then(|ctx| ctx.get_process_by_pid(event.pid)) {
+            Some(live) => expr::process_variable(name, &live, context),
+            None => expr::process_variable(name, event, context),
+        }
+    }
+
+    /// Match the command line of an exec against `matcher`. The procfs
+    /// reads run on the blocking pool.
+    async fn check_exec_risk(
+        &self,
+        rule: &RuleConfig,
+        matcher: &ExecRiskMatcher,
+        event: &ProcessEvent,
+    ) {
+        let pid = event.pid;
+        let Ok(Some(cmdline)) =
+            tokio::task::spawn_blocking(move || exec_risk::read_cmdline(pid)).await
+        else {
+            return;
+        };
+        let Some(signature) = matcher.find(&cmdline) else {
+            return;
+        };
+        let context = self.context.clone();
+        let chain = tokio::task::spawn_blocking(move || lineage::ancestry(pid, context.as_deref()))
+            .await
+            .unwrap_or_default();
+        let mut shown = cmdline;
+        if shown.len() > 512 {
+            let mut end = 512;
+            while !shown.is_char_boundary(end) {
+                end -= 1;
+            }
+            shown.truncate(end);
+        }
+        log::warn!(
+            "[rules] detector=exec_risk rule={} signature={} pid={} lineage={}",
+            rule.name,
+            signature.id,
+            pid,
+            lineage::format_lineage(&chain)
+        );
+        let message = format!(
+            "{} ({}): `{}` via {}",
+            signature.description,
+            signature.id,
+            shown,
+            lineage::format_lineage(&chain)
+        );
+        self.emit_alert_with_lineage(rule, Some(event), message, chain, Evidence::new(event))
+            .await;
+    }
+
+    /// Whether an alert under `key` is still within its cooldown; otherwise
+    /// starts a new one of `cooldown` seconds.
+    async fn cooling_down(&self, key: String, cooldown: u64) -> bool {
         let mut state = self.state.lock().await;
         let now = Instant::now();
         if let Some(until) = state.active.get(&key)
             && now <= *until
         {
-            return;
+            return true;
         }
-        let cooldown = if rule.cooldown == 0 {
+        let cooldown = if cooldown == 0 {
             Duration::from_millis(100)
         } else {
-            Duration::from_secs(rule.cooldown)
+            Duration::from_secs(cooldown)
         };
-        state.active.insert(key.clone(), now + cooldown);
-        drop(state);
+        state.active.insert(key, now + cooldown);
+        false
+    }
+
+    async fn emit_alert(
+        &self,
+        rule: &RuleConfig,
+        event: Option<&ProcessEvent>,
+        message: String,
+        evidence: Evidence,
+    ) {
+        self.emit_alert_with_lineage(rule, event, message, Vec::new(), evidence)
+            .await;
+    }
+
+    /// Ancestors, children and pod of the process behind `event`, so the
+    /// alert stays readable after the process is gone. Detectors that
+    /// resolved `lineage` themselves keep theirs, trimmed to the limit.
+    fn process_context(
+        &self,
+        event: Option<&ProcessEvent>,
+        mut lineage: Vec<LineageEntry>,
+    ) -> (Vec<LineageEntry>, Vec<LineageEntry>, Option<K8sMetadata>) {
+        let Some(event) = event else {
+            return (lineage, Vec::new(), None);
+        };
+        let limits = &self.alert_context;
+        let ctx = self.context.as_deref();
+        if lineage.is_empty() && limits.ancestors > 0 {
+            lineage.push(LineageEntry {
+                pid: event.pid,
+                comm: script::display_name(event),
+                uid: event.uid,
+                age_secs: lineage::age_secs(event.ts_ns),
+            });
+            lineage.extend(lineage::ancestry(event.ppid, ctx));
+        }
+        lineage.truncate(limits.ancestors + 1);
+        let children = lineage::children(event.pid, ctx, limits.children);
+        let pod = ctx
+            .filter(|_| limits.pod)
+            .and_then(|ctx| ctx.k8s_metadata(event.pid))
+            .map(|meta| (*meta).clone());
+        (lineage, children, pod)
+    }
+
+    async fn emit_alert_with_lineage(
+        &self,
+        rule: &RuleConfig,
+        event: Option<&ProcessEvent>,
+        message: String,
+        lineage: Vec<LineageEntry>,
+        mut evidence: Evidence,
+    ) {
+        if !rule.detector.dedup_exempt()
+            && self
+                .cooling_down(format!("{}:{}", self.host, rule.name), rule.cooldown)
+                .await
+        {
+            return;
+        }
 
-        let alert = Alert {
+        let (lineage, children, pod) = self.process_context(event, lineage);
+        if let (Some(event), Some(ctx)) = (event, self.context.as_deref()) {
+            evidence.killed_by = ctx.killed_by(event.pid);
+        }
+        let mut alert = Alert {
             rule: rule.name.clone(),
             severity: rule.severity.clone(),
             message,
             host: self.host.clone(),
+            detection: rule.detector.detection(),
+            suppressed_by: None,
+            lineage,
+            children,
+            pod,
+            evidence: Some(Box::new(evidence)),
+            annotations: (!rule.annotations.is_empty()).then(|| Box::new(rule.annotations.clone())),
         };
+        let comm = event.map(|e| {
+            String::from_utf8_lossy(&e.comm)
+                .trim_end_matches('\0')
+                .to_string()
+        });
+        self.tx.tag(
+            &mut alert,
+            event.zip(comm.as_deref()).map(|(e, comm)| (e.pid, comm)),
+        );
 
         log::info!(
             "[rules] emitting alert rule={} severity={} message={}",
@@ -443,8 +1508,7 @@ impl RuleEngine {
             }
         }
 
-        let _ = self.tx.send(alert);
-        self.metrics.inc_alerts_emitted();
+        self.tx.deliver(alert);
     }
 }
 
@@ -555,6 +1619,32 @@ fn count_recent(queue: &VecDeque<Instant>, window: Duration, now: Instant) -> us
         .count()
 }
 
+fn spike_detectors(cfgs: &[RuleConfig]) -> HashMap<String, SpikeDetector> {
+    cfgs.iter()
+        .filter_map(|cfg| match &cfg.detector {
+            Detector::EwmaSpike {
+                signal,
+                k,
+                alpha,
+                warmup_samples,
+                interval_secs,
+                overrides,
+            } => Some((
+                cfg.name.clone(),
+                SpikeDetector::new(SpikeConfig {
+                    signal: *signal,
+                    k: *k,
+                    alpha: *alpha,
+                    warmup_samples: *warmup_samples,
+                    interval: Duration::from_secs(*interval_secs),
+                    overrides: overrides.clone(),
+                }),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
 #[async_trait]
 impl Handler for RuleEngine {
     fn name(&self) -> &'static str {
@@ -564,10 +1654,11 @@ impl Handler for RuleEngine {
     async fn on_event(&self, event: &ProcessEvent) {
         use linnix_ai_ebpf_common::EventType;
         let now = Instant::now();
-        let fork_keep = Duration::from_secs(self.fork_window_secs.max(1));
-        let exec_keep = Duration::from_secs(self.exec_window_secs.max(1));
-        let completion_keep = Duration::from_secs(self.completion_window_secs.max(1));
-        let runaway_keep = Duration::from_secs(self.runaway_window_secs.max(1));
+        let rules = self.rules();
+        let fork_keep = Duration::from_secs(rules.fork_window_secs.max(1));
+        let exec_keep = Duration::from_secs(rules.exec_window_secs.max(1));
+        let completion_keep = Duration::from_secs(rules.completion_window_secs.max(1));
+        let runaway_keep = Duration::from_secs(rules.runaway_window_secs.max(1));
 
         let mut state = self.state.lock().await;
 
@@ -575,8 +1666,9 @@ impl Handler for RuleEngine {
             x if x == EventType::Fork as u32 => {
                 state.fork_events.push_back(now);
                 trim_instant_queue(&mut state.fork_events, fork_keep, now);
+                state.recent.push(event, None, now);
 
-                if self.runaway_window_secs > 0 {
+                if rules.runaway_window_secs > 0 {
                     let mut remove_entry = false;
                     {
                         let queue = state
@@ -595,16 +1687,27 @@ impl Handler for RuleEngine {
                 }
             }
             x if x == EventType::Exec as u32 => {
+                // exec replaces comm, so chains through this pid are stale
+                state.ancestry.forget(event.pid);
                 state.exec_events.push_back(now);
                 trim_instant_queue(&mut state.exec_events, exec_keep, now);
                 state.exec_start.insert(event.pid, now);
+                state.recent.push(event, None, now);
             }
             x if x == EventType::Exit as u32 => {
-                if let Some(start) = state.exec_start.remove(&event.pid) {
-                    let lifetime = now.saturating_duration_since(start);
+                state.ancestry.forget(event.pid);
+                for detector in state.spikes.values_mut() {
+                    detector.forget(event.pid);
+                }
+                let lifetime = state
+                    .exec_start
+                    .remove(&event.pid)
+                    .map(|start| now.saturating_duration_since(start));
+                if let Some(lifetime) = lifetime {
                     state.exec_completions.push_back((now, lifetime));
                     trim_completion_queue(&mut state.exec_completions, completion_keep, now);
                 }
+                state.recent.push(event, lifetime, now);
             }
             _ => {}
         }
@@ -613,7 +1716,20 @@ impl Handler for RuleEngine {
         let is_exec_event = event.event_type == EventType::Exec as u32;
         let is_exit_event = event.event_type == EventType::Exit as u32;
 
-        for rule in &self.rules {
+        for rule in &rules.rules {
+            if let Some(predicate) = &rule.cfg.lineage {
+                let ancestors = state.ancestry.get_or_resolve(event.ppid, now, || {
+                    lineage::ancestry(event.ppid, self.context.as_deref())
+                });
+                if !predicate.matches(&ancestors) {
+                    continue;
+                }
+            }
+            if let Some(condition) = &rule.cfg.condition
+                && !condition.matches(&|name| self.process_variable(name, event))
+            {
+                continue;
+            }
             match &rule.cfg.detector {
                 Detector::ForksPerSec {
                     threshold,
@@ -642,10 +1758,21 @@ impl Handler for RuleEngine {
                             );
                         }
                         if count >= target.max(*threshold) {
+                            let evidence = Evidence::new(event)
+                                .threshold("forks_per_sec", *threshold as f64)
+                                .threshold("forks_in_window", target.max(*threshold) as f64)
+                                .observed("forks_in_window", count as f64)
+                                .window(
+                                    duration_secs,
+                                    state.recent.window("fork", window, now, |_, _| true),
+                                    count,
+                                );
                             drop(state);
                             self.emit_alert(
                                 &rule.cfg,
+                                None,
                                 format!("fork rate exceeded {} per second", threshold),
+                                evidence,
                             )
                             .await;
                             state = self.state.lock().await;
@@ -672,10 +1799,20 @@ impl Handler for RuleEngine {
                             );
                         }
                         if count >= *threshold {
+                            let evidence = Evidence::new(event)
+                                .threshold("forks_in_window", *threshold as f64)
+                                .observed("forks_in_window", count as f64)
+                                .window(
+                                    window_secs,
+                                    state.recent.window("fork", window, now, |_, _| true),
+                                    count,
+                                );
                             drop(state);
                             self.emit_alert(
                                 &rule.cfg,
+                                None,
                                 format!("fork burst: {} forks in {}s", count, window_seconds),
+                                evidence,
                             )
                             .await;
                             state = self.state.lock().await;
@@ -701,10 +1838,25 @@ impl Handler for RuleEngine {
                             durations.sort_unstable();
                             let median = durations[durations.len() / 2];
                             if median <= *median_lifetime {
+                                let window = Duration::from_secs(60);
+                                let evidence = Evidence::new(event)
+                                    .threshold("execs", *rate_per_min as f64)
+                                    .threshold("median_lifetime_secs", *median_lifetime as f64)
+                                    .observed("execs", state.exec_events.len() as f64)
+                                    .observed("median_lifetime_secs", median as f64)
+                                    .window(
+                                        60,
+                                        state.recent.window("exit", window, now, |_, lifetime| {
+                                            lifetime.is_some()
+                                        }),
+                                        durations.len() as u64,
+                                    );
                                 drop(state);
                                 self.emit_alert(
                                     &rule.cfg,
+                                    None,
                                     format!("exec rate exceeded {rate_per_min}/min"),
+                                    evidence,
                                 )
                                 .await;
                                 state = self.state.lock().await;
@@ -731,13 +1883,27 @@ impl Handler for RuleEngine {
                             if *lifetime <= max_duration {
                                 count += 1;
                                 if count >= *threshold {
+                                    let short =
+                                        state.recent.window("exit", window, now, |_, lifetime| {
+                                            lifetime.is_some_and(|l| l <= max_duration)
+                                        });
+                                    let evidence = Evidence::new(event)
+                                        .threshold("short_execs", *threshold as f64)
+                                        .threshold(
+                                            "max_exec_duration_ms",
+                                            *max_exec_duration_ms as f64,
+                                        )
+                                        .observed("short_execs", count as f64)
+                                        .window(window_secs, short, count);
                                     drop(state);
                                     self.emit_alert(
                                         &rule.cfg,
+                                        None,
                                         format!(
                                             "{} short-lived execs (<= {}ms) in {}s",
                                             threshold, max_exec_duration_ms, window_seconds
                                         ),
+                                        evidence,
                                     )
                                     .await;
                                     state = self.state.lock().await;
@@ -781,13 +1947,22 @@ impl Handler for RuleEngine {
                             );
                         }
                         if count >= *threshold {
+                            let children = state
+                                .recent
+                                .window("fork", window, now, |ppid, _| ppid == event.ppid);
+                            let evidence = Evidence::new(event)
+                                .threshold("forks_in_window", *threshold as f64)
+                                .observed("forks_in_window", count as f64)
+                                .window(window_secs, children, count);
                             drop(state);
                             self.emit_alert(
                                 &rule.cfg,
+                                Some(event),
                                 format!(
                                     "ppid {} spawned {} forks in {}s",
                                     event.ppid, count, window_seconds
                                 ),
+                                evidence,
                             )
                             .await;
                             state = self.state.lock().await;
@@ -812,12 +1987,20 @@ impl Handler for RuleEngine {
                         if cpu > *threshold {
                             let entry =
                                 state.cpu_exceed.entry(rule.cfg.name.clone()).or_insert(now);
-                            if now.duration_since(*entry) > Duration::from_secs(*duration) {
+                            let exceeded = now.duration_since(*entry);
+                            if exceeded > Duration::from_secs(*duration) {
                                 state.cpu_exceed.remove(&rule.cfg.name);
                                 drop(state);
+                                let evidence = Evidence::new(event)
+                                    .threshold("cpu_pct", *threshold)
+                                    .threshold("duration_secs", *duration as f64)
+                                    .observed("cpu_pct", cpu)
+                                    .observed("exceeded_secs", exceeded.as_secs_f64());
                                 self.emit_alert(
                                     &rule.cfg,
+                                    Some(event),
                                     format!("cpu pct {threshold} over {duration}s"),
+                                    evidence,
                                 )
                                 .await;
                                 state = self.state.lock().await;
@@ -853,12 +2036,21 @@ impl Handler for RuleEngine {
                         if used_mb > *threshold {
                             let entry =
                                 state.rss_exceed.entry(rule.cfg.name.clone()).or_insert(now);
-                            if now.duration_since(*entry) > Duration::from_secs(*duration) {
+                            let exceeded = now.duration_since(*entry);
+                            if exceeded > Duration::from_secs(*duration) {
                                 state.rss_exceed.remove(&rule.cfg.name);
                                 drop(state);
+                                let evidence = Evidence::new(event)
+                                    .threshold("rss_mb", *threshold as f64)
+                                    .threshold("duration_secs", *duration as f64)
+                                    .observed("rss_mb", used_mb as f64)
+                                    .observed("mem_pct", mem_pct)
+                                    .observed("exceeded_secs", exceeded.as_secs_f64());
                                 self.emit_alert(
                                     &rule.cfg,
+                                    Some(event),
                                     format!("rss mb {threshold} over {duration}s"),
+                                    evidence,
                                 )
                                 .await;
                                 state = self.state.lock().await;
@@ -868,7 +2060,299 @@ impl Handler for RuleEngine {
                         }
                     }
                 }
-                Detector::ZombieCount { .. } => {}
+                // Evaluated on filesystem samples, not events
+                Detector::ZombieCount { .. } | Detector::FilesystemUsage { .. } => {}
+                Detector::Absence {
+                    events,
+                    comms,
+                    scripts,
+                    within_secs,
+                } => {
+                    if !events.iter().any(|name| {
+                        parse_event_type(name).is_some_and(|t| t as u32 == event.event_type)
+                    }) {
+                        continue;
+                    }
+                    let comm = String::from_utf8_lossy(&event.comm)
+                        .trim_end_matches('\0')
+                        .to_string();
+                    if !matches_process(comms, scripts, &comm, event) {
+                        continue;
+                    }
+                    let timer = state
+                        .absence
+                        .entry(rule.cfg.name.clone())
+                        .or_insert_with(|| AbsenceTimer::new(now));
+                    let silent = now.saturating_duration_since(timer.last_seen);
+                    timer.last_seen = now;
+                    if !std::mem::take(&mut timer.raised) {
+                        continue;
+                    }
+                    drop(state);
+                    let resumed = RuleConfig {
+                        severity: Severity::Info,
+                        ..rule.cfg.clone()
+                    };
+                    let evidence = Evidence::new(event)
+                        .threshold("within_secs", *within_secs as f64)
+                        .observed("silent_secs", silent.as_secs() as f64);
+                    let message = format!(
+                        "{}({}) resumed after {}s without {}",
+                        script::display_name(event),
+                        event.pid,
+                        silent.as_secs(),
+                        events.join("/")
+                    );
+                    self.emit_alert(&resumed, Some(event), message, evidence)
+                        .await;
+                    state = self.state.lock().await;
+                }
+                Detector::ProcessCondition { events } => {
+                    if !events.is_empty()
+                        && !events.iter().any(|name| {
+                            parse_event_type(name).is_some_and(|t| t as u32 == event.event_type)
+                        })
+                    {
+                        continue;
+                    }
+                    let Some(condition) = &rule.cfg.condition else {
+                        continue;
+                    };
+                    drop(state);
+                    let evidence = PROCESS_VARIABLES
+                        .iter()
+                        .filter(|name| condition.uses(name))
+                        .filter_map(|name| Some((*name, self.process_variable(name, event)?)))
+                        .fold(Evidence::new(event), |evidence, (name, value)| {
+                            evidence.observed(name, value)
+                        });
+                    let message = format!(
+                        "{}({}) matched {}",
+                        script::display_name(event),
+                        event.pid,
+                        condition.source()
+                    );
+                    self.emit_alert(&rule.cfg, Some(event), message, evidence)
+                        .await;
+                    state = self.state.lock().await;
+                }
+                Detector::EwmaSpike { signal, .. } => {
+                    let Some((pid, value)) = signal.sample(event) else {
+                        continue;
+                    };
+                    let comm = String::from_utf8_lossy(&event.comm)
+                        .trim_end_matches('\0')
+                        .to_string();
+                    let spike = state
+                        .spikes
+                        .get_mut(&rule.cfg.name)
+                        .and_then(|detector| detector.record(pid, &comm, value, now));
+                    if let Some(spike) = spike {
+                        log::debug!(
+                            "[rules] detector=ewma_spike rule={} signal={} pid={} value={:.1} mean={:.1} sigma={:.1}",
+                            rule.cfg.name,
+                            signal.as_str(),
+                            spike.pid,
+                            spike.value,
+                            spike.mean,
+                            spike.sigma
+                        );
+                        drop(state);
+                        let evidence = Evidence::new(event)
+                            .threshold("k", spike.k)
+                            .observed("value", spike.value)
+                            .observed("mean", spike.mean)
+                            .observed("sigma", spike.sigma)
+                            .observed("z_score", spike.z_score());
+                        self.emit_alert(
+                            &rule.cfg,
+                            Some(event),
+                            format!(
+                                "{} spike for {}({}): {:.0}/s vs baseline {:.0}/s ({:.1}σ, k={})",
+                                signal.as_str(),
+                                comm,
+                                spike.pid,
+                                spike.value,
+                                spike.mean,
+                                spike.z_score(),
+                                spike.k
+                            ),
+                            evidence,
+                        )
+                        .await;
+                        state = self.state.lock().await;
+                    }
+                }
+                Detector::ExecRisk { matcher } => {
+                    if !is_exec_event {
+                        continue;
+                    }
+                    drop(state);
+                    self.check_exec_risk(&rule.cfg, matcher, event).await;
+                    state = self.state.lock().await;
+                }
+                Detector::ProcessExec { comms, scripts } => {
+                    if !is_exec_event {
+                        continue;
+                    }
+                    let comm = String::from_utf8_lossy(&event.comm)
+                        .trim_end_matches('\0')
+                        .to_string();
+                    if !matches_process(comms, scripts, &comm, event) {
+                        continue;
+                    }
+                    let ancestors = state.ancestry.get_or_resolve(event.ppid, now, || {
+                        lineage::ancestry(event.ppid, self.context.as_deref())
+                    });
+                    drop(state);
+                    let mut chain = vec![LineageEntry {
+                        pid: event.pid,
+                        comm: script::display_name(event),
+                        uid: event.uid,
+                        age_secs: lineage::age_secs(event.ts_ns),
+                    }];
+                    chain.extend(ancestors.iter().cloned());
+                    let message = format!("exec {}", lineage::format_lineage(&chain));
+                    self.emit_alert_with_lineage(
+                        &rule.cfg,
+                        Some(event),
+                        message,
+                        chain,
+                        Evidence::new(event),
+                    )
+                    .await;
+                    state = self.state.lock().await;
+                }
+                Detector::StartupLatency {
+                    comms,
+                    scripts,
+                    threshold_ms,
+                    regression_factor,
+                    min_samples,
+                } => {
+                    if event.event_type != EventType::Listen as u32 {
+                        continue;
+                    }
+                    // Handlers run before the event reaches the context store.
+                    let Some(latency) = self
+                        .context
+                        .as_deref()
+                        .and_then(|ctx| ctx.pending_startup(event))
+                    else {
+                        continue;
+                    };
+                    let comm = String::from_utf8_lossy(&event.comm)
+                        .trim_end_matches('\0')
+                        .to_string();
+                    if !matches_process(comms, scripts, &comm, event) {
+                        continue;
+                    }
+                    let comm = script::display_name(event);
+                    let key = (rule.cfg.name.clone(), comm.clone());
+                    if state.startup_baselines.len() >= MAX_STARTUP_BASELINES
+                        && !state.startup_baselines.contains_key(&key)
+                    {
+                        state.startup_baselines.clear();
+                    }
+                    let baseline = state.startup_baselines.entry(key).or_default();
+                    let ms = latency.as_millis_f64();
+                    let mean = baseline.mean();
+                    let regressed = baseline.samples() >= *min_samples
+                        && mean > 0.0
+                        && ms > mean * regression_factor;
+                    baseline.update(ms, STARTUP_BASELINE_ALPHA);
+                    log::debug!(
+                        "[rules] detector=startup_latency rule={} comm={} pid={} port={} ms={:.1} baseline={:.1}",
+                        rule.cfg.name,
+                        comm,
+                        event.pid,
+                        latency.port,
+                        ms,
+                        mean
+                    );
+                    let message = if regressed {
+                        format!(
+                            "{}({}) listening on :{} {:.0}ms after exec, {:.1}x its {:.0}ms baseline",
+                            comm,
+                            event.pid,
+                            latency.port,
+                            ms,
+                            ms / mean,
+                            mean
+                        )
+                    } else if let Some(limit) = threshold_ms.filter(|t| ms > *t as f64) {
+                        format!(
+                            "{}({}) listening on :{} {:.0}ms after exec (threshold {}ms)",
+                            comm, event.pid, latency.port, ms, limit
+                        )
+                    } else {
+                        continue;
+                    };
+                    let mut evidence = Evidence::new(event)
+                        .threshold("regression_factor", *regression_factor)
+                        .threshold("min_samples", *min_samples)
+                        .observed("startup_ms", ms)
+                        .observed("baseline_ms", mean)
+                        .observed("port", latency.port);
+                    if let Some(limit) = threshold_ms {
+                        evidence = evidence.threshold("startup_ms", *limit as f64);
+                    }
+                    drop(state);
+                    self.emit_alert(&rule.cfg, Some(event), message, evidence)
+                        .await;
+                    state = self.state.lock().await;
+                }
+                Detector::FdPressure { used_pct } => {
+                    if event.event_type != EventType::FdPressure as u32 {
+                        continue;
+                    }
+                    let usage = FdUsage::from_event(event);
+                    let below = used_pct
+                        .is_some_and(|threshold| usage.used_pct.is_none_or(|pct| pct < threshold));
+                    if below && !usage.exhausted {
+                        continue;
+                    }
+                    let mut evidence = Evidence::new(event).observed("open_fds", usage.open as f64);
+                    if let Some(limit) = usage.limit {
+                        evidence = evidence.observed("fd_limit", limit as f64);
+                    }
+                    if let Some(pct) = usage.used_pct {
+                        evidence = evidence.observed("used_pct", pct);
+                    }
+                    if let Some(threshold) = used_pct {
+                        evidence = evidence.threshold("used_pct", *threshold);
+                    }
+                    let process = format!("{}({})", script::display_name(event), event.pid);
+                    drop(state);
+                    self.emit_alert(&rule.cfg, Some(event), usage.describe(&process), evidence)
+                        .await;
+                    state = self.state.lock().await;
+                }
+                Detector::Signal { signals } => {
+                    if event.event_type != EventType::Signal as u32 {
+                        continue;
+                    }
+                    let signal = SignalRecord::from_event(event);
+                    if !signals.is_empty() && !signals.contains(&signal.signo) {
+                        continue;
+                    }
+                    let target = self
+                        .context
+                        .as_deref()
+                        .and_then(|ctx| ctx.get_process_by_pid(signal.target_pid))
+                        .map_or_else(
+                            || format!("pid {}", signal.target_pid),
+                            |target| format!("{}({})", script::display_name(&target), target.pid),
+                        );
+                    let message = format!("{target} got {}", signal.describe());
+                    let evidence = Evidence::new(event)
+                        .observed("signal", signal.signo)
+                        .observed("target_pid", signal.target_pid);
+                    drop(state);
+                    self.emit_alert(&rule.cfg, Some(event), message, evidence)
+                        .await;
+                    state = self.state.lock().await;
+                }
             }
         }
     }
@@ -876,6 +2360,12 @@ impl Handler for RuleEngine {
     async fn on_snapshot(&self, _snapshot: &SystemSnapshot) {
         // placeholder for detectors needing snapshots
     }
+
+    /// Sequences such as exec followed by a connection and a quick exit
+    /// only make sense in order.
+    fn ordered(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
@@ -893,10 +2383,23 @@ mod tests {
                 threshold: 1,
                 duration: 1,
             },
+            lineage: None,
+            condition: None,
+            annotations: Annotations::default(),
         };
-        let (tx, _rx) = broadcast::channel(16);
+        test_engine_with(cfg)
+    }
+
+    fn test_engine_with(cfg: RuleConfig) -> RuleEngine {
+        let metrics = Arc::new(Metrics::new());
         RuleEngine {
-            rules: vec![Rule { cfg }],
+            rules: std::sync::RwLock::new(Arc::new(RuleSet {
+                rules: vec![Rule { cfg }],
+                fork_window_secs: 1,
+                exec_window_secs: 60,
+                completion_window_secs: 60,
+                runaway_window_secs: 1,
+            })),
             state: Mutex::new(RuleState {
                 fork_events: VecDeque::new(),
                 exec_events: VecDeque::new(),
@@ -906,17 +2409,20 @@ mod tests {
                 cpu_exceed: HashMap::new(),
                 rss_exceed: HashMap::new(),
                 active: HashMap::new(),
+                spikes: HashMap::new(),
+                ancestry: AncestryCache::default(),
+                startup_baselines: HashMap::new(),
+                recent: RecentEvents::default(),
+                absence: HashMap::new(),
             }),
-            tx,
+            tx: AlertSender::new(16, Arc::clone(&metrics)),
             alerts_file: "/dev/null".into(),
             journald: false,
             host: "test-host".into(),
-            fork_window_secs: 1,
-            exec_window_secs: 60,
-            completion_window_secs: 60,
-            runaway_window_secs: 1,
-            metrics: Arc::new(Metrics::new()),
+            metrics,
             total_memory_bytes: Some(16 * 1024 * 1024 * 1024),
+            context: None,
+            alert_context: AlertContextConfig::default(),
         }
     }
 
@@ -941,6 +2447,7 @@ mod tests {
             data2: 0,
             aux: 0,
             aux2: 0,
+            cgroup_id: 0,
         };
         let event = ProcessEvent::new(base);
         engine.on_event(&event).await;
@@ -975,6 +2482,7 @@ mod tests {
             data2: 0,
             aux: 0,
             aux2: 0,
+            cgroup_id: 0,
         };
         let event = ProcessEvent::new(base);
         let f1 = engine.on_event(&event);
@@ -1024,4 +2532,704 @@ severity = "medium"
         assert_eq!(yaml_rules[1].name, "cpu_spin");
         assert_eq!(toml_rules[1].name, "cpu_spin");
     }
+
+    #[test]
+    fn parses_ewma_spike_rule_with_overrides() {
+        let yaml = r#"- name: net_spike
+  detector: ewma_spike
+  signal: net_bytes
+  k: 5.0
+  overrides:
+    - comm: "backup*"
+      disabled: true
+"#;
+        let bad = r#"- name: bad_alpha
+  detector: ewma_spike
+  signal: fork_rate
+  alpha: 1.5
+"#;
+        let err = parse_rules(bad, Some("yaml")).unwrap_err();
+        assert!(err.to_string().contains("alpha must be in"));
+
+        let rules = parse_rules(yaml, Some("yaml")).expect("yaml parses");
+        let rule = &rules[0];
+        assert_eq!(rule.detector.detection(), "anomaly");
+        match &rule.detector {
+            Detector::EwmaSpike {
+                signal,
+                k,
+                alpha,
+                warmup_samples,
+                overrides,
+                ..
+            } => {
+                assert_eq!(*signal, SpikeSignal::NetBytes);
+                assert_eq!(*k, 5.0);
+                assert_eq!(*alpha, DEFAULT_SPIKE_ALPHA);
+                assert_eq!(*warmup_samples, DEFAULT_SPIKE_WARMUP_SAMPLES);
+                assert!(overrides[0].disabled);
+            }
+            other => panic!("unexpected detector {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn reload_replaces_rules_and_keeps_them_on_error() {
+        let engine = test_engine(60);
+        let yaml = r#"- name: fork_storm
+  detector: forks_per_sec
+  threshold: 5
+  duration: 30
+- name: net_spike
+  detector: ewma_spike
+  signal: net_bytes
+"#;
+        assert_eq!(engine.reload(yaml, Some("yaml")).await.unwrap(), 2);
+        assert_eq!(engine.rule_count(), 2);
+        assert_eq!(engine.rules().fork_window_secs, 30);
+        assert!(engine.state.lock().await.spikes.contains_key("net_spike"));
+
+        let bad = "- name: broken\n  detector: no_such_detector\n";
+        assert!(engine.reload(bad, Some("yaml")).await.is_err());
+        assert_eq!(engine.rule_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn exec_risk_alerts_every_match_with_lineage() {
+        // The test binary's own cmdline stands in for a risky exec.
+        let yaml = r#"- name: risky
+  detector: exec_risk
+  builtin: false
+  patterns:
+    - id: anything
+      regex: "."
+"#;
+        let mut rules = parse_rules(yaml, Some("yaml")).expect("yaml parses");
+        let cfg = rules.remove(0);
+        assert_eq!(cfg.severity, Severity::High);
+        assert!(cfg.detector.dedup_exempt());
+
+        let engine = test_engine_with(cfg);
+        let mut rx = engine.tx.subscribe();
+        let pid = std::process::id();
+        let event = ProcessEvent::new(ProcessEventWire {
+            pid,
+            ppid: 0,
+            uid: 0,
+            gid: 0,
+            event_type: linnix_ai_ebpf_common::EventType::Exec as u32,
+            ts_ns: 0,
+            seq: 0,
+            comm: [0; 16],
+            exit_time_ns: 0,
+            cpu_pct_milli: PERCENT_MILLI_UNKNOWN,
+            mem_pct_milli: PERCENT_MILLI_UNKNOWN,
+            data: 0,
+            data2: 0,
+            aux: 0,
+            aux2: 0,
+            cgroup_id: 0,
+        });
+        engine.on_event(&event).await;
+        engine.on_event(&event).await;
+        let mut other_parent = event.clone();
+        other_parent.ppid = 1;
+        engine.on_event(&other_parent).await;
+
+        let first = rx.recv().await.unwrap();
+        assert_eq!(first.detection, "signature");
+        assert_eq!(first.lineage[0].pid, pid);
+        assert!(rx.recv().await.is_ok(), "repeat is not collapsed");
+        assert!(rx.recv().await.is_ok(), "another parent is not collapsed");
+    }
+
+    #[tokio::test]
+    async fn runaway_tree_alert_carries_window_evidence() {
+        let engine = test_engine_with(RuleConfig {
+            name: "runaway".into(),
+            severity: Severity::High,
+            cooldown: 60,
+            detector: Detector::RunawayTree {
+                threshold: 3,
+                window_seconds: 1,
+            },
+            lineage: None,
+            condition: None,
+            annotations: Annotations::default(),
+        });
+        let mut rx = engine.tx.subscribe();
+        let fork = linnix_ai_ebpf_common::EventType::Fork as u32;
+        engine
+            .on_event(&ProcessEvent::new(wire(900, 77, fork, b"cron")))
+            .await;
+        for pid in 901..904 {
+            engine
+                .on_event(&ProcessEvent::new(wire(pid, 42, fork, b"bash")))
+                .await;
+        }
+
+        let alert = rx.recv().await.unwrap();
+        let evidence = alert.evidence.expect("rule alerts carry evidence");
+        assert_eq!(evidence.trigger.as_ref().map(|e| e.pid), Some(903));
+        assert_eq!(evidence.thresholds["forks_in_window"], 3.0);
+        assert_eq!(evidence.observed["forks_in_window"], 3.0);
+        assert_eq!(evidence.window_secs, Some(1));
+        assert_eq!(evidence.window_total, Some(3));
+        let pids: Vec<u32> = evidence.window.iter().map(|e| e.pid).collect();
+        assert_eq!(pids, [903, 902, 901]);
+    }
+
+    fn wire(pid: u32, ppid: u32, event_type: u32, comm: &[u8]) -> ProcessEventWire {
+        let mut name = [0u8; 16];
+        name[..comm.len()].copy_from_slice(comm);
+        ProcessEventWire {
+            pid,
+            ppid,
+            uid: 33,
+            gid: 0,
+            event_type,
+            ts_ns: 0,
+            seq: 0,
+            comm: name,
+            exit_time_ns: 0,
+            cpu_pct_milli: PERCENT_MILLI_UNKNOWN,
+            mem_pct_milli: PERCENT_MILLI_UNKNOWN,
+            data: 0,
+            data2: 0,
+            aux: 0,
+            aux2: 0,
+            cgroup_id: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn lineage_condition_gates_process_exec() {
+        let yaml = r#"- name: web_shell
+  detector: process_exec
+  comms: ["sh", "bash"]
+  lineage:
+    parent_comm: "nginx*"
+"#;
+        let mut rules = parse_rules(yaml, Some("yaml")).expect("yaml parses");
+        let cfg = rules.remove(0);
+        assert_eq!(cfg.lineage.as_ref().unwrap().within, 8);
+
+        let ctx = Arc::new(ContextStore::new(
+            std::time::Duration::from_secs(60),
+            16,
+            None,
+        ));
+        let fork = linnix_ai_ebpf_common::EventType::Fork as u32;
+        let exec = linnix_ai_ebpf_common::EventType::Exec as u32;
+        ctx.add(ProcessEvent::new(wire(5000, 0, exec, b"nginx")));
+        ctx.add(ProcessEvent::new(wire(6000, 0, exec, b"cron")));
+
+        let engine = test_engine_with(cfg).with_context_store(ctx);
+        let mut rx = engine.tx.subscribe();
+        for event in [
+            wire(5001, 5000, fork, b"nginx"),
+            wire(6001, 6000, exec, b"sh"),
+            wire(5002, 5000, exec, b"python3"),
+            wire(5001, 5000, exec, b"sh"),
+        ] {
+            engine.on_event(&ProcessEvent::new(event)).await;
+        }
+
+        let alert = rx.recv().await.unwrap();
+        assert_eq!(alert.message, "exec sh(5001) <- nginx(5000)");
+        assert_eq!(alert.lineage.len(), 2);
+        assert!(rx.try_recv().is_err(), "only the nginx child alerts");
+
+        let err = parse_rules(
+            "- name: x\n  detector: fork_burst\n  threshold: 1\n  window_seconds: 1\n  lineage: {parent_comm: nginx}\n",
+            Some("yaml"),
+        )
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("per-process detector"));
+    }
+
+    #[tokio::test]
+    async fn process_condition_rule_evaluates_expression() {
+        let yaml = r#"- name: cpu_heavy_root
+  detector: process_condition
+  events: [exec]
+  condition: "cpu_pct > 3 * mem_pct && uid != 0"
+"#;
+        let cfg = parse_rules(yaml, Some("yaml")).unwrap().remove(0);
+        let engine = test_engine_with(cfg);
+        let mut rx = engine.tx.subscribe();
+        let exec = linnix_ai_ebpf_common::EventType::Exec as u32;
+        let fork = linnix_ai_ebpf_common::EventType::Fork as u32;
+        for (pid, event_type, cpu, mem) in [
+            (7001, exec, Some(40.0), Some(20.0)),
+            (7002, fork, Some(60.0), Some(1.0)),
+            (7003, exec, None, Some(1.0)),
+            (7004, exec, Some(60.0), Some(1.0)),
+        ] {
+            let mut event = ProcessEvent::new(wire(pid, 1, event_type, b"burn"));
+            event.set_cpu_percent(cpu);
+            event.set_mem_percent(mem);
+            engine.on_event(&event).await;
+        }
+
+        let alert = rx.recv().await.unwrap();
+        assert_eq!(
+            alert.message,
+            "burn(7004) matched cpu_pct > 3 * mem_pct && uid != 0"
+        );
+        let evidence = alert.evidence.unwrap();
+        assert_eq!(evidence.observed["cpu_pct"], 60.0);
+        assert_eq!(evidence.observed["uid"], 33.0);
+        assert!(rx.try_recv().is_err());
+
+        for (yaml, error) in [
+            (
+                "- name: x\n  detector: process_condition\n  condition: \"rss_mb > 1\"\n",
+                "unknown variable 'rss_mb'",
+            ),
+            (
+                "- name: x\n  detector: process_condition\n  condition: \"cpu_pct * 2\"\n",
+                "a boolean is needed",
+            ),
+            (
+                "- name: x\n  detector: process_condition\n",
+                "needs a condition",
+            ),
+            (
+                "- name: x\n  detector: fork_burst\n  threshold: 1\n  window_seconds: 1\n  condition: \"pid > 1\"\n",
+                "per-process detector",
+            ),
+        ] {
+            let err = parse_rules(yaml, Some("yaml")).unwrap_err();
+            assert!(format!("{err:#}").contains(error), "{err:#}");
+        }
+    }
+
+    #[tokio::test]
+    async fn fd_pressure_fires_above_threshold_or_on_exhaustion() {
+        let yaml = "- name: fds\n  detector: fd_pressure\n  used_pct: 90\n";
+        let cfg = parse_rules(yaml, Some("yaml")).unwrap().remove(0);
+        let engine = test_engine_with(cfg);
+        let mut rx = engine.tx.subscribe();
+        let fd_pressure = linnix_ai_ebpf_common::EventType::FdPressure as u32;
+        for (open, aux) in [
+            (850, 0),
+            (1024, linnix_ai_ebpf_common::fd_pressure::EXHAUSTED),
+        ] {
+            let mut event = ProcessEvent::new(wire(7100, 1, fd_pressure, b"nginx"));
+            event.data = open;
+            event.data2 = 1024;
+            event.aux = aux;
+            engine.on_event(&event).await;
+        }
+
+        let alert = rx.recv().await.unwrap();
+        assert_eq!(
+            alert.message,
+            "nginx(7100) ran out of file descriptors (limit 1024)"
+        );
+        let evidence = alert.evidence.unwrap();
+        assert_eq!(evidence.observed["used_pct"], 100.0);
+        assert_eq!(evidence.thresholds["used_pct"], 90.0);
+        assert!(rx.try_recv().is_err());
+
+        let err = parse_rules(
+            "- name: x\n  detector: fd_pressure\n  used_pct: 150\n",
+            Some("yaml"),
+        )
+        .unwrap_err();
+        assert!(
+            format!("{err:#}").contains("used_pct must be in"),
+            "{err:#}"
+        );
+    }
+
+    #[tokio::test]
+    async fn signal_rule_names_the_target_and_exit_alerts_carry_the_killer() {
+        let ctx = Arc::new(ContextStore::new(
+            std::time::Duration::from_secs(60),
+            64,
+            None,
+        ));
+        let exec = linnix_ai_ebpf_common::EventType::Exec as u32;
+        ctx.add(ProcessEvent::new(wire(7200, 1, exec, b"java")));
+
+        let yaml = "- name: kills\n  detector: signal\n  signals: [kill, SIGTERM]\n";
+        let cfg = parse_rules(yaml, Some("yaml")).unwrap().remove(0);
+        let engine = test_engine_with(cfg).with_context_store(Arc::clone(&ctx));
+        let mut rx = engine.tx.subscribe();
+        let signal = linnix_ai_ebpf_common::EventType::Signal as u32;
+        for signo in [17, 9] {
+            let mut event = ProcessEvent::new(wire(812, 1, signal, b"kubelet"));
+            event.data = 7200;
+            event.aux = signo;
+            engine.on_event(&event).await;
+            ctx.add(event);
+        }
+
+        let alert = rx.recv().await.unwrap();
+        assert_eq!(alert.message, "java(7200) got SIGKILL from kubelet(812)");
+        assert_eq!(alert.evidence.as_ref().unwrap().observed["signal"], 9.0);
+        assert!(rx.try_recv().is_err());
+        assert_eq!(
+            ctx.killed_by(7200).map(|s| s.describe()).as_deref(),
+            Some("SIGKILL from kubelet(812)")
+        );
+
+        let err = parse_rules(
+            "- name: x\n  detector: signal\n  signals: [SIGBOGUS]\n",
+            Some("yaml"),
+        )
+        .unwrap_err();
+        assert!(
+            format!("{err:#}").contains("unknown signal 'SIGBOGUS'"),
+            "{err:#}"
+        );
+    }
+
+    #[tokio::test]
+    async fn process_exec_matches_script_names() {
+        let yaml = "- name: training\n  detector: process_exec\n  comms: [\"python*\"]\n  scripts: [\"train*.py\"]\n";
+        let cfg = parse_rules(yaml, Some("yaml")).unwrap().remove(0);
+        let ctx = Arc::new(ContextStore::new(
+            std::time::Duration::from_secs(60),
+            16,
+            None,
+        ));
+        let exec = linnix_ai_ebpf_common::EventType::Exec as u32;
+        ctx.add(ProcessEvent::new(wire(5000, 0, exec, b"nginx")));
+
+        let engine = test_engine_with(cfg).with_context_store(ctx);
+        let mut rx = engine.tx.subscribe();
+        for (pid, script) in [
+            (5001, None),
+            (5002, Some("/srv/ml/serve.py")),
+            (5003, Some("/srv/ml/train.py")),
+        ] {
+            let event = ProcessEvent::new(wire(pid, 5000, exec, b"python3"))
+                .with_script_path(script.map(String::from));
+            engine.on_event(&event).await;
+        }
+
+        let alert = rx.recv().await.unwrap();
+        assert_eq!(alert.message, "exec train.py(5003) <- nginx(5000)");
+        assert!(rx.try_recv().is_err(), "only the matching script alerts");
+    }
+
+    #[tokio::test]
+    async fn alerts_capture_process_context() {
+        let ctx = Arc::new(ContextStore::new(
+            std::time::Duration::from_secs(60),
+            64,
+            None,
+        ));
+        let fork = linnix_ai_ebpf_common::EventType::Fork as u32;
+        let exec = linnix_ai_ebpf_common::EventType::Exec as u32;
+        ctx.add(ProcessEvent::new(wire(100, 1, exec, b"containerd")));
+        ctx.add(ProcessEvent::new(wire(5000, 100, exec, b"nginx")));
+        for (pid, started_secs) in [(5101, 1), (5102, 3), (5103, 2)] {
+            let mut child = wire(pid, 5001, fork, b"worker");
+            child.ts_ns = started_secs * 1_000_000_000;
+            ctx.add(ProcessEvent::new(child));
+        }
+
+        let yaml = "- name: nginx_exec\n  detector: process_exec\n  comms: [nginx]\n";
+        let cfg = parse_rules(yaml, Some("yaml")).unwrap().remove(0);
+        let engine = test_engine_with(cfg)
+            .with_context_store(ctx)
+            .with_alert_context(AlertContextConfig {
+                ancestors: 1,
+                children: 2,
+                pod: true,
+            });
+        let mut rx = engine.tx.subscribe();
+        engine
+            .on_event(&ProcessEvent::new(wire(5001, 5000, exec, b"nginx")))
+            .await;
+
+        let alert = rx.recv().await.unwrap();
+        let pids = |entries: &[LineageEntry]| entries.iter().map(|e| e.pid).collect::<Vec<_>>();
+        assert_eq!(pids(&alert.lineage), [5001, 5000]);
+        assert_eq!(pids(&alert.children), [5102, 5103], "youngest first");
+        assert!(alert.children.iter().all(|c| c.age_secs.is_some()));
+        assert!(alert.pod.is_none());
+        let lines = alert.process_context_lines();
+        assert_eq!(
+            lines[0],
+            (
+                "Process",
+                "nginx(5001, uid 33) <- nginx(5000, uid 33)".into()
+            )
+        );
+        assert_eq!(lines[1].0, "Children");
+    }
+
+    #[tokio::test]
+    async fn rule_annotations_pass_through_to_alerts() {
+        let yaml = r#"- name: web_shell
+  detector: process_exec
+  comms: ["sh"]
+  runbook_url: https://runbooks.example.com/web-shell
+  description: Shell spawned by a web server
+  owner: platform-security
+  labels: {service: web, tier: edge}
+"#;
+        let cfg = parse_rules(yaml, Some("yaml")).unwrap().remove(0);
+        assert_eq!(cfg.annotations.owner.as_deref(), Some("platform-security"));
+
+        let engine = test_engine_with(cfg);
+        let mut rx = engine.tx.subscribe();
+        let exec = linnix_ai_ebpf_common::EventType::Exec as u32;
+        engine
+            .on_event(&ProcessEvent::new(wire(5001, 1, exec, b"sh")))
+            .await;
+
+        let alert = rx.recv().await.unwrap();
+        let annotations = alert.annotations.as_deref().expect("rule is annotated");
+        assert_eq!(
+            annotations.runbook_url.as_deref(),
+            Some("https://runbooks.example.com/web-shell")
+        );
+        assert_eq!(annotations.labels["tier"], "edge");
+        let json = serde_json::to_value(&alert).unwrap();
+        assert_eq!(json["annotations"]["labels"]["service"], "web");
+
+        // The published type serializes identically
+        let public = linnix_events::Alert::from(&alert);
+        assert_eq!(serde_json::to_value(&public).unwrap(), json);
+
+        // Unannotated rules add nothing to the payload
+        let cfg = parse_rules(
+            "- name: plain\n  detector: process_exec\n  comms: [\"sh\"]\n",
+            Some("yaml"),
+        )
+        .unwrap()
+        .remove(0);
+        assert!(cfg.annotations.is_empty());
+        let engine = test_engine_with(cfg);
+        let mut rx = engine.tx.subscribe();
+        engine
+            .on_event(&ProcessEvent::new(wire(5002, 1, exec, b"sh")))
+            .await;
+        let json = serde_json::to_value(rx.recv().await.unwrap()).unwrap();
+        assert!(json.get("annotations").is_none());
+    }
+
+    #[tokio::test]
+    async fn startup_latency_flags_regressions_against_baseline() {
+        let yaml = "- name: slow_start\n  detector: startup_latency\n  comms: [\"api*\"]\n";
+        let cfg = parse_rules(yaml, Some("yaml")).unwrap().remove(0);
+        let ctx = Arc::new(ContextStore::new(
+            std::time::Duration::from_secs(60),
+            64,
+            None,
+        ));
+        let engine = test_engine_with(cfg).with_context_store(Arc::clone(&ctx));
+        let mut rx = engine.tx.subscribe();
+        let exec = linnix_ai_ebpf_common::EventType::Exec as u32;
+        let listen = linnix_ai_ebpf_common::EventType::Listen as u32;
+
+        // Five restarts at ~100ms build the baseline, the sixth takes 450ms.
+        for (i, latency_ms) in [100u64, 110, 90, 100, 105, 450].into_iter().enumerate() {
+            let pid = 7000 + i as u32;
+            let mut start = wire(pid, 1, exec, b"api-server");
+            start.ts_ns = 1_000_000_000;
+            ctx.add(ProcessEvent::new(start));
+            let mut ready = wire(pid, 1, listen, b"api-server");
+            ready.ts_ns = 1_000_000_000 + latency_ms * 1_000_000;
+            ready.data = 8080;
+            let ready = ProcessEvent::new(ready);
+            engine.on_event(&ready).await;
+            ctx.add(ready.clone());
+            // A second listen is not a new startup
+            engine.on_event(&ready).await;
+            if latency_ms < 450 {
+                assert!(rx.try_recv().is_err(), "{latency_ms}ms is within baseline");
+            }
+        }
+
+        let alert = rx.recv().await.unwrap();
+        assert!(
+            alert
+                .message
+                .starts_with("api-server(7005) listening on :8080 450ms after exec"),
+            "{}",
+            alert.message
+        );
+        assert_eq!(ctx.startup_histogram().snapshot().count, 6);
+    }
+
+    #[tokio::test]
+    async fn filesystem_usage_rule_fires_on_usage_or_time_to_full() {
+        let yaml = "- name: disk_filling\n  detector: filesystem_usage\n  mounts: [\"/var*\"]\n  used_pct: 90\n  hours_to_full: 4\n  severity: high\n";
+        let cfg = parse_rules(yaml, Some("yaml")).unwrap().remove(0);
+        let engine = test_engine_with(cfg);
+        let mut rx = engine.tx.subscribe();
+        let fs = |mount_point: &str, used_pct: f64, secs_to_full: Option<u64>| FilesystemStatus {
+            mount_point: mount_point.into(),
+            device: "/dev/sda1".into(),
+            fs_type: "ext4".into(),
+            total_bytes: 100,
+            used_bytes: used_pct as u64,
+            available_bytes: 100 - used_pct as u64,
+            used_pct,
+            inodes_used_pct: None,
+            growth_bytes_per_sec: None,
+            secs_to_full,
+        };
+
+        engine
+            .on_filesystems(&[
+                fs("/", 99.0, None),
+                fs("/var", 50.0, Some(30 * 3600)),
+                fs("/var/log", 60.0, None),
+            ])
+            .await;
+        assert!(rx.try_recv().is_err(), "/ is not watched by the rule");
+
+        engine
+            .on_filesystems(&[fs("/var", 70.0, Some(3 * 3600)), fs("/var/lib", 95.0, None)])
+            .await;
+        let alert = rx.try_recv().unwrap();
+        assert_eq!(alert.severity, Severity::High);
+        assert_eq!(
+            alert.message,
+            "/var/lib 95.0% used; /var 70.0% used, full in ~3h"
+        );
+        let evidence = alert.evidence.unwrap();
+        assert_eq!(evidence.observed["used_pct"], 95.0);
+        assert_eq!(evidence.observed["filesystems"], 2.0);
+        assert_eq!(evidence.thresholds["hours_to_full"], 4.0);
+
+        assert!(
+            parse_rules(
+                "- name: bad\n  detector: filesystem_usage\n  mounts: [\"/\"]\n",
+                Some("yaml")
+            )
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn absence_rule_fires_on_silence_and_clears_on_resume() {
+        time::pause();
+        let yaml = "- name: backup_missing\n  detector: absence\n  comms: [\"backup*\"]\n  within_secs: 600\n  severity: high\n";
+        let cfg = parse_rules(yaml, Some("yaml")).unwrap().remove(0);
+        let engine = test_engine_with(cfg);
+        let mut rx = engine.tx.subscribe();
+        let exec = linnix_ai_ebpf_common::EventType::Exec as u32;
+        assert_eq!(
+            engine.absence_check_interval(),
+            Some(Duration::from_secs(60))
+        );
+
+        // The first check starts the window
+        engine.check_absence().await;
+        time::advance(Duration::from_secs(300)).await;
+        engine
+            .on_event(&ProcessEvent::new(wire(10, 1, exec, b"cron")))
+            .await;
+        time::advance(Duration::from_secs(301)).await;
+        engine.check_absence().await;
+        let alert = rx.try_recv().unwrap();
+        assert_eq!(alert.severity, Severity::High);
+        assert_eq!(
+            alert.message,
+            "no exec from backup* for 601s (expected within 600s)"
+        );
+
+        time::advance(Duration::from_secs(600)).await;
+        engine.check_absence().await;
+        assert!(rx.try_recv().is_err(), "fires once per silence");
+
+        engine
+            .on_event(&ProcessEvent::new(wire(11, 1, exec, b"backup.sh")))
+            .await;
+        let resumed = rx.try_recv().unwrap();
+        assert_eq!(resumed.severity, Severity::Info);
+        assert_eq!(
+            resumed.message,
+            "backup.sh(11) resumed after 1201s without exec"
+        );
+        engine.check_absence().await;
+        assert!(rx.try_recv().is_err());
+
+        assert!(
+            parse_rules(
+                "- name: bad\n  detector: absence\n  events: [\"boot\"]\n  within_secs: 60\n",
+                Some("yaml")
+            )
+            .is_err()
+        );
+    }
+
+    fn plain_alert(rule: &str, message: &str) -> Alert {
+        Alert {
+            rule: rule.to_string(),
+            severity: Severity::High,
+            message: message.to_string(),
+            host: "node-1".to_string(),
+            detection: "threshold",
+            suppressed_by: None,
+            lineage: Vec::new(),
+            children: Vec::new(),
+            pod: None,
+            evidence: None,
+            annotations: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn sender_suppresses_alerts_outside_rules_in_maintenance() {
+        use crate::config::{MaintenanceConfig, MaintenanceScope};
+        use crate::maintenance::CreateWindowRequest;
+
+        let metrics = Arc::new(Metrics::new());
+        let maintenance =
+            Arc::new(MaintenanceManager::from_config(&MaintenanceConfig::default()).unwrap());
+        maintenance
+            .create(CreateWindowRequest {
+                name: "bpf-upgrade".to_string(),
+                duration_secs: 600,
+                reason: String::new(),
+                created_by: None,
+                scope: MaintenanceScope {
+                    rules: vec!["map_*".to_string()],
+                    ..Default::default()
+                },
+            })
+            .unwrap();
+        let sender = AlertSender::new(16, Arc::clone(&metrics)).with_maintenance(maintenance);
+        let mut rx = sender.subscribe();
+
+        assert!(sender.send(plain_alert("map_cleanup", "pid map nearly full")));
+        assert!(sender.send(plain_alert("canary", "canary exec missing")));
+
+        let suppressed = rx.recv().await.unwrap();
+        assert_eq!(suppressed.suppressed_by.as_deref(), Some("bpf-upgrade"));
+        let delivered = rx.recv().await.unwrap();
+        assert_eq!(delivered.suppressed_by, None);
+        assert_eq!(metrics.alerts_emitted(), 2);
+        assert_eq!(metrics.alerts_suppressed(), 1);
+    }
+
+    #[tokio::test]
+    async fn sender_cooldown_holds_back_repeats_per_subject() {
+        time::pause();
+        let metrics = Arc::new(Metrics::new());
+        let sender =
+            AlertSender::new(16, Arc::clone(&metrics)).with_cooldown(Duration::from_secs(60));
+        let mut rx = sender.subscribe();
+
+        assert!(sender.send(plain_alert("canary", "canary exec missing")));
+        assert!(!sender.send(plain_alert("canary", "canary exec missing")));
+        assert!(sender.send(plain_alert("canary", "canary exit missing")));
+        assert_eq!(rx.recv().await.unwrap().message, "canary exec missing");
+        assert_eq!(rx.recv().await.unwrap().message, "canary exit missing");
+        assert_eq!(metrics.alerts_held_back(), 1);
+
+        time::advance(Duration::from_secs(61)).await;
+        assert!(sender.send(plain_alert("canary", "canary exec missing")));
+        assert!(rx.try_recv().is_ok());
+    }
 }
@@ -0,0 +1,272 @@
+//! CPU time by process ancestry, as folded stacks for flame graphs
+//!
+//! Every `sample_secs` the sampler reads `/proc/<pid>/stat` for every process
+//! and charges the CPU time each used since the previous sample to its
+//! ancestry, root first: `systemd;containerd-shim;python`. Charges are summed
+//! per stack into one-minute buckets kept for `retention_minutes`.
+//! `/profile/ancestry` merges the buckets of a time range into the folded
+//! format read by `flamegraph.pl` and compatible tools, one `stack weight`
+//! line per stack with the weight in CPU milliseconds. CPU used by a process
+//! that exits between two samples is not counted.
+
+use log::warn;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::config::AncestryProfileConfig;
+
+const BUCKET_SECS: i64 = 60;
+/// Ancestors followed before a stack is cut off.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Proc {
+    ppid: i32,
+    comm: String,
+    cpu_ticks: u64,
+}
+
+struct Bucket {
+    /// Unix seconds
+    start: i64,
+    cpu_ms: HashMap<String, u64>,
+}
+
+/// Reads per-process CPU counters and turns them into per-stack deltas.
+struct Sampler {
+    prev: HashMap<i32, u64>,
+    /// False until the first scan, whose counters are only a baseline
+    primed: bool,
+    ticks_per_sec: u64,
+}
+
+impl Sampler {
+    fn scan() -> HashMap<i32, Proc> {
+        let processes = match procfs::process::all_processes() {
+            Ok(processes) => processes,
+            Err(e) => {
+                warn!("[ancestry_profile] cannot list processes: {e}");
+                return HashMap::new();
+            }
+        };
+        processes
+            .flatten()
+            .filter_map(|process| process.stat().ok())
+            .map(|stat| {
+                (
+                    stat.pid,
+                    Proc {
+                        ppid: stat.ppid,
+                        comm: stat.comm,
+                        cpu_ticks: stat.utime + stat.stime,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// CPU milliseconds per stack since the previous call.
+    fn sample(&mut self, procs: &HashMap<i32, Proc>) -> HashMap<String, u64> {
+        let mut stacks = HashMap::new();
+        let mut memo = HashMap::new();
+        for (&pid, proc) in procs {
+            // Processes first seen after the baseline started since then.
+            let base = match self.prev.get(&pid) {
+                Some(&ticks) => ticks,
+                None if self.primed => 0,
+                None => proc.cpu_ticks,
+            };
+            let ms = proc.cpu_ticks.saturating_sub(base) * 1000 / self.ticks_per_sec.max(1);
+            if ms > 0 {
+                *stacks.entry(stack(pid, procs, &mut memo, 0)).or_insert(0) += ms;
+            }
+        }
+        self.prev = procs.iter().map(|(&pid, p)| (pid, p.cpu_ticks)).collect();
+        self.primed = true;
+        stacks
+    }
+}
+
+/// `;` separates frames and the last space the weight; keep both out of
+/// frame names.
+fn frame(comm: &str) -> String {
+    comm.chars()
+        .map(|c| {
+            if c == ';' || c.is_whitespace() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Folded stack of `pid`, root first.
+fn stack(
+    pid: i32,
+    procs: &HashMap<i32, Proc>,
+    memo: &mut HashMap<i32, String>,
+    depth: usize,
+) -> String {
+    if let Some(stack) = memo.get(&pid) {
+        return stack.clone();
+    }
+    let Some(proc) = procs.get(&pid) else {
+        return String::new();
+    };
+    let name = frame(&proc.comm);
+    let stack = if proc.ppid <= 0 || proc.ppid == pid || depth >= MAX_DEPTH {
+        name
+    } else {
+        let parent = stack(proc.ppid, procs, memo, depth + 1);
+        if parent.is_empty() {
+            name
+        } else {
+            format!("{parent};{name}")
+        }
+    };
+    memo.insert(pid, stack.clone());
+    stack
+}
+
+/// Per-stack CPU history shared with the API.
+pub struct AncestryProfile {
+    buckets: Mutex<VecDeque<Bucket>>,
+    retention: i64,
+}
+
+impl AncestryProfile {
+    fn new(retention_minutes: u64) -> Self {
+        Self {
+            buckets: Mutex::new(VecDeque::new()),
+            retention: retention_minutes.max(1) as i64 * 60,
+        }
+    }
+
+    pub fn spawn(config: &AncestryProfileConfig) -> Arc<Self> {
+        let profile = Arc::new(Self::new(config.retention_minutes));
+        let mut sampler = Sampler {
+            prev: HashMap::new(),
+            primed: false,
+            ticks_per_sec: procfs::ticks_per_second(),
+        };
+        let interval = Duration::from_secs(config.sample_secs.max(1));
+        let shared = Arc::clone(&profile);
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(interval);
+            loop {
+                tick.tick().await;
+                let stacks = sampler.sample(&Sampler::scan());
+                shared.charge(chrono::Utc::now().timestamp(), stacks);
+            }
+        });
+        profile
+    }
+
+    fn charge(&self, now: i64, stacks: HashMap<String, u64>) {
+        let start = now - now.rem_euclid(BUCKET_SECS);
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.back().is_none_or(|b| b.start != start) {
+            buckets.push_back(Bucket {
+                start,
+                cpu_ms: HashMap::new(),
+            });
+        }
+        let bucket = buckets.back_mut().expect("pushed");
+        for (stack, ms) in stacks {
+            *bucket.cpu_ms.entry(stack).or_insert(0) += ms;
+        }
+        while buckets
+            .front()
+            .is_some_and(|b| b.start + BUCKET_SECS <= now - self.retention)
+        {
+            buckets.pop_front();
+        }
+    }
+
+    /// Folded stacks of the buckets overlapping `[start, end)` (unix
+    /// seconds), sorted by stack.
+    pub fn folded(&self, start: i64, end: i64) -> String {
+        let mut merged: BTreeMap<String, u64> = BTreeMap::new();
+        for bucket in self.buckets.lock().unwrap().iter() {
+            if bucket.start + BUCKET_SECS <= start || bucket.start >= end {
+                continue;
+            }
+            for (stack, ms) in &bucket.cpu_ms {
+                *merged.entry(stack.clone()).or_insert(0) += ms;
+            }
+        }
+        merged
+            .into_iter()
+            .map(|(stack, ms)| format!("{stack} {ms}\n"))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn procs(entries: &[(i32, i32, &str, u64)]) -> HashMap<i32, Proc> {
+        entries
+            .iter()
+            .map(|&(pid, ppid, comm, cpu_ticks)| {
+                (
+                    pid,
+                    Proc {
+                        ppid,
+                        comm: comm.to_string(),
+                        cpu_ticks,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn charges_cpu_deltas_to_the_ancestry() {
+        let mut sampler = Sampler {
+            prev: HashMap::new(),
+            primed: false,
+            ticks_per_sec: 100,
+        };
+        let first = procs(&[
+            (1, 0, "systemd", 500),
+            (10, 1, "containerd-shim", 20),
+            (11, 10, "python", 100),
+        ]);
+        assert!(sampler.sample(&first).is_empty());
+
+        let second = procs(&[
+            (1, 0, "systemd", 501),
+            (10, 1, "containerd-shim", 20),
+            (11, 10, "python", 250),
+            (12, 10, "python", 50),
+            // Parent unknown: the stack starts at the process
+            (20, 99, "my;job x", 10),
+        ]);
+        let stacks = sampler.sample(&second);
+        assert_eq!(stacks.len(), 3);
+        assert_eq!(stacks["systemd"], 10);
+        assert_eq!(stacks["systemd;containerd-shim;python"], 2000);
+        assert_eq!(stacks["my_job_x"], 100);
+    }
+
+    #[test]
+    fn folds_the_buckets_of_the_range() {
+        let profile = AncestryProfile::new(10);
+        let t0 = 1_700_000_040;
+        profile.charge(t0, HashMap::from([("a;b".to_string(), 30)]));
+        profile.charge(t0 + 10, HashMap::from([("a;b".to_string(), 20)]));
+        profile.charge(t0 + 120, HashMap::from([("a;c".to_string(), 5)]));
+
+        assert_eq!(profile.folded(t0 - 3600, t0 + 3600), "a;b 50\na;c 5\n");
+        assert_eq!(profile.folded(t0 + 100, t0 + 3600), "a;c 5\n");
+        assert_eq!(profile.folded(t0 - 3600, t0 - 60), "");
+
+        // Past retention the oldest bucket is dropped
+        profile.charge(t0 + 660, HashMap::new());
+        assert_eq!(profile.folded(t0 - 3600, t0 + 3600), "a;c 5\n");
+    }
+}
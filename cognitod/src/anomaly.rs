@@ -0,0 +1,345 @@
+//! Threshold-free spike detection
+//!
+//! Each tracked pid keeps an exponentially weighted mean and variance of one
+//! signal (fork rate, network bytes, block IO bytes) sampled in fixed
+//! intervals. A closed interval whose rate exceeds `mean + k·σ` is reported as
+//! a spike once the series has seen `warmup_samples` intervals. Intervals are
+//! closed lazily when the next event for the pid arrives; idle intervals in
+//! between are folded in as zeros so bursty-then-quiet processes keep a
+//! realistic baseline.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::time::{Duration, Instant};
+
+use crate::ProcessEventWire;
+use crate::maintenance::glob_match;
+use linnix_ai_ebpf_common::{BlockOp, EventType};
+
+/// Upper bound on series per detector; the least recently seen are evicted.
+const MAX_SERIES: usize = 8192;
+/// Idle intervals folded in as zeros when a series resumes.
+const MAX_IDLE_FILL: u64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpikeSignal {
+    /// Forks per second issued by a parent pid.
+    ForkRate,
+    /// Network bytes per second sent and received.
+    NetBytes,
+    /// Block IO bytes per second queued.
+    BlockIo,
+}
+
+impl SpikeSignal {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SpikeSignal::ForkRate => "fork_rate",
+            SpikeSignal::NetBytes => "net_bytes",
+            SpikeSignal::BlockIo => "block_io",
+        }
+    }
+
+    /// The pid this event counts against and the amount it contributes.
+    pub fn sample(&self, event: &ProcessEventWire) -> Option<(u32, f64)> {
+        match self {
+            SpikeSignal::ForkRate if event.event_type == EventType::Fork as u32 => {
+                Some((event.ppid, 1.0))
+            }
+            SpikeSignal::NetBytes if event.event_type == EventType::Net as u32 => {
+                Some((event.pid, event.data as f64))
+            }
+            // Queue, issue and complete all carry the same bytes; count once.
+            SpikeSignal::BlockIo
+                if event.event_type == EventType::BlockIo as u32
+                    && event.aux == BlockOp::Queue as u32 =>
+            {
+                Some((event.pid, event.data as f64))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpikeOverride {
+    /// Glob matched against the process comm.
+    pub comm: String,
+    #[serde(default)]
+    pub k: Option<f64>,
+    #[serde(default)]
+    pub disabled: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SpikeConfig {
+    pub signal: SpikeSignal,
+    /// Standard deviations above the mean that count as a spike.
+    pub k: f64,
+    /// EWMA smoothing factor in (0, 1]; smaller adapts more slowly.
+    pub alpha: f64,
+    pub warmup_samples: u32,
+    pub interval: Duration,
+    pub overrides: Vec<SpikeOverride>,
+}
+
+/// Exponentially weighted mean and variance.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Ewma {
+    mean: f64,
+    var: f64,
+    samples: u32,
+}
+
+impl Ewma {
+    pub fn update(&mut self, x: f64, alpha: f64) {
+        if self.samples == 0 {
+            self.mean = x;
+            self.var = 0.0;
+        } else {
+            let diff = x - self.mean;
+            let incr = alpha * diff;
+            self.mean += incr;
+            self.var = (1.0 - alpha) * (self.var + diff * incr);
+        }
+        self.samples = self.samples.saturating_add(1);
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    pub fn stddev(&self) -> f64 {
+        self.var.max(0.0).sqrt()
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Spike {
+    pub pid: u32,
+    /// Rate over the closed interval, per second.
+    pub value: f64,
+    pub mean: f64,
+    pub sigma: f64,
+    pub k: f64,
+}
+
+impl Spike {
+    pub fn z_score(&self) -> f64 {
+        (self.value - self.mean) / self.sigma
+    }
+}
+
+struct Series {
+    stats: Ewma,
+    bucket_start: Instant,
+    bucket_value: f64,
+}
+
+pub struct SpikeDetector {
+    cfg: SpikeConfig,
+    series: HashMap<u32, Series>,
+}
+
+impl SpikeDetector {
+    pub fn new(cfg: SpikeConfig) -> Self {
+        Self {
+            cfg,
+            series: HashMap::new(),
+        }
+    }
+
+    pub fn signal(&self) -> SpikeSignal {
+        self.cfg.signal
+    }
+
+    /// `None` when the comm is excluded; otherwise the k to apply.
+    fn k_for(&self, comm: &str) -> Option<f64> {
+        match self
+            .cfg
+            .overrides
+            .iter()
+            .find(|o| glob_match(&o.comm, comm))
+        {
+            Some(o) if o.disabled => None,
+            Some(o) => Some(o.k.unwrap_or(self.cfg.k)),
+            None => Some(self.cfg.k),
+        }
+    }
+
+    /// Add `value` to the pid's current interval. Returns a spike if this
+    /// call closed an interval that deviated from the baseline.
+    pub fn record(&mut self, pid: u32, comm: &str, value: f64, now: Instant) -> Option<Spike> {
+        let k = self.k_for(comm)?;
+        if !self.series.contains_key(&pid) && self.series.len() >= MAX_SERIES {
+            self.evict_oldest();
+        }
+        let interval = self.cfg.interval.max(Duration::from_millis(1));
+        let series = self.series.entry(pid).or_insert_with(|| Series {
+            stats: Ewma::default(),
+            bucket_start: now,
+            bucket_value: 0.0,
+        });
+
+        let mut spike = None;
+        let elapsed = now.saturating_duration_since(series.bucket_start);
+        if elapsed >= interval {
+            let closed = (elapsed.as_nanos() / interval.as_nanos()) as u64;
+            let rate = series.bucket_value / interval.as_secs_f64();
+            let stats = &series.stats;
+            if stats.samples() >= self.cfg.warmup_samples {
+                // Floor σ so perfectly flat baselines don't alert on noise.
+                let sigma = stats.stddev().max(stats.mean().abs() * 0.05).max(1.0);
+                if rate > stats.mean() + k * sigma {
+                    spike = Some(Spike {
+                        pid,
+                        value: rate,
+                        mean: stats.mean(),
+                        sigma,
+                        k,
+                    });
+                }
+            }
+            series.stats.update(rate, self.cfg.alpha);
+            for _ in 1..closed.min(MAX_IDLE_FILL) {
+                series.stats.update(0.0, self.cfg.alpha);
+            }
+            series.bucket_start += interval * closed.min(u32::MAX as u64) as u32;
+            series.bucket_value = 0.0;
+        }
+        series.bucket_value += value;
+        spike
+    }
+
+    pub fn forget(&mut self, pid: u32) {
+        self.series.remove(&pid);
+    }
+
+    pub fn tracked(&self) -> usize {
+        self.series.len()
+    }
+
+    fn evict_oldest(&mut self) {
+        if let Some(pid) = self
+            .series
+            .iter()
+            .min_by_key(|(_, s)| s.bucket_start)
+            .map(|(pid, _)| *pid)
+        {
+            self.series.remove(&pid);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector(overrides: Vec<SpikeOverride>) -> SpikeDetector {
+        SpikeDetector::new(SpikeConfig {
+            signal: SpikeSignal::NetBytes,
+            k: 4.0,
+            alpha: 0.2,
+            warmup_samples: 10,
+            interval: Duration::from_secs(1),
+            overrides,
+        })
+    }
+
+    /// Feed `per_sec` once per interval for `secs` intervals.
+    fn feed(d: &mut SpikeDetector, comm: &str, start: Instant, secs: u64, per_sec: f64) -> usize {
+        (0..secs)
+            .filter_map(|s| d.record(42, comm, per_sec, start + Duration::from_secs(s)))
+            .count()
+    }
+
+    #[test]
+    fn ewma_tracks_mean_and_variance() {
+        let mut e = Ewma::default();
+        for x in [10.0, 10.0, 10.0, 10.0] {
+            e.update(x, 0.5);
+        }
+        assert_eq!(e.mean(), 10.0);
+        assert_eq!(e.stddev(), 0.0);
+        e.update(20.0, 0.5);
+        assert_eq!(e.mean(), 15.0);
+        assert!(e.stddev() > 0.0);
+    }
+
+    #[test]
+    fn flags_spike_after_warmup_only() {
+        let start = Instant::now();
+        let mut d = detector(vec![]);
+        // A spike during warm-up is absorbed into the baseline.
+        assert_eq!(feed(&mut d, "app", start, 3, 1_000_000.0), 0);
+
+        let mut d = detector(vec![]);
+        assert_eq!(feed(&mut d, "app", start, 30, 1_000.0), 0);
+        d.record(42, "app", 50_000.0, start + Duration::from_secs(30));
+        let spike = d
+            .record(42, "app", 1_000.0, start + Duration::from_secs(31))
+            .expect("spike after warm-up");
+        assert_eq!(spike.pid, 42);
+        assert_eq!(spike.value, 50_000.0);
+        assert!(spike.z_score() > 4.0);
+    }
+
+    #[test]
+    fn overrides_raise_k_or_disable() {
+        let start = Instant::now();
+        let mut d = detector(vec![
+            SpikeOverride {
+                comm: "backup*".into(),
+                k: None,
+                disabled: true,
+            },
+            SpikeOverride {
+                comm: "noisy".into(),
+                k: Some(1_000.0),
+                disabled: false,
+            },
+        ]);
+        assert_eq!(feed(&mut d, "backup-job", start, 5, 10.0), 0);
+        assert_eq!(d.tracked(), 0);
+
+        feed(&mut d, "noisy", start, 30, 1_000.0);
+        d.record(42, "noisy", 50_000.0, start + Duration::from_secs(30));
+        assert!(
+            d.record(42, "noisy", 1_000.0, start + Duration::from_secs(31))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn signals_select_matching_events() {
+        let mut event = ProcessEventWire {
+            pid: 7,
+            ppid: 3,
+            uid: 0,
+            gid: 0,
+            event_type: EventType::Fork as u32,
+            ts_ns: 0,
+            seq: 0,
+            comm: [0; 16],
+            exit_time_ns: 0,
+            cpu_pct_milli: 0,
+            mem_pct_milli: 0,
+            data: 4096,
+            data2: 0,
+            aux: BlockOp::Complete as u32,
+            aux2: 0,
+            cgroup_id: 0,
+        };
+        assert_eq!(SpikeSignal::ForkRate.sample(&event), Some((3, 1.0)));
+        assert_eq!(SpikeSignal::NetBytes.sample(&event), None);
+        event.event_type = EventType::BlockIo as u32;
+        assert_eq!(SpikeSignal::BlockIo.sample(&event), None);
+        event.aux = BlockOp::Queue as u32;
+        assert_eq!(SpikeSignal::BlockIo.sample(&event), Some((7, 4096.0)));
+    }
+}
@@ -0,0 +1,375 @@
+//! Client IP allow/deny lists and per-IP rate limiting for the API
+//!
+//! Runs before authentication so refused and throttled clients never reach a
+//! handler. Denied clients get 403; clients over their token bucket get 429
+//! with `Retry-After`. Rejections are counted per client IP for
+//! `/metrics/prometheus`.
+
+use anyhow::{Context, bail};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use cognitod::config::{ApiConfig, ApiRateLimitConfig};
+
+/// Token buckets kept before idle ones are dropped.
+const MAX_BUCKETS: usize = 65_536;
+/// Clients with their own rejection counters; the rest share `other`.
+const MAX_TRACKED_CLIENTS: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// `10.0.0.0/8`, `fd00::/8`, or a single address.
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let (addr, prefix) = match text.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (text, None),
+        };
+        let network: IpAddr = addr
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid address in {text:?}"))?;
+        let network = network.to_canonical();
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p
+                .trim()
+                .parse::<u8>()
+                .with_context(|| format!("invalid prefix in {text:?}"))?,
+            None => max,
+        };
+        if prefix > max {
+            bail!("prefix /{prefix} too long in {text:?}");
+        }
+        Ok(Self { network, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+fn parse_list(entries: &[String], field: &str) -> anyhow::Result<Vec<Cidr>> {
+    entries
+        .iter()
+        .map(|entry| Cidr::parse(entry).with_context(|| format!("api.{field}")))
+        .collect()
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token bucket per client: `burst` requests at once, refilled at
+/// `requests_per_sec`.
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &ApiRateLimitConfig) -> anyhow::Result<Self> {
+        if !config.requests_per_sec.is_finite() || config.requests_per_sec <= 0.0 {
+            bail!("api.rate_limit.requests_per_sec must be positive");
+        }
+        Ok(Self {
+            rate: config.requests_per_sec,
+            burst: f64::from(config.burst.max(1)),
+            buckets: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Take a token for `ip`, or report how long until one is available.
+    pub fn check(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_BUCKETS && !buckets.contains_key(&ip) {
+            self.drop_refilled(&mut buckets, now);
+        }
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+        }
+    }
+
+    /// Forget clients whose bucket has refilled; they would start full anyway.
+    fn drop_refilled(&self, buckets: &mut HashMap<IpAddr, Bucket>, now: Instant) {
+        buckets.retain(|_, b| {
+            b.tokens + now.saturating_duration_since(b.updated).as_secs_f64() * self.rate
+                < self.burst
+        });
+        if buckets.len() >= MAX_BUCKETS {
+            buckets.clear();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rejections {
+    pub denied: u64,
+    pub rate_limited: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Verdict {
+    Allow,
+    Deny,
+    Throttle(Duration),
+}
+
+pub struct AccessControl {
+    allow: Vec<Cidr>,
+    deny: Vec<Cidr>,
+    trusted_proxies: Vec<Cidr>,
+    limiter: Option<RateLimiter>,
+    rejections: Mutex<HashMap<IpAddr, Rejections>>,
+    /// Rejections of clients beyond `MAX_TRACKED_CLIENTS`.
+    other: Mutex<Rejections>,
+}
+
+impl AccessControl {
+    /// `None` when the config sets no lists and no rate limit.
+    pub fn from_config(config: &ApiConfig) -> anyhow::Result<Option<Self>> {
+        let access = Self {
+            allow: parse_list(&config.allow_cidrs, "allow_cidrs")?,
+            deny: parse_list(&config.deny_cidrs, "deny_cidrs")?,
+            trusted_proxies: parse_list(&config.trusted_proxies, "trusted_proxies")?,
+            limiter: config
+                .rate_limit
+                .as_ref()
+                .map(RateLimiter::new)
+                .transpose()?,
+            rejections: Mutex::new(HashMap::new()),
+            other: Mutex::new(Rejections::default()),
+        };
+        let active =
+            !access.allow.is_empty() || !access.deny.is_empty() || access.limiter.is_some();
+        Ok(active.then_some(access))
+    }
+
+    /// The client behind `peer`: the nearest untrusted hop of
+    /// `X-Forwarded-For` when `peer` is a trusted proxy, else `peer`.
+    fn client_ip(&self, peer: IpAddr, forwarded_for: Option<&str>) -> IpAddr {
+        let peer = peer.to_canonical();
+        let trusted = |ip: IpAddr| self.trusted_proxies.iter().any(|c| c.contains(ip));
+        if !trusted(peer) {
+            return peer;
+        }
+        let hops: Vec<IpAddr> = forwarded_for
+            .unwrap_or("")
+            .split(',')
+            .filter_map(|hop| hop.trim().parse::<IpAddr>().ok())
+            .map(|ip| ip.to_canonical())
+            .collect();
+        hops.iter()
+            .rev()
+            .find(|ip| !trusted(**ip))
+            .or(hops.first())
+            .copied()
+            .unwrap_or(peer)
+    }
+
+    fn check(&self, client: IpAddr, now: Instant) -> Verdict {
+        let client = client.to_canonical();
+        let denied = self.deny.iter().any(|c| c.contains(client))
+            || (!self.allow.is_empty() && !self.allow.iter().any(|c| c.contains(client)));
+        let verdict = if denied {
+            Verdict::Deny
+        } else {
+            match self.limiter.as_ref().map(|l| l.check(client, now)) {
+                Some(Err(retry_after)) => Verdict::Throttle(retry_after),
+                _ => Verdict::Allow,
+            }
+        };
+        if verdict != Verdict::Allow {
+            self.record(client, verdict);
+        }
+        verdict
+    }
+
+    fn record(&self, client: IpAddr, verdict: Verdict) {
+        let bump = |counts: &mut Rejections| match verdict {
+            Verdict::Deny => counts.denied += 1,
+            Verdict::Throttle(_) => counts.rate_limited += 1,
+            Verdict::Allow => {}
+        };
+        let mut rejections = self.rejections.lock().unwrap();
+        if rejections.len() >= MAX_TRACKED_CLIENTS && !rejections.contains_key(&client) {
+            bump(&mut self.other.lock().unwrap());
+        } else {
+            bump(rejections.entry(client).or_default());
+        }
+    }
+
+    /// Rejections per client IP, then `("other", ..)` for untracked clients.
+    pub fn rejections(&self) -> Vec<(String, Rejections)> {
+        let mut out: Vec<(String, Rejections)> = self
+            .rejections
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(ip, counts)| (ip.to_string(), *counts))
+            .collect();
+        out.sort_by(|a, b| a.0.cmp(&b.0));
+        let other = *self.other.lock().unwrap();
+        if other != Rejections::default() {
+            out.push(("other".into(), other));
+        }
+        out
+    }
+}
+
+pub async fn access_middleware(
+    State(access): State<Arc<AccessControl>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let forwarded_for = request
+        .headers()
+        .get("x-forwarded-for")
+        .and_then(|v| v.to_str().ok());
+    let client = access.client_ip(peer.ip(), forwarded_for);
+    match access.check(client, Instant::now()) {
+        Verdict::Allow => next.run(request).await,
+        Verdict::Deny => (StatusCode::FORBIDDEN, "Forbidden").into_response(),
+        Verdict::Throttle(retry_after) => {
+            let secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+            let mut response = (StatusCode::TOO_MANY_REQUESTS, "Too Many Requests").into_response();
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, extract::connect_info::MockConnectInfo, routing::get};
+    use tower::ServiceExt;
+
+    fn ip(text: &str) -> IpAddr {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn filters_and_throttles_clients() {
+        let config = ApiConfig {
+            allow_cidrs: vec!["10.0.0.0/8".into(), "::1".into()],
+            deny_cidrs: vec!["10.6.6.0/24".into()],
+            trusted_proxies: vec!["10.0.0.2".into()],
+            rate_limit: Some(ApiRateLimitConfig {
+                requests_per_sec: 2.0,
+                burst: 3,
+            }),
+            ..ApiConfig::default()
+        };
+        let access = AccessControl::from_config(&config).unwrap().unwrap();
+        assert!(
+            AccessControl::from_config(&ApiConfig::default())
+                .unwrap()
+                .is_none()
+        );
+        assert!(Cidr::parse("10.0.0.0/33").is_err());
+
+        // IPv4-mapped peers match IPv4 ranges
+        let t0 = Instant::now();
+        assert_eq!(access.check(ip("::ffff:10.1.2.3"), t0), Verdict::Allow);
+        assert_eq!(access.check(ip("192.168.1.1"), t0), Verdict::Deny);
+        assert_eq!(access.check(ip("10.6.6.9"), t0), Verdict::Deny);
+
+        // The proxy's own address is not the client
+        let client = access.client_ip(ip("10.0.0.2"), Some("203.0.113.7, 10.9.9.9"));
+        assert_eq!(client, ip("10.9.9.9"));
+        assert_eq!(
+            access.client_ip(ip("10.0.0.3"), Some("10.9.9.9")),
+            ip("10.0.0.3")
+        );
+
+        // Burst of 3 (one spent above), then one token every 500ms
+        let client = ip("10.1.2.3");
+        assert_eq!(access.check(client, t0), Verdict::Allow);
+        assert_eq!(access.check(client, t0), Verdict::Allow);
+        assert_eq!(
+            access.check(client, t0),
+            Verdict::Throttle(Duration::from_millis(500))
+        );
+        let t1 = t0 + Duration::from_millis(500);
+        assert_eq!(access.check(client, t1), Verdict::Allow);
+        assert!(matches!(access.check(client, t1), Verdict::Throttle(_)));
+
+        let rejections = access.rejections();
+        let by_ip: HashMap<_, _> = rejections.into_iter().collect();
+        assert_eq!(by_ip["10.1.2.3"].rate_limited, 2);
+        assert_eq!(by_ip["192.168.1.1"].denied, 1);
+    }
+
+    #[tokio::test]
+    async fn middleware_answers_429_with_retry_after() {
+        let config = ApiConfig {
+            rate_limit: Some(ApiRateLimitConfig {
+                requests_per_sec: 0.5,
+                burst: 1,
+            }),
+            ..ApiConfig::default()
+        };
+        let access = Arc::new(AccessControl::from_config(&config).unwrap().unwrap());
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(
+                access,
+                access_middleware,
+            ))
+            .layer(MockConnectInfo(SocketAddr::from(([192, 0, 2, 1], 4000))));
+
+        let request = || {
+            axum::http::Request::builder()
+                .uri("/")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let first = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+        let second = app.oneshot(request()).await.unwrap();
+        assert_eq!(second.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(second.headers()[header::RETRY_AFTER], "2");
+    }
+}
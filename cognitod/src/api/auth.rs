@@ -1,27 +1,76 @@
 use axum::{
     extract::{Request, State},
-    http::{HeaderMap, StatusCode},
+    http::{HeaderMap, Method, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
 };
+use std::sync::Arc;
 
+use crate::config::{ApiTokenConfig, TokenScope};
+
+/// Bearer tokens the API accepts, with their scopes.
+pub struct Tokens {
+    tokens: Vec<(String, TokenScope)>,
+}
+
+impl Tokens {
+    /// `auth_token` has operator scope. `None` when no token is configured
+    /// and the API is open.
+    pub fn new(auth_token: Option<String>, scoped: &[ApiTokenConfig]) -> Option<Self> {
+        let tokens: Vec<_> = auth_token
+            .map(|token| (token, TokenScope::Operator))
+            .into_iter()
+            .chain(scoped.iter().map(|t| (t.token.clone(), t.scope)))
+            .collect();
+        (!tokens.is_empty()).then_some(Self { tokens })
+    }
+
+    fn scope(&self, token: &str) -> Option<TokenScope> {
+        self.tokens
+            .iter()
+            .find(|(expected, _)| expected == token)
+            .map(|&(_, scope)| scope)
+    }
+}
+
+/// Requests a `read_only` token may make.
+fn is_read(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Admit requests with a known bearer token and record its scope as a
+/// request extension for the handlers.
 pub async fn auth_middleware(
-    State(expected_token): State<Option<String>>,
+    State(tokens): State<Arc<Tokens>>,
     headers: HeaderMap,
-    request: Request,
+    mut request: Request,
     next: Next,
 ) -> Response {
-    let Some(expected) = expected_token else {
-        return next.run(request).await;
-    };
-
-    if let Some(auth_header) = headers.get(axum::http::header::AUTHORIZATION)
-        && let Ok(auth_str) = auth_header.to_str()
-        && let Some(token) = auth_str.strip_prefix("Bearer ")
-        && token == expected
-    {
-        return next.run(request).await;
+    let scope = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|token| tokens.scope(token));
+    match scope {
+        Some(TokenScope::ReadOnly) if !is_read(request.method()) => {
+            (StatusCode::FORBIDDEN, "Read-only token").into_response()
+        }
+        Some(scope) => {
+            request.extensions_mut().insert(scope);
+            next.run(request).await
+        }
+        None => (StatusCode::UNAUTHORIZED, "Unauthorized").into_response(),
     }
+}
 
-    (StatusCode::UNAUTHORIZED, "Unauthorized").into_response()
+/// Refuse read-only tokens on routes that are operator-only whatever the
+/// method. Requests admitted without a token (open API) pass, as they do
+/// everywhere else.
+pub async fn require_operator(request: Request, next: Next) -> Response {
+    match request.extensions().get::<TokenScope>() {
+        Some(TokenScope::ReadOnly) => {
+            (StatusCode::FORBIDDEN, "Operator token required").into_response()
+        }
+        _ => next.run(request).await,
+    }
 }
@@ -0,0 +1,612 @@
+//! Bulk export of incidents, enforcement actions and alert history
+//! (`/export/{dataset}`) for analysis outside the daemon.
+//!
+//! Every dataset is a fixed set of typed columns; a dotted column name
+//! reaches into a nested field (`action.pid`), and nested values without a
+//! column of their own are written as JSON text. Rows are produced in
+//! batches and encoded as they arrive, so a large range never sits in
+//! memory as one response: CSV is written a batch at a time and Parquet
+//! (the `parquet` feature) gets a row group per batch.
+//!
+//! `/state/export` is the other kind of export: the versioned state bundle
+//! a replacement install imports (see `cognitod::state_bundle`).
+
+use axum::body::Body;
+use axum::extract::{Path, Query, State};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use bytes::Bytes;
+use log::warn;
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use super::AppState;
+use cognitod::IncidentStore;
+use cognitod::state_bundle::{self, StateSources};
+
+/// Rows per batch, and per Parquet row group.
+const BATCH_ROWS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Int,
+    Float,
+    Bool,
+    Text,
+}
+
+#[derive(Debug)]
+struct Column {
+    name: &'static str,
+    #[cfg_attr(not(feature = "parquet"), allow(dead_code))]
+    kind: Kind,
+}
+
+const fn col(name: &'static str, kind: Kind) -> Column {
+    Column { name, kind }
+}
+
+const INCIDENT_COLUMNS: &[Column] = &[
+    col("id", Kind::Int),
+    col("timestamp", Kind::Int),
+    col("event_type", Kind::Text),
+    col("psi_cpu", Kind::Float),
+    col("psi_memory", Kind::Float),
+    col("cpu_percent", Kind::Float),
+    col("load_avg", Kind::Text),
+    col("action", Kind::Text),
+    col("target_pid", Kind::Int),
+    col("target_name", Kind::Text),
+    col("recovery_time_ms", Kind::Int),
+    col("psi_after", Kind::Float),
+    col("llm_analysis", Kind::Text),
+    col("llm_analyzed_at", Kind::Int),
+    col("dependents", Kind::Text),
+    col("delivery", Kind::Text),
+    col("system_snapshot", Kind::Text),
+];
+
+const ACTION_COLUMNS: &[Column] = &[
+    col("id", Kind::Text),
+    col("created_at", Kind::Int),
+    col("expires_at", Kind::Int),
+    col("status", Kind::Text),
+    col("action.type", Kind::Text),
+    col("action.pid", Kind::Int),
+    col("action.signal", Kind::Int),
+    col("action.cgroup_path", Kind::Text),
+    col("target.start_ns", Kind::Int),
+    col("reason", Kind::Text),
+    col("source", Kind::Text),
+    col("rule", Kind::Text),
+    col("confidence", Kind::Float),
+    col("approved_by", Kind::Text),
+    col("approved_at", Kind::Int),
+    col("outcome.executed_at", Kind::Int),
+    col("outcome.improved", Kind::Bool),
+    col("outcome", Kind::Text),
+    col("reminders", Kind::Int),
+    col("error", Kind::Text),
+];
+
+const ALERT_COLUMNS: &[Column] = &[
+    col("id", Kind::Text),
+    col("timestamp", Kind::Int),
+    col("severity", Kind::Text),
+    col("rule", Kind::Text),
+    col("message", Kind::Text),
+    col("host", Kind::Text),
+    col("pod.namespace", Kind::Text),
+    col("pod.pod_name", Kind::Text),
+    col("pod.container_name", Kind::Text),
+    col("lineage", Kind::Text),
+    col("annotations", Kind::Text),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Dataset {
+    Incidents,
+    Actions,
+    Alerts,
+}
+
+impl Dataset {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "incidents" => Some(Self::Incidents),
+            "actions" => Some(Self::Actions),
+            "alerts" => Some(Self::Alerts),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Incidents => "incidents",
+            Self::Actions => "actions",
+            Self::Alerts => "alerts",
+        }
+    }
+
+    fn columns(self) -> &'static [Column] {
+        match self {
+            Self::Incidents => INCIDENT_COLUMNS,
+            Self::Actions => ACTION_COLUMNS,
+            Self::Alerts => ALERT_COLUMNS,
+        }
+    }
+
+    /// The requested columns in request order, or all of them.
+    fn select(self, columns: Option<&str>) -> Result<Vec<&'static Column>, String> {
+        let all = self.columns();
+        let Some(columns) = columns.filter(|c| !c.trim().is_empty()) else {
+            return Ok(all.iter().collect());
+        };
+        columns
+            .split(',')
+            .map(str::trim)
+            .map(|name| {
+                all.iter().find(|c| c.name == name).ok_or_else(|| {
+                    let known: Vec<_> = all.iter().map(|c| c.name).collect();
+                    format!(
+                        "unknown {} column '{name}' (known: {})",
+                        self.name(),
+                        known.join(", ")
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Format {
+    #[default]
+    Csv,
+    Parquet,
+}
+
+#[derive(Deserialize)]
+pub(super) struct ExportQuery {
+    /// Unix seconds, inclusive.
+    #[serde(default)]
+    start: Option<u64>,
+    /// Unix seconds, inclusive.
+    #[serde(default)]
+    end: Option<u64>,
+    #[serde(default)]
+    format: Format,
+    /// Comma-separated column names; all columns when absent.
+    #[serde(default)]
+    columns: Option<String>,
+}
+
+/// Where rows come from: records already in memory, or pages of the
+/// incident store.
+enum Source {
+    Records(std::vec::IntoIter<Value>),
+    Incidents {
+        store: Arc<IncidentStore>,
+        start: i64,
+        end: i64,
+        after_id: i64,
+        done: bool,
+    },
+}
+
+impl Source {
+    /// Up to `BATCH_ROWS` records; empty once exhausted.
+    async fn next_batch(&mut self) -> anyhow::Result<Vec<Value>> {
+        match self {
+            Self::Records(records) => Ok(records.by_ref().take(BATCH_ROWS).collect()),
+            Self::Incidents {
+                store,
+                start,
+                end,
+                after_id,
+                done,
+            } => {
+                if *done {
+                    return Ok(Vec::new());
+                }
+                let page = store
+                    .page(*start, *end, *after_id, BATCH_ROWS as i64)
+                    .await?;
+                *done = page.len() < BATCH_ROWS;
+                if let Some(id) = page.last().and_then(|i| i.id) {
+                    *after_id = id;
+                }
+                Ok(page
+                    .iter()
+                    .map(serde_json::to_value)
+                    .collect::<Result<_, _>>()?)
+            }
+        }
+    }
+}
+
+/// Records with a timestamp field in `[start, end]`, oldest first.
+fn in_range<T: serde::Serialize>(
+    records: Vec<T>,
+    timestamp: impl Fn(&T) -> u64,
+    start: u64,
+    end: u64,
+) -> Source {
+    let mut records: Vec<T> = records
+        .into_iter()
+        .filter(|r| (start..=end).contains(&timestamp(r)))
+        .collect();
+    records.sort_by_key(&timestamp);
+    let values: Vec<Value> = records
+        .iter()
+        .filter_map(|r| serde_json::to_value(r).ok())
+        .collect();
+    Source::Records(values.into_iter())
+}
+
+// GET /export/{dataset} - Incidents, actions or alerts as CSV or Parquet
+pub(super) async fn export(
+    State(app): State<Arc<AppState>>,
+    Path(dataset): Path<String>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    let dataset = Dataset::parse(&dataset).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            format!("unknown dataset '{dataset}' (incidents, actions, alerts)"),
+        )
+    })?;
+    let columns = dataset
+        .select(query.columns.as_deref())
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let start = query.start.unwrap_or(0);
+    let end = query.end.unwrap_or(u64::MAX);
+    if start > end {
+        return Err((StatusCode::BAD_REQUEST, "start is after end".to_string()));
+    }
+
+    let source = match dataset {
+        Dataset::Incidents => {
+            let store = app.incident_store.clone().ok_or_else(|| {
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "Incident store not available".to_string(),
+                )
+            })?;
+            Source::Incidents {
+                store,
+                start: start.min(i64::MAX as u64) as i64,
+                end: end.min(i64::MAX as u64) as i64,
+                after_id: 0,
+                done: false,
+            }
+        }
+        Dataset::Actions => {
+            let actions = match &app.enforcement {
+                Some(queue) => queue.get_all().await,
+                None => Vec::new(),
+            };
+            in_range(actions, |a| a.created_at, start, end)
+        }
+        Dataset::Alerts => {
+            let alerts = app.alert_history.get_all().await;
+            in_range(alerts, |a| a.timestamp, start, end)
+        }
+    };
+
+    let (content_type, extension, body) = match query.format {
+        Format::Csv => ("text/csv", "csv", csv_body(source, columns)),
+        #[cfg(feature = "parquet")]
+        Format::Parquet => (
+            "application/vnd.apache.parquet",
+            "parquet",
+            parquet_out::body(source, columns),
+        ),
+        #[cfg(not(feature = "parquet"))]
+        Format::Parquet => {
+            return Err((
+                StatusCode::NOT_IMPLEMENTED,
+                "built without the parquet feature".to_string(),
+            ));
+        }
+    };
+    let disposition = format!("attachment; filename=\"{}.{extension}\"", dataset.name());
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// The value of a (possibly dotted) column in a record; `Null` if absent.
+fn cell<'a>(record: &'a Value, column: &str) -> &'a Value {
+    let pointer = format!("/{}", column.replace('.', "/"));
+    record.pointer(&pointer).unwrap_or(&Value::Null)
+}
+
+/// Text form of a value: strings as-is, nested values as JSON.
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+fn csv_field(out: &mut String, value: &str) {
+    if value.contains([',', '"', '\n', '\r']) {
+        out.push('"');
+        out.push_str(&value.replace('"', "\"\""));
+        out.push('"');
+    } else {
+        out.push_str(value);
+    }
+}
+
+fn csv_header(columns: &[&Column]) -> String {
+    let mut out = String::new();
+    for (i, column) in columns.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        csv_field(&mut out, column.name);
+    }
+    out.push_str("\r\n");
+    out
+}
+
+fn csv_rows(records: &[Value], columns: &[&Column]) -> String {
+    let mut out = String::new();
+    for record in records {
+        for (i, column) in columns.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            if let Some(value) = text(cell(record, column.name)) {
+                csv_field(&mut out, &value);
+            }
+        }
+        out.push_str("\r\n");
+    }
+    out
+}
+
+fn csv_body(mut source: Source, columns: Vec<&'static Column>) -> Body {
+    let (tx, rx) = mpsc::channel::<std::io::Result<Bytes>>(4);
+    tokio::spawn(async move {
+        if tx.send(Ok(csv_header(&columns).into())).await.is_err() {
+            return;
+        }
+        loop {
+            let chunk = match source.next_batch().await {
+                Ok(batch) if batch.is_empty() => return,
+                Ok(batch) => Ok(csv_rows(&batch, &columns).into()),
+                Err(e) => {
+                    warn!("[export] CSV export stopped: {e:#}");
+                    Err(std::io::Error::other(e.to_string()))
+                }
+            };
+            let failed = chunk.is_err();
+            if tx.send(chunk).await.is_err() || failed {
+                return;
+            }
+        }
+    });
+    Body::from_stream(ReceiverStream::new(rx))
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_out {
+    use super::{Column, Kind, Source, cell, text};
+    use arrow_array::{ArrayRef, BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use axum::body::Body;
+    use bytes::Bytes;
+    use log::warn;
+    use parquet::arrow::ArrowWriter;
+    use parquet::basic::Compression;
+    use parquet::file::properties::WriterProperties;
+    use serde_json::Value;
+    use std::io::{self, Write};
+    use std::sync::Arc;
+    use tokio::sync::mpsc;
+    use tokio_stream::wrappers::ReceiverStream;
+
+    /// Bytes buffered before they are handed to the response.
+    const CHUNK_BYTES: usize = 256 * 1024;
+
+    /// `Write` end of the response body, used from a blocking task.
+    struct ChannelWriter {
+        buf: Vec<u8>,
+        tx: mpsc::Sender<io::Result<Bytes>>,
+    }
+
+    impl Write for ChannelWriter {
+        fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            self.buf.extend_from_slice(data);
+            if self.buf.len() >= CHUNK_BYTES {
+                self.flush()?;
+            }
+            Ok(data.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            if self.buf.is_empty() {
+                return Ok(());
+            }
+            let chunk = Bytes::from(std::mem::take(&mut self.buf));
+            self.tx
+                .blocking_send(Ok(chunk))
+                .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "client went away"))
+        }
+    }
+
+    fn schema(columns: &[&Column]) -> Arc<Schema> {
+        let fields: Vec<Field> = columns
+            .iter()
+            .map(|c| {
+                let ty = match c.kind {
+                    Kind::Int => DataType::Int64,
+                    Kind::Float => DataType::Float64,
+                    Kind::Bool => DataType::Boolean,
+                    Kind::Text => DataType::Utf8,
+                };
+                Field::new(c.name, ty, true)
+            })
+            .collect();
+        Arc::new(Schema::new(fields))
+    }
+
+    fn record_batch(
+        schema: &Arc<Schema>,
+        records: &[Value],
+        columns: &[&Column],
+    ) -> anyhow::Result<RecordBatch> {
+        let arrays: Vec<ArrayRef> = columns
+            .iter()
+            .map(|c| -> ArrayRef {
+                let values = records.iter().map(|r| cell(r, c.name));
+                match c.kind {
+                    Kind::Int => Arc::new(values.map(Value::as_i64).collect::<Int64Array>()),
+                    Kind::Float => Arc::new(values.map(Value::as_f64).collect::<Float64Array>()),
+                    Kind::Bool => Arc::new(values.map(Value::as_bool).collect::<BooleanArray>()),
+                    Kind::Text => Arc::new(values.map(text).collect::<StringArray>()),
+                }
+            })
+            .collect();
+        Ok(RecordBatch::try_new(schema.clone(), arrays)?)
+    }
+
+    pub(super) fn body(mut source: Source, columns: Vec<&'static Column>) -> Body {
+        let (rows_tx, mut rows_rx) = mpsc::channel::<Vec<Value>>(2);
+        let (tx, rx) = mpsc::channel::<io::Result<Bytes>>(4);
+
+        tokio::spawn(async move {
+            loop {
+                match source.next_batch().await {
+                    Ok(batch) if batch.is_empty() => return,
+                    Ok(batch) => {
+                        if rows_tx.send(batch).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(e) => {
+                        warn!("[export] Parquet export stopped: {e:#}");
+                        return;
+                    }
+                }
+            }
+        });
+
+        let errors = tx.clone();
+        tokio::task::spawn_blocking(move || {
+            let write = || -> anyhow::Result<()> {
+                let schema = schema(&columns);
+                let props = WriterProperties::builder()
+                    .set_compression(Compression::SNAPPY)
+                    .build();
+                let sink = ChannelWriter {
+                    buf: Vec::new(),
+                    tx,
+                };
+                let mut writer = ArrowWriter::try_new(sink, schema.clone(), Some(props))?;
+                while let Some(records) = rows_rx.blocking_recv() {
+                    writer.write(&record_batch(&schema, &records, &columns)?)?;
+                    writer.flush()?;
+                }
+                writer.into_inner()?.flush()?;
+                Ok(())
+            };
+            if let Err(e) = write() {
+                warn!("[export] Parquet export stopped: {e:#}");
+                let _ = errors.blocking_send(Err(io::Error::other(e.to_string())));
+            }
+        });
+
+        Body::from_stream(ReceiverStream::new(rx))
+    }
+}
+
+// GET /state/export - Versioned state bundle for `[state] import_path`
+pub(super) async fn state(
+    State(app): State<Arc<AppState>>,
+) -> Result<Response, (StatusCode, String)> {
+    let sources = StateSources {
+        incidents: app.incident_store.as_deref(),
+        maintenance: app.maintenance.as_deref(),
+        health: app.health.as_deref(),
+    };
+    let bundle = state_bundle::collect(sources)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}")))?;
+    let body = serde_json::to_vec(&bundle)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"linnix-state-v{}.json\"",
+                    bundle.version
+                ),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn csv_selects_columns_and_escapes_values() {
+        let columns = Dataset::Actions
+            .select(Some("id, action.type,action.pid,reason,outcome.improved"))
+            .unwrap();
+        let records = vec![
+            json!({
+                "id": "a1",
+                "action": {"type": "kill_process", "pid": 42, "signal": 9},
+                "reason": "cpu \"spin\", pid 42",
+                "outcome": {"executed_at": 10, "improved": true},
+            }),
+            json!({
+                "id": "a2",
+                "action": {"type": "throttle_cgroup", "cgroup_path": "/x"},
+                "reason": "line\nbreak",
+            }),
+        ];
+
+        assert_eq!(
+            csv_header(&columns),
+            "id,action.type,action.pid,reason,outcome.improved\r\n"
+        );
+        assert_eq!(
+            csv_rows(&records, &columns),
+            "a1,kill_process,42,\"cpu \"\"spin\"\", pid 42\",true\r\n\
+             a2,throttle_cgroup,,\"line\nbreak\",\r\n"
+        );
+
+        // Nested values without their own column are written as JSON
+        let columns = Dataset::Actions.select(Some("outcome")).unwrap();
+        assert_eq!(
+            csv_rows(&records[..1], &columns),
+            "\"{\"\"executed_at\"\":10,\"\"improved\"\":true}\"\r\n"
+        );
+
+        let err = Dataset::Alerts.select(Some("rule,nope")).unwrap_err();
+        assert!(err.contains("unknown alerts column 'nope'"), "{err}");
+        assert_eq!(Dataset::Incidents.select(None).unwrap().len(), 17);
+    }
+}
@@ -0,0 +1,135 @@
+//! Operator-only process forensics (`/processes/{pid}/maps`)
+//!
+//! Reading another process's memory map, and hashing its W+X regions, is
+//! more than the read-only API token should grant. The endpoint is enabled
+//! by `[forensics] operator_token`, which the API accepts as an operator
+//! token, and is routed behind `auth::require_operator`. Each client gets its
+//! own token bucket, and with `[scope] cgroups` set only processes inside the
+//! scope can be inspected.
+
+use axum::{
+    Json,
+    extract::{ConnectInfo, Path, State},
+    http::{HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use log::info;
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use super::AppState;
+use super::access::RateLimiter;
+use cognitod::config::{ApiRateLimitConfig, ForensicsConfig};
+use cognitod::memory_maps;
+
+pub struct Forensics {
+    limiter: RateLimiter,
+    max_hash_bytes: u64,
+}
+
+impl Forensics {
+    /// `None` while no operator token is configured.
+    pub fn from_config(config: &ForensicsConfig) -> anyhow::Result<Option<Self>> {
+        if config.operator_token.as_deref().is_none_or(str::is_empty) {
+            return Ok(None);
+        }
+        let limiter = RateLimiter::new(&ApiRateLimitConfig {
+            requests_per_sec: f64::from(config.requests_per_min) / 60.0,
+            burst: config.burst,
+        })
+        .map_err(|_| anyhow::anyhow!("forensics.requests_per_min must be positive"))?;
+        Ok(Some(Self {
+            limiter,
+            max_hash_bytes: config.max_hash_bytes,
+        }))
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+/// Shared library inventory and W+X regions of a running process.
+pub async fn get_process_maps(
+    State(app): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Path(pid): Path<u32>,
+) -> Response {
+    let Some(forensics) = app.forensics.clone() else {
+        return error(
+            StatusCode::NOT_FOUND,
+            "process forensics is disabled; set [forensics] operator_token",
+        );
+    };
+    if let Err(retry_after) = forensics.limiter.check(peer.ip(), Instant::now()) {
+        let secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+        let mut response = error(StatusCode::TOO_MANY_REQUESTS, "Too Many Requests");
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        return response;
+    }
+    if let Some(scope) = &app.scope
+        && !scope.contains_pid(pid)
+    {
+        return error(
+            StatusCode::FORBIDDEN,
+            "process is outside the monitored scope",
+        );
+    }
+
+    info!(
+        "[forensics] memory map of pid {pid} requested by {}",
+        peer.ip()
+    );
+    let max_hash_bytes = forensics.max_hash_bytes;
+    match tokio::task::spawn_blocking(move || memory_maps::read_inventory(pid, max_hash_bytes))
+        .await
+    {
+        Ok(Ok(inventory)) => Json(inventory).into_response(),
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            error(StatusCode::NOT_FOUND, "no such process")
+        }
+        Ok(Err(e)) => error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enabled_by_the_operator_token_and_throttled() {
+        assert!(
+            Forensics::from_config(&ForensicsConfig::default())
+                .unwrap()
+                .is_none()
+        );
+
+        let forensics = Forensics::from_config(&ForensicsConfig {
+            operator_token: Some("s3cret".into()),
+            burst: 2,
+            ..ForensicsConfig::default()
+        })
+        .unwrap()
+        .unwrap();
+
+        let client = "10.0.0.7".parse().unwrap();
+        let now = Instant::now();
+        assert!(forensics.limiter.check(client, now).is_ok());
+        assert!(forensics.limiter.check(client, now).is_ok());
+        // 6 per minute: the next inspection is 10 seconds away
+        let retry_after = forensics.limiter.check(client, now).unwrap_err();
+        assert_eq!(retry_after.as_secs(), 10);
+
+        let zero = ForensicsConfig {
+            operator_token: Some("s3cret".into()),
+            requests_per_min: 0,
+            ..ForensicsConfig::default()
+        };
+        assert!(Forensics::from_config(&zero).is_err());
+    }
+}
@@ -0,0 +1,688 @@
+//! System insights as jobs (`/insights`, `/insights/jobs/{id}`)
+//!
+//! Against models on CPU an analysis can take minutes, so it runs as a job.
+//! `POST /insights` starts one and returns its status. While a job is
+//! running, or for `[reasoner] insights_cache_secs` after it succeeded, the
+//! same job is returned instead of asking the model again. `GET
+//! /insights/jobs/{id}` reports its stage and the tokens generated so far,
+//! then its result. `/insights/jobs/{id}/stream` follows it as server-sent
+//! events, token by token when the LLM endpoint streams its answer. `GET
+//! /insights` starts or joins a job and waits for its result, as before.
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use futures_util::stream::{self, Stream};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
+
+use super::{AppState, generate_alerts};
+use crate::config::Egress;
+use cognitod::retry::{Failure, check_response, url_destination};
+use cognitod::units::Percent;
+use cognitod::utils::now_secs;
+
+/// `max_tokens` asked of the model, the denominator of `progress`.
+const MAX_TOKENS: u32 = 200;
+/// Tokens a slow stream subscriber may fall behind by.
+const TOKEN_BUFFER: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStage {
+    /// Reading system state for the prompt
+    Collecting,
+    /// Request sent, no token yet
+    Waiting,
+    /// Tokens arriving
+    Generating,
+    Finished,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    pub id: String,
+    pub state: JobState,
+    pub stage: JobStage,
+    /// Tokens streamed by the model so far; stays 0 for endpoints that
+    /// answer in one piece
+    pub tokens: u32,
+    /// `tokens` over `max_tokens`, 1 once finished
+    pub progress: f32,
+    pub created_at: u64,
+    pub finished_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub struct Job {
+    status: watch::Sender<JobStatus>,
+    tokens: broadcast::Sender<String>,
+}
+
+impl Job {
+    fn new(id: String) -> Self {
+        let status = JobStatus {
+            id,
+            state: JobState::Running,
+            stage: JobStage::Collecting,
+            tokens: 0,
+            progress: 0.0,
+            created_at: now_secs(),
+            finished_at: None,
+            result: None,
+            error: None,
+        };
+        Self {
+            status: watch::channel(status).0,
+            tokens: broadcast::channel(TOKEN_BUFFER).0,
+        }
+    }
+
+    pub fn status(&self) -> JobStatus {
+        self.status.borrow().clone()
+    }
+
+    fn stage(&self, stage: JobStage) {
+        self.status.send_if_modified(|s| {
+            let changed = s.stage != stage;
+            s.stage = stage;
+            changed
+        });
+    }
+
+    fn token(&self, token: String) {
+        self.status.send_modify(|s| {
+            s.stage = JobStage::Generating;
+            s.tokens += 1;
+            s.progress = (s.tokens as f32 / MAX_TOKENS as f32).min(0.99);
+        });
+        // No subscriber is fine
+        let _ = self.tokens.send(token);
+    }
+
+    fn finish(&self, outcome: Result<Value, String>) {
+        self.status.send_modify(|s| {
+            s.stage = JobStage::Finished;
+            s.progress = 1.0;
+            s.finished_at = Some(now_secs());
+            match outcome {
+                Ok(result) => {
+                    s.state = JobState::Done;
+                    s.result = Some(result);
+                }
+                Err(error) => {
+                    s.state = JobState::Failed;
+                    s.error = Some(error);
+                }
+            }
+        });
+    }
+
+    /// Status once the job has finished.
+    async fn finished(&self) -> JobStatus {
+        let mut rx = self.status.subscribe();
+        let status = rx
+            .wait_for(|s| s.state != JobState::Running)
+            .await
+            .map(|s| s.clone());
+        // The sender lives as long as `self`
+        status.unwrap_or_else(|_| self.status())
+    }
+}
+
+/// Running jobs and finished ones still within the cache TTL.
+pub struct InsightJobs {
+    ttl_secs: u64,
+    next_id: AtomicU64,
+    jobs: Mutex<HashMap<String, Arc<Job>>>,
+}
+
+impl InsightJobs {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl_secs: ttl.as_secs(),
+            next_id: AtomicU64::new(1),
+            jobs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The running job, else one that succeeded within the TTL unless
+    /// `fresh`, else a new job; `true` when the job is new and needs running.
+    fn start(&self, fresh: bool) -> (Arc<Job>, bool) {
+        let now = now_secs();
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.retain(|_, job| {
+            job.status
+                .borrow()
+                .finished_at
+                .is_none_or(|at| now.saturating_sub(at) < self.ttl_secs)
+        });
+        let reusable = jobs
+            .values()
+            .filter(|job| {
+                let status = job.status.borrow();
+                status.state == JobState::Running || (!fresh && status.state == JobState::Done)
+            })
+            .max_by_key(|job| job.status.borrow().created_at);
+        if let Some(job) = reusable {
+            return (Arc::clone(job), false);
+        }
+        let id = format!(
+            "insight-job-{}",
+            self.next_id.fetch_add(1, Ordering::Relaxed)
+        );
+        let job = Arc::new(Job::new(id.clone()));
+        jobs.insert(id, Arc::clone(&job));
+        (job, true)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<Job>> {
+        self.jobs.lock().unwrap().get(id).cloned()
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub struct StartQuery {
+    /// Ignore a cached result
+    #[serde(default)]
+    fresh: bool,
+}
+
+/// Start (or join) a job, spawning it when new; `None` while LLM egress is
+/// blocked.
+fn start_job(app: &Arc<AppState>, fresh: bool) -> Option<Arc<Job>> {
+    if !app.offline.check_egress(Egress::Llm, "insights") {
+        return None;
+    }
+    let (job, new) = app.insight_jobs.start(fresh);
+    if new {
+        tokio::spawn(run(Arc::clone(app), Arc::clone(&job)));
+    }
+    Some(job)
+}
+
+pub async fn post_insights(
+    State(app): State<Arc<AppState>>,
+    Query(query): Query<StartQuery>,
+) -> Response {
+    let Some(job) = start_job(&app, query.fresh) else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "error": "llm egress is disabled by the offline policy" })),
+        )
+            .into_response();
+    };
+    let status = job.status();
+    let code = match status.state {
+        JobState::Running => StatusCode::ACCEPTED,
+        _ => StatusCode::OK,
+    };
+    (code, Json(status)).into_response()
+}
+
+/// Synchronous form: the result of a new or cached job.
+pub async fn get_insights(
+    State(app): State<Arc<AppState>>,
+    Query(query): Query<StartQuery>,
+) -> Result<Json<Value>, StatusCode> {
+    let job = start_job(&app, query.fresh).ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+    match job.finished().await {
+        JobStatus {
+            result: Some(result),
+            ..
+        } => Ok(Json(result)),
+        _ => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+pub async fn get_job(
+    State(app): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<JobStatus>, StatusCode> {
+    app.insight_jobs
+        .get(&id)
+        .map(|job| Json(job.status()))
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// `status` events as the stage changes, `token` events as text arrives,
+/// and a final `done` event with the full status.
+pub async fn stream_job(
+    State(app): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    let job = app.insight_jobs.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    let follow = Follow {
+        tokens: job.tokens.subscribe(),
+        status: job.status.subscribe(),
+        started: false,
+        finished: false,
+    };
+    let events = stream::unfold(follow, |mut follow| async move {
+        let event = follow.next().await?;
+        Some((Ok(event), follow))
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::new().interval(Duration::from_secs(10))))
+}
+
+struct Follow {
+    tokens: broadcast::Receiver<String>,
+    status: watch::Receiver<JobStatus>,
+    started: bool,
+    finished: bool,
+}
+
+impl Follow {
+    async fn next(&mut self) -> Option<Event> {
+        if self.finished {
+            return None;
+        }
+        if !self.started {
+            self.started = true;
+            let status = self.status.borrow_and_update().clone();
+            return Some(self.status_event(status));
+        }
+        loop {
+            tokio::select! {
+                // Tokens are sent before the job finishes, so drain them first
+                biased;
+                token = self.tokens.recv() => match token {
+                    Ok(token) => return Some(Event::default().event("token").data(token)),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => {
+                        let status = self.status.borrow().clone();
+                        self.finished = true;
+                        return Some(status_event("done", &status));
+                    }
+                },
+                changed = self.status.changed() => {
+                    let status = self.status.borrow_and_update().clone();
+                    if changed.is_err() {
+                        self.finished = true;
+                        return Some(status_event("done", &status));
+                    }
+                    // While generating, progress is carried by the tokens
+                    if status.stage != JobStage::Generating {
+                        return Some(self.status_event(status));
+                    }
+                }
+            }
+        }
+    }
+
+    fn status_event(&mut self, status: JobStatus) -> Event {
+        if status.state == JobState::Running {
+            status_event("status", &status)
+        } else {
+            self.finished = true;
+            status_event("done", &status)
+        }
+    }
+}
+
+fn status_event(name: &str, status: &JobStatus) -> Event {
+    Event::default()
+        .event(name)
+        .data(serde_json::to_string(status).unwrap_or_default())
+}
+
+async fn run(app: Arc<AppState>, job: Arc<Job>) {
+    let outcome = analyze(&app, &job).await;
+    if let Err(e) = &outcome {
+        log::error!("[insights] {e}");
+    }
+    job.finish(outcome);
+}
+
+async fn analyze(app: &AppState, job: &Job) -> Result<Value, String> {
+    let ctx = &app.context;
+
+    // Update system snapshot on-demand for insights (critical for LLM analysis)
+    ctx.update_system_snapshot();
+    ctx.update_process_stats();
+
+    // Fetch system state
+    let system = ctx.get_system_snapshot();
+    // Fetch alerts (limit to top 5 for prompt brevity)
+    let mut alerts = generate_alerts(ctx);
+    alerts.truncate(5); // Only include first 5 alerts to keep prompt short
+
+    // Get top processes by CPU and memory
+    let top_cpu = ctx.top_cpu_processes(5);
+    let top_rss = ctx.top_rss_processes(5);
+
+    // Create a concise summary instead of full JSON dump
+    let alert_summary = if alerts.is_empty() {
+        "No active alerts".to_string()
+    } else {
+        alerts
+            .iter()
+            .map(|a| format!("{}: {}", a.comm, a.reason))
+            .collect::<Vec<_>>()
+            .join("; ")
+    };
+
+    // Build top CPU summary
+    let top_cpu_summary = if top_cpu.is_empty() {
+        "No CPU data available".to_string()
+    } else {
+        top_cpu
+            .iter()
+            .map(|p| format!("{} ({:.1}%)", p.name(), p.cpu_percent))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    // Build top memory summary
+    let top_mem_summary = if top_rss.is_empty() {
+        "No memory data available".to_string()
+    } else {
+        top_rss
+            .iter()
+            .map(|p| format!("{} ({:.1}%)", p.name(), p.mem_percent))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let prompt = format!(
+        "System Health Analysis:\n\
+         CPU: {:.1}% | Memory: {:.1}% | Load Avg: [{:.2}, {:.2}, {:.2}]\n\
+         Top CPU Consumers: {}\n\
+         Top Memory Consumers: {}\n\
+         Alerts: {}\n\n\
+         Analyze the system state and provide: 1) Overall health assessment, 2) Key risks or anomalies, 3) Recommended actions.",
+        system.cpu_percent,
+        system.mem_percent,
+        system.load_avg[0],
+        system.load_avg[1],
+        system.load_avg[2],
+        top_cpu_summary,
+        top_mem_summary,
+        alert_summary
+    );
+
+    // Call LLM - supports both local models and OpenAI
+    // Default to local Linnix model if available
+    let model = std::env::var("LLM_MODEL").unwrap_or_else(|_| "linnix-3b-distilled".to_string());
+    let llm_endpoint = std::env::var("LLM_ENDPOINT")
+        .unwrap_or_else(|_| "http://localhost:8090/v1/chat/completions".to_string());
+
+    // API key is optional for local models
+    let api_key =
+        std::env::var("OPENAI_API_KEY").unwrap_or_else(|_| "not-needed-for-local".to_string());
+
+    log::info!(
+        "[insights] Using LLM endpoint: {} with model: {}",
+        llm_endpoint,
+        model
+    );
+    let req_body = json!({
+        "model": model,
+        "messages": [
+            {"role": "system", "content": "You are an infrastructure monitoring assistant. Summarize Linux system health and risks for operators in clear, concise language."},
+            {"role": "user", "content": prompt}
+        ],
+        "max_tokens": MAX_TOKENS, // Limit response for faster generation on CPU
+        // Endpoints that cannot stream ignore this and answer in one piece
+        "stream": true
+    });
+
+    job.stage(JobStage::Waiting);
+    let client = app.offline.client(Egress::Llm, Client::builder());
+    let destination = app
+        .offline
+        .retrier()
+        .destination(&url_destination("llm", &llm_endpoint));
+    let (client, endpoint, api_key, body) = (&client, &llm_endpoint, &api_key, &req_body);
+    // Connection failures, 429 and 5xx are retried; other errors are
+    // reported below
+    let mut res = destination
+        .run(|_| async move {
+            let res = client
+                .post(endpoint)
+                .bearer_auth(api_key)
+                .json(body)
+                .timeout(Duration::from_secs(120)) // 2 minutes for CPU inference
+                .send()
+                .await
+                .map_err(Failure::request)?;
+            let status = res.status();
+            if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                return check_response(Ok(res)).await;
+            }
+            Ok(res)
+        })
+        .await
+        .map_err(|e| format!("LLM request failed: {e:#}"))?;
+
+    // Check HTTP status code
+    let status = res.status();
+    if !status.is_success() {
+        let error_text = res
+            .text()
+            .await
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        log::error!(
+            "[insights] LLM returned error status {}: {}",
+            status,
+            error_text
+        );
+        return Ok(json!({
+            "summary": format!("LLM API error: HTTP {}", status),
+            "risks": []
+        }));
+    }
+
+    let streamed = res
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/event-stream"));
+    let summary = if streamed {
+        let mut lines = SseLines::default();
+        let mut summary = String::new();
+        'read: while let Some(chunk) = res
+            .chunk()
+            .await
+            .map_err(|e| format!("LLM stream failed: {e}"))?
+        {
+            for data in lines.push(&chunk) {
+                if data == "[DONE]" {
+                    break 'read;
+                }
+                if let Some(token) = delta(&data) {
+                    summary.push_str(&token);
+                    job.token(token);
+                }
+            }
+        }
+        summary
+    } else {
+        let resp_json: Value = res
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse LLM response as JSON: {e}"))?;
+
+        log::debug!("[insights] LLM response: {:?}", resp_json);
+
+        // Extract the summary from the response (supports both OpenAI and local formats)
+        resp_json["choices"][0]["message"]["content"]
+            .as_str()
+            .unwrap_or_else(|| {
+                log::warn!("[insights] Could not extract content from LLM response");
+                "LLM response format error"
+            })
+            .to_string()
+    };
+
+    // Build structured response with metrics and top processes
+    let top_cpu_data: Vec<Value> = top_cpu
+        .iter()
+        .map(|p| {
+            json!({
+                "pid": p.pid,
+                "comm": p.comm,
+                "cpu": Percent::from(p.cpu_percent),
+                // Deprecated: `cpu.value`
+                "cpu_percent": format!("{:.1}", p.cpu_percent)
+            })
+        })
+        .collect();
+
+    let top_rss_data: Vec<Value> = top_rss
+        .iter()
+        .map(|p| {
+            json!({
+                "pid": p.pid,
+                "comm": p.comm,
+                "mem": Percent::from(p.mem_percent),
+                // Deprecated: `mem.value`
+                "mem_percent": format!("{:.1}", p.mem_percent)
+            })
+        })
+        .collect();
+
+    let alerts_data: Vec<Value> = alerts
+        .iter()
+        .map(|a| {
+            json!({
+                "comm": a.comm,
+                "reason": a.reason,
+                "pid": a.pid
+            })
+        })
+        .collect();
+
+    Ok(json!({
+        "summary": summary,
+        "metrics": {
+            "cpu": Percent::from(system.cpu_percent),
+            "mem": Percent::from(system.mem_percent),
+            // Deprecated: `cpu.value` and `mem.value`
+            "cpu_percent": format!("{:.1}", system.cpu_percent),
+            "mem_percent": format!("{:.1}", system.mem_percent),
+            "load_avg": system.load_avg.map(|l| (f64::from(l) * 100.0).round() / 100.0)
+        },
+        "top_cpu": top_cpu_data,
+        "top_memory": top_rss_data,
+        "alerts": alerts_data,
+        "timestamp": now_secs()
+    }))
+}
+
+/// Splits a `text/event-stream` body into `data:` payloads as chunks arrive.
+#[derive(Default)]
+struct SseLines {
+    buf: Vec<u8>,
+}
+
+impl SseLines {
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buf.extend_from_slice(chunk);
+        let mut data = Vec::new();
+        while let Some(end) = self.buf.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buf.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            if let Some(payload) = line.trim_end().strip_prefix("data:") {
+                data.push(payload.trim_start().to_string());
+            }
+        }
+        data
+    }
+}
+
+/// Text of one streamed chat completion chunk.
+fn delta(data: &str) -> Option<String> {
+    let chunk: Value = serde_json::from_str(data).ok()?;
+    chunk["choices"][0]["delta"]["content"]
+        .as_str()
+        .filter(|text| !text.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_streamed_chat_completions() {
+        let mut lines = SseLines::default();
+        assert!(
+            lines
+                .push(b"data: {\"choices\":[{\"delta\":{\"content\":\"Hea")
+                .is_empty()
+        );
+        let data = lines.push(
+            b"lthy\"}}]}\n\n: keep-alive\ndata: {\"choices\":[{\"delta\":{}}]}\r\ndata: [DONE]\n",
+        );
+        assert_eq!(data.len(), 3);
+        assert_eq!(delta(&data[0]).as_deref(), Some("Healthy"));
+        assert_eq!(delta(&data[1]), None);
+        assert_eq!(data[2], "[DONE]");
+    }
+
+    #[tokio::test]
+    async fn jobs_are_shared_while_running_and_cached_after() {
+        let jobs = InsightJobs::new(Duration::from_secs(300));
+        let (first, new) = jobs.start(false);
+        assert!(new);
+        let (joined, new) = jobs.start(true);
+        assert!(!new);
+        assert!(Arc::ptr_eq(&first, &joined));
+
+        first.token("a".into());
+        assert_eq!(first.status().stage, JobStage::Generating);
+        assert_eq!(first.status().tokens, 1);
+        first.finish(Ok(json!({ "summary": "a" })));
+        let status = first.finished().await;
+        assert_eq!(status.state, JobState::Done);
+        assert_eq!(status.progress, 1.0);
+
+        let (cached, new) = jobs.start(false);
+        assert!(!new && Arc::ptr_eq(&first, &cached));
+        let (fresh, new) = jobs.start(true);
+        assert!(new && !Arc::ptr_eq(&first, &fresh));
+
+        // Failures are not cached; the older success still is
+        fresh.finish(Err("boom".into()));
+        let (again, new) = jobs.start(false);
+        assert!(!new && Arc::ptr_eq(&first, &again));
+        assert!(jobs.get(&fresh.status().id).is_some());
+        assert!(jobs.get("insight-job-99").is_none());
+    }
+
+    #[tokio::test]
+    async fn finished_jobs_expire_after_the_ttl() {
+        let jobs = InsightJobs::new(Duration::ZERO);
+        let (job, _) = jobs.start(false);
+        job.finish(Ok(json!({})));
+        let (next, new) = jobs.start(false);
+        assert!(new);
+        assert!(jobs.get(&job.status().id).is_none());
+        assert!(jobs.get(&next.status().id).is_some());
+    }
+}
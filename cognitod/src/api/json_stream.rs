@@ -0,0 +1,108 @@
+//! Chunked JSON arrays for large list responses (`/processes`, `/history`).
+//!
+//! Serializing a whole list before sending keeps the items and all of their
+//! JSON in memory together, and the client sees nothing until the last item
+//! is written. [`json_array`] encodes the array as the body is polled
+//! instead, sending a chunk whenever `CHUNK_BYTES` of JSON have accumulated.
+
+use axum::body::Body;
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use bytes::Bytes;
+use serde::Serialize;
+
+/// JSON gathered before a chunk is sent.
+const CHUNK_BYTES: usize = 64 * 1024;
+
+struct Chunks<I> {
+    items: I,
+    buf: Vec<u8>,
+    first: bool,
+    done: bool,
+}
+
+impl<I> Iterator for Chunks<I>
+where
+    I: Iterator,
+    I::Item: Serialize,
+{
+    type Item = std::io::Result<Bytes>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        while self.buf.len() < CHUNK_BYTES {
+            let Some(item) = self.items.next() else {
+                self.buf.push(b']');
+                self.done = true;
+                break;
+            };
+            if !self.first {
+                self.buf.push(b',');
+            }
+            self.first = false;
+            if let Err(e) = serde_json::to_writer(&mut self.buf, &item) {
+                // Cut the body short rather than send invalid JSON
+                self.done = true;
+                return Some(Err(std::io::Error::other(e)));
+            }
+        }
+        let chunk = std::mem::replace(&mut self.buf, Vec::with_capacity(CHUNK_BYTES));
+        Some(Ok(Bytes::from(chunk)))
+    }
+}
+
+/// A `200` response with `items` as a JSON array, encoded while it is sent.
+pub(super) fn json_array<I>(items: I) -> Response
+where
+    I: Iterator + Send + 'static,
+    I::Item: Serialize,
+{
+    let mut buf = Vec::with_capacity(CHUNK_BYTES);
+    buf.push(b'[');
+    let chunks = Chunks {
+        items,
+        buf,
+        first: true,
+        done: false,
+    };
+    (
+        [(header::CONTENT_TYPE, "application/json")],
+        Body::from_stream(futures_util::stream::iter(chunks)),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn body_of(response: Response) -> Vec<u8> {
+        axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap()
+            .to_vec()
+    }
+
+    #[tokio::test]
+    async fn matches_serializing_the_whole_list() {
+        let items: Vec<serde_json::Value> = (0..20_000)
+            .map(|i| serde_json::json!({"pid": i, "comm": format!("worker-{i}")}))
+            .collect();
+        let expected = serde_json::to_vec(&items).unwrap();
+        assert!(expected.len() > 2 * CHUNK_BYTES);
+
+        let chunks: Vec<_> = Chunks {
+            items: items.clone().into_iter(),
+            buf: b"[".to_vec(),
+            first: true,
+            done: false,
+        }
+        .collect();
+        assert!(chunks.len() > 2);
+
+        assert_eq!(body_of(json_array(items.into_iter())).await, expected);
+        assert_eq!(body_of(json_array(std::iter::empty::<u32>())).await, b"[]");
+    }
+}
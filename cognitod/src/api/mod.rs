@@ -1,10 +1,22 @@
+pub mod access;
 mod auth;
-
+mod export;
+pub mod forensics;
+pub mod insight_jobs;
+mod json_stream;
+mod offline;
+mod process_sync;
+
+use crate::runtime::BpfControl;
+use crate::runtime::bpf_control::{BpfControlState, parse_event_type};
+use crate::runtime::features::FeatureStatus;
+use crate::runtime::placement::PlacementStatus;
+use crate::runtime::probe_profiles::ProbeBinding;
 use crate::runtime::probes::ProbeState;
 use axum::{
     Router,
-    extract::{Form, Path, Query, State},
-    http::{StatusCode, header},
+    extract::{Form, Path, Query, RawQuery, State, ws::WebSocketUpgrade},
+    http::{HeaderMap, StatusCode, header},
     response::{
         IntoResponse, Json, Response,
         sse::{Event, Sse},
@@ -13,11 +25,10 @@ use axum::{
 };
 use futures_util::stream::{BoxStream, Stream, StreamExt};
 use once_cell::sync::Lazy;
-use reqwest::Client;
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json::{json, to_string};
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 use std::fmt::Write as FmtWrite;
 use std::fs;
 use std::sync::Arc;
@@ -25,19 +36,59 @@ use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 use tokio_stream::wrappers::{BroadcastStream, IntervalStream, errors::BroadcastStreamRecvError};
+use tower_http::compression::CompressionLayer;
 
 use crate::ProcessEvent;
 #[cfg(test)]
 use crate::ProcessEventWire;
-use crate::config::{OfflineGuard, ReasonerConfig};
+use crate::config::{ApiTokenConfig, OfflineGuard, ReasonerConfig, TokenScope, UiConfig};
 use crate::context::ContextStore;
-use cognitod::alerts::Alert;
+use cognitod::alerts::{Alert, AlertSender, Annotations};
+use cognitod::ancestry_profile::AncestryProfile;
+use cognitod::collectors::ephemeral_storage::{EphemeralStorageWatcher, PodEphemeralStorage};
+use cognitod::collectors::filesystems::{FilesystemStatus, FilesystemWatcher, MountChange};
+use cognitod::collectors::memory_events::{CgroupMemoryEvents, MemoryEventsWatcher};
+use cognitod::collectors::power::{PackageEnergy, PodEnergy, PowerMonitor};
+use cognitod::collectors::smart::{DeviceHealth, SmartMonitor};
+use cognitod::coordination::{CoordinationStatus, Coordinator};
+use cognitod::detectors::{DetectorList, DetectorStatus};
+use cognitod::enrichment::{CachedPeer, EnrichmentStats, PeerEnricher};
+use cognitod::evidence::Evidence;
+use cognitod::expr::{self, ComputedField, PROCESS_VARIABLES};
+use cognitod::fd_usage::FdUsage;
+use cognitod::filter::{EventFilter, FilterParams};
+use cognitod::handler::warmth::FleetHealth;
+use cognitod::health_score::{
+    AlertCounts, EnforcementCounts, HealthInputs, HealthScore, HealthScorer,
+};
+use cognitod::leaderboard::{Leaderboard, LeaderboardReport, Metric, Period, WorkloadKind};
+use cognitod::lineage::LineageEntry;
+use cognitod::maintenance::{
+    AdhocWindow, AuditEntry, CreateWindowRequest, MaintenanceManager, WindowStatus,
+};
+use cognitod::net_traffic::{self, NetRates};
+use cognitod::restart_loops::{RestartLoopDetector, RestartLoopState};
+use cognitod::scaling::{
+    Aggregate, ExternalMetricValueList, PodFilter, ScalingReport, ScalingSignals, Signal,
+};
+use cognitod::scope::CgroupScope;
+use cognitod::signals::SignalRecord;
+use cognitod::templates::{MessageKind, Templates};
+use cognitod::topology::{ServiceGraph, TopologyReport};
+use cognitod::units::{Bytes, Percent, Rate};
 // use crate::handler::local_ilm::schema::insight_json_schema; // Removed (YAGNI cleanup)
 use crate::insights::{InsightRecord, InsightStore as InsightsStore};
-use crate::metrics::Metrics;
+use crate::metrics::ingest::EventTypeIngest;
+use crate::metrics::latency::Stage;
+use crate::metrics::{
+    MapOccupancy, Metrics, NotificationQueueStats, SequencerReport, SilentEventType,
+    SuppressedFaults,
+};
 use crate::types::ProcessAlert;
 use crate::types::SystemSnapshot;
-use cognitod::{Incident, IncidentStats, IncidentStore};
+use cognitod::event_schema::{EventRecord, EventSchema, event_schema};
+use cognitod::incidents::{AnalysisPool, AnalysisPriority, AnalysisQueueStats, AnalysisSource};
+use cognitod::{Incident, IncidentStats, IncidentStore, IncidentWrite};
 use linnix_ai_ebpf_common::EventType;
 use sysinfo::{Pid, System};
 use tokio::sync::broadcast;
@@ -53,6 +104,11 @@ enum EventKind {
     Syscall,
     BlockIo,
     PageFault,
+    Listen,
+    Connection,
+    FdPressure,
+    Signal,
+    OomKill,
     Unknown,
 }
 
@@ -67,6 +123,11 @@ impl From<u32> for EventKind {
             x if x == EventType::Syscall as u32 => EventKind::Syscall,
             x if x == EventType::BlockIo as u32 => EventKind::BlockIo,
             x if x == EventType::PageFault as u32 => EventKind::PageFault,
+            x if x == EventType::Listen as u32 => EventKind::Listen,
+            x if x == EventType::Connection as u32 => EventKind::Connection,
+            x if x == EventType::FdPressure as u32 => EventKind::FdPressure,
+            x if x == EventType::Signal as u32 => EventKind::Signal,
+            x if x == EventType::OomKill as u32 => EventKind::OomKill,
             _ => EventKind::Unknown,
         }
     }
@@ -78,7 +139,15 @@ struct ProcessInfo {
     ppid: u32,
     uid: u32,
     gid: u32,
+    /// cgroup v2 id the kernel read with the event; 0 when unknown.
+    cgroup_id: u64,
     comm: String,
+    /// Script run by an interpreter, resolved at exec.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    script_path: Option<String>,
+    /// Executable path at exec; `comm` is its first 15 bytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exe_path: Option<String>,
     event_type: EventKind,
     #[serde(skip_serializing_if = "Option::is_none")]
     cpu_pct: Option<f32>,
@@ -91,6 +160,31 @@ struct ProcessInfo {
     k8s: Option<cognitod::k8s::K8sMetadata>,
     #[serde(skip_serializing_if = "Option::is_none")]
     priority: Option<cognitod::k8s::Priority>,
+    /// Time from exec to the first listening socket.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exec_to_listen_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    listen_port: Option<u16>,
+    /// Bytes per second by socket family and direction, once the process
+    /// has sent or received anything.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    net: Option<NetRates>,
+    /// File descriptors against `RLIMIT_NOFILE`, once the process has come
+    /// near its limit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fds: Option<FdUsage>,
+    /// The signal, for `signal` events, whose pid is the sender's.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signal: Option<SignalRecord>,
+    /// Last fatal signal sent to the process.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    killed_by: Option<SignalRecord>,
+    /// Set while the process's workload is in a restart loop.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    restart_loop: Option<RestartLoopState>,
+    /// `[[computed_fields]]` with a known value.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    computed: BTreeMap<String, serde_json::Value>,
 }
 
 impl ProcessInfo {
@@ -98,15 +192,19 @@ impl ProcessInfo {
         let k8s = app_state
             .k8s
             .as_ref()
-            .and_then(|k| k.get_metadata_for_pid(e.pid));
+            .and_then(|k| k.get_metadata_for_event(e.pid, e.cgroup_id));
+        let startup = app_state.context.startup_latency(e.pid);
         Self {
             pid: e.pid,
             ppid: e.ppid,
             uid: e.uid,
             gid: e.gid,
+            cgroup_id: e.cgroup_id,
             comm: String::from_utf8_lossy(&e.comm)
                 .trim_end_matches('\0')
                 .to_string(),
+            script_path: e.script_path.clone(),
+            exe_path: e.exe_path.clone(),
             event_type: e.event_type.into(),
             cpu_pct: e.cpu_percent(),
             mem_pct: e.mem_percent(),
@@ -114,6 +212,26 @@ impl ProcessInfo {
             state: Some(process_state_str(e.event_type, e.exit_time_ns)),
             k8s: k8s.clone(),
             priority: k8s.map(|m| m.priority),
+            exec_to_listen_ms: startup.map(|s| s.as_millis_f64()),
+            listen_port: startup.map(|s| s.port),
+            net: app_state.context.net_rates(e.pid),
+            fds: app_state.context.fd_usage(e.pid),
+            signal: (e.event_type == EventType::Signal as u32).then(|| SignalRecord::from_event(e)),
+            killed_by: app_state.context.killed_by(e.pid),
+            restart_loop: app_state
+                .restart_loops
+                .as_ref()
+                .and_then(|detector| detector.for_pid(e.pid)),
+            computed: app_state
+                .computed_fields
+                .iter()
+                .filter_map(|field| {
+                    let value = field.expr.eval(&|name| {
+                        expr::process_variable(name, e, Some(app_state.context.as_ref()))
+                    })?;
+                    Some((field.name.clone(), value.to_json()))
+                })
+                .collect(),
         }
     }
 }
@@ -136,34 +254,14 @@ struct GraphResponse {
     nodes: Vec<GraphNode>,
 }
 
-#[derive(Serialize)]
-struct ProcessEventSse {
-    pid: u32,
-    ppid: u32,
-    uid: u32,
-    gid: u32,
-    comm: String,
-    event_type: u32,
-    event_type_name: String,
-    ts_ns: u64,
-    seq: u64,
-    exit_time_ns: u64,
-    cpu_pct_milli: u16,
-    mem_pct_milli: u16,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    cpu_percent: Option<f32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    mem_percent: Option<f32>,
-    data: u64,
-    data2: u64,
-    aux: u32,
-    aux2: u32,
-}
-
 #[derive(Serialize)]
 struct TopRssEntry {
     pid: u32,
     comm: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    script_path: Option<String>,
+    mem: Percent,
+    /// Deprecated: `mem.value`, kept for older clients.
     mem_percent: f32,
     k8s: Option<cognitod::k8s::K8sMetadata>,
 }
@@ -172,6 +270,10 @@ struct TopRssEntry {
 struct TopCpuEntry {
     pid: u32,
     comm: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    script_path: Option<String>,
+    cpu: Percent,
+    /// Deprecated: `cpu.value`, kept for older clients.
     cpu_percent: f32,
     k8s: Option<cognitod::k8s::K8sMetadata>,
 }
@@ -185,16 +287,36 @@ pub(crate) struct AlertRecord {
     rule: String,
     message: String,
     host: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    lineage: Vec<LineageEntry>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<LineageEntry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pod: Option<cognitod::k8s::K8sMetadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    annotations: Option<Box<Annotations>>,
+    /// Served separately by `/timeline/{id}/evidence`.
+    #[serde(skip)]
+    evidence: Option<Arc<Evidence>>,
 }
 
 // System metrics structure
 #[derive(Serialize)]
 struct SystemMetrics {
+    cpu_total: Percent,
+    memory_total: Bytes,
+    memory_used: Bytes,
+    /// Deprecated: `cpu_total.value`, kept for older clients.
     cpu_total_pct: f32,
+    /// Deprecated: `memory_total` in whole MiB, kept for older clients.
     memory_total_mb: u64,
+    /// Deprecated: `memory_used` in whole MiB, kept for older clients.
     memory_used_mb: u64,
     processes_total: usize,
     timestamp: u64,
+    /// Intervals in the last hour where events were lost, for annotating
+    /// the dashboard series.
+    lossy_intervals: Vec<cognitod::metrics::loss::LossInterval>,
 }
 
 // Alert history storage (ring buffer)
@@ -227,6 +349,11 @@ impl AlertHistory {
             rule: alert.rule,
             message: alert.message,
             host: alert.host,
+            lineage: alert.lineage,
+            children: alert.children,
+            pod: alert.pod,
+            annotations: alert.annotations,
+            evidence: alert.evidence.map(|evidence| Arc::new(*evidence)),
         };
 
         let mut records = self.records.write().await;
@@ -239,16 +366,50 @@ impl AlertHistory {
     pub async fn get_all(&self) -> Vec<AlertRecord> {
         self.records.read().await.iter().cloned().collect()
     }
+
+    /// Evidence captured with alert `id`, if it is still retained.
+    pub async fn evidence(&self, id: &str) -> Option<Evidence> {
+        self.records
+            .read()
+            .await
+            .iter()
+            .find(|record| record.id == id)
+            .and_then(|record| record.evidence.as_deref().cloned())
+    }
+
+    /// Alerts recorded at or after `since` (unix seconds), by severity.
+    pub async fn counts_since(&self, since: u64) -> AlertCounts {
+        let mut counts = AlertCounts::default();
+        for record in self.records.read().await.iter().rev() {
+            if record.timestamp < since {
+                break;
+            }
+            counts.add(&record.severity);
+        }
+        counts
+    }
 }
 
 #[derive(Serialize)]
 struct StatusResponse {
     version: &'static str,
+    /// `full`, `minimal` or `custom`; see `build_features`.
+    build_profile: &'static str,
+    build_features: Vec<&'static str>,
     uptime_s: u64,
     offline: bool,
+    /// CPU time of the daemon over its uptime.
+    cpu: Percent,
+    rss: Bytes,
+    event_rate: Rate,
+    /// Deprecated: `cpu.value`, kept for older `linnix-cli`.
     cpu_pct: f64,
+    /// Deprecated: `rss` in whole MiB, kept for older `linnix-cli`.
     rss_mb: u64,
+    /// Deprecated: `event_rate.value`, kept for older `linnix-cli`.
     events_per_sec: u64,
+    /// Ingested events per type, busiest first.
+    event_types: Vec<EventTypeIngest>,
     rb_overflows: u64,
     rate_limited: u64,
     kernel_version: String,
@@ -258,12 +419,43 @@ struct StatusResponse {
     top_rss: Vec<TopRssEntry>,
     top_cpu: Vec<TopCpuEntry>,
     probes: StatusProbeState,
+    /// CPU affinity and scheduling of the daemon's threads.
+    threads: PlacementStatus,
     reasoner: ReasonerStatus,
     incidents_last_1h: Option<usize>,
     feedback_entries: u64,
     slack_stats: SlackStats,
     perf_poll_errors: u64,
     dropped_events_total: u64,
+    alerts_suppressed: u64,
+    alerts_held_back: u64,
+    maintenance: Vec<WindowStatus>,
+    /// Which instance on this host executes enforcement actions.
+    coordination: CoordinationStatus,
+    /// Breaker state and retries per outbound destination.
+    outbound: Vec<cognitod::retry::DestinationStatus>,
+    /// Sequencer cursor restored at startup; absent while no consumer
+    /// persists one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sequencer_cursor: Option<cognitod::metrics::cursor::CursorStatus>,
+    /// Progress through the events left behind the restored cursor.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sequencer_backlog: Option<cognitod::metrics::backlog::BacklogStatus>,
+    /// Time spent per startup phase; absent until the API is listening.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    startup: Option<cognitod::metrics::startup::StartupReport>,
+    /// Node and pod the agent runs as; absent outside Kubernetes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kubernetes: Option<KubernetesStatus>,
+}
+
+#[derive(Serialize)]
+struct KubernetesStatus {
+    in_cluster: bool,
+    node: String,
+    node_labels: BTreeMap<String, String>,
+    namespace: Option<String>,
+    pod: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -278,6 +470,11 @@ struct SlackStats {
 struct StatusProbeState {
     rss_probe: String,
     btf: bool,
+    /// `None` when the main BPF object was not loaded.
+    features: Option<FeatureStatus>,
+    kprobes: Vec<ProbeBinding>,
+    /// Event types that stopped arriving while others flow (`[probe_silence]`).
+    silent_event_types: Vec<SilentEventType>,
 }
 
 #[derive(Serialize)]
@@ -291,6 +488,9 @@ struct ReasonerStatus {
     ilm_timeouts: u64,
     ilm_insights: u64,
     ilm_schema_errors: u64,
+    /// Incident analysis workers; absent when incident analysis is off.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    analysis_queue: Option<AnalysisQueueStats>,
 }
 
 async fn status_handler(State(app_state): State<Arc<AppState>>) -> Json<StatusResponse> {
@@ -300,7 +500,7 @@ async fn status_handler(State(app_state): State<Arc<AppState>>) -> Json<StatusRe
     let uptime = metrics.uptime_seconds();
 
     let mut cpu_pct = 0.0;
-    let mut rss_mb = 0u64;
+    let mut rss = Bytes(0);
 
     if let Ok(proc) = Process::myself()
         && let Ok(stat) = proc.stat()
@@ -310,8 +510,7 @@ async fn status_handler(State(app_state): State<Arc<AppState>>) -> Json<StatusRe
         if uptime > 0 {
             cpu_pct = (total_time as f64 / ticks) / uptime as f64 * 100.0;
         }
-        let page_kb = page_size() / 1024;
-        rss_mb = stat.rss * page_kb / 1024;
+        rss = Bytes(stat.rss * page_size());
     }
 
     let reasoner_cfg = &app_state.reasoner;
@@ -322,6 +521,8 @@ async fn status_handler(State(app_state): State<Arc<AppState>>) -> Json<StatusRe
         .map(|p| TopRssEntry {
             pid: p.pid,
             comm: p.comm,
+            script_path: p.script_path,
+            mem: p.mem_percent.into(),
             mem_percent: p.mem_percent,
             k8s: app_state
                 .k8s
@@ -336,7 +537,9 @@ async fn status_handler(State(app_state): State<Arc<AppState>>) -> Json<StatusRe
         .map(|p| TopCpuEntry {
             pid: p.pid,
             comm: p.comm,
-            cpu_percent: p.mem_percent, // Reusing mem_percent field for CPU in summary struct
+            script_path: p.script_path,
+            cpu: p.cpu_percent.into(),
+            cpu_percent: p.cpu_percent,
             k8s: app_state
                 .k8s
                 .as_ref()
@@ -357,6 +560,7 @@ async fn status_handler(State(app_state): State<Arc<AppState>>) -> Json<StatusRe
         ilm_timeouts: metrics.ilm_timeouts(),
         ilm_insights: metrics.ilm_insights(),
         ilm_schema_errors: metrics.ilm_schema_errors(),
+        analysis_queue: app_state.analysis.as_ref().map(|pool| pool.stats()),
     };
 
     let incidents_last_1h = if let Some(store) = &app_state.incident_store {
@@ -375,11 +579,21 @@ async fn status_handler(State(app_state): State<Arc<AppState>>) -> Json<StatusRe
 
     let resp = StatusResponse {
         version: env!("CARGO_PKG_VERSION"),
+        build_profile: cognitod::build_profile(),
+        build_features: cognitod::BUILD_FEATURES
+            .iter()
+            .filter(|(_, on)| *on)
+            .map(|(name, _)| *name)
+            .collect(),
         uptime_s: uptime,
         offline: app_state.offline.is_offline(),
+        cpu: Percent(cpu_pct),
+        rss,
+        event_rate: Rate::per_second(metrics.events_per_sec() as f64),
         cpu_pct,
-        rss_mb,
+        rss_mb: rss.0 / 1024 / 1024,
         events_per_sec: metrics.events_per_sec(),
+        event_types: metrics.ingest().breakdown(),
         rb_overflows: metrics.rb_overflows(),
         rate_limited: metrics.rate_limited_events(),
         kernel_version: kernel_version_string(),
@@ -391,7 +605,11 @@ async fn status_handler(State(app_state): State<Arc<AppState>>) -> Json<StatusRe
         probes: StatusProbeState {
             rss_probe: app_state.probe_state.rss_probe.as_str().to_string(),
             btf: app_state.probe_state.btf_available,
+            features: app_state.probe_state.features.map(|f| f.status()),
+            kprobes: app_state.probe_state.kprobes.clone(),
+            silent_event_types: metrics.silent_event_types(),
         },
+        threads: crate::runtime::placement::status(),
         reasoner,
         incidents_last_1h,
         feedback_entries: metrics.feedback_entries(),
@@ -400,6 +618,25 @@ async fn status_handler(State(app_state): State<Arc<AppState>>) -> Json<StatusRe
         dropped_events_total: metrics
             .dropped_events_total
             .load(std::sync::atomic::Ordering::Relaxed),
+        alerts_suppressed: metrics.alerts_suppressed(),
+        alerts_held_back: metrics.alerts_held_back(),
+        maintenance: app_state
+            .maintenance
+            .as_ref()
+            .map(|mgr| mgr.active_windows())
+            .unwrap_or_default(),
+        coordination: app_state.coordination.status(),
+        outbound: app_state.offline.retrier().status(),
+        sequencer_cursor: metrics.cursor().snapshot(),
+        sequencer_backlog: metrics.backlog().snapshot(),
+        startup: metrics.startup().snapshot(),
+        kubernetes: app_state.k8s.as_ref().map(|ctx| KubernetesStatus {
+            in_cluster: ctx.in_cluster,
+            node: ctx.node_name(),
+            node_labels: ctx.node_labels(),
+            namespace: ctx.namespace.clone(),
+            pod: ctx.pod_name.clone(),
+        }),
     };
     Json(resp)
 }
@@ -414,12 +651,114 @@ async fn get_context_route(State(app_state): State<Arc<AppState>>) -> Json<Vec<P
     Json(data)
 }
 
+/// Events returned by `/history` unless `limit` says otherwise.
+const HISTORY_DEFAULT_LIMIT: usize = 1000;
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    #[serde(default)]
+    pid: Option<u32>,
+    #[serde(default)]
+    comm: Option<String>,
+    /// `exec`, `fork`, `exit` and the other names `/bpf/sampling` takes.
+    #[serde(default)]
+    event_type: Option<String>,
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+// GET /history - Events in the history window by pid, comm or event type
+async fn get_history(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    let event_type = match &query.event_type {
+        Some(name) => Some(parse_event_type(name).ok_or((
+            StatusCode::BAD_REQUEST,
+            format!("unknown event type '{name}'"),
+        ))? as u32),
+        None => None,
+    };
+    let events = app_state.context.history(
+        query.pid,
+        query.comm.as_deref(),
+        event_type,
+        query.limit.unwrap_or(HISTORY_DEFAULT_LIMIT),
+    );
+    let state = Arc::clone(&app_state);
+    Ok(json_stream::json_array(
+        events
+            .into_iter()
+            .map(move |e| ProcessInfo::from_event(&e, &state)),
+    ))
+}
+
 #[derive(Deserialize)]
 struct ProcessesQuery {
     #[serde(default)]
     filter: Option<String>,
     #[serde(default)]
     sort: Option<String>,
+    #[serde(default)]
+    limit: Option<usize>,
+    #[serde(default)]
+    offset: Option<usize>,
+    /// Comma-separated subset of `PROCESS_FIELDS` to return.
+    #[serde(default)]
+    fields: Option<String>,
+}
+
+/// Field names accepted by `/processes?fields=`.
+const PROCESS_FIELDS: &[&str] = &[
+    "pid",
+    "ppid",
+    "uid",
+    "gid",
+    "cgroup_id",
+    "comm",
+    "script_path",
+    "exe_path",
+    "event_type",
+    "cpu_pct",
+    "mem_pct",
+    "age_sec",
+    "state",
+    "k8s",
+    "priority",
+    "exec_to_listen_ms",
+    "listen_port",
+    "net",
+    "fds",
+    "signal",
+    "killed_by",
+    "computed",
+];
+
+/// Compile a `/processes?filter=` expression over the process variables and
+/// the numeric computed fields.
+fn compile_process_filter(filter: &str, computed: &[ComputedField]) -> anyhow::Result<expr::Expr> {
+    let mut vars = PROCESS_VARIABLES.to_vec();
+    vars.extend(
+        computed
+            .iter()
+            .filter(|field| field.expr.ty() == expr::Type::Number)
+            .map(|field| field.name.as_str()),
+    );
+    expr::compile(filter, &vars, Some(expr::Type::Bool))
+}
+
+/// Process variables that change without an event reaching the context store.
+const UNTRACKED_VARIABLES: &[&str] = &["age_sec", "num_threads"];
+
+/// Weak validator for a `/processes` response: the generations the body is
+/// derived from plus a hash of the query, since filters and paging change the
+/// body. Weak because `age_sec` drifts without the generations moving.
+fn processes_etag(generations: &[u64], raw_query: Option<&str>) -> String {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    raw_query.unwrap_or_default().hash(&mut hasher);
+    let generations: Vec<String> = generations.iter().map(|g| format!("{g:x}")).collect();
+    format!("W/\"{}-{:x}\"", generations.join("."), hasher.finish())
 }
 
 fn calculate_age_sec(ts_ns: u64) -> Option<u64> {
@@ -449,27 +788,95 @@ fn process_state_str(event_type: u32, exit_time_ns: u64) -> String {
 async fn get_processes(
     State(app_state): State<Arc<AppState>>,
     Query(query): Query<ProcessesQuery>,
-) -> Json<Vec<ProcessInfo>> {
+    RawQuery(raw_query): RawQuery,
+    headers: HeaderMap,
+) -> Response {
     let ctx = &app_state.context;
+
+    let fields: Option<Vec<&str>> = query.fields.as_deref().map(|f| {
+        f.split(',')
+            .map(str::trim)
+            .filter(|f| !f.is_empty())
+            .collect()
+    });
+    if let Some(unknown) = fields
+        .iter()
+        .flatten()
+        .find(|f| !PROCESS_FIELDS.contains(*f))
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": format!("unknown field: {unknown}")})),
+        )
+            .into_response();
+    }
+    let filter = match query
+        .filter
+        .as_deref()
+        .map(|f| compile_process_filter(f, &app_state.computed_fields))
+        .transpose()
+    {
+        Ok(filter) => filter,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": format!("invalid filter: {e:#}")})),
+            )
+                .into_response();
+        }
+    };
+
+    // Read the generations before the snapshot so a concurrent update can only
+    // make the tag stale, never attach an old tag to new data. Computed fields
+    // and filters over untracked variables change by the second.
+    let mut generations = vec![
+        ctx.live_generation(),
+        app_state
+            .restart_loops
+            .as_ref()
+            .map_or(0, |detector| detector.generation()),
+    ];
+    let untracked = app_state
+        .computed_fields
+        .iter()
+        .map(|field| &field.expr)
+        .chain(filter.as_ref())
+        .any(|expr| UNTRACKED_VARIABLES.iter().any(|var| expr.uses(var)));
+    if untracked {
+        generations.push(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        );
+    }
+    let etag = processes_etag(&generations, raw_query.as_deref());
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|t| t.trim() == etag || t.trim() == "*"));
+    if not_modified {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+
     let snapshots = ctx.live_snapshot();
     let mut data: Vec<ProcessInfo> = snapshots
         .into_iter()
-        .map(|e| ProcessInfo::from_event(&e, &app_state))
+        .filter_map(|e| {
+            let info = ProcessInfo::from_event(&e, &app_state);
+            let keep = filter.as_ref().is_none_or(|filter| {
+                filter.matches(&|name| {
+                    info.computed
+                        .get(name)
+                        .and_then(serde_json::Value::as_f64)
+                        .or_else(|| expr::process_variable(name, &e, Some(ctx.as_ref())))
+                })
+            });
+            keep.then_some(info)
+        })
         .collect();
-
-    // Apply filtering if specified
-    if let Some(filter) = query.filter {
-        // Simple filter: cpu_pct>10 or mem_pct>50
-        if let Some(threshold_str) = filter.strip_prefix("cpu_pct>") {
-            if let Ok(threshold) = threshold_str.parse::<f32>() {
-                data.retain(|p| p.cpu_pct.unwrap_or(0.0) > threshold);
-            }
-        } else if let Some(threshold_str) = filter.strip_prefix("mem_pct>")
-            && let Ok(threshold) = threshold_str.parse::<f32>()
-        {
-            data.retain(|p| p.mem_pct.unwrap_or(0.0) > threshold);
-        }
-    }
+    // Stable base order so pages don't shuffle between requests
+    data.sort_by_key(|p| p.pid);
 
     // Apply sorting if specified
     if let Some(sort) = query.sort {
@@ -490,7 +897,37 @@ async fn get_processes(
         }
     }
 
-    Json(data)
+    // Pagination
+    let total = data.len();
+    let offset = query.offset.unwrap_or(0).min(total);
+    let limit = query.limit.unwrap_or(total);
+    let page = data.into_iter().skip(offset).take(limit);
+
+    let body = match fields {
+        Some(fields) => {
+            let fields: Vec<String> = fields.into_iter().map(str::to_string).collect();
+            json_stream::json_array(page.map(move |p| {
+                let mut value = serde_json::to_value(p).unwrap_or_default();
+                if let Some(obj) = value.as_object_mut() {
+                    obj.retain(|k, _| fields.contains(k));
+                }
+                value
+            }))
+        }
+        None => json_stream::json_array(page),
+    };
+
+    (
+        [
+            (header::ETAG, etag),
+            (
+                header::HeaderName::from_static("x-total-count"),
+                total.to_string(),
+            ),
+        ],
+        body,
+    )
+        .into_response()
 }
 
 async fn get_process_by_pid(
@@ -510,6 +947,51 @@ async fn get_process_by_pid(
     }
 }
 
+// GET /processes/{pid}/syscalls - Syscall histogram of a process
+async fn get_process_syscalls(
+    State(app_state): State<Arc<AppState>>,
+    Path(pid): Path<u32>,
+) -> impl IntoResponse {
+    match app_state.context.syscalls(pid) {
+        Some(summary) => (
+            axum::http::StatusCode::OK,
+            Json(serde_json::json!({
+                "pid": pid,
+                "arch": std::env::consts::ARCH,
+                "total": summary.total,
+                "first_seen_ns": summary.first_seen_ns,
+                "last_seen_ns": summary.last_seen_ns,
+                "syscalls": summary.syscalls,
+            })),
+        )
+            .into_response(),
+        None => (
+            axum::http::StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Not found"})),
+        )
+            .into_response(),
+    }
+}
+
+// GET /processes/{pid}/flows - Remote endpoints of a process's TCP and UDP traffic
+async fn get_process_flows(
+    State(app_state): State<Arc<AppState>>,
+    Path(pid): Path<u32>,
+) -> impl IntoResponse {
+    match app_state.context.flows(pid) {
+        Some(flows) => (
+            axum::http::StatusCode::OK,
+            Json(serde_json::json!({ "pid": pid, "flows": flows })),
+        )
+            .into_response(),
+        None => (
+            axum::http::StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Not found"})),
+        )
+            .into_response(),
+    }
+}
+
 async fn get_by_ppid(
     State(app_state): State<Arc<AppState>>,
     Path(ppid): Path<u32>,
@@ -697,55 +1179,36 @@ async fn get_graph(
     }
 }
 
+/// Field names, types, units and per-type payload meaning of `/events`.
+pub async fn get_event_schema() -> Json<EventSchema> {
+    Json(event_schema())
+}
+
+/// Live events, optionally narrowed by `FilterParams` (`?type=exec&comm=^nginx`).
 pub async fn stream_events(
     State(app_state): State<Arc<AppState>>,
-) -> Sse<impl Stream<Item = Result<Event, std::convert::Infallible>>> {
+    Query(params): Query<FilterParams>,
+) -> Result<Sse<impl Stream<Item = Result<Event, std::convert::Infallible>>>, (StatusCode, String)>
+{
+    let matcher = EventFilter::try_from(params)
+        .and_then(|filter| filter.compile(app_state.k8s.clone()))
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("{e:#}")))?;
     let ctx = &app_state.context;
     let rx = ctx.broadcaster().subscribe();
     let metrics = Arc::clone(&app_state.metrics);
     metrics.subscribers.fetch_add(1, Ordering::Relaxed);
     let metrics_clone = metrics.clone();
 
+    let matcher = Arc::new(matcher);
     let event_stream = BroadcastStream::new(rx).filter_map(move |msg| {
         let metrics = metrics_clone.clone();
+        let matcher = Arc::clone(&matcher);
         async move {
             match msg {
+                Ok(event) if !matcher.matches(&event) => None,
                 Ok(event) => {
-                    let event_type_name = match event.event_type {
-                        0 => "exec",
-                        1 => "fork",
-                        2 => "exit",
-                        3 => "net",
-                        4 => "fileio",
-                        5 => "syscall",
-                        6 => "blockio",
-                        7 => "pagefault",
-                        _ => "unknown",
-                    }
-                    .to_string();
-
-                    let sse_event = ProcessEventSse {
-                        pid: event.pid,
-                        ppid: event.ppid,
-                        uid: event.uid,
-                        gid: event.gid,
-                        comm: String::from_utf8_lossy(&event.comm)
-                            .trim_end_matches('\0')
-                            .to_string(),
-                        event_type: event.event_type,
-                        event_type_name,
-                        ts_ns: event.ts_ns,
-                        seq: event.seq,
-                        exit_time_ns: event.exit_time_ns,
-                        cpu_pct_milli: event.cpu_pct_milli,
-                        mem_pct_milli: event.mem_pct_milli,
-                        cpu_percent: event.cpu_percent(),
-                        mem_percent: event.mem_percent(),
-                        data: event.data,
-                        data2: event.data2,
-                        aux: event.aux,
-                        aux2: event.aux2,
-                    };
+                    metrics.latency().observe(Stage::Deliver, event.ts_ns);
+                    let sse_event = EventRecord::from(&event);
                     let json = to_string(&sse_event).unwrap();
                     Some(Ok(Event::default().data(json)))
                 }
@@ -779,11 +1242,11 @@ pub async fn stream_events(
         let _ = &guard;
     });
 
-    Sse::new(stream).keep_alive(
+    Ok(Sse::new(stream).keep_alive(
         axum::response::sse::KeepAlive::new()
             .interval(Duration::from_secs(10))
             .text("keep-alive"),
-    )
+    ))
 }
 
 pub async fn stream_alerts(
@@ -849,6 +1312,15 @@ pub async fn stream_processes_live(
     Sse::new(combined)
 }
 
+// GET /processes/sync - Process table as a snapshot followed by deltas
+async fn sync_processes(
+    ws: WebSocketUpgrade,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<process_sync::SyncQuery>,
+) -> Response {
+    ws.on_upgrade(move |socket| process_sync::serve(socket, app_state, query))
+}
+
 pub async fn system_snapshot(State(app_state): State<Arc<AppState>>) -> Json<SystemSnapshot> {
     let ctx = &app_state.context;
     let snapshot = ctx.get_system_snapshot();
@@ -896,6 +1368,19 @@ async fn get_timeline(
     Json(alerts)
 }
 
+// GET /timeline/{id}/evidence - Inputs the rule evaluated for an alert
+async fn get_alert_evidence(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<Evidence>, StatusCode> {
+    app_state
+        .alert_history
+        .evidence(&id)
+        .await
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
 // GET /api/metrics/system - Get current system metrics
 async fn get_system_metrics(State(app_state): State<Arc<AppState>>) -> Json<SystemMetrics> {
     let ctx = &app_state.context;
@@ -908,8 +1393,8 @@ async fn get_system_metrics(State(app_state): State<Arc<AppState>>) -> Json<Syst
     let mut sys = System::new_all();
     sys.refresh_all();
 
-    let memory_total_mb = sys.total_memory() / 1024 / 1024;
-    let memory_used_mb = sys.used_memory() / 1024 / 1024;
+    let memory_total = Bytes(sys.total_memory());
+    let memory_used = Bytes(sys.used_memory());
 
     // Get process count from context
     let processes_total = ctx.live_snapshot().len();
@@ -919,12 +1404,21 @@ async fn get_system_metrics(State(app_state): State<Arc<AppState>>) -> Json<Syst
         .unwrap_or_default()
         .as_secs();
 
+    let lossy_intervals = app_state
+        .metrics
+        .loss()
+        .lossy_intervals(timestamp.saturating_sub(3600), timestamp);
+
     Json(SystemMetrics {
+        cpu_total: cpu_total_pct.into(),
+        memory_total,
+        memory_used,
         cpu_total_pct,
-        memory_total_mb,
-        memory_used_mb,
+        memory_total_mb: memory_total.0 / 1024 / 1024,
+        memory_used_mb: memory_used.0 / 1024 / 1024,
         processes_total,
         timestamp,
+        lossy_intervals,
     })
 }
 
@@ -933,9 +1427,7 @@ fn generate_alerts(ctx: &ContextStore) -> Vec<ProcessAlert> {
     let mut alerts = Vec::new();
 
     for proc in processes {
-        let comm = String::from_utf8_lossy(&proc.comm)
-            .trim_end_matches('\0')
-            .to_string();
+        let comm = cognitod::script::display_name(&proc);
 
         // Alert rules based on CPU/memory thresholds only
         let mut reasons = Vec::new();
@@ -1000,211 +1492,254 @@ pub async fn get_recent_insights(
     Json(records)
 }
 
-pub async fn get_insights(
-    State(app_state): State<Arc<AppState>>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    if !app_state.offline.check("insights") {
-        return Err(StatusCode::SERVICE_UNAVAILABLE);
-    }
-    let ctx = &app_state.context;
-
-    // Update system snapshot on-demand for insights (critical for LLM analysis)
-    ctx.update_system_snapshot();
-    ctx.update_process_stats();
-
-    // Fetch system state
-    let system = ctx.get_system_snapshot();
-    // Fetch alerts (limit to top 5 for prompt brevity)
-    let mut alerts = generate_alerts(ctx);
-    alerts.truncate(5); // Only include first 5 alerts to keep prompt short
+#[derive(Deserialize)]
+struct CgroupsQuery {
+    #[serde(default)]
+    depth: Option<usize>,
+    #[serde(default)]
+    subtree: Option<String>,
+}
 
-    // Get top processes by CPU and memory
-    let top_cpu = ctx.top_cpu_processes(5);
-    let top_rss = ctx.top_rss_processes(5);
+async fn get_cgroups(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<CgroupsQuery>,
+) -> Result<Json<cognitod::collectors::cgroup_tree::CgroupNode>, (StatusCode, String)> {
+    use cognitod::collectors::cgroup_tree;
 
-    // Create a concise summary instead of full JSON dump
-    let alert_summary = if alerts.is_empty() {
-        "No active alerts".to_string()
-    } else {
-        alerts
-            .iter()
-            .map(|a| format!("{}: {}", a.comm, a.reason))
-            .collect::<Vec<_>>()
-            .join("; ")
+    let pids: Vec<u32> = app_state
+        .context
+        .live_snapshot()
+        .iter()
+        .map(|e| e.pid)
+        .collect();
+    let depth = query.depth.unwrap_or(3);
+    let subtree = match (&app_state.scope, query.subtree) {
+        (Some(scope), Some(subtree)) if !scope.contains_path(&subtree) => {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "cgroup subtree outside the monitored scope".to_string(),
+            ));
+        }
+        (Some(scope), None) => scope.subtrees()[0].clone(),
+        (_, subtree) => subtree.unwrap_or_else(|| "/".to_string()),
     };
 
-    // Build top CPU summary
-    let top_cpu_summary = if top_cpu.is_empty() {
-        "No CPU data available".to_string()
-    } else {
-        top_cpu
-            .iter()
-            .map(|p| format!("{} ({:.1}%)", p.comm, p.mem_percent)) // mem_percent holds CPU value
-            .collect::<Vec<_>>()
-            .join(", ")
-    };
+    let tree = tokio::task::spawn_blocking(move || {
+        let by_cgroup = cgroup_tree::map_pids(pids);
+        let root = cgroup_tree::cgroup_root();
+        cgroup_tree::build_tree(std::path::Path::new(&root), &subtree, depth, &by_cgroup)
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    // Build top memory summary
-    let top_mem_summary = if top_rss.is_empty() {
-        "No memory data available".to_string()
-    } else {
-        top_rss
-            .iter()
-            .map(|p| format!("{} ({:.1}%)", p.comm, p.mem_percent))
-            .collect::<Vec<_>>()
-            .join(", ")
-    };
+    tree.map(Json).ok_or((
+        StatusCode::NOT_FOUND,
+        "cgroup subtree not found".to_string(),
+    ))
+}
 
-    let prompt = format!(
-        "System Health Analysis:\n\
-         CPU: {:.1}% | Memory: {:.1}% | Load Avg: [{:.2}, {:.2}, {:.2}]\n\
-         Top CPU Consumers: {}\n\
-         Top Memory Consumers: {}\n\
-         Alerts: {}\n\n\
-         Analyze the system state and provide: 1) Overall health assessment, 2) Key risks or anomalies, 3) Recommended actions.",
-        system.cpu_percent,
-        system.mem_percent,
-        system.load_avg[0],
-        system.load_avg[1],
-        system.load_avg[2],
-        top_cpu_summary,
-        top_mem_summary,
-        alert_summary
-    );
+/// Caveat attached to every energy figure served by the API.
+const ENERGY_ESTIMATE_NOTE: &str = "estimated: RAPL package energy split by CPU share; \
+includes idle, uncore and memory controller power; GPU and DRAM-bound work is under-counted";
 
-    // Call LLM - supports both local models and OpenAI
-    // Default to local Linnix model if available
-    let model = std::env::var("LLM_MODEL").unwrap_or_else(|_| "linnix-3b-distilled".to_string());
-    let llm_endpoint = std::env::var("LLM_ENDPOINT")
-        .unwrap_or_else(|_| "http://localhost:8090/v1/chat/completions".to_string());
+#[derive(Serialize)]
+struct PodSummary {
+    namespace: String,
+    pod: String,
+    processes: usize,
+    cpu_pct: f32,
+    mem_pct: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    estimated_joules_total: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    estimated_watts: Option<f64>,
+    /// `memory.events` counters of the pod's container cgroups.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    memory_events: Vec<CgroupMemoryEvents>,
+    /// Containers of the pod in a restart loop.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    restart_loops: Vec<RestartLoopState>,
+    /// Last ephemeral storage sample with the recent heavy writers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ephemeral_storage: Option<PodEphemeralStorage>,
+}
 
-    // API key is optional for local models
-    let api_key =
-        std::env::var("OPENAI_API_KEY").unwrap_or_else(|_| "not-needed-for-local".to_string());
+#[derive(Serialize)]
+struct PowerSummary {
+    note: &'static str,
+    packages: Vec<PackageEnergy>,
+    /// Energy attributed to processes outside pods.
+    host: PodEnergy,
+}
 
-    log::info!(
-        "[insights] Using LLM endpoint: {} with model: {}",
-        llm_endpoint,
-        model
-    );
-    let req_body = serde_json::json!({
-        "model": model,
-        "messages": [
-            {"role": "system", "content": "You are an infrastructure monitoring assistant. Summarize Linux system health and risks for operators in clear, concise language."},
-            {"role": "user", "content": prompt}
-        ],
-        "max_tokens": 200  // Limit response for faster generation on CPU
-    });
+#[derive(Serialize)]
+struct PodsResponse {
+    pods: Vec<PodSummary>,
+    /// `null` when the node exposes no RAPL counters.
+    power: Option<PowerSummary>,
+}
 
-    let client = Client::new();
-    let res = client
-        .post(&llm_endpoint)
-        .bearer_auth(api_key)
-        .json(&req_body)
-        .timeout(std::time::Duration::from_secs(120)) // 2 minutes for CPU inference
-        .send()
-        .await
-        .map_err(|e| {
-            log::error!("[insights] LLM request failed: {}", e);
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-
-    // Check HTTP status code
-    let status = res.status();
-    if !status.is_success() {
-        let error_text = res
-            .text()
-            .await
-            .unwrap_or_else(|_| "Unknown error".to_string());
-        log::error!(
-            "[insights] LLM returned error status {}: {}",
-            status,
-            error_text
-        );
-        let output = serde_json::json!({
-            "summary": format!("LLM API error: HTTP {}", status),
-            "risks": []
-        });
-        return Ok(Json(output));
+async fn get_pods(State(app_state): State<Arc<AppState>>) -> Json<PodsResponse> {
+    let mut pods: BTreeMap<(String, String), PodSummary> = BTreeMap::new();
+    for (proc, meta) in app_state.context.get_live_map().values() {
+        let Some(meta) = meta else {
+            continue;
+        };
+        if proc.event_type == EventType::Exit as u32 {
+            continue;
+        }
+        let pod = pods
+            .entry((meta.namespace.clone(), meta.pod_name.clone()))
+            .or_insert_with(|| PodSummary {
+                namespace: meta.namespace.clone(),
+                pod: meta.pod_name.clone(),
+                processes: 0,
+                cpu_pct: 0.0,
+                mem_pct: 0.0,
+                estimated_joules_total: None,
+                estimated_watts: None,
+                memory_events: Vec::new(),
+                restart_loops: Vec::new(),
+                ephemeral_storage: None,
+            });
+        pod.processes += 1;
+        pod.cpu_pct += proc.cpu_percent().unwrap_or(0.0);
+        pod.mem_pct += proc.mem_percent().unwrap_or(0.0);
     }
 
-    let resp_json: serde_json::Value = res.json().await.map_err(|e| {
-        log::error!("[insights] Failed to parse LLM response as JSON: {}", e);
-        axum::http::StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-    log::debug!("[insights] LLM response: {:?}", resp_json);
+    let power = app_state.power.as_ref().map(|monitor| monitor.report());
+    if let Some(report) = &power {
+        for energy in &report.pods {
+            if let Some(pod) = pods.get_mut(&(energy.namespace.clone(), energy.pod.clone())) {
+                pod.estimated_joules_total = Some(energy.estimated_joules_total);
+                pod.estimated_watts = Some(energy.estimated_watts);
+            }
+        }
+    }
 
-    // Extract the summary from the response (supports both OpenAI and local formats)
-    let summary = resp_json["choices"][0]["message"]["content"]
-        .as_str()
-        .unwrap_or_else(|| {
-            log::warn!("[insights] Could not extract content from LLM response");
-            "LLM response format error"
-        })
-        .to_string();
+    if let Some(watcher) = &app_state.memory_events {
+        for cgroup in watcher.snapshot() {
+            let (Some(namespace), Some(pod)) = (&cgroup.namespace, &cgroup.pod) else {
+                continue;
+            };
+            if let Some(summary) = pods.get_mut(&(namespace.clone(), pod.clone())) {
+                summary.memory_events.push(cgroup);
+            }
+        }
+    }
 
-    // Build structured response with metrics and top processes
-    let top_cpu_data: Vec<serde_json::Value> = top_cpu
-        .iter()
-        .map(|p| {
-            serde_json::json!({
-                "pid": p.pid,
-                "comm": p.comm,
-                "cpu_percent": format!("{:.1}", p.mem_percent) // mem_percent field holds CPU value
-            })
-        })
-        .collect();
+    if let Some(watcher) = &app_state.ephemeral_storage {
+        for usage in watcher.snapshot() {
+            if let Some(summary) = pods.get_mut(&(usage.namespace.clone(), usage.pod.clone())) {
+                summary.ephemeral_storage = Some(usage);
+            }
+        }
+    }
 
-    let top_rss_data: Vec<serde_json::Value> = top_rss
-        .iter()
-        .map(|p| {
-            serde_json::json!({
-                "pid": p.pid,
-                "comm": p.comm,
-                "mem_percent": format!("{:.1}", p.mem_percent)
-            })
-        })
-        .collect();
+    // A crash-looping pod may have no live process between restarts
+    if let Some(detector) = &app_state.restart_loops {
+        for state in detector.snapshot() {
+            let (Some(namespace), Some(pod)) = (&state.workload.namespace, &state.workload.pod)
+            else {
+                continue;
+            };
+            pods.entry((namespace.clone(), pod.clone()))
+                .or_insert_with(|| PodSummary {
+                    namespace: namespace.clone(),
+                    pod: pod.clone(),
+                    processes: 0,
+                    cpu_pct: 0.0,
+                    mem_pct: 0.0,
+                    estimated_joules_total: None,
+                    estimated_watts: None,
+                    memory_events: Vec::new(),
+                    restart_loops: Vec::new(),
+                    ephemeral_storage: None,
+                })
+                .restart_loops
+                .push(state);
+        }
+    }
 
-    let alerts_data: Vec<serde_json::Value> = alerts
-        .iter()
-        .map(|a| {
-            serde_json::json!({
-                "comm": a.comm,
-                "reason": a.reason,
-                "pid": a.pid
-            })
-        })
-        .collect();
+    Json(PodsResponse {
+        pods: pods.into_values().collect(),
+        power: power.map(|report| PowerSummary {
+            note: ENERGY_ESTIMATE_NOTE,
+            packages: report.packages,
+            host: report.host,
+        }),
+    })
+}
 
-    let output = serde_json::json!({
-        "summary": summary,
-        "metrics": {
-            "cpu_percent": format!("{:.1}", system.cpu_percent),
-            "mem_percent": format!("{:.1}", system.mem_percent),
-            "load_avg": [
-                format!("{:.2}", system.load_avg[0]),
-                format!("{:.2}", system.load_avg[1]),
-                format!("{:.2}", system.load_avg[2])
-            ]
-        },
-        "top_cpu": top_cpu_data,
-        "top_memory": top_rss_data,
-        "alerts": alerts_data,
-        "timestamp": std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .map(|d| d.as_secs())
-            .unwrap_or(0)
-    });
+async fn get_loss_report(
+    State(app_state): State<Arc<AppState>>,
+) -> Json<cognitod::metrics::loss::LossReport> {
+    Json(app_state.metrics.loss().report())
+}
 
-    Ok(Json(output))
+/// The dashboard for the viewer's token scope. Without configured tokens
+/// the API is open, so every viewer is an operator.
+async fn dashboard(
+    State(app_state): State<Arc<AppState>>,
+    scope: Option<axum::Extension<TokenScope>>,
+) -> impl IntoResponse {
+    let scope = scope.map_or(TokenScope::Operator, |axum::Extension(scope)| scope);
+    crate::ui::dashboard(scope, &app_state.ui)
 }
 
 pub async fn healthz() -> axum::Json<serde_json::Value> {
     axum::Json(serde_json::json!({ "status": "ok" }))
 }
 
+/// Why the daemon is not ready to serve telemetry; empty when it is.
+fn readiness_failures(
+    transport: &str,
+    probe_state: &ProbeState,
+    silent: &[SilentEventType],
+) -> Vec<String> {
+    let mut reasons = Vec::new();
+    if transport == "userspace" {
+        reasons.push("no kernel event transport (userspace-only mode)".to_string());
+    }
+    match &probe_state.features {
+        None => reasons.push("main BPF object not loaded".to_string()),
+        Some(features) if !features.enabled(linnix_ai_ebpf_common::features::PROCESS) => {
+            reasons.push("process lifecycle probes not enabled".to_string())
+        }
+        Some(_) => {}
+    }
+    reasons.extend(silent.iter().map(|silent| {
+        format!(
+            "{} events silent for {}s",
+            silent.event_type, silent.silent_secs
+        )
+    }));
+    reasons
+}
+
+/// Readiness for orchestrators: 200 while kernel events flow, 503 with the
+/// reasons otherwise. Unauthenticated, like a kubelet probe.
+async fn readyz(State(app_state): State<Arc<AppState>>) -> impl IntoResponse {
+    let reasons = readiness_failures(
+        app_state.transport,
+        &app_state.probe_state,
+        &app_state.metrics.silent_event_types(),
+    );
+    let status = if reasons.is_empty() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(json!({
+            "ready": reasons.is_empty(),
+            "transport": app_state.transport,
+            "reasons": reasons,
+        })),
+    )
+}
+
 async fn get_actions(
     State(state): State<Arc<AppState>>,
 ) -> Json<Vec<crate::enforcement::EnforcementAction>> {
@@ -1216,19 +1751,254 @@ async fn get_actions(
     }
 }
 
-async fn get_action_by_id(
+/// Service dependency graph as nodes and client-to-server edges.
+async fn get_topology(
     State(state): State<Arc<AppState>>,
-    Path(id): Path<String>,
-) -> Result<Json<crate::enforcement::EnforcementAction>, StatusCode> {
-    if let Some(queue) = &state.enforcement {
-        queue
-            .get_by_id(&id)
-            .await
-            .map(Json)
-            .ok_or(StatusCode::NOT_FOUND)
-    } else {
-        Err(StatusCode::NOT_FOUND)
+) -> Result<Json<TopologyReport>, StatusCode> {
+    let graph = state.topology.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    let mut report = graph.report();
+    if let Some(enricher) = &state.enrichment {
+        enricher.annotate(&mut report);
     }
+    Ok(Json(report))
+}
+
+#[derive(Serialize)]
+struct PeersResponse {
+    stats: EnrichmentStats,
+    peers: Vec<CachedPeer>,
+}
+
+/// Cached reverse DNS and GeoIP names of remote peers.
+async fn get_peers(State(state): State<Arc<AppState>>) -> Result<Json<PeersResponse>, StatusCode> {
+    let enricher = state.enrichment.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(PeersResponse {
+        stats: enricher.stats(),
+        peers: enricher.cached(),
+    }))
+}
+
+#[derive(Serialize)]
+struct FilesystemsResponse {
+    filesystems: Vec<FilesystemStatus>,
+    /// Recent mounts and unmounts, newest first
+    changes: Vec<MountChange>,
+}
+
+/// Usage, growth and time-to-full of every watched mount.
+async fn get_filesystems(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<FilesystemsResponse>, StatusCode> {
+    let watcher = state.filesystems.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(FilesystemsResponse {
+        filesystems: watcher.filesystems(),
+        changes: watcher.changes(),
+    }))
+}
+
+/// SMART health and wear of each polled disk.
+async fn get_devices(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<DeviceHealth>>, StatusCode> {
+    let monitor = state.smart.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(monitor.devices()))
+}
+
+#[derive(Debug, Deserialize)]
+struct LeaderboardQuery {
+    period: Option<Period>,
+    /// Periods back from the current one; 1 with `period=day` is yesterday
+    #[serde(default)]
+    offset: u32,
+    #[serde(default)]
+    metric: Metric,
+    kind: Option<WorkloadKind>,
+    limit: Option<usize>,
+}
+
+/// Workloads ranked by CPU, peak RSS, IO, network use or crashes over an
+/// hour or day.
+async fn get_leaderboard(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LeaderboardQuery>,
+) -> Result<Json<LeaderboardReport>, StatusCode> {
+    let board = state.leaderboard.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(board.top(
+        query.period.unwrap_or(Period::Day),
+        query.offset,
+        query.metric,
+        query.kind,
+        query.limit.unwrap_or(10),
+    )))
+}
+
+#[derive(Deserialize)]
+struct AncestryProfileQuery {
+    /// Unix seconds; defaults to 15 minutes before `end`
+    start: Option<i64>,
+    /// Unix seconds; defaults to now
+    end: Option<i64>,
+}
+
+/// CPU milliseconds per process ancestry in folded-stack format, for
+/// `flamegraph.pl` and compatible tools.
+async fn get_ancestry_profile(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AncestryProfileQuery>,
+) -> Result<Response, StatusCode> {
+    let profile = state
+        .ancestry_profile
+        .as_ref()
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let end = query.end.unwrap_or_else(|| chrono::Utc::now().timestamp());
+    let start = query.start.unwrap_or(end - 15 * 60);
+    if start >= end {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        profile.folded(start, end),
+    )
+        .into_response())
+}
+
+/// Signals of every tracked pod at the last pass; 503 before the first.
+async fn get_scaling(
+    State(state): State<Arc<AppState>>,
+    Query(filter): Query<PodFilter>,
+) -> Result<Json<ScalingReport>, StatusCode> {
+    let scaling = state.scaling.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    scaling
+        .report(&filter)
+        .map(Json)
+        .ok_or(StatusCode::SERVICE_UNAVAILABLE)
+}
+
+#[derive(Deserialize)]
+struct ScalingQuery {
+    #[serde(flatten)]
+    filter: PodFilter,
+    aggregate: Option<Aggregate>,
+    /// `external` for an `ExternalMetricValueList`
+    format: Option<String>,
+}
+
+/// One signal over the selected pods, shaped for KEDA's `metrics-api` scaler
+/// (`valueLocation: value`) or an external metrics adapter.
+async fn get_scaling_signal(
+    State(state): State<Arc<AppState>>,
+    Path(signal): Path<Signal>,
+    Query(query): Query<ScalingQuery>,
+) -> Result<Response, StatusCode> {
+    let scaling = state.scaling.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    let value = scaling
+        .value(signal, &query.filter, query.aggregate)
+        .ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+    match query.format.as_deref() {
+        None | Some("keda") => Ok(Json(value).into_response()),
+        Some("external") => Ok(Json(ExternalMetricValueList::from(&value)).into_response()),
+        Some(_) => Err(StatusCode::BAD_REQUEST),
+    }
+}
+
+/// Ping-based health of the warm standby containers, worst first.
+async fn get_warmth_health() -> Result<Json<FleetHealth>, StatusCode> {
+    let keeper = crate::runtime::WARMTH_KEEPER
+        .get()
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(keeper.fleet_health()))
+}
+
+/// Actions waiting for approval, oldest first, with aging buckets.
+async fn get_pending_actions(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<crate::enforcement::PendingSummary>, StatusCode> {
+    let queue = state.enforcement.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    Ok(Json(queue.pending_summary(now).await))
+}
+
+async fn get_action_by_id(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<crate::enforcement::EnforcementAction>, StatusCode> {
+    if let Some(queue) = &state.enforcement {
+        queue
+            .get_by_id(&id)
+            .await
+            .map(Json)
+            .ok_or(StatusCode::NOT_FOUND)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+#[derive(Serialize)]
+struct RulesEffectiveness {
+    /// Seconds between executing an action and measuring its outcome.
+    outcome_delay_secs: u64,
+    rules: Vec<crate::enforcement::RuleEffectiveness>,
+}
+
+/// Score the node from current pressure and the alerts and enforcement
+/// actions of the configured window.
+async fn node_health_score(state: &AppState) -> Option<HealthScore> {
+    let scorer = state.health.as_ref()?;
+    let since = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        .saturating_sub(scorer.config().window_secs);
+    let mut enforcement = EnforcementCounts::default();
+    if let Some(queue) = &state.enforcement {
+        for action in queue.get_all().await {
+            if action.created_at < since {
+                continue;
+            }
+            match action.status {
+                crate::enforcement::ActionStatus::Executed => enforcement.executed += 1,
+                crate::enforcement::ActionStatus::Pending => enforcement.pending += 1,
+                _ => {}
+            }
+        }
+    }
+    let inputs = HealthInputs {
+        system: state.context.get_system_snapshot(),
+        event_rate_z: scorer.event_rate_z(state.metrics.events_per_sec() as f64),
+        alerts: state.alert_history.counts_since(since).await,
+        enforcement,
+    };
+    Some(scorer.score(&inputs))
+}
+
+async fn get_health_score(State(state): State<Arc<AppState>>) -> Response {
+    match node_health_score(&state).await {
+        Some(score) => Json(score).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Per-rule intervention effectiveness of executed enforcement actions.
+async fn get_rules_effectiveness(State(state): State<Arc<AppState>>) -> Json<RulesEffectiveness> {
+    Json(RulesEffectiveness {
+        outcome_delay_secs: crate::enforcement::OUTCOME_DELAY_SECS,
+        rules: state
+            .enforcement
+            .as_ref()
+            .map(|queue| queue.effectiveness())
+            .unwrap_or_default(),
+    })
+}
+
+/// Built-in detectors with their counters and last finding.
+async fn get_detectors(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<DetectorStatus>>, StatusCode> {
+    let detectors = state.detectors.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(detectors.snapshot()))
 }
 
 #[derive(Deserialize)]
@@ -1288,6 +2058,237 @@ async fn approve_action(
     }
 }
 
+fn bpf_control(state: &AppState) -> Result<&Arc<BpfControl>, (StatusCode, String)> {
+    state.bpf.as_ref().ok_or((
+        StatusCode::SERVICE_UNAVAILABLE,
+        "no BPF object loaded".to_string(),
+    ))
+}
+
+async fn get_bpf_control(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<BpfControlState>, (StatusCode, String)> {
+    Ok(Json(bpf_control(&state)?.state().await))
+}
+
+#[derive(Deserialize)]
+struct PageFaultQuery {
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct PageFaultReport {
+    /// Faults dropped since startup, including those of exited pids
+    suppressed_total: u64,
+    /// Live pids with dropped faults, highest count first
+    pids: Vec<SuppressedFaults>,
+}
+
+async fn get_bpf_page_faults(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<PageFaultQuery>,
+) -> Result<Json<PageFaultReport>, (StatusCode, String)> {
+    bpf_control(&state)?;
+    let mut pids = state.metrics.page_faults_suppressed();
+    pids.truncate(query.limit.unwrap_or(50));
+    Ok(Json(PageFaultReport {
+        suppressed_total: state.metrics.page_faults_suppressed_total(),
+        pids,
+    }))
+}
+
+/// Per-notifier delivery queues; empty when no notifier queues to disk.
+async fn get_notification_queues(
+    State(state): State<Arc<AppState>>,
+) -> Json<Vec<NotificationQueueStats>> {
+    Json(state.metrics.notification_queues())
+}
+
+#[derive(Deserialize)]
+struct TemplatePreviewQuery {
+    #[serde(default)]
+    kind: Option<String>,
+    #[serde(default)]
+    notifier: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TemplatePreview {
+    notifier: &'static str,
+    source: String,
+    rendered: String,
+}
+
+/// Render a sample message of `kind` (default `alert`) with the configured
+/// template of every notifier that sends it, or just `notifier`.
+async fn preview_templates(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TemplatePreviewQuery>,
+) -> impl IntoResponse {
+    let kind_name = query.kind.as_deref().unwrap_or("alert");
+    let Some(kind) = MessageKind::parse(kind_name) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": format!("unknown message kind '{kind_name}'") })),
+        )
+            .into_response();
+    };
+    let sample = cognitod::templates::sample(kind);
+    let previews: Vec<TemplatePreview> = Templates::notifiers()
+        .filter(|notifier| query.notifier.as_deref().is_none_or(|n| n == *notifier))
+        .filter_map(|notifier| {
+            let source = state.templates.source(notifier, kind)?.to_string();
+            let rendered = state.templates.render(notifier, kind, &sample);
+            Some(TemplatePreview {
+                notifier,
+                source,
+                rendered,
+            })
+        })
+        .collect();
+    if previews.is_empty()
+        && let Some(notifier) = &query.notifier
+    {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("{notifier} has no {kind_name} template") })),
+        )
+            .into_response();
+    }
+    Json(json!({ "kind": kind_name, "sample": sample, "templates": previews })).into_response()
+}
+
+/// Exactly what the next fleet telemetry report will send.
+#[cfg(feature = "fleet-telemetry")]
+async fn preview_telemetry(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match &state.fleet_telemetry {
+        Some(telemetry) => Json(telemetry.preview()).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "fleet telemetry unavailable" })),
+        )
+            .into_response(),
+    }
+}
+
+async fn get_debug_sequencer(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<SequencerReport>, (StatusCode, String)> {
+    bpf_control(&state)?;
+    Ok(Json(state.metrics.sequencer_report()))
+}
+
+#[derive(Deserialize)]
+struct SequencerToggle {
+    enabled: bool,
+}
+
+async fn set_bpf_sequencer(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<SequencerToggle>,
+) -> Result<Json<BpfControlState>, (StatusCode, String)> {
+    let control = bpf_control(&state)?;
+    if req.enabled {
+        // Events written to the ring are lost unless a sequencer consumer is
+        // running; it is started only with `[runtime] transport = "sequencer"`.
+        if state.transport != "sequencer" {
+            return Err((
+                StatusCode::CONFLICT,
+                format!("no sequencer consumer for transport '{}'", state.transport),
+            ));
+        }
+        control.enable_sequencer().await
+    } else {
+        control.disable_sequencer().await
+    }
+    .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    Ok(Json(control.state().await))
+}
+
+#[derive(Deserialize)]
+struct SamplingRequest {
+    event_type: String,
+    divisor: u32,
+}
+
+async fn set_bpf_sampling(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<SamplingRequest>,
+) -> Result<Json<BpfControlState>, (StatusCode, String)> {
+    let control = bpf_control(&state)?;
+    let event_type = parse_event_type(&req.event_type).ok_or((
+        StatusCode::BAD_REQUEST,
+        format!("unknown event type '{}'", req.event_type),
+    ))?;
+    control
+        .set_sampling(event_type, req.divisor)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    Ok(Json(control.state().await))
+}
+
+#[derive(Deserialize)]
+struct FilterUpdate {
+    #[serde(default)]
+    add: Vec<u32>,
+    #[serde(default)]
+    remove: Vec<u32>,
+}
+
+async fn update_bpf_filters(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<FilterUpdate>,
+) -> Result<Json<BpfControlState>, (StatusCode, String)> {
+    let control = bpf_control(&state)?;
+    control
+        .update_filters(&req.add, &req.remove)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    Ok(Json(control.state().await))
+}
+
+#[derive(Serialize)]
+struct MaintenanceResponse {
+    windows: Vec<WindowStatus>,
+    audit: Vec<AuditEntry>,
+}
+
+async fn get_maintenance(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<MaintenanceResponse>, StatusCode> {
+    let mgr = state.maintenance.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(MaintenanceResponse {
+        windows: mgr.windows(),
+        audit: mgr.audit(),
+    }))
+}
+
+async fn create_maintenance_window(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CreateWindowRequest>,
+) -> Result<(StatusCode, Json<AdhocWindow>), StatusCode> {
+    let mgr = state.maintenance.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    mgr.create(req)
+        .map(|window| (StatusCode::CREATED, Json(window)))
+        .map_err(|_| StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+struct CancelWindowQuery {
+    by: Option<String>,
+}
+
+async fn cancel_maintenance_window(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(q): Query<CancelWindowQuery>,
+) -> StatusCode {
+    match &state.maintenance {
+        Some(mgr) if mgr.cancel(&id, q.by.as_deref().unwrap_or("api")) => StatusCode::NO_CONTENT,
+        _ => StatusCode::NOT_FOUND,
+    }
+}
+
 async fn reject_action(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -1337,6 +2338,30 @@ pub struct MetricsResponse {
     pub slack_sent: u64,
     pub slack_failed: u64,
     pub alerts_generated: u64,
+    /// Per-process BPF map occupancy; empty until the first cleanup scan.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub bpf_maps: Vec<MapOccupancy>,
+    pub degradation: DegradationMetrics,
+    /// `[ordering]` reorder stage
+    pub reorder: cognitod::metrics::reorder::ReorderMetrics,
+    /// `[canary]` end-to-end check
+    pub canary: cognitod::metrics::canary::CanaryMetrics,
+    /// Approximate memory of the event history and live process map
+    pub context: crate::context::ContextMemory,
+}
+
+#[derive(Serialize)]
+pub struct DegradationMetrics {
+    /// Steps of the degradation ladder in effect (0-5)
+    level: u8,
+    /// Most recent step taken, `None` when not degraded
+    step: Option<&'static str>,
+    escalations: u64,
+    recoveries: u64,
+    ingest_paused: bool,
+    ingest_restarts: u64,
+    ingest_paused_drops: u64,
+    broadcast_shed: u64,
 }
 
 pub async fn prometheus_metrics(State(app_state): State<Arc<AppState>>) -> Response {
@@ -1403,6 +2428,28 @@ pub async fn prometheus_metrics(State(app_state): State<Arc<AppState>>) -> Respo
     let _ = writeln!(body, "# TYPE linnix_alerts_emitted_total counter");
     let _ = writeln!(body, "linnix_alerts_emitted_total {}", alerts_emitted);
 
+    let _ = writeln!(
+        body,
+        "# HELP linnix_alerts_suppressed_total Alerts suppressed by maintenance windows."
+    );
+    let _ = writeln!(body, "# TYPE linnix_alerts_suppressed_total counter");
+    let _ = writeln!(
+        body,
+        "linnix_alerts_suppressed_total {}",
+        metrics.alerts_suppressed()
+    );
+
+    let _ = writeln!(
+        body,
+        "# HELP linnix_alerts_held_back_total Alerts held back by the [alerts] cooldown."
+    );
+    let _ = writeln!(body, "# TYPE linnix_alerts_held_back_total counter");
+    let _ = writeln!(
+        body,
+        "linnix_alerts_held_back_total {}",
+        metrics.alerts_held_back()
+    );
+
     let _ = writeln!(
         body,
         "# HELP linnix_dropped_events_total Total events dropped (sampling/backpressure)."
@@ -1540,32 +2587,618 @@ pub async fn prometheus_metrics(State(app_state): State<Arc<AppState>>) -> Respo
         );
     }
 
-    Response::builder()
-        .status(StatusCode::OK)
-        .header(
-            header::CONTENT_TYPE,
-            "text/plain; version=0.0.4; charset=utf-8",
-        )
-        .body(body.into())
-        .unwrap()
-}
+    let ingest = metrics.ingest().breakdown();
+    let _ = writeln!(
+        body,
+        "# HELP linnix_events_ingested_total Events accepted for processing, by event type."
+    );
+    let _ = writeln!(body, "# TYPE linnix_events_ingested_total counter");
+    for row in &ingest {
+        let _ = writeln!(
+            body,
+            "linnix_events_ingested_total{{event_type=\"{}\"}} {}",
+            row.event_type, row.events
+        );
+    }
+    let _ = writeln!(
+        body,
+        "# HELP linnix_event_bytes_total Bytes read from the kernel for ingested events, by event type."
+    );
+    let _ = writeln!(body, "# TYPE linnix_event_bytes_total counter");
+    for row in &ingest {
+        let _ = writeln!(
+            body,
+            "linnix_event_bytes_total{{event_type=\"{}\"}} {}",
+            row.event_type, row.bytes
+        );
+    }
+    let _ = writeln!(
+        body,
+        "# HELP linnix_event_processing_seconds Time the handlers and context store spend on an event, by event type."
+    );
+    let _ = writeln!(body, "# TYPE linnix_event_processing_seconds histogram");
+    for row in &ingest {
+        let name = row.event_type;
+        let hist = metrics.ingest().processing(row.id);
+        for (le, count) in &hist.buckets {
+            let _ = writeln!(
+                body,
+                "linnix_event_processing_seconds_bucket{{event_type=\"{name}\",le=\"{le}\"}} {count}"
+            );
+        }
+        let _ = writeln!(
+            body,
+            "linnix_event_processing_seconds_bucket{{event_type=\"{name}\",le=\"+Inf\"}} {}",
+            hist.count
+        );
+        let _ = writeln!(
+            body,
+            "linnix_event_processing_seconds_sum{{event_type=\"{name}\"}} {}",
+            hist.sum_secs
+        );
+        let _ = writeln!(
+            body,
+            "linnix_event_processing_seconds_count{{event_type=\"{name}\"}} {}",
+            hist.count
+        );
+    }
 
-pub async fn metrics_handler(State(app_state): State<Arc<AppState>>) -> Json<MetricsResponse> {
-    let mut sys = System::new_all();
-    sys.refresh_all();
-    let pid = Pid::from_u32(std::process::id());
-    let (cpu_percent, rss) = if let Some(proc) = sys.process(pid) {
-        // proc.memory() returns bytes, convert to KB for the response
-        (proc.cpu_usage(), proc.memory() / 1024)
-    } else {
-        (0.0, 0)
-    };
+    let loss = metrics.loss().report();
+    let _ = writeln!(
+        body,
+        "# HELP linnix_events_lost_total Events lost between kernel and daemon, by detection source."
+    );
+    let _ = writeln!(body, "# TYPE linnix_events_lost_total counter");
+    let _ = writeln!(
+        body,
+        "linnix_events_lost_total{{transport=\"{}\",source=\"seq_gap\"}} {}",
+        loss.transport, loss.gap_lost_total
+    );
+    let _ = writeln!(
+        body,
+        "linnix_events_lost_total{{transport=\"{}\",source=\"kernel\"}} {}",
+        loss.transport, loss.kernel_lost_total
+    );
 
-    let metrics = &app_state.metrics;
-    let resp = MetricsResponse {
-        cpu_percent,
-        rss,
-        subscribers: metrics.subscribers.load(Ordering::Relaxed),
+    if let Some(store) = &app_state.incident_store {
+        let writes = store.write_stats();
+        let _ = writeln!(
+            body,
+            "# HELP linnix_incident_write_queue_depth Incident writes waiting to be committed."
+        );
+        let _ = writeln!(body, "# TYPE linnix_incident_write_queue_depth gauge");
+        let _ = writeln!(body, "linnix_incident_write_queue_depth {}", writes.queued);
+        let _ = writeln!(
+            body,
+            "# HELP linnix_incident_writes_total Incident writes by outcome (dropped = queue full)."
+        );
+        let _ = writeln!(body, "# TYPE linnix_incident_writes_total counter");
+        let _ = writeln!(
+            body,
+            "linnix_incident_writes_total{{outcome=\"written\"}} {}",
+            writes.written_total
+        );
+        let _ = writeln!(
+            body,
+            "linnix_incident_writes_total{{outcome=\"failed\"}} {}",
+            writes.failed_total
+        );
+        let _ = writeln!(
+            body,
+            "linnix_incident_writes_total{{outcome=\"dropped\"}} {}",
+            writes.dropped_total
+        );
+        let _ = writeln!(
+            body,
+            "# HELP linnix_incident_write_batches_total Incident write transactions committed."
+        );
+        let _ = writeln!(body, "# TYPE linnix_incident_write_batches_total counter");
+        let _ = writeln!(
+            body,
+            "linnix_incident_write_batches_total {}",
+            writes.batches_total
+        );
+    }
+
+    if let Some(power) = &app_state.power {
+        let report = power.report();
+        let _ = writeln!(
+            body,
+            "# HELP linnix_rapl_package_energy_joules_total Measured RAPL package energy."
+        );
+        let _ = writeln!(
+            body,
+            "# TYPE linnix_rapl_package_energy_joules_total counter"
+        );
+        for package in &report.packages {
+            let _ = writeln!(
+                body,
+                "linnix_rapl_package_energy_joules_total{{package=\"{}\"}} {}",
+                package.package, package.joules_total
+            );
+        }
+        let _ = writeln!(
+            body,
+            "# HELP linnix_pod_estimated_energy_joules_total Package energy attributed by CPU share (estimate)."
+        );
+        let _ = writeln!(
+            body,
+            "# TYPE linnix_pod_estimated_energy_joules_total counter"
+        );
+        for pod in report.pods.iter().chain(std::iter::once(&report.host)) {
+            let _ = writeln!(
+                body,
+                "linnix_pod_estimated_energy_joules_total{{namespace=\"{}\",pod=\"{}\"}} {}",
+                pod.namespace, pod.pod, pod.estimated_joules_total
+            );
+        }
+        let _ = writeln!(
+            body,
+            "# HELP linnix_pod_estimated_power_watts Package power attributed by CPU share over the last interval (estimate)."
+        );
+        let _ = writeln!(body, "# TYPE linnix_pod_estimated_power_watts gauge");
+        for pod in report.pods.iter().chain(std::iter::once(&report.host)) {
+            let _ = writeln!(
+                body,
+                "linnix_pod_estimated_power_watts{{namespace=\"{}\",pod=\"{}\"}} {}",
+                pod.namespace, pod.pod, pod.estimated_watts
+            );
+        }
+    }
+
+    if let Some(smart) = &app_state.smart {
+        type Gauge = (&'static str, &'static str, fn(&DeviceHealth) -> Option<f64>);
+        let devices = smart.devices();
+        let gauges: [Gauge; 6] = [
+            (
+                "linnix_disk_smart_passed",
+                "SMART overall-health self-assessment, 1 passed, 0 failed.",
+                |d| d.passed.map(|p| if p { 1.0 } else { 0.0 }),
+            ),
+            (
+                "linnix_disk_wear_used_percent",
+                "Rated endurance used, percent.",
+                |d| d.wear_used_pct,
+            ),
+            (
+                "linnix_disk_available_spare_percent",
+                "NVMe spare capacity left, percent.",
+                |d| d.available_spare_pct,
+            ),
+            (
+                "linnix_disk_temperature_celsius",
+                "Drive temperature.",
+                |d| d.temperature_c,
+            ),
+            (
+                "linnix_disk_media_errors",
+                "NVMe media and data integrity errors.",
+                |d| d.media_errors.map(|n| n as f64),
+            ),
+            (
+                "linnix_disk_reallocated_sectors",
+                "ATA reallocated sector count.",
+                |d| d.reallocated_sectors.map(|n| n as f64),
+            ),
+        ];
+        for (name, help, value) in gauges {
+            let _ = writeln!(body, "# HELP {name} {help}");
+            let _ = writeln!(body, "# TYPE {name} gauge");
+            for device in &devices {
+                if let Some(v) = value(device) {
+                    let _ = writeln!(body, "{name}{{device=\"{}\"}} {v}", device.device);
+                }
+            }
+        }
+    }
+
+    if let Some(health) = node_health_score(&app_state).await {
+        let _ = writeln!(
+            body,
+            "# HELP linnix_node_health_score Node health score, 0 (critical) to 100 (healthy)."
+        );
+        let _ = writeln!(body, "# TYPE linnix_node_health_score gauge");
+        let _ = writeln!(body, "linnix_node_health_score {}", health.score);
+        let _ = writeln!(
+            body,
+            "# HELP linnix_node_health_component_score Health score components, 0 to 100."
+        );
+        let _ = writeln!(body, "# TYPE linnix_node_health_component_score gauge");
+        for component in &health.components {
+            let _ = writeln!(
+                body,
+                "linnix_node_health_component_score{{component=\"{}\"}} {}",
+                component.name, component.score
+            );
+        }
+    }
+
+    let _ = writeln!(
+        body,
+        "# HELP linnix_degradation_level Steps of the degradation ladder in effect."
+    );
+    let _ = writeln!(body, "# TYPE linnix_degradation_level gauge");
+    let _ = writeln!(
+        body,
+        "linnix_degradation_level {}",
+        metrics.degradation_level()
+    );
+    let _ = writeln!(
+        body,
+        "# HELP linnix_degradation_transitions_total Moves along the degradation ladder."
+    );
+    let _ = writeln!(body, "# TYPE linnix_degradation_transitions_total counter");
+    let _ = writeln!(
+        body,
+        "linnix_degradation_transitions_total{{direction=\"escalate\"}} {}",
+        metrics.degradation_escalations()
+    );
+    let _ = writeln!(
+        body,
+        "linnix_degradation_transitions_total{{direction=\"recover\"}} {}",
+        metrics.degradation_recoveries()
+    );
+    let _ = writeln!(
+        body,
+        "# HELP linnix_ingest_restarts_total Ingestion restarts by the degradation ladder."
+    );
+    let _ = writeln!(body, "# TYPE linnix_ingest_restarts_total counter");
+    let _ = writeln!(
+        body,
+        "linnix_ingest_restarts_total {}",
+        metrics.ingest_restarts()
+    );
+    let _ = writeln!(
+        body,
+        "# HELP linnix_ingest_paused_dropped_total Events dropped while ingestion was paused."
+    );
+    let _ = writeln!(body, "# TYPE linnix_ingest_paused_dropped_total counter");
+    let _ = writeln!(
+        body,
+        "linnix_ingest_paused_dropped_total {}",
+        metrics.ingest_paused_drops()
+    );
+    let _ = writeln!(
+        body,
+        "# HELP linnix_broadcast_shed_total Events not sent to /events subscribers while the broadcast backlog was capped."
+    );
+    let _ = writeln!(body, "# TYPE linnix_broadcast_shed_total counter");
+    let _ = writeln!(
+        body,
+        "linnix_broadcast_shed_total {}",
+        app_state.context.broadcast_shed()
+    );
+
+    let reorder = metrics.reorder().snapshot();
+    if reorder.enabled {
+        let _ = writeln!(
+            body,
+            "# HELP linnix_reorder_buffered Events held by the reorder stage."
+        );
+        let _ = writeln!(body, "# TYPE linnix_reorder_buffered gauge");
+        let _ = writeln!(body, "linnix_reorder_buffered {}", reorder.stats.buffered);
+        let _ = writeln!(
+            body,
+            "# HELP linnix_reorder_out_of_window_total Events that arrived after a later one was delivered to ordered handlers."
+        );
+        let _ = writeln!(body, "# TYPE linnix_reorder_out_of_window_total counter");
+        let _ = writeln!(
+            body,
+            "linnix_reorder_out_of_window_total {}",
+            reorder.stats.out_of_window
+        );
+        let _ = writeln!(
+            body,
+            "# HELP linnix_reorder_forced_total Events released before their delay because the reorder buffer was full."
+        );
+        let _ = writeln!(body, "# TYPE linnix_reorder_forced_total counter");
+        let _ = writeln!(body, "linnix_reorder_forced_total {}", reorder.stats.forced);
+    }
+
+    let memory = app_state.context.memory_usage();
+    let _ = writeln!(
+        body,
+        "# HELP linnix_context_bytes Approximate memory held by the event history and the live process map."
+    );
+    let _ = writeln!(body, "# TYPE linnix_context_bytes gauge");
+    let _ = writeln!(
+        body,
+        "linnix_context_bytes{{map=\"history\"}} {}",
+        memory.history_bytes
+    );
+    let _ = writeln!(
+        body,
+        "linnix_context_bytes{{map=\"live\"}} {}",
+        memory.live_bytes
+    );
+    let _ = writeln!(
+        body,
+        "# HELP linnix_context_evicted_total Entries dropped to keep the history or the live process map within its memory budget."
+    );
+    let _ = writeln!(body, "# TYPE linnix_context_evicted_total counter");
+    let _ = writeln!(
+        body,
+        "linnix_context_evicted_total{{map=\"history\"}} {}",
+        memory.history_evicted
+    );
+    let _ = writeln!(
+        body,
+        "linnix_context_evicted_total{{map=\"live\"}} {}",
+        memory.live_evicted
+    );
+
+    let canary = metrics.canary().snapshot();
+    if canary.enabled {
+        let _ = writeln!(
+            body,
+            "# HELP linnix_canary_up Whether the last canary run's events reached the daemon within the SLA."
+        );
+        let _ = writeln!(body, "# TYPE linnix_canary_up gauge");
+        let _ = writeln!(body, "linnix_canary_up {}", u8::from(canary.up));
+        let _ = writeln!(body, "# HELP linnix_canary_runs_total Canary runs.");
+        let _ = writeln!(body, "# TYPE linnix_canary_runs_total counter");
+        let _ = writeln!(body, "linnix_canary_runs_total {}", canary.runs);
+        let _ = writeln!(
+            body,
+            "# HELP linnix_canary_failures_total Canary runs whose events were missing or late."
+        );
+        let _ = writeln!(body, "# TYPE linnix_canary_failures_total counter");
+        let _ = writeln!(body, "linnix_canary_failures_total {}", canary.failures);
+        if let Some(ms) = canary.last_latency_ms {
+            let _ = writeln!(
+                body,
+                "# HELP linnix_canary_latency_seconds Time from spawning the last passing canary to both of its events being stored."
+            );
+            let _ = writeln!(body, "# TYPE linnix_canary_latency_seconds gauge");
+            let _ = writeln!(body, "linnix_canary_latency_seconds {}", ms as f64 / 1000.0);
+        }
+    }
+
+    if let Some(access) = &app_state.access {
+        let _ = writeln!(
+            body,
+            "# HELP linnix_api_rejected_requests_total API requests refused by client IP, by reason (denied or rate_limited)."
+        );
+        let _ = writeln!(body, "# TYPE linnix_api_rejected_requests_total counter");
+        for (client, counts) in access.rejections() {
+            for (reason, count) in [
+                ("denied", counts.denied),
+                ("rate_limited", counts.rate_limited),
+            ] {
+                if count > 0 {
+                    let _ = writeln!(
+                        body,
+                        "linnix_api_rejected_requests_total{{client=\"{client}\",reason=\"{reason}\"}} {count}"
+                    );
+                }
+            }
+        }
+    }
+
+    let bpf_maps = metrics.bpf_maps();
+    if !bpf_maps.is_empty() {
+        let _ = writeln!(
+            body,
+            "# HELP linnix_bpf_map_entries Entries in per-process BPF maps after the last cleanup."
+        );
+        let _ = writeln!(body, "# TYPE linnix_bpf_map_entries gauge");
+        for map in &bpf_maps {
+            let _ = writeln!(
+                body,
+                "linnix_bpf_map_entries{{map=\"{}\"}} {}",
+                map.map, map.entries
+            );
+        }
+        let _ = writeln!(
+            body,
+            "# HELP linnix_bpf_map_max_entries Capacity of per-process BPF maps."
+        );
+        let _ = writeln!(body, "# TYPE linnix_bpf_map_max_entries gauge");
+        for map in &bpf_maps {
+            let _ = writeln!(
+                body,
+                "linnix_bpf_map_max_entries{{map=\"{}\"}} {}",
+                map.map, map.max_entries
+            );
+        }
+        let _ = writeln!(
+            body,
+            "# HELP linnix_bpf_map_stale_removed_total Entries of exited processes removed from per-process BPF maps."
+        );
+        let _ = writeln!(body, "# TYPE linnix_bpf_map_stale_removed_total counter");
+        for map in &bpf_maps {
+            let _ = writeln!(
+                body,
+                "linnix_bpf_map_stale_removed_total{{map=\"{}\"}} {}",
+                map.map, map.stale_removed_total
+            );
+        }
+    }
+
+    let _ = writeln!(
+        body,
+        "# HELP linnix_page_faults_suppressed_total Page faults dropped by the per-pid in-kernel throttle."
+    );
+    let _ = writeln!(body, "# TYPE linnix_page_faults_suppressed_total counter");
+    let _ = writeln!(
+        body,
+        "linnix_page_faults_suppressed_total {}",
+        metrics.page_faults_suppressed_total()
+    );
+
+    let queues = metrics.notification_queues();
+    if !queues.is_empty() {
+        let _ = writeln!(
+            body,
+            "# HELP linnix_notification_queue_depth Notifications awaiting delivery, per notifier."
+        );
+        let _ = writeln!(body, "# TYPE linnix_notification_queue_depth gauge");
+        for queue in &queues {
+            let _ = writeln!(
+                body,
+                "linnix_notification_queue_depth{{sink=\"{}\"}} {}",
+                queue.sink, queue.depth
+            );
+        }
+        let _ = writeln!(
+            body,
+            "# HELP linnix_notification_queue_oldest_age_seconds Age of the oldest undelivered notification, per notifier."
+        );
+        let _ = writeln!(
+            body,
+            "# TYPE linnix_notification_queue_oldest_age_seconds gauge"
+        );
+        for queue in &queues {
+            let _ = writeln!(
+                body,
+                "linnix_notification_queue_oldest_age_seconds{{sink=\"{}\"}} {}",
+                queue.sink, queue.oldest_age_secs
+            );
+        }
+        let _ = writeln!(
+            body,
+            "# HELP linnix_notification_dead_letters Notifications that exhausted their delivery attempts, per notifier."
+        );
+        let _ = writeln!(body, "# TYPE linnix_notification_dead_letters gauge");
+        for queue in &queues {
+            let _ = writeln!(
+                body,
+                "linnix_notification_dead_letters{{sink=\"{}\"}} {}",
+                queue.sink, queue.dead_letters
+            );
+        }
+    }
+
+    let _ = writeln!(
+        body,
+        "# HELP linnix_event_latency_seconds Age of events, from the kernel timestamp, when they reach each pipeline stage."
+    );
+    let _ = writeln!(body, "# TYPE linnix_event_latency_seconds histogram");
+    for stage in Stage::ALL {
+        let name = stage.name();
+        let hist = metrics.latency().snapshot(stage);
+        for (le, count) in &hist.buckets {
+            let _ = writeln!(
+                body,
+                "linnix_event_latency_seconds_bucket{{stage=\"{name}\",le=\"{le}\"}} {count}"
+            );
+        }
+        let _ = writeln!(
+            body,
+            "linnix_event_latency_seconds_bucket{{stage=\"{name}\",le=\"+Inf\"}} {}",
+            hist.count
+        );
+        let _ = writeln!(
+            body,
+            "linnix_event_latency_seconds_sum{{stage=\"{name}\"}} {}",
+            hist.sum_secs
+        );
+        let _ = writeln!(
+            body,
+            "linnix_event_latency_seconds_count{{stage=\"{name}\"}} {}",
+            hist.count
+        );
+    }
+    let _ = writeln!(
+        body,
+        "# HELP linnix_event_latency_unstamped_total Events without a kernel timestamp to measure."
+    );
+    let _ = writeln!(body, "# TYPE linnix_event_latency_unstamped_total counter");
+    let _ = writeln!(
+        body,
+        "linnix_event_latency_unstamped_total {}",
+        metrics.latency().unstamped()
+    );
+
+    let startup = app_state.context.startup_histogram().snapshot();
+    let _ = writeln!(
+        body,
+        "# HELP linnix_exec_to_listen_seconds Time from exec to the first listening socket."
+    );
+    let _ = writeln!(body, "# TYPE linnix_exec_to_listen_seconds histogram");
+    for (le, count) in &startup.buckets {
+        let _ = writeln!(
+            body,
+            "linnix_exec_to_listen_seconds_bucket{{le=\"{le}\"}} {count}"
+        );
+    }
+    let _ = writeln!(
+        body,
+        "linnix_exec_to_listen_seconds_bucket{{le=\"+Inf\"}} {}",
+        startup.count
+    );
+    let _ = writeln!(
+        body,
+        "linnix_exec_to_listen_seconds_sum {}",
+        startup.sum_secs
+    );
+    let _ = writeln!(
+        body,
+        "linnix_exec_to_listen_seconds_count {}",
+        startup.count
+    );
+
+    let node_net = app_state.context.node_net();
+    let now_ns = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64;
+    let _ = writeln!(
+        body,
+        "# HELP linnix_net_bytes_total Socket bytes seen by the net probes, by family and direction."
+    );
+    let _ = writeln!(body, "# TYPE linnix_net_bytes_total counter");
+    for (family, direction, bytes) in node_net.totals() {
+        let _ = writeln!(
+            body,
+            "linnix_net_bytes_total{{family=\"{}\",direction=\"{}\"}} {bytes}",
+            family.as_str(),
+            direction.as_str()
+        );
+    }
+    let rates = node_net.rates(now_ns);
+    let _ = writeln!(
+        body,
+        "# HELP linnix_net_bytes_per_second Node-wide socket throughput over the last {}s window.",
+        net_traffic::WINDOW.as_secs()
+    );
+    let _ = writeln!(body, "# TYPE linnix_net_bytes_per_second gauge");
+    for (family, direction) in net_traffic::SLOTS {
+        let _ = writeln!(
+            body,
+            "linnix_net_bytes_per_second{{family=\"{}\",direction=\"{}\"}} {}",
+            family.as_str(),
+            direction.as_str(),
+            rates.rate(family, direction)
+        );
+    }
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )
+        .body(body.into())
+        .unwrap()
+}
+
+pub async fn metrics_handler(State(app_state): State<Arc<AppState>>) -> Json<MetricsResponse> {
+    let mut sys = System::new_all();
+    sys.refresh_all();
+    let pid = Pid::from_u32(std::process::id());
+    let (cpu_percent, rss) = if let Some(proc) = sys.process(pid) {
+        // proc.memory() returns bytes, convert to KB for the response
+        (proc.cpu_usage(), proc.memory() / 1024)
+    } else {
+        (0.0, 0)
+    };
+
+    let metrics = &app_state.metrics;
+    let resp = MetricsResponse {
+        cpu_percent,
+        rss,
+        subscribers: metrics.subscribers.load(Ordering::Relaxed),
         queue_depth: app_state.context.queue_depth(),
         dropped_events_total: metrics.dropped_events_total.load(Ordering::Relaxed),
         alerts_active: metrics.alerts_active.load(Ordering::Relaxed),
@@ -1592,6 +3225,20 @@ pub async fn metrics_handler(State(app_state): State<Arc<AppState>>) -> Json<Met
         slack_sent: metrics.slack_sent(),
         slack_failed: metrics.slack_failed(),
         alerts_generated: metrics.alerts_generated(),
+        bpf_maps: metrics.bpf_maps(),
+        degradation: DegradationMetrics {
+            level: metrics.degradation_level(),
+            step: crate::runtime::degradation::step_name(metrics.degradation_level()),
+            escalations: metrics.degradation_escalations(),
+            recoveries: metrics.degradation_recoveries(),
+            ingest_paused: metrics.ingest_paused(),
+            ingest_restarts: metrics.ingest_restarts(),
+            ingest_paused_drops: metrics.ingest_paused_drops(),
+            broadcast_shed: app_state.context.broadcast_shed(),
+        },
+        reorder: metrics.reorder().snapshot(),
+        canary: metrics.canary().snapshot(),
+        context: app_state.context.memory_usage(),
     };
     Json(resp)
 }
@@ -1608,40 +3255,115 @@ fn probe_mode_label(mode: u8) -> &'static str {
 pub struct AppState {
     pub context: Arc<ContextStore>,
     pub metrics: Arc<Metrics>,
-    pub alerts: Option<broadcast::Sender<Alert>>,
+    pub alerts: Option<AlertSender>,
     pub insights: Arc<InsightsStore>,
+    /// `/insights` analyses, running or cached.
+    pub insight_jobs: Arc<insight_jobs::InsightJobs>,
     pub offline: Arc<OfflineGuard>,
     pub transport: &'static str,
     pub probe_state: ProbeState,
     pub reasoner: ReasonerConfig,
     pub prometheus_enabled: bool,
     pub alert_history: Arc<AlertHistory>,
+    /// Operator token; see also `api_tokens`.
     pub auth_token: Option<String>,
+    /// `[[api.tokens]]`, each with its own scope.
+    pub api_tokens: Vec<ApiTokenConfig>,
+    /// Dashboard sections per token scope.
+    pub ui: UiConfig,
     pub enforcement: Option<Arc<crate::enforcement::EnforcementQueue>>,
+    pub coordination: Arc<Coordinator>,
     pub incident_store: Option<Arc<IncidentStore>>,
     pub k8s: Option<Arc<cognitod::k8s::K8sContext>>,
+    pub maintenance: Option<Arc<MaintenanceManager>>,
+    pub bpf: Option<Arc<BpfControl>>,
+    /// Monitored cgroup subtrees; `None` watches the whole node.
+    pub scope: Option<Arc<CgroupScope>>,
+    /// RAPL energy estimates; `None` when the node has no counters.
+    pub power: Option<Arc<PowerMonitor>>,
+    pub memory_events: Option<Arc<MemoryEventsWatcher>>,
+    pub ephemeral_storage: Option<Arc<EphemeralStorageWatcher>>,
+    /// `None` when every built-in detector is disabled.
+    pub detectors: Option<Arc<DetectorList>>,
+    /// LLM incident analysis queue; `None` without a reasoner.
+    pub analysis: Option<Arc<AnalysisPool>>,
+    pub restart_loops: Option<Arc<RestartLoopDetector>>,
+    /// `None` when `[health_score]` is disabled.
+    pub health: Option<Arc<HealthScorer>>,
+    /// Service graph; `None` when `[topology]` is disabled.
+    pub topology: Option<Arc<ServiceGraph>>,
+    /// Names of remote peers; `None` when `[enrichment]` is disabled.
+    pub enrichment: Option<Arc<PeerEnricher>>,
+    /// Usage rollups; `None` when `[leaderboard]` is disabled.
+    pub leaderboard: Option<Arc<Leaderboard>>,
+    /// CPU by process ancestry; `None` when `[ancestry_profile]` is disabled.
+    pub ancestry_profile: Option<Arc<AncestryProfile>>,
+    /// Per-pod autoscaling signals; `None` when `[scaling]` is disabled.
+    pub scaling: Option<Arc<ScalingSignals>>,
+    /// Per-mount usage; `None` when `[filesystems]` is disabled.
+    pub filesystems: Option<Arc<FilesystemWatcher>>,
+    /// SMART health per disk; `None` when `[smart]` is disabled.
+    pub smart: Option<Arc<SmartMonitor>>,
+    /// Expressions shown per process under `computed` in `/processes`.
+    pub computed_fields: Vec<ComputedField>,
+    /// Client IP lists and rate limit; `None` when none are configured.
+    pub access: Option<Arc<access::AccessControl>>,
+    /// `/processes/{pid}/maps`; `None` without `[forensics] operator_token`.
+    pub forensics: Option<Arc<forensics::Forensics>>,
+    /// Verifies `/api/discord/interactions`; `None` without a Discord bot.
+    #[cfg_attr(not(feature = "notifications"), allow(dead_code))]
+    pub discord_public_key: Option<String>,
+    /// Notification message templates, rendered by `/templates/preview`.
+    pub templates: Arc<Templates>,
+    /// Pending anonymized report, served by `/telemetry/preview` whether or
+    /// not reporting is enabled.
+    #[cfg(feature = "fleet-telemetry")]
+    pub fleet_telemetry: Option<Arc<cognitod::fleet_telemetry::FleetTelemetry>>,
 }
 
 pub fn all_routes(app_state: Arc<AppState>) -> Router {
     let prometheus_enabled = app_state.prometheus_enabled;
-    let auth_token = app_state.auth_token.clone();
+    let tokens = auth::Tokens::new(app_state.auth_token.clone(), &app_state.api_tokens);
+    let access = app_state.access.clone();
 
     let mut router = Router::new()
-        .route("/", get(crate::ui::dashboard_handler))
-        .route("/dashboard", get(crate::ui::dashboard_handler))
+        .route("/", get(dashboard))
+        .route("/dashboard", get(dashboard))
         .route("/context", get(get_context_route))
+        .route("/history", get(get_history))
         .route("/processes", get(get_processes))
         .route("/processes/live", get(stream_processes_live))
+        .route("/processes/sync", get(sync_processes))
         .route("/processes/{pid}", get(get_process_by_pid))
+        .route("/processes/{pid}/flows", get(get_process_flows))
+        .route("/processes/{pid}/syscalls", get(get_process_syscalls))
         .route("/ppid/{ppid}", get(get_by_ppid))
         .route("/graph/{pid}", get(get_graph))
         .route("/events", get(stream_events))
         .route("/stream", get(stream_events))
+        .route("/schema/events", get(get_event_schema))
         .route("/system", get(system_snapshot))
         .route("/timeline", get(get_timeline))
+        .route("/timeline/{id}/evidence", get(get_alert_evidence))
+        .route("/topology", get(get_topology))
+        .route("/peers", get(get_peers))
+        .route("/leaderboard", get(get_leaderboard))
+        .route("/profile/ancestry", get(get_ancestry_profile))
+        .route("/scaling", get(get_scaling))
+        .route("/scaling/{signal}", get(get_scaling_signal))
+        .route("/filesystems", get(get_filesystems))
+        .route("/devices", get(get_devices))
         .route("/metrics/system", get(get_system_metrics))
+        .route("/metrics/loss", get(get_loss_report))
         .route("/alerts", get(stream_alerts))
-        .route("/insights", get(get_insights))
+        .route("/notifications/queue", get(get_notification_queues))
+        .route("/templates/preview", get(preview_templates))
+        .route(
+            "/insights",
+            get(insight_jobs::get_insights).post(insight_jobs::post_insights),
+        )
+        .route("/insights/jobs/{id}", get(insight_jobs::get_job))
+        .route("/insights/jobs/{id}/stream", get(insight_jobs::stream_job))
         .route("/insights/recent", get(get_recent_insights))
         .route("/insights/{id}", get(get_insight_by_id))
         .route("/insights/{id}/feedback", post(submit_feedback))
@@ -1650,28 +3372,94 @@ pub fn all_routes(app_state: Arc<AppState>) -> Router {
         .route("/incidents", get(get_incidents))
         .route("/incidents/summary", get(get_incident_summary))
         .route("/incidents/stats", get(get_incident_stats))
+        .route("/incidents/reanalyze", post(reanalyze_incidents))
         .route("/incidents/{id}", get(get_incident_by_id))
+        .route("/incidents/{id}/analyze", post(analyze_incident))
         .route("/attribution", get(get_attributions))
+        .route("/cgroups", get(get_cgroups))
+        .route("/pods", get(get_pods))
         .route("/metrics", get(metrics_handler))
         .route("/status", get(status_handler))
         .route("/healthz", get(healthz))
+        .route(
+            "/offline",
+            get(offline::get_offline).post(offline::set_offline),
+        )
+        .route("/health/score", get(get_health_score))
         // .route("/insights/schema", get(get_insight_schema_route)) // Removed (YAGNI cleanup)
         .route("/actions", get(get_actions))
+        .route("/actions/pending", get(get_pending_actions))
         .route("/actions/{id}", get(get_action_by_id))
         .route("/actions/{id}/approve", axum::routing::post(approve_action))
-        .route("/actions/{id}/reject", axum::routing::post(reject_action));
+        .route("/actions/{id}/reject", axum::routing::post(reject_action))
+        .route("/rules/effectiveness", get(get_rules_effectiveness))
+        .route("/detectors", get(get_detectors))
+        .route(
+            "/maintenance",
+            get(get_maintenance).post(create_maintenance_window),
+        )
+        .route(
+            "/maintenance/{id}",
+            axum::routing::delete(cancel_maintenance_window),
+        )
+        .route("/bpf/control", get(get_bpf_control))
+        .route("/bpf/sequencer", post(set_bpf_sequencer))
+        .route("/debug/sequencer", get(get_debug_sequencer))
+        .route("/bpf/sampling", post(set_bpf_sampling))
+        .route("/bpf/filters", post(update_bpf_filters))
+        .route("/bpf/page_faults", get(get_bpf_page_faults))
+        .route("/warmth/health", get(get_warmth_health));
+
+    // Reads a read-only token may not make; other methods are operator-only
+    // through the auth layer already
+    router = router.merge(
+        Router::new()
+            .route("/processes/{pid}/maps", get(forensics::get_process_maps))
+            .route("/export/{dataset}", get(export::export))
+            .route("/state/export", get(export::state))
+            .route_layer(axum::middleware::from_fn(auth::require_operator)),
+    );
+
+    #[cfg(feature = "fleet-telemetry")]
+    {
+        router = router.route("/telemetry/preview", get(preview_telemetry));
+    }
 
     if prometheus_enabled {
         router = router.route("/metrics/prometheus", get(prometheus_metrics));
     }
 
-    if auth_token.is_some() {
+    if let Some(tokens) = tokens {
         router = router.layer(axum::middleware::from_fn_with_state(
-            auth_token,
+            Arc::new(tokens),
             auth::auth_middleware,
         ));
     }
 
+    // Kubelet probes cannot send a bearer token
+    router = router.route("/readyz", get(readyz));
+
+    // Added after the auth layer: Discord cannot send a bearer token and
+    // signs every request instead.
+    #[cfg(feature = "notifications")]
+    if app_state.discord_public_key.is_some() {
+        router = router.route(
+            "/api/discord/interactions",
+            post(handle_discord_interaction),
+        );
+    }
+
+    // gzip or brotli when the client accepts it; event streams are left as is
+    router = router.layer(CompressionLayer::new());
+
+    // Outermost, so refused and throttled clients never reach authentication
+    if let Some(access) = access {
+        router = router.layer(axum::middleware::from_fn_with_state(
+            access,
+            access::access_middleware,
+        ));
+    }
+
     router.with_state(app_state)
 }
 
@@ -1741,15 +3529,18 @@ async fn submit_feedback_api(
         )
     })?;
 
-    store
-        .insert_feedback(
-            &req.insight_id,
-            &req.label,
-            &req.source,
-            req.user_id.as_deref(),
-        )
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let queued = store.submit(IncidentWrite::Feedback {
+        insight_id: req.insight_id,
+        label: req.label,
+        source: req.source,
+        user_id: req.user_id,
+    });
+    if !queued {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Incident write queue full".to_string(),
+        ));
+    }
 
     app.metrics.inc_feedback_entry();
 
@@ -1826,6 +3617,96 @@ async fn get_incident_by_id(
     Ok(Json(incident))
 }
 
+#[derive(Serialize)]
+struct AnalysisQueued {
+    queued: usize,
+    priority: AnalysisPriority,
+}
+
+type AnalysisServices<'a> = (&'a Arc<IncidentStore>, &'a Arc<AnalysisPool>);
+
+fn analysis_services(app: &AppState) -> Result<AnalysisServices<'_>, (StatusCode, String)> {
+    let store = app.incident_store.as_ref().ok_or_else(|| {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Incident store not available".to_string(),
+        )
+    })?;
+    let pool = app.analysis.as_ref().ok_or_else(|| {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Incident analysis not enabled".to_string(),
+        )
+    })?;
+    Ok((store, pool))
+}
+
+/// POST /incidents/{id}/analyze - Queue an LLM analysis of one incident,
+/// superseding any pending or running analysis of it
+async fn analyze_incident(
+    Path(id): Path<i64>,
+    State(app): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<AnalysisQueued>), (StatusCode, String)> {
+    let (store, pool) = analysis_services(&app)?;
+    let incident = store
+        .get(id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Incident not found".to_string()))?;
+    let priority = AnalysisPriority::Manual;
+    if !pool.submit(id, incident, priority) {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Analysis queue full".to_string(),
+        ));
+    }
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(AnalysisQueued {
+            queued: 1,
+            priority,
+        }),
+    ))
+}
+
+#[derive(Deserialize)]
+struct ReanalyzeParams {
+    /// Most recent incidents considered (default 50, at most 500).
+    limit: Option<i64>,
+    /// Also re-run incidents that already have an LLM analysis; those with
+    /// only a fallback summary are always re-run.
+    #[serde(default)]
+    all: bool,
+}
+
+/// POST /incidents/reanalyze - Queue batch re-analysis of recent incidents
+/// behind circuit-breaker and manual requests
+async fn reanalyze_incidents(
+    Query(params): Query<ReanalyzeParams>,
+    State(app): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<AnalysisQueued>), (StatusCode, String)> {
+    let (store, pool) = analysis_services(&app)?;
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+    let incidents = store
+        .recent(limit)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let priority = AnalysisPriority::Batch;
+    let mut queued = 0;
+    for incident in incidents {
+        let Some(id) = incident.id else { continue };
+        let needs_llm = incident.llm_analysis.is_none()
+            || incident.analysis_source == Some(AnalysisSource::Fallback);
+        if (params.all || needs_llm) && pool.submit(id, incident, priority) {
+            queued += 1;
+        }
+    }
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(AnalysisQueued { queued, priority }),
+    ))
+}
+
 /// GET /incidents/stats - Get incident statistics
 async fn get_incident_stats(
     State(app): State<Arc<AppState>>,
@@ -2100,10 +3981,76 @@ async fn handle_slack_interaction(
     (StatusCode::OK, "").into_response()
 }
 
+#[cfg(feature = "notifications")]
+async fn handle_discord_interaction(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> impl IntoResponse {
+    use cognitod::notifications::discord::{self, Decision, Interaction};
+
+    let Some(public_key) = &state.discord_public_key else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    let verified = match (
+        header("x-signature-ed25519"),
+        header("x-signature-timestamp"),
+    ) {
+        (Some(signature), Some(timestamp)) => {
+            discord::verify_signature(public_key, signature, timestamp, &body)
+        }
+        _ => false,
+    };
+    if !verified {
+        return (StatusCode::UNAUTHORIZED, "invalid request signature").into_response();
+    }
+
+    let interaction: Interaction = match serde_json::from_slice(&body) {
+        Ok(i) => i,
+        Err(e) => {
+            log::warn!("Failed to parse Discord interaction: {}", e);
+            return (StatusCode::BAD_REQUEST, "Invalid payload").into_response();
+        }
+    };
+    if interaction.kind == discord::INTERACTION_PING {
+        return Json(json!({ "type": 1 })).into_response();
+    }
+    let Some((decision, id)) = interaction.decision() else {
+        return (StatusCode::BAD_REQUEST, "Unsupported interaction").into_response();
+    };
+    let Some(enforcement) = &state.enforcement else {
+        log::warn!("Received Discord interaction but enforcement is disabled");
+        return Json(discord::ephemeral_response("Enforcement is disabled")).into_response();
+    };
+
+    let actor = interaction.actor();
+    let result = match decision {
+        Decision::Approve => enforcement.approve(id, actor.clone()).await.map(|_| ()),
+        Decision::Reject => enforcement.reject(id, actor.clone()).await,
+    };
+    let response = match (decision, result) {
+        (Decision::Approve, Ok(())) => {
+            log::info!("Approved action {} via Discord ({})", id, actor);
+            discord::update_response(&format!("✅ Approved by {actor}"))
+        }
+        (Decision::Reject, Ok(())) => {
+            log::info!("Rejected action {} via Discord ({})", id, actor);
+            discord::update_response(&format!("❌ Rejected by {actor}"))
+        }
+        (_, Err(e)) => {
+            log::warn!("Failed to settle action {} via Discord: {}", id, e);
+            discord::ephemeral_response(&format!("Could not update action {id}: {e}"))
+        }
+    };
+    Json(response).into_response()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::insights::InsightStore;
+    use crate::runtime::features::FeatureNegotiation;
     use crate::runtime::probes::{ProbeState, RssProbeMode};
     use crate::{PERCENT_MILLI_UNKNOWN, ProcessEvent};
     use axum::body::{Body, to_bytes};
@@ -2113,6 +4060,55 @@ mod tests {
     use std::sync::atomic::Ordering;
     use tower::ServiceExt;
 
+    /// State with every optional component off; tests override the fields
+    /// they exercise.
+    fn test_state() -> AppState {
+        AppState {
+            context: Arc::new(ContextStore::new(Duration::from_secs(60), 10, None)),
+            metrics: Arc::new(Metrics::new()),
+            alerts: None,
+            insights: Arc::new(InsightStore::new(16, None)),
+            insight_jobs: Arc::new(insight_jobs::InsightJobs::new(Duration::from_secs(60))),
+            offline: Arc::new(OfflineGuard::new(false)),
+            transport: "perf",
+            probe_state: ProbeState::disabled(),
+            enforcement: None,
+            coordination: Arc::new(Coordinator::unlocked()),
+            reasoner: ReasonerConfig::default(),
+            prometheus_enabled: false,
+            alert_history: Arc::new(AlertHistory::new(16)),
+            auth_token: None,
+            api_tokens: Vec::new(),
+            ui: UiConfig::default(),
+            incident_store: None,
+            k8s: None,
+            maintenance: None,
+            bpf: None,
+            scope: None,
+            power: None,
+            memory_events: None,
+            ephemeral_storage: None,
+            detectors: None,
+            analysis: None,
+            restart_loops: None,
+            health: None,
+            topology: None,
+            enrichment: None,
+            leaderboard: None,
+            ancestry_profile: None,
+            scaling: None,
+            filesystems: None,
+            smart: None,
+            computed_fields: Vec::new(),
+            access: None,
+            forensics: None,
+            discord_public_key: None,
+            templates: Templates::defaults(),
+            #[cfg(feature = "fleet-telemetry")]
+            fleet_telemetry: None,
+        }
+    }
+
     #[tokio::test]
     async fn heartbeats_emit_every_10s() {
         tokio::time::pause();
@@ -2173,6 +4169,7 @@ mod tests {
             data2: 0,
             aux: 0,
             aux2: 0,
+            cgroup_id: 0,
         };
         let base_event = ProcessEvent::new(base_wire);
         for _ in 0..1500 {
@@ -2184,25 +4181,12 @@ mod tests {
 
     #[tokio::test]
     async fn status_keys_present() {
-        let ctx = Arc::new(ContextStore::new(Duration::from_secs(60), 10, None));
         let metrics = Arc::new(Metrics::new());
         let app_state = Arc::new(AppState {
-            context: Arc::clone(&ctx),
             metrics: Arc::clone(&metrics),
-            alerts: None,
-            insights: Arc::new(InsightStore::new(16, None)),
-            offline: Arc::new(OfflineGuard::new(false)),
-            transport: "perf",
-            probe_state: ProbeState::disabled(),
-            enforcement: None,
-            reasoner: ReasonerConfig::default(),
-            prometheus_enabled: false,
-            alert_history: Arc::new(AlertHistory::new(16)),
-            auth_token: None,
-            incident_store: None,
-            k8s: None,
+            ..test_state()
         });
-        let Json(resp) = super::status_handler(State(app_state)).await;
+        let Json(resp) = super::status_handler(State(Arc::clone(&app_state))).await;
         let val = serde_json::to_value(resp).unwrap();
         let obj = val.as_object().unwrap();
         for key in [
@@ -2223,32 +4207,98 @@ mod tests {
         ] {
             assert!(obj.contains_key(key));
         }
+        assert_eq!(obj["rss"]["unit"], "bytes");
+        assert_eq!(obj["cpu"]["unit"], "percent");
+        assert_eq!(obj["event_rate"]["unit"], "per_second");
+        assert_eq!(obj["coordination"]["role"], "unlocked");
+
+        // The flat fields of /metrics/system remain beside the typed ones
+        let Json(system) = super::get_system_metrics(State(app_state)).await;
+        let system = serde_json::to_value(system).unwrap();
+        assert_eq!(system["memory_used"]["unit"], "bytes");
+        assert_eq!(
+            system["memory_used_mb"],
+            system["memory_used"]["value"].as_u64().unwrap() / 1024 / 1024
+        );
+        assert!(system["cpu_total_pct"].is_number());
+    }
+
+    #[tokio::test]
+    async fn maintenance_windows_appear_in_status() {
+        let app_state = Arc::new(AppState {
+            maintenance: Some(Arc::new(MaintenanceManager::default())),
+            ..test_state()
+        });
+
+        let req: CreateWindowRequest = serde_json::from_value(json!({
+            "name": "kernel-upgrade",
+            "duration_secs": 900,
+            "reason": "reboot",
+            "scope": {"namespaces": ["batch-*"]}
+        }))
+        .unwrap();
+        let (code, Json(window)) =
+            super::create_maintenance_window(State(Arc::clone(&app_state)), Json(req))
+                .await
+                .unwrap();
+        assert_eq!(code, StatusCode::CREATED);
+
+        let Json(resp) = super::status_handler(State(Arc::clone(&app_state))).await;
+        let val = serde_json::to_value(resp).unwrap();
+        assert_eq!(val["maintenance"][0]["name"], "kernel-upgrade");
+        assert_eq!(val["maintenance"][0]["scope"]["namespaces"][0], "batch-*");
+
+        let code = super::cancel_maintenance_window(
+            State(Arc::clone(&app_state)),
+            Path(window.id.clone()),
+            Query(CancelWindowQuery {
+                by: Some("alice".into()),
+            }),
+        )
+        .await;
+        assert_eq!(code, StatusCode::NO_CONTENT);
+        let Json(resp) = super::get_maintenance(State(app_state)).await.unwrap();
+        assert!(resp.windows.is_empty());
+        assert_eq!(resp.audit.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn bpf_control_unavailable_without_object() {
+        let app_state = Arc::new(AppState {
+            transport: "userspace",
+            ..test_state()
+        });
+
+        let app = all_routes(app_state);
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/bpf/sampling")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"event_type":"net","divisor":10}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
     }
 
     #[tokio::test]
     async fn metrics_includes_probe_state() {
-        let ctx = Arc::new(ContextStore::new(Duration::from_secs(60), 10, None));
         let metrics = Arc::new(Metrics::new());
         metrics.set_rss_probe_mode(RssProbeMode::CoreMm.metric_value());
         metrics.set_kernel_btf_available(true);
         let app_state = Arc::new(AppState {
-            context: Arc::clone(&ctx),
             metrics: Arc::clone(&metrics),
-            alerts: None,
-            insights: Arc::new(InsightStore::new(16, None)),
-            offline: Arc::new(OfflineGuard::new(false)),
             transport: "tracepoint",
             probe_state: ProbeState {
                 rss_probe: RssProbeMode::CoreMm,
                 btf_available: true,
+                features: None,
+                kprobes: Vec::new(),
             },
-            enforcement: None,
-            reasoner: ReasonerConfig::default(),
-            prometheus_enabled: false,
-            alert_history: Arc::new(AlertHistory::new(16)),
-            auth_token: None,
-            incident_store: None,
-            k8s: None,
+            ..test_state()
         });
 
         let Json(resp) = super::metrics_handler(State(app_state)).await;
@@ -2263,23 +4313,10 @@ mod tests {
 
     #[tokio::test]
     async fn prometheus_endpoint_respects_flag() {
-        let ctx = Arc::new(ContextStore::new(Duration::from_secs(60), 10, None));
         let metrics = Arc::new(Metrics::new());
         let app_state = Arc::new(AppState {
-            context: Arc::clone(&ctx),
             metrics: Arc::clone(&metrics),
-            alerts: None,
-            insights: Arc::new(InsightStore::new(16, None)),
-            offline: Arc::new(OfflineGuard::new(false)),
-            transport: "perf",
-            probe_state: ProbeState::disabled(),
-            enforcement: None,
-            reasoner: ReasonerConfig::default(),
-            prometheus_enabled: false,
-            alert_history: Arc::new(AlertHistory::new(16)),
-            auth_token: None,
-            incident_store: None,
-            k8s: None,
+            ..test_state()
         });
         let router = super::all_routes(Arc::clone(&app_state));
         let response = router
@@ -2299,21 +4336,29 @@ mod tests {
         let ctx = Arc::new(ContextStore::new(Duration::from_secs(60), 10, None));
         let metrics = Arc::new(Metrics::new());
         metrics.events_total.fetch_add(42, Ordering::Relaxed);
+        ctx.add(ProcessEvent::new(ProcessEventWire {
+            pid: 7,
+            ppid: 1,
+            uid: 0,
+            gid: 0,
+            event_type: EventType::Net as u32,
+            ts_ns: 0,
+            seq: 0,
+            comm: [0; 16],
+            exit_time_ns: 0,
+            cpu_pct_milli: PERCENT_MILLI_UNKNOWN,
+            mem_pct_milli: PERCENT_MILLI_UNKNOWN,
+            data: 1500,
+            data2: 0,
+            aux: linnix_ai_ebpf_common::NetOp::UdpSend as u32,
+            aux2: 0,
+            cgroup_id: 0,
+        }));
         let app_state = Arc::new(AppState {
             context: Arc::clone(&ctx),
             metrics: Arc::clone(&metrics),
-            alerts: None,
-            insights: Arc::new(InsightStore::new(16, None)),
-            offline: Arc::new(OfflineGuard::new(false)),
-            transport: "perf",
-            probe_state: ProbeState::disabled(),
-            enforcement: None,
-            reasoner: ReasonerConfig::default(),
             prometheus_enabled: true,
-            alert_history: Arc::new(AlertHistory::new(16)),
-            auth_token: None,
-            incident_store: None,
-            k8s: None,
+            ..test_state()
         });
         let router = super::all_routes(Arc::clone(&app_state));
         let response = router
@@ -2341,28 +4386,28 @@ mod tests {
             body_text.contains("linnix_events_total"),
             "expected metric missing: {body_text}"
         );
+        assert!(
+            body_text
+                .contains("linnix_events_lost_total{transport=\"unknown\",source=\"seq_gap\"} 0"),
+            "expected loss metric missing: {body_text}"
+        );
+        assert!(
+            body_text.contains("linnix_exec_to_listen_seconds_bucket{le=\"+Inf\"} 0"),
+            "expected startup histogram missing: {body_text}"
+        );
+        assert!(
+            body_text.contains("linnix_net_bytes_total{family=\"udp\",direction=\"tx\"} 1500"),
+            "expected net bytes by family missing: {body_text}"
+        );
+        assert!(
+            body_text.contains("linnix_net_bytes_per_second{family=\"tcp\",direction=\"rx\"} 0"),
+            "expected net rate gauge missing: {body_text}"
+        );
     }
 
     #[tokio::test]
     async fn test_no_auth_allows_requests() {
-        let ctx = Arc::new(ContextStore::new(Duration::from_secs(60), 10, None));
-        let metrics = Arc::new(Metrics::new());
-        let app_state = Arc::new(AppState {
-            context: Arc::clone(&ctx),
-            metrics: Arc::clone(&metrics),
-            alerts: None,
-            insights: Arc::new(InsightStore::new(16, None)),
-            offline: Arc::new(OfflineGuard::new(false)),
-            transport: "perf",
-            probe_state: ProbeState::disabled(),
-            enforcement: None,
-            reasoner: ReasonerConfig::default(),
-            prometheus_enabled: false,
-            alert_history: Arc::new(AlertHistory::new(16)),
-            auth_token: None,
-            incident_store: None,
-            k8s: None,
-        });
+        let app_state = Arc::new(test_state());
         let router = super::all_routes(app_state);
         let response = router
             .oneshot(
@@ -2378,25 +4423,23 @@ mod tests {
 
     #[tokio::test]
     async fn test_auth_required_when_token_set() {
-        let ctx = Arc::new(ContextStore::new(Duration::from_secs(60), 10, None));
-        let metrics = Arc::new(Metrics::new());
         let app_state = Arc::new(AppState {
-            context: Arc::clone(&ctx),
-            metrics: Arc::clone(&metrics),
-            alerts: None,
-            insights: Arc::new(InsightStore::new(16, None)),
-            offline: Arc::new(OfflineGuard::new(false)),
-            transport: "perf",
-            probe_state: ProbeState::disabled(),
-            enforcement: None,
-            reasoner: ReasonerConfig::default(),
-            prometheus_enabled: false,
-            alert_history: Arc::new(AlertHistory::new(16)),
-            incident_store: None,
             auth_token: Some("secret123".to_string()),
-            k8s: None,
+            ..test_state()
         });
         let router = super::all_routes(app_state);
+        // Readiness stays open to probes; this one fails on the missing object
+        let response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/readyz")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
         let response = router
             .oneshot(
                 Request::builder()
@@ -2411,23 +4454,9 @@ mod tests {
 
     #[tokio::test]
     async fn test_auth_with_valid_bearer_token() {
-        let ctx = Arc::new(ContextStore::new(Duration::from_secs(60), 10, None));
-        let metrics = Arc::new(Metrics::new());
         let app_state = Arc::new(AppState {
-            context: Arc::clone(&ctx),
-            metrics: Arc::clone(&metrics),
-            alerts: None,
-            insights: Arc::new(InsightStore::new(16, None)),
-            offline: Arc::new(OfflineGuard::new(false)),
-            transport: "perf",
-            probe_state: ProbeState::disabled(),
-            enforcement: None,
-            reasoner: ReasonerConfig::default(),
-            prometheus_enabled: false,
-            alert_history: Arc::new(AlertHistory::new(16)),
-            incident_store: None,
             auth_token: Some("secret123".to_string()),
-            k8s: None,
+            ..test_state()
         });
         let router = super::all_routes(app_state);
         let response = router
@@ -2445,23 +4474,9 @@ mod tests {
 
     #[tokio::test]
     async fn test_auth_with_invalid_token() {
-        let ctx = Arc::new(ContextStore::new(Duration::from_secs(60), 10, None));
-        let metrics = Arc::new(Metrics::new());
         let app_state = Arc::new(AppState {
-            context: Arc::clone(&ctx),
-            metrics: Arc::clone(&metrics),
-            alerts: None,
-            insights: Arc::new(InsightStore::new(16, None)),
-            offline: Arc::new(OfflineGuard::new(false)),
-            transport: "perf",
-            probe_state: ProbeState::disabled(),
-            enforcement: None,
-            reasoner: ReasonerConfig::default(),
-            prometheus_enabled: false,
-            alert_history: Arc::new(AlertHistory::new(16)),
-            incident_store: None,
             auth_token: Some("secret123".to_string()),
-            k8s: None,
+            ..test_state()
         });
         let router = super::all_routes(app_state);
         let response = router
@@ -2479,23 +4494,9 @@ mod tests {
 
     #[tokio::test]
     async fn test_auth_with_malformed_header() {
-        let ctx = Arc::new(ContextStore::new(Duration::from_secs(60), 10, None));
-        let metrics = Arc::new(Metrics::new());
         let app_state = Arc::new(AppState {
-            context: Arc::clone(&ctx),
-            metrics: Arc::clone(&metrics),
-            alerts: None,
-            insights: Arc::new(InsightStore::new(16, None)),
-            offline: Arc::new(OfflineGuard::new(false)),
-            transport: "perf",
-            probe_state: ProbeState::disabled(),
-            enforcement: None,
-            reasoner: ReasonerConfig::default(),
-            prometheus_enabled: false,
-            alert_history: Arc::new(AlertHistory::new(16)),
-            incident_store: None,
             auth_token: Some("secret123".to_string()),
-            k8s: None,
+            ..test_state()
         });
         let router = super::all_routes(app_state);
         let response = router
@@ -2510,4 +4511,243 @@ mod tests {
             .unwrap();
         assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
     }
+
+    #[tokio::test]
+    async fn test_read_only_token_gets_read_only_dashboard() {
+        let app_state = Arc::new(AppState {
+            auth_token: Some("secret123".to_string()),
+            api_tokens: vec![ApiTokenConfig {
+                token: "viewer".to_string(),
+                scope: TokenScope::ReadOnly,
+            }],
+            ..test_state()
+        });
+        let router = super::all_routes(app_state);
+        let request = |method: &str, uri: &str, token: &str| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("Authorization", format!("Bearer {token}"))
+                .header("Content-Type", "application/json")
+                .body(Body::from(r#"{"approver":"me"}"#))
+                .unwrap()
+        };
+
+        let response = router
+            .clone()
+            .oneshot(request("GET", "/dashboard", "viewer"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let page = String::from_utf8_lossy(&body);
+        assert!(page.contains(r#""scope":"read_only""#));
+        assert!(page.contains(r#""controls":false"#));
+
+        let response = router
+            .clone()
+            .oneshot(request("POST", "/actions/a1/approve", "viewer"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // The operator token reaches the handler; there is no queue here
+        let response = router
+            .clone()
+            .oneshot(request("POST", "/actions/a1/approve", "secret123"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Operator-only reads
+        for uri in ["/processes/1/maps", "/export/incidents", "/state/export"] {
+            let response = router
+                .clone()
+                .oneshot(request("GET", uri, "viewer"))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN, "{uri}");
+        }
+        // Forensics is off here
+        let peer = std::net::SocketAddr::from(([127, 0, 0, 1], 0));
+        let response = router
+            .layer(axum::extract::connect_info::MockConnectInfo(peer))
+            .oneshot(request("GET", "/processes/1/maps", "secret123"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn processes_paginate_project_and_honor_etag() {
+        let ctx = Arc::new(ContextStore::new(Duration::from_secs(60), 10, None));
+        let exec = |pid| {
+            ProcessEvent::new(ProcessEventWire {
+                pid,
+                ppid: 1,
+                uid: 0,
+                gid: 0,
+                event_type: 0,
+                ts_ns: 0,
+                seq: 0,
+                comm: *b"worker\0\0\0\0\0\0\0\0\0\0",
+                exit_time_ns: 0,
+                cpu_pct_milli: PERCENT_MILLI_UNKNOWN,
+                mem_pct_milli: PERCENT_MILLI_UNKNOWN,
+                data: 0,
+                data2: 0,
+                aux: 0,
+                aux2: 0,
+                cgroup_id: 0,
+            })
+        };
+        for pid in [30, 10, 20] {
+            ctx.add(exec(pid));
+        }
+        let app_state = Arc::new(AppState {
+            context: Arc::clone(&ctx),
+            computed_fields: vec![ComputedField::compile("double_pid", "pid * 2").unwrap()],
+            ..test_state()
+        });
+        let router = super::all_routes(app_state);
+        let uri = "/processes?limit=2&offset=1&fields=pid,comm";
+        let get = |etag: Option<String>| {
+            let mut req = Request::builder().uri(uri);
+            if let Some(etag) = etag {
+                req = req.header(header::IF_NONE_MATCH, etag);
+            }
+            router.clone().oneshot(req.body(Body::empty()).unwrap())
+        };
+
+        // Given three live processes
+        // When the second page of size two is requested with a projection
+        let response = get(None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-total-count"], "3");
+        let etag = response.headers()[header::ETAG]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let rows: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        // Then only the requested fields of pids 20 and 30 come back
+        assert_eq!(
+            rows,
+            json!([{"pid": 20, "comm": "worker"}, {"pid": 30, "comm": "worker"}])
+        );
+
+        // When nothing changed, the same tag yields 304
+        let response = get(Some(etag.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        // When a listed process makes syscalls, the tag no longer matches
+        let mut syscall = exec(20);
+        syscall.event_type = EventType::Syscall as u32;
+        ctx.add(syscall);
+        let response = get(Some(etag)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()[header::ETAG]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        // When a process is added, the tag no longer matches
+        ctx.add(exec(40));
+        let response = get(Some(etag)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-total-count"], "4");
+
+        let response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/processes?fields=pid,bogus")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Filters are expressions over process variables and computed fields
+        let query = |uri: &'static str| {
+            router
+                .clone()
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        };
+        let response =
+            query("/processes?filter=double_pid%3E50%20%26%26%20uid%3D%3D0&fields=pid,computed")
+                .await
+                .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let rows: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            rows,
+            json!([
+                {"pid": 30, "computed": {"double_pid": 60.0}},
+                {"pid": 40, "computed": {"double_pid": 80.0}}
+            ])
+        );
+        let response = query("/processes?filter=rss_mb%3E1").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // History lookups go through the index
+        let response = query("/history?pid=30&comm=worker&event_type=exec")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let rows: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+        assert!(!rows.is_empty() && rows.iter().all(|row| row["pid"] == 30));
+        let response = query("/history?event_type=bogus").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Clients that accept gzip get the list compressed
+        let response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/processes")
+                    .header(header::ACCEPT_ENCODING, "gzip")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let rows: Vec<serde_json::Value> =
+            serde_json::from_reader(flate2::read::GzDecoder::new(&body[..])).unwrap();
+        assert_eq!(rows.len(), 4);
+    }
+
+    #[test]
+    fn readiness_needs_process_events_flowing() {
+        let probes = ProbeState {
+            features: Some(FeatureNegotiation::negotiate(None)),
+            ..ProbeState::disabled()
+        };
+        assert!(readiness_failures("perf", &probes, &[]).is_empty());
+        assert!(readiness_failures("tracepoint", &probes, &[]).is_empty());
+
+        let silent = [SilentEventType {
+            event_type: "exec",
+            silent_secs: 120,
+            expected_per_min: 40.0,
+        }];
+        assert_eq!(
+            readiness_failures("perf", &probes, &silent),
+            vec!["exec events silent for 120s"]
+        );
+        assert_eq!(
+            readiness_failures("userspace", &ProbeState::disabled(), &[]),
+            vec![
+                "no kernel event transport (userspace-only mode)",
+                "main BPF object not loaded"
+            ]
+        );
+    }
 }
@@ -0,0 +1,54 @@
+//! Egress policy (`/offline`)
+//!
+//! `GET` shows which categories of outbound traffic may leave the node.
+//! `POST` allows or blocks categories until the next restart. It is enabled
+//! by `[egress] admin_token`, which the API accepts as an operator token, and
+//! like every non-`GET` request needs operator scope. Every change is written
+//! to the audit log.
+
+use axum::{
+    Json,
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use super::AppState;
+use cognitod::config::Egress;
+
+pub async fn get_offline(State(app): State<Arc<AppState>>) -> Response {
+    Json(app.offline.policy()).into_response()
+}
+
+/// Body: category to allowed, e.g. `{"llm": false, "webhooks": true}`.
+pub async fn set_offline(
+    State(app): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Json(changes): Json<BTreeMap<Egress, bool>>,
+) -> Response {
+    let policy = app.offline.policy();
+    if !policy.runtime_toggles {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "runtime toggles are disabled; set [egress] admin_token" })),
+        )
+            .into_response();
+    }
+    for (category, allowed) in changes {
+        let before = app.offline.set(category, allowed);
+        if before != allowed {
+            log::warn!(
+                target: "linnix_audit",
+                "EGRESS {} {} by {}",
+                category.name(),
+                if allowed { "allowed" } else { "blocked" },
+                peer.ip()
+            );
+        }
+    }
+    Json(app.offline.policy()).into_response()
+}
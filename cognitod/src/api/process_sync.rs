@@ -0,0 +1,320 @@
+//! Delta-encoded process table sync over WebSocket (`/processes/sync`).
+//!
+//! The server opens with a full `snapshot` and then, every interval with
+//! changes, sends a `delta`: processes added, the changed fields of updated
+//! ones (`null` for a field that went away) and the pids that exited. Every
+//! message carries `seq`, one more than the previous message's. A client
+//! that sees a gap sends `{"type":"resync"}` and gets a fresh snapshot; a
+//! `checkpoint` snapshot is also sent periodically so clients that missed a
+//! delta without noticing converge anyway.
+//!
+//! `age_sec` moves every second for every process, so updates leave it out;
+//! clients advance it locally from the value in the snapshot or add.
+
+use axum::extract::ws::{Message, WebSocket};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{AppState, ProcessInfo};
+
+type Fields = Map<String, Value>;
+
+/// Fields that change on every read and are not sent in updates.
+const VOLATILE_FIELDS: &[&str] = &["age_sec"];
+
+const MIN_INTERVAL_MS: u64 = 500;
+
+#[derive(Deserialize)]
+pub(super) struct SyncQuery {
+    /// Milliseconds between deltas.
+    #[serde(default = "default_interval_ms")]
+    interval_ms: u64,
+    /// Seconds between checkpoint snapshots; 0 disables them.
+    #[serde(default = "default_checkpoint_secs")]
+    checkpoint_secs: u64,
+}
+
+fn default_interval_ms() -> u64 {
+    2000
+}
+
+fn default_checkpoint_secs() -> u64 {
+    60
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum SnapshotReason {
+    Initial,
+    Checkpoint,
+    Resync,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum SyncMessage {
+    Snapshot {
+        seq: u64,
+        reason: SnapshotReason,
+        processes: Vec<Fields>,
+    },
+    Delta {
+        seq: u64,
+        adds: Vec<Fields>,
+        updates: Vec<Fields>,
+        removes: Vec<u32>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Resync,
+}
+
+/// One client's view of the process table.
+struct ProcessSync {
+    seq: u64,
+    table: BTreeMap<u32, Fields>,
+    /// Snapshot to send next instead of a delta.
+    pending: Option<SnapshotReason>,
+    checkpoint_every: u64,
+    since_checkpoint: u64,
+}
+
+impl ProcessSync {
+    /// `checkpoint_every` counts ticks; 0 disables checkpoints.
+    fn new(checkpoint_every: u64) -> Self {
+        Self {
+            seq: 0,
+            table: BTreeMap::new(),
+            pending: Some(SnapshotReason::Initial),
+            checkpoint_every,
+            since_checkpoint: 0,
+        }
+    }
+
+    fn resync(&mut self) {
+        self.pending = Some(SnapshotReason::Resync);
+    }
+
+    /// The message bringing the client from its last state to `processes`,
+    /// or `None` when nothing changed.
+    fn next(&mut self, processes: Vec<Fields>) -> Option<SyncMessage> {
+        let current: BTreeMap<u32, Fields> = processes
+            .into_iter()
+            .filter_map(|p| Some((p.get("pid")?.as_u64()? as u32, p)))
+            .collect();
+
+        self.since_checkpoint += 1;
+        if self.pending.is_none()
+            && self.checkpoint_every > 0
+            && self.since_checkpoint >= self.checkpoint_every
+        {
+            self.pending = Some(SnapshotReason::Checkpoint);
+        }
+        if let Some(reason) = self.pending.take() {
+            self.since_checkpoint = 0;
+            self.seq += 1;
+            self.table = current;
+            return Some(SyncMessage::Snapshot {
+                seq: self.seq,
+                reason,
+                processes: self.table.values().cloned().collect(),
+            });
+        }
+
+        let removes: Vec<u32> = self
+            .table
+            .keys()
+            .filter(|pid| !current.contains_key(pid))
+            .copied()
+            .collect();
+        let mut adds = Vec::new();
+        let mut updates = Vec::new();
+        for (pid, fields) in &current {
+            match self.table.get(pid) {
+                None => adds.push(fields.clone()),
+                Some(previous) => {
+                    let changed = changed_fields(previous, fields);
+                    if !changed.is_empty() {
+                        let mut update = Fields::new();
+                        update.insert("pid".into(), Value::from(*pid));
+                        update.extend(changed);
+                        updates.push(update);
+                    }
+                }
+            }
+        }
+        self.table = current;
+        if adds.is_empty() && updates.is_empty() && removes.is_empty() {
+            return None;
+        }
+        self.seq += 1;
+        Some(SyncMessage::Delta {
+            seq: self.seq,
+            adds,
+            updates,
+            removes,
+        })
+    }
+}
+
+fn changed_fields(previous: &Fields, current: &Fields) -> Fields {
+    let mut changed = Fields::new();
+    for (key, value) in current {
+        if !VOLATILE_FIELDS.contains(&key.as_str()) && previous.get(key) != Some(value) {
+            changed.insert(key.clone(), value.clone());
+        }
+    }
+    for key in previous.keys() {
+        if !current.contains_key(key) && !VOLATILE_FIELDS.contains(&key.as_str()) {
+            changed.insert(key.clone(), Value::Null);
+        }
+    }
+    changed
+}
+
+fn read_processes(app_state: &AppState) -> Vec<Fields> {
+    app_state
+        .context
+        .live_snapshot()
+        .iter()
+        .filter_map(
+            |e| match serde_json::to_value(ProcessInfo::from_event(e, app_state)) {
+                Ok(Value::Object(fields)) => Some(fields),
+                _ => None,
+            },
+        )
+        .collect()
+}
+
+/// Run the sync protocol on `socket` until the client goes away.
+pub(super) async fn serve(mut socket: WebSocket, app_state: Arc<AppState>, query: SyncQuery) {
+    let interval_ms = query.interval_ms.max(MIN_INTERVAL_MS);
+    let checkpoint_every = (query.checkpoint_secs * 1000).div_ceil(interval_ms);
+    let mut sync = ProcessSync::new(checkpoint_every);
+    let mut tick = tokio::time::interval(Duration::from_millis(interval_ms));
+    loop {
+        tokio::select! {
+            _ = tick.tick() => {
+                let Some(message) = sync.next(read_processes(&app_state)) else {
+                    continue;
+                };
+                let text = match serde_json::to_string(&message) {
+                    Ok(text) => text,
+                    Err(e) => {
+                        debug!("[process-sync] failed to encode message: {e}");
+                        continue;
+                    }
+                };
+                if socket.send(Message::Text(text.into())).await.is_err() {
+                    break;
+                }
+            }
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Text(text))) => {
+                    match serde_json::from_str::<ClientMessage>(text.as_str()) {
+                        Ok(ClientMessage::Resync) => {
+                            sync.resync();
+                            tick.reset_immediately();
+                        }
+                        Err(e) => debug!("[process-sync] ignoring client message: {e}"),
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    debug!("[process-sync] client disconnected after seq {}", sync.seq);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn process(value: Value) -> Fields {
+        match value {
+            Value::Object(fields) => fields,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn snapshot_then_field_deltas_then_checkpoint() {
+        let mut sync = ProcessSync::new(3);
+        let init = vec![
+            process(json!({"pid": 1, "comm": "init", "cpu_pct": 0.5, "age_sec": 100})),
+            process(json!({"pid": 42, "comm": "bash", "state": "S", "age_sec": 7})),
+        ];
+        match sync.next(init.clone()).unwrap() {
+            SyncMessage::Snapshot {
+                seq,
+                reason,
+                processes,
+            } => {
+                assert_eq!((seq, reason), (1, SnapshotReason::Initial));
+                assert_eq!(processes, init);
+            }
+            other => panic!("expected snapshot, got {other:?}"),
+        }
+
+        // Only age moved: nothing to send
+        let aged = vec![
+            process(json!({"pid": 1, "comm": "init", "cpu_pct": 0.5, "age_sec": 102})),
+            process(json!({"pid": 42, "comm": "bash", "state": "S", "age_sec": 9})),
+        ];
+        assert!(sync.next(aged).is_none());
+
+        let changed = vec![
+            process(json!({"pid": 1, "comm": "init", "cpu_pct": 3.0, "age_sec": 104})),
+            process(json!({"pid": 77, "comm": "sleep", "age_sec": 0})),
+        ];
+        match sync.next(changed.clone()).unwrap() {
+            SyncMessage::Delta {
+                seq,
+                adds,
+                updates,
+                removes,
+            } => {
+                assert_eq!(seq, 2);
+                assert_eq!(adds, vec![changed[1].clone()]);
+                assert_eq!(updates, vec![process(json!({"pid": 1, "cpu_pct": 3.0}))]);
+                assert_eq!(removes, vec![42]);
+            }
+            other => panic!("expected delta, got {other:?}"),
+        }
+
+        match sync.next(changed.clone()).unwrap() {
+            SyncMessage::Snapshot { seq, reason, .. } => {
+                assert_eq!((seq, reason), (3, SnapshotReason::Checkpoint))
+            }
+            other => panic!("expected checkpoint, got {other:?}"),
+        }
+
+        let dropped_state = vec![process(json!({"pid": 1, "comm": "init", "age_sec": 108}))];
+        match sync.next(dropped_state).unwrap() {
+            SyncMessage::Delta { seq, updates, .. } => {
+                assert_eq!(seq, 4);
+                assert_eq!(updates, vec![process(json!({"pid": 1, "cpu_pct": null}))]);
+            }
+            other => panic!("expected delta, got {other:?}"),
+        }
+
+        sync.resync();
+        assert!(matches!(
+            sync.next(Vec::new()),
+            Some(SyncMessage::Snapshot {
+                seq: 5,
+                reason: SnapshotReason::Resync,
+                ..
+            })
+        ));
+    }
+}
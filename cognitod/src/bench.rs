@@ -0,0 +1,503 @@
+//! Overhead benchmark against a running cognitod.
+//!
+//! Each [`Level`] drives three workloads at fixed rates for a while: forks
+//! (`/bin/true`), loopback TCP traffic and file writes. They run inside a
+//! scratch cgroup when one can be created, so they are easy to tell apart
+//! from the rest of the box. Meanwhile the daemon's CPU time and RSS are
+//! sampled from `/proc`, and its `/metrics/loss` counters are read before
+//! and after. The result is one [`LevelReport`] per level.
+
+use anyhow::{Context, bail};
+use log::warn;
+use serde::Serialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+const CHUNK: usize = 64 * 1024;
+/// The IO workload's file is truncated whenever it reaches this size.
+const IO_FILE_LIMIT: u64 = 256 * 1024 * 1024;
+/// Time given to the daemon to drain its buffers before loss is read.
+const SETTLE: Duration = Duration::from_secs(2);
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Workload rates for one step of the benchmark.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Level {
+    pub name: String,
+    /// Processes forked (and exec'd) per second.
+    pub fork_rate: u32,
+    /// Loopback TCP throughput in MiB/s.
+    pub net_mbps: f64,
+    /// File write throughput in MiB/s.
+    pub io_mbps: f64,
+}
+
+impl Level {
+    /// `[name:]fork=N,net=MiB/s,io=MiB/s`; omitted workloads are off.
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let (name, spec) = match text.split_once(':') {
+            Some((name, spec)) => (name.to_string(), spec),
+            None => (text.to_string(), text),
+        };
+        let mut level = Level {
+            name,
+            fork_rate: 0,
+            net_mbps: 0.0,
+            io_mbps: 0.0,
+        };
+        for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .with_context(|| format!("expected key=value, got '{part}'"))?;
+            match key {
+                "fork" => level.fork_rate = value.parse().context("fork rate")?,
+                "net" => level.net_mbps = value.parse().context("net MiB/s")?,
+                "io" => level.io_mbps = value.parse().context("io MiB/s")?,
+                other => bail!("unknown workload '{other}' (fork, net, io)"),
+            }
+        }
+        Ok(level)
+    }
+}
+
+/// Idle baseline followed by increasing load.
+pub fn default_levels() -> Vec<Level> {
+    [
+        ("idle", 0, 0.0, 0.0),
+        ("light", 50, 10.0, 10.0),
+        ("medium", 200, 50.0, 50.0),
+        ("heavy", 1000, 200.0, 200.0),
+    ]
+    .into_iter()
+    .map(|(name, fork_rate, net_mbps, io_mbps)| Level {
+        name: name.into(),
+        fork_rate,
+        net_mbps,
+        io_mbps,
+    })
+    .collect()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchReport {
+    pub cognitod_pid: i32,
+    pub api: String,
+    /// Scratch cgroup the workloads ran in, if one could be created.
+    pub cgroup: Option<String>,
+    pub levels: Vec<LevelReport>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LevelReport {
+    pub level: Level,
+    pub duration_secs: f64,
+    /// Rates the workloads actually reached.
+    pub achieved: Achieved,
+    pub cognitod: Overhead,
+    /// `None` when `/metrics/loss` could not be read.
+    pub events: Option<EventLoss>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Achieved {
+    pub forks_per_sec: f64,
+    pub net_mbps: f64,
+    pub io_mbps: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Overhead {
+    /// CPU time over wall time, 100 being one full core.
+    pub cpu_pct: f64,
+    pub rss_kb_start: u64,
+    pub rss_kb_max: u64,
+    pub rss_kb_end: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EventLoss {
+    pub received: u64,
+    pub lost: u64,
+    pub loss_pct: f64,
+}
+
+/// CPU ticks and resident size of a process at one point in time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProcSample {
+    pub cpu_ticks: u64,
+    pub rss_kb: u64,
+}
+
+impl ProcSample {
+    pub fn read(pid: i32) -> anyhow::Result<Self> {
+        let process = procfs::process::Process::new(pid)
+            .with_context(|| format!("cognitod pid {pid} not found"))?;
+        let stat = process.stat()?;
+        let rss_kb = process.status()?.vmrss.unwrap_or(0);
+        Ok(Self {
+            cpu_ticks: stat.utime + stat.stime,
+            rss_kb,
+        })
+    }
+}
+
+impl Overhead {
+    fn between(
+        start: ProcSample,
+        end: ProcSample,
+        rss_kb_max: u64,
+        elapsed: Duration,
+        ticks_per_sec: u64,
+    ) -> Self {
+        let cpu_secs = end.cpu_ticks.saturating_sub(start.cpu_ticks) as f64 / ticks_per_sec as f64;
+        Self {
+            cpu_pct: cpu_secs * 100.0 / elapsed.as_secs_f64().max(1e-9),
+            rss_kb_start: start.rss_kb,
+            rss_kb_max: rss_kb_max.max(start.rss_kb).max(end.rss_kb),
+            rss_kb_end: end.rss_kb,
+        }
+    }
+}
+
+/// Received and lost totals from `/metrics/loss`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LossCounters {
+    pub received: u64,
+    pub lost: u64,
+}
+
+impl LossCounters {
+    pub fn from_report(report: &serde_json::Value) -> Option<Self> {
+        let field = |name: &str| report.get(name).and_then(serde_json::Value::as_u64);
+        Some(Self {
+            received: field("received_total")?,
+            lost: field("gap_lost_total")? + field("kernel_lost_total")?,
+        })
+    }
+
+    fn since(self, before: Self) -> EventLoss {
+        let received = self.received.saturating_sub(before.received);
+        let lost = self.lost.saturating_sub(before.lost);
+        let total = received + lost;
+        EventLoss {
+            received,
+            lost,
+            loss_pct: if total == 0 {
+                0.0
+            } else {
+                lost as f64 * 100.0 / total as f64
+            },
+        }
+    }
+}
+
+/// First process whose `comm` is `name`.
+pub fn find_pid(name: &str) -> anyhow::Result<i32> {
+    for process in procfs::process::all_processes()?.flatten() {
+        if process.stat().is_ok_and(|stat| stat.comm == name) {
+            return Ok(process.pid);
+        }
+    }
+    bail!("no running '{name}' process; pass --pid")
+}
+
+/// A cgroup v2 group this process moves into for the run and leaves on drop.
+pub struct ScratchCgroup {
+    path: PathBuf,
+    original: PathBuf,
+}
+
+impl ScratchCgroup {
+    pub fn create() -> anyhow::Result<Self> {
+        let own = fs::read_to_string("/proc/self/cgroup")?;
+        let original = own
+            .lines()
+            .find_map(|line| line.strip_prefix("0::"))
+            .context("not on a cgroup v2 hierarchy")?;
+        let original = PathBuf::from(CGROUP_ROOT).join(original.trim_start_matches('/'));
+        let path = PathBuf::from(CGROUP_ROOT).join(format!("linnix-bench-{}", std::process::id()));
+        fs::create_dir(&path).with_context(|| format!("creating {}", path.display()))?;
+        let cgroup = Self { path, original };
+        fs::write(
+            cgroup.path.join("cgroup.procs"),
+            std::process::id().to_string(),
+        )
+        .context("joining the scratch cgroup")?;
+        Ok(cgroup)
+    }
+
+    pub fn path(&self) -> String {
+        self.path.display().to_string()
+    }
+}
+
+impl Drop for ScratchCgroup {
+    fn drop(&mut self) {
+        let _ = fs::write(
+            self.original.join("cgroup.procs"),
+            std::process::id().to_string(),
+        );
+        if let Err(e) = fs::remove_dir(&self.path) {
+            warn!("[bench] failed to remove {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Run `step` until `stop`, keeping the units it reports at `rate` per
+/// second. Falls behind rather than bursting to catch up.
+fn paced(
+    rate: f64,
+    stop: &AtomicBool,
+    mut step: impl FnMut() -> std::io::Result<u64>,
+) -> std::io::Result<u64> {
+    let started = Instant::now();
+    let mut done = 0u64;
+    while !stop.load(Ordering::Relaxed) {
+        let due = started + Duration::from_secs_f64(done as f64 / rate);
+        let now = Instant::now();
+        if due > now {
+            thread::sleep((due - now).min(Duration::from_millis(50)));
+            continue;
+        }
+        done += step()?;
+    }
+    Ok(done)
+}
+
+fn spawn_forks(rate: u32, stop: Arc<AtomicBool>) -> JoinHandle<std::io::Result<u64>> {
+    thread::spawn(move || {
+        paced(rate as f64, &stop, || {
+            std::process::Command::new("/bin/true").status()?;
+            Ok(1)
+        })
+    })
+}
+
+fn spawn_net(
+    mbps: f64,
+    stop: Arc<AtomicBool>,
+) -> std::io::Result<JoinHandle<std::io::Result<u64>>> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let mut writer = TcpStream::connect(listener.local_addr()?)?;
+    let (mut reader, _) = listener.accept()?;
+    thread::spawn(move || {
+        let mut buf = vec![0u8; CHUNK];
+        while matches!(reader.read(&mut buf), Ok(n) if n > 0) {}
+    });
+    Ok(thread::spawn(move || {
+        let chunk = vec![0xa5u8; CHUNK];
+        paced(mbps * 1024.0 * 1024.0, &stop, || {
+            writer.write_all(&chunk)?;
+            Ok(CHUNK as u64)
+        })
+    }))
+}
+
+fn spawn_io(mbps: f64, stop: Arc<AtomicBool>) -> std::io::Result<JoinHandle<std::io::Result<u64>>> {
+    let path = std::env::temp_dir().join(format!("linnix-bench-{}.dat", std::process::id()));
+    let mut file: File = OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .read(true)
+        .write(true)
+        .open(&path)?;
+    Ok(thread::spawn(move || {
+        let chunk = vec![0x5au8; CHUNK];
+        let mut written = 0u64;
+        let result = paced(mbps * 1024.0 * 1024.0, &stop, || {
+            file.write_all(&chunk)?;
+            written += CHUNK as u64;
+            if written.is_multiple_of(16 * CHUNK as u64) {
+                file.sync_data()?;
+            }
+            if written >= IO_FILE_LIMIT {
+                file.set_len(0)?;
+                file.seek(SeekFrom::Start(0))?;
+                written = 0;
+            }
+            Ok(CHUNK as u64)
+        });
+        let _ = fs::remove_file(&path);
+        result
+    }))
+}
+
+fn joined(handle: Option<JoinHandle<std::io::Result<u64>>>, what: &str) -> u64 {
+    match handle.map(|h| h.join()) {
+        None => 0,
+        Some(Ok(Ok(units))) => units,
+        Some(Ok(Err(e))) => {
+            warn!("[bench] {what} workload failed: {e}");
+            0
+        }
+        Some(Err(_)) => {
+            warn!("[bench] {what} workload panicked");
+            0
+        }
+    }
+}
+
+/// Where the daemon under test is and how to reach its API.
+pub struct Target {
+    pub pid: i32,
+    pub api: String,
+    client: reqwest::Client,
+}
+
+impl Target {
+    pub fn new(pid: i32, api: impl Into<String>) -> Self {
+        Self {
+            pid,
+            api: api.into().trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn loss(&self) -> Option<LossCounters> {
+        let url = format!("{}/metrics/loss", self.api);
+        let report: serde_json::Value = match self.client.get(&url).send().await {
+            Ok(resp) => resp.json().await.ok()?,
+            Err(e) => {
+                warn!("[bench] {url} unreachable: {e}");
+                return None;
+            }
+        };
+        LossCounters::from_report(&report)
+    }
+
+    /// Drive `level` for `duration` and measure the daemon meanwhile.
+    pub async fn run_level(
+        &self,
+        level: &Level,
+        duration: Duration,
+    ) -> anyhow::Result<LevelReport> {
+        let loss_before = self.loss().await;
+        let start = ProcSample::read(self.pid)?;
+        let started = Instant::now();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let forks = (level.fork_rate > 0).then(|| spawn_forks(level.fork_rate, Arc::clone(&stop)));
+        let net = if level.net_mbps > 0.0 {
+            Some(spawn_net(level.net_mbps, Arc::clone(&stop)).context("starting net workload")?)
+        } else {
+            None
+        };
+        let io = if level.io_mbps > 0.0 {
+            Some(spawn_io(level.io_mbps, Arc::clone(&stop)).context("starting io workload")?)
+        } else {
+            None
+        };
+
+        let mut rss_kb_max = start.rss_kb;
+        while started.elapsed() < duration {
+            tokio::time::sleep(SAMPLE_INTERVAL).await;
+            if let Ok(sample) = ProcSample::read(self.pid) {
+                rss_kb_max = rss_kb_max.max(sample.rss_kb);
+            }
+        }
+        let end = ProcSample::read(self.pid)?;
+        let elapsed = started.elapsed();
+        stop.store(true, Ordering::Relaxed);
+
+        let secs = elapsed.as_secs_f64();
+        let mib = 1024.0 * 1024.0;
+        let achieved = Achieved {
+            forks_per_sec: joined(forks, "fork") as f64 / secs,
+            net_mbps: joined(net, "net") as f64 / mib / secs,
+            io_mbps: joined(io, "io") as f64 / mib / secs,
+        };
+
+        tokio::time::sleep(SETTLE).await;
+        let events = match (loss_before, self.loss().await) {
+            (Some(before), Some(after)) => Some(after.since(before)),
+            _ => None,
+        };
+        Ok(LevelReport {
+            level: level.clone(),
+            duration_secs: secs,
+            achieved,
+            cognitod: Overhead::between(
+                start,
+                end,
+                rss_kb_max,
+                elapsed,
+                procfs::ticks_per_second(),
+            ),
+            events,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_levels_and_computes_overhead() {
+        let level = Level::parse("burst:fork=500, io=20").unwrap();
+        assert_eq!(
+            level,
+            Level {
+                name: "burst".into(),
+                fork_rate: 500,
+                net_mbps: 0.0,
+                io_mbps: 20.0,
+            }
+        );
+        assert_eq!(Level::parse("net=5").unwrap().name, "net=5");
+        assert!(Level::parse("disk=5").is_err());
+        assert!(Level::parse("fork").is_err());
+
+        let start = ProcSample {
+            cpu_ticks: 1000,
+            rss_kb: 40_000,
+        };
+        let end = ProcSample {
+            cpu_ticks: 1050,
+            rss_kb: 42_000,
+        };
+        let overhead = Overhead::between(start, end, 45_000, Duration::from_secs(10), 100);
+        assert_eq!(overhead.cpu_pct, 5.0);
+        assert_eq!(overhead.rss_kb_max, 45_000);
+
+        let before = LossCounters::from_report(
+            &json!({"received_total": 100, "gap_lost_total": 0, "kernel_lost_total": 1}),
+        )
+        .unwrap();
+        let after = LossCounters::from_report(
+            &json!({"received_total": 1090, "gap_lost_total": 6, "kernel_lost_total": 5}),
+        )
+        .unwrap();
+        assert_eq!(
+            after.since(before),
+            EventLoss {
+                received: 990,
+                lost: 10,
+                loss_pct: 1.0,
+            }
+        );
+        assert_eq!(LossCounters::from_report(&json!({})), None);
+    }
+
+    #[test]
+    fn paced_steps_stop_when_asked() {
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&stop);
+        let handle = thread::spawn(move || paced(1000.0, &flag, || Ok(1)).unwrap());
+        thread::sleep(Duration::from_millis(100));
+        stop.store(true, Ordering::Relaxed);
+        let done = handle.join().unwrap();
+        assert!(
+            (20..=200).contains(&done),
+            "{done} steps in 100ms at 1000/s"
+        );
+    }
+}
@@ -0,0 +1,100 @@
+//! Overhead benchmark for a running cognitod
+//!
+//! Drives fork, network and file IO workloads at increasing levels and
+//! prints a JSON report of the daemon's CPU, RSS and event loss at each
+//! one. Run it as root on the box being sized, next to the daemon.
+
+use anyhow::Result;
+use clap::Parser;
+use log::{info, warn};
+use std::time::Duration;
+
+use cognitod::bench::{self, BenchReport, Level, ScratchCgroup, Target};
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "linnix-bench",
+    about = "Measure cognitod's overhead under controlled load"
+)]
+struct Args {
+    /// cognitod pid; found by name when omitted
+    #[arg(long)]
+    pid: Option<i32>,
+
+    /// cognitod API base URL, for event loss
+    #[arg(long, default_value = "http://127.0.0.1:3000")]
+    api: String,
+
+    /// Seconds of load per level
+    #[arg(long, default_value = "10")]
+    duration: u64,
+
+    /// Levels as `[name:]fork=N,net=MiB/s,io=MiB/s`, in order; defaults to
+    /// idle, light, medium and heavy
+    #[arg(long = "level")]
+    levels: Vec<String>,
+
+    /// Run the workloads in the current cgroup instead of a scratch one
+    #[arg(long)]
+    no_cgroup: bool,
+
+    /// Write the report here instead of stdout
+    #[arg(long)]
+    output: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let args = Args::parse();
+    let levels = if args.levels.is_empty() {
+        bench::default_levels()
+    } else {
+        args.levels
+            .iter()
+            .map(|l| Level::parse(l))
+            .collect::<Result<_>>()?
+    };
+    let pid = match args.pid {
+        Some(pid) => pid,
+        None => bench::find_pid("cognitod")?,
+    };
+
+    let cgroup = if args.no_cgroup {
+        None
+    } else {
+        match ScratchCgroup::create() {
+            Ok(cgroup) => Some(cgroup),
+            Err(e) => {
+                warn!("[bench] running without a scratch cgroup: {e:#}");
+                None
+            }
+        }
+    };
+
+    let target = Target::new(pid, args.api);
+    let duration = Duration::from_secs(args.duration.max(1));
+    let mut report = BenchReport {
+        cognitod_pid: pid,
+        api: target.api.clone(),
+        cgroup: cgroup.as_ref().map(ScratchCgroup::path),
+        levels: Vec::new(),
+    };
+    for level in &levels {
+        info!("[bench] level '{}' for {}s", level.name, duration.as_secs());
+        let result = target.run_level(level, duration).await?;
+        info!(
+            "[bench] '{}': cognitod {:.1}% cpu, {} kB max rss",
+            level.name, result.cognitod.cpu_pct, result.cognitod.rss_kb_max
+        );
+        report.levels.push(result);
+    }
+    drop(cgroup);
+
+    let json = serde_json::to_string_pretty(&report)?;
+    match &args.output {
+        Some(path) => std::fs::write(path, json + "\n")?,
+        None => println!("{json}"),
+    }
+    Ok(())
+}
@@ -5,6 +5,20 @@
 //! 2. Consuming events from the sequencer ring
 //! 3. Validating strict ordering
 //! 4. Printing performance statistics
+//!
+//! With `--numa` the ring is partitioned per NUMA node, one pinned consumer
+//! per node, and the report shows where each ring's pages live. Comparing
+//! `remote_page_share` with a run without `--numa` shows the reduction in
+//! cross-node ring writes.
+//!
+//! With `--cursor-path` the consumer position is checkpointed to that file
+//! and reconciled with the kernel's ticket counter on the next run; the
+//! decision is logged and printed with the results. Resuming reads live
+//! events from the kernel's head and the backlog behind them, at most
+//! `--catchup-per-poll` tickets per poll; with `--summarize-backlog-after`
+//! older backlogged events are only counted. Adding `--pin-path` pins the
+//! ring and ticket counters to that bpffs directory so the next run finds
+//! them again; without it every run starts from fresh maps and resets.
 
 use anyhow::{Context, Result};
 use aya::maps::{Array, Map};
@@ -13,11 +27,19 @@ use aya::{Btf, EbpfLoader, Pod};
 use clap::Parser;
 use log::{error, info, warn};
 use std::os::fd::AsFd;
+use std::path::Path;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use cognitod::bpf_config::derive_telemetry_config;
-use cognitod::runtime::sequencer::SequencerConsumer;
-use linnix_ai_ebpf_common::TelemetryConfig;
+use cognitod::metrics::Metrics;
+use cognitod::metrics::cursor::Decision;
+use cognitod::metrics::latency::monotonic_ns;
+use cognitod::runtime::backlog::{Backlog, BacklogConfig};
+use cognitod::runtime::cursor::{self, Checkpointer, CursorStore};
+use cognitod::runtime::numa::{self, Partition, PartitionedSequencer};
+use cognitod::runtime::sequencer::{SequencerConsumer, SequencerStats};
+use linnix_ai_ebpf_common::{SEQUENCER_RING_SIZE, TelemetryConfig};
 
 /// Wrapper to satisfy aya's Pod requirement for set_global
 #[repr(transparent)]
@@ -44,6 +66,34 @@ struct Args {
     /// Batch size for polling
     #[arg(short = 'B', long, default_value = "256")]
     batch_size: usize,
+
+    /// Partition the ring per NUMA node with one pinned consumer per node
+    #[arg(long)]
+    numa: bool,
+
+    /// Print the NUMA placement report as JSON
+    #[arg(long)]
+    json: bool,
+
+    /// Checkpoint the consumer cursor here and resume from it (shared ring)
+    #[arg(long)]
+    cursor_path: Option<String>,
+
+    /// Interval between cursor checkpoints
+    #[arg(long, default_value = "1000")]
+    checkpoint_ms: u64,
+
+    /// bpffs directory to pin the ring and ticket counters in (with --cursor-path)
+    #[arg(long)]
+    pin_path: Option<String>,
+
+    /// Most backlogged tickets read between two live batches after resuming
+    #[arg(long, default_value = "4096")]
+    catchup_per_poll: usize,
+
+    /// Only count backlogged events older than this many seconds
+    #[arg(long)]
+    summarize_backlog_after: Option<u64>,
 }
 
 fn main() -> Result<()> {
@@ -75,10 +125,53 @@ fn main() -> Result<()> {
         telemetry_result.config.task_tgid_offset, telemetry_result.config.task_comm_offset
     );
 
+    let nodes =
+        numa::online_nodes(Path::new(numa::NODE_ROOT)).context("Failed to read NUMA topology")?;
+    let partitions = if args.numa {
+        numa::partitions_for(&nodes)
+    } else {
+        1
+    };
+    info!(
+        "NUMA nodes: {}, sequencer partitions: {}",
+        nodes.len(),
+        partitions
+    );
+
     // Load eBPF with telemetry config injected into global variable
     let telemetry_pod = TelemetryConfigPod(telemetry_result.config);
     let mut loader = EbpfLoader::new();
     loader.set_global("TELEMETRY_CONFIG", &telemetry_pod, true);
+    // The per-node rings are declared with one slot; grow the ones we use
+    for ring in &numa::RING_MAPS[1..partitions as usize] {
+        loader.set_max_entries(ring, SEQUENCER_RING_SIZE);
+    }
+    // Pins are only worth keeping while a cursor refers to them
+    let pin_dir = match (&args.pin_path, &args.cursor_path) {
+        (Some(dir), Some(_)) if !args.numa => Some(Path::new(dir)),
+        _ => None,
+    };
+    let mut maps_reused = false;
+    if let Some(dir) = pin_dir {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        if !cursor::on_bpffs(dir) {
+            anyhow::bail!("{} is not on bpffs", dir.display());
+        }
+        maps_reused = cursor::pinned(dir);
+        if !maps_reused {
+            cursor::unpin(dir)
+                .with_context(|| format!("Failed to remove stale pins in {}", dir.display()))?;
+        }
+        info!(
+            "Sequencer maps pinned in {} (reused: {})",
+            dir.display(),
+            maps_reused
+        );
+        for name in cursor::PINNED_MAPS {
+            loader.map_pin_path(name, dir.join(name));
+        }
+    }
     let mut ebpf = loader
         .load(&bpf_data)
         .context("Failed to load eBPF program")?;
@@ -179,30 +272,60 @@ fn main() -> Result<()> {
     // Otherwise we race: eBPF writes -> memset overwrites -> corruption.
     info!("Creating sequencer consumer (mmap mode)...");
 
-    // Take ownership of the ring map - we need to keep it alive for the mmap
-    let ring_map = ebpf
-        .take_map("SEQUENCER_RING")
-        .context("Failed to find SEQUENCER_RING map")?;
-
-    // Extract MapData from the Map enum
-    let ring_map_data = match ring_map {
-        Map::Array(data) => data,
-        other => anyhow::bail!("SEQUENCER_RING is not an Array map, got {:?}", other),
+    // Reconcile the saved cursor while the ticket counter cannot move yet
+    let metrics = Arc::new(Metrics::new());
+    let restored = match &args.cursor_path {
+        Some(_) if args.numa => {
+            warn!("--cursor-path covers the shared ring only; ignored with --numa");
+            None
+        }
+        Some(path) => {
+            let head = cursor::kernel_head(&ebpf)?;
+            let store = CursorStore::new(path);
+            let reconciliation =
+                cursor::restore(&store, head, maps_reused, SEQUENCER_RING_SIZE as u64);
+            Some((store, reconciliation))
+        }
+        None => None,
     };
-
-    // Get the fd from MapData and create consumer with mmap
-    // Note: ring_map_data must stay alive for the mmap to remain valid
-    let fd = ring_map_data.fd().as_fd();
-    info!("SEQUENCER_RING map fd: {:?}", fd);
-
-    // Create consumer - this will mmap AND ZERO the ring buffer
-    let consumer = SequencerConsumer::from_fd(fd)
-        .context("Failed to create SequencerConsumer. Ensure BPF_F_MMAPABLE flag is set.")?;
-
-    // NOTE: SEQUENCER_INDEX is now a .bss global variable (GLOBAL_SEQUENCER),
-    // not a BPF map. It automatically initializes to 0 when the eBPF program loads.
-    // No explicit reset is needed!
-    info!("Sequencer index auto-initialized to 0 (via .bss global)");
+    // Only a ring that still holds the saved tickets is kept as it is
+    let keep_ring = restored
+        .as_ref()
+        .is_some_and(|(_, r)| matches!(r.decision, Decision::Resume | Decision::Overrun));
+
+    // Take ownership of the ring maps - we need to keep them alive for the mmap
+    let mut ring_maps = Vec::new();
+    let mut consumers = Vec::new();
+    for ring in &numa::RING_MAPS[..partitions as usize] {
+        let ring_map = ebpf
+            .take_map(ring)
+            .with_context(|| format!("Failed to find {ring} map"))?;
+
+        // Extract MapData from the Map enum
+        let ring_map_data = match ring_map {
+            Map::Array(data) => data,
+            other => anyhow::bail!("{} is not an Array map, got {:?}", ring, other),
+        };
+
+        // Get the fd from MapData and create consumer with mmap
+        // Note: ring_map_data must stay alive for the mmap to remain valid
+        let fd = ring_map_data.fd().as_fd();
+        info!("{} map fd: {:?}", ring, fd);
+
+        // Create consumer - this will mmap AND ZERO the ring buffer,
+        // unless a restored cursor resumes in it
+        let consumer = if keep_ring {
+            SequencerConsumer::attach_fd(fd)
+        } else {
+            SequencerConsumer::from_fd(fd)
+        };
+        consumers.push(
+            consumer.context(
+                "Failed to create SequencerConsumer. Ensure BPF_F_MMAPABLE flag is set.",
+            )?,
+        );
+        ring_maps.push(ring_map_data);
+    }
 
     // Enable sequencer mode - NOW eBPF will start writing to the clean ring
     info!("Enabling sequencer mode...");
@@ -213,10 +336,11 @@ fn main() -> Result<()> {
         )
         .context("Failed to create Array from SEQUENCER_ENABLED map")?;
 
+        // 1 is the shared ring, N > 1 partitions it across N node rings
         enabled_map
-            .set(0, 1, 0)
-            .context("Failed to set SEQUENCER_ENABLED to 1")?;
-        info!("Sequencer ENABLED in eBPF");
+            .set(0, partitions, 0)
+            .context("Failed to enable the sequencer")?;
+        info!("Sequencer ENABLED in eBPF ({} partitions)", partitions);
     }
 
     // Run the consumer loop
@@ -231,14 +355,82 @@ fn main() -> Result<()> {
 
     let start = Instant::now();
     let deadline = start + Duration::from_secs(args.duration);
-    let mut consumer = consumer;
+    let mut shared = None;
+    let mut partitioned = None;
+    if args.numa {
+        let partitions = consumers
+            .into_iter()
+            .enumerate()
+            .map(|(index, consumer)| Partition {
+                consumer,
+                node: nodes.get(index).cloned(),
+            })
+            .collect();
+        partitioned = Some(PartitionedSequencer::start(partitions, args.batch_size));
+    } else {
+        shared = consumers.pop();
+    }
+    let mut backlog = None;
+    let checkpoint = match (restored, &mut shared) {
+        (Some((store, reconciliation)), Some(consumer)) => {
+            let config = BacklogConfig {
+                catchup_per_poll: args.catchup_per_poll,
+                summarize_after: args.summarize_backlog_after.map(Duration::from_secs),
+            };
+            backlog = keep_ring
+                .then(|| {
+                    Backlog::new(
+                        reconciliation.cursor,
+                        reconciliation.kernel_head,
+                        config,
+                        Arc::clone(&metrics),
+                    )
+                })
+                .flatten();
+            // With a backlog, live reading starts at the head
+            consumer.set_cursor(match &backlog {
+                Some(_) => reconciliation.kernel_head,
+                None => reconciliation.cursor,
+            });
+            let checkpointer = Arc::new(Checkpointer::new(
+                store,
+                reconciliation,
+                Arc::clone(&metrics),
+            ));
+            let thread = checkpointer.spawn(Duration::from_millis(args.checkpoint_ms.max(1)));
+            Some((checkpointer, thread))
+        }
+        _ => None,
+    };
     let mut _total_events: u64 = 0;
     let mut poll_cycles: u64 = 0;
     let mut max_batch: usize = 0;
     let mut empty_polls: u64 = 0;
 
     while Instant::now() < deadline {
-        let events = consumer.poll_batch(args.batch_size);
+        let events = match (&mut shared, &mut partitioned) {
+            (Some(consumer), _) => {
+                let mut events = consumer.poll_batch(args.batch_size);
+                let mut position = consumer.cursor();
+                if let Some(backlog) = &mut backlog {
+                    let oldest = position.saturating_sub(SEQUENCER_RING_SIZE as u64);
+                    let now = monotonic_ns().unwrap_or(0);
+                    backlog.poll(
+                        oldest,
+                        now,
+                        |ticket| consumer.read_behind(ticket),
+                        |_, event| events.push(event),
+                    );
+                    position = backlog.checkpoint(position);
+                }
+                if let Some((checkpointer, _)) = &checkpoint {
+                    checkpointer.set_cursor(position);
+                }
+                events
+            }
+            (None, Some(merged)) => merged.poll(args.batch_size),
+            (None, None) => unreachable!("one consumer mode is always set"),
+        };
         poll_cycles += 1;
 
         if events.is_empty() {
@@ -255,7 +447,33 @@ fn main() -> Result<()> {
     }
 
     let elapsed = start.elapsed();
-    let stats = consumer.stats();
+    if let Some((checkpointer, thread)) = checkpoint {
+        checkpointer.finish(thread);
+    }
+    let (stats, report) = match (&shared, &partitioned) {
+        (Some(consumer), _) => {
+            let report = consumer.page_placement().ok().map(|pages| {
+                serde_json::json!({
+                    "nodes": nodes.len(),
+                    "pages": pages,
+                    "remote_page_share": numa::shared_ring_remote_share(&pages, &nodes),
+                })
+            });
+            (consumer.stats().clone(), report)
+        }
+        (None, Some(merged)) => {
+            let report = merged.report();
+            let mut stats = SequencerStats::default();
+            for partition in &report.partitions {
+                stats.events_processed += partition.events;
+                stats.events_reaped += partition.events_reaped;
+                stats.ordering_violations += partition.ordering_violations;
+            }
+            (stats, serde_json::to_value(&report).ok())
+        }
+        (None, None) => unreachable!("one consumer mode is always set"),
+    };
+    let stats = &stats;
 
     // Print results
     println!();
@@ -301,6 +519,30 @@ fn main() -> Result<()> {
     );
     println!("╚══════════════════════════════════════════════════════════════╝");
 
+    for (cpu, seq) in &stats.per_cpu {
+        println!(
+            "cpu {:>3}: events={} reaped={} gaps={} inversions={}",
+            cpu, seq.events, seq.reaped, seq.gaps, seq.inversions
+        );
+    }
+
+    if let Some(status) = metrics.cursor().snapshot() {
+        println!("cursor: {}", serde_json::to_string(&status)?);
+    }
+    if let Some(status) = metrics.backlog().snapshot() {
+        println!("backlog: {}", serde_json::to_string(&status)?);
+    }
+
+    match report {
+        Some(report) if args.json => println!("{}", serde_json::to_string_pretty(&report)?),
+        Some(report) => println!(
+            "NUMA: remote_page_share={} ({} nodes)",
+            report["remote_page_share"],
+            nodes.len()
+        ),
+        None => warn!("Ring page placement unavailable (move_pages failed)"),
+    }
+
     if stats.ordering_violations > 0 {
         error!(
             "❌ ORDERING VIOLATIONS DETECTED: {}",
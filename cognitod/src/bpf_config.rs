@@ -1,14 +1,21 @@
 use anyhow::{Context, Result, anyhow};
 use btf::btf::{Array, Btf, Struct, Type};
 use linnix_ai_ebpf_common::{TelemetryConfig, rss_source};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
 use std::convert::TryFrom;
 use std::env;
+use std::hash::{BuildHasher, Hasher};
+use std::io::Write;
+use std::path::Path;
 use sysinfo::System;
 
 const KERNEL_BTF_PATH: &str = "/sys/kernel/btf/vmlinux";
 const ENV_KERNEL_BTF_PATH: &str = "LINNIX_KERNEL_BTF";
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum CoreRssMode {
     SignalStruct,
     MmStruct,
@@ -32,6 +39,19 @@ pub fn derive_telemetry_config() -> Result<TelemetryConfigResult> {
     let (pid_bits, _) = member_offset(task_struct, "pid")?;
     let (comm_bits, _) = member_offset(task_struct, "comm")?;
     let (se_bits, se_type) = member_offset(task_struct, "se")?;
+    // Optional: exit events simply carry no status without it
+    let exit_code_bits = member_offset(task_struct, "exit_code")
+        .ok()
+        .map(|(bits, _)| bits);
+    // Optional: fd pressure is then reported on exhaustion only
+    let nofile_rlim = nofile_rlim_offset(&btf, task_struct).ok();
+    // Optional: exec paths then come from /proc for the BTF exec handler
+    let bprm_filename_bits = expect_named_struct(&btf, "linux_binprm")
+        .and_then(|bprm| member_offset(bprm, "filename"))
+        .ok()
+        .map(|(bits, _)| bits);
+    // Optional: file I/O is then not reported
+    let file_offsets = file_inode_offsets(&btf).ok();
 
     let signal_candidate = rss_layout_for_field(&btf, task_struct, "signal")?;
     let mm_candidate = rss_layout_for_field(&btf, task_struct, "mm")?;
@@ -72,17 +92,6 @@ pub fn derive_telemetry_config() -> Result<TelemetryConfigResult> {
     let se_struct = resolve_struct(&btf, se_type)?;
     let (sum_exec_bits, _) = member_offset(se_struct, "sum_exec_runtime")?;
 
-    let page_size_raw = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
-    let page_size = if page_size_raw > 0 {
-        page_size_raw as u32
-    } else {
-        0
-    };
-
-    let mut sys = System::new_all();
-    sys.refresh_memory();
-    let total_memory_bytes = sys.total_memory().saturating_mul(1024);
-
     let mut telemetry = TelemetryConfig::zeroed();
     telemetry.task_real_parent_offset = to_bytes(real_parent_bits)?;
     telemetry.task_tgid_offset = to_bytes(tgid_bits)?;
@@ -90,16 +99,32 @@ pub fn derive_telemetry_config() -> Result<TelemetryConfigResult> {
     telemetry.task_comm_offset = to_bytes(comm_bits)?;
     telemetry.task_se_offset = to_bytes(se_bits)?;
     telemetry.se_sum_exec_runtime_offset = to_bytes(sum_exec_bits)?;
+    if let Some(bits) = exit_code_bits {
+        telemetry.task_exit_code_offset = to_bytes(bits)?;
+    }
+    if let Some(bits) = bprm_filename_bits {
+        telemetry.bprm_filename_offset = to_bytes(bits)?;
+    }
+    if let Some([file_inode, inode_mode, inode_ino, inode_sb, sb_dev]) = file_offsets {
+        telemetry.file_inode_offset = file_inode;
+        telemetry.inode_mode_offset = inode_mode;
+        telemetry.inode_ino_offset = inode_ino;
+        telemetry.inode_sb_offset = inode_sb;
+        telemetry.sb_dev_offset = sb_dev;
+    }
     telemetry.rss_count_offset = selected_layout.count_offset;
     telemetry.rss_item_size = selected_layout.item_size;
     telemetry.rss_file_index = file_index;
     telemetry.rss_anon_index = anon_index;
-    telemetry.page_size = page_size;
-    telemetry.total_memory_bytes = total_memory_bytes;
+    set_host_fields(&mut telemetry);
 
     if let Some(bits) = signal_bits {
         telemetry.task_signal_offset = to_bytes(bits)?;
     }
+    if let Some((signal_offset, rlim_offset)) = nofile_rlim {
+        telemetry.task_signal_offset = signal_offset;
+        telemetry.signal_nofile_rlim_offset = rlim_offset;
+    }
     if let Some(layout) = signal_layout {
         telemetry.signal_rss_stat_offset = layout.field_offset;
     }
@@ -123,6 +148,248 @@ pub fn derive_telemetry_config() -> Result<TelemetryConfigResult> {
     })
 }
 
+/// Page size and memory of this host, which the offsets do not depend on.
+fn set_host_fields(telemetry: &mut TelemetryConfig) {
+    let page_size_raw = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    telemetry.page_size = if page_size_raw > 0 {
+        page_size_raw as u32
+    } else {
+        0
+    };
+
+    let mut sys = System::new_all();
+    sys.refresh_memory();
+    telemetry.total_memory_bytes = sys.total_memory().saturating_mul(1024);
+}
+
+/// Key for the socket ids of net events, new for each run so ids cannot be
+/// turned back into kernel addresses.
+pub fn socket_id_key() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+/// Where [`derive_telemetry_config_cached`] got the offsets from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BtfCache {
+    Hit,
+    Miss,
+    Disabled,
+}
+
+impl BtfCache {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Hit => "hit",
+            Self::Miss => "miss",
+            Self::Disabled => "disabled",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedOffsets {
+    kernel: String,
+    mode: CoreRssMode,
+    signal_supported: bool,
+    mm_supported: bool,
+    config: TelemetryConfig,
+}
+
+/// [`derive_telemetry_config`] with the result kept in `cache` for the
+/// running kernel build, so BTF is only parsed the first time a kernel is
+/// seen. `None` disables the cache.
+pub fn derive_telemetry_config_cached(
+    cache: Option<&Path>,
+) -> Result<(TelemetryConfigResult, BtfCache)> {
+    let Some(cache) = cache else {
+        return Ok((derive_telemetry_config()?, BtfCache::Disabled));
+    };
+    let Some(kernel) = kernel_key() else {
+        debug!("[bpf] no kernel build id; BTF offset cache unused");
+        return Ok((derive_telemetry_config()?, BtfCache::Disabled));
+    };
+    if let Some(mut cached) = std::fs::read(cache)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<CachedOffsets>(&bytes).ok())
+        .filter(|cached| cached.kernel == kernel)
+    {
+        set_host_fields(&mut cached.config);
+        let result = TelemetryConfigResult {
+            config: cached.config,
+            mode: cached.mode,
+            signal_supported: cached.signal_supported,
+            mm_supported: cached.mm_supported,
+        };
+        return Ok((result, BtfCache::Hit));
+    }
+    let result = derive_telemetry_config()?;
+    let cached = CachedOffsets {
+        kernel,
+        mode: result.mode,
+        signal_supported: result.signal_supported,
+        mm_supported: result.mm_supported,
+        config: result.config,
+    };
+    if let Err(e) = write_cache(cache, &cached) {
+        warn!(
+            "[bpf] cannot write BTF offset cache {}: {e}",
+            cache.display()
+        );
+    }
+    Ok((result, BtfCache::Miss))
+}
+
+fn write_cache(path: &Path, cached: &CachedOffsets) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("tmp");
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(&serde_json::to_vec(cached)?)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Identity of the running kernel build: its GNU build id, and the BTF file
+/// when `LINNIX_KERNEL_BTF` overrides it.
+fn kernel_key() -> Option<String> {
+    let notes = std::fs::read("/sys/kernel/notes").ok()?;
+    let build_id = parse_build_id(&notes)?;
+    Some(match env::var(ENV_KERNEL_BTF_PATH) {
+        Ok(path) => format!("{build_id} {path}"),
+        Err(_) => build_id,
+    })
+}
+
+/// Hex `NT_GNU_BUILD_ID` from a sequence of ELF notes.
+fn parse_build_id(notes: &[u8]) -> Option<String> {
+    const NT_GNU_BUILD_ID: u32 = 3;
+    let word = |at: usize| -> Option<u32> {
+        Some(u32::from_ne_bytes(notes.get(at..at + 4)?.try_into().ok()?))
+    };
+    let align = |len: usize| (len + 3) & !3;
+    let mut at = 0;
+    while at + 12 <= notes.len() {
+        let name_len = word(at)? as usize;
+        let desc_len = word(at + 4)? as usize;
+        let kind = word(at + 8)?;
+        let name = notes.get(at + 12..at + 12 + name_len)?;
+        let desc_at = at + 12 + align(name_len);
+        let desc = notes.get(desc_at..desc_at + desc_len)?;
+        if kind == NT_GNU_BUILD_ID && name == b"GNU\0" {
+            return Some(desc.iter().map(|b| format!("{b:02x}")).collect());
+        }
+        at = desc_at + align(desc_len);
+    }
+    None
+}
+
+/// Offsets of `signal` in `task_struct` and of `rlim[RLIMIT_NOFILE].rlim_cur`
+/// in `signal_struct`.
+fn nofile_rlim_offset(btf: &Btf, task_struct: &Struct) -> Result<(u32, u32)> {
+    let (signal_bits, signal_type) = member_offset(task_struct, "signal")?;
+    let signal_struct = resolve_struct_deep(btf, signal_type)?;
+    let (rlim_bits, rlim_type) = member_offset(signal_struct, "rlim")?;
+    let array = expect_array(btf, rlim_type)?;
+    let element_bits = btf
+        .get_type_by_id(array.elem_type_id)
+        .context("unable to resolve rlimit element type")?
+        .bits;
+    Ok((
+        to_bytes(signal_bits)?,
+        nofile_rlim_bytes(rlim_bits, element_bits)?,
+    ))
+}
+
+/// Offsets of `f_inode` in `struct file`, `i_mode`, `i_ino` and `i_sb` in
+/// `struct inode`, and `s_dev` in `struct super_block`.
+fn file_inode_offsets(btf: &Btf) -> Result<[u32; 5]> {
+    let file = expect_named_struct(btf, "file")?;
+    let inode = expect_named_struct(btf, "inode")?;
+    let super_block = expect_named_struct(btf, "super_block")?;
+    Ok([
+        to_bytes(member_offset(file, "f_inode")?.0)?,
+        to_bytes(member_offset(inode, "i_mode")?.0)?,
+        to_bytes(member_offset(inode, "i_ino")?.0)?,
+        to_bytes(member_offset(inode, "i_sb")?.0)?,
+        to_bytes(member_offset(super_block, "s_dev")?.0)?,
+    ])
+}
+
+/// Byte offset of `rlim[RLIMIT_NOFILE]` given where `rlim` starts and the
+/// size of one `struct rlimit`, both in bits as BTF reports them.
+fn nofile_rlim_bytes(rlim_bits: u32, element_bits: u32) -> Result<u32> {
+    if element_bits == 0 || element_bits % 8 != 0 {
+        return Err(anyhow!("unexpected rlimit size: {element_bits} bits"));
+    }
+    to_bytes(rlim_bits)?
+        .checked_add(libc::RLIMIT_NOFILE as u32 * (element_bits / 8))
+        .ok_or_else(|| anyhow!("RLIMIT_NOFILE offset overflows"))
+}
+
+/// Offsets in `config` that cannot be right on any 64-bit kernel: missing
+/// required fields, misaligned members, or an unusual page size. x86_64
+/// and arm64 share the alignment rules, so a problem here means the BTF
+/// was misread rather than that the architecture differs. Optional offsets
+/// left at 0 are not reported.
+#[allow(clippy::manual_is_multiple_of)] // is_multiple_of not stable in nightly-2024-12-10
+pub fn offset_problems(config: &TelemetryConfig) -> Vec<String> {
+    let mut problems = Vec::new();
+    let required = [
+        ("task_struct.real_parent", config.task_real_parent_offset),
+        ("task_struct.tgid", config.task_tgid_offset),
+        ("task_struct.pid", config.task_pid_offset),
+        ("task_struct.comm", config.task_comm_offset),
+        ("task_struct.se", config.task_se_offset),
+    ];
+    for (name, offset) in required {
+        if offset == 0 {
+            problems.push(format!("{name} offset was not discovered"));
+        }
+    }
+    let aligned = [
+        ("task_struct.real_parent", config.task_real_parent_offset, 8),
+        ("task_struct.signal", config.task_signal_offset, 8),
+        ("task_struct.mm", config.task_mm_offset, 8),
+        ("task_struct.tgid", config.task_tgid_offset, 4),
+        ("task_struct.pid", config.task_pid_offset, 4),
+        ("task_struct.exit_code", config.task_exit_code_offset, 4),
+        (
+            "sched_entity.sum_exec_runtime",
+            config.se_sum_exec_runtime_offset,
+            8,
+        ),
+        (
+            "signal_struct.rlim[RLIMIT_NOFILE]",
+            config.signal_nofile_rlim_offset,
+            8,
+        ),
+        ("linux_binprm.filename", config.bprm_filename_offset, 8),
+        ("file.f_inode", config.file_inode_offset, 8),
+        ("inode.i_mode", config.inode_mode_offset, 2),
+        ("inode.i_ino", config.inode_ino_offset, 8),
+        ("inode.i_sb", config.inode_sb_offset, 8),
+        ("super_block.s_dev", config.sb_dev_offset, 4),
+    ];
+    for (name, offset, align) in aligned {
+        if offset % align != 0 {
+            problems.push(format!(
+                "{name} offset {offset} is not {align}-byte aligned"
+            ));
+        }
+    }
+    if config.rss_item_size != 8 {
+        problems.push(format!(
+            "rss_stat counters are {} bytes, expected 8",
+            config.rss_item_size
+        ));
+    }
+    if !config.page_size.is_power_of_two() || !(4096..=65536).contains(&config.page_size) {
+        problems.push(format!("unexpected page size {}", config.page_size));
+    }
+    problems
+}
+
 #[derive(Clone)]
 struct RssLayout {
     field_offset: u32,
@@ -431,10 +698,87 @@ fn to_bytes(bits: u32) -> Result<u32> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn build_id_from_kernel_notes() {
+        let note = |name: &[u8], kind: u32, desc: &[u8]| {
+            let mut out = Vec::new();
+            out.extend((name.len() as u32).to_ne_bytes());
+            out.extend((desc.len() as u32).to_ne_bytes());
+            out.extend(kind.to_ne_bytes());
+            out.extend(name);
+            out.resize(out.len().next_multiple_of(4), 0);
+            out.extend(desc);
+            out.resize(out.len().next_multiple_of(4), 0);
+            out
+        };
+        let mut notes = note(b"Xen\0", 6, &[1, 2, 3]);
+        notes.extend(note(b"GNU\0", 3, &[0xde, 0xad, 0xbe, 0xef, 0x01]));
+        assert_eq!(parse_build_id(&notes).as_deref(), Some("deadbeef01"));
+        assert_eq!(parse_build_id(&notes[..20]), None);
+    }
+
     #[test]
     fn to_bytes_roundtrip() {
         assert_eq!(to_bytes(0).unwrap(), 0);
         assert_eq!(to_bytes(8).unwrap(), 1);
         assert!(to_bytes(3).is_err());
     }
+
+    /// Offsets as derived from a 6.1 arm64 kernel, where `task_struct`
+    /// starts with a larger `thread_info` than on x86_64.
+    fn arm64_config(page_size: u32) -> TelemetryConfig {
+        let mut config = TelemetryConfig::zeroed();
+        config.task_real_parent_offset = 1_464;
+        config.task_tgid_offset = 1_452;
+        config.task_pid_offset = 1_448;
+        config.task_comm_offset = 1_912;
+        config.task_se_offset = 128;
+        config.se_sum_exec_runtime_offset = 72;
+        config.task_signal_offset = 2_008;
+        config.task_mm_offset = 1_216;
+        config.task_exit_code_offset = 1_324;
+        config.signal_nofile_rlim_offset = nofile_rlim_bytes(5_248, 128).unwrap();
+        config.mm_rss_stat_offset = 784;
+        config.rss_item_size = 8;
+        config.page_size = page_size;
+        config
+    }
+
+    #[test]
+    fn accepts_arm64_offsets_for_every_page_size() {
+        for page_size in [4_096, 16_384, 65_536] {
+            let config = arm64_config(page_size);
+            assert_eq!(
+                offset_problems(&config),
+                Vec::<String>::new(),
+                "{page_size}"
+            );
+        }
+        // rlim is an array of two-u64 `struct rlimit` on both architectures
+        assert_eq!(nofile_rlim_bytes(5_248, 128).unwrap(), 656 + 7 * 16);
+        assert!(nofile_rlim_bytes(5_248, 0).is_err());
+
+        let mut x86 = arm64_config(4_096);
+        x86.task_real_parent_offset = 2_456;
+        x86.task_tgid_offset = 2_444;
+        x86.task_pid_offset = 2_440;
+        assert!(offset_problems(&x86).is_empty());
+    }
+
+    #[test]
+    fn reports_misread_offsets() {
+        let mut config = arm64_config(8_000);
+        config.task_mm_offset = 1_220;
+        config.task_comm_offset = 0;
+        config.rss_item_size = 4;
+        assert_eq!(
+            offset_problems(&config),
+            vec![
+                "task_struct.comm offset was not discovered",
+                "task_struct.mm offset 1220 is not 8-byte aligned",
+                "rss_stat counters are 4 bytes, expected 8",
+                "unexpected page size 8000",
+            ]
+        );
+    }
 }
@@ -0,0 +1,321 @@
+//! cgroup v2 hierarchy walker
+//!
+//! Builds a tree view of `/sys/fs/cgroup` with per-node usage and pressure so
+//! operators can reason about `system.slice` vs `kubepods.slice` instead of
+//! flat process lists. cgroup v2 accounting files (`cpu.stat`,
+//! `memory.current`, `io.stat`, `*.pressure`) are already hierarchical, so the
+//! values reported on a node include all of its descendants. Tracked process
+//! counts are the only field rolled up here.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use crate::utils::psi::parse_avg10;
+
+/// Hard upper bound on walk depth regardless of what the caller asks for.
+pub const MAX_DEPTH: usize = 16;
+
+pub fn cgroup_root() -> String {
+    env::var("LINNIX_CGROUP_ROOT").unwrap_or_else(|_| "/sys/fs/cgroup".to_string())
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct CgroupPressure {
+    pub cpu_some_avg10: Option<f32>,
+    pub memory_some_avg10: Option<f32>,
+    pub memory_full_avg10: Option<f32>,
+    pub io_some_avg10: Option<f32>,
+    pub io_full_avg10: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CgroupNode {
+    /// Path relative to the cgroup root, e.g. `/kubepods.slice`.
+    pub path: String,
+    pub name: String,
+    pub depth: usize,
+    pub cpu_usage_usec: Option<u64>,
+    pub memory_current_bytes: Option<u64>,
+    pub io_read_bytes: Option<u64>,
+    pub io_write_bytes: Option<u64>,
+    pub pressure: CgroupPressure,
+    /// Tracked pids whose cgroup is exactly this node.
+    pub pids: Vec<u32>,
+    /// Tracked pids in this node and all descendants (including ones below
+    /// the depth limit).
+    pub subtree_process_count: usize,
+    /// True when children exist but were cut off by the depth limit.
+    pub truncated: bool,
+    pub children: Vec<CgroupNode>,
+}
+
+/// Resolve the cgroup v2 path of a process from `/proc/<pid>/cgroup`.
+pub fn process_cgroup_path(pid: u32) -> Option<String> {
+    let content = fs::read_to_string(format!("/proc/{pid}/cgroup")).ok()?;
+    parse_proc_cgroup(&content)
+}
+
+/// Extract the unified hierarchy entry (`0::/path`) from `/proc/<pid>/cgroup`.
+pub fn parse_proc_cgroup(content: &str) -> Option<String> {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(|p| p.trim().to_string())
+}
+
+/// Group pids by cgroup path.
+pub fn map_pids(pids: impl IntoIterator<Item = u32>) -> HashMap<String, Vec<u32>> {
+    let mut map: HashMap<String, Vec<u32>> = HashMap::new();
+    for pid in pids {
+        if let Some(path) = process_cgroup_path(pid) {
+            map.entry(path).or_default().push(pid);
+        }
+    }
+    map
+}
+
+/// Walk the hierarchy below `root` starting at `subtree` (a path relative to
+/// the root, `/` for the whole tree), descending at most `max_depth` levels.
+pub fn build_tree(
+    root: &Path,
+    subtree: &str,
+    max_depth: usize,
+    pids_by_cgroup: &HashMap<String, Vec<u32>>,
+) -> Option<CgroupNode> {
+    let rel = normalize(subtree);
+    if rel.split('/').any(|c| c == "..") {
+        return None;
+    }
+    let dir = root.join(rel.trim_start_matches('/'));
+    if !dir.is_dir() {
+        return None;
+    }
+    Some(walk(
+        &dir,
+        &rel,
+        0,
+        max_depth.min(MAX_DEPTH),
+        pids_by_cgroup,
+    ))
+}
+
+pub(crate) fn normalize(path: &str) -> String {
+    let trimmed = path.trim().trim_end_matches('/');
+    if trimmed.is_empty() {
+        "/".to_string()
+    } else if trimmed.starts_with('/') {
+        trimmed.to_string()
+    } else {
+        format!("/{trimmed}")
+    }
+}
+
+fn walk(
+    dir: &Path,
+    rel: &str,
+    depth: usize,
+    max_depth: usize,
+    pids_by_cgroup: &HashMap<String, Vec<u32>>,
+) -> CgroupNode {
+    let mut pids = pids_by_cgroup.get(rel).cloned().unwrap_or_default();
+    pids.sort_unstable();
+
+    let mut child_dirs: Vec<(String, std::path::PathBuf)> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .filter(|e| e.file_type().map(|t| t.is_dir()).unwrap_or(false))
+                .map(|e| (e.file_name().to_string_lossy().into_owned(), e.path()))
+                .collect()
+        })
+        .unwrap_or_default();
+    child_dirs.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let truncated = depth >= max_depth && !child_dirs.is_empty();
+    let children: Vec<CgroupNode> = if truncated {
+        Vec::new()
+    } else {
+        child_dirs
+            .iter()
+            .map(|(name, path)| {
+                let child_rel = if rel == "/" {
+                    format!("/{name}")
+                } else {
+                    format!("{rel}/{name}")
+                };
+                walk(path, &child_rel, depth + 1, max_depth, pids_by_cgroup)
+            })
+            .collect()
+    };
+
+    let subtree_process_count = if truncated {
+        let prefix = format!("{}/", rel.trim_end_matches('/'));
+        pids_by_cgroup
+            .iter()
+            .filter(|(path, _)| path.as_str() == rel || path.starts_with(&prefix))
+            .map(|(_, p)| p.len())
+            .sum()
+    } else {
+        pids.len()
+            + children
+                .iter()
+                .map(|c| c.subtree_process_count)
+                .sum::<usize>()
+    };
+
+    let (io_read_bytes, io_write_bytes) = read_file(dir, "io.stat")
+        .map(|s| parse_io_stat(&s))
+        .map(|(r, w)| (Some(r), Some(w)))
+        .unwrap_or((None, None));
+
+    CgroupNode {
+        path: rel.to_string(),
+        name: if rel == "/" {
+            "/".to_string()
+        } else {
+            rel.rsplit('/').next().unwrap_or(rel).to_string()
+        },
+        depth,
+        cpu_usage_usec: read_file(dir, "cpu.stat").and_then(|s| parse_cpu_usage(&s)),
+        memory_current_bytes: read_file(dir, "memory.current")
+            .and_then(|s| s.trim().parse::<u64>().ok()),
+        io_read_bytes,
+        io_write_bytes,
+        pressure: read_pressure(dir),
+        pids,
+        subtree_process_count,
+        truncated,
+        children,
+    }
+}
+
+fn read_file(dir: &Path, name: &str) -> Option<String> {
+    fs::read_to_string(dir.join(name)).ok()
+}
+
+pub(crate) fn read_pressure(dir: &Path) -> CgroupPressure {
+    let cpu = read_file(dir, "cpu.pressure");
+    let memory = read_file(dir, "memory.pressure");
+    let io = read_file(dir, "io.pressure");
+    CgroupPressure {
+        cpu_some_avg10: cpu.as_deref().and_then(|c| parse_avg10(c, "some")),
+        memory_some_avg10: memory.as_deref().and_then(|c| parse_avg10(c, "some")),
+        memory_full_avg10: memory.as_deref().and_then(|c| parse_avg10(c, "full")),
+        io_some_avg10: io.as_deref().and_then(|c| parse_avg10(c, "some")),
+        io_full_avg10: io.as_deref().and_then(|c| parse_avg10(c, "full")),
+    }
+}
+
+/// Parse `usage_usec` from `cpu.stat`.
+fn parse_cpu_usage(content: &str) -> Option<u64> {
+    content.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        match (parts.next(), parts.next()) {
+            (Some("usage_usec"), Some(v)) => v.parse().ok(),
+            _ => None,
+        }
+    })
+}
+
+/// Sum `rbytes`/`wbytes` across all devices in `io.stat`.
+fn parse_io_stat(content: &str) -> (u64, u64) {
+    let mut read = 0u64;
+    let mut write = 0u64;
+    for field in content.split_whitespace() {
+        if let Some(v) = field.strip_prefix("rbytes=") {
+            read = read.saturating_add(v.parse().unwrap_or(0));
+        } else if let Some(v) = field.strip_prefix("wbytes=") {
+            write = write.saturating_add(v.parse().unwrap_or(0));
+        }
+    }
+    (read, write)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn fake_hierarchy() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("system.slice/sshd.service")).unwrap();
+        fs::create_dir_all(root.join("kubepods.slice/pod-a")).unwrap();
+        fs::write(
+            root.join("kubepods.slice/cpu.stat"),
+            "usage_usec 5000\nuser_usec 3000\n",
+        )
+        .unwrap();
+        fs::write(root.join("kubepods.slice/memory.current"), "4096\n").unwrap();
+        fs::write(
+            root.join("kubepods.slice/io.stat"),
+            "8:0 rbytes=100 wbytes=200 rios=1 wios=2\n259:0 rbytes=1 wbytes=2\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("kubepods.slice/memory.pressure"),
+            "some avg10=1.50 avg60=0.00 avg300=0.00 total=1\nfull avg10=0.25 avg60=0.00 avg300=0.00 total=1\n",
+        )
+        .unwrap();
+        dir
+    }
+
+    #[test]
+    fn parses_unified_cgroup_line() {
+        let content = "12:memory:/legacy\n0::/kubepods.slice/pod-a\n";
+        assert_eq!(
+            parse_proc_cgroup(content).as_deref(),
+            Some("/kubepods.slice/pod-a")
+        );
+        assert_eq!(parse_proc_cgroup("1:cpu:/foo\n"), None);
+    }
+
+    #[test]
+    fn builds_tree_with_usage_and_rollups() {
+        let dir = fake_hierarchy();
+        let mut pids = HashMap::new();
+        pids.insert("/kubepods.slice/pod-a".to_string(), vec![42, 7]);
+        pids.insert("/system.slice/sshd.service".to_string(), vec![100]);
+
+        let tree = build_tree(dir.path(), "/", 3, &pids).expect("root exists");
+        assert_eq!(tree.subtree_process_count, 3);
+        let kube = tree
+            .children
+            .iter()
+            .find(|c| c.name == "kubepods.slice")
+            .unwrap();
+        assert_eq!(kube.cpu_usage_usec, Some(5000));
+        assert_eq!(kube.memory_current_bytes, Some(4096));
+        assert_eq!(kube.io_read_bytes, Some(101));
+        assert_eq!(kube.io_write_bytes, Some(202));
+        assert_eq!(kube.pressure.memory_full_avg10, Some(0.25));
+        assert_eq!(kube.subtree_process_count, 2);
+        assert_eq!(kube.children[0].pids, vec![7, 42]);
+    }
+
+    #[test]
+    fn depth_and_subtree_filters() {
+        let dir = fake_hierarchy();
+        let mut pids = HashMap::new();
+        pids.insert("/kubepods.slice/pod-a".to_string(), vec![42]);
+
+        let shallow = build_tree(dir.path(), "/", 1, &pids).unwrap();
+        let kube = shallow
+            .children
+            .iter()
+            .find(|c| c.name == "kubepods.slice")
+            .unwrap();
+        assert!(kube.truncated);
+        assert!(kube.children.is_empty());
+        assert_eq!(kube.subtree_process_count, 1);
+
+        let sub = build_tree(dir.path(), "kubepods.slice/", 4, &pids).unwrap();
+        assert_eq!(sub.path, "/kubepods.slice");
+        assert_eq!(sub.children.len(), 1);
+        assert!(build_tree(dir.path(), "/missing", 4, &pids).is_none());
+        assert!(build_tree(dir.path(), "/kubepods.slice/../..", 4, &pids).is_none());
+    }
+}
@@ -0,0 +1,671 @@
+//! Per-pod ephemeral storage usage
+//!
+//! The kubelet evicts a pod once its emptyDir volumes, container writable
+//! layers and container logs together exceed the pod's `ephemeral-storage`
+//! limit, and the eviction message does not say which process wrote the data.
+//! This samples the three parts du-style for every pod with a tracked
+//! process, keeps the heaviest recent writers from `fileio` write events, and
+//! raises `ephemeral_storage_usage` alerts as usage crosses `warn_pct` and
+//! `critical_pct` of the limit.
+//!
+//! Walking large trees is expensive, so each pass visits at most
+//! `max_entries_per_scan` entries; pods sampled longest ago go first and a
+//! pod whose walk ran out of budget is reported as `partial`. Project quota
+//! accounting is not read; the walk is the only source.
+//!
+//! Writers are ranked by all bytes they wrote, including to persistent
+//! volumes. Each names the file it wrote most to, resolved through
+//! `file_paths` while the writer still has it open.
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use linnix_ai_ebpf_common::EventType;
+use log::{debug, info};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::ProcessEvent;
+use crate::alerts::{Alert, AlertSender, Severity};
+use crate::config::EphemeralStorageConfig;
+use crate::context::ContextStore;
+use crate::file_paths::{FileId, FilePaths};
+use crate::handler::Handler;
+use crate::types::SystemSnapshot;
+use crate::utils::now_secs;
+
+pub const RULE_NAME: &str = "ephemeral_storage_usage";
+
+/// `(namespace, pod)`
+type PodKey = (String, String);
+
+/// Files counted per pid and pass; writes to others only add to the total.
+const MAX_FILES_PER_WRITER: usize = 64;
+
+/// Bytes written by one pid since the last pass, with the comm on the event.
+#[derive(Debug, Clone, Default)]
+struct PidWrites {
+    comm: [u8; 16],
+    bytes: u64,
+    files: HashMap<FileId, u64>,
+}
+
+impl PidWrites {
+    fn record(&mut self, file: Option<FileId>, bytes: u64) {
+        self.bytes += bytes;
+        let Some(file) = file else {
+            return;
+        };
+        if self.files.len() < MAX_FILES_PER_WRITER || self.files.contains_key(&file) {
+            *self.files.entry(file).or_default() += bytes;
+        }
+    }
+
+    /// The file with the most bytes, and those bytes.
+    fn top_file(&self) -> Option<(FileId, u64)> {
+        self.files
+            .iter()
+            .max_by_key(|(_, bytes)| **bytes)
+            .map(|(file, bytes)| (*file, *bytes))
+    }
+}
+
+type WriteBytes = DashMap<u32, PidWrites>;
+
+/// One pass's writes: pod -> pid -> writer.
+type PassWrites = HashMap<PodKey, HashMap<u32, Writer>>;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Writer {
+    pub pid: u32,
+    pub comm: String,
+    pub container: String,
+    /// Bytes written within the writer window
+    pub bytes: u64,
+    /// File the writer wrote most to in a pass of the window, when it could
+    /// be resolved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_file: Option<String>,
+    /// Bytes written to `top_file`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_file_bytes: Option<u64>,
+}
+
+impl Writer {
+    /// Add a later pass of the same pid. The top file is the one with the
+    /// most bytes in either, summed when it is the same file.
+    fn merge(&mut self, other: &Writer) {
+        self.bytes += other.bytes;
+        match (&self.top_file, &other.top_file) {
+            (Some(mine), Some(theirs)) if mine == theirs => {
+                self.top_file_bytes =
+                    Some(self.top_file_bytes.unwrap_or(0) + other.top_file_bytes.unwrap_or(0));
+            }
+            (_, Some(_)) if other.top_file_bytes > self.top_file_bytes => {
+                self.top_file = other.top_file.clone();
+                self.top_file_bytes = other.top_file_bytes;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Last sample of one pod.
+#[derive(Debug, Clone, Serialize)]
+pub struct PodEphemeralStorage {
+    pub namespace: String,
+    pub pod: String,
+    pub used_bytes: u64,
+    pub empty_dir_bytes: u64,
+    pub writable_layer_bytes: u64,
+    pub logs_bytes: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub used_pct: Option<f64>,
+    /// The walk ran out of budget; the sizes are lower bounds
+    pub partial: bool,
+    /// Unix time of the sample
+    pub sampled_at: u64,
+    pub top_writers: Vec<Writer>,
+}
+
+/// What a pass needs to know about a pod, gathered from its live processes.
+#[derive(Debug, Clone, Default)]
+pub struct PodTarget {
+    pub uid: String,
+    pub limit: Option<u64>,
+    /// Container name -> a pid running in it
+    pub containers: HashMap<String, u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Level {
+    Normal,
+    Warn,
+    Critical,
+}
+
+struct PodState {
+    usage: PodEphemeralStorage,
+    level: Level,
+}
+
+pub struct EphemeralStorageWatcher {
+    config: EphemeralStorageConfig,
+    /// Prefix for the container root filesystems found in mountinfo
+    host_root: PathBuf,
+    proc_root: PathBuf,
+    host: String,
+    writes: Arc<WriteBytes>,
+    paths: FilePaths,
+    /// Per pass: the bytes each pod's processes wrote, by pid
+    recent: Mutex<VecDeque<(u64, PassWrites)>>,
+    pods: Mutex<HashMap<PodKey, PodState>>,
+}
+
+impl EphemeralStorageWatcher {
+    pub fn new(config: &EphemeralStorageConfig) -> Self {
+        Self {
+            config: config.clone(),
+            host_root: PathBuf::from("/"),
+            proc_root: PathBuf::from("/proc"),
+            host: std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown".into()),
+            writes: Arc::new(DashMap::new()),
+            paths: FilePaths::default(),
+            recent: Mutex::new(VecDeque::new()),
+            pods: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[cfg(test)]
+    fn with_roots(mut self, host_root: &Path, proc_root: &Path) -> Self {
+        self.host_root = host_root.to_path_buf();
+        self.proc_root = proc_root.to_path_buf();
+        self.paths = FilePaths::new(proc_root);
+        self
+    }
+
+    /// Counts `fileio` write bytes for the writer ranking.
+    pub fn write_handler(&self) -> FileWriteHandler {
+        FileWriteHandler {
+            writes: Arc::clone(&self.writes),
+        }
+    }
+
+    /// Last sample of every pod, sorted by namespace and name.
+    pub fn snapshot(&self) -> Vec<PodEphemeralStorage> {
+        let mut out: Vec<_> = self
+            .pods
+            .lock()
+            .unwrap()
+            .values()
+            .map(|state| state.usage.clone())
+            .collect();
+        out.sort_by(|a, b| (&a.namespace, &a.pod).cmp(&(&b.namespace, &b.pod)));
+        out
+    }
+
+    pub fn pod(&self, namespace: &str, pod: &str) -> Option<PodEphemeralStorage> {
+        self.pods
+            .lock()
+            .unwrap()
+            .get(&(namespace.to_string(), pod.to_string()))
+            .map(|state| state.usage.clone())
+    }
+
+    /// Pods of live processes whose metadata carries a UID, and the pods
+    /// each writing pid belongs to.
+    fn targets(
+        &self,
+        context: &ContextStore,
+    ) -> (HashMap<PodKey, PodTarget>, HashMap<u32, (PodKey, String)>) {
+        let live = context.get_live_map();
+        let mut targets: HashMap<PodKey, PodTarget> = HashMap::new();
+        let mut owners = HashMap::new();
+        for (pid, (_, meta)) in live.iter() {
+            let Some(meta) = meta else {
+                continue;
+            };
+            let Some(uid) = &meta.pod_uid else {
+                continue;
+            };
+            let key = (meta.namespace.clone(), meta.pod_name.clone());
+            let target = targets.entry(key.clone()).or_default();
+            target.uid = uid.clone();
+            target.limit = meta.ephemeral_storage_limit;
+            target
+                .containers
+                .entry(meta.container_name.clone())
+                .or_insert(*pid);
+            owners.insert(*pid, (key, meta.container_name.clone()));
+        }
+        (targets, owners)
+    }
+
+    /// Move the write counters into the window, attributed to pods.
+    fn record_writes(&self, owners: &HashMap<u32, (PodKey, String)>, now: u64) {
+        let mut pass = PassWrites::new();
+        let pids: Vec<u32> = self.writes.iter().map(|entry| *entry.key()).collect();
+        for pid in pids {
+            let Some((_, writes)) = self.writes.remove(&pid) else {
+                continue;
+            };
+            let Some((key, container)) = owners.get(&pid) else {
+                continue;
+            };
+            let top = writes
+                .top_file()
+                .and_then(|(file, bytes)| Some((self.paths.resolve(pid, file)?, bytes)));
+            let comm = String::from_utf8_lossy(&writes.comm);
+            pass.entry(key.clone()).or_default().insert(
+                pid,
+                Writer {
+                    pid,
+                    comm: comm.trim_end_matches('\0').to_string(),
+                    container: container.clone(),
+                    bytes: writes.bytes,
+                    top_file: top.as_ref().map(|(path, _)| path.clone()),
+                    top_file_bytes: top.map(|(_, bytes)| bytes),
+                },
+            );
+        }
+        let mut recent = self.recent.lock().unwrap();
+        recent.push_back((now, pass));
+        let horizon = now.saturating_sub(self.config.writer_window_secs);
+        while recent.front().is_some_and(|(at, _)| *at < horizon) {
+            recent.pop_front();
+        }
+    }
+
+    fn top_writers(&self, key: &PodKey) -> Vec<Writer> {
+        let mut totals: HashMap<u32, Writer> = HashMap::new();
+        for (_, pass) in self.recent.lock().unwrap().iter() {
+            for writer in pass.get(key).into_iter().flat_map(|pids| pids.values()) {
+                totals
+                    .entry(writer.pid)
+                    .and_modify(|total| total.merge(writer))
+                    .or_insert_with(|| writer.clone());
+            }
+        }
+        let mut writers: Vec<_> = totals.into_values().collect();
+        writers.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.pid.cmp(&b.pid)));
+        writers.truncate(self.config.top_writers);
+        writers
+    }
+
+    /// Sample as many pods as the entry budget allows, least recently
+    /// sampled first, and return alerts for pods whose usage crossed a
+    /// threshold. Pods no longer in `targets` are forgotten.
+    pub fn poll(&self, targets: &HashMap<PodKey, PodTarget>, now: u64) -> Vec<Alert> {
+        let mut pods = self.pods.lock().unwrap();
+        pods.retain(|key, _| targets.contains_key(key));
+        let mut order: Vec<_> = targets.keys().collect();
+        order.sort_by_key(|key| pods.get(*key).map(|state| state.usage.sampled_at));
+
+        let mut budget = self.config.max_entries_per_scan;
+        let mut alerts = Vec::new();
+        for key in order {
+            if budget == 0 {
+                break;
+            }
+            let target = &targets[key];
+            let usage = self.sample(key, target, &mut budget, now);
+            let level = self.level(usage.used_pct);
+            let previous = pods.get(key).map_or(Level::Normal, |state| state.level);
+            if level > previous {
+                alerts.push(self.alert(&usage, level));
+            }
+            pods.insert(key.clone(), PodState { usage, level });
+        }
+        alerts
+    }
+
+    fn sample(
+        &self,
+        (namespace, pod): &PodKey,
+        target: &PodTarget,
+        budget: &mut u64,
+        now: u64,
+    ) -> PodEphemeralStorage {
+        let kubelet = Path::new(&self.config.kubelet_root);
+        let empty_dirs = kubelet
+            .join("pods")
+            .join(&target.uid)
+            .join("volumes/kubernetes.io~empty-dir");
+        let logs =
+            Path::new(&self.config.pod_logs_root).join(format!("{namespace}_{pod}_{}", target.uid));
+        let mut complete = true;
+        let mut walk = |path: &Path, budget: &mut u64| {
+            let (bytes, done) = disk_usage(path, budget);
+            complete &= done;
+            bytes
+        };
+        let empty_dir_bytes = walk(&empty_dirs, budget);
+        let logs_bytes = walk(&logs, budget);
+        let mut writable_layer_bytes = 0;
+        for pid in target.containers.values() {
+            match self.upper_dir(*pid) {
+                Some(upper) => writable_layer_bytes += walk(&upper, budget),
+                None => debug!("[ephemeral-storage] no overlay upperdir for pid {pid}"),
+            }
+        }
+        let used_bytes = empty_dir_bytes + logs_bytes + writable_layer_bytes;
+        PodEphemeralStorage {
+            namespace: namespace.clone(),
+            pod: pod.clone(),
+            used_bytes,
+            empty_dir_bytes,
+            writable_layer_bytes,
+            logs_bytes,
+            limit_bytes: target.limit,
+            used_pct: target
+                .limit
+                .filter(|limit| *limit > 0)
+                .map(|limit| used_bytes as f64 * 100.0 / limit as f64),
+            partial: !complete,
+            sampled_at: now,
+            top_writers: self.top_writers(&(namespace.clone(), pod.clone())),
+        }
+    }
+
+    /// Host path of the writable layer of the container `pid` runs in.
+    fn upper_dir(&self, pid: u32) -> Option<PathBuf> {
+        let mountinfo =
+            std::fs::read_to_string(self.proc_root.join(pid.to_string()).join("mountinfo")).ok()?;
+        let upper = parse_root_upperdir(&mountinfo)?;
+        Some(self.host_root.join(upper.trim_start_matches('/')))
+    }
+
+    fn level(&self, used_pct: Option<f64>) -> Level {
+        match used_pct {
+            Some(pct) if pct >= self.config.critical_pct => Level::Critical,
+            Some(pct) if pct >= self.config.warn_pct => Level::Warn,
+            _ => Level::Normal,
+        }
+    }
+
+    fn alert(&self, usage: &PodEphemeralStorage, level: Level) -> Alert {
+        Alert {
+            rule: RULE_NAME.to_string(),
+            severity: if level == Level::Critical {
+                Severity::High
+            } else {
+                Severity::Medium
+            },
+            message: describe(usage),
+            host: self.host.clone(),
+            detection: "threshold",
+            suppressed_by: None,
+            lineage: Vec::new(),
+            children: Vec::new(),
+            pod: None,
+            evidence: None,
+            annotations: None,
+        }
+    }
+
+    pub async fn run(self: Arc<Self>, context: Arc<ContextStore>, tx: Option<AlertSender>) {
+        let interval = Duration::from_secs(self.config.interval_secs.max(1));
+        info!(
+            "[ephemeral-storage] sampling pod storage every {}s, at most {} entries per pass",
+            interval.as_secs(),
+            self.config.max_entries_per_scan
+        );
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let (targets, owners) = self.targets(&context);
+            let now = now_secs();
+            self.record_writes(&owners, now);
+            let watcher = Arc::clone(&self);
+            let alerts =
+                match tokio::task::spawn_blocking(move || watcher.poll(&targets, now)).await {
+                    Ok(alerts) => alerts,
+                    Err(e) => {
+                        debug!("[ephemeral-storage] pass failed: {e}");
+                        continue;
+                    }
+                };
+            for alert in alerts {
+                info!("[ephemeral-storage] {}", alert.message);
+                if let Some(tx) = &tx {
+                    tx.send(alert);
+                }
+            }
+        }
+    }
+}
+
+/// Counts bytes of `fileio` writes per pid.
+pub struct FileWriteHandler {
+    writes: Arc<WriteBytes>,
+}
+
+#[async_trait]
+impl Handler for FileWriteHandler {
+    fn name(&self) -> &'static str {
+        "ephemeral_storage"
+    }
+
+    async fn on_event(&self, event: &ProcessEvent) {
+        if event.event_type == EventType::FileIo as u32 && event.aux == 1 {
+            self.writes
+                .entry(event.pid)
+                .or_insert_with(|| PidWrites {
+                    comm: event.comm,
+                    ..PidWrites::default()
+                })
+                .record(FileId::from_event(event), event.data);
+        }
+    }
+
+    async fn on_snapshot(&self, _snapshot: &SystemSnapshot) {}
+}
+
+/// Allocated bytes under `path`, like `du -s`, without following symlinks or
+/// crossing into other filesystems. Each visited entry takes one unit of
+/// `budget`; the flag is false when it ran out before the walk finished. A
+/// missing `path` is empty.
+pub fn disk_usage(path: &Path, budget: &mut u64) -> (u64, bool) {
+    let Ok(root) = std::fs::symlink_metadata(path) else {
+        return (0, true);
+    };
+    let device = root.dev();
+    let mut bytes = 0;
+    let mut stack = vec![(path.to_path_buf(), root)];
+    while let Some((path, meta)) = stack.pop() {
+        if *budget == 0 {
+            return (bytes, false);
+        }
+        *budget -= 1;
+        bytes += meta.blocks() * 512;
+        if !meta.is_dir() {
+            continue;
+        }
+        let Ok(entries) = std::fs::read_dir(&path) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if let Ok(meta) = std::fs::symlink_metadata(&path)
+                && meta.dev() == device
+            {
+                stack.push((path, meta));
+            }
+        }
+    }
+    (bytes, true)
+}
+
+/// `upperdir` of the overlay mounted at `/` in a process's `mountinfo`.
+pub fn parse_root_upperdir(mountinfo: &str) -> Option<String> {
+    mountinfo.lines().find_map(|line| {
+        let (mount, fs) = line.split_once(" - ")?;
+        if mount.split_whitespace().nth(4)? != "/" {
+            return None;
+        }
+        let mut fs = fs.split_whitespace();
+        if fs.next()? != "overlay" {
+            return None;
+        }
+        fs.nth(1)?
+            .split(',')
+            .find_map(|option| option.strip_prefix("upperdir="))
+            .map(String::from)
+    })
+}
+
+fn describe(usage: &PodEphemeralStorage) -> String {
+    let writers = usage
+        .top_writers
+        .iter()
+        .map(|w| {
+            let file = match &w.top_file {
+                Some(path) => format!(" (most to {path})"),
+                None => String::new(),
+            };
+            format!(
+                "{}[{}] in {} wrote {} MiB{file}",
+                w.comm,
+                w.pid,
+                w.container,
+                w.bytes >> 20
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "pod {}/{} uses {} MiB of ephemeral storage ({:.0}% of its {} MiB limit; emptyDir {} MiB, writable layers {} MiB, logs {} MiB){}{}",
+        usage.namespace,
+        usage.pod,
+        usage.used_bytes >> 20,
+        usage.used_pct.unwrap_or(0.0),
+        usage.limit_bytes.unwrap_or(0) >> 20,
+        usage.empty_dir_bytes >> 20,
+        usage.writable_layer_bytes >> 20,
+        usage.logs_bytes >> 20,
+        if usage.partial { ", partial scan" } else { "" },
+        if writers.is_empty() {
+            String::new()
+        } else {
+            format!("; recent writers: {writers}")
+        }
+    )
+}
+
+/// Start sampling the pods of tracked processes. Alerts go to `tx` when a
+/// rules engine is loaded; samples are kept either way for `/pods`.
+pub fn spawn(
+    config: &EphemeralStorageConfig,
+    context: Arc<ContextStore>,
+    tx: Option<AlertSender>,
+) -> Arc<EphemeralStorageWatcher> {
+    let watcher = Arc::new(EphemeralStorageWatcher::new(config));
+    tokio::spawn(Arc::clone(&watcher).run(context, tx));
+    watcher
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_file(path: &Path, len: usize) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, vec![1u8; len]).unwrap();
+    }
+
+    #[test]
+    fn root_upperdir_from_mountinfo() {
+        let mountinfo = "\
+1 0 0:20 / /proc rw - proc proc rw
+2 0 0:50 / / rw,relatime - overlay overlay rw,lowerdir=/l1:/l2,upperdir=/var/lib/containerd/snap/42/fs,workdir=/var/lib/containerd/snap/42/work
+3 2 0:51 / /data rw - overlay overlay rw,upperdir=/other
+";
+        assert_eq!(
+            parse_root_upperdir(mountinfo).as_deref(),
+            Some("/var/lib/containerd/snap/42/fs")
+        );
+        assert_eq!(
+            parse_root_upperdir("1 0 0:20 / / rw - ext4 /dev/sda1 rw"),
+            None
+        );
+    }
+
+    #[test]
+    fn usage_crossing_limit_alerts_with_writers() {
+        let root = tempfile::tempdir().unwrap();
+        let kubelet = root.path().join("kubelet");
+        let logs = root.path().join("logs");
+        let proc_root = root.path().join("proc");
+        let empty_dir = kubelet.join("pods/uid-1/volumes/kubernetes.io~empty-dir/cache");
+        write_file(&empty_dir.join("blob"), 3 << 20);
+        write_file(&logs.join("prod_api-0_uid-1/api/0.log"), 64 << 10);
+        write_file(&root.path().join("snap/1/fs/tmp/scratch"), 256 << 10);
+        std::fs::create_dir_all(proc_root.join("100")).unwrap();
+        std::fs::write(
+            proc_root.join("100/mountinfo"),
+            "2 0 0:50 / / rw - overlay overlay rw,lowerdir=/l,upperdir=/snap/1/fs,workdir=/snap/1/work\n",
+        )
+        .unwrap();
+
+        let config = EphemeralStorageConfig {
+            kubelet_root: kubelet.display().to_string(),
+            pod_logs_root: logs.display().to_string(),
+            ..EphemeralStorageConfig::default()
+        };
+        let watcher = EphemeralStorageWatcher::new(&config).with_roots(root.path(), &proc_root);
+        let key = ("prod".to_string(), "api-0".to_string());
+        let targets = HashMap::from([(
+            key.clone(),
+            PodTarget {
+                uid: "uid-1".into(),
+                limit: Some(4 << 20),
+                containers: HashMap::from([("api".to_string(), 100)]),
+            },
+        )]);
+
+        let blob = empty_dir.join("blob");
+        std::fs::create_dir_all(proc_root.join("100/fd")).unwrap();
+        std::os::unix::fs::symlink(&blob, proc_root.join("100/fd/3")).unwrap();
+        let file = FileId::from_metadata(&std::fs::metadata(&blob).unwrap());
+        let mut writes = PidWrites::default();
+        writes.comm[..6].copy_from_slice(b"cacher");
+        writes.record(Some(file), 3 << 20);
+        writes.record(None, 1 << 10);
+        watcher.writes.insert(100, writes);
+        let owners = HashMap::from([(100, (key.clone(), "api".to_string()))]);
+        watcher.record_writes(&owners, 1_000);
+
+        let alerts = watcher.poll(&targets, 1_000);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].severity, Severity::Medium);
+        assert!(alerts[0].message.contains(&format!(
+            "cacher[100] in api wrote 3 MiB (most to {})",
+            blob.display()
+        )));
+
+        let usage = watcher.pod("prod", "api-0").unwrap();
+        assert!(!usage.partial);
+        assert!(usage.empty_dir_bytes >= 3 << 20);
+        assert!(usage.logs_bytes >= 64 << 10);
+        assert!(usage.writable_layer_bytes >= 256 << 10);
+        assert_eq!(usage.top_writers[0].bytes, (3 << 20) + (1 << 10));
+        assert_eq!(usage.top_writers[0].top_file_bytes, Some(3 << 20));
+
+        // Same level again: no repeat
+        assert!(watcher.poll(&targets, 1_060).is_empty());
+
+        // Out of budget: a lower bound, flagged
+        let tight = EphemeralStorageWatcher::new(&EphemeralStorageConfig {
+            max_entries_per_scan: 2,
+            ..config
+        })
+        .with_roots(root.path(), &proc_root);
+        tight.poll(&targets, 1_000);
+        assert!(tight.pod("prod", "api-0").unwrap().partial);
+    }
+}
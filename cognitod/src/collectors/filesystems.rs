@@ -0,0 +1,452 @@
+//! Filesystem usage per mount
+//!
+//! Every `interval_secs` the mount table is read and each real filesystem is
+//! measured with `statvfs`. Used bytes are kept for `growth_window_secs`; a
+//! least-squares fit over that window gives the growth rate and, from it,
+//! the time until the filesystem is full ("/var full in ~3h"). Mounts that
+//! appear or disappear between samples are logged and kept as the last
+//! `MAX_MOUNT_CHANGES` changes; the first read only sets the baseline.
+//!
+//! Served at `/filesystems`. Rules with `detector = "filesystem_usage"` are
+//! evaluated after every sample.
+
+use log::{debug, info};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ffi::CString;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::alerts::RuleEngine;
+use crate::config::FilesystemsConfig;
+use crate::maintenance::glob_match;
+
+/// Mount and unmount events kept for the API.
+pub const MAX_MOUNT_CHANGES: usize = 64;
+
+/// Samples needed before a growth rate is reported.
+const MIN_GROWTH_SAMPLES: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mount {
+    pub device: String,
+    pub mount_point: String,
+    pub fs_type: String,
+}
+
+/// `\040`-style octal escapes used in `/proc/*/mounts`.
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\'
+            && i + 3 < bytes.len()
+            && let Ok(value) = u8::from_str_radix(&field[i + 1..i + 4], 8)
+        {
+            out.push(value);
+            i += 4;
+            continue;
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Parse a `/proc/*/mounts` table. A mount point mounted over keeps only the
+/// topmost entry.
+pub fn parse_mounts(content: &str) -> Vec<Mount> {
+    let mut by_point: HashMap<String, usize> = HashMap::new();
+    let mut mounts: Vec<Mount> = Vec::new();
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(device), Some(point), Some(fs_type)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let mount = Mount {
+            device: unescape(device),
+            mount_point: unescape(point),
+            fs_type: fs_type.to_string(),
+        };
+        match by_point.get(&mount.mount_point) {
+            Some(&index) => mounts[index] = mount,
+            None => {
+                by_point.insert(mount.mount_point.clone(), mounts.len());
+                mounts.push(mount);
+            }
+        }
+    }
+    mounts
+}
+
+/// `statvfs` of one mount, in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capacity {
+    pub total: u64,
+    pub free: u64,
+    /// Free space usable by unprivileged users
+    pub available: u64,
+    pub inodes_total: u64,
+    pub inodes_free: u64,
+}
+
+fn statvfs(path: &str) -> Option<Capacity> {
+    let path = CString::new(path).ok()?;
+    let mut st: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut st) } != 0 {
+        return None;
+    }
+    let block = st.f_frsize as u64;
+    Some(Capacity {
+        total: st.f_blocks as u64 * block,
+        free: st.f_bfree as u64 * block,
+        available: st.f_bavail as u64 * block,
+        inodes_total: st.f_files as u64,
+        inodes_free: st.f_ffree as u64,
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FilesystemStatus {
+    pub mount_point: String,
+    pub device: String,
+    pub fs_type: String,
+    pub total_bytes: u64,
+    pub used_bytes: u64,
+    pub available_bytes: u64,
+    /// Used share of the space available to users, as `df` reports it
+    pub used_pct: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inodes_used_pct: Option<f64>,
+    /// Bytes per second over the growth window; negative while shrinking
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub growth_bytes_per_sec: Option<f64>,
+    /// Seconds until full at the current growth rate; `None` when not growing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secs_to_full: Option<u64>,
+}
+
+impl FilesystemStatus {
+    /// `/var 91.2% used, full in ~3h`
+    pub fn describe(&self) -> String {
+        match self.secs_to_full {
+            Some(secs) => format!(
+                "{} {:.1}% used, full in ~{}",
+                self.mount_point,
+                self.used_pct,
+                approx_duration(secs)
+            ),
+            None => format!("{} {:.1}% used", self.mount_point, self.used_pct),
+        }
+    }
+}
+
+fn approx_duration(secs: u64) -> String {
+    match secs {
+        0..120 => format!("{secs}s"),
+        120..7200 => format!("{}m", secs / 60),
+        7200..172_800 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86_400),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MountChangeKind {
+    Mount,
+    Umount,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MountChange {
+    /// Unix seconds of the sample that saw the change
+    pub at: u64,
+    pub kind: MountChangeKind,
+    pub mount_point: String,
+    pub device: String,
+    pub fs_type: String,
+}
+
+/// Slope of `used` over time, in bytes per second.
+fn growth_rate(samples: &VecDeque<(u64, u64)>) -> Option<f64> {
+    if samples.len() < MIN_GROWTH_SAMPLES {
+        return None;
+    }
+    let n = samples.len() as f64;
+    let t0 = samples.front()?.0;
+    let (sum_t, sum_u) = samples.iter().fold((0.0, 0.0), |(st, su), (t, u)| {
+        (st + t.saturating_sub(t0) as f64, su + *u as f64)
+    });
+    let (mean_t, mean_u) = (sum_t / n, sum_u / n);
+    let (mut cov, mut var) = (0.0, 0.0);
+    for (t, u) in samples {
+        let dt = t.saturating_sub(t0) as f64 - mean_t;
+        cov += dt * (*u as f64 - mean_u);
+        var += dt * dt;
+    }
+    (var > 0.0).then(|| cov / var)
+}
+
+#[derive(Debug, Default)]
+struct WatcherState {
+    /// `None` until the first sample
+    mounts: Option<Vec<Mount>>,
+    /// Mount point -> (unix secs, used bytes) within the growth window
+    history: HashMap<String, VecDeque<(u64, u64)>>,
+    filesystems: Vec<FilesystemStatus>,
+    changes: VecDeque<MountChange>,
+}
+
+pub struct FilesystemWatcher {
+    mounts_file: PathBuf,
+    exclude_types: HashSet<String>,
+    exclude_mounts: Vec<String>,
+    growth_window_secs: u64,
+    state: Mutex<WatcherState>,
+}
+
+impl FilesystemWatcher {
+    pub fn new(config: &FilesystemsConfig) -> Self {
+        Self {
+            mounts_file: PathBuf::from(&config.mounts_file),
+            exclude_types: config.exclude_types.iter().cloned().collect(),
+            exclude_mounts: config.exclude_mounts.clone(),
+            growth_window_secs: config.growth_window_secs,
+            state: Mutex::new(WatcherState::default()),
+        }
+    }
+
+    /// Latest sample of every watched filesystem, sorted by mount point.
+    pub fn filesystems(&self) -> Vec<FilesystemStatus> {
+        self.state.lock().unwrap().filesystems.clone()
+    }
+
+    /// Recent mounts and unmounts, newest first.
+    pub fn changes(&self) -> Vec<MountChange> {
+        self.state
+            .lock()
+            .unwrap()
+            .changes
+            .iter()
+            .rev()
+            .cloned()
+            .collect()
+    }
+
+    fn watched(&self, mount: &Mount) -> bool {
+        !self.exclude_types.contains(&mount.fs_type)
+            && !self
+                .exclude_mounts
+                .iter()
+                .any(|pattern| glob_match(pattern, &mount.mount_point))
+    }
+
+    /// Record one sample of `mounts` taken at unix second `now`, measuring
+    /// each with `stat`. Returns the mount table changes since the previous
+    /// sample.
+    fn record(
+        &self,
+        now: u64,
+        mounts: Vec<Mount>,
+        stat: impl Fn(&str) -> Option<Capacity>,
+    ) -> Vec<MountChange> {
+        let mounts: Vec<Mount> = mounts.into_iter().filter(|m| self.watched(m)).collect();
+        let mut state = self.state.lock().unwrap();
+
+        let mut changes = Vec::new();
+        if let Some(previous) = &state.mounts {
+            let change = |kind, mount: &Mount| MountChange {
+                at: now,
+                kind,
+                mount_point: mount.mount_point.clone(),
+                device: mount.device.clone(),
+                fs_type: mount.fs_type.clone(),
+            };
+            for mount in previous.iter().filter(|m| !mounts.contains(m)) {
+                changes.push(change(MountChangeKind::Umount, mount));
+            }
+            for mount in mounts.iter().filter(|m| !previous.contains(m)) {
+                changes.push(change(MountChangeKind::Mount, mount));
+            }
+        }
+        for change in &changes {
+            if state.changes.len() >= MAX_MOUNT_CHANGES {
+                state.changes.pop_front();
+            }
+            state.changes.push_back(change.clone());
+        }
+
+        let cutoff = now.saturating_sub(self.growth_window_secs);
+        let mut filesystems = Vec::new();
+        for mount in &mounts {
+            let Some(capacity) = stat(&mount.mount_point) else {
+                debug!("[filesystems] statvfs failed for {}", mount.mount_point);
+                continue;
+            };
+            if capacity.total == 0 {
+                continue;
+            }
+            let used = capacity.total.saturating_sub(capacity.free);
+            let usable = used + capacity.available;
+            let samples = state.history.entry(mount.mount_point.clone()).or_default();
+            samples.push_back((now, used));
+            while samples.front().is_some_and(|(t, _)| *t < cutoff) {
+                samples.pop_front();
+            }
+            let growth = growth_rate(samples);
+            let secs_to_full = growth
+                .filter(|rate| *rate > 0.0)
+                .map(|rate| (capacity.available as f64 / rate) as u64);
+            filesystems.push(FilesystemStatus {
+                mount_point: mount.mount_point.clone(),
+                device: mount.device.clone(),
+                fs_type: mount.fs_type.clone(),
+                total_bytes: capacity.total,
+                used_bytes: used,
+                available_bytes: capacity.available,
+                used_pct: if usable == 0 {
+                    0.0
+                } else {
+                    used as f64 * 100.0 / usable as f64
+                },
+                inodes_used_pct: (capacity.inodes_total > 0).then(|| {
+                    capacity.inodes_total.saturating_sub(capacity.inodes_free) as f64 * 100.0
+                        / capacity.inodes_total as f64
+                }),
+                growth_bytes_per_sec: growth,
+                secs_to_full,
+            });
+        }
+        filesystems.sort_by(|a, b| a.mount_point.cmp(&b.mount_point));
+        state
+            .history
+            .retain(|point, _| mounts.iter().any(|m| &m.mount_point == point));
+        state.filesystems = filesystems;
+        state.mounts = Some(mounts);
+        changes
+    }
+
+    /// Read the mount table and measure every watched filesystem.
+    pub fn sample(&self) -> Vec<MountChange> {
+        let content = match std::fs::read_to_string(&self.mounts_file) {
+            Ok(content) => content,
+            Err(e) => {
+                debug!(
+                    "[filesystems] failed to read {}: {e}",
+                    self.mounts_file.display()
+                );
+                return Vec::new();
+            }
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.record(now, parse_mounts(&content), statvfs)
+    }
+
+    pub async fn run(self: Arc<Self>, rules: Option<Arc<RuleEngine>>, interval: Duration) {
+        info!(
+            "[filesystems] sampling mounts from {} every {}s",
+            self.mounts_file.display(),
+            interval.as_secs()
+        );
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            // statvfs can block on unresponsive network filesystems
+            let watcher = Arc::clone(&self);
+            let Ok(changes) = tokio::task::spawn_blocking(move || watcher.sample()).await else {
+                continue;
+            };
+            for change in &changes {
+                let verb = match change.kind {
+                    MountChangeKind::Mount => "mounted",
+                    MountChangeKind::Umount => "unmounted",
+                };
+                info!(
+                    "[filesystems] {} {verb} ({} on {})",
+                    change.mount_point, change.fs_type, change.device
+                );
+            }
+            if let Some(rules) = &rules {
+                rules.on_filesystems(&self.filesystems()).await;
+            }
+        }
+    }
+}
+
+/// Start sampling filesystems. `rules` evaluates `filesystem_usage` rules
+/// after each sample.
+pub fn spawn(config: &FilesystemsConfig, rules: Option<Arc<RuleEngine>>) -> Arc<FilesystemWatcher> {
+    let watcher = Arc::new(FilesystemWatcher::new(config));
+    let interval = Duration::from_secs(config.interval_secs.max(1));
+    tokio::spawn(Arc::clone(&watcher).run(rules, interval));
+    watcher
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: u64 = 1 << 20;
+    const TOTAL: u64 = 102_400 * MIB;
+
+    #[test]
+    fn predicts_time_to_full_and_tracks_mount_changes() {
+        let watcher = FilesystemWatcher::new(&FilesystemsConfig::default());
+        let table = |extra: &str| {
+            parse_mounts(&format!(
+                "/dev/sda1 / ext4 rw 0 0\n\
+                 proc /proc proc rw 0 0\n\
+                 /dev/sda2 /var xfs rw 0 0\n\
+                 overlay /var/lib/docker/overlay2/abc/merged overlay rw 0 0\n\
+                 {extra}"
+            ))
+        };
+        // /var grows 1 MiB/s and has 3h of space left after the third sample
+        let stat = |free_mib: u64| {
+            move |path: &str| {
+                let free = if path == "/var" { free_mib } else { 51_200 } * MIB;
+                Some(Capacity {
+                    total: TOTAL,
+                    free,
+                    available: free,
+                    inodes_total: 1000,
+                    inodes_free: 250,
+                })
+            }
+        };
+        assert!(watcher.record(0, table(""), stat(18_000)).is_empty());
+        let mounted = watcher.record(
+            3600,
+            table("/dev/sdb1 /mnt/backup\\040disk ext4 rw 0 0\n"),
+            stat(14_400),
+        );
+        assert_eq!(mounted.len(), 1);
+        assert_eq!(mounted[0].kind, MountChangeKind::Mount);
+        assert_eq!(mounted[0].mount_point, "/mnt/backup disk");
+        let unmounted = watcher.record(7200, table(""), stat(10_800));
+        assert_eq!(unmounted[0].kind, MountChangeKind::Umount);
+        assert_eq!(watcher.changes()[0].kind, MountChangeKind::Umount);
+
+        let filesystems = watcher.filesystems();
+        let points: Vec<&str> = filesystems.iter().map(|f| f.mount_point.as_str()).collect();
+        assert_eq!(
+            points,
+            ["/", "/var"],
+            "pseudo and container mounts excluded"
+        );
+        let var = &filesystems[1];
+        assert_eq!(var.used_pct, 89.453125);
+        assert_eq!(var.inodes_used_pct, Some(75.0));
+        assert_eq!(var.growth_bytes_per_sec, Some(MIB as f64));
+        assert_eq!(var.secs_to_full, Some(3 * 3600));
+        assert_eq!(var.describe(), "/var 89.5% used, full in ~3h");
+        assert_eq!(filesystems[0].secs_to_full, None, "flat usage never fills");
+    }
+}
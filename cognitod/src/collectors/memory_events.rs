@@ -0,0 +1,339 @@
+//! cgroup v2 `memory.events` watcher
+//!
+//! The kernel counts limit hits (`max`), failed charges (`oom`) and OOM kills
+//! per cgroup in `memory.events`, but nothing is pushed when they change. This
+//! polls the file for every cgroup that holds a tracked process and turns
+//! increments of `max`, `oom`, `oom_kill` and `oom_group_kill` into
+//! `cgroup_memory_event` alerts attributed to the owning pod. The first read
+//! of a cgroup only sets the baseline, so restarts do not replay old kills.
+
+use log::{debug, info};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::alerts::{Alert, AlertSender, Severity};
+use crate::collectors::cgroup_tree;
+use crate::context::ContextStore;
+use crate::k8s::K8sMetadata;
+
+pub const RULE_NAME: &str = "cgroup_memory_event";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct MemoryEvents {
+    pub low: u64,
+    pub high: u64,
+    pub max: u64,
+    pub oom: u64,
+    pub oom_kill: u64,
+    pub oom_group_kill: u64,
+}
+
+/// Parse `memory.events`; unknown keys are ignored and missing ones are 0
+/// (`oom_group_kill` only exists since 5.13).
+pub fn parse_memory_events(content: &str) -> MemoryEvents {
+    let mut events = MemoryEvents::default();
+    for line in content.lines() {
+        let mut parts = line.split_whitespace();
+        let (Some(key), Some(value)) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        let Ok(value) = value.parse() else {
+            continue;
+        };
+        match key {
+            "low" => events.low = value,
+            "high" => events.high = value,
+            "max" => events.max = value,
+            "oom" => events.oom = value,
+            "oom_kill" => events.oom_kill = value,
+            "oom_group_kill" => events.oom_group_kill = value,
+            _ => {}
+        }
+    }
+    events
+}
+
+impl MemoryEvents {
+    /// Alerting counters that grew since `prev`, most severe first. A counter
+    /// that went backwards (cgroup recreated under the same path) is ignored.
+    fn increments(&self, prev: &MemoryEvents) -> Vec<(&'static str, u64, Severity)> {
+        [
+            ("oom_group_kill", self.oom_group_kill, prev.oom_group_kill),
+            ("oom_kill", self.oom_kill, prev.oom_kill),
+            ("oom", self.oom, prev.oom),
+            ("max", self.max, prev.max),
+        ]
+        .into_iter()
+        .filter(|(_, cur, prev)| cur > prev)
+        .map(|(name, cur, prev)| {
+            let severity = match name {
+                "oom_group_kill" | "oom_kill" => Severity::High,
+                "oom" => Severity::Medium,
+                _ => Severity::Low,
+            };
+            (name, cur - prev, severity)
+        })
+        .collect()
+    }
+}
+
+/// Counters of one watched cgroup, with the pod that owns it if known.
+#[derive(Debug, Clone, Serialize)]
+pub struct CgroupMemoryEvents {
+    /// Path relative to the cgroup root.
+    pub cgroup: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pod: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    pub counters: MemoryEvents,
+}
+
+pub struct MemoryEventsWatcher {
+    root: PathBuf,
+    host: String,
+    /// pid -> cgroup path; processes rarely migrate, so resolve once.
+    pid_cgroups: Mutex<HashMap<u32, String>>,
+    cgroups: Mutex<HashMap<String, CgroupMemoryEvents>>,
+}
+
+impl MemoryEventsWatcher {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            host: std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown".into()),
+            pid_cgroups: Mutex::new(HashMap::new()),
+            cgroups: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Current counters of every watched cgroup, sorted by path.
+    pub fn snapshot(&self) -> Vec<CgroupMemoryEvents> {
+        let mut out: Vec<_> = self.cgroups.lock().unwrap().values().cloned().collect();
+        out.sort_by(|a, b| a.cgroup.cmp(&b.cgroup));
+        out
+    }
+
+    /// Counters of the cgroups owned by one pod.
+    pub fn pod(&self, namespace: &str, pod: &str) -> Vec<CgroupMemoryEvents> {
+        self.snapshot()
+            .into_iter()
+            .filter(|c| c.namespace.as_deref() == Some(namespace) && c.pod.as_deref() == Some(pod))
+            .collect()
+    }
+
+    /// Cgroups of live processes with their pod metadata. The root cgroup has
+    /// no `memory.events` and is skipped.
+    fn targets(&self, context: &ContextStore) -> HashMap<String, Option<Arc<K8sMetadata>>> {
+        let live = context.get_live_map();
+        let mut pid_cgroups = self.pid_cgroups.lock().unwrap();
+        pid_cgroups.retain(|pid, _| live.contains_key(pid));
+        let mut targets: HashMap<String, Option<Arc<K8sMetadata>>> = HashMap::new();
+        for (pid, (_, meta)) in live.iter() {
+            let path = match pid_cgroups.get(pid) {
+                Some(path) => path.clone(),
+                None => {
+                    let Some(path) = cgroup_tree::process_cgroup_path(*pid) else {
+                        continue;
+                    };
+                    pid_cgroups.insert(*pid, path.clone());
+                    path
+                }
+            };
+            if path == "/" {
+                continue;
+            }
+            let slot = targets.entry(path).or_default();
+            if slot.is_none() {
+                *slot = meta.clone();
+            }
+        }
+        targets
+    }
+
+    /// Read `memory.events` for each target and return alerts for counters
+    /// that grew since the previous poll. Cgroups no longer in `targets` are
+    /// forgotten.
+    pub fn poll(&self, targets: &HashMap<String, Option<Arc<K8sMetadata>>>) -> Vec<Alert> {
+        let mut cgroups = self.cgroups.lock().unwrap();
+        cgroups.retain(|path, _| targets.contains_key(path));
+        let mut alerts = Vec::new();
+        for (path, meta) in targets {
+            let file = self
+                .root
+                .join(path.trim_start_matches('/'))
+                .join("memory.events");
+            let Ok(content) = std::fs::read_to_string(&file) else {
+                debug!("[memory-events] failed to read {}", file.display());
+                continue;
+            };
+            let counters = parse_memory_events(&content);
+            let entry = cgroups
+                .entry(path.clone())
+                .or_insert_with(|| CgroupMemoryEvents {
+                    cgroup: path.clone(),
+                    namespace: None,
+                    pod: None,
+                    container: None,
+                    counters,
+                });
+            if let Some(meta) = meta {
+                entry.namespace = Some(meta.namespace.clone());
+                entry.pod = Some(meta.pod_name.clone());
+                entry.container = Some(meta.container_name.clone());
+            }
+            let increments = counters.increments(&entry.counters);
+            entry.counters = counters;
+            if let Some((_, _, severity)) = increments.first() {
+                alerts.push(Alert {
+                    rule: RULE_NAME.to_string(),
+                    severity: severity.clone(),
+                    message: describe(entry, &increments),
+                    host: self.host.clone(),
+                    detection: "threshold",
+                    suppressed_by: None,
+                    lineage: Vec::new(),
+                    children: Vec::new(),
+                    pod: None,
+                    evidence: None,
+                    annotations: None,
+                });
+            }
+        }
+        alerts
+    }
+
+    pub async fn run(
+        self: Arc<Self>,
+        context: Arc<ContextStore>,
+        tx: Option<AlertSender>,
+        interval: Duration,
+    ) {
+        info!(
+            "[memory-events] watching memory.events under {} every {}s",
+            self.root.display(),
+            interval.as_secs()
+        );
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let targets = self.targets(&context);
+            for alert in self.poll(&targets) {
+                info!("[memory-events] {}", alert.message);
+                if let Some(tx) = &tx {
+                    tx.send(alert);
+                }
+            }
+        }
+    }
+}
+
+fn describe(cgroup: &CgroupMemoryEvents, increments: &[(&'static str, u64, Severity)]) -> String {
+    let deltas = increments
+        .iter()
+        .map(|(name, delta, _)| format!("{name} +{delta}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    match (&cgroup.namespace, &cgroup.pod) {
+        (Some(ns), Some(pod)) => format!(
+            "memory.events {deltas} in pod {ns}/{pod} (container {}), cgroup {}",
+            cgroup.container.as_deref().unwrap_or("-"),
+            cgroup.cgroup
+        ),
+        _ => format!("memory.events {deltas} in cgroup {}", cgroup.cgroup),
+    }
+}
+
+/// Start polling the cgroups of tracked processes. Alerts go to `tx` when a
+/// rules engine is loaded; counters are kept either way for the API.
+pub fn spawn(
+    context: Arc<ContextStore>,
+    tx: Option<AlertSender>,
+    interval: Duration,
+) -> Arc<MemoryEventsWatcher> {
+    let watcher = Arc::new(MemoryEventsWatcher::new(cgroup_tree::cgroup_root()));
+    tokio::spawn(Arc::clone(&watcher).run(context, tx, interval));
+    watcher
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::k8s::Priority;
+
+    fn write_events(dir: &std::path::Path, max: u64, oom: u64, oom_kill: u64) {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(
+            dir.join("memory.events"),
+            format!("low 0\nhigh 4\nmax {max}\noom {oom}\noom_kill {oom_kill}\n"),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn increments_raise_attributed_alerts() {
+        let root = tempfile::tempdir().unwrap();
+        let pod_cg = "/kubepods.slice/pod-a/cri-1";
+        let host_cg = "/system.slice/cron.service";
+        write_events(&root.path().join(&pod_cg[1..]), 2, 0, 0);
+        write_events(&root.path().join(&host_cg[1..]), 0, 0, 0);
+
+        let meta = Arc::new(K8sMetadata {
+            pod_name: "api-0".into(),
+            namespace: "prod".into(),
+            container_name: "api".into(),
+            owner_kind: None,
+            owner_name: None,
+            priority: Priority::default(),
+            slo_tier: None,
+            pod_uid: None,
+            ephemeral_storage_limit: None,
+        });
+        let mut targets = HashMap::from([
+            (pod_cg.to_string(), Some(meta)),
+            (host_cg.to_string(), None),
+        ]);
+        let watcher = MemoryEventsWatcher::new(root.path());
+        assert!(watcher.poll(&targets).is_empty(), "first read is baseline");
+
+        write_events(&root.path().join(&pod_cg[1..]), 5, 1, 1);
+        let alerts = watcher.poll(&targets);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].rule, RULE_NAME);
+        assert_eq!(alerts[0].severity, Severity::High);
+        assert_eq!(
+            alerts[0].message,
+            "memory.events oom_kill +1, oom +1, max +3 in pod prod/api-0 (container api), \
+cgroup /kubepods.slice/pod-a/cri-1"
+        );
+        assert!(watcher.poll(&targets).is_empty(), "no change, no alert");
+
+        let pod = watcher.pod("prod", "api-0");
+        assert_eq!(pod.len(), 1);
+        assert_eq!(pod[0].counters.oom_kill, 1);
+        assert_eq!(pod[0].counters.high, 4);
+
+        targets.remove(pod_cg);
+        watcher.poll(&targets);
+        assert_eq!(watcher.snapshot().len(), 1, "vanished cgroups are dropped");
+    }
+
+    #[test]
+    fn parses_partial_files() {
+        let events = parse_memory_events("max 7\noom_group_kill 2\nbogus x\n");
+        assert_eq!(events.max, 7);
+        assert_eq!(events.oom_group_kill, 2);
+        assert_eq!(events.oom_kill, 0);
+        let prev = MemoryEvents {
+            max: 9,
+            ..Default::default()
+        };
+        assert!(events.increments(&prev)[0].0 == "oom_group_kill");
+        assert_eq!(events.increments(&prev).len(), 1, "reset counters ignored");
+    }
+}
@@ -1 +1,8 @@
+pub mod cgroup_tree;
+pub mod ephemeral_storage;
+pub mod filesystems;
+pub mod memory_events;
+pub mod power;
 pub mod psi;
+pub mod smart;
+pub mod sock_diag;
@@ -0,0 +1,354 @@
+//! Energy estimates from RAPL package counters
+//!
+//! RAPL (`/sys/class/powercap/intel-rapl:N`, also exposed by AMD Zen) reports
+//! cumulative package energy. Each interval the measured energy is split
+//! across live processes by their share of CPU time and summed per pod.
+//! These are estimates: package energy includes idle, uncore and memory
+//! controller power, which is attributed to whoever was using CPU, and GPU or
+//! DRAM-heavy workloads are under-counted. Metric names carry `estimated` to
+//! keep that visible downstream.
+
+use log::{debug, info, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::context::ContextStore;
+
+pub const POWERCAP_ROOT: &str = "/sys/class/powercap";
+
+/// Label used for processes outside any pod.
+pub const HOST_BUCKET: &str = "host";
+
+#[derive(Debug)]
+struct RaplZone {
+    name: String,
+    energy_path: PathBuf,
+    max_range_uj: u64,
+    last_uj: Option<u64>,
+    joules_total: f64,
+    watts: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PackageEnergy {
+    pub package: String,
+    pub joules_total: f64,
+    pub watts: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PodEnergy {
+    pub namespace: String,
+    pub pod: String,
+    pub estimated_joules_total: f64,
+    pub estimated_watts: f64,
+    /// Share of CPU time during the last interval, 0..1.
+    pub cpu_share: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PowerReport {
+    pub packages: Vec<PackageEnergy>,
+    pub pods: Vec<PodEnergy>,
+    /// Energy attributed to processes outside pods.
+    pub host: PodEnergy,
+}
+
+/// Top-level `intel-rapl:N` package zones. Subzones (`intel-rapl:N:M`) are
+/// contained in their package and skipped to avoid double counting.
+fn discover_packages(root: &Path) -> Vec<RaplZone> {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut zones: Vec<RaplZone> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let dir_name = entry.file_name().to_string_lossy().into_owned();
+            let index = dir_name.strip_prefix("intel-rapl:")?;
+            if index.contains(':') {
+                return None;
+            }
+            let dir = entry.path();
+            let name = std::fs::read_to_string(dir.join("name"))
+                .map(|s| s.trim().to_string())
+                .unwrap_or(dir_name);
+            let max_range_uj = std::fs::read_to_string(dir.join("max_energy_range_uj"))
+                .ok()
+                .and_then(|s| s.trim().parse().ok())
+                .unwrap_or(u64::MAX);
+            Some(RaplZone {
+                name,
+                energy_path: dir.join("energy_uj"),
+                max_range_uj,
+                last_uj: None,
+                joules_total: 0.0,
+                watts: 0.0,
+            })
+        })
+        .collect();
+    zones.sort_by(|a, b| a.name.cmp(&b.name));
+    zones
+}
+
+/// Energy consumed between two counter readings, allowing for one wrap.
+fn counter_delta_uj(prev: u64, cur: u64, max_range_uj: u64) -> u64 {
+    if cur >= prev {
+        cur - prev
+    } else {
+        max_range_uj.saturating_sub(prev).saturating_add(cur)
+    }
+}
+
+/// Split `joules` across keys in proportion to their CPU usage. Returns an
+/// empty map when nothing used CPU.
+pub fn apportion<K: std::hash::Hash + Eq + Clone>(
+    joules: f64,
+    usage: &HashMap<K, f64>,
+) -> HashMap<K, f64> {
+    let total: f64 = usage.values().filter(|v| **v > 0.0).sum();
+    if total <= 0.0 {
+        return HashMap::new();
+    }
+    usage
+        .iter()
+        .map(|(k, v)| (k.clone(), joules * v.max(0.0) / total))
+        .collect()
+}
+
+pub struct PowerMonitor {
+    zones: Mutex<Vec<RaplZone>>,
+    pods: Mutex<HashMap<(String, String), PodEnergy>>,
+    last_sample: Mutex<Option<Instant>>,
+    report: RwLock<PowerReport>,
+}
+
+impl PowerMonitor {
+    /// `None` when the node exposes no readable RAPL package counters.
+    pub fn discover(root: &Path) -> Option<Self> {
+        let zones: Vec<RaplZone> = discover_packages(root)
+            .into_iter()
+            .filter(|zone| std::fs::read_to_string(&zone.energy_path).is_ok())
+            .collect();
+        if zones.is_empty() {
+            return None;
+        }
+        Some(Self {
+            zones: Mutex::new(zones),
+            pods: Mutex::new(HashMap::new()),
+            last_sample: Mutex::new(None),
+            report: RwLock::new(PowerReport::default()),
+        })
+    }
+
+    pub fn report(&self) -> PowerReport {
+        self.report.read().unwrap().clone()
+    }
+
+    /// Estimated energy of one pod, if it has been seen.
+    pub fn pod(&self, namespace: &str, pod: &str) -> Option<PodEnergy> {
+        self.pods
+            .lock()
+            .unwrap()
+            .get(&(namespace.to_string(), pod.to_string()))
+            .cloned()
+    }
+
+    /// Read the counters and attribute the energy used since the last call.
+    pub fn sample(&self, context: &ContextStore) {
+        let now = Instant::now();
+        let elapsed = self
+            .last_sample
+            .lock()
+            .unwrap()
+            .replace(now)
+            .map(|prev| now.duration_since(prev).as_secs_f64());
+
+        let mut joules = 0.0;
+        let packages = {
+            let mut zones = self.zones.lock().unwrap();
+            for zone in zones.iter_mut() {
+                let Some(cur) = std::fs::read_to_string(&zone.energy_path)
+                    .ok()
+                    .and_then(|s| s.trim().parse::<u64>().ok())
+                else {
+                    debug!("[power] failed to read {}", zone.energy_path.display());
+                    continue;
+                };
+                if let Some(prev) = zone.last_uj.replace(cur) {
+                    let delta = counter_delta_uj(prev, cur, zone.max_range_uj) as f64 / 1e6;
+                    zone.joules_total += delta;
+                    zone.watts = elapsed.filter(|s| *s > 0.0).map_or(0.0, |s| delta / s);
+                    joules += delta;
+                }
+            }
+            zones
+                .iter()
+                .map(|zone| PackageEnergy {
+                    package: zone.name.clone(),
+                    joules_total: zone.joules_total,
+                    watts: zone.watts,
+                })
+                .collect()
+        };
+        let Some(elapsed) = elapsed.filter(|s| *s > 0.0) else {
+            self.report.write().unwrap().packages = packages;
+            return;
+        };
+
+        let mut usage: HashMap<(String, String), f64> = HashMap::new();
+        for (proc, meta) in context.get_live_map().values() {
+            let Some(cpu) = proc.cpu_percent() else {
+                continue;
+            };
+            let key = meta.as_ref().map_or_else(
+                || (String::new(), HOST_BUCKET.to_string()),
+                |m| (m.namespace.clone(), m.pod_name.clone()),
+            );
+            *usage.entry(key).or_default() += cpu as f64;
+        }
+        let total_cpu: f64 = usage.values().sum();
+        let shares = apportion(joules, &usage);
+
+        let mut pods = self.pods.lock().unwrap();
+        pods.retain(|key, _| usage.contains_key(key));
+        for (key, cpu) in &usage {
+            let share = shares.get(key).copied().unwrap_or(0.0);
+            let entry = pods.entry(key.clone()).or_insert_with(|| PodEnergy {
+                namespace: key.0.clone(),
+                pod: key.1.clone(),
+                ..Default::default()
+            });
+            entry.estimated_joules_total += share;
+            entry.estimated_watts = share / elapsed;
+            entry.cpu_share = if total_cpu > 0.0 {
+                cpu / total_cpu
+            } else {
+                0.0
+            };
+        }
+        let host_key = (String::new(), HOST_BUCKET.to_string());
+        let mut report = PowerReport {
+            packages,
+            pods: pods
+                .iter()
+                .filter(|(key, _)| **key != host_key)
+                .map(|(_, pod)| pod.clone())
+                .collect(),
+            host: pods.get(&host_key).cloned().unwrap_or_else(|| PodEnergy {
+                pod: HOST_BUCKET.to_string(),
+                ..Default::default()
+            }),
+        };
+        report
+            .pods
+            .sort_by(|a, b| (&a.namespace, &a.pod).cmp(&(&b.namespace, &b.pod)));
+        *self.report.write().unwrap() = report;
+    }
+
+    pub async fn run(self: Arc<Self>, context: Arc<ContextStore>, interval: Duration) {
+        let packages: Vec<String> = self
+            .zones
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|z| z.name.clone())
+            .collect();
+        info!(
+            "[power] RAPL energy attribution every {}s over {}",
+            interval.as_secs(),
+            packages.join(", ")
+        );
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            self.sample(&context);
+        }
+    }
+}
+
+/// Start the collector when RAPL is available; logs and returns `None`
+/// otherwise (VMs, non-x86 hosts, or counters restricted to root).
+pub fn spawn(
+    root: &Path,
+    context: Arc<ContextStore>,
+    interval: Duration,
+) -> Option<Arc<PowerMonitor>> {
+    let Some(monitor) = PowerMonitor::discover(root) else {
+        warn!(
+            "[power] no readable RAPL package counters under {}; energy estimates disabled",
+            root.display()
+        );
+        return None;
+    };
+    let monitor = Arc::new(monitor);
+    tokio::spawn(Arc::clone(&monitor).run(context, interval));
+    Some(monitor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ProcessEvent, ProcessEventWire};
+
+    #[test]
+    fn counter_wraps_and_energy_splits_by_cpu() {
+        assert_eq!(counter_delta_uj(100, 250, 1000), 150);
+        assert_eq!(counter_delta_uj(900, 50, 1000), 150);
+
+        let usage = HashMap::from([("a", 30.0), ("b", 10.0), ("idle", 0.0)]);
+        let split = apportion(8.0, &usage);
+        assert_eq!(split["a"], 6.0);
+        assert_eq!(split["b"], 2.0);
+        assert_eq!(split["idle"], 0.0);
+        assert!(apportion(8.0, &HashMap::from([("a", 0.0)])).is_empty());
+    }
+
+    #[test]
+    fn samples_packages_and_attributes_to_host() {
+        let root = tempfile::tempdir().unwrap();
+        for (dir, name) in [("intel-rapl:0", "package-0"), ("intel-rapl:0:0", "core")] {
+            let zone = root.path().join(dir);
+            std::fs::create_dir_all(&zone).unwrap();
+            std::fs::write(zone.join("name"), format!("{name}\n")).unwrap();
+            std::fs::write(zone.join("energy_uj"), "1000000\n").unwrap();
+            std::fs::write(zone.join("max_energy_range_uj"), "262143328850\n").unwrap();
+        }
+        let monitor = PowerMonitor::discover(root.path()).unwrap();
+        assert_eq!(monitor.zones.lock().unwrap().len(), 1, "subzones skipped");
+
+        let ctx = ContextStore::new(Duration::from_secs(60), 16, None);
+        ctx.add(ProcessEvent::new(ProcessEventWire {
+            pid: 10,
+            ppid: 1,
+            uid: 0,
+            gid: 0,
+            event_type: 0,
+            ts_ns: 0,
+            seq: 0,
+            comm: [0; 16],
+            exit_time_ns: 0,
+            cpu_pct_milli: 50_000,
+            mem_pct_milli: 1_000,
+            data: 0,
+            data2: 0,
+            aux: 0,
+            aux2: 0,
+            cgroup_id: 0,
+        }));
+        monitor.sample(&ctx);
+        std::fs::write(root.path().join("intel-rapl:0/energy_uj"), "6000000\n").unwrap();
+        monitor.sample(&ctx);
+
+        let report = monitor.report();
+        assert_eq!(report.packages.len(), 1);
+        assert_eq!(report.packages[0].package, "package-0");
+        assert_eq!(report.packages[0].joules_total, 5.0);
+        assert!(report.pods.is_empty());
+        assert_eq!(report.host.estimated_joules_total, 5.0);
+        assert_eq!(report.host.cpu_share, 1.0);
+        assert!(PowerMonitor::discover(&root.path().join("missing")).is_none());
+    }
+}
@@ -8,6 +8,7 @@ use tokio::time::sleep;
 use walkdir::WalkDir;
 
 use crate::context::ContextStore;
+use crate::incidents::{IncidentWrite, StallAttribution};
 use crate::k8s::K8sContext;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -242,22 +243,27 @@ impl PsiMonitor {
                                 // Persist to database if available
                                 if let Some(ref store) = self.incident_store {
                                     for attr in &attributions {
-                                        if let Err(e) = store
-                                            .insert_stall_attribution(
-                                                &attr.victim_pod,
-                                                &attr.victim_namespace,
-                                                &attr.offender_pod,
-                                                &attr.offender_namespace,
-                                                attr.stall_us,
-                                                attr.blame_score,
-                                                attr.timestamp,
-                                                attr.cpu_share,
-                                                attr.fork_count,
-                                                attr.short_job_count,
-                                            )
-                                            .await
-                                        {
-                                            debug!("[psi] Failed to persist attribution: {}", e);
+                                        let queued =
+                                            store.submit(IncidentWrite::StallAttribution {
+                                                victim_pod: attr.victim_pod.clone(),
+                                                victim_namespace: attr.victim_namespace.clone(),
+                                                attribution: StallAttribution {
+                                                    offender_pod: attr.offender_pod.clone(),
+                                                    offender_namespace: attr
+                                                        .offender_namespace
+                                                        .clone(),
+                                                    stall_us: attr.stall_us,
+                                                    blame_score: attr.blame_score,
+                                                    timestamp: attr.timestamp,
+                                                    cpu_share: attr.cpu_share,
+                                                    fork_count: attr.fork_count,
+                                                    short_job_count: attr.short_job_count,
+                                                },
+                                            });
+                                        if !queued {
+                                            debug!(
+                                                "[psi] Attribution dropped: incident write queue full"
+                                            );
                                         }
                                     }
                                 }
@@ -432,6 +438,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "k8s")]
     fn test_calculate_blame_attributions_with_forks() {
         // Set env vars to force K8sContext creation
         unsafe {
@@ -0,0 +1,522 @@
+//! SMART health and SSD wear per disk
+//!
+//! Every `interval_secs` each configured device (or everything
+//! `smartctl --scan` finds) is read with `smartctl -a -j`. NVMe health logs
+//! and ATA attributes are reduced to one [`DeviceHealth`]: overall verdict,
+//! temperature, wear, spare capacity and error counters. Wear is kept for
+//! `wear_window_secs` to estimate how fast it grows. A `disk_health` alert is
+//! raised when a device starts failing a check (verdict, critical warning,
+//! wear, spare, temperature, wear rate), and on every poll where its media
+//! error or bad sector counters grew. A check re-arms once it passes again.
+
+use log::{debug, info, warn};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::alerts::{Alert, AlertSender, Severity};
+use crate::config::SmartConfig;
+
+pub const RULE_NAME: &str = "disk_health";
+
+/// Shortest wear history a rate is estimated from. `percentage_used` moves
+/// in whole percents, so shorter spans exaggerate.
+const MIN_RATE_SPAN_SECS: u64 = 86_400;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DeviceHealth {
+    pub device: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub serial: Option<String>,
+    /// `NVMe`, `ATA` or `SCSI`, as smartctl reports it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    /// SMART overall-health self-assessment.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub passed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature_c: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub power_on_hours: Option<u64>,
+    /// Rated endurance used; NVMe `percentage_used` or the ATA wear
+    /// attribute. Can exceed 100.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wear_used_pct: Option<f64>,
+    /// Wear growth over the kept history, percent per day.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wear_pct_per_day: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub available_spare_pct: Option<f64>,
+    /// Spare level below which the drive itself reports a problem.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spare_threshold_pct: Option<f64>,
+    /// NVMe critical warning bits; 0 is healthy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub critical_warning: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media_errors: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reallocated_sectors: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending_sectors: Option<u64>,
+    /// Unix seconds of the last read attempt.
+    pub polled_at: u64,
+    /// Why the last read failed; the other fields are from the last success.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// ATA attribute with its normalised value and raw counter.
+fn ata_attribute(json: &Value, id: u64) -> Option<(u64, u64)> {
+    json.pointer("/ata_smart_attributes/table")?
+        .as_array()?
+        .iter()
+        .find(|attr| attr.get("id").and_then(Value::as_u64) == Some(id))
+        .and_then(|attr| {
+            Some((
+                attr.get("value")?.as_u64()?,
+                attr.pointer("/raw/value")?.as_u64()?,
+            ))
+        })
+}
+
+/// Reduce `smartctl -a -j` output for `device`.
+pub fn parse_smartctl(device: &str, json: &Value, polled_at: u64) -> DeviceHealth {
+    let text = |path: &str| json.pointer(path).and_then(Value::as_str).map(String::from);
+    let number = |path: &str| json.pointer(path).and_then(Value::as_f64);
+    let count = |path: &str| json.pointer(path).and_then(Value::as_u64);
+    let nvme = "/nvme_smart_health_information_log";
+
+    let mut health = DeviceHealth {
+        device: device.to_string(),
+        model: text("/model_name"),
+        serial: text("/serial_number"),
+        protocol: text("/device/protocol"),
+        passed: json
+            .pointer("/smart_status/passed")
+            .and_then(Value::as_bool),
+        temperature_c: number("/temperature/current"),
+        power_on_hours: count("/power_on_time/hours"),
+        wear_used_pct: number(&format!("{nvme}/percentage_used")),
+        available_spare_pct: number(&format!("{nvme}/available_spare")),
+        spare_threshold_pct: number(&format!("{nvme}/available_spare_threshold")),
+        critical_warning: count(&format!("{nvme}/critical_warning")),
+        media_errors: count(&format!("{nvme}/media_errors")),
+        polled_at,
+        ..DeviceHealth::default()
+    };
+    // ATA: 5 Reallocated_Sector_Ct, 197 Current_Pending_Sector; wear from
+    // 177 Wear_Leveling_Count, 231 SSD_Life_Left or 233 Media_Wearout_Indicator
+    // (normalised 100 when new)
+    health.reallocated_sectors = ata_attribute(json, 5).map(|(_, raw)| raw);
+    health.pending_sectors = ata_attribute(json, 197).map(|(_, raw)| raw);
+    if health.wear_used_pct.is_none() {
+        health.wear_used_pct = [177, 231, 233]
+            .into_iter()
+            .find_map(|id| ata_attribute(json, id))
+            .map(|(value, _)| 100.0 - value.min(100) as f64);
+    }
+    health
+}
+
+/// Wear growth per day between the oldest and newest kept sample, once they
+/// are at least a day apart.
+fn wear_rate(history: &VecDeque<(u64, f64)>) -> Option<f64> {
+    let (first_at, first) = history.front()?;
+    let (last_at, last) = history.back()?;
+    let span = last_at.saturating_sub(*first_at);
+    (span >= MIN_RATE_SPAN_SECS).then(|| (last - first).max(0.0) * 86_400.0 / span as f64)
+}
+
+#[derive(Default)]
+struct DeviceState {
+    health: DeviceHealth,
+    wear_history: VecDeque<(u64, f64)>,
+    /// Checks currently failing, alerted once each.
+    raised: BTreeSet<&'static str>,
+}
+
+pub struct SmartMonitor {
+    config: SmartConfig,
+    host: String,
+    devices: Mutex<BTreeMap<String, DeviceState>>,
+}
+
+impl SmartMonitor {
+    pub fn new(config: &SmartConfig) -> Self {
+        Self {
+            config: config.clone(),
+            host: std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown".into()),
+            devices: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Latest health of every polled device, by device path.
+    pub fn devices(&self) -> Vec<DeviceHealth> {
+        self.devices
+            .lock()
+            .unwrap()
+            .values()
+            .map(|state| state.health.clone())
+            .collect()
+    }
+
+    /// Store a successful read and return the alert it warrants, if any.
+    fn record(&self, mut reading: DeviceHealth) -> Option<Alert> {
+        let mut devices = self.devices.lock().unwrap();
+        let state = devices.entry(reading.device.clone()).or_default();
+        let previous = std::mem::take(&mut state.health);
+
+        if let Some(wear) = reading.wear_used_pct {
+            state.wear_history.push_back((reading.polled_at, wear));
+            let keep_from = reading
+                .polled_at
+                .saturating_sub(self.config.wear_window_secs);
+            while state
+                .wear_history
+                .front()
+                .is_some_and(|(at, _)| *at < keep_from)
+            {
+                state.wear_history.pop_front();
+            }
+            reading.wear_pct_per_day = wear_rate(&state.wear_history);
+        }
+
+        let cfg = &self.config;
+        let checks: [(&'static str, Option<(Severity, String)>); 6] = [
+            (
+                "failed",
+                (reading.passed == Some(false))
+                    .then(|| (Severity::High, "SMART health check FAILED".to_string())),
+            ),
+            (
+                "critical_warning",
+                reading
+                    .critical_warning
+                    .filter(|w| *w > 0)
+                    .map(|w| (Severity::High, format!("NVMe critical warning 0x{w:02x}"))),
+            ),
+            (
+                "spare",
+                reading
+                    .available_spare_pct
+                    .zip(reading.spare_threshold_pct)
+                    .filter(|(spare, threshold)| spare < threshold)
+                    .map(|(spare, threshold)| {
+                        (Severity::High, format!("spare {spare}% below {threshold}%"))
+                    }),
+            ),
+            (
+                "wear",
+                reading
+                    .wear_used_pct
+                    .filter(|w| *w >= cfg.wear_used_pct)
+                    .map(|w| {
+                        (
+                            Severity::Medium,
+                            format!("wear {w}% (limit {}%)", cfg.wear_used_pct),
+                        )
+                    }),
+            ),
+            (
+                "wear_rate",
+                reading
+                    .wear_pct_per_day
+                    .filter(|r| *r >= cfg.max_wear_pct_per_day)
+                    .map(|r| {
+                        (
+                            Severity::Medium,
+                            format!(
+                                "wearing {r:.2}%/day (limit {}%/day)",
+                                cfg.max_wear_pct_per_day
+                            ),
+                        )
+                    }),
+            ),
+            (
+                "temperature",
+                reading
+                    .temperature_c
+                    .filter(|t| *t >= cfg.temperature_c)
+                    .map(|t| {
+                        (
+                            Severity::Medium,
+                            format!("temperature {t}C (limit {}C)", cfg.temperature_c),
+                        )
+                    }),
+            ),
+        ];
+
+        let mut findings: Vec<(Severity, String)> = Vec::new();
+        for (name, failing) in checks {
+            match failing {
+                Some(finding) => {
+                    if state.raised.insert(name) {
+                        findings.push(finding);
+                    }
+                }
+                None => {
+                    state.raised.remove(name);
+                }
+            }
+        }
+        for (name, cur, prev) in [
+            ("media errors", reading.media_errors, previous.media_errors),
+            (
+                "reallocated sectors",
+                reading.reallocated_sectors,
+                previous.reallocated_sectors,
+            ),
+            (
+                "pending sectors",
+                reading.pending_sectors,
+                previous.pending_sectors,
+            ),
+        ] {
+            if let (Some(cur), Some(prev)) = (cur, prev)
+                && cur > prev
+            {
+                findings.push((Severity::High, format!("{name} +{} ({cur})", cur - prev)));
+            }
+        }
+        state.health = reading;
+
+        let severity = findings
+            .iter()
+            .map(|(severity, _)| severity.clone())
+            .reduce(|a, b| if b > a { b } else { a })?;
+        let health = &state.health;
+        let details = findings
+            .into_iter()
+            .map(|(_, detail)| detail)
+            .collect::<Vec<_>>()
+            .join("; ");
+        Some(Alert {
+            rule: RULE_NAME.to_string(),
+            severity,
+            message: format!(
+                "{} ({}, S/N {}): {details}",
+                health.device,
+                health.model.as_deref().unwrap_or("unknown model"),
+                health.serial.as_deref().unwrap_or("-")
+            ),
+            host: self.host.clone(),
+            detection: "threshold",
+            suppressed_by: None,
+            lineage: Vec::new(),
+            children: Vec::new(),
+            pod: None,
+            evidence: None,
+            annotations: None,
+        })
+    }
+
+    /// Keep the last good reading of `device` and note why this one failed.
+    fn record_error(&self, device: &str, polled_at: u64, error: String) {
+        let mut devices = self.devices.lock().unwrap();
+        let state = devices.entry(device.to_string()).or_default();
+        if state.health.error.is_none() {
+            warn!("[smart] failed to read {device}: {error}");
+        }
+        state.health.device = device.to_string();
+        state.health.polled_at = polled_at;
+        state.health.error = Some(error);
+    }
+
+    fn discover(&self) -> Vec<String> {
+        if !self.config.devices.is_empty() {
+            return self.config.devices.clone();
+        }
+        let output = match std::process::Command::new(&self.config.smartctl)
+            .args(["--scan", "-j"])
+            .output()
+        {
+            Ok(output) => output,
+            Err(e) => {
+                debug!("[smart] {} --scan failed: {e}", self.config.smartctl);
+                return Vec::new();
+            }
+        };
+        serde_json::from_slice::<Value>(&output.stdout)
+            .ok()
+            .and_then(|json| {
+                json.get("devices")?.as_array().map(|devices| {
+                    devices
+                        .iter()
+                        .filter_map(|d| d.get("name")?.as_str().map(String::from))
+                        .collect()
+                })
+            })
+            .unwrap_or_default()
+    }
+
+    /// Read `device`. smartctl's exit status is a bit mask; only bits 0 and 1
+    /// (bad arguments, device open failed) mean there is nothing to parse.
+    fn read(&self, device: &str, polled_at: u64) -> Result<DeviceHealth, String> {
+        let output = std::process::Command::new(&self.config.smartctl)
+            .args(["-a", "-j", device])
+            .output()
+            .map_err(|e| format!("running {}: {e}", self.config.smartctl))?;
+        let status = output.status.code().unwrap_or(0);
+        if status & 0b11 != 0 {
+            return Err(format!("smartctl exited with status {status}"));
+        }
+        let json: Value = serde_json::from_slice(&output.stdout)
+            .map_err(|e| format!("bad smartctl JSON: {e}"))?;
+        Ok(parse_smartctl(device, &json, polled_at))
+    }
+
+    /// Poll every device once and return the alerts raised.
+    pub fn poll(&self) -> Vec<Alert> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut alerts = Vec::new();
+        for device in self.discover() {
+            match self.read(&device, now) {
+                Ok(reading) => alerts.extend(self.record(reading)),
+                Err(e) => self.record_error(&device, now, e),
+            }
+        }
+        alerts
+    }
+
+    pub async fn run(self: Arc<Self>, tx: Option<AlertSender>, interval: Duration) {
+        info!(
+            "[smart] polling {} every {}s",
+            if self.config.devices.is_empty() {
+                "scanned devices".to_string()
+            } else {
+                self.config.devices.join(", ")
+            },
+            interval.as_secs()
+        );
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let monitor = Arc::clone(&self);
+            let Ok(alerts) = tokio::task::spawn_blocking(move || monitor.poll()).await else {
+                continue;
+            };
+            for alert in alerts {
+                warn!("[smart] {}", alert.message);
+                if let Some(tx) = &tx {
+                    tx.send(alert);
+                }
+            }
+        }
+    }
+}
+
+/// Start polling SMART data. Alerts go to `tx` when a rules engine is loaded;
+/// readings are kept either way for the API.
+pub fn spawn(config: &SmartConfig, tx: Option<AlertSender>) -> Arc<SmartMonitor> {
+    let monitor = Arc::new(SmartMonitor::new(config));
+    let interval = Duration::from_secs(config.interval_secs.max(1));
+    tokio::spawn(Arc::clone(&monitor).run(tx, interval));
+    monitor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn nvme(percentage_used: u64, media_errors: u64, temp: u64) -> Value {
+        json!({
+            "device": {"name": "/dev/nvme0", "protocol": "NVMe"},
+            "model_name": "Acme NVMe 1TB",
+            "serial_number": "S123",
+            "smart_status": {"passed": true},
+            "temperature": {"current": temp},
+            "power_on_time": {"hours": 9000},
+            "nvme_smart_health_information_log": {
+                "critical_warning": 0,
+                "available_spare": 100,
+                "available_spare_threshold": 10,
+                "percentage_used": percentage_used,
+                "media_errors": media_errors
+            }
+        })
+    }
+
+    #[test]
+    fn alerts_on_new_failures_and_rapid_wear() {
+        let monitor = SmartMonitor::new(&SmartConfig::default());
+        let day = 86_400;
+
+        let first = parse_smartctl("/dev/nvme0", &nvme(10, 0, 40), day);
+        assert_eq!(first.protocol.as_deref(), Some("NVMe"));
+        assert_eq!(first.wear_used_pct, Some(10.0));
+        assert_eq!(first.spare_threshold_pct, Some(10.0));
+        assert!(monitor.record(first).is_none());
+
+        // Two days later: 3%/day, hot, and new media errors
+        let alert = monitor
+            .record(parse_smartctl("/dev/nvme0", &nvme(16, 2, 75), 3 * day))
+            .expect("degradation alerts");
+        assert_eq!(alert.severity, Severity::High);
+        assert_eq!(
+            alert.message,
+            "/dev/nvme0 (Acme NVMe 1TB, S/N S123): wearing 3.00%/day (limit 1%/day); \
+             temperature 75C (limit 70C); media errors +2 (2)"
+        );
+        assert_eq!(monitor.devices()[0].wear_pct_per_day, Some(3.0));
+
+        // Still hot and wearing fast, nothing new: silent
+        assert!(
+            monitor
+                .record(parse_smartctl(
+                    "/dev/nvme0",
+                    &nvme(17, 2, 75),
+                    3 * day + 600
+                ))
+                .is_none()
+        );
+
+        // Cooled down, then hot again: alerts again
+        monitor.record(parse_smartctl(
+            "/dev/nvme0",
+            &nvme(17, 2, 40),
+            3 * day + 1200,
+        ));
+        let alert = monitor
+            .record(parse_smartctl(
+                "/dev/nvme0",
+                &nvme(17, 2, 71),
+                3 * day + 1800,
+            ))
+            .unwrap();
+        assert!(alert.message.ends_with("temperature 71C (limit 70C)"));
+
+        monitor.record_error(
+            "/dev/nvme0",
+            4 * day,
+            "smartctl exited with status 2".into(),
+        );
+        let kept = &monitor.devices()[0];
+        assert_eq!(kept.wear_used_pct, Some(17.0));
+        assert!(kept.error.is_some());
+
+        let ata = json!({
+            "smart_status": {"passed": false},
+            "ata_smart_attributes": {"table": [
+                {"id": 5, "value": 100, "raw": {"value": 8}},
+                {"id": 177, "value": 85, "raw": {"value": 1234}}
+            ]}
+        });
+        let sda = parse_smartctl("/dev/sda", &ata, day);
+        assert_eq!(sda.wear_used_pct, Some(15.0));
+        assert_eq!(sda.reallocated_sectors, Some(8));
+        let alert = monitor.record(sda).unwrap();
+        assert_eq!(
+            alert.message,
+            "/dev/sda (unknown model, S/N -): SMART health check FAILED"
+        );
+    }
+}
@@ -0,0 +1,333 @@
+//! TCP socket table via `NETLINK_SOCK_DIAG`
+//!
+//! One `SOCK_DIAG_BY_FAMILY` dump per address family returns every listening
+//! and established TCP socket of a network namespace with its inode and, from
+//! `tcp_info`, the bytes acknowledged by the peer and received from it
+//! (kernel 4.1+; zero before). Pods have their own namespaces, so the dump is
+//! repeated inside each one. Socket inodes are unique across namespaces and
+//! are mapped back to processes through `/proc/<pid>/fd`.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::Path;
+
+const SOCK_DIAG_BY_FAMILY: u16 = 20;
+/// `idiag_ext` bit requesting `INET_DIAG_INFO` (`struct tcp_info`).
+const INET_DIAG_INFO: u16 = 2;
+const TCP_ESTABLISHED: u8 = 1;
+const TCP_LISTEN: u8 = 10;
+
+const NLMSG_HDRLEN: usize = 16;
+/// `struct inet_diag_req_v2`
+const REQ_LEN: usize = 56;
+/// `struct inet_diag_msg`
+const MSG_LEN: usize = 72;
+/// `tcpi_bytes_acked` and `tcpi_bytes_received` in `struct tcp_info`.
+const TCPI_BYTES_ACKED: usize = 120;
+const TCPI_BYTES_RECEIVED: usize = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TcpState {
+    Listen,
+    Established,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TcpSocket {
+    pub state: TcpState,
+    pub local: SocketAddr,
+    pub peer: SocketAddr,
+    pub inode: u64,
+    /// Bytes sent and acknowledged by the peer.
+    pub bytes_acked: u64,
+    pub bytes_received: u64,
+}
+
+fn netlink_socket() -> io::Result<OwnedFd> {
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            libc::NETLINK_SOCK_DIAG,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+fn dump_request(family: u8, seq: u32) -> Vec<u8> {
+    let len = NLMSG_HDRLEN + REQ_LEN;
+    let mut buf = Vec::with_capacity(len);
+    buf.extend_from_slice(&(len as u32).to_ne_bytes());
+    buf.extend_from_slice(&SOCK_DIAG_BY_FAMILY.to_ne_bytes());
+    buf.extend_from_slice(&((libc::NLM_F_REQUEST | libc::NLM_F_DUMP) as u16).to_ne_bytes());
+    buf.extend_from_slice(&seq.to_ne_bytes());
+    buf.extend_from_slice(&0u32.to_ne_bytes());
+    buf.push(family);
+    buf.push(libc::IPPROTO_TCP as u8);
+    buf.push(1 << (INET_DIAG_INFO - 1));
+    buf.push(0);
+    let states: u32 = (1 << TCP_ESTABLISHED) | (1 << TCP_LISTEN);
+    buf.extend_from_slice(&states.to_ne_bytes());
+    // Zeroed inet_diag_sockid matches every socket
+    buf.resize(len, 0);
+    buf
+}
+
+fn read_u16(buf: &[u8], at: usize) -> u16 {
+    u16::from_ne_bytes([buf[at], buf[at + 1]])
+}
+
+fn read_u32(buf: &[u8], at: usize) -> u32 {
+    u32::from_ne_bytes(buf[at..at + 4].try_into().unwrap())
+}
+
+fn read_u64(buf: &[u8], at: usize) -> u64 {
+    u64::from_ne_bytes(buf[at..at + 8].try_into().unwrap())
+}
+
+fn address(family: u8, raw: &[u8]) -> IpAddr {
+    if family == libc::AF_INET as u8 {
+        IpAddr::V4(Ipv4Addr::new(raw[0], raw[1], raw[2], raw[3]))
+    } else {
+        let octets: [u8; 16] = raw[..16].try_into().unwrap();
+        IpAddr::V6(Ipv6Addr::from(octets)).to_canonical()
+    }
+}
+
+/// One `inet_diag_msg` and its attributes.
+fn parse_socket(msg: &[u8]) -> Option<TcpSocket> {
+    if msg.len() < MSG_LEN {
+        return None;
+    }
+    let family = msg[0];
+    let state = match msg[1] {
+        TCP_LISTEN => TcpState::Listen,
+        TCP_ESTABLISHED => TcpState::Established,
+        _ => return None,
+    };
+    // inet_diag_sockid: ports are big-endian
+    let sport = u16::from_be_bytes([msg[4], msg[5]]);
+    let dport = u16::from_be_bytes([msg[6], msg[7]]);
+    let local = SocketAddr::new(address(family, &msg[8..24]), sport);
+    let peer = SocketAddr::new(address(family, &msg[24..40]), dport);
+    let inode = read_u32(msg, 68) as u64;
+
+    let mut socket = TcpSocket {
+        state,
+        local,
+        peer,
+        inode,
+        bytes_acked: 0,
+        bytes_received: 0,
+    };
+    let mut at = MSG_LEN;
+    while at + 4 <= msg.len() {
+        let len = read_u16(msg, at) as usize;
+        let kind = read_u16(msg, at + 2);
+        if len < 4 || at + len > msg.len() {
+            break;
+        }
+        let payload = &msg[at + 4..at + len];
+        if kind == INET_DIAG_INFO && payload.len() >= TCPI_BYTES_RECEIVED + 8 {
+            socket.bytes_acked = read_u64(payload, TCPI_BYTES_ACKED);
+            socket.bytes_received = read_u64(payload, TCPI_BYTES_RECEIVED);
+        }
+        at += (len + 3) & !3;
+    }
+    Some(socket)
+}
+
+/// Parse one datagram of a dump into `out`. Returns true once the dump is
+/// complete.
+pub fn parse_messages(buf: &[u8], out: &mut Vec<TcpSocket>) -> io::Result<bool> {
+    let mut at = 0;
+    while at + NLMSG_HDRLEN <= buf.len() {
+        let len = read_u32(buf, at) as usize;
+        let kind = read_u16(buf, at + 4);
+        if len < NLMSG_HDRLEN || at + len > buf.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "truncated netlink message",
+            ));
+        }
+        let body = &buf[at + NLMSG_HDRLEN..at + len];
+        match kind as i32 {
+            libc::NLMSG_DONE => return Ok(true),
+            libc::NLMSG_ERROR => {
+                let errno = if body.len() >= 4 {
+                    -(read_u32(body, 0) as i32)
+                } else {
+                    libc::EIO
+                };
+                return Err(io::Error::from_raw_os_error(errno));
+            }
+            _ if kind == SOCK_DIAG_BY_FAMILY => out.extend(parse_socket(body)),
+            _ => {}
+        }
+        at += (len + 3) & !3;
+    }
+    Ok(false)
+}
+
+/// Listening and established TCP sockets of the caller's network namespace.
+pub fn dump() -> io::Result<Vec<TcpSocket>> {
+    let fd = netlink_socket()?;
+    let mut sockets = Vec::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    for (seq, family) in [(1, libc::AF_INET), (2, libc::AF_INET6)] {
+        let request = dump_request(family as u8, seq);
+        let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as u16;
+        let sent = unsafe {
+            libc::sendto(
+                fd.as_raw_fd(),
+                request.as_ptr().cast(),
+                request.len(),
+                0,
+                (&addr as *const libc::sockaddr_nl).cast(),
+                std::mem::size_of::<libc::sockaddr_nl>() as u32,
+            )
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        loop {
+            let n = unsafe { libc::recv(fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), 0) };
+            if n < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if parse_messages(&buf[..n as usize], &mut sockets)? {
+                break;
+            }
+        }
+    }
+    Ok(sockets)
+}
+
+/// `dump` inside the network namespace at `ns` (e.g. `/proc/<pid>/ns/net`).
+/// Runs on a short-lived thread so the caller's namespace never changes.
+pub fn dump_in(ns: &Path) -> io::Result<Vec<TcpSocket>> {
+    let target = File::open(ns)?;
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                if unsafe { libc::setns(target.as_raw_fd(), libc::CLONE_NEWNET) } != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                dump()
+            })
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("sock_diag thread panicked")))
+    })
+}
+
+/// Inode of a `socket:[N]` fd link.
+pub fn socket_inode(link: &str) -> Option<u64> {
+    link.strip_prefix("socket:[")?
+        .strip_suffix(']')?
+        .parse()
+        .ok()
+}
+
+/// Socket inode to owning pid, and one pid per network namespace (keyed by
+/// the namespace inode), from `/proc`. Processes whose fds cannot be read are
+/// skipped.
+pub fn scan_proc(proc_root: &Path) -> (HashMap<u64, u32>, HashMap<u64, u32>) {
+    let mut owners = HashMap::new();
+    let mut namespaces = HashMap::new();
+    let Ok(entries) = std::fs::read_dir(proc_root) else {
+        return (owners, namespaces);
+    };
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let dir = entry.path();
+        if let Some(ns) = std::fs::read_link(dir.join("ns/net"))
+            .ok()
+            .and_then(|link| {
+                let link = link.to_string_lossy().into_owned();
+                link.strip_prefix("net:[")?.strip_suffix(']')?.parse().ok()
+            })
+        {
+            namespaces.entry(ns).or_insert(pid);
+        }
+        let Ok(fds) = std::fs::read_dir(dir.join("fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            if let Some(inode) = std::fs::read_link(fd.path())
+                .ok()
+                .and_then(|link| socket_inode(&link.to_string_lossy()))
+            {
+                owners.entry(inode).or_insert(pid);
+            }
+        }
+    }
+    (owners, namespaces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(state: u8, local: [u8; 4], sport: u16, peer: [u8; 4], dport: u16) -> Vec<u8> {
+        let mut msg = vec![0u8; MSG_LEN];
+        msg[0] = libc::AF_INET as u8;
+        msg[1] = state;
+        msg[4..6].copy_from_slice(&sport.to_be_bytes());
+        msg[6..8].copy_from_slice(&dport.to_be_bytes());
+        msg[8..12].copy_from_slice(&local);
+        msg[24..28].copy_from_slice(&peer);
+        msg[68..72].copy_from_slice(&4242u32.to_ne_bytes());
+        let mut info = vec![0u8; TCPI_BYTES_RECEIVED + 8];
+        info[TCPI_BYTES_ACKED..TCPI_BYTES_ACKED + 8].copy_from_slice(&1500u64.to_ne_bytes());
+        info[TCPI_BYTES_RECEIVED..].copy_from_slice(&700u64.to_ne_bytes());
+        msg.extend_from_slice(&((info.len() + 4) as u16).to_ne_bytes());
+        msg.extend_from_slice(&INET_DIAG_INFO.to_ne_bytes());
+        msg.extend_from_slice(&info);
+        let mut out = Vec::new();
+        out.extend_from_slice(&((msg.len() + NLMSG_HDRLEN) as u32).to_ne_bytes());
+        out.extend_from_slice(&SOCK_DIAG_BY_FAMILY.to_ne_bytes());
+        out.extend_from_slice(&[0; 10]);
+        out.extend_from_slice(&msg);
+        out
+    }
+
+    #[test]
+    fn dump_messages_become_sockets() {
+        let mut buf = message(TCP_ESTABLISHED, [10, 0, 0, 5], 41000, [10, 0, 0, 9], 5432);
+        buf.extend(message(7, [10, 0, 0, 5], 1, [0; 4], 0));
+        let mut sockets = Vec::new();
+        assert!(!parse_messages(&buf, &mut sockets).unwrap());
+        assert_eq!(
+            sockets,
+            [TcpSocket {
+                state: TcpState::Established,
+                local: "10.0.0.5:41000".parse().unwrap(),
+                peer: "10.0.0.9:5432".parse().unwrap(),
+                inode: 4242,
+                bytes_acked: 1500,
+                bytes_received: 700,
+            }]
+        );
+
+        let mut done = Vec::new();
+        done.extend_from_slice(&(NLMSG_HDRLEN as u32).to_ne_bytes());
+        done.extend_from_slice(&(libc::NLMSG_DONE as u16).to_ne_bytes());
+        done.extend_from_slice(&[0; 10]);
+        assert!(parse_messages(&done, &mut sockets).unwrap());
+        assert_eq!(socket_inode("socket:[981]"), Some(981));
+        assert_eq!(socket_inode("pipe:[981]"), None);
+    }
+}
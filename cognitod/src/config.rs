@@ -1,8 +1,14 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::PathBuf;
 
+use crate::anomaly::{SpikeOverride, SpikeSignal};
+pub use crate::egress::{Egress, OfflineGuard};
+use crate::filter::EventFilter;
+pub use crate::retry::RetryConfig;
+use linnix_ai_ebpf_common::DEFAULT_SIGNAL_MASK;
+
 const DEFAULT_CONFIG_PATH: &str = "/etc/linnix/linnix.toml";
 const ENV_CONFIG_PATH: &str = "LINNIX_CONFIG";
 
@@ -18,6 +24,18 @@ pub struct WarmthConfig {
     pub idle_threshold_secs: u64,
     #[serde(default = "default_ping_interval_secs")]
     pub ping_interval_secs: u64,
+    /// Median ping latency at which a standby counts as degraded
+    #[serde(default = "default_degraded_latency_ms")]
+    pub degraded_latency_ms: u64,
+    /// Consecutive failed pings before a standby counts as degraded
+    #[serde(default = "default_degraded_after_failures")]
+    pub degraded_after_failures: u32,
+    /// Consecutive failed pings before a standby counts as unhealthy
+    #[serde(default = "default_unhealthy_after_failures")]
+    pub unhealthy_after_failures: u32,
+    /// Recent successful pings the latency median is taken over
+    #[serde(default = "default_health_window")]
+    pub health_window: usize,
 }
 
 fn default_idle_threshold_secs() -> u64 {
@@ -28,12 +46,32 @@ fn default_ping_interval_secs() -> u64 {
     60 // 1 minute
 }
 
+fn default_degraded_latency_ms() -> u64 {
+    1000
+}
+
+fn default_degraded_after_failures() -> u32 {
+    1
+}
+
+fn default_unhealthy_after_failures() -> u32 {
+    3
+}
+
+fn default_health_window() -> usize {
+    20
+}
+
 impl Default for WarmthConfig {
     fn default() -> Self {
         Self {
             enabled: false,
             idle_threshold_secs: default_idle_threshold_secs(),
             ping_interval_secs: default_ping_interval_secs(),
+            degraded_latency_ms: default_degraded_latency_ms(),
+            degraded_after_failures: default_degraded_after_failures(),
+            unhealthy_after_failures: default_unhealthy_after_failures(),
+            health_window: default_health_window(),
         }
     }
 }
@@ -46,13 +84,61 @@ pub struct ContainerConfig {
     pub warmth_url: Option<String>,
 }
 
+/// Scheduled maintenance windows (see `crate::maintenance`)
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct MaintenanceConfig {
+    #[serde(default)]
+    pub windows: Vec<MaintenanceWindowConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceWindowConfig {
+    pub name: String,
+    /// Five-field cron expression in UTC marking when the window opens
+    pub cron: String,
+    /// How long the window stays open after each cron match
+    pub duration_secs: u64,
+    #[serde(flatten)]
+    pub scope: MaintenanceScope,
+}
+
+/// Restricts a window to matching alerts/targets. Empty lists match all.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct MaintenanceScope {
+    /// Rule names (glob)
+    #[serde(default)]
+    pub rules: Vec<String>,
+    /// Process comm patterns (glob)
+    #[serde(default)]
+    pub comms: Vec<String>,
+    /// Kubernetes namespaces (glob)
+    #[serde(default)]
+    pub namespaces: Vec<String>,
+}
+
 /// API server configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApiConfig {
     #[serde(default = "default_listen_addr")]
     pub listen_addr: String,
+    /// Bearer token with operator scope
     #[serde(default)]
     pub auth_token: Option<String>,
+    /// Further bearer tokens, each with its own scope (`[[api.tokens]]`)
+    #[serde(default)]
+    pub tokens: Vec<ApiTokenConfig>,
+    /// Only clients in these CIDRs may connect; empty allows all
+    #[serde(default)]
+    pub allow_cidrs: Vec<String>,
+    /// Clients refused even when allowed
+    #[serde(default)]
+    pub deny_cidrs: Vec<String>,
+    /// Reverse proxies whose `X-Forwarded-For` names the real client
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
+    /// Per-client request budget; unlimited when unset
+    #[serde(default)]
+    pub rate_limit: Option<ApiRateLimitConfig>,
 }
 
 impl Default for ApiConfig {
@@ -60,76 +146,2126 @@ impl Default for ApiConfig {
         Self {
             listen_addr: default_listen_addr(),
             auth_token: None,
+            tokens: Vec::new(),
+            allow_cidrs: Vec::new(),
+            deny_cidrs: Vec::new(),
+            trusted_proxies: Vec::new(),
+            rate_limit: None,
+        }
+    }
+}
+
+fn default_listen_addr() -> String {
+    "127.0.0.1:3000".to_string()
+}
+
+/// What a request carrying a token may do.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenScope {
+    /// `GET` requests only; the dashboard hides enforcement controls
+    #[default]
+    ReadOnly,
+    /// Every endpoint, including approving and rejecting actions
+    Operator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiTokenConfig {
+    pub token: String,
+    #[serde(default)]
+    pub scope: TokenScope,
+}
+
+/// Token bucket per client IP
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiRateLimitConfig {
+    /// Sustained requests per second
+    #[serde(default = "default_api_rate_limit_per_sec")]
+    pub requests_per_sec: f64,
+    /// Requests a client may make at once after being idle
+    #[serde(default = "default_api_rate_limit_burst")]
+    pub burst: u32,
+}
+
+fn default_api_rate_limit_per_sec() -> f64 {
+    10.0
+}
+
+fn default_api_rate_limit_burst() -> u32 {
+    50
+}
+
+impl Default for ApiRateLimitConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_sec: default_api_rate_limit_per_sec(),
+            burst: default_api_rate_limit_burst(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct NotificationConfig {
+    pub apprise: Option<AppriseConfig>,
+    pub slack: Option<SlackConfig>,
+    pub discord: Option<DiscordConfig>,
+    #[serde(default)]
+    pub wal: NotificationWalConfig,
+}
+
+/// Persistent per-notifier queue giving at-least-once delivery
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationWalConfig {
+    #[serde(default = "default_notification_wal_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_notification_wal_path")]
+    pub path: String,
+    /// Identical alerts delivered within this window are not sent again
+    #[serde(default = "default_notification_dedup_window_secs")]
+    pub dedup_window_secs: u64,
+    /// Delay before retrying a failed notification, doubled per failure
+    #[serde(default = "default_notification_retry_secs")]
+    pub retry_secs: u64,
+    /// Cap on the retry delay
+    #[serde(default = "default_notification_max_retry_secs")]
+    pub max_retry_secs: u64,
+    /// Failed attempts before a notification is dead-lettered (0 = never)
+    #[serde(default = "default_notification_max_attempts")]
+    pub max_attempts: u32,
+}
+
+fn default_notification_wal_enabled() -> bool {
+    true
+}
+
+fn default_notification_wal_path() -> String {
+    "/var/lib/linnix/notifications.wal".to_string()
+}
+
+fn default_notification_dedup_window_secs() -> u64 {
+    300
+}
+
+fn default_notification_retry_secs() -> u64 {
+    30
+}
+
+fn default_notification_max_retry_secs() -> u64 {
+    3600
+}
+
+fn default_notification_max_attempts() -> u32 {
+    10
+}
+
+impl Default for NotificationWalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_notification_wal_enabled(),
+            path: default_notification_wal_path(),
+            dedup_window_secs: default_notification_dedup_window_secs(),
+            retry_secs: default_notification_retry_secs(),
+            max_retry_secs: default_notification_max_retry_secs(),
+            max_attempts: default_notification_max_attempts(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppriseConfig {
+    pub urls: Vec<String>,
+    #[serde(default)]
+    pub min_severity: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlackConfig {
+    pub webhook_url: String,
+    #[serde(default)]
+    pub channel: Option<String>,
+    #[serde(default = "default_dashboard_url")]
+    pub dashboard_base_url: String,
+}
+
+fn default_dashboard_url() -> String {
+    "http://localhost:3000".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscordConfig {
+    /// Channel webhook alerts are posted to
+    pub webhook_url: String,
+    /// Alerts posted per minute; the rest wait in the WAL
+    #[serde(default = "default_discord_max_per_minute")]
+    pub max_per_minute: u32,
+    /// Post pending enforcement actions with Approve/Reject buttons
+    #[serde(default)]
+    pub bot: Option<DiscordBotConfig>,
+}
+
+fn default_discord_max_per_minute() -> u32 {
+    20
+}
+
+/// Discord application used for approval prompts. Its Interactions Endpoint
+/// URL must point at `/api/discord/interactions`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscordBotConfig {
+    pub token: String,
+    /// Channel approval prompts are posted to
+    pub channel_id: String,
+    /// Application public key (hex) used to verify interaction signatures
+    pub public_key: String,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+#[allow(dead_code)]
+pub struct Config {
+    #[serde(default)]
+    pub api: ApiConfig,
+    #[serde(default)]
+    pub runtime: RuntimeConfig,
+    #[serde(default)]
+    pub sequencer: SequencerConfig,
+    #[serde(default)]
+    pub startup: StartupConfig,
+    #[serde(default)]
+    pub context: ContextConfig,
+    #[serde(default)]
+    #[allow(dead_code)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    #[allow(dead_code)]
+    pub outputs: OutputConfig,
+    #[serde(default)]
+    #[allow(dead_code)]
+    pub rules: RulesFileConfig,
+    #[serde(default)]
+    pub reasoner: ReasonerConfig,
+    #[serde(default)]
+    pub probes: ProbesConfig,
+    #[serde(default)]
+    pub notifications: Option<NotificationConfig>,
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+    #[serde(default)]
+    pub noise_budget: NoiseBudgetConfig,
+    #[serde(default)]
+    pub privacy: PrivacyConfig,
+    #[serde(default)]
+    pub psi: PsiConfig,
+    #[serde(default)]
+    pub docker_enforcement: Option<DockerEnforcementConfig>,
+    #[serde(default)]
+    pub warmth: WarmthConfig,
+    #[serde(default)]
+    pub containers: Vec<ContainerConfig>,
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+    #[serde(default)]
+    pub incidents: IncidentStoreConfig,
+    #[serde(default)]
+    pub scope: ScopeConfig,
+    #[serde(default)]
+    pub power: PowerConfig,
+    #[serde(default)]
+    pub memory_events: MemoryEventsConfig,
+    #[serde(default)]
+    pub ephemeral_storage: EphemeralStorageConfig,
+    #[serde(default)]
+    pub filesystems: FilesystemsConfig,
+    #[serde(default)]
+    pub smart: SmartConfig,
+    #[serde(default)]
+    pub alert_context: AlertContextConfig,
+    #[serde(default)]
+    pub alerts: AlertsConfig,
+    #[serde(default)]
+    pub restart_loops: RestartLoopsConfig,
+    #[serde(default)]
+    pub crashes: CrashesConfig,
+    #[serde(default)]
+    pub oom_kills: OomKillsConfig,
+    #[serde(default)]
+    pub ddos: DdosConfig,
+    #[serde(default)]
+    pub cloudflare: CloudflareConfig,
+    #[serde(default)]
+    pub threads: ThreadsConfig,
+    #[serde(default)]
+    pub health_score: HealthScoreConfig,
+    #[serde(default)]
+    pub approvals: ApprovalsConfig,
+    #[serde(default)]
+    pub coordination: CoordinationConfig,
+    #[serde(default)]
+    pub topology: TopologyConfig,
+    #[serde(default)]
+    pub enrichment: EnrichmentConfig,
+    #[serde(default)]
+    pub map_cleanup: MapCleanupConfig,
+    #[serde(default)]
+    pub staleness: StalenessConfig,
+    #[serde(default)]
+    pub detectors: DetectorsConfig,
+    #[serde(default)]
+    pub ordering: OrderingConfig,
+    #[serde(default)]
+    pub canary: CanaryConfig,
+    #[serde(default)]
+    pub probe_silence: ProbeSilenceConfig,
+    #[serde(default)]
+    pub leaderboard: LeaderboardConfig,
+    #[serde(default)]
+    pub ancestry_profile: AncestryProfileConfig,
+    #[serde(default)]
+    pub scaling: ScalingConfig,
+    #[serde(default)]
+    pub degradation: DegradationConfig,
+    #[serde(default)]
+    pub recordings: Vec<RecordingConfig>,
+    #[serde(default)]
+    pub event_webhooks: Vec<EventWebhookConfig>,
+    #[serde(default)]
+    pub computed_fields: Vec<ComputedFieldConfig>,
+    #[serde(default)]
+    pub state: StateConfig,
+    /// Message template overrides, keyed by notifier then message kind
+    /// (`templates`)
+    #[serde(default)]
+    pub templates: BTreeMap<String, BTreeMap<String, String>>,
+    #[serde(default)]
+    pub fleet_telemetry: FleetTelemetryConfig,
+    #[serde(default)]
+    pub kubernetes: KubernetesConfig,
+    #[serde(default)]
+    pub forensics: ForensicsConfig,
+    #[serde(default)]
+    pub egress: EgressConfig,
+    #[serde(default)]
+    pub ui: UiConfig,
+}
+
+/// Part of the embedded dashboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DashboardSection {
+    /// System, AI status and performance cards
+    Overview,
+    Processes,
+    Events,
+    Insights,
+    /// Pending enforcement actions
+    Actions,
+}
+
+fn all_dashboard_sections() -> Vec<DashboardSection> {
+    vec![
+        DashboardSection::Overview,
+        DashboardSection::Processes,
+        DashboardSection::Events,
+        DashboardSection::Insights,
+        DashboardSection::Actions,
+    ]
+}
+
+/// Dashboard served at `/` and `/dashboard`, per scope of the viewer's
+/// token. Without any API token every viewer is an operator.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UiConfig {
+    /// Sections shown to `read_only` tokens, which never get approve or
+    /// reject buttons
+    #[serde(default = "all_dashboard_sections")]
+    pub read_only_sections: Vec<DashboardSection>,
+    #[serde(default = "all_dashboard_sections")]
+    pub operator_sections: Vec<DashboardSection>,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            read_only_sections: all_dashboard_sections(),
+            operator_sections: all_dashboard_sections(),
+        }
+    }
+}
+
+impl UiConfig {
+    pub fn sections(&self, scope: TokenScope) -> &[DashboardSection] {
+        match scope {
+            TokenScope::ReadOnly => &self.read_only_sections,
+            TokenScope::Operator => &self.operator_sections,
+        }
+    }
+}
+
+/// Self-management when running in a cluster (`daemonset`). Ignored outside
+/// one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KubernetesConfig {
+    /// Keep a `coordination.k8s.io` Lease named `linnix-<node>` renewed
+    #[serde(default = "default_kubernetes_lease")]
+    pub lease: bool,
+    #[serde(default = "default_kubernetes_lease_duration_secs")]
+    pub lease_duration_secs: u64,
+    /// Namespace of the Lease and of a `rules_configmap` given without one;
+    /// defaults to the agent's own
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// `name` or `namespace/name` of a ConfigMap to load alert rules from
+    #[serde(default)]
+    pub rules_configmap: Option<String>,
+    /// Key of the rules document in the ConfigMap
+    #[serde(default = "default_kubernetes_rules_key")]
+    pub rules_key: String,
+    #[serde(default = "default_kubernetes_rules_poll_secs")]
+    pub rules_poll_secs: u64,
+}
+
+fn default_kubernetes_lease() -> bool {
+    true
+}
+
+fn default_kubernetes_lease_duration_secs() -> u64 {
+    40
+}
+
+fn default_kubernetes_rules_key() -> String {
+    "rules.yaml".to_string()
+}
+
+fn default_kubernetes_rules_poll_secs() -> u64 {
+    30
+}
+
+impl Default for KubernetesConfig {
+    fn default() -> Self {
+        Self {
+            lease: default_kubernetes_lease(),
+            lease_duration_secs: default_kubernetes_lease_duration_secs(),
+            namespace: None,
+            rules_configmap: None,
+            rules_key: default_kubernetes_rules_key(),
+            rules_poll_secs: default_kubernetes_rules_poll_secs(),
+        }
+    }
+}
+
+/// Opt-in anonymized usage reports (`fleet_telemetry`). Ignored in builds
+/// without the `fleet-telemetry` feature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FleetTelemetryConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Collector the report is POSTed to; required when enabled
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    /// Seconds between reports (at least an hour)
+    #[serde(default = "default_fleet_telemetry_interval_secs")]
+    pub interval_secs: u64,
+    /// Privacy budget per report; smaller adds more noise
+    #[serde(default = "default_fleet_telemetry_epsilon")]
+    pub epsilon: f64,
+}
+
+fn default_fleet_telemetry_interval_secs() -> u64 {
+    86_400
+}
+
+fn default_fleet_telemetry_epsilon() -> f64 {
+    1.0
+}
+
+impl Default for FleetTelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: None,
+            interval_secs: default_fleet_telemetry_interval_secs(),
+            epsilon: default_fleet_telemetry_epsilon(),
         }
     }
 }
 
-fn default_listen_addr() -> String {
-    "127.0.0.1:3000".to_string()
+/// Carry operational state across upgrades and re-provisioning
+/// (`state_bundle`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StateConfig {
+    /// Bundle written on shutdown; unset disables
+    #[serde(default)]
+    pub export_path: Option<String>,
+    /// Bundle imported on startup when the file exists, then renamed to
+    /// `<path>.imported`
+    #[serde(default)]
+    pub import_path: Option<String>,
+}
+
+/// Restrict monitoring to part of the cgroup hierarchy (multi-tenant nodes)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScopeConfig {
+    /// cgroup v2 subtrees relative to the cgroup root, all at the same depth.
+    /// Empty watches the whole node.
+    #[serde(default)]
+    pub cgroups: Vec<String>,
+}
+
+/// RAPL energy estimates per pod
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowerConfig {
+    /// Sample RAPL counters when the node exposes them
+    #[serde(default = "default_power_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_power_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_power_enabled() -> bool {
+    true
+}
+
+fn default_power_interval_secs() -> u64 {
+    5
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_power_enabled(),
+            interval_secs: default_power_interval_secs(),
+        }
+    }
+}
+
+/// cgroup `memory.events` polling for OOM and limit hits
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryEventsConfig {
+    #[serde(default = "default_memory_events_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_memory_events_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_memory_events_enabled() -> bool {
+    true
+}
+
+fn default_memory_events_interval_secs() -> u64 {
+    2
+}
+
+impl Default for MemoryEventsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_memory_events_enabled(),
+            interval_secs: default_memory_events_interval_secs(),
+        }
+    }
+}
+
+/// Per-pod ephemeral storage sampling and its limit alerts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EphemeralStorageConfig {
+    #[serde(default = "default_ephemeral_storage_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_ephemeral_storage_interval_secs")]
+    pub interval_secs: u64,
+    /// Files and directories visited per pass, across all pods
+    #[serde(default = "default_ephemeral_storage_max_entries")]
+    pub max_entries_per_scan: u64,
+    #[serde(default = "default_ephemeral_storage_kubelet_root")]
+    pub kubelet_root: String,
+    #[serde(default = "default_ephemeral_storage_pod_logs_root")]
+    pub pod_logs_root: String,
+    /// Usage in percent of the pod's limit that raises a medium alert
+    #[serde(default = "default_ephemeral_storage_warn_pct")]
+    pub warn_pct: f64,
+    /// Usage in percent of the pod's limit that raises a high alert
+    #[serde(default = "default_ephemeral_storage_critical_pct")]
+    pub critical_pct: f64,
+    /// How far back file writes count towards a pod's top writers
+    #[serde(default = "default_ephemeral_storage_writer_window_secs")]
+    pub writer_window_secs: u64,
+    #[serde(default = "default_ephemeral_storage_top_writers")]
+    pub top_writers: usize,
+}
+
+fn default_ephemeral_storage_enabled() -> bool {
+    true
+}
+
+fn default_ephemeral_storage_interval_secs() -> u64 {
+    60
+}
+
+fn default_ephemeral_storage_max_entries() -> u64 {
+    200_000
+}
+
+fn default_ephemeral_storage_kubelet_root() -> String {
+    "/var/lib/kubelet".to_string()
+}
+
+fn default_ephemeral_storage_pod_logs_root() -> String {
+    "/var/log/pods".to_string()
+}
+
+fn default_ephemeral_storage_warn_pct() -> f64 {
+    80.0
+}
+
+fn default_ephemeral_storage_critical_pct() -> f64 {
+    95.0
+}
+
+fn default_ephemeral_storage_writer_window_secs() -> u64 {
+    600
+}
+
+fn default_ephemeral_storage_top_writers() -> usize {
+    5
+}
+
+impl Default for EphemeralStorageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_ephemeral_storage_enabled(),
+            interval_secs: default_ephemeral_storage_interval_secs(),
+            max_entries_per_scan: default_ephemeral_storage_max_entries(),
+            kubelet_root: default_ephemeral_storage_kubelet_root(),
+            pod_logs_root: default_ephemeral_storage_pod_logs_root(),
+            warn_pct: default_ephemeral_storage_warn_pct(),
+            critical_pct: default_ephemeral_storage_critical_pct(),
+            writer_window_secs: default_ephemeral_storage_writer_window_secs(),
+            top_writers: default_ephemeral_storage_top_writers(),
+        }
+    }
+}
+
+/// SMART health and SSD wear polled with smartctl
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmartConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Seconds between polls
+    #[serde(default = "default_smart_interval_secs")]
+    pub interval_secs: u64,
+    /// smartctl binary (smartmontools 7.0+ for JSON output)
+    #[serde(default = "default_smart_smartctl")]
+    pub smartctl: String,
+    /// Device paths to poll; empty polls what `smartctl --scan` finds
+    #[serde(default)]
+    pub devices: Vec<String>,
+    /// Alert once rated endurance used reaches this percentage
+    #[serde(default = "default_smart_wear_used_pct")]
+    pub wear_used_pct: f64,
+    /// Alert when wear grows faster than this many percent per day
+    #[serde(default = "default_smart_max_wear_pct_per_day")]
+    pub max_wear_pct_per_day: f64,
+    /// Wear history the growth rate is measured over
+    #[serde(default = "default_smart_wear_window_secs")]
+    pub wear_window_secs: u64,
+    /// Alert at or above this drive temperature
+    #[serde(default = "default_smart_temperature_c")]
+    pub temperature_c: f64,
+}
+
+fn default_smart_interval_secs() -> u64 {
+    600
+}
+
+fn default_smart_smartctl() -> String {
+    "smartctl".to_string()
+}
+
+fn default_smart_wear_used_pct() -> f64 {
+    80.0
+}
+
+fn default_smart_max_wear_pct_per_day() -> f64 {
+    1.0
+}
+
+fn default_smart_wear_window_secs() -> u64 {
+    7 * 86_400
+}
+
+fn default_smart_temperature_c() -> f64 {
+    70.0
+}
+
+impl Default for SmartConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: default_smart_interval_secs(),
+            smartctl: default_smart_smartctl(),
+            devices: Vec::new(),
+            wear_used_pct: default_smart_wear_used_pct(),
+            max_wear_pct_per_day: default_smart_max_wear_pct_per_day(),
+            wear_window_secs: default_smart_wear_window_secs(),
+            temperature_c: default_smart_temperature_c(),
+        }
+    }
+}
+
+/// Per-mount disk usage and time-to-full prediction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilesystemsConfig {
+    #[serde(default = "default_filesystems_enabled")]
+    pub enabled: bool,
+    /// Seconds between samples
+    #[serde(default = "default_filesystems_interval_secs")]
+    pub interval_secs: u64,
+    /// Usage history the growth rate is fitted over
+    #[serde(default = "default_filesystems_growth_window_secs")]
+    pub growth_window_secs: u64,
+    /// Mount table to read; `/proc/1/mounts` sees the host's with `hostPID`
+    #[serde(default = "default_filesystems_mounts_file")]
+    pub mounts_file: String,
+    /// Filesystem types never sampled
+    #[serde(default = "default_filesystems_exclude_types")]
+    pub exclude_types: Vec<String>,
+    /// Mount point globs never sampled
+    #[serde(default = "default_filesystems_exclude_mounts")]
+    pub exclude_mounts: Vec<String>,
+}
+
+fn default_filesystems_enabled() -> bool {
+    true
+}
+
+fn default_filesystems_interval_secs() -> u64 {
+    60
+}
+
+fn default_filesystems_growth_window_secs() -> u64 {
+    6 * 3600
+}
+
+fn default_filesystems_mounts_file() -> String {
+    "/proc/self/mounts".to_string()
+}
+
+fn default_filesystems_exclude_types() -> Vec<String> {
+    [
+        "autofs",
+        "binfmt_misc",
+        "bpf",
+        "cgroup",
+        "cgroup2",
+        "configfs",
+        "debugfs",
+        "devpts",
+        "devtmpfs",
+        "efivarfs",
+        "fusectl",
+        "hugetlbfs",
+        "mqueue",
+        "nsfs",
+        "proc",
+        "pstore",
+        "rpc_pipefs",
+        "securityfs",
+        "selinuxfs",
+        "squashfs",
+        "sysfs",
+        "tracefs",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+fn default_filesystems_exclude_mounts() -> Vec<String> {
+    [
+        "/run/containerd/*",
+        "/run/k3s/*",
+        "/var/lib/containers/*",
+        "/var/lib/docker/*",
+        "/var/lib/kubelet/pods/*",
+        "/snap/*",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+impl Default for FilesystemsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_filesystems_enabled(),
+            interval_secs: default_filesystems_interval_secs(),
+            growth_window_secs: default_filesystems_growth_window_secs(),
+            mounts_file: default_filesystems_mounts_file(),
+            exclude_types: default_filesystems_exclude_types(),
+            exclude_mounts: default_filesystems_exclude_mounts(),
+        }
+    }
+}
+
+/// Alerting on workloads that are re-exec'd after failing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestartLoopsConfig {
+    #[serde(default = "default_restart_loops_enabled")]
+    pub enabled: bool,
+    /// Alert once a workload restarts more than this many times in the window
+    #[serde(default = "default_restart_loops_threshold")]
+    pub threshold: usize,
+    #[serde(default = "default_restart_loops_window_secs")]
+    pub window_secs: u64,
+}
+
+fn default_restart_loops_enabled() -> bool {
+    true
+}
+
+fn default_restart_loops_threshold() -> usize {
+    5
+}
+
+fn default_restart_loops_window_secs() -> u64 {
+    300
+}
+
+impl Default for RestartLoopsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_restart_loops_enabled(),
+            threshold: default_restart_loops_threshold(),
+            window_secs: default_restart_loops_window_secs(),
+        }
+    }
+}
+
+/// Alerting on processes killed by SIGSEGV, SIGABRT and other crash signals
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashesConfig {
+    #[serde(default = "default_crashes_enabled")]
+    pub enabled: bool,
+    /// Page faults this recent before the crash are attached to the alert
+    #[serde(default = "default_crashes_fault_window_secs")]
+    pub fault_window_secs: u64,
+}
+
+fn default_crashes_enabled() -> bool {
+    true
+}
+
+fn default_crashes_fault_window_secs() -> u64 {
+    30
+}
+
+impl Default for CrashesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_crashes_enabled(),
+            fault_window_secs: default_crashes_fault_window_secs(),
+        }
+    }
+}
+
+/// Alerts and incidents for processes killed by the kernel's OOM killer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OomKillsConfig {
+    #[serde(default = "default_oom_kills_enabled")]
+    pub enabled: bool,
+}
+
+fn default_oom_kills_enabled() -> bool {
+    true
+}
+
+impl Default for OomKillsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_oom_kills_enabled(),
+        }
+    }
+}
+
+/// On-demand inspection of a process's memory map (`/processes/{pid}/maps`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForensicsConfig {
+    /// Operator bearer token, also accepted by the rest of the API; the
+    /// endpoint is off while unset
+    #[serde(default)]
+    pub operator_token: Option<String>,
+    /// Inspections per minute per client
+    #[serde(default = "default_forensics_requests_per_min")]
+    pub requests_per_min: u32,
+    /// Inspections a client may make at once after being idle
+    #[serde(default = "default_forensics_burst")]
+    pub burst: u32,
+    /// Bytes hashed per writable and executable region; longer regions are
+    /// hashed up to this length
+    #[serde(default = "default_forensics_max_hash_bytes")]
+    pub max_hash_bytes: u64,
+}
+
+fn default_forensics_requests_per_min() -> u32 {
+    6
+}
+
+fn default_forensics_burst() -> u32 {
+    3
+}
+
+fn default_forensics_max_hash_bytes() -> u64 {
+    16 * 1024 * 1024
+}
+
+impl Default for ForensicsConfig {
+    fn default() -> Self {
+        Self {
+            operator_token: None,
+            requests_per_min: default_forensics_requests_per_min(),
+            burst: default_forensics_burst(),
+            max_hash_bytes: default_forensics_max_hash_bytes(),
+        }
+    }
+}
+
+/// Outbound traffic allowed per destination category. Unset categories
+/// follow `[runtime] offline`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EgressConfig {
+    #[serde(default)]
+    pub llm: Option<bool>,
+    #[serde(default)]
+    pub notifications: Option<bool>,
+    #[serde(default)]
+    pub webhooks: Option<bool>,
+    #[serde(default)]
+    pub config_sync: Option<bool>,
+    #[serde(default)]
+    pub otlp: Option<bool>,
+    /// Operator bearer token, also accepted by the rest of the API; enables
+    /// changing the policy at runtime (`POST /offline`), read-only while unset
+    #[serde(default)]
+    pub admin_token: Option<String>,
+    /// Retries, backoff and circuit breaking of outbound clients
+    #[serde(default)]
+    pub retry: RetryConfig,
+}
+
+impl EgressConfig {
+    pub fn allowed(&self, category: Egress) -> Option<bool> {
+        match category {
+            Egress::Llm => self.llm,
+            Egress::Notifications => self.notifications,
+            Egress::Webhooks => self.webhooks,
+            Egress::ConfigSync => self.config_sync,
+            Egress::Otlp => self.otlp,
+        }
+    }
+}
+
+/// SYN flood and connection-rate detection (`handler::ddos`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DdosConfig {
+    #[serde(default = "default_ddos_enabled")]
+    pub enabled: bool,
+    /// Seconds of connection events rates are computed over
+    #[serde(default = "default_ddos_window_secs")]
+    pub window_secs: u64,
+    /// Node-wide SYNs per second above which a low completion ratio is a flood
+    #[serde(default = "default_ddos_syn_rate")]
+    pub syn_rate: u64,
+    /// SYNs per second above which a single source counts as a flooder
+    #[serde(default = "default_ddos_source_rate")]
+    pub source_rate: u64,
+    /// Share of SYNs completing the handshake below which traffic is hostile
+    #[serde(default = "default_ddos_min_established_ratio")]
+    pub min_established_ratio: f64,
+    /// Sources listed in the alert
+    #[serde(default = "default_ddos_top_talkers")]
+    pub top_talkers: usize,
+    /// Propose blocking flooders through the enforcement queue; unset only
+    /// alerts
+    #[serde(default)]
+    pub block: Option<crate::enforcement::BlockBackend>,
+    #[serde(default = "default_ddos_block_ttl_secs")]
+    pub block_ttl_secs: u64,
+}
+
+fn default_ddos_enabled() -> bool {
+    true
+}
+
+fn default_ddos_window_secs() -> u64 {
+    10
+}
+
+fn default_ddos_syn_rate() -> u64 {
+    1000
+}
+
+fn default_ddos_source_rate() -> u64 {
+    100
+}
+
+fn default_ddos_min_established_ratio() -> f64 {
+    0.5
+}
+
+fn default_ddos_top_talkers() -> usize {
+    5
+}
+
+fn default_ddos_block_ttl_secs() -> u64 {
+    600
+}
+
+impl Default for DdosConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_ddos_enabled(),
+            window_secs: default_ddos_window_secs(),
+            syn_rate: default_ddos_syn_rate(),
+            source_rate: default_ddos_source_rate(),
+            min_established_ratio: default_ddos_min_established_ratio(),
+            top_talkers: default_ddos_top_talkers(),
+            block: None,
+            block_ttl_secs: default_ddos_block_ttl_secs(),
+        }
+    }
+}
+
+/// Edge mitigation on Cloudflare zones (`handler::cloudflare`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloudflareConfig {
+    /// Log the API calls approved actions would make instead of making them
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Execute proposed rate-limit rules without waiting for approval
+    #[serde(default)]
+    pub auto_approve: bool,
+    /// Seconds a rate-limit rule stays on the zone before it is deleted
+    #[serde(default = "default_cloudflare_rule_ttl_secs")]
+    pub rule_ttl_secs: u64,
+    #[serde(default)]
+    pub zones: Vec<CloudflareZoneConfig>,
+}
+
+fn default_cloudflare_rule_ttl_secs() -> u64 {
+    3600
+}
+
+impl Default for CloudflareConfig {
+    fn default() -> Self {
+        Self {
+            dry_run: false,
+            auto_approve: false,
+            rule_ttl_secs: default_cloudflare_rule_ttl_secs(),
+            zones: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloudflareZoneConfig {
+    /// Name used in actions and logs
+    pub name: String,
+    pub zone_id: String,
+    /// Token with Zone Firewall and Zone WAF edit access
+    pub api_token: String,
+    /// What an IP Access Rule created for a blocked source does
+    #[serde(default)]
+    pub mode: AccessRuleMode,
+    /// Create a rate-limiting rule when one of `on_rules` fires; unset never
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+}
+
+/// Action of a Cloudflare IP Access Rule.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessRuleMode {
+    #[default]
+    Block,
+    Challenge,
+    JsChallenge,
+    ManagedChallenge,
+}
+
+impl AccessRuleMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccessRuleMode::Block => "block",
+            AccessRuleMode::Challenge => "challenge",
+            AccessRuleMode::JsChallenge => "js_challenge",
+            AccessRuleMode::ManagedChallenge => "managed_challenge",
+        }
+    }
+}
+
+/// Rate-limiting rule counted per client IP and data center.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    /// Alert rules that propose the rule
+    #[serde(default = "default_rate_limit_on_rules")]
+    pub on_rules: Vec<String>,
+    /// Requests the rule applies to, in Cloudflare's rules language
+    #[serde(default = "default_rate_limit_expression")]
+    pub expression: String,
+    #[serde(default = "default_rate_limit_requests_per_period")]
+    pub requests_per_period: u64,
+    /// One of the periods Cloudflare accepts (10, 60, 120, 300, 600, 3600)
+    #[serde(default = "default_rate_limit_period_secs")]
+    pub period_secs: u64,
+    /// How long a client over the limit stays blocked
+    #[serde(default = "default_rate_limit_mitigation_timeout_secs")]
+    pub mitigation_timeout_secs: u64,
+}
+
+fn default_rate_limit_on_rules() -> Vec<String> {
+    vec!["ddos".to_string()]
+}
+
+fn default_rate_limit_expression() -> String {
+    "true".to_string()
+}
+
+fn default_rate_limit_requests_per_period() -> u64 {
+    100
+}
+
+fn default_rate_limit_period_secs() -> u64 {
+    60
+}
+
+fn default_rate_limit_mitigation_timeout_secs() -> u64 {
+    600
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            on_rules: default_rate_limit_on_rules(),
+            expression: default_rate_limit_expression(),
+            requests_per_period: default_rate_limit_requests_per_period(),
+            period_secs: default_rate_limit_period_secs(),
+            mitigation_timeout_secs: default_rate_limit_mitigation_timeout_secs(),
+        }
+    }
+}
+
+/// Service dependency graph served at `/topology`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopologyConfig {
+    #[serde(default = "default_topology_enabled")]
+    pub enabled: bool,
+    /// Seconds between samples of the TCP socket tables
+    #[serde(default = "default_topology_interval_secs")]
+    pub interval_secs: u64,
+    /// Edges without an open connection for this long are dropped
+    #[serde(default = "default_topology_retention_secs")]
+    pub retention_secs: u64,
+}
+
+fn default_topology_enabled() -> bool {
+    true
+}
+
+fn default_topology_interval_secs() -> u64 {
+    15
+}
+
+fn default_topology_retention_secs() -> u64 {
+    3600
+}
+
+impl Default for TopologyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_topology_enabled(),
+            interval_secs: default_topology_interval_secs(),
+            retention_secs: default_topology_retention_secs(),
+        }
+    }
+}
+
+/// Reverse DNS and GeoIP names of remote peers, shown on `/topology` and
+/// `/peers`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnrichmentConfig {
+    #[serde(default = "default_enrichment_enabled")]
+    pub enabled: bool,
+    /// PTR lookups through the system resolver; skipped in offline mode
+    #[serde(default = "default_enrichment_reverse_dns")]
+    pub reverse_dns: bool,
+    /// MaxMind City or Country database (`.mmdb`)
+    #[serde(default)]
+    pub geoip_db: Option<String>,
+    /// MaxMind ASN database (`.mmdb`)
+    #[serde(default)]
+    pub asn_db: Option<String>,
+    /// Lookups in flight at once
+    #[serde(default = "default_enrichment_concurrency")]
+    pub concurrency: usize,
+    #[serde(default = "default_enrichment_ttl_secs")]
+    pub ttl_secs: u64,
+    /// Cache time of addresses nothing was found for
+    #[serde(default = "default_enrichment_negative_ttl_secs")]
+    pub negative_ttl_secs: u64,
+    #[serde(default = "default_enrichment_max_entries")]
+    pub max_entries: usize,
+}
+
+fn default_enrichment_enabled() -> bool {
+    true
+}
+
+fn default_enrichment_reverse_dns() -> bool {
+    true
+}
+
+fn default_enrichment_concurrency() -> usize {
+    4
+}
+
+fn default_enrichment_ttl_secs() -> u64 {
+    3600
+}
+
+fn default_enrichment_negative_ttl_secs() -> u64 {
+    300
+}
+
+fn default_enrichment_max_entries() -> usize {
+    10_000
+}
+
+impl Default for EnrichmentConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enrichment_enabled(),
+            reverse_dns: default_enrichment_reverse_dns(),
+            geoip_db: None,
+            asn_db: None,
+            concurrency: default_enrichment_concurrency(),
+            ttl_secs: default_enrichment_ttl_secs(),
+            negative_ttl_secs: default_enrichment_negative_ttl_secs(),
+            max_entries: default_enrichment_max_entries(),
+        }
+    }
+}
+
+/// Scanner for per-process BPF map entries left by processes that exited
+/// while probes were detached
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MapCleanupConfig {
+    #[serde(default = "default_map_cleanup_enabled")]
+    pub enabled: bool,
+    /// Seconds between scans
+    #[serde(default = "default_map_cleanup_interval_secs")]
+    pub interval_secs: u64,
+    /// Occupancy, in percent of capacity, that raises an alert
+    #[serde(default = "default_map_cleanup_alert_pct")]
+    pub alert_pct: f64,
+}
+
+fn default_map_cleanup_enabled() -> bool {
+    true
+}
+
+fn default_map_cleanup_interval_secs() -> u64 {
+    60
+}
+
+fn default_map_cleanup_alert_pct() -> f64 {
+    80.0
+}
+
+impl Default for MapCleanupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_map_cleanup_enabled(),
+            interval_secs: default_map_cleanup_interval_secs(),
+            alert_pct: default_map_cleanup_alert_pct(),
+        }
+    }
+}
+
+/// Alert when events reach consumers late, judged by the p99 age (from the
+/// kernel timestamp) of the events seen in each window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StalenessConfig {
+    #[serde(default = "default_staleness_enabled")]
+    pub enabled: bool,
+    /// p99 event age, in milliseconds, that raises an alert
+    #[serde(default = "default_staleness_p99_bound_ms")]
+    pub p99_bound_ms: u64,
+    /// Seconds covered by each check
+    #[serde(default = "default_staleness_window_secs")]
+    pub window_secs: u64,
+    /// Events a window needs before it is judged
+    #[serde(default = "default_staleness_min_samples")]
+    pub min_samples: u64,
+}
+
+fn default_staleness_enabled() -> bool {
+    true
+}
+
+fn default_staleness_p99_bound_ms() -> u64 {
+    1000
+}
+
+fn default_staleness_window_secs() -> u64 {
+    60
+}
+
+fn default_staleness_min_samples() -> u64 {
+    100
+}
+
+impl Default for StalenessConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_staleness_enabled(),
+            p99_bound_ms: default_staleness_p99_bound_ms(),
+            window_secs: default_staleness_window_secs(),
+            min_samples: default_staleness_min_samples(),
+        }
+    }
+}
+
+/// Built-in anomaly detectors, evaluated next to the rules file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetectorsConfig {
+    /// Seconds before a detector fires again for the same process
+    #[serde(default = "default_detectors_cooldown_secs")]
+    pub cooldown_secs: u64,
+    #[serde(default)]
+    pub fork_storm: ForkStormDetectorConfig,
+    #[serde(default)]
+    pub memory_leak: MemoryLeakDetectorConfig,
+    #[serde(default)]
+    pub spike: SpikeDetectorConfig,
+}
+
+fn default_detectors_cooldown_secs() -> u64 {
+    300
+}
+
+impl Default for DetectorsConfig {
+    fn default() -> Self {
+        Self {
+            cooldown_secs: default_detectors_cooldown_secs(),
+            fork_storm: ForkStormDetectorConfig::default(),
+            memory_leak: MemoryLeakDetectorConfig::default(),
+            spike: SpikeDetectorConfig::default(),
+        }
+    }
+}
+
+/// A parent forking `forks` children within `window_secs`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForkStormDetectorConfig {
+    #[serde(default = "default_detector_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_fork_storm_forks")]
+    pub forks: u64,
+    #[serde(default = "default_fork_storm_window_secs")]
+    pub window_secs: u64,
+}
+
+fn default_detector_enabled() -> bool {
+    true
+}
+
+fn default_fork_storm_forks() -> u64 {
+    200
+}
+
+fn default_fork_storm_window_secs() -> u64 {
+    5
+}
+
+impl Default for ForkStormDetectorConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_detector_enabled(),
+            forks: default_fork_storm_forks(),
+            window_secs: default_fork_storm_window_secs(),
+        }
+    }
+}
+
+/// A process whose memory share keeps growing by `growth_pct` points over
+/// `window_secs`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryLeakDetectorConfig {
+    #[serde(default = "default_detector_enabled")]
+    pub enabled: bool,
+    /// Percentage points of host memory gained over the window
+    #[serde(default = "default_memory_leak_growth_pct")]
+    pub growth_pct: f32,
+    #[serde(default = "default_memory_leak_window_secs")]
+    pub window_secs: u64,
+    /// Samples kept per process over the window
+    #[serde(default = "default_memory_leak_samples")]
+    pub samples: usize,
+}
+
+fn default_memory_leak_growth_pct() -> f32 {
+    5.0
+}
+
+fn default_memory_leak_window_secs() -> u64 {
+    300
+}
+
+fn default_memory_leak_samples() -> usize {
+    10
+}
+
+impl Default for MemoryLeakDetectorConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_detector_enabled(),
+            growth_pct: default_memory_leak_growth_pct(),
+            window_secs: default_memory_leak_window_secs(),
+            samples: default_memory_leak_samples(),
+        }
+    }
+}
+
+/// Per-process EWMA baselines of `signals`; fires on rates beyond `k`·σ
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpikeDetectorConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_spike_signals")]
+    pub signals: Vec<SpikeSignal>,
+    #[serde(default = "default_spike_k")]
+    pub k: f64,
+    #[serde(default = "default_spike_alpha")]
+    pub alpha: f64,
+    #[serde(default = "default_spike_warmup_samples")]
+    pub warmup_samples: u32,
+    #[serde(default = "default_spike_interval_secs")]
+    pub interval_secs: u64,
+    #[serde(default)]
+    pub overrides: Vec<SpikeOverride>,
+}
+
+fn default_spike_signals() -> Vec<SpikeSignal> {
+    vec![
+        SpikeSignal::ForkRate,
+        SpikeSignal::NetBytes,
+        SpikeSignal::BlockIo,
+    ]
+}
+
+fn default_spike_k() -> f64 {
+    4.0
+}
+
+fn default_spike_alpha() -> f64 {
+    0.1
+}
+
+fn default_spike_warmup_samples() -> u32 {
+    30
+}
+
+fn default_spike_interval_secs() -> u64 {
+    1
+}
+
+impl Default for SpikeDetectorConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            signals: default_spike_signals(),
+            k: default_spike_k(),
+            alpha: default_spike_alpha(),
+            warmup_samples: default_spike_warmup_samples(),
+            interval_secs: default_spike_interval_secs(),
+            overrides: Vec::new(),
+        }
+    }
+}
+
+/// Deliver events to handlers that need them in order (the rules engine) by
+/// kernel timestamp, after holding each for `max_delay_ms`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderingConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// How long an event is held for earlier ones still on their way
+    #[serde(default = "default_ordering_max_delay_ms")]
+    pub max_delay_ms: u64,
+    /// Events held at most; beyond it the oldest are released early
+    #[serde(default = "default_ordering_max_buffered")]
+    pub max_buffered: usize,
+}
+
+fn default_ordering_max_delay_ms() -> u64 {
+    50
+}
+
+fn default_ordering_max_buffered() -> usize {
+    65_536
+}
+
+impl Default for OrderingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_delay_ms: default_ordering_max_delay_ms(),
+            max_buffered: default_ordering_max_buffered(),
+        }
+    }
+}
+
+/// Periodic end-to-end check: run a known command and expect its exec and
+/// exit events in the context store within `sla_ms`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanaryConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Program and arguments of the canary process
+    #[serde(default = "default_canary_command")]
+    pub command: Vec<String>,
+    #[serde(default = "default_canary_interval_secs")]
+    pub interval_secs: u64,
+    #[serde(default = "default_canary_sla_ms")]
+    pub sla_ms: u64,
+    /// cgroup v2 directory the canary runs in; empty runs it in the
+    /// daemon's own cgroup
+    #[serde(default = "default_canary_cgroup")]
+    pub cgroup: String,
+    /// Failed runs in a row before `canary_failed` is raised
+    #[serde(default = "default_canary_failures_before_alert")]
+    pub failures_before_alert: u64,
+}
+
+fn default_canary_command() -> Vec<String> {
+    vec!["/bin/true".to_string()]
+}
+
+fn default_canary_interval_secs() -> u64 {
+    60
+}
+
+fn default_canary_sla_ms() -> u64 {
+    5000
+}
+
+fn default_canary_cgroup() -> String {
+    "/sys/fs/cgroup/linnix-canary".to_string()
+}
+
+fn default_canary_failures_before_alert() -> u64 {
+    2
+}
+
+impl Default for CanaryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            command: default_canary_command(),
+            interval_secs: default_canary_interval_secs(),
+            sla_ms: default_canary_sla_ms(),
+            cgroup: default_canary_cgroup(),
+            failures_before_alert: default_canary_failures_before_alert(),
+        }
+    }
+}
+
+/// Alert when an event type that was flowing stops while others continue,
+/// the usual sign of a probe that detached or broke
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProbeSilenceConfig {
+    #[serde(default = "default_probe_silence_enabled")]
+    pub enabled: bool,
+    /// Seconds without an event of a type before it counts as silent
+    #[serde(default = "default_probe_silence_window_secs")]
+    pub window_secs: u64,
+    /// Events the learned rate must predict within `window_secs` for the
+    /// silence to be judged; rarer types are never flagged
+    #[serde(default = "default_probe_silence_min_expected")]
+    pub min_expected: f64,
+    /// Seconds between checks, and the interval the rates are learned over
+    #[serde(default = "default_probe_silence_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_probe_silence_enabled() -> bool {
+    true
+}
+
+fn default_probe_silence_window_secs() -> u64 {
+    300
+}
+
+fn default_probe_silence_min_expected() -> f64 {
+    20.0
+}
+
+fn default_probe_silence_interval_secs() -> u64 {
+    10
+}
+
+impl Default for ProbeSilenceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_probe_silence_enabled(),
+            window_secs: default_probe_silence_window_secs(),
+            min_expected: default_probe_silence_min_expected(),
+            interval_secs: default_probe_silence_interval_secs(),
+        }
+    }
+}
+
+/// Hourly and daily usage rollups served at `/leaderboard`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeaderboardConfig {
+    #[serde(default = "default_leaderboard_enabled")]
+    pub enabled: bool,
+    /// Seconds between reads of per-process counters
+    #[serde(default = "default_leaderboard_sample_secs")]
+    pub sample_secs: u64,
+    /// Hourly rollups kept (at least 25)
+    #[serde(default = "default_leaderboard_retention_hours")]
+    pub retention_hours: u64,
+    #[serde(default = "default_leaderboard_retention_days")]
+    pub retention_days: u64,
+}
+
+fn default_leaderboard_enabled() -> bool {
+    true
+}
+
+fn default_leaderboard_sample_secs() -> u64 {
+    60
+}
+
+fn default_leaderboard_retention_hours() -> u64 {
+    48
+}
+
+fn default_leaderboard_retention_days() -> u64 {
+    30
+}
+
+impl Default for LeaderboardConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_leaderboard_enabled(),
+            sample_secs: default_leaderboard_sample_secs(),
+            retention_hours: default_leaderboard_retention_hours(),
+            retention_days: default_leaderboard_retention_days(),
+        }
+    }
+}
+
+/// CPU time by process ancestry, served as folded stacks at
+/// `/profile/ancestry`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AncestryProfileConfig {
+    #[serde(default = "default_ancestry_profile_enabled")]
+    pub enabled: bool,
+    /// Seconds between reads of per-process CPU counters
+    #[serde(default = "default_ancestry_profile_sample_secs")]
+    pub sample_secs: u64,
+    #[serde(default = "default_ancestry_profile_retention_minutes")]
+    pub retention_minutes: u64,
+}
+
+fn default_ancestry_profile_enabled() -> bool {
+    true
+}
+
+fn default_ancestry_profile_sample_secs() -> u64 {
+    10
+}
+
+fn default_ancestry_profile_retention_minutes() -> u64 {
+    60
+}
+
+impl Default for AncestryProfileConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_ancestry_profile_enabled(),
+            sample_secs: default_ancestry_profile_sample_secs(),
+            retention_minutes: default_ancestry_profile_retention_minutes(),
+        }
+    }
+}
+
+/// Per-pod autoscaling signals served at `/scaling` for KEDA and external
+/// metrics adapters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScalingConfig {
+    #[serde(default = "default_scaling_enabled")]
+    pub enabled: bool,
+    /// Seconds between passes; rates are averaged over one pass
+    #[serde(default = "default_scaling_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_scaling_enabled() -> bool {
+    true
+}
+
+fn default_scaling_interval_secs() -> u64 {
+    15
+}
+
+impl Default for ScalingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_scaling_enabled(),
+            interval_secs: default_scaling_interval_secs(),
+        }
+    }
+}
+
+/// Steps taken when the daemon exceeds `runtime.cpu_target_pct` or
+/// `runtime.rss_cap_mb`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DegradationConfig {
+    #[serde(default = "default_degradation_enabled")]
+    pub enabled: bool,
+    /// Seconds between usage checks
+    #[serde(default = "default_degradation_check_secs")]
+    pub check_secs: u64,
+    /// Consecutive checks over a cap before taking the next step
+    #[serde(default = "default_degradation_escalate_checks")]
+    pub escalate_checks: u32,
+    /// Usage, in percent of each cap, below which a check counts as recovered
+    #[serde(default = "default_degradation_recover_pct")]
+    pub recover_pct: u64,
+    /// Consecutive recovered checks before undoing the last step
+    #[serde(default = "default_degradation_recover_checks")]
+    pub recover_checks: u32,
+    /// Factor applied to the sampling divisor of high-volume event types
+    #[serde(default = "default_degradation_sample_factor")]
+    pub sample_factor: u32,
+    /// Seconds ingestion stays paused when it is restarted
+    #[serde(default = "default_degradation_restart_pause_secs")]
+    pub restart_pause_secs: u64,
+}
+
+fn default_degradation_enabled() -> bool {
+    true
+}
+
+fn default_degradation_check_secs() -> u64 {
+    5
+}
+
+fn default_degradation_escalate_checks() -> u32 {
+    3
+}
+
+fn default_degradation_recover_pct() -> u64 {
+    80
+}
+
+fn default_degradation_recover_checks() -> u32 {
+    12
+}
+
+fn default_degradation_sample_factor() -> u32 {
+    8
+}
+
+fn default_degradation_restart_pause_secs() -> u64 {
+    10
+}
+
+impl Default for DegradationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_degradation_enabled(),
+            check_secs: default_degradation_check_secs(),
+            escalate_checks: default_degradation_escalate_checks(),
+            recover_pct: default_degradation_recover_pct(),
+            recover_checks: default_degradation_recover_checks(),
+            sample_factor: default_degradation_sample_factor(),
+            restart_pause_secs: default_degradation_restart_pause_secs(),
+        }
+    }
+}
+
+/// JSONL recording of events matching `filter` (snapshots are always written)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingConfig {
+    pub path: String,
+    #[serde(default)]
+    pub filter: EventFilter,
+}
+
+/// Per-process value of `expr` (see [`crate::expr`]), shown in `/processes`
+/// under `computed.<name>`; numeric ones can be used in `?filter=`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComputedFieldConfig {
+    pub name: String,
+    pub expr: String,
+}
+
+/// Batched POST of events matching `filter` to `url`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventWebhookConfig {
+    pub url: String,
+    #[serde(default)]
+    pub filter: EventFilter,
+    /// Longest an event waits before its batch is sent
+    #[serde(default = "default_event_webhook_flush_interval_ms")]
+    pub flush_interval_ms: u64,
+    /// Most events sent in one POST
+    #[serde(default = "default_event_webhook_max_batch")]
+    pub max_batch: usize,
+}
+
+fn default_event_webhook_flush_interval_ms() -> u64 {
+    1000
+}
+
+fn default_event_webhook_max_batch() -> usize {
+    500
+}
+
+/// Node health score served at `/health/score`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthScoreConfig {
+    #[serde(default = "default_health_score_enabled")]
+    pub enabled: bool,
+    /// Alerts and enforcement actions older than this no longer count
+    #[serde(default = "default_health_score_window_secs")]
+    pub window_secs: u64,
+    #[serde(default)]
+    pub weights: HealthWeights,
+}
+
+fn default_health_score_enabled() -> bool {
+    true
+}
+
+fn default_health_score_window_secs() -> u64 {
+    900
+}
+
+impl Default for HealthScoreConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_health_score_enabled(),
+            window_secs: default_health_score_window_secs(),
+            weights: HealthWeights::default(),
+        }
+    }
+}
+
+/// Relative weight of each health score component; normalised to sum to 1.
+/// Zero leaves a component out of the total.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct HealthWeights {
+    #[serde(default = "default_weight_pressure")]
+    pub pressure: f64,
+    #[serde(default = "default_weight_headroom")]
+    pub headroom: f64,
+    #[serde(default = "default_weight_anomaly")]
+    pub anomaly: f64,
+    #[serde(default = "default_weight_alerts")]
+    pub alerts: f64,
+    #[serde(default = "default_weight_enforcement")]
+    pub enforcement: f64,
+}
+
+fn default_weight_pressure() -> f64 {
+    0.35
+}
+
+fn default_weight_headroom() -> f64 {
+    0.2
+}
+
+fn default_weight_anomaly() -> f64 {
+    0.15
+}
+
+fn default_weight_alerts() -> f64 {
+    0.2
+}
+
+fn default_weight_enforcement() -> f64 {
+    0.1
+}
+
+impl Default for HealthWeights {
+    fn default() -> Self {
+        Self {
+            pressure: default_weight_pressure(),
+            headroom: default_weight_headroom(),
+            anomaly: default_weight_anomaly(),
+            alerts: default_weight_alerts(),
+            enforcement: default_weight_enforcement(),
+        }
+    }
+}
+
+/// Expiry and escalation of enforcement actions waiting for approval
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalsConfig {
+    /// Seconds a proposed action waits for a decision
+    #[serde(default = "default_approvals_ttl_secs")]
+    pub ttl_secs: u64,
+    /// Decision taken when nobody reacts before the TTL
+    #[serde(default)]
+    pub on_expiry: ExpiryPolicy,
+    /// Per action type overrides of `on_expiry`, keyed by `kill_process`,
+    /// `freeze_process`, `unfreeze_process`, `throttle_cgroup`,
+    /// `block_source` or `cloudflare_rate_limit`
+    #[serde(default)]
+    pub on_expiry_by_action: HashMap<String, ExpiryPolicy>,
+    /// Remind notifiers about a pending action this often; 0 disables
+    #[serde(default = "default_approvals_escalation_interval_secs")]
+    pub escalation_interval_secs: u64,
+}
+
+fn default_approvals_ttl_secs() -> u64 {
+    300
+}
+
+fn default_approvals_escalation_interval_secs() -> u64 {
+    120
+}
+
+impl Default for ApprovalsConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: default_approvals_ttl_secs(),
+            on_expiry: ExpiryPolicy::default(),
+            on_expiry_by_action: HashMap::new(),
+            escalation_interval_secs: default_approvals_escalation_interval_secs(),
+        }
+    }
+}
+
+impl ApprovalsConfig {
+    /// Policy for an action type, as named by `ActionType::kind`.
+    pub fn expiry_policy(&self, kind: &str) -> ExpiryPolicy {
+        self.on_expiry_by_action
+            .get(kind)
+            .copied()
+            .unwrap_or(self.on_expiry)
+    }
+}
+
+/// What happens to a pending action once its TTL passes.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpiryPolicy {
+    /// Drop the action (status `expired`)
+    #[default]
+    Reject,
+    /// Execute the action as if approved
+    Approve,
+}
+
+/// Host-wide lock that lets a single instance execute enforcement actions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoordinationConfig {
+    #[serde(default = "default_coordination_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_coordination_lock_path")]
+    pub lock_path: String,
+    /// Ask the instance holding the lock to hand enforcement over at startup
+    #[serde(default = "default_coordination_takeover")]
+    pub takeover: bool,
+    /// Time to wait for the handover before warning
+    #[serde(default = "default_coordination_takeover_timeout_secs")]
+    pub takeover_timeout_secs: u64,
+}
+
+fn default_coordination_enabled() -> bool {
+    true
+}
+
+fn default_coordination_lock_path() -> String {
+    "/run/linnix.lock".to_string()
+}
+
+fn default_coordination_takeover() -> bool {
+    true
+}
+
+fn default_coordination_takeover_timeout_secs() -> u64 {
+    10
+}
+
+impl Default for CoordinationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_coordination_enabled(),
+            lock_path: default_coordination_lock_path(),
+            takeover: default_coordination_takeover(),
+            takeover_timeout_secs: default_coordination_takeover_timeout_secs(),
+        }
+    }
+}
+
+/// CPU affinity and scheduling of cognitod's own threads, to keep it off
+/// latency-sensitive cores
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ThreadsConfig {
+    /// Tokio worker threads; defaults to one per CPU in `workers.cpus`, or
+    /// one per online CPU
+    #[serde(default)]
+    pub worker_threads: Option<usize>,
+    /// API, rules, enforcement and other async work
+    #[serde(default)]
+    pub workers: ThreadPlacementConfig,
+    /// Readers draining the kernel perf/sequencer buffers. When set they get
+    /// their own threads, one per CPU in `consumers.cpus`.
+    #[serde(default)]
+    pub consumers: ThreadPlacementConfig,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ThreadPlacementConfig {
+    /// CPU list such as "0-1,6"; unset inherits the process affinity
+    #[serde(default)]
+    pub cpus: Option<String>,
+    /// Nice value; negative values need CAP_SYS_NICE
+    #[serde(default)]
+    pub nice: Option<i32>,
+    /// Run under SCHED_BATCH so the scheduler treats the threads as
+    /// throughput work
+    #[serde(default)]
+    pub sched_batch: bool,
+}
+
+impl ThreadPlacementConfig {
+    pub fn is_set(&self) -> bool {
+        *self != Self::default()
+    }
+}
+
+/// Incident database write-behind queue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncidentStoreConfig {
+    /// Pending writes held in memory; further writes are dropped and counted
+    #[serde(default = "default_incident_queue_capacity")]
+    pub queue_capacity: usize,
+    /// Maximum writes committed in one transaction
+    #[serde(default = "default_incident_batch_size")]
+    pub batch_size: usize,
+    #[serde(default)]
+    pub fsync: FsyncPolicy,
+    /// Remote endpoints told when incidents open and resolve
+    #[serde(default)]
+    pub sinks: Vec<IncidentSinkConfig>,
+}
+
+fn default_incident_queue_capacity() -> usize {
+    1024
+}
+
+fn default_incident_batch_size() -> usize {
+    64
+}
+
+impl Default for IncidentStoreConfig {
+    fn default() -> Self {
+        Self {
+            queue_capacity: default_incident_queue_capacity(),
+            batch_size: default_incident_batch_size(),
+            fsync: FsyncPolicy::default(),
+            sinks: Vec::new(),
+        }
+    }
+}
+
+/// One remote incident sink. Delivery state is recorded on the incident
+/// under `name`, which defaults to the kind.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncidentSinkConfig {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub kind: IncidentSinkKind,
+    /// Attempts per event before giving up
+    #[serde(default = "default_sink_max_attempts")]
+    pub max_attempts: u32,
+    /// First retry delay; doubles per attempt up to 5 minutes
+    #[serde(default = "default_sink_backoff_secs")]
+    pub backoff_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum IncidentSinkKind {
+    /// POST the incident as JSON
+    Webhook {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+    /// PagerDuty Events API v2
+    Pagerduty {
+        routing_key: String,
+        #[serde(default = "default_pagerduty_url")]
+        url: String,
+    },
+    /// Opsgenie Alert API
+    Opsgenie {
+        api_key: String,
+        #[serde(default = "default_opsgenie_url")]
+        url: String,
+    },
+    /// Discord channel webhook, as an embed
+    Discord { url: String },
+}
+
+impl IncidentSinkConfig {
+    pub fn name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| {
+            match self.kind {
+                IncidentSinkKind::Webhook { .. } => "webhook",
+                IncidentSinkKind::Pagerduty { .. } => "pagerduty",
+                IncidentSinkKind::Opsgenie { .. } => "opsgenie",
+                IncidentSinkKind::Discord { .. } => "discord",
+            }
+            .to_string()
+        })
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct NotificationConfig {
-    pub apprise: Option<AppriseConfig>,
-    pub slack: Option<SlackConfig>,
+fn default_sink_max_attempts() -> u32 {
+    6
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AppriseConfig {
-    pub urls: Vec<String>,
-    #[serde(default)]
-    pub min_severity: Option<String>,
+fn default_sink_backoff_secs() -> u64 {
+    5
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SlackConfig {
-    pub webhook_url: String,
-    #[serde(default)]
-    pub channel: Option<String>,
-    #[serde(default = "default_dashboard_url")]
-    pub dashboard_base_url: String,
+fn default_pagerduty_url() -> String {
+    "https://events.pagerduty.com/v2/enqueue".to_string()
 }
 
-fn default_dashboard_url() -> String {
-    "http://localhost:3000".to_string()
+fn default_opsgenie_url() -> String {
+    "https://api.opsgenie.com/v2/alerts".to_string()
 }
 
-#[derive(Debug, Deserialize, Clone, Default)]
-#[allow(dead_code)]
-pub struct Config {
-    #[serde(default)]
-    pub api: ApiConfig,
-    #[serde(default)]
-    pub runtime: RuntimeConfig,
-    #[serde(default)]
-    #[allow(dead_code)]
-    pub logging: LoggingConfig,
-    #[serde(default)]
-    #[allow(dead_code)]
-    pub outputs: OutputConfig,
-    #[serde(default)]
-    #[allow(dead_code)]
-    pub rules: RulesFileConfig,
-    #[serde(default)]
-    pub reasoner: ReasonerConfig,
-    #[serde(default)]
-    pub probes: ProbesConfig,
-    #[serde(default)]
-    pub notifications: Option<NotificationConfig>,
-    #[serde(default)]
-    pub circuit_breaker: CircuitBreakerConfig,
-    #[serde(default)]
-    pub noise_budget: NoiseBudgetConfig,
-    #[serde(default)]
-    pub privacy: PrivacyConfig,
-    #[serde(default)]
-    pub psi: PsiConfig,
-    #[serde(default)]
-    pub docker_enforcement: Option<DockerEnforcementConfig>,
-    #[serde(default)]
-    pub warmth: WarmthConfig,
-    #[serde(default)]
-    pub containers: Vec<ContainerConfig>,
+/// SQLite `synchronous` level for the incident database (WAL journal).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FsyncPolicy {
+    /// fsync on every commit
+    Full,
+    /// fsync at WAL checkpoints; a power loss may drop the last commits
+    #[default]
+    Normal,
+    /// Leave flushing to the OS
+    Off,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -216,6 +2352,20 @@ impl Config {
             }
         }
     }
+
+    /// `[[api.tokens]]` plus the feature tokens of `[forensics]` and
+    /// `[egress]`, which carry operator scope.
+    pub fn api_tokens(&self) -> Vec<ApiTokenConfig> {
+        let operator = [&self.forensics.operator_token, &self.egress.admin_token]
+            .into_iter()
+            .flatten()
+            .filter(|token| !token.is_empty())
+            .map(|token| ApiTokenConfig {
+                token: token.clone(),
+                scope: TokenScope::Operator,
+            });
+        self.api.tokens.iter().cloned().chain(operator).collect()
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -229,6 +2379,12 @@ pub struct RuntimeConfig {
     pub rss_cap_mb: u64,
     #[serde(default = "default_events_rate_cap")]
     pub events_rate_cap: u64,
+    /// How kernel events reach userspace
+    #[serde(default)]
+    pub transport: EventTransport,
+    /// Most events read from the sequencer ring per poll
+    #[serde(default = "default_sequencer_batch")]
+    pub sequencer_batch: usize,
 }
 
 impl Default for RuntimeConfig {
@@ -238,6 +2394,139 @@ impl Default for RuntimeConfig {
             cpu_target_pct: default_cpu_target_pct(),
             rss_cap_mb: default_rss_cap_mb(),
             events_rate_cap: default_events_rate_cap(),
+            transport: EventTransport::default(),
+            sequencer_batch: default_sequencer_batch(),
+        }
+    }
+}
+
+/// Kernel-to-userspace event transport.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EventTransport {
+    /// The strictly ordered ring shared by all CPUs; falls back to `perf`
+    /// when the object has no ring or it cannot be mapped
+    #[default]
+    Sequencer,
+    /// One perf buffer per CPU
+    Perf,
+}
+
+/// Sequencer rings and the consumer position across restarts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SequencerConfig {
+    /// One ring per NUMA node, each read by a consumer pinned to the node
+    #[serde(default)]
+    pub numa: bool,
+    /// File the consumer position is checkpointed to; empty disables
+    #[serde(default = "default_sequencer_cursor_path")]
+    pub cursor_path: String,
+    /// bpffs directory the ring and ticket counters are pinned in while the
+    /// cursor is checkpointed
+    #[serde(default = "default_sequencer_pin_path")]
+    pub pin_path: String,
+    /// Seconds between checkpoints
+    #[serde(default = "default_sequencer_checkpoint_secs")]
+    pub checkpoint_secs: u64,
+    /// Most backlogged tickets read between two live batches after resuming
+    #[serde(default = "default_sequencer_catchup_per_poll")]
+    pub catchup_per_poll: usize,
+    /// Backlogged events older than this many seconds are only counted
+    #[serde(default)]
+    pub summarize_backlog_after_secs: Option<u64>,
+}
+
+fn default_sequencer_cursor_path() -> String {
+    "/var/lib/linnix/sequencer.cursor".to_string()
+}
+
+fn default_sequencer_pin_path() -> String {
+    "/sys/fs/bpf/linnix".to_string()
+}
+
+fn default_sequencer_checkpoint_secs() -> u64 {
+    5
+}
+
+fn default_sequencer_catchup_per_poll() -> usize {
+    4096
+}
+
+impl Default for SequencerConfig {
+    fn default() -> Self {
+        Self {
+            numa: false,
+            cursor_path: default_sequencer_cursor_path(),
+            pin_path: default_sequencer_pin_path(),
+            checkpoint_secs: default_sequencer_checkpoint_secs(),
+            catchup_per_poll: default_sequencer_catchup_per_poll(),
+            summarize_backlog_after_secs: None,
+        }
+    }
+}
+
+/// Event history and live process map kept in memory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextConfig {
+    /// Events kept in the history
+    #[serde(default = "default_context_history_len")]
+    pub history_len: usize,
+    /// Seconds exited processes stay in the history and the live map
+    #[serde(default = "default_context_max_age_secs")]
+    pub max_age_secs: u64,
+    /// Approximate MB the history may hold; 0 limits it by `history_len` only
+    #[serde(default)]
+    pub history_budget_mb: u64,
+    /// Approximate MB the live process map may hold; 0 for no limit
+    #[serde(default)]
+    pub live_budget_mb: u64,
+}
+
+fn default_context_history_len() -> usize {
+    1000
+}
+
+fn default_context_max_age_secs() -> u64 {
+    300
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            history_len: default_context_history_len(),
+            max_age_secs: default_context_max_age_secs(),
+            history_budget_mb: 0,
+            live_budget_mb: 0,
+        }
+    }
+}
+
+/// Startup ordering and the BTF offset cache
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartupConfig {
+    /// Look up Kubernetes metadata while BTF is parsed and the eBPF
+    /// programs load, instead of afterwards
+    #[serde(default = "default_startup_parallel")]
+    pub parallel: bool,
+    /// Offsets derived from kernel BTF, keyed by the kernel build id; empty
+    /// parses BTF on every start
+    #[serde(default = "default_startup_btf_cache")]
+    pub btf_cache: String,
+}
+
+fn default_startup_parallel() -> bool {
+    true
+}
+
+fn default_startup_btf_cache() -> String {
+    "/var/lib/linnix/btf-offsets.json".to_string()
+}
+
+impl Default for StartupConfig {
+    fn default() -> Self {
+        Self {
+            parallel: default_startup_parallel(),
+            btf_cache: default_startup_btf_cache(),
         }
     }
 }
@@ -254,6 +2543,9 @@ fn default_rss_cap_mb() -> u64 {
 fn default_events_rate_cap() -> u64 {
     100_000
 }
+fn default_sequencer_batch() -> usize {
+    1024
+}
 
 #[derive(Debug, Deserialize, Clone)]
 #[allow(dead_code)]
@@ -289,6 +2581,52 @@ fn default_insights_file() -> String {
     "/var/log/linnix/insights.ndjson".to_string()
 }
 
+/// Process context captured into rule alerts when they fire
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertContextConfig {
+    /// Ancestors recorded above the alerting process; 0 records none
+    #[serde(default = "default_alert_context_ancestors")]
+    pub ancestors: usize,
+    /// Youngest children recorded below it; 0 records none
+    #[serde(default = "default_alert_context_children")]
+    pub children: usize,
+    /// Attach the pod of the alerting process
+    #[serde(default = "default_alert_context_pod")]
+    pub pod: bool,
+}
+
+fn default_alert_context_ancestors() -> usize {
+    5
+}
+
+fn default_alert_context_children() -> usize {
+    5
+}
+
+fn default_alert_context_pod() -> bool {
+    true
+}
+
+impl Default for AlertContextConfig {
+    fn default() -> Self {
+        Self {
+            ancestors: default_alert_context_ancestors(),
+            children: default_alert_context_children(),
+            pod: default_alert_context_pod(),
+        }
+    }
+}
+
+/// Delivery settings shared by every alert source
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AlertsConfig {
+    /// Seconds an alert with the same rule, severity and subject is held
+    /// back after it fires; 0 (the default) sends them all. Rules keep their
+    /// own `cooldown`.
+    #[serde(default)]
+    pub cooldown_secs: u64,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 #[allow(dead_code)]
 pub struct RulesFileConfig {
@@ -317,6 +2655,26 @@ pub struct ReasonerConfig {
     pub endpoint: String,
     #[serde(default = "default_reasoner_timeout")]
     pub timeout_ms: u64,
+    /// Incident analyses run concurrently.
+    #[serde(default = "default_analysis_workers")]
+    pub analysis_workers: usize,
+    /// Incident analyses waiting for a worker; the least urgent is dropped
+    /// when full.
+    #[serde(default = "default_analysis_queue_capacity")]
+    pub analysis_queue_capacity: usize,
+    /// An incident analysis still running after this gets the rule-based
+    /// fallback summary.
+    #[serde(default = "default_analysis_deadline_ms")]
+    pub analysis_deadline_ms: u64,
+    /// Delay before the LLM is asked again after a fallback; doubles per
+    /// attempt.
+    #[serde(default = "default_analysis_retry_secs")]
+    pub analysis_retry_secs: u64,
+    #[serde(default = "default_analysis_max_retries")]
+    pub analysis_max_retries: u32,
+    /// A successful `/insights` analysis is served again for this long.
+    #[serde(default = "default_insights_cache_secs")]
+    pub insights_cache_secs: u64,
 }
 
 impl Default for ReasonerConfig {
@@ -325,6 +2683,12 @@ impl Default for ReasonerConfig {
             enabled: default_reasoner_enabled(),
             endpoint: default_reasoner_endpoint(),
             timeout_ms: default_reasoner_timeout(),
+            analysis_workers: default_analysis_workers(),
+            analysis_queue_capacity: default_analysis_queue_capacity(),
+            analysis_deadline_ms: default_analysis_deadline_ms(),
+            analysis_retry_secs: default_analysis_retry_secs(),
+            analysis_max_retries: default_analysis_max_retries(),
+            insights_cache_secs: default_insights_cache_secs(),
         }
     }
 }
@@ -341,6 +2705,30 @@ fn default_reasoner_timeout() -> u64 {
     150
 }
 
+fn default_analysis_workers() -> usize {
+    2
+}
+
+fn default_analysis_queue_capacity() -> usize {
+    64
+}
+
+fn default_analysis_deadline_ms() -> u64 {
+    30_000
+}
+
+fn default_analysis_retry_secs() -> u64 {
+    300
+}
+
+fn default_analysis_max_retries() -> u32 {
+    3
+}
+
+fn default_insights_cache_secs() -> u64 {
+    300
+}
+
 #[derive(Debug, Deserialize, Clone, Default)]
 #[allow(dead_code)]
 pub struct OutputConfig {
@@ -352,30 +2740,6 @@ pub struct OutputConfig {
     pub prometheus: bool,
 }
 
-#[derive(Clone)]
-pub struct OfflineGuard {
-    offline: bool,
-}
-
-impl OfflineGuard {
-    pub fn new(offline: bool) -> Self {
-        Self { offline }
-    }
-    pub fn is_offline(&self) -> bool {
-        self.offline
-    }
-    /// Returns true if network operations are allowed.
-    #[allow(dead_code)]
-    pub fn check(&self, sink: &str) -> bool {
-        if self.offline {
-            log::warn!("offline mode: blocking {sink} sink");
-            false
-        } else {
-            true
-        }
-    }
-}
-
 #[derive(Debug, Deserialize, Clone)]
 pub struct PsiConfig {
     /// Duration in seconds of sustained pressure required to trigger attribution
@@ -397,7 +2761,201 @@ fn default_psi_sustained_pressure_seconds() -> u64 {
 
 #[derive(Debug, Deserialize, Clone, Default)]
 pub struct ProbesConfig {
-    // Configuration for probe settings (reserved for future use)
+    #[serde(default)]
+    pub page_faults: PageFaultThrottleConfig,
+    #[serde(default)]
+    pub fd_pressure: FdPressureConfig,
+    #[serde(default)]
+    pub signals: SignalProbeConfig,
+    #[serde(default)]
+    pub syscalls: SyscallProbeConfig,
+}
+
+/// Per-pid page-fault throttle, written to the BPF object at load time.
+/// Faults over the limit are dropped in-kernel and counted per pid.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageFaultThrottleConfig {
+    #[serde(default)]
+    pub mode: PageFaultThrottleMode,
+    /// `interval` mode: minimum spacing between a pid's faults
+    #[serde(default = "default_page_fault_min_interval_ms")]
+    pub min_interval_ms: u64,
+    /// `budget` mode: sustained faults per second per pid
+    #[serde(default = "default_page_fault_rate")]
+    pub rate: u32,
+    /// `budget` mode: faults a quiet pid may emit back to back
+    #[serde(default = "default_page_fault_burst")]
+    pub burst: u32,
+    /// Seconds between reads of the per-pid suppressed counts
+    #[serde(default = "default_page_fault_accounting_secs")]
+    pub accounting_secs: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PageFaultThrottleMode {
+    /// At most one fault per `min_interval_ms`
+    #[default]
+    Interval,
+    /// Token bucket of `rate` faults per second with bursts up to `burst`
+    Budget,
+}
+
+fn default_page_fault_min_interval_ms() -> u64 {
+    50
+}
+
+fn default_page_fault_rate() -> u32 {
+    20
+}
+
+fn default_page_fault_burst() -> u32 {
+    10
+}
+
+fn default_page_fault_accounting_secs() -> u64 {
+    10
+}
+
+impl Default for PageFaultThrottleConfig {
+    fn default() -> Self {
+        Self {
+            mode: PageFaultThrottleMode::default(),
+            min_interval_ms: default_page_fault_min_interval_ms(),
+            rate: default_page_fault_rate(),
+            burst: default_page_fault_burst(),
+            accounting_secs: default_page_fault_accounting_secs(),
+        }
+    }
+}
+
+impl PageFaultThrottleConfig {
+    /// Spacing between emitted faults once the burst is spent, and the burst
+    /// size, as written to `TelemetryConfig`.
+    pub fn kernel_params(&self) -> (u64, u32) {
+        match self.mode {
+            PageFaultThrottleMode::Interval => {
+                (self.min_interval_ms.max(1).saturating_mul(1_000_000), 1)
+            }
+            PageFaultThrottleMode::Budget => (
+                1_000_000_000 / u64::from(self.rate.max(1)),
+                self.burst.max(1),
+            ),
+        }
+    }
+}
+
+/// When the fd allocation probe reports a process, written to the BPF object
+/// at load time. Running out of fds (`EMFILE`) is always reported.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FdPressureConfig {
+    /// Share of the soft `RLIMIT_NOFILE` at which allocations are reported
+    #[serde(default = "default_fd_pressure_threshold_pct")]
+    pub threshold_pct: u32,
+    /// Minimum spacing between a process's reports
+    #[serde(default = "default_fd_pressure_min_interval_ms")]
+    pub min_interval_ms: u64,
+}
+
+fn default_fd_pressure_threshold_pct() -> u32 {
+    80
+}
+
+fn default_fd_pressure_min_interval_ms() -> u64 {
+    1000
+}
+
+impl Default for FdPressureConfig {
+    fn default() -> Self {
+        Self {
+            threshold_pct: default_fd_pressure_threshold_pct(),
+            min_interval_ms: default_fd_pressure_min_interval_ms(),
+        }
+    }
+}
+
+impl FdPressureConfig {
+    /// Threshold percent and spacing in ns, as written to `TelemetryConfig`.
+    pub fn kernel_params(&self) -> (u32, u64) {
+        (
+            self.threshold_pct.clamp(1, 100),
+            self.min_interval_ms.max(1).saturating_mul(1_000_000),
+        )
+    }
+}
+
+/// Which signals the `signal_generate` tracepoint reports, written to the
+/// BPF object at load time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignalProbeConfig {
+    /// Report only signals whose default action terminates the process;
+    /// `false` reports every signal, `SIGCHLD` and `SIGALRM` included
+    #[serde(default = "default_signals_fatal_only")]
+    pub fatal_only: bool,
+}
+
+fn default_signals_fatal_only() -> bool {
+    true
+}
+
+impl Default for SignalProbeConfig {
+    fn default() -> Self {
+        Self {
+            fatal_only: default_signals_fatal_only(),
+        }
+    }
+}
+
+impl SignalProbeConfig {
+    /// Signal mask as written to `TelemetryConfig`.
+    pub fn kernel_mask(&self) -> u64 {
+        if self.fatal_only {
+            DEFAULT_SIGNAL_MASK
+        } else {
+            u64::MAX
+        }
+    }
+}
+
+/// How often the `sys_enter` tracepoint emits a process's syscall tallies,
+/// written to the BPF object at load time. Calls are counted per process and
+/// syscall number on each CPU; a tally is emitted once it reaches `batch`
+/// calls or is `flush_ms` old, whichever comes first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyscallProbeConfig {
+    /// Calls counted before a tally is emitted
+    #[serde(default = "default_syscalls_batch")]
+    pub batch: u32,
+    /// Age at which a tally is emitted whatever its count
+    #[serde(default = "default_syscalls_flush_ms")]
+    pub flush_ms: u64,
+}
+
+fn default_syscalls_batch() -> u32 {
+    64
+}
+
+fn default_syscalls_flush_ms() -> u64 {
+    1000
+}
+
+impl Default for SyscallProbeConfig {
+    fn default() -> Self {
+        Self {
+            batch: default_syscalls_batch(),
+            flush_ms: default_syscalls_flush_ms(),
+        }
+    }
+}
+
+impl SyscallProbeConfig {
+    /// Batch size and flush interval in ns, as written to `TelemetryConfig`.
+    pub fn kernel_params(&self) -> (u32, u64) {
+        (
+            self.batch.max(1),
+            self.flush_ms.max(1).saturating_mul(1_000_000),
+        )
+    }
 }
 
 /// Circuit breaker configuration for automatic remediation based on PSI (Pressure Stall Information)
@@ -543,8 +3101,12 @@ offline = true
 "#;
         let cfg: Config = toml::from_str(toml).unwrap();
         assert!(cfg.runtime.offline);
+        assert_eq!(cfg.runtime.transport, EventTransport::Sequencer);
         assert_eq!(cfg.api.listen_addr, "127.0.0.1:3000");
         assert!(cfg.api.auth_token.is_none());
+
+        let cfg: Config = toml::from_str("[runtime]\ntransport = \"perf\"\n").unwrap();
+        assert_eq!(cfg.runtime.transport, EventTransport::Perf);
     }
 
     #[test]
@@ -558,6 +3120,82 @@ auth_token = "secret123"
         assert_eq!(cfg.api.auth_token, Some("secret123".to_string()));
     }
 
+    #[test]
+    fn parse_scoped_tokens_and_ui_sections() {
+        let toml = r#"[[api.tokens]]
+token = "viewer"
+
+[[api.tokens]]
+token = "oncall"
+scope = "operator"
+
+[ui]
+read_only_sections = ["overview", "events"]
+"#;
+        let cfg: Config = toml::from_str(toml).unwrap();
+        assert_eq!(cfg.api.tokens[0].scope, TokenScope::ReadOnly);
+        assert_eq!(cfg.api.tokens[1].scope, TokenScope::Operator);
+        assert_eq!(cfg.api_tokens().len(), 2);
+        assert_eq!(
+            cfg.ui.sections(TokenScope::ReadOnly),
+            [DashboardSection::Overview, DashboardSection::Events]
+        );
+        assert_eq!(cfg.ui.sections(TokenScope::Operator).len(), 5);
+    }
+
+    #[test]
+    fn parse_incident_sinks() {
+        let toml = r#"[[incidents.sinks]]
+kind = "pagerduty"
+routing_key = "R0UT1NG"
+
+[[incidents.sinks]]
+kind = "webhook"
+name = "central"
+url = "https://ops.example.com/linnix"
+headers = { Authorization = "Bearer t" }
+max_attempts = 3
+"#;
+        let cfg: Config = toml::from_str(toml).unwrap();
+        let sinks = &cfg.incidents.sinks;
+        assert_eq!(sinks.len(), 2);
+        assert_eq!(sinks[0].name(), "pagerduty");
+        assert_eq!(sinks[0].max_attempts, 6);
+        assert!(matches!(
+            &sinks[0].kind,
+            IncidentSinkKind::Pagerduty { url, .. } if url.ends_with("/v2/enqueue")
+        ));
+        assert_eq!(sinks[1].name(), "central");
+        assert_eq!(sinks[1].max_attempts, 3);
+    }
+
+    #[test]
+    fn page_fault_throttle_params() {
+        let cfg: Config = toml::from_str("").unwrap();
+        assert_eq!(cfg.probes.page_faults.kernel_params(), (50_000_000, 1));
+
+        let toml = r#"[probes.page_faults]
+mode = "budget"
+rate = 200
+burst = 40
+"#;
+        let cfg: Config = toml::from_str(toml).unwrap();
+        assert_eq!(cfg.probes.page_faults.kernel_params(), (5_000_000, 40));
+    }
+
+    #[test]
+    fn syscall_tally_params() {
+        let cfg: Config = toml::from_str("").unwrap();
+        assert_eq!(cfg.probes.syscalls.kernel_params(), (64, 1_000_000_000));
+
+        let toml = r#"[probes.syscalls]
+batch = 0
+flush_ms = 250
+"#;
+        let cfg: Config = toml::from_str(toml).unwrap();
+        assert_eq!(cfg.probes.syscalls.kernel_params(), (1, 250_000_000));
+    }
+
     #[test]
     fn env_override() {
         let mut file = NamedTempFile::new().unwrap();
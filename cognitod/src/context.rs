@@ -1,16 +1,27 @@
-use std::{collections::VecDeque, sync::Arc, sync::Mutex, time::Duration};
+use std::{sync::Arc, sync::Mutex, time::Duration};
 
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use serde::Serialize;
 use tokio::sync::broadcast;
 
 use crate::ProcessEvent;
+use crate::fd_usage::FdUsage;
+use crate::flows::{self, Flow, FlowTracker};
+use crate::identity::{ProcessKey, START_TOLERANCE_NS};
 use crate::k8s::{K8sContext, K8sMetadata};
+use crate::net_traffic::{NetCounters, NetRates};
+use crate::signals::SignalRecord;
+use crate::startup::{self, StartupHistogram, StartupLatency};
+use crate::syscalls::{SyscallHistogram, SyscallSummary};
 use crate::types::SystemSnapshot;
 use crate::utils::psi::PsiMetrics;
 
+mod history;
+use history::History;
+
 use sysinfo::{
     Disks,    // disk container (sysinfo ≥ 0.36)
     Networks, // network container
@@ -22,37 +33,183 @@ pub type ProcessEntry = (ProcessEvent, Option<Arc<K8sMetadata>>);
 
 pub type ProcessHistoryEntry = (u64, ProcessEvent, Option<Arc<K8sMetadata>>);
 
+/// Capacity of the `/events` broadcast channel.
+pub const BROADCAST_CAPACITY: usize = 1024;
+
+/// Traffic and syscalls of live processes advance the live generation at
+/// most once per this many nanoseconds, since nearly every event changes them.
+const ACTIVITY_BUCKET_NS: u64 = 1_000_000_000;
+
+/// Approximate heap bytes of an event's strings and its pod metadata.
+fn event_bytes(event: &ProcessEvent, meta: Option<&Arc<K8sMetadata>>) -> usize {
+    event.hostname.as_ref().map_or(0, String::capacity)
+        + event.script_path.as_ref().map_or(0, String::capacity)
+        + event.exe_path.as_ref().map_or(0, String::capacity)
+        + meta.map_or(0, |meta| meta.approx_bytes())
+}
+
+fn live_entry_bytes(event: &ProcessEvent, meta: Option<&Arc<K8sMetadata>>) -> usize {
+    std::mem::size_of::<(u32, ProcessEntry)>() + event_bytes(event, meta)
+}
+
+/// Approximate memory held by the history and the live map, for `/metrics`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ContextMemory {
+    pub history_entries: usize,
+    pub history_bytes: usize,
+    /// `None` without a byte budget
+    pub history_budget_bytes: Option<usize>,
+    /// Entries dropped to stay within the budget
+    pub history_evicted: u64,
+    pub live_entries: usize,
+    pub live_bytes: usize,
+    pub live_budget_bytes: Option<usize>,
+    pub live_evicted: u64,
+}
+
 pub struct ContextStore {
-    // Store timestamp, event, and optional cached metadata
-    inner: Mutex<VecDeque<ProcessHistoryEntry>>,
+    // Store timestamp, event, and optional cached metadata, indexed by pid,
+    // comm and event type
+    inner: Mutex<History>,
     // Store live process state and cached metadata
     live: Mutex<HashMap<u32, ProcessEntry>>,
     max_age: Duration,
     max_len: usize,
+    // History length in effect; below `max_len` while degraded
+    history_limit: AtomicUsize,
     broadcaster: broadcast::Sender<ProcessEvent>,
+    // Backlog at which events stop being broadcast; 0 uses the full channel
+    broadcast_limit: AtomicUsize,
+    broadcast_shed: AtomicU64,
     seq: AtomicU64,
+    // Bumped whenever the live map changes; lets readers skip unchanged snapshots
+    generation: AtomicU64,
+    // `ACTIVITY_BUCKET_NS` bucket of the last traffic or syscall update that
+    // bumped it
+    activity_bucket: AtomicU64,
     system_snapshot: Mutex<SystemSnapshot>,
     sys: Mutex<System>,
     k8s_ctx: Option<Arc<K8sContext>>,
+    // Exec-to-first-listen latency of live processes; locked after `live`
+    startup: Mutex<HashMap<u32, StartupLatency>>,
+    startup_hist: StartupHistogram,
+    // Traffic by socket family of live processes; locked after `live`
+    net: Mutex<HashMap<u32, NetCounters>>,
+    node_net: Mutex<NetCounters>,
+    // Remote endpoints of live processes' TCP and UDP traffic; locked after `live`
+    flows: Mutex<FlowTracker>,
+    // Latest fd pressure report of live processes; locked after `live`
+    fds: Mutex<HashMap<u32, FdUsage>>,
+    // Syscall histograms of live processes; locked after `live`
+    syscalls: Mutex<HashMap<u32, SyscallHistogram>>,
+    // Last fatal signal sent to live processes; locked after `live`
+    signals: Mutex<HashMap<u32, SignalRecord>>,
+    // Identity of each entry in `live`; locked after `live`
+    keys: Mutex<HashMap<u32, ProcessKey>>,
+    // Approximate size of `live`, kept while it is locked
+    live_bytes: AtomicUsize,
+    // Byte budgets; 0 leaves only the entry limits
+    history_budget: AtomicUsize,
+    live_budget: AtomicUsize,
+    history_evicted: AtomicU64,
+    live_evicted: AtomicU64,
+}
+
+/// How an event relates to the live entry of its pid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Relation {
+    /// Same process as the entry, or there is no entry
+    Same,
+    /// A later process that was given the pid of the entry
+    Successor,
+    /// An event of an earlier process, arriving after the entry replaced it
+    Stale,
+}
+
+fn relation_to(entry: &ProcessEvent, key: Option<&ProcessKey>, event: &ProcessEvent) -> Relation {
+    let exited = entry.event_type == 2;
+    let known = key.filter(|_| event.ts_ns != 0);
+    if event.event_type == 1 {
+        // A fork stamped before the entry started is the entry's own fork,
+        // arriving after its exec
+        return match known {
+            Some(key) if key.started_before(event.ts_ns) => Relation::Successor,
+            Some(_) => Relation::Same,
+            None if exited => Relation::Successor,
+            None => Relation::Same,
+        };
+    }
+    match known {
+        Some(key) if event.ts_ns.saturating_add(START_TOLERANCE_NS) < key.start_ns => {
+            Relation::Stale
+        }
+        _ if exited => Relation::Successor,
+        _ => Relation::Same,
+    }
 }
 
+/// Entry of [`ContextStore::top_rss_processes`].
 #[derive(Clone, Debug)]
 pub struct ProcessMemorySummary {
     pub pid: u32,
     pub comm: String,
     pub mem_percent: f32,
+    pub script_path: Option<String>,
+}
+
+/// Entry of [`ContextStore::top_cpu_processes`].
+#[derive(Clone, Debug)]
+pub struct ProcessCpuSummary {
+    pub pid: u32,
+    pub comm: String,
+    pub cpu_percent: f32,
+    pub script_path: Option<String>,
+}
+
+impl ProcessMemorySummary {
+    /// Script name for interpreters, otherwise `comm`.
+    pub fn name(&self) -> &str {
+        display_name(&self.comm, self.script_path.as_deref())
+    }
+}
+
+impl ProcessCpuSummary {
+    /// Script name for interpreters, otherwise `comm`.
+    pub fn name(&self) -> &str {
+        display_name(&self.comm, self.script_path.as_deref())
+    }
+}
+
+fn display_name<'a>(comm: &'a str, script_path: Option<&'a str>) -> &'a str {
+    script_path.map_or(comm, crate::script::script_name)
+}
+
+fn comm_to_string(comm: &[u8; 16]) -> String {
+    let nul = comm.iter().position(|b| *b == 0).unwrap_or(comm.len());
+    let slice = &comm[..nul];
+    let text = String::from_utf8_lossy(slice).trim().to_string();
+    if text.is_empty() {
+        "unknown".to_string()
+    } else {
+        text
+    }
 }
 
 impl ContextStore {
     pub fn new(max_age: Duration, max_len: usize, k8s_ctx: Option<Arc<K8sContext>>) -> Self {
-        let (broadcaster, _) = broadcast::channel(1024);
+        let (broadcaster, _) = broadcast::channel(BROADCAST_CAPACITY);
         Self {
-            inner: Mutex::new(VecDeque::new()),
+            inner: Mutex::new(History::default()),
             live: Mutex::new(HashMap::new()),
             max_age,
             max_len,
+            history_limit: AtomicUsize::new(max_len),
             broadcaster,
+            broadcast_limit: AtomicUsize::new(0),
+            broadcast_shed: AtomicU64::new(0),
             seq: AtomicU64::new(1),
+            generation: AtomicU64::new(0),
+            activity_bucket: AtomicU64::new(0),
             system_snapshot: Mutex::new(SystemSnapshot {
                 timestamp: 0,
                 cpu_percent: 0.0,
@@ -70,6 +227,20 @@ impl ContextStore {
             }),
             sys: Mutex::new(System::new_all()),
             k8s_ctx,
+            startup: Mutex::new(HashMap::new()),
+            startup_hist: StartupHistogram::default(),
+            net: Mutex::new(HashMap::new()),
+            node_net: Mutex::new(NetCounters::default()),
+            flows: Mutex::new(FlowTracker::default()),
+            fds: Mutex::new(HashMap::new()),
+            syscalls: Mutex::new(HashMap::new()),
+            signals: Mutex::new(HashMap::new()),
+            keys: Mutex::new(HashMap::new()),
+            live_bytes: AtomicUsize::new(0),
+            history_budget: AtomicUsize::new(0),
+            live_budget: AtomicUsize::new(0),
+            history_evicted: AtomicU64::new(0),
+            live_evicted: AtomicU64::new(0),
         }
     }
 
@@ -83,6 +254,21 @@ impl ContextStore {
             .unwrap_or_default()
             .as_nanos() as u64;
 
+        // Remote end of a net event, read from the kernel before `live` is locked
+        let transfer = flows::transfer(&event);
+
+        // A pid is reused once its process exits; nothing recorded for the
+        // earlier process may carry over to the later one
+        let relation = {
+            let live = self.live.lock().unwrap();
+            match live.get(&event.pid) {
+                Some((proc, _)) => {
+                    relation_to(proc, self.keys.lock().unwrap().get(&event.pid), &event)
+                }
+                None => Relation::Same,
+            }
+        };
+
         // Try to fetch or inherit metadata
         let mut metadata: Option<Arc<K8sMetadata>> = None;
 
@@ -90,7 +276,7 @@ impl ContextStore {
             match event.event_type {
                 0 | 1 => {
                     // Exec or Fork: try to get fresh metadata
-                    if let Some(meta) = ctx.get_metadata_for_pid(event.pid) {
+                    if let Some(meta) = ctx.get_metadata_for_event(event.pid, event.cgroup_id) {
                         metadata = Some(Arc::new(meta));
                     } else if event.event_type == 1 {
                         // Fork fallback: inherit parent's metadata if we can't find child's yet
@@ -104,14 +290,18 @@ impl ContextStore {
                 2 => {
                     // Exit: check if we have it in live map
                     let live = self.live.lock().unwrap();
-                    if let Some((_, meta)) = live.get(&event.pid) {
+                    if relation == Relation::Same
+                        && let Some((_, meta)) = live.get(&event.pid)
+                    {
                         metadata = meta.clone();
                     }
                 }
                 _ => {
                     // Other events: try to lookup in live map first
                     let live = self.live.lock().unwrap();
-                    if let Some((_, meta)) = live.get(&event.pid) {
+                    if relation == Relation::Same
+                        && let Some((_, meta)) = live.get(&event.pid)
+                    {
                         metadata = meta.clone();
                     }
                 }
@@ -119,7 +309,7 @@ impl ContextStore {
         }
 
         // Timestamp fix for Exit events: use start time from live map
-        if event.event_type == 2 {
+        if event.event_type == 2 && relation == Relation::Same {
             let live = self.live.lock().unwrap();
             if let Some((proc, _)) = live.get(&event.pid) {
                 // The Exit event currently has ts_ns = exit time.
@@ -130,10 +320,12 @@ impl ContextStore {
         }
 
         // If we still don't have metadata (e.g. late discovery), try one last check for non-exit
+        // events of a task (node-wide events such as connections carry pid 0)
         if metadata.is_none()
             && event.event_type != 2
+            && event.pid != 0
             && let Some(ctx) = &self.k8s_ctx
-            && let Some(meta) = ctx.get_metadata_for_pid(event.pid)
+            && let Some(meta) = ctx.get_metadata_for_event(event.pid, event.cgroup_id)
         {
             metadata = Some(Arc::new(meta));
         }
@@ -141,22 +333,55 @@ impl ContextStore {
         {
             let mut queue = self.inner.lock().unwrap();
             queue.push_back((now, event.clone(), metadata.clone()));
-            Self::prune_locked(&mut queue, self.max_age, self.max_len);
+            self.prune_history_locked(&mut queue, self.history_limit());
         }
 
         {
             let mut live = self.get_live_map();
-            match event.event_type {
+            if relation == Relation::Successor && matches!(event.event_type, 0..=2) {
+                if let Some((proc, meta)) = live.remove(&event.pid) {
+                    self.live_bytes
+                        .fetch_sub(live_entry_bytes(&proc, meta.as_ref()), Ordering::Relaxed);
+                }
+                self.forget_locked(event.pid);
+            }
+            let mut changed = match event.event_type {
+                _ if relation == Relation::Stale => false,
                 0 => {
                     // Exec
                     event.set_exit_time(None);
-                    live.insert(event.pid, (event.clone(), metadata));
+                    self.live_bytes.fetch_add(
+                        live_entry_bytes(&event, metadata.as_ref()),
+                        Ordering::Relaxed,
+                    );
+                    if let Some((proc, meta)) = live.insert(event.pid, (event.clone(), metadata)) {
+                        self.live_bytes
+                            .fetch_sub(live_entry_bytes(&proc, meta.as_ref()), Ordering::Relaxed);
+                    }
+                    self.startup.lock().unwrap().remove(&event.pid);
+                    true
                 }
                 1 => {
-                    // Fork
+                    // Fork: children run their parent's executable, and
+                    // workers of an interpreter keep running its script
                     event.set_exit_time(None);
+                    if let Some((parent, _)) = live.get(&event.ppid) {
+                        if event.exe_path.is_none() {
+                            event.exe_path = parent.exe_path.clone();
+                        }
+                        if event.script_path.is_none() && parent.comm == event.comm {
+                            event.script_path = parent.script_path.clone();
+                        }
+                    }
+                    let bytes = live_entry_bytes(&event, metadata.as_ref());
+                    let before = live.len();
                     live.entry(event.pid)
                         .or_insert_with(|| (event.clone(), metadata));
+                    let added = live.len() != before;
+                    if added {
+                        self.live_bytes.fetch_add(bytes, Ordering::Relaxed);
+                    }
+                    added
                 }
                 2 => {
                     if let Some((proc, _)) = live.get_mut(&event.pid) {
@@ -164,24 +389,359 @@ impl ContextStore {
                         proc.event_type = 2;
                     } else {
                         event.set_exit_time(Some(now));
+                        self.live_bytes.fetch_add(
+                            live_entry_bytes(&event, metadata.as_ref()),
+                            Ordering::Relaxed,
+                        );
                         live.insert(event.pid, (event.clone(), metadata));
                     }
+                    true
+                }
+                3 => {
+                    // Net: traffic is kept for processes in the live map
+                    self.node_net
+                        .lock()
+                        .unwrap()
+                        .record(event.aux, event.data, now);
+                    if relation == Relation::Same && live.contains_key(&event.pid) {
+                        self.net
+                            .lock()
+                            .unwrap()
+                            .entry(event.pid)
+                            .or_default()
+                            .record(event.aux, event.data, now);
+                        if let Some(transfer) = &transfer {
+                            self.flows.lock().unwrap().record(event.pid, transfer, now);
+                        }
+                        self.activity_changed(now)
+                    } else {
+                        false
+                    }
+                }
+                5 => {
+                    // Syscall: tallies are kept for processes in the live map
+                    if relation == Relation::Same && live.contains_key(&event.pid) {
+                        self.syscalls
+                            .lock()
+                            .unwrap()
+                            .entry(event.pid)
+                            .or_default()
+                            .record(event.data as u32, event.data2, now);
+                        self.activity_changed(now)
+                    } else {
+                        false
+                    }
+                }
+                8 => {
+                    // Listen: the first one after exec marks the service ready
+                    let mut startup = self.startup.lock().unwrap();
+                    match live.get(&event.pid) {
+                        Some((proc, _))
+                            if relation == Relation::Same && !startup.contains_key(&event.pid) =>
+                        {
+                            startup::measure(proc, &event).is_some_and(|latency| {
+                                self.startup_hist.observe(&latency);
+                                startup.insert(event.pid, latency);
+                                true
+                            })
+                        }
+                        _ => false,
+                    }
+                }
+                10 => {
+                    // FdPressure: the latest report is kept for processes in the live map
+                    let tracked = relation == Relation::Same && live.contains_key(&event.pid);
+                    if tracked {
+                        self.fds
+                            .lock()
+                            .unwrap()
+                            .insert(event.pid, FdUsage::from_event(&event));
+                    }
+                    tracked
+                }
+                11 => {
+                    // Signal: the last fatal one sent to a live process is
+                    // kept as what killed it
+                    let record = SignalRecord::from_event(&event);
+                    if record.is_fatal() && live.contains_key(&record.target_pid) {
+                        let mut signals = self.signals.lock().unwrap();
+                        let kept = signals
+                            .get(&record.target_pid)
+                            .is_none_or(|previous| record.supersedes(previous));
+                        if kept {
+                            signals.insert(record.target_pid, record);
+                        }
+                        kept
+                    } else {
+                        false
+                    }
                 }
-                _ => {}
+                _ => false,
+            };
+            // The first stamped event of a process marks its start: the fork,
+            // or the exec or exit of a process whose fork was not seen
+            if relation != Relation::Stale
+                && matches!(event.event_type, 0..=2)
+                && event.ts_ns != 0
+                && live.contains_key(&event.pid)
+            {
+                self.keys
+                    .lock()
+                    .unwrap()
+                    .entry(event.pid)
+                    .or_insert(ProcessKey::new(event.pid, event.ts_ns));
             }
 
-            live.retain(|_, (proc, _)| {
-                proc.event_type != 2
+            let before = live.len();
+            let mut freed = 0;
+            live.retain(|_, (proc, meta)| {
+                let keep = proc.event_type != 2
                     || proc
                         .exit_time()
-                        .is_none_or(|t| now.saturating_sub(t) < self.max_age.as_nanos() as u64)
+                        .is_none_or(|t| now.saturating_sub(t) < self.max_age.as_nanos() as u64);
+                if !keep {
+                    freed += live_entry_bytes(proc, meta.as_ref());
+                }
+                keep
             });
+            self.live_bytes.fetch_sub(freed, Ordering::Relaxed);
+            changed |= self.evict_live_locked(&mut live);
+            if live.len() != before {
+                changed = true;
+                self.startup
+                    .lock()
+                    .unwrap()
+                    .retain(|pid, _| live.contains_key(pid));
+                self.net
+                    .lock()
+                    .unwrap()
+                    .retain(|pid, _| live.contains_key(pid));
+                self.flows
+                    .lock()
+                    .unwrap()
+                    .retain(|pid| live.contains_key(&pid));
+                self.fds
+                    .lock()
+                    .unwrap()
+                    .retain(|pid, _| live.contains_key(pid));
+                self.syscalls
+                    .lock()
+                    .unwrap()
+                    .retain(|pid, _| live.contains_key(pid));
+                self.signals
+                    .lock()
+                    .unwrap()
+                    .retain(|pid, _| live.contains_key(pid));
+                self.keys
+                    .lock()
+                    .unwrap()
+                    .retain(|pid, _| live.contains_key(pid));
+            }
+            if changed {
+                self.generation.fetch_add(1, Ordering::Relaxed);
+            }
         }
 
         event.seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        let limit = self.broadcast_limit.load(Ordering::Relaxed);
+        if limit > 0 && self.broadcaster.len() >= limit {
+            self.broadcast_shed.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
         let _ = self.broadcaster.send(event);
     }
 
+    /// Whether a traffic or syscall update at `now` starts a new
+    /// `ACTIVITY_BUCKET_NS` bucket.
+    fn activity_changed(&self, now: u64) -> bool {
+        let bucket = now / ACTIVITY_BUCKET_NS;
+        self.activity_bucket.swap(bucket, Ordering::Relaxed) != bucket
+    }
+
+    /// Drop the side data of the process last seen as `pid`; `live` must be
+    /// locked.
+    fn forget_locked(&self, pid: u32) {
+        self.startup.lock().unwrap().remove(&pid);
+        self.net.lock().unwrap().remove(&pid);
+        self.flows.lock().unwrap().remove(pid);
+        self.fds.lock().unwrap().remove(&pid);
+        self.syscalls.lock().unwrap().remove(&pid);
+        self.signals.lock().unwrap().remove(&pid);
+        self.keys.lock().unwrap().remove(&pid);
+    }
+
+    pub fn history_limit(&self) -> usize {
+        self.history_limit.load(Ordering::Relaxed)
+    }
+
+    /// Keep at most `limit` history entries (capped at the configured
+    /// length), pruning the excess now.
+    pub fn set_history_limit(&self, limit: usize) {
+        let limit = limit.min(self.max_len);
+        self.history_limit.store(limit, Ordering::Relaxed);
+        let mut queue = self.inner.lock().unwrap();
+        self.prune_history_locked(&mut queue, limit);
+    }
+
+    /// Cap the approximate bytes held by the history and by the live map;
+    /// `None` leaves only the entry limits. Over its budget, the history
+    /// drops its oldest entries and the live map its exited processes, then
+    /// the longest-running ones.
+    pub fn set_memory_budget(&self, history: Option<usize>, live: Option<usize>) {
+        self.history_budget
+            .store(history.unwrap_or(0), Ordering::Relaxed);
+        self.live_budget.store(live.unwrap_or(0), Ordering::Relaxed);
+        {
+            let mut queue = self.inner.lock().unwrap();
+            self.prune_history_locked(&mut queue, self.history_limit());
+        }
+        let mut live = self.get_live_map();
+        if self.evict_live_locked(&mut live) {
+            self.generation.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn memory_usage(&self) -> ContextMemory {
+        let (history_entries, history_bytes) = {
+            let queue = self.inner.lock().unwrap();
+            (queue.len(), queue.bytes())
+        };
+        let budget = |b: &AtomicUsize| Some(b.load(Ordering::Relaxed)).filter(|b| *b > 0);
+        ContextMemory {
+            history_entries,
+            history_bytes,
+            history_budget_bytes: budget(&self.history_budget),
+            history_evicted: self.history_evicted.load(Ordering::Relaxed),
+            live_entries: self.live.lock().unwrap().len(),
+            live_bytes: self.live_bytes.load(Ordering::Relaxed),
+            live_budget_bytes: budget(&self.live_budget),
+            live_evicted: self.live_evicted.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Drop live entries until the map is back under nine tenths of its
+    /// budget: exited processes first, oldest exit first, then running
+    /// processes, earliest start first. `live` must be locked. Returns
+    /// whether anything was dropped.
+    fn evict_live_locked(&self, live: &mut HashMap<u32, ProcessEntry>) -> bool {
+        let budget = self.live_budget.load(Ordering::Relaxed);
+        if budget == 0 || self.live_bytes.load(Ordering::Relaxed) <= budget {
+            return false;
+        }
+        let target = budget / 10 * 9;
+        let mut order: Vec<(bool, u64, u32)> = live
+            .iter()
+            .map(|(pid, (proc, _))| match proc.exit_time() {
+                Some(exited) if proc.event_type == 2 => (false, exited, *pid),
+                _ => (true, proc.ts_ns, *pid),
+            })
+            .collect();
+        order.sort_unstable();
+        let mut evicted = 0;
+        for (_, _, pid) in order {
+            if self.live_bytes.load(Ordering::Relaxed) <= target {
+                break;
+            }
+            if let Some((proc, meta)) = live.remove(&pid) {
+                self.live_bytes
+                    .fetch_sub(live_entry_bytes(&proc, meta.as_ref()), Ordering::Relaxed);
+                self.forget_locked(pid);
+                evicted += 1;
+            }
+        }
+        self.live_evicted.fetch_add(evicted, Ordering::Relaxed);
+        evicted > 0
+    }
+
+    /// Restore the configured history length.
+    pub fn reset_history_limit(&self) {
+        self.history_limit.store(self.max_len, Ordering::Relaxed);
+    }
+
+    pub fn clear_history(&self) {
+        self.inner.lock().unwrap().clear();
+    }
+
+    /// Stop broadcasting while `limit` events are waiting for subscribers;
+    /// `None` allows the whole channel.
+    pub fn set_broadcast_limit(&self, limit: Option<usize>) {
+        self.broadcast_limit
+            .store(limit.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    /// Events not broadcast because of the broadcast limit.
+    pub fn broadcast_shed(&self) -> u64 {
+        self.broadcast_shed.load(Ordering::Relaxed)
+    }
+
+    /// Exec-to-listen latency recorded for a live process.
+    pub fn startup_latency(&self, pid: u32) -> Option<StartupLatency> {
+        self.startup.lock().unwrap().get(&pid).copied()
+    }
+
+    /// Latency a listen event will record once added, or `None` when it is
+    /// not the first listen since exec. Lets handlers that run before `add`
+    /// see the measurement.
+    pub fn pending_startup(&self, listen: &ProcessEvent) -> Option<StartupLatency> {
+        let live = self.live.lock().unwrap();
+        let (proc, _) = live.get(&listen.pid)?;
+        if self.startup.lock().unwrap().contains_key(&listen.pid) {
+            return None;
+        }
+        startup::measure(proc, listen)
+    }
+
+    /// Network rates by socket family of a live process; `None` before its
+    /// first net event.
+    pub fn net_rates(&self, pid: u32) -> Option<NetRates> {
+        let net = self.net.lock().unwrap();
+        net.get(&pid)
+            .map(|counters| counters.rates(wall_clock_ns()))
+    }
+
+    /// TCP and UDP flows of a live process, most bytes first; `None` when
+    /// `pid` is not in the live map.
+    pub fn flows(&self, pid: u32) -> Option<Vec<Flow>> {
+        let live = self.live.lock().unwrap();
+        live.contains_key(&pid)
+            .then(|| self.flows.lock().unwrap().flows(pid))
+    }
+
+    /// Syscall histogram of a live process; `None` when `pid` is not in the
+    /// live map. Empty until its first tally is emitted.
+    pub fn syscalls(&self, pid: u32) -> Option<SyscallSummary> {
+        let live = self.live.lock().unwrap();
+        live.contains_key(&pid).then(|| {
+            self.syscalls
+                .lock()
+                .unwrap()
+                .get(&pid)
+                .map(SyscallHistogram::summary)
+                .unwrap_or_else(|| SyscallHistogram::default().summary())
+        })
+    }
+
+    /// Latest fd pressure report of a live process; `None` for processes
+    /// that never came near their limit.
+    pub fn fd_usage(&self, pid: u32) -> Option<FdUsage> {
+        self.fds.lock().unwrap().get(&pid).copied()
+    }
+
+    /// Last fatal signal sent to a live or recently exited process; `None`
+    /// when none was seen.
+    pub fn killed_by(&self, pid: u32) -> Option<SignalRecord> {
+        self.signals.lock().unwrap().get(&pid).cloned()
+    }
+
+    /// Node-wide traffic by socket family, of every process.
+    pub fn node_net(&self) -> NetCounters {
+        self.node_net.lock().unwrap().clone()
+    }
+
+    pub fn startup_histogram(&self) -> &StartupHistogram {
+        &self.startup_hist
+    }
+
     pub fn get_recent(&self) -> Vec<ProcessEvent> {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -195,7 +755,45 @@ impl ContextStore {
             .collect()
     }
 
-    fn prune_locked(queue: &mut VecDeque<ProcessHistoryEntry>, max_age: Duration, max_len: usize) {
+    /// History events matching every given filter, oldest first, at most
+    /// `limit` of the newest. Served from the history indexes.
+    pub fn history(
+        &self,
+        pid: Option<u32>,
+        comm: Option<&str>,
+        event_type: Option<u32>,
+        limit: usize,
+    ) -> Vec<ProcessEvent> {
+        let max_age = self.max_age.as_nanos() as u64;
+        let now = wall_clock_ns();
+        let queue = self.inner.lock().unwrap();
+        let mut events: Vec<ProcessEvent> = queue
+            .select(pid, comm, event_type)
+            .rev()
+            .filter(|(t, _, _)| now.saturating_sub(*t) <= max_age)
+            .take(limit)
+            .map(|(_, e, _)| e.clone())
+            .collect();
+        events.reverse();
+        events
+    }
+
+    /// Prune to `max_len` entries and the byte budget, counting the entries
+    /// dropped for size.
+    fn prune_history_locked(&self, queue: &mut History, max_len: usize) {
+        Self::prune_locked(queue, self.max_age, max_len);
+        let budget = self.history_budget.load(Ordering::Relaxed);
+        if budget == 0 {
+            return;
+        }
+        let mut evicted = 0;
+        while queue.bytes() > budget && queue.pop_front().is_some() {
+            evicted += 1;
+        }
+        self.history_evicted.fetch_add(evicted, Ordering::Relaxed);
+    }
+
+    fn prune_locked(queue: &mut History, max_age: Duration, max_len: usize) {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
@@ -222,11 +820,30 @@ impl ContextStore {
         live.values().map(|(e, _)| e.clone()).collect()
     }
 
+    /// Counter that advances whenever a live process is added, removed, marked
+    /// exited, gains the fatal signal it is reported as killed by, or has its
+    /// CPU/memory usage refreshed to a new value; traffic and syscalls advance
+    /// it at most once a second.
+    pub fn live_generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
     pub fn get_process_by_pid(&self, pid: u32) -> Option<ProcessEvent> {
         let live = self.get_live_map();
         live.get(&pid).map(|(e, _)| e.clone())
     }
 
+    /// Identity of the process in the live map under `pid`.
+    pub fn process_key(&self, pid: u32) -> Option<ProcessKey> {
+        self.keys.lock().unwrap().get(&pid).copied()
+    }
+
+    /// Pod metadata cached for a live process.
+    pub fn k8s_metadata(&self, pid: u32) -> Option<Arc<K8sMetadata>> {
+        let live = self.get_live_map();
+        live.get(&pid).and_then(|(_, meta)| meta.clone())
+    }
+
     pub fn broadcaster(&self) -> broadcast::Sender<ProcessEvent> {
         self.broadcaster.clone()
     }
@@ -238,17 +855,6 @@ impl ContextStore {
     pub fn top_rss_processes(&self, limit: usize) -> Vec<ProcessMemorySummary> {
         use std::cmp::Ordering;
 
-        fn comm_to_string(comm: &[u8; 16]) -> String {
-            let nul = comm.iter().position(|b| *b == 0).unwrap_or(comm.len());
-            let slice = &comm[..nul];
-            let text = String::from_utf8_lossy(slice).trim().to_string();
-            if text.is_empty() {
-                "unknown".to_string()
-            } else {
-                text
-            }
-        }
-
         let live = self.get_live_map();
         let mut entries: Vec<ProcessMemorySummary> = live
             .values()
@@ -261,6 +867,7 @@ impl ContextStore {
                     pid: proc.pid,
                     comm: comm_to_string(&proc.comm),
                     mem_percent: mem,
+                    script_path: proc.script_path.clone(),
                 })
             })
             .collect();
@@ -277,40 +884,30 @@ impl ContextStore {
         entries
     }
 
-    pub fn top_cpu_processes(&self, limit: usize) -> Vec<ProcessMemorySummary> {
+    pub fn top_cpu_processes(&self, limit: usize) -> Vec<ProcessCpuSummary> {
         use std::cmp::Ordering;
 
-        fn comm_to_string(comm: &[u8; 16]) -> String {
-            let nul = comm.iter().position(|b| *b == 0).unwrap_or(comm.len());
-            let slice = &comm[..nul];
-            let text = String::from_utf8_lossy(slice).trim().to_string();
-            if text.is_empty() {
-                "unknown".to_string()
-            } else {
-                text
-            }
-        }
-
         let live = self.get_live_map();
-        let mut entries: Vec<ProcessMemorySummary> = live
+        let mut entries: Vec<ProcessCpuSummary> = live
             .values()
             .filter_map(|(proc, _)| {
                 let cpu = proc.cpu_percent()?;
                 if cpu <= 0.0 {
                     return None;
                 }
-                Some(ProcessMemorySummary {
+                Some(ProcessCpuSummary {
                     pid: proc.pid,
                     comm: comm_to_string(&proc.comm),
-                    mem_percent: cpu, // Reusing struct field for CPU
+                    cpu_percent: cpu,
+                    script_path: proc.script_path.clone(),
                 })
             })
             .collect();
         drop(live);
 
         entries.sort_by(|a, b| {
-            b.mem_percent
-                .partial_cmp(&a.mem_percent)
+            b.cpu_percent
+                .partial_cmp(&a.cpu_percent)
                 .unwrap_or(Ordering::Equal)
         });
         if entries.len() > limit {
@@ -391,8 +988,20 @@ impl ContextStore {
         sys.refresh_all();
 
         let mut live = self.get_live_map();
+        let keys = self.keys.lock().unwrap();
+        let mut changed = false;
         for (event, _) in live.values_mut() {
-            if let Some(proc) = sys.process(Pid::from_u32(event.pid)) {
+            // An exited entry's pid may already run another process
+            if event.event_type == 2 {
+                continue;
+            }
+            if let Some(proc) = sys.process(Pid::from_u32(event.pid))
+                && keys
+                    .get(&event.pid)
+                    .and_then(ProcessKey::started_epoch_secs)
+                    .is_none_or(|started| proc.start_time() <= started + 1)
+            {
+                let (cpu_before, mem_before) = (event.cpu_percent(), event.mem_percent());
                 event.set_cpu_percent(Some(proc.cpu_usage()));
                 let mem_pct = if sys.total_memory() > 0 {
                     Some((proc.memory() as f32 / sys.total_memory() as f32) * 100.0)
@@ -400,17 +1009,21 @@ impl ContextStore {
                     Some(0.0)
                 };
                 event.set_mem_percent(mem_pct);
+                changed |= event.cpu_percent() != cpu_before || event.mem_percent() != mem_before;
             }
         }
+        if changed {
+            self.generation.fetch_add(1, Ordering::Relaxed);
+        }
     }
 
     /// Get top CPU processes from the entire system (not just eBPF-tracked ones).
     /// This is a fallback for circuit breaker when no eBPF-tracked processes exist.
-    pub fn top_cpu_processes_systemwide(&self, limit: usize) -> Vec<ProcessMemorySummary> {
+    pub fn top_cpu_processes_systemwide(&self, limit: usize) -> Vec<ProcessCpuSummary> {
         use std::cmp::Ordering;
 
         let sys = self.sys.lock().unwrap();
-        let mut entries: Vec<ProcessMemorySummary> = sys
+        let mut entries: Vec<ProcessCpuSummary> = sys
             .processes()
             .values()
             .filter_map(|proc| {
@@ -418,17 +1031,24 @@ impl ContextStore {
                 if cpu <= 0.0 {
                     return None;
                 }
-                Some(ProcessMemorySummary {
+                let comm = proc.name().to_string_lossy().to_string();
+                let args: Vec<String> = proc
+                    .cmd()
+                    .iter()
+                    .map(|a| a.to_string_lossy().into_owned())
+                    .collect();
+                Some(ProcessCpuSummary {
                     pid: proc.pid().as_u32(),
-                    comm: proc.name().to_string_lossy().to_string(),
-                    mem_percent: cpu,
+                    script_path: crate::script::script_from_args(&comm, &args, |_| false),
+                    comm,
+                    cpu_percent: cpu,
                 })
             })
             .collect();
 
         entries.sort_by(|a, b| {
-            b.mem_percent
-                .partial_cmp(&a.mem_percent)
+            b.cpu_percent
+                .partial_cmp(&a.cpu_percent)
                 .unwrap_or(Ordering::Equal)
         });
         if entries.len() > limit {
@@ -454,12 +1074,13 @@ impl ContextStore {
 
         let queue = self.inner.lock().unwrap();
 
-        // Scan history for relevant events
-        for (ts, event, meta_opt) in queue.iter() {
-            if *ts < cutoff {
-                continue;
-            }
-
+        // Forks and exits only, newest first until the window starts
+        let forks = queue.select(None, None, Some(1)).rev();
+        let exits = queue.select(None, None, Some(2)).rev();
+        for (_, event, meta_opt) in forks
+            .take_while(|(ts, _, _)| *ts >= cutoff)
+            .chain(exits.take_while(|(ts, _, _)| *ts >= cutoff))
+        {
             // Use the metadata cached at time of event
             if let Some(meta) = meta_opt {
                 let key = format!("{}/{}", meta.namespace, meta.pod_name);
@@ -469,7 +1090,6 @@ impl ContextStore {
                     *fork_counts.entry(key.clone()).or_default() += 1;
                 }
 
-                // Count short jobs (exit event with lifetime < 1s)
                 // Count short jobs (exit event with lifetime < 1s)
                 if event.event_type == 2
                     && let Some(exit_time) = event.exit_time()
@@ -486,6 +1106,13 @@ impl ContextStore {
     }
 }
 
+fn wall_clock_ns() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -511,6 +1138,7 @@ mod tests {
             data2: 0,
             aux: 0,
             aux2: 0,
+            cgroup_id: 0,
         };
         ProcessEvent::new(base)
     }
@@ -541,6 +1169,233 @@ mod tests {
         assert!(proc.exit_time().is_some());
     }
 
+    #[test]
+    fn first_listen_after_exec_records_startup_latency() {
+        let store = ContextStore::new(Duration::from_secs(10), 128, None);
+        let mut exec = sample_event(7, 1, EventType::Exec);
+        exec.ts_ns = 1_000_000_000;
+        store.add(exec.clone());
+
+        let mut listen = sample_event(7, 1, EventType::Listen);
+        listen.ts_ns = 1_250_000_000;
+        listen.data = 8080;
+        assert_eq!(
+            store.pending_startup(&listen).map(|l| l.exec_to_listen_ns),
+            Some(250_000_000)
+        );
+        store.add(listen.clone());
+        let latency = store.startup_latency(7).unwrap();
+        assert_eq!(
+            (latency.port, latency.exec_to_listen_ns),
+            (8080, 250_000_000)
+        );
+
+        // Second listen (another port) does not overwrite the first
+        listen.ts_ns = 3_000_000_000;
+        listen.data = 9090;
+        assert!(store.pending_startup(&listen).is_none());
+        store.add(listen.clone());
+        assert_eq!(store.startup_latency(7), Some(latency));
+        assert_eq!(store.startup_histogram().snapshot().count, 1);
+
+        // Re-exec starts a new measurement
+        exec.ts_ns = 2_900_000_000;
+        store.add(exec);
+        assert!(store.startup_latency(7).is_none());
+        store.add(listen);
+        assert_eq!(
+            store.startup_latency(7).map(|l| l.exec_to_listen_ns),
+            Some(100_000_000)
+        );
+    }
+
+    #[test]
+    fn forked_workers_inherit_script() {
+        let store = ContextStore::new(Duration::from_secs(10), 128, None);
+        let mut exec = sample_event(10, 1, EventType::Exec);
+        exec.script_path = Some("/srv/ml/train.py".into());
+        exec.set_mem_percent(Some(4.0));
+        store.add(exec);
+        let mut worker = sample_event(11, 10, EventType::Fork);
+        worker.set_mem_percent(Some(9.0));
+        store.add(worker);
+
+        let top = store.top_rss_processes(2);
+        assert_eq!(top.len(), 2);
+        assert!(
+            top.iter()
+                .all(|p| p.name() == "train.py" && p.comm == "test")
+        );
+    }
+
+    #[test]
+    fn exited_process_keeps_the_fatal_signal_sent_to_it() {
+        let store = ContextStore::new(Duration::from_secs(10), 128, None);
+        store.add(sample_event(20, 1, EventType::Exec));
+        store.add(sample_event(21, 1, EventType::Exec));
+        let signal = |target: u64, signo: u32, aux2: u32| {
+            let mut event = sample_event(21, 1, EventType::Signal);
+            event.data = target;
+            event.aux = signo;
+            event.aux2 = aux2;
+            event
+        };
+        // Non-fatal signals and signals to unknown processes are not kept
+        let generation = store.live_generation();
+        store.add(signal(20, 17, 0));
+        store.add(signal(99, 9, 0));
+        assert!(store.killed_by(20).is_none());
+        assert!(store.killed_by(99).is_none());
+        assert_eq!(store.live_generation(), generation);
+
+        store.add(signal(20, 9, 0));
+        assert!(store.live_generation() > generation);
+        // A repeat that was already pending does not replace the delivered one
+        let generation = store.live_generation();
+        store.add(signal(
+            20,
+            15,
+            2 << linnix_ai_ebpf_common::signal_event::RESULT_SHIFT,
+        ));
+        assert_eq!(store.live_generation(), generation);
+        store.add(sample_event(20, 1, EventType::Exit));
+        let killer = store.killed_by(20).unwrap();
+        assert_eq!((killer.signal.as_str(), killer.sender_pid), ("SIGKILL", 21));
+        assert_eq!(
+            store
+                .history(None, None, Some(EventType::Signal as u32), 10)
+                .len(),
+            4
+        );
+
+        // A successor at the same pid starts clean
+        store.add(sample_event(20, 1, EventType::Fork));
+        assert!(store.killed_by(20).is_none());
+    }
+
+    #[test]
+    fn history_lookups_respect_the_length_limit() {
+        let store = ContextStore::new(Duration::from_secs(60), 4, None);
+        for pid in [30, 31, 30, 32, 30, 33] {
+            store.add(sample_event(pid, 1, EventType::Exec));
+        }
+        // The first two events were pruned along with their index entries
+        let pids = |events: Vec<ProcessEvent>| events.iter().map(|e| e.pid).collect::<Vec<_>>();
+        assert_eq!(pids(store.history(Some(30), None, None, 10)), [30, 30]);
+        assert_eq!(pids(store.history(None, Some("test"), None, 2)), [30, 33]);
+        assert!(store.history(Some(31), None, None, 10).is_empty());
+        assert!(
+            store
+                .history(None, None, Some(EventType::Exit as u32), 10)
+                .is_empty()
+        );
+
+        store.clear_history();
+        store.add(sample_event(34, 1, EventType::Fork));
+        assert_eq!(pids(store.history(None, None, None, 10)), [34]);
+    }
+
+    #[test]
+    fn byte_budgets_evict_oldest_history_and_earliest_started_processes() {
+        let store = ContextStore::new(Duration::from_secs(60), 1000, None);
+        for pid in 10..20 {
+            let mut exec = sample_event(pid, 1, EventType::Exec);
+            exec.ts_ns = u64::from(pid) * 1_000;
+            store.add(exec);
+        }
+        let entry = live_entry_bytes(&sample_event(0, 1, EventType::Exec), None);
+        let usage = store.memory_usage();
+        assert_eq!((usage.live_entries, usage.live_bytes), (10, 10 * entry));
+        assert_eq!(usage.history_entries, 10);
+        assert_eq!(usage.history_budget_bytes, None);
+        let history_entry = usage.history_bytes / 10;
+
+        store.set_memory_budget(Some(4 * history_entry), Some(5 * entry));
+        let usage = store.memory_usage();
+        assert_eq!(usage.history_entries, 4);
+        assert_eq!(usage.history_evicted, 6);
+        // Down to nine tenths of the budget, earliest started first
+        assert_eq!((usage.live_entries, usage.live_bytes), (4, 4 * entry));
+        assert_eq!(usage.live_evicted, 6);
+        let mut pids: Vec<u32> = store.live_snapshot().iter().map(|e| e.pid).collect();
+        pids.sort_unstable();
+        assert_eq!(pids, [16, 17, 18, 19]);
+
+        // Exited processes go before running ones
+        let mut exit = sample_event(19, 1, EventType::Exit);
+        exit.ts_ns = 19_000;
+        store.add(exit);
+        for pid in [20, 21] {
+            let mut exec = sample_event(pid, 1, EventType::Exec);
+            exec.ts_ns = u64::from(pid) * 1_000;
+            store.add(exec);
+        }
+        assert!(store.get_process_by_pid(19).is_none());
+        assert!(store.get_process_by_pid(16).is_none());
+        assert!(store.get_process_by_pid(17).is_some());
+        let usage = store.memory_usage();
+        assert_eq!(usage.live_evicted, 8);
+        assert_eq!((usage.live_entries, usage.live_bytes), (4, 4 * entry));
+        assert_eq!(usage.history_entries, 4);
+    }
+
+    /// History lookups by pid per second through the index and by scanning,
+    /// over a full-size history. See CONTRIBUTING.md for the command.
+    #[test]
+    #[ignore = "benchmark"]
+    fn bench_history_lookups() {
+        use std::time::Instant;
+
+        let len = 100_000;
+        let store = ContextStore::new(Duration::from_secs(3600), len, None);
+        for i in 0..len as u32 {
+            store.add(sample_event(1000 + i % 5000, 1, EventType::Exec));
+        }
+        let lookups = 10_000;
+
+        let started = Instant::now();
+        let indexed: usize = (0..lookups)
+            .map(|i| {
+                store
+                    .history(Some(1000 + i % 5000), None, None, usize::MAX)
+                    .len()
+            })
+            .sum();
+        let indexed_secs = started.elapsed().as_secs_f64();
+
+        let started = Instant::now();
+        let scanned: usize = (0..lookups)
+            .map(|i| {
+                let pid = 1000 + i % 5000;
+                store.get_recent().iter().filter(|e| e.pid == pid).count()
+            })
+            .sum();
+        let scanned_secs = started.elapsed().as_secs_f64();
+
+        assert_eq!(indexed, scanned);
+        println!(
+            "{len} events: {:.0} lookups/s indexed, {:.0} lookups/s scanned",
+            lookups as f64 / indexed_secs,
+            lookups as f64 / scanned_secs
+        );
+    }
+
+    #[test]
+    fn top_cpu_ranks_by_cpu_not_memory() {
+        let store = ContextStore::new(Duration::from_secs(10), 128, None);
+        for (pid, cpu, mem) in [(20, 5.0, 40.0), (21, 60.0, 1.0)] {
+            let mut exec = sample_event(pid, 1, EventType::Exec);
+            exec.set_cpu_percent(Some(cpu));
+            exec.set_mem_percent(Some(mem));
+            store.add(exec);
+        }
+
+        let cpu = store.top_cpu_processes(2);
+        assert_eq!(cpu.iter().map(|p| p.pid).collect::<Vec<_>>(), [21, 20]);
+        assert_eq!(cpu[0].cpu_percent, 60.0);
+        assert_eq!(store.top_rss_processes(1)[0].pid, 20);
+    }
+
     #[test]
     fn lone_exit_backfills_record() {
         let store = ContextStore::new(Duration::from_secs(10), 128, None);
@@ -558,6 +1413,46 @@ mod tests {
         assert_eq!(proc.event_type, EventType::Exit as u32);
         assert!(proc.exit_time().is_some());
     }
+    #[test]
+    fn reused_pid_does_not_inherit_the_exited_process() {
+        let store = ContextStore::new(Duration::from_secs(10), 128, None);
+        let at = |kind, secs: u64| {
+            let mut event = sample_event(500, 1, kind);
+            event.ts_ns = secs * 1_000_000_000;
+            event
+        };
+        store.add(at(EventType::Fork, 1));
+        let mut send = at(EventType::Net, 2);
+        send.data = 4096;
+        let generation = store.live_generation();
+        store.add(send.clone());
+        assert!(store.live_generation() > generation, "net rates changed");
+        store.add(at(EventType::Exit, 3));
+        assert!(store.net_rates(500).is_some());
+
+        // Traffic of a successor whose fork was missed is not attributed
+        send.ts_ns = 4_000_000_000;
+        store.add(send);
+        assert_eq!(store.get_process_by_pid(500).unwrap().event_type, 2);
+
+        store.add(at(EventType::Fork, 5));
+        let proc = store.get_process_by_pid(500).unwrap();
+        assert_eq!((proc.event_type, proc.exit_time()), (1, None));
+        assert_eq!(store.process_key(500).unwrap().start_ns, 5_000_000_000);
+        assert!(store.net_rates(500).is_none());
+
+        // A late exit of the earlier process leaves the successor running
+        store.add(at(EventType::Exit, 3));
+        assert!(store.get_process_by_pid(500).unwrap().exit_time().is_none());
+
+        store.add(at(EventType::Exit, 8));
+        let exit = store.get_recent().pop().unwrap();
+        assert_eq!(
+            (exit.ts_ns, exit.exit_time_ns),
+            (5_000_000_000, 8_000_000_000)
+        );
+    }
+
     #[test]
     fn exit_uses_start_time_from_exec() {
         let store = ContextStore::new(Duration::from_secs(10), 128, None);
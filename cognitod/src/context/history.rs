@@ -0,0 +1,238 @@
+//! Event history with secondary indexes
+//!
+//! The history is a FIFO ring: entries are appended as events arrive and
+//! dropped from the front when they age out or the ring is over its length.
+//! Each entry gets a sequence number. Per pid, per comm and per event type,
+//! the index keeps the sequence numbers of matching entries in arrival
+//! order. Because entries only leave from the front, the entry being dropped
+//! is always at the front of each of its index lists, so both insert and
+//! prune cost O(1) and a lookup touches only the matching entries.
+//!
+//! The ring also keeps the approximate bytes its entries hold, so it can be
+//! pruned to a memory budget as well as to a length.
+
+use std::collections::{HashMap, VecDeque};
+
+use super::{ProcessHistoryEntry, event_bytes};
+
+/// Index slots taken by one entry: its sequence number in three lists.
+const INDEX_BYTES: usize = 3 * std::mem::size_of::<u64>();
+
+fn entry_bytes((_, event, meta): &ProcessHistoryEntry) -> usize {
+    std::mem::size_of::<ProcessHistoryEntry>() + INDEX_BYTES + event_bytes(event, meta.as_ref())
+}
+
+/// `comm` up to its first NUL, zero padded: equal for equal names whatever
+/// trailed the terminator.
+type CommKey = [u8; 16];
+
+fn comm_key(comm: &[u8]) -> CommKey {
+    let len = comm
+        .iter()
+        .position(|b| *b == 0)
+        .unwrap_or(comm.len())
+        .min(16);
+    let mut key = [0u8; 16];
+    key[..len].copy_from_slice(&comm[..len]);
+    key
+}
+
+#[derive(Default)]
+pub(super) struct History {
+    entries: VecDeque<ProcessHistoryEntry>,
+    /// Sequence number of `entries[0]`
+    first: u64,
+    by_pid: HashMap<u32, VecDeque<u64>>,
+    by_comm: HashMap<CommKey, VecDeque<u64>>,
+    by_type: HashMap<u32, VecDeque<u64>>,
+    /// Approximate size of `entries` and their index slots
+    bytes: usize,
+}
+
+fn unindex<K: std::hash::Hash + Eq>(index: &mut HashMap<K, VecDeque<u64>>, key: K, seq: u64) {
+    if let Some(list) = index.get_mut(&key) {
+        debug_assert_eq!(list.front(), Some(&seq));
+        list.pop_front();
+        if list.is_empty() {
+            index.remove(&key);
+        }
+    }
+}
+
+impl History {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn front(&self) -> Option<&ProcessHistoryEntry> {
+        self.entries.front()
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &ProcessHistoryEntry> {
+        self.entries.iter()
+    }
+
+    pub fn push_back(&mut self, entry: ProcessHistoryEntry) {
+        let seq = self.first + self.entries.len() as u64;
+        let event = &entry.1;
+        self.by_pid.entry(event.pid).or_default().push_back(seq);
+        self.by_comm
+            .entry(comm_key(&event.comm))
+            .or_default()
+            .push_back(seq);
+        self.by_type
+            .entry(event.event_type)
+            .or_default()
+            .push_back(seq);
+        self.bytes += entry_bytes(&entry);
+        self.entries.push_back(entry);
+    }
+
+    pub fn pop_front(&mut self) -> Option<ProcessHistoryEntry> {
+        let entry = self.entries.pop_front()?;
+        let seq = self.first;
+        self.first += 1;
+        let event = &entry.1;
+        unindex(&mut self.by_pid, event.pid, seq);
+        unindex(&mut self.by_comm, comm_key(&event.comm), seq);
+        unindex(&mut self.by_type, event.event_type, seq);
+        self.bytes -= entry_bytes(&entry);
+        Some(entry)
+    }
+
+    pub fn clear(&mut self) {
+        self.first += self.entries.len() as u64;
+        self.entries.clear();
+        self.by_pid.clear();
+        self.by_comm.clear();
+        self.by_type.clear();
+        self.bytes = 0;
+    }
+
+    /// Entries matching every given key, oldest first. Walks the shortest
+    /// index list among the keys, or everything when none is given.
+    pub fn select<'a>(
+        &'a self,
+        pid: Option<u32>,
+        comm: Option<&str>,
+        event_type: Option<u32>,
+    ) -> Box<dyn DoubleEndedIterator<Item = &'a ProcessHistoryEntry> + 'a> {
+        let comm = comm.map(|c| comm_key(c.as_bytes()));
+        let lists = [
+            pid.map(|pid| self.by_pid.get(&pid)),
+            comm.map(|comm| self.by_comm.get(&comm)),
+            event_type.map(|t| self.by_type.get(&t)),
+        ];
+        let mut shortest: Option<&VecDeque<u64>> = None;
+        for list in lists.into_iter().flatten() {
+            let Some(list) = list else {
+                // A key with no entries at all
+                return Box::new(std::iter::empty());
+            };
+            if shortest.is_none_or(|s| list.len() < s.len()) {
+                shortest = Some(list);
+            }
+        }
+        let matches = move |(_, event, _): &&ProcessHistoryEntry| {
+            pid.is_none_or(|pid| event.pid == pid)
+                && comm.is_none_or(|comm| comm_key(&event.comm) == comm)
+                && event_type.is_none_or(|t| event.event_type == t)
+        };
+        match shortest {
+            Some(list) => Box::new(
+                list.iter()
+                    .map(|seq| &self.entries[(seq - self.first) as usize])
+                    .filter(matches),
+            ),
+            None => Box::new(self.entries.iter()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PERCENT_MILLI_UNKNOWN, ProcessEvent, ProcessEventWire};
+
+    fn entry(pid: u32, comm: &[u8], event_type: u32) -> ProcessHistoryEntry {
+        let mut name = [0u8; 16];
+        name[..comm.len()].copy_from_slice(comm);
+        let event = ProcessEvent::new(ProcessEventWire {
+            pid,
+            ppid: 1,
+            uid: 0,
+            gid: 0,
+            event_type,
+            ts_ns: 0,
+            seq: 0,
+            comm: name,
+            exit_time_ns: 0,
+            cpu_pct_milli: PERCENT_MILLI_UNKNOWN,
+            mem_pct_milli: PERCENT_MILLI_UNKNOWN,
+            data: 0,
+            data2: 0,
+            aux: 0,
+            aux2: 0,
+            cgroup_id: 0,
+        });
+        (u64::from(pid), event, None)
+    }
+
+    fn pids<'a>(entries: impl Iterator<Item = &'a ProcessHistoryEntry>) -> Vec<u32> {
+        entries.map(|(_, e, _)| e.pid).collect()
+    }
+
+    #[test]
+    fn lookups_follow_inserts_and_prunes() {
+        let mut history = History::default();
+        history.push_back(entry(10, b"bash", 0));
+        history.push_back(entry(11, b"curl", 0));
+        history.push_back(entry(10, b"bash", 2));
+        history.push_back(entry(12, b"bash", 1));
+
+        assert_eq!(pids(history.select(Some(10), None, None)), [10, 10]);
+        assert_eq!(pids(history.select(None, Some("bash"), None)), [10, 10, 12]);
+        assert_eq!(pids(history.select(None, Some("bash"), Some(0))), [10]);
+        assert!(
+            history
+                .select(Some(11), Some("bash"), None)
+                .next()
+                .is_none()
+        );
+        assert!(history.select(Some(99), None, None).next().is_none());
+        assert_eq!(history.select(None, None, None).count(), 4);
+
+        history.pop_front();
+        history.pop_front();
+        assert_eq!(pids(history.select(None, Some("bash"), None)), [10, 12]);
+        assert!(!history.by_comm.contains_key(&comm_key(b"curl")));
+        assert!(history.select(None, None, Some(0)).next().is_none());
+
+        history.clear();
+        history.push_back(entry(13, b"sh", 0));
+        assert_eq!(pids(history.select(Some(13), None, None)), [13]);
+        assert!(history.by_pid.len() == 1 && history.len() == 1);
+    }
+
+    #[test]
+    fn bytes_follow_inserts_and_prunes() {
+        let mut history = History::default();
+        let plain = entry(10, b"bash", 0);
+        let mut scripted = entry(11, b"python3", 0);
+        scripted.1.script_path = Some("train.py".to_string());
+        let (plain_bytes, scripted_bytes) = (entry_bytes(&plain), entry_bytes(&scripted));
+        assert!(scripted_bytes >= plain_bytes + "train.py".len());
+
+        history.push_back(plain);
+        history.push_back(scripted);
+        assert_eq!(history.bytes(), plain_bytes + scripted_bytes);
+        history.pop_front();
+        assert_eq!(history.bytes(), scripted_bytes);
+        history.clear();
+        assert_eq!(history.bytes(), 0);
+    }
+}
@@ -0,0 +1,356 @@
+//! Host-wide enforcement lock
+//!
+//! Two daemons on one host, for instance while an upgrade overlaps the old
+//! and the new instance, would both act on the same alerts and could kill the
+//! same process twice. Only the instance holding an exclusive `flock` on the
+//! `[coordination]` lock file executes enforcement actions; the others keep
+//! collecting and serving telemetry. The holder writes its pid into the file.
+//!
+//! A new instance that finds the lock taken sends the holder `SIGUSR1`. The
+//! holder stops enforcing and closes the lock file, while its telemetry keeps
+//! running until it is stopped; the new instance then takes the lock. The
+//! kernel also releases the lock when the holder exits, so a holder that
+//! ignores the request is replaced once it is gone.
+
+use log::{info, warn};
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::FileExt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::signal::unix::{SignalKind, signal};
+
+use crate::config::CoordinationConfig;
+use crate::utils::now_secs;
+
+/// How often a standby instance retries the lock.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Enforces without a lock: `[coordination]` is off or the lock file
+    /// cannot be opened
+    Unlocked,
+    /// Holds the lock and enforces
+    Active,
+    /// Waits for the lock; does not enforce
+    Standby,
+    /// Handed enforcement over to another instance
+    Yielded,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CoordinationStatus {
+    pub role: Role,
+    pub lock_path: String,
+    /// Instance holding the lock, when it is not this one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub holder_pid: Option<u32>,
+    /// Unix time the role was entered
+    pub since: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+struct State {
+    role: Role,
+    /// Open while `Active` or `Standby`; closing it releases the lock
+    file: Option<File>,
+    holder_pid: Option<u32>,
+    since: u64,
+    error: Option<String>,
+}
+
+pub struct Coordinator {
+    path: PathBuf,
+    state: Mutex<State>,
+}
+
+/// Take the lock without blocking; false while another open file holds it.
+fn try_lock(file: &File) -> io::Result<bool> {
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let err = io::Error::last_os_error();
+    if err.kind() == io::ErrorKind::WouldBlock {
+        Ok(false)
+    } else {
+        Err(err)
+    }
+}
+
+fn write_pid(file: &File) -> io::Result<()> {
+    file.set_len(0)?;
+    file.write_all_at(format!("{}\n", std::process::id()).as_bytes(), 0)
+}
+
+fn read_pid(file: &File) -> Option<u32> {
+    let mut buf = [0u8; 16];
+    let len = file.read_at(&mut buf, 0).ok()?;
+    std::str::from_utf8(&buf[..len]).ok()?.trim().parse().ok()
+}
+
+fn comm(pid: &str) -> Option<String> {
+    std::fs::read_to_string(format!("/proc/{pid}/comm"))
+        .ok()
+        .map(|c| c.trim().to_string())
+}
+
+impl Coordinator {
+    /// Enforce without taking part in coordination.
+    pub fn unlocked() -> Self {
+        Self {
+            path: PathBuf::new(),
+            state: Mutex::new(State {
+                role: Role::Unlocked,
+                file: None,
+                holder_pid: None,
+                since: now_secs(),
+                error: None,
+            }),
+        }
+    }
+
+    /// Open the lock file at `path` and take the lock if it is free. When the
+    /// file cannot be opened the instance enforces unlocked, as it would
+    /// without coordination.
+    pub fn acquire(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let mut state = State {
+            role: Role::Unlocked,
+            file: None,
+            holder_pid: None,
+            since: now_secs(),
+            error: None,
+        };
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path);
+        match file.and_then(|file| try_lock(&file).map(|locked| (file, locked))) {
+            Ok((file, true)) => {
+                if let Err(e) = write_pid(&file) {
+                    warn!(
+                        "[coordination] cannot record pid in {}: {e}",
+                        path.display()
+                    );
+                }
+                state.role = Role::Active;
+                state.file = Some(file);
+            }
+            Ok((file, false)) => {
+                state.role = Role::Standby;
+                state.holder_pid = read_pid(&file);
+                state.file = Some(file);
+            }
+            Err(e) => {
+                warn!(
+                    "[coordination] cannot lock {}: {e}; enforcing without coordination",
+                    path.display()
+                );
+                state.error = Some(e.to_string());
+            }
+        }
+        Self {
+            path,
+            state: Mutex::new(state),
+        }
+    }
+
+    pub fn from_config(config: &CoordinationConfig) -> Self {
+        if config.enabled {
+            Self::acquire(&config.lock_path)
+        } else {
+            Self::unlocked()
+        }
+    }
+
+    pub fn role(&self) -> Role {
+        self.state.lock().unwrap().role
+    }
+
+    /// Whether this instance may execute enforcement actions.
+    pub fn may_enforce(&self) -> Result<(), String> {
+        let state = self.state.lock().unwrap();
+        match state.role {
+            Role::Unlocked | Role::Active => Ok(()),
+            Role::Standby => Err(match state.holder_pid {
+                Some(pid) => format!("enforcement is held by linnix instance pid {pid}"),
+                None => "enforcement is held by another linnix instance".to_string(),
+            }),
+            Role::Yielded => Err("enforcement was handed over to another instance".to_string()),
+        }
+    }
+
+    pub fn status(&self) -> CoordinationStatus {
+        let state = self.state.lock().unwrap();
+        CoordinationStatus {
+            role: state.role,
+            lock_path: self.path.display().to_string(),
+            holder_pid: state.holder_pid,
+            since: state.since,
+            error: state.error.clone(),
+        }
+    }
+
+    /// Stop enforcing and release the lock. Returns false when there was
+    /// nothing to hand over.
+    pub fn hand_over(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if !matches!(state.role, Role::Active | Role::Standby) {
+            return false;
+        }
+        state.role = Role::Yielded;
+        state.file = None;
+        state.holder_pid = None;
+        state.since = now_secs();
+        true
+    }
+
+    /// Take the lock if a standby instance finds it free.
+    pub fn try_take(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.role != Role::Standby {
+            return false;
+        }
+        let Some(file) = &state.file else {
+            return false;
+        };
+        match try_lock(file) {
+            Ok(true) => {
+                if let Err(e) = write_pid(file) {
+                    warn!(
+                        "[coordination] cannot record pid in {}: {e}",
+                        self.path.display()
+                    );
+                }
+                state.role = Role::Active;
+                state.holder_pid = None;
+                state.since = now_secs();
+                true
+            }
+            Ok(false) => {
+                state.holder_pid = read_pid(file);
+                false
+            }
+            Err(e) => {
+                state.error = Some(e.to_string());
+                false
+            }
+        }
+    }
+
+    /// Ask the holder to hand enforcement over. Only a process running the
+    /// same program is signalled, since `SIGUSR1` terminates most others.
+    pub fn request_takeover(&self) -> Result<u32, String> {
+        let pid = self
+            .state
+            .lock()
+            .unwrap()
+            .holder_pid
+            .ok_or_else(|| "lock file names no holder".to_string())?;
+        let holder = comm(&pid.to_string());
+        if holder.is_none() || holder != comm("self") {
+            return Err(format!("pid {pid} is not a linnix instance"));
+        }
+        if unsafe { libc::kill(pid as i32, libc::SIGUSR1) } != 0 {
+            return Err(io::Error::last_os_error().to_string());
+        }
+        Ok(pid)
+    }
+
+    /// Hand enforcement over on `SIGUSR1` and, while on standby, ask the
+    /// holder to hand over and wait for the lock.
+    pub fn spawn(self: Arc<Self>, config: &CoordinationConfig) -> io::Result<()> {
+        // Registered before returning so a request cannot kill the daemon
+        let mut usr1 = signal(SignalKind::user_defined1())?;
+        let this = Arc::clone(&self);
+        tokio::spawn(async move {
+            while usr1.recv().await.is_some() {
+                if this.hand_over() {
+                    warn!(
+                        "[coordination] handed enforcement over to a newer instance; telemetry continues"
+                    );
+                } else {
+                    info!("[coordination] handover requested but this instance does not enforce");
+                }
+            }
+        });
+
+        if self.role() != Role::Standby {
+            return Ok(());
+        }
+        if config.takeover {
+            match self.request_takeover() {
+                Ok(pid) => info!("[coordination] asked pid {pid} to hand enforcement over"),
+                Err(e) => warn!("[coordination] cannot request handover: {e}"),
+            }
+        }
+        let timeout = Duration::from_secs(config.takeover_timeout_secs);
+        tokio::spawn(async move {
+            let started = Instant::now();
+            let mut warned = false;
+            let mut ticker = tokio::time::interval(POLL_INTERVAL);
+            loop {
+                ticker.tick().await;
+                if self.try_take() {
+                    info!("[coordination] took over enforcement");
+                    break;
+                }
+                if self.role() != Role::Standby {
+                    break;
+                }
+                if !warned && started.elapsed() >= timeout {
+                    warned = true;
+                    warn!(
+                        "[coordination] {}; enforcement stays off until it is released",
+                        self.may_enforce().err().unwrap_or_default()
+                    );
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_instance_waits_until_the_first_hands_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("linnix.lock");
+
+        let first = Coordinator::acquire(&path);
+        assert_eq!(first.role(), Role::Active);
+        assert!(first.may_enforce().is_ok());
+
+        // Each open file is its own lock owner, as in another process
+        let second = Coordinator::acquire(&path);
+        assert_eq!(second.role(), Role::Standby);
+        assert_eq!(second.status().holder_pid, Some(std::process::id()));
+        assert!(second.may_enforce().is_err());
+        assert!(!second.try_take());
+
+        assert!(first.hand_over());
+        assert!(first.may_enforce().is_err());
+        assert!(second.try_take());
+        assert_eq!(second.role(), Role::Active);
+        assert!(!first.hand_over());
+    }
+
+    #[test]
+    fn unopenable_lock_enforces_unlocked() {
+        let coordinator = Coordinator::acquire("/nonexistent/dir/linnix.lock");
+        assert_eq!(coordinator.role(), Role::Unlocked);
+        assert!(coordinator.may_enforce().is_ok());
+        assert!(coordinator.status().error.is_some());
+    }
+}
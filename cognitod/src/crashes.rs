@@ -0,0 +1,300 @@
+//! Crash telemetry for monitored processes
+//!
+//! An exit whose wait status shows a program-error signal (SIGSEGV, SIGBUS,
+//! SIGILL, SIGFPE, SIGABRT, SIGTRAP) or a core dump is a crash. Page faults
+//! reported for the pid within `fault_window_secs` before it died are
+//! attached, since a segfault is usually preceded by the fault that caused
+//! it. Each crash raises a `process_crash` alert and is counted against the
+//! process's comm in the leaderboard rollups, so `/leaderboard?metric=crashes`
+//! gives crash counts per comm per day. Page faults are throttled per pid in
+//! the kernel, so the fault count is a lower bound.
+
+use linnix_ai_ebpf_common::EventType;
+use log::{debug, info};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use crate::ProcessEvent;
+use crate::alerts::{Alert, AlertSender, Severity};
+use crate::config::CrashesConfig;
+use crate::context::ContextStore;
+use crate::leaderboard::Leaderboard;
+use crate::lineage::{self, LineageEntry};
+use crate::restart_loops::ExitReason;
+use crate::utils::now_secs;
+
+pub const RULE_NAME: &str = "process_crash";
+
+/// Faulting addresses below this are reported as null dereferences.
+const NULL_PAGE: u64 = 4096;
+
+/// Page faults of one pid within the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct FaultTrail {
+    pub count: u64,
+    pub first_at: u64,
+    pub last_at: u64,
+    pub last_addr: u64,
+    pub last_ip: u64,
+    /// The last fault was taken in kernel mode
+    pub kernel: bool,
+}
+
+/// A process killed by a crash signal.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Crash {
+    pub pid: u32,
+    pub ppid: u32,
+    pub comm: String,
+    pub reason: ExitReason,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub faults: Option<FaultTrail>,
+}
+
+impl Crash {
+    fn message(&self, window_secs: u64) -> String {
+        let mut message = format!(
+            "process crash: {}({}) killed by {}",
+            self.comm,
+            self.pid,
+            self.reason.label()
+        );
+        if let Some(faults) = &self.faults {
+            message.push_str(&format!(
+                " after {} page fault{} in {window_secs}s, last at {:#x} (ip {:#x}, {} mode{})",
+                faults.count,
+                if faults.count == 1 { "" } else { "s" },
+                faults.last_addr,
+                faults.last_ip,
+                if faults.kernel { "kernel" } else { "user" },
+                if faults.last_addr < NULL_PAGE {
+                    ", null dereference"
+                } else {
+                    ""
+                }
+            ));
+        }
+        message
+    }
+}
+
+pub struct CrashTracker {
+    fault_window_secs: u64,
+    host: String,
+    faults: Mutex<HashMap<u32, FaultTrail>>,
+    leaderboard: Option<Arc<Leaderboard>>,
+}
+
+impl CrashTracker {
+    pub fn new(config: &CrashesConfig, leaderboard: Option<Arc<Leaderboard>>) -> Self {
+        Self {
+            fault_window_secs: config.fault_window_secs.max(1),
+            host: std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown".into()),
+            faults: Mutex::new(HashMap::new()),
+            leaderboard,
+        }
+    }
+
+    /// Record a page fault of `pid`.
+    pub fn on_fault(&self, pid: u32, addr: u64, ip: u64, kernel: bool, now: u64) {
+        let mut faults = self.faults.lock().unwrap();
+        let trail = faults.entry(pid).or_insert(FaultTrail {
+            count: 0,
+            first_at: now,
+            last_at: now,
+            last_addr: addr,
+            last_ip: ip,
+            kernel,
+        });
+        if now.saturating_sub(trail.last_at) > self.fault_window_secs {
+            trail.count = 0;
+            trail.first_at = now;
+        }
+        trail.count += 1;
+        trail.last_at = now;
+        trail.last_addr = addr;
+        trail.last_ip = ip;
+        trail.kernel = kernel;
+    }
+
+    /// Record the exit of `pid`. Returns the crash when it died of one.
+    pub fn on_exit(
+        &self,
+        pid: u32,
+        ppid: u32,
+        comm: String,
+        reason: Option<ExitReason>,
+        now: u64,
+    ) -> Option<Crash> {
+        let faults = self
+            .faults
+            .lock()
+            .unwrap()
+            .remove(&pid)
+            .filter(|trail| now.saturating_sub(trail.last_at) <= self.fault_window_secs);
+        let reason = reason.filter(ExitReason::is_crash)?;
+        Some(Crash {
+            pid,
+            ppid,
+            comm,
+            reason,
+            faults,
+        })
+    }
+
+    /// Forget faults of pids that went quiet without exiting.
+    pub fn expire(&self, now: u64) {
+        let window = self.fault_window_secs;
+        self.faults
+            .lock()
+            .unwrap()
+            .retain(|_, trail| now.saturating_sub(trail.last_at) <= window);
+    }
+
+    fn alert(&self, crash: &Crash, lineage: Vec<LineageEntry>) -> Alert {
+        Alert {
+            rule: RULE_NAME.to_string(),
+            severity: Severity::High,
+            message: crash.message(self.fault_window_secs),
+            host: self.host.clone(),
+            detection: "threshold",
+            suppressed_by: None,
+            lineage,
+            children: Vec::new(),
+            pod: None,
+            evidence: None,
+            annotations: None,
+        }
+    }
+
+    fn observe(&self, event: &ProcessEvent, context: &ContextStore) -> Option<Alert> {
+        let now = now_secs();
+        if event.event_type == EventType::PageFault as u32 {
+            self.on_fault(event.pid, event.data, event.data2, event.aux2 == 1, now);
+            return None;
+        }
+        if event.event_type != EventType::Exit as u32 {
+            return None;
+        }
+        let comm = String::from_utf8_lossy(&event.comm)
+            .trim_end_matches('\0')
+            .to_string();
+        let crash = self.on_exit(
+            event.pid,
+            event.ppid,
+            comm,
+            ExitReason::from_event(event),
+            now,
+        )?;
+        if let Some(board) = &self.leaderboard {
+            board.record_crash(&crash.comm);
+        }
+        // The crashed process has left the live table; its parent has not.
+        let mut lineage = vec![LineageEntry {
+            pid: crash.pid,
+            comm: crash.comm.clone(),
+            uid: event.uid,
+            age_secs: None,
+        }];
+        lineage.extend(lineage::ancestry(crash.ppid, Some(context)));
+        Some(self.alert(&crash, lineage))
+    }
+
+    pub async fn run(self: Arc<Self>, context: Arc<ContextStore>, tx: Option<AlertSender>) {
+        info!(
+            "[crashes] correlating crashes with page faults from the last {}s",
+            self.fault_window_secs
+        );
+        let mut events = context.broadcaster().subscribe();
+        let mut ticker = tokio::time::interval(Duration::from_secs(self.fault_window_secs.min(30)));
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(event) => {
+                        if let Some(alert) = self.observe(&event, &context) {
+                            info!("[crashes] {}", alert.message);
+                            if let Some(tx) = &tx {
+                                tx.send(alert);
+                            }
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        debug!("[crashes] lagged, skipped {skipped} events");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                _ = ticker.tick() => self.expire(now_secs()),
+            }
+        }
+    }
+}
+
+/// Start watching exits for crashes. Alerts go to `tx` when a rules engine
+/// is loaded; crashes are counted in `leaderboard` either way.
+pub fn spawn(
+    context: Arc<ContextStore>,
+    tx: Option<AlertSender>,
+    leaderboard: Option<Arc<Leaderboard>>,
+    config: &CrashesConfig,
+) -> Arc<CrashTracker> {
+    let tracker = Arc::new(CrashTracker::new(config, leaderboard));
+    tokio::spawn(Arc::clone(&tracker).run(context, tx));
+    tracker
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segfault_after_faults_is_a_crash_with_its_faults() {
+        let tracker = CrashTracker::new(
+            &CrashesConfig {
+                enabled: true,
+                fault_window_secs: 30,
+            },
+            None,
+        );
+        tracker.on_fault(42, 0x1000, 0x5555_0000, false, 100);
+        tracker.on_fault(42, 0x8, 0x5555_0010, false, 105);
+        // Faults of another pid, and faults that aged out, are not attached.
+        tracker.on_fault(43, 0x8, 0x5555_0010, false, 105);
+        tracker.on_fault(44, 0x8, 0x5555_0010, false, 10);
+
+        let crash = tracker
+            .on_exit(
+                42,
+                1,
+                "api".into(),
+                Some(ExitReason::from_wait_status(0x80 | 11)),
+                106,
+            )
+            .unwrap();
+        assert_eq!(crash.faults.map(|f| (f.count, f.last_addr)), Some((2, 0x8)));
+        assert_eq!(
+            crash.message(30),
+            "process crash: api(42) killed by SIGSEGV (core) after 2 page faults in 30s, \
+             last at 0x8 (ip 0x55550010, user mode, null dereference)"
+        );
+
+        let abort = tracker
+            .on_exit(
+                44,
+                1,
+                "db".into(),
+                Some(ExitReason::from_wait_status(6)),
+                106,
+            )
+            .unwrap();
+        assert_eq!(abort.faults, None);
+        assert_eq!(abort.message(30), "process crash: db(44) killed by SIGABRT");
+
+        // Clean exits and kills are not crashes, but still drop the faults.
+        let killed = Some(ExitReason::from_wait_status(9));
+        assert_eq!(tracker.on_exit(43, 1, "job".into(), killed, 106), None);
+        assert!(tracker.faults.lock().unwrap().is_empty());
+    }
+}
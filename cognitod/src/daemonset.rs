@@ -0,0 +1,261 @@
+//! Running as a Kubernetes DaemonSet
+//!
+//! In a cluster (service account mounted, `KUBERNETES_SERVICE_HOST` set) the
+//! agent sets itself up from the API server instead of relying on
+//! hand-written manifests. [`K8sContext::self_configure`] finds the node it
+//! runs on and the node's labels. A Lease named `linnix-<node>` is renewed
+//! every third of `lease_duration_secs`, so `kubectl get leases` shows which
+//! nodes have a live agent. With `rules_configmap` set, alert rules are
+//! loaded from that ConfigMap at startup and reloaded whenever its
+//! `resourceVersion` changes. Readiness is served by the API at `/readyz`.
+
+use anyhow::{Context, anyhow};
+use log::{debug, info, warn};
+use reqwest::Method;
+use serde_json::{Value, json};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::alerts::RuleEngine;
+use crate::config::{Egress, KubernetesConfig, OfflineGuard};
+use crate::egress::EgressBlocked;
+use crate::k8s::K8sContext;
+
+/// One key of a ConfigMap and the `resourceVersion` it was read at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigMapDocument {
+    pub version: String,
+    pub text: String,
+}
+
+/// Namespace the Lease and unqualified ConfigMaps live in.
+fn namespace(ctx: &K8sContext, config: &KubernetesConfig) -> String {
+    config
+        .namespace
+        .clone()
+        .or_else(|| ctx.namespace.clone())
+        .unwrap_or_else(|| "default".to_string())
+}
+
+/// `(namespace, name)` of a `name` or `namespace/name` reference.
+fn configmap_ref<'a>(spec: &'a str, namespace: &'a str) -> (&'a str, &'a str) {
+    spec.split_once('/').unwrap_or((namespace, spec))
+}
+
+fn document(configmap: &Value, key: &str) -> anyhow::Result<ConfigMapDocument> {
+    let text = configmap["data"][key]
+        .as_str()
+        .ok_or_else(|| anyhow!("no key {key}"))?;
+    Ok(ConfigMapDocument {
+        version: configmap["metadata"]["resourceVersion"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        text: text.to_string(),
+    })
+}
+
+/// File extension of `rules_key`, as the format hint for the rules parser.
+pub fn rules_hint(config: &KubernetesConfig) -> Option<&str> {
+    Path::new(&config.rules_key)
+        .extension()
+        .and_then(|ext| ext.to_str())
+}
+
+/// Rules document of `rules_configmap`, or `None` when none is configured.
+///
+/// The API client is shared with metadata lookups, which are not config
+/// sync, so the `config_sync` egress policy is checked here rather than by
+/// the client.
+pub async fn fetch_rules(
+    ctx: &K8sContext,
+    config: &KubernetesConfig,
+    guard: &OfflineGuard,
+) -> anyhow::Result<Option<ConfigMapDocument>> {
+    let Some(spec) = &config.rules_configmap else {
+        return Ok(None);
+    };
+    if !guard.allows(Egress::ConfigSync) {
+        return Err(EgressBlocked(Egress::ConfigSync).into());
+    }
+    let default_ns = namespace(ctx, config);
+    let (ns, name) = configmap_ref(spec, &default_ns);
+    let configmap = ctx
+        .api_get(&format!("/api/v1/namespaces/{ns}/configmaps/{name}"))
+        .await?
+        .ok_or_else(|| anyhow!("ConfigMap {ns}/{name} not found"))?;
+    document(&configmap, &config.rules_key)
+        .with_context(|| format!("ConfigMap {ns}/{name}"))
+        .map(Some)
+}
+
+/// A new Lease held by `holder`, acquired and renewed at `now`.
+fn lease(name: &str, namespace: &str, holder: &str, duration_secs: u64, now: &str) -> Value {
+    json!({
+        "apiVersion": "coordination.k8s.io/v1",
+        "kind": "Lease",
+        "metadata": {
+            "name": name,
+            "namespace": namespace,
+            "labels": { "app": "linnix" },
+        },
+        "spec": {
+            "holderIdentity": holder,
+            "leaseDurationSeconds": duration_secs,
+            "acquireTime": now,
+            "renewTime": now,
+        },
+    })
+}
+
+/// Renew the Lease, creating it when it does not exist. Returns whether it
+/// was created.
+async fn renew_lease(
+    ctx: &K8sContext,
+    name: &str,
+    namespace: &str,
+    holder: &str,
+    duration_secs: u64,
+) -> anyhow::Result<bool> {
+    // MicroTime: RFC 3339 with exactly six fractional digits
+    let now = chrono::Utc::now()
+        .format("%Y-%m-%dT%H:%M:%S%.6fZ")
+        .to_string();
+    let leases = format!("/apis/coordination.k8s.io/v1/namespaces/{namespace}/leases");
+    let renewal = json!({
+        "spec": {
+            "holderIdentity": holder,
+            "leaseDurationSeconds": duration_secs,
+            "renewTime": now,
+        },
+    });
+    if ctx
+        .api_send(Method::PATCH, &format!("{leases}/{name}"), Some(&renewal))
+        .await?
+        .is_some()
+    {
+        return Ok(false);
+    }
+    let body = lease(name, namespace, holder, duration_secs, &now);
+    ctx.api_send(Method::POST, &leases, Some(&body)).await?;
+    Ok(true)
+}
+
+/// Start renewing the Lease and, when `engine` is given, syncing its rules
+/// from `rules_configmap`. `rules_version` is the version already loaded.
+/// Polls are skipped while config sync egress is blocked.
+pub fn spawn(
+    ctx: Arc<K8sContext>,
+    config: &KubernetesConfig,
+    engine: Option<Arc<RuleEngine>>,
+    rules_version: Option<String>,
+    guard: Arc<OfflineGuard>,
+) {
+    if !ctx.in_cluster {
+        return;
+    }
+    let namespace = namespace(&ctx, config);
+
+    if config.lease {
+        let ctx = Arc::clone(&ctx);
+        let namespace = namespace.clone();
+        let duration_secs = config.lease_duration_secs.max(3);
+        tokio::spawn(async move {
+            let name = format!("linnix-{}", ctx.node_name());
+            let holder = ctx.pod_name.clone().unwrap_or_else(|| ctx.node_name());
+            info!(
+                "[daemonset] renewing lease {namespace}/{name} every {}s",
+                duration_secs / 3
+            );
+            let mut tick = tokio::time::interval(Duration::from_secs(duration_secs / 3));
+            loop {
+                tick.tick().await;
+                match renew_lease(&ctx, &name, &namespace, &holder, duration_secs).await {
+                    Ok(true) => info!("[daemonset] created lease {namespace}/{name}"),
+                    Ok(false) => debug!("[daemonset] renewed lease {namespace}/{name}"),
+                    Err(e) => warn!("[daemonset] lease renewal failed: {e:#}"),
+                }
+            }
+        });
+    }
+
+    let Some(spec) = config.rules_configmap.clone() else {
+        return;
+    };
+    let Some(engine) = engine else {
+        warn!("[daemonset] no rules engine loaded; not syncing rules from ConfigMap {spec}");
+        return;
+    };
+    let config = config.clone();
+    tokio::spawn(async move {
+        let hint = rules_hint(&config);
+        let mut version = rules_version;
+        let mut tick = tokio::time::interval(Duration::from_secs(config.rules_poll_secs.max(1)));
+        loop {
+            tick.tick().await;
+            if !guard.allows(Egress::ConfigSync) {
+                continue;
+            }
+            let document = match fetch_rules(&ctx, &config, &guard).await {
+                Ok(Some(document)) => document,
+                Ok(None) => return,
+                Err(e) => {
+                    warn!("[daemonset] cannot read rules: {e:#}");
+                    continue;
+                }
+            };
+            if version.as_deref() == Some(document.version.as_str()) {
+                continue;
+            }
+            // Remembered even when invalid, so a bad edit is reported once
+            version = Some(document.version.clone());
+            match engine.reload(&document.text, hint).await {
+                Ok(count) => info!(
+                    "[daemonset] reloaded {count} rules from ConfigMap {spec} (version {})",
+                    document.version
+                ),
+                Err(e) => warn!(
+                    "[daemonset] ConfigMap {spec} version {} has invalid rules, keeping the current ones: {e:#}",
+                    document.version
+                ),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_rules_and_builds_leases() {
+        assert_eq!(
+            configmap_ref("linnix-rules", "ops"),
+            ("ops", "linnix-rules")
+        );
+        assert_eq!(
+            configmap_ref("monitoring/linnix-rules", "ops"),
+            ("monitoring", "linnix-rules")
+        );
+
+        let configmap = json!({
+            "metadata": { "name": "linnix-rules", "resourceVersion": "4711" },
+            "data": { "rules.yaml": "- name: fork_storm\n" },
+        });
+        assert_eq!(
+            document(&configmap, "rules.yaml").unwrap(),
+            ConfigMapDocument {
+                version: "4711".into(),
+                text: "- name: fork_storm\n".into(),
+            }
+        );
+        assert!(document(&configmap, "rules.toml").is_err());
+        assert_eq!(rules_hint(&KubernetesConfig::default()), Some("yaml"));
+
+        let lease = lease("linnix-node-1", "ops", "linnix-agent-x7", 40, "t");
+        assert_eq!(lease["spec"]["leaseDurationSeconds"], 40);
+        assert_eq!(lease["spec"]["holderIdentity"], "linnix-agent-x7");
+        assert_eq!(lease["metadata"]["namespace"], "ops");
+    }
+}
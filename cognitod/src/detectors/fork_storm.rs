@@ -0,0 +1,138 @@
+//! A single parent forking faster than `forks` per `window_secs`.
+
+use linnix_ai_ebpf_common::EventType;
+use std::collections::{HashMap, VecDeque};
+use tokio::time::{Duration, Instant};
+
+use super::{Detector, Finding, MAX_TRACKED, comm};
+use crate::ProcessEvent;
+use crate::alerts::Severity;
+use crate::config::ForkStormDetectorConfig;
+
+struct Parent {
+    comm: String,
+    forks: VecDeque<Instant>,
+}
+
+pub struct ForkStorm {
+    forks: usize,
+    window: Duration,
+    parents: HashMap<u32, Parent>,
+    pending: Vec<Finding>,
+}
+
+impl ForkStorm {
+    pub fn new(config: &ForkStormDetectorConfig) -> Self {
+        Self {
+            forks: config.forks.max(1) as usize,
+            window: Duration::from_secs(config.window_secs.max(1)),
+            parents: HashMap::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    fn prune(&mut self, now: Instant) {
+        let window = self.window;
+        self.parents.retain(|_, parent| {
+            parent
+                .forks
+                .back()
+                .is_some_and(|last| now.saturating_duration_since(*last) <= window)
+        });
+    }
+}
+
+impl Detector for ForkStorm {
+    fn name(&self) -> &'static str {
+        "fork_storm"
+    }
+
+    fn on_event(&mut self, event: &ProcessEvent, now: Instant) {
+        if event.event_type == EventType::Exit as u32 {
+            self.parents.remove(&event.pid);
+            return;
+        }
+        if event.event_type != EventType::Fork as u32 {
+            return;
+        }
+        if !self.parents.contains_key(&event.ppid) && self.parents.len() >= MAX_TRACKED {
+            self.prune(now);
+        }
+        // The child starts with its parent's comm
+        let parent = self.parents.entry(event.ppid).or_insert_with(|| Parent {
+            comm: comm(event),
+            forks: VecDeque::new(),
+        });
+        parent.forks.push_back(now);
+        while parent
+            .forks
+            .front()
+            .is_some_and(|first| now.saturating_duration_since(*first) > self.window)
+        {
+            parent.forks.pop_front();
+        }
+        if parent.forks.len() >= self.forks {
+            self.pending.push(Finding {
+                severity: Severity::High,
+                pid: Some(event.ppid),
+                message: format!(
+                    "{}({}) forked {} children within {}s",
+                    parent.comm,
+                    event.ppid,
+                    parent.forks.len(),
+                    self.window.as_secs()
+                ),
+            });
+            // Re-arm once the parent forks another full window's worth
+            parent.forks.clear();
+        }
+    }
+
+    fn emit(&mut self, _now: Instant) -> Vec<Finding> {
+        std::mem::take(&mut self.pending)
+    }
+
+    fn tracked(&self) -> usize {
+        self.parents.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detectors::tests::event;
+
+    #[test]
+    fn fires_per_parent_within_the_window() {
+        let mut storm = ForkStorm::new(&ForkStormDetectorConfig {
+            enabled: true,
+            forks: 5,
+            window_secs: 1,
+        });
+        let start = Instant::now();
+
+        // Two parents at four forks each: neither crosses the threshold
+        for i in 0..4 {
+            let at = start + Duration::from_millis(100 * i);
+            storm.on_event(&event(EventType::Fork, 100 + i as u32, 10, b"make"), at);
+            storm.on_event(&event(EventType::Fork, 200 + i as u32, 20, b"cron"), at);
+        }
+        assert!(storm.emit(start).is_empty());
+        assert_eq!(storm.tracked(), 2);
+
+        // Too late: the first forks left the window
+        let late = start + Duration::from_millis(1050);
+        storm.on_event(&event(EventType::Fork, 300, 20, b"cron"), late);
+        assert!(storm.emit(late).is_empty());
+
+        let at = start + Duration::from_millis(500);
+        storm.on_event(&event(EventType::Fork, 104, 10, b"make"), at);
+        let findings = storm.emit(at);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].pid, Some(10));
+        assert_eq!(findings[0].message, "make(10) forked 5 children within 1s");
+
+        storm.on_event(&event(EventType::Exit, 10, 1, b"make"), at);
+        assert_eq!(storm.tracked(), 1);
+    }
+}
@@ -0,0 +1,205 @@
+//! A process whose share of host memory keeps rising.
+//!
+//! Each process keeps up to `samples` memory readings spread over
+//! `window_secs`. Once the readings span the window, the process counts as
+//! leaking when it gained `growth_pct` points and rose in most steps, so a
+//! single large allocation followed by a plateau does not fire.
+
+use linnix_ai_ebpf_common::EventType;
+use std::collections::{HashMap, VecDeque};
+use tokio::time::{Duration, Instant};
+
+use super::{Detector, Finding, MAX_TRACKED, comm};
+use crate::ProcessEvent;
+use crate::alerts::Severity;
+use crate::config::MemoryLeakDetectorConfig;
+
+/// Share of steps between readings that must rise.
+const MIN_RISING: f64 = 0.8;
+
+struct Series {
+    comm: String,
+    readings: VecDeque<(Instant, f32)>,
+}
+
+pub struct MemoryLeak {
+    growth_pct: f32,
+    window: Duration,
+    spacing: Duration,
+    series: HashMap<u32, Series>,
+    pending: Vec<Finding>,
+}
+
+impl MemoryLeak {
+    pub fn new(config: &MemoryLeakDetectorConfig) -> Self {
+        let window = Duration::from_secs(config.window_secs.max(1));
+        Self {
+            growth_pct: config.growth_pct,
+            window,
+            spacing: window / config.samples.max(2) as u32,
+            series: HashMap::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    fn evict_oldest(&mut self) {
+        if let Some(pid) = self
+            .series
+            .iter()
+            .min_by_key(|(_, s)| s.readings.back().map(|(at, _)| *at))
+            .map(|(pid, _)| *pid)
+        {
+            self.series.remove(&pid);
+        }
+    }
+
+    fn check(&self, pid: u32, series: &Series) -> Option<Finding> {
+        let &(first_at, first) = series.readings.front()?;
+        let &(last_at, last) = series.readings.back()?;
+        if last_at.saturating_duration_since(first_at) < self.window
+            || last - first < self.growth_pct
+        {
+            return None;
+        }
+        let steps = series.readings.len() - 1;
+        let rising = series
+            .readings
+            .iter()
+            .zip(series.readings.iter().skip(1))
+            .filter(|((_, a), (_, b))| b > a)
+            .count();
+        if (rising as f64) < MIN_RISING * steps as f64 {
+            return None;
+        }
+        Some(Finding {
+            severity: Severity::Medium,
+            pid: Some(pid),
+            message: format!(
+                "{}({}) grew from {:.1}% to {:.1}% of host memory over {}s",
+                series.comm,
+                pid,
+                first,
+                last,
+                last_at.saturating_duration_since(first_at).as_secs()
+            ),
+        })
+    }
+}
+
+impl Detector for MemoryLeak {
+    fn name(&self) -> &'static str {
+        "memory_leak"
+    }
+
+    fn on_event(&mut self, event: &ProcessEvent, now: Instant) {
+        if event.event_type == EventType::Exit as u32 {
+            self.series.remove(&event.pid);
+            return;
+        }
+        let Some(mem_pct) = event.mem_percent() else {
+            return;
+        };
+        if !self.series.contains_key(&event.pid) && self.series.len() >= MAX_TRACKED {
+            self.evict_oldest();
+        }
+        let series = self.series.entry(event.pid).or_insert_with(|| Series {
+            comm: comm(event),
+            readings: VecDeque::new(),
+        });
+        if series
+            .readings
+            .back()
+            .is_some_and(|(at, _)| now.saturating_duration_since(*at) < self.spacing)
+        {
+            return;
+        }
+        series.readings.push_back((now, mem_pct));
+        // Keep one reading at or beyond the start of the window
+        while series
+            .readings
+            .get(1)
+            .is_some_and(|(at, _)| now.saturating_duration_since(*at) >= self.window)
+        {
+            series.readings.pop_front();
+        }
+        let series = &self.series[&event.pid];
+        if let Some(finding) = self.check(event.pid, series) {
+            self.pending.push(finding);
+            // Re-arm after another full window of growth
+            if let Some(series) = self.series.get_mut(&event.pid) {
+                series.readings.clear();
+            }
+        }
+    }
+
+    fn emit(&mut self, _now: Instant) -> Vec<Finding> {
+        std::mem::take(&mut self.pending)
+    }
+
+    fn tracked(&self) -> usize {
+        self.series.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detectors::tests::event;
+
+    fn sample(pid: u32, mem_pct: f32) -> ProcessEvent {
+        let mut event = event(EventType::Syscall, pid, 1, b"leaky");
+        event.set_mem_percent(Some(mem_pct));
+        event
+    }
+
+    fn detector() -> MemoryLeak {
+        MemoryLeak::new(&MemoryLeakDetectorConfig {
+            enabled: true,
+            growth_pct: 5.0,
+            window_secs: 60,
+            samples: 6,
+        })
+    }
+
+    #[test]
+    fn fires_on_steady_growth_over_the_window() {
+        let mut leak = detector();
+        let start = Instant::now();
+        for s in 0..=60 {
+            leak.on_event(
+                &sample(7, 10.0 + s as f32 * 0.1),
+                start + Duration::from_secs(s),
+            );
+        }
+        let findings = leak.emit(start);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].pid, Some(7));
+        assert_eq!(
+            findings[0].message,
+            "leaky(7) grew from 10.0% to 16.0% of host memory over 60s"
+        );
+    }
+
+    #[test]
+    fn ignores_a_step_followed_by_a_plateau_or_short_series() {
+        let mut leak = detector();
+        let start = Instant::now();
+        for s in 0..=120 {
+            // One large allocation at 10s, then flat with small dips
+            let mem = if s < 10 {
+                10.0
+            } else {
+                20.0 - (s / 10 % 2) as f32 * 0.5
+            };
+            leak.on_event(&sample(8, mem), start + Duration::from_secs(s));
+            // Fast growth that has not yet spanned the window
+            if s < 50 {
+                leak.on_event(&sample(9, 10.0 + s as f32), start + Duration::from_secs(s));
+            }
+        }
+        assert!(leak.emit(start).is_empty());
+
+        leak.on_event(&event(EventType::Exit, 9, 1, b"leaky"), start);
+        assert_eq!(leak.tracked(), 1);
+    }
+}
@@ -0,0 +1,368 @@
+//! Pluggable anomaly detectors.
+//!
+//! A [`Detector`] watches the event stream and host snapshots and hands back
+//! [`Finding`]s from [`Detector::emit`]. [`DetectorList`] owns the registered
+//! detectors, runs as one handler, turns findings into alerts (one per
+//! detector and process per cooldown) and keeps the per-detector counters
+//! served by `/detectors`. The built-in fork storm, memory leak and spike
+//! detectors are configured under `[detectors]`; rules from the rules file
+//! are evaluated separately by the rules engine.
+
+use async_trait::async_trait;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::time::{Duration, Instant};
+
+use crate::ProcessEvent;
+use crate::alerts::{Alert, AlertSender, Severity};
+use crate::config::DetectorsConfig;
+use crate::handler::Handler;
+use crate::types::SystemSnapshot;
+
+pub mod fork_storm;
+pub mod memory_leak;
+pub mod spike;
+
+pub use fork_storm::ForkStorm;
+pub use memory_leak::MemoryLeak;
+pub use spike::Spikes;
+
+/// Upper bound on processes a detector tracks; the least recently seen are
+/// dropped first.
+pub(crate) const MAX_TRACKED: usize = 8192;
+
+/// Something a detector noticed, waiting to be raised as an alert.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub severity: Severity,
+    /// Process the finding is about, for cooldowns and the alert context
+    pub pid: Option<u32>,
+    pub message: String,
+}
+
+pub trait Detector: Send {
+    /// Rule name of the alerts this detector raises.
+    fn name(&self) -> &'static str;
+    /// `threshold` for fixed limits, `anomaly` for learned baselines.
+    fn detection(&self) -> &'static str {
+        "threshold"
+    }
+    fn on_event(&mut self, event: &ProcessEvent, now: Instant);
+    fn on_snapshot(&mut self, _snapshot: &SystemSnapshot, _now: Instant) {}
+    /// Findings since the last call.
+    fn emit(&mut self, now: Instant) -> Vec<Finding>;
+    /// Processes or series currently held.
+    fn tracked(&self) -> usize {
+        0
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LastFired {
+    /// Unix time
+    pub at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DetectorStatus {
+    pub name: &'static str,
+    pub detection: &'static str,
+    pub events: u64,
+    pub snapshots: u64,
+    /// Findings raised as alerts
+    pub fired: u64,
+    /// Findings dropped inside the cooldown
+    pub suppressed: u64,
+    pub tracked: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_fired: Option<LastFired>,
+}
+
+struct Entry {
+    detector: Box<dyn Detector>,
+    status: DetectorStatus,
+}
+
+struct State {
+    entries: Vec<Entry>,
+    /// Detector and pid to the end of their cooldown
+    cooldowns: HashMap<(&'static str, Option<u32>), Instant>,
+}
+
+pub struct DetectorList {
+    state: Mutex<State>,
+    cooldown: Duration,
+    alerts: Option<AlertSender>,
+    host: String,
+}
+
+impl DetectorList {
+    pub fn new(cooldown: Duration, alerts: Option<AlertSender>) -> Self {
+        Self {
+            state: Mutex::new(State {
+                entries: Vec::new(),
+                cooldowns: HashMap::new(),
+            }),
+            cooldown,
+            alerts,
+            host: std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown".into()),
+        }
+    }
+
+    /// The built-in detectors enabled in `config`.
+    pub fn from_config(config: &DetectorsConfig, alerts: Option<AlertSender>) -> Self {
+        let mut list = Self::new(Duration::from_secs(config.cooldown_secs), alerts);
+        if config.fork_storm.enabled {
+            list.register(ForkStorm::new(&config.fork_storm));
+        }
+        if config.memory_leak.enabled {
+            list.register(MemoryLeak::new(&config.memory_leak));
+        }
+        if config.spike.enabled && !config.spike.signals.is_empty() {
+            list.register(Spikes::new(&config.spike));
+        }
+        list
+    }
+
+    pub fn register<D: Detector + 'static>(&mut self, detector: D) {
+        let status = DetectorStatus {
+            name: detector.name(),
+            detection: detector.detection(),
+            events: 0,
+            snapshots: 0,
+            fired: 0,
+            suppressed: 0,
+            tracked: 0,
+            last_fired: None,
+        };
+        self.state.get_mut().unwrap().entries.push(Entry {
+            detector: Box::new(detector),
+            status,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.lock().unwrap().entries.is_empty()
+    }
+
+    pub fn snapshot(&self) -> Vec<DetectorStatus> {
+        self.state
+            .lock()
+            .unwrap()
+            .entries
+            .iter()
+            .map(|entry| DetectorStatus {
+                tracked: entry.detector.tracked(),
+                ..entry.status.clone()
+            })
+            .collect()
+    }
+
+    /// Collect the findings of every detector, apply the cooldown and build
+    /// the alerts to send.
+    fn drain(&self, state: &mut State, now: Instant) -> Vec<Alert> {
+        let State {
+            entries, cooldowns, ..
+        } = state;
+        let mut alerts = Vec::new();
+        for entry in entries.iter_mut() {
+            for finding in entry.detector.emit(now) {
+                let key = (entry.status.name, finding.pid);
+                if cooldowns.get(&key).is_some_and(|until| now < *until) {
+                    entry.status.suppressed += 1;
+                    continue;
+                }
+                cooldowns.insert(key, now + self.cooldown);
+                entry.status.fired += 1;
+                entry.status.last_fired = Some(LastFired {
+                    at: SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|d| d.as_secs())
+                        .unwrap_or(0),
+                    pid: finding.pid,
+                    message: finding.message.clone(),
+                });
+                log::info!("[detectors] {}: {}", entry.status.name, finding.message);
+                alerts.push(Alert {
+                    rule: entry.status.name.to_string(),
+                    severity: finding.severity,
+                    message: finding.message,
+                    host: self.host.clone(),
+                    detection: entry.status.detection,
+                    suppressed_by: None,
+                    lineage: Vec::new(),
+                    children: Vec::new(),
+                    pod: None,
+                    evidence: None,
+                    annotations: None,
+                });
+            }
+        }
+        if cooldowns.len() > MAX_TRACKED {
+            cooldowns.retain(|_, until| now < *until);
+        }
+        alerts
+    }
+
+    fn send(&self, alerts: Vec<Alert>) {
+        if let Some(tx) = &self.alerts {
+            for alert in alerts {
+                tx.send(alert);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Handler for DetectorList {
+    fn name(&self) -> &'static str {
+        "detectors"
+    }
+
+    async fn on_event(&self, event: &ProcessEvent) {
+        let now = Instant::now();
+        let alerts = {
+            let mut state = self.state.lock().unwrap();
+            for entry in state.entries.iter_mut() {
+                entry.status.events += 1;
+                entry.detector.on_event(event, now);
+            }
+            self.drain(&mut state, now)
+        };
+        self.send(alerts);
+    }
+
+    async fn on_snapshot(&self, snapshot: &SystemSnapshot) {
+        let now = Instant::now();
+        let alerts = {
+            let mut state = self.state.lock().unwrap();
+            for entry in state.entries.iter_mut() {
+                entry.status.snapshots += 1;
+                entry.detector.on_snapshot(snapshot, now);
+            }
+            self.drain(&mut state, now)
+        };
+        self.send(alerts);
+    }
+}
+
+/// NUL-trimmed comm of `event`.
+pub(crate) fn comm(event: &ProcessEvent) -> String {
+    String::from_utf8_lossy(&event.comm)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::metrics::Metrics;
+    use crate::{PERCENT_MILLI_UNKNOWN, ProcessEventWire};
+    use linnix_ai_ebpf_common::EventType;
+    use std::sync::Arc;
+
+    pub(crate) fn event(event_type: EventType, pid: u32, ppid: u32, comm: &[u8]) -> ProcessEvent {
+        let mut name = [0u8; 16];
+        name[..comm.len()].copy_from_slice(comm);
+        ProcessEvent::new(ProcessEventWire {
+            pid,
+            ppid,
+            uid: 0,
+            gid: 0,
+            event_type: event_type as u32,
+            ts_ns: 0,
+            seq: 0,
+            comm: name,
+            exit_time_ns: 0,
+            cpu_pct_milli: PERCENT_MILLI_UNKNOWN,
+            mem_pct_milli: PERCENT_MILLI_UNKNOWN,
+            data: 0,
+            data2: 0,
+            aux: 0,
+            aux2: 0,
+            cgroup_id: 0,
+        })
+    }
+
+    /// Fires on every exec it sees.
+    struct EveryExec(Vec<Finding>);
+
+    impl Detector for EveryExec {
+        fn name(&self) -> &'static str {
+            "every_exec"
+        }
+
+        fn on_event(&mut self, event: &ProcessEvent, _now: Instant) {
+            if event.event_type == EventType::Exec as u32 {
+                self.0.push(Finding {
+                    severity: Severity::Low,
+                    pid: Some(event.pid),
+                    message: format!("exec by {}", event.pid),
+                });
+            }
+        }
+
+        fn emit(&mut self, _now: Instant) -> Vec<Finding> {
+            std::mem::take(&mut self.0)
+        }
+    }
+
+    #[tokio::test]
+    async fn findings_become_alerts_once_per_cooldown() {
+        let tx = AlertSender::new(16, Arc::new(Metrics::new()));
+        let mut rx = tx.subscribe();
+        let mut list = DetectorList::new(Duration::from_secs(60), Some(tx));
+        list.register(EveryExec(Vec::new()));
+
+        list.on_event(&event(EventType::Exec, 10, 1, b"sh")).await;
+        list.on_event(&event(EventType::Exec, 10, 1, b"sh")).await;
+        list.on_event(&event(EventType::Fork, 11, 10, b"sh")).await;
+        list.on_event(&event(EventType::Exec, 12, 1, b"sh")).await;
+
+        let alert = rx.try_recv().unwrap();
+        assert_eq!(alert.rule, "every_exec");
+        assert_eq!(alert.severity, Severity::Low);
+        assert_eq!(alert.message, "exec by 10");
+        assert_eq!(rx.try_recv().unwrap().message, "exec by 12");
+        assert!(rx.try_recv().is_err());
+
+        let status = &list.snapshot()[0];
+        assert_eq!(status.events, 4);
+        assert_eq!(status.fired, 2);
+        assert_eq!(status.suppressed, 1);
+        let last = status.last_fired.as_ref().unwrap();
+        assert_eq!(last.pid, Some(12));
+        assert_eq!(last.message, "exec by 12");
+    }
+
+    #[test]
+    fn config_enables_the_built_ins() {
+        let list = DetectorList::from_config(&DetectorsConfig::default(), None);
+        let names: Vec<_> = list.snapshot().iter().map(|s| s.name).collect();
+        assert_eq!(names, vec!["fork_storm", "memory_leak"]);
+
+        let config: DetectorsConfig = toml::from_str(
+            r#"
+[fork_storm]
+enabled = false
+
+[spike]
+enabled = true
+signals = ["net_bytes"]
+"#,
+        )
+        .unwrap();
+        let list = DetectorList::from_config(&config, None);
+        let status = list.snapshot();
+        let names: Vec<_> = status.iter().map(|s| (s.name, s.detection)).collect();
+        assert_eq!(
+            names,
+            vec![("memory_leak", "threshold"), ("spike", "anomaly")]
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Per-process spikes against a learned baseline, one series per signal.
+//! See [`crate::anomaly`] for the baseline itself.
+
+use linnix_ai_ebpf_common::EventType;
+use tokio::time::{Duration, Instant};
+
+use super::{Detector, Finding, comm};
+use crate::ProcessEvent;
+use crate::alerts::Severity;
+use crate::anomaly::{SpikeConfig, SpikeDetector};
+use crate::config::SpikeDetectorConfig;
+
+pub struct Spikes {
+    detectors: Vec<SpikeDetector>,
+    pending: Vec<Finding>,
+}
+
+impl Spikes {
+    pub fn new(config: &SpikeDetectorConfig) -> Self {
+        let mut signals = Vec::new();
+        for signal in &config.signals {
+            if !signals.contains(signal) {
+                signals.push(*signal);
+            }
+        }
+        Self {
+            detectors: signals
+                .into_iter()
+                .map(|signal| {
+                    SpikeDetector::new(SpikeConfig {
+                        signal,
+                        k: config.k,
+                        alpha: config.alpha.clamp(f64::EPSILON, 1.0),
+                        warmup_samples: config.warmup_samples,
+                        interval: Duration::from_secs(config.interval_secs.max(1)),
+                        overrides: config.overrides.clone(),
+                    })
+                })
+                .collect(),
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl Detector for Spikes {
+    fn name(&self) -> &'static str {
+        "spike"
+    }
+
+    fn detection(&self) -> &'static str {
+        "anomaly"
+    }
+
+    fn on_event(&mut self, event: &ProcessEvent, now: Instant) {
+        if event.event_type == EventType::Exit as u32 {
+            for detector in &mut self.detectors {
+                detector.forget(event.pid);
+            }
+            return;
+        }
+        for detector in &mut self.detectors {
+            let signal = detector.signal();
+            let Some((pid, value)) = signal.sample(event) else {
+                continue;
+            };
+            let comm = comm(event);
+            if let Some(spike) = detector.record(pid, &comm, value, now) {
+                self.pending.push(Finding {
+                    severity: Severity::Medium,
+                    pid: Some(spike.pid),
+                    message: format!(
+                        "{} spike for {}({}): {:.0}/s vs baseline {:.0}/s ({:.1}σ, k={})",
+                        signal.as_str(),
+                        comm,
+                        spike.pid,
+                        spike.value,
+                        spike.mean,
+                        spike.z_score(),
+                        spike.k
+                    ),
+                });
+            }
+        }
+    }
+
+    fn emit(&mut self, _now: Instant) -> Vec<Finding> {
+        std::mem::take(&mut self.pending)
+    }
+
+    fn tracked(&self) -> usize {
+        self.detectors.iter().map(SpikeDetector::tracked).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anomaly::SpikeSignal;
+    use crate::detectors::tests::event;
+
+    #[test]
+    fn reports_a_net_spike_after_warmup() {
+        let mut spikes = Spikes::new(&SpikeDetectorConfig {
+            enabled: true,
+            signals: vec![SpikeSignal::NetBytes],
+            warmup_samples: 5,
+            ..SpikeDetectorConfig::default()
+        });
+        let start = Instant::now();
+        let net = |bytes| {
+            let mut e = event(EventType::Net, 42, 1, b"curl");
+            e.data = bytes;
+            e
+        };
+        for s in 0..10 {
+            spikes.on_event(&net(1000), start + Duration::from_secs(s));
+            // Fork events do not feed the net series
+            spikes.on_event(&event(EventType::Fork, 43, 42, b"curl"), start);
+        }
+        assert!(spikes.emit(start).is_empty());
+        assert_eq!(spikes.tracked(), 1);
+
+        spikes.on_event(&net(10_000_000), start + Duration::from_secs(10));
+        spikes.on_event(&net(1000), start + Duration::from_secs(11));
+        let findings = spikes.emit(start);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].pid, Some(42));
+        assert!(
+            findings[0]
+                .message
+                .starts_with("net_bytes spike for curl(42)")
+        );
+
+        spikes.on_event(&event(EventType::Exit, 42, 1, b"curl"), start);
+        assert_eq!(spikes.tracked(), 0);
+    }
+}
@@ -0,0 +1,293 @@
+//! Offline mode and per-category egress policy
+//!
+//! `[runtime] offline` blocks all outbound traffic. `[egress]` refines it
+//! per destination category: LLM endpoints, notifiers, event webhooks,
+//! config sync from the API server and OTLP export. Each flag may allow or
+//! block its category whatever `offline` says. The policy is enforced by the
+//! HTTP clients themselves. [`OfflineGuard::client`] wraps the client's
+//! connector, so a blocked category cannot open a connection, whichever code
+//! path sends the request. Such clients keep no idle connections. That way a
+//! category blocked at runtime (`POST /offline`) stops on the next request
+//! instead of reusing a connection opened before.
+//!
+//! The guard also carries the [`Retrier`] (`[egress.retry]`) the same
+//! clients send through, so every destination has one breaker.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll};
+
+use crate::config::{EgressConfig, RuntimeConfig};
+use crate::retry::{Retrier, RetryConfig};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Destination category of outbound traffic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Egress {
+    /// LLM endpoints for insights and incident analysis
+    Llm,
+    /// Slack, Discord, Apprise and incident sinks
+    Notifications,
+    /// `[[event_webhooks]]`
+    Webhooks,
+    /// Rules synced from a ConfigMap
+    ConfigSync,
+    /// OpenTelemetry export
+    Otlp,
+}
+
+impl Egress {
+    pub const ALL: [Egress; 5] = [
+        Egress::Llm,
+        Egress::Notifications,
+        Egress::Webhooks,
+        Egress::ConfigSync,
+        Egress::Otlp,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Egress::Llm => "llm",
+            Egress::Notifications => "notifications",
+            Egress::Webhooks => "webhooks",
+            Egress::ConfigSync => "config_sync",
+            Egress::Otlp => "otlp",
+        }
+    }
+}
+
+/// Error of a connection refused by the policy.
+#[derive(Debug)]
+pub struct EgressBlocked(pub Egress);
+
+impl std::fmt::Display for EgressBlocked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} egress is disabled by the offline policy",
+            self.0.name()
+        )
+    }
+}
+
+impl std::error::Error for EgressBlocked {}
+
+/// Current policy, as served by `GET /offline`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EgressPolicy {
+    /// `[runtime] offline`, which unset categories started from
+    pub offline: bool,
+    /// Whether each category may send
+    pub categories: BTreeMap<Egress, bool>,
+    /// Whether `POST /offline` is enabled
+    pub runtime_toggles: bool,
+}
+
+pub struct OfflineGuard {
+    offline: bool,
+    allowed: [AtomicBool; Egress::ALL.len()],
+    runtime_toggles: bool,
+    retrier: Arc<Retrier>,
+}
+
+impl OfflineGuard {
+    /// Every category blocked when `offline`, allowed otherwise.
+    pub fn new(offline: bool) -> Self {
+        Self {
+            offline,
+            allowed: Egress::ALL.map(|_| AtomicBool::new(!offline)),
+            runtime_toggles: false,
+            retrier: Arc::new(Retrier::new(RetryConfig::default())),
+        }
+    }
+
+    pub fn from_config(runtime: &RuntimeConfig, egress: &EgressConfig) -> Self {
+        let guard = Self {
+            runtime_toggles: egress.admin_token.as_deref().is_some_and(|t| !t.is_empty()),
+            retrier: Arc::new(Retrier::new(egress.retry.clone())),
+            ..Self::new(runtime.offline)
+        };
+        for category in Egress::ALL {
+            if let Some(allowed) = egress.allowed(category) {
+                guard.set(category, allowed);
+            }
+        }
+        guard
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Returns true if network operations without a category are allowed.
+    #[allow(dead_code)]
+    pub fn check(&self, sink: &str) -> bool {
+        if self.offline {
+            log::warn!("offline mode: blocking {sink} sink");
+            false
+        } else {
+            true
+        }
+    }
+
+    pub fn allows(&self, category: Egress) -> bool {
+        self.allowed[category as usize].load(Ordering::Relaxed)
+    }
+
+    /// Like `allows`, logging when `sink` is blocked.
+    pub fn check_egress(&self, category: Egress, sink: &str) -> bool {
+        let allowed = self.allows(category);
+        if !allowed {
+            log::warn!(
+                "offline policy: blocking {sink} ({} egress)",
+                category.name()
+            );
+        }
+        allowed
+    }
+
+    /// Allow or block a category; returns whether it was allowed before.
+    pub fn set(&self, category: Egress, allowed: bool) -> bool {
+        self.allowed[category as usize].swap(allowed, Ordering::Relaxed)
+    }
+
+    /// Retries and breakers of the outbound destinations.
+    pub fn retrier(&self) -> &Arc<Retrier> {
+        &self.retrier
+    }
+
+    pub fn policy(&self) -> EgressPolicy {
+        EgressPolicy {
+            offline: self.offline,
+            categories: Egress::ALL
+                .into_iter()
+                .map(|category| (category, self.allows(category)))
+                .collect(),
+            runtime_toggles: self.runtime_toggles,
+        }
+    }
+
+    /// Build an HTTP client whose connections are refused while `category`
+    /// is blocked.
+    ///
+    /// # Panics
+    ///
+    /// Like `reqwest::Client::new`, when the TLS backend cannot be
+    /// initialized.
+    pub fn client(
+        self: &Arc<Self>,
+        category: Egress,
+        builder: reqwest::ClientBuilder,
+    ) -> reqwest::Client {
+        builder
+            .connector_layer(EgressLayer {
+                guard: Arc::clone(self),
+                category,
+            })
+            .pool_max_idle_per_host(0)
+            .build()
+            .expect("TLS backend cannot be initialized")
+    }
+}
+
+#[derive(Clone)]
+struct EgressLayer {
+    guard: Arc<OfflineGuard>,
+    category: Egress,
+}
+
+impl<S> tower::Layer<S> for EgressLayer {
+    type Service = EgressService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        EgressService {
+            inner,
+            guard: Arc::clone(&self.guard),
+            category: self.category,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct EgressService<S> {
+    inner: S,
+    guard: Arc<OfflineGuard>,
+    category: Egress,
+}
+
+impl<S, R> tower::Service<R> for EgressService<S>
+where
+    S: tower::Service<R, Error = BoxError>,
+    S::Response: 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        if !self.guard.allows(self.category) {
+            let error: BoxError = Box::new(EgressBlocked(self.category));
+            return Box::pin(async move { Err(error) });
+        }
+        Box::pin(self.inner.call(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn categories_override_offline_mode() {
+        let guard = OfflineGuard::from_config(
+            &RuntimeConfig {
+                offline: true,
+                ..RuntimeConfig::default()
+            },
+            &EgressConfig {
+                notifications: Some(true),
+                admin_token: Some("s3cret".into()),
+                ..EgressConfig::default()
+            },
+        );
+        assert!(guard.is_offline());
+        assert!(guard.allows(Egress::Notifications));
+        assert!(!guard.allows(Egress::Llm));
+
+        assert!(!guard.set(Egress::Llm, true));
+        let policy = guard.policy();
+        assert!(policy.categories[&Egress::Llm]);
+        assert!(!policy.categories[&Egress::Webhooks]);
+        assert!(policy.runtime_toggles);
+        assert_eq!(
+            serde_json::to_value(&policy).unwrap()["categories"]["config_sync"],
+            false
+        );
+        assert!(!OfflineGuard::new(false).policy().runtime_toggles);
+    }
+
+    #[tokio::test]
+    async fn blocked_clients_do_not_connect() {
+        let guard = Arc::new(OfflineGuard::new(true));
+        let client = guard.client(Egress::Webhooks, reqwest::Client::builder());
+        let error = client.get("http://127.0.0.1:9/").send().await.unwrap_err();
+        assert!(format!("{error:?}").contains("EgressBlocked(Webhooks)"));
+
+        // Allowed again at runtime: the request gets as far as connecting
+        guard.set(Egress::Webhooks, true);
+        if let Err(error) = client.get("http://127.0.0.1:9/").send().await {
+            assert!(!format!("{error:?}").contains("EgressBlocked"));
+        }
+    }
+}
@@ -1,11 +1,24 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 
+use crate::config::{ApprovalsConfig, ExpiryPolicy};
+use crate::identity::ProcessKey;
+use crate::maintenance::{MaintenanceManager, MaintenanceSubject};
+use crate::scope::CgroupScope;
+
+pub mod approvals;
+mod outcome;
 mod safety;
 
+pub use approvals::{PendingEntry, PendingSummary};
+pub use outcome::{
+    ActionOutcome, Effectiveness, OUTCOME_DELAY_SECS, PressureSample, RuleEffectiveness,
+};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case", tag = "type")]
 pub enum ActionType {
@@ -17,6 +30,29 @@ pub enum ActionType {
     UnfreezeProcess { pid: u32 },
     /// Throttle a cgroup by writing to cpu.max (microseconds per period)
     ThrottleCgroup { cgroup_path: String, quota_us: u64, period_us: u64 },
+    /// Drop traffic from a remote address for `ttl_secs`
+    BlockSource {
+        addr: String,
+        backend: BlockBackend,
+        ttl_secs: u64,
+    },
+    /// Rate-limit clients of a configured Cloudflare zone for `ttl_secs`
+    CloudflareRateLimit {
+        zone: String,
+        requests_per_period: u64,
+        period_secs: u64,
+        ttl_secs: u64,
+    },
+}
+
+/// Where a `BlockSource` action is enforced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockBackend {
+    /// Element of the `linnix` nftables set on this node
+    Nftables,
+    /// Cloudflare IP Access Rule on every configured zone
+    Cloudflare,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -27,14 +63,23 @@ pub enum ActionStatus {
     Rejected,
     Expired,
     Executed,
+    /// Approved but not executed, see `error`
+    Aborted,
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct EnforcementAction {
     pub id: String,
     pub action: ActionType,
+    /// Identity of the targeted process when the action was proposed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<ProcessKey>,
     pub reason: String,
     pub source: String,
+    /// Rule or breaker event that triggered the action; effectiveness is
+    /// tracked under this name, falling back to `source`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub confidence: Option<f64>,
     pub status: ActionStatus,
@@ -44,12 +89,50 @@ pub struct EnforcementAction {
     pub approved_by: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub approved_at: Option<u64>,
+    /// Pressure before and after execution.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<ActionOutcome>,
+    /// Reminders sent to notifiers while pending.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub reminders: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_reminder_at: Option<u64>,
+    /// Why an approved action was aborted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+impl EnforcementAction {
+    fn effectiveness_key(&self) -> &str {
+        self.rule.as_deref().unwrap_or(&self.source)
+    }
+
+    /// Check, right before signaling, that the pid still belongs to the
+    /// process the action was proposed against. Approval can take minutes,
+    /// long enough for the process to exit and its pid to be reused.
+    pub fn verify_target(&self) -> Result<(), String> {
+        match (self.action.pid(), &self.target) {
+            (None, _) => Ok(()),
+            (Some(_), Some(target)) => target.verify(),
+            (Some(pid), None) => Err(format!(
+                "pid {pid} was not running when the action was proposed"
+            )),
+        }
+    }
 }
 
 pub struct EnforcementQueue {
     next_id: AtomicU64,
     actions: RwLock<HashMap<String, EnforcementAction>>,
     ttl_secs: u64,
+    approvals: ApprovalsConfig,
+    maintenance: Option<Arc<MaintenanceManager>>,
+    scope: Option<Arc<CgroupScope>>,
+    effectiveness: Mutex<Effectiveness>,
 }
 
 impl EnforcementQueue {
@@ -58,7 +141,82 @@ impl EnforcementQueue {
             next_id: AtomicU64::new(1),
             actions: RwLock::new(HashMap::new()),
             ttl_secs,
+            approvals: ApprovalsConfig::default(),
+            maintenance: None,
+            scope: None,
+            effectiveness: Mutex::new(Effectiveness::default()),
+        }
+    }
+
+    /// Disable auto-approval for targets covered by an active maintenance
+    /// window; such actions stay pending for a human.
+    pub fn with_maintenance(mut self, maintenance: Arc<MaintenanceManager>) -> Self {
+        self.maintenance = Some(maintenance);
+        self
+    }
+
+    /// Expiry policy and reminder interval of pending actions. Replaces the
+    /// TTL given to `new`.
+    pub fn with_approvals(mut self, approvals: ApprovalsConfig) -> Self {
+        self.ttl_secs = approvals.ttl_secs;
+        self.approvals = approvals;
+        self
+    }
+
+    /// Refuse actions on processes or cgroups outside the monitored subtrees.
+    pub fn with_scope(mut self, scope: Arc<CgroupScope>) -> Self {
+        self.scope = Some(scope);
+        self
+    }
+
+    fn check_scope(&self, action: &ActionType) -> Result<(), String> {
+        let Some(scope) = &self.scope else {
+            return Ok(());
+        };
+        match action {
+            ActionType::KillProcess { pid, .. }
+            | ActionType::FreezeProcess { pid }
+            | ActionType::UnfreezeProcess { pid } => {
+                if !scope.contains_pid(*pid) {
+                    return Err(format!("pid {} is outside the monitored cgroup scope", pid));
+                }
+            }
+            ActionType::ThrottleCgroup { cgroup_path, .. } => {
+                if !scope.contains_path(cgroup_path) {
+                    return Err(format!(
+                        "cgroup '{}' is outside the monitored cgroup scope",
+                        cgroup_path
+                    ));
+                }
+            }
+            // Remote addresses and edge rules are not bound to a cgroup
+            ActionType::BlockSource { .. } | ActionType::CloudflareRateLimit { .. } => {}
         }
+        Ok(())
+    }
+
+    /// Name of the maintenance window covering `action`, if any. Unfreezing
+    /// is never held back.
+    fn maintenance_hold(&self, action: &ActionType) -> Option<String> {
+        let mgr = self.maintenance.as_ref()?;
+        let pid = match action {
+            ActionType::KillProcess { pid, .. } | ActionType::FreezeProcess { pid } => Some(*pid),
+            ActionType::UnfreezeProcess { .. } => return None,
+            ActionType::ThrottleCgroup { .. }
+            | ActionType::BlockSource { .. }
+            | ActionType::CloudflareRateLimit { .. } => None,
+        };
+        let comm = pid.and_then(|pid| {
+            std::fs::read_to_string(format!("/proc/{pid}/comm"))
+                .ok()
+                .map(|c| c.trim().to_string())
+        });
+        let namespace = pid.and_then(|pid| mgr.namespace_for_pid(pid));
+        mgr.matching_window(&MaintenanceSubject {
+            rule: None,
+            comm: comm.as_deref(),
+            namespace: namespace.as_deref(),
+        })
     }
 
     pub async fn propose(
@@ -68,7 +226,7 @@ impl EnforcementQueue {
         source: String,
         confidence: Option<f64>,
     ) -> Result<String, String> {
-        self.propose_internal(action, reason, source, confidence, false)
+        self.propose_internal(action, reason, source, None, confidence, false)
             .await
     }
 
@@ -84,15 +242,38 @@ impl EnforcementQueue {
         confidence: Option<f64>,
         auto_approve: bool,
     ) -> Result<String, String> {
-        self.propose_internal(action, reason, source, confidence, auto_approve)
+        self.propose_internal(action, reason, source, None, confidence, auto_approve)
             .await
     }
 
+    /// Like `propose_auto`, attributing the action to `rule` for
+    /// effectiveness tracking.
+    pub async fn propose_for_rule(
+        &self,
+        rule: &str,
+        action: ActionType,
+        reason: String,
+        source: String,
+        confidence: Option<f64>,
+        auto_approve: bool,
+    ) -> Result<String, String> {
+        self.propose_internal(
+            action,
+            reason,
+            source,
+            Some(rule.to_string()),
+            confidence,
+            auto_approve,
+        )
+        .await
+    }
+
     async fn propose_internal(
         &self,
         action: ActionType,
         reason: String,
         source: String,
+        rule: Option<String>,
         confidence: Option<f64>,
         auto_approve: bool,
     ) -> Result<String, String> {
@@ -110,11 +291,33 @@ impl EnforcementQueue {
             ActionType::ThrottleCgroup { cgroup_path, .. } => {
                 safety::SafetyGuard::is_safe_cgroup(cgroup_path)?;
             }
+            ActionType::BlockSource { addr, .. } => {
+                safety::SafetyGuard::is_safe_to_block(addr)?;
+            }
+            ActionType::CloudflareRateLimit { .. } => {
+                // Applies at the edge, never to this node
+            }
         }
+        self.check_scope(&action)?;
+        let target = action.pid().and_then(ProcessKey::read);
 
         let id = format!("action-{}", self.next_id.fetch_add(1, Ordering::SeqCst));
         let now = current_epoch_secs();
 
+        let hold = if auto_approve {
+            self.maintenance_hold(&action)
+        } else {
+            None
+        };
+        if let (Some(window), Some(mgr)) = (&hold, &self.maintenance) {
+            mgr.record_suppression(
+                window,
+                "auto_approval_disabled",
+                format!("action={id} source={source}"),
+            );
+        }
+        let auto_approve = auto_approve && hold.is_none();
+
         let (status, approved_by, approved_at) = if auto_approve {
             (
                 ActionStatus::Approved,
@@ -128,14 +331,20 @@ impl EnforcementQueue {
         let enforcement_action = EnforcementAction {
             id: id.clone(),
             action,
+            target,
             reason: reason.clone(),
             source: source.clone(),
+            rule,
             confidence,
             status,
             created_at: now,
             expires_at: now + self.ttl_secs,
             approved_by: approved_by.clone(),
             approved_at,
+            outcome: None,
+            reminders: 0,
+            last_reminder_at: None,
+            error: None,
         };
 
         self.actions
@@ -166,8 +375,10 @@ impl EnforcementQueue {
 
         let now = current_epoch_secs();
         if now > action.expires_at {
-            action.status = ActionStatus::Expired;
-            return Err("expired".to_string());
+            return Err(match self.expire(action, now) {
+                ExpiryPolicy::Reject => "expired".to_string(),
+                ExpiryPolicy::Approve => "expired; approved by expiry policy".to_string(),
+            });
         }
 
         action.status = ActionStatus::Approved;
@@ -209,16 +420,147 @@ impl EnforcementQueue {
         Ok(())
     }
 
-    #[allow(dead_code)]
-    pub async fn get_pending(&self) -> Vec<EnforcementAction> {
+    /// Give up on an approved action instead of executing it.
+    pub async fn abort(&self, id: &str, error: String) -> Result<(), String> {
+        let mut actions = self.actions.write().await;
+        let action = actions.get_mut(id).ok_or("action not found")?;
+
+        if action.status != ActionStatus::Approved {
+            return Err(format!("not approved: {:?}", action.status));
+        }
+
+        log::warn!(
+            target: "linnix_audit",
+            "ABORTED {} reason={}",
+            id, error
+        );
+        action.status = ActionStatus::Aborted;
+        action.error = Some(error);
+        Ok(())
+    }
+
+    /// Record pressure at execution. Unfreezing releases an earlier action
+    /// and is not an intervention of its own, so it is not tracked.
+    pub async fn record_baseline(&self, id: &str, before: PressureSample) {
+        let mut actions = self.actions.write().await;
+        let Some(action) = actions.get_mut(id) else {
+            return;
+        };
+        if matches!(action.action, ActionType::UnfreezeProcess { .. }) {
+            return;
+        }
         let now = current_epoch_secs();
+        action.outcome = Some(ActionOutcome::new(now, before));
+        self.effectiveness
+            .lock()
+            .unwrap()
+            .executed(action.effectiveness_key(), now);
+    }
+
+    /// Record pressure `OUTCOME_DELAY_SECS` after execution and fold the
+    /// result into the rule's statistics.
+    pub async fn record_outcome(&self, id: &str, after: PressureSample) {
         let mut actions = self.actions.write().await;
+        let Some(action) = actions.get_mut(id) else {
+            return;
+        };
+        let Some(outcome) = action.outcome.as_mut().filter(|o| o.after.is_none()) else {
+            return;
+        };
+        outcome.measure(current_epoch_secs(), after);
+        log::info!(
+            "[enforcement] outcome of {id}: psi_cpu {:.1}% -> {:.1}%, psi_memory {:.1}% -> {:.1}%",
+            outcome.before.psi_cpu,
+            after.psi_cpu,
+            outcome.before.psi_memory,
+            after.psi_memory
+        );
+        let outcome = outcome.clone();
+        self.effectiveness
+            .lock()
+            .unwrap()
+            .measured(action.effectiveness_key(), &outcome);
+    }
+
+    /// Per-rule intervention effectiveness, least effective first.
+    pub fn effectiveness(&self) -> Vec<RuleEffectiveness> {
+        self.effectiveness.lock().unwrap().report()
+    }
+
+    fn expiry_policy(&self, action: &EnforcementAction) -> ExpiryPolicy {
+        self.approvals.expiry_policy(action.action.kind())
+    }
+
+    /// Decide an action nobody decided in time, as its policy says.
+    fn expire(&self, action: &mut EnforcementAction, now: u64) -> ExpiryPolicy {
+        let policy = self.expiry_policy(action);
+        match policy {
+            ExpiryPolicy::Reject => {
+                action.status = ActionStatus::Expired;
+                log::info!("[enforcement] {} expired unapproved", action.id);
+            }
+            ExpiryPolicy::Approve => {
+                action.status = ActionStatus::Approved;
+                action.approved_by = Some(approvals::EXPIRY_APPROVER.to_string());
+                action.approved_at = Some(now);
+                log::warn!(
+                    target: "linnix_audit",
+                    "EXPIRY_APPROVED {} source={} reason={}",
+                    action.id, action.source, action.reason
+                );
+            }
+        }
+        policy
+    }
 
+    fn expire_overdue(&self, actions: &mut HashMap<String, EnforcementAction>, now: u64) {
         for action in actions.values_mut() {
             if action.status == ActionStatus::Pending && now > action.expires_at {
-                action.status = ActionStatus::Expired;
+                self.expire(action, now);
+            }
+        }
+    }
+
+    /// Apply the expiry policy to overdue actions and return the pending
+    /// ones due a reminder, with their reminder count already raised.
+    pub async fn sweep(&self, now: u64) -> Vec<PendingEntry> {
+        let mut actions = self.actions.write().await;
+        self.expire_overdue(&mut actions, now);
+        let interval = self.approvals.escalation_interval_secs;
+        if interval == 0 {
+            return Vec::new();
+        }
+        let mut due = Vec::new();
+        for action in actions.values_mut() {
+            let last = action.last_reminder_at.unwrap_or(action.created_at);
+            if action.status != ActionStatus::Pending || now.saturating_sub(last) < interval {
+                continue;
             }
+            action.reminders += 1;
+            action.last_reminder_at = Some(now);
+            due.push(PendingEntry::new(action, self.expiry_policy(action), now));
         }
+        due
+    }
+
+    /// Pending actions with their age, for on-call.
+    pub async fn pending_summary(&self, now: u64) -> PendingSummary {
+        let mut actions = self.actions.write().await;
+        self.expire_overdue(&mut actions, now);
+        PendingSummary::new(
+            actions
+                .values()
+                .filter(|a| a.status == ActionStatus::Pending)
+                .map(|a| PendingEntry::new(a, self.expiry_policy(a), now))
+                .collect(),
+        )
+    }
+
+    #[allow(dead_code)]
+    pub async fn get_pending(&self) -> Vec<EnforcementAction> {
+        let now = current_epoch_secs();
+        let mut actions = self.actions.write().await;
+        self.expire_overdue(&mut actions, now);
 
         actions
             .values()
@@ -246,6 +588,46 @@ fn current_epoch_secs() -> u64 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::maintenance::CreateWindowRequest;
+
+    #[tokio::test]
+    async fn maintenance_window_disables_auto_approval() {
+        // Given: An active maintenance window covering all targets
+        let mgr = Arc::new(MaintenanceManager::default());
+        mgr.create(CreateWindowRequest {
+            name: "deploy".to_string(),
+            duration_secs: 600,
+            reason: "rollout".to_string(),
+            created_by: Some("alice".to_string()),
+            scope: Default::default(),
+        })
+        .unwrap();
+        let queue = EnforcementQueue::new(300).with_maintenance(Arc::clone(&mgr));
+
+        // When: The circuit breaker proposes an auto-approved kill
+        let action_id = queue
+            .propose_auto(
+                ActionType::KillProcess {
+                    pid: 123,
+                    signal: 9,
+                },
+                "CPU thrashing".to_string(),
+                "circuit_breaker".to_string(),
+                None,
+                true,
+            )
+            .await
+            .unwrap();
+
+        // Then: The action waits for a human and the hold is audited
+        let action = queue.get_by_id(&action_id).await.unwrap();
+        assert_eq!(action.status, ActionStatus::Pending);
+        assert!(
+            mgr.audit()
+                .iter()
+                .any(|e| e.action == "auto_approval_disabled" && e.window == "deploy")
+        );
+    }
 
     #[tokio::test]
     async fn kill_action_requires_approval_by_operator() {
@@ -300,6 +682,92 @@ mod tests {
         assert!(result.unwrap_err().contains("expired"));
     }
 
+    #[tokio::test]
+    async fn expiry_policy_decides_unanswered_actions() {
+        // Given: Freezes are approved on expiry, everything else rejected
+        let queue = EnforcementQueue::new(0).with_approvals(ApprovalsConfig {
+            ttl_secs: 60,
+            on_expiry_by_action: HashMap::from([(
+                "freeze_process".to_string(),
+                ExpiryPolicy::Approve,
+            )]),
+            ..ApprovalsConfig::default()
+        });
+        let kill = queue
+            .propose(
+                ActionType::KillProcess {
+                    pid: 123,
+                    signal: 9,
+                },
+                "runaway".to_string(),
+                "circuit_breaker".to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+        let freeze = queue
+            .propose(
+                ActionType::FreezeProcess { pid: 124 },
+                "runaway".to_string(),
+                "circuit_breaker".to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+        let created = queue.get_by_id(&kill).await.unwrap().created_at;
+
+        // When: Nobody reacts before the TTL passes
+        queue.sweep(created + 61).await;
+
+        // Then: Each action is decided by its policy
+        let kill = queue.get_by_id(&kill).await.unwrap();
+        assert_eq!(kill.status, ActionStatus::Expired);
+        let freeze = queue.get_by_id(&freeze).await.unwrap();
+        assert_eq!(freeze.status, ActionStatus::Approved);
+        assert_eq!(
+            freeze.approved_by.as_deref(),
+            Some(approvals::EXPIRY_APPROVER)
+        );
+        assert_eq!(queue.pending_summary(created + 61).await.total, 0);
+    }
+
+    #[tokio::test]
+    async fn pending_actions_are_escalated_at_intervals() {
+        // Given: Reminders every two minutes on a one hour TTL
+        let queue = EnforcementQueue::new(0).with_approvals(ApprovalsConfig {
+            ttl_secs: 3600,
+            escalation_interval_secs: 120,
+            ..ApprovalsConfig::default()
+        });
+        let id = queue
+            .propose(
+                ActionType::FreezeProcess { pid: 123 },
+                "memory leak".to_string(),
+                "rules".to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+        let created = queue.get_by_id(&id).await.unwrap().created_at;
+
+        // When: The sweeper runs over the next five minutes
+        let mut sent = Vec::new();
+        for t in (0..=300).step_by(5) {
+            sent.extend(queue.sweep(created + t).await);
+        }
+
+        // Then: Two reminders went out, the second at high severity
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].age_secs, 120);
+        assert_eq!(sent[1].age_secs, 240);
+        let alert = approvals::reminder_alert(&sent[1], "node-1");
+        assert_eq!(alert.severity, crate::alerts::Severity::High);
+        assert!(alert.message.contains(&id));
+        let summary = queue.pending_summary(created + 300).await;
+        assert_eq!(summary.actions[0].reminders, 2);
+        assert_eq!(summary.buckets[2].count, 1);
+    }
+
     #[tokio::test]
     async fn rejected_actions_cannot_be_approved_later() {
         // Given: A proposed kill action
@@ -357,4 +825,120 @@ mod tests {
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("not pending"));
     }
+
+    #[tokio::test]
+    async fn executed_actions_feed_rule_effectiveness() {
+        // Given: An approved freeze attributed to the CPU breaker
+        let queue = EnforcementQueue::new(300);
+        let action_id = queue
+            .propose_for_rule(
+                "circuit_breaker_cpu",
+                ActionType::FreezeProcess { pid: 123 },
+                "CPU thrashing".to_string(),
+                "circuit_breaker".to_string(),
+                None,
+                true,
+            )
+            .await
+            .unwrap();
+        let before = PressureSample {
+            psi_cpu: 62.0,
+            psi_memory: 3.0,
+            cpu_percent: 97.0,
+        };
+
+        // When: It executes and pressure is measured again later
+        queue.complete(&action_id).await.unwrap();
+        queue.record_baseline(&action_id, before).await;
+        assert_eq!(queue.effectiveness()[0].measured, 0);
+        queue
+            .record_outcome(
+                &action_id,
+                PressureSample {
+                    psi_cpu: 12.0,
+                    ..before
+                },
+            )
+            .await;
+
+        // Then: The outcome is kept on the action and credited to the rule
+        let action = queue.get_by_id(&action_id).await.unwrap();
+        assert_eq!(action.outcome.unwrap().improved, Some(true));
+        let stats = queue.effectiveness();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].rule, "circuit_breaker_cpu");
+        assert_eq!(stats[0].effectiveness, Some(1.0));
+        assert_eq!(stats[0].mean_psi_cpu_delta, Some(-50.0));
+    }
+
+    #[tokio::test]
+    async fn reused_pids_are_not_signaled() {
+        // Given: An auto-approved freeze of a pid that is not running
+        let queue = EnforcementQueue::new(300);
+        let action_id = queue
+            .propose_auto(
+                ActionType::FreezeProcess { pid: 99_999_999 },
+                "CPU thrashing".to_string(),
+                "circuit_breaker".to_string(),
+                None,
+                true,
+            )
+            .await
+            .unwrap();
+        let mut action = queue.get_by_id(&action_id).await.unwrap();
+        assert!(action.target.is_none());
+        assert!(action.verify_target().is_err());
+
+        // Then: A target is verified by its start time, not just its pid
+        let me = ProcessKey::read(std::process::id()).unwrap();
+        action.target = Some(me);
+        assert!(action.verify_target().is_ok());
+        action.target = Some(ProcessKey::new(me.pid, me.start_ns + 60_000_000_000));
+        let error = action.verify_target().unwrap_err();
+
+        // When: The executor gives up on the action
+        queue.abort(&action_id, error).await.unwrap();
+
+        // Then: It is recorded as aborted and can no longer complete
+        let action = queue.get_by_id(&action_id).await.unwrap();
+        assert_eq!(action.status, ActionStatus::Aborted);
+        assert!(action.error.unwrap().contains("another process"));
+        assert!(queue.complete(&action_id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn actions_outside_cgroup_scope_are_refused() {
+        // Given: A queue scoped to one tenant's cgroup subtree
+        let root = tempfile::tempdir().unwrap();
+        for dir in ["tenants/a/app", "tenants/b"] {
+            std::fs::create_dir_all(root.path().join(dir)).unwrap();
+        }
+        let scope = CgroupScope::resolve(root.path(), &["/tenants/a".to_string()])
+            .unwrap()
+            .unwrap();
+        let queue = EnforcementQueue::new(300).with_scope(Arc::new(scope));
+        let throttle = |path: &str| ActionType::ThrottleCgroup {
+            cgroup_path: path.to_string(),
+            quota_us: 50_000,
+            period_us: 100_000,
+        };
+
+        // When: Throttles are proposed inside and outside the subtree
+        let inside = queue
+            .propose(
+                throttle("/tenants/a/app"),
+                "noisy".into(),
+                "test".into(),
+                None,
+            )
+            .await;
+        let outside = queue
+            .propose(throttle("/tenants/b"), "noisy".into(), "test".into(), None)
+            .await;
+
+        // Then: Only the in-scope cgroup is accepted
+        assert!(inside.is_ok());
+        let err = outside.unwrap_err();
+        assert!(err.contains("outside the monitored cgroup scope"));
+    }
 }
@@ -0,0 +1,241 @@
+//! Approval expiry and escalation
+//!
+//! A proposed action waits `ttl_secs` for a human. While it waits, the
+//! sweeper reminds notifiers every `escalation_interval_secs`: the first
+//! reminder is medium severity, later ones high. Once the TTL passes the
+//! action's `ExpiryPolicy` decides for the operator, rejecting it (status
+//! `expired`) or approving it.
+
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{ActionType, EnforcementAction, EnforcementQueue, current_epoch_secs};
+use crate::alerts::{Alert, AlertSender, Severity};
+use crate::config::ExpiryPolicy;
+
+/// How often pending actions are checked for expiry and reminders.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Rule name of reminder alerts.
+pub const REMINDER_RULE: &str = "enforcement_approval_pending";
+
+/// Approver recorded when the expiry policy approves an action.
+pub const EXPIRY_APPROVER: &str = "expiry_policy";
+
+/// Age buckets of `/actions/pending`: exclusive upper bound in seconds and
+/// label.
+const AGE_BUCKETS: [(u64, &str); 5] = [
+    (60, "<1m"),
+    (300, "1-5m"),
+    (900, "5-15m"),
+    (3600, "15-60m"),
+    (u64::MAX, ">=1h"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AgeBucket {
+    pub label: &'static str,
+    pub count: usize,
+}
+
+/// One action waiting for approval.
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingEntry {
+    pub id: String,
+    pub kind: &'static str,
+    pub target: String,
+    pub source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    pub reason: String,
+    pub age_secs: u64,
+    pub expires_in_secs: u64,
+    pub on_expiry: ExpiryPolicy,
+    pub reminders: u32,
+}
+
+impl PendingEntry {
+    pub(super) fn new(action: &EnforcementAction, policy: ExpiryPolicy, now: u64) -> Self {
+        Self {
+            id: action.id.clone(),
+            kind: action.action.kind(),
+            target: action.action.target(),
+            source: action.source.clone(),
+            rule: action.rule.clone(),
+            reason: action.reason.clone(),
+            age_secs: now.saturating_sub(action.created_at),
+            expires_in_secs: action.expires_at.saturating_sub(now),
+            on_expiry: policy,
+            reminders: action.reminders,
+        }
+    }
+}
+
+/// Response of `/actions/pending`.
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingSummary {
+    pub total: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oldest_age_secs: Option<u64>,
+    pub buckets: Vec<AgeBucket>,
+    /// Oldest first.
+    pub actions: Vec<PendingEntry>,
+}
+
+impl PendingSummary {
+    pub fn new(mut actions: Vec<PendingEntry>) -> Self {
+        actions.sort_by(|a, b| b.age_secs.cmp(&a.age_secs).then(a.id.cmp(&b.id)));
+        let mut buckets: Vec<AgeBucket> = AGE_BUCKETS
+            .iter()
+            .map(|&(_, label)| AgeBucket { label, count: 0 })
+            .collect();
+        for entry in &actions {
+            let index = AGE_BUCKETS
+                .iter()
+                .position(|&(below, _)| entry.age_secs < below)
+                .unwrap_or(AGE_BUCKETS.len() - 1);
+            buckets[index].count += 1;
+        }
+        Self {
+            total: actions.len(),
+            oldest_age_secs: actions.first().map(|a| a.age_secs),
+            buckets,
+            actions,
+        }
+    }
+}
+
+/// Reminder sent to notifiers for an action still waiting for approval.
+pub fn reminder_alert(entry: &PendingEntry, host: &str) -> Alert {
+    let outcome = match entry.on_expiry {
+        ExpiryPolicy::Reject => "rejected",
+        ExpiryPolicy::Approve => "approved automatically",
+    };
+    Alert {
+        rule: REMINDER_RULE.to_string(),
+        severity: if entry.reminders > 1 {
+            Severity::High
+        } else {
+            Severity::Medium
+        },
+        message: format!(
+            "{} {} ({}) proposed by {} has waited {}s for approval (reminder {}); \
+             it will be {} in {}s. Reason: {}",
+            entry.kind,
+            entry.target,
+            entry.id,
+            entry.source,
+            entry.age_secs,
+            entry.reminders,
+            outcome,
+            entry.expires_in_secs,
+            entry.reason
+        ),
+        host: host.to_string(),
+        detection: "threshold",
+        suppressed_by: None,
+        lineage: Vec::new(),
+        children: Vec::new(),
+        pod: None,
+        evidence: None,
+        annotations: None,
+    }
+}
+
+impl ActionType {
+    /// Name of the variant as serialized in `type`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ActionType::KillProcess { .. } => "kill_process",
+            ActionType::FreezeProcess { .. } => "freeze_process",
+            ActionType::UnfreezeProcess { .. } => "unfreeze_process",
+            ActionType::ThrottleCgroup { .. } => "throttle_cgroup",
+            ActionType::BlockSource { .. } => "block_source",
+            ActionType::CloudflareRateLimit { .. } => "cloudflare_rate_limit",
+        }
+    }
+
+    /// Process the action signals.
+    pub fn pid(&self) -> Option<u32> {
+        match self {
+            ActionType::KillProcess { pid, .. }
+            | ActionType::FreezeProcess { pid }
+            | ActionType::UnfreezeProcess { pid } => Some(*pid),
+            _ => None,
+        }
+    }
+
+    pub fn target(&self) -> String {
+        match self {
+            ActionType::KillProcess { pid, .. }
+            | ActionType::FreezeProcess { pid }
+            | ActionType::UnfreezeProcess { pid } => format!("pid {pid}"),
+            ActionType::ThrottleCgroup { cgroup_path, .. } => format!("cgroup {cgroup_path}"),
+            ActionType::BlockSource { addr, backend, .. } => format!("{addr} via {backend:?}"),
+            ActionType::CloudflareRateLimit { zone, .. } => format!("zone {zone}"),
+        }
+    }
+}
+
+async fn run(queue: Arc<EnforcementQueue>, tx: Option<AlertSender>) {
+    let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown".into());
+    let mut tick = tokio::time::interval(SWEEP_INTERVAL);
+    loop {
+        tick.tick().await;
+        let reminders = queue.sweep(current_epoch_secs()).await;
+        let Some(tx) = &tx else {
+            continue;
+        };
+        for entry in &reminders {
+            log::info!(
+                "[enforcement] reminder {} for pending {}",
+                entry.reminders,
+                entry.id
+            );
+            tx.send(reminder_alert(entry, &host));
+        }
+    }
+}
+
+/// Expire pending actions and send reminders. Runs without notifiers too,
+/// since the expiry policy may approve actions.
+pub fn spawn(queue: Arc<EnforcementQueue>, tx: Option<AlertSender>) {
+    tokio::spawn(run(queue, tx));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, age_secs: u64) -> PendingEntry {
+        PendingEntry {
+            id: id.to_string(),
+            kind: "freeze_process",
+            target: "pid 1".to_string(),
+            source: "test".to_string(),
+            rule: None,
+            reason: "test".to_string(),
+            age_secs,
+            expires_in_secs: 0,
+            on_expiry: ExpiryPolicy::Reject,
+            reminders: 0,
+        }
+    }
+
+    #[test]
+    fn pending_actions_are_bucketed_by_age() {
+        let summary = PendingSummary::new(vec![
+            entry("a", 30),
+            entry("b", 4000),
+            entry("c", 60),
+            entry("d", 299),
+        ]);
+        assert_eq!(summary.total, 4);
+        assert_eq!(summary.oldest_age_secs, Some(4000));
+        let counts: Vec<usize> = summary.buckets.iter().map(|b| b.count).collect();
+        assert_eq!(counts, [1, 2, 0, 0, 1]);
+        let order: Vec<&str> = summary.actions.iter().map(|a| a.id.as_str()).collect();
+        assert_eq!(order, ["b", "d", "c", "a"]);
+    }
+}
@@ -0,0 +1,201 @@
+//! Intervention outcome tracking
+//!
+//! When an action executes we record node pressure, then sample it again
+//! `OUTCOME_DELAY_SECS` later. The deltas are kept on the action and folded
+//! into per-rule statistics so rules whose interventions rarely relieve
+//! pressure can be tuned or removed.
+
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::types::SystemSnapshot;
+
+/// Delay between executing an action and measuring its effect. PSI `avg10`
+/// needs a few windows to reflect the change.
+pub const OUTCOME_DELAY_SECS: u64 = 30;
+
+/// Drop in PSI (percentage points) that counts as relief.
+pub const IMPROVEMENT_MIN_PSI_DROP: f32 = 5.0;
+
+/// Node pressure at one point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PressureSample {
+    pub psi_cpu: f32,
+    pub psi_memory: f32,
+    pub cpu_percent: f32,
+}
+
+impl From<&SystemSnapshot> for PressureSample {
+    fn from(snapshot: &SystemSnapshot) -> Self {
+        Self {
+            psi_cpu: snapshot.psi_cpu_some_avg10,
+            psi_memory: snapshot.psi_memory_some_avg10,
+            cpu_percent: snapshot.cpu_percent,
+        }
+    }
+}
+
+impl PressureSample {
+    /// The stall signal an intervention is meant to relieve.
+    fn pressure(&self) -> f32 {
+        self.psi_cpu.max(self.psi_memory)
+    }
+}
+
+/// Before/after pressure of one executed action.
+#[derive(Debug, Clone, Serialize)]
+pub struct ActionOutcome {
+    pub executed_at: u64,
+    pub before: PressureSample,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<PressureSample>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub measured_at: Option<u64>,
+    /// Set once measured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub improved: Option<bool>,
+}
+
+impl ActionOutcome {
+    pub fn new(executed_at: u64, before: PressureSample) -> Self {
+        Self {
+            executed_at,
+            before,
+            after: None,
+            measured_at: None,
+            improved: None,
+        }
+    }
+
+    pub fn measure(&mut self, measured_at: u64, after: PressureSample) {
+        self.after = Some(after);
+        self.measured_at = Some(measured_at);
+        self.improved = Some(self.before.pressure() - after.pressure() >= IMPROVEMENT_MIN_PSI_DROP);
+    }
+}
+
+#[derive(Debug, Default)]
+struct Totals {
+    executed: u64,
+    measured: u64,
+    improved: u64,
+    psi_cpu_delta: f64,
+    psi_memory_delta: f64,
+    cpu_percent_delta: f64,
+    last_executed_at: u64,
+}
+
+/// Intervention effectiveness of one rule, as served by
+/// `/rules/effectiveness`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleEffectiveness {
+    pub rule: String,
+    pub executed: u64,
+    pub measured: u64,
+    pub improved: u64,
+    /// `improved / measured`; `None` until an outcome is measured.
+    pub effectiveness: Option<f64>,
+    /// Mean after-minus-before deltas; negative is relief.
+    pub mean_psi_cpu_delta: Option<f64>,
+    pub mean_psi_memory_delta: Option<f64>,
+    pub mean_cpu_percent_delta: Option<f64>,
+    pub last_executed_at: u64,
+}
+
+/// Per-rule outcome totals.
+#[derive(Debug, Default)]
+pub struct Effectiveness {
+    rules: HashMap<String, Totals>,
+}
+
+impl Effectiveness {
+    pub fn executed(&mut self, rule: &str, at: u64) {
+        let totals = self.rules.entry(rule.to_string()).or_default();
+        totals.executed += 1;
+        totals.last_executed_at = totals.last_executed_at.max(at);
+    }
+
+    pub fn measured(&mut self, rule: &str, outcome: &ActionOutcome) {
+        let Some(after) = outcome.after else {
+            return;
+        };
+        let totals = self.rules.entry(rule.to_string()).or_default();
+        totals.measured += 1;
+        if outcome.improved == Some(true) {
+            totals.improved += 1;
+        }
+        totals.psi_cpu_delta += f64::from(after.psi_cpu - outcome.before.psi_cpu);
+        totals.psi_memory_delta += f64::from(after.psi_memory - outcome.before.psi_memory);
+        totals.cpu_percent_delta += f64::from(after.cpu_percent - outcome.before.cpu_percent);
+    }
+
+    /// Statistics of every rule that executed an action, least effective
+    /// first.
+    pub fn report(&self) -> Vec<RuleEffectiveness> {
+        let mut report: Vec<RuleEffectiveness> = self
+            .rules
+            .iter()
+            .map(|(rule, t)| {
+                let mean = |sum: f64| (t.measured > 0).then(|| sum / t.measured as f64);
+                RuleEffectiveness {
+                    rule: rule.clone(),
+                    executed: t.executed,
+                    measured: t.measured,
+                    improved: t.improved,
+                    effectiveness: mean(t.improved as f64),
+                    mean_psi_cpu_delta: mean(t.psi_cpu_delta),
+                    mean_psi_memory_delta: mean(t.psi_memory_delta),
+                    mean_cpu_percent_delta: mean(t.cpu_percent_delta),
+                    last_executed_at: t.last_executed_at,
+                }
+            })
+            .collect();
+        report.sort_by(|a, b| {
+            let key = |r: &RuleEffectiveness| r.effectiveness.unwrap_or(f64::INFINITY);
+            key(a).total_cmp(&key(b)).then_with(|| a.rule.cmp(&b.rule))
+        });
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(psi_cpu: f32, psi_memory: f32, cpu_percent: f32) -> PressureSample {
+        PressureSample {
+            psi_cpu,
+            psi_memory,
+            cpu_percent,
+        }
+    }
+
+    #[test]
+    fn aggregates_deltas_and_ranks_least_effective_first() {
+        let mut stats = Effectiveness::default();
+        for (rule, before, after) in [
+            ("cpu_spin", sample(60.0, 5.0, 98.0), sample(10.0, 5.0, 40.0)),
+            ("cpu_spin", sample(50.0, 5.0, 95.0), sample(48.0, 4.0, 93.0)),
+            (
+                "fork_storm",
+                sample(45.0, 2.0, 90.0),
+                sample(44.0, 2.0, 91.0),
+            ),
+        ] {
+            stats.executed(rule, 100);
+            let mut outcome = ActionOutcome::new(100, before);
+            outcome.measure(130, after);
+            stats.measured(rule, &outcome);
+        }
+        stats.executed("oom_risk", 200);
+
+        let report = stats.report();
+        let rules: Vec<&str> = report.iter().map(|r| r.rule.as_str()).collect();
+        assert_eq!(rules, vec!["fork_storm", "cpu_spin", "oom_risk"]);
+        let cpu_spin = &report[1];
+        assert_eq!((cpu_spin.measured, cpu_spin.improved), (2, 1));
+        assert_eq!(cpu_spin.effectiveness, Some(0.5));
+        assert_eq!(cpu_spin.mean_psi_cpu_delta, Some(-26.0));
+        assert_eq!(report[2].effectiveness, None, "not measured yet");
+    }
+}
@@ -64,6 +64,18 @@ impl SafetyGuard {
         }
         Ok(())
     }
+
+    /// Check that an address may be blocked: a valid address that is not
+    /// loopback, unspecified or multicast.
+    pub fn is_safe_to_block(addr: &str) -> Result<(), String> {
+        let ip: std::net::IpAddr = addr
+            .parse()
+            .map_err(|_| format!("'{}' is not an IP address", addr))?;
+        if ip.is_loopback() || ip.is_unspecified() || ip.is_multicast() {
+            return Err(format!("address {} cannot be blocked", ip));
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -90,4 +102,12 @@ mod tests {
         let result = SafetyGuard::is_safe_to_kill(999999);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_cannot_block_loopback() {
+        assert!(SafetyGuard::is_safe_to_block("127.0.0.1").is_err());
+        assert!(SafetyGuard::is_safe_to_block("::1").is_err());
+        assert!(SafetyGuard::is_safe_to_block("not-an-ip").is_err());
+        assert!(SafetyGuard::is_safe_to_block("203.0.113.7").is_ok());
+    }
 }
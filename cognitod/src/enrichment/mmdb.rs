@@ -0,0 +1,495 @@
+//! Minimal reader for MaxMind DB (`.mmdb`) files such as GeoLite2-City,
+//! GeoLite2-Country and GeoLite2-ASN.
+//!
+//! Implements the lookup side of the MaxMind DB 2.0 format: the metadata
+//! map at the end of the file, the binary search tree with 24, 28 or 32 bit
+//! records, and the data section types that appear in those databases. The
+//! file is memory mapped and decoded only along the path of a lookup.
+
+use anyhow::{Context, Result, bail};
+use memmap2::Mmap;
+use std::fs::File;
+use std::net::IpAddr;
+use std::path::Path;
+
+const METADATA_MARKER: &[u8] = b"\xab\xcd\xefMaxMind.com";
+/// Zero bytes between the search tree and the data section.
+const DATA_SEPARATOR: usize = 16;
+/// Pointers and containers nested deeper than this are rejected.
+const MAX_NESTING: usize = 32;
+
+/// A decoded data section value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Double(f64),
+    Bytes(Vec<u8>),
+    Uint(u128),
+    Int(i32),
+    Map(Vec<(String, Value)>),
+    Array(Vec<Value>),
+    Bool(bool),
+    Float(f32),
+}
+
+impl Value {
+    /// The value at `path` through nested maps, e.g. `["city", "names", "en"]`.
+    pub fn get(&self, path: &[&str]) -> Option<&Value> {
+        let Some((key, rest)) = path.split_first() else {
+            return Some(self);
+        };
+        match self {
+            Value::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| k == key)
+                .and_then(|(_, v)| v.get(rest)),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Uint(n) => u64::try_from(*n).ok(),
+            Value::Int(n) => u64::try_from(*n).ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Bytes of a database, mapped or (in tests) owned.
+pub enum Source {
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+}
+
+impl AsRef<[u8]> for Source {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            Source::Mapped(map) => map,
+            Source::Owned(bytes) => bytes,
+        }
+    }
+}
+
+pub struct Reader {
+    source: Source,
+    node_count: usize,
+    record_size: usize,
+    ip_version: u64,
+    /// Start of the data section
+    data: usize,
+    /// Node reached after the 96 zero bits of an IPv4-mapped address
+    ipv4_start: usize,
+    pub database_type: String,
+}
+
+impl Reader {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("open {}", path.display()))?;
+        // SAFETY: the database is read-only; a file replaced in place is
+        // only picked up on restart.
+        let map = unsafe { Mmap::map(&file) }.with_context(|| format!("map {}", path.display()))?;
+        Self::from_source(Source::Mapped(map))
+            .with_context(|| format!("read MaxMind DB {}", path.display()))
+    }
+
+    pub fn from_source(source: Source) -> Result<Self> {
+        let bytes = source.as_ref();
+        let Some(marker) = bytes
+            .windows(METADATA_MARKER.len())
+            .rposition(|w| w == METADATA_MARKER)
+        else {
+            bail!("metadata marker not found");
+        };
+        let (metadata, _) = Decoder {
+            bytes,
+            base: marker + METADATA_MARKER.len(),
+        }
+        .decode(marker + METADATA_MARKER.len(), 0)?;
+        let field = |name: &str| {
+            metadata
+                .get(&[name])
+                .and_then(Value::as_u64)
+                .with_context(|| format!("metadata has no {name}"))
+        };
+        let node_count = field("node_count")? as usize;
+        let record_size = field("record_size")? as usize;
+        let ip_version = field("ip_version")?;
+        if !matches!(record_size, 24 | 28 | 32) {
+            bail!("unsupported record size {record_size}");
+        }
+        let data = node_count * record_size / 4 + DATA_SEPARATOR;
+        if data > marker {
+            bail!("search tree runs past the metadata");
+        }
+        let database_type = metadata
+            .get(&["database_type"])
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let mut reader = Self {
+            source,
+            node_count,
+            record_size,
+            ip_version,
+            data,
+            ipv4_start: 0,
+            database_type,
+        };
+        if ip_version == 6 {
+            let mut node = 0;
+            for _ in 0..96 {
+                if node >= node_count {
+                    break;
+                }
+                node = reader.record(node, 0);
+            }
+            reader.ipv4_start = node;
+        }
+        Ok(reader)
+    }
+
+    fn bytes(&self) -> &[u8] {
+        self.source.as_ref()
+    }
+
+    /// Left (`bit` 0) or right record of `node`.
+    fn record(&self, node: usize, bit: u8) -> usize {
+        let bytes = self.bytes();
+        let size = self.record_size / 4;
+        let at = node * size;
+        let b = |i: usize| bytes[at + i] as usize;
+        match (self.record_size, bit) {
+            (24, 0) => b(0) << 16 | b(1) << 8 | b(2),
+            (24, _) => b(3) << 16 | b(4) << 8 | b(5),
+            (28, 0) => (b(3) & 0xf0) << 20 | b(0) << 16 | b(1) << 8 | b(2),
+            (28, _) => (b(3) & 0x0f) << 24 | b(4) << 16 | b(5) << 8 | b(6),
+            (_, 0) => b(0) << 24 | b(1) << 16 | b(2) << 8 | b(3),
+            _ => b(4) << 24 | b(5) << 16 | b(6) << 8 | b(7),
+        }
+    }
+
+    /// The record of the most specific network containing `ip`.
+    pub fn lookup(&self, ip: IpAddr) -> Result<Option<Value>> {
+        let (bits, mut node): (Vec<u8>, usize) = match (ip, self.ip_version) {
+            (IpAddr::V4(v4), 6) => (v4.octets().to_vec(), self.ipv4_start),
+            (IpAddr::V4(v4), _) => (v4.octets().to_vec(), 0),
+            (IpAddr::V6(v6), 6) => (v6.octets().to_vec(), 0),
+            (IpAddr::V6(v6), _) => match v6.to_ipv4_mapped() {
+                Some(v4) => (v4.octets().to_vec(), 0),
+                None => return Ok(None),
+            },
+        };
+        for i in 0..bits.len() * 8 {
+            if node >= self.node_count {
+                break;
+            }
+            let bit = (bits[i / 8] >> (7 - i % 8)) & 1;
+            node = self.record(node, bit);
+        }
+        if node <= self.node_count {
+            return Ok(None);
+        }
+        let Some(offset) = (node - self.node_count).checked_sub(DATA_SEPARATOR) else {
+            bail!("record {node} points into the data separator");
+        };
+        let decoder = Decoder {
+            bytes: self.bytes(),
+            base: self.data,
+        };
+        let (value, _) = decoder.decode(self.data + offset, 0)?;
+        Ok(Some(value))
+    }
+}
+
+/// Decodes values of a data section starting at `base`.
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    base: usize,
+}
+
+impl Decoder<'_> {
+    fn take(&self, at: usize, len: usize) -> Result<&[u8]> {
+        self.bytes
+            .get(at..at + len)
+            .context("value runs past the end of the database")
+    }
+
+    fn uint(&self, at: usize, len: usize) -> Result<u128> {
+        if len > 16 {
+            bail!("integer of {len} bytes");
+        }
+        Ok(self
+            .take(at, len)?
+            .iter()
+            .fold(0u128, |n, b| n << 8 | *b as u128))
+    }
+
+    /// Value at `at` and the offset just past it.
+    fn decode(&self, at: usize, depth: usize) -> Result<(Value, usize)> {
+        if depth > MAX_NESTING {
+            bail!("values nested too deeply");
+        }
+        let ctrl = *self.take(at, 1)?.first().unwrap_or(&0);
+        let mut pos = at + 1;
+        let mut kind = ctrl >> 5;
+        if kind == 1 {
+            let ss = (ctrl >> 3) & 0x3;
+            let vvv = (ctrl & 0x7) as usize;
+            let len = ss as usize + 1;
+            let raw = self.uint(pos, len)? as usize;
+            let target = match ss {
+                0 => vvv << 8 | raw,
+                1 => (vvv << 16 | raw) + 2048,
+                2 => (vvv << 24 | raw) + 526_336,
+                _ => raw,
+            };
+            let (value, _) = self.decode(self.base + target, depth + 1)?;
+            return Ok((value, pos + len));
+        }
+        if kind == 0 {
+            kind = 7 + *self.take(pos, 1)?.first().unwrap_or(&0);
+            pos += 1;
+        }
+        let mut size = (ctrl & 0x1f) as usize;
+        if size >= 29 {
+            let extra = size - 28;
+            let n = self.uint(pos, extra)? as usize;
+            pos += extra;
+            size = match extra {
+                1 => 29 + n,
+                2 => 285 + n,
+                _ => 65_821 + n,
+            };
+        }
+        let value = match kind {
+            2 => Value::String(String::from_utf8_lossy(self.take(pos, size)?).into_owned()),
+            3 => {
+                let raw: [u8; 8] = self.take(pos, 8)?.try_into()?;
+                size = 8;
+                Value::Double(f64::from_be_bytes(raw))
+            }
+            4 => Value::Bytes(self.take(pos, size)?.to_vec()),
+            5 | 6 | 9 | 10 => Value::Uint(self.uint(pos, size)?),
+            8 => Value::Int(self.uint(pos, size)? as u32 as i32),
+            7 => {
+                let mut entries = Vec::with_capacity(size.min(64));
+                for _ in 0..size {
+                    let (key, next) = self.decode(pos, depth + 1)?;
+                    let (value, next) = self.decode(next, depth + 1)?;
+                    pos = next;
+                    let Value::String(key) = key else {
+                        bail!("map key is not a string");
+                    };
+                    entries.push((key, value));
+                }
+                return Ok((Value::Map(entries), pos));
+            }
+            11 => {
+                let mut items = Vec::with_capacity(size.min(64));
+                for _ in 0..size {
+                    let (value, next) = self.decode(pos, depth + 1)?;
+                    pos = next;
+                    items.push(value);
+                }
+                return Ok((Value::Array(items), pos));
+            }
+            14 => {
+                let value = Value::Bool(size != 0);
+                size = 0;
+                value
+            }
+            15 => {
+                let raw: [u8; 4] = self.take(pos, 4)?.try_into()?;
+                size = 4;
+                Value::Float(f32::from_be_bytes(raw))
+            }
+            _ => bail!("unsupported data type {kind}"),
+        };
+        Ok((value, pos + size))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn ctrl(kind: u8, size: usize, out: &mut Vec<u8>) {
+        assert!(size < 29);
+        if kind > 7 {
+            out.push(size as u8);
+            out.push(kind - 7);
+        } else {
+            out.push(kind << 5 | size as u8);
+        }
+    }
+
+    pub(crate) fn string(s: &str, out: &mut Vec<u8>) {
+        ctrl(2, s.len(), out);
+        out.extend_from_slice(s.as_bytes());
+    }
+
+    pub(crate) fn uint(kind: u8, n: u32, out: &mut Vec<u8>) {
+        let bytes: Vec<u8> = n
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|b| *b == 0)
+            .collect();
+        ctrl(kind, bytes.len(), out);
+        out.extend(bytes);
+    }
+
+    pub(crate) fn map(len: usize, out: &mut Vec<u8>) {
+        ctrl(7, len, out);
+    }
+
+    /// A 24-bit IPv4 database holding `networks` (address, prefix length,
+    /// encoded record). Unlisted addresses are not found.
+    pub(crate) fn database(networks: &[([u8; 4], u8, Vec<u8>)]) -> Vec<u8> {
+        // Tree as (left, right) children: Some(node) or a data offset
+        #[derive(Clone, Copy)]
+        enum Rec {
+            Empty,
+            Node(usize),
+            Data(usize),
+        }
+        let mut nodes = vec![[Rec::Empty, Rec::Empty]];
+        let mut data = Vec::new();
+        for (addr, prefix, record) in networks {
+            let offset = data.len();
+            data.extend_from_slice(record);
+            let mut node = 0;
+            for i in 0..*prefix as usize {
+                let bit = ((addr[i / 8] >> (7 - i % 8)) & 1) as usize;
+                if i + 1 == *prefix as usize {
+                    nodes[node][bit] = Rec::Data(offset);
+                    break;
+                }
+                node = match nodes[node][bit] {
+                    Rec::Node(next) => next,
+                    _ => {
+                        nodes.push([Rec::Empty, Rec::Empty]);
+                        let next = nodes.len() - 1;
+                        nodes[node][bit] = Rec::Node(next);
+                        next
+                    }
+                };
+            }
+        }
+        let count = nodes.len();
+        let mut out = Vec::new();
+        for node in &nodes {
+            for rec in node {
+                let n = match *rec {
+                    Rec::Empty => count,
+                    Rec::Node(next) => next,
+                    Rec::Data(offset) => count + DATA_SEPARATOR + offset,
+                };
+                out.extend_from_slice(&(n as u32).to_be_bytes()[1..]);
+            }
+        }
+        out.extend_from_slice(&[0; DATA_SEPARATOR]);
+        out.extend(data);
+        out.extend_from_slice(METADATA_MARKER);
+        map(4, &mut out);
+        string("node_count", &mut out);
+        uint(6, count as u32, &mut out);
+        string("record_size", &mut out);
+        uint(5, 24, &mut out);
+        string("ip_version", &mut out);
+        uint(5, 4, &mut out);
+        string("database_type", &mut out);
+        string("Test-City", &mut out);
+        out
+    }
+
+    #[test]
+    fn looks_up_the_most_specific_network() {
+        let mut city = Vec::new();
+        map(2, &mut city);
+        string("country", &mut city);
+        map(1, &mut city);
+        string("iso_code", &mut city);
+        string("NL", &mut city);
+        string("city", &mut city);
+        map(1, &mut city);
+        string("names", &mut city);
+        map(1, &mut city);
+        string("en", &mut city);
+        string("Amsterdam", &mut city);
+
+        // The country is a pointer to the first record
+        let mut asn = Vec::new();
+        map(2, &mut asn);
+        string("autonomous_system_number", &mut asn);
+        uint(6, 64_496, &mut asn);
+        string("country", &mut asn);
+        asn.push(1 << 5);
+        asn.push(0);
+
+        let db = database(&[([198, 51, 100, 0], 24, city), ([203, 0, 113, 0], 24, asn)]);
+        let reader = Reader::from_source(Source::Owned(db)).unwrap();
+        assert_eq!(reader.database_type, "Test-City");
+
+        let value = reader
+            .lookup("198.51.100.7".parse().unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            value.get(&["country", "iso_code"]).and_then(Value::as_str),
+            Some("NL")
+        );
+        assert_eq!(
+            value.get(&["city", "names", "en"]).and_then(Value::as_str),
+            Some("Amsterdam")
+        );
+
+        let value = reader
+            .lookup("203.0.113.9".parse().unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            value
+                .get(&["autonomous_system_number"])
+                .and_then(Value::as_u64),
+            Some(64_496)
+        );
+        assert_eq!(
+            value
+                .get(&["country", "country", "iso_code"])
+                .and_then(Value::as_str),
+            Some("NL")
+        );
+
+        assert!(
+            reader
+                .lookup("192.0.2.1".parse().unwrap())
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            reader
+                .lookup("::ffff:198.51.100.7".parse().unwrap())
+                .unwrap()
+                .is_some()
+        );
+        assert!(
+            reader
+                .lookup("2001:db8::1".parse().unwrap())
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn rejects_files_without_metadata() {
+        assert!(Reader::from_source(Source::Owned(vec![0; 64])).is_err());
+    }
+}
@@ -0,0 +1,532 @@
+//! Reverse DNS and GeoIP names for remote peers
+//!
+//! [`PeerEnricher::get`] only reads the cache. A miss queues the address
+//! for the worker and returns `None`, so callers never wait on a resolver.
+//! The worker resolves at most `concurrency` addresses at a time. For each
+//! it looks up the PTR name through the system resolver and, when
+//! configured, the country, city and AS number in local MaxMind databases.
+//! Results are cached for `ttl_secs`, or `negative_ttl_secs` when nothing
+//! was found. An expired entry is still served while it is refreshed. The
+//! queue is bounded: addresses that do not fit are dropped and asked for
+//! again on the next miss. Remote endpoints of `/topology` are annotated
+//! from the cache. Established inbound connections are queued as they are
+//! seen, so their names are usually ready when the graph is read. SYNs are
+//! not, so a flood of spoofed sources cannot drive PTR queries.
+//!
+//! PTR names are chosen by whoever controls the address's reverse zone and
+//! are not forward-confirmed; treat them as hints.
+
+use async_trait::async_trait;
+use linnix_ai_ebpf_common::ConnOp;
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::ffi::CStr;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{Semaphore, mpsc};
+
+use crate::ProcessEvent;
+use crate::config::EnrichmentConfig;
+use crate::handler::Handler;
+use crate::handler::ddos;
+use crate::topology::{EndpointKind, TopologyReport};
+use crate::types::SystemSnapshot;
+
+pub mod mmdb;
+
+/// Addresses waiting for the worker.
+const QUEUE: usize = 1024;
+
+/// What is known about one remote address.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub struct PeerInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// ISO 3166-1 alpha-2
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asn: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub as_org: Option<String>,
+}
+
+impl PeerInfo {
+    fn is_empty(&self) -> bool {
+        *self == PeerInfo::default()
+    }
+
+    /// Fields found in a GeoIP City/Country or ASN record.
+    fn merge_geoip(&mut self, record: &mmdb::Value) {
+        let text = |path: &[&str]| record.get(path).and_then(|v| v.as_str()).map(String::from);
+        if let Some(country) = text(&["country", "iso_code"]) {
+            self.country = Some(country);
+        }
+        if let Some(city) = text(&["city", "names", "en"]) {
+            self.city = Some(city);
+        }
+        if let Some(asn) = record
+            .get(&["autonomous_system_number"])
+            .and_then(|v| v.as_u64())
+        {
+            self.asn = u32::try_from(asn).ok();
+        }
+        if let Some(org) = text(&["autonomous_system_organization"]) {
+            self.as_org = Some(org);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CachedPeer {
+    pub address: IpAddr,
+    #[serde(flatten)]
+    pub info: PeerInfo,
+    /// Seconds until the entry is refreshed; negative once stale
+    pub expires_in_secs: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EnrichmentStats {
+    pub cached: usize,
+    pub pending: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub resolved: u64,
+    /// Addresses not queued because the queue was full
+    pub dropped: u64,
+}
+
+struct Entry {
+    info: PeerInfo,
+    expires: Instant,
+}
+
+/// Resolves one address; reverse DNS and the databases are swappable for
+/// tests.
+type Resolver = dyn Fn(IpAddr) -> PeerInfo + Send + Sync;
+
+pub struct PeerEnricher {
+    cache: Mutex<HashMap<IpAddr, Entry>>,
+    pending: Mutex<HashSet<IpAddr>>,
+    tx: mpsc::Sender<IpAddr>,
+    ttl: Duration,
+    negative_ttl: Duration,
+    max_entries: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    resolved: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl PeerEnricher {
+    /// Start the worker. Reverse DNS is skipped when `offline`.
+    pub fn spawn(config: &EnrichmentConfig, offline: bool) -> Arc<Self> {
+        let open = |path: &Option<String>| {
+            let path = path.as_deref().filter(|p| !p.is_empty())?;
+            match mmdb::Reader::open(std::path::Path::new(path)) {
+                Ok(reader) => {
+                    info!("[enrichment] loaded {} from {path}", reader.database_type);
+                    Some(reader)
+                }
+                Err(e) => {
+                    warn!("[enrichment] GeoIP lookups disabled for {path}: {e:#}");
+                    None
+                }
+            }
+        };
+        let databases: Vec<mmdb::Reader> = [open(&config.geoip_db), open(&config.asn_db)]
+            .into_iter()
+            .flatten()
+            .collect();
+        let reverse_dns = config.reverse_dns && !offline;
+        if config.reverse_dns && offline {
+            info!("[enrichment] reverse DNS disabled in offline mode");
+        }
+        let resolver: Arc<Resolver> = Arc::new(move |ip| {
+            let mut info = PeerInfo {
+                hostname: reverse_dns.then(|| reverse_lookup(ip)).flatten(),
+                ..PeerInfo::default()
+            };
+            for db in &databases {
+                match db.lookup(ip) {
+                    Ok(Some(record)) => info.merge_geoip(&record),
+                    Ok(None) => {}
+                    Err(e) => warn!("[enrichment] {} lookup of {ip}: {e:#}", db.database_type),
+                }
+            }
+            info
+        });
+        Self::start(config, resolver)
+    }
+
+    fn start(config: &EnrichmentConfig, resolver: Arc<Resolver>) -> Arc<Self> {
+        let (tx, mut rx) = mpsc::channel(QUEUE);
+        let enricher = Arc::new(Self {
+            cache: Mutex::new(HashMap::new()),
+            pending: Mutex::new(HashSet::new()),
+            tx,
+            ttl: Duration::from_secs(config.ttl_secs),
+            negative_ttl: Duration::from_secs(config.negative_ttl_secs),
+            max_entries: config.max_entries.max(1),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            resolved: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        });
+        let permits = Arc::new(Semaphore::new(config.concurrency.max(1)));
+        let shared = Arc::clone(&enricher);
+        tokio::spawn(async move {
+            while let Some(ip) = rx.recv().await {
+                let Ok(permit) = Arc::clone(&permits).acquire_owned().await else {
+                    break;
+                };
+                let resolver = Arc::clone(&resolver);
+                let shared = Arc::clone(&shared);
+                tokio::spawn(async move {
+                    let info = tokio::task::spawn_blocking(move || resolver(ip))
+                        .await
+                        .unwrap_or_default();
+                    shared.store(ip, info, Instant::now());
+                    drop(permit);
+                });
+            }
+        });
+        enricher
+    }
+
+    /// Cached names of `ip`, queueing a lookup when missing or stale.
+    pub fn get(&self, ip: IpAddr) -> Option<PeerInfo> {
+        let ip = ip.to_canonical();
+        if !worth_resolving(ip) {
+            return None;
+        }
+        let cached = self
+            .cache
+            .lock()
+            .unwrap()
+            .get(&ip)
+            .map(|entry| (entry.info.clone(), entry.expires <= Instant::now()));
+        match cached {
+            Some((info, stale)) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                if stale {
+                    self.request(ip);
+                }
+                (!info.is_empty()).then_some(info)
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                self.request(ip);
+                None
+            }
+        }
+    }
+
+    /// Queue `ip` unless it is already queued. Never waits.
+    pub fn request(&self, ip: IpAddr) {
+        let ip = ip.to_canonical();
+        if !worth_resolving(ip) || !self.pending.lock().unwrap().insert(ip) {
+            return;
+        }
+        if self.tx.try_send(ip).is_err() {
+            self.pending.lock().unwrap().remove(&ip);
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn store(&self, ip: IpAddr, info: PeerInfo, now: Instant) {
+        self.pending.lock().unwrap().remove(&ip);
+        self.resolved.fetch_add(1, Ordering::Relaxed);
+        let ttl = if info.is_empty() {
+            self.negative_ttl
+        } else {
+            self.ttl
+        };
+        let mut cache = self.cache.lock().unwrap();
+        if !cache.contains_key(&ip) && cache.len() >= self.max_entries {
+            cache.retain(|_, entry| entry.expires > now);
+            if cache.len() >= self.max_entries
+                && let Some(oldest) = cache
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.expires)
+                    .map(|(ip, _)| *ip)
+            {
+                cache.remove(&oldest);
+            }
+        }
+        cache.insert(
+            ip,
+            Entry {
+                info,
+                expires: now + ttl,
+            },
+        );
+    }
+
+    /// Names of the remote endpoints of `report`, from the cache.
+    pub fn annotate(&self, report: &mut TopologyReport) {
+        for node in report
+            .nodes
+            .iter_mut()
+            .filter(|node| node.kind == EndpointKind::Remote)
+        {
+            if let Some(ip) = parse_address(&node.label) {
+                node.peer = self.get(ip);
+            }
+        }
+    }
+
+    /// Cached entries, most recently resolved first.
+    pub fn cached(&self) -> Vec<CachedPeer> {
+        let now = Instant::now();
+        let mut peers: Vec<(Instant, CachedPeer)> = self
+            .cache
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(ip, entry)| {
+                let expires_in_secs = if entry.expires >= now {
+                    entry.expires.duration_since(now).as_secs() as i64
+                } else {
+                    -(now.duration_since(entry.expires).as_secs() as i64)
+                };
+                (
+                    entry.expires,
+                    CachedPeer {
+                        address: *ip,
+                        info: entry.info.clone(),
+                        expires_in_secs,
+                    },
+                )
+            })
+            .collect();
+        peers.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.address.cmp(&b.1.address)));
+        peers.into_iter().map(|(_, peer)| peer).collect()
+    }
+
+    pub fn stats(&self) -> EnrichmentStats {
+        EnrichmentStats {
+            cached: self.cache.lock().unwrap().len(),
+            pending: self.pending.lock().unwrap().len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            resolved: self.resolved.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn handler(self: &Arc<Self>) -> ConnectionPrefetch {
+        ConnectionPrefetch {
+            enricher: Arc::clone(self),
+        }
+    }
+}
+
+/// Queues the remote address of every established inbound connection.
+pub struct ConnectionPrefetch {
+    enricher: Arc<PeerEnricher>,
+}
+
+#[async_trait]
+impl Handler for ConnectionPrefetch {
+    fn name(&self) -> &'static str {
+        "enrichment"
+    }
+
+    async fn on_event(&self, event: &ProcessEvent) {
+        if let Some((ConnOp::Established, ip)) = ddos::decode(event) {
+            self.enricher.request(ip);
+        }
+    }
+
+    async fn on_snapshot(&self, _snapshot: &SystemSnapshot) {}
+}
+
+/// Loopback, unspecified and link-local addresses have nothing to look up.
+fn worth_resolving(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => !(v4.is_loopback() || v4.is_unspecified() || v4.is_link_local()),
+        IpAddr::V6(v6) => !(v6.is_loopback() || v6.is_unspecified() || v6.is_unicast_link_local()),
+    }
+}
+
+/// Address of a remote endpoint label, `ip` or `ip:port`.
+fn parse_address(label: &str) -> Option<IpAddr> {
+    label
+        .parse::<IpAddr>()
+        .ok()
+        .or_else(|| label.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+/// PTR name of `ip` through the system resolver. Blocks.
+fn reverse_lookup(ip: IpAddr) -> Option<String> {
+    const HOST_LEN: usize = 1025;
+    let mut host = [0 as libc::c_char; HOST_LEN];
+    let rc = match ip {
+        IpAddr::V4(v4) => {
+            // SAFETY: zeroed is a valid sockaddr_in; getnameinfo reads `len`
+            // bytes of it and writes at most HOST_LEN bytes to `host`.
+            let mut addr: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+            addr.sin_family = libc::AF_INET as libc::sa_family_t;
+            addr.sin_addr.s_addr = u32::from_ne_bytes(v4.octets());
+            unsafe {
+                libc::getnameinfo(
+                    &addr as *const _ as *const libc::sockaddr,
+                    size_of::<libc::sockaddr_in>() as libc::socklen_t,
+                    host.as_mut_ptr(),
+                    HOST_LEN as libc::socklen_t,
+                    std::ptr::null_mut(),
+                    0,
+                    libc::NI_NAMEREQD,
+                )
+            }
+        }
+        IpAddr::V6(v6) => {
+            // SAFETY: as above, for sockaddr_in6.
+            let mut addr: libc::sockaddr_in6 = unsafe { std::mem::zeroed() };
+            addr.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            addr.sin6_addr.s6_addr = v6.octets();
+            unsafe {
+                libc::getnameinfo(
+                    &addr as *const _ as *const libc::sockaddr,
+                    size_of::<libc::sockaddr_in6>() as libc::socklen_t,
+                    host.as_mut_ptr(),
+                    HOST_LEN as libc::socklen_t,
+                    std::ptr::null_mut(),
+                    0,
+                    libc::NI_NAMEREQD,
+                )
+            }
+        }
+    };
+    if rc != 0 {
+        return None;
+    }
+    // SAFETY: getnameinfo NUL-terminates `host` on success.
+    let name = unsafe { CStr::from_ptr(host.as_ptr()) };
+    name.to_str()
+        .ok()
+        .map(|name| name.trim_end_matches('.').to_string())
+        .filter(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::Endpoint;
+    use std::sync::atomic::AtomicUsize;
+
+    fn config() -> EnrichmentConfig {
+        EnrichmentConfig {
+            concurrency: 2,
+            max_entries: 2,
+            ..EnrichmentConfig::default()
+        }
+    }
+
+    async fn settle(enricher: &PeerEnricher) {
+        for _ in 0..100 {
+            if enricher.stats().pending == 0 {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        panic!("lookups did not finish");
+    }
+
+    #[tokio::test]
+    async fn misses_queue_a_lookup_and_later_reads_hit_the_cache() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&calls);
+        let enricher = PeerEnricher::start(
+            &config(),
+            Arc::new(move |ip: IpAddr| {
+                counted.fetch_add(1, Ordering::Relaxed);
+                match ip.to_string().as_str() {
+                    "203.0.113.9" => PeerInfo {
+                        hostname: Some("edge.example.net".into()),
+                        asn: Some(64_496),
+                        ..PeerInfo::default()
+                    },
+                    _ => PeerInfo::default(),
+                }
+            }),
+        );
+        let peer: IpAddr = "203.0.113.9".parse().unwrap();
+        assert_eq!(enricher.get(peer), None);
+        // Already queued: not looked up twice
+        enricher.request(peer);
+        settle(&enricher).await;
+        let info = enricher.get(peer).unwrap();
+        assert_eq!(info.hostname.as_deref(), Some("edge.example.net"));
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        // IPv4-mapped addresses share the IPv4 entry; loopback is skipped
+        assert!(
+            enricher
+                .get("::ffff:203.0.113.9".parse().unwrap())
+                .is_some()
+        );
+        assert_eq!(enricher.get("127.0.0.1".parse().unwrap()), None);
+
+        // Nothing found is cached too, and evicts the oldest when full
+        enricher.get("198.51.100.1".parse().unwrap());
+        settle(&enricher).await;
+        enricher.get("198.51.100.2".parse().unwrap());
+        settle(&enricher).await;
+        let stats = enricher.stats();
+        assert_eq!(stats.cached, 2);
+        assert_eq!(stats.resolved, 3);
+        assert_eq!(stats.hits, 2);
+        assert_eq!(stats.misses, 3);
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+        // The negative entry expires first and was evicted
+        let mut cached: Vec<String> = enricher
+            .cached()
+            .iter()
+            .map(|p| p.address.to_string())
+            .collect();
+        cached.sort();
+        assert_eq!(cached, ["198.51.100.2", "203.0.113.9"]);
+
+        let mut report = TopologyReport {
+            interval_secs: 15,
+            retention_secs: 3600,
+            nodes: vec![
+                Endpoint::remote("203.0.113.9:443".into()),
+                Endpoint::process("curl"),
+            ],
+            edges: Vec::new(),
+        };
+        enricher.annotate(&mut report);
+        assert_eq!(report.nodes[0].peer.as_ref().unwrap().asn, Some(64_496));
+        assert!(report.nodes[1].peer.is_none());
+    }
+
+    #[test]
+    fn geoip_records_fill_country_city_and_asn() {
+        let mut info = PeerInfo::default();
+        let record = mmdb::Value::Map(vec![
+            (
+                "country".into(),
+                mmdb::Value::Map(vec![("iso_code".into(), mmdb::Value::String("NL".into()))]),
+            ),
+            ("autonomous_system_number".into(), mmdb::Value::Uint(64_496)),
+            (
+                "autonomous_system_organization".into(),
+                mmdb::Value::String("Example Transit".into()),
+            ),
+        ]);
+        info.merge_geoip(&record);
+        assert_eq!(info.country.as_deref(), Some("NL"));
+        assert_eq!(info.city, None);
+        assert_eq!(info.asn, Some(64_496));
+        assert_eq!(info.as_org.as_deref(), Some("Example Transit"));
+    }
+}
@@ -0,0 +1,448 @@
+//! Machine-readable description of the `/events` stream
+//!
+//! `EventRecord` is the JSON object sent for every SSE event. It is declared
+//! through `event_record!`, which emits the struct and its field descriptors
+//! from the same tokens, so the published schema cannot drift from what is
+//! serialized. Payload fields (`data`, `data2`, `aux`, `aux2`) are reused per
+//! event type; their meaning is described in `EVENT_TYPES`.
+
+use linnix_ai_ebpf_common::{EventType, WIRE_SCHEMA_VERSION};
+use serde::Serialize;
+
+use crate::ProcessEvent;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldDescriptor {
+    pub name: &'static str,
+    /// JSON type: `integer`, `number` or `string`.
+    #[serde(rename = "type")]
+    pub ty: &'static str,
+    pub rust_type: &'static str,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    pub unit: &'static str,
+    /// Omitted from the JSON object when it has no value.
+    pub optional: bool,
+    pub description: &'static str,
+}
+
+macro_rules! event_record {
+    (
+        $(#[$meta:meta])*
+        pub struct $name:ident {
+            $(
+                $(#[$fmeta:meta])*
+                $field:ident: $ty:ty => ($unit:literal, $desc:literal),
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        pub struct $name {
+            $(
+                $(#[$fmeta])*
+                pub $field: $ty,
+            )*
+        }
+
+        impl $name {
+            pub fn fields() -> Vec<FieldDescriptor> {
+                vec![$(
+                    FieldDescriptor {
+                        name: stringify!($field),
+                        ty: json_type(stringify!($ty)),
+                        rust_type: stringify!($ty),
+                        unit: $unit,
+                        optional: stringify!($ty).starts_with("Option"),
+                        description: $desc,
+                    },
+                )*]
+            }
+        }
+    };
+}
+
+fn json_type(rust_type: &str) -> &'static str {
+    if rust_type.contains("f32") || rust_type.contains("f64") {
+        "number"
+    } else if rust_type.contains("String") {
+        "string"
+    } else {
+        "integer"
+    }
+}
+
+event_record! {
+    #[derive(Debug, Clone, Serialize)]
+    pub struct EventRecord {
+        pid: u32 => ("", "Process id (tgid)."),
+        ppid: u32 => ("", "Parent process id."),
+        uid: u32 => ("", "Real user id."),
+        gid: u32 => ("", "Real group id."),
+        cgroup_id: u64 => ("", "cgroup v2 id of the task when the event was taken, the inode number of its cgroup directory; 0 for node-wide events."),
+        comm: String => ("", "Task command name, at most 15 bytes."),
+        #[serde(skip_serializing_if = "Option::is_none")]
+        script_path: Option<String> => ("", "Script run by an interpreter, resolved from the command line at exec."),
+        #[serde(skip_serializing_if = "Option::is_none")]
+        exe_path: Option<String> => ("", "Executable path at exec, of which `comm` holds the first 15 bytes; inherited on fork."),
+        event_type: u32 => ("", "Numeric event type; see `event_types`."),
+        event_type_name: String => ("", "Name of `event_type`."),
+        ts_ns: u64 => ("ns", "Kernel monotonic timestamp of the event."),
+        seq: u64 => ("", "Per-CPU perf sequence number; 0 when not stamped."),
+        exit_time_ns: u64 => ("ns", "Monotonic exit timestamp; 0 until the process exits."),
+        cpu_pct_milli: u16 => ("milli-percent", "CPU usage x1000; 65535 when unknown."),
+        mem_pct_milli: u16 => ("milli-percent", "Memory usage x1000; 65535 when unknown."),
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cpu_percent: Option<f32> => ("percent", "CPU usage."),
+        #[serde(skip_serializing_if = "Option::is_none")]
+        mem_percent: Option<f32> => ("percent", "Memory usage as a share of RAM."),
+        data: u64 => ("", "Primary payload; see `event_types`."),
+        data2: u64 => ("", "Secondary payload; see `event_types`."),
+        aux: u32 => ("", "Auxiliary op code or flags; see `event_types`."),
+        aux2: u32 => ("", "Extended auxiliary field; see `event_types`."),
+    }
+}
+
+/// SSE name for an event type id.
+pub fn event_type_name(event_type: u32) -> &'static str {
+    EVENT_TYPES
+        .iter()
+        .find(|t| t.id == event_type)
+        .map(|t| t.name)
+        .unwrap_or("unknown")
+}
+
+impl From<&ProcessEvent> for EventRecord {
+    fn from(event: &ProcessEvent) -> Self {
+        Self {
+            pid: event.pid,
+            ppid: event.ppid,
+            uid: event.uid,
+            gid: event.gid,
+            cgroup_id: event.cgroup_id,
+            comm: String::from_utf8_lossy(&event.comm)
+                .trim_end_matches('\0')
+                .to_string(),
+            script_path: event.script_path.clone(),
+            exe_path: event.exe_path.clone(),
+            event_type: event.event_type,
+            event_type_name: event_type_name(event.event_type).to_string(),
+            ts_ns: event.ts_ns,
+            seq: event.seq,
+            exit_time_ns: event.exit_time_ns,
+            cpu_pct_milli: event.cpu_pct_milli,
+            mem_pct_milli: event.mem_pct_milli,
+            cpu_percent: event.cpu_percent(),
+            mem_percent: event.mem_percent(),
+            data: event.data,
+            data2: event.data2,
+            aux: event.aux,
+            aux2: event.aux2,
+        }
+    }
+}
+
+impl From<EventRecord> for linnix_events::ProcessEvent {
+    fn from(record: EventRecord) -> Self {
+        Self {
+            pid: record.pid,
+            ppid: record.ppid,
+            uid: record.uid,
+            gid: record.gid,
+            cgroup_id: record.cgroup_id,
+            comm: record.comm,
+            script_path: record.script_path,
+            exe_path: record.exe_path,
+            event_type: record.event_type,
+            event_type_name: record.event_type_name,
+            ts_ns: record.ts_ns,
+            seq: record.seq,
+            exit_time_ns: record.exit_time_ns,
+            cpu_percent: record.cpu_percent,
+            mem_percent: record.mem_percent,
+            data: record.data,
+            data2: record.data2,
+            aux: record.aux,
+            aux2: record.aux2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PayloadField {
+    pub field: &'static str,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    pub unit: &'static str,
+    pub meaning: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EventTypeDescriptor {
+    pub id: u32,
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Payload fields carrying data for this type; the rest are 0.
+    pub payload: &'static [PayloadField],
+}
+
+const fn payload(field: &'static str, unit: &'static str, meaning: &'static str) -> PayloadField {
+    PayloadField {
+        field,
+        unit,
+        meaning,
+    }
+}
+
+pub const EVENT_TYPES: &[EventTypeDescriptor] = &[
+    EventTypeDescriptor {
+        id: EventType::Exec as u32,
+        name: "exec",
+        description: "Process replaced its image (execve).",
+        payload: &[],
+    },
+    EventTypeDescriptor {
+        id: EventType::Fork as u32,
+        name: "fork",
+        description: "Process created; `pid` is the child, `ppid` the parent.",
+        payload: &[],
+    },
+    EventTypeDescriptor {
+        id: EventType::Exit as u32,
+        name: "exit",
+        description: "Process exited; `exit_time_ns` is set.",
+        payload: &[
+            payload("data", "ns", "Exit timestamp (sequencer path only)."),
+            payload(
+                "aux",
+                "",
+                "Raw wait status: exit code in bits 8-15, terminating signal in bits 0-6, \
+                 core dump in bit 7. Only meaningful when aux2 bit 0 is set.",
+            ),
+            payload(
+                "aux2",
+                "",
+                "Flags: bit 0 set when aux holds the wait status.",
+            ),
+        ],
+    },
+    EventTypeDescriptor {
+        id: EventType::Net as u32,
+        name: "net",
+        description: "Socket send or receive that moved data.",
+        payload: &[
+            payload("data", "bytes", "Bytes the call returned."),
+            payload(
+                "data2",
+                "",
+                "Socket id: opaque, the same for every call on a socket while it is open; \
+                 changes when the daemon restarts.",
+            ),
+            payload(
+                "aux",
+                "",
+                "Operation: 0 tcp_send, 1 tcp_recv, 2 udp_send, 3 udp_recv, \
+                 4 unix_stream_send, 5 unix_stream_recv, 6 unix_dgram_send, 7 unix_dgram_recv.",
+            ),
+            payload(
+                "aux2",
+                "",
+                "Address family << 16 | remote port of a connected TCP or UDP socket, else 0.",
+            ),
+        ],
+    },
+    EventTypeDescriptor {
+        id: EventType::FileIo as u32,
+        name: "fileio",
+        description: "Read or write of a regular file.",
+        payload: &[
+            payload("data", "bytes", "Bytes transferred."),
+            payload("data2", "", "Inode number of the file."),
+            payload("aux", "", "Operation: 0 read, 1 write."),
+            payload("aux2", "", "Device of the file as (major << 20) | minor."),
+        ],
+    },
+    EventTypeDescriptor {
+        id: EventType::Syscall as u32,
+        name: "syscall",
+        description: "Calls of one syscall by a process, counted in-kernel and emitted in batches.",
+        payload: &[
+            payload("data", "", "Syscall number in the kernel's native table."),
+            payload("data2", "calls", "Calls counted since the previous batch."),
+        ],
+    },
+    EventTypeDescriptor {
+        id: EventType::BlockIo as u32,
+        name: "blockio",
+        description: "Block request queued, issued or completed.",
+        payload: &[
+            payload("data", "bytes", "Request size."),
+            payload("data2", "sectors", "Starting sector."),
+            payload("aux", "", "Stage: 0 queue, 1 issue, 2 complete."),
+            payload("aux2", "", "Device as (major << 20) | minor."),
+        ],
+    },
+    EventTypeDescriptor {
+        id: EventType::PageFault as u32,
+        name: "pagefault",
+        description: "Page fault (throttled per pid).",
+        payload: &[
+            payload("data", "", "Faulting address."),
+            payload("data2", "", "Instruction pointer."),
+            payload(
+                "aux",
+                "",
+                "Error flags: 1 protection, 2 write, 4 user, 8 reserved, 16 instruction, \
+                 32 shadow stack.",
+            ),
+            payload("aux2", "", "Origin: 0 user, 1 kernel."),
+        ],
+    },
+    EventTypeDescriptor {
+        id: EventType::Listen as u32,
+        name: "listen",
+        description: "Socket entered the listening state.",
+        payload: &[
+            payload("data", "", "Local port."),
+            payload("aux", "", "Address family: 2 inet, 10 inet6."),
+        ],
+    },
+    EventTypeDescriptor {
+        id: EventType::Connection as u32,
+        name: "connection",
+        description: "Inbound TCP handshake step on a listening socket (node-wide, pid 0).",
+        payload: &[
+            payload(
+                "data",
+                "",
+                "Remote IPv4 address, or the first 8 bytes of an IPv6 one, as raw bytes.",
+            ),
+            payload("data2", "", "Last 8 bytes of a remote IPv6 address."),
+            payload(
+                "aux",
+                "",
+                "Step: 0 SYN queued, 1 SYN answered with a syncookie, 2 handshake completed.",
+            ),
+            payload("aux2", "", "Address family << 16 | local port."),
+        ],
+    },
+    EventTypeDescriptor {
+        id: EventType::FdPressure as u32,
+        name: "fd_pressure",
+        description: "File descriptors near or at RLIMIT_NOFILE (throttled per pid).",
+        payload: &[
+            payload(
+                "data",
+                "fds",
+                "Open file descriptors, at least; the limit itself on exhaustion.",
+            ),
+            payload("data2", "fds", "Soft RLIMIT_NOFILE; 0 when unknown."),
+            payload("aux", "", "Flags: 1 allocation failed with EMFILE."),
+        ],
+    },
+    EventTypeDescriptor {
+        id: EventType::Signal as u32,
+        name: "signal",
+        description: "Signal generated for a process (fatal ones by default); pid and comm are the sender's.",
+        payload: &[
+            payload("data", "pid", "Target process."),
+            payload(
+                "data2",
+                "",
+                "si_code, sign-extended: 0 kill(2), -6 tkill(2), positive when raised by the kernel.",
+            ),
+            payload("aux", "", "Signal number."),
+            payload(
+                "aux2",
+                "",
+                "Flags: 1 sent to the thread group, 2 raised by the kernel; bits 8-15 delivery result (0 delivered, 1 ignored, 2 already pending, 3 queue overflow, 4 info lost).",
+            ),
+        ],
+    },
+    EventTypeDescriptor {
+        id: EventType::OomKill as u32,
+        name: "oom_kill",
+        description: "The kernel's OOM killer picked a victim; pid and comm are the task whose allocation ran out of memory.",
+        payload: &[
+            payload("data", "pid", "Victim process."),
+            payload(
+                "data2",
+                "bytes",
+                "Victim's anonymous, file and shmem RSS; 0 when the kernel does not report it.",
+            ),
+        ],
+    },
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EventSchema {
+    /// Version of the kernel/userspace event layout this describes.
+    pub schema_version: u32,
+    pub fields: Vec<FieldDescriptor>,
+    pub event_types: &'static [EventTypeDescriptor],
+}
+
+pub fn event_schema() -> EventSchema {
+    EventSchema {
+        schema_version: WIRE_SCHEMA_VERSION,
+        fields: EventRecord::fields(),
+        event_types: EVENT_TYPES,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PERCENT_MILLI_UNKNOWN, ProcessEventWire};
+    use linnix_ai_ebpf_common::CONTROL_EVENT_TYPES;
+
+    #[test]
+    fn descriptor_matches_serialized_record() {
+        let mut wire = ProcessEventWire {
+            pid: 1,
+            ppid: 0,
+            uid: 0,
+            gid: 0,
+            event_type: EventType::BlockIo as u32,
+            ts_ns: 1,
+            seq: 1,
+            comm: *b"kworker\0\0\0\0\0\0\0\0\0",
+            exit_time_ns: 0,
+            cpu_pct_milli: 1500,
+            mem_pct_milli: 250,
+            data: 4096,
+            data2: 8,
+            aux: 0,
+            aux2: 0,
+            cgroup_id: 0,
+        };
+        let fields = EventRecord::fields();
+        let event = ProcessEvent::new(wire)
+            .with_script_path(Some("job.py".into()))
+            .with_exe_path(Some("/usr/bin/python3".into()));
+        let json = serde_json::to_value(EventRecord::from(&event)).unwrap();
+        let keys: Vec<&str> = json
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        let names: Vec<&str> = fields.iter().map(|f| f.name).collect();
+        assert_eq!(keys.len(), names.len());
+        assert!(keys.iter().all(|k| names.contains(k)));
+        assert_eq!(json["event_type_name"], "blockio");
+        assert_eq!(json["cpu_percent"], 1.5);
+
+        wire.cpu_pct_milli = PERCENT_MILLI_UNKNOWN;
+        let json = serde_json::to_value(EventRecord::from(&ProcessEvent::new(wire))).unwrap();
+        let cpu = fields.iter().find(|f| f.name == "cpu_percent").unwrap();
+        assert!(cpu.optional && cpu.ty == "number");
+        assert!(json.get("cpu_percent").is_none());
+    }
+
+    #[test]
+    fn every_event_type_is_described() {
+        for id in 0..CONTROL_EVENT_TYPES {
+            assert_ne!(event_type_name(id), "unknown", "event type {id}");
+        }
+        assert_eq!(EVENT_TYPES.len(), CONTROL_EVENT_TYPES as usize);
+        assert_eq!(event_type_name(99), "unknown");
+    }
+}
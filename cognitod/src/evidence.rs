@@ -0,0 +1,225 @@
+//! Inputs a rule evaluated when it fired
+//!
+//! Each rule alert carries the event that tripped it, the thresholds it was
+//! compared against, the values observed, and the events of its aggregation
+//! window. The rules engine keeps the last `RECENT_EVENTS` fork/exec/exit
+//! events in a `RecentEvents` ring so window contents can be captured
+//! without storing every event of every window; at most `MAX_WINDOW_EVENTS`
+//! of them are kept per alert, newest first, with the full count alongside.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use tokio::time::{Duration, Instant};
+
+use crate::ProcessEvent;
+use crate::event_schema::event_type_name;
+use crate::signals::SignalRecord;
+
+/// Fork/exec/exit events kept for window capture.
+const RECENT_EVENTS: usize = 512;
+
+/// Window events stored with one alert.
+pub const MAX_WINDOW_EVENTS: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EvidenceEvent {
+    pub event: &'static str,
+    pub pid: u32,
+    pub ppid: u32,
+    pub comm: String,
+    /// How long before the alert the event was seen
+    pub age_ms: u64,
+    /// Exec-to-exit time, for exits of processes seen exec'ing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lifetime_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+struct RecentEvent {
+    at: Instant,
+    event: &'static str,
+    pid: u32,
+    ppid: u32,
+    comm: String,
+    lifetime: Option<Duration>,
+}
+
+impl RecentEvent {
+    fn to_evidence(&self, now: Instant) -> EvidenceEvent {
+        EvidenceEvent {
+            event: self.event,
+            pid: self.pid,
+            ppid: self.ppid,
+            comm: self.comm.clone(),
+            age_ms: now.saturating_duration_since(self.at).as_millis() as u64,
+            lifetime_ms: self.lifetime.map(|d| d.as_millis() as u64),
+        }
+    }
+}
+
+fn comm_of(event: &ProcessEvent) -> String {
+    String::from_utf8_lossy(&event.comm)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+/// Ring of recent fork/exec/exit events.
+#[derive(Debug, Default)]
+pub struct RecentEvents {
+    events: VecDeque<RecentEvent>,
+}
+
+impl RecentEvents {
+    pub fn push(&mut self, event: &ProcessEvent, lifetime: Option<Duration>, now: Instant) {
+        if self.events.len() >= RECENT_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(RecentEvent {
+            at: now,
+            event: event_type_name(event.event_type),
+            pid: event.pid,
+            ppid: event.ppid,
+            comm: comm_of(event),
+            lifetime,
+        });
+    }
+
+    /// Newest events of type `event` seen within `window` that pass
+    /// `filter`, at most `MAX_WINDOW_EVENTS`.
+    pub fn window(
+        &self,
+        event: &str,
+        window: Duration,
+        now: Instant,
+        filter: impl Fn(u32, Option<Duration>) -> bool,
+    ) -> Vec<EvidenceEvent> {
+        self.events
+            .iter()
+            .rev()
+            .take_while(|e| now.saturating_duration_since(e.at) <= window)
+            .filter(|e| e.event == event && filter(e.ppid, e.lifetime))
+            .take(MAX_WINDOW_EVENTS)
+            .map(|e| e.to_evidence(now))
+            .collect()
+    }
+}
+
+/// What a rule saw when it fired.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Evidence {
+    /// Event being evaluated when the rule fired
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger: Option<EvidenceEvent>,
+    /// Rule parameters at evaluation time
+    pub thresholds: BTreeMap<&'static str, f64>,
+    /// Values compared against `thresholds`
+    pub observed: BTreeMap<&'static str, f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_secs: Option<u64>,
+    /// Events in the window, newest first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub window: Vec<EvidenceEvent>,
+    /// Events in the window before truncation to `MAX_WINDOW_EVENTS`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_total: Option<u64>,
+    /// Last fatal signal sent to the trigger's process
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub killed_by: Option<SignalRecord>,
+}
+
+impl Evidence {
+    pub fn new(trigger: &ProcessEvent) -> Self {
+        Self {
+            trigger: Some(EvidenceEvent {
+                event: event_type_name(trigger.event_type),
+                pid: trigger.pid,
+                ppid: trigger.ppid,
+                comm: comm_of(trigger),
+                age_ms: 0,
+                lifetime_ms: None,
+            }),
+            ..Self::default()
+        }
+    }
+
+    pub fn threshold(mut self, name: &'static str, value: impl Into<f64>) -> Self {
+        self.thresholds.insert(name, value.into());
+        self
+    }
+
+    pub fn observed(mut self, name: &'static str, value: impl Into<f64>) -> Self {
+        self.observed.insert(name, value.into());
+        self
+    }
+
+    pub fn window(mut self, secs: u64, events: Vec<EvidenceEvent>, total: u64) -> Self {
+        self.window_secs = Some(secs);
+        self.window = events;
+        self.window_total = Some(total);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PERCENT_MILLI_UNKNOWN, ProcessEventWire};
+    use linnix_ai_ebpf_common::EventType;
+
+    fn event(pid: u32, ppid: u32, event_type: EventType) -> ProcessEvent {
+        let mut comm = [0u8; 16];
+        comm[..4].copy_from_slice(b"bash");
+        ProcessEvent::new(ProcessEventWire {
+            pid,
+            ppid,
+            uid: 0,
+            gid: 0,
+            event_type: event_type as u32,
+            ts_ns: 0,
+            seq: 0,
+            comm,
+            exit_time_ns: 0,
+            cpu_pct_milli: PERCENT_MILLI_UNKNOWN,
+            mem_pct_milli: PERCENT_MILLI_UNKNOWN,
+            data: 0,
+            data2: 0,
+            aux: 0,
+            aux2: 0,
+            cgroup_id: 0,
+        })
+    }
+
+    #[test]
+    fn window_keeps_newest_matching_events() {
+        let start = Instant::now();
+        let mut recent = RecentEvents::default();
+        for i in 0..(RECENT_EVENTS as u32 + 10) {
+            let ppid = if i % 2 == 0 { 1 } else { 2 };
+            recent.push(
+                &event(100 + i, ppid, EventType::Fork),
+                None,
+                start + Duration::from_millis(i as u64),
+            );
+        }
+        recent.push(
+            &event(7, 1, EventType::Exit),
+            Some(Duration::from_millis(5)),
+            start + Duration::from_secs(1),
+        );
+
+        let now = start + Duration::from_secs(1);
+        let forks = recent.window("fork", Duration::from_secs(5), now, |ppid, _| ppid == 1);
+        assert_eq!(forks.len(), MAX_WINDOW_EVENTS);
+        assert!(forks.iter().all(|e| e.ppid == 1 && e.comm == "bash"));
+        assert!(forks.windows(2).all(|w| w[0].age_ms <= w[1].age_ms));
+
+        let exits = recent.window("exit", Duration::from_millis(1), now, |_, _| true);
+        assert_eq!(exits.len(), 1);
+        assert_eq!(exits[0].lifetime_ms, Some(5));
+        assert!(
+            recent
+                .window("fork", Duration::from_millis(1), now, |_, _| true)
+                .is_empty()
+        );
+    }
+}
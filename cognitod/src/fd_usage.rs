@@ -0,0 +1,105 @@
+//! File descriptor usage against `RLIMIT_NOFILE`
+//!
+//! The kernel reports a process with an `fd_pressure` event once an fd
+//! allocation brings it to `[probes.fd_pressure] threshold_pct` of its soft
+//! limit, and whenever an allocation fails with `EMFILE`. Quiet processes
+//! are never reported, so [`FdUsage`] exists only for processes that came
+//! close. The open count is a lower bound: the kernel hands out the lowest
+//! free descriptor, so a process given fd `n` has at least `n + 1` open.
+
+use linnix_ai_ebpf_common::fd_pressure;
+use serde::Serialize;
+
+use crate::ProcessEvent;
+
+/// Latest fd pressure report of a process, as shown in `/processes`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct FdUsage {
+    /// Open descriptors, at least
+    pub open: u64,
+    /// Soft `RLIMIT_NOFILE`; `None` when the kernel offsets are unknown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub used_pct: Option<f64>,
+    /// The latest report was a failed allocation
+    pub exhausted: bool,
+}
+
+impl FdUsage {
+    /// Usage reported by an `fd_pressure` event.
+    pub fn from_event(event: &ProcessEvent) -> Self {
+        let limit = (event.data2 > 0).then_some(event.data2);
+        Self {
+            open: event.data,
+            limit,
+            used_pct: limit.map(|limit| event.data as f64 * 100.0 / limit as f64),
+            exhausted: event.aux & fd_pressure::EXHAUSTED != 0,
+        }
+    }
+
+    /// `comm(pid)` and what the report says, for alert messages.
+    pub fn describe(&self, process: &str) -> String {
+        match (self.exhausted, self.limit) {
+            (true, Some(limit)) => {
+                format!("{process} ran out of file descriptors (limit {limit})")
+            }
+            (true, None) => format!("{process} ran out of file descriptors"),
+            (false, Some(limit)) => format!(
+                "{process} has at least {} of {limit} file descriptors open ({:.0}%)",
+                self.open,
+                self.used_pct.unwrap_or_default()
+            ),
+            (false, None) => format!("{process} has at least {} file descriptors open", self.open),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PERCENT_MILLI_UNKNOWN, ProcessEventWire};
+    use linnix_ai_ebpf_common::EventType;
+
+    fn report(open: u64, limit: u64, aux: u32) -> ProcessEvent {
+        ProcessEvent::new(ProcessEventWire {
+            pid: 42,
+            ppid: 1,
+            uid: 0,
+            gid: 0,
+            event_type: EventType::FdPressure as u32,
+            ts_ns: 0,
+            seq: 0,
+            comm: [0; 16],
+            exit_time_ns: 0,
+            cpu_pct_milli: PERCENT_MILLI_UNKNOWN,
+            mem_pct_milli: PERCENT_MILLI_UNKNOWN,
+            data: open,
+            data2: limit,
+            aux,
+            aux2: 0,
+            cgroup_id: 0,
+        })
+    }
+
+    #[test]
+    fn reads_usage_from_reports() {
+        let usage = FdUsage::from_event(&report(900, 1024, 0));
+        assert_eq!(usage.limit, Some(1024));
+        assert!((usage.used_pct.unwrap() - 87.890625).abs() < 1e-9);
+        assert_eq!(
+            usage.describe("nginx(42)"),
+            "nginx(42) has at least 900 of 1024 file descriptors open (88%)"
+        );
+
+        let usage = FdUsage::from_event(&report(1024, 1024, fd_pressure::EXHAUSTED));
+        assert!(usage.exhausted);
+        assert_eq!(
+            usage.describe("nginx(42)"),
+            "nginx(42) ran out of file descriptors (limit 1024)"
+        );
+
+        let usage = FdUsage::from_event(&report(0, 0, fd_pressure::EXHAUSTED));
+        assert_eq!((usage.limit, usage.used_pct), (None, None));
+    }
+}
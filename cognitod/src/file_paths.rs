@@ -0,0 +1,124 @@
+//! Paths of files read and written in `fileio` events
+//!
+//! The kretprobes on `vfs_read`/`vfs_write` report regular files only, by
+//! inode number (`data2`) and device (`aux2`, `(major << 20) | minor` as the
+//! kernel keeps it); a path walk in the kernel would cost every call. A
+//! [`FilePaths`] turns them into a path by looking through the files the
+//! process has open in `/proc/<pid>/fd`, which works for as long as it keeps
+//! the file open. Resolved paths are cached by file, so a process writing
+//! the same file over and over is only looked up once. A file removed since
+//! it was opened keeps the ` (deleted)` suffix of its link.
+
+use linnix_ai_ebpf_common::EventType;
+use std::collections::HashMap;
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::ProcessEvent;
+
+/// Files whose path is remembered; the cache starts over when full.
+const MAX_PATHS: usize = 4096;
+
+/// Regular file as the kernel identifies it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId {
+    /// `(major << 20) | minor`
+    pub dev: u32,
+    pub ino: u64,
+}
+
+impl FileId {
+    /// File of a `fileio` event; `None` for other events.
+    pub fn from_event(event: &ProcessEvent) -> Option<Self> {
+        (event.event_type == EventType::FileIo as u32 && event.data2 != 0).then_some(Self {
+            dev: event.aux2,
+            ino: event.data2,
+        })
+    }
+
+    /// Identity of a file from its userspace `stat`.
+    pub fn from_metadata(meta: &std::fs::Metadata) -> Self {
+        let dev = meta.dev();
+        Self {
+            dev: (libc::major(dev) << 20) | libc::minor(dev),
+            ino: meta.ino(),
+        }
+    }
+}
+
+/// Resolver from [`FileId`] to path, with a cache of the paths found.
+#[derive(Debug)]
+pub struct FilePaths {
+    proc_root: PathBuf,
+    paths: Mutex<HashMap<FileId, String>>,
+}
+
+impl Default for FilePaths {
+    fn default() -> Self {
+        Self::new("/proc")
+    }
+}
+
+impl FilePaths {
+    pub fn new(proc_root: impl Into<PathBuf>) -> Self {
+        Self {
+            proc_root: proc_root.into(),
+            paths: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Path of `file`, written to by `pid`.
+    pub fn resolve(&self, pid: u32, file: FileId) -> Option<String> {
+        if let Some(path) = self.paths.lock().unwrap().get(&file) {
+            return Some(path.clone());
+        }
+        let path = self.open_path(pid, file)?;
+        let mut paths = self.paths.lock().unwrap();
+        if paths.len() >= MAX_PATHS {
+            paths.clear();
+        }
+        paths.insert(file, path.clone());
+        Some(path)
+    }
+
+    /// Target of the fd of `pid` that has `file` open.
+    fn open_path(&self, pid: u32, file: FileId) -> Option<String> {
+        let fds = std::fs::read_dir(self.proc_root.join(pid.to_string()).join("fd")).ok()?;
+        fds.flatten().find_map(|fd| {
+            let meta = std::fs::metadata(fd.path()).ok()?;
+            if !meta.is_file() || FileId::from_metadata(&meta) != file {
+                return None;
+            }
+            let target = std::fs::read_link(fd.path()).ok()?;
+            Some(target.to_string_lossy().into_owned())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_files_open_in_a_process() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.bin");
+        let open = std::fs::File::create(&path).unwrap();
+        let file = FileId::from_metadata(&open.metadata().unwrap());
+
+        let paths = FilePaths::default();
+        let resolved = paths.resolve(std::process::id(), file).unwrap();
+        assert_eq!(
+            std::path::Path::new(&resolved),
+            path.canonicalize().unwrap()
+        );
+
+        // Cached once found, even after the file is closed
+        drop(open);
+        assert_eq!(paths.resolve(u32::MAX, file), Some(resolved));
+
+        let unknown = FileId { dev: 0, ino: 1 };
+        assert_eq!(paths.resolve(std::process::id(), unknown), None);
+    }
+}
@@ -0,0 +1,284 @@
+//! Event filters shared by `/events`, recordings, event webhooks and replay
+//!
+//! `EventFilter` is the stored form: it deserializes from config tables and
+//! from `FilterParams` query strings (comma-separated lists). `compile`
+//! validates it once into an `EventMatcher`. Set fields must all match;
+//! empty ones match everything. The matcher checks the cheap fields first
+//! (type bitmask, pid and uid sets, comm regex). Namespace, pod and cgroup
+//! prefix need `/proc/<pid>/cgroup`, so they are read only when set and only
+//! for events that passed the other checks. Namespace and pod are globs and
+//! need Kubernetes metadata; without it no event matches them.
+
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::ProcessEvent;
+use crate::collectors::cgroup_tree::process_cgroup_path;
+use crate::event_schema::EVENT_TYPES;
+use crate::k8s::K8sContext;
+use crate::maintenance::glob_match;
+use crate::patterns::PatternSet;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EventFilter {
+    /// Event type names as in `/schema/events` (`exec`, `fork`, ...)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub types: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pids: Vec<u32>,
+    /// Regex matched against the process name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comm: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub uids: Vec<u32>,
+    /// Pod namespace (glob)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    /// Pod name (glob)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pod: Option<String>,
+    /// Prefix of the cgroup v2 path, e.g. `/kubepods.slice`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cgroup_prefix: Option<String>,
+}
+
+/// Query string form of `EventFilter`: `type=exec,fork&pid=1,2&comm=^nginx`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FilterParams {
+    #[serde(rename = "type")]
+    pub types: Option<String>,
+    pub pid: Option<String>,
+    pub comm: Option<String>,
+    pub uid: Option<String>,
+    pub namespace: Option<String>,
+    pub pod: Option<String>,
+    pub cgroup: Option<String>,
+}
+
+fn split_list(value: Option<&str>) -> impl Iterator<Item = &str> {
+    value
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+fn parse_ids(name: &str, value: Option<&str>) -> anyhow::Result<Vec<u32>> {
+    split_list(value)
+        .map(|item| {
+            item.parse()
+                .with_context(|| format!("invalid {name} {item:?}"))
+        })
+        .collect()
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.filter(|v| !v.is_empty())
+}
+
+impl TryFrom<FilterParams> for EventFilter {
+    type Error = anyhow::Error;
+
+    fn try_from(params: FilterParams) -> anyhow::Result<Self> {
+        Ok(Self {
+            types: split_list(params.types.as_deref())
+                .map(str::to_string)
+                .collect(),
+            pids: parse_ids("pid", params.pid.as_deref())?,
+            comm: non_empty(params.comm),
+            uids: parse_ids("uid", params.uid.as_deref())?,
+            namespace: non_empty(params.namespace),
+            pod: non_empty(params.pod),
+            cgroup_prefix: non_empty(params.cgroup),
+        })
+    }
+}
+
+impl EventFilter {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn compile(&self, k8s: Option<Arc<K8sContext>>) -> anyhow::Result<EventMatcher> {
+        let mut types = 0u64;
+        for name in &self.types {
+            let descriptor = EVENT_TYPES
+                .iter()
+                .find(|t| t.name == name.as_str())
+                .ok_or_else(|| anyhow!("unknown event type {name:?}"))?;
+            types |= 1 << descriptor.id;
+        }
+        let comm = self
+            .comm
+            .as_deref()
+            .map(|comm| PatternSet::regexes(&[comm]))
+            .transpose()
+            .context("invalid comm regex")?;
+        Ok(EventMatcher {
+            types,
+            pids: self.pids.iter().copied().collect(),
+            uids: self.uids.iter().copied().collect(),
+            comm,
+            namespace: self.namespace.clone(),
+            pod: self.pod.clone(),
+            cgroup_prefix: self.cgroup_prefix.clone(),
+            k8s,
+        })
+    }
+}
+
+/// Compiled `EventFilter`.
+#[derive(Clone, Default)]
+pub struct EventMatcher {
+    /// Bit per event type id; 0 matches all
+    types: u64,
+    pids: HashSet<u32>,
+    uids: HashSet<u32>,
+    comm: Option<PatternSet>,
+    namespace: Option<String>,
+    pod: Option<String>,
+    cgroup_prefix: Option<String>,
+    k8s: Option<Arc<K8sContext>>,
+}
+
+impl EventMatcher {
+    /// Matches every event.
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    pub fn matches(&self, event: &ProcessEvent) -> bool {
+        if self.types != 0 && (event.event_type >= 64 || self.types & (1 << event.event_type) == 0)
+        {
+            return false;
+        }
+        if !self.pids.is_empty() && !self.pids.contains(&event.pid) {
+            return false;
+        }
+        if !self.uids.is_empty() && !self.uids.contains(&event.uid) {
+            return false;
+        }
+        if let Some(comm) = &self.comm {
+            let name = String::from_utf8_lossy(&event.comm);
+            if !comm.is_match(name.trim_end_matches('\0')) {
+                return false;
+            }
+        }
+        if let Some(prefix) = &self.cgroup_prefix {
+            let Some(path) = process_cgroup_path(event.pid) else {
+                return false;
+            };
+            if !path.starts_with(prefix.as_str()) {
+                return false;
+            }
+        }
+        if self.namespace.is_some() || self.pod.is_some() {
+            let Some(meta) = self
+                .k8s
+                .as_ref()
+                .and_then(|k8s| k8s.get_metadata_for_event(event.pid, event.cgroup_id))
+            else {
+                return false;
+            };
+            if let Some(pattern) = &self.namespace
+                && !glob_match(pattern, &meta.namespace)
+            {
+                return false;
+            }
+            if let Some(pattern) = &self.pod
+                && !glob_match(pattern, &meta.pod_name)
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PERCENT_MILLI_UNKNOWN, ProcessEventWire};
+    use linnix_ai_ebpf_common::EventType;
+
+    fn event(pid: u32, uid: u32, event_type: EventType, comm: &[u8]) -> ProcessEvent {
+        let mut name = [0u8; 16];
+        name[..comm.len()].copy_from_slice(comm);
+        ProcessEvent::new(ProcessEventWire {
+            pid,
+            ppid: 1,
+            uid,
+            gid: 0,
+            event_type: event_type as u32,
+            ts_ns: 0,
+            seq: 0,
+            comm: name,
+            exit_time_ns: 0,
+            cpu_pct_milli: PERCENT_MILLI_UNKNOWN,
+            mem_pct_milli: PERCENT_MILLI_UNKNOWN,
+            data: 0,
+            data2: 0,
+            aux: 0,
+            aux2: 0,
+            cgroup_id: 0,
+        })
+    }
+
+    #[test]
+    fn query_params_and_config_compile_to_the_same_matcher() {
+        let from_query = EventFilter::try_from(FilterParams {
+            types: Some("exec, fork".into()),
+            uid: Some("0,33".into()),
+            comm: Some("^(nginx|php)".into()),
+            ..FilterParams::default()
+        })
+        .unwrap();
+        let from_config: EventFilter = toml::from_str(
+            r#"
+types = ["exec", "fork"]
+uids = [0, 33]
+comm = "^(nginx|php)"
+"#,
+        )
+        .unwrap();
+        assert_eq!(from_query, from_config);
+
+        let matcher = from_query.compile(None).unwrap();
+        assert!(matcher.matches(&event(10, 33, EventType::Exec, b"nginx")));
+        assert!(matcher.matches(&event(10, 0, EventType::Fork, b"php-fpm")));
+        assert!(!matcher.matches(&event(10, 33, EventType::Exit, b"nginx")));
+        assert!(!matcher.matches(&event(10, 1000, EventType::Exec, b"nginx")));
+        assert!(!matcher.matches(&event(10, 33, EventType::Exec, b"bash")));
+        assert!(EventMatcher::all().matches(&event(10, 1000, EventType::Exit, b"bash")));
+
+        // Pod filters need Kubernetes metadata
+        let pods = EventFilter {
+            namespace: Some("prod-*".into()),
+            ..EventFilter::default()
+        };
+        assert!(
+            !pods
+                .compile(None)
+                .unwrap()
+                .matches(&event(10, 0, EventType::Exec, b"nginx"))
+        );
+
+        assert!(
+            EventFilter {
+                types: vec!["bogus".into()],
+                ..EventFilter::default()
+            }
+            .compile(None)
+            .is_err()
+        );
+        assert!(
+            EventFilter::try_from(FilterParams {
+                pid: Some("12,x".into()),
+                ..FilterParams::default()
+            })
+            .is_err()
+        );
+    }
+}
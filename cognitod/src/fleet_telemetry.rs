@@ -0,0 +1,394 @@
+//! Opt-in fleet telemetry.
+//!
+//! With `[fleet_telemetry] enabled = true` the daemon periodically posts a
+//! few coarse aggregates to `endpoint`: daemon version, architecture, kernel
+//! major.minor, orders of magnitude of the event rate, process count and
+//! alert rate, and which features are configured. Nothing identifies the
+//! node: no host name, addresses, process or pod names, and no installation
+//! id.
+//!
+//! The aggregates are made locally differentially private before they leave
+//! the node. A magnitude is `log10(1 + value)` plus Laplace noise of scale
+//! `1 / epsilon`, rounded, so values an order of magnitude apart are
+//! `epsilon`-indistinguishable. A feature flag goes through randomized
+//! response: it is reported truthfully with probability
+//! `e^epsilon / (1 + e^epsilon)` and flipped otherwise.
+//!
+//! The next report is computed ahead of time and served as-is at
+//! `/telemetry/preview`, so operators see exactly what will be sent, even
+//! before opting in. Nothing is sent in offline mode. Builds without the
+//! `fleet-telemetry` feature contain none of this.
+
+use log::{debug, info, warn};
+use rand::Rng;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::config::{Config, FleetTelemetryConfig, OfflineGuard};
+use crate::context::ContextStore;
+use crate::metrics::Metrics;
+use crate::retry::check_response;
+use crate::utils::now_secs;
+
+/// Version of the report layout.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// How often the pending report is recomputed from current metrics.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(900);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Largest reported magnitude (10^9).
+const MAX_MAGNITUDE: f64 = 9.0;
+
+/// What is sent. Every field is listed in the Configuration Guide.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Report {
+    pub schema: u32,
+    pub version: &'static str,
+    pub arch: &'static str,
+    /// `major.minor` only
+    pub kernel: String,
+    /// Days since the Unix epoch
+    pub day: u64,
+    pub epsilon: f64,
+    /// Noisy `round(log10(1 + value))`
+    pub magnitudes: BTreeMap<&'static str, u32>,
+    /// Configured features, after randomized response
+    pub features: BTreeMap<&'static str, bool>,
+}
+
+/// Exact values a report is derived from. Never sent.
+#[derive(Debug, Clone)]
+struct Inputs {
+    kernel: String,
+    events_per_sec: f64,
+    processes: f64,
+    alerts_per_hour: f64,
+    features: BTreeMap<&'static str, bool>,
+}
+
+/// Response of `/telemetry/preview`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Preview {
+    pub enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    pub offline: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_send_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_sent_at: Option<u64>,
+    /// The report the next send posts, verbatim
+    pub report: Report,
+}
+
+/// Which optional subsystems `config` turns on.
+pub fn feature_usage(config: &Config) -> BTreeMap<&'static str, bool> {
+    BTreeMap::from([
+        ("reasoner", config.reasoner.enabled),
+        ("notifications", config.notifications.is_some()),
+        ("circuit_breaker", config.circuit_breaker.enabled),
+        ("incident_sinks", !config.incidents.sinks.is_empty()),
+        (
+            "maintenance_windows",
+            !config.maintenance.windows.is_empty(),
+        ),
+        ("restart_loops", config.restart_loops.enabled),
+        ("ddos", config.ddos.enabled),
+        ("cloudflare", !config.cloudflare.zones.is_empty()),
+        ("health_score", config.health_score.enabled),
+        ("topology", config.topology.enabled),
+        ("leaderboard", config.leaderboard.enabled),
+        ("power", config.power.enabled),
+        ("smart", config.smart.enabled),
+        ("filesystems", config.filesystems.enabled),
+        ("warmth", config.warmth.enabled),
+        ("recordings", !config.recordings.is_empty()),
+        ("event_webhooks", !config.event_webhooks.is_empty()),
+        ("computed_fields", !config.computed_fields.is_empty()),
+        ("templates", !config.templates.is_empty()),
+    ])
+}
+
+/// `6.8.0-45-generic` -> `6.8`.
+fn coarse_kernel(release: &str) -> String {
+    let mut parts = release.trim().split(|c: char| !c.is_ascii_digit());
+    match (parts.next(), parts.next()) {
+        (Some(major), Some(minor)) if !major.is_empty() && !minor.is_empty() => {
+            format!("{major}.{minor}")
+        }
+        _ => "unknown".to_string(),
+    }
+}
+
+fn laplace<R: Rng>(scale: f64, rng: &mut R) -> f64 {
+    let u: f64 = rng.gen_range(-0.5..0.5);
+    -scale * u.signum() * (1.0 - 2.0 * u.abs()).max(f64::MIN_POSITIVE).ln()
+}
+
+fn noisy_magnitude<R: Rng>(value: f64, epsilon: f64, rng: &mut R) -> u32 {
+    let magnitude = (1.0 + value.max(0.0)).log10();
+    (magnitude + laplace(1.0 / epsilon, rng))
+        .round()
+        .clamp(0.0, MAX_MAGNITUDE) as u32
+}
+
+fn randomized_response<R: Rng>(value: bool, epsilon: f64, rng: &mut R) -> bool {
+    // e^ε / (1 + e^ε), without overflowing for large budgets
+    let truthful = 1.0 / (1.0 + (-epsilon).exp());
+    if rng.gen_bool(truthful) {
+        value
+    } else {
+        !value
+    }
+}
+
+fn build_report<R: Rng>(inputs: &Inputs, epsilon: f64, now: u64, rng: &mut R) -> Report {
+    let magnitudes = [
+        ("events_per_sec", inputs.events_per_sec),
+        ("processes", inputs.processes),
+        ("alerts_per_hour", inputs.alerts_per_hour),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name, noisy_magnitude(value, epsilon, rng)))
+    .collect();
+    let features = inputs
+        .features
+        .iter()
+        .map(|(&name, &on)| (name, randomized_response(on, epsilon, rng)))
+        .collect();
+    Report {
+        schema: SCHEMA_VERSION,
+        version: env!("CARGO_PKG_VERSION"),
+        arch: std::env::consts::ARCH,
+        kernel: inputs.kernel.clone(),
+        day: now / 86_400,
+        epsilon,
+        magnitudes,
+        features,
+    }
+}
+
+fn collect_inputs(
+    kernel: &str,
+    features: &BTreeMap<&'static str, bool>,
+    metrics: &Metrics,
+    context: &ContextStore,
+) -> Inputs {
+    let uptime_hours = metrics.uptime_seconds().max(1) as f64 / 3600.0;
+    Inputs {
+        kernel: kernel.to_string(),
+        events_per_sec: metrics.events_per_sec() as f64,
+        processes: context.live_snapshot().len() as f64,
+        alerts_per_hour: metrics.alerts_emitted() as f64 / uptime_hours,
+        features: features.clone(),
+    }
+}
+
+struct State {
+    next: Report,
+    next_send_at: Option<u64>,
+    last_sent_at: Option<u64>,
+}
+
+pub struct FleetTelemetry {
+    config: FleetTelemetryConfig,
+    features: BTreeMap<&'static str, bool>,
+    kernel: String,
+    metrics: Arc<Metrics>,
+    context: Arc<ContextStore>,
+    offline: Arc<OfflineGuard>,
+    state: Mutex<State>,
+}
+
+impl FleetTelemetry {
+    pub fn new(
+        config: &Config,
+        metrics: Arc<Metrics>,
+        context: Arc<ContextStore>,
+        offline: Arc<OfflineGuard>,
+    ) -> Self {
+        let mut settings = config.fleet_telemetry.clone();
+        if !settings.epsilon.is_finite() || settings.epsilon <= 0.0 {
+            let fallback = FleetTelemetryConfig::default().epsilon;
+            warn!("[fleet_telemetry] epsilon must be positive, using {fallback}");
+            settings.epsilon = fallback;
+        }
+        let kernel = std::fs::read_to_string("/proc/sys/kernel/osrelease")
+            .map(|release| coarse_kernel(&release))
+            .unwrap_or_else(|_| "unknown".to_string());
+        let features = feature_usage(config);
+        let next = build_report(
+            &collect_inputs(&kernel, &features, &metrics, &context),
+            settings.epsilon,
+            now_secs(),
+            &mut rand::thread_rng(),
+        );
+        Self {
+            config: settings,
+            features,
+            kernel,
+            metrics,
+            context,
+            offline,
+            state: Mutex::new(State {
+                next,
+                next_send_at: None,
+                last_sent_at: None,
+            }),
+        }
+    }
+
+    /// Replace the pending report with one from current metrics.
+    pub fn refresh(&self) {
+        let report = build_report(
+            &collect_inputs(&self.kernel, &self.features, &self.metrics, &self.context),
+            self.config.epsilon,
+            now_secs(),
+            &mut rand::thread_rng(),
+        );
+        self.state.lock().unwrap().next = report;
+    }
+
+    pub fn preview(&self) -> Preview {
+        let state = self.state.lock().unwrap();
+        Preview {
+            enabled: self.config.enabled,
+            endpoint: self.config.endpoint.clone(),
+            offline: self.offline.is_offline(),
+            next_send_at: state.next_send_at,
+            last_sent_at: state.last_sent_at,
+            report: state.next.clone(),
+        }
+    }
+
+    async fn send(&self, client: &reqwest::Client, endpoint: &str) {
+        if !self.offline.check("fleet_telemetry") {
+            return;
+        }
+        let report = &self.state.lock().unwrap().next.clone();
+        let sent = self
+            .offline
+            .retrier()
+            .destination("fleet_telemetry")
+            .run(|_| async move {
+                check_response(client.post(endpoint).json(report).send().await).await
+            })
+            .await;
+        match sent {
+            Ok(_) => {
+                debug!("[fleet_telemetry] report sent");
+                self.state.lock().unwrap().last_sent_at = Some(now_secs());
+            }
+            Err(e) => warn!("[fleet_telemetry] send failed: {:#}", e),
+        }
+    }
+
+    /// Keep the pending report fresh and post it every `interval_secs`. The
+    /// first report goes out one interval after startup.
+    pub async fn run(self: Arc<Self>) {
+        let Some(endpoint) = self.config.endpoint.clone() else {
+            warn!("[fleet_telemetry] enabled without an endpoint, nothing will be sent");
+            return;
+        };
+        let interval = Duration::from_secs(self.config.interval_secs.max(3600));
+        info!(
+            "[fleet_telemetry] reporting to {} every {}s (epsilon {})",
+            endpoint,
+            interval.as_secs(),
+            self.config.epsilon
+        );
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+
+        let mut refresh = tokio::time::interval(REFRESH_INTERVAL);
+        let mut send = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+        self.state.lock().unwrap().next_send_at = Some(now_secs() + interval.as_secs());
+        loop {
+            tokio::select! {
+                _ = refresh.tick() => self.refresh(),
+                _ = send.tick() => {
+                    self.send(&client, &endpoint).await;
+                    self.refresh();
+                    self.state.lock().unwrap().next_send_at =
+                        Some(now_secs() + interval.as_secs());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn inputs() -> Inputs {
+        Inputs {
+            kernel: coarse_kernel("6.8.0-45-generic\n"),
+            events_per_sec: 1_500.0,
+            processes: 320.0,
+            alerts_per_hour: 0.0,
+            features: BTreeMap::from([("reasoner", true), ("ddos", false)]),
+        }
+    }
+
+    #[test]
+    fn large_budget_reports_exact_coarse_values() {
+        let report = build_report(
+            &inputs(),
+            1e9,
+            86_400 * 20_000 + 5,
+            &mut StdRng::seed_from_u64(7),
+        );
+        assert_eq!(report.kernel, "6.8");
+        assert_eq!(report.day, 20_000);
+        assert_eq!(report.magnitudes["events_per_sec"], 3);
+        assert_eq!(report.magnitudes["processes"], 3);
+        assert_eq!(report.magnitudes["alerts_per_hour"], 0);
+        assert!(report.features["reasoner"]);
+        assert!(!report.features["ddos"]);
+
+        let json = serde_json::to_value(&report).unwrap();
+        let keys: Vec<&str> = json
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                "arch",
+                "day",
+                "epsilon",
+                "features",
+                "kernel",
+                "magnitudes",
+                "schema",
+                "version"
+            ]
+        );
+    }
+
+    #[test]
+    fn small_budget_adds_noise() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let reports: Vec<Report> = (0..200)
+            .map(|_| build_report(&inputs(), 0.5, 0, &mut rng))
+            .collect();
+        assert!(reports.iter().any(|r| r.magnitudes["events_per_sec"] != 3));
+        assert!(reports.iter().any(|r| !r.features["reasoner"]));
+        assert!(
+            reports
+                .iter()
+                .all(|r| r.magnitudes["events_per_sec"] <= MAX_MAGNITUDE as u32)
+        );
+    }
+}
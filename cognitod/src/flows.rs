@@ -0,0 +1,369 @@
+//! Remote endpoints of each process's TCP and UDP traffic
+//!
+//! Net events of TCP and UDP sockets carry the socket id in `data2` and the
+//! remote `family << 16 | port` in `aux2`; the address does not fit beside
+//! them, so the kretprobes leave it in the `SOCKET_PEERS` map under the
+//! socket id. [`SocketPeers`] reads it once per socket and keeps it while
+//! the socket reports the same port. The context store keeps a
+//! [`FlowTracker`] of the live processes: bytes and calls each way per
+//! protocol, remote address and remote port, served by
+//! `/processes/{pid}/flows`. Unix sockets and unconnected UDP sockets have
+//! no remote end and make no flows, and neither does traffic before the map
+//! was opened.
+
+use aya::Pod;
+use aya::maps::{HashMap as BpfHashMap, Map, MapData};
+use linnix_ai_ebpf_common::{EventType, SOCKET_PEERS_MAX_ENTRIES, SocketPeer};
+use once_cell::sync::OnceCell;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Mutex;
+
+use crate::ProcessEvent;
+use crate::net_traffic::{self, Direction, Family};
+
+/// Kernel socket peers, set at startup when the object provides
+/// `SOCKET_PEERS`.
+pub static SOCKET_PEERS: OnceCell<SocketPeers> = OnceCell::new();
+
+/// Flows kept per process; the one idle longest makes room for a new one.
+pub const MAX_FLOWS_PER_PROCESS: usize = 256;
+
+/// Sockets whose remote end is remembered; the cache starts over when full.
+const MAX_SOCKETS: usize = SOCKET_PEERS_MAX_ENTRIES as usize;
+
+const AF_INET: u16 = 2;
+const AF_INET6: u16 = 10;
+
+#[repr(transparent)]
+#[derive(Copy, Clone)]
+struct SocketPeerPod(SocketPeer);
+
+unsafe impl Pod for SocketPeerPod {}
+
+/// The `SOCKET_PEERS` map and the remote ends already read from it.
+pub struct SocketPeers {
+    map: Mutex<BpfHashMap<MapData, u64, SocketPeerPod>>,
+    known: Mutex<HashMap<u64, SocketAddr>>,
+}
+
+impl SocketPeers {
+    pub fn new(map: Map) -> anyhow::Result<Self> {
+        Ok(Self {
+            map: Mutex::new(BpfHashMap::try_from(map)?),
+            known: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Remote end of `socket`, read again when its port is not `port` (a
+    /// UDP socket connected elsewhere).
+    fn lookup(&self, socket: u64, port: u16) -> Option<SocketAddr> {
+        let mut known = self.known.lock().unwrap();
+        if let Some(addr) = known.get(&socket).filter(|addr| addr.port() == port) {
+            return Some(*addr);
+        }
+        let SocketPeerPod(peer) = self.map.lock().unwrap().get(&socket, 0).ok()?;
+        let addr = peer_addr(&peer)?;
+        if known.len() >= MAX_SOCKETS {
+            known.clear();
+        }
+        known.insert(socket, addr);
+        Some(addr)
+    }
+}
+
+/// Address of a peer; raw bytes in memory order, as in connection events.
+fn peer_addr(peer: &SocketPeer) -> Option<SocketAddr> {
+    let ip = match peer.family {
+        AF_INET => IpAddr::V4(Ipv4Addr::from((peer.addr[0] as u32).to_ne_bytes())),
+        AF_INET6 => {
+            let mut bytes = [0u8; 16];
+            bytes[..8].copy_from_slice(&peer.addr[0].to_ne_bytes());
+            bytes[8..].copy_from_slice(&peer.addr[1].to_ne_bytes());
+            Ipv6Addr::from(bytes).to_canonical()
+        }
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, peer.port))
+}
+
+/// Bytes a TCP or UDP call moved to or from a remote end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transfer {
+    pub family: Family,
+    pub direction: Direction,
+    pub remote: SocketAddr,
+    pub bytes: u64,
+}
+
+/// Transfer of a net event whose remote end is known.
+pub fn transfer(event: &ProcessEvent) -> Option<Transfer> {
+    transfer_with(event, |socket, port| {
+        SOCKET_PEERS.get()?.lookup(socket, port)
+    })
+}
+
+fn transfer_with(
+    event: &ProcessEvent,
+    lookup: impl FnOnce(u64, u16) -> Option<SocketAddr>,
+) -> Option<Transfer> {
+    if event.event_type != EventType::Net as u32 || event.aux2 == 0 {
+        return None;
+    }
+    let (family, direction) = net_traffic::classify(event.aux)?;
+    if family == Family::Unix {
+        return None;
+    }
+    Some(Transfer {
+        family,
+        direction,
+        remote: lookup(event.data2, event.aux2 as u16)?,
+        bytes: event.data,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FlowKey {
+    family: Family,
+    remote: SocketAddr,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct FlowStats {
+    tx_bytes: u64,
+    rx_bytes: u64,
+    tx_calls: u64,
+    rx_calls: u64,
+    first_seen_ns: u64,
+    last_seen_ns: u64,
+}
+
+/// Traffic of one process with one remote end, as `/processes/{pid}/flows`
+/// reports it. Times are wall-clock nanoseconds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Flow {
+    /// `tcp` or `udp`
+    pub protocol: &'static str,
+    pub remote_ip: IpAddr,
+    pub remote_port: u16,
+    pub tx_bytes: u64,
+    pub rx_bytes: u64,
+    pub tx_calls: u64,
+    pub rx_calls: u64,
+    pub first_seen_ns: u64,
+    pub last_seen_ns: u64,
+}
+
+/// Flows of each process, by pid.
+#[derive(Debug, Default)]
+pub struct FlowTracker {
+    processes: HashMap<u32, HashMap<FlowKey, FlowStats>>,
+}
+
+impl FlowTracker {
+    pub fn record(&mut self, pid: u32, transfer: &Transfer, now: u64) {
+        let flows = self.processes.entry(pid).or_default();
+        let key = FlowKey {
+            family: transfer.family,
+            remote: transfer.remote,
+        };
+        if !flows.contains_key(&key) && flows.len() >= MAX_FLOWS_PER_PROCESS {
+            let idle = flows
+                .iter()
+                .min_by_key(|(_, stats)| stats.last_seen_ns)
+                .map(|(key, _)| *key);
+            if let Some(idle) = idle {
+                flows.remove(&idle);
+            }
+        }
+        let stats = flows.entry(key).or_insert(FlowStats {
+            first_seen_ns: now,
+            ..FlowStats::default()
+        });
+        stats.last_seen_ns = now;
+        match transfer.direction {
+            Direction::Tx => {
+                stats.tx_bytes += transfer.bytes;
+                stats.tx_calls += 1;
+            }
+            Direction::Rx => {
+                stats.rx_bytes += transfer.bytes;
+                stats.rx_calls += 1;
+            }
+        }
+    }
+
+    /// Flows of `pid`, most bytes first.
+    pub fn flows(&self, pid: u32) -> Vec<Flow> {
+        let mut flows: Vec<Flow> = self
+            .processes
+            .get(&pid)
+            .into_iter()
+            .flatten()
+            .map(|(key, stats)| Flow {
+                protocol: key.family.as_str(),
+                remote_ip: key.remote.ip(),
+                remote_port: key.remote.port(),
+                tx_bytes: stats.tx_bytes,
+                rx_bytes: stats.rx_bytes,
+                tx_calls: stats.tx_calls,
+                rx_calls: stats.rx_calls,
+                first_seen_ns: stats.first_seen_ns,
+                last_seen_ns: stats.last_seen_ns,
+            })
+            .collect();
+        flows.sort_by(|a, b| {
+            (b.tx_bytes + b.rx_bytes)
+                .cmp(&(a.tx_bytes + a.rx_bytes))
+                .then_with(|| (a.remote_ip, a.remote_port).cmp(&(b.remote_ip, b.remote_port)))
+        });
+        flows
+    }
+
+    pub fn remove(&mut self, pid: u32) {
+        self.processes.remove(&pid);
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(u32) -> bool) {
+        self.processes.retain(|pid, _| keep(*pid));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PERCENT_MILLI_UNKNOWN, ProcessEventWire};
+    use linnix_ai_ebpf_common::NetOp;
+
+    fn net_event(op: NetOp, bytes: u64, socket: u64, port: u16) -> ProcessEvent {
+        ProcessEvent::new(ProcessEventWire {
+            pid: 1,
+            ppid: 0,
+            uid: 0,
+            gid: 0,
+            event_type: EventType::Net as u32,
+            ts_ns: 0,
+            seq: 0,
+            comm: [0u8; 16],
+            exit_time_ns: 0,
+            cpu_pct_milli: PERCENT_MILLI_UNKNOWN,
+            mem_pct_milli: PERCENT_MILLI_UNKNOWN,
+            data: bytes,
+            data2: socket,
+            aux: op as u32,
+            aux2: ((AF_INET as u32) << 16) | port as u32,
+            cgroup_id: 0,
+        })
+    }
+
+    fn remote(addr: &str) -> SocketAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn decodes_peers() {
+        let mut peer = SocketPeer {
+            family: AF_INET,
+            port: 443,
+            ..SocketPeer::default()
+        };
+        peer.addr[0] = u32::from_ne_bytes([10, 0, 0, 7]) as u64;
+        assert_eq!(peer_addr(&peer), Some(remote("10.0.0.7:443")));
+
+        let v6: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let octets = v6.octets();
+        peer.family = AF_INET6;
+        peer.addr[0] = u64::from_ne_bytes(octets[..8].try_into().unwrap());
+        peer.addr[1] = u64::from_ne_bytes(octets[8..].try_into().unwrap());
+        assert_eq!(peer_addr(&peer), Some(remote("[2001:db8::1]:443")));
+
+        // v4-mapped addresses of dual-stack sockets read as IPv4
+        let mapped: Ipv6Addr = "::ffff:10.0.0.7".parse().unwrap();
+        let octets = mapped.octets();
+        peer.addr[0] = u64::from_ne_bytes(octets[..8].try_into().unwrap());
+        peer.addr[1] = u64::from_ne_bytes(octets[8..].try_into().unwrap());
+        assert_eq!(peer_addr(&peer), Some(remote("10.0.0.7:443")));
+
+        peer.family = 1;
+        assert_eq!(peer_addr(&peer), None);
+    }
+
+    #[test]
+    fn transfers_need_a_remote_end() {
+        let addr = remote("10.0.0.7:5432");
+        let send = net_event(NetOp::TcpSend, 100, 9, 5432);
+        let transfer = transfer_with(&send, |socket, port| {
+            assert_eq!((socket, port), (9, 5432));
+            Some(addr)
+        })
+        .unwrap();
+        assert_eq!(transfer.family, Family::Tcp);
+        assert_eq!(transfer.direction, Direction::Tx);
+        assert_eq!(transfer.bytes, 100);
+
+        let mut unconnected = net_event(NetOp::UdpSend, 100, 9, 0);
+        unconnected.aux2 = 0;
+        assert!(transfer_with(&unconnected, |_, _| Some(addr)).is_none());
+        let unix = net_event(NetOp::UnixStreamSend, 100, 9, 5432);
+        assert!(transfer_with(&unix, |_, _| Some(addr)).is_none());
+        assert!(transfer_with(&send, |_, _| None).is_none());
+    }
+
+    #[test]
+    fn aggregates_flows_per_process() {
+        let db = remote("10.0.0.7:5432");
+        let dns = remote("10.0.0.2:53");
+        let mut tracker = FlowTracker::default();
+        let transfer = |family, direction, remote, bytes| Transfer {
+            family,
+            direction,
+            remote,
+            bytes,
+        };
+        tracker.record(1, &transfer(Family::Tcp, Direction::Tx, db, 100), 10);
+        tracker.record(1, &transfer(Family::Tcp, Direction::Rx, db, 900), 20);
+        tracker.record(1, &transfer(Family::Udp, Direction::Tx, dns, 40), 30);
+        tracker.record(2, &transfer(Family::Tcp, Direction::Tx, db, 5), 40);
+
+        let flows = tracker.flows(1);
+        assert_eq!(flows.len(), 2);
+        assert_eq!(flows[0].protocol, "tcp");
+        assert_eq!(flows[0].remote_ip, db.ip());
+        assert_eq!(flows[0].remote_port, 5432);
+        assert_eq!((flows[0].tx_bytes, flows[0].rx_bytes), (100, 900));
+        assert_eq!((flows[0].tx_calls, flows[0].rx_calls), (1, 1));
+        assert_eq!((flows[0].first_seen_ns, flows[0].last_seen_ns), (10, 20));
+        assert_eq!(flows[1].protocol, "udp");
+
+        tracker.retain(|pid| pid != 1);
+        assert!(tracker.flows(1).is_empty());
+        assert_eq!(tracker.flows(2).len(), 1);
+    }
+
+    #[test]
+    fn idle_flows_make_room() {
+        let mut tracker = FlowTracker::default();
+        for port in 0..MAX_FLOWS_PER_PROCESS as u16 {
+            let remote = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1000 + port);
+            let transfer = Transfer {
+                family: Family::Tcp,
+                direction: Direction::Tx,
+                remote,
+                bytes: 1,
+            };
+            tracker.record(1, &transfer, 100 + port as u64);
+        }
+        let newcomer = Transfer {
+            family: Family::Tcp,
+            direction: Direction::Tx,
+            remote: remote("10.0.0.7:80"),
+            bytes: 1,
+        };
+        tracker.record(1, &newcomer, 1_000);
+        let flows = tracker.flows(1);
+        assert_eq!(flows.len(), MAX_FLOWS_PER_PROCESS);
+        assert!(flows.iter().all(|flow| flow.remote_port != 1000));
+        assert!(flows.iter().any(|flow| flow.remote_port == 80));
+    }
+}
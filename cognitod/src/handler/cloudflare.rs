@@ -1,6 +1,32 @@
-// Cloudflare cache purge on deployment
+// Cloudflare cache purge on deployment, and edge mitigation
+//
+// `CloudflareMitigator` executes approved enforcement actions on the
+// configured zones: `BlockSource` actions with the `cloudflare` backend
+// become IP Access Rules in each zone's mode, and `CloudflareRateLimit`
+// actions, proposed when an alert rule a zone listens to fires, become
+// rate-limiting rules. Every rule it creates is deleted again once its TTL
+// passes. In dry-run mode nothing is sent and the calls are logged.
 use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use crate::alerts::{Alert, AlertSender};
+use crate::config::{AccessRuleMode, CloudflareConfig, CloudflareZoneConfig, RateLimitConfig};
+use crate::enforcement::{ActionType, EnforcementQueue};
+use crate::utils::now_secs;
+
+const ACCESS_RULES: &str = "firewall/access_rules/rules";
+const RATELIMIT_ENTRYPOINT: &str = "rulesets/phases/http_ratelimit/entrypoint";
+
+/// How often created rules are checked for expiry
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(30);
 
 #[derive(Debug, Serialize)]
 struct CloudflarePurgeRequest {
@@ -13,6 +39,33 @@ struct CloudflarePurgeResponse {
     errors: Vec<serde_json::Value>,
 }
 
+#[derive(Debug, Deserialize)]
+struct CloudflareResponse<T> {
+    success: bool,
+    #[serde(default)]
+    errors: Vec<serde_json::Value>,
+    result: Option<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccessRule {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Ruleset {
+    id: String,
+    #[serde(default)]
+    rules: Vec<RulesetRule>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RulesetRule {
+    id: String,
+    #[serde(rename = "ref", default)]
+    reference: Option<String>,
+}
+
 pub struct CloudflareSync {
     api_token: String,
     zone_id: String,
@@ -85,6 +138,406 @@ impl CloudflareSync {
         log::info!("✅ Cloudflare URLs purged successfully");
         Ok(())
     }
+
+    fn zone_url(&self, path: &str) -> String {
+        format!(
+            "https://api.cloudflare.com/client/v4/zones/{}/{}",
+            self.zone_id, path
+        )
+    }
+
+    /// Send an authenticated request and unwrap the `result` of the reply
+    async fn call<T: DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+        what: &str,
+    ) -> Result<T> {
+        let response = request
+            .header("Authorization", format!("Bearer {}", self.api_token))
+            .send()
+            .await
+            .with_context(|| format!("Failed to send Cloudflare {what} request"))?;
+
+        let body: CloudflareResponse<T> = response
+            .json()
+            .await
+            .context("Failed to parse Cloudflare response")?;
+
+        match body.result {
+            Some(result) if body.success => Ok(result),
+            _ => anyhow::bail!("Cloudflare {what} failed: {:?}", body.errors),
+        }
+    }
+
+    /// Create an IP Access Rule and return its id
+    pub async fn create_access_rule(
+        &self,
+        ip: IpAddr,
+        mode: AccessRuleMode,
+        notes: &str,
+    ) -> Result<String> {
+        let target = if ip.is_ipv4() { "ip" } else { "ip6" };
+        let request = self
+            .client
+            .post(self.zone_url(ACCESS_RULES))
+            .json(&serde_json::json!({
+                "mode": mode.as_str(),
+                "configuration": {"target": target, "value": ip.to_string()},
+                "notes": notes,
+            }));
+        let rule: AccessRule = self.call(request, "access rule").await?;
+        Ok(rule.id)
+    }
+
+    pub async fn delete_access_rule(&self, id: &str) -> Result<()> {
+        let url = format!("{}/{}", self.zone_url(ACCESS_RULES), id);
+        self.call::<serde_json::Value>(self.client.delete(url), "access rule deletion")
+            .await
+            .map(|_| ())
+    }
+
+    /// Id of the zone's rate-limiting entry point ruleset, if it has one
+    async fn ratelimit_entrypoint(&self) -> Result<Option<String>> {
+        let response = self
+            .client
+            .get(self.zone_url(RATELIMIT_ENTRYPOINT))
+            .header("Authorization", format!("Bearer {}", self.api_token))
+            .send()
+            .await
+            .context("Failed to send Cloudflare ruleset request")?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let body: CloudflareResponse<Ruleset> = response
+            .json()
+            .await
+            .context("Failed to parse Cloudflare response")?;
+
+        match body.result {
+            Some(ruleset) if body.success => Ok(Some(ruleset.id)),
+            _ => anyhow::bail!("Cloudflare ruleset lookup failed: {:?}", body.errors),
+        }
+    }
+
+    /// Add a rate-limiting rule, identified by its `ref`, to the zone's
+    /// entry point ruleset (created if missing). Returns the ruleset and
+    /// rule ids.
+    pub async fn create_rate_limit(
+        &self,
+        rule: &serde_json::Value,
+        reference: &str,
+    ) -> Result<(String, String)> {
+        let ruleset: Ruleset = match self.ratelimit_entrypoint().await? {
+            Some(id) => {
+                let url = self.zone_url(&format!("rulesets/{id}/rules"));
+                self.call(self.client.post(url).json(rule), "rate limit rule")
+                    .await?
+            }
+            None => {
+                let request = self
+                    .client
+                    .put(self.zone_url(RATELIMIT_ENTRYPOINT))
+                    .json(&serde_json::json!({ "rules": [rule] }));
+                self.call(request, "rate limit ruleset").await?
+            }
+        };
+        let rule_id = ruleset
+            .rules
+            .into_iter()
+            .find(|r| r.reference.as_deref() == Some(reference))
+            .map(|r| r.id)
+            .context("created rate limit rule missing from the ruleset")?;
+        Ok((ruleset.id, rule_id))
+    }
+
+    pub async fn delete_ruleset_rule(&self, ruleset_id: &str, rule_id: &str) -> Result<()> {
+        let url = self.zone_url(&format!("rulesets/{ruleset_id}/rules/{rule_id}"));
+        self.call::<serde_json::Value>(self.client.delete(url), "rate limit rule deletion")
+            .await
+            .map(|_| ())
+    }
+}
+
+/// Rate-limiting rule counting requests per client IP and data center.
+fn rate_limit_rule(
+    limit: &RateLimitConfig,
+    requests_per_period: u64,
+    period_secs: u64,
+    reference: &str,
+) -> serde_json::Value {
+    serde_json::json!({
+        "ref": reference,
+        "description": "linnix rate limit",
+        "expression": limit.expression,
+        "action": "block",
+        "ratelimit": {
+            "characteristics": ["cf.colo.id", "ip.src"],
+            "period": period_secs,
+            "requests_per_period": requests_per_period,
+            "mitigation_timeout": limit.mitigation_timeout_secs,
+        },
+    })
+}
+
+/// Edge rule created by an approved action.
+#[derive(Debug, Clone)]
+enum CreatedRule {
+    AccessRule { id: String },
+    RateLimit { ruleset_id: String, rule_id: String },
+}
+
+impl CreatedRule {
+    fn describe(&self) -> String {
+        match self {
+            CreatedRule::AccessRule { id } => format!("access rule {id}"),
+            CreatedRule::RateLimit { rule_id, .. } => format!("rate limit rule {rule_id}"),
+        }
+    }
+}
+
+struct Zone {
+    config: CloudflareZoneConfig,
+    api: CloudflareSync,
+}
+
+pub struct CloudflareMitigator {
+    zones: Vec<Zone>,
+    dry_run: bool,
+    auto_approve: bool,
+    rule_ttl_secs: u64,
+    /// Rules on the zones: zone index, rule, expiry
+    created: Mutex<Vec<(usize, CreatedRule, u64)>>,
+    /// Zones with a rate-limit rule proposed, until that rule would expire
+    proposed: Mutex<HashMap<String, u64>>,
+}
+
+impl CloudflareMitigator {
+    pub fn new(config: &CloudflareConfig) -> Self {
+        Self {
+            zones: config
+                .zones
+                .iter()
+                .map(|zone| Zone {
+                    config: zone.clone(),
+                    api: CloudflareSync::new(zone.api_token.clone(), zone.zone_id.clone()),
+                })
+                .collect(),
+            dry_run: config.dry_run,
+            auto_approve: config.auto_approve,
+            rule_ttl_secs: config.rule_ttl_secs.max(1),
+            created: Mutex::new(Vec::new()),
+            proposed: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Put an IP Access Rule for `addr` on every zone, in the zone's mode.
+    pub async fn block(&self, addr: &str, ttl_secs: u64) -> Result<()> {
+        let ip: IpAddr = addr
+            .parse()
+            .with_context(|| format!("'{addr}' is not an IP address"))?;
+        if self.zones.is_empty() {
+            anyhow::bail!("no Cloudflare zones configured");
+        }
+        let notes = format!("linnix: expires after {ttl_secs}s");
+        let expires_at = now_secs() + ttl_secs;
+        let mut failures = Vec::new();
+        for (index, zone) in self.zones.iter().enumerate() {
+            let mode = zone.config.mode.as_str();
+            if self.dry_run {
+                info!(
+                    "[cloudflare] dry run: would {mode} {ip} on zone {} for {ttl_secs}s",
+                    zone.config.name
+                );
+                continue;
+            }
+            match zone
+                .api
+                .create_access_rule(ip, zone.config.mode, &notes)
+                .await
+            {
+                Ok(id) => {
+                    info!(
+                        "🛡️  {mode} {ip} on zone {} for {ttl_secs}s (access rule {id})",
+                        zone.config.name
+                    );
+                    self.created.lock().unwrap().push((
+                        index,
+                        CreatedRule::AccessRule { id },
+                        expires_at,
+                    ));
+                }
+                Err(e) => failures.push(format!("{}: {e:#}", zone.config.name)),
+            }
+        }
+        if !failures.is_empty() {
+            anyhow::bail!("{}", failures.join("; "));
+        }
+        Ok(())
+    }
+
+    /// Add a rate-limiting rule to `zone` for `ttl_secs`.
+    pub async fn rate_limit(
+        &self,
+        zone: &str,
+        requests_per_period: u64,
+        period_secs: u64,
+        ttl_secs: u64,
+    ) -> Result<()> {
+        let (index, zone) = self
+            .zones
+            .iter()
+            .enumerate()
+            .find(|(_, z)| z.config.name == zone)
+            .with_context(|| format!("unknown Cloudflare zone '{zone}'"))?;
+        let limit = zone.config.rate_limit.clone().unwrap_or_default();
+        let reference = format!("linnix_{}_{}", index, now_secs());
+        let rule = rate_limit_rule(&limit, requests_per_period, period_secs, &reference);
+        if self.dry_run {
+            info!(
+                "[cloudflare] dry run: would add to zone {} for {ttl_secs}s: {rule}",
+                zone.config.name
+            );
+            return Ok(());
+        }
+        let (ruleset_id, rule_id) = zone.api.create_rate_limit(&rule, &reference).await?;
+        info!(
+            "🛡️  rate limiting zone {} to {requests_per_period} requests per {period_secs}s for {ttl_secs}s (rule {rule_id})",
+            zone.config.name
+        );
+        self.created.lock().unwrap().push((
+            index,
+            CreatedRule::RateLimit {
+                ruleset_id,
+                rule_id,
+            },
+            now_secs() + ttl_secs,
+        ));
+        Ok(())
+    }
+
+    /// Rate-limit actions called for by `alert`: one per zone listening to
+    /// its rule that had none proposed within the rule TTL.
+    pub fn on_alert(&self, alert: &Alert, now: u64) -> Vec<ActionType> {
+        if alert.suppressed_by.is_some() {
+            return Vec::new();
+        }
+        let mut proposed = self.proposed.lock().unwrap();
+        proposed.retain(|_, until| *until > now);
+        let mut actions = Vec::new();
+        for zone in &self.zones {
+            let Some(limit) = &zone.config.rate_limit else {
+                continue;
+            };
+            if !limit.on_rules.contains(&alert.rule) {
+                continue;
+            }
+            if let Entry::Vacant(slot) = proposed.entry(zone.config.name.clone()) {
+                slot.insert(now + self.rule_ttl_secs);
+                actions.push(ActionType::CloudflareRateLimit {
+                    zone: zone.config.name.clone(),
+                    requests_per_period: limit.requests_per_period,
+                    period_secs: limit.period_secs,
+                    ttl_secs: self.rule_ttl_secs,
+                });
+            }
+        }
+        actions
+    }
+
+    /// Delete created rules whose TTL passed; failed deletions are retried
+    /// on the next pass.
+    pub async fn expire(&self, now: u64) {
+        let expired: Vec<_> = {
+            let mut created = self.created.lock().unwrap();
+            let (expired, live): (Vec<_>, Vec<_>) =
+                created.drain(..).partition(|(_, _, at)| *at <= now);
+            *created = live;
+            expired
+        };
+        for (index, rule, at) in expired {
+            let zone = &self.zones[index];
+            let result = match &rule {
+                CreatedRule::AccessRule { id } => zone.api.delete_access_rule(id).await,
+                CreatedRule::RateLimit {
+                    ruleset_id,
+                    rule_id,
+                } => zone.api.delete_ruleset_rule(ruleset_id, rule_id).await,
+            };
+            match result {
+                Ok(()) => info!(
+                    "✅ Removed expired {} from zone {}",
+                    rule.describe(),
+                    zone.config.name
+                ),
+                Err(e) => {
+                    warn!(
+                        "[cloudflare] failed to remove {} from zone {}: {e:#}",
+                        rule.describe(),
+                        zone.config.name
+                    );
+                    self.created.lock().unwrap().push((index, rule, at));
+                }
+            }
+        }
+    }
+
+    pub async fn run(
+        self: Arc<Self>,
+        alerts: Option<AlertSender>,
+        queue: Option<Arc<EnforcementQueue>>,
+    ) {
+        let mut ticker = tokio::time::interval(EXPIRY_INTERVAL);
+        let (Some(alerts), Some(queue)) = (alerts, queue) else {
+            loop {
+                ticker.tick().await;
+                self.expire(now_secs()).await;
+            }
+        };
+        let mut alert_rx = alerts.subscribe();
+        loop {
+            tokio::select! {
+                alert = alert_rx.recv() => match alert {
+                    Ok(alert) => {
+                        for action in self.on_alert(&alert, now_secs()) {
+                            let reason = format!("{} alert: {}", alert.rule, alert.message);
+                            match queue
+                                .propose_for_rule(
+                                    &alert.rule,
+                                    action,
+                                    reason,
+                                    "cloudflare".into(),
+                                    None,
+                                    self.auto_approve,
+                                )
+                                .await
+                            {
+                                Ok(id) => info!("[cloudflare] proposed rate limit ({id})"),
+                                Err(e) => warn!("[cloudflare] rate limit not proposed: {e}"),
+                            }
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        debug!("[cloudflare] lagged, skipped {skipped} alerts");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                _ = ticker.tick() => self.expire(now_secs()).await,
+            }
+        }
+    }
+}
+
+/// Start expiring created rules and, with a rules engine and enforcement
+/// queue, proposing rate limits from alerts.
+pub fn spawn(
+    config: &CloudflareConfig,
+    alerts: Option<AlertSender>,
+    queue: Option<Arc<EnforcementQueue>>,
+) -> Arc<CloudflareMitigator> {
+    let mitigator = Arc::new(CloudflareMitigator::new(config));
+    tokio::spawn(Arc::clone(&mitigator).run(alerts, queue));
+    mitigator
 }
 
 /// Detect Coolify deployment events by watching Docker container creations
@@ -93,3 +546,73 @@ pub fn is_deployment_event(comm: &str, cmdline: &str) -> bool {
     comm == "docker" && (cmdline.contains("create") || cmdline.contains("start"))
         && (cmdline.contains("coolify") || cmdline.contains("deployment"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::Severity;
+
+    fn mitigator() -> CloudflareMitigator {
+        CloudflareMitigator::new(&CloudflareConfig {
+            dry_run: true,
+            zones: vec![
+                CloudflareZoneConfig {
+                    name: "shop".into(),
+                    zone_id: "z1".into(),
+                    api_token: "t".into(),
+                    mode: AccessRuleMode::ManagedChallenge,
+                    rate_limit: Some(RateLimitConfig::default()),
+                },
+                CloudflareZoneConfig {
+                    name: "docs".into(),
+                    zone_id: "z2".into(),
+                    api_token: "t".into(),
+                    mode: AccessRuleMode::Block,
+                    rate_limit: None,
+                },
+            ],
+            ..CloudflareConfig::default()
+        })
+    }
+
+    fn alert(rule: &str) -> Alert {
+        Alert {
+            rule: rule.into(),
+            severity: Severity::High,
+            message: "SYN flood".into(),
+            host: "node".into(),
+            detection: "threshold",
+            suppressed_by: None,
+            lineage: Vec::new(),
+            children: Vec::new(),
+            pod: None,
+            evidence: None,
+            annotations: None,
+        }
+    }
+
+    #[test]
+    fn alerts_propose_one_rate_limit_per_listening_zone() {
+        let mitigator = mitigator();
+        assert!(mitigator.on_alert(&alert("fork_storm"), 100).is_empty());
+
+        let actions = mitigator.on_alert(&alert("ddos"), 100);
+        assert_eq!(actions.len(), 1);
+        assert!(matches!(
+            &actions[0],
+            ActionType::CloudflareRateLimit { zone, .. } if zone == "shop"
+        ));
+        // Not again until the proposed rule would have expired
+        assert!(mitigator.on_alert(&alert("ddos"), 200).is_empty());
+        assert_eq!(mitigator.on_alert(&alert("ddos"), 100 + 3600).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn dry_run_creates_nothing() {
+        let mitigator = mitigator();
+        mitigator.block("203.0.113.7", 600).await.unwrap();
+        mitigator.rate_limit("shop", 100, 60, 600).await.unwrap();
+        assert!(mitigator.created.lock().unwrap().is_empty());
+        assert!(mitigator.rate_limit("blog", 100, 60, 600).await.is_err());
+    }
+}
@@ -1,169 +1,475 @@
-// eBPF-based DDoS protection
-use anyhow::Result;
-use dashmap::DashMap;
-use std::net::IpAddr;
-use std::sync::Arc;
-use std::time::{Duration, Instant};
-
-pub struct DDoSShield {
-    /// Track request rates per IP
-    ip_requests: Arc<DashMap<IpAddr, RequestTracker>>,
-    /// Requests per second threshold
-    rate_limit: u32,
-    /// Ban duration
-    ban_duration: Duration,
+//! SYN flood and connection-rate detection
+//!
+//! Connection events are node-wide (pid 0) and report every inbound SYN,
+//! every SYN answered with a syncookie and every completed handshake, with
+//! the remote address. Each `window_secs` the counts are turned into rates.
+//! The node is under attack when SYNs exceed `syn_rate` per second while
+//! fewer than `min_established_ratio` of them complete, or when a single
+//! source exceeds `source_rate` at that completion ratio. The start of an
+//! attack raises a `ddos` alert with the top talkers; the attack clears once
+//! a window passes without either condition. With a block backend
+//! configured, every flooding source is also proposed as a `BlockSource`
+//! action, at most once per block TTL.
+use anyhow::{Context, Result};
+use linnix_ai_ebpf_common::{ConnOp, EventType};
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::broadcast;
+
+use crate::ProcessEvent;
+use crate::alerts::{Alert, AlertSender, Severity};
+use crate::config::DdosConfig;
+use crate::context::ContextStore;
+use crate::enforcement::{ActionType, BlockBackend, EnforcementQueue};
+use crate::utils::now_secs;
+
+pub const RULE_NAME: &str = "ddos";
+
+const AF_INET: u32 = 2;
+const AF_INET6: u32 = 10;
+
+/// Sources tracked per window. A spoofed flood beyond this is still counted
+/// node-wide.
+const MAX_SOURCES: usize = 65_536;
+
+/// Handshake step and remote address of a connection event.
+pub fn decode(event: &ProcessEvent) -> Option<(ConnOp, IpAddr)> {
+    if event.event_type != EventType::Connection as u32 {
+        return None;
+    }
+    let op = match event.aux {
+        x if x == ConnOp::Syn as u32 => ConnOp::Syn,
+        x if x == ConnOp::SynCookie as u32 => ConnOp::SynCookie,
+        x if x == ConnOp::Established as u32 => ConnOp::Established,
+        _ => return None,
+    };
+    // Addresses are raw bytes in memory order, family in the high half of aux2
+    let addr = match event.aux2 >> 16 {
+        AF_INET => IpAddr::V4(Ipv4Addr::from((event.data as u32).to_ne_bytes())),
+        AF_INET6 => {
+            let mut bytes = [0u8; 16];
+            bytes[..8].copy_from_slice(&event.data.to_ne_bytes());
+            bytes[8..].copy_from_slice(&event.data2.to_ne_bytes());
+            Ipv6Addr::from(bytes).to_canonical()
+        }
+        _ => return None,
+    };
+    Some((op, addr))
+}
+
+/// Handshake steps seen from one source, or the whole node, in a window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HandshakeCounts {
+    /// SYNs, including those answered with a syncookie
+    pub syns: u64,
+    pub syn_cookies: u64,
+    pub established: u64,
 }
 
-struct RequestTracker {
-    count: u32,
-    window_start: Instant,
-    banned_until: Option<Instant>,
+impl HandshakeCounts {
+    fn record(&mut self, op: ConnOp) {
+        match op {
+            ConnOp::Syn => self.syns += 1,
+            ConnOp::SynCookie => {
+                self.syns += 1;
+                self.syn_cookies += 1;
+            }
+            ConnOp::Established => self.established += 1,
+        }
+    }
+
+    /// Share of SYNs that completed the handshake.
+    pub fn established_ratio(&self) -> f64 {
+        if self.syns == 0 {
+            return 1.0;
+        }
+        (self.established as f64 / self.syns as f64).min(1.0)
+    }
+}
+
+/// Outcome of one window.
+#[derive(Debug, Default)]
+pub struct WindowVerdict {
+    /// Raised when an attack starts
+    pub alert: Option<Alert>,
+    /// Flooding sources not proposed for blocking within the TTL
+    pub block: Vec<IpAddr>,
 }
 
-impl DDoSShield {
-    pub fn new(rate_limit: u32, ban_minutes: u64) -> Self {
+#[derive(Debug, Default)]
+struct State {
+    node: HandshakeCounts,
+    sources: HashMap<IpAddr, HandshakeCounts>,
+    attack_since: Option<u64>,
+    /// Sources proposed for blocking, until their block expires
+    proposed: HashMap<IpAddr, u64>,
+}
+
+pub struct DdosDetector {
+    window_secs: u64,
+    syn_rate: u64,
+    source_rate: u64,
+    min_established_ratio: f64,
+    top_talkers: usize,
+    block: Option<BlockBackend>,
+    block_ttl_secs: u64,
+    host: String,
+    state: Mutex<State>,
+}
+
+impl DdosDetector {
+    pub fn new(config: &DdosConfig) -> Self {
         Self {
-            ip_requests: Arc::new(DashMap::new()),
-            rate_limit,
-            ban_duration: Duration::from_secs(ban_minutes * 60),
+            window_secs: config.window_secs.max(1),
+            syn_rate: config.syn_rate,
+            source_rate: config.source_rate,
+            min_established_ratio: config.min_established_ratio,
+            top_talkers: config.top_talkers,
+            block: config.block,
+            block_ttl_secs: config.block_ttl_secs.max(1),
+            host: std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown".into()),
+            state: Mutex::new(State::default()),
         }
     }
 
-    /// Check if IP is currently banned
-    pub fn is_banned(&self, ip: IpAddr) -> bool {
-        if let Some(tracker) = self.ip_requests.get(&ip) {
-            if let Some(banned_until) = tracker.banned_until {
-                return Instant::now() < banned_until;
-            }
+    /// Record one handshake step from `addr`.
+    pub fn record(&self, op: ConnOp, addr: IpAddr) {
+        let mut state = self.state.lock().unwrap();
+        state.node.record(op);
+        if state.sources.len() < MAX_SOURCES || state.sources.contains_key(&addr) {
+            state.sources.entry(addr).or_default().record(op);
         }
-        false
-    }
-
-    /// Record a request from an IP
-    pub fn record_request(&self, ip: IpAddr) -> bool {
-        let now = Instant::now();
-        let mut tracker = self.ip_requests.entry(ip).or_insert(RequestTracker {
-            count: 0,
-            window_start: now,
-            banned_until: None,
-        });
-
-        // Reset window if it's been more than 1 second
-        if tracker.window_start.elapsed() > Duration::from_secs(1) {
-            tracker.count = 1;
-            tracker.window_start = now;
-            return true;
+    }
+
+    /// Rate of `count` over the window, per second.
+    fn rate(&self, count: u64) -> f64 {
+        count as f64 / self.window_secs as f64
+    }
+
+    fn hostile(&self, counts: &HandshakeCounts) -> bool {
+        counts.established_ratio() < self.min_established_ratio
+    }
+
+    /// Evaluate the window that just ended and start a new one.
+    pub fn close_window(&self, now: u64) -> WindowVerdict {
+        let mut state = self.state.lock().unwrap();
+        let node = std::mem::take(&mut state.node);
+        let mut talkers: Vec<(IpAddr, HandshakeCounts)> = std::mem::take(&mut state.sources)
+            .into_iter()
+            .filter(|(_, counts)| counts.syns > 0)
+            .collect();
+        talkers.sort_by(|a, b| b.1.syns.cmp(&a.1.syns).then_with(|| a.0.cmp(&b.0)));
+        state.proposed.retain(|_, until| *until > now);
+
+        let flooders: Vec<IpAddr> = talkers
+            .iter()
+            .filter(|(_, counts)| {
+                self.rate(counts.syns) > self.source_rate as f64 && self.hostile(counts)
+            })
+            .map(|(addr, _)| *addr)
+            .collect();
+        let node_flood = self.rate(node.syns) > self.syn_rate as f64 && self.hostile(&node);
+        if !node_flood && flooders.is_empty() {
+            if let Some(since) = state.attack_since.take() {
+                info!(
+                    "[ddos] attack subsided after {}s",
+                    now.saturating_sub(since)
+                );
+            }
+            return WindowVerdict::default();
         }
 
-        tracker.count += 1;
+        let alert = match state.attack_since {
+            Some(_) => None,
+            None => {
+                state.attack_since = Some(now);
+                Some(self.alert(&node, &talkers))
+            }
+        };
+        let mut block = Vec::new();
+        if self.block.is_some() {
+            for addr in flooders {
+                if let Entry::Vacant(slot) = state.proposed.entry(addr) {
+                    slot.insert(now + self.block_ttl_secs);
+                    block.push(addr);
+                }
+            }
+        }
+        WindowVerdict { alert, block }
+    }
 
-        // Check if rate limit exceeded
-        if tracker.count > self.rate_limit {
-            log::warn!("🚨 DDoS detected from {} ({} req/s) - BANNING", ip, tracker.count);
-            tracker.banned_until = Some(now + self.ban_duration);
-            return false;
+    fn alert(&self, node: &HandshakeCounts, talkers: &[(IpAddr, HandshakeCounts)]) -> Alert {
+        let mut message = format!(
+            "SYN flood: {:.0} SYN/s over {}s, {:.0}% completed the handshake",
+            self.rate(node.syns),
+            self.window_secs,
+            node.established_ratio() * 100.0
+        );
+        if node.syn_cookies > 0 {
+            message.push_str(&format!(", {} answered with syncookies", node.syn_cookies));
         }
+        let top = talkers
+            .iter()
+            .take(self.top_talkers)
+            .map(|(addr, counts)| {
+                format!(
+                    "{addr} ({:.0} SYN/s, {} established)",
+                    self.rate(counts.syns),
+                    counts.established
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        if !top.is_empty() {
+            message.push_str(&format!("; top talkers: {top}"));
+        }
+        Alert {
+            rule: RULE_NAME.to_string(),
+            severity: Severity::High,
+            message,
+            host: self.host.clone(),
+            detection: "threshold",
+            suppressed_by: None,
+            lineage: Vec::new(),
+            children: Vec::new(),
+            pod: None,
+            evidence: None,
+            annotations: None,
+        }
+    }
 
-        true
-    }
-
-    /// Ban an IP using iptables
-    pub async fn ban_ip(&self, ip: IpAddr) -> Result<()> {
-        let ip_str = ip.to_string();
-        
-        // Add iptables DROP rule
-        let output = tokio::process::Command::new("iptables")
-            .args(&[
-                "-I", "INPUT",
-                "-s", &ip_str,
-                "-j", "DROP",
-                "-m", "comment",
-                "--comment", &format!("linnix-ddos-ban-{}", chrono::Utc::now().timestamp()),
-            ])
-            .output()
-            .await?;
-
-        if !output.status.success() {
-            anyhow::bail!(
-                "Failed to ban IP {}: {}",
-                ip,
-                String::from_utf8_lossy(&output.stderr)
+    async fn propose_blocks(&self, queue: &EnforcementQueue, addrs: Vec<IpAddr>) {
+        let Some(backend) = self.block else {
+            return;
+        };
+        for addr in addrs {
+            let action = ActionType::BlockSource {
+                addr: addr.to_string(),
+                backend,
+                ttl_secs: self.block_ttl_secs,
+            };
+            let reason = format!(
+                "{addr} exceeded {} SYN/s without completing handshakes",
+                self.source_rate
             );
+            match queue
+                .propose_for_rule(RULE_NAME, action, reason, "ddos".into(), None, false)
+                .await
+            {
+                Ok(id) => info!("[ddos] proposed blocking {addr} ({id})"),
+                Err(e) => warn!("[ddos] not blocking {addr}: {e}"),
+            }
         }
+    }
 
-        log::info!("🛡️  Banned IP {} via iptables", ip);
-
-        // Schedule unban
-        let ip_requests = Arc::clone(&self.ip_requests);
-        let ban_duration = self.ban_duration;
-        tokio::spawn(async move {
-            tokio::time::sleep(ban_duration).await;
-            
-            // Unban via iptables
-            let ip_str = ip.to_string();
-            let output = tokio::process::Command::new("iptables")
-                .args(&["-D", "INPUT", "-s", &ip_str, "-j", "DROP"])
-                .output()
-                .await;
-            
-            match output {
-                Ok(out) if out.status.success() => {
-                    log::info!("✅ Unbanned IP {}", ip);
-                    ip_requests.remove(&ip);
-                }
-                Ok(out) => {
-                    log::error!("Failed to unban IP {}: {}", ip, String::from_utf8_lossy(&out.stderr));
-                }
-                Err(e) => {
-                    log::error!("Failed to unban IP {}: {}", ip, e);
+    pub async fn run(
+        self: Arc<Self>,
+        context: Arc<ContextStore>,
+        tx: Option<AlertSender>,
+        queue: Option<Arc<EnforcementQueue>>,
+    ) {
+        info!(
+            "[ddos] alerting above {} SYN/s node-wide or {} SYN/s per source under {:.0}% completed handshakes",
+            self.syn_rate,
+            self.source_rate,
+            self.min_established_ratio * 100.0
+        );
+        let mut events = context.broadcaster().subscribe();
+        let mut ticker = tokio::time::interval(Duration::from_secs(self.window_secs));
+        // The first tick completes immediately
+        ticker.tick().await;
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(event) => {
+                        if let Some((op, addr)) = decode(&event) {
+                            self.record(op, addr);
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        debug!("[ddos] lagged, skipped {skipped} events");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                _ = ticker.tick() => {
+                    let verdict = self.close_window(now_secs());
+                    if let Some(alert) = verdict.alert {
+                        warn!("[ddos] {}", alert.message);
+                        if let Some(tx) = &tx {
+                            tx.send(alert);
+                        }
+                    }
+                    if let Some(queue) = &queue {
+                        self.propose_blocks(queue, verdict.block).await;
+                    }
                 }
             }
-        });
-
-        Ok(())
-    }
-
-    /// Unban an IP manually
-    pub async fn unban_ip(&self, ip: IpAddr) -> Result<()> {
-        let ip_str = ip.to_string();
-        
-        // Remove iptables rule
-        let output = tokio::process::Command::new("iptables")
-            .args(&[
-                "-D", "INPUT",
-                "-s", &ip_str,
-                "-j", "DROP",
-            ])
-            .output()
-            .await?;
-
-        if !output.status.success() {
-            anyhow::bail!(
-                "Failed to unban IP {}: {}",
-                ip,
-                String::from_utf8_lossy(&output.stderr)
-            );
         }
+    }
+}
+
+/// Start tracking inbound handshakes. Alerts go to `tx` when a rules engine
+/// is loaded; block proposals go to `queue`.
+pub fn spawn(
+    context: Arc<ContextStore>,
+    tx: Option<AlertSender>,
+    queue: Option<Arc<EnforcementQueue>>,
+    config: &DdosConfig,
+) -> Arc<DdosDetector> {
+    let detector = Arc::new(DdosDetector::new(config));
+    tokio::spawn(Arc::clone(&detector).run(context, tx, queue));
+    detector
+}
+
+/// Table holding the sets `BlockBackend::Nftables` adds to.
+const NFT_RULESET: &str = "table inet linnix {
+    set ddos_v4 { type ipv4_addr; flags timeout; }
+    set ddos_v6 { type ipv6_addr; flags timeout; }
+    chain ddos_input {
+        type filter hook input priority -10; policy accept;
+        ip saddr @ddos_v4 drop
+        ip6 saddr @ddos_v6 drop
+    }
+}
+";
+
+async fn nft(args: &[&str]) -> Result<std::process::Output> {
+    tokio::process::Command::new("nft")
+        .args(args)
+        .output()
+        .await
+        .context("failed to run nft")
+}
 
-        log::info!("✅ Unbanned IP {}", ip);
-        self.ip_requests.remove(&ip);
-        Ok(())
+/// Create the `inet linnix` table unless its drop chain already exists.
+async fn ensure_nft_table() -> Result<()> {
+    if nft(&["list", "chain", "inet", "linnix", "ddos_input"])
+        .await?
+        .status
+        .success()
+    {
+        return Ok(());
     }
+    let mut child = tokio::process::Command::new("nft")
+        .args(["-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to run nft")?;
+    child
+        .stdin
+        .take()
+        .context("nft stdin unavailable")?
+        .write_all(NFT_RULESET.as_bytes())
+        .await?;
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "failed to create nftables table: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
 
-    /// Cleanup old entries (run periodically)
-    pub fn cleanup(&self) {
-        let now = Instant::now();
-        self.ip_requests.retain(|_, tracker| {
-            // Remove entries older than 5 minutes
-            tracker.window_start.elapsed() < Duration::from_secs(300)
-        });
+/// Drop traffic from `addr` for `ttl_secs`; nftables expires the element.
+pub async fn block_nftables(addr: &str, ttl_secs: u64) -> Result<()> {
+    let ip: IpAddr = addr
+        .parse()
+        .with_context(|| format!("'{addr}' is not an IP address"))?;
+    ensure_nft_table().await?;
+    let set = if ip.is_ipv4() { "ddos_v4" } else { "ddos_v6" };
+    let element = format!("{{ {ip} timeout {ttl_secs}s }}");
+    let output = nft(&["add", "element", "inet", "linnix", set, element.as_str()]).await?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "failed to block {ip}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
     }
+    info!("[ddos] blocked {ip} via nftables for {ttl_secs}s");
+    Ok(())
 }
 
-/// Extract source IP from network packet metadata
-/// (This would integrate with eBPF network probes)
-pub fn extract_source_ip(/* eBPF packet data */) -> Option<IpAddr> {
-    // TODO: Implement eBPF network probe to capture packet headers
-    // For now, we can parse from nginx logs or use existing network monitoring
-    None
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector(block: Option<BlockBackend>) -> DdosDetector {
+        DdosDetector::new(&DdosConfig {
+            window_secs: 10,
+            syn_rate: 100,
+            source_rate: 20,
+            block,
+            ..DdosConfig::default()
+        })
+    }
+
+    fn handshakes(detector: &DdosDetector, addr: &str, syns: u64, established: u64) {
+        let addr: IpAddr = addr.parse().unwrap();
+        for _ in 0..syns {
+            detector.record(ConnOp::Syn, addr);
+        }
+        for _ in 0..established {
+            detector.record(ConnOp::Established, addr);
+        }
+    }
+
+    #[test]
+    fn flooding_source_alerts_once_and_is_proposed_once() {
+        let detector = detector(Some(BlockBackend::Nftables));
+        handshakes(&detector, "203.0.113.7", 500, 0);
+        handshakes(&detector, "198.51.100.1", 50, 50);
+        let verdict = detector.close_window(1_000);
+        let alert = verdict.alert.expect("attack starts");
+        assert_eq!(alert.rule, RULE_NAME);
+        assert!(
+            alert
+                .message
+                .contains("top talkers: 203.0.113.7 (50 SYN/s, 0 established)")
+        );
+        assert_eq!(
+            verdict.block,
+            vec!["203.0.113.7".parse::<IpAddr>().unwrap()]
+        );
+
+        // Still flooding: no new alert, no second proposal within the TTL
+        handshakes(&detector, "203.0.113.7", 500, 0);
+        let verdict = detector.close_window(1_010);
+        assert!(verdict.alert.is_none());
+        assert!(verdict.block.is_empty());
+
+        // A quiet window clears the attack
+        assert!(detector.close_window(1_020).alert.is_none());
+        handshakes(&detector, "203.0.113.7", 500, 0);
+        assert!(detector.close_window(1_030).alert.is_some());
+    }
+
+    #[test]
+    fn busy_clients_completing_handshakes_are_not_flooders() {
+        let detector = detector(Some(BlockBackend::Nftables));
+        handshakes(&detector, "198.51.100.1", 2_000, 1_900);
+        let verdict = detector.close_window(1_000);
+        assert!(verdict.alert.is_none());
+        assert!(verdict.block.is_empty());
+    }
+
+    #[test]
+    fn spoofed_flood_alerts_without_blocking() {
+        let detector = detector(Some(BlockBackend::Nftables));
+        for i in 0..2_000u32 {
+            detector.record(ConnOp::SynCookie, IpAddr::V4(Ipv4Addr::from(i)));
+        }
+        let verdict = detector.close_window(1_000);
+        let alert = verdict.alert.expect("node-wide flood");
+        assert!(alert.message.contains("2000 answered with syncookies"));
+        assert!(verdict.block.is_empty());
+    }
 }
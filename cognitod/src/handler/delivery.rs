@@ -0,0 +1,149 @@
+//! Delivery high-water marks of at-least-once exporters.
+//!
+//! Events read from the sequencer carry their ticket. An exporter records,
+//! under its own key, the ticket below which everything it was handed was
+//! delivered downstream; the marks are checkpointed with the sequencer
+//! cursor (see `runtime::cursor`). After a restart the consumer resumes at
+//! the lowest mark, so events an exporter had queued but not delivered are
+//! read again, and each exporter skips the tickets below its restored mark.
+//!
+//! Events the exporter does not send (filtered out, or dropped when its
+//! queue is full) count as delivered once nothing queued before them is
+//! outstanding, so a quiet exporter does not hold the resume point back.
+//! Like a dropped event, a batch given up after its retries is not read
+//! again.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Per-handler delivery high-water marks.
+#[derive(Debug, Default)]
+pub struct DeliveryMarks {
+    /// Marks restored at startup
+    restored: BTreeMap<String, u64>,
+    current: Mutex<BTreeMap<String, u64>>,
+}
+
+impl DeliveryMarks {
+    /// Marks of exporters no longer configured are not saved again, so
+    /// they stop holding the resume point back.
+    pub fn new(restored: BTreeMap<String, u64>) -> Self {
+        Self {
+            restored,
+            current: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Carry `handler`'s restored mark into the next checkpoint.
+    fn register(&self, handler: &str) {
+        if let Some(&mark) = self.restored.get(handler) {
+            self.delivered(handler, mark);
+        }
+    }
+
+    /// Whether `handler` still has to deliver `ticket`; false for tickets
+    /// it delivered before the restart.
+    pub fn pending(&self, handler: &str, ticket: u64) -> bool {
+        self.restored
+            .get(handler)
+            .is_none_or(|&mark| ticket >= mark)
+    }
+
+    /// Record that `handler` delivered every ticket below `next`.
+    pub fn delivered(&self, handler: &str, next: u64) {
+        let mut current = self.current.lock().unwrap();
+        let mark = current.entry(handler.to_string()).or_default();
+        *mark = (*mark).max(next);
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, u64> {
+        self.current.lock().unwrap().clone()
+    }
+}
+
+/// One exporter's mark. Events without a ticket (read from the perf
+/// buffers) are always delivered and never move it.
+#[derive(Debug)]
+pub struct DeliveryMark {
+    marks: Arc<DeliveryMarks>,
+    key: String,
+    /// Events queued and not yet delivered or given up
+    in_flight: AtomicU64,
+}
+
+impl DeliveryMark {
+    pub fn new(marks: Arc<DeliveryMarks>, key: impl Into<String>) -> Self {
+        let key = key.into();
+        marks.register(&key);
+        Self {
+            marks,
+            key,
+            in_flight: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether the event at `ticket` still has to be delivered.
+    pub fn pending(&self, ticket: Option<u64>) -> bool {
+        ticket.is_none_or(|ticket| self.marks.pending(&self.key, ticket))
+    }
+
+    /// The event at `ticket` was delivered, after everything before it.
+    pub fn delivered(&self, ticket: Option<u64>) {
+        if let Some(ticket) = ticket {
+            self.marks.delivered(&self.key, ticket + 1);
+        }
+    }
+
+    /// The event at `ticket` is not sent.
+    pub fn skipped(&self, ticket: Option<u64>) {
+        if self.in_flight.load(Ordering::Acquire) == 0 {
+            self.delivered(ticket);
+        }
+    }
+
+    /// An event was queued for delivery.
+    pub fn queued(&self) {
+        self.in_flight.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// `count` queued events, the last at `last`, were delivered or given up.
+    pub fn settled(&self, count: usize, last: Option<u64>) {
+        self.delivered(last);
+        self.in_flight.fetch_sub(count as u64, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_delivered_tickets_and_holds_the_mark_behind_queued_events() {
+        let marks = Arc::new(DeliveryMarks::new(BTreeMap::from([
+            ("jsonl".to_string(), 40),
+            ("webhook:removed".to_string(), 3),
+        ])));
+        assert!(!marks.pending("jsonl", 39));
+        assert!(marks.pending("jsonl", 40));
+        assert!(marks.pending("webhook", 0));
+        marks.delivered("jsonl", 45);
+        marks.delivered("jsonl", 41);
+        assert_eq!(marks.snapshot()["jsonl"], 45);
+
+        let webhook = DeliveryMark::new(Arc::clone(&marks), "webhook");
+        assert!(webhook.pending(None));
+        webhook.skipped(Some(9));
+        assert_eq!(marks.snapshot()["webhook"], 10);
+        // 10 and 11 are queued; 12 is filtered out while they are in flight
+        webhook.queued();
+        webhook.queued();
+        webhook.skipped(Some(12));
+        assert_eq!(marks.snapshot()["webhook"], 10);
+        webhook.settled(2, Some(11));
+        assert_eq!(marks.snapshot()["webhook"], 12);
+        webhook.skipped(Some(13));
+        assert_eq!(marks.snapshot()["webhook"], 14);
+        assert!(!marks.snapshot().contains_key("webhook:removed"));
+    }
+}
@@ -0,0 +1,740 @@
+//! JSONL event log with rotation, compression and retention
+//!
+//! `jsonl:<path>[?options]` appends events and snapshots to `path`, one JSON
+//! object per line. Options are `&`-separated:
+//!
+//! - `rotate=100MB` starts a new file once the current one would exceed the
+//!   size, `rotate_every=1h` once it is that old (sizes take K, M and G
+//!   suffixes in powers of 1024, durations s, m, h and d)
+//! - `keep=10` and `max_total=1GB` bound the number and combined size of
+//!   rotated segments; the oldest are deleted first
+//! - `compress=zstd` compresses rotated segments in the background
+//! - `fsync=rotate` (default) syncs a segment before it is rotated, `always`
+//!   after every line, `never` leaves it to the kernel, and a duration syncs
+//!   at most that often
+//!
+//! Rotated segments are named `<path>.<UTC timestamp>` (plus `.zst`). A
+//! segment is compressed to a temporary file that is renamed into place
+//! before the original is removed, so a crash leaves either segment intact;
+//! leftovers are cleaned up the next time the file is opened.
+//!
+//! Event timestamps (`ts_ns`) come from the host's monotonic clock, which
+//! means nothing on another host. Each segment therefore starts with a
+//! `{"clock_anchor": {...}}` line pairing that clock with wall-clock time,
+//! repeated every `ANCHOR_INTERVAL` so a slewed clock is followed;
+//! `linnix-cli merge` uses them to put recordings from several nodes on one
+//! timeline.
+//!
+//! With a sequencer checkpoint, each written event advances the handler's
+//! delivery mark (`jsonl:<path>`), and events written before a restart are
+//! not appended again; see `delivery`.
+
+use anyhow::{Context, anyhow, bail};
+use async_trait::async_trait;
+use serde::Serialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::Handler;
+use super::delivery::{DeliveryMark, DeliveryMarks};
+use crate::filter::EventMatcher;
+use crate::metrics::latency::monotonic_ns;
+use crate::{ProcessEvent, types::SystemSnapshot};
+
+const ZSTD_LEVEL: i32 = 3;
+const COMPRESSED_EXT: &str = "zst";
+const TMP_EXT: &str = "tmp";
+/// Longest gap between clock anchors within a segment.
+const ANCHOR_INTERVAL: Duration = Duration::from_secs(60);
+
+/// When written data is flushed to stable storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsyncPolicy {
+    Never,
+    /// Before a segment is rotated (and after it is compressed).
+    Rotate,
+    Always,
+    /// At most once per interval, on the next write.
+    Every(Duration),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonlOptions {
+    pub rotate_bytes: Option<u64>,
+    pub rotate_every: Option<Duration>,
+    /// Rotated segments to retain.
+    pub keep: Option<usize>,
+    /// Combined size of rotated segments to retain.
+    pub max_total_bytes: Option<u64>,
+    pub compress: bool,
+    pub fsync: FsyncPolicy,
+}
+
+impl Default for JsonlOptions {
+    fn default() -> Self {
+        Self {
+            rotate_bytes: None,
+            rotate_every: None,
+            keep: None,
+            max_total_bytes: None,
+            compress: false,
+            fsync: FsyncPolicy::Rotate,
+        }
+    }
+}
+
+/// Split a handler spec into the file path and its options.
+pub fn parse_spec(spec: &str) -> anyhow::Result<(PathBuf, JsonlOptions)> {
+    let (path, query) = spec.split_once('?').unwrap_or((spec, ""));
+    if path.is_empty() {
+        bail!("jsonl handler needs a path");
+    }
+    let mut options = JsonlOptions::default();
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = pair
+            .split_once('=')
+            .ok_or_else(|| anyhow!("jsonl option {pair:?} needs a value"))?;
+        match key {
+            "rotate" => options.rotate_bytes = Some(parse_size(value)?),
+            "rotate_every" => options.rotate_every = Some(parse_duration(value)?),
+            "keep" => options.keep = Some(value.parse().with_context(|| format!("keep={value}"))?),
+            "max_total" => options.max_total_bytes = Some(parse_size(value)?),
+            "compress" => {
+                options.compress = match value {
+                    "zstd" => true,
+                    "none" => false,
+                    other => bail!("unsupported jsonl compression {other:?}"),
+                }
+            }
+            "fsync" => {
+                options.fsync = match value {
+                    "never" => FsyncPolicy::Never,
+                    "rotate" => FsyncPolicy::Rotate,
+                    "always" => FsyncPolicy::Always,
+                    interval => FsyncPolicy::Every(parse_duration(interval)?),
+                }
+            }
+            other => bail!("unknown jsonl option {other:?}"),
+        }
+    }
+    Ok((PathBuf::from(path), options))
+}
+
+fn parse_size(value: &str) -> anyhow::Result<u64> {
+    let upper = value.to_ascii_uppercase();
+    let trimmed = upper
+        .strip_suffix("IB")
+        .or_else(|| upper.strip_suffix('B'))
+        .unwrap_or(&upper);
+    let (digits, shift) = match trimmed.chars().last() {
+        Some('K') => (&trimmed[..trimmed.len() - 1], 10),
+        Some('M') => (&trimmed[..trimmed.len() - 1], 20),
+        Some('G') => (&trimmed[..trimmed.len() - 1], 30),
+        _ => (trimmed, 0),
+    };
+    let n: u64 = digits
+        .trim()
+        .parse()
+        .with_context(|| format!("invalid size {value:?}"))?;
+    n.checked_shl(shift)
+        .filter(|bytes| *bytes > 0 && bytes >> shift == n)
+        .ok_or_else(|| anyhow!("size {value:?} out of range"))
+}
+
+fn parse_duration(value: &str) -> anyhow::Result<Duration> {
+    let (digits, unit) = value.split_at(
+        value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len()),
+    );
+    let n: u64 = digits
+        .parse()
+        .with_context(|| format!("invalid duration {value:?}"))?;
+    let secs = match unit {
+        "" | "s" => n,
+        "m" => n.saturating_mul(60),
+        "h" => n.saturating_mul(3600),
+        "d" => n.saturating_mul(86_400),
+        other => bail!("unknown duration unit {other:?} in {value:?}"),
+    };
+    if secs == 0 {
+        bail!("duration {value:?} must be positive");
+    }
+    Ok(Duration::from_secs(secs))
+}
+
+/// Append-only file that rotates itself according to `JsonlOptions`.
+pub struct RotatingFile {
+    path: PathBuf,
+    options: JsonlOptions,
+    file: File,
+    written: u64,
+    opened_at: Instant,
+    last_sync: Instant,
+    compressor: Option<JoinHandle<()>>,
+}
+
+impl RotatingFile {
+    pub fn open(path: PathBuf, options: JsonlOptions) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        let now = Instant::now();
+        let mut rotating = Self {
+            path,
+            options,
+            file,
+            written,
+            opened_at: now,
+            last_sync: now,
+            compressor: None,
+        };
+        rotating.recover()?;
+        Ok(rotating)
+    }
+
+    pub fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        if self.should_rotate(line.len() as u64) {
+            self.rotate()?;
+        }
+        self.file.write_all(line)?;
+        self.written += line.len() as u64;
+        match self.options.fsync {
+            FsyncPolicy::Always => self.file.sync_data()?,
+            FsyncPolicy::Every(interval) if self.last_sync.elapsed() >= interval => {
+                self.file.sync_data()?;
+                self.last_sync = Instant::now();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Whether a line of `incoming` bytes would be the first of a segment.
+    pub fn starts_segment(&self, incoming: u64) -> bool {
+        self.written == 0 || self.should_rotate(incoming)
+    }
+
+    fn should_rotate(&self, incoming: u64) -> bool {
+        if self.written == 0 {
+            return false;
+        }
+        self.options
+            .rotate_bytes
+            .is_some_and(|max| self.written + incoming > max)
+            || self
+                .options
+                .rotate_every
+                .is_some_and(|every| self.opened_at.elapsed() >= every)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let sync = self.options.fsync != FsyncPolicy::Never;
+        if sync {
+            self.file.sync_all()?;
+        }
+        let segment = self.next_segment_path();
+        fs::rename(&self.path, &segment)?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.written = 0;
+        self.opened_at = Instant::now();
+        if sync {
+            sync_dir(&self.path)?;
+        }
+        log::debug!(
+            "[jsonl] rotated {} to {}",
+            self.path.display(),
+            segment.display()
+        );
+
+        if self.options.compress {
+            self.compress_in_background(vec![segment]);
+        } else {
+            prune(&self.path, &self.options);
+        }
+        Ok(())
+    }
+
+    /// Remove interrupted compressions and compress segments left over from
+    /// a previous run.
+    fn recover(&mut self) -> io::Result<()> {
+        let mut pending = Vec::new();
+        for segment in segments(&self.path)? {
+            let name = segment.path.to_string_lossy();
+            if segment.compressed {
+                continue;
+            }
+            let compressed = PathBuf::from(format!("{name}.{COMPRESSED_EXT}"));
+            let tmp = PathBuf::from(format!("{name}.{COMPRESSED_EXT}.{TMP_EXT}"));
+            let _ = fs::remove_file(&tmp);
+            if compressed.exists() {
+                // Crashed after the rename, before removing the original
+                fs::remove_file(&segment.path)?;
+            } else if self.options.compress {
+                pending.push(segment.path);
+            }
+        }
+        if pending.is_empty() {
+            prune(&self.path, &self.options);
+        } else {
+            self.compress_in_background(pending);
+        }
+        Ok(())
+    }
+
+    /// Compress `segments` on a helper thread, then apply retention. Waits
+    /// for the previous compression so at most one runs at a time.
+    fn compress_in_background(&mut self, segments: Vec<PathBuf>) {
+        if let Some(previous) = self.compressor.take() {
+            let _ = previous.join();
+        }
+        let path = self.path.clone();
+        let options = self.options.clone();
+        self.compressor = Some(std::thread::spawn(move || {
+            let sync = options.fsync != FsyncPolicy::Never;
+            for segment in segments {
+                if let Err(e) = compress_segment(&segment, sync) {
+                    log::warn!("[jsonl] failed to compress {}: {e}", segment.display());
+                }
+            }
+            prune(&path, &options);
+        }));
+    }
+
+    fn next_segment_path(&self) -> PathBuf {
+        let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%SZ");
+        let base = format!("{}.{stamp}", self.path.display());
+        let mut candidate = PathBuf::from(&base);
+        let mut n = 1;
+        while candidate.exists()
+            || PathBuf::from(format!("{}.{COMPRESSED_EXT}", candidate.display())).exists()
+        {
+            candidate = PathBuf::from(format!("{base}-{n}"));
+            n += 1;
+        }
+        candidate
+    }
+
+    #[cfg(test)]
+    fn wait_for_compression(&mut self) {
+        if let Some(handle) = self.compressor.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for RotatingFile {
+    fn drop(&mut self) {
+        if let Some(handle) = self.compressor.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+struct Segment {
+    path: PathBuf,
+    /// Timestamp (and collision counter) the segment was rotated at.
+    stamp: String,
+    compressed: bool,
+    bytes: u64,
+}
+
+/// Rotated segments of `path`, oldest first.
+fn segments(path: &Path) -> io::Result<Vec<Segment>> {
+    let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+        return Ok(Vec::new());
+    };
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let prefix = format!("{file_name}.");
+    let mut found = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(rest) = name.to_str().and_then(|n| n.strip_prefix(&prefix)) else {
+            continue;
+        };
+        let (stamp, compressed) = match rest.strip_suffix(&format!(".{COMPRESSED_EXT}")) {
+            Some(stamp) => (stamp, true),
+            None => (rest, false),
+        };
+        if !is_segment_stamp(stamp) {
+            continue;
+        }
+        found.push(Segment {
+            path: entry.path(),
+            stamp: stamp.to_string(),
+            compressed,
+            bytes: entry.metadata().map(|m| m.len()).unwrap_or(0),
+        });
+    }
+    found.sort_by(|a, b| a.stamp.cmp(&b.stamp));
+    Ok(found)
+}
+
+/// `YYYYMMDDTHHMMSSZ`, optionally followed by `-<n>`.
+fn is_segment_stamp(stamp: &str) -> bool {
+    let (time, counter) = stamp.split_once('-').unwrap_or((stamp, "0"));
+    let bytes = time.as_bytes();
+    bytes.len() == 16
+        && bytes[8] == b'T'
+        && bytes[15] == b'Z'
+        && bytes[..8]
+            .iter()
+            .chain(&bytes[9..15])
+            .all(u8::is_ascii_digit)
+        && !counter.is_empty()
+        && counter.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Delete the oldest segments beyond the `keep` and `max_total` limits.
+fn prune(path: &Path, options: &JsonlOptions) {
+    if options.keep.is_none() && options.max_total_bytes.is_none() {
+        return;
+    }
+    let segments = match segments(path) {
+        Ok(segments) => segments,
+        Err(e) => {
+            log::warn!("[jsonl] failed to list segments of {}: {e}", path.display());
+            return;
+        }
+    };
+    let mut count = segments.len();
+    let mut total: u64 = segments.iter().map(|s| s.bytes).sum();
+    for segment in segments {
+        let over_count = options.keep.is_some_and(|keep| count > keep);
+        let over_size = options.max_total_bytes.is_some_and(|max| total > max);
+        if !over_count && !over_size {
+            break;
+        }
+        match fs::remove_file(&segment.path) {
+            Ok(()) => log::debug!("[jsonl] removed segment {}", segment.path.display()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => {
+                log::warn!("[jsonl] failed to remove {}: {e}", segment.path.display());
+                continue;
+            }
+        }
+        count -= 1;
+        total -= segment.bytes;
+    }
+}
+
+/// Compress `segment` to `<segment>.zst` and remove the original.
+fn compress_segment(segment: &Path, sync: bool) -> io::Result<PathBuf> {
+    let target = PathBuf::from(format!("{}.{COMPRESSED_EXT}", segment.display()));
+    let tmp = PathBuf::from(format!("{}.{TMP_EXT}", target.display()));
+    let mut input = File::open(segment)?;
+    let mut encoder = zstd::Encoder::new(File::create(&tmp)?, ZSTD_LEVEL)?;
+    io::copy(&mut input, &mut encoder)?;
+    let out = encoder.finish()?;
+    if sync {
+        out.sync_all()?;
+    }
+    fs::rename(&tmp, &target)?;
+    if sync {
+        sync_dir(&target)?;
+    }
+    fs::remove_file(segment)?;
+    Ok(target)
+}
+
+/// fsync the directory holding `path` so renames in it are durable.
+fn sync_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => File::open(dir)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+/// The monotonic clock of `ts_ns` and wall-clock time, read together.
+#[derive(Serialize)]
+struct ClockAnchor<'a> {
+    host: &'a str,
+    monotonic_ns: u64,
+    wall_ns: u64,
+}
+
+#[derive(Serialize)]
+struct AnchorLine<'a> {
+    clock_anchor: ClockAnchor<'a>,
+}
+
+fn anchor_line(host: &str) -> Option<Vec<u8>> {
+    let monotonic_ns = monotonic_ns()?;
+    let wall_ns = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_nanos() as u64;
+    let mut line = serde_json::to_vec(&AnchorLine {
+        clock_anchor: ClockAnchor {
+            host,
+            monotonic_ns,
+            wall_ns,
+        },
+    })
+    .ok()?;
+    line.push(b'\n');
+    Some(line)
+}
+
+struct Output {
+    file: RotatingFile,
+    anchored_at: Option<Instant>,
+}
+
+impl Output {
+    fn write_line(&mut self, line: &[u8], host: &str) -> io::Result<()> {
+        let due = self
+            .anchored_at
+            .is_none_or(|at| at.elapsed() >= ANCHOR_INTERVAL);
+        if (due || self.file.starts_segment(line.len() as u64))
+            && let Some(mut anchor) = anchor_line(host)
+        {
+            // Written as one so a rotation cannot separate the two
+            anchor.extend_from_slice(line);
+            self.file.write_line(&anchor)?;
+            self.anchored_at = Some(Instant::now());
+            return Ok(());
+        }
+        self.file.write_line(line)
+    }
+}
+
+pub struct JsonlHandler {
+    writer: Mutex<Output>,
+    filter: EventMatcher,
+    host: String,
+    delivery: Option<DeliveryMark>,
+}
+
+impl JsonlHandler {
+    /// Open the file named by a `<path>[?options]` spec.
+    pub async fn new(spec: &str) -> io::Result<Self> {
+        let (path, options) = parse_spec(spec)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("{e:#}")))?;
+        let writer = tokio::task::spawn_blocking(move || RotatingFile::open(path, options))
+            .await
+            .map_err(io::Error::other)??;
+        let host = hostname::get()
+            .ok()
+            .and_then(|h| h.into_string().ok())
+            .unwrap_or_else(|| "unknown".to_string());
+        Ok(Self {
+            writer: Mutex::new(Output {
+                file: writer,
+                anchored_at: None,
+            }),
+            filter: EventMatcher::all(),
+            host,
+            delivery: None,
+        })
+    }
+
+    /// Record only events accepted by `filter`.
+    pub fn with_filter(mut self, filter: EventMatcher) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Record this file's delivery mark in `marks`.
+    pub fn with_delivery_marks(mut self, marks: Arc<DeliveryMarks>) -> Self {
+        let key = format!(
+            "jsonl:{}",
+            self.writer.get_mut().unwrap().file.path.display()
+        );
+        self.delivery = Some(DeliveryMark::new(marks, key));
+        self
+    }
+
+    /// Whether `value` was written.
+    fn append<T: Serialize>(&self, value: &T) -> bool {
+        let Ok(mut line) = serde_json::to_vec(value) else {
+            return false;
+        };
+        line.push(b'\n');
+        let mut writer = self.writer.lock().unwrap();
+        match writer.write_line(&line, &self.host) {
+            Ok(()) => true,
+            Err(e) => {
+                log::debug!(
+                    "[jsonl] write to {} failed: {e}",
+                    writer.file.path.display()
+                );
+                false
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Handler for JsonlHandler {
+    fn name(&self) -> &'static str {
+        "jsonl"
+    }
+
+    async fn on_event(&self, event: &ProcessEvent) {
+        let Some(delivery) = &self.delivery else {
+            if self.filter.matches(event) {
+                self.append(event);
+            }
+            return;
+        };
+        if !delivery.pending(event.ticket) {
+            return;
+        }
+        if !self.filter.matches(event) {
+            delivery.skipped(event.ticket);
+        } else if self.append(event) {
+            delivery.delivered(event.ticket);
+        }
+    }
+
+    async fn on_snapshot(&self, snapshot: &SystemSnapshot) {
+        self.append(snapshot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_compresses_and_prunes_segments() {
+        let (_, options) = parse_spec("x?rotate=1K&keep=2&compress=zstd&fsync=30s").unwrap();
+        assert_eq!(options.rotate_bytes, Some(1024));
+        assert_eq!(options.fsync, FsyncPolicy::Every(Duration::from_secs(30)));
+        assert!(parse_spec("x?rotate=10XB").is_err());
+        assert!(parse_spec("x?retain=3").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        // Leftovers of a crash mid-compression
+        let stale = dir.path().join("events.jsonl.20240101T000000Z");
+        fs::write(&stale, b"old\n").unwrap();
+        fs::write(
+            dir.path().join("events.jsonl.20240101T000000Z.zst.tmp"),
+            b"",
+        )
+        .unwrap();
+
+        let mut file = RotatingFile::open(path.clone(), options).unwrap();
+        file.wait_for_compression();
+        assert!(!stale.exists());
+        assert!(
+            !dir.path()
+                .join("events.jsonl.20240101T000000Z.zst.tmp")
+                .exists()
+        );
+
+        let mut line = vec![b'x'; 511];
+        line.push(b'\n');
+        // Two lines per segment; six lines rotate twice within a second
+        for _ in 0..6 {
+            file.write_line(&line).unwrap();
+        }
+        file.wait_for_compression();
+
+        let kept = segments(&path).unwrap();
+        assert_eq!(kept.len(), 2, "keep=2 drops the oldest segment");
+        assert!(kept.iter().all(|s| s.compressed));
+        assert!(kept.iter().all(|s| !s.stamp.starts_with("2024")));
+        let restored = zstd::decode_all(File::open(&kept[1].path).unwrap()).unwrap();
+        assert_eq!(restored.len(), 1024);
+        assert_eq!(fs::metadata(&path).unwrap().len(), 1024);
+    }
+
+    #[tokio::test]
+    async fn every_segment_starts_with_a_clock_anchor() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        let handler = JsonlHandler::new(&format!("{}?rotate=1K", path.display()))
+            .await
+            .unwrap();
+        for timestamp in 0..20 {
+            handler
+                .on_snapshot(&SystemSnapshot {
+                    timestamp,
+                    ..Default::default()
+                })
+                .await;
+        }
+        drop(handler);
+
+        let mut files: Vec<PathBuf> = segments(&path)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.path)
+            .collect();
+        assert!(!files.is_empty(), "20 snapshots exceed 1K");
+        files.push(path);
+        for file in files {
+            let text = fs::read_to_string(&file).unwrap();
+            let first: serde_json::Value =
+                serde_json::from_str(text.lines().next().unwrap()).unwrap();
+            let anchor = &first["clock_anchor"];
+            assert!(anchor["monotonic_ns"].as_u64().unwrap() > 0);
+            assert!(anchor["wall_ns"].as_u64().unwrap() > 1_600_000_000_000_000_000);
+            assert_eq!(
+                text.matches("clock_anchor").count(),
+                1,
+                "{}",
+                file.display()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn skips_events_written_before_a_restart() {
+        use crate::{PERCENT_MILLI_UNKNOWN, ProcessEventWire};
+        use std::collections::BTreeMap;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        let key = format!("jsonl:{}", path.display());
+        let marks = Arc::new(DeliveryMarks::new(BTreeMap::from([(key.clone(), 5)])));
+        let handler = JsonlHandler::new(&path.display().to_string())
+            .await
+            .unwrap()
+            .with_delivery_marks(Arc::clone(&marks));
+        for ticket in 3..8 {
+            let event = ProcessEvent::new(ProcessEventWire {
+                pid: ticket as u32,
+                ppid: 1,
+                uid: 0,
+                gid: 0,
+                event_type: 0,
+                ts_ns: 0,
+                seq: 0,
+                comm: [0; 16],
+                exit_time_ns: 0,
+                cpu_pct_milli: PERCENT_MILLI_UNKNOWN,
+                mem_pct_milli: PERCENT_MILLI_UNKNOWN,
+                data: 0,
+                data2: 0,
+                aux: 0,
+                aux2: 0,
+                cgroup_id: 0,
+            });
+            handler.on_event(&event.with_ticket(Some(ticket))).await;
+        }
+        drop(handler);
+
+        let text = fs::read_to_string(&path).unwrap();
+        let pids: Vec<u64> = text
+            .lines()
+            .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+            .filter_map(|value| value["pid"].as_u64())
+            .collect();
+        assert_eq!(pids, [5, 6, 7]);
+        assert_eq!(marks.snapshot()[&key], 8);
+    }
+}
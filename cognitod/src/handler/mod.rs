@@ -1,17 +1,23 @@
 #[cfg(test)]
 use crate::ProcessEventWire;
+use crate::config::OrderingConfig;
+use crate::metrics::Metrics;
 use crate::{ProcessEvent, types::SystemSnapshot};
 use async_trait::async_trait;
 use std::sync::Arc;
-use tokio::fs::OpenOptions;
-use tokio::io::AsyncWriteExt;
-use tokio::sync::Mutex;
+use tokio::sync::mpsc;
 
 pub mod docker;
 pub mod cloudflare;
 pub mod warmth;
 pub mod ddos;
+pub mod delivery;
 pub mod discord;
+pub mod jsonl;
+pub mod reorder;
+pub mod webhook;
+
+pub use jsonl::JsonlHandler;
 
 #[async_trait]
 pub trait Handler: Send + Sync {
@@ -19,10 +25,17 @@ pub trait Handler: Send + Sync {
     fn name(&self) -> &'static str;
     async fn on_event(&self, event: &ProcessEvent);
     async fn on_snapshot(&self, snapshot: &SystemSnapshot);
+    /// Whether the handler needs events in kernel timestamp order. Such
+    /// handlers go through the reorder stage when `[ordering]` is enabled.
+    fn ordered(&self) -> bool {
+        false
+    }
 }
 
 pub struct HandlerList {
     handlers: Vec<Arc<dyn Handler>>,
+    /// Reorder stage feeding the ordered handlers, when enabled
+    ordered: Option<mpsc::Sender<ProcessEvent>>,
 }
 
 impl Default for HandlerList {
@@ -35,6 +48,7 @@ impl HandlerList {
     pub fn new() -> Self {
         Self {
             handlers: Vec::new(),
+            ordered: None,
         }
     }
 
@@ -42,10 +56,36 @@ impl HandlerList {
         self.handlers.push(Arc::new(handler));
     }
 
+    /// Register a handler that is also used outside the event loop.
+    pub fn register_shared(&mut self, handler: Arc<dyn Handler>) {
+        self.handlers.push(handler);
+    }
+
+    /// Route the ordered handlers registered so far through a reorder
+    /// stage. Needs a Tokio runtime.
+    pub fn enable_ordering(&mut self, config: &OrderingConfig, metrics: Arc<Metrics>) {
+        let ordered: Vec<_> = self
+            .handlers
+            .iter()
+            .filter(|h| h.ordered())
+            .cloned()
+            .collect();
+        if !ordered.is_empty() {
+            self.ordered = Some(reorder::spawn(ordered, config, metrics));
+        }
+    }
+
     pub async fn on_event(&self, event: &ProcessEvent) {
         for h in &self.handlers {
+            if self.ordered.is_some() && h.ordered() {
+                continue;
+            }
             h.on_event(event).await;
         }
+        if let Some(tx) = &self.ordered {
+            // Waits while the stage is backed up rather than drop events
+            let _ = tx.send(event.clone()).await;
+        }
     }
 
     pub async fn on_snapshot(&self, snapshot: &SystemSnapshot) {
@@ -55,50 +95,85 @@ impl HandlerList {
     }
 }
 
-pub struct JsonlHandler {
-    file: Arc<Mutex<tokio::fs::File>>,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PERCENT_MILLI_UNKNOWN;
+    use crate::metrics::latency::monotonic_ns;
+    use std::sync::Mutex;
 
-impl JsonlHandler {
-    pub async fn new(path: &str) -> std::io::Result<Self> {
-        let file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(path)
-            .await?;
-        Ok(Self {
-            file: Arc::new(Mutex::new(file)),
-        })
+    struct Recorder {
+        ordered: bool,
+        seen: Mutex<Vec<u32>>,
     }
-}
 
-#[async_trait]
-impl Handler for JsonlHandler {
-    fn name(&self) -> &'static str {
-        "jsonl"
-    }
+    #[async_trait]
+    impl Handler for Recorder {
+        fn name(&self) -> &'static str {
+            "recorder"
+        }
 
-    async fn on_event(&self, event: &ProcessEvent) {
-        if let Ok(json) = serde_json::to_string(event) {
-            let mut f = self.file.lock().await;
-            let _ = f.write_all(json.as_bytes()).await;
-            let _ = f.write_all(b"\n").await;
+        async fn on_event(&self, event: &ProcessEvent) {
+            self.seen.lock().unwrap().push(event.pid);
         }
-    }
 
-    async fn on_snapshot(&self, snapshot: &SystemSnapshot) {
-        if let Ok(json) = serde_json::to_string(snapshot) {
-            let mut f = self.file.lock().await;
-            let _ = f.write_all(json.as_bytes()).await;
-            let _ = f.write_all(b"\n").await;
+        async fn on_snapshot(&self, _snapshot: &SystemSnapshot) {}
+
+        fn ordered(&self) -> bool {
+            self.ordered
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::PERCENT_MILLI_UNKNOWN;
+    #[tokio::test]
+    async fn ordered_handlers_see_events_by_timestamp() {
+        let recorder = |ordered| {
+            Arc::new(Recorder {
+                ordered,
+                seen: Mutex::new(Vec::new()),
+            })
+        };
+        let (direct, ordered) = (recorder(false), recorder(true));
+        let mut list = HandlerList::new();
+        list.register_shared(direct.clone());
+        list.register_shared(ordered.clone());
+        let metrics = Arc::new(Metrics::new());
+        let config = OrderingConfig {
+            enabled: true,
+            max_delay_ms: 20,
+            ..OrderingConfig::default()
+        };
+        list.enable_ordering(&config, Arc::clone(&metrics));
+
+        let now = monotonic_ns().unwrap();
+        for (pid, age_ms) in [(2, 5), (1, 10)] {
+            let wire = ProcessEventWire {
+                pid,
+                ppid: 0,
+                uid: 0,
+                gid: 0,
+                event_type: 0,
+                ts_ns: now - age_ms * 1_000_000,
+                seq: u64::from(pid),
+                comm: [0; 16],
+                exit_time_ns: 0,
+                cpu_pct_milli: PERCENT_MILLI_UNKNOWN,
+                mem_pct_milli: PERCENT_MILLI_UNKNOWN,
+                data: 0,
+                data2: 0,
+                aux: 0,
+                aux2: 0,
+                cgroup_id: 0,
+            };
+            list.on_event(&ProcessEvent::new(wire)).await;
+        }
+        assert_eq!(*direct.seen.lock().unwrap(), [2, 1]);
+
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert_eq!(*ordered.seen.lock().unwrap(), [1, 2]);
+        let reorder = metrics.reorder().snapshot();
+        assert!(reorder.enabled);
+        assert_eq!((reorder.stats.released, reorder.stats.buffered), (2, 0));
+    }
 
     #[tokio::test]
     async fn jsonl_writes_lines() {
@@ -122,6 +197,7 @@ mod tests {
             data2: 0,
             aux: 0,
             aux2: 0,
+            cgroup_id: 0,
         };
         let event = ProcessEvent::new(base);
         handler.on_event(&event).await;
@@ -142,6 +218,7 @@ mod tests {
         };
         handler.on_snapshot(&snap).await;
         let content = tokio::fs::read_to_string(file.path()).await.unwrap();
-        assert_eq!(content.lines().count(), 2);
+        // Clock anchor, event, snapshot
+        assert_eq!(content.lines().count(), 3);
     }
 }
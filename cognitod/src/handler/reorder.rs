@@ -0,0 +1,260 @@
+//! Timestamp-ordered delivery for handlers that need it
+//!
+//! The perf listener reads one buffer per CPU and hands every event to its
+//! own task, so an exit can reach the handlers before the exec and the
+//! connection that preceded it. Handlers whose [`Handler::ordered`] is true
+//! get their events through a single task instead. It holds each event until
+//! it is `max_delay_ms` old by the kernel clock, then delivers the held
+//! events oldest first. An event that shows up after a later one was already
+//! delivered missed its window: it is delivered at once and counted as
+//! out of window.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tokio::time::MissedTickBehavior;
+
+use super::Handler;
+use crate::ProcessEvent;
+use crate::config::OrderingConfig;
+use crate::metrics::Metrics;
+use crate::metrics::latency::monotonic_ns;
+use crate::metrics::reorder::ReorderStats;
+
+/// Events waiting for the reorder task.
+const CHANNEL_CAPACITY: usize = 4096;
+
+struct Held {
+    /// Timestamp, per-stream sequence and arrival order
+    key: (u64, u64, u64),
+    event: ProcessEvent,
+}
+
+impl PartialEq for Held {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for Held {}
+
+impl PartialOrd for Held {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Held {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+pub struct ReorderBuffer {
+    max_delay_ns: u64,
+    max_buffered: usize,
+    heap: BinaryHeap<Reverse<Held>>,
+    arrivals: u64,
+    /// Timestamp of the latest event delivered in order
+    released_ts: u64,
+    stats: ReorderStats,
+}
+
+impl ReorderBuffer {
+    pub fn new(max_delay: Duration, max_buffered: usize) -> Self {
+        Self {
+            max_delay_ns: max_delay.as_nanos() as u64,
+            max_buffered: max_buffered.max(1),
+            heap: BinaryHeap::new(),
+            arrivals: 0,
+            released_ts: 0,
+            stats: ReorderStats::default(),
+        }
+    }
+
+    /// Hold `event`, or give it back when it cannot be put in order: it is
+    /// unstamped or too late. Stamps ahead of `now_ns` are taken as `now_ns`,
+    /// so a bad clock cannot hold an event forever.
+    pub fn push(&mut self, event: ProcessEvent, now_ns: u64) -> Option<ProcessEvent> {
+        if event.ts_ns == 0 {
+            return Some(event);
+        }
+        let ts = event.ts_ns.min(now_ns);
+        if ts < self.released_ts {
+            self.stats.out_of_window += 1;
+            return Some(event);
+        }
+        self.arrivals += 1;
+        self.heap.push(Reverse(Held {
+            key: (ts, event.seq, self.arrivals),
+            event,
+        }));
+        self.stats.buffered = self.heap.len() as u64;
+        None
+    }
+
+    /// Move the events that have waited long enough, plus any beyond
+    /// `max_buffered`, to `out` in timestamp order.
+    pub fn drain_ready(&mut self, now_ns: u64, out: &mut Vec<ProcessEvent>) {
+        let watermark = now_ns.saturating_sub(self.max_delay_ns);
+        while let Some(Reverse(top)) = self.heap.peek() {
+            if top.key.0 > watermark {
+                if self.heap.len() <= self.max_buffered {
+                    break;
+                }
+                self.stats.forced += 1;
+            }
+            self.release(out);
+        }
+        self.stats.buffered = self.heap.len() as u64;
+    }
+
+    /// Move every held event to `out` in timestamp order.
+    pub fn flush(&mut self, out: &mut Vec<ProcessEvent>) {
+        while !self.heap.is_empty() {
+            self.release(out);
+        }
+        self.stats.buffered = 0;
+    }
+
+    fn release(&mut self, out: &mut Vec<ProcessEvent>) {
+        if let Some(Reverse(held)) = self.heap.pop() {
+            self.released_ts = self.released_ts.max(held.key.0);
+            self.stats.released += 1;
+            out.push(held.event);
+        }
+    }
+
+    pub fn stats(&self) -> ReorderStats {
+        self.stats
+    }
+}
+
+/// Start the reorder task feeding `handlers`; events sent to the returned
+/// channel reach them in timestamp order.
+pub fn spawn(
+    handlers: Vec<Arc<dyn Handler>>,
+    config: &OrderingConfig,
+    metrics: Arc<Metrics>,
+) -> mpsc::Sender<ProcessEvent> {
+    let (tx, mut rx) = mpsc::channel::<ProcessEvent>(CHANNEL_CAPACITY);
+    let mut buffer = ReorderBuffer::new(
+        Duration::from_millis(config.max_delay_ms),
+        config.max_buffered,
+    );
+    let tick = Duration::from_millis((config.max_delay_ms / 2).max(1));
+    metrics.reorder().enable(config.max_delay_ms);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tick);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut ready = Vec::new();
+        loop {
+            // Without a clock, waiting would hold events forever
+            let now = monotonic_ns().unwrap_or(u64::MAX);
+            let open = tokio::select! {
+                event = rx.recv() => match event {
+                    Some(event) => {
+                        ready.extend(buffer.push(event, now));
+                        true
+                    }
+                    None => {
+                        buffer.flush(&mut ready);
+                        false
+                    }
+                },
+                _ = interval.tick() => {
+                    buffer.drain_ready(now, &mut ready);
+                    true
+                }
+            };
+            for event in ready.drain(..) {
+                for handler in &handlers {
+                    handler.on_event(&event).await;
+                }
+            }
+            metrics.reorder().set(buffer.stats());
+            if !open {
+                break;
+            }
+        }
+    });
+    tx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PERCENT_MILLI_UNKNOWN, ProcessEventWire};
+
+    fn event(pid: u32, ts_ns: u64) -> ProcessEvent {
+        ProcessEvent::new(ProcessEventWire {
+            pid,
+            ppid: 1,
+            uid: 0,
+            gid: 0,
+            event_type: 0,
+            ts_ns,
+            seq: 0,
+            comm: [0; 16],
+            exit_time_ns: 0,
+            cpu_pct_milli: PERCENT_MILLI_UNKNOWN,
+            mem_pct_milli: PERCENT_MILLI_UNKNOWN,
+            data: 0,
+            data2: 0,
+            aux: 0,
+            aux2: 0,
+            cgroup_id: 0,
+        })
+    }
+
+    const MS: u64 = 1_000_000;
+
+    fn pids(events: &[ProcessEvent]) -> Vec<u32> {
+        events.iter().map(|e| e.pid).collect()
+    }
+
+    #[test]
+    fn releases_in_timestamp_order_after_the_delay() {
+        let mut buffer = ReorderBuffer::new(Duration::from_millis(50), 100);
+        let mut out = Vec::new();
+        // exec, net and exit arriving from different CPUs out of order
+        assert!(buffer.push(event(3, 1_030 * MS), 1_031 * MS).is_none());
+        assert!(buffer.push(event(1, 1_010 * MS), 1_032 * MS).is_none());
+        assert!(buffer.push(event(2, 1_020 * MS), 1_033 * MS).is_none());
+
+        buffer.drain_ready(1_072 * MS, &mut out);
+        assert_eq!(pids(&out), [1, 2]);
+        buffer.drain_ready(1_080 * MS, &mut out);
+        assert_eq!(pids(&out), [1, 2, 3]);
+
+        // Too late to be put in order: delivered at once and counted
+        let late = buffer.push(event(4, 1_015 * MS), 1_090 * MS);
+        assert_eq!(late.map(|e| e.pid), Some(4));
+        let stats = buffer.stats();
+        assert_eq!(
+            (stats.released, stats.out_of_window, stats.buffered),
+            (3, 1, 0)
+        );
+    }
+
+    #[test]
+    fn a_full_buffer_releases_early_and_flush_empties_it() {
+        let mut buffer = ReorderBuffer::new(Duration::from_millis(50), 2);
+        let mut out = Vec::new();
+        for (pid, ts) in [(1, 3 * MS), (2, MS), (3, 2 * MS)] {
+            buffer.push(event(pid, ts), 4 * MS);
+        }
+        buffer.drain_ready(4 * MS, &mut out);
+        assert_eq!(pids(&out), [2]);
+        assert_eq!(buffer.stats().forced, 1);
+
+        // Stamps from the future are held as if stamped now
+        buffer.push(event(4, u64::MAX), 5 * MS);
+        buffer.flush(&mut out);
+        assert_eq!(pids(&out), [2, 3, 1, 4]);
+        assert_eq!(buffer.stats().buffered, 0);
+    }
+}
@@ -1,11 +1,253 @@
 // Keep containers warm - prevent cold starts
+//
+// Every ping also feeds a per-container health score: consecutive failures
+// and the median latency of recent successful pings put a standby into
+// `degraded` or `unhealthy`, and each change of state raises a
+// `warmth_health` alert.
 use anyhow::Result;
 use dashmap::DashMap;
-use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::{Duration, Instant};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::time::sleep;
 
+use crate::alerts::{Alert, AlertSender, Severity};
+use crate::config::WarmthConfig;
+
+pub const RULE_NAME: &str = "warmth_health";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StandbyState {
+    /// Not pinged yet
+    Unknown,
+    Healthy,
+    Degraded,
+    Unhealthy,
+}
+
+impl StandbyState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StandbyState::Unknown => "unknown",
+            StandbyState::Healthy => "healthy",
+            StandbyState::Degraded => "degraded",
+            StandbyState::Unhealthy => "unhealthy",
+        }
+    }
+}
+
+/// When a standby stops counting as healthy.
+#[derive(Debug, Clone, Copy)]
+pub struct HealthThresholds {
+    pub degraded_latency_ms: u64,
+    pub degraded_after_failures: u32,
+    pub unhealthy_after_failures: u32,
+    /// Recent pings the latency median is taken over
+    pub window: usize,
+}
+
+impl From<&WarmthConfig> for HealthThresholds {
+    fn from(config: &WarmthConfig) -> Self {
+        Self {
+            degraded_latency_ms: config.degraded_latency_ms,
+            degraded_after_failures: config.degraded_after_failures.max(1),
+            unhealthy_after_failures: config
+                .unhealthy_after_failures
+                .max(config.degraded_after_failures.max(1)),
+            window: config.health_window.max(1),
+        }
+    }
+}
+
+/// Ping results of one container.
+#[derive(Debug)]
+struct PingHistory {
+    /// Latencies of recent successful pings, oldest first
+    latencies_ms: VecDeque<u64>,
+    consecutive_failures: u32,
+    pings: u64,
+    failures: u64,
+    last_ping_at: Option<u64>,
+    last_error: Option<String>,
+    state: StandbyState,
+    state_since: Option<u64>,
+}
+
+impl Default for PingHistory {
+    fn default() -> Self {
+        Self {
+            latencies_ms: VecDeque::new(),
+            consecutive_failures: 0,
+            pings: 0,
+            failures: 0,
+            last_ping_at: None,
+            last_error: None,
+            state: StandbyState::Unknown,
+            state_since: None,
+        }
+    }
+}
+
+impl PingHistory {
+    fn median_latency_ms(&self) -> Option<u64> {
+        let mut sorted: Vec<u64> = self.latencies_ms.iter().copied().collect();
+        sorted.sort_unstable();
+        sorted.get(sorted.len() / 2).copied()
+    }
+
+    /// Record one ping, `Ok(latency_ms)` or the error, and return the new
+    /// state with the reason when it changed.
+    fn record(
+        &mut self,
+        result: Result<u64, String>,
+        thresholds: &HealthThresholds,
+        now: u64,
+    ) -> Option<(StandbyState, String)> {
+        self.pings += 1;
+        self.last_ping_at = Some(now);
+        match result {
+            Ok(latency_ms) => {
+                self.consecutive_failures = 0;
+                self.latencies_ms.push_back(latency_ms);
+                while self.latencies_ms.len() > thresholds.window {
+                    self.latencies_ms.pop_front();
+                }
+            }
+            Err(error) => {
+                self.failures += 1;
+                self.consecutive_failures += 1;
+                self.last_error = Some(error);
+            }
+        }
+
+        let median = self.median_latency_ms();
+        let (state, reason) = if self.consecutive_failures >= thresholds.unhealthy_after_failures {
+            (StandbyState::Unhealthy, self.failure_reason())
+        } else if self.consecutive_failures >= thresholds.degraded_after_failures {
+            (StandbyState::Degraded, self.failure_reason())
+        } else if let Some(ms) = median.filter(|ms| *ms >= thresholds.degraded_latency_ms) {
+            (
+                StandbyState::Degraded,
+                format!(
+                    "median ping latency {ms}ms >= {}ms",
+                    thresholds.degraded_latency_ms
+                ),
+            )
+        } else {
+            (
+                StandbyState::Healthy,
+                format!("median ping latency {}ms", median.unwrap_or(0)),
+            )
+        };
+        if state == self.state {
+            return None;
+        }
+        self.state = state;
+        self.state_since = Some(now);
+        Some((state, reason))
+    }
+
+    fn failure_reason(&self) -> String {
+        format!(
+            "{} consecutive failed pings: {}",
+            self.consecutive_failures,
+            self.last_error.as_deref().unwrap_or("unknown error")
+        )
+    }
+}
+
+/// One standby in `/warmth/health`.
+#[derive(Debug, Clone, Serialize)]
+pub struct StandbyHealth {
+    pub container: String,
+    pub url: String,
+    pub state: StandbyState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state_since: Option<u64>,
+    pub consecutive_failures: u32,
+    pub pings: u64,
+    pub failures: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub median_latency_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_latency_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_ping_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// Response of `/warmth/health`.
+#[derive(Debug, Clone, Serialize)]
+pub struct FleetHealth {
+    pub healthy: usize,
+    pub degraded: usize,
+    pub unhealthy: usize,
+    pub unknown: usize,
+    pub containers: Vec<StandbyHealth>,
+}
+
+/// Health of every configured standby, shared with the ping loops.
+struct HealthTracker {
+    thresholds: HealthThresholds,
+    histories: Mutex<HashMap<String, PingHistory>>,
+    alerts: Option<AlertSender>,
+    host: String,
+}
+
+impl HealthTracker {
+    fn record(&self, container: &str, url: &str, result: Result<u64, String>) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let change = self
+            .histories
+            .lock()
+            .unwrap()
+            .entry(container.to_string())
+            .or_default()
+            .record(result, &self.thresholds, now);
+        let Some((state, reason)) = change else {
+            return;
+        };
+        log::info!(
+            "[warmth] standby '{}' is {}: {}",
+            container,
+            state.as_str(),
+            reason
+        );
+        if let Some(tx) = &self.alerts {
+            tx.send(state_alert(container, url, state, &reason, &self.host));
+        }
+    }
+}
+
+fn state_alert(container: &str, url: &str, state: StandbyState, reason: &str, host: &str) -> Alert {
+    Alert {
+        rule: RULE_NAME.to_string(),
+        severity: match state {
+            StandbyState::Unhealthy => Severity::High,
+            StandbyState::Degraded => Severity::Medium,
+            StandbyState::Healthy | StandbyState::Unknown => Severity::Info,
+        },
+        message: format!(
+            "warm standby '{container}' ({url}) is {}: {reason}",
+            state.as_str()
+        ),
+        host: host.to_string(),
+        detection: "threshold",
+        suppressed_by: None,
+        lineage: Vec::new(),
+        children: Vec::new(),
+        pod: None,
+        evidence: None,
+        annotations: None,
+    }
+}
+
 pub struct WarmthKeeper {
     /// Track last activity per container
     last_activity: Arc<DashMap<String, Instant>>,
@@ -17,13 +259,15 @@ pub struct WarmthKeeper {
     ping_interval: Duration,
     /// Container ID -> health URL mapping
     container_urls: HashMap<String, String>,
+    /// Ping-based health of each container
+    health: Arc<HealthTracker>,
 }
 
 impl WarmthKeeper {
     pub fn new(
-        idle_threshold_secs: u64,
-        ping_interval_secs: u64,
+        config: &WarmthConfig,
         containers: Vec<crate::config::ContainerConfig>,
+        alerts: Option<AlertSender>,
     ) -> Self {
         // Build container URL map
         let mut container_urls = HashMap::new();
@@ -39,9 +283,59 @@ impl WarmthKeeper {
                 .timeout(Duration::from_secs(5))
                 .build()
                 .unwrap(),
-            idle_threshold: Duration::from_secs(idle_threshold_secs),
-            ping_interval: Duration::from_secs(ping_interval_secs),
+            idle_threshold: Duration::from_secs(config.idle_threshold_secs),
+            ping_interval: Duration::from_secs(config.ping_interval_secs),
             container_urls,
+            health: Arc::new(HealthTracker {
+                thresholds: HealthThresholds::from(config),
+                histories: Mutex::new(HashMap::new()),
+                alerts,
+                host: std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown".into()),
+            }),
+        }
+    }
+
+    /// Health of the standby fleet, worst first.
+    pub fn fleet_health(&self) -> FleetHealth {
+        let histories = self.health.histories.lock().unwrap();
+        let mut containers: Vec<StandbyHealth> = self
+            .container_urls
+            .iter()
+            .map(|(name, url)| {
+                let history = histories.get(name);
+                StandbyHealth {
+                    container: name.clone(),
+                    url: url.clone(),
+                    state: history.map_or(StandbyState::Unknown, |h| h.state),
+                    state_since: history.and_then(|h| h.state_since),
+                    consecutive_failures: history.map_or(0, |h| h.consecutive_failures),
+                    pings: history.map_or(0, |h| h.pings),
+                    failures: history.map_or(0, |h| h.failures),
+                    median_latency_ms: history.and_then(|h| h.median_latency_ms()),
+                    max_latency_ms: history.and_then(|h| h.latencies_ms.iter().max().copied()),
+                    last_ping_at: history.and_then(|h| h.last_ping_at),
+                    last_error: history.and_then(|h| h.last_error.clone()),
+                }
+            })
+            .collect();
+        let rank = |state: StandbyState| match state {
+            StandbyState::Unhealthy => 0,
+            StandbyState::Degraded => 1,
+            StandbyState::Unknown => 2,
+            StandbyState::Healthy => 3,
+        };
+        containers.sort_by(|a, b| {
+            rank(a.state)
+                .cmp(&rank(b.state))
+                .then_with(|| a.container.cmp(&b.container))
+        });
+        let count = |state| containers.iter().filter(|c| c.state == state).count();
+        FleetHealth {
+            healthy: count(StandbyState::Healthy),
+            degraded: count(StandbyState::Degraded),
+            unhealthy: count(StandbyState::Unhealthy),
+            unknown: count(StandbyState::Unknown),
+            containers,
         }
     }
 
@@ -73,6 +367,7 @@ impl WarmthKeeper {
         let last_activity = Arc::clone(&self.last_activity);
         let idle_threshold = self.idle_threshold;
         let ping_interval = self.ping_interval;
+        let health = Arc::clone(&self.health);
 
         tokio::spawn(async move {
             loop {
@@ -89,17 +384,22 @@ impl WarmthKeeper {
                 if should_ping {
                     log::debug!("Warming container '{}' via {}", container_id, health_url);
                     
-                    match client.get(&health_url).send().await {
+                    let started = Instant::now();
+                    let result = match client.get(&health_url).send().await {
                         Ok(resp) if resp.status().is_success() => {
                             log::trace!("Container '{}' warm ({})", container_id, resp.status());
+                            Ok(started.elapsed().as_millis() as u64)
                         }
                         Ok(resp) => {
                             log::warn!("Container '{}' warmth check failed: {}", container_id, resp.status());
+                            Err(format!("HTTP {}", resp.status()))
                         }
                         Err(e) => {
                             log::warn!("Container '{}' warmth check error: {}", container_id, e);
+                            Err(e.to_string())
                         }
-                    }
+                    };
+                    health.record(&container_id, &health_url, result);
                 }
             }
         });
@@ -126,3 +426,34 @@ pub fn extract_container_id(cgroup: &str) -> Option<String> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pings_move_a_standby_between_states() {
+        let thresholds = HealthThresholds {
+            degraded_latency_ms: 500,
+            degraded_after_failures: 1,
+            unhealthy_after_failures: 3,
+            window: 3,
+        };
+        let mut history = PingHistory::default();
+        let mut record = |result: Result<u64, String>| {
+            history
+                .record(result, &thresholds, 0)
+                .map(|(state, _)| state)
+        };
+
+        assert_eq!(record(Ok(20)), Some(StandbyState::Healthy));
+        assert_eq!(record(Ok(30)), None);
+        assert_eq!(record(Err("timeout".into())), Some(StandbyState::Degraded));
+        assert_eq!(record(Err("timeout".into())), None);
+        assert_eq!(record(Err("timeout".into())), Some(StandbyState::Unhealthy));
+        assert_eq!(record(Ok(25)), Some(StandbyState::Healthy));
+        // Slow answers degrade once they dominate the window
+        assert_eq!(record(Ok(900)), None);
+        assert_eq!(record(Ok(800)), Some(StandbyState::Degraded));
+    }
+}
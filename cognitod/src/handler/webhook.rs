@@ -0,0 +1,241 @@
+//! Event webhooks: POST filtered events as JSON arrays of `EventRecord`
+//!
+//! Events that pass the filter are queued without blocking the event loop and
+//! sent in batches of up to `max_batch`, at least every `flush_interval_ms`.
+//! When the endpoint falls behind and the queue fills, new events are dropped
+//! and counted rather than stalling other handlers. A failed batch is
+//! retried under the endpoint's breaker (`webhook:<host>`) before it is
+//! dropped. While webhook egress is blocked by the offline policy, events
+//! are not queued at all.
+//!
+//! With a sequencer checkpoint, the handler records its delivery mark
+//! (`webhook:<url>`) as batches complete and skips events it delivered
+//! before a restart; see `delivery`.
+
+use async_trait::async_trait;
+use log::{info, warn};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use super::Handler;
+use super::delivery::{DeliveryMark, DeliveryMarks};
+use crate::config::{Egress, EventWebhookConfig, OfflineGuard};
+use crate::event_schema::EventRecord;
+use crate::filter::EventMatcher;
+use crate::retry::{Destination, check_response, url_destination};
+use crate::{ProcessEvent, types::SystemSnapshot};
+
+/// Batches that may be queued behind the one being sent.
+const QUEUED_BATCHES: usize = 4;
+
+pub struct EventWebhookHandler {
+    filter: EventMatcher,
+    tx: mpsc::Sender<(Option<u64>, EventRecord)>,
+    dropped: AtomicU64,
+    guard: Arc<OfflineGuard>,
+    delivery: Option<Arc<DeliveryMark>>,
+}
+
+impl EventWebhookHandler {
+    fn channel(
+        filter: EventMatcher,
+        capacity: usize,
+        guard: Arc<OfflineGuard>,
+        delivery: Option<Arc<DeliveryMark>>,
+    ) -> (Self, mpsc::Receiver<(Option<u64>, EventRecord)>) {
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        let handler = Self {
+            filter,
+            tx,
+            dropped: AtomicU64::new(0),
+            guard,
+            delivery,
+        };
+        (handler, rx)
+    }
+
+    /// Create the handler and start the task that posts its batches,
+    /// recording its delivery mark in `marks` if given.
+    pub fn spawn(
+        config: &EventWebhookConfig,
+        filter: EventMatcher,
+        guard: Arc<OfflineGuard>,
+        marks: Option<Arc<DeliveryMarks>>,
+    ) -> Self {
+        let max_batch = config.max_batch.max(1);
+        let client = guard.client(Egress::Webhooks, reqwest::Client::builder());
+        let destination = guard
+            .retrier()
+            .destination(&url_destination("webhook", &config.url));
+        let delivery = marks
+            .map(|marks| Arc::new(DeliveryMark::new(marks, format!("webhook:{}", config.url))));
+        let (handler, rx) =
+            Self::channel(filter, max_batch * QUEUED_BATCHES, guard, delivery.clone());
+        let url = config.url.clone();
+        let flush = Duration::from_millis(config.flush_interval_ms.max(1));
+        info!("[webhook] posting events to {url}");
+        tokio::spawn(post_batches(
+            client,
+            destination,
+            url,
+            rx,
+            max_batch,
+            flush,
+            delivery,
+        ));
+        handler
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+async fn post_batches(
+    client: reqwest::Client,
+    destination: Arc<Destination>,
+    url: String,
+    mut rx: mpsc::Receiver<(Option<u64>, EventRecord)>,
+    max_batch: usize,
+    flush: Duration,
+    delivery: Option<Arc<DeliveryMark>>,
+) {
+    let mut batch = Vec::with_capacity(max_batch);
+    loop {
+        // Wait for the first event, then give the batch `flush` to fill.
+        let Some((ticket, first)) = rx.recv().await else {
+            return;
+        };
+        batch.push(first);
+        let mut last = ticket;
+        let deadline = tokio::time::Instant::now() + flush;
+        while batch.len() < max_batch {
+            match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(Some((ticket, event))) => {
+                    batch.push(event);
+                    last = ticket.or(last);
+                }
+                Ok(None) | Err(_) => break,
+            }
+        }
+        let (client, url, events) = (&client, &url, &batch);
+        let result = destination
+            .run(|_| async move {
+                check_response(
+                    client
+                        .post(url)
+                        .timeout(Duration::from_secs(10))
+                        .json(events)
+                        .send()
+                        .await,
+                )
+                .await
+            })
+            .await;
+        if let Err(e) = result {
+            warn!("[webhook] dropping {} events for {url}: {e:#}", batch.len());
+        }
+        if let Some(delivery) = &delivery {
+            delivery.settled(batch.len(), last);
+        }
+        batch.clear();
+    }
+}
+
+#[async_trait]
+impl Handler for EventWebhookHandler {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn on_event(&self, event: &ProcessEvent) {
+        let delivery = self.delivery.as_deref();
+        if delivery.is_some_and(|d| !d.pending(event.ticket)) {
+            return;
+        }
+        if !self.guard.allows(Egress::Webhooks) || !self.filter.matches(event) {
+            if let Some(delivery) = delivery {
+                delivery.skipped(event.ticket);
+            }
+            return;
+        }
+        if let Some(delivery) = delivery {
+            delivery.queued();
+        }
+        if self
+            .tx
+            .try_send((event.ticket, EventRecord::from(event)))
+            .is_err()
+        {
+            if let Some(delivery) = delivery {
+                delivery.settled(1, None);
+                delivery.skipped(event.ticket);
+            }
+            let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            if dropped.is_power_of_two() {
+                warn!("[webhook] queue full, {dropped} events dropped so far");
+            }
+        }
+    }
+
+    async fn on_snapshot(&self, _snapshot: &SystemSnapshot) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::EventFilter;
+    use crate::{PERCENT_MILLI_UNKNOWN, ProcessEventWire};
+
+    fn event(pid: u32, uid: u32) -> ProcessEvent {
+        ProcessEvent::new(ProcessEventWire {
+            pid,
+            ppid: 1,
+            uid,
+            gid: 0,
+            event_type: 0,
+            ts_ns: 0,
+            seq: 0,
+            comm: [0; 16],
+            exit_time_ns: 0,
+            cpu_pct_milli: PERCENT_MILLI_UNKNOWN,
+            mem_pct_milli: PERCENT_MILLI_UNKNOWN,
+            data: 0,
+            data2: 0,
+            aux: 0,
+            aux2: 0,
+            cgroup_id: 0,
+        })
+    }
+
+    #[tokio::test]
+    async fn queues_matching_events_and_drops_when_full() {
+        let filter = EventFilter {
+            uids: vec![0],
+            ..EventFilter::default()
+        };
+        let guard = Arc::new(OfflineGuard::new(false));
+        let (handler, mut rx) = EventWebhookHandler::channel(
+            filter.compile(None).unwrap(),
+            2,
+            Arc::clone(&guard),
+            None,
+        );
+        for pid in 1..=4 {
+            handler.on_event(&event(pid, 0)).await;
+        }
+        handler.on_event(&event(9, 1000)).await;
+
+        assert_eq!(rx.recv().await.unwrap().1.pid, 1);
+        assert_eq!(rx.recv().await.unwrap().1.pid, 2);
+        assert!(rx.try_recv().is_err());
+        assert_eq!(handler.dropped(), 2);
+
+        guard.set(Egress::Webhooks, false);
+        handler.on_event(&event(5, 0)).await;
+        assert!(rx.try_recv().is_err());
+        assert_eq!(handler.dropped(), 2);
+    }
+}
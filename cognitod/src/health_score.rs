@@ -0,0 +1,351 @@
+//! Node health score
+//!
+//! One 0–100 number per node for dashboards. Each component (pressure stall,
+//! CPU/memory headroom, event-rate anomaly, recent alerts, recent
+//! enforcement) is scored 0–100 with 100 meaning healthy, and the total is
+//! their mean weighted by `[health_score.weights]`. A component with weight
+//! zero is reported but does not count.
+
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::Metrics;
+use crate::anomaly::Ewma;
+use crate::config::{HealthScoreConfig, HealthWeights};
+use crate::types::SystemSnapshot;
+
+/// How often the event-rate baseline is sampled.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+/// EWMA smoothing of the event-rate baseline (about ten minutes at 10 s).
+const BASELINE_ALPHA: f64 = 0.02;
+/// Samples before the event-rate baseline is trusted.
+const BASELINE_WARMUP: u32 = 30;
+
+/// PSI `some avg10` (percent) at which the pressure component reaches zero.
+const PSI_ZERO_AT: f64 = 50.0;
+/// CPU or memory use (percent) below which headroom costs nothing.
+const HEADROOM_FREE_BELOW: f64 = 50.0;
+/// Event-rate z-scores that start and finish the anomaly penalty.
+const ANOMALY_Z_START: f64 = 2.0;
+const ANOMALY_Z_ZERO: f64 = 6.0;
+/// Points each alert in the window costs, by severity.
+const ALERT_PENALTY_HIGH: f64 = 25.0;
+const ALERT_PENALTY_MEDIUM: f64 = 10.0;
+const ALERT_PENALTY_LOW: f64 = 3.0;
+/// Points each enforcement action in the window costs.
+const ENFORCEMENT_PENALTY_EXECUTED: f64 = 20.0;
+const ENFORCEMENT_PENALTY_PENDING: f64 = 10.0;
+
+/// Alerts raised within the scoring window, by severity (`info` is free).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct AlertCounts {
+    pub high: u64,
+    pub medium: u64,
+    pub low: u64,
+    pub info: u64,
+}
+
+impl AlertCounts {
+    pub fn add(&mut self, severity: &str) {
+        match severity {
+            "high" => self.high += 1,
+            "medium" => self.medium += 1,
+            "low" => self.low += 1,
+            _ => self.info += 1,
+        }
+    }
+}
+
+/// Enforcement actions proposed within the scoring window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct EnforcementCounts {
+    pub executed: u64,
+    pub pending: u64,
+}
+
+/// Everything the score is computed from.
+#[derive(Debug, Clone)]
+pub struct HealthInputs {
+    pub system: SystemSnapshot,
+    /// Current event rate against its baseline; `None` while warming up.
+    pub event_rate_z: Option<f64>,
+    pub alerts: AlertCounts,
+    pub enforcement: EnforcementCounts,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ComponentScore {
+    pub name: &'static str,
+    /// 0–100, 100 is healthy.
+    pub score: f64,
+    /// Share of the total, after normalising the configured weights.
+    pub weight: f64,
+    pub detail: String,
+}
+
+/// Response of `/health/score`.
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthScore {
+    pub score: f64,
+    pub window_secs: u64,
+    pub components: Vec<ComponentScore>,
+}
+
+/// `100` at or below `start`, `0` at or beyond `zero`, linear in between.
+fn falloff(value: f64, start: f64, zero: f64) -> f64 {
+    if value <= start {
+        100.0
+    } else if value >= zero {
+        0.0
+    } else {
+        100.0 * (zero - value) / (zero - start)
+    }
+}
+
+fn round1(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+pub fn compute(inputs: &HealthInputs, config: &HealthScoreConfig) -> HealthScore {
+    let s = &inputs.system;
+    let HealthWeights {
+        pressure,
+        headroom,
+        anomaly,
+        alerts,
+        enforcement,
+    } = config.weights;
+
+    // Memory `full` means every task stalled; weigh it like twice the `some`.
+    let psi = [
+        s.psi_cpu_some_avg10,
+        s.psi_memory_some_avg10,
+        s.psi_memory_full_avg10 * 2.0,
+        s.psi_io_some_avg10,
+    ]
+    .into_iter()
+    .fold(0.0f32, f32::max) as f64;
+    let usage = s.cpu_percent.max(s.mem_percent) as f64;
+    let a = &inputs.alerts;
+    let alert_penalty = a.high as f64 * ALERT_PENALTY_HIGH
+        + a.medium as f64 * ALERT_PENALTY_MEDIUM
+        + a.low as f64 * ALERT_PENALTY_LOW;
+    let e = &inputs.enforcement;
+    let enforcement_penalty = e.executed as f64 * ENFORCEMENT_PENALTY_EXECUTED
+        + e.pending as f64 * ENFORCEMENT_PENALTY_PENDING;
+
+    let raw = [
+        (
+            "pressure",
+            pressure,
+            falloff(psi, 0.0, PSI_ZERO_AT),
+            format!(
+                "psi cpu={:.1} memory={:.1}/{:.1} io={:.1}",
+                s.psi_cpu_some_avg10,
+                s.psi_memory_some_avg10,
+                s.psi_memory_full_avg10,
+                s.psi_io_some_avg10
+            ),
+        ),
+        (
+            "headroom",
+            headroom,
+            falloff(usage, HEADROOM_FREE_BELOW, 100.0),
+            format!("cpu={:.1}% memory={:.1}%", s.cpu_percent, s.mem_percent),
+        ),
+        (
+            "anomaly",
+            anomaly,
+            inputs
+                .event_rate_z
+                .map_or(100.0, |z| falloff(z, ANOMALY_Z_START, ANOMALY_Z_ZERO)),
+            match inputs.event_rate_z {
+                Some(z) => format!("event rate z={z:.1}"),
+                None => "event rate baseline warming up".to_string(),
+            },
+        ),
+        (
+            "alerts",
+            alerts,
+            (100.0 - alert_penalty).max(0.0),
+            format!("high={} medium={} low={}", a.high, a.medium, a.low),
+        ),
+        (
+            "enforcement",
+            enforcement,
+            (100.0 - enforcement_penalty).max(0.0),
+            format!("executed={} pending={}", e.executed, e.pending),
+        ),
+    ];
+
+    let total_weight: f64 = raw.iter().map(|(_, w, _, _)| w.max(0.0)).sum();
+    let components: Vec<ComponentScore> = raw
+        .into_iter()
+        .map(|(name, weight, score, detail)| ComponentScore {
+            name,
+            score: round1(score),
+            weight: if total_weight > 0.0 {
+                weight.max(0.0) / total_weight
+            } else {
+                0.0
+            },
+            detail,
+        })
+        .collect();
+    // All weights zero leaves nothing to penalise.
+    let score = if total_weight > 0.0 {
+        components.iter().map(|c| c.score * c.weight).sum()
+    } else {
+        100.0
+    };
+    HealthScore {
+        score: round1(score),
+        window_secs: config.window_secs,
+        components,
+    }
+}
+
+/// Keeps the event-rate baseline the anomaly component is measured against.
+pub struct HealthScorer {
+    config: HealthScoreConfig,
+    baseline: Mutex<Ewma>,
+}
+
+impl HealthScorer {
+    pub fn new(config: HealthScoreConfig) -> Self {
+        Self {
+            config,
+            baseline: Mutex::new(Ewma::default()),
+        }
+    }
+
+    pub fn config(&self) -> &HealthScoreConfig {
+        &self.config
+    }
+
+    /// The event-rate baseline, for carrying it across restarts.
+    pub fn baseline(&self) -> Ewma {
+        self.baseline.lock().unwrap().clone()
+    }
+
+    /// Replace the baseline with one learned by an earlier run, unless this
+    /// run has already learned more.
+    pub fn restore_baseline(&self, baseline: Ewma) -> bool {
+        let mut current = self.baseline.lock().unwrap();
+        if current.samples() >= baseline.samples() {
+            return false;
+        }
+        *current = baseline;
+        true
+    }
+
+    pub fn observe_event_rate(&self, rate: f64) {
+        self.baseline.lock().unwrap().update(rate, BASELINE_ALPHA);
+    }
+
+    /// Deviation of `rate` from the baseline in standard deviations. Only
+    /// rises count: a quiet node is not unhealthy.
+    pub fn event_rate_z(&self, rate: f64) -> Option<f64> {
+        let baseline = self.baseline.lock().unwrap();
+        if baseline.samples() < BASELINE_WARMUP {
+            return None;
+        }
+        // Floor σ so a perfectly steady rate does not turn noise into spikes
+        let sigma = baseline.stddev().max(baseline.mean() * 0.05).max(1.0);
+        Some(((rate - baseline.mean()) / sigma).max(0.0))
+    }
+
+    pub fn score(&self, inputs: &HealthInputs) -> HealthScore {
+        compute(inputs, &self.config)
+    }
+
+    async fn run(self: Arc<Self>, metrics: Arc<Metrics>) {
+        let mut tick = tokio::time::interval(SAMPLE_INTERVAL);
+        loop {
+            tick.tick().await;
+            self.observe_event_rate(metrics.events_per_sec() as f64);
+        }
+    }
+}
+
+pub fn spawn(metrics: Arc<Metrics>, config: &HealthScoreConfig) -> Arc<HealthScorer> {
+    let scorer = Arc::new(HealthScorer::new(config.clone()));
+    tokio::spawn(Arc::clone(&scorer).run(metrics));
+    scorer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn idle() -> HealthInputs {
+        HealthInputs {
+            system: SystemSnapshot {
+                timestamp: 0,
+                cpu_percent: 20.0,
+                mem_percent: 30.0,
+                load_avg: [0.0; 3],
+                disk_read_bytes: 0,
+                disk_write_bytes: 0,
+                net_rx_bytes: 0,
+                net_tx_bytes: 0,
+                psi_cpu_some_avg10: 0.0,
+                psi_memory_some_avg10: 0.0,
+                psi_memory_full_avg10: 0.0,
+                psi_io_some_avg10: 0.0,
+                psi_io_full_avg10: 0.0,
+            },
+            event_rate_z: None,
+            alerts: AlertCounts::default(),
+            enforcement: EnforcementCounts::default(),
+        }
+    }
+
+    #[test]
+    fn components_combine_by_weight() {
+        let config = HealthScoreConfig::default();
+        assert_eq!(compute(&idle(), &config).score, 100.0);
+
+        let mut busy = idle();
+        busy.system.psi_cpu_some_avg10 = 25.0;
+        busy.system.cpu_percent = 75.0;
+        busy.alerts.high = 2;
+        busy.enforcement.executed = 1;
+        let score = compute(&busy, &config);
+        let by_name = |name| score.components.iter().find(|c| c.name == name).unwrap();
+        assert_eq!(by_name("pressure").score, 50.0);
+        assert_eq!(by_name("headroom").score, 50.0);
+        assert_eq!(by_name("anomaly").score, 100.0);
+        assert_eq!(by_name("alerts").score, 50.0);
+        assert_eq!(by_name("enforcement").score, 80.0);
+        let weights: f64 = score.components.iter().map(|c| c.weight).sum();
+        assert!((weights - 1.0).abs() < 1e-9);
+        assert!(score.score > 0.0 && score.score < 100.0);
+
+        // Only pressure counts
+        let pressure_only = HealthScoreConfig {
+            weights: HealthWeights {
+                pressure: 1.0,
+                headroom: 0.0,
+                anomaly: 0.0,
+                alerts: 0.0,
+                enforcement: 0.0,
+            },
+            ..HealthScoreConfig::default()
+        };
+        assert_eq!(compute(&busy, &pressure_only).score, 50.0);
+    }
+
+    #[test]
+    fn event_rate_needs_a_baseline() {
+        let scorer = HealthScorer::new(HealthScoreConfig::default());
+        assert_eq!(scorer.event_rate_z(10_000.0), None);
+        for _ in 0..BASELINE_WARMUP {
+            scorer.observe_event_rate(1000.0);
+        }
+        assert_eq!(scorer.event_rate_z(500.0), Some(0.0));
+        let z = scorer.event_rate_z(2000.0).unwrap();
+        assert!(z > ANOMALY_Z_ZERO, "{z}");
+    }
+}
@@ -0,0 +1,124 @@
+//! Process identity across pid reuse
+//!
+//! A pid names a process only until it exits; the kernel then hands the
+//! number to the next process. [`ProcessKey`] pairs the pid with the time the
+//! process started, so the live table, lineage and enforcement can tell a
+//! process from its successor. Start times are nanoseconds on
+//! `CLOCK_MONOTONIC`, the clock BPF events are stamped with: the fork
+//! timestamp when the daemon saw the process start, or the start time from
+//! `/proc/<pid>/stat` moved onto that clock. The procfs value only has clock
+//! tick resolution, so keys of one pid are the same process when their
+//! starts are within [`START_TOLERANCE_NS`]. No kernel cycles through its pid
+//! space that quickly.
+
+use nix::time::{ClockId, clock_gettime};
+use serde::Serialize;
+
+/// Two ticks at the usual 100 Hz `CLK_TCK`.
+pub const START_TOLERANCE_NS: u64 = 20_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct ProcessKey {
+    pub pid: u32,
+    /// Start of the process on `CLOCK_MONOTONIC`
+    pub start_ns: u64,
+}
+
+impl ProcessKey {
+    pub fn new(pid: u32, start_ns: u64) -> Self {
+        Self { pid, start_ns }
+    }
+
+    /// Identity of the process running as `pid`; `None` once it has exited.
+    pub fn read(pid: u32) -> Option<Self> {
+        let stat = procfs::process::Process::new(pid as i32)
+            .ok()?
+            .stat()
+            .ok()?;
+        Some(Self::from_ticks(
+            pid,
+            stat.starttime,
+            procfs::ticks_per_second(),
+            suspended_ns()?,
+        ))
+    }
+
+    /// Key from a procfs start time, `ticks` after boot. Procfs counts time
+    /// spent suspended (`CLOCK_BOOTTIME`), event timestamps do not.
+    pub fn from_ticks(pid: u32, ticks: u64, ticks_per_sec: u64, suspended_ns: u64) -> Self {
+        let boot_ns = ticks.saturating_mul(1_000_000_000) / ticks_per_sec.max(1);
+        Self::new(pid, boot_ns.saturating_sub(suspended_ns))
+    }
+
+    pub fn same_process(&self, other: &ProcessKey) -> bool {
+        self.pid == other.pid && self.start_ns.abs_diff(other.start_ns) <= START_TOLERANCE_NS
+    }
+
+    /// Whether a process started at `start_ns` began after this one, beyond
+    /// the tolerance.
+    pub fn started_before(&self, start_ns: u64) -> bool {
+        self.start_ns.saturating_add(START_TOLERANCE_NS) < start_ns
+    }
+
+    /// Check that the pid still belongs to this process.
+    pub fn verify(&self) -> Result<(), String> {
+        match Self::read(self.pid) {
+            Some(current) if self.same_process(&current) => Ok(()),
+            Some(_) => Err(format!("pid {} now belongs to another process", self.pid)),
+            None => Err(format!("pid {} has exited", self.pid)),
+        }
+    }
+
+    /// Wall clock second the process started.
+    pub fn started_epoch_secs(&self) -> Option<u64> {
+        let now = clock_ns(ClockId::CLOCK_REALTIME)?;
+        let age = clock_ns(ClockId::CLOCK_MONOTONIC)?.saturating_sub(self.start_ns);
+        Some(now.saturating_sub(age) / 1_000_000_000)
+    }
+}
+
+fn clock_ns(clock: ClockId) -> Option<u64> {
+    let now = clock_gettime(clock).ok()?;
+    Some((now.tv_sec() as u64) * 1_000_000_000 + (now.tv_nsec() as u64))
+}
+
+/// Time the host spent suspended since boot.
+fn suspended_ns() -> Option<u64> {
+    let monotonic = clock_ns(ClockId::CLOCK_MONOTONIC)?;
+    let boottime = clock_ns(ClockId::CLOCK_BOOTTIME)?;
+    Some(boottime.saturating_sub(monotonic))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_tell_a_process_from_its_successor() {
+        let key = ProcessKey::from_ticks(4242, 123_456, 100, 2_000_000_000);
+        assert_eq!(key.start_ns, 1_232_560_000_000);
+
+        // A fork stamped a few milliseconds after the procfs tick
+        let forked = ProcessKey::new(4242, key.start_ns + 7_000_000);
+        assert!(key.same_process(&forked));
+        assert!(!key.started_before(forked.start_ns));
+
+        let successor = ProcessKey::new(4242, key.start_ns + 60_000_000_000);
+        assert!(!key.same_process(&successor));
+        assert!(key.started_before(successor.start_ns));
+        assert!(!key.same_process(&ProcessKey::new(4243, key.start_ns)));
+    }
+
+    #[test]
+    fn reads_and_verifies_running_processes() {
+        let me = ProcessKey::read(std::process::id()).unwrap();
+        assert!(me.verify().is_ok());
+        assert!(
+            me.started_epoch_secs()
+                .is_some_and(|secs| secs > 1_600_000_000)
+        );
+
+        let reused = ProcessKey::new(me.pid, me.start_ns + 60_000_000_000);
+        assert!(reused.verify().unwrap_err().contains("another process"));
+    }
+}
@@ -2,16 +2,46 @@
 //!
 //! This module provides persistent storage for circuit breaker incidents,
 //! system events, and LLM analysis. Uses SQLite for simplicity and reliability.
+//! Hot-path callers should use `submit`/`submit_incident`, which go through
+//! the write-behind queue in `writer`. Builds without the `incidents` feature
+//! drop SQLite and get a store that can never be opened.
 
+mod analysis_pool;
 mod analyzer;
+#[cfg(not(feature = "incidents"))]
+mod disabled;
+mod fallback;
+#[cfg(feature = "incidents")]
+mod sinks;
+mod writer;
 
+pub use analysis_pool::{AnalysisPool, AnalysisPriority, AnalysisQueueStats};
 pub use analyzer::{IncidentAnalysis, IncidentAnalyzer};
+#[cfg(not(feature = "incidents"))]
+pub use disabled::{IncidentSinks, IncidentStore, StoreError};
+pub use fallback::fallback_analysis;
+#[cfg(feature = "incidents")]
+pub use sinks::IncidentSinks;
+pub use writer::{IncidentWrite, WriteQueueStats};
 
-use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use sqlx::{Row, SqlitePool, sqlite::SqlitePoolOptions};
-use std::path::Path;
-use tracing::{debug, info};
+use std::collections::BTreeMap;
+#[cfg(feature = "incidents")]
+use {
+    crate::config::{FsyncPolicy, IncidentStoreConfig},
+    crate::leaderboard::UsageRollup,
+    sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous},
+    sqlx::{Row, SqlitePool},
+    std::path::Path,
+    std::str::FromStr,
+    std::sync::Arc,
+    std::sync::atomic::Ordering,
+    tokio::sync::{mpsc, oneshot},
+    tracing::{debug, info},
+};
+
+#[cfg(feature = "incidents")]
+pub type StoreError = sqlx::Error;
 
 /// Represents a circuit breaker incident or system event
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,10 +68,118 @@ pub struct Incident {
     // LLM analysis (added asynchronously)
     pub llm_analysis: Option<String>,
     pub llm_analyzed_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub analysis_source: Option<AnalysisSource>,
 
     // Outcome
     pub recovery_time_ms: Option<i64>,
     pub psi_after: Option<f32>,
+
+    // Remote sinks, keyed by sink name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub delivery: BTreeMap<String, SinkDelivery>,
+
+    // Endpoints connected to the target when it was acted on (`/topology`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependents: Vec<String>,
+}
+
+/// Who wrote `llm_analysis`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AnalysisSource {
+    Llm,
+    /// Rule-based summary written when the LLM missed its deadline; replaced
+    /// once a retry succeeds
+    Fallback,
+}
+
+impl AnalysisSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AnalysisSource::Llm => "llm",
+            AnalysisSource::Fallback => "fallback",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "llm" => Some(AnalysisSource::Llm),
+            "fallback" => Some(AnalysisSource::Fallback),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryState {
+    /// Last attempt failed; retrying
+    Pending,
+    Delivered,
+    /// Attempts exhausted or the endpoint rejected the request
+    Failed,
+}
+
+/// Outcome of sending the latest incident event to one sink
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SinkDelivery {
+    /// `trigger` or `resolve`
+    pub event: String,
+    pub state: DeliveryState,
+    pub attempts: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// Unix epoch seconds
+    pub updated_at: i64,
+}
+
+impl From<&Incident> for linnix_events::Incident {
+    fn from(incident: &Incident) -> Self {
+        Self {
+            id: incident.id,
+            timestamp: incident.timestamp,
+            event_type: incident.event_type.clone(),
+            psi_cpu: incident.psi_cpu,
+            psi_memory: incident.psi_memory,
+            cpu_percent: incident.cpu_percent,
+            load_avg: incident.load_avg.clone(),
+            action: incident.action.clone(),
+            target_pid: incident.target_pid,
+            target_name: incident.target_name.clone(),
+            system_snapshot: incident.system_snapshot.clone(),
+            llm_analysis: incident.llm_analysis.clone(),
+            llm_analyzed_at: incident.llm_analyzed_at,
+            analysis_source: incident.analysis_source.map(|source| match source {
+                AnalysisSource::Llm => linnix_events::AnalysisSource::Llm,
+                AnalysisSource::Fallback => linnix_events::AnalysisSource::Fallback,
+            }),
+            recovery_time_ms: incident.recovery_time_ms,
+            psi_after: incident.psi_after,
+            delivery: incident
+                .delivery
+                .iter()
+                .map(|(sink, d)| (sink.clone(), d.into()))
+                .collect(),
+            dependents: incident.dependents.clone(),
+        }
+    }
+}
+
+impl From<&SinkDelivery> for linnix_events::SinkDelivery {
+    fn from(delivery: &SinkDelivery) -> Self {
+        Self {
+            event: delivery.event.clone(),
+            state: match delivery.state {
+                DeliveryState::Pending => linnix_events::DeliveryState::Pending,
+                DeliveryState::Delivered => linnix_events::DeliveryState::Delivered,
+                DeliveryState::Failed => linnix_events::DeliveryState::Failed,
+            },
+            attempts: delivery.attempts,
+            last_error: delivery.last_error.clone(),
+            updated_at: delivery.updated_at,
+        }
+    }
 }
 
 /// Represents a stall attribution event
@@ -59,18 +197,39 @@ pub struct StallAttribution {
 }
 
 /// Incident storage backed by SQLite
+#[cfg(feature = "incidents")]
 pub struct IncidentStore {
     pool: SqlitePool,
+    queue: mpsc::Sender<writer::Command>,
+    queue_capacity: usize,
+    counters: Arc<writer::Counters>,
 }
 
+#[cfg(feature = "incidents")]
 impl IncidentStore {
-    /// Create a new incident store
-    pub async fn new<P: AsRef<Path>>(db_path: P) -> Result<Self, sqlx::Error> {
+    /// Create a new incident store with the default write queue settings
+    pub async fn new<P: AsRef<Path>>(db_path: P) -> Result<Self, StoreError> {
+        Self::with_config(db_path, &IncidentStoreConfig::default()).await
+    }
+
+    /// Create a new incident store and start its background writer
+    pub async fn with_config<P: AsRef<Path>>(
+        db_path: P,
+        config: &IncidentStoreConfig,
+    ) -> Result<Self, StoreError> {
         let db_url = format!("sqlite://{}?mode=rwc", db_path.as_ref().display());
+        let synchronous = match config.fsync {
+            FsyncPolicy::Full => SqliteSynchronous::Full,
+            FsyncPolicy::Normal => SqliteSynchronous::Normal,
+            FsyncPolicy::Off => SqliteSynchronous::Off,
+        };
+        let options = SqliteConnectOptions::from_str(&db_url)?
+            .journal_mode(SqliteJournalMode::Wal)
+            .synchronous(synchronous);
 
         let pool = SqlitePoolOptions::new()
             .max_connections(5)
-            .connect(&db_url)
+            .connect_with(options)
             .await?;
 
         // Create schema
@@ -91,7 +250,10 @@ impl IncidentStore {
                 llm_analysis TEXT,
                 llm_analyzed_at INTEGER,
                 recovery_time_ms INTEGER,
-                psi_after REAL
+                psi_after REAL,
+                delivery TEXT,
+                dependents TEXT,
+                analysis_source TEXT
             );
             CREATE INDEX IF NOT EXISTS idx_timestamp ON incidents(timestamp);
             CREATE INDEX IF NOT EXISTS idx_event_type ON incidents(event_type);
@@ -121,6 +283,18 @@ impl IncidentStore {
             CREATE INDEX IF NOT EXISTS idx_victim_time ON stall_attributions(victim_pod, victim_namespace, timestamp);
             CREATE INDEX IF NOT EXISTS idx_offender_time ON stall_attributions(offender_pod, offender_namespace, timestamp);
             CREATE INDEX IF NOT EXISTS idx_timestamp_attr ON stall_attributions(timestamp);
+            CREATE TABLE IF NOT EXISTS usage_rollups (
+                period TEXT NOT NULL,
+                start INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                name TEXT NOT NULL,
+                cpu_seconds REAL NOT NULL,
+                peak_rss_bytes INTEGER NOT NULL,
+                io_bytes INTEGER NOT NULL,
+                net_bytes INTEGER NOT NULL,
+                crashes INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (period, start, kind, name)
+            );
             "#,
         )
         .execute(&pool)
@@ -140,56 +314,108 @@ impl IncidentStore {
         )
         .execute(&pool)
         .await;
+        let _ = sqlx::query("ALTER TABLE incidents ADD COLUMN delivery TEXT")
+            .execute(&pool)
+            .await;
+        let _ = sqlx::query("ALTER TABLE incidents ADD COLUMN dependents TEXT")
+            .execute(&pool)
+            .await;
+        let _ = sqlx::query("ALTER TABLE incidents ADD COLUMN analysis_source TEXT")
+            .execute(&pool)
+            .await;
+        let _ =
+            sqlx::query("ALTER TABLE usage_rollups ADD COLUMN crashes INTEGER NOT NULL DEFAULT 0")
+                .execute(&pool)
+                .await;
+
+        let queue_capacity = config.queue_capacity.max(1);
+        let (queue, rx) = mpsc::channel(queue_capacity);
+        let counters = Arc::new(writer::Counters::default());
+        tokio::spawn(writer::run(
+            pool.clone(),
+            rx,
+            config.batch_size.max(1),
+            Arc::clone(&counters),
+        ));
 
         info!(
-            "Incident store initialized at {}",
-            db_path.as_ref().display()
+            "Incident store initialized at {} (fsync={:?}, queue={})",
+            db_path.as_ref().display(),
+            config.fsync,
+            queue_capacity
         );
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            queue,
+            queue_capacity,
+            counters,
+        })
     }
 
-    /// Insert a new incident
-    pub async fn insert(&self, incident: &Incident) -> Result<i64, sqlx::Error> {
-        let result = sqlx::query(
-            r#"
-            INSERT INTO incidents (
-                timestamp, event_type, psi_cpu, psi_memory, cpu_percent, load_avg,
-                action, target_pid, target_name, system_snapshot,
-                recovery_time_ms, psi_after
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-            "#,
-        )
-        .bind(incident.timestamp)
-        .bind(&incident.event_type)
-        .bind(incident.psi_cpu)
-        .bind(incident.psi_memory)
-        .bind(incident.cpu_percent)
-        .bind(&incident.load_avg)
-        .bind(&incident.action)
-        .bind(incident.target_pid)
-        .bind(&incident.target_name)
-        .bind(&incident.system_snapshot)
-        .bind(incident.recovery_time_ms)
-        .bind(incident.psi_after)
-        .execute(&self.pool)
-        .await?;
+    /// Queue a write without waiting for the disk. Returns `false` when the
+    /// queue is full and the write was dropped.
+    pub fn submit(&self, write: IncidentWrite) -> bool {
+        self.enqueue(writer::Command::Write(Box::new(write), None))
+    }
+
+    /// Queue an incident; the receiver yields its id once committed, or an
+    /// error if the write was dropped or failed.
+    pub fn submit_incident(&self, incident: Incident) -> oneshot::Receiver<i64> {
+        let (reply, rx) = oneshot::channel();
+        self.enqueue(writer::Command::Write(
+            Box::new(IncidentWrite::Incident(incident)),
+            Some(reply),
+        ));
+        rx
+    }
+
+    fn enqueue(&self, cmd: writer::Command) -> bool {
+        match self.queue.try_send(cmd) {
+            Ok(()) => true,
+            Err(_) => {
+                let dropped = self.counters.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                if dropped.is_power_of_two() {
+                    tracing::warn!("[incidents] write queue full, {} writes dropped", dropped);
+                }
+                false
+            }
+        }
+    }
+
+    /// Wait until every write queued before this call has been committed.
+    pub async fn flush(&self) {
+        let (done, rx) = oneshot::channel();
+        if self.queue.send(writer::Command::Flush(done)).await.is_ok() {
+            let _ = rx.await;
+        }
+    }
 
-        let id = result.last_insert_rowid();
+    pub fn write_stats(&self) -> WriteQueueStats {
+        WriteQueueStats {
+            queued: self.queue_capacity - self.queue.capacity(),
+            capacity: self.queue_capacity,
+            dropped_total: self.counters.dropped.load(Ordering::Relaxed),
+            written_total: self.counters.written.load(Ordering::Relaxed),
+            failed_total: self.counters.failed.load(Ordering::Relaxed),
+            batches_total: self.counters.batches.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Insert a new incident, waiting for the write
+    pub async fn insert(&self, incident: &Incident) -> Result<i64, StoreError> {
+        let id = writer::execute(&self.pool, &IncidentWrite::Incident(incident.clone())).await?;
         debug!("Inserted incident #{} (type: {})", id, incident.event_type);
         Ok(id)
     }
 
     /// Add LLM analysis to an existing incident
-    pub async fn add_llm_analysis(&self, id: i64, analysis: String) -> Result<(), sqlx::Error> {
-        let now = Utc::now().timestamp();
-
-        sqlx::query("UPDATE incidents SET llm_analysis = ?, llm_analyzed_at = ? WHERE id = ?")
-            .bind(analysis)
-            .bind(now)
-            .bind(id)
-            .execute(&self.pool)
-            .await?;
-
+    pub async fn add_llm_analysis(&self, id: i64, analysis: String) -> Result<(), StoreError> {
+        let write = IncidentWrite::LlmAnalysis {
+            id,
+            analysis,
+            source: AnalysisSource::Llm,
+        };
+        writer::execute(&self.pool, &write).await?;
         debug!("Added LLM analysis to incident #{}", id);
         Ok(())
     }
@@ -201,23 +427,14 @@ impl IncidentStore {
         label: &str,
         source: &str,
         user_id: Option<&str>,
-    ) -> Result<i64, sqlx::Error> {
-        let now = Utc::now().timestamp();
-        let result = sqlx::query(
-            r#"
-            INSERT INTO feedback (insight_id, timestamp, label, source, user_id)
-            VALUES (?, ?, ?, ?, ?)
-            "#,
-        )
-        .bind(insight_id)
-        .bind(now)
-        .bind(label)
-        .bind(source)
-        .bind(user_id)
-        .execute(&self.pool)
-        .await?;
-
-        let id = result.last_insert_rowid();
+    ) -> Result<i64, StoreError> {
+        let write = IncidentWrite::Feedback {
+            insight_id: insight_id.to_string(),
+            label: label.to_string(),
+            source: source.to_string(),
+            user_id: user_id.map(str::to_string),
+        };
+        let id = writer::execute(&self.pool, &write).await?;
         debug!("Inserted feedback #{} for insight {}", id, insight_id);
         Ok(id)
     }
@@ -236,30 +453,22 @@ impl IncidentStore {
         cpu_share: f64,
         fork_count: u64,
         short_job_count: u64,
-    ) -> Result<i64, sqlx::Error> {
-        let result = sqlx::query(
-            r#"
-            INSERT INTO stall_attributions (
-                victim_pod, victim_namespace, offender_pod, offender_namespace,
-                stall_us, blame_score, timestamp,
-                cpu_share, fork_count, short_job_count
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-            "#,
-        )
-        .bind(victim_pod)
-        .bind(victim_namespace)
-        .bind(offender_pod)
-        .bind(offender_namespace)
-        .bind(stall_us as i64)
-        .bind(blame_score)
-        .bind(timestamp as i64)
-        .bind(cpu_share)
-        .bind(fork_count as i64)
-        .bind(short_job_count as i64)
-        .execute(&self.pool)
-        .await?;
-
-        let id = result.last_insert_rowid();
+    ) -> Result<i64, StoreError> {
+        let write = IncidentWrite::StallAttribution {
+            victim_pod: victim_pod.to_string(),
+            victim_namespace: victim_namespace.to_string(),
+            attribution: StallAttribution {
+                offender_pod: offender_pod.to_string(),
+                offender_namespace: offender_namespace.to_string(),
+                stall_us,
+                blame_score,
+                timestamp,
+                cpu_share,
+                fork_count,
+                short_job_count,
+            },
+        };
+        let id = writer::execute(&self.pool, &write).await?;
         debug!(
             "Inserted stall attribution #{}: {}/{} blamed {}/{} (score={:.2})",
             id, victim_namespace, victim_pod, offender_namespace, offender_pod, blame_score
@@ -273,7 +482,7 @@ impl IncidentStore {
         victim_pod: &str,
         victim_namespace: &str,
         window_seconds: i64,
-    ) -> Result<Vec<StallAttribution>, sqlx::Error> {
+    ) -> Result<Vec<StallAttribution>, StoreError> {
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
@@ -310,13 +519,163 @@ impl IncidentStore {
             .collect())
     }
 
+    /// Usage rollups starting at or after `start` (unix seconds)
+    pub async fn usage_rollups_since(&self, start: i64) -> Result<Vec<UsageRollup>, StoreError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT json_object(
+                       'period', period, 'start', start, 'kind', kind, 'name', name,
+                       'cpu_seconds', cpu_seconds, 'peak_rss_bytes', peak_rss_bytes,
+                       'io_bytes', io_bytes, 'net_bytes', net_bytes, 'crashes', crashes)
+            FROM usage_rollups
+            WHERE start >= ?
+            "#,
+        )
+        .bind(start)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|r| serde_json::from_str(&r.get::<String, _>(0)).ok())
+            .collect())
+    }
+
+    /// Every feedback label, oldest first
+    pub async fn feedback_labels(&self) -> Result<Vec<FeedbackLabel>, StoreError> {
+        let rows = sqlx::query(
+            "SELECT insight_id, timestamp, label, source, user_id FROM feedback ORDER BY id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| FeedbackLabel {
+                insight_id: r.get(0),
+                timestamp: r.get(1),
+                label: r.get(2),
+                source: r.get(3),
+                user_id: r.get(4),
+            })
+            .collect())
+    }
+
+    /// Add rows exported from another store in one transaction. Incidents
+    /// keep their id unless it is taken by a different incident; rows
+    /// already present (same incident id and timestamp, same feedback) are
+    /// skipped, so importing a bundle twice adds nothing.
+    pub async fn import(
+        &self,
+        incidents: &[Incident],
+        feedback: &[FeedbackLabel],
+        usage_rollups: &[UsageRollup],
+    ) -> Result<ImportCounts, StoreError> {
+        let mut counts = ImportCounts::default();
+        let mut tx = self.pool.begin().await?;
+
+        for incident in incidents {
+            let existing: Option<(i64, String)> = match incident.id {
+                Some(id) => {
+                    sqlx::query_as("SELECT timestamp, event_type FROM incidents WHERE id = ?")
+                        .bind(id)
+                        .fetch_optional(&mut *tx)
+                        .await?
+                }
+                None => None,
+            };
+            let id = match existing {
+                Some((ts, kind)) if ts == incident.timestamp && kind == incident.event_type => {
+                    continue;
+                }
+                Some(_) => None,
+                None => incident.id,
+            };
+            let delivery = (!incident.delivery.is_empty())
+                .then(|| serde_json::to_string(&incident.delivery).ok())
+                .flatten();
+            let dependents = (!incident.dependents.is_empty())
+                .then(|| serde_json::to_string(&incident.dependents).ok())
+                .flatten();
+            sqlx::query(
+                r#"
+                INSERT INTO incidents (
+                    id, timestamp, event_type, psi_cpu, psi_memory, cpu_percent, load_avg,
+                    action, target_pid, target_name, system_snapshot,
+                    llm_analysis, llm_analyzed_at, recovery_time_ms, psi_after, delivery,
+                    dependents, analysis_source
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(id)
+            .bind(incident.timestamp)
+            .bind(&incident.event_type)
+            .bind(incident.psi_cpu)
+            .bind(incident.psi_memory)
+            .bind(incident.cpu_percent)
+            .bind(&incident.load_avg)
+            .bind(&incident.action)
+            .bind(incident.target_pid)
+            .bind(&incident.target_name)
+            .bind(&incident.system_snapshot)
+            .bind(&incident.llm_analysis)
+            .bind(incident.llm_analyzed_at)
+            .bind(incident.recovery_time_ms)
+            .bind(incident.psi_after)
+            .bind(delivery)
+            .bind(dependents)
+            .bind(incident.analysis_source.map(AnalysisSource::as_str))
+            .execute(&mut *tx)
+            .await?;
+            counts.incidents += 1;
+        }
+
+        for label in feedback {
+            let result = sqlx::query(
+                r#"
+                INSERT INTO feedback (insight_id, timestamp, label, source, user_id)
+                SELECT ?, ?, ?, ?, ?
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM feedback
+                    WHERE insight_id = ? AND timestamp = ? AND label = ? AND source = ?
+                )
+                "#,
+            )
+            .bind(&label.insight_id)
+            .bind(label.timestamp)
+            .bind(&label.label)
+            .bind(&label.source)
+            .bind(&label.user_id)
+            .bind(&label.insight_id)
+            .bind(label.timestamp)
+            .bind(&label.label)
+            .bind(&label.source)
+            .execute(&mut *tx)
+            .await?;
+            counts.feedback += result.rows_affected() as usize;
+        }
+
+        if !usage_rollups.is_empty() {
+            writer::execute(
+                &mut *tx,
+                &IncidentWrite::UsageRollups(usage_rollups.to_vec()),
+            )
+            .await?;
+            counts.usage_rollups = usage_rollups.len();
+        }
+
+        tx.commit().await?;
+        Ok(counts)
+    }
+
     /// Get incident by ID
-    pub async fn get(&self, id: i64) -> Result<Option<Incident>, sqlx::Error> {
+    pub async fn get(&self, id: i64) -> Result<Option<Incident>, StoreError> {
         let row = sqlx::query(
             r#"
             SELECT id, timestamp, event_type, psi_cpu, psi_memory, cpu_percent, load_avg,
                    action, target_pid, target_name, system_snapshot,
-                   llm_analysis, llm_analyzed_at, recovery_time_ms, psi_after
+                   llm_analysis, llm_analyzed_at, recovery_time_ms, psi_after, delivery,
+                   dependents, analysis_source
             FROM incidents WHERE id = ?
             "#,
         )
@@ -340,16 +699,20 @@ impl IncidentStore {
             llm_analyzed_at: r.get(12),
             recovery_time_ms: r.get(13),
             psi_after: r.get(14),
+            delivery: parse_delivery(r.get(15)),
+            dependents: parse_dependents(r.get(16)),
+            analysis_source: parse_source(r.get(17)),
         }))
     }
 
     /// Get recent incidents
-    pub async fn recent(&self, limit: i64) -> Result<Vec<Incident>, sqlx::Error> {
+    pub async fn recent(&self, limit: i64) -> Result<Vec<Incident>, StoreError> {
         let rows = sqlx::query(
             r#"
             SELECT id, timestamp, event_type, psi_cpu, psi_memory, cpu_percent, load_avg,
                    action, target_pid, target_name, system_snapshot,
-                   llm_analysis, llm_analyzed_at, recovery_time_ms, psi_after
+                   llm_analysis, llm_analyzed_at, recovery_time_ms, psi_after, delivery,
+                   dependents, analysis_source
             FROM incidents
             ORDER BY timestamp DESC
             LIMIT ?
@@ -377,6 +740,9 @@ impl IncidentStore {
                 llm_analyzed_at: r.get(12),
                 recovery_time_ms: r.get(13),
                 psi_after: r.get(14),
+                delivery: parse_delivery(r.get(15)),
+                dependents: parse_dependents(r.get(16)),
+                analysis_source: parse_source(r.get(17)),
             })
             .collect())
     }
@@ -386,13 +752,14 @@ impl IncidentStore {
         &self,
         start_timestamp: i64,
         event_type: Option<&str>,
-    ) -> Result<Vec<Incident>, sqlx::Error> {
+    ) -> Result<Vec<Incident>, StoreError> {
         let rows = if let Some(evt_type) = event_type {
             sqlx::query(
                 r#"
                 SELECT id, timestamp, event_type, psi_cpu, psi_memory, cpu_percent, load_avg,
                        action, target_pid, target_name, system_snapshot,
-                       llm_analysis, llm_analyzed_at, recovery_time_ms, psi_after
+                       llm_analysis, llm_analyzed_at, recovery_time_ms, psi_after, delivery,
+                       dependents, analysis_source
                 FROM incidents
                 WHERE timestamp >= ? AND event_type = ?
                 ORDER BY timestamp DESC
@@ -407,7 +774,8 @@ impl IncidentStore {
                 r#"
                 SELECT id, timestamp, event_type, psi_cpu, psi_memory, cpu_percent, load_avg,
                        action, target_pid, target_name, system_snapshot,
-                       llm_analysis, llm_analyzed_at, recovery_time_ms, psi_after
+                       llm_analysis, llm_analyzed_at, recovery_time_ms, psi_after, delivery,
+                       dependents, analysis_source
                 FROM incidents
                 WHERE timestamp >= ?
                 ORDER BY timestamp DESC
@@ -436,12 +804,68 @@ impl IncidentStore {
                 llm_analyzed_at: r.get(12),
                 recovery_time_ms: r.get(13),
                 psi_after: r.get(14),
+                delivery: parse_delivery(r.get(15)),
+                dependents: parse_dependents(r.get(16)),
+                analysis_source: parse_source(r.get(17)),
+            })
+            .collect())
+    }
+
+    /// Incidents with `start <= timestamp <= end` and an id above
+    /// `after_id`, in id order. Exports page through large ranges with it.
+    pub async fn page(
+        &self,
+        start: i64,
+        end: i64,
+        after_id: i64,
+        limit: i64,
+    ) -> Result<Vec<Incident>, StoreError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, timestamp, event_type, psi_cpu, psi_memory, cpu_percent, load_avg,
+                   action, target_pid, target_name, system_snapshot,
+                   llm_analysis, llm_analyzed_at, recovery_time_ms, psi_after, delivery,
+                   dependents, analysis_source
+            FROM incidents
+            WHERE timestamp >= ? AND timestamp <= ? AND id > ?
+            ORDER BY id ASC
+            LIMIT ?
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .bind(after_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| Incident {
+                id: Some(r.get(0)),
+                timestamp: r.get(1),
+                event_type: r.get(2),
+                psi_cpu: r.get(3),
+                psi_memory: r.get(4),
+                cpu_percent: r.get(5),
+                load_avg: r.get(6),
+                action: r.get(7),
+                target_pid: r.get(8),
+                target_name: r.get(9),
+                system_snapshot: r.get(10),
+                llm_analysis: r.get(11),
+                llm_analyzed_at: r.get(12),
+                recovery_time_ms: r.get(13),
+                psi_after: r.get(14),
+                delivery: parse_delivery(r.get(15)),
+                dependents: parse_dependents(r.get(16)),
+                analysis_source: parse_source(r.get(17)),
             })
             .collect())
     }
 
     /// Get statistics about incidents
-    pub async fn stats(&self) -> Result<IncidentStats, sqlx::Error> {
+    pub async fn stats(&self) -> Result<IncidentStats, StoreError> {
         let total_row = sqlx::query("SELECT COUNT(*) FROM incidents")
             .fetch_one(&self.pool)
             .await?;
@@ -474,6 +898,28 @@ impl IncidentStore {
     }
 }
 
+/// Delivery state column; unreadable JSON is treated as no deliveries.
+#[cfg(feature = "incidents")]
+fn parse_delivery(column: Option<String>) -> BTreeMap<String, SinkDelivery> {
+    column
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Dependents column; unreadable JSON is treated as none recorded.
+#[cfg(feature = "incidents")]
+fn parse_dependents(column: Option<String>) -> Vec<String> {
+    column
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Analysis source column; unknown names are treated as unset.
+#[cfg(feature = "incidents")]
+fn parse_source(column: Option<String>) -> Option<AnalysisSource> {
+    column.as_deref().and_then(AnalysisSource::parse)
+}
+
 /// Statistics about stored incidents
 #[derive(Debug, Serialize)]
 pub struct IncidentStats {
@@ -482,3 +928,120 @@ pub struct IncidentStats {
     pub avg_recovery_time_ms: Option<u64>,
     pub feedback_entries: u64,
 }
+
+/// A feedback label on an insight, as carried in state bundles
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FeedbackLabel {
+    pub insight_id: String,
+    /// Unix epoch seconds
+    pub timestamp: i64,
+    pub label: String,
+    pub source: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+}
+
+/// Rows added by `IncidentStore::import`
+#[derive(Debug, Default, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct ImportCounts {
+    pub incidents: usize,
+    pub feedback: usize,
+    pub usage_rollups: usize,
+}
+
+#[cfg(all(test, feature = "incidents"))]
+mod tests {
+    use super::*;
+
+    fn incident(ts: i64) -> Incident {
+        Incident {
+            id: None,
+            timestamp: ts,
+            event_type: "circuit_breaker_cpu".to_string(),
+            psi_cpu: 42.0,
+            psi_memory: 0.0,
+            cpu_percent: 97.0,
+            load_avg: "4.00,3.00,2.00".to_string(),
+            action: "auto_kill".to_string(),
+            target_pid: Some(1234),
+            target_name: Some("stress".to_string()),
+            system_snapshot: None,
+            llm_analysis: None,
+            llm_analyzed_at: None,
+            analysis_source: None,
+            recovery_time_ms: None,
+            psi_after: None,
+            delivery: BTreeMap::new(),
+            dependents: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn queued_writes_commit_in_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = IncidentStoreConfig {
+            queue_capacity: 64,
+            batch_size: 8,
+            fsync: FsyncPolicy::Off,
+            sinks: Vec::new(),
+        };
+        let store = IncidentStore::with_config(dir.path().join("incidents.db"), &config)
+            .await
+            .unwrap();
+
+        let pending: Vec<_> = (0..20)
+            .map(|ts| store.submit_incident(incident(ts)))
+            .collect();
+        store.flush().await;
+
+        let mut ids = Vec::new();
+        for rx in pending {
+            ids.push(rx.await.expect("incident committed"));
+        }
+        ids.dedup();
+        assert_eq!(ids.len(), 20);
+
+        assert!(store.submit(IncidentWrite::LlmAnalysis {
+            id: ids[0],
+            analysis: "runaway loop".to_string(),
+            source: AnalysisSource::Llm,
+        }));
+        store.flush().await;
+        let first = store.get(ids[0]).await.unwrap().unwrap();
+        assert_eq!(first.llm_analysis.as_deref(), Some("runaway loop"));
+
+        let stats = store.write_stats();
+        assert_eq!(stats.written_total, 21);
+        assert!(stats.batches_total >= 3, "batches of at most 8");
+        assert_eq!(stats.queued, 0);
+    }
+
+    #[tokio::test]
+    async fn full_queue_drops_and_counts() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = IncidentStoreConfig {
+            queue_capacity: 2,
+            ..IncidentStoreConfig::default()
+        };
+        let store = IncidentStore::with_config(dir.path().join("incidents.db"), &config)
+            .await
+            .unwrap();
+
+        // The writer cannot run until we yield, so the queue fills up.
+        let accepted = (0..5)
+            .filter(|i| {
+                store.submit(IncidentWrite::Feedback {
+                    insight_id: format!("insight-{i}"),
+                    label: "useful".to_string(),
+                    source: "cli".to_string(),
+                    user_id: None,
+                })
+            })
+            .count();
+        assert_eq!(accepted, 2);
+        assert_eq!(store.write_stats().dropped_total, 3);
+
+        store.flush().await;
+        assert_eq!(store.stats().await.unwrap().feedback_entries, 2);
+    }
+}
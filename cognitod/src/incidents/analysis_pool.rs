@@ -0,0 +1,459 @@
+//! Worker pool for LLM incident analysis
+//!
+//! Analyses are queued by priority (circuit-breaker incidents, then manual
+//! requests, then batch re-analysis; FIFO within a priority) and run by a
+//! fixed number of workers, so a burst of incidents cannot open unbounded
+//! connections to the LLM. A new request for an incident supersedes the
+//! previous one: a queued job is replaced and an in-flight one is cancelled.
+//! When the queue is full the least urgent job is dropped.
+//!
+//! An analysis that fails or outlives `analysis_deadline_ms` gets the
+//! rule-based summary from `fallback` instead, and is retried at batch
+//! priority after `analysis_retry_secs`, doubling per attempt, up to
+//! `analysis_max_retries` times. A successful retry replaces the fallback.
+
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::{debug, info, warn};
+
+use super::{AnalysisSource, Incident, IncidentAnalyzer, IncidentStore, IncidentWrite};
+use crate::config::ReasonerConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalysisPriority {
+    CircuitBreaker,
+    Manual,
+    Batch,
+}
+
+struct Job {
+    priority: AnalysisPriority,
+    seq: u64,
+    id: i64,
+    incident: Incident,
+    /// Retries already made after a fallback
+    attempt: u32,
+}
+
+impl Job {
+    /// Heap key: most urgent priority first, then oldest.
+    fn key(&self) -> (Reverse<AnalysisPriority>, Reverse<u64>) {
+        (Reverse(self.priority), Reverse(self.seq))
+    }
+}
+
+impl PartialEq for Job {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Job {}
+
+impl PartialOrd for Job {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Job {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: BinaryHeap<Job>,
+    /// Incidents being analysed, with the signal that cancels them.
+    in_flight: HashMap<i64, Arc<Notify>>,
+    seq: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    completed: AtomicU64,
+    failed: AtomicU64,
+    cancelled: AtomicU64,
+    dropped: AtomicU64,
+    timed_out: AtomicU64,
+    fallbacks: AtomicU64,
+    retries: AtomicU64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QueuedByPriority {
+    pub circuit_breaker: usize,
+    pub manual: usize,
+    pub batch: usize,
+}
+
+/// Queue depth and outcome counters, reported in `/status`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AnalysisQueueStats {
+    pub workers: usize,
+    pub capacity: usize,
+    pub queued: QueuedByPriority,
+    pub in_flight: usize,
+    pub completed_total: u64,
+    pub failed_total: u64,
+    /// Superseded by a newer request for the same incident.
+    pub cancelled_total: u64,
+    /// Evicted or rejected because the queue was full.
+    pub dropped_total: u64,
+    /// Analyses that outlived the deadline (not counted as failed).
+    pub timed_out_total: u64,
+    /// Rule-based summaries written after a failure or timeout.
+    pub fallbacks_total: u64,
+    pub retries_total: u64,
+}
+
+pub struct AnalysisPool {
+    queue: Mutex<Queue>,
+    ready: Notify,
+    workers: usize,
+    capacity: usize,
+    deadline: Duration,
+    retry_delay: Duration,
+    max_retries: u32,
+    counters: Counters,
+}
+
+impl AnalysisPool {
+    fn new(config: &ReasonerConfig) -> Self {
+        Self {
+            queue: Mutex::new(Queue::default()),
+            ready: Notify::new(),
+            workers: config.analysis_workers.max(1),
+            capacity: config.analysis_queue_capacity.max(1),
+            deadline: Duration::from_millis(config.analysis_deadline_ms.max(1)),
+            retry_delay: Duration::from_secs(config.analysis_retry_secs.max(1)),
+            max_retries: config.analysis_max_retries,
+            counters: Counters::default(),
+        }
+    }
+
+    /// Start the workers that analyse queued incidents and store the result.
+    pub fn spawn(
+        analyzer: Arc<IncidentAnalyzer>,
+        store: Arc<IncidentStore>,
+        config: &ReasonerConfig,
+    ) -> Arc<Self> {
+        let pool = Arc::new(Self::new(config));
+        for _ in 0..pool.workers {
+            tokio::spawn(Arc::clone(&pool).work(Arc::clone(&analyzer), Arc::clone(&store)));
+        }
+        info!(
+            "[incident_analyzer] {} analysis workers, queue capacity {}, deadline {:?}",
+            pool.workers, pool.capacity, pool.deadline
+        );
+        pool
+    }
+
+    /// Queue an analysis of incident `id`. Replaces a queued request for the
+    /// same incident (keeping the more urgent priority) and cancels one in
+    /// flight. Returns false when the queue is full of more urgent work.
+    pub fn submit(&self, id: i64, incident: Incident, priority: AnalysisPriority) -> bool {
+        self.enqueue(id, incident, priority, 0)
+    }
+
+    fn enqueue(
+        &self,
+        id: i64,
+        incident: Incident,
+        priority: AnalysisPriority,
+        attempt: u32,
+    ) -> bool {
+        let mut queue = self.queue.lock().unwrap();
+        let mut priority = priority;
+        let before = queue.jobs.len();
+        queue.jobs.retain(|job| {
+            if job.id == id {
+                priority = priority.min(job.priority);
+            }
+            job.id != id
+        });
+        let mut superseded = (before - queue.jobs.len()) as u64;
+        if let Some(cancel) = queue.in_flight.remove(&id) {
+            cancel.notify_one();
+            superseded += 1;
+        }
+        self.counters
+            .cancelled
+            .fetch_add(superseded, Ordering::Relaxed);
+
+        if queue.jobs.len() >= self.capacity {
+            let least_urgent = queue.jobs.iter().min().map(|job| job.priority);
+            if least_urgent.is_none_or(|p| p <= priority) {
+                self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                debug!("[incident_analyzer] queue full, dropped incident #{id}");
+                return false;
+            }
+            let mut jobs = std::mem::take(&mut queue.jobs).into_vec();
+            jobs.sort();
+            let evicted = jobs.remove(0);
+            queue.jobs = jobs.into();
+            self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+            debug!(
+                "[incident_analyzer] queue full, evicted incident #{}",
+                evicted.id
+            );
+        }
+
+        queue.seq += 1;
+        let seq = queue.seq;
+        queue.jobs.push(Job {
+            priority,
+            seq,
+            id,
+            incident,
+            attempt,
+        });
+        drop(queue);
+        self.ready.notify_one();
+        true
+    }
+
+    /// Most urgent queued job, registered as in flight.
+    fn take(&self) -> Option<(Job, Arc<Notify>)> {
+        let mut queue = self.queue.lock().unwrap();
+        let job = queue.jobs.pop()?;
+        let cancel = Arc::new(Notify::new());
+        queue.in_flight.insert(job.id, Arc::clone(&cancel));
+        Some((job, cancel))
+    }
+
+    /// Clear the in-flight entry unless a newer request already replaced it.
+    fn finish(&self, id: i64, cancel: &Arc<Notify>) {
+        let mut queue = self.queue.lock().unwrap();
+        if queue
+            .in_flight
+            .get(&id)
+            .is_some_and(|current| Arc::ptr_eq(current, cancel))
+        {
+            queue.in_flight.remove(&id);
+        }
+    }
+
+    /// Queue a retry of `job` unless a newer request for the incident is
+    /// already queued or running.
+    fn retry(&self, job: Job) -> bool {
+        {
+            let queue = self.queue.lock().unwrap();
+            if queue.in_flight.contains_key(&job.id) || queue.jobs.iter().any(|j| j.id == job.id) {
+                return false;
+            }
+        }
+        self.counters.retries.fetch_add(1, Ordering::Relaxed);
+        self.enqueue(
+            job.id,
+            job.incident,
+            AnalysisPriority::Batch,
+            job.attempt + 1,
+        )
+    }
+
+    /// Store the rule-based summary and schedule the next LLM attempt.
+    fn fall_back(self: &Arc<Self>, store: &IncidentStore, job: Job) {
+        let summary = super::fallback_analysis(&job.incident);
+        if let Ok(analysis) = serde_json::to_string(&summary) {
+            self.counters.fallbacks.fetch_add(1, Ordering::Relaxed);
+            store.submit(IncidentWrite::LlmAnalysis {
+                id: job.id,
+                analysis,
+                source: AnalysisSource::Fallback,
+            });
+        }
+        if job.attempt >= self.max_retries {
+            debug!(
+                "[incident_analyzer] incident #{} keeps its fallback summary",
+                job.id
+            );
+            return;
+        }
+        let delay = self.retry_delay * 2u32.saturating_pow(job.attempt);
+        let pool = Arc::clone(self);
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            pool.retry(job);
+        });
+    }
+
+    async fn work(self: Arc<Self>, analyzer: Arc<IncidentAnalyzer>, store: Arc<IncidentStore>) {
+        loop {
+            let Some((job, cancel)) = self.take() else {
+                self.ready.notified().await;
+                continue;
+            };
+            let id = job.id;
+            tokio::select! {
+                result = tokio::time::timeout(self.deadline, analyzer.analyze(&job.incident)) => {
+                    match result {
+                        Ok(Ok(analysis)) => {
+                            self.counters.completed.fetch_add(1, Ordering::Relaxed);
+                            store.submit(IncidentWrite::LlmAnalysis {
+                                id,
+                                analysis,
+                                source: AnalysisSource::Llm,
+                            });
+                        }
+                        Ok(Err(e)) => {
+                            self.counters.failed.fetch_add(1, Ordering::Relaxed);
+                            warn!("[incident_analyzer] Failed: {}", e);
+                            self.fall_back(&store, job);
+                        }
+                        Err(_) => {
+                            self.counters.timed_out.fetch_add(1, Ordering::Relaxed);
+                            warn!(
+                                "[incident_analyzer] incident #{} missed the {:?} deadline",
+                                id, self.deadline
+                            );
+                            self.fall_back(&store, job);
+                        }
+                    }
+                }
+                _ = cancel.notified() => {
+                    debug!("[incident_analyzer] analysis of incident #{} superseded", id);
+                }
+            }
+            self.finish(id, &cancel);
+        }
+    }
+
+    pub fn stats(&self) -> AnalysisQueueStats {
+        let queue = self.queue.lock().unwrap();
+        let mut queued = QueuedByPriority::default();
+        for job in &queue.jobs {
+            match job.priority {
+                AnalysisPriority::CircuitBreaker => queued.circuit_breaker += 1,
+                AnalysisPriority::Manual => queued.manual += 1,
+                AnalysisPriority::Batch => queued.batch += 1,
+            }
+        }
+        AnalysisQueueStats {
+            workers: self.workers,
+            capacity: self.capacity,
+            queued,
+            in_flight: queue.in_flight.len(),
+            completed_total: self.counters.completed.load(Ordering::Relaxed),
+            failed_total: self.counters.failed.load(Ordering::Relaxed),
+            cancelled_total: self.counters.cancelled.load(Ordering::Relaxed),
+            dropped_total: self.counters.dropped.load(Ordering::Relaxed),
+            timed_out_total: self.counters.timed_out.load(Ordering::Relaxed),
+            fallbacks_total: self.counters.fallbacks.load(Ordering::Relaxed),
+            retries_total: self.counters.retries.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(capacity: usize) -> AnalysisPool {
+        AnalysisPool::new(&ReasonerConfig {
+            analysis_workers: 1,
+            analysis_queue_capacity: capacity,
+            ..Default::default()
+        })
+    }
+
+    fn incident(event_type: &str) -> Incident {
+        Incident {
+            id: None,
+            timestamp: 0,
+            event_type: event_type.to_string(),
+            psi_cpu: 0.0,
+            psi_memory: 0.0,
+            cpu_percent: 0.0,
+            load_avg: String::new(),
+            action: "freeze".to_string(),
+            target_pid: None,
+            target_name: None,
+            system_snapshot: None,
+            llm_analysis: None,
+            llm_analyzed_at: None,
+            analysis_source: None,
+            recovery_time_ms: None,
+            psi_after: None,
+            delivery: Default::default(),
+            dependents: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn takes_by_priority_then_age() {
+        let pool = pool(8);
+        pool.submit(1, incident("a"), AnalysisPriority::Batch);
+        pool.submit(2, incident("b"), AnalysisPriority::Manual);
+        pool.submit(3, incident("c"), AnalysisPriority::CircuitBreaker);
+        pool.submit(4, incident("d"), AnalysisPriority::Manual);
+        let order: Vec<i64> = std::iter::from_fn(|| pool.take().map(|(job, _)| job.id)).collect();
+        assert_eq!(order, vec![3, 2, 4, 1]);
+        assert_eq!(pool.stats().in_flight, 4);
+    }
+
+    #[test]
+    fn newer_request_supersedes_queued_and_in_flight() {
+        let pool = pool(8);
+        pool.submit(1, incident("old"), AnalysisPriority::CircuitBreaker);
+        pool.submit(1, incident("new"), AnalysisPriority::Batch);
+        let (job, cancel) = pool.take().unwrap();
+        assert_eq!(job.incident.event_type, "new");
+        assert_eq!(
+            job.priority,
+            AnalysisPriority::CircuitBreaker,
+            "keeps urgency"
+        );
+
+        pool.submit(1, incident("newer"), AnalysisPriority::Manual);
+        assert!(
+            futures_util::FutureExt::now_or_never(cancel.notified()).is_some(),
+            "in-flight analysis cancelled"
+        );
+        pool.finish(1, &cancel);
+        let (job, _) = pool.take().unwrap();
+        assert_eq!(job.incident.event_type, "newer");
+        assert_eq!(pool.stats().cancelled_total, 2);
+    }
+
+    #[test]
+    fn full_queue_evicts_least_urgent() {
+        let pool = pool(2);
+        assert!(pool.submit(1, incident("a"), AnalysisPriority::Batch));
+        assert!(pool.submit(2, incident("b"), AnalysisPriority::Manual));
+        assert!(!pool.submit(3, incident("c"), AnalysisPriority::Batch));
+        assert!(pool.submit(4, incident("d"), AnalysisPriority::CircuitBreaker));
+        let stats = pool.stats();
+        assert_eq!((stats.queued.batch, stats.dropped_total), (0, 2));
+        let order: Vec<i64> = std::iter::from_fn(|| pool.take().map(|(job, _)| job.id)).collect();
+        assert_eq!(order, vec![4, 2]);
+    }
+
+    #[test]
+    fn retry_yields_to_newer_requests() {
+        let pool = pool(8);
+        pool.submit(1, incident("a"), AnalysisPriority::CircuitBreaker);
+        let (job, cancel) = pool.take().unwrap();
+        pool.finish(1, &cancel);
+
+        pool.submit(1, incident("manual"), AnalysisPriority::Manual);
+        assert!(!pool.retry(job), "a queued request wins");
+        let (job, cancel) = pool.take().unwrap();
+        pool.finish(1, &cancel);
+
+        assert!(pool.retry(job));
+        let (retry, _) = pool.take().unwrap();
+        assert_eq!(
+            (retry.priority, retry.attempt),
+            (AnalysisPriority::Batch, 1)
+        );
+        assert_eq!(pool.stats().retries_total, 1);
+    }
+}
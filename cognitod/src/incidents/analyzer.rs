@@ -7,9 +7,18 @@
 //! - Detect patterns across multiple incidents
 
 use super::Incident;
+#[cfg(feature = "reasoner")]
+use crate::config::{Egress, OfflineGuard};
+#[cfg(feature = "reasoner")]
+use crate::retry::{Destination, check_response, url_destination};
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "reasoner")]
 use serde_json::json;
+#[cfg(feature = "reasoner")]
+use std::sync::Arc;
+#[cfg(feature = "reasoner")]
 use std::time::Duration;
+#[cfg(feature = "reasoner")]
 use tracing::{debug, error, info};
 
 /// Analysis result from LLM
@@ -31,17 +40,28 @@ pub struct PodContribution {
 }
 
 /// Incident analyzer using local LLM
+#[cfg(feature = "reasoner")]
 pub struct IncidentAnalyzer {
     endpoint: String,
     client: reqwest::Client,
+    destination: Arc<Destination>,
 }
 
+#[cfg(feature = "reasoner")]
 impl IncidentAnalyzer {
-    /// Create a new incident analyzer
-    pub fn new(endpoint: String, timeout: Duration) -> Result<Self, reqwest::Error> {
-        let client = reqwest::Client::builder().timeout(timeout).build()?;
-
-        Ok(Self { endpoint, client })
+    /// Create a new incident analyzer; requests obey the `llm` egress policy
+    /// and are retried under the endpoint's breaker.
+    pub fn new(endpoint: String, timeout: Duration, guard: &Arc<OfflineGuard>) -> Self {
+        let client = guard.client(Egress::Llm, reqwest::Client::builder().timeout(timeout));
+        let destination = guard
+            .retrier()
+            .destination(&url_destination("llm", &endpoint));
+
+        Self {
+            endpoint,
+            client,
+            destination,
+        }
     }
 
     /// Analyze an incident using the LLM
@@ -74,19 +94,17 @@ impl IncidentAnalyzer {
             incident.target_name
         );
 
-        let response = self
-            .client
-            .post(&self.endpoint)
-            .json(&request_body)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            error!(target: "audit", "LLM request failed. Status: {}, Error: {}", status, body);
-            return Err(format!("LLM request failed: {} - {}", status, body).into());
-        }
+        let (client, endpoint, body) = (&self.client, &self.endpoint, &request_body);
+        let response =
+            self.destination
+                .run(|_| async move {
+                    check_response(client.post(endpoint).json(body).send().await).await
+                })
+                .await
+                .map_err(|e| {
+                    error!(target: "audit", "LLM request failed: {:#}", e);
+                    format!("LLM request failed: {e:#}")
+                })?;
 
         let response_json: serde_json::Value = response.json().await?;
 
@@ -121,6 +139,9 @@ Event Type: {}
 ACTION TAKEN BY CIRCUIT BREAKER:
 {} - Target Process: {} (PID: {})
 
+DEPENDENTS (clients connected to the target, i.e. the blast radius):
+{}
+
 SYSTEM METRICS AT INCIDENT TIME:
 - CPU Usage: {:.1}%
 - CPU PSI (Pressure Stall): {:.1}%
@@ -156,6 +177,11 @@ Format your response as a JSON object:
             incident.action,
             incident.target_name.as_deref().unwrap_or("unknown"),
             incident.target_pid.unwrap_or(0),
+            if incident.dependents.is_empty() {
+                "none observed".to_string()
+            } else {
+                incident.dependents.join(", ")
+            },
             incident.cpu_percent,
             incident.psi_cpu,
             incident.psi_memory,
@@ -201,7 +227,24 @@ Format your response as a JSON object:
     }
 }
 
-#[cfg(test)]
+/// Stand-in for builds without the `reasoner` feature. It has no
+/// constructor, so callers' `Option<Arc<IncidentAnalyzer>>` is always `None`.
+#[cfg(not(feature = "reasoner"))]
+pub struct IncidentAnalyzer {
+    never: std::convert::Infallible,
+}
+
+#[cfg(not(feature = "reasoner"))]
+impl IncidentAnalyzer {
+    pub async fn analyze(
+        &self,
+        _incident: &Incident,
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        match self.never {}
+    }
+}
+
+#[cfg(all(test, feature = "reasoner"))]
 mod tests {
     use super::*;
 
@@ -240,20 +283,24 @@ Here is the analysis:
             system_snapshot: None,
             llm_analysis: None,
             llm_analyzed_at: None,
+            analysis_source: None,
             recovery_time_ms: None,
             psi_after: None,
+            delivery: Default::default(),
+            dependents: vec!["pod/shop/api-1".to_string()],
         };
 
         let analyzer = IncidentAnalyzer::new(
             "http://localhost:8090/v1/chat/completions".to_string(),
             Duration::from_secs(30),
-        )
-        .unwrap();
+            &Arc::new(OfflineGuard::new(false)),
+        );
 
         let prompt = analyzer.build_analysis_prompt(&incident);
 
         assert!(prompt.contains("75.2%")); // .1 precision
         assert!(prompt.contains("aggressive-stress.sh"));
         assert!(prompt.contains("Dual-signal CPU thrashing"));
+        assert!(prompt.contains("pod/shop/api-1"));
     }
 }
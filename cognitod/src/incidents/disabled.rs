@@ -0,0 +1,176 @@
+//! Incident store for builds without the `incidents` feature
+//!
+//! Opening always fails, so callers keep their `Option<Arc<IncidentStore>>`
+//! at `None` and the existing "store unavailable" paths apply. The methods
+//! mirror the SQLite store so call sites compile unchanged.
+
+use std::convert::Infallible;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::oneshot;
+
+use super::{
+    FeedbackLabel, ImportCounts, Incident, IncidentStats, IncidentWrite, StallAttribution,
+    WriteQueueStats,
+};
+use crate::config::{IncidentSinkConfig, IncidentStoreConfig, OfflineGuard};
+use crate::leaderboard::UsageRollup;
+
+#[derive(Debug)]
+pub struct StoreError;
+
+impl std::fmt::Display for StoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("incident store not compiled in (build with the `incidents` feature)")
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+pub struct IncidentStore {
+    never: Infallible,
+}
+
+impl IncidentStore {
+    pub async fn new<P: AsRef<Path>>(_db_path: P) -> Result<Self, StoreError> {
+        Err(StoreError)
+    }
+
+    pub async fn with_config<P: AsRef<Path>>(
+        _db_path: P,
+        _config: &IncidentStoreConfig,
+    ) -> Result<Self, StoreError> {
+        Err(StoreError)
+    }
+
+    pub fn submit(&self, _write: IncidentWrite) -> bool {
+        match self.never {}
+    }
+
+    pub fn submit_incident(&self, _incident: Incident) -> oneshot::Receiver<i64> {
+        match self.never {}
+    }
+
+    pub async fn flush(&self) {
+        match self.never {}
+    }
+
+    pub fn write_stats(&self) -> WriteQueueStats {
+        match self.never {}
+    }
+
+    pub async fn insert(&self, _incident: &Incident) -> Result<i64, StoreError> {
+        match self.never {}
+    }
+
+    pub async fn add_llm_analysis(&self, _id: i64, _analysis: String) -> Result<(), StoreError> {
+        match self.never {}
+    }
+
+    pub async fn insert_feedback(
+        &self,
+        _insight_id: &str,
+        _label: &str,
+        _source: &str,
+        _user_id: Option<&str>,
+    ) -> Result<i64, StoreError> {
+        match self.never {}
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn insert_stall_attribution(
+        &self,
+        _victim_pod: &str,
+        _victim_namespace: &str,
+        _offender_pod: &str,
+        _offender_namespace: &str,
+        _stall_us: u64,
+        _blame_score: f64,
+        _timestamp: u64,
+        _cpu_share: f64,
+        _fork_count: u64,
+        _short_job_count: u64,
+    ) -> Result<i64, StoreError> {
+        match self.never {}
+    }
+
+    pub async fn query_attributions(
+        &self,
+        _victim_pod: &str,
+        _victim_namespace: &str,
+        _window_seconds: i64,
+    ) -> Result<Vec<StallAttribution>, StoreError> {
+        match self.never {}
+    }
+
+    pub async fn usage_rollups_since(&self, _start: i64) -> Result<Vec<UsageRollup>, StoreError> {
+        match self.never {}
+    }
+
+    pub async fn get(&self, _id: i64) -> Result<Option<Incident>, StoreError> {
+        match self.never {}
+    }
+
+    pub async fn recent(&self, _limit: i64) -> Result<Vec<Incident>, StoreError> {
+        match self.never {}
+    }
+
+    pub async fn since(
+        &self,
+        _start_timestamp: i64,
+        _event_type: Option<&str>,
+    ) -> Result<Vec<Incident>, StoreError> {
+        match self.never {}
+    }
+
+    pub async fn page(
+        &self,
+        _start: i64,
+        _end: i64,
+        _after_id: i64,
+        _limit: i64,
+    ) -> Result<Vec<Incident>, StoreError> {
+        match self.never {}
+    }
+
+    pub async fn feedback_labels(&self) -> Result<Vec<FeedbackLabel>, StoreError> {
+        match self.never {}
+    }
+
+    pub async fn import(
+        &self,
+        _incidents: &[Incident],
+        _feedback: &[FeedbackLabel],
+        _usage_rollups: &[UsageRollup],
+    ) -> Result<ImportCounts, StoreError> {
+        match self.never {}
+    }
+
+    pub async fn stats(&self) -> Result<IncidentStats, StoreError> {
+        match self.never {}
+    }
+}
+
+pub struct IncidentSinks {
+    never: Infallible,
+}
+
+impl IncidentSinks {
+    pub fn spawn(
+        _configs: &[IncidentSinkConfig],
+        _store: Arc<IncidentStore>,
+        _host: String,
+        _templates: Arc<crate::templates::Templates>,
+        _guard: &Arc<OfflineGuard>,
+    ) -> Option<Arc<Self>> {
+        None
+    }
+
+    pub fn opened(&self, _id: i64, _incident: &Incident) {
+        match self.never {}
+    }
+
+    pub fn resolved(&self, _id: i64, _incident: &Incident) {
+        match self.never {}
+    }
+}
@@ -0,0 +1,153 @@
+//! Rule-based incident summary
+//!
+//! Written in place of the LLM analysis when the LLM misses its deadline, so
+//! an incident is never left unexplained. It reads only what the incident
+//! recorded (trigger metrics, the system snapshot, the target and its
+//! dependents) and has the same JSON shape the LLM is asked for, with a low
+//! confidence. A later LLM retry replaces it.
+
+use linnix_events::SystemSnapshot;
+
+use super::{Incident, IncidentAnalysis};
+
+/// Confidence reported for rule-based summaries.
+const FALLBACK_CONFIDENCE: f32 = 0.3;
+
+/// Stall percentage above which a resource counts as the bottleneck.
+const PRESSURE_PCT: f32 = 10.0;
+
+/// Dependents named in the summary before the rest are counted.
+const MAX_DEPENDENTS: usize = 3;
+
+pub fn fallback_analysis(incident: &Incident) -> IncidentAnalysis {
+    let snapshot: Option<SystemSnapshot> = incident
+        .system_snapshot
+        .as_deref()
+        .and_then(|json| serde_json::from_str(json).ok());
+    let io_full = snapshot.as_ref().map_or(0.0, |s| s.psi_io_full_avg10);
+
+    let (reason_code, cause, next_step) = if incident.event_type == crate::oom::RULE_NAME {
+        (
+            "oom_risk",
+            format!(
+                "the kernel ran out of memory ({:.1}% full memory stall)",
+                incident.psi_memory
+            ),
+            "Compare the victim's RSS with its cgroup's memory limit.",
+        )
+    } else if incident.event_type.contains("memory") || incident.psi_memory >= PRESSURE_PCT {
+        (
+            "oom_risk",
+            format!("memory pressure ({:.1}% full stall)", incident.psi_memory),
+            "Check the target's memory limit and recent growth in its RSS.",
+        )
+    } else if incident.event_type.ends_with("_io") || io_full >= PRESSURE_PCT {
+        (
+            "io_saturation",
+            format!("I/O pressure ({io_full:.1}% full stall)"),
+            "Find the heaviest writers on the saturated device and throttle them.",
+        )
+    } else if incident.event_type.contains("cpu") {
+        (
+            "cpu_spin",
+            format!(
+                "CPU at {:.1}% with {:.1}% CPU pressure",
+                incident.cpu_percent, incident.psi_cpu
+            ),
+            "Profile the target or lower its CPU quota before restarting it.",
+        )
+    } else {
+        (
+            "normal",
+            format!("a {} event", incident.event_type),
+            "Review the incident snapshot; no dominant resource stood out.",
+        )
+    };
+
+    let target = match (&incident.target_name, incident.target_pid) {
+        (Some(name), Some(pid)) => format!("{name} (pid {pid})"),
+        (Some(name), None) => name.clone(),
+        (None, Some(pid)) => format!("pid {pid}"),
+        (None, None) => "no process".to_string(),
+    };
+    let mut summary = format!(
+        "{} on {} after {}, load {}.",
+        incident.action, target, cause, incident.load_avg
+    );
+    if !incident.dependents.is_empty() {
+        let named: Vec<&str> = incident
+            .dependents
+            .iter()
+            .take(MAX_DEPENDENTS)
+            .map(String::as_str)
+            .collect();
+        summary.push_str(&format!(" Connected clients: {}", named.join(", ")));
+        let rest = incident.dependents.len().saturating_sub(MAX_DEPENDENTS);
+        if rest > 0 {
+            summary.push_str(&format!(" and {rest} more"));
+        }
+        summary.push('.');
+    }
+
+    IncidentAnalysis {
+        reason_code: reason_code.to_string(),
+        summary,
+        confidence: FALLBACK_CONFIDENCE,
+        suggested_next_step: next_step.to_string(),
+        top_pods: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarizes_from_recorded_data() {
+        let incident = Incident {
+            id: Some(7),
+            timestamp: 0,
+            event_type: "circuit_breaker_cpu".to_string(),
+            psi_cpu: 61.5,
+            psi_memory: 0.0,
+            cpu_percent: 97.0,
+            load_avg: "8.00,6.00,4.00".to_string(),
+            action: "auto_killed".to_string(),
+            target_pid: Some(4242),
+            target_name: Some("stress".to_string()),
+            system_snapshot: None,
+            llm_analysis: None,
+            llm_analyzed_at: None,
+            analysis_source: None,
+            recovery_time_ms: None,
+            psi_after: None,
+            delivery: Default::default(),
+            dependents: (1..=5).map(|i| format!("pod/shop/api-{i}")).collect(),
+        };
+        let analysis = fallback_analysis(&incident);
+        assert_eq!(analysis.reason_code, "cpu_spin");
+        assert_eq!(
+            analysis.summary,
+            "auto_killed on stress (pid 4242) after CPU at 97.0% with 61.5% CPU pressure, \
+             load 8.00,6.00,4.00. Connected clients: pod/shop/api-1, pod/shop/api-2, \
+             pod/shop/api-3 and 2 more."
+        );
+        assert_eq!(fallback_analysis(&incident).summary, analysis.summary);
+
+        let mut memory = incident;
+        memory.psi_memory = 35.0;
+        assert_eq!(fallback_analysis(&memory).reason_code, "oom_risk");
+
+        let mut oom = memory;
+        oom.event_type = "oom_kill".to_string();
+        oom.action = "kernel_oom_kill".to_string();
+        oom.dependents.clear();
+        let analysis = fallback_analysis(&oom);
+        assert_eq!(analysis.reason_code, "oom_risk");
+        assert_eq!(
+            analysis.summary,
+            "kernel_oom_kill on stress (pid 4242) after the kernel ran out of memory \
+             (35.0% full memory stall), load 8.00,6.00,4.00."
+        );
+    }
+}
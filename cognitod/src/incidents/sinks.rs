@@ -0,0 +1,501 @@
+//! Remote incident sinks
+//!
+//! Incidents are pushed to a generic webhook, PagerDuty (Events API v2),
+//! Opsgenie or a Discord channel webhook when they open and again when they resolve. Every event for an
+//! incident carries the same dedup key (`linnix-<host>-<id>`), so the remote
+//! side groups trigger and resolve and a retried trigger never pages twice.
+//! Each sink has its own worker, which keeps trigger/resolve in order and
+//! retries with jittered exponential backoff under the sink's breaker
+//! (`incident_sink:<name>`); the outcome of every attempt is written to the
+//! incident's `delivery` column.
+
+use chrono::Utc;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use super::{DeliveryState, Incident, IncidentStore, IncidentWrite, SinkDelivery};
+use crate::config::{Egress, IncidentSinkConfig, IncidentSinkKind, OfflineGuard};
+use crate::retry::{Backoff, Destination, Failure, RetryPolicy, check_response};
+use crate::templates::{IncidentContext, MessageKind, Templates};
+
+/// Events buffered per sink while it is retrying.
+const QUEUE_CAPACITY: usize = 256;
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SinkEvent {
+    Trigger,
+    Resolve,
+}
+
+impl SinkEvent {
+    fn as_str(self) -> &'static str {
+        match self {
+            SinkEvent::Trigger => "trigger",
+            SinkEvent::Resolve => "resolve",
+        }
+    }
+
+    fn message_kind(self) -> MessageKind {
+        match self {
+            SinkEvent::Trigger => MessageKind::Incident,
+            SinkEvent::Resolve => MessageKind::Recovery,
+        }
+    }
+}
+
+struct Job {
+    id: i64,
+    event: SinkEvent,
+    incident: Incident,
+}
+
+#[derive(Debug)]
+struct SinkRequest {
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Value,
+}
+
+pub fn dedup_key(host: &str, id: i64) -> String {
+    format!("linnix-{host}-{id}")
+}
+
+fn summary(host: &str, incident: &Incident) -> String {
+    let target = match (&incident.target_name, incident.target_pid) {
+        (Some(name), Some(pid)) => format!(" {name}({pid})"),
+        (Some(name), None) => format!(" {name}"),
+        _ => String::new(),
+    };
+    format!(
+        "{} on {}: {}{} (CPU {:.1}%, PSI {:.1}%)",
+        incident.event_type, host, incident.action, target, incident.cpu_percent, incident.psi_cpu
+    )
+}
+
+/// Template notifier of a sink kind.
+fn notifier(kind: &IncidentSinkKind) -> &'static str {
+    match kind {
+        IncidentSinkKind::Webhook { .. } => "webhook",
+        IncidentSinkKind::Pagerduty { .. } => "pagerduty",
+        IncidentSinkKind::Opsgenie { .. } => "opsgenie",
+        IncidentSinkKind::Discord { .. } => "discord",
+    }
+}
+
+fn build_request(
+    kind: &IncidentSinkKind,
+    event: SinkEvent,
+    host: &str,
+    id: i64,
+    incident: &Incident,
+    templates: &Templates,
+) -> SinkRequest {
+    let key = dedup_key(host, id);
+    let context = IncidentContext {
+        incident,
+        id,
+        host,
+        summary: summary(host, incident),
+        dedup_key: key.clone(),
+    };
+    let text = |event: SinkEvent| templates.render(notifier(kind), event.message_kind(), &context);
+    match kind {
+        IncidentSinkKind::Webhook { url, headers } => SinkRequest {
+            url: url.clone(),
+            headers: headers
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            body: json!({
+                "event": event.as_str(),
+                "dedup_key": key,
+                "host": host,
+                "summary": text(event),
+                "incident": incident,
+            }),
+        },
+        IncidentSinkKind::Pagerduty { routing_key, url } => {
+            let mut body = json!({
+                "routing_key": routing_key,
+                "event_action": event.as_str(),
+                "dedup_key": key,
+            });
+            if event == SinkEvent::Trigger {
+                body["payload"] = json!({
+                    "summary": text(event),
+                    "source": host,
+                    "severity": if incident.action.contains("kill") { "critical" } else { "error" },
+                    "component": incident.target_name,
+                    "class": incident.event_type,
+                    "timestamp": chrono::DateTime::from_timestamp(incident.timestamp, 0)
+                        .map(|t| t.to_rfc3339()),
+                    "custom_details": incident,
+                });
+            }
+            SinkRequest {
+                url: url.clone(),
+                headers: Vec::new(),
+                body,
+            }
+        }
+        IncidentSinkKind::Opsgenie { api_key, url } => {
+            let headers = vec![("Authorization".to_string(), format!("GenieKey {api_key}"))];
+            match event {
+                SinkEvent::Trigger => SinkRequest {
+                    url: url.clone(),
+                    headers,
+                    body: json!({
+                        "message": text(event),
+                        "alias": key,
+                        "source": "linnix",
+                        "entity": host,
+                        "priority": if incident.action.contains("kill") { "P1" } else { "P2" },
+                        "details": {
+                            "event_type": incident.event_type,
+                            "action": incident.action,
+                            "psi_cpu": incident.psi_cpu.to_string(),
+                            "cpu_percent": incident.cpu_percent.to_string(),
+                        },
+                    }),
+                },
+                SinkEvent::Resolve => SinkRequest {
+                    url: format!(
+                        "{}/{}/close?identifierType=alias",
+                        url.trim_end_matches('/'),
+                        key
+                    ),
+                    headers,
+                    body: json!({ "source": "linnix" }),
+                },
+            }
+        }
+        IncidentSinkKind::Discord { url } => {
+            let mut embed = match event {
+                SinkEvent::Trigger => json!({
+                    "title": format!("🔥 Incident #{id}: {}", incident.event_type),
+                    "description": text(event),
+                    "color": 0xE74C3C,
+                    "fields": [
+                        { "name": "Host", "value": host, "inline": true },
+                        { "name": "Action", "value": incident.action, "inline": true },
+                    ],
+                }),
+                SinkEvent::Resolve => json!({
+                    "title": format!("✅ Resolved incident #{id}: {}", incident.event_type),
+                    "description": text(event),
+                    "color": 0x2ECC71,
+                }),
+            };
+            embed["footer"] = json!({ "text": key });
+            embed["timestamp"] = json!(
+                chrono::DateTime::from_timestamp(incident.timestamp, 0).map(|t| t.to_rfc3339())
+            );
+            SinkRequest {
+                url: url.clone(),
+                headers: Vec::new(),
+                body: json!({ "username": "linnix", "embeds": [embed] }),
+            }
+        }
+    }
+}
+
+async fn send(client: &reqwest::Client, request: &SinkRequest) -> Result<(), Failure> {
+    let mut builder = client.post(&request.url).json(&request.body);
+    for (name, value) in &request.headers {
+        builder = builder.header(name, value);
+    }
+    check_response(builder.send().await).await.map(|_| ())
+}
+
+struct Worker {
+    name: String,
+    config: IncidentSinkConfig,
+    host: String,
+    client: reqwest::Client,
+    destination: Arc<Destination>,
+    jitter: f64,
+    store: Arc<IncidentStore>,
+    templates: Arc<Templates>,
+}
+
+impl Worker {
+    fn record(&self, job: &Job, state: DeliveryState, attempts: u32, last_error: Option<String>) {
+        self.store.submit(IncidentWrite::Delivery {
+            id: job.id,
+            sink: self.name.clone(),
+            delivery: SinkDelivery {
+                event: job.event.as_str().to_string(),
+                state,
+                attempts,
+                last_error,
+                updated_at: Utc::now().timestamp(),
+            },
+        });
+    }
+
+    async fn deliver(&self, job: Job) {
+        let request = build_request(
+            &self.config.kind,
+            job.event,
+            &self.host,
+            job.id,
+            &job.incident,
+            &self.templates,
+        );
+        let policy = RetryPolicy {
+            max_attempts: self.config.max_attempts.max(1),
+            backoff: Backoff {
+                initial: Duration::from_secs(self.config.backoff_secs.max(1)),
+                max: MAX_BACKOFF,
+                jitter: self.jitter,
+            },
+        };
+        let (job, request) = (&job, &request);
+        let (result, attempts) = self
+            .destination
+            .run_with(&policy, |attempt| async move {
+                let result = send(&self.client, request).await;
+                if let Err(Failure::Retry(err)) = &result
+                    && attempt < policy.max_attempts
+                {
+                    warn!(
+                        "[incident_sinks] {} attempt {}/{} for incident #{} failed: {:#}",
+                        self.name, attempt, policy.max_attempts, job.id, err
+                    );
+                    self.record(
+                        job,
+                        DeliveryState::Pending,
+                        attempt,
+                        Some(format!("{err:#}")),
+                    );
+                }
+                result
+            })
+            .await;
+        match result {
+            Ok(()) => {
+                info!(
+                    "[incident_sinks] {} incident #{} sent to {}",
+                    job.event.as_str(),
+                    job.id,
+                    self.name
+                );
+                self.record(job, DeliveryState::Delivered, attempts, None);
+            }
+            Err(Failure::Reject(err)) => {
+                warn!(
+                    "[incident_sinks] {} rejected incident #{}: {:#}",
+                    self.name, job.id, err
+                );
+                self.record(
+                    job,
+                    DeliveryState::Failed,
+                    attempts,
+                    Some(format!("{err:#}")),
+                );
+            }
+            Err(Failure::Retry(err)) => {
+                warn!(
+                    "[incident_sinks] giving up on incident #{} at {} after {} attempts: {:#}",
+                    job.id, self.name, attempts, err
+                );
+                self.record(
+                    job,
+                    DeliveryState::Failed,
+                    attempts,
+                    Some(format!("{err:#}")),
+                );
+            }
+        }
+    }
+
+    async fn run(self, mut rx: mpsc::Receiver<Job>) {
+        while let Some(job) = rx.recv().await {
+            self.deliver(job).await;
+        }
+    }
+}
+
+pub struct IncidentSinks {
+    queues: Vec<(String, mpsc::Sender<Job>)>,
+}
+
+impl IncidentSinks {
+    /// Start one worker per configured sink; `None` when there are none.
+    pub fn spawn(
+        configs: &[IncidentSinkConfig],
+        store: Arc<IncidentStore>,
+        host: String,
+        templates: Arc<Templates>,
+        guard: &Arc<OfflineGuard>,
+    ) -> Option<Arc<Self>> {
+        if configs.is_empty() {
+            return None;
+        }
+        let client = guard.client(
+            Egress::Notifications,
+            reqwest::Client::builder().timeout(REQUEST_TIMEOUT),
+        );
+        let queues = configs
+            .iter()
+            .map(|config| {
+                let name = config.name();
+                let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+                let worker = Worker {
+                    name: name.clone(),
+                    config: config.clone(),
+                    host: host.clone(),
+                    client: client.clone(),
+                    destination: guard
+                        .retrier()
+                        .destination(&format!("incident_sink:{name}")),
+                    jitter: guard.retrier().config().jitter,
+                    store: Arc::clone(&store),
+                    templates: Arc::clone(&templates),
+                };
+                tokio::spawn(worker.run(rx));
+                (name, tx)
+            })
+            .collect::<Vec<_>>();
+        info!(
+            "[incident_sinks] forwarding incidents to {}",
+            queues
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        Some(Arc::new(Self { queues }))
+    }
+
+    /// Page for a newly recorded incident.
+    pub fn opened(&self, id: i64, incident: &Incident) {
+        self.enqueue(id, SinkEvent::Trigger, incident);
+    }
+
+    /// Close the page once the incident's pressure cleared.
+    pub fn resolved(&self, id: i64, incident: &Incident) {
+        self.enqueue(id, SinkEvent::Resolve, incident);
+    }
+
+    fn enqueue(&self, id: i64, event: SinkEvent, incident: &Incident) {
+        for (name, tx) in &self.queues {
+            let job = Job {
+                id,
+                event,
+                incident: incident.clone(),
+            };
+            if tx.try_send(job).is_err() {
+                warn!(
+                    "[incident_sinks] {} queue full, dropped {} for incident #{}",
+                    name,
+                    event.as_str(),
+                    id
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn incident() -> Incident {
+        Incident {
+            id: Some(7),
+            timestamp: 1_732_242_135,
+            event_type: "circuit_breaker_cpu".to_string(),
+            psi_cpu: 75.0,
+            psi_memory: 0.0,
+            cpu_percent: 96.5,
+            load_avg: "4.00,3.00,2.00".to_string(),
+            action: "auto_kill".to_string(),
+            target_pid: Some(4242),
+            target_name: Some("stress".to_string()),
+            system_snapshot: None,
+            llm_analysis: None,
+            llm_analyzed_at: None,
+            analysis_source: None,
+            recovery_time_ms: None,
+            psi_after: None,
+            delivery: Default::default(),
+            dependents: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn payloads_share_a_dedup_key_per_incident() {
+        let templates = Templates::defaults();
+        let pd = IncidentSinkKind::Pagerduty {
+            routing_key: "R".to_string(),
+            url: "https://events.pagerduty.com/v2/enqueue".to_string(),
+        };
+        let trigger = build_request(
+            &pd,
+            SinkEvent::Trigger,
+            "node-1",
+            7,
+            &incident(),
+            &templates,
+        );
+        assert_eq!(trigger.body["event_action"], "trigger");
+        assert_eq!(trigger.body["dedup_key"], "linnix-node-1-7");
+        assert_eq!(trigger.body["payload"]["severity"], "critical");
+        assert_eq!(
+            trigger.body["payload"]["summary"],
+            "circuit_breaker_cpu on node-1: auto_kill stress(4242) (CPU 96.5%, PSI 75.0%)"
+        );
+        let resolve = build_request(
+            &pd,
+            SinkEvent::Resolve,
+            "node-1",
+            7,
+            &incident(),
+            &templates,
+        );
+        assert_eq!(resolve.body["event_action"], "resolve");
+        assert_eq!(resolve.body["dedup_key"], "linnix-node-1-7");
+        assert!(resolve.body.get("payload").is_none());
+
+        let og = IncidentSinkKind::Opsgenie {
+            api_key: "k".to_string(),
+            url: "https://api.opsgenie.com/v2/alerts/".to_string(),
+        };
+        let close = build_request(
+            &og,
+            SinkEvent::Resolve,
+            "node-1",
+            7,
+            &incident(),
+            &templates,
+        );
+        assert_eq!(
+            close.url,
+            "https://api.opsgenie.com/v2/alerts/linnix-node-1-7/close?identifierType=alias"
+        );
+        assert_eq!(close.headers[0].1, "GenieKey k");
+
+        let discord = IncidentSinkKind::Discord {
+            url: "https://discord.com/api/webhooks/1/t".to_string(),
+        };
+        let embed = build_request(
+            &discord,
+            SinkEvent::Trigger,
+            "node-1",
+            7,
+            &incident(),
+            &templates,
+        );
+        assert_eq!(embed.body["embeds"][0]["footer"]["text"], "linnix-node-1-7");
+        assert_eq!(
+            embed.body["embeds"][0]["title"],
+            "🔥 Incident #7: circuit_breaker_cpu"
+        );
+    }
+}
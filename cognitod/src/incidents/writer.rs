@@ -0,0 +1,360 @@
+//! Write-behind queue for the incident store
+//!
+//! Producers (circuit breaker, PSI attribution, feedback API) hand writes to a
+//! bounded channel and return immediately. A single writer task drains the
+//! channel and commits whatever has accumulated, up to `batch_size`, in one
+//! transaction, so a slow disk grows the batch instead of stalling callers.
+//! When the queue is full the write is dropped and counted.
+
+use serde::Serialize;
+#[cfg(feature = "incidents")]
+use {
+    chrono::Utc,
+    sqlx::{Sqlite, SqlitePool},
+    std::sync::Arc,
+    std::sync::atomic::{AtomicU64, Ordering},
+    tokio::sync::{mpsc, oneshot},
+    tracing::{debug, warn},
+};
+
+use super::{AnalysisSource, Incident, SinkDelivery, StallAttribution};
+use crate::leaderboard::UsageRollup;
+
+/// A queued mutation of the incident database.
+#[derive(Debug, Clone)]
+pub enum IncidentWrite {
+    Incident(Incident),
+    /// A fallback summary only fills an empty analysis, so it never
+    /// replaces an LLM result that landed first.
+    LlmAnalysis {
+        id: i64,
+        analysis: String,
+        source: AnalysisSource,
+    },
+    Feedback {
+        insight_id: String,
+        label: String,
+        source: String,
+        user_id: Option<String>,
+    },
+    StallAttribution {
+        victim_pod: String,
+        victim_namespace: String,
+        attribution: StallAttribution,
+    },
+    /// Pressure cleared after the incident's action.
+    Resolved {
+        id: i64,
+        recovery_time_ms: i64,
+        psi_after: f32,
+    },
+    /// Latest delivery state of the incident at one remote sink.
+    Delivery {
+        id: i64,
+        sink: String,
+        delivery: SinkDelivery,
+    },
+    /// Closed hourly and daily usage rollups (`/leaderboard`).
+    UsageRollups(Vec<UsageRollup>),
+    /// Drop usage rollups older than the leaderboard's retention.
+    PruneUsage {
+        hours_before: i64,
+        days_before: i64,
+    },
+}
+
+#[cfg(feature = "incidents")]
+pub(super) enum Command {
+    /// The reply receives the row id once the batch commits.
+    Write(Box<IncidentWrite>, Option<oneshot::Sender<i64>>),
+    Flush(oneshot::Sender<()>),
+}
+
+#[cfg(feature = "incidents")]
+#[derive(Default)]
+pub(super) struct Counters {
+    pub dropped: AtomicU64,
+    pub written: AtomicU64,
+    pub failed: AtomicU64,
+    pub batches: AtomicU64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WriteQueueStats {
+    pub queued: usize,
+    pub capacity: usize,
+    pub dropped_total: u64,
+    pub written_total: u64,
+    pub failed_total: u64,
+    pub batches_total: u64,
+}
+
+#[cfg(feature = "incidents")]
+pub(super) async fn run(
+    pool: SqlitePool,
+    mut rx: mpsc::Receiver<Command>,
+    batch_size: usize,
+    counters: Arc<Counters>,
+) {
+    let mut buf = Vec::with_capacity(batch_size);
+    while rx.recv_many(&mut buf, batch_size).await > 0 {
+        let mut writes = Vec::with_capacity(buf.len());
+        let mut flushes = Vec::new();
+        for cmd in buf.drain(..) {
+            match cmd {
+                Command::Write(write, reply) => writes.push((*write, reply)),
+                Command::Flush(done) => flushes.push(done),
+            }
+        }
+        if !writes.is_empty() {
+            commit_batch(&pool, writes, &counters).await;
+        }
+        for done in flushes {
+            let _ = done.send(());
+        }
+    }
+    debug!("[incidents] writer stopped");
+}
+
+#[cfg(feature = "incidents")]
+async fn commit_batch(
+    pool: &SqlitePool,
+    writes: Vec<(IncidentWrite, Option<oneshot::Sender<i64>>)>,
+    counters: &Counters,
+) {
+    let total = writes.len() as u64;
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            warn!("[incidents] failed to open write transaction: {}", e);
+            counters.failed.fetch_add(total, Ordering::Relaxed);
+            return;
+        }
+    };
+
+    let mut replies = Vec::with_capacity(writes.len());
+    let mut failed = 0u64;
+    for (write, reply) in writes {
+        match execute(&mut *tx, &write).await {
+            Ok(id) => replies.push((reply, id)),
+            Err(e) => {
+                warn!("[incidents] dropped {} write: {}", write.kind(), e);
+                failed += 1;
+            }
+        }
+    }
+
+    if let Err(e) = tx.commit().await {
+        warn!("[incidents] failed to commit {} writes: {}", total, e);
+        counters.failed.fetch_add(total, Ordering::Relaxed);
+        return;
+    }
+    counters.batches.fetch_add(1, Ordering::Relaxed);
+    counters.failed.fetch_add(failed, Ordering::Relaxed);
+    counters
+        .written
+        .fetch_add(replies.len() as u64, Ordering::Relaxed);
+    debug!("[incidents] committed batch of {} writes", replies.len());
+    for (reply, id) in replies {
+        if let Some(reply) = reply {
+            let _ = reply.send(id);
+        }
+    }
+}
+
+#[cfg(feature = "incidents")]
+impl IncidentWrite {
+    fn kind(&self) -> &'static str {
+        match self {
+            IncidentWrite::Incident(_) => "incident",
+            IncidentWrite::LlmAnalysis { .. } => "llm_analysis",
+            IncidentWrite::Feedback { .. } => "feedback",
+            IncidentWrite::StallAttribution { .. } => "stall_attribution",
+            IncidentWrite::Resolved { .. } => "resolved",
+            IncidentWrite::Delivery { .. } => "delivery",
+            IncidentWrite::UsageRollups(_) => "usage_rollups",
+            IncidentWrite::PruneUsage { .. } => "prune_usage",
+        }
+    }
+}
+
+/// Run one write against a pool or an open transaction; returns the row id.
+#[cfg(feature = "incidents")]
+pub(super) async fn execute<'c, E>(exec: E, write: &IncidentWrite) -> Result<i64, sqlx::Error>
+where
+    E: sqlx::Executor<'c, Database = Sqlite>,
+{
+    let result = match write {
+        IncidentWrite::Incident(incident) => {
+            sqlx::query(
+                r#"
+                INSERT INTO incidents (
+                    timestamp, event_type, psi_cpu, psi_memory, cpu_percent, load_avg,
+                    action, target_pid, target_name, system_snapshot,
+                    recovery_time_ms, psi_after, dependents
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(incident.timestamp)
+            .bind(&incident.event_type)
+            .bind(incident.psi_cpu)
+            .bind(incident.psi_memory)
+            .bind(incident.cpu_percent)
+            .bind(&incident.load_avg)
+            .bind(&incident.action)
+            .bind(incident.target_pid)
+            .bind(&incident.target_name)
+            .bind(&incident.system_snapshot)
+            .bind(incident.recovery_time_ms)
+            .bind(incident.psi_after)
+            .bind(
+                (!incident.dependents.is_empty())
+                    .then(|| serde_json::to_string(&incident.dependents).ok())
+                    .flatten(),
+            )
+            .execute(exec)
+            .await?
+        }
+        IncidentWrite::LlmAnalysis {
+            id,
+            analysis,
+            source,
+        } => {
+            let sql = match source {
+                AnalysisSource::Llm => {
+                    "UPDATE incidents SET llm_analysis = ?, llm_analyzed_at = ?, \
+                     analysis_source = ? WHERE id = ?"
+                }
+                AnalysisSource::Fallback => {
+                    "UPDATE incidents SET llm_analysis = ?, llm_analyzed_at = ?, \
+                     analysis_source = ? WHERE id = ? AND llm_analysis IS NULL"
+                }
+            };
+            sqlx::query(sql)
+                .bind(analysis)
+                .bind(Utc::now().timestamp())
+                .bind(source.as_str())
+                .bind(id)
+                .execute(exec)
+                .await?;
+            return Ok(*id);
+        }
+        IncidentWrite::Feedback {
+            insight_id,
+            label,
+            source,
+            user_id,
+        } => {
+            sqlx::query(
+                r#"
+                INSERT INTO feedback (insight_id, timestamp, label, source, user_id)
+                VALUES (?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(insight_id)
+            .bind(Utc::now().timestamp())
+            .bind(label)
+            .bind(source)
+            .bind(user_id)
+            .execute(exec)
+            .await?
+        }
+        IncidentWrite::StallAttribution {
+            victim_pod,
+            victim_namespace,
+            attribution: a,
+        } => {
+            sqlx::query(
+                r#"
+                INSERT INTO stall_attributions (
+                    victim_pod, victim_namespace, offender_pod, offender_namespace,
+                    stall_us, blame_score, timestamp,
+                    cpu_share, fork_count, short_job_count
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(victim_pod)
+            .bind(victim_namespace)
+            .bind(&a.offender_pod)
+            .bind(&a.offender_namespace)
+            .bind(a.stall_us as i64)
+            .bind(a.blame_score)
+            .bind(a.timestamp as i64)
+            .bind(a.cpu_share)
+            .bind(a.fork_count as i64)
+            .bind(a.short_job_count as i64)
+            .execute(exec)
+            .await?
+        }
+        IncidentWrite::Resolved {
+            id,
+            recovery_time_ms,
+            psi_after,
+        } => {
+            sqlx::query("UPDATE incidents SET recovery_time_ms = ?, psi_after = ? WHERE id = ?")
+                .bind(recovery_time_ms)
+                .bind(psi_after)
+                .bind(id)
+                .execute(exec)
+                .await?;
+            return Ok(*id);
+        }
+        IncidentWrite::Delivery { id, sink, delivery } => {
+            let state =
+                serde_json::to_string(delivery).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+            sqlx::query(
+                r#"
+                UPDATE incidents
+                SET delivery = json_set(COALESCE(delivery, '{}'), '$."' || ? || '"', json(?))
+                WHERE id = ?
+                "#,
+            )
+            .bind(sink)
+            .bind(state)
+            .bind(id)
+            .execute(exec)
+            .await?;
+            return Ok(*id);
+        }
+        IncidentWrite::UsageRollups(rollups) => {
+            let rows =
+                serde_json::to_string(rollups).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+            sqlx::query(
+                r#"
+                INSERT OR REPLACE INTO usage_rollups (
+                    period, start, kind, name,
+                    cpu_seconds, peak_rss_bytes, io_bytes, net_bytes, crashes
+                )
+                SELECT json_extract(value, '$.period'), json_extract(value, '$.start'),
+                       json_extract(value, '$.kind'), json_extract(value, '$.name'),
+                       json_extract(value, '$.cpu_seconds'),
+                       json_extract(value, '$.peak_rss_bytes'),
+                       json_extract(value, '$.io_bytes'), json_extract(value, '$.net_bytes'),
+                       json_extract(value, '$.crashes')
+                FROM json_each(?)
+                "#,
+            )
+            .bind(rows)
+            .execute(exec)
+            .await?;
+            return Ok(0);
+        }
+        IncidentWrite::PruneUsage {
+            hours_before,
+            days_before,
+        } => {
+            sqlx::query(
+                r#"
+                DELETE FROM usage_rollups
+                WHERE (period = 'hour' AND start < ?) OR (period = 'day' AND start < ?)
+                "#,
+            )
+            .bind(hours_before)
+            .bind(days_before)
+            .execute(exec)
+            .await?;
+            return Ok(0);
+        }
+    };
+    Ok(result.last_insert_rowid())
+}
@@ -1,11 +1,19 @@
+use anyhow::{Context, anyhow};
 use log::{debug, info, warn};
-use reqwest::Client;
+use reqwest::{Client, Method, StatusCode};
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
 use std::sync::{Arc, RwLock};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::time::sleep;
 
+use crate::collectors::cgroup_tree::{MAX_DEPTH, cgroup_root};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+const MAX_PID_CACHE: usize = 65_536;
+
 #[derive(Debug, Clone, Deserialize, serde::Serialize, PartialEq, Default)]
 #[serde(rename_all = "snake_case")]
 pub enum Priority {
@@ -37,18 +45,88 @@ pub struct K8sMetadata {
     pub owner_name: Option<String>,
     pub priority: Priority,
     pub slo_tier: Option<String>,
+    #[serde(default)]
+    pub pod_uid: Option<String>,
+    /// Sum of the containers' `ephemeral-storage` limits; `None` unless every
+    /// container sets one, as the kubelet then has no pod-level limit.
+    #[serde(default)]
+    pub ephemeral_storage_limit: Option<u64>,
+}
+
+impl Priority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Critical => "critical",
+            Self::High => "high",
+            Self::Medium => "medium",
+            Self::Low => "low",
+        }
+    }
+}
+
+impl K8sMetadata {
+    /// Approximate heap and inline size, for memory budgets.
+    pub fn approx_bytes(&self) -> usize {
+        let strings = [&self.pod_name, &self.namespace, &self.container_name]
+            .into_iter()
+            .chain(&self.owner_kind)
+            .chain(&self.owner_name)
+            .chain(&self.slo_tier)
+            .chain(&self.pod_uid);
+        std::mem::size_of::<Self>() + strings.map(String::capacity).sum::<usize>()
+    }
+}
+
+impl From<&K8sMetadata> for linnix_events::PodInfo {
+    fn from(meta: &K8sMetadata) -> Self {
+        Self {
+            pod_name: meta.pod_name.clone(),
+            namespace: meta.namespace.clone(),
+            container_name: meta.container_name.clone(),
+            owner_kind: meta.owner_kind.clone(),
+            owner_name: meta.owner_name.clone(),
+            priority: meta.priority.as_str().to_string(),
+            slo_tier: meta.slo_tier.clone(),
+        }
+    }
 }
 
 pub struct K8sContext {
     // Map from Container ID (stripped) to Metadata
     container_map: RwLock<HashMap<String, K8sMetadata>>,
+    /// pid -> container ID, filled from the containers' `cgroup.procs` on
+    /// every refresh and from `/proc/<pid>/cgroup` between them.
+    pid_cache: RwLock<HashMap<u32, String>>,
+    /// cgroup id -> container ID, filled from the inodes of the containers'
+    /// cgroup directories on every refresh and from pid lookups between them.
+    cgroup_cache: RwLock<HashMap<u64, String>>,
     client: Client,
     api_url: String,
+    /// Kubelet read API (`https://<node-ip>:10250`), tried first at startup.
+    kubelet_url: Option<String>,
     token: String,
-    pub node_name: String,
+    node_name: RwLock<String>,
+    /// `NODE_NAME` was not set; [`Self::self_configure`] looks it up.
+    node_name_guessed: bool,
+    node_labels: RwLock<BTreeMap<String, String>>,
+    /// Running in a pod with a service account, not from `K8S_API_URL`.
+    pub in_cluster: bool,
+    /// Namespace of the agent's own pod (`POD_NAMESPACE` or the service
+    /// account's); `None` outside the cluster.
+    pub namespace: Option<String>,
+    /// The agent's own pod (`POD_NAME` or the hostname).
+    pub pod_name: Option<String>,
 }
 
 impl K8sContext {
+    /// Builds without the `k8s` feature never talk to the API server; pod
+    /// attribution is then unavailable, as when the agent runs off-cluster.
+    #[cfg(not(feature = "k8s"))]
+    pub fn new() -> Option<Arc<Self>> {
+        None
+    }
+
+    #[cfg(feature = "k8s")]
     pub fn new() -> Option<Arc<Self>> {
         let (api_url, token, ca_cert) = if let (Ok(url), Ok(t)) =
             (std::env::var("K8S_API_URL"), std::env::var("K8S_TOKEN"))
@@ -65,10 +143,24 @@ impl K8sContext {
             let ca = std::fs::read("/var/run/secrets/kubernetes.io/serviceaccount/ca.crt").ok()?;
             (url, t, Some(ca))
         };
+        let in_cluster = ca_cert.is_some();
+        let namespace = match std::env::var("POD_NAMESPACE") {
+            Ok(ns) => Some(ns),
+            Err(_) if in_cluster => {
+                std::fs::read_to_string("/var/run/secrets/kubernetes.io/serviceaccount/namespace")
+                    .ok()
+                    .map(|ns| ns.trim().to_string())
+            }
+            Err(_) => None,
+        };
+        let pod_name = std::env::var("POD_NAME")
+            .ok()
+            .or_else(|| std::env::var("HOSTNAME").ok().filter(|_| in_cluster));
 
         // Try to get node name from env (downward API) or hostname
-        let node_name = std::env::var("NODE_NAME")
-            .ok()
+        let node_from_env = std::env::var("NODE_NAME").ok();
+        let node_name_guessed = node_from_env.is_none();
+        let node_name = node_from_env
             .or_else(|| std::env::var("HOSTNAME").ok())
             .unwrap_or_else(|| "localhost".to_string());
 
@@ -84,33 +176,177 @@ impl K8sContext {
 
         Some(Arc::new(Self {
             container_map: RwLock::new(HashMap::new()),
+            pid_cache: RwLock::new(HashMap::new()),
+            cgroup_cache: RwLock::new(HashMap::new()),
             client,
             api_url,
+            kubelet_url: std::env::var("K8S_KUBELET_URL").ok(),
             token,
-            node_name,
+            node_name: RwLock::new(node_name),
+            node_name_guessed,
+            node_labels: RwLock::new(BTreeMap::new()),
+            in_cluster,
+            namespace,
+            pod_name,
         }))
     }
 
-    pub fn start_watcher(self: Arc<Self>) {
+    pub fn node_name(&self) -> String {
+        self.node_name.read().unwrap().clone()
+    }
+
+    /// Labels of this node, once [`Self::self_configure`] has read them.
+    pub fn node_labels(&self) -> BTreeMap<String, String> {
+        self.node_labels.read().unwrap().clone()
+    }
+
+    /// In a cluster, find this node from the agent's own pod when
+    /// `NODE_NAME` is not set, then read the node's labels. Call before
+    /// [`Self::warm_up`], which lists the pods of this node.
+    pub async fn self_configure(&self) {
+        if !self.in_cluster {
+            return;
+        }
+        if self.node_name_guessed
+            && let (Some(ns), Some(pod)) = (&self.namespace, &self.pod_name)
+        {
+            let path = format!("/api/v1/namespaces/{ns}/pods/{pod}");
+            match self.api_get(&path).await {
+                Ok(Some(own)) => match own["spec"]["nodeName"].as_str() {
+                    Some(node) => {
+                        info!(
+                            "[k8s] running on node {node} (from pod {ns}/{})",
+                            self.pod()
+                        );
+                        *self.node_name.write().unwrap() = node.to_string();
+                    }
+                    None => warn!("[k8s] own pod has no nodeName yet; set NODE_NAME"),
+                },
+                Ok(None) => warn!("[k8s] own pod {ns}/{} not found; set NODE_NAME", self.pod()),
+                Err(e) => warn!("[k8s] cannot read own pod, set NODE_NAME: {e:#}"),
+            }
+        }
+        let path = format!("/api/v1/nodes/{}", self.node_name());
+        match self.api_get(&path).await {
+            Ok(Some(node)) => {
+                let labels: BTreeMap<String, String> =
+                    serde_json::from_value(node["metadata"]["labels"].clone()).unwrap_or_default();
+                debug!("[k8s] node has {} labels", labels.len());
+                *self.node_labels.write().unwrap() = labels;
+            }
+            Ok(None) => warn!("[k8s] node {} not found", self.node_name()),
+            Err(e) => warn!("[k8s] cannot read node labels: {e:#}"),
+        }
+    }
+
+    fn pod(&self) -> &str {
+        self.pod_name.as_deref().unwrap_or("-")
+    }
+
+    /// GET an API server path. `None` when the object does not exist.
+    pub async fn api_get(&self, path: &str) -> anyhow::Result<Option<serde_json::Value>> {
+        self.api_send(Method::GET, path, None).await
+    }
+
+    /// Send `body` (JSON, or a merge patch for PATCH) to an API server path.
+    /// `None` when the object does not exist.
+    pub async fn api_send(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&serde_json::Value>,
+    ) -> anyhow::Result<Option<serde_json::Value>> {
+        let content_type = if method == Method::PATCH {
+            "application/merge-patch+json"
+        } else {
+            "application/json"
+        };
+        let mut request = self
+            .client
+            .request(method, format!("{}{path}", self.api_url))
+            .bearer_auth(self.token.trim());
+        if let Some(body) = body {
+            request = request
+                .header(reqwest::header::CONTENT_TYPE, content_type)
+                .body(body.to_string());
+        }
+        let resp = request.send().await.with_context(|| path.to_string())?;
+        match resp.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => Ok(Some(resp.json().await?)),
+            status => Err(anyhow!(
+                "{path}: {status}: {}",
+                resp.text().await.unwrap_or_default()
+            )),
+        }
+    }
+
+    /// Load this node's pods and the pids of their containers before the
+    /// first event is processed, so early events are attributed. Lists pods
+    /// from the kubelet when `K8S_KUBELET_URL` is set, falling back to the
+    /// API server. Returns whether the pod list was loaded.
+    pub async fn warm_up(&self, timeout: Duration) -> bool {
+        let started = Instant::now();
+        let mut pods = None;
+        if let Some(kubelet) = &self.kubelet_url {
+            let url = format!("{}/pods", kubelet.trim_end_matches('/'));
+            match tokio::time::timeout(timeout, self.fetch_pods(&url)).await {
+                Ok(Ok(list)) => pods = Some(("kubelet", list)),
+                Ok(Err(e)) => warn!("[k8s] kubelet pod list failed, using API server: {e}"),
+                Err(_) => warn!("[k8s] kubelet pod list timed out, using API server"),
+            }
+        }
+        if pods.is_none() {
+            let remaining = timeout.saturating_sub(started.elapsed());
+            match tokio::time::timeout(remaining, self.fetch_pods(&self.pods_url())).await {
+                Ok(Ok(list)) => pods = Some(("API server", list)),
+                Ok(Err(e)) => warn!("[k8s] warm-up failed: {e}"),
+                Err(_) => warn!("[k8s] warm-up timed out after {}ms", timeout.as_millis()),
+            }
+        }
+        let Some((source, list)) = pods else {
+            return false;
+        };
+        let pod_count = list.items.len();
+        self.install(container_map(list));
+        info!(
+            "[k8s] warm-up from {source}: {pod_count} pods, {} containers, {} pids in {}ms",
+            self.container_map.read().unwrap().len(),
+            self.pid_cache.read().unwrap().len(),
+            started.elapsed().as_millis()
+        );
+        true
+    }
+
+    /// Refresh the pod list every 30s. With `warm`, [`Self::warm_up`] has
+    /// just loaded it and the first refresh waits a full interval.
+    pub fn start_watcher(self: Arc<Self>, warm: bool) {
         tokio::spawn(async move {
-            info!("[k8s] starting pod watcher for node {}", self.node_name);
+            info!("[k8s] starting pod watcher for node {}", self.node_name());
+            if warm {
+                sleep(REFRESH_INTERVAL).await;
+            }
             loop {
                 if let Err(e) = self.refresh_pods().await {
                     warn!("[k8s] failed to refresh pods: {}", e);
                 }
-                sleep(Duration::from_secs(30)).await;
+                sleep(REFRESH_INTERVAL).await;
             }
         });
     }
 
-    async fn refresh_pods(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let url = format!(
+    fn pods_url(&self) -> String {
+        format!(
             "{}/api/v1/pods?fieldSelector=spec.nodeName={}",
-            self.api_url, self.node_name
-        );
+            self.api_url,
+            self.node_name()
+        )
+    }
+
+    async fn fetch_pods(&self, url: &str) -> Result<PodList, Box<dyn std::error::Error>> {
         let resp = self
             .client
-            .get(&url)
+            .get(url)
             .header("Authorization", format!("Bearer {}", self.token))
             .send()
             .await?;
@@ -118,75 +354,12 @@ impl K8sContext {
         if !resp.status().is_success() {
             return Err(format!("API error: {}", resp.status()).into());
         }
+        Ok(resp.json().await?)
+    }
 
-        let pod_list: PodList = resp.json().await?;
-        let mut new_map = HashMap::new();
-
-        for pod in pod_list.items {
-            let ns = pod.metadata.namespace.unwrap_or_default();
-            let pod_name = pod.metadata.name.unwrap_or_default();
-
-            let (owner_kind, owner_name) = if let Some(owners) = pod.metadata.owner_references {
-                if let Some(owner) = owners.first() {
-                    (Some(owner.kind.clone()), Some(owner.name.clone()))
-                } else {
-                    (None, None)
-                }
-            } else {
-                (None, None)
-            };
-
-            let (priority, slo_tier) = if let Some(labels) = &pod.metadata.labels {
-                let p = labels
-                    .get("linnix.dev/priority")
-                    .map(|s| Priority::from(s.as_str()))
-                    .unwrap_or_default();
-                let s = labels.get("linnix.dev/slo-tier").cloned();
-                (p, s)
-            } else {
-                (Priority::default(), None)
-            };
-
-            if let Some(statuses) = pod.status.container_statuses {
-                for status in statuses {
-                    if let Some(container_id) = status.container_id {
-                        // container_id is usually "containerd://<id>" or "docker://<id>"
-                        if let Some(stripped) = container_id.strip_prefix("containerd://") {
-                            new_map.insert(
-                                stripped.to_string(),
-                                K8sMetadata {
-                                    pod_name: pod_name.clone(),
-                                    namespace: ns.clone(),
-                                    container_name: status.name.clone(),
-                                    owner_kind: owner_kind.clone(),
-                                    owner_name: owner_name.clone(),
-                                    priority: priority.clone(),
-                                    slo_tier: slo_tier.clone(),
-                                },
-                            );
-                        } else if let Some(stripped) = container_id.strip_prefix("docker://") {
-                            new_map.insert(
-                                stripped.to_string(),
-                                K8sMetadata {
-                                    pod_name: pod_name.clone(),
-                                    namespace: ns.clone(),
-                                    container_name: status.name.clone(),
-                                    owner_kind: owner_kind.clone(),
-                                    owner_name: owner_name.clone(),
-                                    priority: priority.clone(),
-                                    slo_tier: slo_tier.clone(),
-                                },
-                            );
-                        }
-                    }
-                }
-            }
-        }
-
-        {
-            let mut map = self.container_map.write().unwrap();
-            *map = new_map;
-        }
+    async fn refresh_pods(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let pod_list = self.fetch_pods(&self.pods_url()).await?;
+        self.install(container_map(pod_list));
         debug!(
             "[k8s] refreshed pod map, {} containers tracked",
             self.container_map.read().unwrap().len()
@@ -194,7 +367,49 @@ impl K8sContext {
         Ok(())
     }
 
+    /// Replace the container map and rebuild the pid and cgroup caches from
+    /// it.
+    fn install(&self, containers: HashMap<String, K8sMetadata>) {
+        let scan = scan_containers(Path::new(&cgroup_root()), &containers);
+        *self.container_map.write().unwrap() = containers;
+        *self.pid_cache.write().unwrap() = scan.pids;
+        *self.cgroup_cache.write().unwrap() = scan.cgroups;
+    }
+
+    /// Metadata for an event, by the cgroup id the kernel stamped on it when
+    /// known, else by its pid. The cgroup id still resolves after the
+    /// process is gone.
+    pub fn get_metadata_for_event(&self, pid: u32, cgroup_id: u64) -> Option<K8sMetadata> {
+        if cgroup_id == 0 {
+            return self.get_metadata_for_pid(pid);
+        }
+        let cached = self.cgroup_cache.read().unwrap().get(&cgroup_id).cloned();
+        let id = match cached {
+            Some(id) => id,
+            None => {
+                let id = self.container_for_pid(pid)?;
+                let mut cache = self.cgroup_cache.write().unwrap();
+                if cache.len() >= MAX_PID_CACHE {
+                    cache.clear();
+                }
+                cache.insert(cgroup_id, id.clone());
+                id
+            }
+        };
+        self.get_metadata(&id)
+    }
+
     pub fn get_metadata_for_pid(&self, pid: u32) -> Option<K8sMetadata> {
+        self.get_metadata(&self.container_for_pid(pid)?)
+    }
+
+    /// Container ID of a known container `pid` runs in.
+    fn container_for_pid(&self, pid: u32) -> Option<String> {
+        let cached = self.pid_cache.read().unwrap().get(&pid).cloned();
+        if cached.is_some() {
+            return cached;
+        }
+
         // Read /proc/<pid>/cgroup
         let content = std::fs::read_to_string(format!("/proc/{}/cgroup", pid)).ok()?;
 
@@ -204,19 +419,17 @@ impl K8sContext {
 
         for line in content.lines() {
             // Simple heuristic: look for last part that looks like a container ID
-            if let Some(last_part) = line.split('/').next_back() {
-                // Remove .scope suffix if present
-                let clean = last_part.trim_end_matches(".scope");
-                // Remove prefix like "cri-containerd-" or "docker-"
-                let id = if let Some(idx) = clean.rfind('-') {
-                    &clean[idx + 1..]
-                } else {
-                    clean
-                };
-
-                if id.len() == 64 {
-                    return self.get_metadata(id);
+            if let Some(id) = line.split('/').next_back().and_then(container_id) {
+                if !self.container_map.read().unwrap().contains_key(id) {
+                    return None;
                 }
+                let mut cache = self.pid_cache.write().unwrap();
+                // Refreshes rebuild the cache; this bounds it while they fail
+                if cache.len() >= MAX_PID_CACHE {
+                    cache.clear();
+                }
+                cache.insert(pid, id.to_string());
+                return Some(id.to_string());
             }
         }
         None
@@ -228,6 +441,180 @@ impl K8sContext {
     }
 }
 
+/// Container ID from the last segment of a cgroup path
+/// (`cri-containerd-<id>.scope`, `docker-<id>.scope` or `<id>`).
+fn container_id(segment: &str) -> Option<&str> {
+    // Remove .scope suffix if present
+    let clean = segment.trim_end_matches(".scope");
+    // Remove prefix like "cri-containerd-" or "docker-"
+    let id = match clean.rfind('-') {
+        Some(idx) => &clean[idx + 1..],
+        None => clean,
+    };
+    (id.len() == 64).then_some(id)
+}
+
+/// Container IDs of the known containers by pid and by cgroup id.
+#[derive(Default)]
+struct ContainerScan {
+    pids: HashMap<u32, String>,
+    cgroups: HashMap<u64, String>,
+}
+
+/// Find the cgroups of the known containers below the `kubepods` trees
+/// under `root`: their pids from `cgroup.procs`, their cgroup ids from the
+/// directory inodes.
+fn scan_containers(root: &Path, containers: &HashMap<String, K8sMetadata>) -> ContainerScan {
+    fn walk(
+        dir: &Path,
+        depth: usize,
+        containers: &HashMap<String, K8sMetadata>,
+        scan: &mut ContainerScan,
+    ) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            if !entry.file_type().is_ok_and(|t| t.is_dir()) {
+                continue;
+            }
+            let path = entry.path();
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if depth == 0 && !name.starts_with("kubepods") {
+                continue;
+            }
+            match container_id(&name).filter(|id| containers.contains_key(*id)) {
+                Some(id) => {
+                    let procs =
+                        std::fs::read_to_string(path.join("cgroup.procs")).unwrap_or_default();
+                    for pid in procs.lines().filter_map(|l| l.trim().parse().ok()) {
+                        scan.pids.insert(pid, id.to_string());
+                    }
+                    // cgroup v2 ids are the inode numbers of the directories
+                    if let Ok(meta) = entry.metadata() {
+                        scan.cgroups.insert(meta.ino(), id.to_string());
+                    }
+                }
+                None if depth < MAX_DEPTH => walk(&path, depth + 1, containers, scan),
+                None => {}
+            }
+        }
+    }
+
+    let mut scan = ContainerScan::default();
+    walk(root, 0, containers, &mut scan);
+    scan
+}
+
+/// Container ID -> metadata for every running container in `pod_list`.
+fn container_map(pod_list: PodList) -> HashMap<String, K8sMetadata> {
+    let mut new_map = HashMap::new();
+
+    for pod in pod_list.items {
+        let ns = pod.metadata.namespace.unwrap_or_default();
+        let pod_name = pod.metadata.name.unwrap_or_default();
+
+        let (owner_kind, owner_name) = if let Some(owners) = pod.metadata.owner_references {
+            if let Some(owner) = owners.first() {
+                (Some(owner.kind.clone()), Some(owner.name.clone()))
+            } else {
+                (None, None)
+            }
+        } else {
+            (None, None)
+        };
+
+        let (priority, slo_tier) = if let Some(labels) = &pod.metadata.labels {
+            let p = labels
+                .get("linnix.dev/priority")
+                .map(|s| Priority::from(s.as_str()))
+                .unwrap_or_default();
+            let s = labels.get("linnix.dev/slo-tier").cloned();
+            (p, s)
+        } else {
+            (Priority::default(), None)
+        };
+
+        let pod_uid = pod.metadata.uid;
+        let ephemeral_storage_limit = pod.spec.as_ref().and_then(ephemeral_storage_limit);
+
+        if let Some(statuses) = pod.status.container_statuses {
+            for status in statuses {
+                // container_id is usually "containerd://<id>" or "docker://<id>"
+                let stripped = status.container_id.as_deref().and_then(|id| {
+                    id.strip_prefix("containerd://")
+                        .or_else(|| id.strip_prefix("docker://"))
+                });
+                if let Some(stripped) = stripped {
+                    new_map.insert(
+                        stripped.to_string(),
+                        K8sMetadata {
+                            pod_name: pod_name.clone(),
+                            namespace: ns.clone(),
+                            container_name: status.name.clone(),
+                            owner_kind: owner_kind.clone(),
+                            owner_name: owner_name.clone(),
+                            priority: priority.clone(),
+                            slo_tier: slo_tier.clone(),
+                            pod_uid: pod_uid.clone(),
+                            ephemeral_storage_limit,
+                        },
+                    );
+                }
+            }
+        }
+    }
+    new_map
+}
+
+/// Pod-level `ephemeral-storage` limit: the sum over the app containers,
+/// when each of them has one.
+fn ephemeral_storage_limit(spec: &PodSpec) -> Option<u64> {
+    spec.containers.iter().try_fold(0u64, |sum, container| {
+        let limit = container
+            .resources
+            .as_ref()?
+            .limits
+            .as_ref()?
+            .get("ephemeral-storage")?;
+        Some(sum.saturating_add(parse_quantity(limit)?))
+    })
+}
+
+/// Parse a resource quantity such as `2Gi`, `500M` or `1e9` into bytes.
+pub fn parse_quantity(quantity: &str) -> Option<u64> {
+    let quantity = quantity.trim();
+    let split = quantity
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(quantity.len());
+    let (number, suffix) = quantity.split_at(split);
+    let multiplier: f64 = match suffix {
+        "" => 1.0,
+        "k" => 1e3,
+        "M" => 1e6,
+        "G" => 1e9,
+        "T" => 1e12,
+        "P" => 1e15,
+        "E" => 1e18,
+        "Ki" => 1024.0,
+        "Mi" => 1024f64.powi(2),
+        "Gi" => 1024f64.powi(3),
+        "Ti" => 1024f64.powi(4),
+        "Pi" => 1024f64.powi(5),
+        "Ei" => 1024f64.powi(6),
+        "m" => 1e-3,
+        // Decimal exponent, `1e9`
+        _ if suffix.starts_with(['e', 'E']) => {
+            let exponent: i32 = suffix[1..].parse().ok()?;
+            10f64.powi(exponent)
+        }
+        _ => return None,
+    };
+    let value = number.parse::<f64>().ok()? * multiplier;
+    (value.is_finite() && value >= 0.0).then(|| value.ceil() as u64)
+}
+
 #[derive(Deserialize)]
 struct PodList {
     items: Vec<Pod>,
@@ -236,13 +623,32 @@ struct PodList {
 #[derive(Deserialize)]
 struct Pod {
     metadata: PodMetadata,
+    #[serde(default)]
+    spec: Option<PodSpec>,
     status: PodStatus,
 }
 
+#[derive(Deserialize)]
+struct PodSpec {
+    #[serde(default)]
+    containers: Vec<Container>,
+}
+
+#[derive(Deserialize)]
+struct Container {
+    resources: Option<ResourceRequirements>,
+}
+
+#[derive(Deserialize)]
+struct ResourceRequirements {
+    limits: Option<HashMap<String, String>>,
+}
+
 #[derive(Deserialize)]
 struct PodMetadata {
     name: Option<String>,
     namespace: Option<String>,
+    uid: Option<String>,
     #[serde(rename = "ownerReferences")]
     owner_references: Option<Vec<OwnerReference>>,
     labels: Option<HashMap<String, String>>,
@@ -293,4 +699,68 @@ mod tests {
         );
         assert_eq!(serde_json::to_string(&Priority::Low).unwrap(), "\"low\"");
     }
+
+    #[test]
+    fn warm_up_maps_pids_of_listed_containers() {
+        let web = "a".repeat(64);
+        let gone = "b".repeat(64);
+        let pods: PodList = serde_json::from_value(serde_json::json!({
+            "items": [{
+                "metadata": {"name": "web-0", "namespace": "prod", "labels": {"linnix.dev/priority": "high"}},
+                "status": {"containerStatuses": [
+                    {"name": "nginx", "containerID": format!("containerd://{web}")},
+                    {"name": "init", "containerID": null}
+                ]}
+            }]
+        }))
+        .unwrap();
+        let containers = container_map(pods);
+        assert_eq!(containers.len(), 1);
+        assert_eq!(containers[&web].priority, Priority::High);
+
+        let root = tempfile::tempdir().unwrap();
+        let pod_dir = root
+            .path()
+            .join("kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod1.slice");
+        for (id, procs) in [(&web, "100\n101\n"), (&gone, "200\n")] {
+            let dir = pod_dir.join(format!("cri-containerd-{id}.scope"));
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("cgroup.procs"), procs).unwrap();
+        }
+        let outside = root.path().join(format!("system.slice/docker-{web}.scope"));
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("cgroup.procs"), "300\n").unwrap();
+
+        let scan = scan_containers(root.path(), &containers);
+        assert_eq!(scan.pids.len(), 2);
+        assert_eq!(scan.pids[&100], web);
+        assert_eq!(scan.pids[&101], web);
+        let web_dir = pod_dir.join(format!("cri-containerd-{web}.scope"));
+        let cgroup_id = std::fs::metadata(web_dir).unwrap().ino();
+        assert_eq!(scan.cgroups.len(), 1);
+        assert_eq!(scan.cgroups[&cgroup_id], web);
+    }
+
+    #[test]
+    fn pod_ephemeral_storage_limit_needs_every_container() {
+        assert_eq!(parse_quantity("2Gi"), Some(2 << 30));
+        assert_eq!(parse_quantity("500M"), Some(500_000_000));
+        assert_eq!(parse_quantity("1e9"), Some(1_000_000_000));
+        assert_eq!(parse_quantity("12345"), Some(12345));
+        assert_eq!(parse_quantity("1Zi"), None);
+
+        let spec = |limits: serde_json::Value| -> PodSpec {
+            serde_json::from_value(serde_json::json!({ "containers": limits })).unwrap()
+        };
+        let both = spec(serde_json::json!([
+            {"resources": {"limits": {"ephemeral-storage": "1Gi", "memory": "1Gi"}}},
+            {"resources": {"limits": {"ephemeral-storage": "512Mi"}}}
+        ]));
+        assert_eq!(ephemeral_storage_limit(&both), Some(1536 << 20));
+        let one = spec(serde_json::json!([
+            {"resources": {"limits": {"ephemeral-storage": "1Gi"}}},
+            {"resources": {}}
+        ]));
+        assert_eq!(ephemeral_storage_limit(&one), None);
+    }
 }
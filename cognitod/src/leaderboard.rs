@@ -0,0 +1,621 @@
+//! Historical resource usage by workload
+//!
+//! Every `sample_secs` the sampler reads `/proc/<pid>/stat` and
+//! `/proc/<pid>/io` for every process and charges the CPU time and storage IO
+//! used since the previous sample to the process's workload: its pod when
+//! Kubernetes metadata is known, otherwise its command name. Peak RSS is the
+//! largest summed RSS of a workload's processes seen in one sample. Network
+//! bytes come from `net` events. Crashes come from the `crashes` tracker and
+//! are always charged to the command name. Usage accumulates per UTC hour;
+//! hours are summed into UTC days once the day is over. Closed hours and days are
+//! written to the incident store when it is open and restored from it on
+//! startup. `/leaderboard` ranks workloads over one hour or day.
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::ProcessEvent;
+use crate::config::LeaderboardConfig;
+use crate::handler::Handler;
+use crate::incidents::{IncidentStore, IncidentWrite};
+use crate::k8s::K8sContext;
+use crate::types::SystemSnapshot;
+use linnix_ai_ebpf_common::EventType;
+
+pub const HOUR: i64 = 3600;
+pub const DAY: i64 = 86_400;
+
+/// Closed hours kept; a day is summed from its hours after it ends.
+const MIN_RETENTION_HOURS: u64 = 25;
+
+pub const MAX_LIMIT: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    Hour,
+    Day,
+}
+
+impl Period {
+    pub fn secs(self) -> i64 {
+        match self {
+            Period::Hour => HOUR,
+            Period::Day => DAY,
+        }
+    }
+
+    /// Start of the period containing `ts` (unix seconds, UTC).
+    pub fn start_of(self, ts: i64) -> i64 {
+        ts - ts.rem_euclid(self.secs())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkloadKind {
+    Pod,
+    Comm,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Workload {
+    pub kind: WorkloadKind,
+    /// `namespace/pod` for pods, otherwise the command name
+    pub name: String,
+}
+
+impl Workload {
+    fn comm(comm: &str) -> Self {
+        Self {
+            kind: WorkloadKind::Comm,
+            name: comm.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    pub cpu_seconds: f64,
+    pub peak_rss_bytes: u64,
+    /// Storage bytes read and written
+    pub io_bytes: u64,
+    /// Socket bytes sent and received
+    pub net_bytes: u64,
+    /// Exits by a crash signal
+    #[serde(default)]
+    pub crashes: u64,
+}
+
+impl Usage {
+    fn merge(&mut self, other: &Usage) {
+        self.cpu_seconds += other.cpu_seconds;
+        self.peak_rss_bytes = self.peak_rss_bytes.max(other.peak_rss_bytes);
+        self.io_bytes += other.io_bytes;
+        self.net_bytes += other.net_bytes;
+        self.crashes += other.crashes;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Metric {
+    #[default]
+    Cpu,
+    Rss,
+    Io,
+    Net,
+    Crashes,
+}
+
+impl Metric {
+    fn value(self, usage: &Usage) -> f64 {
+        match self {
+            Metric::Cpu => usage.cpu_seconds,
+            Metric::Rss => usage.peak_rss_bytes as f64,
+            Metric::Io => usage.io_bytes as f64,
+            Metric::Net => usage.net_bytes as f64,
+            Metric::Crashes => usage.crashes as f64,
+        }
+    }
+}
+
+/// One workload's usage over one period, as stored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageRollup {
+    pub period: Period,
+    /// Unix seconds
+    pub start: i64,
+    #[serde(flatten)]
+    pub workload: Workload,
+    #[serde(flatten)]
+    pub usage: Usage,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LeaderboardEntry {
+    #[serde(flatten)]
+    pub workload: Workload,
+    #[serde(flatten)]
+    pub usage: Usage,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LeaderboardReport {
+    pub period: Period,
+    pub start: i64,
+    pub end: i64,
+    /// The period has not ended yet
+    pub in_progress: bool,
+    pub metric: Metric,
+    /// Workloads with usage in the period, before `limit`
+    pub workloads: usize,
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+type Buckets = BTreeMap<i64, HashMap<Workload, Usage>>;
+
+/// Hourly and daily usage per workload.
+#[derive(Debug)]
+pub struct UsageLedger {
+    /// Keyed by hour start; includes the open hour
+    hours: Buckets,
+    /// Keyed by day start; only days that are over
+    days: Buckets,
+    /// Start of the hour being accumulated
+    open_hour: i64,
+    hour_retention: i64,
+    day_retention: i64,
+}
+
+impl UsageLedger {
+    pub fn new(now: i64, retention_hours: u64, retention_days: u64) -> Self {
+        Self {
+            hours: Buckets::new(),
+            days: Buckets::new(),
+            open_hour: Period::Hour.start_of(now),
+            hour_retention: retention_hours.max(MIN_RETENTION_HOURS) as i64 * HOUR,
+            day_retention: retention_days.max(1) as i64 * DAY,
+        }
+    }
+
+    /// Load rollups written by an earlier run.
+    pub fn restore(&mut self, rollups: Vec<UsageRollup>) {
+        for row in rollups {
+            let buckets = match row.period {
+                Period::Hour => &mut self.hours,
+                Period::Day => &mut self.days,
+            };
+            buckets
+                .entry(row.start)
+                .or_default()
+                .entry(row.workload)
+                .or_default()
+                .merge(&row.usage);
+        }
+    }
+
+    /// Add usage sampled at `now`.
+    pub fn charge(&mut self, now: i64, workload: Workload, usage: &Usage) {
+        self.hours
+            .entry(Period::Hour.start_of(now))
+            .or_default()
+            .entry(workload)
+            .or_default()
+            .merge(usage);
+    }
+
+    /// Close the hours and days that ended before `now` and drop those past
+    /// retention. Returns the closed rollups, for persisting.
+    pub fn advance(&mut self, now: i64) -> Vec<UsageRollup> {
+        let hour = Period::Hour.start_of(now);
+        let mut closed = Vec::new();
+        for (start, usage) in self.hours.range(self.open_hour..hour) {
+            closed.extend(rollups(Period::Hour, *start, usage));
+        }
+        self.open_hour = self.open_hour.max(hour);
+
+        let today = Period::Day.start_of(now);
+        let oldest_day = today - self.day_retention;
+        let mut pending: Vec<i64> = self
+            .hours
+            .range(..today)
+            .map(|(start, _)| Period::Day.start_of(*start))
+            .filter(|day| *day >= oldest_day && !self.days.contains_key(day))
+            .collect();
+        pending.dedup();
+        for day in pending {
+            let usage = self.sum_hours(day, day + DAY);
+            closed.extend(rollups(Period::Day, day, &usage));
+            self.days.insert(day, usage);
+        }
+
+        self.hours
+            .retain(|start, _| *start >= hour - self.hour_retention);
+        self.days.retain(|start, _| *start >= oldest_day);
+        closed
+    }
+
+    /// Oldest hour and day start still retained, for pruning the store.
+    pub fn retention_cutoffs(&self, now: i64) -> (i64, i64) {
+        (
+            Period::Hour.start_of(now) - self.hour_retention,
+            Period::Day.start_of(now) - self.day_retention,
+        )
+    }
+
+    fn sum_hours(&self, start: i64, end: i64) -> HashMap<Workload, Usage> {
+        let mut totals: HashMap<Workload, Usage> = HashMap::new();
+        for usage in self.hours.range(start..end).map(|(_, usage)| usage) {
+            for (workload, usage) in usage {
+                totals.entry(workload.clone()).or_default().merge(usage);
+            }
+        }
+        totals
+    }
+
+    /// Workloads ranked by `metric` for the period `offset` periods before
+    /// the current one (`offset` 0 is the period in progress).
+    pub fn top(
+        &self,
+        period: Period,
+        offset: u32,
+        now: i64,
+        metric: Metric,
+        kind: Option<WorkloadKind>,
+        limit: usize,
+    ) -> LeaderboardReport {
+        let start = period.start_of(now) - i64::from(offset) * period.secs();
+        let end = start + period.secs();
+        let totals = match (period, self.days.get(&start)) {
+            (Period::Day, Some(day)) => day.clone(),
+            _ => self.sum_hours(start, end),
+        };
+        let mut entries: Vec<LeaderboardEntry> = totals
+            .into_iter()
+            .filter(|(workload, _)| kind.is_none_or(|k| workload.kind == k))
+            .map(|(workload, usage)| LeaderboardEntry { workload, usage })
+            .collect();
+        let workloads = entries.len();
+        entries.sort_by(|a, b| {
+            metric
+                .value(&b.usage)
+                .total_cmp(&metric.value(&a.usage))
+                .then_with(|| a.workload.cmp(&b.workload))
+        });
+        entries.truncate(limit.min(MAX_LIMIT));
+        LeaderboardReport {
+            period,
+            start,
+            end,
+            in_progress: offset == 0,
+            metric,
+            workloads,
+            entries,
+        }
+    }
+}
+
+fn rollups(period: Period, start: i64, usage: &HashMap<Workload, Usage>) -> Vec<UsageRollup> {
+    usage
+        .iter()
+        .map(|(workload, usage)| UsageRollup {
+            period,
+            start,
+            workload: workload.clone(),
+            usage: *usage,
+        })
+        .collect()
+}
+
+/// Net bytes per pid since the last sample, with the comm seen on the event
+/// for pids that exit before the sampler can name them.
+type NetBytes = DashMap<u32, ([u8; 16], u64)>;
+
+/// Counts `net` event bytes for the leaderboard.
+pub struct NetUsageHandler {
+    net: Arc<NetBytes>,
+}
+
+#[async_trait]
+impl Handler for NetUsageHandler {
+    fn name(&self) -> &'static str {
+        "leaderboard"
+    }
+
+    async fn on_event(&self, event: &ProcessEvent) {
+        if event.event_type == EventType::Net as u32 {
+            self.net.entry(event.pid).or_insert((event.comm, 0)).1 += event.data;
+        }
+    }
+
+    async fn on_snapshot(&self, _snapshot: &SystemSnapshot) {}
+}
+
+struct Counters {
+    workload: Workload,
+    cpu_ticks: u64,
+    io_bytes: u64,
+}
+
+/// Reads per-process counters and turns them into per-workload deltas.
+struct Sampler {
+    k8s: Option<Arc<K8sContext>>,
+    prev: HashMap<i32, Counters>,
+    /// False until the first scan, whose counters are only a baseline
+    primed: bool,
+    ticks_per_sec: f64,
+    page_size: u64,
+}
+
+impl Sampler {
+    fn workload(&self, pid: i32, comm: &str) -> Workload {
+        match self
+            .k8s
+            .as_ref()
+            .and_then(|k8s| k8s.get_metadata_for_pid(pid as u32))
+        {
+            Some(meta) => Workload {
+                kind: WorkloadKind::Pod,
+                name: format!("{}/{}", meta.namespace, meta.pod_name),
+            },
+            None => Workload::comm(comm),
+        }
+    }
+
+    fn sample(&mut self, net: &NetBytes) -> HashMap<Workload, Usage> {
+        let mut usage: HashMap<Workload, Usage> = HashMap::new();
+        let mut seen = HashMap::with_capacity(self.prev.len());
+        let processes = match procfs::process::all_processes() {
+            Ok(processes) => processes,
+            Err(e) => {
+                warn!("[leaderboard] cannot list processes: {e}");
+                return usage;
+            }
+        };
+        for process in processes.flatten() {
+            let Ok(stat) = process.stat() else {
+                continue;
+            };
+            let cpu_ticks = stat.utime + stat.stime;
+            let io_bytes = process
+                .io()
+                .map(|io| io.read_bytes + io.write_bytes)
+                .unwrap_or(0);
+            let prev = self.prev.remove(&stat.pid);
+            let workload = match &prev {
+                Some(prev) => prev.workload.clone(),
+                None => self.workload(stat.pid, &stat.comm),
+            };
+            // Processes first seen after the baseline started since then.
+            let (cpu_base, io_base) = match &prev {
+                Some(prev) => (prev.cpu_ticks, prev.io_bytes),
+                None if self.primed => (0, 0),
+                None => (cpu_ticks, io_bytes),
+            };
+            let entry = usage.entry(workload.clone()).or_default();
+            entry.cpu_seconds += cpu_ticks.saturating_sub(cpu_base) as f64 / self.ticks_per_sec;
+            entry.io_bytes += io_bytes.saturating_sub(io_base);
+            entry.peak_rss_bytes += stat.rss * self.page_size;
+            seen.insert(
+                stat.pid,
+                Counters {
+                    workload,
+                    cpu_ticks,
+                    io_bytes,
+                },
+            );
+        }
+        self.prev = seen;
+        self.primed = true;
+
+        let pids: Vec<u32> = net.iter().map(|entry| *entry.key()).collect();
+        for pid in pids {
+            let Some((_, (comm, bytes))) = net.remove(&pid) else {
+                continue;
+            };
+            let workload = match self.prev.get(&(pid as i32)) {
+                Some(counters) => counters.workload.clone(),
+                None => {
+                    let comm = String::from_utf8_lossy(&comm);
+                    Workload::comm(comm.trim_end_matches('\0'))
+                }
+            };
+            usage.entry(workload).or_default().net_bytes += bytes;
+        }
+        usage
+    }
+}
+
+/// Usage history shared with the API.
+pub struct Leaderboard {
+    ledger: Mutex<UsageLedger>,
+    net: Arc<NetBytes>,
+}
+
+impl Leaderboard {
+    /// Start sampling. The handler from `net_handler` must be registered for
+    /// network bytes to be counted.
+    pub fn spawn(
+        config: &LeaderboardConfig,
+        store: Option<Arc<IncidentStore>>,
+        k8s: Option<Arc<K8sContext>>,
+    ) -> Arc<Self> {
+        let now = chrono::Utc::now().timestamp();
+        let board = Arc::new(Self {
+            ledger: Mutex::new(UsageLedger::new(
+                now,
+                config.retention_hours,
+                config.retention_days,
+            )),
+            net: Arc::new(NetBytes::new()),
+        });
+        let mut sampler = Sampler {
+            k8s,
+            prev: HashMap::new(),
+            primed: false,
+            ticks_per_sec: procfs::ticks_per_second() as f64,
+            page_size: procfs::page_size(),
+        };
+        let interval = Duration::from_secs(config.sample_secs.max(1));
+        let shared = Arc::clone(&board);
+        tokio::spawn(async move {
+            if let Some(store) = &store {
+                let (hours, days) = shared.ledger.lock().unwrap().retention_cutoffs(now);
+                match store.usage_rollups_since(hours.min(days)).await {
+                    Ok(rows) => {
+                        info!("[leaderboard] restored {} rollups", rows.len());
+                        shared.ledger.lock().unwrap().restore(rows);
+                    }
+                    Err(e) => warn!("[leaderboard] failed to restore rollups: {e}"),
+                }
+            }
+            let mut tick = tokio::time::interval(interval);
+            loop {
+                tick.tick().await;
+                let usage = sampler.sample(&shared.net);
+                let now = chrono::Utc::now().timestamp();
+                let (closed, cutoffs) = {
+                    let mut ledger = shared.ledger.lock().unwrap();
+                    let closed = ledger.advance(now);
+                    for (workload, usage) in usage {
+                        ledger.charge(now, workload, &usage);
+                    }
+                    (closed, ledger.retention_cutoffs(now))
+                };
+                if closed.is_empty() {
+                    continue;
+                }
+                debug!("[leaderboard] closed {} rollups", closed.len());
+                if let Some(store) = &store {
+                    store.submit(IncidentWrite::UsageRollups(closed));
+                    store.submit(IncidentWrite::PruneUsage {
+                        hours_before: cutoffs.0,
+                        days_before: cutoffs.1,
+                    });
+                }
+            }
+        });
+        board
+    }
+
+    pub fn net_handler(&self) -> NetUsageHandler {
+        NetUsageHandler {
+            net: Arc::clone(&self.net),
+        }
+    }
+
+    /// Count a crash of a process named `comm`.
+    pub fn record_crash(&self, comm: &str) {
+        let now = chrono::Utc::now().timestamp();
+        let crash = Usage {
+            crashes: 1,
+            ..Usage::default()
+        };
+        self.ledger
+            .lock()
+            .unwrap()
+            .charge(now, Workload::comm(comm), &crash);
+    }
+
+    pub fn top(
+        &self,
+        period: Period,
+        offset: u32,
+        metric: Metric,
+        kind: Option<WorkloadKind>,
+        limit: usize,
+    ) -> LeaderboardReport {
+        let now = chrono::Utc::now().timestamp();
+        self.ledger
+            .lock()
+            .unwrap()
+            .top(period, offset, now, metric, kind, limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pod(name: &str) -> Workload {
+        Workload {
+            kind: WorkloadKind::Pod,
+            name: name.to_string(),
+        }
+    }
+
+    fn usage(cpu_seconds: f64, peak_rss_bytes: u64) -> Usage {
+        Usage {
+            cpu_seconds,
+            peak_rss_bytes,
+            ..Usage::default()
+        }
+    }
+
+    #[test]
+    fn hours_roll_up_into_days() {
+        // 22:00 UTC on day 10
+        let start = 10 * DAY + 22 * HOUR;
+        let mut ledger = UsageLedger::new(start, 48, 7);
+        ledger.charge(start + 60, pod("prod/etl"), &usage(100.0, 1 << 30));
+        ledger.charge(start + 120, pod("prod/etl"), &usage(50.0, 2 << 30));
+        ledger.charge(start + 120, Workload::comm("backup"), &usage(10.0, 1 << 20));
+        assert!(ledger.advance(start + 300).is_empty());
+
+        let hour = ledger.advance(start + HOUR + 5);
+        assert_eq!(hour.len(), 2);
+        assert!(
+            hour.iter()
+                .all(|r| r.period == Period::Hour && r.start == start)
+        );
+        ledger.charge(start + HOUR + 5, Workload::comm("backup"), &usage(500.0, 0));
+        let crash = Usage {
+            crashes: 1,
+            ..Usage::default()
+        };
+        ledger.charge(start + HOUR + 9, Workload::comm("api"), &crash);
+        ledger.charge(start + HOUR + 60, Workload::comm("api"), &crash);
+
+        // Past midnight: 23:00 closes and day 10 is summed.
+        let closed = ledger.advance(11 * DAY + 10);
+        let days: Vec<_> = closed.iter().filter(|r| r.period == Period::Day).collect();
+        assert_eq!(days.len(), 3);
+        let etl = days.iter().find(|r| r.workload == pod("prod/etl")).unwrap();
+        assert_eq!(etl.usage.cpu_seconds, 150.0);
+        assert_eq!(etl.usage.peak_rss_bytes, 2 << 30);
+
+        let now = 11 * DAY + 10;
+        let yesterday = ledger.top(Period::Day, 1, now, Metric::Cpu, None, 10);
+        assert_eq!(yesterday.start, 10 * DAY);
+        assert!(!yesterday.in_progress);
+        assert_eq!(yesterday.entries[0].workload, Workload::comm("backup"));
+        assert_eq!(yesterday.entries[0].usage.cpu_seconds, 510.0);
+        let by_rss = ledger.top(
+            Period::Day,
+            1,
+            now,
+            Metric::Rss,
+            Some(WorkloadKind::Pod),
+            10,
+        );
+        assert_eq!(by_rss.workloads, 1);
+        assert_eq!(by_rss.entries[0].workload, pod("prod/etl"));
+        let by_crashes = ledger.top(Period::Day, 1, now, Metric::Crashes, None, 1);
+        assert_eq!(by_crashes.entries[0].workload, Workload::comm("api"));
+        assert_eq!(by_crashes.entries[0].usage.crashes, 2);
+
+        // A restart restores the day instead of summing it again.
+        let mut restored = UsageLedger::new(now, 48, 7);
+        restored.restore(closed);
+        assert!(restored.advance(now + 60).is_empty());
+        let day = restored.top(Period::Day, 1, now, Metric::Cpu, None, 1);
+        assert_eq!(day.workloads, 3);
+        assert_eq!(day.entries[0].usage.cpu_seconds, 510.0);
+    }
+}
@@ -2,27 +2,93 @@
 // Both local stable and Docker stable support it without feature flags
 
 pub mod alerts;
+pub mod ancestry_profile;
+pub mod anomaly;
+pub mod bench;
 pub mod bpf_config;
 pub mod collectors;
 pub mod config;
 pub mod context;
+pub mod coordination;
+pub mod crashes;
+pub mod daemonset;
+pub mod detectors;
+pub mod egress;
 pub mod enforcement;
+pub mod enrichment;
+pub mod event_schema;
+pub mod evidence;
+pub mod exec_path;
+pub mod exec_risk;
+pub mod expr;
+pub mod fd_usage;
+pub mod file_paths;
+pub mod filter;
+#[cfg(feature = "fleet-telemetry")]
+pub mod fleet_telemetry;
+pub mod flows;
 pub mod handler;
+pub mod health_score;
+pub mod identity;
 pub mod incidents;
 pub mod insights;
 pub mod k8s;
+pub mod leaderboard;
+pub mod lineage;
+pub mod maintenance;
+pub mod memory_maps;
 pub mod metrics;
+pub mod net_traffic;
+#[cfg(feature = "notifications")]
 pub mod notifications;
+pub mod oom;
+pub mod patterns;
+pub mod restart_loops;
+pub mod retry;
 pub mod runtime;
+pub mod scaling;
 pub mod schema;
+pub mod scope;
+pub mod script;
+pub mod signals;
+#[cfg(feature = "simulator")]
+pub mod simulator;
+pub mod startup;
+pub mod state_bundle;
+pub mod syscalls;
+pub mod templates;
+pub mod topology;
 pub mod types;
 pub mod ui;
+pub mod units;
 pub mod utils;
 
 pub use config::{Config, LoggingConfig, OfflineGuard, OutputConfig, RuntimeConfig};
-pub use incidents::{Incident, IncidentAnalyzer, IncidentStats, IncidentStore};
+pub use incidents::{Incident, IncidentAnalyzer, IncidentStats, IncidentStore, IncidentWrite};
 pub use metrics::Metrics;
 
 pub use linnix_ai_ebpf_common::PERCENT_MILLI_UNKNOWN;
 pub use linnix_ai_ebpf_common::ProcessEvent as ProcessEventWire;
 pub use linnix_ai_ebpf_common::ProcessEventExt as ProcessEvent;
+
+/// Optional components and whether this binary was built with them.
+pub const BUILD_FEATURES: [(&str, bool); 6] = [
+    ("reasoner", cfg!(feature = "reasoner")),
+    ("notifications", cfg!(feature = "notifications")),
+    ("k8s", cfg!(feature = "k8s")),
+    ("docker", cfg!(feature = "docker")),
+    ("incidents", cfg!(feature = "incidents")),
+    ("fleet-telemetry", cfg!(feature = "fleet-telemetry")),
+];
+
+/// `full` with every optional component, `minimal` with none (telemetry
+/// only), `custom` otherwise.
+pub fn build_profile() -> &'static str {
+    if BUILD_FEATURES.iter().all(|(_, on)| *on) {
+        "full"
+    } else if BUILD_FEATURES.iter().all(|(_, on)| !*on) {
+        "minimal"
+    } else {
+        "custom"
+    }
+}
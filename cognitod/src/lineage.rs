@@ -0,0 +1,451 @@
+//! Process ancestry for rule evaluation and alert context
+//!
+//! Ancestry is resolved from the live process table, falling back to procfs
+//! for processes that started before the daemon. Rules with lineage
+//! predicates resolve the ancestry of an event's parent through
+//! `AncestryCache`, keyed by parent pid, so a busy parent forking thousands of
+//! children costs one walk per TTL instead of one per event.
+//!
+//! Pids are reused, so a `ppid` may by now name a process that started after
+//! the child. Such a parent is not followed, and `children` skips processes
+//! that started before the pid they name as parent.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::time::{Duration, Instant};
+
+use crate::context::ContextStore;
+use crate::identity::ProcessKey;
+use crate::maintenance::glob_match;
+
+/// Longest ancestry chain walked.
+const MAX_DEPTH: usize = 32;
+const DEFAULT_WITHIN: usize = 8;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LineageEntry {
+    pub pid: u32,
+    pub comm: String,
+    pub uid: u32,
+    /// Seconds since the process started, when known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub age_secs: Option<u64>,
+}
+
+impl From<&LineageEntry> for linnix_events::LineageEntry {
+    fn from(entry: &LineageEntry) -> Self {
+        Self {
+            pid: entry.pid,
+            comm: entry.comm.clone(),
+            uid: entry.uid,
+            age_secs: entry.age_secs,
+        }
+    }
+}
+
+/// Ancestry of `pid`, starting with the process itself.
+pub fn ancestry(pid: u32, ctx: Option<&ContextStore>) -> Vec<LineageEntry> {
+    let mut chain: Vec<LineageEntry> = Vec::new();
+    let mut current = pid;
+    let mut child: Option<ProcessKey> = None;
+    while current != 0 && chain.len() < MAX_DEPTH && !chain.iter().any(|e| e.pid == current) {
+        let live = ctx.and_then(|ctx| {
+            ctx.get_process_by_pid(current)
+                .map(|e| (e, ctx.process_key(current)))
+        });
+        let key = match &live {
+            Some((_, key)) => *key,
+            None => ProcessKey::read(current),
+        };
+        // A parent cannot start after its child; this one got the pid later
+        if let (Some(child), Some(key)) = (child, key)
+            && child.started_before(key.start_ns)
+        {
+            break;
+        }
+        let Some((ppid, entry)) = live
+            .map(|(e, _)| {
+                let comm = String::from_utf8_lossy(&e.comm)
+                    .trim_end_matches('\0')
+                    .to_string();
+                (
+                    e.ppid,
+                    LineageEntry {
+                        pid: current,
+                        comm,
+                        uid: e.uid,
+                        age_secs: age_secs(e.ts_ns),
+                    },
+                )
+            })
+            .or_else(|| procfs_entry(current))
+        else {
+            break;
+        };
+        chain.push(entry);
+        child = key;
+        current = ppid;
+    }
+    chain
+}
+
+fn procfs_entry(pid: u32) -> Option<(u32, LineageEntry)> {
+    let process = procfs::process::Process::new(pid as i32).ok()?;
+    let stat = process.stat().ok()?;
+    let uid = process.uid().unwrap_or(u32::MAX);
+    let age_secs = procfs::boot_time_secs().ok().and_then(|boot| {
+        let started = boot + stat.starttime / procfs::ticks_per_second();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()?
+            .as_secs();
+        Some(now.saturating_sub(started))
+    });
+    Some((
+        stat.ppid.max(0) as u32,
+        LineageEntry {
+            pid,
+            comm: stat.comm,
+            uid,
+            age_secs,
+        },
+    ))
+}
+
+/// Up to `limit` children of `pid`, youngest first. Uses the live process
+/// table, or procfs when the table has none.
+pub fn children(pid: u32, ctx: Option<&ContextStore>, limit: usize) -> Vec<LineageEntry> {
+    if pid == 0 || limit == 0 {
+        return Vec::new();
+    }
+    let mut found: Vec<LineageEntry> = ctx
+        .map(|ctx| {
+            let parent = ctx.process_key(pid);
+            ctx.get_live_map()
+                .values()
+                .filter(|(e, _)| e.ppid == pid && e.pid != pid && e.exit_time().is_none())
+                .filter(|(e, _)| {
+                    !parent
+                        .zip(ctx.process_key(e.pid))
+                        .is_some_and(|(parent, child)| child.started_before(parent.start_ns))
+                })
+                .map(|(e, _)| LineageEntry {
+                    pid: e.pid,
+                    comm: String::from_utf8_lossy(&e.comm)
+                        .trim_end_matches('\0')
+                        .to_string(),
+                    uid: e.uid,
+                    age_secs: age_secs(e.ts_ns),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if found.is_empty() {
+        found = procfs_children(pid);
+    }
+    found.sort_by_key(|e| (e.age_secs.unwrap_or(u64::MAX), e.pid));
+    found.truncate(limit);
+    found
+}
+
+fn procfs_children(pid: u32) -> Vec<LineageEntry> {
+    let path = format!("/proc/{pid}/task/{pid}/children");
+    let Ok(list) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    list.split_whitespace()
+        .filter_map(|child| child.parse().ok())
+        .filter_map(|child| procfs_entry(child).map(|(_, entry)| entry))
+        .collect()
+}
+
+/// Age of a process whose start was stamped `ts_ns` by the kernel
+/// (`CLOCK_MONOTONIC`). Unstamped events have no age.
+pub fn age_secs(ts_ns: u64) -> Option<u64> {
+    use nix::time::{ClockId, clock_gettime};
+    if ts_ns == 0 {
+        return None;
+    }
+    let now = clock_gettime(ClockId::CLOCK_MONOTONIC).ok()?;
+    let now_ns = (now.tv_sec() as u64) * 1_000_000_000 + (now.tv_nsec() as u64);
+    Some(now_ns.saturating_sub(ts_ns) / 1_000_000_000)
+}
+
+/// `bash(4211) <- sshd(980) <- systemd(1)`
+pub fn format_lineage(chain: &[LineageEntry]) -> String {
+    chain
+        .iter()
+        .map(|e| format!("{}({})", e.comm, e.pid))
+        .collect::<Vec<_>>()
+        .join(" <- ")
+}
+
+/// `bash(4211, uid 1000, 3m)`
+pub fn describe_entry(entry: &LineageEntry) -> String {
+    match entry.age_secs {
+        Some(age) => format!(
+            "{}({}, uid {}, {})",
+            entry.comm,
+            entry.pid,
+            entry.uid,
+            format_age(age)
+        ),
+        None => format!("{}({}, uid {})", entry.comm, entry.pid, entry.uid),
+    }
+}
+
+fn format_age(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3600 => format!("{}m", secs / 60),
+        3600..86_400 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86_400),
+    }
+}
+
+/// Rule condition on a process's ancestors. Every field that is set must
+/// match; comm fields are globs.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LineagePredicate {
+    #[serde(default)]
+    pub parent_comm: Option<String>,
+    #[serde(default)]
+    pub parent_uid: Option<u32>,
+    /// Matches any ancestor up to `within` levels above the process.
+    #[serde(default)]
+    pub ancestor_comm: Option<String>,
+    #[serde(default = "default_within")]
+    pub within: usize,
+}
+
+fn default_within() -> usize {
+    DEFAULT_WITHIN
+}
+
+impl Default for LineagePredicate {
+    fn default() -> Self {
+        Self {
+            parent_comm: None,
+            parent_uid: None,
+            ancestor_comm: None,
+            within: DEFAULT_WITHIN,
+        }
+    }
+}
+
+impl LineagePredicate {
+    /// `ancestors` starts with the parent.
+    pub fn matches(&self, ancestors: &[LineageEntry]) -> bool {
+        let parent = ancestors.first();
+        if let Some(pattern) = &self.parent_comm
+            && !parent.is_some_and(|p| glob_match(pattern, &p.comm))
+        {
+            return false;
+        }
+        if let Some(uid) = self.parent_uid
+            && parent.is_none_or(|p| p.uid != uid)
+        {
+            return false;
+        }
+        if let Some(pattern) = &self.ancestor_comm
+            && !ancestors
+                .iter()
+                .take(self.within)
+                .any(|a| glob_match(pattern, &a.comm))
+        {
+            return false;
+        }
+        true
+    }
+}
+
+/// TTL cache of ancestry chains keyed by the pid they start at.
+pub struct AncestryCache {
+    entries: HashMap<u32, (Instant, Arc<[LineageEntry]>)>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+impl AncestryCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            ttl,
+            capacity,
+        }
+    }
+
+    pub fn get_or_resolve(
+        &mut self,
+        pid: u32,
+        now: Instant,
+        resolve: impl FnOnce() -> Vec<LineageEntry>,
+    ) -> Arc<[LineageEntry]> {
+        if let Some((at, chain)) = self.entries.get(&pid)
+            && now.saturating_duration_since(*at) < self.ttl
+        {
+            return Arc::clone(chain);
+        }
+        if self.entries.len() >= self.capacity {
+            let ttl = self.ttl;
+            self.entries
+                .retain(|_, (at, _)| now.saturating_duration_since(*at) < ttl);
+            if self.entries.len() >= self.capacity {
+                self.entries.clear();
+            }
+        }
+        let chain: Arc<[LineageEntry]> = resolve().into();
+        self.entries.insert(pid, (now, Arc::clone(&chain)));
+        chain
+    }
+
+    /// Drop a pid whose comm or existence changed (exec, exit).
+    pub fn forget(&mut self, pid: u32) {
+        self.entries.remove(&pid);
+    }
+}
+
+impl Default for AncestryCache {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(5), 8192)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PERCENT_MILLI_UNKNOWN, ProcessEvent, ProcessEventWire};
+
+    fn entry(pid: u32, comm: &str, uid: u32) -> LineageEntry {
+        LineageEntry {
+            pid,
+            comm: comm.to_string(),
+            uid,
+            age_secs: None,
+        }
+    }
+
+    #[test]
+    fn ancestry_walks_live_table() {
+        let ctx = ContextStore::new(std::time::Duration::from_secs(60), 16, None);
+        for (pid, ppid, comm) in [
+            (900u32, 0u32, b"sshd"),
+            (901, 900, b"bash"),
+            (902, 901, b"curl"),
+        ] {
+            let mut name = [0u8; 16];
+            name[..comm.len()].copy_from_slice(comm);
+            ctx.add(ProcessEvent::new(ProcessEventWire {
+                pid,
+                ppid,
+                uid: 1000,
+                gid: 0,
+                event_type: 0,
+                ts_ns: 0,
+                seq: 0,
+                comm: name,
+                exit_time_ns: 0,
+                cpu_pct_milli: PERCENT_MILLI_UNKNOWN,
+                mem_pct_milli: PERCENT_MILLI_UNKNOWN,
+                data: 0,
+                data2: 0,
+                aux: 0,
+                aux2: 0,
+                cgroup_id: 0,
+            }));
+        }
+        let chain = ancestry(902, Some(&ctx));
+        assert_eq!(
+            format_lineage(&chain),
+            "curl(902) <- bash(901) <- sshd(900)"
+        );
+        assert!(chain.iter().all(|e| e.uid == 1000));
+    }
+
+    #[test]
+    fn ancestry_stops_at_a_reused_parent_pid() {
+        let ctx = ContextStore::new(std::time::Duration::from_secs(60), 16, None);
+        for (pid, ppid, event_type, secs) in [
+            (910u32, 0u32, 1u32, 1u64),
+            (911, 910, 1, 2),
+            (910, 0, 2, 3),
+            // pid 910 is handed to an unrelated process
+            (910, 0, 1, 4),
+        ] {
+            ctx.add(ProcessEvent::new(ProcessEventWire {
+                pid,
+                ppid,
+                uid: 0,
+                gid: 0,
+                event_type,
+                ts_ns: secs * 1_000_000_000,
+                seq: 0,
+                comm: [0; 16],
+                exit_time_ns: 0,
+                cpu_pct_milli: PERCENT_MILLI_UNKNOWN,
+                mem_pct_milli: PERCENT_MILLI_UNKNOWN,
+                data: 0,
+                data2: 0,
+                aux: 0,
+                aux2: 0,
+                cgroup_id: 0,
+            }));
+        }
+        let chain = ancestry(911, Some(&ctx));
+        assert_eq!(chain.len(), 1, "{}", format_lineage(&chain));
+        assert_eq!(chain[0].pid, 911);
+    }
+
+    #[test]
+    fn predicate_checks_parent_and_ancestors() {
+        let chain = [
+            entry(20, "nginx", 33),
+            entry(10, "nginx", 0),
+            entry(1, "systemd", 0),
+        ];
+        let parent = LineagePredicate {
+            parent_comm: Some("nginx".into()),
+            parent_uid: Some(33),
+            ..Default::default()
+        };
+        assert!(parent.matches(&chain));
+        assert!(!parent.matches(&chain[1..]), "root-owned master");
+
+        let ancestor = LineagePredicate {
+            ancestor_comm: Some("system?".into()),
+            within: 2,
+            ..Default::default()
+        };
+        assert!(!ancestor.matches(&chain));
+        assert!(ancestor.matches(&chain[1..]));
+        assert!(!parent.matches(&[]));
+    }
+
+    #[test]
+    fn cache_reuses_chain_until_ttl_or_forget() {
+        let mut cache = AncestryCache::new(Duration::from_secs(5), 4);
+        let now = Instant::now();
+        let mut walks = 0;
+        let mut resolve = |pid| {
+            cache.get_or_resolve(pid, now, || {
+                walks += 1;
+                vec![entry(pid, "nginx", 0)]
+            });
+        };
+        resolve(20);
+        resolve(20);
+        assert_eq!(walks, 1);
+
+        cache.forget(20);
+        cache.get_or_resolve(20, now, || {
+            walks += 1;
+            Vec::new()
+        });
+        cache.get_or_resolve(20, now + Duration::from_secs(6), || {
+            walks += 1;
+            Vec::new()
+        });
+        assert_eq!(walks, 3);
+    }
+}
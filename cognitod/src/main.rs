@@ -5,7 +5,7 @@
 use anyhow::Context;
 use aya::Pod;
 use aya::maps::{
-    MapData,
+    Map, MapData,
     perf::{PerfEventArray, PerfEventArrayBuffer},
 };
 use aya::programs::{KProbe, TracePoint};
@@ -13,33 +13,41 @@ use aya::util::online_cpus;
 use aya::{Ebpf, EbpfLoader};
 use aya_log::EbpfLogger;
 use caps::{CapSet, Capability};
-use log::{info, warn};
-use std::{convert::TryFrom, error::Error, path::PathBuf, sync::Arc, time::Duration};
+use log::{debug, info, warn};
+use std::{convert::TryFrom, error::Error, os::fd::AsFd, path::PathBuf, sync::Arc, time::Duration};
 use tokio::fs::OpenOptions;
 use tokio::io::{AsyncWriteExt, BufWriter};
 use tokio::sync::broadcast;
 use tokio::time::{sleep, timeout};
 
 use crate::insights::InsightStore;
-use crate::runtime::start_perf_listener;
+use crate::runtime::{
+    BpfControl, SequencerConsumer, start_perf_listener, start_sequencer_listener,
+};
 pub use linnix_ai_ebpf_common::PERCENT_MILLI_UNKNOWN;
 pub use linnix_ai_ebpf_common::ProcessEvent as ProcessEventWire;
 pub use linnix_ai_ebpf_common::ProcessEventExt as ProcessEvent;
-use linnix_ai_ebpf_common::TelemetryConfig;
+use linnix_ai_ebpf_common::{SEQUENCER_RING_SIZE, TelemetryConfig, features};
 
 mod api;
 mod runtime;
 // mod routes; // Deleted (dead code cleanup)
 
+use cognitod::alerts;
 use cognitod::bpf_config;
 use cognitod::config;
 use cognitod::context;
 use cognitod::enforcement;
+use cognitod::event_schema;
+use cognitod::exec_path;
+use cognitod::flows;
 use cognitod::handler;
 use cognitod::insights;
 use cognitod::metrics;
+use cognitod::script;
 use cognitod::types;
 use cognitod::ui;
+use cognitod::utils;
 
 #[repr(transparent)]
 #[derive(Copy, Clone)]
@@ -48,26 +56,95 @@ struct TelemetryConfigPod(TelemetryConfig);
 unsafe impl Pod for TelemetryConfigPod {}
 
 struct BpfRuntimeGuards {
-    _bpf: Ebpf,
+    control: Arc<BpfControl>,
     _logger: Option<EbpfLogger>,
+    kprobes: Vec<ProbeBinding>,
+}
+
+/// The mapped sequencer rings; their maps stay open while they are read.
+struct SequencerRing {
+    rings: SequencerRings,
+    /// Checkpoint matched against the ticket counter before the sequencer
+    /// was enabled
+    restored: Option<(CursorStore, Reconciliation)>,
 }
 
 const INSIGHT_STORE_CAPACITY: usize = 50;
+/// Longest startup waits for the pod list before events are processed.
+const K8S_WARM_UP_TIMEOUT: Duration = Duration::from_secs(5);
 
-fn attach_kprobe_internal(bpf: &mut Ebpf, program: &str, symbol: &str) -> anyhow::Result<()> {
+/// Attach `program` to the first symbol of its profile that the kernel has.
+/// Without `/proc/kallsyms` every symbol of the profile is tried in order.
+fn attach_kprobe_internal(
+    bpf: &mut Ebpf,
+    symbols: Option<&KernelSymbols>,
+    program: &'static str,
+) -> anyhow::Result<String> {
+    let profile = probe_profiles::profile(program)
+        .ok_or_else(|| anyhow::anyhow!("no probe profile for {program}"))?;
+    let candidates = match symbols {
+        Some(symbols) => symbols.candidates(profile.symbols),
+        None => profile.symbols.iter().map(|s| s.to_string()).collect(),
+    };
+    if candidates.is_empty() {
+        anyhow::bail!("none of {:?} in /proc/kallsyms", profile.symbols);
+    }
     let probe: &mut KProbe = bpf
         .program_mut(program)
         .ok_or_else(|| anyhow::anyhow!("{program} program not found"))?
         .try_into()?;
     probe.load()?;
-    probe.attach(symbol, 0)?;
-    Ok(())
+    let mut last_err = None;
+    for symbol in candidates {
+        match probe.attach(&symbol, 0) {
+            Ok(_) => return Ok(symbol),
+            Err(e) => {
+                debug!("[cognitod] kprobe {symbol} ({program}) refused: {e}");
+                last_err = Some(e);
+            }
+        }
+    }
+    Err(last_err
+        .map(Into::into)
+        .unwrap_or_else(|| anyhow::anyhow!("no symbol attached")))
 }
 
-fn attach_kprobe_optional(bpf: &mut Ebpf, program: &str, symbol: &str) {
-    if let Err(err) = attach_kprobe_internal(bpf, program, symbol) {
-        warn!("[cognitod] optional kprobe {symbol} ({program}) not attached: {err:?}");
+/// Attach `program`, recording the outcome in `bindings`. Errors are
+/// returned only for `required` probes.
+fn attach_kprobe(
+    bpf: &mut Ebpf,
+    symbols: Option<&KernelSymbols>,
+    bindings: &mut Vec<ProbeBinding>,
+    program: &'static str,
+    required: bool,
+) -> anyhow::Result<()> {
+    let preferred = probe_profiles::profile(program)
+        .and_then(|p| p.symbols.first().copied())
+        .unwrap_or(program);
+    let mut binding = ProbeBinding {
+        program,
+        preferred,
+        symbol: None,
+        error: None,
+    };
+    match attach_kprobe_internal(bpf, symbols, program) {
+        Ok(symbol) => {
+            binding.symbol = Some(symbol);
+            if binding.fallback() {
+                info!(
+                    "[cognitod] kprobe {program} bound to {} instead of {preferred}",
+                    binding.symbol.as_deref().unwrap_or_default()
+                );
+            }
+        }
+        Err(err) if required => return Err(err.context(format!("kprobe {program}"))),
+        Err(err) => {
+            warn!("[cognitod] optional kprobe {preferred} ({program}) not attached: {err:?}");
+            binding.error = Some(format!("{err:#}"));
+        }
     }
+    bindings.push(binding);
+    Ok(())
 }
 
 fn attach_tracepoint_internal(
@@ -92,13 +169,31 @@ fn attach_tracepoint_optional(bpf: &mut Ebpf, program: &str, category: &str, nam
 }
 
 use crate::api::{AppState, all_routes};
-use crate::bpf_config::{CoreRssMode, derive_telemetry_config};
+use crate::bpf_config::{CoreRssMode, derive_telemetry_config_cached};
+use crate::runtime::arch::ArchReport;
+use crate::runtime::backlog::BacklogConfig;
+use crate::runtime::cursor::{self, Checkpointer, CursorStore, DeliveryMarks};
+use crate::runtime::features::{
+    FeatureNegotiation, PT_REGS_FEATURES, read_manifest, read_target_arch,
+};
+use crate::runtime::numa::{self, NumaNode, Partition};
+use crate::runtime::placement;
+use crate::runtime::probe_profiles::{self, KernelSymbols, ProbeBinding};
 use crate::runtime::probes::{ProbeState, RssProbeMode};
+use crate::runtime::stream_listener::SequencerRings;
 use clap::Parser;
 use cognitod::alerts::RuleEngine;
-use cognitod::config::{Config, OfflineGuard};
+use cognitod::collectors::cgroup_tree;
+use cognitod::config::{Config, EventTransport, OfflineGuard, SequencerConfig};
+use cognitod::coordination::Coordinator;
+use cognitod::expr::ComputedField;
+use cognitod::handler::webhook::EventWebhookHandler;
 use cognitod::handler::{HandlerList, JsonlHandler};
+use cognitod::maintenance::MaintenanceManager;
 use cognitod::metrics::Metrics;
+use cognitod::metrics::cursor::{Decision, Reconciliation};
+use cognitod::scope::CgroupScope;
+use cognitod::state_bundle::{self, StateSources};
 use serde_json::json;
 use std::{fs, path::Path};
 
@@ -218,13 +313,188 @@ fn read_rss_trace_bytes() -> anyhow::Result<(Vec<u8>, String)> {
     read_bpf_object("LINNIX_RSS_TRACE_BPF_PATH", "rss_trace")
 }
 
+/// Map `SEQUENCER_RING`, zeroing it unless the checkpoint at
+/// `config.cursor_path` resumes in it, or with more than one NUMA node in
+/// `nodes` one ring per node; `None`, with a warning, when the object has no
+/// rings or they cannot be mapped. `maps_reused` is true when the ring and
+/// counters were loaded from their pins.
+fn open_sequencer_ring(
+    bpf: &mut Ebpf,
+    config: &SequencerConfig,
+    nodes: Vec<NumaNode>,
+    maps_reused: bool,
+) -> Option<SequencerRing> {
+    if nodes.len() > 1 {
+        if !config.cursor_path.is_empty() {
+            info!("[cognitod] the sequencer cursor is not checkpointed with [sequencer] numa");
+        }
+        return match open_partitioned_rings(bpf, nodes) {
+            Ok(rings) => Some(SequencerRing {
+                rings,
+                restored: None,
+            }),
+            Err(e) => {
+                warn!("[cognitod] cannot map the per-node sequencer rings: {e:#}");
+                None
+            }
+        };
+    }
+    // Read while the ticket counter cannot move: the sequencer is still off
+    let restored = match cursor::kernel_head(bpf) {
+        _ if config.cursor_path.is_empty() => None,
+        Ok(head) => {
+            let store = CursorStore::new(&config.cursor_path);
+            let reconciliation =
+                cursor::restore(&store, head, maps_reused, SEQUENCER_RING_SIZE as u64);
+            Some((store, reconciliation))
+        }
+        Err(e) => {
+            warn!("[cognitod] cannot read the sequencer ticket counter; cursor not restored: {e}");
+            None
+        }
+    };
+    // Only a ring that still holds the saved tickets is kept as it is
+    let keep_ring = restored
+        .as_ref()
+        .is_some_and(|(_, r)| matches!(r.decision, Decision::Resume | Decision::Overrun));
+    let map = match take_sequencer_ring(bpf, numa::RING_MAPS[0]) {
+        Ok(map) => map,
+        Err(e) => {
+            warn!("[cognitod] {e:#}");
+            return None;
+        }
+    };
+    let consumer = if keep_ring {
+        SequencerConsumer::attach_fd(map.fd().as_fd())
+    } else {
+        SequencerConsumer::from_fd(map.fd().as_fd())
+    };
+    match consumer {
+        Ok(consumer) => Some(SequencerRing {
+            rings: SequencerRings::Shared { consumer, map },
+            restored,
+        }),
+        Err(e) => {
+            warn!("[cognitod] cannot map SEQUENCER_RING (needs BPF_F_MMAPABLE): {e}");
+            None
+        }
+    }
+}
+
+/// Map and zero one ring per partition for the NUMA `nodes`; partition `n`
+/// is read on node `n`.
+fn open_partitioned_rings(bpf: &mut Ebpf, nodes: Vec<NumaNode>) -> anyhow::Result<SequencerRings> {
+    let count = numa::partitions_for(&nodes) as usize;
+    let mut nodes = nodes.into_iter();
+    let mut partitions = Vec::new();
+    let mut maps = Vec::new();
+    for ring in &numa::RING_MAPS[..count] {
+        let map = take_sequencer_ring(bpf, ring)?;
+        let consumer = SequencerConsumer::from_fd(map.fd().as_fd())
+            .with_context(|| format!("cannot map {ring} (needs BPF_F_MMAPABLE)"))?;
+        partitions.push(Partition {
+            consumer,
+            node: nodes.next(),
+        });
+        maps.push(map);
+    }
+    info!("[cognitod] sequencer partitioned across {count} NUMA nodes");
+    Ok(SequencerRings::Partitioned { partitions, maps })
+}
+
+fn take_sequencer_ring(bpf: &mut Ebpf, name: &str) -> anyhow::Result<MapData> {
+    match bpf.take_map(name) {
+        Some(Map::Array(data)) => Ok(data),
+        Some(other) => anyhow::bail!("{name} is not an array map ({other:?})"),
+        None => anyhow::bail!("BPF object has no {name} map"),
+    }
+}
+
+/// NUMA nodes to partition the sequencer across; empty when the host
+/// topology cannot be read.
+fn sequencer_nodes() -> Vec<NumaNode> {
+    match numa::online_nodes(Path::new(numa::NODE_ROOT)) {
+        Ok(nodes) => {
+            if nodes.len() < 2 {
+                info!("[cognitod] one NUMA node; [sequencer] numa keeps the shared ring");
+            }
+            nodes
+        }
+        Err(e) => {
+            warn!("[cognitod] cannot read the NUMA topology ({e}); using the shared ring");
+            Vec::new()
+        }
+    }
+}
+
+/// Prepare `dir` for the shared ring's pins; true when a previous run left
+/// all of them there to be reused. `None`, with a warning, when `dir` is not
+/// on bpffs, and the maps are then not pinned.
+fn sequencer_pins(dir: &Path) -> Option<bool> {
+    if let Err(e) = fs::create_dir_all(dir) {
+        warn!("[cognitod] cannot create {}: {e}", dir.display());
+    }
+    if !cursor::on_bpffs(dir) {
+        warn!(
+            "[cognitod] {} is not on bpffs; the sequencer ring will not survive a restart",
+            dir.display()
+        );
+        return None;
+    }
+    let reused = cursor::pinned(dir);
+    // Keep no partial set: reused maps would not match fresh ones
+    if !reused && let Err(e) = cursor::unpin(dir) {
+        warn!(
+            "[cognitod] cannot remove stale pins in {}: {e}",
+            dir.display()
+        );
+        return None;
+    }
+    Some(reused)
+}
+
 fn init_ebpf(
     bpf_bytes: &[u8],
     telemetry_cfg: TelemetryConfig,
-) -> anyhow::Result<(BpfRuntimeGuards, Vec<PerfEventArrayBuffer<MapData>>)> {
+    negotiated: &FeatureNegotiation,
+    transport: EventTransport,
+    sequencer: &SequencerConfig,
+) -> anyhow::Result<(
+    BpfRuntimeGuards,
+    Vec<PerfEventArrayBuffer<MapData>>,
+    Option<SequencerRing>,
+)> {
+    if !negotiated.enabled(features::PROCESS) {
+        anyhow::bail!("BPF object does not provide process lifecycle tracepoints");
+    }
+
     let telemetry = TelemetryConfigPod(telemetry_cfg);
     let mut loader = EbpfLoader::new();
     loader.set_global("TELEMETRY_CONFIG", &telemetry, true);
+    // The per-node rings are declared with one slot; grow the ones used
+    let nodes = match transport {
+        EventTransport::Sequencer if sequencer.numa => sequencer_nodes(),
+        _ => Vec::new(),
+    };
+    if nodes.len() > 1 {
+        for ring in &numa::RING_MAPS[1..numa::partitions_for(&nodes) as usize] {
+            loader.set_max_entries(ring, SEQUENCER_RING_SIZE);
+        }
+    }
+    // While the cursor is checkpointed the shared ring and its counters are
+    // pinned, so the next start reads on where this one stopped
+    let pin_dir = Path::new(&sequencer.pin_path);
+    let pins = match transport {
+        EventTransport::Sequencer if nodes.len() <= 1 && !sequencer.cursor_path.is_empty() => {
+            sequencer_pins(pin_dir)
+        }
+        _ => None,
+    };
+    if pins.is_some() {
+        for name in cursor::PINNED_MAPS {
+            loader.map_pin_path(name, pin_dir.join(name));
+        }
+    }
     let mut bpf = loader.load(bpf_bytes)?;
 
     let logger = match EbpfLogger::init(&mut bpf) {
@@ -251,28 +521,85 @@ fn init_ebpf(
 
     attach_tracepoint_internal(&mut bpf, "handle_exit", "sched", "sched_process_exit")?;
 
-    attach_kprobe_internal(&mut bpf, "trace_tcp_send", "tcp_sendmsg")?;
-    attach_kprobe_internal(&mut bpf, "trace_tcp_recv", "tcp_recvmsg")?;
-    attach_kprobe_internal(&mut bpf, "trace_vfs_read", "vfs_read")?;
-    attach_kprobe_internal(&mut bpf, "trace_vfs_write", "vfs_write")?;
-
-    attach_kprobe_optional(&mut bpf, "trace_udp_send", "udp_sendmsg");
-    attach_kprobe_optional(&mut bpf, "trace_udp_recv", "udp_recvmsg");
-    attach_kprobe_optional(&mut bpf, "trace_unix_stream_send", "unix_stream_sendmsg");
-    attach_kprobe_optional(&mut bpf, "trace_unix_stream_recv", "unix_stream_recvmsg");
-    attach_kprobe_optional(&mut bpf, "trace_unix_dgram_send", "unix_dgram_sendmsg");
-    attach_kprobe_optional(&mut bpf, "trace_unix_dgram_recv", "unix_dgram_recvmsg");
-
-    attach_tracepoint_internal(&mut bpf, "trace_sys_enter", "raw_syscalls", "sys_enter")?;
-
-    attach_tracepoint_optional(&mut bpf, "trace_block_queue", "block", "block_bio_queue");
-    attach_tracepoint_optional(&mut bpf, "trace_block_issue", "block", "block_rq_issue");
-    attach_tracepoint_optional(
-        &mut bpf,
-        "trace_block_complete",
-        "block",
-        "block_rq_complete",
-    );
+    // Features the object does not advertise are skipped, not attempted.
+    let symbols = KernelSymbols::load();
+    if symbols.is_none() {
+        warn!("[cognitod] /proc/kallsyms unreadable; trying kprobe symbols in profile order");
+    }
+    let symbols = symbols.as_ref();
+    let mut kprobes = Vec::new();
+    let mut probe = |bpf: &mut Ebpf, program, required| {
+        attach_kprobe(bpf, symbols, &mut kprobes, program, required)
+    };
+    // Net kprobes keep the socket for the kretprobe that reports the bytes
+    if negotiated.enabled(features::NET_TCP) {
+        probe(&mut bpf, "trace_tcp_send", true)?;
+        probe(&mut bpf, "trace_tcp_send_ret", true)?;
+        probe(&mut bpf, "trace_tcp_recv", true)?;
+        probe(&mut bpf, "trace_tcp_recv_ret", true)?;
+    }
+    // File kprobes likewise keep the file for their kretprobes
+    if negotiated.enabled(features::FILE_IO) {
+        probe(&mut bpf, "trace_vfs_read", true)?;
+        probe(&mut bpf, "trace_vfs_read_ret", true)?;
+        probe(&mut bpf, "trace_vfs_write", true)?;
+        probe(&mut bpf, "trace_vfs_write_ret", true)?;
+    }
+
+    if negotiated.enabled(features::NET_UDP) {
+        probe(&mut bpf, "trace_udp_send", false)?;
+        probe(&mut bpf, "trace_udp_send_ret", false)?;
+        probe(&mut bpf, "trace_udp_recv", false)?;
+        probe(&mut bpf, "trace_udp_recv_ret", false)?;
+    }
+    if negotiated.enabled(features::NET_UNIX) {
+        probe(&mut bpf, "trace_unix_stream_send", false)?;
+        probe(&mut bpf, "trace_unix_stream_send_ret", false)?;
+        probe(&mut bpf, "trace_unix_stream_recv", false)?;
+        probe(&mut bpf, "trace_unix_stream_recv_ret", false)?;
+        probe(&mut bpf, "trace_unix_dgram_send", false)?;
+        probe(&mut bpf, "trace_unix_dgram_send_ret", false)?;
+        probe(&mut bpf, "trace_unix_dgram_recv", false)?;
+        probe(&mut bpf, "trace_unix_dgram_recv_ret", false)?;
+    }
+    if negotiated.enabled(features::LISTEN) {
+        probe(&mut bpf, "trace_listen_start", false)?;
+    }
+    if negotiated.enabled(features::CONNECTIONS) {
+        probe(&mut bpf, "trace_conn_request", false)?;
+        probe(&mut bpf, "trace_syn_cookie_v4", false)?;
+        probe(&mut bpf, "trace_syn_cookie_v6", false)?;
+        attach_tracepoint_optional(&mut bpf, "trace_sock_state", "sock", "inet_sock_set_state");
+    }
+    if negotiated.enabled(features::FD_PRESSURE) {
+        probe(&mut bpf, "trace_fd_alloc", false)?;
+    }
+    if negotiated.enabled(features::SIGNALS) {
+        attach_tracepoint_optional(
+            &mut bpf,
+            "trace_signal_generate",
+            "signal",
+            "signal_generate",
+        );
+    }
+    if negotiated.enabled(features::OOM_KILLS) {
+        attach_tracepoint_optional(&mut bpf, "trace_oom_mark_victim", "oom", "mark_victim");
+    }
+
+    if negotiated.enabled(features::SYSCALLS) {
+        attach_tracepoint_internal(&mut bpf, "trace_sys_enter", "raw_syscalls", "sys_enter")?;
+    }
+
+    if negotiated.enabled(features::BLOCK_IO) {
+        attach_tracepoint_optional(&mut bpf, "trace_block_queue", "block", "block_bio_queue");
+        attach_tracepoint_optional(&mut bpf, "trace_block_issue", "block", "block_rq_issue");
+        attach_tracepoint_optional(
+            &mut bpf,
+            "trace_block_complete",
+            "block",
+            "block_rq_complete",
+        );
+    }
 
     info!("[cognitod] Program attached. Setting up perf buffers...");
 
@@ -285,12 +612,49 @@ fn init_ebpf(
         perf_buffers.push(perf_array.open(cpu, None)?);
     }
 
+    // Programs write to the perf buffers until the sequencer is enabled,
+    // after the ring is zeroed and its consumer runs.
+    let sequencer = match transport {
+        EventTransport::Sequencer => {
+            open_sequencer_ring(&mut bpf, sequencer, nodes, pins.unwrap_or(false))
+        }
+        EventTransport::Perf => None,
+    };
+
+    // Without it exec paths are read from /proc
+    if negotiated.enabled(features::EXEC_PATHS) {
+        match bpf.take_map("EXEC_DETAILS").map(exec_path::ExecPaths::new) {
+            Some(Ok(paths)) => {
+                exec_path::EXEC_PATHS.set(paths).ok();
+            }
+            Some(Err(e)) => warn!("[cognitod] cannot open EXEC_DETAILS: {e}"),
+            None => warn!("[cognitod] BPF object has no EXEC_DETAILS map"),
+        }
+    }
+
+    // Without it net events make no flows
+    if negotiated.enabled(features::SOCKET_PEERS) {
+        match bpf.take_map("SOCKET_PEERS").map(flows::SocketPeers::new) {
+            Some(Ok(peers)) => {
+                flows::SOCKET_PEERS.set(peers).ok();
+            }
+            Some(Err(e)) => warn!("[cognitod] cannot open SOCKET_PEERS: {e}"),
+            None => warn!("[cognitod] BPF object has no SOCKET_PEERS map"),
+        }
+    }
+
+    let control = Arc::new(BpfControl::new(bpf, Some(telemetry_cfg)));
+    if let Some(ring) = &sequencer {
+        control.set_sequencer_partitions(ring.rings.mode());
+    }
     Ok((
         BpfRuntimeGuards {
-            _bpf: bpf,
+            control,
             _logger: logger,
+            kprobes,
         },
         perf_buffers,
+        sequencer,
     ))
 }
 
@@ -312,8 +676,9 @@ fn init_rss_trace(bpf_bytes: &[u8]) -> anyhow::Result<BpfRuntimeGuards> {
     attach_tracepoint_internal(&mut bpf, "trace_rss_stat", "mm", "rss_stat")?;
 
     Ok(BpfRuntimeGuards {
-        _bpf: bpf,
+        control: Arc::new(BpfControl::new(bpf, None)),
         _logger: logger,
+        kprobes: Vec::new(),
     })
 }
 
@@ -423,17 +788,20 @@ fn get_process_cgroup_context(pid: u32) -> Option<String> {
     None
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
+fn main() -> Result<(), Box<dyn Error>> {
     env_logger::init();
     let args = Args::parse();
-    let handler = args.handler.clone();
     let detach = args.detach;
     if detach {
         println!("[cognitod] Detaching eBPF programs...");
         // eBPF programs are not pinned, so dropping the process is enough.
-        // This hook allows uninstall scripts to trigger any additional cleanup
-        // if necessary.
+        // The sequencer ring and counters are, while the cursor is
+        // checkpointed; removing the pins frees them once the daemon is gone.
+        let config = Config::load_from(&args.config);
+        let pins = Path::new(&config.sequencer.pin_path);
+        if let Err(e) = cursor::unpin(pins) {
+            eprintln!("[cognitod] cannot remove pins in {}: {e}", pins.display());
+        }
         return Ok(());
     }
     println!("[cognitod] Starting Cognition Daemon...");
@@ -442,16 +810,60 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     // Load configuration from CLI-specified path
     let config = Config::load_from(&args.config);
-    let offline_guard = Arc::new(OfflineGuard::new(config.runtime.offline));
+    // Worker placement has to be known before the runtime spawns them
+    let runtime = placement::build_runtime(&config.threads)?;
+    runtime.block_on(run(args, config))
+}
+
+/// Discover the node and list its pods. Finishes before the event listeners
+/// start, so early events are attributed.
+async fn init_k8s(
+    metrics: Arc<Metrics>,
+    background: bool,
+) -> Option<Arc<cognitod::k8s::K8sContext>> {
+    let started = std::time::Instant::now();
+    let k8s_context = cognitod::k8s::K8sContext::new();
+    if let Some(ctx) = &k8s_context {
+        ctx.self_configure().await;
+        info!(
+            "[cognitod] K8s context initialized (node: {})",
+            ctx.node_name()
+        );
+        let warm = ctx.warm_up(K8S_WARM_UP_TIMEOUT).await;
+        ctx.clone().start_watcher(warm);
+    } else {
+        info!("[cognitod] K8s context not available (missing env/tokens)");
+    }
+    metrics
+        .startup()
+        .record("kubernetes", started.elapsed(), background);
+    k8s_context
+}
+
+async fn run(args: Args, config: Config) -> Result<(), Box<dyn Error>> {
+    let handler = args.handler.clone();
+    let offline_guard = Arc::new(OfflineGuard::from_config(&config.runtime, &config.egress));
+    let cgroup_scope = CgroupScope::resolve(
+        Path::new(&cgroup_tree::cgroup_root()),
+        &config.scope.cgroups,
+    )
+    .context("invalid [scope] configuration")?
+    .map(Arc::new);
 
     // Initialize metrics and spawn background reporting tasks
     let metrics = Arc::new(Metrics::new());
     spawn_metrics_tasks(Arc::clone(&metrics));
 
+    // Kubernetes metadata does not depend on the kernel side; look it up
+    // while BTF is parsed and the programs load.
+    let k8s_init = (config.startup.parallel && !args.probe_only && !args.dry_run)
+        .then(|| tokio::spawn(init_k8s(Arc::clone(&metrics), true)));
+
     // --- Prepare kernel instrumentation with graceful fallback ---
     let mut perf_buffers: Vec<PerfEventArrayBuffer<MapData>> = Vec::new();
+    let mut sequencer_ring: Option<SequencerRing> = None;
     let mut transport: &'static str = "userspace";
-    let mut _bpf_runtime: Option<BpfRuntimeGuards> = None;
+    let mut bpf_runtime: Option<BpfRuntimeGuards> = None;
     let mut probe_state = ProbeState::disabled();
 
     let btf_path = std::env::var("LINNIX_KERNEL_BTF")
@@ -461,27 +873,99 @@ async fn main() -> Result<(), Box<dyn Error>> {
         std::path::Path::new("/sys/kernel/tracing/events/mm/rss_stat").is_dir();
     let mut core_signal_ok = false;
     let mut core_mm_ok = false;
+    let mut object_arch = None;
+    let mut offset_problems = None;
 
     if btf_available {
-        match derive_telemetry_config() {
-            Ok(result) => {
+        let btf_cache = Some(config.startup.btf_cache.trim())
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from);
+        let started = std::time::Instant::now();
+        let derive = tokio::task::spawn_blocking(move || {
+            derive_telemetry_config_cached(btf_cache.as_deref())
+        });
+        // The object is read while BTF is parsed; it is only needed, and its
+        // absence only fatal, once the offsets are known.
+        let object = metrics.startup().time("bpf_object", read_bpf_bytes);
+        let derived = derive
+            .await
+            .map_err(|e| anyhow::anyhow!("BTF task failed: {e}"))
+            .and_then(|derived| derived);
+        metrics.startup().record("btf", started.elapsed(), false);
+        match derived {
+            Ok((result, btf_cache)) => {
+                metrics.startup().set_btf_cache(btf_cache.as_str());
                 core_signal_ok = result.signal_supported;
                 core_mm_ok = result.mm_supported;
-                let telemetry_cfg = result.config;
-                let (bpf_bytes, chosen_path) = read_bpf_bytes()?;
+                let problems = bpf_config::offset_problems(&result.config);
+                for problem in &problems {
+                    warn!("[cognitod] suspicious BTF offset: {problem}");
+                }
+                offset_problems = Some(problems);
+                let mut telemetry_cfg = result.config;
+                (
+                    telemetry_cfg.page_fault_interval_ns,
+                    telemetry_cfg.page_fault_burst,
+                ) = config.probes.page_faults.kernel_params();
+                (
+                    telemetry_cfg.fd_pressure_pct,
+                    telemetry_cfg.fd_pressure_interval_ns,
+                ) = config.probes.fd_pressure.kernel_params();
+                telemetry_cfg.signal_mask = config.probes.signals.kernel_mask();
+                (
+                    telemetry_cfg.syscall_batch,
+                    telemetry_cfg.syscall_flush_interval_ns,
+                ) = config.probes.syscalls.kernel_params();
+                telemetry_cfg.socket_id_key = bpf_config::socket_id_key();
+                [
+                    telemetry_cfg.oom_anon_rss_offset,
+                    telemetry_cfg.oom_file_rss_offset,
+                    telemetry_cfg.oom_shmem_rss_offset,
+                ] = cognitod::oom::victim_rss_offsets();
+                let (bpf_bytes, chosen_path) = object?;
                 println!("[cognitod] Using BPF object: {chosen_path}");
-                match init_ebpf(&bpf_bytes, telemetry_cfg) {
-                    Ok((guards, buffers)) => {
-                        transport = "perf";
+                let mut negotiated = FeatureNegotiation::negotiate(read_manifest(&bpf_bytes));
+                negotiated.log();
+                object_arch = read_target_arch(&bpf_bytes);
+                if let Some(arch) = object_arch
+                    .as_deref()
+                    .filter(|arch| *arch != std::env::consts::ARCH)
+                {
+                    warn!(
+                        "[bpf] object kprobes were built for {arch}, host is {}; disabled: {} (rebuild with `cargo xtask build-ebpf --arch {}`)",
+                        std::env::consts::ARCH,
+                        runtime::features::names(negotiated.enabled & PT_REGS_FEATURES).join(","),
+                        std::env::consts::ARCH
+                    );
+                    negotiated.disable(PT_REGS_FEATURES);
+                }
+                match metrics.startup().time("bpf_load", || {
+                    init_ebpf(
+                        &bpf_bytes,
+                        telemetry_cfg,
+                        &negotiated,
+                        config.runtime.transport,
+                        &config.sequencer,
+                    )
+                }) {
+                    Ok((guards, buffers, sequencer)) => {
+                        transport = if sequencer.is_some() {
+                            "sequencer"
+                        } else {
+                            "perf"
+                        };
                         perf_buffers = buffers;
-                        _bpf_runtime = Some(guards);
+                        sequencer_ring = sequencer;
                         probe_state = ProbeState {
                             rss_probe: match result.mode {
                                 CoreRssMode::MmStruct => RssProbeMode::CoreMm,
                                 CoreRssMode::SignalStruct => RssProbeMode::CoreSignal,
                             },
                             btf_available,
+                            features: Some(negotiated),
+                            kprobes: guards.kprobes.clone(),
                         };
+                        bpf_runtime = Some(guards);
                     }
                     Err(err) => {
                         warn!(
@@ -505,7 +989,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 match init_rss_trace(&trace_bytes) {
                     Ok(guards) => {
                         transport = "tracepoint";
-                        _bpf_runtime = Some(guards);
+                        bpf_runtime = Some(guards);
                         probe_state.rss_probe = RssProbeMode::Tracepoint;
                         info!("[cognitod] Tracepoint fallback mm:rss_stat attached");
                     }
@@ -547,13 +1031,44 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     metrics.set_rss_probe_mode(probe_state.rss_probe.metric_value());
     metrics.set_kernel_btf_available(btf_available);
+    metrics.loss().set_transport(transport);
+
+    if let Some(scope) = &cgroup_scope {
+        match bpf_runtime
+            .as_ref()
+            .filter(|_| matches!(transport, "perf" | "sequencer"))
+        {
+            // Fail closed: a tenant-scoped agent must not stream the whole node.
+            Some(guards) => guards
+                .control
+                .set_cgroup_scope(scope.level(), scope.cgroup_ids())
+                .await
+                .context("failed to install cgroup scope filter")?,
+            None => {
+                warn!("[scope] no kernel event stream; scope applies to enforcement and API only")
+            }
+        }
+        info!(
+            "[scope] monitoring restricted to {}",
+            scope.subtrees().join(", ")
+        );
+    }
 
     if args.probe_only {
+        let arch = ArchReport::collect(object_arch, offset_problems);
+        let ok = arch.ok();
         let payload = json!({
             "rss_probe": probe_state.rss_probe.as_str(),
             "btf": probe_state.btf_available,
+            "features": probe_state.features.map(|f| f.status()),
+            "kprobes": probe_state.kprobes,
+            "arch": arch,
+            "ok": ok,
         });
         println!("{payload}");
+        if !ok {
+            std::process::exit(1);
+        }
         return Ok(());
     }
 
@@ -568,22 +1083,24 @@ async fn main() -> Result<(), Box<dyn Error>> {
         );
     }
 
-    let k8s_context = cognitod::k8s::K8sContext::new();
-    if let Some(ctx) = &k8s_context {
-        info!(
-            "[cognitod] K8s context initialized (node: {})",
-            ctx.node_name
-        );
-        ctx.clone().start_watcher();
-    } else {
-        info!("[cognitod] K8s context not available (missing env/tokens)");
-    }
+    let k8s_context = match k8s_init {
+        Some(task) => task.await.unwrap_or_else(|e| {
+            warn!("[cognitod] K8s initialization failed: {e}");
+            None
+        }),
+        None => init_k8s(Arc::clone(&metrics), false).await,
+    };
 
     let context = Arc::new(context::ContextStore::new(
-        Duration::from_secs(300),
-        1000,
+        Duration::from_secs(config.context.max_age_secs),
+        config.context.history_len,
         k8s_context.clone(),
     ));
+    let mb = |mb: u64| (mb > 0).then(|| (mb as usize).saturating_mul(1 << 20));
+    context.set_memory_budget(
+        mb(config.context.history_budget_mb),
+        mb(config.context.live_budget_mb),
+    );
     let insight_store = {
         let path = config.logging.insights_file.trim();
         let path = if path.is_empty() {
@@ -610,8 +1127,10 @@ async fn main() -> Result<(), Box<dyn Error>> {
             }
         });
 
-    let mut db_path_valid = true;
-    if let Some(parent) = incident_db_path.parent() {
+    // Without the `incidents` feature the store cannot be opened; skip the
+    // directory checks instead of warning about a path that is never used.
+    let mut db_path_valid = cfg!(feature = "incidents");
+    if db_path_valid && let Some(parent) = incident_db_path.parent() {
         if let Err(e) = std::fs::create_dir_all(parent) {
             warn!(
                 "[cognitod] Failed to create incident DB directory {}: {}",
@@ -636,7 +1155,12 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     let incident_store: Option<Arc<cognitod::IncidentStore>> = if db_path_valid {
         let db_path_str = incident_db_path.to_string_lossy().to_string();
-        match cognitod::IncidentStore::new(&db_path_str).await {
+        let started = std::time::Instant::now();
+        let store = cognitod::IncidentStore::with_config(&db_path_str, &config.incidents).await;
+        metrics
+            .startup()
+            .record("incident_store", started.elapsed(), false);
+        match store {
             Ok(store) => {
                 info!(
                     "[cognitod] Incident store initialized at {}",
@@ -653,32 +1177,99 @@ async fn main() -> Result<(), Box<dyn Error>> {
         None
     };
 
+    let templates = Arc::new(
+        cognitod::templates::Templates::from_config(&config.templates)
+            .context("invalid [templates]")?,
+    );
+
+    let incident_sinks = incident_store.as_ref().and_then(|store| {
+        let host = hostname::get()
+            .ok()
+            .and_then(|h| h.into_string().ok())
+            .unwrap_or_else(|| "unknown".to_string());
+        cognitod::incidents::IncidentSinks::spawn(
+            &config.incidents.sinks,
+            Arc::clone(store),
+            host,
+            Arc::clone(&templates),
+            &offline_guard,
+        )
+    });
+    #[cfg(not(feature = "incidents"))]
+    if !config.incidents.sinks.is_empty() {
+        warn!("[cognitod] incident sinks configured but this build has no incident store");
+    }
+
+    #[cfg(feature = "reasoner")]
     let incident_analyzer = if config.reasoner.enabled && !config.reasoner.endpoint.is_empty() {
-        match cognitod::IncidentAnalyzer::new(
+        info!("[incident_analyzer] LLM analysis enabled for incidents");
+        Some(Arc::new(cognitod::IncidentAnalyzer::new(
             config.reasoner.endpoint.clone(),
             Duration::from_millis(config.reasoner.timeout_ms),
-        ) {
-            Ok(analyzer) => {
-                info!("[incident_analyzer] LLM analysis enabled for incidents");
-                Some(Arc::new(analyzer))
-            }
-            Err(e) => {
-                warn!("[incident_analyzer] Failed to initialize: {}", e);
-                None
-            }
-        }
+            &offline_guard,
+        )))
     } else {
         None
     };
+    #[cfg(not(feature = "reasoner"))]
+    let incident_analyzer: Option<Arc<cognitod::IncidentAnalyzer>> = None;
+    let analysis_pool = incident_analyzer
+        .zip(incident_store.clone())
+        .map(|(analyzer, store)| {
+            cognitod::incidents::AnalysisPool::spawn(analyzer, store, &config.reasoner)
+        });
 
     // Handlers specified on the command line
     let mut handler_list = HandlerList::new();
-    let enforcement_queue = Some(Arc::new(enforcement::EnforcementQueue::new(300)));
+    let maintenance = match MaintenanceManager::from_config(&config.maintenance) {
+        Ok(mgr) => {
+            if !config.maintenance.windows.is_empty() {
+                info!(
+                    "[maintenance] {} scheduled window(s) configured",
+                    config.maintenance.windows.len()
+                );
+            }
+            Arc::new(mgr.with_k8s(k8s_context.clone()))
+        }
+        Err(e) => {
+            warn!("[maintenance] invalid configuration, scheduled windows disabled: {e:#}");
+            Arc::new(MaintenanceManager::default().with_k8s(k8s_context.clone()))
+        }
+    };
+    // A single instance per host executes enforcement actions
+    let coordination = Arc::new(Coordinator::from_config(&config.coordination));
+    if let Err(e) = coordination.may_enforce() {
+        info!("[coordination] {e}; enforcement is off until it hands over");
+    }
+    if let Err(e) = Arc::clone(&coordination).spawn(&config.coordination) {
+        warn!("[coordination] cannot listen for handover requests: {e}");
+    }
+    let enforcement_queue = Some(Arc::new({
+        let queue = enforcement::EnforcementQueue::new(config.approvals.ttl_secs)
+            .with_approvals(config.approvals.clone())
+            .with_maintenance(Arc::clone(&maintenance));
+        match &cgroup_scope {
+            Some(scope) => queue.with_scope(Arc::clone(scope)),
+            None => queue,
+        }
+    }));
+    // The jsonl and webhook exporters save their delivery marks with the
+    // sequencer cursor
+    let delivery_marks = sequencer_ring
+        .as_ref()
+        .and_then(|ring| ring.restored.as_ref())
+        .map(|(_, restored)| Arc::new(DeliveryMarks::new(restored.delivered.clone())));
+    let with_marks = |hdl: JsonlHandler| match &delivery_marks {
+        Some(marks) => hdl.with_delivery_marks(Arc::clone(marks)),
+        None => hdl,
+    };
     let mut alert_tx = None;
+    let mut rule_engine: Option<Arc<RuleEngine>> = None;
     for h in handler {
         if let Some(path) = h.strip_prefix("jsonl:") {
-            if let Ok(hdl) = JsonlHandler::new(path).await {
-                handler_list.register(hdl);
+            match JsonlHandler::new(path).await {
+                Ok(hdl) => handler_list.register(with_marks(hdl)),
+                Err(e) => warn!("[cognitod] failed to open jsonl handler {}: {e}", path),
             }
         } else if let Some(path) = h.strip_prefix("rules:") {
             match RuleEngine::from_path(
@@ -688,15 +1279,24 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 Arc::clone(&metrics),
             ) {
                 Ok(engine) => {
+                    let engine = Arc::new(
+                        engine
+                            .with_maintenance(Arc::clone(&maintenance))
+                            .with_context_store(Arc::clone(&context))
+                            .with_alert_context(config.alert_context.clone()),
+                    );
                     let rule_count = engine.rule_count();
-                    let broadcaster = engine.broadcaster();
+                    let broadcaster = engine
+                        .broadcaster()
+                        .with_cooldown(Duration::from_secs(config.alerts.cooldown_secs));
                     info!(
                         "[cognitod] Rules handler loaded from {} ({} rules)",
                         path, rule_count
                     );
                     metrics.add_active_rules(rule_count);
                     alert_tx = Some(broadcaster);
-                    handler_list.register(engine);
+                    handler_list.register_shared(engine.clone());
+                    rule_engine = Some(engine);
                 }
                 Err(e) => warn!("[cognitod] failed to load rules from {}: {e}", path),
             }
@@ -723,51 +1323,150 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 config.docker_enforcement.clone()
             };
 
+            #[cfg(feature = "docker")]
             if let Some(docker_cfg) = docker_config {
-                let enforcer = handler::docker::DockerEnforcer::new(docker_cfg);
+                let enforcer = handler::docker::DockerEnforcer::new(docker_cfg)
+                    .with_coordination(Arc::clone(&coordination));
                 handler_list.register(enforcer);
                 info!("[cognitod] Docker enforcement handler registered");
             } else {
                 warn!("[cognitod] Docker handler requested but not configured");
             }
+            #[cfg(not(feature = "docker"))]
+            {
+                let _ = docker_config;
+                warn!("[cognitod] Docker handler requested but this build has no docker support");
+            }
+        }
+    }
+
+    for recording in &config.recordings {
+        let filter = match recording.filter.compile(k8s_context.clone()) {
+            Ok(filter) => filter,
+            Err(e) => {
+                warn!("[cognitod] recording {} disabled: {e:#}", recording.path);
+                continue;
+            }
+        };
+        match JsonlHandler::new(&recording.path).await {
+            Ok(hdl) => {
+                handler_list.register(with_marks(hdl.with_filter(filter)));
+                info!("[cognitod] recording events to {}", recording.path);
+            }
+            Err(e) => warn!(
+                "[cognitod] failed to open recording {}: {e}",
+                recording.path
+            ),
+        }
+    }
+    for webhook in &config.event_webhooks {
+        match webhook.filter.compile(k8s_context.clone()) {
+            Ok(filter) => handler_list.register(EventWebhookHandler::spawn(
+                webhook,
+                filter,
+                Arc::clone(&offline_guard),
+                delivery_marks.clone(),
+            )),
+            Err(e) => warn!("[cognitod] event webhook {} disabled: {e:#}", webhook.url),
         }
     }
 
     // Load rules engine from config if not specified via CLI
+    let mut rules_version = None;
     if alert_tx.is_none() {
         let rules_path = &config.rules.path;
-        match RuleEngine::from_path(
-            rules_path,
-            config.logging.alerts_file.clone(),
-            config.logging.journald,
-            Arc::clone(&metrics),
-        ) {
+        // In a cluster the rules ConfigMap, when configured, wins over the file
+        let configmap_rules = match k8s_context.as_ref().filter(|ctx| ctx.in_cluster) {
+            Some(ctx) => cognitod::daemonset::fetch_rules(ctx, &config.kubernetes, &offline_guard)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("[cognitod] cannot read rules ConfigMap, using {rules_path}: {e:#}");
+                    None
+                }),
+            None => None,
+        };
+        let (source, loaded) = match &configmap_rules {
+            Some(document) => (
+                format!(
+                    "ConfigMap {}",
+                    config
+                        .kubernetes
+                        .rules_configmap
+                        .as_deref()
+                        .unwrap_or_default()
+                ),
+                RuleEngine::from_text(
+                    &document.text,
+                    cognitod::daemonset::rules_hint(&config.kubernetes),
+                    config.logging.alerts_file.clone(),
+                    config.logging.journald,
+                    Arc::clone(&metrics),
+                ),
+            ),
+            None => (
+                format!("config {rules_path}"),
+                RuleEngine::from_path(
+                    rules_path,
+                    config.logging.alerts_file.clone(),
+                    config.logging.journald,
+                    Arc::clone(&metrics),
+                ),
+            ),
+        };
+        rules_version = configmap_rules.map(|document| document.version);
+        match loaded {
             Ok(engine) => {
+                let engine = Arc::new(
+                    engine
+                        .with_maintenance(Arc::clone(&maintenance))
+                        .with_context_store(Arc::clone(&context))
+                        .with_alert_context(config.alert_context.clone()),
+                );
                 let rule_count = engine.rule_count();
-                let broadcaster = engine.broadcaster();
+                let broadcaster = engine
+                    .broadcaster()
+                    .with_cooldown(Duration::from_secs(config.alerts.cooldown_secs));
                 info!(
-                    "[cognitod] Rules handler loaded from config {} ({} rules)",
-                    rules_path, rule_count
+                    "[cognitod] Rules handler loaded from {} ({} rules)",
+                    source, rule_count
                 );
                 metrics.add_active_rules(rule_count);
                 alert_tx = Some(broadcaster);
-                handler_list.register(engine);
+                handler_list.register_shared(engine.clone());
+                rule_engine = Some(engine);
             }
-            Err(e) => warn!(
-                "[cognitod] rules engine unavailable; failed to load {}: {e}",
-                rules_path
-            ),
+            Err(e) => warn!("[cognitod] rules engine unavailable; failed to load {source}: {e}"),
         }
     }
 
+    if let Some(ctx) = k8s_context.as_ref().filter(|ctx| ctx.in_cluster) {
+        cognitod::daemonset::spawn(
+            Arc::clone(ctx),
+            &config.kubernetes,
+            rule_engine.clone(),
+            rules_version,
+            Arc::clone(&offline_guard),
+        );
+    }
+
     // Load docker enforcement from config if present
+    #[cfg(feature = "docker")]
     if let Some(docker_cfg) = config.docker_enforcement.clone() {
         if docker_cfg.enabled {
-            let enforcer = handler::docker::DockerEnforcer::new(docker_cfg);
+            let enforcer = handler::docker::DockerEnforcer::new(docker_cfg)
+                .with_coordination(Arc::clone(&coordination));
             handler_list.register(enforcer);
             info!("[cognitod] Docker enforcement handler loaded from config");
         }
     }
+    #[cfg(not(feature = "docker"))]
+    if config
+        .docker_enforcement
+        .as_ref()
+        .is_some_and(|c| c.enabled)
+    {
+        warn!("[cognitod] docker enforcement configured but this build has no docker support");
+    }
 
     if let Some(path) = config.logging.incident_context_file.clone() {
         if let Some(sender) = alert_tx.clone() {
@@ -839,7 +1538,61 @@ async fn main() -> Result<(), Box<dyn Error>> {
         }
     }
 
+    #[cfg(not(feature = "notifications"))]
+    if config.notifications.is_some() {
+        warn!("[cognitod] notifications configured but this build has no notifiers");
+    }
+
+    // Alerts are queued on disk per notifier so outages and crashes cannot
+    // drop them.
+    #[cfg(feature = "notifications")]
+    let notification_wal = config
+        .notifications
+        .as_ref()
+        .filter(|n| {
+            n.wal.enabled && (n.slack.is_some() || n.discord.is_some() || n.apprise.is_some())
+        })
+        .and_then(|n| {
+            match cognitod::notifications::NotificationWal::open(
+                &n.wal.path,
+                Duration::from_secs(n.wal.dedup_window_secs),
+            ) {
+                Ok(wal) => {
+                    let retry = cognitod::notifications::RetryPolicy {
+                        initial: Duration::from_secs(n.wal.retry_secs.max(1)),
+                        max_delay: Duration::from_secs(n.wal.max_retry_secs),
+                        max_attempts: n.wal.max_attempts,
+                    };
+                    let wal = Arc::new(wal.with_retry_policy(retry));
+                    let mut sinks = Vec::new();
+                    if n.slack.is_some() {
+                        sinks.push(cognitod::notifications::SLACK_SINK);
+                    }
+                    if n.discord.is_some() {
+                        sinks.push(cognitod::notifications::DISCORD_SINK);
+                    }
+                    if n.apprise.is_some() {
+                        sinks.push(cognitod::notifications::APPRISE_SINK);
+                    }
+                    cognitod::notifications::queue::spawn_stats(
+                        Arc::clone(&wal),
+                        sinks,
+                        Arc::clone(&metrics),
+                    );
+                    Some(wal)
+                }
+                Err(err) => {
+                    warn!(
+                        "[cognitod] notification WAL unavailable, delivery is best-effort: {:#}",
+                        err
+                    );
+                    None
+                }
+            }
+        });
+
     // Spawn Apprise notifier if configured
+    #[cfg(feature = "notifications")]
     if let Some(ref notif_config) = config.notifications
         && let Some(ref apprise_config) = notif_config.apprise
     {
@@ -848,9 +1601,17 @@ async fn main() -> Result<(), Box<dyn Error>> {
             let url_count = apprise_config.urls.len();
 
             let apprise_config_owned = apprise_config.clone();
+            let wal = notification_wal.clone();
+            let templates = Arc::clone(&templates);
+            let guard = Arc::clone(&offline_guard);
             tokio::spawn(async move {
-                let notifier =
-                    cognitod::notifications::AppriseNotifier::new(apprise_config_owned, apprise_rx);
+                let mut notifier =
+                    cognitod::notifications::AppriseNotifier::new(apprise_config_owned, apprise_rx)
+                        .with_templates(templates)
+                        .with_egress(guard);
+                if let Some(wal) = wal {
+                    notifier = notifier.with_wal(wal);
+                }
                 notifier.run().await;
             });
 
@@ -865,6 +1626,183 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     // KB Index removed (YAGNI cleanup)
 
+    // Energy is split across the processes we see, which in a scoped agent
+    // would charge the whole package to one tenant.
+    let power_monitor = if config.power.enabled && cgroup_scope.is_none() {
+        cognitod::collectors::power::spawn(
+            Path::new(cognitod::collectors::power::POWERCAP_ROOT),
+            Arc::clone(&context),
+            Duration::from_secs(config.power.interval_secs.max(1)),
+        )
+    } else {
+        None
+    };
+
+    let memory_events = config.memory_events.enabled.then(|| {
+        cognitod::collectors::memory_events::spawn(
+            Arc::clone(&context),
+            alert_tx.clone(),
+            Duration::from_secs(config.memory_events.interval_secs.max(1)),
+        )
+    });
+
+    let ephemeral_storage =
+        (config.ephemeral_storage.enabled && k8s_context.is_some()).then(|| {
+            let watcher = cognitod::collectors::ephemeral_storage::spawn(
+                &config.ephemeral_storage,
+                Arc::clone(&context),
+                alert_tx.clone(),
+            );
+            handler_list.register(watcher.write_handler());
+            watcher
+        });
+
+    let detectors =
+        cognitod::detectors::DetectorList::from_config(&config.detectors, alert_tx.clone());
+    let detectors = (!detectors.is_empty()).then(|| {
+        let detectors = Arc::new(detectors);
+        handler_list.register_shared(detectors.clone());
+        detectors
+    });
+
+    if let Some(engine) = &rule_engine {
+        engine.spawn_absence_timer();
+    }
+
+    let filesystems = config.filesystems.enabled.then(|| {
+        cognitod::collectors::filesystems::spawn(&config.filesystems, rule_engine.clone())
+    });
+
+    let smart = config
+        .smart
+        .enabled
+        .then(|| cognitod::collectors::smart::spawn(&config.smart, alert_tx.clone()));
+
+    let computed_fields = config
+        .computed_fields
+        .iter()
+        .filter_map(
+            |field| match ComputedField::compile(&field.name, &field.expr) {
+                Ok(field) => Some(field),
+                Err(e) => {
+                    warn!("[cognitod] computed field {} disabled: {e:#}", field.name);
+                    None
+                }
+            },
+        )
+        .collect();
+
+    let restart_loops = config.restart_loops.enabled.then(|| {
+        cognitod::restart_loops::spawn(
+            Arc::clone(&context),
+            alert_tx.clone(),
+            &config.restart_loops,
+        )
+    });
+
+    let cloudflare = (!config.cloudflare.zones.is_empty()).then(|| {
+        info!(
+            "[cognitod] Cloudflare mitigation enabled for {} zone(s){}",
+            config.cloudflare.zones.len(),
+            if config.cloudflare.dry_run {
+                " (dry run)"
+            } else {
+                ""
+            }
+        );
+        handler::cloudflare::spawn(
+            &config.cloudflare,
+            alert_tx.clone(),
+            enforcement_queue.clone(),
+        )
+    });
+
+    if config.ddos.enabled {
+        if config.ddos.block == Some(enforcement::BlockBackend::Cloudflare) && cloudflare.is_none()
+        {
+            warn!("[ddos] block = \"cloudflare\" without [[cloudflare.zones]]; blocks will fail");
+        }
+        handler::ddos::spawn(
+            Arc::clone(&context),
+            alert_tx.clone(),
+            enforcement_queue.clone(),
+            &config.ddos,
+        );
+    }
+
+    let topology = config
+        .topology
+        .enabled
+        .then(|| cognitod::topology::spawn(Arc::clone(&context), &config.topology));
+
+    let enrichment = config.enrichment.enabled.then(|| {
+        let enricher = cognitod::enrichment::PeerEnricher::spawn(
+            &config.enrichment,
+            offline_guard.is_offline(),
+        );
+        handler_list.register(enricher.handler());
+        enricher
+    });
+
+    let leaderboard = config.leaderboard.enabled.then(|| {
+        let board = cognitod::leaderboard::Leaderboard::spawn(
+            &config.leaderboard,
+            incident_store.clone(),
+            k8s_context.clone(),
+        );
+        handler_list.register(board.net_handler());
+        board
+    });
+
+    let ancestry_profile = config
+        .ancestry_profile
+        .enabled
+        .then(|| cognitod::ancestry_profile::AncestryProfile::spawn(&config.ancestry_profile));
+
+    let scaling = config.scaling.enabled.then(|| {
+        let signals =
+            cognitod::scaling::ScalingSignals::spawn(&config.scaling, Arc::clone(&context));
+        handler_list.register(signals.handler());
+        signals
+    });
+
+    if config.crashes.enabled {
+        cognitod::crashes::spawn(
+            Arc::clone(&context),
+            alert_tx.clone(),
+            leaderboard.clone(),
+            &config.crashes,
+        );
+    }
+
+    if config.oom_kills.enabled {
+        cognitod::oom::spawn(
+            Arc::clone(&context),
+            alert_tx.clone(),
+            incident_store.clone(),
+            incident_sinks.clone(),
+            analysis_pool.clone(),
+        );
+    }
+
+    let health_scorer = config
+        .health_score
+        .enabled
+        .then(|| cognitod::health_score::spawn(Arc::clone(&metrics), &config.health_score));
+
+    // Restore state exported by a previous install before anything reads it
+    if let Some(path) = &config.state.import_path {
+        let sources = StateSources {
+            incidents: incident_store.as_deref(),
+            maintenance: Some(&*maintenance),
+            health: health_scorer.as_deref(),
+        };
+        if let Err(e) = state_bundle::import_on_startup(Path::new(path), sources).await {
+            warn!("[state] import of {path} failed: {e:#}");
+        }
+    }
+    let state_export_path = config.state.export_path.clone();
+
     // Start PSI monitor (after incident store is ready)
     if let Some(ctx) = &k8s_context {
         let psi_monitor = cognitod::collectors::psi::PsiMonitor::new(
@@ -879,19 +1817,25 @@ async fn main() -> Result<(), Box<dyn Error>> {
     }
 
     // Initialize Slack Notifier
+    #[cfg(feature = "notifications")]
     let _slack_notifier = if let Some(ref notif_cfg) = config.notifications {
         if let Some(ref slack_cfg) = notif_cfg.slack {
             if let Some(ref tx) = alert_tx {
                 // SlackNotifier workaround: create two instances because run() consumes self.
                 // One for the alert loop, one for ILM insights (with dummy channel).
                 let (_dummy_tx, dummy_rx) = tokio::sync::broadcast::channel(1);
-                let notifier_ilm = Arc::new(cognitod::notifications::SlackNotifier::new(
-                    slack_cfg.clone(),
-                    dummy_rx,
-                ));
+                let notifier_ilm = Arc::new(
+                    cognitod::notifications::SlackNotifier::new(slack_cfg.clone(), dummy_rx)
+                        .with_egress(&offline_guard),
+                );
 
-                let notifier_alerts =
-                    cognitod::notifications::SlackNotifier::new(slack_cfg.clone(), tx.subscribe());
+                let mut notifier_alerts =
+                    cognitod::notifications::SlackNotifier::new(slack_cfg.clone(), tx.subscribe())
+                        .with_templates(Arc::clone(&templates))
+                        .with_egress(&offline_guard);
+                if let Some(wal) = notification_wal.clone() {
+                    notifier_alerts = notifier_alerts.with_wal(wal);
+                }
                 tokio::spawn(async move {
                     notifier_alerts.run().await;
                 });
@@ -901,10 +1845,10 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 // No alert_tx (e.g. rules disabled), but we might still want ILM insights to go to Slack.
                 // We still need a dummy rx.
                 let (_dummy_tx, dummy_rx) = tokio::sync::broadcast::channel(1);
-                let notifier = Arc::new(cognitod::notifications::SlackNotifier::new(
-                    slack_cfg.clone(),
-                    dummy_rx,
-                ));
+                let notifier = Arc::new(
+                    cognitod::notifications::SlackNotifier::new(slack_cfg.clone(), dummy_rx)
+                        .with_egress(&offline_guard),
+                );
                 Some(notifier)
             }
         } else {
@@ -914,16 +1858,60 @@ async fn main() -> Result<(), Box<dyn Error>> {
         None
     };
 
+    #[cfg(feature = "notifications")]
+    if let Some(discord_cfg) = config
+        .notifications
+        .as_ref()
+        .and_then(|n| n.discord.clone())
+    {
+        match &alert_tx {
+            Some(tx) => {
+                let mut notifier = cognitod::notifications::DiscordNotifier::new(
+                    discord_cfg.clone(),
+                    tx.subscribe(),
+                )
+                .with_templates(Arc::clone(&templates))
+                .with_egress(&offline_guard);
+                if let Some(wal) = notification_wal.clone() {
+                    notifier = notifier.with_wal(wal);
+                }
+                tokio::spawn(notifier.run());
+            }
+            None => warn!("[cognitod] Discord configured but no alert handler is active"),
+        }
+        match (discord_cfg.bot, &enforcement_queue) {
+            (Some(bot), Some(queue)) => {
+                cognitod::notifications::discord::spawn_approvals(
+                    bot,
+                    Arc::clone(queue),
+                    Arc::clone(&templates),
+                    &offline_guard,
+                );
+            }
+            (Some(_), None) => {
+                warn!("[cognitod] Discord bot configured but enforcement is disabled")
+            }
+            (None, _) => {}
+        }
+    }
+
     // LocalIlmHandlerRag removed (YAGNI cleanup)
 
+    if config.ordering.enabled {
+        handler_list.enable_ordering(&config.ordering, Arc::clone(&metrics));
+        info!(
+            "[cognitod] ordered handlers get events after {}ms",
+            config.ordering.max_delay_ms
+        );
+    }
     let handlers = Arc::new(handler_list);
     
     // Initialize Warmth Keeper (Pro feature)
     if config.warmth.enabled {
         let keeper = Arc::new(handler::warmth::WarmthKeeper::new(
-            config.warmth.idle_threshold_secs,
-            config.warmth.ping_interval_secs,
+            &config.warmth,
             config.containers.clone(),
+            alert_tx.clone(),
         ));
         info!("[cognitod] Warmth Keeper enabled (idle={} ping={} containers={})",
             config.warmth.idle_threshold_secs,
@@ -945,9 +1933,76 @@ async fn main() -> Result<(), Box<dyn Error>> {
             Arc::clone(&handlers),
             Arc::clone(&offline_guard),
             config.runtime.events_rate_cap,
+            placement::consumer_handle(&config.threads.consumers),
         );
     }
 
+    // Once the sequencer is enabled its consumer carries the events; the perf
+    // listener drains what was written before, and takes over again if the
+    // sequencer is switched off through /bpf/sequencer.
+    let sequencer_diag = Arc::new(runtime::sequencer_diag::SequencerDiagnostics::new());
+    let mut sequencer_checkpoint = None;
+    if let (Some(mut ring), Some(guards)) = (sequencer_ring.take(), &bpf_runtime) {
+        // The listener resumes at the restored cursor; its position is saved
+        // from then on
+        let resume = ring
+            .restored
+            .as_ref()
+            .map(|(_, reconciliation)| reconciliation.clone());
+        let checkpoint = ring.restored.take().map(|(store, reconciliation)| {
+            let checkpointer = Checkpointer::new(store, reconciliation, Arc::clone(&metrics));
+            let checkpointer = Arc::new(match &delivery_marks {
+                Some(marks) => checkpointer.with_marks(Arc::clone(marks)),
+                None => checkpointer,
+            });
+            let thread =
+                checkpointer.spawn(Duration::from_secs(config.sequencer.checkpoint_secs.max(1)));
+            (checkpointer, thread)
+        });
+        let started = match guards.control.enable_sequencer().await {
+            Ok(()) => start_sequencer_listener(
+                ring.rings,
+                Arc::clone(&context),
+                Arc::clone(&metrics),
+                Arc::clone(&handlers),
+                Arc::clone(&offline_guard),
+                config.runtime.events_rate_cap,
+                config.runtime.sequencer_batch,
+                config.threads.consumers.is_set().then(|| {
+                    placement::ThreadPlacement::new("consumers", &config.threads.consumers)
+                }),
+                Arc::clone(&sequencer_diag),
+                checkpoint
+                    .as_ref()
+                    .map(|(checkpointer, _)| Arc::clone(checkpointer)),
+                resume,
+                BacklogConfig {
+                    catchup_per_poll: config.sequencer.catchup_per_poll,
+                    summarize_after: config
+                        .sequencer
+                        .summarize_backlog_after_secs
+                        .map(Duration::from_secs),
+                },
+            )
+            .map_err(anyhow::Error::from),
+            Err(e) => Err(e),
+        };
+        match started {
+            Ok(()) => sequencer_checkpoint = checkpoint,
+            Err(e) => {
+                if let Some((checkpointer, thread)) = checkpoint {
+                    checkpointer.finish(thread);
+                }
+                if let Err(off) = guards.control.disable_sequencer().await {
+                    warn!("[cognitod] failed to switch the sequencer back off: {off}");
+                }
+                warn!("[cognitod] sequencer unavailable ({e}); events stay on the perf buffers");
+                transport = "perf";
+                metrics.loss().set_transport(transport);
+            }
+        }
+    }
+
     // 🔁 Periodically refresh system snapshot (conditional on activity)
     let ctx_clone = Arc::clone(&context);
     let handlers_clone = Arc::clone(&handlers);
@@ -996,7 +2051,10 @@ async fn main() -> Result<(), Box<dyn Error>> {
         let metrics_clone = Arc::clone(&metrics);
         let queue_clone = Arc::clone(queue);
         let incident_store_clone = incident_store.clone();
-        let incident_analyzer_clone = incident_analyzer.clone();
+        let analysis_pool_clone = analysis_pool.clone();
+        let incident_sinks_clone = incident_sinks.clone();
+        let topology_clone = topology.clone();
+        let scoped = cgroup_scope.is_some();
 
         tokio::spawn(async move {
             if !cb_cfg.enabled {
@@ -1026,6 +2084,10 @@ async fn main() -> Result<(), Box<dyn Error>> {
             let mut breach_started_at: Option<std::time::Instant> = None;
             // Track frozen processes: (pid, comm, frozen_at)
             let mut frozen_processes: Vec<(u32, String, std::time::Instant)> = Vec::new();
+            // Recorded incidents awaiting recovery: (id, incident, action time)
+            let open_incidents: Arc<
+                std::sync::Mutex<Vec<(i64, cognitod::Incident, std::time::Instant)>>,
+            > = Arc::default();
 
             loop {
                 let snapshot = ctx_clone.get_system_snapshot();
@@ -1062,7 +2124,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
                             breach_started_at = None;
 
                             let mut top_cpu_procs = ctx_clone.top_cpu_processes(1);
-                            if top_cpu_procs.is_empty() {
+                            // The systemwide fallback would reach outside the scope.
+                            if top_cpu_procs.is_empty() && !scoped {
                                 top_cpu_procs = ctx_clone.top_cpu_processes_systemwide(1);
                             }
 
@@ -1070,9 +2133,9 @@ async fn main() -> Result<(), Box<dyn Error>> {
                                 // Get cgroup context for better attribution
                                 let cgroup_context = get_process_cgroup_context(proc.pid);
                                 let proc_context = if let Some(ref ctx) = cgroup_context {
-                                    format!("[{}] {}({})", ctx, proc.comm, proc.pid)
+                                    format!("[{}] {}({})", ctx, proc.name(), proc.pid)
                                 } else {
-                                    format!("{}({})", proc.comm, proc.pid)
+                                    format!("{}({})", proc.name(), proc.pid)
                                 };
 
                                 let reason = format!(
@@ -1103,8 +2166,14 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
                                 let action = if use_freeze && already_frozen.is_none() {
                                     // First offense: freeze the process (warning shot)
-                                    frozen_processes.push((proc.pid, proc.comm.clone(), std::time::Instant::now()));
-                                    cognitod::enforcement::ActionType::FreezeProcess { pid: proc.pid }
+                                    frozen_processes.push((
+                                        proc.pid,
+                                        proc.name().to_string(),
+                                        std::time::Instant::now(),
+                                    ));
+                                    cognitod::enforcement::ActionType::FreezeProcess {
+                                        pid: proc.pid,
+                                    }
                                 } else {
                                     // Either kill strategy, panic level, or freeze expired - execute kill
                                     frozen_processes.retain(|(pid, _, _)| *pid != proc.pid);
@@ -1123,7 +2192,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
                                 };
 
                                 match queue_clone
-                                    .propose_auto(
+                                    .propose_for_rule(
+                                        "circuit_breaker_cpu",
                                         action,
                                         reason.clone(),
                                         "circuit_breaker".to_string(),
@@ -1158,42 +2228,48 @@ async fn main() -> Result<(), Box<dyn Error>> {
                                                 ),
                                                 action: format!("auto_{}", action_name.to_lowercase()),
                                                 target_pid: Some(proc.pid as i32),
-                                                target_name: Some(proc.comm.clone()),
+                                                target_name: Some(proc.name().to_string()),
                                                 system_snapshot: serde_json::to_string(&snapshot)
                                                     .ok(),
                                                 llm_analysis: None,
                                                 llm_analyzed_at: None,
+                                                analysis_source: None,
                                                 recovery_time_ms: None,
                                                 psi_after: None,
+                                                delivery: Default::default(),
+                                                // Blast radius: who talked to the target
+                                                dependents: topology_clone
+                                                    .as_ref()
+                                                    .map(|t| t.dependents_of_pid(proc.pid))
+                                                    .unwrap_or_default(),
                                             };
 
-                                            let store_clone = Arc::clone(store);
-                                            let analyzer_clone = incident_analyzer_clone.clone();
+                                            let pool_clone = analysis_pool_clone.clone();
+                                            let sinks_clone = incident_sinks_clone.clone();
+                                            let open_clone = Arc::clone(&open_incidents);
+                                            let acted_at = std::time::Instant::now();
+                                            let inserted = store.submit_incident(incident.clone());
                                             tokio::spawn(async move {
-                                                if let Ok(id) = store_clone.insert(&incident).await
-                                                {
+                                                if let Ok(id) = inserted.await {
                                                     info!(
                                                         "[circuit_breaker] Incident #{} recorded",
                                                         id
                                                     );
-
-                                                    if let Some(analyzer) = analyzer_clone {
-                                                        tokio::spawn(async move {
-                                                            match analyzer.analyze(&incident).await
-                                                            {
-                                                                Ok(analysis) => {
-                                                                    let _ = store_clone
-                                                                        .add_llm_analysis(
-                                                                            id, analysis,
-                                                                        )
-                                                                        .await;
-                                                                }
-                                                                Err(e) => warn!(
-                                                                    "[incident_analyzer] Failed: {}",
-                                                                    e
-                                                                ),
-                                                            }
-                                                        });
+                                                    if let Some(sinks) = &sinks_clone {
+                                                        sinks.opened(id, &incident);
+                                                    }
+                                                    open_clone.lock().unwrap().push((
+                                                        id,
+                                                        incident.clone(),
+                                                        acted_at,
+                                                    ));
+
+                                                    if let Some(pool) = pool_clone {
+                                                        pool.submit(
+                                                            id,
+                                                            incident,
+                                                            cognitod::incidents::AnalysisPriority::CircuitBreaker,
+                                                        );
                                                     }
                                                 }
                                             });
@@ -1215,6 +2291,28 @@ async fn main() -> Result<(), Box<dyn Error>> {
                         info!("[circuit_breaker] conditions normalized - grace period reset");
                         breach_started_at = None;
                     }
+
+                    // Record recovery and close any remote pages
+                    let recovered: Vec<_> = open_incidents.lock().unwrap().drain(..).collect();
+                    for (id, mut incident, acted_at) in recovered {
+                        let recovery_time_ms = acted_at.elapsed().as_millis() as i64;
+                        incident.recovery_time_ms = Some(recovery_time_ms);
+                        incident.psi_after = Some(snapshot.psi_cpu_some_avg10);
+                        if let Some(store) = &incident_store_clone {
+                            store.submit(cognitod::IncidentWrite::Resolved {
+                                id,
+                                recovery_time_ms,
+                                psi_after: snapshot.psi_cpu_some_avg10,
+                            });
+                        }
+                        if let Some(sinks) = &incident_sinks_clone {
+                            sinks.resolved(id, &incident);
+                        }
+                        info!(
+                            "[circuit_breaker] Incident #{} resolved after {}ms",
+                            id, recovery_time_ms
+                        );
+                    }
                     
                     // Unfreeze processes if pressure is gone
                     for (pid, comm, _) in frozen_processes.drain(..) {
@@ -1236,46 +2334,106 @@ async fn main() -> Result<(), Box<dyn Error>> {
         });
     }
 
-    // Resource monitoring loop
+    // Compare the daemon's usage with its caps and degrade while over them
+    runtime::degradation::spawn(
+        Arc::clone(&context),
+        Arc::clone(&metrics),
+        bpf_runtime
+            .as_ref()
+            .map(|guards| Arc::clone(&guards.control)),
+        &config.runtime,
+        &config.degradation,
+    );
+
+    // Expire unanswered actions and remind notifiers about pending ones
+    if let Some(ref queue) = enforcement_queue {
+        enforcement::approvals::spawn(Arc::clone(queue), alert_tx.clone());
+    }
+
+    // Delete per-process map entries of processes that exited while probes
+    // were detached
+    if config.map_cleanup.enabled
+        && let Some(guards) = &bpf_runtime
     {
-        let runtime_cfg = config.runtime.clone();
-        tokio::spawn(async move {
-            use procfs::{page_size, process::Process, ticks_per_second};
-            let ticks = ticks_per_second() as f64;
-            let page_kb = page_size() / 1024;
-            let mut prev_total = 0u64;
-            loop {
-                if let Ok(stat) = Process::myself().and_then(|proc| proc.stat()) {
-                    let total = stat.utime + stat.stime;
-                    let dt = total.saturating_sub(prev_total);
-
-                    if prev_total > 0 {
-                        let cpu_pct = (dt as f64 / ticks) * 100.0;
-                        let rss_mb = stat.rss * page_kb / 1024;
-                        if cpu_pct > runtime_cfg.cpu_target_pct as f64 {
-                            warn!(
-                                "cpu usage {:.1}% exceeds target {}",
-                                cpu_pct, runtime_cfg.cpu_target_pct
-                            );
-                        }
-                        if rss_mb > runtime_cfg.rss_cap_mb {
-                            warn!("rss {}MB exceeds cap {}", rss_mb, runtime_cfg.rss_cap_mb);
-                        }
-                    }
-                    prev_total = total;
-                }
-                sleep(Duration::from_secs(1)).await;
-            }
-        });
+        runtime::map_cleanup::spawn(
+            Arc::clone(&guards.control),
+            Arc::clone(&metrics),
+            alert_tx.clone(),
+            &config.map_cleanup,
+        );
+    }
+
+    // Alert when events reach handlers and SSE clients later than the
+    // configured p99 bound
+    if config.staleness.enabled {
+        runtime::staleness::spawn(Arc::clone(&metrics), alert_tx.clone(), &config.staleness);
+    }
+
+    // Run a known process and expect its events end to end
+    if config.canary.enabled {
+        runtime::canary::spawn(
+            Arc::clone(&context),
+            Arc::clone(&metrics),
+            alert_tx.clone(),
+            &config.canary,
+        );
+    }
+
+    // Alert when one event type stops arriving while the others continue
+    if config.probe_silence.enabled {
+        runtime::probe_silence::spawn(
+            Arc::clone(&metrics),
+            alert_tx.clone(),
+            &config.probe_silence,
+        );
+    }
+
+    // Count page faults dropped by the in-kernel throttle, including those
+    // of processes that exit between reads
+    if let Some(guards) = &bpf_runtime {
+        runtime::page_faults::spawn(
+            Arc::clone(&guards.control),
+            Arc::clone(&metrics),
+            &config.probes.page_faults,
+        );
+    }
+
+    // Per-CPU sequencer production for /debug/sequencer
+    if let Some(guards) = &bpf_runtime {
+        runtime::sequencer_diag::spawn(
+            Arc::clone(&guards.control),
+            Arc::clone(&metrics),
+            sequencer_diag,
+        );
     }
 
     // Enforcement executor loop - actually executes approved actions
     if let Some(ref queue) = enforcement_queue {
         let queue_clone = Arc::clone(queue);
+        let ctx_clone = Arc::clone(&context);
+        let coordination_clone = Arc::clone(&coordination);
+        let cloudflare = cloudflare.clone();
         tokio::spawn(async move {
             loop {
                 for action in queue_clone.get_all().await {
                     if action.status == cognitod::enforcement::ActionStatus::Approved {
+                        // Another instance on this host enforces
+                        if let Err(e) = coordination_clone.may_enforce() {
+                            warn!("[enforcement] not executing {}: {}", action.id, e);
+                            let _ = queue_clone.abort(&action.id, e).await;
+                            continue;
+                        }
+                        // Pressure before the action, compared against a
+                        // later sample to score the triggering rule
+                        // The pid may belong to another process by now
+                        if let Err(e) = action.verify_target() {
+                            warn!("[enforcement] not executing {}: {}", action.id, e);
+                            let _ = queue_clone.abort(&action.id, e).await;
+                            continue;
+                        }
+                        let before = cognitod::enforcement::PressureSample::from(
+                            &ctx_clone.get_system_snapshot(),
+                        );
                         match action.action {
                             cognitod::enforcement::ActionType::KillProcess { pid, signal } => {
                                 info!("[enforcement] EXECUTING KILL pid={} signal={}", pid, signal);
@@ -1308,7 +2466,77 @@ async fn main() -> Result<(), Box<dyn Error>> {
                                 }
                                 let _ = queue_clone.complete(&action.id).await;
                             }
+                            cognitod::enforcement::ActionType::BlockSource {
+                                ref addr,
+                                backend,
+                                ttl_secs,
+                            } => {
+                                info!(
+                                    "[enforcement] BLOCKING {} via {:?} for {}s",
+                                    addr, backend, ttl_secs
+                                );
+                                let result = match backend {
+                                    cognitod::enforcement::BlockBackend::Nftables => {
+                                        handler::ddos::block_nftables(addr, ttl_secs).await
+                                    }
+                                    cognitod::enforcement::BlockBackend::Cloudflare => {
+                                        match &cloudflare {
+                                            Some(cf) => cf.block(addr, ttl_secs).await,
+                                            None => Err(anyhow::anyhow!(
+                                                "no Cloudflare zones configured"
+                                            )),
+                                        }
+                                    }
+                                };
+                                if let Err(e) = result {
+                                    warn!("[enforcement] Failed to block {}: {:#}", addr, e);
+                                }
+                                let _ = queue_clone.complete(&action.id).await;
+                            }
+                            cognitod::enforcement::ActionType::CloudflareRateLimit {
+                                ref zone,
+                                requests_per_period,
+                                period_secs,
+                                ttl_secs,
+                            } => {
+                                info!(
+                                    "[enforcement] RATE LIMITING zone {} to {} requests per {}s for {}s",
+                                    zone, requests_per_period, period_secs, ttl_secs
+                                );
+                                let result = match &cloudflare {
+                                    Some(cf) => {
+                                        cf.rate_limit(
+                                            zone,
+                                            requests_per_period,
+                                            period_secs,
+                                            ttl_secs,
+                                        )
+                                        .await
+                                    }
+                                    None => Err(anyhow::anyhow!("no Cloudflare zones configured")),
+                                };
+                                if let Err(e) = result {
+                                    warn!(
+                                        "[enforcement] Failed to rate limit zone {}: {:#}",
+                                        zone, e
+                                    );
+                                }
+                                let _ = queue_clone.complete(&action.id).await;
+                            }
                         }
+                        queue_clone.record_baseline(&action.id, before).await;
+                        let queue_outcome = Arc::clone(&queue_clone);
+                        let ctx_outcome = Arc::clone(&ctx_clone);
+                        tokio::spawn(async move {
+                            sleep(Duration::from_secs(
+                                cognitod::enforcement::OUTCOME_DELAY_SECS,
+                            ))
+                            .await;
+                            let after = cognitod::enforcement::PressureSample::from(
+                                &ctx_outcome.get_system_snapshot(),
+                            );
+                            queue_outcome.record_outcome(&action.id, after).await;
+                        });
                     }
                 }
                 sleep(Duration::from_secs(1)).await;
@@ -1334,15 +2562,38 @@ async fn main() -> Result<(), Box<dyn Error>> {
         });
     }
 
+    #[cfg(feature = "fleet-telemetry")]
+    let fleet_telemetry = {
+        let telemetry = Arc::new(cognitod::fleet_telemetry::FleetTelemetry::new(
+            &config,
+            Arc::clone(&metrics),
+            Arc::clone(&context),
+            Arc::clone(&offline_guard),
+        ));
+        if config.fleet_telemetry.enabled {
+            tokio::spawn(Arc::clone(&telemetry).run());
+        }
+        telemetry
+    };
+
     let auth_token = std::env::var("LINNIX_API_TOKEN")
         .ok()
         .or(config.api.auth_token.clone());
+    let access = api::access::AccessControl::from_config(&config.api)
+        .context("invalid [api] access control")?
+        .map(Arc::new);
+    let forensics = api::forensics::Forensics::from_config(&config.forensics)
+        .context("invalid [forensics] configuration")?
+        .map(Arc::new);
 
     let app_state = Arc::new(AppState {
         context: Arc::clone(&context),
         metrics: Arc::clone(&metrics),
         alerts: alert_tx,
         insights: Arc::clone(&insight_store),
+        insight_jobs: Arc::new(api::insight_jobs::InsightJobs::new(Duration::from_secs(
+            config.reasoner.insights_cache_secs,
+        ))),
         offline: Arc::clone(&offline_guard),
         transport,
         probe_state,
@@ -1350,16 +2601,50 @@ async fn main() -> Result<(), Box<dyn Error>> {
         prometheus_enabled: config.outputs.prometheus,
         alert_history: Arc::clone(&alert_history),
         auth_token: auth_token.clone(),
+        api_tokens: config.api_tokens(),
+        ui: config.ui.clone(),
         enforcement: enforcement_queue.clone(),
+        coordination: Arc::clone(&coordination),
         incident_store: incident_store.clone(),
         k8s: k8s_context.clone(),
+        maintenance: Some(Arc::clone(&maintenance)),
+        bpf: bpf_runtime
+            .as_ref()
+            .map(|guards| Arc::clone(&guards.control)),
+        scope: cgroup_scope.clone(),
+        power: power_monitor,
+        memory_events,
+        ephemeral_storage,
+        detectors,
+        analysis: analysis_pool,
+        restart_loops,
+        health: health_scorer,
+        topology,
+        enrichment,
+        leaderboard,
+        ancestry_profile,
+        scaling,
+        filesystems,
+        smart,
+        computed_fields,
+        access,
+        forensics,
+        discord_public_key: config
+            .notifications
+            .as_ref()
+            .and_then(|n| n.discord.as_ref())
+            .and_then(|d| d.bot.as_ref())
+            .map(|bot| bot.public_key.clone()),
+        templates,
+        #[cfg(feature = "fleet-telemetry")]
+        fleet_telemetry: Some(fleet_telemetry),
     });
 
     let api = all_routes(app_state.clone());
     let listen_addr = std::env::var("LINNIX_LISTEN_ADDR").unwrap_or(config.api.listen_addr.clone());
     let listener = TcpListener::bind(&listen_addr).await?;
 
-    if listen_addr.starts_with("0.0.0.0") && auth_token.is_none() {
+    if listen_addr.starts_with("0.0.0.0") && auth_token.is_none() && config.api_tokens().is_empty() {
         warn!(
             "API listening on {} with NO AUTHENTICATION. \
             Set LINNIX_API_TOKEN to secure the API.",
@@ -1368,32 +2653,58 @@ async fn main() -> Result<(), Box<dyn Error>> {
     }
 
     info!("[cognitod] HTTP server on http://{}", listen_addr);
+    let startup = metrics.startup().finish();
+    info!(
+        "[cognitod] started in {}ms: {}",
+        startup.total_ms,
+        startup.summary()
+    );
     tokio::spawn(async move {
+        let api = api.into_make_service_with_connect_info::<std::net::SocketAddr>();
         if let Err(e) = axum::serve(listener, api).await {
             eprintln!("server error: {e}");
         }
     });
 
-    tokio::spawn(async {
-        let mut sigterm = signal(SignalKind::terminate()).unwrap();
-        sigterm.recv().await;
-        println!("[cognitod] SIGTERM received, shutting down...");
-        std::process::exit(0);
-    });
-
+    let mut sigterm = signal(SignalKind::terminate())?;
     println!("[cognitod] Running. Press Ctrl+C to exit.");
-    tokio::signal::ctrl_c().await?;
+    tokio::select! {
+        res = tokio::signal::ctrl_c() => res?,
+        _ = sigterm.recv() => println!("[cognitod] SIGTERM received"),
+    }
     println!("[cognitod] Shutting down...");
     // Try graceful shutdown for 3 seconds
     if timeout(std::time::Duration::from_secs(3), async {
-        // Place any graceful shutdown logic here if needed
-        // e.g., notify background tasks to stop, flush logs, etc.
+        // Commit queued incident writes before exiting
+        if let Some(store) = &incident_store {
+            store.flush().await;
+        }
     })
     .await
     .is_err()
     {
         println!("[cognitod] Graceful shutdown timed out, forcing exit.");
     }
+    if let Some((checkpointer, thread)) = sequencer_checkpoint {
+        checkpointer.finish(thread);
+    }
+    if let Some(path) = &state_export_path {
+        let sources = StateSources {
+            incidents: incident_store.as_deref(),
+            maintenance: app_state.maintenance.as_deref(),
+            health: app_state.health.as_deref(),
+        };
+        match timeout(
+            std::time::Duration::from_secs(10),
+            state_bundle::export_to(Path::new(path), sources),
+        )
+        .await
+        {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!("[state] export to {path} failed: {e:#}"),
+            Err(_) => warn!("[state] export to {path} timed out"),
+        }
+    }
     std::process::exit(0);
 }
 
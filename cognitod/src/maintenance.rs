@@ -0,0 +1,637 @@
+//! Maintenance windows
+//!
+//! During planned work (deploys, node drains) alerts are expected and the
+//! circuit breaker should not act on its own. A maintenance window either
+//! recurs on a cron schedule from config or is created through the API with a
+//! TTL. While a window is active:
+//!
+//! - matching alerts are still recorded but tagged `suppressed_by` so
+//!   notifiers skip them;
+//! - enforcement auto-approval is disabled for matching targets, leaving the
+//!   action pending for a human.
+//!
+//! Windows can be scoped by rule name, comm glob, or namespace. An empty scope
+//! matches everything. Every create/cancel and every suppression decision is
+//! appended to an audit trail and logged under the `linnix_audit` target.
+
+use anyhow::{Context, anyhow, bail};
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::config::{MaintenanceConfig, MaintenanceScope, MaintenanceWindowConfig};
+use crate::k8s::K8sContext;
+
+const AUDIT_CAPACITY: usize = 256;
+/// Longest maintenance window, and so how far back a cron schedule is
+/// scanned.
+const MAX_WINDOW_SECS: u64 = 7 * 24 * 3600;
+
+/// Five-field cron expression (minute hour day-of-month month day-of-week),
+/// evaluated in UTC. Supports `*`, lists, ranges and `/step`.
+#[derive(Debug, Clone)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expr: &str) -> anyhow::Result<Self> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 {
+            bail!("expected 5 cron fields, got {}", fields.len());
+        }
+        let mut days_of_week = parse_cron_field(fields[4], 0, 7)
+            .with_context(|| format!("invalid day-of-week field '{}'", fields[4]))?;
+        // 7 is an alias for Sunday
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week & !(1 << 7)) | 1;
+        }
+        Ok(Self {
+            minutes: parse_cron_field(fields[0], 0, 59)
+                .with_context(|| format!("invalid minute field '{}'", fields[0]))?,
+            hours: parse_cron_field(fields[1], 0, 23)
+                .with_context(|| format!("invalid hour field '{}'", fields[1]))?,
+            days_of_month: parse_cron_field(fields[2], 1, 31)
+                .with_context(|| format!("invalid day-of-month field '{}'", fields[2]))?,
+            months: parse_cron_field(fields[3], 1, 12)
+                .with_context(|| format!("invalid month field '{}'", fields[3]))?,
+            days_of_week,
+            dom_restricted: fields[2] != "*",
+            dow_restricted: fields[4] != "*",
+        })
+    }
+
+    pub fn matches(&self, t: &DateTime<Utc>) -> bool {
+        let bit = |mask: u64, v: u32| mask & (1u64 << v) != 0;
+        if !bit(self.minutes, t.minute()) || !bit(self.hours, t.hour()) {
+            return false;
+        }
+        if !bit(self.months, t.month()) {
+            return false;
+        }
+        let dom = bit(self.days_of_month, t.day());
+        let dow = bit(self.days_of_week, t.weekday().num_days_from_sunday());
+        // Classic cron: when both day fields are restricted either may match.
+        if self.dom_restricted && self.dow_restricted {
+            dom || dow
+        } else {
+            dom && dow
+        }
+    }
+
+    /// Most recent start time within `lookback_secs` of `now`, if any.
+    fn last_start(&self, now: &DateTime<Utc>, lookback_secs: u64) -> Option<DateTime<Utc>> {
+        let floor = now.with_second(0)?.with_nanosecond(0)?;
+        let steps = lookback_secs.min(MAX_WINDOW_SECS) / 60;
+        (0..=steps as i64)
+            .map(|m| floor - ChronoDuration::minutes(m))
+            .find(|t| self.matches(t) && (*now - *t).num_seconds() < lookback_secs as i64)
+    }
+}
+
+fn parse_cron_field(field: &str, min: u32, max: u32) -> anyhow::Result<u64> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((r, s)) => (r, s.parse::<u32>().context("invalid step")?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            bail!("step must be positive");
+        }
+        let (lo, hi) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (a.parse::<u32>()?, b.parse::<u32>()?)
+        } else {
+            let v = range.parse::<u32>()?;
+            // "5/10" means starting at 5 through the end of the range
+            (v, if part.contains('/') { max } else { v })
+        };
+        if lo < min || hi > max || lo > hi {
+            bail!("value out of range {min}-{max}");
+        }
+        let mut v = lo;
+        while v <= hi {
+            mask |= 1u64 << v;
+            let Some(next) = v.checked_add(step) else {
+                break;
+            };
+            v = next;
+        }
+    }
+    Ok(mask)
+}
+
+/// What an alert or enforcement action is about, for scope matching.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MaintenanceSubject<'a> {
+    pub rule: Option<&'a str>,
+    pub comm: Option<&'a str>,
+    pub namespace: Option<&'a str>,
+}
+
+impl MaintenanceScope {
+    fn is_empty(&self) -> bool {
+        self.rules.is_empty() && self.comms.is_empty() && self.namespaces.is_empty()
+    }
+
+    /// Each non-empty scope list must match the subject; empty lists are
+    /// wildcards.
+    pub fn matches(&self, subject: &MaintenanceSubject<'_>) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let list_matches = |patterns: &[String], value: Option<&str>| {
+            patterns.is_empty() || value.is_some_and(|v| patterns.iter().any(|p| glob_match(p, v)))
+        };
+        list_matches(&self.rules, subject.rule)
+            && list_matches(&self.comms, subject.comm)
+            && list_matches(&self.namespaces, subject.namespace)
+    }
+}
+
+/// Minimal glob supporting `*` and `?`.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    let (mut pi, mut ti) = (0usize, 0usize);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+struct ScheduledWindow {
+    config: MaintenanceWindowConfig,
+    schedule: CronSchedule,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdhocWindow {
+    pub id: String,
+    pub name: String,
+    pub reason: String,
+    pub created_by: String,
+    pub starts_at: i64,
+    pub ends_at: i64,
+    pub scope: MaintenanceScope,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateWindowRequest {
+    pub name: String,
+    pub duration_secs: u64,
+    #[serde(default)]
+    pub reason: String,
+    #[serde(default)]
+    pub created_by: Option<String>,
+    #[serde(default)]
+    pub scope: MaintenanceScope,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WindowStatus {
+    pub id: String,
+    pub name: String,
+    /// `scheduled` (from config) or `adhoc` (API-created)
+    pub kind: &'static str,
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cron: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active_since: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ends_at: Option<i64>,
+    pub scope: MaintenanceScope,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub timestamp: i64,
+    pub action: String,
+    pub window: String,
+    pub detail: String,
+}
+
+pub struct MaintenanceManager {
+    scheduled: Vec<ScheduledWindow>,
+    adhoc: RwLock<Vec<AdhocWindow>>,
+    audit: Mutex<VecDeque<AuditEntry>>,
+    next_id: AtomicU64,
+    k8s: Option<Arc<K8sContext>>,
+}
+
+impl Default for MaintenanceManager {
+    fn default() -> Self {
+        Self {
+            scheduled: Vec::new(),
+            adhoc: RwLock::new(Vec::new()),
+            audit: Mutex::new(VecDeque::new()),
+            next_id: AtomicU64::new(1),
+            k8s: None,
+        }
+    }
+}
+
+impl MaintenanceManager {
+    pub fn from_config(config: &MaintenanceConfig) -> anyhow::Result<Self> {
+        let mut scheduled = Vec::with_capacity(config.windows.len());
+        for window in &config.windows {
+            if window.duration_secs == 0 {
+                return Err(anyhow!(
+                    "maintenance window '{}' has zero duration",
+                    window.name
+                ));
+            }
+            if window.duration_secs > MAX_WINDOW_SECS {
+                return Err(anyhow!(
+                    "maintenance window '{}' is longer than {MAX_WINDOW_SECS}s",
+                    window.name
+                ));
+            }
+            let schedule = CronSchedule::parse(&window.cron)
+                .with_context(|| format!("maintenance window '{}'", window.name))?;
+            scheduled.push(ScheduledWindow {
+                config: window.clone(),
+                schedule,
+            });
+        }
+        Ok(Self {
+            scheduled,
+            ..Self::default()
+        })
+    }
+
+    /// Resolve pod namespaces for namespace-scoped windows.
+    pub fn with_k8s(mut self, k8s: Option<Arc<K8sContext>>) -> Self {
+        self.k8s = k8s;
+        self
+    }
+
+    pub fn namespace_for_pid(&self, pid: u32) -> Option<String> {
+        self.k8s
+            .as_ref()?
+            .get_metadata_for_pid(pid)
+            .map(|meta| meta.namespace)
+    }
+
+    pub fn create(&self, req: CreateWindowRequest) -> anyhow::Result<AdhocWindow> {
+        self.create_at(Utc::now(), req)
+    }
+
+    fn create_at(
+        &self,
+        now: DateTime<Utc>,
+        req: CreateWindowRequest,
+    ) -> anyhow::Result<AdhocWindow> {
+        if req.duration_secs == 0 || req.duration_secs > MAX_WINDOW_SECS {
+            bail!("duration_secs must be between 1 and {MAX_WINDOW_SECS}");
+        }
+        let ends_at = i64::try_from(req.duration_secs)
+            .ok()
+            .and_then(|duration| now.timestamp().checked_add(duration))
+            .context("duration_secs overflows the window end")?;
+        let id = format!("mw-{}", self.next_id.fetch_add(1, Ordering::SeqCst));
+        let window = AdhocWindow {
+            id: id.clone(),
+            name: req.name,
+            reason: req.reason,
+            created_by: req.created_by.unwrap_or_else(|| "api".to_string()),
+            starts_at: now.timestamp(),
+            ends_at,
+            scope: req.scope,
+        };
+        self.record(
+            now,
+            "created",
+            &window.name,
+            format!(
+                "id={} by={} duration={}s reason={}",
+                id, window.created_by, req.duration_secs, window.reason
+            ),
+        );
+        if let Ok(mut adhoc) = self.adhoc.write() {
+            adhoc.retain(|w| w.ends_at > now.timestamp());
+            adhoc.push(window.clone());
+        }
+        Ok(window)
+    }
+
+    /// API-created windows that have not ended, for carrying them across
+    /// restarts.
+    pub fn adhoc_windows(&self) -> Vec<AdhocWindow> {
+        let now = Utc::now().timestamp();
+        self.adhoc
+            .read()
+            .map(|adhoc| adhoc.iter().filter(|w| w.ends_at > now).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Re-create windows saved by an earlier run, keeping their ids. Ended
+    /// windows and ids already present are skipped. Returns the number
+    /// restored.
+    pub fn restore(&self, windows: Vec<AdhocWindow>) -> usize {
+        let now = Utc::now();
+        let mut restored = Vec::new();
+        if let Ok(mut adhoc) = self.adhoc.write() {
+            for window in windows {
+                if window.ends_at <= now.timestamp() || adhoc.iter().any(|w| w.id == window.id) {
+                    continue;
+                }
+                // Keep new ids clear of restored ones
+                if let Some(n) = window
+                    .id
+                    .strip_prefix("mw-")
+                    .and_then(|n| n.parse::<u64>().ok())
+                {
+                    self.next_id.fetch_max(n + 1, Ordering::SeqCst);
+                }
+                restored.push((window.name.clone(), window.id.clone(), window.ends_at));
+                adhoc.push(window);
+            }
+        }
+        for (name, id, ends_at) in &restored {
+            self.record(now, "restored", name, format!("id={id} ends_at={ends_at}"));
+        }
+        restored.len()
+    }
+
+    /// End an API-created window early. Returns false if the id is unknown.
+    pub fn cancel(&self, id: &str, by: &str) -> bool {
+        let removed = match self.adhoc.write() {
+            Ok(mut adhoc) => {
+                let before = adhoc.len();
+                let name = adhoc.iter().find(|w| w.id == id).map(|w| w.name.clone());
+                adhoc.retain(|w| w.id != id);
+                name.filter(|_| adhoc.len() < before)
+            }
+            Err(_) => None,
+        };
+        match removed {
+            Some(name) => {
+                self.record(Utc::now(), "cancelled", &name, format!("id={id} by={by}"));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Name of the first active window matching `subject`, if any.
+    pub fn matching_window(&self, subject: &MaintenanceSubject<'_>) -> Option<String> {
+        self.matching_window_at(Utc::now(), subject)
+    }
+
+    fn matching_window_at(
+        &self,
+        now: DateTime<Utc>,
+        subject: &MaintenanceSubject<'_>,
+    ) -> Option<String> {
+        self.windows_at(now)
+            .into_iter()
+            .find(|w| w.active && w.scope.matches(subject))
+            .map(|w| w.name)
+    }
+
+    /// Record that `window` suppressed something (alert, auto-approval).
+    pub fn record_suppression(&self, window: &str, what: &str, detail: String) {
+        self.record(Utc::now(), what, window, detail);
+    }
+
+    pub fn windows(&self) -> Vec<WindowStatus> {
+        self.windows_at(Utc::now())
+    }
+
+    pub fn active_windows(&self) -> Vec<WindowStatus> {
+        self.windows().into_iter().filter(|w| w.active).collect()
+    }
+
+    fn windows_at(&self, now: DateTime<Utc>) -> Vec<WindowStatus> {
+        let mut out: Vec<WindowStatus> = self
+            .scheduled
+            .iter()
+            .map(|s| {
+                let start = s.schedule.last_start(&now, s.config.duration_secs);
+                WindowStatus {
+                    id: format!("cfg-{}", s.config.name),
+                    name: s.config.name.clone(),
+                    kind: "scheduled",
+                    active: start.is_some(),
+                    cron: Some(s.config.cron.clone()),
+                    active_since: start.map(|t| t.timestamp()),
+                    ends_at: start.and_then(|t| {
+                        let duration = i64::try_from(s.config.duration_secs).ok()?;
+                        t.timestamp().checked_add(duration)
+                    }),
+                    scope: s.config.scope.clone(),
+                }
+            })
+            .collect();
+        if let Ok(adhoc) = self.adhoc.read() {
+            out.extend(
+                adhoc
+                    .iter()
+                    .filter(|w| w.ends_at > now.timestamp())
+                    .map(|w| WindowStatus {
+                        id: w.id.clone(),
+                        name: w.name.clone(),
+                        kind: "adhoc",
+                        active: w.starts_at <= now.timestamp(),
+                        cron: None,
+                        active_since: Some(w.starts_at),
+                        ends_at: Some(w.ends_at),
+                        scope: w.scope.clone(),
+                    }),
+            );
+        }
+        out
+    }
+
+    pub fn audit(&self) -> Vec<AuditEntry> {
+        self.audit
+            .lock()
+            .map(|a| a.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn record(&self, now: DateTime<Utc>, action: &str, window: &str, detail: String) {
+        log::info!(
+            target: "linnix_audit",
+            "MAINTENANCE {} window={} {}",
+            action, window, detail
+        );
+        if let Ok(mut audit) = self.audit.lock() {
+            if audit.len() >= AUDIT_CAPACITY {
+                audit.pop_front();
+            }
+            audit.push_back(AuditEntry {
+                timestamp: now.timestamp(),
+                action: action.to_string(),
+                window: window.to_string(),
+                detail,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn utc(ts: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(ts, 0).unwrap()
+    }
+
+    fn window(name: &str, cron: &str, duration_secs: u64) -> MaintenanceWindowConfig {
+        MaintenanceWindowConfig {
+            name: name.to_string(),
+            cron: cron.to_string(),
+            duration_secs,
+            scope: MaintenanceScope::default(),
+        }
+    }
+
+    #[test]
+    fn cron_fields_parse() {
+        let c = CronSchedule::parse("*/15 2 * * 1-5").unwrap();
+        // 2024-01-01 is a Monday
+        let t = Utc.with_ymd_and_hms(2024, 1, 1, 2, 30, 0).unwrap();
+        assert!(c.matches(&t));
+        assert!(!c.matches(&(t + ChronoDuration::minutes(1))));
+        let sunday = Utc.with_ymd_and_hms(2024, 1, 7, 2, 30, 0).unwrap();
+        assert!(!c.matches(&sunday));
+        assert!(CronSchedule::parse("61 * * * *").is_err());
+        assert!(CronSchedule::parse("* * *").is_err());
+        assert!(CronSchedule::parse("0 0 * * 7").is_ok());
+        let c = CronSchedule::parse("59/4294967295 * * * *").unwrap();
+        assert!(c.matches(&Utc.with_ymd_and_hms(2024, 1, 1, 0, 59, 0).unwrap()));
+    }
+
+    #[test]
+    fn scheduled_window_active_for_duration() {
+        let cfg = MaintenanceConfig {
+            windows: vec![window("nightly", "0 2 * * *", 1800)],
+        };
+        let mgr = MaintenanceManager::from_config(&cfg).unwrap();
+        let subject = MaintenanceSubject::default();
+        let inside = Utc.with_ymd_and_hms(2024, 3, 5, 2, 29, 0).unwrap();
+        let after = Utc.with_ymd_and_hms(2024, 3, 5, 2, 30, 0).unwrap();
+        let before = Utc.with_ymd_and_hms(2024, 3, 5, 1, 59, 0).unwrap();
+        assert_eq!(
+            mgr.matching_window_at(inside, &subject).as_deref(),
+            Some("nightly")
+        );
+        assert!(mgr.matching_window_at(after, &subject).is_none());
+        assert!(mgr.matching_window_at(before, &subject).is_none());
+    }
+
+    #[test]
+    fn adhoc_window_respects_scope_and_ttl() {
+        let mgr = MaintenanceManager::default();
+        let now = utc(1_700_000_000);
+        let created = mgr
+            .create_at(
+                now,
+                CreateWindowRequest {
+                    name: "deploy".into(),
+                    duration_secs: 600,
+                    reason: "rollout".into(),
+                    created_by: Some("alice".into()),
+                    scope: MaintenanceScope {
+                        rules: vec![],
+                        comms: vec!["java*".into()],
+                        namespaces: vec![],
+                    },
+                },
+            )
+            .unwrap();
+
+        let java = MaintenanceSubject {
+            comm: Some("java-app"),
+            ..Default::default()
+        };
+        let nginx = MaintenanceSubject {
+            comm: Some("nginx"),
+            ..Default::default()
+        };
+        assert_eq!(
+            mgr.matching_window_at(now, &java).as_deref(),
+            Some("deploy")
+        );
+        assert!(mgr.matching_window_at(now, &nginx).is_none());
+        assert!(
+            mgr.matching_window_at(now + ChronoDuration::seconds(600), &java)
+                .is_none()
+        );
+
+        assert!(mgr.cancel(&created.id, "bob"));
+        assert!(!mgr.cancel(&created.id, "bob"));
+        let audit = mgr.audit();
+        assert_eq!(audit.len(), 2);
+        assert_eq!(audit[0].action, "created");
+        assert_eq!(audit[1].action, "cancelled");
+    }
+
+    #[test]
+    fn glob_patterns() {
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("kube-*", "kube-proxy"));
+        assert!(glob_match("py?hon", "python"));
+        assert!(!glob_match("kube-*", "kubelet"));
+    }
+
+    #[test]
+    fn invalid_config_is_rejected() {
+        let cfg = MaintenanceConfig {
+            windows: vec![window("bad", "not a cron", 60)],
+        };
+        assert!(MaintenanceManager::from_config(&cfg).is_err());
+        let cfg = MaintenanceConfig {
+            windows: vec![window("zero", "* * * * *", 0)],
+        };
+        assert!(MaintenanceManager::from_config(&cfg).is_err());
+        let cfg = MaintenanceConfig {
+            windows: vec![window("long", "* * * * *", MAX_WINDOW_SECS + 1)],
+        };
+        assert!(MaintenanceManager::from_config(&cfg).is_err());
+    }
+
+    #[test]
+    fn adhoc_duration_is_bounded() {
+        let mgr = MaintenanceManager::default();
+        let request = |duration_secs| CreateWindowRequest {
+            name: "deploy".into(),
+            duration_secs,
+            reason: String::new(),
+            created_by: None,
+            scope: MaintenanceScope::default(),
+        };
+        let now = utc(1_700_000_000);
+        assert!(mgr.create_at(now, request(u64::MAX)).is_err());
+        assert!(mgr.create_at(now, request(MAX_WINDOW_SECS + 1)).is_err());
+        let window = mgr.create_at(now, request(MAX_WINDOW_SECS)).unwrap();
+        assert_eq!(window.ends_at, now.timestamp() + MAX_WINDOW_SECS as i64);
+    }
+}
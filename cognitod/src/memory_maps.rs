@@ -0,0 +1,300 @@
+//! Memory map and shared library inventory of a live process
+//!
+//! Parses `/proc/<pid>/maps` for `/processes/{pid}/maps`. File-backed
+//! regions are grouped into one [`Library`] per path with the total mapped
+//! size and every permission set it is mapped with. Regions that are both
+//! writable and executable are listed separately as suspicious: legitimate
+//! code is mapped read-only, so W+X memory usually means a JIT, a packer or
+//! injected code. Their contents are read through `/proc/<pid>/mem` and
+//! hashed with SHA-256 so the payload can be compared across hosts. Reading
+//! another process's memory needs ptrace access to it, which the daemon has
+//! as root.
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::FileExt;
+
+/// Read size while hashing a region.
+const HASH_CHUNK: usize = 64 * 1024;
+
+/// One line of `/proc/<pid>/maps`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapRegion {
+    pub start: u64,
+    pub end: u64,
+    /// `rwxp` style, `-` for a missing permission, `p` private or `s` shared
+    pub perms: String,
+    pub offset: u64,
+    pub inode: u64,
+    /// File path, or a pseudo path such as `[heap]`; `None` for anonymous
+    /// memory
+    pub path: Option<String>,
+}
+
+impl MapRegion {
+    pub fn len(&self) -> u64 {
+        self.end.saturating_sub(self.start)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn writable_executable(&self) -> bool {
+        self.perms.as_bytes().get(1) == Some(&b'w') && self.perms.as_bytes().get(2) == Some(&b'x')
+    }
+
+    fn file_path(&self) -> Option<&str> {
+        self.path.as_deref().filter(|path| path.starts_with('/'))
+    }
+}
+
+/// A file mapped into the process: the executable, shared libraries and
+/// other mapped files.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Library {
+    pub path: String,
+    /// Bytes mapped across all regions of the file
+    pub size: u64,
+    /// Distinct permission sets, in the order they appear
+    pub perms: Vec<String>,
+    pub regions: usize,
+    /// Unlinked since it was mapped, e.g. replaced by an upgrade
+    pub deleted: bool,
+}
+
+/// A writable and executable region.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SuspiciousRegion {
+    pub start: String,
+    pub end: String,
+    pub perms: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    pub size: u64,
+    /// SHA-256 of the first `hashed_bytes` bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    pub hashed_bytes: u64,
+    /// Why the region could not be hashed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MapsInventory {
+    pub pid: u32,
+    pub regions: usize,
+    pub mapped_bytes: u64,
+    /// Anonymous memory, including `[heap]` and `[stack]`
+    pub anonymous_bytes: u64,
+    pub libraries: Vec<Library>,
+    pub suspicious: Vec<SuspiciousRegion>,
+}
+
+/// Parse one line of `/proc/<pid>/maps`.
+pub fn parse_line(line: &str) -> Option<MapRegion> {
+    let mut rest = line.trim_start();
+    let mut fields = [""; 5];
+    for field in &mut fields {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        *field = &rest[..end];
+        rest = rest[end..].trim_start();
+    }
+    let [range, perms, offset, _dev, inode] = fields;
+    let (start, end) = range.split_once('-')?;
+    let path = rest.trim_end();
+    Some(MapRegion {
+        start: u64::from_str_radix(start, 16).ok()?,
+        end: u64::from_str_radix(end, 16).ok()?,
+        perms: perms.to_string(),
+        offset: u64::from_str_radix(offset, 16).ok()?,
+        inode: inode.parse().ok()?,
+        path: (!path.is_empty()).then(|| path.to_string()),
+    })
+}
+
+pub fn parse_maps(text: &str) -> Vec<MapRegion> {
+    text.lines().filter_map(parse_line).collect()
+}
+
+/// Group file-backed regions by path, in order of first appearance.
+pub fn libraries(regions: &[MapRegion]) -> Vec<Library> {
+    let mut libraries: Vec<Library> = Vec::new();
+    for region in regions {
+        let Some(path) = region.file_path() else {
+            continue;
+        };
+        let (path, deleted) = match path.strip_suffix(" (deleted)") {
+            Some(path) => (path, true),
+            None => (path, false),
+        };
+        let index = match libraries.iter().position(|lib| lib.path == path) {
+            Some(index) => index,
+            None => {
+                libraries.push(Library {
+                    path: path.to_string(),
+                    size: 0,
+                    perms: Vec::new(),
+                    regions: 0,
+                    deleted,
+                });
+                libraries.len() - 1
+            }
+        };
+        let library = &mut libraries[index];
+        library.size += region.len();
+        library.regions += 1;
+        library.deleted |= deleted;
+        if !library.perms.contains(&region.perms) {
+            library.perms.push(region.perms.clone());
+        }
+    }
+    libraries
+}
+
+/// SHA-256 of `len` bytes of `mem` from `start`.
+pub fn hash_region(mem: &File, start: u64, len: u64) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; HASH_CHUNK];
+    let mut done = 0;
+    while done < len {
+        let want = (len - done).min(HASH_CHUNK as u64) as usize;
+        let read = mem.read_at(&mut buf[..want], start + done)?;
+        if read == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("region ends at {:#x}", start + done),
+            ));
+        }
+        hasher.update(&buf[..read]);
+        done += read as u64;
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Inventory of `regions`. W+X regions are hashed from `mem` up to
+/// `max_hash_bytes` each; without `mem` they are listed unhashed.
+pub fn inventory(
+    pid: u32,
+    regions: &[MapRegion],
+    mem: Option<&File>,
+    max_hash_bytes: u64,
+) -> MapsInventory {
+    let suspicious = regions
+        .iter()
+        .filter(|region| region.writable_executable())
+        .map(|region| {
+            let hashed_bytes = region.len().min(max_hash_bytes);
+            let hash = match mem {
+                Some(mem) => hash_region(mem, region.start, hashed_bytes),
+                None => Err(io::Error::other("process memory not readable")),
+            };
+            let (sha256, error) = match hash {
+                Ok(digest) => (Some(digest), None),
+                Err(e) => (None, Some(e.to_string())),
+            };
+            SuspiciousRegion {
+                start: format!("{:#x}", region.start),
+                end: format!("{:#x}", region.end),
+                perms: region.perms.clone(),
+                path: region.path.clone(),
+                size: region.len(),
+                hashed_bytes: if sha256.is_some() { hashed_bytes } else { 0 },
+                sha256,
+                error,
+            }
+        })
+        .collect();
+    MapsInventory {
+        pid,
+        regions: regions.len(),
+        mapped_bytes: regions.iter().map(MapRegion::len).sum(),
+        anonymous_bytes: regions
+            .iter()
+            .filter(|region| region.file_path().is_none())
+            .map(MapRegion::len)
+            .sum(),
+        libraries: libraries(regions),
+        suspicious,
+    }
+}
+
+/// Inventory of a running process. Fails when its maps cannot be read,
+/// typically because it exited.
+pub fn read_inventory(pid: u32, max_hash_bytes: u64) -> io::Result<MapsInventory> {
+    let regions = parse_maps(&std::fs::read_to_string(format!("/proc/{pid}/maps"))?);
+    let mem = regions
+        .iter()
+        .any(MapRegion::writable_executable)
+        .then(|| File::open(format!("/proc/{pid}/mem")).ok())
+        .flatten();
+    Ok(inventory(pid, &regions, mem.as_ref(), max_hash_bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const MAPS: &str = "\
+55d0c0a00000-55d0c0a02000 r--p 00000000 fd:01 1835101                    /usr/bin/python3.11
+55d0c0a02000-55d0c0a80000 r-xp 00002000 fd:01 1835101                    /usr/bin/python3.11
+55d0c1e4d000-55d0c1f6e000 rw-p 00000000 00:00 0                          [heap]
+7f2c38000000-7f2c38021000 rwxp 00000000 00:00 0
+7f2c3a000000-7f2c3a028000 r--p 00000000 fd:01 1837200                    /usr/lib/x86_64-linux-gnu/libc.so.6
+7f2c3a028000-7f2c3a1bd000 r-xp 00028000 fd:01 1837200                    /usr/lib/x86_64-linux-gnu/libc.so.6
+7f2c3a200000-7f2c3a201000 r-xp 00000000 00:19 4711                       /tmp/my lib.so (deleted)
+7ffd4b1e0000-7ffd4b201000 rw-p 00000000 00:00 0                          [stack]
+";
+
+    #[test]
+    fn groups_libraries_and_flags_writable_executable_regions() {
+        let regions = parse_maps(MAPS);
+        assert_eq!(regions.len(), 8);
+        assert_eq!(regions[3].path, None);
+        assert_eq!(regions[1].offset, 0x2000);
+        assert_eq!(regions[6].path.as_deref(), Some("/tmp/my lib.so (deleted)"));
+
+        let inventory = inventory(42, &regions, None, 1024);
+        let python = &inventory.libraries[0];
+        assert_eq!(python.path, "/usr/bin/python3.11");
+        assert_eq!(python.size, 0x80000);
+        assert_eq!(python.perms, ["r--p", "r-xp"]);
+        assert_eq!(python.regions, 2);
+        let unlinked = &inventory.libraries[2];
+        assert_eq!(
+            (unlinked.path.as_str(), unlinked.deleted),
+            ("/tmp/my lib.so", true)
+        );
+        assert_eq!(inventory.libraries.len(), 3);
+        assert_eq!(
+            inventory.anonymous_bytes,
+            0x121000 + 0x21000 + 0x21000,
+            "heap, the W+X region and the stack"
+        );
+
+        assert_eq!(inventory.suspicious.len(), 1);
+        let region = &inventory.suspicious[0];
+        assert_eq!(
+            (region.start.as_str(), region.size),
+            ("0x7f2c38000000", 0x21000)
+        );
+        assert_eq!(region.sha256, None);
+        assert!(region.error.is_some());
+    }
+
+    #[test]
+    fn hashes_region_contents() {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&[0u8; 16]).unwrap();
+        file.write_all(b"abc").unwrap();
+        assert_eq!(
+            hash_region(&file, 16, 3).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert!(hash_region(&file, 16, 4).is_err());
+    }
+}
@@ -1,9 +1,121 @@
+use serde::Serialize;
 use std::sync::RwLock;
 use std::sync::atomic::AtomicU32;
 use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, AtomicUsize, Ordering};
 use std::time::SystemTime;
 
+pub mod backlog;
+pub mod canary;
+pub mod cursor;
+pub mod ingest;
+pub mod latency;
+pub mod loss;
+pub mod numa;
+pub mod reorder;
+pub mod startup;
+
+use backlog::BacklogTracker;
+use canary::CanaryTracker;
+use cursor::CursorTracker;
+use ingest::IngestTracker;
+use latency::LatencyTracker;
+use loss::LossTracker;
+use reorder::ReorderTracker;
+use startup::StartupTimeline;
+
 const EVENT_TYPE_SLOTS: usize = 8;
+/// Received-event counters, one per event type id.
+const RECEIVED_TYPE_SLOTS: usize = 16;
+
+/// Per-process BPF map occupancy as of the last cleanup scan.
+#[derive(Debug, Clone, Serialize)]
+pub struct MapOccupancy {
+    pub map: &'static str,
+    pub entries: usize,
+    pub max_entries: u32,
+    /// Entries of exited processes deleted since startup
+    pub stale_removed_total: u64,
+}
+
+/// An event type that stopped arriving, as of the last silence check.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SilentEventType {
+    pub event_type: &'static str,
+    pub silent_secs: u64,
+    /// Learned rate while the type was flowing
+    pub expected_per_min: f64,
+}
+
+/// Page faults the in-kernel throttle dropped for one live pid.
+#[derive(Debug, Clone, Serialize)]
+pub struct SuppressedFaults {
+    pub pid: u32,
+    pub suppressed: u64,
+}
+
+/// Sequencer production and consumption, served at `/debug/sequencer`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SequencerReport {
+    /// `SEQUENCER_ENABLED`: 0 off, 1 shared ring, N partitions. `None` when
+    /// the object has no sequencer.
+    pub mode: Option<u32>,
+    /// Tickets reserved since load, over all CPUs and partitions.
+    pub tickets: u64,
+    /// Sum of the consumers' cursors; `None` while no consumer runs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<u64>,
+    /// Tickets reserved but not yet read or reaped.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lag: Option<u64>,
+    pub consumers: usize,
+    pub events_reaped: u64,
+    pub ordering_violations: u64,
+    /// Window the per-second rates cover; 0 on the first sample.
+    pub interval_secs: f64,
+    pub cpus: Vec<CpuSequenceReport>,
+    /// Page placement of the rings on NUMA hosts, per partition with
+    /// `[sequencer] numa`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub numa: Option<numa::NumaReport>,
+}
+
+/// One producing CPU. Consumer-side counts are summed over consumers.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct CpuSequenceReport {
+    pub cpu: u16,
+    pub tickets: u64,
+    pub tickets_per_sec: f64,
+    pub events: u64,
+    pub reaped: u64,
+    pub gaps: u64,
+    pub inversions: u64,
+}
+
+/// Delivery queue of one notifier, from the notification WAL.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NotificationQueueStats {
+    pub sink: String,
+    /// Entries awaiting delivery, including ones backing off.
+    pub depth: u64,
+    /// Entries that failed at least once and are scheduled for a retry.
+    pub retrying: u64,
+    /// Age of the oldest undelivered entry; 0 when the queue is empty.
+    pub oldest_age_secs: u64,
+    /// Entries that exhausted their attempts.
+    pub dead_letters: u64,
+    /// Error of the most recent dead letter.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+impl MapOccupancy {
+    pub fn percent(&self) -> f64 {
+        if self.max_entries == 0 {
+            return 0.0;
+        }
+        self.entries as f64 * 100.0 / self.max_entries as f64
+    }
+}
 
 /// Global metrics for the cognition daemon.
 ///
@@ -24,7 +136,11 @@ pub struct Metrics {
     lineage_hits: AtomicU64,
     lineage_misses: AtomicU64,
     drops_by_type: [AtomicU64; EVENT_TYPE_SLOTS],
+    received_by_type: [AtomicU64; RECEIVED_TYPE_SLOTS],
+    silent_event_types: RwLock<Vec<SilentEventType>>,
     alerts_emitted_total: AtomicU64,
+    alerts_suppressed_total: AtomicU64,
+    alerts_held_back_total: AtomicU64,
     perf_poll_errors: AtomicU64,
     active_rules: AtomicUsize,
     rss_probe_mode: AtomicU8,
@@ -54,6 +170,34 @@ pub struct Metrics {
     pub slack_failed_total: AtomicU64,
     pub alerts_generated_total: AtomicU64,
     pub feedback_entries_total: AtomicU64,
+    // Sequence-gap based loss accounting
+    loss: LossTracker,
+    // Kernel-to-API event age per pipeline stage
+    latency: LatencyTracker,
+    // Ingested volume and processing time per event type
+    ingest: IngestTracker,
+    // Reorder stage in front of ordered handlers
+    reorder: ReorderTracker,
+    // End-to-end canary runs
+    canary: CanaryTracker,
+    // Sequencer cursor persistence
+    cursor: CursorTracker,
+    // Sequencer backlog catch-up after a restored cursor
+    backlog: BacklogTracker,
+    // Startup phase timings
+    startup: StartupTimeline,
+    bpf_maps: RwLock<Vec<MapOccupancy>>,
+    page_faults_suppressed_total: AtomicU64,
+    page_faults_suppressed: RwLock<Vec<SuppressedFaults>>,
+    notification_queues: RwLock<Vec<NotificationQueueStats>>,
+    sequencer: RwLock<SequencerReport>,
+    // Degradation ladder (see runtime::degradation)
+    degradation_level: AtomicU8,
+    degradation_escalations: AtomicU64,
+    degradation_recoveries: AtomicU64,
+    ingest_paused: AtomicBool,
+    ingest_restarts: AtomicU64,
+    ingest_paused_drops: AtomicU64,
 }
 
 #[allow(dead_code)]
@@ -73,7 +217,11 @@ impl Metrics {
             lineage_hits: AtomicU64::new(0),
             lineage_misses: AtomicU64::new(0),
             drops_by_type: std::array::from_fn(|_| AtomicU64::new(0)),
+            received_by_type: std::array::from_fn(|_| AtomicU64::new(0)),
+            silent_event_types: RwLock::new(Vec::new()),
             alerts_emitted_total: AtomicU64::new(0),
+            alerts_suppressed_total: AtomicU64::new(0),
+            alerts_held_back_total: AtomicU64::new(0),
             perf_poll_errors: AtomicU64::new(0),
             active_rules: AtomicUsize::new(0),
             rss_probe_mode: AtomicU8::new(0),
@@ -100,6 +248,25 @@ impl Metrics {
             slack_failed_total: AtomicU64::new(0),
             alerts_generated_total: AtomicU64::new(0),
             feedback_entries_total: AtomicU64::new(0),
+            loss: LossTracker::new(),
+            latency: LatencyTracker::new(),
+            ingest: IngestTracker::new(),
+            reorder: ReorderTracker::new(),
+            canary: CanaryTracker::new(),
+            cursor: CursorTracker::new(),
+            backlog: BacklogTracker::new(),
+            startup: StartupTimeline::new(),
+            bpf_maps: RwLock::new(Vec::new()),
+            page_faults_suppressed_total: AtomicU64::new(0),
+            page_faults_suppressed: RwLock::new(Vec::new()),
+            notification_queues: RwLock::new(Vec::new()),
+            sequencer: RwLock::new(SequencerReport::default()),
+            degradation_level: AtomicU8::new(0),
+            degradation_escalations: AtomicU64::new(0),
+            degradation_recoveries: AtomicU64::new(0),
+            ingest_paused: AtomicBool::new(false),
+            ingest_restarts: AtomicU64::new(0),
+            ingest_paused_drops: AtomicU64::new(0),
         }
     }
 
@@ -111,6 +278,14 @@ impl Metrics {
         const SAMPLE_N: u64 = 10; // keep 1 in N events for critical events
         let count = self.events_this_sec.fetch_add(1, Ordering::Relaxed) + 1;
         self.events_total.fetch_add(1, Ordering::Relaxed);
+        if let Some(slot) = self.received_by_type.get(event_type as usize) {
+            slot.fetch_add(1, Ordering::Relaxed);
+        }
+        if self.ingest_paused.load(Ordering::Relaxed) {
+            self.ingest_paused_drops.fetch_add(1, Ordering::Relaxed);
+            self.dropped_events_total.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
         if cap > 0 && count > cap {
             if event_type > 2 {
                 self.record_drop(event_type);
@@ -172,6 +347,28 @@ impl Metrics {
             .collect()
     }
 
+    /// Events received per event type id, before sampling or pausing.
+    pub fn received_by_type(&self) -> Vec<(u32, u64)> {
+        self.received_by_type
+            .iter()
+            .enumerate()
+            .map(|(idx, count)| (idx as u32, count.load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    pub fn set_silent_event_types(&self, silent: Vec<SilentEventType>) {
+        if let Ok(mut slot) = self.silent_event_types.write() {
+            *slot = silent;
+        }
+    }
+
+    pub fn silent_event_types(&self) -> Vec<SilentEventType> {
+        self.silent_event_types
+            .read()
+            .map(|silent| silent.clone())
+            .unwrap_or_default()
+    }
+
     pub fn inc_alerts_emitted(&self) {
         self.alerts_emitted_total.fetch_add(1, Ordering::Relaxed);
     }
@@ -180,6 +377,22 @@ impl Metrics {
         self.alerts_emitted_total.load(Ordering::Relaxed)
     }
 
+    pub fn inc_alerts_suppressed(&self) {
+        self.alerts_suppressed_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn alerts_suppressed(&self) -> u64 {
+        self.alerts_suppressed_total.load(Ordering::Relaxed)
+    }
+
+    pub fn inc_alerts_held_back(&self) {
+        self.alerts_held_back_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn alerts_held_back(&self) -> u64 {
+        self.alerts_held_back_total.load(Ordering::Relaxed)
+    }
+
     pub fn inc_perf_poll_error(&self) {
         self.perf_poll_errors.fetch_add(1, Ordering::Relaxed);
     }
@@ -192,6 +405,10 @@ impl Metrics {
         self.active_rules.fetch_add(count, Ordering::Relaxed);
     }
 
+    pub fn remove_active_rules(&self, count: usize) {
+        self.active_rules.fetch_sub(count, Ordering::Relaxed);
+    }
+
     pub fn active_rules(&self) -> usize {
         self.active_rules.load(Ordering::Relaxed)
     }
@@ -421,6 +638,139 @@ impl Metrics {
     pub fn feedback_entries(&self) -> u64 {
         self.feedback_entries_total.load(Ordering::Relaxed)
     }
+
+    pub fn loss(&self) -> &LossTracker {
+        &self.loss
+    }
+
+    pub fn latency(&self) -> &LatencyTracker {
+        &self.latency
+    }
+
+    pub fn ingest(&self) -> &IngestTracker {
+        &self.ingest
+    }
+
+    pub fn reorder(&self) -> &ReorderTracker {
+        &self.reorder
+    }
+
+    pub fn canary(&self) -> &CanaryTracker {
+        &self.canary
+    }
+
+    pub fn cursor(&self) -> &CursorTracker {
+        &self.cursor
+    }
+
+    pub fn backlog(&self) -> &BacklogTracker {
+        &self.backlog
+    }
+
+    pub fn startup(&self) -> &StartupTimeline {
+        &self.startup
+    }
+
+    pub fn set_bpf_maps(&self, maps: Vec<MapOccupancy>) {
+        if let Ok(mut slot) = self.bpf_maps.write() {
+            *slot = maps;
+        }
+    }
+
+    pub fn bpf_maps(&self) -> Vec<MapOccupancy> {
+        self.bpf_maps
+            .read()
+            .map(|maps| maps.clone())
+            .unwrap_or_default()
+    }
+
+    /// Publish the page-fault throttle's drops: the running total, including
+    /// exited pids, and the current count of each live pid, highest first.
+    pub fn set_page_faults_suppressed(&self, total: u64, pids: Vec<SuppressedFaults>) {
+        self.page_faults_suppressed_total
+            .store(total, Ordering::Relaxed);
+        if let Ok(mut slot) = self.page_faults_suppressed.write() {
+            *slot = pids;
+        }
+    }
+
+    pub fn page_faults_suppressed_total(&self) -> u64 {
+        self.page_faults_suppressed_total.load(Ordering::Relaxed)
+    }
+
+    pub fn page_faults_suppressed(&self) -> Vec<SuppressedFaults> {
+        self.page_faults_suppressed
+            .read()
+            .map(|pids| pids.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn set_notification_queues(&self, queues: Vec<NotificationQueueStats>) {
+        if let Ok(mut slot) = self.notification_queues.write() {
+            *slot = queues;
+        }
+    }
+
+    pub fn notification_queues(&self) -> Vec<NotificationQueueStats> {
+        self.notification_queues
+            .read()
+            .map(|queues| queues.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn set_sequencer_report(&self, report: SequencerReport) {
+        if let Ok(mut slot) = self.sequencer.write() {
+            *slot = report;
+        }
+    }
+
+    pub fn sequencer_report(&self) -> SequencerReport {
+        self.sequencer
+            .read()
+            .map(|report| report.clone())
+            .unwrap_or_default()
+    }
+
+    /// Record a move to `level` on the degradation ladder.
+    pub fn set_degradation_level(&self, level: u8) {
+        let previous = self.degradation_level.swap(level, Ordering::Relaxed);
+        if level > previous {
+            self.degradation_escalations.fetch_add(1, Ordering::Relaxed);
+        } else if level < previous {
+            self.degradation_recoveries.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn degradation_level(&self) -> u8 {
+        self.degradation_level.load(Ordering::Relaxed)
+    }
+
+    pub fn degradation_escalations(&self) -> u64 {
+        self.degradation_escalations.load(Ordering::Relaxed)
+    }
+
+    pub fn degradation_recoveries(&self) -> u64 {
+        self.degradation_recoveries.load(Ordering::Relaxed)
+    }
+
+    /// While paused, `record_event` drops every event.
+    pub fn set_ingest_paused(&self, paused: bool) {
+        if !self.ingest_paused.swap(paused, Ordering::Relaxed) && paused {
+            self.ingest_restarts.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn ingest_paused(&self) -> bool {
+        self.ingest_paused.load(Ordering::Relaxed)
+    }
+
+    pub fn ingest_restarts(&self) -> u64 {
+        self.ingest_restarts.load(Ordering::Relaxed)
+    }
+
+    pub fn ingest_paused_drops(&self) -> u64 {
+        self.ingest_paused_drops.load(Ordering::Relaxed)
+    }
 }
 
 impl Default for Metrics {
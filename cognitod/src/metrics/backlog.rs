@@ -0,0 +1,52 @@
+//! Catch-up through the sequencer backlog left behind a restored cursor,
+//! for `/status`.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BacklogStatus {
+    /// First backlogged ticket: the restored cursor
+    pub first_ticket: u64,
+    /// Tickets the kernel had handed out at startup; live reading starts here
+    pub kernel_head: u64,
+    pub total: u64,
+    /// Backlogged tickets not read yet
+    pub remaining: u64,
+    /// Events handed to the handlers
+    pub processed: u64,
+    /// Events older than `summarize_after_secs`, counted only
+    pub summarized: u64,
+    /// Tickets overwritten by live events before they were read
+    pub lost: u64,
+    /// Summarized events per event type
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub summarized_by_type: BTreeMap<String, u64>,
+    /// Most backlogged tickets read per poll
+    pub catchup_per_poll: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summarize_after_secs: Option<u64>,
+    pub polls: u64,
+    pub done: bool,
+}
+
+#[derive(Default)]
+pub struct BacklogTracker {
+    state: Mutex<Option<BacklogStatus>>,
+}
+
+impl BacklogTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&self, status: &BacklogStatus) {
+        *self.state.lock().unwrap() = Some(status.clone());
+    }
+
+    /// `None` while no consumer resumed with a backlog.
+    pub fn snapshot(&self) -> Option<BacklogStatus> {
+        self.state.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,57 @@
+//! Results of the `[canary]` end-to-end check.
+
+use serde::Serialize;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CanaryMetrics {
+    pub enabled: bool,
+    /// Whether the last run's events reached the context store in time
+    pub up: bool,
+    pub runs: u64,
+    pub failures: u64,
+    pub consecutive_failures: u64,
+    /// Spawn to both events stored, for the last successful run
+    pub last_latency_ms: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Default)]
+pub struct CanaryTracker {
+    state: Mutex<CanaryMetrics>,
+}
+
+impl CanaryTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn enable(&self) {
+        self.state.lock().unwrap().enabled = true;
+    }
+
+    /// Record a run; returns the failures in a row including this one.
+    pub fn record(&self, result: Result<u64, String>) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        state.runs += 1;
+        match result {
+            Ok(latency_ms) => {
+                state.up = true;
+                state.consecutive_failures = 0;
+                state.last_latency_ms = Some(latency_ms);
+                state.last_error = None;
+            }
+            Err(e) => {
+                state.up = false;
+                state.failures += 1;
+                state.consecutive_failures += 1;
+                state.last_error = Some(e);
+            }
+        }
+        state.consecutive_failures
+    }
+
+    pub fn snapshot(&self) -> CanaryMetrics {
+        self.state.lock().unwrap().clone()
+    }
+}
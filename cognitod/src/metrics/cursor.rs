@@ -0,0 +1,99 @@
+//! Sequencer cursor restored at startup and its checkpoints, for `/status`.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// How the saved cursor was matched against the kernel's ticket counter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Decision {
+    /// Nothing saved; reading starts at the producers' position
+    Fresh,
+    /// The saved position is still in the ring; reading continues there
+    Resume,
+    /// Producers lapped the saved position; reading starts at the oldest
+    /// slot still in the ring
+    Overrun,
+    /// The ticket counter restarted (reboot, or the maps were created
+    /// afresh); the saved position and delivery marks no longer apply
+    Reset,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Reconciliation {
+    pub decision: Decision,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saved_cursor: Option<u64>,
+    /// Tickets the kernel had handed out when the cursor was restored
+    pub kernel_head: u64,
+    /// The ring and ticket counters were found pinned by a previous run
+    pub maps_reused: bool,
+    /// Ticket the consumer starts at
+    pub cursor: u64,
+    /// Tickets read again although the consumer had read them before; the
+    /// bound on duplicates for handlers without a delivery mark
+    pub replayed: u64,
+    /// Tickets overwritten before they could be read again
+    pub lost: u64,
+    /// Per handler: tickets below the mark are not delivered again
+    pub delivered: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CursorStatus {
+    pub path: String,
+    pub reconciliation: Reconciliation,
+    pub checkpoints: u64,
+    /// Cursor written by the last successful checkpoint
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checkpointed_cursor: Option<u64>,
+    /// Unix time of the last successful checkpoint
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_checkpoint: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+#[derive(Default)]
+pub struct CursorTracker {
+    state: Mutex<Option<CursorStatus>>,
+}
+
+impl CursorTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn restored(&self, path: String, reconciliation: Reconciliation) {
+        *self.state.lock().unwrap() = Some(CursorStatus {
+            path,
+            reconciliation,
+            checkpoints: 0,
+            checkpointed_cursor: None,
+            last_checkpoint: None,
+            last_error: None,
+        });
+    }
+
+    pub fn checkpoint(&self, result: Result<(u64, u64), String>) {
+        let mut state = self.state.lock().unwrap();
+        let Some(status) = state.as_mut() else {
+            return;
+        };
+        match result {
+            Ok((cursor, at)) => {
+                status.checkpoints += 1;
+                status.checkpointed_cursor = Some(cursor);
+                status.last_checkpoint = Some(at);
+                status.last_error = None;
+            }
+            Err(e) => status.last_error = Some(e),
+        }
+    }
+
+    /// `None` while no sequencer consumer persists its cursor.
+    pub fn snapshot(&self) -> Option<CursorStatus> {
+        self.state.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,180 @@
+//! Ingest volume and processing time per event type.
+//!
+//! An event counts as ingested once it passes sampling in `record_event`;
+//! `bytes` is its size as read from the kernel buffer. Processing time runs
+//! from when a worker picks the event up until the handlers and the context
+//! store are done with it. Time spent queued before that is covered by
+//! `latency`.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use super::RECEIVED_TYPE_SLOTS;
+use super::latency::{BUCKETS_SECS, quantile};
+use crate::event_schema::event_type_name;
+use crate::startup::HistogramSnapshot;
+
+#[derive(Default)]
+struct TypeIngest {
+    events: AtomicU64,
+    bytes: AtomicU64,
+    /// Per-bucket processing time counts; the last slot is `+Inf`.
+    buckets: [AtomicU64; BUCKETS_SECS.len() + 1],
+    processing_ns: AtomicU64,
+}
+
+/// One row of the `/status` event type breakdown.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct EventTypeIngest {
+    #[serde(skip)]
+    pub id: u32,
+    pub event_type: &'static str,
+    pub events: u64,
+    pub bytes: u64,
+    /// Share of all ingested events
+    pub share_pct: f64,
+    pub avg_processing_us: f64,
+    /// Upper bound of the bucket holding the 99th percentile; `None` past
+    /// the last bucket or before any sample.
+    pub p99_processing_us: Option<f64>,
+}
+
+#[derive(Default)]
+pub struct IngestTracker {
+    types: [TypeIngest; RECEIVED_TYPE_SLOTS],
+}
+
+impl IngestTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn slot(&self, event_type: u32) -> Option<&TypeIngest> {
+        self.types.get(event_type as usize)
+    }
+
+    /// Count an event of `bytes` that passed sampling.
+    pub fn record(&self, event_type: u32, bytes: usize) {
+        if let Some(slot) = self.slot(event_type) {
+            slot.events.fetch_add(1, Ordering::Relaxed);
+            slot.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub fn observe_processing(&self, event_type: u32, elapsed: Duration) {
+        let Some(slot) = self.slot(event_type) else {
+            return;
+        };
+        let secs = elapsed.as_secs_f64();
+        let bucket = BUCKETS_SECS
+            .iter()
+            .position(|le| secs <= *le)
+            .unwrap_or(BUCKETS_SECS.len());
+        slot.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        slot.processing_ns
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Processing time histogram of `event_type`, in the shape of
+    /// [`super::latency::LatencyTracker::snapshot`].
+    pub fn processing(&self, event_type: u32) -> HistogramSnapshot {
+        let Some(slot) = self.slot(event_type) else {
+            return HistogramSnapshot {
+                buckets: Vec::new(),
+                count: 0,
+                sum_secs: 0.0,
+            };
+        };
+        let mut cumulative = 0;
+        let buckets = BUCKETS_SECS
+            .iter()
+            .zip(&slot.buckets)
+            .map(|(le, n)| {
+                cumulative += n.load(Ordering::Relaxed);
+                (*le, cumulative)
+            })
+            .collect();
+        HistogramSnapshot {
+            buckets,
+            count: slot.buckets.iter().map(|n| n.load(Ordering::Relaxed)).sum(),
+            sum_secs: slot.processing_ns.load(Ordering::Relaxed) as f64 / 1e9,
+        }
+    }
+
+    /// Event types seen so far, busiest first.
+    pub fn breakdown(&self) -> Vec<EventTypeIngest> {
+        let total: u64 = self
+            .types
+            .iter()
+            .map(|slot| slot.events.load(Ordering::Relaxed))
+            .sum();
+        let mut rows: Vec<EventTypeIngest> = self
+            .types
+            .iter()
+            .enumerate()
+            .filter_map(|(id, slot)| {
+                let events = slot.events.load(Ordering::Relaxed);
+                if events == 0 {
+                    return None;
+                }
+                let counts: Vec<u64> = slot
+                    .buckets
+                    .iter()
+                    .map(|n| n.load(Ordering::Relaxed))
+                    .collect();
+                let processed: u64 = counts.iter().sum();
+                let processing_ns = slot.processing_ns.load(Ordering::Relaxed);
+                Some(EventTypeIngest {
+                    id: id as u32,
+                    event_type: event_type_name(id as u32),
+                    events,
+                    bytes: slot.bytes.load(Ordering::Relaxed),
+                    share_pct: events as f64 * 100.0 / total as f64,
+                    avg_processing_us: if processed == 0 {
+                        0.0
+                    } else {
+                        processing_ns as f64 / processed as f64 / 1e3
+                    },
+                    p99_processing_us: quantile(&counts, 0.99)
+                        .filter(|secs| secs.is_finite())
+                        .map(|secs| secs * 1e6),
+                })
+            })
+            .collect();
+        rows.sort_by(|a, b| b.events.cmp(&a.events).then(a.id.cmp(&b.id)));
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linnix_ai_ebpf_common::EventType;
+
+    #[test]
+    fn breaks_down_by_type() {
+        let tracker = IngestTracker::new();
+        for _ in 0..3 {
+            tracker.record(EventType::Net as u32, 64);
+            tracker.observe_processing(EventType::Net as u32, Duration::from_micros(50));
+        }
+        tracker.record(EventType::Exec as u32, 200);
+        tracker.observe_processing(EventType::Exec as u32, Duration::from_millis(2));
+        tracker.record(99, 10);
+
+        let rows = tracker.breakdown();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].event_type, "net");
+        assert_eq!((rows[0].events, rows[0].bytes), (3, 192));
+        assert_eq!(rows[0].share_pct, 75.0);
+        assert!((rows[0].avg_processing_us - 50.0).abs() < 1e-6);
+        assert_eq!(rows[0].p99_processing_us, Some(100.0));
+        assert_eq!(rows[1].event_type, "exec");
+        assert_eq!(rows[1].p99_processing_us, Some(2500.0));
+
+        let hist = tracker.processing(EventType::Exec as u32);
+        assert_eq!(hist.count, 1);
+        assert_eq!(hist.buckets[4], (0.0025, 1));
+    }
+}
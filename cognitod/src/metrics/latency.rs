@@ -0,0 +1,172 @@
+//! End-to-end event latency, kernel to API.
+//!
+//! Events carry the kernel's `bpf_ktime_get_ns` stamp (`CLOCK_MONOTONIC`).
+//! Each pipeline stage records how old an event is when it gets there:
+//! `receive` when the perf listener reads it, `dispatch` when it is handed to
+//! the handlers and `deliver` when it is written to an SSE subscriber, so
+//! `deliver` is the staleness of what `/stream` serves. Events without a
+//! usable stamp (userspace sources, replays) are counted but not measured.
+
+use nix::time::{ClockId, clock_gettime};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::startup::HistogramSnapshot;
+
+/// Histogram bucket upper bounds in seconds.
+pub const BUCKETS_SECS: [f64; 14] = [
+    0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0, 5.0, 30.0,
+];
+
+/// Stamps further in the past than this are not kernel timestamps.
+const MAX_PLAUSIBLE_NS: u64 = 3_600_000_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    Receive,
+    Dispatch,
+    Deliver,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 3] = [Stage::Receive, Stage::Dispatch, Stage::Deliver];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Receive => "receive",
+            Stage::Dispatch => "dispatch",
+            Stage::Deliver => "deliver",
+        }
+    }
+}
+
+pub fn monotonic_ns() -> Option<u64> {
+    let now = clock_gettime(ClockId::CLOCK_MONOTONIC).ok()?;
+    Some((now.tv_sec() as u64) * 1_000_000_000 + (now.tv_nsec() as u64))
+}
+
+#[derive(Default)]
+struct StageHistogram {
+    /// Per-bucket counts; the last slot is `+Inf`.
+    buckets: [AtomicU64; BUCKETS_SECS.len() + 1],
+    sum_ns: AtomicU64,
+}
+
+#[derive(Default)]
+pub struct LatencyTracker {
+    stages: [StageHistogram; 3],
+    unstamped: AtomicU64,
+}
+
+impl LatencyTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that an event stamped `ts_ns` reached `stage` now.
+    pub fn observe(&self, stage: Stage, ts_ns: u64) {
+        match monotonic_ns() {
+            Some(now) if ts_ns != 0 && ts_ns <= now && now - ts_ns <= MAX_PLAUSIBLE_NS => {
+                self.observe_ns(stage, now - ts_ns)
+            }
+            _ => {
+                self.unstamped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn observe_ns(&self, stage: Stage, latency_ns: u64) {
+        let hist = &self.stages[stage as usize];
+        let secs = latency_ns as f64 / 1e9;
+        let slot = BUCKETS_SECS
+            .iter()
+            .position(|le| secs <= *le)
+            .unwrap_or(BUCKETS_SECS.len());
+        hist.buckets[slot].fetch_add(1, Ordering::Relaxed);
+        hist.sum_ns.fetch_add(latency_ns, Ordering::Relaxed);
+    }
+
+    /// Events whose stamp could not be measured, over all stages.
+    pub fn unstamped(&self) -> u64 {
+        self.unstamped.load(Ordering::Relaxed)
+    }
+
+    /// Per-bucket (not cumulative) counts for `stage`, `+Inf` last.
+    pub fn counts(&self, stage: Stage) -> Vec<u64> {
+        self.stages[stage as usize]
+            .buckets
+            .iter()
+            .map(|n| n.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    pub fn snapshot(&self, stage: Stage) -> HistogramSnapshot {
+        let counts = self.counts(stage);
+        let mut cumulative = 0;
+        let buckets = BUCKETS_SECS
+            .iter()
+            .zip(&counts)
+            .map(|(le, n)| {
+                cumulative += n;
+                (*le, cumulative)
+            })
+            .collect();
+        HistogramSnapshot {
+            buckets,
+            count: counts.iter().sum(),
+            sum_secs: self.stages[stage as usize].sum_ns.load(Ordering::Relaxed) as f64 / 1e9,
+        }
+    }
+}
+
+/// Upper bound, in seconds, of the bucket holding quantile `q` of `counts`
+/// (as returned by [`LatencyTracker::counts`], or a difference of two such
+/// reads). `f64::INFINITY` when it falls past the last bound; `None` for no
+/// samples.
+pub fn quantile(counts: &[u64], q: f64) -> Option<f64> {
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return None;
+    }
+    let rank = ((total as f64) * q).ceil().max(1.0) as u64;
+    let mut seen = 0;
+    for (i, n) in counts.iter().enumerate() {
+        seen += n;
+        if seen >= rank {
+            return Some(BUCKETS_SECS.get(i).copied().unwrap_or(f64::INFINITY));
+        }
+    }
+    Some(f64::INFINITY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_latency_per_stage_and_estimates_p99() {
+        let tracker = LatencyTracker::new();
+        for _ in 0..98 {
+            tracker.observe_ns(Stage::Deliver, 200_000);
+        }
+        tracker.observe_ns(Stage::Deliver, 40_000_000);
+        tracker.observe_ns(Stage::Deliver, 60_000_000_000);
+        tracker.observe_ns(Stage::Receive, 50_000);
+
+        let snap = tracker.snapshot(Stage::Deliver);
+        assert_eq!(snap.count, 100);
+        assert_eq!(snap.buckets[1], (0.00025, 98));
+        assert_eq!(snap.buckets.last(), Some(&(30.0, 99)));
+        assert_eq!(tracker.snapshot(Stage::Dispatch).count, 0);
+
+        let counts = tracker.counts(Stage::Deliver);
+        assert_eq!(quantile(&counts, 0.5), Some(0.00025));
+        assert_eq!(quantile(&counts, 0.99), Some(0.05));
+        assert_eq!(quantile(&counts, 1.0), Some(f64::INFINITY));
+        assert_eq!(quantile(&tracker.counts(Stage::Dispatch), 0.99), None);
+
+        tracker.observe(Stage::Receive, 0);
+        assert_eq!(tracker.unstamped(), 1);
+    }
+}
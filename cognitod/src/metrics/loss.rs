@@ -0,0 +1,360 @@
+//! Event loss accounting based on producer sequence numbers.
+//!
+//! Every perf event carries a per-CPU `seq` stamped by the eBPF producer.
+//! Consumers feed each event through [`StreamLoss::observe`], the handle of
+//! the stream (perf buffer) it came from; any jump in `seq` is counted as lost
+//! events and attributed to the time bucket in which the gap was noticed.
+//! Losses cannot be tied to an event type directly (the dropped records never
+//! reach us), so the report estimates a per-type split from the mix of events
+//! received in the same bucket.
+//!
+//! Each stream keeps its counters behind its own lock, taken only by its
+//! consumer and while a report is built, so consumers never contend.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use super::EVENT_TYPE_SLOTS;
+use crate::utils::now_secs;
+
+/// Width of a loss bucket in seconds.
+pub const BUCKET_SECS: u64 = 60;
+/// Buckets retained (one hour at the default width).
+pub const MAX_BUCKETS: usize = 60;
+
+#[derive(Debug, Clone, Default)]
+struct LossBucket {
+    start: u64,
+    received: [u64; EVENT_TYPE_SLOTS],
+    gap_lost: u64,
+    kernel_lost: u64,
+}
+
+impl LossBucket {
+    fn received_total(&self) -> u64 {
+        self.received.iter().sum()
+    }
+
+    fn lost_total(&self) -> u64 {
+        self.gap_lost.max(self.kernel_lost)
+    }
+
+    fn merge(&mut self, other: &LossBucket) {
+        for (slot, count) in self.received.iter_mut().zip(other.received) {
+            *slot += count;
+        }
+        self.gap_lost += other.gap_lost;
+        self.kernel_lost += other.kernel_lost;
+    }
+}
+
+/// Counters of one stream, or of the losses the kernel reported.
+#[derive(Default)]
+struct LossState {
+    last_seq: Option<u64>,
+    buckets: VecDeque<LossBucket>,
+    received_total: u64,
+    gap_lost_total: u64,
+    kernel_lost_total: u64,
+    unsequenced_total: u64,
+}
+
+impl LossState {
+    fn bucket_mut(&mut self, now: u64) -> &mut LossBucket {
+        let start = now - now % BUCKET_SECS;
+        if self.buckets.back().map(|b| b.start) != Some(start) {
+            self.buckets.push_back(LossBucket {
+                start,
+                ..LossBucket::default()
+            });
+            while self.buckets.len() > MAX_BUCKETS {
+                self.buckets.pop_front();
+            }
+        }
+        self.buckets.back_mut().expect("bucket just pushed")
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeLoss {
+    pub event_type: u32,
+    pub received: u64,
+    pub estimated_lost: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LossInterval {
+    pub start: u64,
+    pub end: u64,
+    pub received: u64,
+    /// Gaps observed in producer sequence numbers.
+    pub gap_lost: u64,
+    /// Lost samples reported by the kernel perf ring.
+    pub kernel_lost: u64,
+    pub loss_pct: f64,
+    pub lossy: bool,
+    pub by_type: Vec<TypeLoss>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LossReport {
+    pub transport: &'static str,
+    pub bucket_secs: u64,
+    pub received_total: u64,
+    pub gap_lost_total: u64,
+    pub kernel_lost_total: u64,
+    /// Events without a sequence number (older BPF object); not gap-checked.
+    pub unsequenced_total: u64,
+    pub loss_pct: f64,
+    pub intervals: Vec<LossInterval>,
+}
+
+fn loss_pct(received: u64, lost: u64) -> f64 {
+    let total = received + lost;
+    if total == 0 {
+        0.0
+    } else {
+        lost as f64 * 100.0 / total as f64
+    }
+}
+
+pub struct LossTracker {
+    transport: Mutex<&'static str>,
+    /// Counters by stream; locked to hand out a stream and to report
+    streams: Mutex<HashMap<usize, Arc<Mutex<LossState>>>>,
+    kernel: Mutex<LossState>,
+}
+
+impl Default for LossTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LossTracker {
+    pub fn new() -> Self {
+        Self {
+            transport: Mutex::new("unknown"),
+            streams: Mutex::new(HashMap::new()),
+            kernel: Mutex::new(LossState::default()),
+        }
+    }
+
+    pub fn set_transport(&self, transport: &'static str) {
+        *lock(&self.transport) = transport;
+    }
+
+    /// Handle for the consumer of `stream`; a stream read again later
+    /// continues from its last sequence number.
+    pub fn stream(&self, stream: usize) -> StreamLoss {
+        StreamLoss {
+            state: Arc::clone(lock(&self.streams).entry(stream).or_default()),
+        }
+    }
+
+    /// Record lost samples reported by the kernel (perf `lost` records).
+    pub fn record_kernel_lost(&self, lost: u64) {
+        self.record_kernel_lost_at(now_secs(), lost);
+    }
+
+    fn record_kernel_lost_at(&self, now: u64, lost: u64) {
+        if lost == 0 {
+            return;
+        }
+        let mut state = lock(&self.kernel);
+        state.kernel_lost_total += lost;
+        state.bucket_mut(now).kernel_lost += lost;
+    }
+
+    pub fn report(&self) -> LossReport {
+        let streams: Vec<_> = lock(&self.streams).values().cloned().collect();
+        let mut totals = LossState::default();
+        let mut buckets: BTreeMap<u64, LossBucket> = BTreeMap::new();
+        let mut add = |state: &LossState| {
+            totals.received_total += state.received_total;
+            totals.gap_lost_total += state.gap_lost_total;
+            totals.kernel_lost_total += state.kernel_lost_total;
+            totals.unsequenced_total += state.unsequenced_total;
+            for bucket in &state.buckets {
+                buckets
+                    .entry(bucket.start)
+                    .or_insert_with(|| LossBucket {
+                        start: bucket.start,
+                        ..LossBucket::default()
+                    })
+                    .merge(bucket);
+            }
+        };
+        for stream in &streams {
+            add(&lock(stream));
+        }
+        add(&lock(&self.kernel));
+
+        let skip = buckets.len().saturating_sub(MAX_BUCKETS);
+        let intervals = buckets
+            .values()
+            .skip(skip)
+            .map(|b| {
+                let received = b.received_total();
+                let lost = b.lost_total();
+                let by_type = b
+                    .received
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, count)| **count > 0)
+                    .map(|(event_type, count)| TypeLoss {
+                        event_type: event_type as u32,
+                        received: *count,
+                        estimated_lost: if received == 0 {
+                            0
+                        } else {
+                            (lost as f64 * *count as f64 / received as f64).round() as u64
+                        },
+                    })
+                    .collect();
+                LossInterval {
+                    start: b.start,
+                    end: b.start + BUCKET_SECS,
+                    received,
+                    gap_lost: b.gap_lost,
+                    kernel_lost: b.kernel_lost,
+                    loss_pct: loss_pct(received, lost),
+                    lossy: lost > 0,
+                    by_type,
+                }
+            })
+            .collect();
+
+        LossReport {
+            transport: *lock(&self.transport),
+            bucket_secs: BUCKET_SECS,
+            received_total: totals.received_total,
+            gap_lost_total: totals.gap_lost_total,
+            kernel_lost_total: totals.kernel_lost_total,
+            unsequenced_total: totals.unsequenced_total,
+            loss_pct: loss_pct(
+                totals.received_total,
+                totals.gap_lost_total.max(totals.kernel_lost_total),
+            ),
+            intervals,
+        }
+    }
+
+    /// Lossy intervals overlapping `[start, end]` (unix seconds), used to
+    /// annotate stats series.
+    pub fn lossy_intervals(&self, start: u64, end: u64) -> Vec<LossInterval> {
+        self.report()
+            .intervals
+            .into_iter()
+            .filter(|i| i.lossy && i.end > start && i.start <= end)
+            .collect()
+    }
+}
+
+/// Loss accounting of one stream, held by its consumer.
+pub struct StreamLoss {
+    state: Arc<Mutex<LossState>>,
+}
+
+impl StreamLoss {
+    /// Record an event carrying producer sequence `seq`. Returns the number
+    /// of events found missing before it.
+    pub fn observe(&self, seq: u64, event_type: u32) -> u64 {
+        self.observe_at(now_secs(), seq, event_type)
+    }
+
+    fn observe_at(&self, now: u64, seq: u64, event_type: u32) -> u64 {
+        let mut state = lock(&self.state);
+        state.received_total += 1;
+
+        let gap = if seq == 0 {
+            state.unsequenced_total += 1;
+            0
+        } else {
+            match state.last_seq.replace(seq) {
+                // seq going backwards means the producer restarted (BPF reload)
+                Some(last) if seq > last => seq - last - 1,
+                _ => 0,
+            }
+        };
+        state.gap_lost_total += gap;
+
+        let bucket = state.bucket_mut(now);
+        if let Some(slot) = bucket.received.get_mut(event_type as usize) {
+            *slot += 1;
+        }
+        bucket.gap_lost += gap;
+        gap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_gaps_per_stream() {
+        let tracker = LossTracker::new();
+        let (first, second) = (tracker.stream(0), tracker.stream(1));
+        assert_eq!(first.observe_at(120, 1, 0), 0);
+        assert_eq!(second.observe_at(120, 1, 1), 0);
+        assert_eq!(first.observe_at(120, 2, 0), 0);
+        // stream 0 skips 3..=5, also through a handle taken later
+        assert_eq!(tracker.stream(0).observe_at(121, 6, 0), 3);
+        // stream 1 is independent
+        assert_eq!(second.observe_at(121, 2, 1), 0);
+
+        let report = tracker.report();
+        assert_eq!(report.received_total, 5);
+        assert_eq!(report.gap_lost_total, 3);
+        assert_eq!(report.intervals.len(), 1);
+        let interval = &report.intervals[0];
+        assert!(interval.lossy);
+        assert_eq!(interval.start, 120);
+        let exec = interval.by_type.iter().find(|t| t.event_type == 0).unwrap();
+        assert_eq!(exec.received, 3);
+        assert_eq!(exec.estimated_lost, 2);
+    }
+
+    #[test]
+    fn restart_and_unsequenced_events_are_not_losses() {
+        let tracker = LossTracker::new();
+        let stream = tracker.stream(0);
+        stream.observe_at(0, 100, 0);
+        assert_eq!(stream.observe_at(0, 1, 0), 0, "producer restart");
+        assert_eq!(stream.observe_at(0, 0, 0), 0, "unsequenced");
+        let report = tracker.report();
+        assert_eq!(report.gap_lost_total, 0);
+        assert_eq!(report.unsequenced_total, 1);
+    }
+
+    #[test]
+    fn buckets_roll_and_mark_lossy_intervals() {
+        let tracker = LossTracker::new();
+        let stream = tracker.stream(0);
+        stream.observe_at(0, 1, 2);
+        stream.observe_at(60, 2, 2);
+        tracker.record_kernel_lost_at(61, 4);
+        stream.observe_at(120, 3, 2);
+
+        let report = tracker.report();
+        assert_eq!(report.intervals.len(), 3);
+        assert_eq!(report.kernel_lost_total, 4);
+        let lossy = tracker.lossy_intervals(0, 200);
+        assert_eq!(lossy.len(), 1);
+        assert_eq!(lossy[0].start, 60);
+        assert_eq!(lossy[0].kernel_lost, 4);
+
+        for i in 0..(MAX_BUCKETS as u64 + 5) {
+            stream.observe_at(200 + i * BUCKET_SECS, 4 + i, 2);
+        }
+        assert_eq!(tracker.report().intervals.len(), MAX_BUCKETS);
+    }
+}
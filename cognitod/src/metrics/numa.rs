@@ -0,0 +1,59 @@
+//! Page placement of the sequencer rings, for `/debug/sequencer`.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Pages of a mapping counted by the node they reside on.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PagePlacement {
+    pub by_node: BTreeMap<u32, usize>,
+    /// Pages the kernel could not place (not present or not queryable).
+    pub unresolved: usize,
+}
+
+impl PagePlacement {
+    pub fn total(&self) -> usize {
+        self.by_node.values().sum()
+    }
+
+    /// Share of the placed pages on `node`; `None` when nothing was placed.
+    pub fn share_on(&self, node: u32) -> Option<f64> {
+        let total = self.total();
+        (total > 0).then(|| *self.by_node.get(&node).unwrap_or(&0) as f64 / total as f64)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PartitionReport {
+    pub partition: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consumer_cpus: Option<String>,
+    pub events: u64,
+    pub events_reaped: u64,
+    pub events_lost: u64,
+    pub ordering_violations: u64,
+    /// Where the ring's pages are; `None` when move_pages(2) is unavailable.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pages: Option<PagePlacement>,
+    /// Share of the ring's pages on the node that writes it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local_page_share: Option<f64>,
+}
+
+/// Measurements of a sequencer run, comparable between the shared ring and
+/// the partitioned rings.
+#[derive(Debug, Clone, Serialize)]
+pub struct NumaReport {
+    pub nodes: usize,
+    pub partitions: Vec<PartitionReport>,
+    pub merged: u64,
+    pub merge_inversions: u64,
+    /// Events read from the partitions and waiting to be merged.
+    pub pending: usize,
+    /// Share of ring writes that land on a page of another node: weighted by
+    /// events per partition, or for a shared ring by CPUs per node.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_page_share: Option<f64>,
+}
@@ -0,0 +1,70 @@
+//! Counters of the `[ordering]` reorder stage.
+//!
+//! The stage holds events for handlers that need them in kernel timestamp
+//! order. Events that arrive after a later one was already delivered are
+//! `out_of_window`: they are delivered at once, out of order. `forced`
+//! counts events released before their delay was up because the buffer was
+//! full.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ReorderStats {
+    /// Events held right now
+    pub buffered: u64,
+    /// Events delivered in order
+    pub released: u64,
+    pub out_of_window: u64,
+    pub forced: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ReorderMetrics {
+    pub enabled: bool,
+    pub max_delay_ms: u64,
+    #[serde(flatten)]
+    pub stats: ReorderStats,
+}
+
+#[derive(Default)]
+pub struct ReorderTracker {
+    enabled: AtomicBool,
+    max_delay_ms: AtomicU64,
+    buffered: AtomicU64,
+    released: AtomicU64,
+    out_of_window: AtomicU64,
+    forced: AtomicU64,
+}
+
+impl ReorderTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn enable(&self, max_delay_ms: u64) {
+        self.max_delay_ms.store(max_delay_ms, Ordering::Relaxed);
+        self.enabled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn set(&self, stats: ReorderStats) {
+        self.buffered.store(stats.buffered, Ordering::Relaxed);
+        self.released.store(stats.released, Ordering::Relaxed);
+        self.out_of_window
+            .store(stats.out_of_window, Ordering::Relaxed);
+        self.forced.store(stats.forced, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> ReorderMetrics {
+        ReorderMetrics {
+            enabled: self.enabled.load(Ordering::Relaxed),
+            max_delay_ms: self.max_delay_ms.load(Ordering::Relaxed),
+            stats: ReorderStats {
+                buffered: self.buffered.load(Ordering::Relaxed),
+                released: self.released.load(Ordering::Relaxed),
+                out_of_window: self.out_of_window.load(Ordering::Relaxed),
+                forced: self.forced.load(Ordering::Relaxed),
+            },
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Startup phase timings, logged once the API is up and served in `/status`.
+
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StartupPhase {
+    pub name: &'static str,
+    pub ms: u64,
+    /// Ran alongside the other phases instead of before them
+    pub background: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StartupReport {
+    /// Until the API was listening
+    pub total_ms: u64,
+    pub phases: Vec<StartupPhase>,
+    /// `hit`, `miss` or `disabled`; absent without kernel BTF
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub btf_cache: Option<&'static str>,
+}
+
+struct State {
+    phases: Vec<StartupPhase>,
+    btf_cache: Option<&'static str>,
+    total: Option<Duration>,
+}
+
+pub struct StartupTimeline {
+    started: Instant,
+    state: Mutex<State>,
+}
+
+impl Default for StartupTimeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StartupTimeline {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            state: Mutex::new(State {
+                phases: Vec::new(),
+                btf_cache: None,
+                total: None,
+            }),
+        }
+    }
+
+    pub fn record(&self, name: &'static str, elapsed: Duration, background: bool) {
+        self.state.lock().unwrap().phases.push(StartupPhase {
+            name,
+            ms: elapsed.as_millis() as u64,
+            background,
+        });
+    }
+
+    /// Run `f` as the phase `name`.
+    pub fn time<T>(&self, name: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let out = f();
+        self.record(name, start.elapsed(), false);
+        out
+    }
+
+    pub fn set_btf_cache(&self, outcome: &'static str) {
+        self.state.lock().unwrap().btf_cache = Some(outcome);
+    }
+
+    /// Close the timeline; later calls keep the first total.
+    pub fn finish(&self) -> StartupReport {
+        let mut state = self.state.lock().unwrap();
+        state.total.get_or_insert_with(|| self.started.elapsed());
+        drop(state);
+        self.snapshot().expect("finished")
+    }
+
+    /// `None` until startup finished.
+    pub fn snapshot(&self) -> Option<StartupReport> {
+        let state = self.state.lock().unwrap();
+        Some(StartupReport {
+            total_ms: state.total?.as_millis() as u64,
+            phases: state.phases.clone(),
+            btf_cache: state.btf_cache,
+        })
+    }
+}
+
+impl StartupReport {
+    /// One-line breakdown for the log, `btf 310ms, bpf_load 220ms, ...`.
+    pub fn summary(&self) -> String {
+        self.phases
+            .iter()
+            .map(|phase| {
+                format!(
+                    "{} {}ms{}",
+                    phase.name,
+                    phase.ms,
+                    if phase.background {
+                        " (background)"
+                    } else {
+                        ""
+                    }
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
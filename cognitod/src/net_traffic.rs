@@ -0,0 +1,253 @@
+//! Network traffic by socket family and direction
+//!
+//! Net events carry the operation in `aux` (`NetOp`): TCP, UDP or Unix
+//! (stream and datagram together), sent or received. The context store keeps
+//! a [`NetCounters`] per live process and one for the node: byte totals for
+//! each of the six family/direction pairs, and their rates over the last
+//! completed `WINDOW`. Rates are what `/processes` shows, what rule
+//! conditions see as `tcp_tx_rate`, `udp_rx_rate` and so on, and what the
+//! node-wide Prometheus gauges report; totals back the byte counters.
+
+use linnix_ai_ebpf_common::NetOp;
+use serde::Serialize;
+use std::time::Duration;
+
+/// Span over which rates are measured.
+pub const WINDOW: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Family {
+    Tcp,
+    Udp,
+    Unix,
+}
+
+impl Family {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Family::Tcp => "tcp",
+            Family::Udp => "udp",
+            Family::Unix => "unix",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Tx,
+    Rx,
+}
+
+impl Direction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Direction::Tx => "tx",
+            Direction::Rx => "rx",
+        }
+    }
+}
+
+/// Family/direction pairs in slot order.
+pub const SLOTS: [(Family, Direction); 6] = [
+    (Family::Tcp, Direction::Tx),
+    (Family::Tcp, Direction::Rx),
+    (Family::Udp, Direction::Tx),
+    (Family::Udp, Direction::Rx),
+    (Family::Unix, Direction::Tx),
+    (Family::Unix, Direction::Rx),
+];
+
+/// Rule and filter variable for each slot, bytes per second.
+pub const RATE_VARIABLES: [&str; 6] = [
+    "tcp_tx_rate",
+    "tcp_rx_rate",
+    "udp_tx_rate",
+    "udp_rx_rate",
+    "unix_tx_rate",
+    "unix_rx_rate",
+];
+
+/// Family and direction of a net event's `aux`.
+pub fn classify(op: u32) -> Option<(Family, Direction)> {
+    let pair = match op {
+        x if x == NetOp::TcpSend as u32 => (Family::Tcp, Direction::Tx),
+        x if x == NetOp::TcpRecv as u32 => (Family::Tcp, Direction::Rx),
+        x if x == NetOp::UdpSend as u32 => (Family::Udp, Direction::Tx),
+        x if x == NetOp::UdpRecv as u32 => (Family::Udp, Direction::Rx),
+        x if x == NetOp::UnixStreamSend as u32 || x == NetOp::UnixDgramSend as u32 => {
+            (Family::Unix, Direction::Tx)
+        }
+        x if x == NetOp::UnixStreamRecv as u32 || x == NetOp::UnixDgramRecv as u32 => {
+            (Family::Unix, Direction::Rx)
+        }
+        _ => return None,
+    };
+    Some(pair)
+}
+
+fn slot(family: Family, direction: Direction) -> usize {
+    SLOTS
+        .iter()
+        .position(|pair| *pair == (family, direction))
+        .unwrap_or_default()
+}
+
+/// Bytes per second by family and direction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct NetRates {
+    pub tcp_tx_rate: f64,
+    pub tcp_rx_rate: f64,
+    pub udp_tx_rate: f64,
+    pub udp_rx_rate: f64,
+    pub unix_tx_rate: f64,
+    pub unix_rx_rate: f64,
+}
+
+impl NetRates {
+    fn from_slots(rates: [f64; 6]) -> Self {
+        let [
+            tcp_tx_rate,
+            tcp_rx_rate,
+            udp_tx_rate,
+            udp_rx_rate,
+            unix_tx_rate,
+            unix_rx_rate,
+        ] = rates;
+        Self {
+            tcp_tx_rate,
+            tcp_rx_rate,
+            udp_tx_rate,
+            udp_rx_rate,
+            unix_tx_rate,
+            unix_rx_rate,
+        }
+    }
+
+    fn slots(&self) -> [f64; 6] {
+        [
+            self.tcp_tx_rate,
+            self.tcp_rx_rate,
+            self.udp_tx_rate,
+            self.udp_rx_rate,
+            self.unix_tx_rate,
+            self.unix_rx_rate,
+        ]
+    }
+
+    /// Rate for one of `RATE_VARIABLES`.
+    pub fn get(&self, variable: &str) -> Option<f64> {
+        let index = RATE_VARIABLES.iter().position(|v| *v == variable)?;
+        Some(self.slots()[index])
+    }
+
+    pub fn rate(&self, family: Family, direction: Direction) -> f64 {
+        self.slots()[slot(family, direction)]
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.slots().iter().all(|r| *r == 0.0)
+    }
+}
+
+/// Byte totals and windowed rates for one process or the node.
+#[derive(Debug, Clone, Default)]
+pub struct NetCounters {
+    totals: [u64; 6],
+    /// Start of the open window, wall-clock ns; 0 before the first event
+    window_start_ns: u64,
+    window: [u64; 6],
+    /// Rates of the last closed window
+    last: [f64; 6],
+}
+
+impl NetCounters {
+    /// Count a net event's bytes. Returns false for an unknown operation.
+    pub fn record(&mut self, op: u32, bytes: u64, now_ns: u64) -> bool {
+        let Some((family, direction)) = classify(op) else {
+            return false;
+        };
+        self.roll(now_ns);
+        let slot = slot(family, direction);
+        self.totals[slot] = self.totals[slot].saturating_add(bytes);
+        self.window[slot] = self.window[slot].saturating_add(bytes);
+        true
+    }
+
+    /// Close the open window once it spans `WINDOW`.
+    fn roll(&mut self, now_ns: u64) {
+        if self.window_start_ns == 0 {
+            self.window_start_ns = now_ns;
+            return;
+        }
+        let elapsed = now_ns.saturating_sub(self.window_start_ns);
+        if elapsed < WINDOW.as_nanos() as u64 {
+            return;
+        }
+        let secs = elapsed as f64 / 1e9;
+        for (last, bytes) in self.last.iter_mut().zip(&mut self.window) {
+            *last = std::mem::take(bytes) as f64 / secs;
+        }
+        self.window_start_ns = now_ns;
+    }
+
+    /// Rates as of `now_ns`: the last closed window, or the open one once
+    /// it is overdue, so a process that went quiet decays to zero.
+    pub fn rates(&self, now_ns: u64) -> NetRates {
+        if self.window_start_ns == 0 {
+            return NetRates::default();
+        }
+        let elapsed = now_ns.saturating_sub(self.window_start_ns);
+        if elapsed < WINDOW.as_nanos() as u64 {
+            return NetRates::from_slots(self.last);
+        }
+        let secs = elapsed as f64 / 1e9;
+        NetRates::from_slots(self.window.map(|bytes| bytes as f64 / secs))
+    }
+
+    /// Bytes since the counters were created, by family and direction.
+    pub fn totals(&self) -> impl Iterator<Item = (Family, Direction, u64)> {
+        SLOTS
+            .iter()
+            .zip(self.totals)
+            .map(|(&(family, direction), bytes)| (family, direction, bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEC: u64 = 1_000_000_000;
+
+    #[test]
+    fn rates_split_by_family_and_direction() {
+        let mut counters = NetCounters::default();
+        let t0 = 1_000 * SEC;
+        assert!(counters.record(NetOp::UdpSend as u32, 5_000, t0));
+        assert!(counters.record(NetOp::UdpSend as u32, 5_000, t0 + SEC));
+        assert!(counters.record(NetOp::TcpRecv as u32, 2_000, t0 + 2 * SEC));
+        assert!(counters.record(NetOp::UnixDgramSend as u32, 100, t0 + 3 * SEC));
+        assert!(counters.record(NetOp::UnixStreamSend as u32, 100, t0 + 4 * SEC));
+        assert!(!counters.record(99, 1_000, t0 + 4 * SEC));
+
+        // Window still open: no closed window yet
+        assert!(counters.rates(t0 + 5 * SEC).is_zero());
+
+        // The next event closes the first window
+        counters.record(NetOp::TcpSend as u32, 1, t0 + 10 * SEC);
+        let rates = counters.rates(t0 + 11 * SEC);
+        assert_eq!(rates.udp_tx_rate, 1_000.0);
+        assert_eq!(rates.get("tcp_rx_rate"), Some(200.0));
+        assert_eq!(rates.rate(Family::Unix, Direction::Tx), 20.0);
+        assert_eq!(rates.get("tcp_tx_rate"), Some(0.0));
+        assert_eq!(rates.get("bytes"), None);
+
+        // Quiet for long enough: the overdue open window decays the rates
+        let rates = counters.rates(t0 + 110 * SEC);
+        assert_eq!(rates.tcp_tx_rate, 0.01);
+        assert_eq!(rates.udp_tx_rate, 0.0);
+
+        let totals: Vec<_> = counters.totals().map(|(_, _, bytes)| bytes).collect();
+        assert_eq!(totals, vec![1, 2_000, 10_000, 0, 200, 0]);
+    }
+}
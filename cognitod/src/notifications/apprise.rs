@@ -1,18 +1,34 @@
 use crate::alerts::{Alert, Severity};
-use crate::config::AppriseConfig;
+use crate::config::{AppriseConfig, Egress, OfflineGuard};
+use crate::egress::EgressBlocked;
+use crate::notifications::NotificationWal;
+use crate::notifications::queue::{self, AlertSink};
+use crate::retry::{Destination, Failure, RetryConfig};
+use crate::templates::{AlertContext, MessageKind, Templates};
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use log::{debug, error, info};
+use std::sync::Arc;
 use tokio::process::Command;
 use tokio::sync::broadcast;
 
+/// Sink name in the notification WAL.
+pub const WAL_SINK: &str = "apprise";
+
 /// Apprise notification handler
 ///
 /// Subscribes to the alert broadcast channel and forwards alerts to Apprise CLI,
 /// which handles delivery to 100+ notification services (Slack, Discord, etc.)
 pub struct AppriseNotifier {
     urls: Vec<String>,
+    /// One per URL, named after its position and scheme
+    destinations: Vec<Arc<Destination>>,
     min_severity: Severity,
-    rx: broadcast::Receiver<Alert>,
+    /// Taken by `run`.
+    rx: Option<broadcast::Receiver<Alert>>,
+    wal: Option<Arc<NotificationWal>>,
+    templates: Arc<Templates>,
+    guard: Option<Arc<OfflineGuard>>,
 }
 
 impl AppriseNotifier {
@@ -20,13 +36,53 @@ impl AppriseNotifier {
     pub fn new(config: AppriseConfig, rx: broadcast::Receiver<Alert>) -> Self {
         let min_severity = parse_severity(config.min_severity.as_deref().unwrap_or("info"));
 
+        let destinations = config
+            .urls
+            .iter()
+            .enumerate()
+            .map(|(i, url)| {
+                Arc::new(Destination::new(
+                    &destination_name(i, url),
+                    RetryConfig::default(),
+                ))
+            })
+            .collect();
         Self {
             urls: config.urls,
+            destinations,
             min_severity,
-            rx,
+            rx: Some(rx),
+            wal: None,
+            templates: Templates::defaults(),
+            guard: None,
         }
     }
 
+    /// Queue alerts in `wal` so they survive outages and restarts.
+    pub fn with_wal(mut self, wal: Arc<NotificationWal>) -> Self {
+        self.wal = Some(wal);
+        self
+    }
+
+    pub fn with_templates(mut self, templates: Arc<Templates>) -> Self {
+        self.templates = templates;
+        self
+    }
+
+    /// Apprise delivers from its own process, out of reach of the policy's
+    /// HTTP clients, so it is checked before each run instead. Each URL is
+    /// retried under its own breaker.
+    pub fn with_egress(mut self, guard: Arc<OfflineGuard>) -> Self {
+        self.destinations = self
+            .urls
+            .iter()
+            .enumerate()
+            .map(|(i, url)| guard.retrier().destination(&destination_name(i, url)))
+            .collect();
+        self.guard = Some(guard);
+        self
+    }
+
     /// Run the notifier loop
     ///
     /// Listens for alerts on the broadcast channel and sends them via Apprise.
@@ -37,63 +93,52 @@ impl AppriseNotifier {
             self.urls.len(),
             self.min_severity.as_str()
         );
-
-        loop {
-            match self.rx.recv().await {
-                Ok(alert) => {
-                    // Filter by severity
-                    if alert.severity < self.min_severity {
-                        debug!(
-                            "Skipping alert '{}' (severity {} < threshold {})",
-                            alert.rule,
-                            alert.severity.as_str(),
-                            self.min_severity.as_str()
-                        );
-                        continue;
-                    }
-
-                    // Send notification
-                    if let Err(e) = self.notify(&alert).await {
-                        error!("Failed to send Apprise notification: {}", e);
-                    }
-                }
-                Err(broadcast::error::RecvError::Lagged(n)) => {
-                    error!(
-                        "Apprise notifier lagged by {} alerts (processing too slow or burst too fast)",
-                        n
-                    );
-                }
-                Err(broadcast::error::RecvError::Closed) => {
-                    info!("Alert channel closed, stopping Apprise notifier");
-                    break;
-                }
-            }
-        }
+        let rx = self.rx.take().expect("run consumes the notifier");
+        let wal = self.wal.take();
+        queue::run(Arc::new(self), rx, wal).await;
     }
 
-    /// Send a single alert via Apprise CLI
+    /// Send a single alert via Apprise CLI. Fails only when no URL accepted
+    /// it, so a retry does not repeat it on URLs that did.
     async fn notify(&self, alert: &Alert) -> Result<()> {
+        if let Some(guard) = &self.guard
+            && !guard.allows(Egress::Notifications)
+        {
+            return Err(EgressBlocked(Egress::Notifications).into());
+        }
         let title = format!(
             "[{}] {}",
             alert.severity.as_str().to_uppercase(),
             alert.rule
         );
-        let body = format!("Host: {}\n\n{}", alert.host, alert.message);
+        let body = self
+            .templates
+            .render(WAL_SINK, MessageKind::Alert, &AlertContext::new(alert));
 
         debug!("Sending notification: '{}'", title);
 
         // Send to each URL (failures on one don't block others)
-        for url in &self.urls {
-            if let Err(e) = self.send_to_url(url, &title, &body).await {
-                error!("Failed to notify {}: {}", mask_url(url), e);
+        let mut delivered = 0;
+        for (url, destination) in self.urls.iter().zip(&self.destinations) {
+            let (title, body) = (&title, &body);
+            let sent = destination
+                .run(|_| async move { self.send_to_url(url, title, body).await })
+                .await;
+            match sent {
+                Ok(()) => delivered += 1,
+                Err(e) => error!("Failed to notify {}: {}", mask_url(url), e),
             }
         }
 
+        if delivered == 0 && !self.urls.is_empty() {
+            anyhow::bail!("no Apprise URL accepted the notification");
+        }
         Ok(())
     }
 
-    /// Send notification to a single Apprise URL
-    async fn send_to_url(&self, url: &str, title: &str, body: &str) -> Result<()> {
+    /// Send notification to a single Apprise URL; a missing `apprise`
+    /// binary is not retried.
+    async fn send_to_url(&self, url: &str, title: &str, body: &str) -> Result<(), Failure> {
         let output = Command::new("apprise")
             .arg("--title")
             .arg(title)
@@ -102,11 +147,15 @@ impl AppriseNotifier {
             .arg(url)
             .output()
             .await
-            .context("Failed to execute apprise command")?;
+            .context("Failed to execute apprise command")
+            .map_err(Failure::Reject)?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            anyhow::bail!("Apprise command failed: {}", stderr.trim());
+            return Err(Failure::Retry(anyhow::anyhow!(
+                "Apprise command failed: {}",
+                stderr.trim()
+            )));
         }
 
         debug!("Successfully notified {}", mask_url(url));
@@ -114,6 +163,30 @@ impl AppriseNotifier {
     }
 }
 
+#[async_trait]
+impl AlertSink for AppriseNotifier {
+    fn name(&self) -> &'static str {
+        WAL_SINK
+    }
+
+    fn wants(&self, alert: &Alert) -> bool {
+        if alert.severity < self.min_severity {
+            debug!(
+                "Skipping alert '{}' (severity {} < threshold {})",
+                alert.rule,
+                alert.severity.as_str(),
+                self.min_severity.as_str()
+            );
+            return false;
+        }
+        true
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        self.notify(alert).await
+    }
+}
+
 /// Parse severity string into Severity enum
 fn parse_severity(s: &str) -> Severity {
     match s.to_lowercase().as_str() {
@@ -124,6 +197,15 @@ fn parse_severity(s: &str) -> Severity {
     }
 }
 
+/// `apprise:2:slack` for the second URL; the rest of an Apprise URL holds
+/// its credentials.
+fn destination_name(index: usize, url: &str) -> String {
+    let scheme = url
+        .split_once("://")
+        .map_or("unknown", |(scheme, _)| scheme);
+    format!("apprise:{}:{}", index + 1, scheme)
+}
+
 /// Mask sensitive information in URLs for logging
 fn mask_url(url: &str) -> String {
     if let Some(scheme_end) = url.find("://") {
@@ -153,5 +235,9 @@ mod tests {
         assert_eq!(mask_url("slack://token/channel"), "slack://***");
         assert_eq!(mask_url("discord://id/token"), "discord://***");
         assert_eq!(mask_url("invalid-url"), "***");
+        assert_eq!(
+            destination_name(1, "slack://token/channel"),
+            "apprise:2:slack"
+        );
     }
 }
@@ -0,0 +1,595 @@
+//! Discord delivery.
+//!
+//! Alerts go to a channel webhook as embeds coloured by severity, throttled
+//! to `max_per_minute`; alerts over the limit fail the send and wait in the
+//! WAL. With a bot configured, pending enforcement actions are also posted
+//! with Approve/Reject buttons. Clicks arrive at `/api/discord/interactions`,
+//! signed by Discord, and go through the same `approve`/`reject` calls as the
+//! API and Slack. Prompts whose action was settled anywhere lose their
+//! buttons on the next poll. A 429 pauses sending until Discord's
+//! `retry_after` instead of retrying at once.
+
+use crate::alerts::{Alert, Annotations, Severity};
+use crate::config::{DiscordBotConfig, DiscordConfig, Egress, OfflineGuard};
+use crate::enforcement::{ActionStatus, EnforcementAction, EnforcementQueue};
+use crate::notifications::NotificationWal;
+use crate::notifications::queue::{self, AlertSink};
+use crate::retry::{Destination, Failure, RetryConfig, check_response};
+use crate::templates::{AlertContext, EnforcementContext, MessageKind, Templates};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use log::{debug, info, warn};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// Sink name in the notification WAL.
+pub const WAL_SINK: &str = "discord";
+
+/// Destination of the bot's API calls.
+const BOT_DESTINATION: &str = "discord_bot";
+
+const API_BASE: &str = "https://discord.com/api/v10";
+
+/// How often pending actions are checked for prompts to post or settle.
+const APPROVAL_POLL: Duration = Duration::from_secs(5);
+
+/// Discord embed limits.
+const TITLE_LIMIT: usize = 256;
+const DESCRIPTION_LIMIT: usize = 4096;
+const FIELD_LIMIT: usize = 1024;
+
+const PENDING_COLOR: u32 = 0xFFA500;
+
+pub const INTERACTION_PING: u8 = 1;
+pub const INTERACTION_COMPONENT: u8 = 3;
+
+fn severity_color(severity: &Severity) -> u32 {
+    match severity {
+        Severity::High => 0xFF0000,
+        Severity::Medium => 0xFFA500,
+        Severity::Low => 0xFFFF00,
+        Severity::Info => 0x0000FF,
+    }
+}
+
+/// Token bucket holding up to a minute's worth of sends, paused while
+/// Discord's own rate limit is in effect.
+struct RateLimit {
+    per_minute: f64,
+    tokens: f64,
+    refilled_at: Instant,
+    paused_until: Option<Instant>,
+}
+
+impl RateLimit {
+    fn new(per_minute: u32, now: Instant) -> Self {
+        let per_minute = f64::from(per_minute.max(1));
+        Self {
+            per_minute,
+            tokens: per_minute,
+            refilled_at: now,
+            paused_until: None,
+        }
+    }
+
+    fn try_acquire(&mut self, now: Instant) -> bool {
+        if self.paused_until.is_some_and(|until| now < until) {
+            return false;
+        }
+        let elapsed = now
+            .saturating_duration_since(self.refilled_at)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_minute / 60.0).min(self.per_minute);
+        self.refilled_at = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn pause(&mut self, until: Instant) {
+        self.paused_until = Some(until);
+    }
+}
+
+fn truncate(text: &str, limit: usize) -> String {
+    if text.chars().count() <= limit {
+        return text.to_string();
+    }
+    let mut out: String = text.chars().take(limit - 1).collect();
+    out.push('…');
+    out
+}
+
+/// Seconds Discord asked us to wait, from a 429 body.
+async fn retry_after(res: Response) -> Duration {
+    let body: Value = res.json().await.unwrap_or_default();
+    Duration::from_secs_f64(body["retry_after"].as_f64().unwrap_or(1.0).max(0.0))
+}
+
+/// Discord webhook notification handler
+pub struct DiscordNotifier {
+    webhook_url: String,
+    /// Taken by `run`.
+    rx: Option<broadcast::Receiver<Alert>>,
+    client: Client,
+    destination: Arc<Destination>,
+    wal: Option<Arc<NotificationWal>>,
+    limit: Mutex<RateLimit>,
+    templates: Arc<Templates>,
+}
+
+impl DiscordNotifier {
+    pub fn new(config: DiscordConfig, rx: broadcast::Receiver<Alert>) -> Self {
+        Self {
+            webhook_url: config.webhook_url,
+            rx: Some(rx),
+            client: Client::new(),
+            destination: Arc::new(Destination::new(WAL_SINK, RetryConfig::default())),
+            wal: None,
+            limit: Mutex::new(RateLimit::new(config.max_per_minute, Instant::now())),
+            templates: Templates::defaults(),
+        }
+    }
+
+    /// Queue alerts in `wal` so they survive Discord outages, rate limits
+    /// and restarts.
+    pub fn with_wal(mut self, wal: Arc<NotificationWal>) -> Self {
+        self.wal = Some(wal);
+        self
+    }
+
+    pub fn with_templates(mut self, templates: Arc<Templates>) -> Self {
+        self.templates = templates;
+        self
+    }
+
+    /// Send through a client that honours the `notifications` egress policy,
+    /// retrying under the guard's breaker.
+    pub fn with_egress(mut self, guard: &Arc<OfflineGuard>) -> Self {
+        self.client = guard.client(Egress::Notifications, Client::builder());
+        self.destination = guard.retrier().destination(WAL_SINK);
+        self
+    }
+
+    pub async fn run(mut self) {
+        info!("Discord notifier started");
+        let rx = self.rx.take().expect("run consumes the notifier");
+        let wal = self.wal.take();
+        queue::run(Arc::new(self), rx, wal).await;
+    }
+
+    async fn send_alert(&self, alert: &Alert) -> Result<()> {
+        if !self.limit.lock().unwrap().try_acquire(Instant::now()) {
+            anyhow::bail!(
+                "Discord rate limit reached, deferring alert '{}'",
+                alert.rule
+            );
+        }
+
+        let payload = json!({
+            "username": "linnix",
+            "embeds": [alert_embed(alert, &self.templates)],
+        });
+        let (client, url, payload) = (&self.client, &self.webhook_url, &payload);
+        self.destination
+            .run(|_| async move {
+                let res = client
+                    .post(url)
+                    .json(payload)
+                    .send()
+                    .await
+                    .map_err(Failure::request)?;
+                if res.status() == StatusCode::TOO_MANY_REQUESTS {
+                    let wait = retry_after(res).await;
+                    self.limit.lock().unwrap().pause(Instant::now() + wait);
+                    return Err(Failure::Reject(anyhow::anyhow!(
+                        "Discord rate limited the webhook for {:.1}s",
+                        wait.as_secs_f64()
+                    )));
+                }
+                check_response(Ok(res)).await
+            })
+            .await
+            .context("Failed to send request to Discord")?;
+
+        debug!("Successfully sent notification to Discord");
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AlertSink for DiscordNotifier {
+    fn name(&self) -> &'static str {
+        WAL_SINK
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        self.send_alert(alert).await
+    }
+}
+
+fn alert_embed(alert: &Alert, templates: &Templates) -> Value {
+    let mut fields = vec![
+        json!({ "name": "Severity", "value": alert.severity.as_str().to_uppercase(), "inline": true }),
+        json!({ "name": "Host", "value": truncate(&alert.host, FIELD_LIMIT), "inline": true }),
+    ];
+    for (label, line) in alert.process_context_lines() {
+        fields.push(json!({ "name": label, "value": truncate(&format!("`{line}`"), FIELD_LIMIT) }));
+    }
+    if let Some(annotations) = &alert.annotations {
+        fields.extend(annotation_fields(annotations));
+    }
+
+    let description = templates.render(WAL_SINK, MessageKind::Alert, &AlertContext::new(alert));
+
+    json!({
+        "title": truncate(&format!("🚨 Alert: {}", alert.rule), TITLE_LIMIT),
+        "description": truncate(&description, DESCRIPTION_LIMIT),
+        "color": severity_color(&alert.severity),
+        "fields": fields,
+        "footer": { "text": format!("linnix · {}", alert.detection) },
+    })
+}
+
+/// Owner, labels and a runbook link for an annotated alert.
+fn annotation_fields(annotations: &Annotations) -> Vec<Value> {
+    let mut fields = Vec::new();
+    if let Some(owner) = &annotations.owner {
+        fields.push(
+            json!({ "name": "Owner", "value": truncate(owner, FIELD_LIMIT), "inline": true }),
+        );
+    }
+    if !annotations.labels.is_empty() {
+        let labels: Vec<String> = annotations
+            .labels
+            .iter()
+            .map(|(key, value)| format!("`{key}={value}`"))
+            .collect();
+        fields.push(json!({ "name": "Labels", "value": truncate(&labels.join(" "), FIELD_LIMIT) }));
+    }
+    if let Some(url) = &annotations.runbook_url {
+        fields.push(json!({ "name": "Runbook", "value": truncate(&format!("[📖 Open runbook]({url})"), FIELD_LIMIT) }));
+    }
+    fields
+}
+
+/// Posts approval prompts for pending actions and settles them once the
+/// action leaves `Pending`.
+struct ApprovalPrompts {
+    config: DiscordBotConfig,
+    client: Client,
+    destination: Arc<Destination>,
+    queue: Arc<EnforcementQueue>,
+    templates: Arc<Templates>,
+    /// Action id to the prompt's message id.
+    posted: HashMap<String, String>,
+}
+
+/// Start posting approval prompts for `queue` to the bot's channel.
+pub fn spawn_approvals(
+    config: DiscordBotConfig,
+    queue: Arc<EnforcementQueue>,
+    templates: Arc<Templates>,
+    guard: &Arc<OfflineGuard>,
+) {
+    info!(
+        "[discord] posting approval prompts to channel {}",
+        config.channel_id
+    );
+    let prompts = ApprovalPrompts {
+        config,
+        client: guard.client(Egress::Notifications, Client::builder()),
+        destination: guard.retrier().destination(BOT_DESTINATION),
+        queue,
+        templates,
+        posted: HashMap::new(),
+    };
+    tokio::spawn(prompts.run());
+}
+
+impl ApprovalPrompts {
+    async fn run(mut self) {
+        let mut tick = tokio::time::interval(APPROVAL_POLL);
+        loop {
+            tick.tick().await;
+            self.sync().await;
+        }
+    }
+
+    async fn sync(&mut self) {
+        let pending = self.queue.get_pending().await;
+        for action in &pending {
+            if self.posted.contains_key(&action.id) {
+                continue;
+            }
+            match self.post(&prompt_message(action, &self.templates)).await {
+                Ok(message_id) => {
+                    self.posted.insert(action.id.clone(), message_id);
+                }
+                // Retried on the next poll.
+                Err(e) => warn!("[discord] failed to post prompt for {}: {:#}", action.id, e),
+            }
+        }
+
+        let pending: HashSet<&str> = pending.iter().map(|a| a.id.as_str()).collect();
+        let settled: Vec<String> = self
+            .posted
+            .keys()
+            .filter(|id| !pending.contains(id.as_str()))
+            .cloned()
+            .collect();
+        if settled.is_empty() {
+            return;
+        }
+        let all = self.queue.get_all().await;
+        for id in settled {
+            let Some(message_id) = self.posted.remove(&id) else {
+                continue;
+            };
+            let content = all
+                .iter()
+                .find(|a| a.id == id)
+                .map(outcome_line)
+                .unwrap_or_else(|| "Action no longer exists".to_string());
+            if let Err(e) = self.settle(&message_id, &content).await {
+                warn!("[discord] failed to settle prompt for {}: {:#}", id, e);
+            }
+        }
+    }
+
+    fn request(&self, builder: RequestBuilder) -> RequestBuilder {
+        builder.header("Authorization", format!("Bot {}", self.config.token))
+    }
+
+    async fn post(&self, message: &Value) -> Result<String> {
+        let url = format!("{API_BASE}/channels/{}/messages", self.config.channel_id);
+        let url = &url;
+        let res = self
+            .destination
+            .run(|_| async move {
+                check(
+                    self.request(self.client.post(url))
+                        .json(message)
+                        .send()
+                        .await,
+                )
+                .await
+            })
+            .await
+            .context("Failed to send request to Discord")?;
+        let body: Value = res.json().await?;
+        body["id"]
+            .as_str()
+            .map(str::to_string)
+            .context("Discord response has no message id")
+    }
+
+    async fn settle(&self, message_id: &str, content: &str) -> Result<()> {
+        let url = format!(
+            "{API_BASE}/channels/{}/messages/{}",
+            self.config.channel_id, message_id
+        );
+        let (url, body) = (&url, &json!({ "content": content, "components": [] }));
+        self.destination
+            .run(|_| async move {
+                check(self.request(self.client.patch(url)).json(body).send().await).await
+            })
+            .await
+            .context("Failed to send request to Discord")
+            .map(|_| ())
+    }
+}
+
+/// A bot API response; a 429 is not retried, the prompt waits for the next
+/// poll.
+async fn check(result: reqwest::Result<Response>) -> Result<Response, Failure> {
+    let res = result.map_err(Failure::request)?;
+    if res.status() == StatusCode::TOO_MANY_REQUESTS {
+        let wait = retry_after(res).await;
+        return Err(Failure::Reject(anyhow::anyhow!(
+            "Discord rate limited the bot for {:.1}s",
+            wait.as_secs_f64()
+        )));
+    }
+    check_response(Ok(res)).await
+}
+
+fn prompt_message(action: &EnforcementAction, templates: &Templates) -> Value {
+    let mut fields = vec![
+        json!({ "name": "Target", "value": truncate(&action.action.target(), FIELD_LIMIT), "inline": true }),
+        json!({ "name": "Source", "value": truncate(&action.source, FIELD_LIMIT), "inline": true }),
+    ];
+    if let Some(confidence) = action.confidence {
+        fields.push(json!({ "name": "Confidence", "value": format!("{:.0}%", confidence * 100.0), "inline": true }));
+    }
+    fields.push(json!({ "name": "Expires", "value": format!("<t:{}:R>", action.expires_at), "inline": true }));
+
+    json!({
+        "embeds": [{
+            "title": format!("⏳ Approval needed: {}", action.action.kind()),
+            "description": truncate(
+                &templates.render(WAL_SINK, MessageKind::Enforcement, &EnforcementContext::new(action)),
+                DESCRIPTION_LIMIT,
+            ),
+            "color": PENDING_COLOR,
+            "fields": fields,
+            "footer": { "text": action.id },
+        }],
+        "components": [{
+            "type": 1,
+            "components": [
+                { "type": 2, "style": 3, "label": "Approve", "custom_id": format!("approve:{}", action.id) },
+                { "type": 2, "style": 4, "label": "Reject", "custom_id": format!("reject:{}", action.id) },
+            ]
+        }]
+    })
+}
+
+fn outcome_line(action: &EnforcementAction) -> String {
+    let approver = action.approved_by.as_deref().unwrap_or("expiry policy");
+    match action.status {
+        ActionStatus::Pending => "⏳ Pending".to_string(),
+        ActionStatus::Approved => format!("✅ Approved by {approver}"),
+        ActionStatus::Executed => format!("✅ Executed, approved by {approver}"),
+        ActionStatus::Rejected => "❌ Rejected".to_string(),
+        ActionStatus::Expired => "⌛ Expired without a decision".to_string(),
+        ActionStatus::Aborted => format!(
+            "⚠️ Not executed: {}",
+            action.error.as_deref().unwrap_or("aborted")
+        ),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Approve,
+    Reject,
+}
+
+/// Interaction webhook body; only the fields the approval buttons need.
+#[derive(Debug, Deserialize)]
+pub struct Interaction {
+    #[serde(rename = "type")]
+    pub kind: u8,
+    #[serde(default)]
+    data: Option<InteractionData>,
+    /// Set in guild channels.
+    #[serde(default)]
+    member: Option<InteractionMember>,
+    /// Set in DMs.
+    #[serde(default)]
+    user: Option<InteractionUser>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InteractionData {
+    #[serde(default)]
+    custom_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InteractionMember {
+    user: InteractionUser,
+}
+
+#[derive(Debug, Deserialize)]
+struct InteractionUser {
+    username: String,
+}
+
+impl Interaction {
+    /// The button pressed and the action id it refers to.
+    pub fn decision(&self) -> Option<(Decision, &str)> {
+        let custom_id = self.data.as_ref()?.custom_id.as_deref()?;
+        if let Some(id) = custom_id.strip_prefix("approve:") {
+            Some((Decision::Approve, id))
+        } else {
+            custom_id
+                .strip_prefix("reject:")
+                .map(|id| (Decision::Reject, id))
+        }
+    }
+
+    /// Approver recorded on the action.
+    pub fn actor(&self) -> String {
+        let user = self.member.as_ref().map(|m| &m.user).or(self.user.as_ref());
+        match user {
+            Some(user) => format!("discord:{}", user.username),
+            None => "discord".to_string(),
+        }
+    }
+}
+
+/// Replace the prompt's content and drop its buttons.
+pub fn update_response(content: &str) -> Value {
+    json!({ "type": 7, "data": { "content": content, "components": [] } })
+}
+
+/// Reply visible only to the user who clicked.
+pub fn ephemeral_response(content: &str) -> Value {
+    json!({ "type": 4, "data": { "content": content, "flags": 64 } })
+}
+
+/// Check Discord's Ed25519 signature over `timestamp || body`.
+pub fn verify_signature(public_key: &str, signature: &str, timestamp: &str, body: &[u8]) -> bool {
+    let (Some(key), Some(signature)) = (decode_hex(public_key), decode_hex(signature)) else {
+        return false;
+    };
+    let mut message = Vec::with_capacity(timestamp.len() + body.len());
+    message.extend_from_slice(timestamp.as_bytes());
+    message.extend_from_slice(body);
+    ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, key)
+        .verify(&message, &signature)
+        .is_ok()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    let hex = hex.trim();
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    #[test]
+    fn rate_limit_refills_over_the_minute() {
+        let start = Instant::now();
+        let mut limit = RateLimit::new(2, start);
+        assert!(limit.try_acquire(start));
+        assert!(limit.try_acquire(start));
+        assert!(!limit.try_acquire(start));
+        assert!(limit.try_acquire(start + Duration::from_secs(30)));
+
+        limit.pause(start + Duration::from_secs(120));
+        assert!(!limit.try_acquire(start + Duration::from_secs(90)));
+        assert!(limit.try_acquire(start + Duration::from_secs(121)));
+    }
+
+    #[test]
+    fn signatures_and_buttons_round_trip() {
+        let rng = ring::rand::SystemRandom::new();
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+        let public_key = hex(pair.public_key().as_ref());
+
+        let body = br#"{"type":3,"data":{"custom_id":"approve:abc"},"member":{"user":{"username":"alice"}}}"#;
+        let signature = hex(pair
+            .sign(&[b"1700000000".as_slice(), body].concat())
+            .as_ref());
+        assert!(verify_signature(
+            &public_key,
+            &signature,
+            "1700000000",
+            body
+        ));
+        assert!(!verify_signature(
+            &public_key,
+            &signature,
+            "1700000001",
+            body
+        ));
+        assert!(!verify_signature(&public_key, "zz", "1700000000", body));
+
+        let interaction: Interaction = serde_json::from_slice(body).unwrap();
+        assert_eq!(interaction.kind, INTERACTION_COMPONENT);
+        assert_eq!(interaction.decision(), Some((Decision::Approve, "abc")));
+        assert_eq!(interaction.actor(), "discord:alice");
+    }
+}
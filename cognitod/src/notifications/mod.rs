@@ -1,7 +1,13 @@
 //! Notification handlers for external alerting systems
 
 mod apprise;
+pub mod discord;
+pub mod queue;
 mod slack;
+pub mod wal;
 
-pub use apprise::AppriseNotifier;
-pub use slack::SlackNotifier;
+pub use apprise::{AppriseNotifier, WAL_SINK as APPRISE_SINK};
+pub use discord::{DiscordNotifier, WAL_SINK as DISCORD_SINK};
+pub use queue::AlertSink;
+pub use slack::{SlackNotifier, WAL_SINK as SLACK_SINK};
+pub use wal::{NotificationWal, RetryPolicy};
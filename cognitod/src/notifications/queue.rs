@@ -0,0 +1,259 @@
+//! Delivery loop between the alert broadcast and one notifier.
+//!
+//! With a WAL the receiving side only queues each alert and wakes the sender,
+//! so a slow or unreachable endpoint never makes the notifier lag the
+//! broadcast channel. The sender delivers whatever is due, oldest first, and
+//! reports failures back to the WAL, which schedules the retry or
+//! dead-letters the entry. Without a WAL each alert is sent once, inline.
+
+use async_trait::async_trait;
+use log::{debug, error, info, warn};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Notify, broadcast};
+
+use crate::alerts::Alert;
+use crate::metrics::Metrics;
+use crate::notifications::NotificationWal;
+use crate::utils::now_secs;
+
+/// How often the sender looks for entries whose backoff elapsed.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often queue depth and age are published to the metrics.
+const STATS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// An endpoint alerts are delivered to.
+#[async_trait]
+pub trait AlertSink: Send + Sync + 'static {
+    /// Sink name in the WAL, metrics and logs.
+    fn name(&self) -> &'static str;
+
+    /// Whether this sink delivers `alert` at all.
+    fn wants(&self, _alert: &Alert) -> bool {
+        true
+    }
+
+    async fn send(&self, alert: &Alert) -> anyhow::Result<()>;
+}
+
+/// Deliver alerts from `rx` to `sink` until the channel closes. Undelivered
+/// entries stay in the WAL for the next start.
+pub async fn run<S: AlertSink>(
+    sink: Arc<S>,
+    mut rx: broadcast::Receiver<Alert>,
+    wal: Option<Arc<NotificationWal>>,
+) {
+    let Some(wal) = wal else {
+        while let Some(alert) = recv(sink.name(), &mut rx).await {
+            if accepts(&*sink, &alert)
+                && let Err(e) = sink.send(&alert).await
+            {
+                error!("[notify-queue] {} delivery failed: {:#}", sink.name(), e);
+            }
+        }
+        return;
+    };
+
+    let wake = Arc::new(Notify::new());
+    let receiver = {
+        let sink = Arc::clone(&sink);
+        let wal = Arc::clone(&wal);
+        let wake = Arc::clone(&wake);
+        tokio::spawn(async move {
+            while let Some(alert) = recv(sink.name(), &mut rx).await {
+                if !accepts(&*sink, &alert) {
+                    continue;
+                }
+                match wal.append(sink.name(), &alert) {
+                    Ok(Some(_)) => wake.notify_one(),
+                    Ok(None) => debug!(
+                        "[notify-queue] skipping duplicate {} alert '{}'",
+                        sink.name(),
+                        alert.rule
+                    ),
+                    // Sending unqueued beats dropping the alert.
+                    Err(e) => {
+                        error!(
+                            "[notify-queue] failed to queue {} alert, sending directly: {:#}",
+                            sink.name(),
+                            e
+                        );
+                        if let Err(e) = sink.send(&alert).await {
+                            error!("[notify-queue] {} delivery failed: {:#}", sink.name(), e);
+                        }
+                    }
+                }
+            }
+            wake.notify_one();
+        })
+    };
+
+    // The first tick fires immediately, delivering alerts left by a restart.
+    let mut tick = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = wake.notified() => {}
+            _ = tick.tick() => {}
+        }
+        // Checked first so the last queued alerts get one more drain.
+        let finished = receiver.is_finished();
+        drain(&*sink, &wal).await;
+        if finished {
+            break;
+        }
+    }
+}
+
+async fn recv(name: &str, rx: &mut broadcast::Receiver<Alert>) -> Option<Alert> {
+    loop {
+        match rx.recv().await {
+            Ok(alert) => return Some(alert),
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                error!("[notify-queue] {} notifier lagged by {} alerts", name, n);
+            }
+            Err(broadcast::error::RecvError::Closed) => {
+                info!(
+                    "[notify-queue] alert channel closed, stopping {} notifier",
+                    name
+                );
+                return None;
+            }
+        }
+    }
+}
+
+fn accepts<S: AlertSink>(sink: &S, alert: &Alert) -> bool {
+    if let Some(window) = &alert.suppressed_by {
+        debug!(
+            "[notify-queue] skipping {} alert '{}' (maintenance window '{}')",
+            sink.name(),
+            alert.rule,
+            window
+        );
+        return false;
+    }
+    sink.wants(alert)
+}
+
+/// Attempt every entry whose retry time has come.
+async fn drain<S: AlertSink>(sink: &S, wal: &NotificationWal) {
+    for (id, alert) in wal.due(sink.name(), now_secs()) {
+        let updated = match sink.send(&alert).await {
+            Ok(()) => wal.ack(id),
+            Err(err) => {
+                let error = format!("{err:#}");
+                wal.fail(id, &error).map(|next| match next {
+                    Some(next_at) => debug!(
+                        "[notify-queue] {} delivery of '{}' failed, retrying in {}s: {}",
+                        sink.name(),
+                        alert.rule,
+                        next_at.saturating_sub(now_secs()),
+                        error
+                    ),
+                    None => warn!(
+                        "[notify-queue] giving up on {} alert '{}', moved to dead letters: {}",
+                        sink.name(),
+                        alert.rule,
+                        error
+                    ),
+                })
+            }
+        };
+        if let Err(e) = updated {
+            error!(
+                "[notify-queue] failed to update {} entry {}: {:#}",
+                sink.name(),
+                id,
+                e
+            );
+        }
+    }
+}
+
+/// Periodically publish the queues of `sinks` to the metrics.
+pub fn spawn_stats(wal: Arc<NotificationWal>, sinks: Vec<&'static str>, metrics: Arc<Metrics>) {
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(STATS_INTERVAL);
+        loop {
+            tick.tick().await;
+            metrics.set_notification_queues(wal.stats(&sinks));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::Severity;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    struct FlakySink {
+        up: AtomicBool,
+        sent: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl AlertSink for FlakySink {
+        fn name(&self) -> &'static str {
+            "test"
+        }
+
+        async fn send(&self, alert: &Alert) -> anyhow::Result<()> {
+            if !self.up.load(Ordering::Relaxed) {
+                anyhow::bail!("endpoint down");
+            }
+            self.sent.lock().unwrap().push(alert.message.clone());
+            Ok(())
+        }
+    }
+
+    fn alert(message: &str) -> Alert {
+        Alert {
+            rule: "fork_storm".into(),
+            severity: Severity::High,
+            message: message.into(),
+            host: "node-1".into(),
+            detection: "threshold",
+            suppressed_by: None,
+            lineage: Vec::new(),
+            children: Vec::new(),
+            pod: None,
+            evidence: None,
+            annotations: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_entries_back_off_while_new_alerts_flow() {
+        let dir = tempfile::tempdir().unwrap();
+        let wal = Arc::new(
+            NotificationWal::open(dir.path().join("q.wal"), Duration::from_secs(300)).unwrap(),
+        );
+        let sink = Arc::new(FlakySink {
+            up: AtomicBool::new(false),
+            sent: Mutex::new(Vec::new()),
+        });
+        wal.append("test", &alert("a")).unwrap().unwrap();
+        wal.append("test", &alert("b")).unwrap().unwrap();
+        drain(&*sink, &wal).await;
+        let stats = &wal.stats(&["test"])[0];
+        assert_eq!((stats.depth, stats.retrying), (2, 2));
+
+        // Endpoint back: the new alert goes out, the failed ones wait out
+        // their backoff
+        sink.up.store(true, Ordering::Relaxed);
+        let (tx, rx) = broadcast::channel(8);
+        tx.send(alert("c")).unwrap();
+        let mut maintenance = alert("d");
+        maintenance.suppressed_by = Some("patching".into());
+        tx.send(maintenance).unwrap();
+        drop(tx);
+        run(Arc::clone(&sink), rx, Some(Arc::clone(&wal))).await;
+
+        assert_eq!(*sink.sent.lock().unwrap(), ["c"]);
+        assert_eq!(wal.stats(&["test"])[0].depth, 2);
+        assert_eq!(wal.due("test", now_secs() + 3600).len(), 2);
+    }
+}
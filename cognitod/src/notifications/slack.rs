@@ -1,19 +1,32 @@
-use crate::alerts::{Alert, Severity};
-use crate::config::SlackConfig;
+use crate::alerts::{Alert, Annotations, Severity};
+use crate::config::{Egress, OfflineGuard, SlackConfig};
+use crate::notifications::NotificationWal;
+use crate::notifications::queue::{self, AlertSink};
+use crate::retry::{Destination, RetryConfig, check_response};
 use crate::schema::Insight;
+use crate::templates::{AlertContext, MessageKind, Templates};
 use anyhow::{Context, Result};
-use log::{debug, error, info};
+use async_trait::async_trait;
+use log::{debug, info};
 use reqwest::Client;
 use serde_json::json;
+use std::sync::Arc;
 use tokio::sync::broadcast;
 
+/// Sink name in the notification WAL.
+pub const WAL_SINK: &str = "slack";
+
 /// Slack notification handler
 pub struct SlackNotifier {
     webhook_url: String,
     channel: Option<String>,
     dashboard_base_url: String,
-    rx: broadcast::Receiver<Alert>,
+    /// Taken by `run`.
+    rx: Option<broadcast::Receiver<Alert>>,
     client: Client,
+    destination: Arc<Destination>,
+    wal: Option<Arc<NotificationWal>>,
+    templates: Arc<Templates>,
 }
 
 impl SlackNotifier {
@@ -22,30 +35,38 @@ impl SlackNotifier {
             webhook_url: config.webhook_url,
             channel: config.channel,
             dashboard_base_url: config.dashboard_base_url,
-            rx,
+            rx: Some(rx),
             client: Client::new(),
+            destination: Arc::new(Destination::new(WAL_SINK, RetryConfig::default())),
+            wal: None,
+            templates: Templates::defaults(),
         }
     }
 
+    /// Queue alerts in `wal` so they survive Slack outages and restarts.
+    pub fn with_wal(mut self, wal: Arc<NotificationWal>) -> Self {
+        self.wal = Some(wal);
+        self
+    }
+
+    pub fn with_templates(mut self, templates: Arc<Templates>) -> Self {
+        self.templates = templates;
+        self
+    }
+
+    /// Send through a client that honours the `notifications` egress policy,
+    /// retrying under the guard's breaker.
+    pub fn with_egress(mut self, guard: &Arc<OfflineGuard>) -> Self {
+        self.client = guard.client(Egress::Notifications, Client::builder());
+        self.destination = guard.retrier().destination(WAL_SINK);
+        self
+    }
+
     pub async fn run(mut self) {
         info!("Slack notifier started");
-
-        loop {
-            match self.rx.recv().await {
-                Ok(alert) => {
-                    if let Err(e) = self.send_alert(&alert).await {
-                        error!("Failed to send Slack alert: {}", e);
-                    }
-                }
-                Err(broadcast::error::RecvError::Lagged(n)) => {
-                    error!("Slack notifier lagged by {} alerts", n);
-                }
-                Err(broadcast::error::RecvError::Closed) => {
-                    info!("Alert channel closed, stopping Slack notifier");
-                    break;
-                }
-            }
-        }
+        let rx = self.rx.take().expect("run consumes the notifier");
+        let wal = self.wal.take();
+        queue::run(Arc::new(self), rx, wal).await;
     }
 
     async fn send_alert(&self, alert: &Alert) -> Result<()> {
@@ -56,7 +77,10 @@ impl SlackNotifier {
             Severity::Info => "#0000FF",   // Blue
         };
 
-        let payload = json!({
+        let text = self
+            .templates
+            .render(WAL_SINK, MessageKind::Alert, &AlertContext::new(alert));
+        let mut payload = json!({
             "channel": self.channel,
             "attachments": [{
                 "color": color,
@@ -86,13 +110,30 @@ impl SlackNotifier {
                         "type": "section",
                         "text": {
                             "type": "mrkdwn",
-                            "text": format!("*Message:*\n{}", alert.message)
+                            "text": text
                         }
                     }
                 ]
             }]
         });
 
+        if let Some(blocks) = payload["attachments"][0]["blocks"].as_array_mut() {
+            let context = alert.process_context_lines();
+            if !context.is_empty() {
+                let text: Vec<String> = context
+                    .iter()
+                    .map(|(label, line)| format!("*{label}:* `{line}`"))
+                    .collect();
+                blocks.push(json!({
+                    "type": "section",
+                    "text": { "type": "mrkdwn", "text": text.join("\n") }
+                }));
+            }
+            if let Some(annotations) = &alert.annotations {
+                blocks.extend(annotation_blocks(annotations));
+            }
+        }
+
         self.post_to_slack(&payload).await
     }
 
@@ -248,20 +289,63 @@ impl SlackNotifier {
     }
 
     async fn post_to_slack(&self, payload: &serde_json::Value) -> Result<()> {
-        let res = self
-            .client
-            .post(&self.webhook_url)
-            .json(payload)
-            .send()
+        let (client, url) = (&self.client, &self.webhook_url);
+        self.destination
+            .run(|_| async move { check_response(client.post(url).json(payload).send().await).await })
             .await
             .context("Failed to send request to Slack")?;
 
-        if !res.status().is_success() {
-            let text = res.text().await.unwrap_or_default();
-            anyhow::bail!("Slack API error: {}", text);
-        }
-
         debug!("Successfully sent notification to Slack");
         Ok(())
     }
 }
+
+#[async_trait]
+impl AlertSink for SlackNotifier {
+    fn name(&self) -> &'static str {
+        WAL_SINK
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        self.send_alert(alert).await
+    }
+}
+
+/// Description, owner, labels and a runbook button for an annotated alert.
+fn annotation_blocks(annotations: &Annotations) -> Vec<serde_json::Value> {
+    let mut blocks = Vec::new();
+    if let Some(description) = &annotations.description {
+        blocks.push(json!({
+            "type": "section",
+            "text": { "type": "mrkdwn", "text": description }
+        }));
+    }
+
+    let mut context = Vec::new();
+    if let Some(owner) = &annotations.owner {
+        context.push(json!({ "type": "mrkdwn", "text": format!("*Owner:* {owner}") }));
+    }
+    if !annotations.labels.is_empty() {
+        let labels: Vec<String> = annotations
+            .labels
+            .iter()
+            .map(|(key, value)| format!("`{key}={value}`"))
+            .collect();
+        context.push(json!({ "type": "mrkdwn", "text": labels.join(" ") }));
+    }
+    if !context.is_empty() {
+        blocks.push(json!({ "type": "context", "elements": context }));
+    }
+
+    if let Some(url) = &annotations.runbook_url {
+        blocks.push(json!({
+            "type": "actions",
+            "elements": [{
+                "type": "button",
+                "text": { "type": "plain_text", "text": "📖 Runbook" },
+                "url": url
+            }]
+        }));
+    }
+    blocks
+}
@@ -0,0 +1,727 @@
+//! Write-ahead log for outgoing alert notifications
+//!
+//! Each notifier (a "sink") queues an alert here as it arrives and acks the
+//! entry once delivery succeeded. Unacked entries survive restarts, so an
+//! endpoint outage or a crash between alert generation and delivery costs at
+//! most a duplicate instead of a lost alert. Every entry carries a
+//! suppression key (sink + rule + host + message): an alert whose key is still
+//! pending, or was delivered within the dedup window, is not logged again.
+//!
+//! A failed delivery is rescheduled with exponential backoff; after
+//! `max_attempts` the entry becomes a dead letter, kept (bounded) for
+//! inspection but no longer retried.
+//!
+//! The log is JSON lines of `put`/`retry`/`dead`/`ack` records, compacted on
+//! open and whenever acked records dominate the file.
+
+use anyhow::Context;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::alerts::{Alert, Annotations, Severity};
+use crate::k8s::K8sMetadata;
+use crate::lineage::LineageEntry;
+use crate::metrics::NotificationQueueStats;
+use crate::utils::now_secs;
+
+/// Rewrite the log once it holds this many records more than are pending.
+const COMPACT_AFTER_RECORDS: usize = 4096;
+
+/// Dead letters kept per log; the oldest are dropped first.
+const MAX_DEAD_LETTERS: usize = 256;
+
+/// Retry schedule for failed deliveries.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Delay after the first failure, doubled on each further one.
+    pub initial: Duration,
+    pub max_delay: Duration,
+    /// Failed attempts after which an entry is dead-lettered; 0 retries
+    /// forever.
+    pub max_attempts: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(30),
+            max_delay: Duration::from_secs(3600),
+            max_attempts: 10,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the next attempt after `attempts` failures.
+    fn delay(&self, attempts: u32) -> u64 {
+        let initial = self.initial.as_secs().max(1);
+        let factor = 1u64 << attempts.saturating_sub(1).min(32);
+        initial
+            .saturating_mul(factor)
+            .min(self.max_delay.as_secs().max(initial))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredAlert {
+    rule: String,
+    severity: Severity,
+    message: String,
+    host: String,
+    detection: String,
+    #[serde(default)]
+    lineage: Vec<LineageEntry>,
+    #[serde(default)]
+    children: Vec<LineageEntry>,
+    #[serde(default)]
+    pod: Option<K8sMetadata>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    annotations: Option<Box<Annotations>>,
+}
+
+impl From<&Alert> for StoredAlert {
+    fn from(alert: &Alert) -> Self {
+        Self {
+            rule: alert.rule.clone(),
+            severity: alert.severity.clone(),
+            message: alert.message.clone(),
+            host: alert.host.clone(),
+            detection: alert.detection.to_string(),
+            lineage: alert.lineage.clone(),
+            children: alert.children.clone(),
+            pod: alert.pod.clone(),
+            annotations: alert.annotations.clone(),
+        }
+    }
+}
+
+impl StoredAlert {
+    fn to_alert(&self) -> Alert {
+        Alert {
+            rule: self.rule.clone(),
+            severity: self.severity.clone(),
+            message: self.message.clone(),
+            host: self.host.clone(),
+            detection: match self.detection.as_str() {
+                "anomaly" => "anomaly",
+                "signature" => "signature",
+                _ => "threshold",
+            },
+            suppressed_by: None,
+            lineage: self.lineage.clone(),
+            children: self.children.clone(),
+            pod: self.pod.clone(),
+            evidence: None,
+            annotations: self.annotations.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Record {
+    Put {
+        id: u64,
+        sink: String,
+        key: String,
+        ts: u64,
+        alert: Box<StoredAlert>,
+    },
+    Retry {
+        id: u64,
+        attempts: u32,
+        next_at: u64,
+        error: String,
+    },
+    Dead {
+        id: u64,
+        ts: u64,
+        attempts: u32,
+        error: String,
+    },
+    Ack {
+        id: u64,
+        key: String,
+        ts: u64,
+    },
+}
+
+struct Pending {
+    sink: String,
+    key: String,
+    ts: u64,
+    alert: StoredAlert,
+    attempts: u32,
+    /// Unix time before which the entry is not retried.
+    next_at: u64,
+    last_error: Option<String>,
+}
+
+struct DeadLetter {
+    entry: Pending,
+    ts: u64,
+}
+
+/// A notification that exhausted its delivery attempts.
+#[derive(Debug, Clone)]
+pub struct DeadLetterInfo {
+    pub id: u64,
+    pub alert: Alert,
+    pub attempts: u32,
+    pub error: String,
+    /// Unix time it was dead-lettered.
+    pub ts: u64,
+}
+
+struct WalState {
+    file: File,
+    next_id: u64,
+    pending: BTreeMap<u64, Pending>,
+    dead: BTreeMap<u64, DeadLetter>,
+    /// Suppression key -> unix time of the last delivery.
+    delivered: HashMap<String, u64>,
+    records: usize,
+}
+
+pub struct NotificationWal {
+    path: PathBuf,
+    dedup_window: Duration,
+    retry: RetryPolicy,
+    state: Mutex<WalState>,
+}
+
+/// Stable across restarts and releases, unlike `DefaultHasher`.
+pub fn suppression_key(sink: &str, alert: &Alert) -> String {
+    let mut hasher = Sha256::new();
+    for part in [sink, &alert.rule, &alert.host, &alert.message] {
+        hasher.update(part.as_bytes());
+        hasher.update([0u8]);
+    }
+    format!("{sink}:{}", &format!("{:x}", hasher.finalize())[..16])
+}
+
+impl NotificationWal {
+    /// Open (or create) the log at `path`, recovering unacked entries.
+    pub fn open(path: impl Into<PathBuf>, dedup_window: Duration) -> anyhow::Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        let mut pending = BTreeMap::new();
+        let mut dead = BTreeMap::new();
+        let mut delivered = HashMap::new();
+        let mut next_id = 1;
+        if path.exists() {
+            let reader = BufReader::new(
+                File::open(&path).with_context(|| format!("failed to open {}", path.display()))?,
+            );
+            for (n, line) in reader.lines().enumerate() {
+                let line = line.with_context(|| format!("failed to read {}", path.display()))?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                // A torn final line from a crash mid-write is expected.
+                let record: Record = match serde_json::from_str(&line) {
+                    Ok(record) => record,
+                    Err(err) => {
+                        warn!(
+                            "[notify-wal] skipping bad record at {}:{}: {}",
+                            path.display(),
+                            n + 1,
+                            err
+                        );
+                        continue;
+                    }
+                };
+                match record {
+                    Record::Put {
+                        id,
+                        sink,
+                        key,
+                        ts,
+                        alert,
+                    } => {
+                        next_id = next_id.max(id + 1);
+                        pending.insert(
+                            id,
+                            Pending {
+                                sink,
+                                key,
+                                ts,
+                                alert: *alert,
+                                attempts: 0,
+                                next_at: 0,
+                                last_error: None,
+                            },
+                        );
+                    }
+                    Record::Retry {
+                        id,
+                        attempts,
+                        next_at,
+                        error,
+                    } => {
+                        if let Some(entry) = pending.get_mut(&id) {
+                            entry.attempts = attempts;
+                            entry.next_at = next_at;
+                            entry.last_error = Some(error);
+                        }
+                    }
+                    Record::Dead {
+                        id,
+                        ts,
+                        attempts,
+                        error,
+                    } => {
+                        if let Some(mut entry) = pending.remove(&id) {
+                            entry.attempts = attempts;
+                            entry.last_error = Some(error);
+                            dead.insert(id, DeadLetter { entry, ts });
+                        }
+                    }
+                    Record::Ack { id, key, ts } => {
+                        next_id = next_id.max(id + 1);
+                        pending.remove(&id);
+                        dead.remove(&id);
+                        delivered.insert(key, ts);
+                    }
+                }
+            }
+        }
+
+        let now = now_secs();
+        delivered.retain(|_, at| now.saturating_sub(*at) < dedup_window.as_secs());
+        trim_dead(&mut dead);
+        let file = rewrite(&path, &pending, &dead, &delivered)?;
+        if !pending.is_empty() {
+            info!(
+                "[notify-wal] recovered {} undelivered notification(s) from {}",
+                pending.len(),
+                path.display()
+            );
+        }
+        let records = pending.len() + dead.len() + delivered.len();
+        Ok(Self {
+            path,
+            dedup_window,
+            retry: RetryPolicy::default(),
+            state: Mutex::new(WalState {
+                file,
+                next_id,
+                pending,
+                dead,
+                delivered,
+                records,
+            }),
+        })
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Log `alert` for `sink` before dispatch. `None` when an identical alert
+    /// is pending or was delivered within the dedup window.
+    pub fn append(&self, sink: &str, alert: &Alert) -> anyhow::Result<Option<u64>> {
+        let key = suppression_key(sink, alert);
+        let now = now_secs();
+        let mut state = self.state.lock().unwrap();
+        let window = self.dedup_window.as_secs();
+        if state.pending.values().any(|p| p.key == key)
+            || state
+                .delivered
+                .get(&key)
+                .is_some_and(|at| now.saturating_sub(*at) < window)
+        {
+            return Ok(None);
+        }
+        let id = state.next_id;
+        state.next_id += 1;
+        let stored = StoredAlert::from(alert);
+        write_record(
+            &mut state.file,
+            &Record::Put {
+                id,
+                sink: sink.to_string(),
+                key: key.clone(),
+                ts: now,
+                alert: Box::new(stored.clone()),
+            },
+        )?;
+        // The put must survive a crash; acks may be lost (costing a duplicate).
+        state.file.sync_data()?;
+        state.records += 1;
+        state.pending.insert(
+            id,
+            Pending {
+                sink: sink.to_string(),
+                key,
+                ts: now,
+                alert: stored,
+                attempts: 0,
+                next_at: now,
+                last_error: None,
+            },
+        );
+        Ok(Some(id))
+    }
+
+    /// Record a failed delivery of entry `id`. Returns the unix time of the
+    /// next attempt, or `None` once the entry has been dead-lettered.
+    pub fn fail(&self, id: u64, error: &str) -> anyhow::Result<Option<u64>> {
+        let now = now_secs();
+        let mut state = self.state.lock().unwrap();
+        let Some(entry) = state.pending.get_mut(&id) else {
+            return Ok(None);
+        };
+        entry.attempts += 1;
+        entry.last_error = Some(error.to_string());
+        let attempts = entry.attempts;
+        if self.retry.max_attempts == 0 || attempts < self.retry.max_attempts {
+            let next_at = now + self.retry.delay(attempts);
+            entry.next_at = next_at;
+            write_record(
+                &mut state.file,
+                &Record::Retry {
+                    id,
+                    attempts,
+                    next_at,
+                    error: error.to_string(),
+                },
+            )?;
+            state.records += 1;
+            self.compact_if_needed(&mut state, now)?;
+            return Ok(Some(next_at));
+        }
+
+        let entry = state.pending.remove(&id).expect("entry checked above");
+        write_record(
+            &mut state.file,
+            &Record::Dead {
+                id,
+                ts: now,
+                attempts,
+                error: error.to_string(),
+            },
+        )?;
+        state.file.sync_data()?;
+        state.records += 1;
+        state.dead.insert(id, DeadLetter { entry, ts: now });
+        trim_dead(&mut state.dead);
+        self.compact_if_needed(&mut state, now)?;
+        Ok(None)
+    }
+
+    /// Mark an entry delivered.
+    pub fn ack(&self, id: u64) -> anyhow::Result<()> {
+        let now = now_secs();
+        let mut state = self.state.lock().unwrap();
+        let Some(entry) = state.pending.remove(&id) else {
+            return Ok(());
+        };
+        write_record(
+            &mut state.file,
+            &Record::Ack {
+                id,
+                key: entry.key.clone(),
+                ts: now,
+            },
+        )?;
+        state.delivered.insert(entry.key, now);
+        state.records += 1;
+        self.compact_if_needed(&mut state, now)
+    }
+
+    /// Rewrite the log once superseded records dominate it.
+    fn compact_if_needed(&self, state: &mut WalState, now: u64) -> anyhow::Result<()> {
+        if state.records > state.pending.len() + state.dead.len() + COMPACT_AFTER_RECORDS {
+            let window = self.dedup_window.as_secs();
+            state
+                .delivered
+                .retain(|_, at| now.saturating_sub(*at) < window);
+            state.file = rewrite(&self.path, &state.pending, &state.dead, &state.delivered)?;
+            state.records = state.pending.len() + state.dead.len() + state.delivered.len();
+        }
+        Ok(())
+    }
+
+    /// Undelivered entries for `sink`, oldest first.
+    pub fn pending(&self, sink: &str) -> Vec<(u64, Alert)> {
+        self.state
+            .lock()
+            .unwrap()
+            .pending
+            .iter()
+            .filter(|(_, p)| p.sink == sink)
+            .map(|(id, p)| (*id, p.alert.to_alert()))
+            .collect()
+    }
+
+    /// Entries for `sink` whose next attempt is due at unix time `now`,
+    /// oldest first.
+    pub fn due(&self, sink: &str, now: u64) -> Vec<(u64, Alert)> {
+        self.state
+            .lock()
+            .unwrap()
+            .pending
+            .iter()
+            .filter(|(_, p)| p.sink == sink && p.next_at <= now)
+            .map(|(id, p)| (*id, p.alert.to_alert()))
+            .collect()
+    }
+
+    pub fn pending_len(&self) -> usize {
+        self.state.lock().unwrap().pending.len()
+    }
+
+    /// Dead letters for `sink`, oldest first.
+    pub fn dead_letters(&self, sink: &str) -> Vec<DeadLetterInfo> {
+        self.state
+            .lock()
+            .unwrap()
+            .dead
+            .iter()
+            .filter(|(_, d)| d.entry.sink == sink)
+            .map(|(id, d)| DeadLetterInfo {
+                id: *id,
+                alert: d.entry.alert.to_alert(),
+                attempts: d.entry.attempts,
+                error: d.entry.last_error.clone().unwrap_or_default(),
+                ts: d.ts,
+            })
+            .collect()
+    }
+
+    /// Queue depth, age and dead letters for each sink in `sinks`.
+    pub fn stats(&self, sinks: &[&str]) -> Vec<NotificationQueueStats> {
+        let now = now_secs();
+        let state = self.state.lock().unwrap();
+        sinks
+            .iter()
+            .map(|sink| {
+                let mut stats = NotificationQueueStats {
+                    sink: sink.to_string(),
+                    ..NotificationQueueStats::default()
+                };
+                for p in state.pending.values().filter(|p| p.sink == *sink) {
+                    stats.depth += 1;
+                    if p.attempts > 0 {
+                        stats.retrying += 1;
+                    }
+                    stats.oldest_age_secs = stats.oldest_age_secs.max(now.saturating_sub(p.ts));
+                }
+                for d in state.dead.values().filter(|d| d.entry.sink == *sink) {
+                    stats.dead_letters += 1;
+                    stats.last_error = d.entry.last_error.clone();
+                }
+                stats
+            })
+            .collect()
+    }
+}
+
+fn trim_dead(dead: &mut BTreeMap<u64, DeadLetter>) {
+    while dead.len() > MAX_DEAD_LETTERS {
+        dead.pop_first();
+    }
+}
+
+fn write_record(file: &mut File, record: &Record) -> anyhow::Result<()> {
+    let mut line = serde_json::to_vec(record)?;
+    line.push(b'\n');
+    file.write_all(&line)
+        .context("failed to append to notification log")
+}
+
+/// Replace the log with the pending entries, dead letters and delivery
+/// markers, returning an append handle to the new file.
+fn rewrite(
+    path: &Path,
+    pending: &BTreeMap<u64, Pending>,
+    dead: &BTreeMap<u64, DeadLetter>,
+    delivered: &HashMap<String, u64>,
+) -> anyhow::Result<File> {
+    let tmp = path.with_extension("tmp");
+    {
+        let mut out =
+            File::create(&tmp).with_context(|| format!("failed to create {}", tmp.display()))?;
+        for (id, p) in pending
+            .iter()
+            .chain(dead.iter().map(|(id, d)| (id, &d.entry)))
+        {
+            write_record(
+                &mut out,
+                &Record::Put {
+                    id: *id,
+                    sink: p.sink.clone(),
+                    key: p.key.clone(),
+                    ts: p.ts,
+                    alert: Box::new(p.alert.clone()),
+                },
+            )?;
+            if let Some(error) = &p.last_error {
+                write_record(
+                    &mut out,
+                    &Record::Retry {
+                        id: *id,
+                        attempts: p.attempts,
+                        next_at: p.next_at,
+                        error: error.clone(),
+                    },
+                )?;
+            }
+        }
+        for (id, d) in dead {
+            write_record(
+                &mut out,
+                &Record::Dead {
+                    id: *id,
+                    ts: d.ts,
+                    attempts: d.entry.attempts,
+                    error: d.entry.last_error.clone().unwrap_or_default(),
+                },
+            )?;
+        }
+        // Delivery markers keep duplicate suppression across restarts; id 0
+        // never matches a put.
+        for (key, ts) in delivered {
+            write_record(
+                &mut out,
+                &Record::Ack {
+                    id: 0,
+                    key: key.clone(),
+                    ts: *ts,
+                },
+            )?;
+        }
+        out.sync_all()?;
+    }
+    std::fs::rename(&tmp, path).with_context(|| format!("failed to replace {}", path.display()))?;
+    OpenOptions::new()
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alert(message: &str) -> Alert {
+        Alert {
+            rule: "fork_storm".into(),
+            severity: Severity::High,
+            message: message.into(),
+            host: "node-1".into(),
+            detection: "threshold",
+            suppressed_by: None,
+            lineage: Vec::new(),
+            children: Vec::new(),
+            pod: None,
+            evidence: None,
+            annotations: None,
+        }
+    }
+
+    #[test]
+    fn unacked_entries_survive_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify.wal");
+        let window = Duration::from_secs(300);
+        {
+            let wal = NotificationWal::open(&path, window).unwrap();
+            let delivered = wal.append("slack", &alert("a")).unwrap().unwrap();
+            wal.append("slack", &alert("b")).unwrap().unwrap();
+            wal.append("apprise", &alert("b")).unwrap().unwrap();
+            wal.ack(delivered).unwrap();
+            // Simulate a torn write at crash time
+            let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+            file.write_all(b"{\"op\":\"put\",\"id\":9").unwrap();
+        }
+
+        let wal = NotificationWal::open(&path, window).unwrap();
+        let pending = wal.pending("slack");
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].1.message, "b");
+        assert_eq!(pending[0].1.severity, Severity::High);
+        assert_eq!(wal.pending("apprise").len(), 1);
+
+        // Delivered and pending keys are both suppressed after restart
+        assert!(wal.append("slack", &alert("a")).unwrap().is_none());
+        assert!(wal.append("slack", &alert("b")).unwrap().is_none());
+        let next = wal.append("slack", &alert("c")).unwrap().unwrap();
+        assert!(next > pending[0].0, "ids keep increasing after recovery");
+    }
+
+    #[test]
+    fn dedup_window_expires() {
+        let dir = tempfile::tempdir().unwrap();
+        let wal = NotificationWal::open(dir.path().join("w.log"), Duration::ZERO).unwrap();
+        let id = wal.append("slack", &alert("a")).unwrap().unwrap();
+        wal.ack(id).unwrap();
+        assert!(wal.append("slack", &alert("a")).unwrap().is_some());
+        assert_ne!(
+            suppression_key("slack", &alert("a")),
+            suppression_key("apprise", &alert("a"))
+        );
+    }
+
+    #[test]
+    fn failures_back_off_then_dead_letter_across_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify.wal");
+        let policy = RetryPolicy {
+            initial: Duration::from_secs(10),
+            max_delay: Duration::from_secs(25),
+            max_attempts: 3,
+        };
+        let open = || {
+            NotificationWal::open(&path, Duration::from_secs(300))
+                .unwrap()
+                .with_retry_policy(policy)
+        };
+        let now = now_secs();
+        let id = {
+            let wal = open();
+            let id = wal.append("slack", &alert("a")).unwrap().unwrap();
+            assert_eq!(wal.due("slack", now).len(), 1);
+            let first = wal.fail(id, "503").unwrap().unwrap();
+            assert!(first >= now + 10 && first <= now + 11);
+            assert!(wal.due("slack", now).is_empty());
+            id
+        };
+
+        // The schedule survives a restart
+        let wal = open();
+        assert!(wal.due("slack", now).is_empty());
+        assert_eq!(wal.due("slack", now + 11).len(), 1);
+        let second = wal.fail(id, "503").unwrap().unwrap();
+        assert!(second >= now + 20, "delay doubles");
+        let stats = &wal.stats(&["slack"])[0];
+        assert_eq!((stats.depth, stats.retrying, stats.dead_letters), (1, 1, 0));
+
+        assert_eq!(wal.fail(id, "timeout").unwrap(), None);
+        assert!(wal.pending("slack").is_empty());
+        drop(wal);
+
+        let wal = open();
+        let dead = wal.dead_letters("slack");
+        assert_eq!(dead.len(), 1);
+        assert_eq!((dead[0].attempts, dead[0].error.as_str()), (3, "timeout"));
+        let stats = &wal.stats(&["slack", "apprise"]);
+        assert_eq!((stats[0].depth, stats[0].dead_letters), (0, 1));
+        assert_eq!(stats[0].last_error.as_deref(), Some("timeout"));
+        assert_eq!(stats[1].dead_letters, 0);
+        // A repeat of a dead-lettered alert is queued afresh
+        assert!(wal.append("slack", &alert("a")).unwrap().is_some());
+    }
+}
@@ -0,0 +1,406 @@
+//! Kernel OOM kills
+//!
+//! Memory pressure only ever predicts an OOM kill (`oom_risk`); the
+//! `oom:mark_victim` tracepoint reports the ones that happen, as `oom_kill`
+//! events with the victim pid in `data`. The event's own pid and comm are the
+//! task whose allocation ran out of memory, which is often not the victim.
+//! Kernels whose tracepoint carries the victim's RSS have it reported in
+//! `data2`; the daemon finds those fields in the tracefs format at load time.
+//! For each kill an [`OomKill`] is put together from what the context store
+//! knows of the victim and the memory files of its cgroup, read straight
+//! away while the victim is still being torn down, and raised as an
+//! `oom_kill` alert and an incident.
+
+use linnix_ai_ebpf_common::EventType;
+use log::{debug, info};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use crate::ProcessEvent;
+use crate::alerts::{Alert, AlertSender, Severity};
+use crate::collectors::cgroup_tree;
+use crate::collectors::memory_events::{MemoryEvents, parse_memory_events};
+use crate::context::ContextStore;
+use crate::incidents::{AnalysisPool, AnalysisPriority, IncidentSinks};
+use crate::k8s::K8sMetadata;
+use crate::lineage::{self, LineageEntry};
+use crate::runtime::arch;
+use crate::types::SystemSnapshot;
+use crate::{Incident, IncidentStore};
+
+pub const RULE_NAME: &str = "oom_kill";
+
+/// Offsets of `anon_rss`, `file_rss` and `shmem_rss` in the `mark_victim`
+/// format, as written to `TelemetryConfig`; all 0 when the kernel's
+/// tracepoint only has the pid.
+pub fn victim_rss_offsets() -> [u32; 3] {
+    arch::tracepoint_format("oom", "mark_victim")
+        .map(|format| rss_offsets(&format))
+        .unwrap_or_default()
+}
+
+fn rss_offsets(format: &str) -> [u32; 3] {
+    let fields = arch::parse_format(format);
+    let offset = |field: &str| {
+        fields
+            .iter()
+            .find(|(name, _, size)| name == field && *size == 8)
+            .map_or(0, |&(_, offset, _)| offset as u32)
+    };
+    match offset("anon_rss") {
+        0 => [0; 3],
+        anon => [anon, offset("file_rss"), offset("shmem_rss")],
+    }
+}
+
+/// Memory accounting of the victim's cgroup right after the kill.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CgroupMemory {
+    pub current_bytes: Option<u64>,
+    /// `None` when the cgroup has no limit
+    pub max_bytes: Option<u64>,
+    /// High-water mark, on kernels that keep one
+    pub peak_bytes: Option<u64>,
+    pub events: MemoryEvents,
+}
+
+impl CgroupMemory {
+    /// Read the memory files of `cgroup`, a path relative to `root`; `None`
+    /// when the cgroup is gone.
+    pub fn read(root: &Path, cgroup: &str) -> Option<Self> {
+        let dir = root.join(cgroup.trim_start_matches('/'));
+        let read = |file: &str| std::fs::read_to_string(dir.join(file)).ok();
+        let bytes = |file: &str| read(file).and_then(|text| text.trim().parse().ok());
+        let events = read("memory.events")?;
+        Some(Self {
+            current_bytes: bytes("memory.current"),
+            max_bytes: bytes("memory.max"),
+            peak_bytes: bytes("memory.peak"),
+            events: parse_memory_events(&events),
+        })
+    }
+}
+
+/// A process the kernel's OOM killer picked.
+#[derive(Debug, Clone, Serialize)]
+pub struct OomKill {
+    pub pid: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comm: Option<String>,
+    /// Task whose allocation ran out of memory
+    pub trigger_pid: u32,
+    pub trigger_comm: String,
+    /// Victim's anonymous, file and shmem RSS, when the kernel reports it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rss_bytes: Option<u64>,
+    /// Path relative to the cgroup root
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cgroup: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<CgroupMemory>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pod: Option<K8sMetadata>,
+}
+
+fn mib(bytes: u64) -> u64 {
+    bytes / (1024 * 1024)
+}
+
+impl OomKill {
+    fn message(&self) -> String {
+        let mut message = format!(
+            "oom kill: {}({}) killed by the kernel OOM killer",
+            self.comm.as_deref().unwrap_or("?"),
+            self.pid
+        );
+        if let Some(rss) = self.rss_bytes {
+            message.push_str(&format!(" at {} MiB RSS", mib(rss)));
+        }
+        if let Some(pod) = &self.pod {
+            message.push_str(&format!(" in pod {}/{}", pod.namespace, pod.pod_name));
+        } else if let Some(cgroup) = &self.cgroup {
+            message.push_str(&format!(" in {cgroup}"));
+        }
+        if let Some(memory) = &self.memory {
+            match (memory.current_bytes, memory.max_bytes) {
+                (Some(current), Some(max)) => message.push_str(&format!(
+                    " (cgroup at {} of {} MiB)",
+                    mib(current),
+                    mib(max)
+                )),
+                (Some(current), None) => {
+                    message.push_str(&format!(" (cgroup at {} MiB, no limit)", mib(current)))
+                }
+                _ => {}
+            }
+        }
+        if self.trigger_pid != self.pid {
+            message.push_str(&format!(
+                "; allocation by {}({})",
+                self.trigger_comm, self.trigger_pid
+            ));
+        }
+        message
+    }
+}
+
+fn comm_of(event: &ProcessEvent) -> String {
+    String::from_utf8_lossy(&event.comm)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+pub struct OomTracker {
+    cgroup_root: PathBuf,
+    host: String,
+    incidents: Option<Arc<IncidentStore>>,
+    sinks: Option<Arc<IncidentSinks>>,
+    analysis: Option<Arc<AnalysisPool>>,
+}
+
+impl OomTracker {
+    pub fn new(
+        incidents: Option<Arc<IncidentStore>>,
+        sinks: Option<Arc<IncidentSinks>>,
+        analysis: Option<Arc<AnalysisPool>>,
+    ) -> Self {
+        Self {
+            cgroup_root: PathBuf::from(cgroup_tree::cgroup_root()),
+            host: std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown".into()),
+            incidents,
+            sinks,
+            analysis,
+        }
+    }
+
+    /// The kill reported by `event`, with what is known of the victim.
+    fn observe(&self, event: &ProcessEvent, context: &ContextStore) -> Option<OomKill> {
+        if event.event_type != EventType::OomKill as u32 {
+            return None;
+        }
+        let pid = event.data as u32;
+        let comm = context
+            .get_process_by_pid(pid)
+            .map(|proc| comm_of(&proc))
+            .or_else(|| {
+                std::fs::read_to_string(format!("/proc/{pid}/comm"))
+                    .ok()
+                    .map(|comm| comm.trim_end().to_string())
+            });
+        let cgroup = cgroup_tree::process_cgroup_path(pid);
+        let memory = cgroup
+            .as_deref()
+            .and_then(|cgroup| CgroupMemory::read(&self.cgroup_root, cgroup));
+        Some(OomKill {
+            pid,
+            comm,
+            trigger_pid: event.pid,
+            trigger_comm: comm_of(event),
+            rss_bytes: (event.data2 != 0).then_some(event.data2),
+            cgroup,
+            memory,
+            pod: context.k8s_metadata(pid).map(|meta| (*meta).clone()),
+        })
+    }
+
+    fn alert(&self, kill: &OomKill, lineage: Vec<LineageEntry>) -> Alert {
+        Alert {
+            rule: RULE_NAME.to_string(),
+            severity: Severity::High,
+            message: kill.message(),
+            host: self.host.clone(),
+            detection: "threshold",
+            suppressed_by: None,
+            lineage,
+            children: Vec::new(),
+            pod: kill.pod.clone(),
+            evidence: None,
+            annotations: None,
+        }
+    }
+
+    fn incident(&self, kill: &OomKill, snapshot: &SystemSnapshot) -> Incident {
+        // The snapshot stays readable as a `SystemSnapshot`; the kill rides along.
+        let system_snapshot = serde_json::to_value(snapshot).ok().map(|mut value| {
+            if let Some(object) = value.as_object_mut() {
+                object.insert(
+                    RULE_NAME.to_string(),
+                    serde_json::to_value(kill).unwrap_or_default(),
+                );
+            }
+            value.to_string()
+        });
+        Incident {
+            id: None,
+            timestamp: chrono::Utc::now().timestamp(),
+            event_type: RULE_NAME.to_string(),
+            psi_cpu: snapshot.psi_cpu_some_avg10,
+            psi_memory: snapshot.psi_memory_full_avg10,
+            cpu_percent: snapshot.cpu_percent,
+            load_avg: format!(
+                "{:.2},{:.2},{:.2}",
+                snapshot.load_avg[0], snapshot.load_avg[1], snapshot.load_avg[2]
+            ),
+            action: "kernel_oom_kill".to_string(),
+            target_pid: Some(kill.pid as i32),
+            target_name: kill.comm.clone(),
+            system_snapshot,
+            llm_analysis: None,
+            llm_analyzed_at: None,
+            analysis_source: None,
+            recovery_time_ms: None,
+            psi_after: None,
+            delivery: Default::default(),
+            dependents: Vec::new(),
+        }
+    }
+
+    /// Record `kill` as an incident, handing it to the sinks and the analysis
+    /// pool once it has an id.
+    fn record(&self, kill: &OomKill, context: &ContextStore) {
+        let Some(store) = &self.incidents else {
+            return;
+        };
+        let incident = self.incident(kill, &context.get_system_snapshot());
+        let inserted = store.submit_incident(incident.clone());
+        let sinks = self.sinks.clone();
+        let analysis = self.analysis.clone();
+        tokio::spawn(async move {
+            let Ok(id) = inserted.await else {
+                return;
+            };
+            info!("[oom] Incident #{id} recorded");
+            if let Some(sinks) = &sinks {
+                sinks.opened(id, &incident);
+            }
+            if let Some(pool) = &analysis {
+                pool.submit(id, incident, AnalysisPriority::Batch);
+            }
+        });
+    }
+
+    pub async fn run(self: Arc<Self>, context: Arc<ContextStore>, tx: Option<AlertSender>) {
+        info!("[oom] recording kernel OOM kills");
+        let mut events = context.broadcaster().subscribe();
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    let Some(kill) = self.observe(&event, &context) else {
+                        continue;
+                    };
+                    let mut lineage = lineage::ancestry(kill.pid, Some(&context));
+                    if lineage.is_empty() {
+                        lineage.push(LineageEntry {
+                            pid: kill.pid,
+                            comm: kill.comm.clone().unwrap_or_default(),
+                            uid: 0,
+                            age_secs: None,
+                        });
+                    }
+                    let alert = self.alert(&kill, lineage);
+                    info!("[oom] {}", alert.message);
+                    self.record(&kill, &context);
+                    if let Some(tx) = &tx {
+                        tx.send(alert);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    debug!("[oom] lagged, skipped {skipped} events");
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+}
+
+/// Start recording OOM kills. Alerts go to `tx` when a rules engine is
+/// loaded; incidents are written when the store is open.
+pub fn spawn(
+    context: Arc<ContextStore>,
+    tx: Option<AlertSender>,
+    incidents: Option<Arc<IncidentStore>>,
+    sinks: Option<Arc<IncidentSinks>>,
+    analysis: Option<Arc<AnalysisPool>>,
+) -> Arc<OomTracker> {
+    let tracker = Arc::new(OomTracker::new(incidents, sinks, analysis));
+    tokio::spawn(Arc::clone(&tracker).run(context, tx));
+    tracker
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `events/oom/mark_victim/format` from a 6.8 x86_64 kernel.
+    const MARK_VICTIM: &str = "name: mark_victim
+ID: 566
+format:
+\tfield:unsigned short common_type;\toffset:0;\tsize:2;\tsigned:0;
+\tfield:unsigned char common_flags;\toffset:2;\tsize:1;\tsigned:0;
+\tfield:unsigned char common_preempt_count;\toffset:3;\tsize:1;\tsigned:0;
+\tfield:int common_pid;\toffset:4;\tsize:4;\tsigned:1;
+
+\tfield:int pid;\toffset:8;\tsize:4;\tsigned:1;
+\tfield:__data_loc char[] comm;\toffset:12;\tsize:4;\tsigned:0;
+\tfield:unsigned long total_vm;\toffset:16;\tsize:8;\tsigned:0;
+\tfield:unsigned long anon_rss;\toffset:24;\tsize:8;\tsigned:0;
+\tfield:unsigned long file_rss;\toffset:32;\tsize:8;\tsigned:0;
+\tfield:unsigned long shmem_rss;\toffset:40;\tsize:8;\tsigned:0;
+\tfield:uid_t uid;\toffset:48;\tsize:4;\tsigned:0;
+\tfield:unsigned long pgtables;\toffset:56;\tsize:8;\tsigned:0;
+\tfield:short oom_score_adj;\toffset:64;\tsize:2;\tsigned:1;
+
+print fmt: \"pid=%d\"
+";
+
+    #[test]
+    fn finds_victim_rss_fields() {
+        assert_eq!(rss_offsets(MARK_VICTIM), [24, 32, 40]);
+
+        // Older kernels only report the pid.
+        let end = MARK_VICTIM.find("\tfield:__data_loc").unwrap();
+        assert_eq!(rss_offsets(&MARK_VICTIM[..end]), [0; 3]);
+    }
+
+    #[test]
+    fn reads_cgroup_memory() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("kubepods.slice/pod1");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("memory.current"), "524288000\n").unwrap();
+        std::fs::write(dir.join("memory.max"), "max\n").unwrap();
+        std::fs::write(
+            dir.join("memory.events"),
+            "low 0\nhigh 0\nmax 12\noom 1\noom_kill 1\n",
+        )
+        .unwrap();
+
+        let memory = CgroupMemory::read(root.path(), "/kubepods.slice/pod1").unwrap();
+        assert_eq!(memory.current_bytes, Some(524_288_000));
+        assert_eq!(memory.max_bytes, None);
+        assert_eq!(memory.peak_bytes, None);
+        assert_eq!((memory.events.max, memory.events.oom_kill), (12, 1));
+        assert_eq!(CgroupMemory::read(root.path(), "/gone"), None);
+
+        let kill = OomKill {
+            pid: 42,
+            comm: Some("java".into()),
+            trigger_pid: 43,
+            trigger_comm: "java".into(),
+            rss_bytes: Some(500 << 20),
+            cgroup: Some("/kubepods.slice/pod1".into()),
+            memory: Some(CgroupMemory {
+                max_bytes: Some(512 << 20),
+                ..memory
+            }),
+            pod: None,
+        };
+        assert_eq!(
+            kill.message(),
+            "oom kill: java(42) killed by the kernel OOM killer at 500 MiB RSS in \
+             /kubepods.slice/pod1 (cgroup at 500 of 512 MiB); allocation by java(43)"
+        );
+    }
+}
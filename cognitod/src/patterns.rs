@@ -0,0 +1,344 @@
+//! Compiled multi-pattern matching for per-event hot paths
+//!
+//! Rules, event filters and the exec risk detector test every event against
+//! lists of patterns, and going through them one glob walk or regex at a
+//! time shows up at high event rates. A [`PatternSet`] is compiled once, when
+//! the rules or filter are loaded. Patterns that are plain strings once the
+//! wildcards at their ends are set aside (`python*`, `*backup*`, a literal
+//! regex like `kubectl`) are all found in one aho-corasick pass over the
+//! text. Exact names are a hash lookup. Only the remaining patterns go
+//! through a single `RegexSet`.
+
+use aho_corasick::AhoCorasick;
+use regex::RegexSet;
+use std::collections::HashMap;
+
+/// Where a literal must be found for its pattern to match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Anchor {
+    Prefix,
+    Suffix,
+    Contains,
+}
+
+impl Anchor {
+    fn admits(self, start: usize, end: usize, len: usize) -> bool {
+        match self {
+            Anchor::Prefix => start == 0,
+            Anchor::Suffix => end == len,
+            Anchor::Contains => true,
+        }
+    }
+}
+
+/// How one pattern is matched.
+enum Part {
+    Always,
+    Exact(String),
+    Literal(Anchor, String),
+    Regex(String),
+}
+
+/// Pattern that failed to compile.
+#[derive(Debug)]
+pub struct PatternError {
+    /// Index of the pattern; `None` when only the set as a whole is too large
+    pub index: Option<usize>,
+    pub message: String,
+}
+
+impl std::fmt::Display for PatternError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.index {
+            Some(index) => write!(f, "pattern {index}: {}", self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+#[derive(Debug, Clone, Default)]
+pub struct PatternSet {
+    patterns: Vec<String>,
+    /// Lowest index of a pattern matching every text
+    always: Option<usize>,
+    exact: HashMap<Box<str>, usize>,
+    literals: Option<AhoCorasick>,
+    /// Per aho-corasick pattern, the patterns it stands for
+    anchors: Vec<Vec<(Anchor, usize)>>,
+    regexes: Option<RegexSet>,
+    /// Pattern index of each member of `regexes`
+    regex_index: Vec<usize>,
+}
+
+impl PatternSet {
+    /// Globs with `*` and `?` matched against the whole text, like
+    /// [`crate::maintenance::glob_match`].
+    pub fn globs<S: AsRef<str>>(patterns: &[S]) -> Result<Self, PatternError> {
+        Self::build(patterns, glob_part)
+    }
+
+    /// Regexes matched anywhere in the text, like `Regex::is_match`.
+    pub fn regexes<S: AsRef<str>>(patterns: &[S]) -> Result<Self, PatternError> {
+        Self::build(patterns, regex_part)
+    }
+
+    fn build<S: AsRef<str>>(patterns: &[S], part: fn(&str) -> Part) -> Result<Self, PatternError> {
+        let mut set = Self {
+            patterns: patterns.iter().map(|p| p.as_ref().to_string()).collect(),
+            ..Self::default()
+        };
+        let mut literals: Vec<String> = Vec::new();
+        let mut regexes = Vec::new();
+        for (index, pattern) in set.patterns.iter().enumerate() {
+            match part(pattern) {
+                Part::Always => {
+                    set.always.get_or_insert(index);
+                }
+                Part::Exact(text) => {
+                    set.exact.entry(text.into()).or_insert(index);
+                }
+                Part::Literal(anchor, text) => {
+                    let slot = match literals.iter().position(|l| *l == text) {
+                        Some(slot) => slot,
+                        None => {
+                            literals.push(text);
+                            set.anchors.push(Vec::new());
+                            literals.len() - 1
+                        }
+                    };
+                    set.anchors[slot].push((anchor, index));
+                }
+                Part::Regex(regex) => {
+                    regexes.push(regex);
+                    set.regex_index.push(index);
+                }
+            }
+        }
+        if !literals.is_empty() {
+            let automaton = AhoCorasick::new(&literals).map_err(|e| PatternError {
+                index: None,
+                message: e.to_string(),
+            })?;
+            set.literals = Some(automaton);
+        }
+        if !regexes.is_empty() {
+            let compiled = RegexSet::new(&regexes).map_err(|e| {
+                // Name the culprit when a single pattern is invalid
+                let index = regexes
+                    .iter()
+                    .position(|r| regex::Regex::new(r).is_err())
+                    .map(|i| set.regex_index[i]);
+                PatternError {
+                    index,
+                    message: e.to_string(),
+                }
+            })?;
+            set.regexes = Some(compiled);
+        }
+        Ok(set)
+    }
+
+    /// The patterns as given.
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        if self.always.is_some() || self.exact.contains_key(text) {
+            return true;
+        }
+        if let Some(literals) = &self.literals {
+            let found = literals.find_overlapping_iter(text).any(|m| {
+                self.anchors[m.pattern().as_usize()]
+                    .iter()
+                    .any(|(anchor, _)| anchor.admits(m.start(), m.end(), text.len()))
+            });
+            if found {
+                return true;
+            }
+        }
+        self.regexes.as_ref().is_some_and(|set| set.is_match(text))
+    }
+
+    /// Index of the first pattern, in the order given, that matches.
+    pub fn first_match(&self, text: &str) -> Option<usize> {
+        let mut first = self.always;
+        let mut consider = |index: usize| {
+            first = Some(first.map_or(index, |f| f.min(index)));
+        };
+        if let Some(&index) = self.exact.get(text) {
+            consider(index);
+        }
+        if let Some(literals) = &self.literals {
+            for m in literals.find_overlapping_iter(text) {
+                for &(anchor, index) in &self.anchors[m.pattern().as_usize()] {
+                    if anchor.admits(m.start(), m.end(), text.len()) {
+                        consider(index);
+                    }
+                }
+            }
+        }
+        if let Some(index) = self
+            .regexes
+            .as_ref()
+            .and_then(|set| set.matches(text).iter().next())
+        {
+            consider(self.regex_index[index]);
+        }
+        first
+    }
+}
+
+fn glob_part(pattern: &str) -> Part {
+    let unprefixed = pattern.trim_start_matches('*');
+    let inner = unprefixed.trim_end_matches('*');
+    if inner.contains(['*', '?']) {
+        return Part::Regex(glob_regex(pattern));
+    }
+    let leading = unprefixed.len() < pattern.len();
+    let trailing = inner.len() < unprefixed.len();
+    match (leading, trailing) {
+        _ if (leading || trailing) && inner.is_empty() => Part::Always,
+        (false, false) => Part::Exact(inner.to_string()),
+        (false, true) => Part::Literal(Anchor::Prefix, inner.to_string()),
+        (true, false) => Part::Literal(Anchor::Suffix, inner.to_string()),
+        (true, true) => Part::Literal(Anchor::Contains, inner.to_string()),
+    }
+}
+
+fn glob_regex(pattern: &str) -> String {
+    let mut regex = String::from("(?s)^");
+    for c in pattern.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+fn regex_part(pattern: &str) -> Part {
+    const META: &[char] = &[
+        '\\', '.', '+', '*', '?', '(', ')', '|', '[', ']', '{', '}', '^', '$',
+    ];
+    if pattern.is_empty() {
+        Part::Always
+    } else if pattern.contains(META) {
+        Part::Regex(pattern.to_string())
+    } else {
+        Part::Literal(Anchor::Contains, pattern.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maintenance::glob_match;
+    use std::time::Instant;
+
+    const GLOBS: &[&str] = &[
+        "sh",
+        "bash",
+        "python*",
+        "*backup*",
+        "*.sh",
+        "kube-?roxy",
+        "",
+        "java",
+        "node*",
+    ];
+    const COMMS: &[&str] = &[
+        "sh",
+        "bash",
+        "zsh",
+        "python3.12",
+        "python",
+        "pgbackupd",
+        "backup",
+        "run.sh",
+        "run.shx",
+        "kube-proxy",
+        "kube-proxxy",
+        "",
+        "javac",
+        "nodejs",
+        "cron",
+    ];
+
+    #[test]
+    fn globs_agree_with_glob_match() {
+        let set = PatternSet::globs(GLOBS).unwrap();
+        for comm in COMMS {
+            let expected = GLOBS.iter().position(|g| glob_match(g, comm));
+            assert_eq!(set.first_match(comm), expected, "{comm:?}");
+            assert_eq!(set.is_match(comm), expected.is_some(), "{comm:?}");
+        }
+        let star = PatternSet::globs(&["kube-*", "*"]).unwrap();
+        assert_eq!(star.first_match("kubelet"), Some(1));
+        assert_eq!(star.first_match("kube-proxy"), Some(0));
+        assert!(!PatternSet::globs::<&str>(&[]).unwrap().is_match("sh"));
+    }
+
+    #[test]
+    fn regexes_keep_their_order_and_report_the_invalid_one() {
+        let set = PatternSet::regexes(&[r"\bnc\s+-e", "curl", "^cur", "wget"]).unwrap();
+        assert_eq!(set.first_match("curl x | sh"), Some(1));
+        assert_eq!(set.first_match("nc -e /bin/sh curl"), Some(0));
+        assert_eq!(set.first_match("wget -qO-"), Some(3));
+        assert_eq!(set.first_match("ls"), None);
+        assert!(set.is_match("xcurlx"));
+
+        let err = PatternSet::regexes(&["ok", "(", "fine"]).unwrap_err();
+        assert_eq!(err.index, Some(1));
+    }
+
+    /// Comms matched per second against a rules-sized glob list, compiled
+    /// and one glob at a time. See CONTRIBUTING.md for the command.
+    #[test]
+    #[ignore = "benchmark"]
+    fn bench_comm_matching() {
+        let globs: Vec<String> = (0..40)
+            .map(|i| match i % 4 {
+                0 => format!("svc{i}"),
+                1 => format!("worker{i}*"),
+                2 => format!("*job{i}*"),
+                _ => format!("app{i}-?x"),
+            })
+            .collect();
+        let comms: Vec<String> = (0..1024).map(|i| format!("worker{}-{i}", i % 97)).collect();
+        let set = PatternSet::globs(&globs).unwrap();
+        let events = 1_000_000;
+
+        let started = Instant::now();
+        let compiled = (0..events)
+            .filter(|i| set.is_match(&comms[i % comms.len()]))
+            .count();
+        let compiled_secs = started.elapsed().as_secs_f64();
+
+        let started = Instant::now();
+        let one_by_one = (0..events)
+            .filter(|i| globs.iter().any(|g| glob_match(g, &comms[i % comms.len()])))
+            .count();
+        let one_by_one_secs = started.elapsed().as_secs_f64();
+
+        assert_eq!(compiled, one_by_one);
+        println!(
+            "{} patterns: {:.0} events/s compiled, {:.0} events/s one by one",
+            globs.len(),
+            events as f64 / compiled_secs,
+            events as f64 / one_by_one_secs
+        );
+    }
+}
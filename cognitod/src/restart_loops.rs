@@ -0,0 +1,550 @@
+//! Restart-loop detection
+//!
+//! Exit events carry the wait status of the exiting task. A workload (comm
+//! plus its container in Kubernetes, or comm plus cgroup elsewhere) that is
+//! exec'd again after a failed exit counts one restart; more than `threshold`
+//! restarts within `window_secs` puts it in a restart loop. Entering a loop
+//! raises a `restart_loop` alert with the exit reasons seen in the window and
+//! the ancestry of the latest exec (usually the supervisor doing the
+//! restarting). The loop clears once a window passes without a restart.
+//! Exits without a status (BPF objects or kernels that do not report it)
+//! never count as failures.
+
+use linnix_ai_ebpf_common::{EventType, exit_status};
+use log::{debug, info};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use crate::ProcessEvent;
+use crate::alerts::{Alert, AlertSender, Severity};
+use crate::collectors::cgroup_tree;
+use crate::config::RestartLoopsConfig;
+use crate::context::ContextStore;
+use crate::k8s::K8sMetadata;
+use crate::lineage::{self, LineageEntry};
+use crate::utils::now_secs;
+
+pub const RULE_NAME: &str = "restart_loop";
+
+/// Decoded wait status of an exited process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ExitReason {
+    Exited { code: u8 },
+    Signaled { signal: u8, core_dumped: bool },
+}
+
+impl ExitReason {
+    /// Reason reported by an exit event, or `None` when it carries no status.
+    pub fn from_event(event: &ProcessEvent) -> Option<Self> {
+        (event.event_type == EventType::Exit as u32 && event.aux2 & exit_status::VALID != 0)
+            .then(|| Self::from_wait_status(event.aux))
+    }
+
+    /// Decode a raw wait status (`task_struct::exit_code`).
+    pub fn from_wait_status(status: u32) -> Self {
+        let signal = (status & 0x7f) as u8;
+        if signal == 0 {
+            Self::Exited {
+                code: ((status >> 8) & 0xff) as u8,
+            }
+        } else {
+            Self::Signaled {
+                signal,
+                core_dumped: status & 0x80 != 0,
+            }
+        }
+    }
+
+    pub fn is_failure(&self) -> bool {
+        *self != Self::Exited { code: 0 }
+    }
+
+    /// Killed by a program-error signal (SIGSEGV, SIGABRT, ...) or by any
+    /// signal that dumped core.
+    pub fn is_crash(&self) -> bool {
+        match *self {
+            Self::Exited { .. } => false,
+            Self::Signaled {
+                signal,
+                core_dumped,
+            } => core_dumped || matches!(signal, 4..=8 | 11),
+        }
+    }
+
+    /// `exit 1`, `SIGKILL`, `SIGSEGV (core)`
+    pub fn label(&self) -> String {
+        match *self {
+            Self::Exited { code } => format!("exit {code}"),
+            Self::Signaled {
+                signal,
+                core_dumped,
+            } => {
+                let name =
+                    signal_name(signal).map_or_else(|| format!("signal {signal}"), Into::into);
+                if core_dumped {
+                    format!("{name} (core)")
+                } else {
+                    name
+                }
+            }
+        }
+    }
+}
+
+fn signal_name(signal: u8) -> Option<&'static str> {
+    Some(match signal {
+        1 => "SIGHUP",
+        2 => "SIGINT",
+        3 => "SIGQUIT",
+        4 => "SIGILL",
+        5 => "SIGTRAP",
+        6 => "SIGABRT",
+        7 => "SIGBUS",
+        8 => "SIGFPE",
+        9 => "SIGKILL",
+        10 => "SIGUSR1",
+        11 => "SIGSEGV",
+        12 => "SIGUSR2",
+        13 => "SIGPIPE",
+        14 => "SIGALRM",
+        15 => "SIGTERM",
+        _ => return None,
+    })
+}
+
+/// What gets restarted. Containers get a fresh cgroup on every restart, so
+/// they are identified by pod and container name instead.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub struct Workload {
+    pub comm: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cgroup: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pod: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+}
+
+impl Workload {
+    pub fn new(comm: String, cgroup: Option<String>, meta: Option<&K8sMetadata>) -> Self {
+        match meta {
+            Some(meta) => Self {
+                comm,
+                cgroup: None,
+                namespace: Some(meta.namespace.clone()),
+                pod: Some(meta.pod_name.clone()),
+                container: Some(meta.container_name.clone()),
+            },
+            None => Self {
+                comm,
+                cgroup,
+                namespace: None,
+                pod: None,
+                container: None,
+            },
+        }
+    }
+
+    fn describe(&self) -> String {
+        match (&self.namespace, &self.pod) {
+            (Some(ns), Some(pod)) => format!(
+                "{} in pod {ns}/{pod} (container {})",
+                self.comm,
+                self.container.as_deref().unwrap_or("-")
+            ),
+            _ => format!(
+                "{} in cgroup {}",
+                self.comm,
+                self.cgroup.as_deref().unwrap_or("-")
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct WorkloadState {
+    /// Failed exit not yet followed by an exec.
+    pending: Option<(u64, ExitReason)>,
+    /// Restarts in the window: when, and the exit that caused it.
+    restarts: VecDeque<(u64, ExitReason)>,
+    looping_since: Option<u64>,
+    /// Ancestry of the latest exec, self first.
+    lineage: Vec<LineageEntry>,
+    live: usize,
+}
+
+/// A workload in a restart loop, as served by `/processes` and `/pods`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RestartLoopState {
+    #[serde(flatten)]
+    pub workload: Workload,
+    /// Restarts after a failed exit within `window_secs`.
+    pub restarts: usize,
+    pub window_secs: u64,
+    pub looping_since: u64,
+    pub last_restart_at: u64,
+    /// Exit reason label to count, over the restarts in the window.
+    pub exit_reasons: BTreeMap<String, u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supervisor: Option<String>,
+}
+
+#[derive(Default)]
+struct State {
+    pids: HashMap<u32, Workload>,
+    workloads: HashMap<Workload, WorkloadState>,
+}
+
+pub struct RestartLoopDetector {
+    threshold: usize,
+    window_secs: u64,
+    host: String,
+    state: Mutex<State>,
+    /// Advances whenever a workload's restarts change
+    generation: AtomicU64,
+}
+
+impl RestartLoopDetector {
+    pub fn new(config: &RestartLoopsConfig) -> Self {
+        Self {
+            threshold: config.threshold.max(1),
+            window_secs: config.window_secs.max(1),
+            host: std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown".into()),
+            state: Mutex::new(State::default()),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Counter that advances whenever the loop state served for a pid may
+    /// have changed; part of the `/processes` ETag.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    /// Record an exec of `pid`. Returns an alert when this restart puts the
+    /// workload into a loop.
+    pub fn on_exec(
+        &self,
+        pid: u32,
+        workload: Workload,
+        lineage: Vec<LineageEntry>,
+        now: u64,
+    ) -> Option<Alert> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(previous) = state.pids.insert(pid, workload.clone())
+            && let Some(prev) = state.workloads.get_mut(&previous)
+        {
+            // Same pid exec'd a new image
+            prev.live = prev.live.saturating_sub(1);
+        }
+        let entry = state.workloads.entry(workload.clone()).or_default();
+        entry.live += 1;
+        entry.lineage = lineage;
+        let (failed_at, reason) = entry.pending.take()?;
+        if now.saturating_sub(failed_at) > self.window_secs {
+            return None;
+        }
+        entry.restarts.push_back((now, reason));
+        trim(&mut entry.restarts, now, self.window_secs);
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        if entry.restarts.len() <= self.threshold || entry.looping_since.is_some() {
+            return None;
+        }
+        entry.looping_since = Some(now);
+        let loop_state = self.describe_state(&workload, entry);
+        Some(Alert {
+            rule: RULE_NAME.to_string(),
+            severity: Severity::High,
+            message: alert_message(&loop_state),
+            host: self.host.clone(),
+            detection: "threshold",
+            suppressed_by: None,
+            lineage: entry.lineage.clone(),
+            children: Vec::new(),
+            pod: None,
+            evidence: None,
+            annotations: None,
+        })
+    }
+
+    /// Record the exit of a tracked pid.
+    pub fn on_exit(&self, pid: u32, reason: Option<ExitReason>, now: u64) {
+        let mut state = self.state.lock().unwrap();
+        let Some(workload) = state.pids.remove(&pid) else {
+            return;
+        };
+        if let Some(entry) = state.workloads.get_mut(&workload) {
+            entry.live = entry.live.saturating_sub(1);
+            entry.pending = reason
+                .filter(ExitReason::is_failure)
+                .map(|reason| (now, reason));
+        }
+    }
+
+    /// Age out restarts, clear loops that went quiet and forget idle
+    /// workloads.
+    pub fn expire(&self, now: u64) {
+        let window = self.window_secs;
+        let mut state = self.state.lock().unwrap();
+        let mut changed = false;
+        state.workloads.retain(|workload, entry| {
+            let before = entry.restarts.len();
+            trim(&mut entry.restarts, now, window);
+            changed |= entry.restarts.len() != before;
+            if entry.restarts.is_empty() && entry.looping_since.take().is_some() {
+                info!(
+                    "[restart-loop] {} no longer restarting",
+                    workload.describe()
+                );
+            }
+            if entry
+                .pending
+                .is_some_and(|(at, _)| now.saturating_sub(at) > window)
+            {
+                entry.pending = None;
+            }
+            entry.live > 0 || entry.pending.is_some() || !entry.restarts.is_empty()
+        });
+        if changed {
+            self.generation.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn describe_state(&self, workload: &Workload, entry: &WorkloadState) -> RestartLoopState {
+        let mut exit_reasons = BTreeMap::new();
+        for (_, reason) in &entry.restarts {
+            *exit_reasons.entry(reason.label()).or_default() += 1;
+        }
+        RestartLoopState {
+            workload: workload.clone(),
+            restarts: entry.restarts.len(),
+            window_secs: self.window_secs,
+            looping_since: entry.looping_since.unwrap_or_default(),
+            last_restart_at: entry.restarts.back().map_or(0, |(at, _)| *at),
+            exit_reasons,
+            supervisor: entry.lineage.get(1).map(|parent| parent.comm.clone()),
+        }
+    }
+
+    /// Workloads currently in a restart loop.
+    pub fn snapshot(&self) -> Vec<RestartLoopState> {
+        let state = self.state.lock().unwrap();
+        let mut out: Vec<_> = state
+            .workloads
+            .iter()
+            .filter(|(_, entry)| entry.looping_since.is_some())
+            .map(|(workload, entry)| self.describe_state(workload, entry))
+            .collect();
+        out.sort_by(|a, b| a.workload.cmp(&b.workload));
+        out
+    }
+
+    /// Loop state of the workload `pid` belongs to, if it is looping.
+    pub fn for_pid(&self, pid: u32) -> Option<RestartLoopState> {
+        let state = self.state.lock().unwrap();
+        let workload = state.pids.get(&pid)?;
+        let entry = state.workloads.get(workload)?;
+        entry.looping_since?;
+        Some(self.describe_state(workload, entry))
+    }
+
+    /// Loops in the containers of one pod.
+    pub fn pod(&self, namespace: &str, pod: &str) -> Vec<RestartLoopState> {
+        self.snapshot()
+            .into_iter()
+            .filter(|s| {
+                s.workload.namespace.as_deref() == Some(namespace)
+                    && s.workload.pod.as_deref() == Some(pod)
+            })
+            .collect()
+    }
+
+    fn observe(&self, event: &ProcessEvent, context: &ContextStore) -> Option<Alert> {
+        if event.event_type == EventType::Exit as u32 {
+            self.on_exit(event.pid, ExitReason::from_event(event), now_secs());
+            return None;
+        }
+        if event.event_type != EventType::Exec as u32 {
+            return None;
+        }
+        let meta = context
+            .get_live_map()
+            .get(&event.pid)
+            .and_then(|(_, meta)| meta.clone());
+        let cgroup = match meta {
+            Some(_) => None,
+            None => cgroup_tree::process_cgroup_path(event.pid),
+        };
+        let comm = String::from_utf8_lossy(&event.comm)
+            .trim_end_matches('\0')
+            .to_string();
+        let workload = Workload::new(comm, cgroup, meta.as_deref());
+        let lineage = lineage::ancestry(event.pid, Some(context));
+        self.on_exec(event.pid, workload, lineage, now_secs())
+    }
+
+    pub async fn run(self: Arc<Self>, context: Arc<ContextStore>, tx: Option<AlertSender>) {
+        info!(
+            "[restart-loop] alerting after more than {} restarts in {}s",
+            self.threshold, self.window_secs
+        );
+        let mut events = context.broadcaster().subscribe();
+        let mut ticker = tokio::time::interval(Duration::from_secs(self.window_secs.min(30)));
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(event) => {
+                        if let Some(alert) = self.observe(&event, &context) {
+                            info!("[restart-loop] {}", alert.message);
+                            if let Some(tx) = &tx {
+                                tx.send(alert);
+                            }
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        debug!("[restart-loop] lagged, skipped {skipped} events");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                _ = ticker.tick() => self.expire(now_secs()),
+            }
+        }
+    }
+}
+
+fn trim(restarts: &mut VecDeque<(u64, ExitReason)>, now: u64, window_secs: u64) {
+    while restarts
+        .front()
+        .is_some_and(|(at, _)| now.saturating_sub(*at) > window_secs)
+    {
+        restarts.pop_front();
+    }
+}
+
+fn alert_message(state: &RestartLoopState) -> String {
+    let reasons = state
+        .exit_reasons
+        .iter()
+        .map(|(label, count)| format!("{label} x{count}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut message = format!(
+        "restart loop: {} restarted {} times in {}s after failing ({reasons})",
+        state.workload.describe(),
+        state.restarts,
+        state.window_secs
+    );
+    if let Some(supervisor) = &state.supervisor {
+        message.push_str(&format!(", restarted by {supervisor}"));
+    }
+    message
+}
+
+/// Start tracking restarts of exec'd processes. Alerts go to `tx` when a
+/// rules engine is loaded; loop state is kept either way for the API.
+pub fn spawn(
+    context: Arc<ContextStore>,
+    tx: Option<AlertSender>,
+    config: &RestartLoopsConfig,
+) -> Arc<RestartLoopDetector> {
+    let detector = Arc::new(RestartLoopDetector::new(config));
+    tokio::spawn(Arc::clone(&detector).run(context, tx));
+    detector
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workload(comm: &str) -> Workload {
+        Workload::new(
+            comm.to_string(),
+            Some("/system.slice/api.service".into()),
+            None,
+        )
+    }
+
+    fn supervised(pid: u32) -> Vec<LineageEntry> {
+        vec![
+            LineageEntry {
+                pid,
+                comm: "api".into(),
+                uid: 0,
+                age_secs: None,
+            },
+            LineageEntry {
+                pid: 1,
+                comm: "systemd".into(),
+                uid: 0,
+                age_secs: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn decodes_wait_status() {
+        assert_eq!(
+            ExitReason::from_wait_status(1 << 8),
+            ExitReason::Exited { code: 1 }
+        );
+        assert!(!ExitReason::from_wait_status(0).is_failure());
+        assert_eq!(ExitReason::from_wait_status(9).label(), "SIGKILL");
+        assert_eq!(
+            ExitReason::from_wait_status(0x80 | 11).label(),
+            "SIGSEGV (core)"
+        );
+        assert_eq!(ExitReason::from_wait_status(40).label(), "signal 40");
+        assert!(ExitReason::from_wait_status(6).is_crash());
+        assert!(ExitReason::from_wait_status(0x80 | 3).is_crash());
+        assert!(!ExitReason::from_wait_status(9).is_crash());
+        assert!(!ExitReason::from_wait_status(139 << 8).is_crash());
+    }
+
+    #[test]
+    fn failed_restarts_beyond_threshold_raise_one_alert() {
+        let detector = RestartLoopDetector::new(&RestartLoopsConfig {
+            enabled: true,
+            threshold: 3,
+            window_secs: 60,
+        });
+        let api = workload("api");
+        let mut alerts = Vec::new();
+        for i in 0..6u32 {
+            let now = 100 + u64::from(i) * 5;
+            alerts.extend(detector.on_exec(10 + i, api.clone(), supervised(10 + i), now));
+            let status = if i % 2 == 0 { 1 << 8 } else { 0x80 | 11 };
+            detector.on_exit(10 + i, Some(ExitReason::from_wait_status(status)), now + 1);
+        }
+        // A clean exit followed by an exec is not a restart after failure
+        detector.on_exec(30, workload("cron-job"), Vec::new(), 120);
+        detector.on_exit(30, Some(ExitReason::Exited { code: 0 }), 121);
+        detector.on_exec(31, workload("cron-job"), Vec::new(), 122);
+        alerts.extend(detector.on_exec(40, api.clone(), supervised(40), 131));
+
+        assert_eq!(alerts.len(), 1, "one alert per loop");
+        assert_eq!(
+            alerts[0].message,
+            "restart loop: api in cgroup /system.slice/api.service restarted 4 times in 60s \
+after failing (SIGSEGV (core) x2, exit 1 x2), restarted by systemd"
+        );
+        let state = detector.for_pid(40).expect("loop visible for the live pid");
+        assert_eq!(state.restarts, 6);
+        assert_eq!(state.exit_reasons["exit 1"], 3);
+        assert!(detector.for_pid(31).is_none());
+
+        let generation = detector.generation();
+        detector.expire(131 + 61);
+        assert!(
+            detector.snapshot().is_empty(),
+            "quiet window clears the loop"
+        );
+        assert!(detector.generation() > generation);
+    }
+}
@@ -0,0 +1,585 @@
+//! Retries, backoff and circuit breaking for outbound clients
+//!
+//! Slack, Discord, Apprise, event webhooks, incident sinks, LLM endpoints
+//! and fleet telemetry send through the [`Retrier`] of the egress guard,
+//! each under the name of its destination. A failed attempt is retried
+//! after an exponential backoff with jitter, up to `max_attempts`, while the
+//! destination's retry budget lasts (`budget_per_min` retries per minute),
+//! so an outage does not multiply the traffic sent to it. Failures that a
+//! retry cannot fix, such as a 4xx response or a blocked egress category,
+//! are returned at once.
+//!
+//! After `breaker_failures` calls in a row fail, the destination's breaker
+//! opens and calls fail without a request for `breaker_open_secs`. The next
+//! call after that is let through alone (half open): its success closes the
+//! breaker, its failure opens it again. Callers with a queue behind them
+//! (the notification WAL, incident sinks) keep the alert and try again
+//! later. The state of every destination is shown under `outbound` in
+//! `/status`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::BuildHasher;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::egress::EgressBlocked;
+use crate::utils::now_secs;
+
+/// `[egress.retry]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryConfig {
+    /// Attempts per call, the first one included
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each further one
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    /// Share of each delay drawn at random, 0 to 1
+    #[serde(default = "default_jitter")]
+    pub jitter: f64,
+    /// Retries per destination per minute; 0 disables retries
+    #[serde(default = "default_budget_per_min")]
+    pub budget_per_min: u32,
+    /// Failed calls in a row that open a destination's breaker; 0 never
+    /// opens it
+    #[serde(default = "default_breaker_failures")]
+    pub breaker_failures: u32,
+    /// How long an open breaker fails calls before letting one through
+    #[serde(default = "default_breaker_open_secs")]
+    pub breaker_open_secs: u64,
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    500
+}
+
+fn default_max_backoff_ms() -> u64 {
+    10_000
+}
+
+fn default_jitter() -> f64 {
+    0.5
+}
+
+fn default_budget_per_min() -> u32 {
+    30
+}
+
+fn default_breaker_failures() -> u32 {
+    5
+}
+
+fn default_breaker_open_secs() -> u64 {
+    60
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_max_attempts(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+            jitter: default_jitter(),
+            budget_per_min: default_budget_per_min(),
+            breaker_failures: default_breaker_failures(),
+            breaker_open_secs: default_breaker_open_secs(),
+        }
+    }
+}
+
+/// Exponential backoff with jitter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    /// Share of each delay drawn at random, 0 to 1
+    pub jitter: f64,
+}
+
+impl Backoff {
+    /// The delay before retry `attempt` (1-based) without jitter: `initial`,
+    /// `2*initial`, ... capped at `max`.
+    pub fn ceiling(&self, attempt: u32) -> Duration {
+        self.initial
+            .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+            .min(self.max.max(self.initial))
+    }
+
+    /// The delay before retry `attempt`, between `(1 - jitter) * ceiling`
+    /// and the ceiling, so clients that failed together do not retry
+    /// together.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let ceiling = self.ceiling(attempt);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let draw = RandomState::new().hash_one(attempt) as f64 / u64::MAX as f64;
+        ceiling.mul_f64(1.0 - jitter * draw)
+    }
+}
+
+/// How many attempts a call gets and how long it waits between them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub backoff: Backoff,
+}
+
+impl From<&RetryConfig> for RetryPolicy {
+    fn from(config: &RetryConfig) -> Self {
+        Self {
+            max_attempts: config.max_attempts.max(1),
+            backoff: Backoff {
+                initial: Duration::from_millis(config.initial_backoff_ms),
+                max: Duration::from_millis(config.max_backoff_ms),
+                jitter: config.jitter,
+            },
+        }
+    }
+}
+
+/// A failed attempt.
+#[derive(Debug)]
+pub enum Failure {
+    /// Worth another attempt: no connection, a timeout, 429 or 5xx
+    Retry(anyhow::Error),
+    /// The destination answered and refused; retrying will not help
+    Reject(anyhow::Error),
+}
+
+impl Failure {
+    pub fn error(&self) -> &anyhow::Error {
+        match self {
+            Failure::Retry(e) | Failure::Reject(e) => e,
+        }
+    }
+
+    pub fn into_error(self) -> anyhow::Error {
+        match self {
+            Failure::Retry(e) | Failure::Reject(e) => e,
+        }
+    }
+
+    /// A request that got no response; a blocked egress category is not
+    /// retried.
+    pub fn request(error: reqwest::Error) -> Self {
+        let mut source: Option<&(dyn std::error::Error + 'static)> = Some(&error);
+        while let Some(e) = source {
+            if e.is::<EgressBlocked>() {
+                return Failure::Reject(error.into());
+            }
+            source = e.source();
+        }
+        Failure::Retry(error.into())
+    }
+}
+
+/// The response of a request if it succeeded; otherwise the failure, with
+/// the status and body, and 429 and 5xx worth a retry.
+pub async fn check_response(
+    result: reqwest::Result<reqwest::Response>,
+) -> Result<reqwest::Response, Failure> {
+    let response = result.map_err(Failure::request)?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    let error = anyhow::anyhow!("HTTP {status}: {}", body.trim());
+    if status.is_client_error() && status != reqwest::StatusCode::TOO_MANY_REQUESTS {
+        Err(Failure::Reject(error))
+    } else {
+        Err(Failure::Retry(error))
+    }
+}
+
+/// Error of a call refused while the destination's breaker is open.
+#[derive(Debug)]
+pub struct BreakerOpen {
+    pub destination: String,
+    pub retry_in: Duration,
+}
+
+impl std::fmt::Display for BreakerOpen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "circuit breaker for {} is open, next attempt in {}s",
+            self.destination,
+            self.retry_in.as_secs().max(1)
+        )
+    }
+}
+
+impl std::error::Error for BreakerOpen {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    Closed,
+    Open,
+    /// One call is let through to probe the destination
+    HalfOpen,
+}
+
+/// One destination's breaker and counters, as shown in `/status`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DestinationStatus {
+    pub name: String,
+    pub state: BreakerState,
+    pub consecutive_failures: u32,
+    pub calls: u64,
+    pub retries: u64,
+    /// Calls that failed after their last attempt
+    pub failures: u64,
+    /// Calls failed without a request while the breaker was open
+    pub short_circuited: u64,
+    /// Retries skipped because the destination's budget was spent
+    pub budget_exhausted: u64,
+    pub opened_total: u64,
+    /// Unix time the breaker last opened
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opened_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+struct DestinationState {
+    status: DestinationStatus,
+    open_until: Option<Instant>,
+    probing: bool,
+    budget_window: Instant,
+    budget_used: u32,
+}
+
+/// Breaker and retry budget of one destination.
+pub struct Destination {
+    config: RetryConfig,
+    state: Mutex<DestinationState>,
+}
+
+impl Destination {
+    /// Outside a [`Retrier`], for clients built without the egress guard.
+    pub fn new(name: &str, config: RetryConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(DestinationState {
+                status: DestinationStatus {
+                    name: name.to_string(),
+                    state: BreakerState::Closed,
+                    consecutive_failures: 0,
+                    calls: 0,
+                    retries: 0,
+                    failures: 0,
+                    short_circuited: 0,
+                    budget_exhausted: 0,
+                    opened_total: 0,
+                    opened_at: None,
+                    last_error: None,
+                },
+                open_until: None,
+                probing: false,
+                budget_window: Instant::now(),
+                budget_used: 0,
+            }),
+        }
+    }
+
+    /// Whether a call may go out now.
+    fn admit(&self, now: Instant) -> Result<(), BreakerOpen> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(until) = state.open_until {
+            if now < until || state.probing {
+                state.status.short_circuited += 1;
+                return Err(BreakerOpen {
+                    destination: state.status.name.clone(),
+                    retry_in: until.saturating_duration_since(now),
+                });
+            }
+            state.probing = true;
+            state.status.state = BreakerState::HalfOpen;
+        }
+        state.status.calls += 1;
+        Ok(())
+    }
+
+    /// Take a retry from the budget.
+    fn take_retry(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if now.duration_since(state.budget_window) >= Duration::from_secs(60) {
+            state.budget_window = now;
+            state.budget_used = 0;
+        }
+        if state.budget_used >= self.config.budget_per_min {
+            state.status.budget_exhausted += 1;
+            return false;
+        }
+        state.budget_used += 1;
+        state.status.retries += 1;
+        true
+    }
+
+    /// Record the outcome of a call: a success or a rejection shows the
+    /// destination is up, a retryable failure counts towards the breaker.
+    fn finish(&self, now: Instant, failure: Option<&Failure>) {
+        let mut state = self.state.lock().unwrap();
+        let probing = std::mem::take(&mut state.probing);
+        if let Some(failure) = failure {
+            state.status.last_error = Some(format!("{:#}", failure.error()));
+        }
+        let Some(Failure::Retry(_)) = failure else {
+            if failure.is_some() {
+                state.status.failures += 1;
+            }
+            state.status.consecutive_failures = 0;
+            state.open_until = None;
+            state.status.state = BreakerState::Closed;
+            return;
+        };
+        state.status.failures += 1;
+        state.status.consecutive_failures += 1;
+        let threshold = self.config.breaker_failures;
+        if probing || (threshold > 0 && state.status.consecutive_failures >= threshold) {
+            if state.status.state == BreakerState::Closed {
+                state.status.opened_total += 1;
+            }
+            state.status.opened_at = Some(now_secs());
+            state.open_until = Some(now + Duration::from_secs(self.config.breaker_open_secs));
+            state.status.state = BreakerState::Open;
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.state.lock().unwrap().status.state == BreakerState::Open
+    }
+
+    /// Run `op`, given the attempt number (1-based), until it succeeds, is
+    /// rejected or `policy` runs out of attempts; returns the outcome and
+    /// the attempts made (0 when the breaker refused the call).
+    pub async fn run_with<T, F, Fut>(
+        &self,
+        policy: &RetryPolicy,
+        mut op: F,
+    ) -> (Result<T, Failure>, u32)
+    where
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<T, Failure>>,
+    {
+        if let Err(open) = self.admit(Instant::now()) {
+            return (Err(Failure::Retry(open.into())), 0);
+        }
+        let mut attempt = 1;
+        loop {
+            let failure = match op(attempt).await {
+                Ok(value) => {
+                    self.finish(Instant::now(), None);
+                    return (Ok(value), attempt);
+                }
+                Err(failure) => failure,
+            };
+            let retry = matches!(failure, Failure::Retry(_))
+                && attempt < policy.max_attempts
+                && !self.is_open()
+                && self.take_retry(Instant::now());
+            if !retry {
+                self.finish(Instant::now(), Some(&failure));
+                return (Err(failure), attempt);
+            }
+            tokio::time::sleep(policy.backoff.delay(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    /// [`Destination::run_with`] under the configured policy.
+    pub async fn run<T, F, Fut>(&self, op: F) -> anyhow::Result<T>
+    where
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<T, Failure>>,
+    {
+        let policy = RetryPolicy::from(&self.config);
+        self.run_with(&policy, op)
+            .await
+            .0
+            .map_err(Failure::into_error)
+    }
+
+    pub fn status(&self) -> DestinationStatus {
+        self.state.lock().unwrap().status.clone()
+    }
+}
+
+/// Destinations by name, created on first use.
+pub struct Retrier {
+    config: RetryConfig,
+    destinations: Mutex<BTreeMap<String, Arc<Destination>>>,
+}
+
+impl Retrier {
+    pub fn new(config: RetryConfig) -> Self {
+        Self {
+            config,
+            destinations: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &RetryConfig {
+        &self.config
+    }
+
+    pub fn destination(&self, name: &str) -> Arc<Destination> {
+        let mut destinations = self.destinations.lock().unwrap();
+        Arc::clone(
+            destinations
+                .entry(name.to_string())
+                .or_insert_with(|| Arc::new(Destination::new(name, self.config.clone()))),
+        )
+    }
+
+    pub fn status(&self) -> Vec<DestinationStatus> {
+        self.destinations
+            .lock()
+            .unwrap()
+            .values()
+            .map(|destination| destination.status())
+            .collect()
+    }
+}
+
+/// Destination name of a URL: its host, leaving out paths and queries that
+/// may carry tokens.
+pub fn url_destination(prefix: &str, url: &str) -> String {
+    let host = reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_else(|| "invalid-url".to_string());
+    format!("{prefix}:{host}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> RetryConfig {
+        RetryConfig {
+            initial_backoff_ms: 1,
+            max_backoff_ms: 1,
+            budget_per_min: 3,
+            breaker_failures: 2,
+            ..RetryConfig::default()
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_cap_with_jitter() {
+        let backoff = Backoff {
+            initial: Duration::from_secs(5),
+            max: Duration::from_secs(300),
+            jitter: 0.5,
+        };
+        assert_eq!(backoff.ceiling(1), Duration::from_secs(5));
+        assert_eq!(backoff.ceiling(3), Duration::from_secs(20));
+        assert_eq!(backoff.ceiling(40), Duration::from_secs(300));
+        for attempt in 1..10 {
+            let delay = backoff.delay(attempt);
+            assert!(delay <= backoff.ceiling(attempt));
+            assert!(delay >= backoff.ceiling(attempt) / 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_within_budget_then_opens_the_breaker() {
+        let retrier = Retrier::new(config());
+        let slack = retrier.destination("slack");
+
+        // Succeeds on the third attempt
+        let (result, attempts) = slack
+            .run_with(&RetryPolicy::from(&config()), |attempt| async move {
+                if attempt < 3 {
+                    Err(Failure::Retry(anyhow::anyhow!("HTTP 503")))
+                } else {
+                    Ok(attempt)
+                }
+            })
+            .await;
+        assert_eq!((result.unwrap(), attempts), (3, 3));
+
+        // Rejections are not retried and do not count towards the breaker
+        let (result, attempts) = slack
+            .run_with(&RetryPolicy::from(&config()), |_| async {
+                Err::<(), _>(Failure::Reject(anyhow::anyhow!("HTTP 400")))
+            })
+            .await;
+        assert!(matches!(result, Err(Failure::Reject(_))));
+        assert_eq!(attempts, 1);
+
+        // One retry left in the budget, then two failed calls open it
+        for expected in [2, 1] {
+            let (_, attempts) = slack
+                .run_with(&RetryPolicy::from(&config()), |_| async {
+                    Err::<(), _>(Failure::Retry(anyhow::anyhow!("connection refused")))
+                })
+                .await;
+            assert_eq!(attempts, expected);
+        }
+        let status = slack.status();
+        assert_eq!(status.state, BreakerState::Open);
+        assert_eq!((status.retries, status.budget_exhausted), (3, 2));
+        assert_eq!(status.last_error.as_deref(), Some("connection refused"));
+
+        let refused = slack.run(|_| async { Ok(()) }).await.unwrap_err();
+        assert!(refused.is::<BreakerOpen>());
+        assert_eq!(slack.status().short_circuited, 1);
+        assert_eq!(retrier.status().len(), 1);
+    }
+
+    #[test]
+    fn half_open_probe_closes_or_reopens() {
+        let destination = Destination::new("llm", config());
+        let now = Instant::now();
+        for _ in 0..2 {
+            destination.admit(now).unwrap();
+            destination.finish(now, Some(&Failure::Retry(anyhow::anyhow!("timeout"))));
+        }
+        assert!(destination.admit(now).is_err());
+
+        let later = now + Duration::from_secs(61);
+        destination.admit(later).unwrap();
+        assert_eq!(destination.status().state, BreakerState::HalfOpen);
+        assert!(destination.admit(later).is_err(), "one probe at a time");
+        destination.finish(later, Some(&Failure::Retry(anyhow::anyhow!("timeout"))));
+        assert_eq!(destination.status().state, BreakerState::Open);
+        assert_eq!(destination.status().opened_total, 1);
+
+        let much_later = later + Duration::from_secs(61);
+        destination.admit(much_later).unwrap();
+        destination.finish(much_later, None);
+        assert_eq!(destination.status().state, BreakerState::Closed);
+        assert_eq!(destination.status().consecutive_failures, 0);
+    }
+
+    #[tokio::test]
+    async fn blocked_egress_is_rejected_not_retried() {
+        let guard = Arc::new(crate::egress::OfflineGuard::new(true));
+        let client = guard.client(crate::egress::Egress::Webhooks, reqwest::Client::builder());
+        let failure = check_response(client.get("http://127.0.0.1:9/").send().await)
+            .await
+            .unwrap_err();
+        assert!(matches!(failure, Failure::Reject(_)), "{failure:?}");
+    }
+
+    #[test]
+    fn url_destinations_keep_only_the_host() {
+        assert_eq!(
+            url_destination("webhook", "https://hooks.example.com/T0/B0/secret"),
+            "webhook:hooks.example.com"
+        );
+        assert_eq!(url_destination("llm", "not a url"), "llm:invalid-url");
+    }
+}
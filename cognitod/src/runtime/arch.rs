@@ -0,0 +1,267 @@
+//! Architecture checks for deployment gating
+//!
+//! The daemon and its BPF object run on x86_64 and arm64, and a few things
+//! differ between them. Kprobes read arguments through `pt_regs`, whose
+//! layout is fixed when the object is built. The page size can be 4K, 16K or
+//! 64K on arm64. Cache lines are 64 bytes on x86_64 and may be 128 bytes on
+//! some arm64 parts, which the sequencer's 128-byte slots cover. Classic
+//! tracepoints are read at fixed offsets that are meant to be the same
+//! everywhere, but that depends on the kernel. [`ArchReport`] holds one
+//! check per assumption. `--probe-only` prints it and exits non-zero when a
+//! check fails, so a rollout can stop before the agent runs.
+
+use linnix_ai_ebpf_common::{SequencedSlot, tracepoint_layout};
+use serde::Serialize;
+use std::path::Path;
+
+/// tracefs mount points, newest first.
+const TRACEFS_ROOTS: [&str; 2] = ["/sys/kernel/tracing", "/sys/kernel/debug/tracing"];
+
+const CACHE_LINE_PATH: &str = "/sys/devices/system/cpu/cpu0/cache/index0/coherency_line_size";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, ok: bool, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ok,
+            detail: detail.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchReport {
+    pub host: &'static str,
+    /// Architecture the BPF object's kprobes were built for, when recorded
+    pub object: Option<String>,
+    pub page_size: u64,
+    pub cache_line: Option<u64>,
+    pub checks: Vec<Check>,
+}
+
+impl ArchReport {
+    /// Check the running host against `object_arch` and the BTF-derived
+    /// offset problems (see `bpf_config::offset_problems`), if any were
+    /// derived.
+    pub fn collect(object_arch: Option<String>, offset_problems: Option<Vec<String>>) -> Self {
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(0) as u64;
+        let cache_line = std::fs::read_to_string(CACHE_LINE_PATH)
+            .ok()
+            .and_then(|text| text.trim().parse().ok());
+        let host = std::env::consts::ARCH;
+
+        let mut checks = vec![
+            object_arch_check(host, object_arch.as_deref()),
+            page_size_check(host, page_size),
+            cache_line_check(cache_line),
+        ];
+        if let Some(problems) = offset_problems {
+            checks.push(Check::new(
+                "btf_offsets",
+                problems.is_empty(),
+                if problems.is_empty() {
+                    "offsets derived from kernel BTF are consistent".to_string()
+                } else {
+                    problems.join("; ")
+                },
+            ));
+        }
+        for event in tracepoint_layout::EVENTS {
+            let format = tracepoint_format(event.category, event.event);
+            checks.push(tracepoint_check(event, format.as_deref()));
+        }
+
+        Self {
+            host,
+            object: object_arch,
+            page_size,
+            cache_line,
+            checks,
+        }
+    }
+
+    pub fn ok(&self) -> bool {
+        self.checks.iter().all(|check| check.ok)
+    }
+}
+
+fn object_arch_check(host: &str, object: Option<&str>) -> Check {
+    match object {
+        Some(object) if object != host => Check::new(
+            "object_arch",
+            false,
+            format!(
+                "BPF object built for {object}, host is {host}; kprobe features are disabled (rebuild with `cargo xtask build-ebpf --arch {host}`)"
+            ),
+        ),
+        Some(object) => Check::new("object_arch", true, format!("built for {object}")),
+        None => Check::new(
+            "object_arch",
+            true,
+            "object does not record its architecture",
+        ),
+    }
+}
+
+fn page_size_check(host: &str, page_size: u64) -> Check {
+    let ok = if host == "x86_64" {
+        page_size == 4096
+    } else {
+        page_size.is_power_of_two() && (4096..=65536).contains(&page_size)
+    };
+    Check::new("page_size", ok, format!("{page_size} bytes"))
+}
+
+/// Sequencer slots are aligned so that no two share a cache line.
+#[allow(clippy::manual_is_multiple_of)] // is_multiple_of not stable in nightly-2024-12-10
+fn cache_line_check(cache_line: Option<u64>) -> Check {
+    let slot_align = std::mem::align_of::<SequencedSlot>() as u64;
+    match cache_line {
+        Some(line) => Check::new(
+            "cache_line",
+            line > 0 && slot_align % line == 0,
+            format!("{line} bytes, sequencer slots aligned to {slot_align}"),
+        ),
+        None => Check::new("cache_line", true, "unknown"),
+    }
+}
+
+/// The tracefs `format` file of a tracepoint; `None` when tracefs is not
+/// mounted or the kernel lacks the tracepoint.
+pub fn tracepoint_format(category: &str, event: &str) -> Option<String> {
+    let root = TRACEFS_ROOTS
+        .iter()
+        .map(Path::new)
+        .find(|root| root.join("events").is_dir())?;
+    std::fs::read_to_string(
+        root.join("events")
+            .join(category)
+            .join(event)
+            .join("format"),
+    )
+    .ok()
+}
+
+/// Offset and size of each field in a tracefs `format` file.
+pub fn parse_format(text: &str) -> Vec<(String, usize, usize)> {
+    text.lines()
+        .filter_map(|line| {
+            let mut name = None;
+            let mut offset = None;
+            let mut size = None;
+            for part in line.trim().split(';') {
+                let part = part.trim();
+                if let Some(decl) = part.strip_prefix("field:") {
+                    // `unsigned long call_site`, `char comm[16]`
+                    let last = decl.split_whitespace().last()?;
+                    name = Some(last.split('[').next()?.to_string());
+                } else if let Some(value) = part.strip_prefix("offset:") {
+                    offset = value.parse().ok();
+                } else if let Some(value) = part.strip_prefix("size:") {
+                    size = value.parse().ok();
+                }
+            }
+            Some((name?, offset?, size?))
+        })
+        .collect()
+}
+
+fn tracepoint_check(event: &tracepoint_layout::Event, format: Option<&str>) -> Check {
+    let name = format!("tracepoint:{}/{}", event.category, event.event);
+    let Some(format) = format else {
+        return Check::new(name, true, "format not readable; not verified");
+    };
+    let fields = parse_format(format);
+    let mut mismatches = Vec::new();
+    for &(field, offset, size) in event.fields {
+        match fields.iter().find(|(name, ..)| name == field) {
+            Some(&(_, o, s)) if (o, s) == (offset, size) => {}
+            Some((_, o, s)) => {
+                mismatches.push(format!("{field} at {o}+{s}, expected {offset}+{size}"))
+            }
+            None => mismatches.push(format!("{field} missing")),
+        }
+    }
+    if mismatches.is_empty() {
+        Check::new(name, true, "layout matches")
+    } else {
+        Check::new(name, false, mismatches.join("; "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `events/sock/inet_sock_set_state/format` from a 6.1 arm64 kernel.
+    const ARM64_SOCK_STATE: &str = "name: inet_sock_set_state
+ID: 1401
+format:
+\tfield:unsigned short common_type;\toffset:0;\tsize:2;\tsigned:0;
+\tfield:unsigned char common_flags;\toffset:2;\tsize:1;\tsigned:0;
+\tfield:unsigned char common_preempt_count;\toffset:3;\tsize:1;\tsigned:0;
+\tfield:int common_pid;\toffset:4;\tsize:4;\tsigned:1;
+
+\tfield:const void * skaddr;\toffset:8;\tsize:8;\tsigned:0;
+\tfield:int oldstate;\toffset:16;\tsize:4;\tsigned:1;
+\tfield:int newstate;\toffset:20;\tsize:4;\tsigned:1;
+\tfield:__u16 sport;\toffset:24;\tsize:2;\tsigned:0;
+\tfield:__u16 dport;\toffset:26;\tsize:2;\tsigned:0;
+\tfield:__u16 family;\toffset:28;\tsize:2;\tsigned:0;
+\tfield:__u16 protocol;\toffset:30;\tsize:2;\tsigned:0;
+\tfield:__u8 saddr[4];\toffset:32;\tsize:4;\tsigned:0;
+\tfield:__u8 daddr[4];\toffset:36;\tsize:4;\tsigned:0;
+\tfield:__u8 saddr_v6[16];\toffset:40;\tsize:16;\tsigned:0;
+\tfield:__u8 daddr_v6[16];\toffset:56;\tsize:16;\tsigned:0;
+
+print fmt: \"family=%s protocol=%s\"
+";
+
+    fn sock_state() -> &'static tracepoint_layout::Event {
+        tracepoint_layout::EVENTS
+            .iter()
+            .find(|event| event.event == "inet_sock_set_state")
+            .unwrap()
+    }
+
+    #[test]
+    fn checks_tracepoint_layouts_against_format_files() {
+        let fields = parse_format(ARM64_SOCK_STATE);
+        assert_eq!(fields[4], ("skaddr".to_string(), 8, 8));
+        assert_eq!(fields[12], ("daddr".to_string(), 36, 4));
+
+        let check = tracepoint_check(sock_state(), Some(ARM64_SOCK_STATE));
+        assert!(check.ok, "{}", check.detail);
+
+        // A kernel that widened `sport` would shift everything after it.
+        let shifted = ARM64_SOCK_STATE.replace("offset:28;\tsize:2", "offset:32;\tsize:2");
+        let check = tracepoint_check(sock_state(), Some(&shifted));
+        assert!(!check.ok);
+        assert_eq!(check.detail, "family at 32+2, expected 28+2");
+
+        assert!(tracepoint_check(sock_state(), None).ok);
+    }
+
+    #[test]
+    fn checks_host_assumptions() {
+        assert!(page_size_check("x86_64", 4096).ok);
+        assert!(!page_size_check("x86_64", 16384).ok);
+        assert!(page_size_check("aarch64", 65536).ok);
+        assert!(!page_size_check("aarch64", 8000).ok);
+
+        assert!(cache_line_check(Some(64)).ok);
+        assert!(cache_line_check(Some(128)).ok);
+        assert!(!cache_line_check(Some(256)).ok);
+
+        assert!(object_arch_check("aarch64", Some("aarch64")).ok);
+        assert!(object_arch_check("aarch64", None).ok);
+        assert!(!object_arch_check("aarch64", Some("x86_64")).ok);
+    }
+}
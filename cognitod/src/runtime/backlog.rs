@@ -0,0 +1,206 @@
+//! Bounded catch-up through the sequencer backlog.
+//!
+//! A consumer resuming from a saved cursor on a busy host can find up to a
+//! ring of tickets (about a million) between the cursor and the kernel's
+//! head. Reading them first would hold back live events until they are
+//! done, so the consumer reads live from the head and the backlog is read
+//! behind it, at most `catchup_per_poll` tickets between two live batches.
+//! Backlogged events older than `summarize_after` are counted per event
+//! type instead of being handed to the handlers. Slots that producers
+//! overwrite before the backlog reaches them are counted as lost.
+//!
+//! Until the backlog is done the checkpointed cursor stays at the backlog
+//! position, so a restart reads the rest of it again and the live events
+//! read since then count as replayed. Progress is shown under
+//! `sequencer_backlog` in `/status`.
+
+use linnix_ai_ebpf_common::ProcessEvent;
+use log::info;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::event_schema::event_type_name;
+use crate::metrics::Metrics;
+use crate::metrics::backlog::BacklogStatus;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BacklogConfig {
+    /// Most backlogged tickets read per poll
+    pub catchup_per_poll: usize,
+    /// Backlogged events older than this are counted only
+    pub summarize_after: Option<Duration>,
+}
+
+/// Tickets from a restored cursor up to the kernel's head at startup.
+pub struct Backlog {
+    next: u64,
+    summarize_after_ns: Option<u64>,
+    status: BacklogStatus,
+    metrics: Arc<Metrics>,
+}
+
+impl Backlog {
+    /// `None` when the cursor is already at the head.
+    pub fn new(
+        cursor: u64,
+        kernel_head: u64,
+        config: BacklogConfig,
+        metrics: Arc<Metrics>,
+    ) -> Option<Self> {
+        if cursor >= kernel_head {
+            return None;
+        }
+        let total = kernel_head - cursor;
+        info!(
+            "[sequencer] {} backlogged tickets, reading live from {} and at most {} behind it per poll",
+            total, kernel_head, config.catchup_per_poll
+        );
+        let backlog = Self {
+            next: cursor,
+            summarize_after_ns: config.summarize_after.map(|age| age.as_nanos() as u64),
+            status: BacklogStatus {
+                first_ticket: cursor,
+                kernel_head,
+                total,
+                remaining: total,
+                catchup_per_poll: config.catchup_per_poll.max(1),
+                summarize_after_secs: config.summarize_after.map(|age| age.as_secs()),
+                ..Default::default()
+            },
+            metrics,
+        };
+        backlog.metrics.backlog().update(&backlog.status);
+        Some(backlog)
+    }
+
+    pub fn done(&self) -> bool {
+        self.status.done
+    }
+
+    /// Cursor to checkpoint while the consumer reads live at `live`.
+    pub fn checkpoint(&self, live: u64) -> u64 {
+        if self.done() { live } else { self.next }
+    }
+
+    /// Read the next backlogged tickets with `read` and hand the recent
+    /// ones to `sink`; returns how many. Tickets below `oldest` are no
+    /// longer in the ring. `now_ns` is `CLOCK_MONOTONIC`, as event stamps.
+    pub fn poll(
+        &mut self,
+        oldest: u64,
+        now_ns: u64,
+        mut read: impl FnMut(u64) -> Option<ProcessEvent>,
+        mut sink: impl FnMut(u64, ProcessEvent),
+    ) -> usize {
+        if self.done() {
+            return 0;
+        }
+        let end = self.status.kernel_head;
+        if self.next < oldest {
+            let lapped = oldest.min(end) - self.next;
+            self.status.lost += lapped;
+            self.next += lapped;
+        }
+        let stop = end.min(self.next + self.status.catchup_per_poll as u64);
+        let mut handed = 0;
+        while self.next < stop {
+            let ticket = self.next;
+            self.next += 1;
+            let Some(event) = read(ticket) else {
+                self.status.lost += 1;
+                continue;
+            };
+            let stale = self
+                .summarize_after_ns
+                .is_some_and(|age| event.ts_ns != 0 && now_ns.saturating_sub(event.ts_ns) > age);
+            if stale {
+                self.status.summarized += 1;
+                *self
+                    .status
+                    .summarized_by_type
+                    .entry(event_type_name(event.event_type).to_string())
+                    .or_default() += 1;
+            } else {
+                self.status.processed += 1;
+                handed += 1;
+                sink(ticket, event);
+            }
+        }
+        self.status.polls += 1;
+        self.status.remaining = end - self.next;
+        if self.next >= end {
+            self.status.done = true;
+            info!(
+                "[sequencer] backlog done: {} processed, {} summarized, {} lost",
+                self.status.processed, self.status.summarized, self.status.lost
+            );
+        }
+        self.metrics.backlog().update(&self.status);
+        handed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linnix_ai_ebpf_common::EventType;
+
+    fn event(event_type: EventType, ts_ns: u64) -> ProcessEvent {
+        ProcessEvent {
+            pid: 1,
+            ppid: 0,
+            uid: 0,
+            gid: 0,
+            event_type: event_type as u32,
+            ts_ns,
+            seq: 0,
+            comm: [0; 16],
+            exit_time_ns: 0,
+            cpu_pct_milli: 0,
+            mem_pct_milli: 0,
+            data: 0,
+            data2: 0,
+            aux: 0,
+            aux2: 0,
+            cgroup_id: 0,
+        }
+    }
+
+    #[test]
+    fn catches_up_in_capped_polls_and_summarizes_old_events() {
+        let secs = 1_000_000_000;
+        let metrics = Arc::new(Metrics::new());
+        let config = BacklogConfig {
+            catchup_per_poll: 4,
+            summarize_after: Some(Duration::from_secs(60)),
+        };
+        assert!(Backlog::new(10, 10, config, Arc::clone(&metrics)).is_none());
+        let mut backlog = Backlog::new(100, 110, config, Arc::clone(&metrics)).unwrap();
+        // Tickets 100-103 are two minutes old, 107 was overwritten
+        let read = |ticket: u64| match ticket {
+            100..=103 => Some(event(EventType::Fork, 60 * secs)),
+            107 => None,
+            _ => Some(event(EventType::Exec, 170 * secs)),
+        };
+        let mut handed = Vec::new();
+        let now = 180 * secs;
+
+        assert_eq!(backlog.poll(0, now, read, |t, _| handed.push(t)), 0);
+        assert_eq!(backlog.checkpoint(500), 104);
+        assert_eq!(backlog.poll(0, now, read, |t, _| handed.push(t)), 3);
+        assert_eq!(handed, vec![104, 105, 106]);
+        let status = metrics.backlog().snapshot().unwrap();
+        assert_eq!(
+            (status.summarized, status.lost, status.remaining),
+            (4, 1, 2)
+        );
+        assert_eq!(status.summarized_by_type["fork"], 4);
+        assert!(!status.done);
+
+        // Producers lapped the rest
+        assert_eq!(backlog.poll(200, now, read, |t, _| handed.push(t)), 0);
+        assert!(backlog.done());
+        assert_eq!(metrics.backlog().snapshot().unwrap().lost, 3);
+        assert_eq!(backlog.checkpoint(500), 500);
+    }
+}
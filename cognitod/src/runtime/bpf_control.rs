@@ -0,0 +1,411 @@
+//! Typed control plane for the loaded eBPF object.
+//!
+//! `BpfControl` takes ownership of the `Ebpf` handle once programs are attached
+//! and is the only place userspace touches control maps (`SEQUENCER_ENABLED`,
+//! `SAMPLE_DIVISOR`, `PID_FILTER`, `CGROUP_SCOPE`, `CGROUP_ALLOW`), plus the
+//! per-process `TaskMap`s swept by `map_cleanup`, the page-fault counts
+//! drained by `page_faults` and the per-CPU sequencer counters read by
+//! `sequencer_diag`. Map access is serialized
+//! behind an async mutex so API handlers can share one `Arc<BpfControl>`.
+//! Maps missing from the loaded object (older builds, the rss_trace fallback)
+//! surface as errors instead of panics.
+
+use anyhow::{Context, anyhow, bail};
+use aya::maps::{Array, HashMap as BpfHashMap, MapData, PerCpuArray};
+use aya::{Ebpf, Pod};
+use linnix_ai_ebpf_common::{
+    CGROUP_ALLOW_MAX_ENTRIES, CONTROL_EVENT_TYPES, EventType, PID_FILTER_MAX_ENTRIES,
+    TASK_STATE_MAX_ENTRIES, TelemetryConfig,
+};
+use log::info;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU32, Ordering};
+use tokio::sync::Mutex;
+
+const SEQUENCER_ENABLED: &str = "SEQUENCER_ENABLED";
+const SAMPLE_DIVISOR: &str = "SAMPLE_DIVISOR";
+const PID_FILTER: &str = "PID_FILTER";
+const CGROUP_SCOPE: &str = "CGROUP_SCOPE";
+const CGROUP_ALLOW: &str = "CGROUP_ALLOW";
+const PAGE_FAULT_SUPPRESSED: &str = "PAGE_FAULT_SUPPRESSED";
+const SEQUENCER_CPU_SEQ: &str = "SEQUENCER_CPU_SEQ";
+
+/// `TASK_STATS` value: last_runtime_ns, last_timestamp_ns.
+type TaskStatsValue = [u64; 2];
+
+/// Per-process kernel maps keyed by pid. The exit tracepoint removes a pid's
+/// entries, so only processes exiting while probes are detached leave them
+/// behind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskMap {
+    TaskStats,
+    PageFaultThrottle,
+}
+
+impl TaskMap {
+    pub const ALL: [TaskMap; 2] = [TaskMap::TaskStats, TaskMap::PageFaultThrottle];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TaskMap::TaskStats => "TASK_STATS",
+            TaskMap::PageFaultThrottle => "PAGE_FAULT_THROTTLE",
+        }
+    }
+
+    pub fn max_entries(self) -> u32 {
+        TASK_STATE_MAX_ENTRIES
+    }
+}
+
+/// Event types that feed the process table; sampling them would corrupt
+/// lineage, lifetimes and startup latency, so `set_sampling` refuses them.
+const LIFECYCLE_EVENTS: [EventType; 4] = [
+    EventType::Exec,
+    EventType::Fork,
+    EventType::Exit,
+    EventType::Listen,
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SamplingEntry {
+    pub event_type: u32,
+    pub divisor: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CgroupScopeState {
+    /// Hierarchy level compared against `cgroup_ids`.
+    pub level: u32,
+    pub cgroup_ids: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BpfControlState {
+    /// `None` when the loaded object has no sequencer.
+    pub sequencer_enabled: Option<bool>,
+    pub sampling: Vec<SamplingEntry>,
+    pub filtered_pids: Vec<u32>,
+    /// `None` when scoping is disabled or unsupported by the object.
+    pub cgroup_scope: Option<CgroupScopeState>,
+    pub telemetry: Option<TelemetryConfig>,
+}
+
+pub struct BpfControl {
+    bpf: Mutex<Ebpf>,
+    telemetry: Option<TelemetryConfig>,
+    /// `SEQUENCER_ENABLED` value written when the sequencer is enabled
+    sequencer_partitions: AtomicU32,
+}
+
+impl BpfControl {
+    /// Wrap a loaded object. `telemetry` is the config written to the
+    /// read-only `TELEMETRY_CONFIG` global at load time; it cannot be read back
+    /// from the kernel, so we keep our copy.
+    pub fn new(bpf: Ebpf, telemetry: Option<TelemetryConfig>) -> Self {
+        Self {
+            bpf: Mutex::new(bpf),
+            telemetry,
+            sequencer_partitions: AtomicU32::new(1),
+        }
+    }
+
+    /// Enable the sequencer with `partitions` per-node rings from now on;
+    /// 1, the default, is the shared ring.
+    pub fn set_sequencer_partitions(&self, partitions: u32) {
+        self.sequencer_partitions
+            .store(partitions.max(1), Ordering::Relaxed);
+    }
+
+    pub fn telemetry_config(&self) -> Option<TelemetryConfig> {
+        self.telemetry
+    }
+
+    pub async fn enable_sequencer(&self) -> anyhow::Result<()> {
+        self.set_sequencer(true).await?;
+        info!("Sequencer ENABLED - eBPF will now use the lock-free ring buffer");
+        Ok(())
+    }
+
+    pub async fn disable_sequencer(&self) -> anyhow::Result<()> {
+        self.set_sequencer(false).await?;
+        info!("Sequencer DISABLED - eBPF will use legacy perf buffer");
+        Ok(())
+    }
+
+    async fn set_sequencer(&self, enabled: bool) -> anyhow::Result<()> {
+        let mode = if enabled {
+            self.sequencer_partitions.load(Ordering::Relaxed)
+        } else {
+            0
+        };
+        let mut bpf = self.bpf.lock().await;
+        let mut map = array_mut(&mut bpf, SEQUENCER_ENABLED)?;
+        map.set(0, mode, 0)
+            .with_context(|| format!("Failed to set {SEQUENCER_ENABLED} to {mode}"))
+    }
+
+    pub async fn sequencer_enabled(&self) -> anyhow::Result<bool> {
+        let bpf = self.bpf.lock().await;
+        let map = array(&bpf, SEQUENCER_ENABLED)?;
+        Ok(map.get(&0, 0)? != 0)
+    }
+
+    /// Element 0 of `SEQUENCER_ENABLED`: 0 off, 1 shared ring, N partitions.
+    pub async fn sequencer_mode(&self) -> anyhow::Result<u32> {
+        let bpf = self.bpf.lock().await;
+        let map = array(&bpf, SEQUENCER_ENABLED)?;
+        Ok(map.get(&0, 0)?)
+    }
+
+    /// Tickets reserved by each possible CPU since load, indexed by CPU.
+    pub async fn sequencer_cpu_tickets(&self) -> anyhow::Result<Vec<u64>> {
+        let bpf = self.bpf.lock().await;
+        let map: PerCpuArray<&MapData, u64> = PerCpuArray::try_from(
+            bpf.map(SEQUENCER_CPU_SEQ)
+                .ok_or_else(|| missing_map(SEQUENCER_CPU_SEQ))?,
+        )
+        .with_context(|| format!("Failed to open {SEQUENCER_CPU_SEQ} as a per-CPU array"))?;
+        Ok(map.get(&0, 0)?.to_vec())
+    }
+
+    /// Keep one in `divisor` events of `event_type` (0 or 1 keeps all).
+    pub async fn set_sampling(&self, event_type: EventType, divisor: u32) -> anyhow::Result<()> {
+        if LIFECYCLE_EVENTS.contains(&event_type) {
+            bail!("{event_type:?} events cannot be sampled");
+        }
+        let index = event_type as u32;
+        if index >= CONTROL_EVENT_TYPES {
+            bail!("event type {index} outside sampling map");
+        }
+        let mut bpf = self.bpf.lock().await;
+        let mut map = array_mut(&mut bpf, SAMPLE_DIVISOR)?;
+        map.set(index, divisor, 0)
+            .with_context(|| format!("Failed to set sampling divisor for {event_type:?}"))?;
+        info!("[bpf_control] sampling {event_type:?} 1/{}", divisor.max(1));
+        Ok(())
+    }
+
+    /// Current divisors for event types with sampling enabled.
+    pub async fn sampling(&self) -> anyhow::Result<Vec<SamplingEntry>> {
+        let bpf = self.bpf.lock().await;
+        let map = array(&bpf, SAMPLE_DIVISOR)?;
+        let mut out = Vec::new();
+        for event_type in 0..CONTROL_EVENT_TYPES {
+            let divisor = map.get(&event_type, 0)?;
+            if divisor > 1 {
+                out.push(SamplingEntry {
+                    event_type,
+                    divisor,
+                });
+            }
+        }
+        Ok(out)
+    }
+
+    /// Add and remove PIDs from the in-kernel drop filter.
+    pub async fn update_filters(&self, add: &[u32], remove: &[u32]) -> anyhow::Result<()> {
+        let mut bpf = self.bpf.lock().await;
+        let mut map: BpfHashMap<&mut MapData, u32, u8> = BpfHashMap::try_from(
+            bpf.map_mut(PID_FILTER)
+                .ok_or_else(|| missing_map(PID_FILTER))?,
+        )
+        .with_context(|| format!("Failed to open {PID_FILTER} as a hash map"))?;
+
+        for pid in remove {
+            // Removing an absent key is not an error for callers.
+            let _ = map.remove(pid);
+        }
+        let current = map.keys().filter_map(Result::ok).count();
+        let new = add.iter().filter(|pid| map.get(pid, 0).is_err()).count();
+        if current + new > PID_FILTER_MAX_ENTRIES as usize {
+            bail!("PID filter full ({PID_FILTER_MAX_ENTRIES} entries)");
+        }
+        for pid in add {
+            map.insert(pid, 1, 0)
+                .with_context(|| format!("Failed to filter pid {pid}"))?;
+        }
+        info!(
+            "[bpf_control] pid filter updated (+{} -{})",
+            add.len(),
+            remove.len()
+        );
+        Ok(())
+    }
+
+    pub async fn filtered_pids(&self) -> anyhow::Result<Vec<u32>> {
+        let bpf = self.bpf.lock().await;
+        let map: BpfHashMap<&MapData, u32, u8> =
+            BpfHashMap::try_from(bpf.map(PID_FILTER).ok_or_else(|| missing_map(PID_FILTER))?)?;
+        let mut pids: Vec<u32> = map.keys().filter_map(Result::ok).collect();
+        pids.sort_unstable();
+        Ok(pids)
+    }
+
+    /// Restrict telemetry to tasks whose ancestor cgroup at `level` is one of
+    /// `ids`. A `level` of 0 lifts the restriction.
+    pub async fn set_cgroup_scope(&self, level: u32, ids: &[u64]) -> anyhow::Result<()> {
+        if ids.len() > CGROUP_ALLOW_MAX_ENTRIES as usize {
+            bail!("cgroup scope limited to {CGROUP_ALLOW_MAX_ENTRIES} subtrees");
+        }
+        let mut bpf = self.bpf.lock().await;
+        {
+            let mut allow: BpfHashMap<&mut MapData, u64, u8> = BpfHashMap::try_from(
+                bpf.map_mut(CGROUP_ALLOW)
+                    .ok_or_else(|| missing_map(CGROUP_ALLOW))?,
+            )
+            .with_context(|| format!("Failed to open {CGROUP_ALLOW} as a hash map"))?;
+            let stale: Vec<u64> = allow.keys().filter_map(Result::ok).collect();
+            for id in stale.iter().filter(|id| !ids.contains(id)) {
+                let _ = allow.remove(id);
+            }
+            for id in ids {
+                allow
+                    .insert(id, 1, 0)
+                    .with_context(|| format!("Failed to allow cgroup {id}"))?;
+            }
+        }
+        // Written last so the kernel never sees a level without its ids.
+        let mut scope = array_mut(&mut bpf, CGROUP_SCOPE)?;
+        scope
+            .set(0, level, 0)
+            .with_context(|| format!("Failed to set {CGROUP_SCOPE}"))?;
+        info!(
+            "[bpf_control] cgroup scope level={level} subtrees={}",
+            ids.len()
+        );
+        Ok(())
+    }
+
+    pub async fn cgroup_scope(&self) -> anyhow::Result<Option<CgroupScopeState>> {
+        let bpf = self.bpf.lock().await;
+        let level = array(&bpf, CGROUP_SCOPE)?.get(&0, 0)?;
+        if level == 0 {
+            return Ok(None);
+        }
+        let allow: BpfHashMap<&MapData, u64, u8> = BpfHashMap::try_from(
+            bpf.map(CGROUP_ALLOW)
+                .ok_or_else(|| missing_map(CGROUP_ALLOW))?,
+        )?;
+        let mut cgroup_ids: Vec<u64> = allow.keys().filter_map(Result::ok).collect();
+        cgroup_ids.sort_unstable();
+        Ok(Some(CgroupScopeState { level, cgroup_ids }))
+    }
+
+    /// Pids with an entry in `map`.
+    pub async fn task_map_pids(&self, map: TaskMap) -> anyhow::Result<Vec<u32>> {
+        let bpf = self.bpf.lock().await;
+        match map {
+            TaskMap::TaskStats => hash_map_keys::<TaskStatsValue>(&bpf, map.name()),
+            TaskMap::PageFaultThrottle => hash_map_keys::<u64>(&bpf, map.name()),
+        }
+    }
+
+    /// Delete the entries of `pids` from `map`, returning how many existed.
+    pub async fn remove_task_map_pids(&self, map: TaskMap, pids: &[u32]) -> anyhow::Result<usize> {
+        let mut bpf = self.bpf.lock().await;
+        match map {
+            TaskMap::TaskStats => hash_map_remove::<TaskStatsValue>(&mut bpf, map.name(), pids),
+            TaskMap::PageFaultThrottle => hash_map_remove::<u64>(&mut bpf, map.name(), pids),
+        }
+    }
+
+    /// Page faults dropped by the in-kernel throttle, per pid.
+    pub async fn page_faults_suppressed(&self) -> anyhow::Result<Vec<(u32, u64)>> {
+        let bpf = self.bpf.lock().await;
+        let map: BpfHashMap<&MapData, u32, u64> = BpfHashMap::try_from(
+            bpf.map(PAGE_FAULT_SUPPRESSED)
+                .ok_or_else(|| missing_map(PAGE_FAULT_SUPPRESSED))?,
+        )
+        .with_context(|| format!("Failed to open {PAGE_FAULT_SUPPRESSED} as a hash map"))?;
+        Ok(map.iter().filter_map(Result::ok).collect())
+    }
+
+    /// Delete the suppressed-fault counts of `pids`, returning how many
+    /// existed.
+    pub async fn remove_page_faults_suppressed(&self, pids: &[u32]) -> anyhow::Result<usize> {
+        let mut bpf = self.bpf.lock().await;
+        hash_map_remove::<u64>(&mut bpf, PAGE_FAULT_SUPPRESSED, pids)
+    }
+
+    /// Snapshot of every control map; maps absent from the object read as
+    /// empty.
+    pub async fn state(&self) -> BpfControlState {
+        BpfControlState {
+            sequencer_enabled: self.sequencer_enabled().await.ok(),
+            sampling: self.sampling().await.unwrap_or_default(),
+            filtered_pids: self.filtered_pids().await.unwrap_or_default(),
+            cgroup_scope: self.cgroup_scope().await.ok().flatten(),
+            telemetry: self.telemetry_config(),
+        }
+    }
+}
+
+fn missing_map(name: &str) -> anyhow::Error {
+    anyhow!("{name} map not present in loaded BPF object")
+}
+
+fn array<'a>(bpf: &'a Ebpf, name: &str) -> anyhow::Result<Array<&'a MapData, u32>> {
+    Array::try_from(bpf.map(name).ok_or_else(|| missing_map(name))?)
+        .with_context(|| format!("Failed to open {name} as an array"))
+}
+
+fn array_mut<'a>(bpf: &'a mut Ebpf, name: &str) -> anyhow::Result<Array<&'a mut MapData, u32>> {
+    Array::try_from(bpf.map_mut(name).ok_or_else(|| missing_map(name))?)
+        .with_context(|| format!("Failed to open {name} as an array"))
+}
+
+fn hash_map_keys<V: Pod>(bpf: &Ebpf, name: &str) -> anyhow::Result<Vec<u32>> {
+    let map: BpfHashMap<&MapData, u32, V> =
+        BpfHashMap::try_from(bpf.map(name).ok_or_else(|| missing_map(name))?)
+            .with_context(|| format!("Failed to open {name} as a hash map"))?;
+    Ok(map.keys().filter_map(Result::ok).collect())
+}
+
+fn hash_map_remove<V: Pod>(bpf: &mut Ebpf, name: &str, keys: &[u32]) -> anyhow::Result<usize> {
+    let mut map: BpfHashMap<&mut MapData, u32, V> =
+        BpfHashMap::try_from(bpf.map_mut(name).ok_or_else(|| missing_map(name))?)
+            .with_context(|| format!("Failed to open {name} as a hash map"))?;
+    // Entries removed by the kernel since they were listed are not errors.
+    Ok(keys.iter().filter(|key| map.remove(key).is_ok()).count())
+}
+
+/// Parse an event type name as used by the control API (`net`, `file_io`,
+/// `syscall`, `block_io`, `page_fault`).
+pub fn parse_event_type(name: &str) -> Option<EventType> {
+    Some(match name {
+        "exec" => EventType::Exec,
+        "fork" => EventType::Fork,
+        "exit" => EventType::Exit,
+        "net" => EventType::Net,
+        "file_io" => EventType::FileIo,
+        "syscall" => EventType::Syscall,
+        "block_io" => EventType::BlockIo,
+        "page_fault" => EventType::PageFault,
+        "listen" => EventType::Listen,
+        "connection" => EventType::Connection,
+        "fd_pressure" => EventType::FdPressure,
+        "signal" => EventType::Signal,
+        "oom_kill" => EventType::OomKill,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_type_names_round_trip() {
+        for (name, ty) in [
+            ("net", EventType::Net),
+            ("file_io", EventType::FileIo),
+            ("page_fault", EventType::PageFault),
+            ("oom_kill", EventType::OomKill),
+        ] {
+            assert_eq!(parse_event_type(name), Some(ty));
+            assert!((ty as u32) < CONTROL_EVENT_TYPES);
+        }
+        assert_eq!(parse_event_type("bogus"), None);
+        assert!(LIFECYCLE_EVENTS.contains(&parse_event_type("fork").unwrap()));
+    }
+}
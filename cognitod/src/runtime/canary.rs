@@ -0,0 +1,239 @@
+//! End-to-end canary for the event pipeline.
+//!
+//! Every `interval_secs` the daemon runs the `[canary]` command, a short
+//! process it knows the pid of, in a cgroup of its own. The run passes when
+//! the process's exec and exit events have both reached the context store
+//! within `sla_ms` of the spawn, which covers the probes, the perf buffers,
+//! the listener and the handlers in one check. `canary_failed` fires once
+//! after `failures_before_alert` failed runs in a row and re-arms on the
+//! next pass; every run is counted under `canary` in `/metrics`.
+
+use linnix_ai_ebpf_common::EventType;
+use log::{info, warn};
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::alerts::{Alert, AlertSender, Severity};
+use crate::config::CanaryConfig;
+use crate::context::ContextStore;
+use crate::metrics::Metrics;
+use crate::metrics::latency::monotonic_ns;
+
+pub const RULE_NAME: &str = "canary_failed";
+
+/// Time between lookups while waiting for the canary's events.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The first of the canary's exec and exit events not yet in `ctx`. Events
+/// stamped before `since_ns` belong to an earlier process with the same pid.
+fn missing_event(ctx: &ContextStore, pid: u32, since_ns: u64) -> Option<&'static str> {
+    [(EventType::Exec, "exec"), (EventType::Exit, "exit")]
+        .into_iter()
+        .find(|(event_type, _)| {
+            !ctx.history(Some(pid), None, Some(*event_type as u32), 4)
+                .iter()
+                .any(|e| e.ts_ns >= since_ns)
+        })
+        .map(|(_, name)| name)
+}
+
+/// Move the calling process into the cgroup whose `cgroup.procs` is
+/// `procs`. Runs between fork and exec, so it only makes raw syscalls.
+fn join_cgroup(procs: &CString) {
+    unsafe {
+        let fd = libc::open(procs.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        if fd >= 0 {
+            // "0" is the writing process itself
+            libc::write(fd, b"0".as_ptr().cast(), 1);
+            libc::close(fd);
+        }
+    }
+}
+
+struct Canary {
+    command: Vec<String>,
+    /// `cgroup.procs` of the canary cgroup, when it could be created
+    procs: Option<CString>,
+    sla: Duration,
+}
+
+impl Canary {
+    fn new(config: &CanaryConfig) -> Self {
+        let procs = (!config.cgroup.is_empty())
+            .then(|| std::path::Path::new(&config.cgroup))
+            .and_then(|dir| match std::fs::create_dir_all(dir) {
+                Ok(()) => CString::new(dir.join("cgroup.procs").as_os_str().as_bytes()).ok(),
+                Err(e) => {
+                    warn!(
+                        "[canary] cannot create cgroup {}: {e}; running in the daemon's cgroup",
+                        dir.display()
+                    );
+                    None
+                }
+            });
+        Self {
+            command: config.command.clone(),
+            procs,
+            sla: Duration::from_millis(config.sla_ms),
+        }
+    }
+
+    /// Run the command once; the time until both events were stored.
+    async fn run(&self, ctx: &ContextStore) -> Result<Duration, String> {
+        let (program, args) = self
+            .command
+            .split_first()
+            .ok_or("no canary command configured")?;
+        let mut command = tokio::process::Command::new(program);
+        command
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true);
+        if let Some(procs) = self.procs.clone() {
+            unsafe {
+                command.pre_exec(move || {
+                    join_cgroup(&procs);
+                    Ok(())
+                });
+            }
+        }
+
+        let since_ns = monotonic_ns().unwrap_or(0);
+        let started = Instant::now();
+        let deadline = started + self.sla;
+        let mut child = command
+            .spawn()
+            .map_err(|e| format!("cannot start {program}: {e}"))?;
+        let pid = child.id().ok_or("canary exited before its pid was read")?;
+        if tokio::time::timeout_at(deadline.into(), child.wait())
+            .await
+            .is_err()
+        {
+            return Err(format!(
+                "canary pid {pid} still running after {}ms",
+                self.sla.as_millis()
+            ));
+        }
+
+        loop {
+            let missing = missing_event(ctx, pid, since_ns);
+            let Some(missing) = missing else {
+                return Ok(started.elapsed());
+            };
+            if Instant::now() >= deadline {
+                return Err(format!(
+                    "{missing} event of canary pid {pid} not stored within {}ms",
+                    self.sla.as_millis()
+                ));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Start the periodic canary runs.
+pub fn spawn(
+    context: Arc<ContextStore>,
+    metrics: Arc<Metrics>,
+    alerts: Option<AlertSender>,
+    config: &CanaryConfig,
+) {
+    let canary = Canary::new(config);
+    let interval = Duration::from_secs(config.interval_secs.max(1));
+    let failures_before_alert = config.failures_before_alert.max(1);
+    let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown".into());
+    metrics.canary().enable();
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(interval);
+        let mut raised = false;
+        loop {
+            tick.tick().await;
+            let result = canary.run(&context).await;
+            let failures = metrics.canary().record(
+                result
+                    .as_ref()
+                    .map(|latency| latency.as_millis() as u64)
+                    .map_err(Clone::clone),
+            );
+            let error = match result {
+                Ok(_) => {
+                    if raised {
+                        info!("[canary] events flow end to end again");
+                    }
+                    raised = false;
+                    continue;
+                }
+                Err(e) => e,
+            };
+            warn!("[canary] {error}");
+            if raised || failures < failures_before_alert {
+                continue;
+            }
+            raised = true;
+            if let Some(tx) = &alerts {
+                tx.send(Alert {
+                    rule: RULE_NAME.to_string(),
+                    severity: Severity::High,
+                    message: format!(
+                        "canary failed {failures} runs in a row: {error}; \
+                         process events are not reaching the daemon"
+                    ),
+                    host: host.clone(),
+                    detection: "threshold",
+                    suppressed_by: None,
+                    lineage: Vec::new(),
+                    children: Vec::new(),
+                    pod: None,
+                    evidence: None,
+                    annotations: None,
+                });
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PERCENT_MILLI_UNKNOWN, ProcessEvent, ProcessEventWire};
+
+    fn event(pid: u32, event_type: EventType, ts_ns: u64) -> ProcessEvent {
+        ProcessEvent::new(ProcessEventWire {
+            pid,
+            ppid: 1,
+            uid: 0,
+            gid: 0,
+            event_type: event_type as u32,
+            ts_ns,
+            seq: 0,
+            comm: *b"true\0\0\0\0\0\0\0\0\0\0\0\0",
+            exit_time_ns: 0,
+            cpu_pct_milli: PERCENT_MILLI_UNKNOWN,
+            mem_pct_milli: PERCENT_MILLI_UNKNOWN,
+            data: 0,
+            data2: 0,
+            aux: 0,
+            aux2: 0,
+            cgroup_id: 0,
+        })
+    }
+
+    #[test]
+    fn needs_exec_and_exit_of_this_run() {
+        let ctx = ContextStore::new(Duration::from_secs(60), 100, None);
+        // An earlier process with the same pid does not count
+        ctx.add(event(42, EventType::Exec, 100));
+        ctx.add(event(42, EventType::Exit, 200));
+        assert_eq!(missing_event(&ctx, 42, 1_000), Some("exec"));
+
+        ctx.add(event(42, EventType::Exec, 1_100));
+        assert_eq!(missing_event(&ctx, 42, 1_000), Some("exit"));
+        ctx.add(event(42, EventType::Exit, 1_200));
+        assert_eq!(missing_event(&ctx, 42, 1_000), None);
+    }
+}
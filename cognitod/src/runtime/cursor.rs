@@ -0,0 +1,397 @@
+//! Sequencer consumer cursor across restarts.
+//!
+//! The consumer's position in the ticket stream, and for each at-least-once
+//! exporter the ticket below which everything was delivered downstream, are
+//! written to a small JSON file at intervals (via a temporary file and a
+//! rename, so a crash leaves the previous checkpoint). While checkpoints are
+//! kept, the ring and the ticket counters ([`PINNED_MAPS`]) are pinned to
+//! bpffs and reused when the object is loaded again, so they outlive the
+//! daemon. On startup the saved position is matched against the kernel's
+//! ticket counter (`GLOBAL_SEQUENCER`), read before the sequencer is enabled:
+//!
+//! - maps created afresh, counter below the saved cursor, or a different
+//!   boot: the counter restarted, nothing saved applies (`reset`)
+//! - saved position still within one ring of the counter: reading resumes
+//!   at the lowest of the cursor and the delivery marks (`resume`)
+//! - producers lapped it: reading starts at the oldest slot still in the
+//!   ring and the overwritten tickets are counted as lost (`overrun`)
+//!
+//! Exporters skip tickets below their restored mark, so resuming redelivers
+//! nothing they confirmed; other handlers see at most `replayed` events
+//! twice. The decision is logged and shown under `sequencer_cursor` in
+//! `/status`.
+
+use anyhow::Context;
+use aya::Ebpf;
+use aya::maps::{Array, MapData};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+pub use crate::handler::delivery::DeliveryMarks;
+use crate::metrics::Metrics;
+use crate::metrics::cursor::{Decision, Reconciliation};
+use crate::utils::now_secs;
+
+const BOOT_ID: &str = "/proc/sys/kernel/random/boot_id";
+
+/// Checkpoint contents.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SavedCursor {
+    pub boot_id: String,
+    /// Next ticket the consumer would have read
+    pub cursor: u64,
+    /// Per handler: every ticket below this one was delivered
+    #[serde(default)]
+    pub delivered: BTreeMap<String, u64>,
+    /// Unix time of the checkpoint
+    pub saved_at: u64,
+}
+
+/// Identifier of the running boot; empty when unavailable.
+pub fn boot_id() -> String {
+    fs::read_to_string(BOOT_ID)
+        .map(|id| id.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Maps pinned while the cursor is checkpointed: the shared ring, the
+/// per-CPU reservation counts and the ticket counters.
+pub const PINNED_MAPS: [&str; 3] = ["SEQUENCER_RING", "SEQUENCER_CPU_SEQ", "GLOBAL_SEQUENCER"];
+
+/// Whether every map in [`PINNED_MAPS`] is pinned under `dir`, so loading
+/// the object with them pinned there reuses them.
+pub fn pinned(dir: &Path) -> bool {
+    PINNED_MAPS.iter().all(|name| dir.join(name).exists())
+}
+
+/// Whether `dir` is on bpffs, where maps can be pinned.
+pub fn on_bpffs(dir: &Path) -> bool {
+    let Ok(path) = CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stat` is a valid out-pointer
+    let rc = unsafe { libc::statfs(path.as_ptr(), &mut stat) };
+    rc == 0 && stat.f_type as i64 == libc::BPF_FS_MAGIC as i64
+}
+
+/// Remove the pins under `dir`; the maps are freed once no program holds
+/// them.
+pub fn unpin(dir: &Path) -> io::Result<()> {
+    for name in PINNED_MAPS {
+        match fs::remove_file(dir.join(name)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Tickets handed out on the shared ring: the first word of element 0 of
+/// `GLOBAL_SEQUENCER`, a cache-line-sized counter.
+pub fn kernel_head(bpf: &Ebpf) -> anyhow::Result<u64> {
+    let counters: Array<&MapData, [u64; 8]> = Array::try_from(
+        bpf.map("GLOBAL_SEQUENCER")
+            .context("BPF object has no GLOBAL_SEQUENCER map")?,
+    )?;
+    Ok(counters.get(&0, 0)?[0])
+}
+
+pub struct CursorStore {
+    path: PathBuf,
+}
+
+impl CursorStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The last checkpoint; `None` when there is none yet.
+    pub fn load(&self) -> io::Result<Option<SavedCursor>> {
+        match fs::read(&self.path) {
+            Ok(raw) => serde_json::from_slice(&raw)
+                .map(Some)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, saved: &SavedCursor) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&serde_json::to_vec(saved)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+/// Where to start reading, given the checkpoint and the kernel's ticket
+/// counter. `maps_reused` says whether the ring and counters were found
+/// pinned rather than created by this load; `ring_size` is the number of
+/// slots in the ring.
+pub fn reconcile(
+    saved: Option<&SavedCursor>,
+    boot_id: &str,
+    kernel_head: u64,
+    maps_reused: bool,
+    ring_size: u64,
+) -> Reconciliation {
+    let mut result = Reconciliation {
+        decision: Decision::Fresh,
+        saved_cursor: saved.map(|s| s.cursor),
+        kernel_head,
+        maps_reused,
+        cursor: kernel_head,
+        replayed: 0,
+        lost: 0,
+        delivered: BTreeMap::new(),
+    };
+    let Some(saved) = saved else {
+        return result;
+    };
+    // Fresh maps start counting at 0 again; their tickets are unrelated
+    if !maps_reused || saved.boot_id != boot_id || kernel_head < saved.cursor {
+        result.decision = Decision::Reset;
+        return result;
+    }
+
+    // Tickets an exporter had received but not yet delivered are read again
+    let resume_at = saved
+        .delivered
+        .values()
+        .fold(saved.cursor, |at, &mark| at.min(mark));
+    let oldest = kernel_head.saturating_sub(ring_size);
+    result.delivered = saved.delivered.clone();
+    if resume_at < oldest {
+        result.decision = Decision::Overrun;
+        result.cursor = oldest;
+        result.lost = oldest - resume_at;
+    } else {
+        result.decision = Decision::Resume;
+        result.cursor = resume_at;
+    }
+    result.replayed = saved.cursor.saturating_sub(result.cursor);
+    result
+}
+
+/// Load the checkpoint from `store` and reconcile it; an unreadable
+/// checkpoint is treated as none.
+pub fn restore(
+    store: &CursorStore,
+    kernel_head: u64,
+    maps_reused: bool,
+    ring_size: u64,
+) -> Reconciliation {
+    let saved = store.load().unwrap_or_else(|e| {
+        warn!(
+            "[sequencer] ignoring cursor checkpoint {}: {e}",
+            store.path().display()
+        );
+        None
+    });
+    let result = reconcile(
+        saved.as_ref(),
+        &boot_id(),
+        kernel_head,
+        maps_reused,
+        ring_size,
+    );
+    match result.decision {
+        Decision::Fresh => info!(
+            "[sequencer] no cursor checkpoint; starting at ticket {}",
+            result.cursor
+        ),
+        Decision::Resume => info!(
+            "[sequencer] resuming at ticket {} (kernel at {}, {} tickets replayed)",
+            result.cursor, kernel_head, result.replayed
+        ),
+        Decision::Overrun => warn!(
+            "[sequencer] checkpoint was lapped by producers; {} tickets lost, resuming at {}",
+            result.lost, result.cursor
+        ),
+        Decision::Reset if !maps_reused => warn!(
+            "[sequencer] ring and ticket counter were not pinned (checkpoint at {:?}); \
+             events after the last checkpoint may have been lost",
+            result.saved_cursor
+        ),
+        Decision::Reset => warn!(
+            "[sequencer] ticket counter restarted (checkpoint at {:?}, kernel at {}); \
+             events after the last checkpoint may have been lost",
+            result.saved_cursor, kernel_head
+        ),
+    }
+    result
+}
+
+/// Writes the consumer position and delivery marks at intervals.
+pub struct Checkpointer {
+    store: CursorStore,
+    boot_id: String,
+    cursor: AtomicU64,
+    marks: Arc<DeliveryMarks>,
+    metrics: Arc<Metrics>,
+    stop: AtomicBool,
+}
+
+impl Checkpointer {
+    /// Start from a restored position; reports it under `/status`.
+    pub fn new(store: CursorStore, restored: Reconciliation, metrics: Arc<Metrics>) -> Self {
+        let marks = Arc::new(DeliveryMarks::new(restored.delivered.clone()));
+        let cursor = AtomicU64::new(restored.cursor);
+        metrics
+            .cursor()
+            .restored(store.path().display().to_string(), restored);
+        Self {
+            store,
+            boot_id: boot_id(),
+            cursor,
+            marks,
+            metrics,
+            stop: AtomicBool::new(false),
+        }
+    }
+
+    /// Save `marks`, shared with the exporters, instead of the restored ones.
+    pub fn with_marks(mut self, marks: Arc<DeliveryMarks>) -> Self {
+        self.marks = marks;
+        self
+    }
+
+    /// Record the consumer's next ticket.
+    pub fn set_cursor(&self, cursor: u64) {
+        self.cursor.store(cursor, Ordering::Relaxed);
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let saved = SavedCursor {
+            boot_id: self.boot_id.clone(),
+            cursor: self.cursor.load(Ordering::Relaxed),
+            delivered: self.marks.snapshot(),
+            saved_at: now_secs(),
+        };
+        let result = self.store.save(&saved);
+        self.metrics.cursor().checkpoint(match &result {
+            Ok(()) => Ok((saved.cursor, saved.saved_at)),
+            Err(e) => Err(e.to_string()),
+        });
+        result
+    }
+
+    /// Save every `interval` until [`Checkpointer::finish`].
+    pub fn spawn(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let this = Arc::clone(self);
+        std::thread::Builder::new()
+            .name("seq-cursor".into())
+            .spawn(move || {
+                while !this.stop.load(Ordering::Relaxed) {
+                    std::thread::park_timeout(interval);
+                    if let Err(e) = this.save() {
+                        warn!(
+                            "[sequencer] cannot write cursor checkpoint {}: {e}",
+                            this.store.path().display()
+                        );
+                    }
+                }
+            })
+            .expect("spawn sequencer cursor thread")
+    }
+
+    /// Stop the checkpoint thread after a last save.
+    pub fn finish(&self, thread: JoinHandle<()>) {
+        self.stop.store(true, Ordering::Relaxed);
+        thread.thread().unpark();
+        let _ = thread.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn saved(cursor: u64, delivered: &[(&str, u64)]) -> SavedCursor {
+        SavedCursor {
+            boot_id: "boot".into(),
+            cursor,
+            delivered: delivered
+                .iter()
+                .map(|&(name, mark)| (name.to_string(), mark))
+                .collect(),
+            saved_at: 0,
+        }
+    }
+
+    #[test]
+    fn reconcile_bounds_replay_and_loss() {
+        let fresh = reconcile(None, "boot", 7, false, 100);
+        assert_eq!((fresh.decision, fresh.cursor), (Decision::Fresh, 7));
+
+        // An exporter behind the consumer pulls the resume point back
+        let checkpoint = saved(500, &[("webhook", 450)]);
+        let resume = reconcile(Some(&checkpoint), "boot", 520, true, 100);
+        assert_eq!(resume.decision, Decision::Resume);
+        assert_eq!((resume.cursor, resume.replayed, resume.lost), (450, 50, 0));
+        assert_eq!(resume.delivered["webhook"], 450);
+
+        let overrun = reconcile(Some(&checkpoint), "boot", 700, true, 100);
+        assert_eq!(overrun.decision, Decision::Overrun);
+        assert_eq!((overrun.cursor, overrun.lost), (600, 150));
+
+        // Maps created afresh, counter behind, or machine rebooted
+        for (boot, head, reused) in [
+            ("boot", 520, false),
+            ("boot", 20, true),
+            ("other", 900, true),
+        ] {
+            let reset = reconcile(Some(&checkpoint), boot, head, reused, 100);
+            assert_eq!((reset.decision, reset.cursor), (Decision::Reset, head));
+            assert!(reset.delivered.is_empty());
+        }
+    }
+
+    #[test]
+    fn pins_count_only_when_complete() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!pinned(dir.path()));
+        for name in &PINNED_MAPS[..2] {
+            File::create(dir.path().join(name)).unwrap();
+        }
+        assert!(!pinned(dir.path()));
+        File::create(dir.path().join(PINNED_MAPS[2])).unwrap();
+        assert!(pinned(dir.path()));
+
+        unpin(dir.path()).unwrap();
+        assert!(!pinned(dir.path()));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+        // Nothing left to remove is not an error
+        unpin(dir.path()).unwrap();
+    }
+
+    #[test]
+    fn checkpoint_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = CursorStore::new(dir.path().join("state/sequencer.cursor"));
+        assert_eq!(store.load().unwrap(), None);
+
+        let checkpoint = saved(42, &[("jsonl", 40)]);
+        store.save(&checkpoint).unwrap();
+        assert_eq!(store.load().unwrap(), Some(checkpoint));
+    }
+}
@@ -0,0 +1,410 @@
+//! Degradation ladder for when the daemon outgrows its budget.
+//!
+//! Every `check_secs` the daemon's CPU (since the previous check) and RSS are
+//! compared with `runtime.cpu_target_pct` and `runtime.rss_cap_mb`. After
+//! `escalate_checks` consecutive checks over either cap the next step is
+//! taken; after `recover_checks` consecutive checks below `recover_pct` of
+//! both caps the most recent step is undone. Steps, cheapest first:
+//!
+//! 1. `history`: keep a quarter of the ContextStore event history
+//! 2. `broadcast`: stop broadcasting to `/events` subscribers once a quarter
+//!    of the channel is backlogged
+//! 3. `sampling`: multiply the in-kernel sampling divisor of network, file,
+//!    syscall, block I/O and page-fault events by `sample_factor`
+//! 4. `probes`: mute syscall, block I/O and page-fault events in the kernel
+//! 5. `ingestion`: drop every event for `restart_pause_secs`, clear the
+//!    history and resume; repeated while usage stays over budget
+//!
+//! Steps 3 and 4 need the BPF control maps and are skipped without them.
+//! Sampling divisors in effect before a step are restored when it is undone.
+//! The current level is published to `Metrics`.
+
+use linnix_ai_ebpf_common::EventType;
+use log::{info, warn};
+use procfs::{page_size, process::Process, ticks_per_second};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::bpf_control::BpfControl;
+use crate::config::{DegradationConfig, RuntimeConfig};
+use crate::context::{BROADCAST_CAPACITY, ContextStore};
+use crate::metrics::Metrics;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Step {
+    History,
+    Broadcast,
+    Sampling,
+    Probes,
+    Ingestion,
+}
+
+impl Step {
+    pub const ALL: [Step; 5] = [
+        Step::History,
+        Step::Broadcast,
+        Step::Sampling,
+        Step::Probes,
+        Step::Ingestion,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Step::History => "history",
+            Step::Broadcast => "broadcast",
+            Step::Sampling => "sampling",
+            Step::Probes => "probes",
+            Step::Ingestion => "ingestion",
+        }
+    }
+}
+
+/// Most recent step in effect at `level`; `None` when not degraded.
+pub fn step_name(level: u8) -> Option<&'static str> {
+    let index = usize::from(level).checked_sub(1)?;
+    Step::ALL.get(index).map(|step| step.name())
+}
+
+const SAMPLED: [EventType; 5] = [
+    EventType::Net,
+    EventType::FileIo,
+    EventType::Syscall,
+    EventType::BlockIo,
+    EventType::PageFault,
+];
+
+const MUTED: [EventType; 3] = [EventType::Syscall, EventType::BlockIo, EventType::PageFault];
+
+/// Divisor that keeps practically none of an event type.
+const MUTE_DIVISOR: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Usage {
+    pub cpu_pct: f64,
+    pub rss_mb: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Move {
+    Escalate,
+    Recover,
+    Hold,
+}
+
+/// Decides when to move along the ladder. A cap of 0 is never exceeded.
+#[derive(Debug)]
+struct Ladder {
+    cpu_cap: f64,
+    rss_cap_mb: u64,
+    recover_fraction: f64,
+    escalate_checks: u32,
+    recover_checks: u32,
+    over: u32,
+    under: u32,
+}
+
+impl Ladder {
+    fn new(runtime: &RuntimeConfig, config: &DegradationConfig) -> Self {
+        Self {
+            cpu_cap: runtime.cpu_target_pct as f64,
+            rss_cap_mb: runtime.rss_cap_mb,
+            recover_fraction: config.recover_pct as f64 / 100.0,
+            escalate_checks: config.escalate_checks.max(1),
+            recover_checks: config.recover_checks.max(1),
+            over: 0,
+            under: 0,
+        }
+    }
+
+    fn observe(&mut self, usage: Usage) -> Move {
+        let rss_mb = usage.rss_mb as f64;
+        let rss_cap = self.rss_cap_mb as f64;
+        let over = (self.cpu_cap > 0.0 && usage.cpu_pct > self.cpu_cap)
+            || (self.rss_cap_mb > 0 && usage.rss_mb > self.rss_cap_mb);
+        let recovered = (self.cpu_cap == 0.0
+            || usage.cpu_pct < self.cpu_cap * self.recover_fraction)
+            && (self.rss_cap_mb == 0 || rss_mb < rss_cap * self.recover_fraction);
+        if over {
+            self.under = 0;
+            self.over += 1;
+            if self.over >= self.escalate_checks {
+                self.over = 0;
+                return Move::Escalate;
+            }
+        } else if recovered {
+            self.over = 0;
+            self.under += 1;
+            if self.under >= self.recover_checks {
+                self.under = 0;
+                return Move::Recover;
+            }
+        } else {
+            self.over = 0;
+            self.under = 0;
+        }
+        Move::Hold
+    }
+}
+
+/// CPU and RSS of this process from `/proc/self/stat`.
+struct SelfUsage {
+    ticks_per_sec: f64,
+    page_kb: u64,
+    prev: Option<(u64, Instant)>,
+}
+
+impl SelfUsage {
+    fn new() -> Self {
+        Self {
+            ticks_per_sec: ticks_per_second() as f64,
+            page_kb: page_size() / 1024,
+            prev: None,
+        }
+    }
+
+    /// `None` on the first read, which only sets the CPU baseline.
+    fn read(&mut self) -> Option<Usage> {
+        let stat = Process::myself().and_then(|proc| proc.stat()).ok()?;
+        let total = stat.utime + stat.stime;
+        let now = Instant::now();
+        let (prev_total, prev_at) = self.prev.replace((total, now))?;
+        let secs = now.duration_since(prev_at).as_secs_f64();
+        if secs <= 0.0 {
+            return None;
+        }
+        let cpu_secs = total.saturating_sub(prev_total) as f64 / self.ticks_per_sec;
+        Some(Usage {
+            cpu_pct: cpu_secs / secs * 100.0,
+            rss_mb: stat.rss * self.page_kb / 1024,
+        })
+    }
+}
+
+struct Degrader {
+    context: Arc<ContextStore>,
+    metrics: Arc<Metrics>,
+    control: Option<Arc<BpfControl>>,
+    sample_factor: u32,
+    restart_pause: Duration,
+    /// Number of steps in effect
+    level: usize,
+    /// Divisors to restore when a sampling step is undone
+    saved: HashMap<Step, Vec<(EventType, u32)>>,
+}
+
+impl Degrader {
+    async fn escalate(&mut self, usage: Usage) {
+        let Some(step) = Step::ALL.get(self.level).copied() else {
+            // Already on the last step
+            self.restart_ingestion();
+            return;
+        };
+        warn!(
+            "[degrade] cpu {:.1}% rss {}MB over budget; step {} ({})",
+            usage.cpu_pct,
+            usage.rss_mb,
+            self.level + 1,
+            step.name()
+        );
+        self.apply(step).await;
+        self.level += 1;
+        self.metrics.set_degradation_level(self.level as u8);
+    }
+
+    async fn recover(&mut self, usage: Usage) {
+        let Some(level) = self.level.checked_sub(1) else {
+            return;
+        };
+        let step = Step::ALL[level];
+        info!(
+            "[degrade] cpu {:.1}% rss {}MB back under budget; undoing step {} ({})",
+            usage.cpu_pct,
+            usage.rss_mb,
+            level + 1,
+            step.name()
+        );
+        self.undo(step).await;
+        self.level = level;
+        self.metrics.set_degradation_level(self.level as u8);
+    }
+
+    async fn apply(&mut self, step: Step) {
+        match step {
+            Step::History => {
+                let limit = (self.context.history_limit() / 4).max(1);
+                self.context.set_history_limit(limit);
+            }
+            Step::Broadcast => self
+                .context
+                .set_broadcast_limit(Some(BROADCAST_CAPACITY / 4)),
+            Step::Sampling => {
+                let factor = self.sample_factor.max(2);
+                self.set_divisors(step, &SAMPLED, |divisor| {
+                    divisor.max(1).saturating_mul(factor)
+                })
+                .await
+            }
+            Step::Probes => self.set_divisors(step, &MUTED, |_| MUTE_DIVISOR).await,
+            Step::Ingestion => self.restart_ingestion(),
+        }
+    }
+
+    async fn undo(&mut self, step: Step) {
+        match step {
+            Step::History => self.context.reset_history_limit(),
+            Step::Broadcast => self.context.set_broadcast_limit(None),
+            Step::Sampling | Step::Probes => self.restore_divisors(step).await,
+            // The pause ends on its own
+            Step::Ingestion => {}
+        }
+    }
+
+    async fn set_divisors(
+        &mut self,
+        step: Step,
+        types: &[EventType],
+        divisor: impl Fn(u32) -> u32,
+    ) {
+        let Some(control) = &self.control else {
+            info!(
+                "[degrade] no BPF control maps; {} step skipped",
+                step.name()
+            );
+            return;
+        };
+        let current: HashMap<u32, u32> = match control.sampling().await {
+            Ok(entries) => entries
+                .into_iter()
+                .map(|entry| (entry.event_type, entry.divisor))
+                .collect(),
+            Err(e) => {
+                warn!("[degrade] cannot read sampling divisors: {e:#}");
+                return;
+            }
+        };
+        let mut saved = Vec::new();
+        for &event_type in types {
+            let before = current.get(&(event_type as u32)).copied().unwrap_or(1);
+            match control.set_sampling(event_type, divisor(before)).await {
+                Ok(()) => saved.push((event_type, before)),
+                Err(e) => warn!("[degrade] failed to sample {event_type:?}: {e:#}"),
+            }
+        }
+        self.saved.insert(step, saved);
+    }
+
+    async fn restore_divisors(&mut self, step: Step) {
+        let (Some(control), Some(saved)) = (&self.control, self.saved.remove(&step)) else {
+            return;
+        };
+        for (event_type, divisor) in saved {
+            if let Err(e) = control.set_sampling(event_type, divisor).await {
+                warn!("[degrade] failed to restore sampling of {event_type:?}: {e:#}");
+            }
+        }
+    }
+
+    fn restart_ingestion(&self) {
+        if self.metrics.ingest_paused() {
+            return;
+        }
+        warn!(
+            "[degrade] restarting ingestion: dropping events for {}s and clearing history",
+            self.restart_pause.as_secs()
+        );
+        self.metrics.set_ingest_paused(true);
+        self.context.clear_history();
+        let metrics = Arc::clone(&self.metrics);
+        let pause = self.restart_pause;
+        tokio::spawn(async move {
+            tokio::time::sleep(pause).await;
+            metrics.set_ingest_paused(false);
+            info!("[degrade] ingestion resumed");
+        });
+    }
+}
+
+/// Start checking the daemon's usage against its caps. When degradation is
+/// disabled, sustained overuse is only logged.
+pub fn spawn(
+    context: Arc<ContextStore>,
+    metrics: Arc<Metrics>,
+    control: Option<Arc<BpfControl>>,
+    runtime: &RuntimeConfig,
+    config: &DegradationConfig,
+) {
+    let mut ladder = Ladder::new(runtime, config);
+    let mut degrader = Degrader {
+        context,
+        metrics,
+        control,
+        sample_factor: config.sample_factor,
+        restart_pause: Duration::from_secs(config.restart_pause_secs.max(1)),
+        level: 0,
+        saved: HashMap::new(),
+    };
+    let enabled = config.enabled;
+    let (cpu_cap, rss_cap) = (runtime.cpu_target_pct, runtime.rss_cap_mb);
+    let interval = Duration::from_secs(config.check_secs.max(1));
+    tokio::spawn(async move {
+        let mut usage = SelfUsage::new();
+        let mut tick = tokio::time::interval(interval);
+        loop {
+            tick.tick().await;
+            let Some(current) = usage.read() else {
+                continue;
+            };
+            match ladder.observe(current) {
+                Move::Escalate if enabled => degrader.escalate(current).await,
+                Move::Escalate => warn!(
+                    "[degrade] cpu {:.1}% rss {}MB over budget (cpu {cpu_cap}%, rss {rss_cap}MB)",
+                    current.cpu_pct, current.rss_mb
+                ),
+                Move::Recover => degrader.recover(current).await,
+                Move::Hold => {}
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ladder_escalates_on_sustained_overuse_and_recovers_with_hysteresis() {
+        let runtime = RuntimeConfig {
+            cpu_target_pct: 20,
+            rss_cap_mb: 100,
+            ..RuntimeConfig::default()
+        };
+        let config = DegradationConfig {
+            escalate_checks: 2,
+            recover_checks: 3,
+            recover_pct: 80,
+            ..DegradationConfig::default()
+        };
+        let mut ladder = Ladder::new(&runtime, &config);
+        let usage = |cpu_pct, rss_mb| Usage { cpu_pct, rss_mb };
+
+        // One spike is not enough
+        assert_eq!(ladder.observe(usage(50.0, 10)), Move::Hold);
+        assert_eq!(ladder.observe(usage(5.0, 10)), Move::Hold);
+        assert_eq!(ladder.observe(usage(5.0, 150)), Move::Hold);
+        assert_eq!(ladder.observe(usage(5.0, 150)), Move::Escalate);
+
+        // Between the recovery threshold and the cap holds and resets counts
+        assert_eq!(ladder.observe(usage(5.0, 90)), Move::Hold);
+        assert_eq!(ladder.observe(usage(5.0, 50)), Move::Hold);
+        assert_eq!(ladder.observe(usage(18.0, 50)), Move::Hold);
+        assert_eq!(ladder.observe(usage(5.0, 50)), Move::Hold);
+        assert_eq!(ladder.observe(usage(5.0, 50)), Move::Hold);
+        assert_eq!(ladder.observe(usage(5.0, 50)), Move::Recover);
+
+        assert_eq!(step_name(0), None);
+        assert_eq!(step_name(1), Some("history"));
+        assert_eq!(step_name(5), Some("ingestion"));
+        assert_eq!(step_name(6), None);
+    }
+}
@@ -0,0 +1,231 @@
+//! Feature negotiation between the BPF object and the daemon
+//!
+//! The object embeds a `LINNIX_FEATURES` manifest listing the programs and
+//! maps it provides. Before loading, the daemon reads it from the object file
+//! and enables only the features both sides know, so a newer object or a
+//! newer daemon degrades with a warning instead of silently losing probes.
+//! Objects built before the manifest existed are assumed to provide
+//! everything the daemon uses. The object also records the architecture its
+//! kprobes were built for; on any other architecture they would read
+//! arguments from the wrong registers, so those features are dropped.
+
+use linnix_ai_ebpf_common::{ARCH_MANIFEST_MAGIC, FEATURE_MANIFEST_MAGIC, features};
+use log::{info, warn};
+use serde::Serialize;
+
+/// Features this daemon attaches or drives.
+pub const DAEMON_FEATURES: u64 = features::PROCESS
+    | features::NET_TCP
+    | features::NET_UDP
+    | features::NET_UNIX
+    | features::LISTEN
+    | features::FILE_IO
+    | features::BLOCK_IO
+    | features::SYSCALLS
+    | features::SAMPLING
+    | features::PID_FILTER
+    | features::CGROUP_SCOPE
+    | features::SEQUENCER
+    | features::SEQUENCER_CPU
+    | features::PERF_SEQ
+    | features::CONNECTIONS
+    | features::FD_PRESSURE
+    | features::SIGNALS
+    | features::EXEC_PATHS
+    | features::SOCKET_PEERS
+    | features::OOM_KILLS;
+
+/// Features backed by kprobes, which read arguments through `pt_regs` and
+/// therefore only work on the architecture the object was built for.
+pub const PT_REGS_FEATURES: u64 = features::NET_TCP
+    | features::NET_UDP
+    | features::NET_UNIX
+    | features::LISTEN
+    | features::FILE_IO
+    | features::CONNECTIONS
+    | features::FD_PRESSURE;
+
+/// Bits this daemon has a name for, whether or not it uses them.
+fn known_bits() -> u64 {
+    features::NAMES.iter().fold(0, |acc, (bit, _)| acc | bit)
+}
+
+/// Names of the known features in `bits`, in bit order.
+pub fn names(bits: u64) -> Vec<&'static str> {
+    features::NAMES
+        .iter()
+        .filter(|(bit, _)| bits & bit != 0)
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+/// Feature bitmap embedded in a BPF object file, or `None` for objects
+/// without a manifest.
+pub fn read_manifest(object: &[u8]) -> Option<u64> {
+    let magic = FEATURE_MANIFEST_MAGIC.as_slice();
+    let at = object.windows(magic.len()).position(|w| w == magic)?;
+    let bits = object.get(at + magic.len()..at + magic.len() + 8)?;
+    Some(u64::from_le_bytes(bits.try_into().ok()?))
+}
+
+/// Architecture the object's kprobes were built for (`x86_64`, `aarch64`,
+/// ...), or `None` for objects that do not record it.
+pub fn read_target_arch(object: &[u8]) -> Option<String> {
+    let magic = ARCH_MANIFEST_MAGIC.as_slice();
+    let at = object.windows(magic.len()).position(|w| w == magic)?;
+    let name = object.get(at + magic.len()..at + magic.len() + 16)?;
+    let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+    let arch = std::str::from_utf8(&name[..len]).ok()?;
+    (!arch.is_empty()).then(|| arch.to_string())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeatureNegotiation {
+    /// Bitmap advertised by the object; `None` when it has no manifest.
+    pub object: Option<u64>,
+    pub daemon: u64,
+    pub enabled: u64,
+}
+
+impl FeatureNegotiation {
+    pub fn negotiate(object: Option<u64>) -> Self {
+        Self::with_daemon(object, DAEMON_FEATURES)
+    }
+
+    fn with_daemon(object: Option<u64>, daemon: u64) -> Self {
+        Self {
+            object,
+            daemon,
+            enabled: object.map_or(daemon, |bits| bits & daemon),
+        }
+    }
+
+    /// Turn off `bits` regardless of what both sides support.
+    pub fn disable(&mut self, bits: u64) {
+        self.enabled &= !bits;
+    }
+
+    pub fn enabled(&self, feature: u64) -> bool {
+        self.enabled & feature == feature
+    }
+
+    /// Provided by the object but unused by this daemon.
+    pub fn object_only(&self) -> u64 {
+        self.object.unwrap_or(0) & !self.daemon
+    }
+
+    /// Used by this daemon but missing from the object.
+    pub fn daemon_only(&self) -> u64 {
+        self.object.map_or(0, |bits| self.daemon & !bits)
+    }
+
+    /// Log what was negotiated and every mismatch.
+    pub fn log(&self) {
+        let Some(object) = self.object else {
+            warn!(
+                "[bpf] object has no feature manifest (built before feature negotiation); assuming it provides {}",
+                names(self.daemon).join(",")
+            );
+            return;
+        };
+        info!(
+            "[bpf] negotiated features: {}",
+            names(self.enabled).join(",")
+        );
+        let missing = self.daemon_only();
+        if missing != 0 {
+            warn!(
+                "[bpf] object lacks features this daemon uses, disabled: {} (rebuild or update the BPF object)",
+                names(missing).join(",")
+            );
+        }
+        let unused = self.object_only();
+        if unused & known_bits() != 0 {
+            info!(
+                "[bpf] object provides features this daemon does not use: {}",
+                names(unused).join(",")
+            );
+        }
+        let unknown = object & !known_bits();
+        if unknown != 0 {
+            warn!(
+                "[bpf] object provides features unknown to this daemon (bits {unknown:#x}); upgrade cognitod to use them"
+            );
+        }
+    }
+
+    pub fn status(&self) -> FeatureStatus {
+        FeatureStatus {
+            manifest: self.object.is_some(),
+            enabled: names(self.enabled),
+            object_only: names(self.object_only()),
+            object_unknown_bits: self.object.unwrap_or(0) & !known_bits(),
+            daemon_only: names(self.daemon_only()),
+        }
+    }
+}
+
+/// Negotiated features as reported in `/status`.
+#[derive(Debug, Clone, Serialize)]
+pub struct FeatureStatus {
+    /// False for objects built before feature negotiation.
+    pub manifest: bool,
+    pub enabled: Vec<&'static str>,
+    /// Provided by the object, not used by this daemon.
+    pub object_only: Vec<&'static str>,
+    /// Bits set by a newer object that this daemon cannot name.
+    pub object_unknown_bits: u64,
+    /// Used by this daemon, missing from the object.
+    pub daemon_only: Vec<&'static str>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_manifest_from_object_bytes() {
+        let bits = features::PROCESS | features::SEQUENCER;
+        let mut object = b"\x7fELF....rodata".to_vec();
+        object.extend_from_slice(&FEATURE_MANIFEST_MAGIC);
+        object.extend_from_slice(&bits.to_le_bytes());
+        object.extend_from_slice(b"trailing");
+        assert_eq!(read_manifest(&object), Some(bits));
+        assert_eq!(read_manifest(b"\x7fELF legacy object"), None);
+    }
+
+    #[test]
+    fn reads_target_arch_from_object_bytes() {
+        let mut object = b"\x7fELF....rodata".to_vec();
+        object.extend_from_slice(&ARCH_MANIFEST_MAGIC);
+        object.extend_from_slice(b"aarch64\0\0\0\0\0\0\0\0\0");
+        assert_eq!(read_target_arch(&object).as_deref(), Some("aarch64"));
+        assert_eq!(read_target_arch(b"\x7fELF legacy object"), None);
+
+        let mut negotiated = FeatureNegotiation::with_daemon(None, DAEMON_FEATURES);
+        negotiated.disable(PT_REGS_FEATURES);
+        assert!(negotiated.enabled(features::PROCESS | features::BLOCK_IO));
+        assert!(!negotiated.enabled(features::NET_TCP));
+    }
+
+    #[test]
+    fn negotiates_intersection_and_reports_mismatches() {
+        let daemon = features::PROCESS | features::NET_TCP | features::BLOCK_IO;
+        let object = features::PROCESS | features::NET_TCP | features::PAGE_FAULTS | 1 << 63;
+        let negotiated = FeatureNegotiation::with_daemon(Some(object), daemon);
+        assert!(negotiated.enabled(features::NET_TCP));
+        assert!(!negotiated.enabled(features::BLOCK_IO));
+        assert!(!negotiated.enabled(features::PAGE_FAULTS));
+
+        let status = negotiated.status();
+        assert_eq!(status.enabled, vec!["process", "net_tcp"]);
+        assert_eq!(status.daemon_only, vec!["block_io"]);
+        assert_eq!(status.object_only, vec!["page_faults"]);
+        assert_eq!(status.object_unknown_bits, 1 << 63);
+
+        let legacy = FeatureNegotiation::with_daemon(None, daemon);
+        assert!(legacy.enabled(features::BLOCK_IO));
+        assert_eq!(legacy.daemon_only(), 0);
+        assert!(!legacy.status().manifest);
+    }
+}
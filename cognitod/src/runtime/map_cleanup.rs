@@ -0,0 +1,190 @@
+//! Stale per-process BPF map entries.
+//!
+//! The exit tracepoint deletes a pid's `TASK_STATS` and `PAGE_FAULT_THROTTLE`
+//! entries, so processes that exit while probes are detached (daemon restarts,
+//! probe reloads) leave theirs behind. Once a map fills, new processes go
+//! untracked. Every `interval_secs` the cleaner lists each map's pids, deletes
+//! those without a `/proc` entry, publishes occupancy to `Metrics`, and raises
+//! `bpf_map_near_capacity` when a map is still above `alert_pct` after cleanup.
+//!
+//! Kernel pids are only comparable with `/proc` in the initial pid namespace;
+//! elsewhere (a container without `hostPID`) the cleaner reports occupancy but
+//! deletes nothing.
+
+use log::{debug, info, warn};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::bpf_control::{BpfControl, TaskMap};
+use crate::alerts::{Alert, AlertSender, Severity};
+use crate::config::MapCleanupConfig;
+use crate::metrics::{MapOccupancy, Metrics};
+
+pub const RULE_NAME: &str = "bpf_map_near_capacity";
+
+/// Inode of the initial pid namespace (`PROC_PID_INIT_INO`).
+const INIT_PID_NS: &str = "pid:[4026531836]";
+
+pub(super) fn in_init_pid_namespace() -> bool {
+    std::fs::read_link("/proc/self/ns/pid")
+        .map(|link| link.to_string_lossy() == INIT_PID_NS)
+        .unwrap_or(false)
+}
+
+pub(super) fn pid_alive(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+/// Fires once when a map crosses the threshold and re-arms when it drops
+/// back below.
+#[derive(Debug, Default)]
+struct CapacityAlarm {
+    raised: HashSet<&'static str>,
+}
+
+impl CapacityAlarm {
+    fn crossed(&mut self, map: &'static str, percent: f64, threshold: f64) -> bool {
+        if percent < threshold {
+            self.raised.remove(map);
+            return false;
+        }
+        self.raised.insert(map)
+    }
+}
+
+struct MapCleaner {
+    control: Arc<BpfControl>,
+    metrics: Arc<Metrics>,
+    alerts: Option<AlertSender>,
+    alert_pct: f64,
+    prune: bool,
+    removed_total: HashMap<&'static str, u64>,
+    alarm: CapacityAlarm,
+    host: String,
+}
+
+impl MapCleaner {
+    async fn scan(&mut self) {
+        let mut report = Vec::new();
+        for map in TaskMap::ALL {
+            let pids = match self.control.task_map_pids(map).await {
+                Ok(pids) => pids,
+                Err(e) => {
+                    debug!("[map_cleanup] skipping {}: {e:#}", map.name());
+                    continue;
+                }
+            };
+            let mut entries = pids.len();
+            if self.prune {
+                let stale: Vec<u32> = pids.into_iter().filter(|pid| !pid_alive(*pid)).collect();
+                if !stale.is_empty() {
+                    match self.control.remove_task_map_pids(map, &stale).await {
+                        Ok(removed) => {
+                            entries = entries.saturating_sub(removed);
+                            *self.removed_total.entry(map.name()).or_default() += removed as u64;
+                            info!(
+                                "[map_cleanup] removed {removed} stale entries from {}",
+                                map.name()
+                            );
+                        }
+                        Err(e) => warn!("[map_cleanup] failed to prune {}: {e:#}", map.name()),
+                    }
+                }
+            }
+            let occupancy = MapOccupancy {
+                map: map.name(),
+                entries,
+                max_entries: map.max_entries(),
+                stale_removed_total: self.removed_total.get(map.name()).copied().unwrap_or(0),
+            };
+            if self
+                .alarm
+                .crossed(occupancy.map, occupancy.percent(), self.alert_pct)
+            {
+                self.raise(&occupancy);
+            }
+            report.push(occupancy);
+        }
+        self.metrics.set_bpf_maps(report);
+    }
+
+    fn raise(&self, occupancy: &MapOccupancy) {
+        warn!(
+            "[map_cleanup] {} at {:.0}% of capacity after cleanup",
+            occupancy.map,
+            occupancy.percent()
+        );
+        let Some(tx) = &self.alerts else {
+            return;
+        };
+        tx.send(Alert {
+            rule: RULE_NAME.to_string(),
+            severity: Severity::High,
+            message: format!(
+                "{} holds {} of {} entries ({:.0}%) after removing exited processes; \
+                 new processes stop being tracked once it is full",
+                occupancy.map,
+                occupancy.entries,
+                occupancy.max_entries,
+                occupancy.percent()
+            ),
+            host: self.host.clone(),
+            detection: "threshold",
+            suppressed_by: None,
+            lineage: Vec::new(),
+            children: Vec::new(),
+            pod: None,
+            evidence: None,
+            annotations: None,
+        });
+    }
+}
+
+/// Start the periodic scan of the per-process maps.
+pub fn spawn(
+    control: Arc<BpfControl>,
+    metrics: Arc<Metrics>,
+    alerts: Option<AlertSender>,
+    config: &MapCleanupConfig,
+) {
+    let prune = in_init_pid_namespace();
+    if !prune {
+        warn!("[map_cleanup] not in the host pid namespace; stale entries will not be removed");
+    }
+    let mut cleaner = MapCleaner {
+        control,
+        metrics,
+        alerts,
+        alert_pct: config.alert_pct,
+        prune,
+        removed_total: HashMap::new(),
+        alarm: CapacityAlarm::default(),
+        host: std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown".into()),
+    };
+    let interval = Duration::from_secs(config.interval_secs.max(1));
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(interval);
+        loop {
+            tick.tick().await;
+            cleaner.scan().await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capacity_alarm_fires_once_per_crossing() {
+        let mut alarm = CapacityAlarm::default();
+        assert!(!alarm.crossed("TASK_STATS", 50.0, 80.0));
+        assert!(alarm.crossed("TASK_STATS", 85.0, 80.0));
+        assert!(!alarm.crossed("TASK_STATS", 95.0, 80.0));
+        assert!(alarm.crossed("PAGE_FAULT_THROTTLE", 80.0, 80.0));
+        assert!(!alarm.crossed("TASK_STATS", 79.0, 80.0));
+        assert!(alarm.crossed("TASK_STATS", 81.0, 80.0));
+    }
+}
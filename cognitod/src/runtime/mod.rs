@@ -1,13 +1,27 @@
 #![allow(unused_imports)]
+pub mod arch;
+pub mod backlog;
+pub mod bpf_control;
+pub mod canary;
+pub mod cursor;
+pub mod degradation;
+pub mod features;
 pub mod lineage;
+pub mod map_cleanup;
+pub mod numa;
+pub mod page_faults;
+pub mod placement;
+pub mod probe_profiles;
+pub mod probe_silence;
 pub mod probes;
 pub mod sequencer;
+pub mod sequencer_diag;
+pub mod staleness;
 pub mod stream_listener;
 
-pub use sequencer::{
-    OrderingValidator, SequencerConsumer, SequencerStats, disable_sequencer, enable_sequencer,
-};
-pub use stream_listener::start_perf_listener;
+pub use bpf_control::BpfControl;
+pub use sequencer::{OrderingValidator, SequencerConsumer, SequencerStats};
+pub use stream_listener::{start_perf_listener, start_sequencer_listener};
 
 use std::sync::Arc;
 use once_cell::sync::OnceCell;
@@ -0,0 +1,462 @@
+//! NUMA-partitioned sequencer consumption
+//!
+//! With a single sequencer ring every CPU bumps the same ticket counter, and
+//! a consumer on one socket pulls slots written on the other across the
+//! interconnect. With `SEQUENCER_ENABLED` set to the number of partitions,
+//! each node's CPUs write their own ring (see `SEQUENCER_MAX_PARTITIONS`).
+//! One consumer thread per ring, pinned to that node's CPUs, drains it while
+//! the slots are still in the node's caches, and the per-node streams are
+//! merged back into one stream ordered by timestamp.
+//!
+//! Ring memory is allocated by the kernel when the object is loaded, not by
+//! us. [`NumaReport`] reports which node each ring's pages are on, so the
+//! locality gained can be measured instead of assumed.
+
+use linnix_ai_ebpf_common::{ProcessEvent, REAPER_TIMEOUT_NS, SEQUENCER_MAX_PARTITIONS};
+use log::{info, warn};
+use std::collections::VecDeque;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use super::placement::{ThreadPlacement, format_cpu_list, parse_cpu_list};
+use super::sequencer::{IdleBackoff, SequencerConsumer, SequencerStats};
+use crate::config::ThreadPlacementConfig;
+pub use crate::metrics::numa::{NumaReport, PagePlacement, PartitionReport};
+
+pub const NODE_ROOT: &str = "/sys/devices/system/node";
+
+/// Ring map of each partition.
+pub const RING_MAPS: [&str; SEQUENCER_MAX_PARTITIONS as usize] = [
+    "SEQUENCER_RING",
+    "SEQUENCER_RING_NODE1",
+    "SEQUENCER_RING_NODE2",
+    "SEQUENCER_RING_NODE3",
+];
+
+/// Placement classes of the per-partition consumer threads, as shown under
+/// `threads` in `/status`.
+const CONSUMER_CLASSES: [&str; SEQUENCER_MAX_PARTITIONS as usize] = [
+    "sequencer_node0",
+    "sequencer_node1",
+    "sequencer_node2",
+    "sequencer_node3",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumaNode {
+    pub id: u32,
+    pub cpus: Vec<usize>,
+}
+
+/// Online NUMA nodes with CPUs. Empty when the kernel exposes no topology
+/// (non-NUMA builds).
+pub fn online_nodes(root: &Path) -> io::Result<Vec<NumaNode>> {
+    let online = match std::fs::read_to_string(root.join("online")) {
+        Ok(online) => online,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let ids = parse_cpu_list(online.trim()).map_err(io::Error::other)?;
+    let mut nodes = Vec::new();
+    for id in ids {
+        let cpulist = std::fs::read_to_string(root.join(format!("node{id}/cpulist")))?;
+        // Memory-only nodes have an empty cpulist
+        if let Ok(cpus) = parse_cpu_list(cpulist.trim()) {
+            nodes.push(NumaNode {
+                id: id as u32,
+                cpus,
+            });
+        }
+    }
+    Ok(nodes)
+}
+
+/// Rings to partition into for `nodes` nodes: one per node, up to
+/// `SEQUENCER_MAX_PARTITIONS`. Node `n` writes partition `n % partitions`.
+pub fn partitions_for(nodes: &[NumaNode]) -> u32 {
+    (nodes.len() as u32).clamp(1, SEQUENCER_MAX_PARTITIONS)
+}
+
+/// Query the node of every page in `[addr, addr + len)` with move_pages(2)
+/// in query mode (no pages are moved).
+pub fn page_placement(addr: *const u8, len: usize) -> io::Result<PagePlacement> {
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(4096) as usize;
+    let pages: Vec<*const libc::c_void> = (0..len.div_ceil(page_size))
+        .map(|i| addr.wrapping_add(i * page_size) as *const libc::c_void)
+        .collect();
+    let mut status = vec![0i32; pages.len()];
+    // pid 0 is the calling process; a null node list only reports placement
+    let rc = unsafe {
+        libc::syscall(
+            libc::SYS_move_pages,
+            0,
+            pages.len(),
+            pages.as_ptr(),
+            std::ptr::null::<i32>(),
+            status.as_mut_ptr(),
+            0,
+        )
+    };
+    if rc < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut placement = PagePlacement::default();
+    for node in status {
+        match u32::try_from(node) {
+            Ok(node) => *placement.by_node.entry(node).or_default() += 1,
+            Err(_) => placement.unresolved += 1,
+        }
+    }
+    Ok(placement)
+}
+
+/// Merges per-partition streams, each in ticket order, into one stream in
+/// timestamp order.
+///
+/// The oldest head is released once every partition has a head to compare
+/// it with. An idle partition holds the others back for at most `lateness`
+/// ns; by then anything it still had in flight was committed or reaped.
+#[derive(Debug)]
+pub struct TimestampMerge {
+    queues: Vec<VecDeque<ProcessEvent>>,
+    lateness_ns: u64,
+    last_ts: u64,
+    merged: u64,
+    inversions: u64,
+}
+
+impl TimestampMerge {
+    pub fn new(partitions: usize, lateness_ns: u64) -> Self {
+        Self {
+            queues: (0..partitions).map(|_| VecDeque::new()).collect(),
+            lateness_ns,
+            last_ts: 0,
+            merged: 0,
+            inversions: 0,
+        }
+    }
+
+    pub fn push(&mut self, partition: usize, events: impl IntoIterator<Item = ProcessEvent>) {
+        self.queues[partition].extend(events);
+    }
+
+    pub fn pending(&self) -> usize {
+        self.queues.iter().map(VecDeque::len).sum()
+    }
+
+    /// Up to `max` events that can no longer be preceded by another, given
+    /// the current `CLOCK_MONOTONIC` time.
+    pub fn pop_ready(&mut self, now_ns: u64, max: usize) -> Vec<ProcessEvent> {
+        let watermark = now_ns.saturating_sub(self.lateness_ns);
+        let mut out = Vec::new();
+        while out.len() < max {
+            let all_have_heads = self.queues.iter().all(|q| !q.is_empty());
+            let Some((partition, ts)) = self
+                .queues
+                .iter()
+                .enumerate()
+                .filter_map(|(i, q)| q.front().map(|e| (i, e.ts_ns)))
+                .min_by_key(|&(_, ts)| ts)
+            else {
+                break;
+            };
+            if !all_have_heads && ts > watermark {
+                break;
+            }
+            let Some(event) = self.queues[partition].pop_front() else {
+                break;
+            };
+            // Timestamps are taken before the ticket, so a partition may
+            // itself be slightly out of timestamp order
+            if ts < self.last_ts {
+                self.inversions += 1;
+            }
+            self.last_ts = self.last_ts.max(ts);
+            self.merged += 1;
+            out.push(event);
+        }
+        out
+    }
+
+    pub fn merged(&self) -> u64 {
+        self.merged
+    }
+
+    /// Events released with an older timestamp than one released before.
+    pub fn inversions(&self) -> u64 {
+        self.inversions
+    }
+}
+
+fn monotonic_ns() -> u64 {
+    use nix::time::{ClockId, clock_gettime};
+    // bpf_ktime_get_ns is CLOCK_MONOTONIC
+    match clock_gettime(ClockId::CLOCK_MONOTONIC) {
+        Ok(ts) => (ts.tv_sec() as u64) * 1_000_000_000 + (ts.tv_nsec() as u64),
+        Err(_) => 0,
+    }
+}
+
+/// One ring and the node whose CPUs write it.
+pub struct Partition {
+    pub consumer: SequencerConsumer,
+    /// `None` leaves the consumer thread unpinned.
+    pub node: Option<NumaNode>,
+}
+
+/// Remote share of a single ring written by every node's CPUs, assuming
+/// events are spread across nodes in proportion to their CPUs.
+pub fn shared_ring_remote_share(pages: &PagePlacement, nodes: &[NumaNode]) -> Option<f64> {
+    let cpus: usize = nodes.iter().map(|n| n.cpus.len()).sum();
+    if cpus == 0 || pages.total() == 0 {
+        return None;
+    }
+    Some(
+        nodes
+            .iter()
+            .map(|n| {
+                n.cpus.len() as f64 / cpus as f64 * (1.0 - pages.share_on(n.id).unwrap_or(0.0))
+            })
+            .sum(),
+    )
+}
+
+struct PartitionState {
+    node: Option<NumaNode>,
+    pages: Option<PagePlacement>,
+    stats: Arc<Mutex<SequencerStats>>,
+}
+
+/// Per-node consumer threads feeding a [`TimestampMerge`].
+pub struct PartitionedSequencer {
+    rx: Receiver<(usize, Vec<ProcessEvent>)>,
+    merge: TimestampMerge,
+    states: Vec<PartitionState>,
+    stop: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl PartitionedSequencer {
+    /// Start one consumer thread per partition. Rings must be mapped (and
+    /// therefore zeroed) before the sequencer is switched to partitioned mode.
+    pub fn start(partitions: Vec<Partition>, batch_size: usize) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut states = Vec::new();
+        let mut threads = Vec::new();
+        for (index, partition) in partitions.into_iter().enumerate() {
+            let pages = match partition.consumer.page_placement() {
+                Ok(pages) => Some(pages),
+                Err(e) => {
+                    warn!("[numa] partition {index}: cannot query ring page placement: {e}");
+                    None
+                }
+            };
+            let stats = Arc::new(Mutex::new(SequencerStats::default()));
+            let placement = partition.node.as_ref().map(|node| {
+                info!(
+                    "[numa] partition {index}: consumer on node {} cpus {}",
+                    node.id,
+                    format_cpu_list(&node.cpus)
+                );
+                ThreadPlacement::new(
+                    CONSUMER_CLASSES[index],
+                    &ThreadPlacementConfig {
+                        cpus: Some(format_cpu_list(&node.cpus)),
+                        nice: None,
+                        sched_batch: false,
+                    },
+                )
+            });
+            threads.push(spawn_consumer(
+                index,
+                partition.consumer,
+                placement,
+                batch_size,
+                tx.clone(),
+                Arc::clone(&stats),
+                Arc::clone(&stop),
+            ));
+            states.push(PartitionState {
+                node: partition.node,
+                pages,
+                stats,
+            });
+        }
+        Self {
+            rx,
+            merge: TimestampMerge::new(states.len(), REAPER_TIMEOUT_NS),
+            states,
+            stop,
+            threads,
+        }
+    }
+
+    /// Up to `max` merged events; empty when nothing is ready yet.
+    pub fn poll(&mut self, max: usize) -> Vec<ProcessEvent> {
+        while let Ok((partition, batch)) = self.rx.try_recv() {
+            self.merge.push(partition, batch);
+        }
+        self.merge.pop_ready(monotonic_ns(), max)
+    }
+
+    /// Stats of each partition's consumer, refreshed by its thread.
+    pub fn consumer_stats(&self) -> Vec<Arc<Mutex<SequencerStats>>> {
+        self.states.iter().map(|s| Arc::clone(&s.stats)).collect()
+    }
+
+    pub fn report(&self) -> NumaReport {
+        let mut partitions = Vec::new();
+        let (mut weighted_remote, mut weighted_events) = (0.0, 0u64);
+        for (index, state) in self.states.iter().enumerate() {
+            let stats = state.stats.lock().unwrap().clone();
+            let local_page_share = match (&state.node, &state.pages) {
+                (Some(node), Some(pages)) => pages.share_on(node.id),
+                _ => None,
+            };
+            if let Some(local) = local_page_share {
+                weighted_remote += (1.0 - local) * stats.events_processed as f64;
+                weighted_events += stats.events_processed;
+            }
+            partitions.push(PartitionReport {
+                partition: index,
+                node: state.node.as_ref().map(|n| n.id),
+                consumer_cpus: state.node.as_ref().map(|n| format_cpu_list(&n.cpus)),
+                events: stats.events_processed,
+                events_reaped: stats.events_reaped,
+                events_lost: stats.events_lost,
+                ordering_violations: stats.ordering_violations,
+                pages: state.pages.clone(),
+                local_page_share,
+            });
+        }
+        NumaReport {
+            nodes: self.states.len(),
+            partitions,
+            merged: self.merge.merged(),
+            merge_inversions: self.merge.inversions(),
+            pending: self.merge.pending(),
+            remote_page_share: (weighted_events > 0)
+                .then(|| weighted_remote / weighted_events as f64),
+        }
+    }
+}
+
+impl Drop for PartitionedSequencer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn spawn_consumer(
+    index: usize,
+    mut consumer: SequencerConsumer,
+    placement: Option<ThreadPlacement>,
+    batch_size: usize,
+    tx: Sender<(usize, Vec<ProcessEvent>)>,
+    stats: Arc<Mutex<SequencerStats>>,
+    stop: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    std::thread::Builder::new()
+        .name(format!("seq-node{index}"))
+        .spawn(move || {
+            if let Some(placement) = placement {
+                placement.apply();
+            }
+            let mut idle = IdleBackoff::default();
+            while !stop.load(Ordering::Relaxed) {
+                let batch = consumer.poll_batch(batch_size);
+                *stats.lock().unwrap() = consumer.stats().clone();
+                if batch.is_empty() {
+                    idle.wait();
+                    continue;
+                }
+                idle.reset();
+                if tx.send((index, batch)).is_err() {
+                    break;
+                }
+            }
+        })
+        .expect("spawn sequencer consumer thread")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn event(ts_ns: u64, pid: u32) -> ProcessEvent {
+        ProcessEvent {
+            pid,
+            ppid: 1,
+            uid: 0,
+            gid: 0,
+            event_type: 0,
+            ts_ns,
+            seq: 0,
+            comm: [0; 16],
+            exit_time_ns: 0,
+            cpu_pct_milli: 0,
+            mem_pct_milli: 0,
+            data: 0,
+            data2: 0,
+            aux: 0,
+            aux2: 0,
+            cgroup_id: 0,
+        }
+    }
+
+    fn pids(events: &[ProcessEvent]) -> Vec<u32> {
+        events.iter().map(|e| e.pid).collect()
+    }
+
+    #[test]
+    fn merges_in_timestamp_order_and_waits_for_idle_partitions() {
+        let mut merge = TimestampMerge::new(2, 1_000);
+        merge.push(0, [event(10, 1), event(30, 3)]);
+        merge.push(1, [event(20, 2)]);
+        // Partition 1 runs dry after ts 20: ts 30 waits for the watermark
+        assert_eq!(pids(&merge.pop_ready(100, 10)), vec![1, 2]);
+        assert_eq!(merge.pending(), 1);
+        assert_eq!(pids(&merge.pop_ready(1_030, 10)), vec![3]);
+
+        // A late arrival older than what was released counts as an inversion
+        merge.push(1, [event(25, 4)]);
+        assert_eq!(pids(&merge.pop_ready(2_000, 10)), vec![4]);
+        assert_eq!((merge.merged(), merge.inversions()), (4, 1));
+    }
+
+    #[test]
+    fn reads_node_topology() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("online"), "0-2\n").unwrap();
+        for (node, cpus) in [(0, "0-3,8-11"), (1, "4-7,12-15"), (2, "")] {
+            let node_dir = dir.path().join(format!("node{node}"));
+            std::fs::create_dir_all(&node_dir).unwrap();
+            std::fs::write(node_dir.join("cpulist"), format!("{cpus}\n")).unwrap();
+        }
+        let nodes = online_nodes(dir.path()).unwrap();
+        assert_eq!(nodes.len(), 2, "memory-only node 2 is skipped");
+        assert_eq!(nodes[1].cpus, vec![4, 5, 6, 7, 12, 13, 14, 15]);
+        assert_eq!(partitions_for(&nodes), 2);
+
+        let pages = PagePlacement {
+            by_node: BTreeMap::from([(0, 3), (1, 1)]),
+            unresolved: 0,
+        };
+        // Half the CPUs see 25% local pages, half 75%
+        assert_eq!(shared_ring_remote_share(&pages, &nodes), Some(0.5));
+        assert!(
+            online_nodes(&dir.path().join("missing"))
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Accounting for page faults dropped by the in-kernel throttle.
+//!
+//! The throttle counts drops per pid in `PAGE_FAULT_SUPPRESSED`. The kernel
+//! keeps a pid's count after it exits, so every `accounting_secs` this task
+//! reads the map, folds the growth of each count into a running total, and
+//! deletes the entries of exited pids once their final count is in. Counts
+//! that shrink (LRU eviction followed by pid reuse) are taken as fresh.
+
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::bpf_control::BpfControl;
+use super::map_cleanup::{in_init_pid_namespace, pid_alive};
+use crate::config::PageFaultThrottleConfig;
+use crate::metrics::{Metrics, SuppressedFaults};
+
+#[derive(Debug, Default)]
+struct FaultLedger {
+    /// Count last read per pid
+    seen: HashMap<u32, u64>,
+    total: u64,
+}
+
+impl FaultLedger {
+    /// Fold one read of the map into the total. Returns the pids whose
+    /// entries can be deleted.
+    fn observe(&mut self, counts: &[(u32, u64)], alive: impl Fn(u32) -> bool) -> Vec<u32> {
+        let mut seen = HashMap::with_capacity(counts.len());
+        let mut exited = Vec::new();
+        for &(pid, count) in counts {
+            let previous = self.seen.get(&pid).copied().unwrap_or(0);
+            self.total += count.checked_sub(previous).unwrap_or(count);
+            if alive(pid) {
+                seen.insert(pid, count);
+            } else {
+                exited.push(pid);
+            }
+        }
+        self.seen = seen;
+        exited
+    }
+
+    fn live(&self) -> Vec<SuppressedFaults> {
+        let mut pids: Vec<SuppressedFaults> = self
+            .seen
+            .iter()
+            .map(|(&pid, &suppressed)| SuppressedFaults { pid, suppressed })
+            .collect();
+        pids.sort_unstable_by(|a, b| b.suppressed.cmp(&a.suppressed).then(a.pid.cmp(&b.pid)));
+        pids
+    }
+}
+
+/// Start the periodic read of the suppressed-fault counts.
+pub fn spawn(control: Arc<BpfControl>, metrics: Arc<Metrics>, config: &PageFaultThrottleConfig) {
+    // Outside the host pid namespace kernel pids can't be checked against
+    // /proc; treat every pid as live and leave eviction to the LRU.
+    let prune = in_init_pid_namespace();
+    let interval = Duration::from_secs(config.accounting_secs.max(1));
+    tokio::spawn(async move {
+        let mut ledger = FaultLedger::default();
+        let mut tick = tokio::time::interval(interval);
+        loop {
+            tick.tick().await;
+            let counts = match control.page_faults_suppressed().await {
+                Ok(counts) => counts,
+                Err(e) => {
+                    debug!("[page_faults] stopping accounting: {e:#}");
+                    return;
+                }
+            };
+            let exited = ledger.observe(&counts, |pid| !prune || pid_alive(pid));
+            if !exited.is_empty()
+                && let Err(e) = control.remove_page_faults_suppressed(&exited).await
+            {
+                warn!("[page_faults] failed to delete counts of exited pids: {e:#}");
+            }
+            metrics.set_page_faults_suppressed(ledger.total, ledger.live());
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ledger_counts_growth_once_and_releases_exited_pids() {
+        let mut ledger = FaultLedger::default();
+        assert!(ledger.observe(&[(10, 5), (20, 2)], |_| true).is_empty());
+        assert_eq!(ledger.total, 7);
+
+        // pid 20 exited after two more drops; pid 10 grew by 3
+        let exited = ledger.observe(&[(10, 8), (20, 4)], |pid| pid != 20);
+        assert_eq!(exited, vec![20]);
+        assert_eq!(ledger.total, 12);
+        let live = ledger.live();
+        assert_eq!(live.len(), 1);
+        assert_eq!((live[0].pid, live[0].suppressed), (10, 8));
+
+        // pid 20 reused after its entry was deleted: counted from zero
+        ledger.observe(&[(10, 8), (20, 1)], |_| true);
+        assert_eq!(ledger.total, 13);
+
+        // pid 10's entry evicted and the pid reused with a smaller count
+        ledger.observe(&[(10, 2), (20, 1)], |_| true);
+        assert_eq!(ledger.total, 15);
+    }
+}
@@ -0,0 +1,316 @@
+//! CPU affinity and scheduling of cognitod's threads
+//!
+//! Tokio workers and the kernel buffer consumers can be pinned to a CPU list
+//! and run at a lower priority (`nice`, `SCHED_BATCH`) so the daemon stays
+//! off serving cores. Placement is applied by each thread to itself when it
+//! starts. Anything the kernel refuses (CPUs outside the cpuset, negative
+//! nice without CAP_SYS_NICE) is logged and reported in `/status`; the
+//! thread keeps running with what it inherited.
+
+use log::{info, warn};
+use once_cell::sync::{Lazy, OnceCell};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::Mutex;
+use tokio::runtime::{Builder, Handle, Runtime};
+
+use crate::config::{ThreadPlacementConfig, ThreadsConfig};
+
+/// Most errors kept per thread class.
+const MAX_ERRORS: usize = 8;
+
+/// Parse a kernel-style CPU list (`0-3,6`).
+pub fn parse_cpu_list(list: &str) -> anyhow::Result<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for part in list.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let (start, end): (usize, usize) = (start.trim().parse()?, end.trim().parse()?);
+                anyhow::ensure!(start <= end, "descending range {part}");
+                cpus.extend(start..=end);
+            }
+            None => cpus.push(part.parse()?),
+        }
+    }
+    anyhow::ensure!(!cpus.is_empty(), "empty CPU list");
+    cpus.sort_unstable();
+    cpus.dedup();
+    if let Some(&cpu) = cpus.iter().find(|&&cpu| cpu >= libc::CPU_SETSIZE as usize) {
+        anyhow::bail!("CPU {cpu} exceeds CPU_SETSIZE");
+    }
+    Ok(cpus)
+}
+
+/// Format CPUs as a compact list (`0-3,6`).
+pub fn format_cpu_list(cpus: &[usize]) -> String {
+    let mut out: Vec<String> = Vec::new();
+    let mut iter = cpus.iter().copied().peekable();
+    while let Some(start) = iter.next() {
+        let mut end = start;
+        while iter.peek() == Some(&(end + 1)) {
+            end = iter.next().unwrap_or(end);
+        }
+        out.push(if start == end {
+            start.to_string()
+        } else {
+            format!("{start}-{end}")
+        });
+    }
+    out.join(",")
+}
+
+fn set_affinity(cpus: &[usize]) -> io::Result<()> {
+    // SAFETY: cpu_set_t is a plain bitmask; CPUs were bounds-checked when parsed.
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    for &cpu in cpus {
+        unsafe { libc::CPU_SET(cpu, &mut set) };
+    }
+    // pid 0 is the calling thread
+    let rc = unsafe { libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) };
+    if rc == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+fn current_affinity() -> io::Result<Vec<usize>> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let rc =
+        unsafe { libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) };
+    if rc != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((0..libc::CPU_SETSIZE as usize)
+        .filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) })
+        .collect())
+}
+
+fn set_sched_batch() -> io::Result<()> {
+    let param = libc::sched_param { sched_priority: 0 };
+    if unsafe { libc::sched_setscheduler(0, libc::SCHED_BATCH, &param) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+fn set_nice(nice: i32) -> io::Result<()> {
+    // Linux applies PRIO_PROCESS to a single thread when given its tid.
+    let tid = unsafe { libc::syscall(libc::SYS_gettid) } as libc::id_t;
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, nice) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Effective placement of one thread class, as reported in `/status`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ThreadClassStatus {
+    /// Threads that applied the placement.
+    pub threads: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requested_cpus: Option<String>,
+    /// Affinity the most recent thread ended up with.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effective_cpus: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nice: Option<i32>,
+    pub sched_batch: bool,
+    pub errors: Vec<String>,
+}
+
+static STATUS: Lazy<Mutex<BTreeMap<&'static str, ThreadClassStatus>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+/// Placement of one thread class with its CPU list parsed.
+#[derive(Debug, Clone)]
+pub struct ThreadPlacement {
+    class: &'static str,
+    cpus: Option<Vec<usize>>,
+    nice: Option<i32>,
+    sched_batch: bool,
+}
+
+impl ThreadPlacement {
+    /// An unparseable CPU list is reported and ignored.
+    pub fn new(class: &'static str, config: &ThreadPlacementConfig) -> Self {
+        let cpus = config
+            .cpus
+            .as_deref()
+            .and_then(|list| match parse_cpu_list(list) {
+                Ok(cpus) => Some(cpus),
+                Err(e) => {
+                    record_error(class, format!("invalid cpus {list:?}: {e}"));
+                    None
+                }
+            });
+        let mut status = STATUS.lock().unwrap();
+        let entry = status.entry(class).or_default();
+        entry.requested_cpus = config.cpus.clone();
+        entry.nice = config.nice;
+        entry.sched_batch = config.sched_batch;
+        Self {
+            class,
+            cpus,
+            nice: config.nice,
+            sched_batch: config.sched_batch,
+        }
+    }
+
+    /// Number of CPUs requested, if any.
+    pub fn cpu_count(&self) -> Option<usize> {
+        self.cpus.as_ref().map(Vec::len)
+    }
+
+    /// Apply to the calling thread. Failures are logged and recorded.
+    pub fn apply(&self) {
+        let class = self.class;
+        if let Some(cpus) = &self.cpus
+            && let Err(e) = set_affinity(cpus)
+        {
+            record_error(class, format!("affinity {}: {e}", format_cpu_list(cpus)));
+        }
+        if self.sched_batch
+            && let Err(e) = set_sched_batch()
+        {
+            record_error(class, format!("SCHED_BATCH: {e}"));
+        }
+        if let Some(nice) = self.nice
+            && let Err(e) = set_nice(nice)
+        {
+            record_error(class, format!("nice {nice}: {e}"));
+        }
+        let effective = current_affinity().ok().map(|cpus| format_cpu_list(&cpus));
+        let mut status = STATUS.lock().unwrap();
+        let entry = status.entry(class).or_default();
+        entry.threads += 1;
+        entry.effective_cpus = effective;
+    }
+}
+
+fn record_error(class: &'static str, error: String) {
+    let mut status = STATUS.lock().unwrap();
+    let entry = status.entry(class).or_default();
+    if !entry.errors.contains(&error) {
+        // Log each distinct failure once, not per thread
+        warn!("[placement] {class}: {error}; continuing with inherited placement");
+        if entry.errors.len() < MAX_ERRORS {
+            entry.errors.push(error);
+        }
+    }
+}
+
+/// Build the main runtime with worker placement applied to every worker
+/// and to the calling thread, which drives `block_on`.
+pub fn build_runtime(config: &ThreadsConfig) -> io::Result<Runtime> {
+    let mut builder = Builder::new_multi_thread();
+    builder.enable_all();
+    let workers = config
+        .workers
+        .is_set()
+        .then(|| ThreadPlacement::new("workers", &config.workers));
+    let threads = config
+        .worker_threads
+        .or_else(|| workers.as_ref().and_then(ThreadPlacement::cpu_count));
+    if let Some(threads) = threads {
+        builder.worker_threads(threads.max(1));
+    }
+    if let Some(workers) = workers {
+        workers.apply();
+        info!(
+            "[placement] workers: cpus={} nice={:?} sched_batch={}",
+            workers.cpus.as_deref().map_or("-".into(), format_cpu_list),
+            workers.nice,
+            workers.sched_batch
+        );
+        builder.on_thread_start(move || workers.apply());
+    }
+    builder.build()
+}
+
+static CONSUMER_RUNTIME: OnceCell<Runtime> = OnceCell::new();
+
+/// Handle of a dedicated runtime for the buffer consumers, or `None` when
+/// no consumer placement is configured (they then share the workers).
+pub fn consumer_handle(config: &ThreadPlacementConfig) -> Option<Handle> {
+    if !config.is_set() {
+        return None;
+    }
+    let runtime = CONSUMER_RUNTIME.get_or_try_init(|| {
+        let placement = ThreadPlacement::new("consumers", config);
+        Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("cognitod-consumer")
+            .worker_threads(placement.cpu_count().unwrap_or(1))
+            .on_thread_start(move || placement.apply())
+            .build()
+    });
+    match runtime {
+        Ok(runtime) => Some(runtime.handle().clone()),
+        Err(e) => {
+            warn!("[placement] consumer runtime unavailable ({e}); consumers share the workers");
+            None
+        }
+    }
+}
+
+/// Placement of every configured thread class plus the process affinity.
+#[derive(Debug, Clone, Serialize)]
+pub struct PlacementStatus {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub process_cpus: Option<String>,
+    pub classes: BTreeMap<&'static str, ThreadClassStatus>,
+}
+
+pub fn status() -> PlacementStatus {
+    let process_cpus = std::fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find_map(|l| l.strip_prefix("Cpus_allowed_list:"))
+                .map(|v| v.trim().to_string())
+        });
+    PlacementStatus {
+        process_cpus,
+        classes: STATUS.lock().unwrap().clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_lists_round_trip() {
+        assert_eq!(parse_cpu_list("0-3, 6,2").unwrap(), vec![0, 1, 2, 3, 6]);
+        assert_eq!(format_cpu_list(&[0, 1, 2, 3, 6, 8, 9]), "0-3,6,8-9");
+        assert!(parse_cpu_list("3-1").is_err());
+        assert!(parse_cpu_list("").is_err());
+        assert!(parse_cpu_list("a").is_err());
+        assert!(parse_cpu_list("99999").is_err());
+    }
+
+    #[test]
+    fn applying_current_affinity_succeeds() {
+        let cpus = current_affinity().unwrap();
+        let placement = ThreadPlacement::new(
+            "test",
+            &ThreadPlacementConfig {
+                cpus: Some(format_cpu_list(&cpus)),
+                nice: None,
+                sched_batch: false,
+            },
+        );
+        std::thread::spawn(move || placement.apply())
+            .join()
+            .unwrap();
+        let status = status().classes["test"].clone();
+        assert_eq!(status.threads, 1);
+        assert!(status.errors.is_empty(), "{:?}", status.errors);
+        assert_eq!(status.effective_cpus, Some(format_cpu_list(&cpus)));
+    }
+}
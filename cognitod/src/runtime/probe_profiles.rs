@@ -0,0 +1,205 @@
+//! Kprobe symbol profiles
+//!
+//! Kernel function names are not stable: distributions rename or inline
+//! functions, and the compiler emits clones such as `tcp_sendmsg.isra.0`
+//! when it specializes one. Each kprobe program has an ordered list of
+//! symbols it can attach to. At attach time the first one present in
+//! `/proc/kallsyms` is used, trying each name before its compiler clones.
+//! The symbol each program bound to is reported under `probes.kprobes` in
+//! `/status`. A kretprobe `<program>_ret` uses the profile of `<program>`, so
+//! both ends of a call attach to the same function.
+
+use log::debug;
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Kprobe program and the kernel symbols it can attach to, preferred first.
+#[derive(Debug, Clone, Copy)]
+pub struct ProbeProfile {
+    pub program: &'static str,
+    pub symbols: &'static [&'static str],
+}
+
+pub const PROFILES: &[ProbeProfile] = &[
+    ProbeProfile {
+        program: "trace_tcp_send",
+        symbols: &["tcp_sendmsg", "tcp_sendmsg_locked"],
+    },
+    ProbeProfile {
+        program: "trace_tcp_recv",
+        symbols: &["tcp_recvmsg", "tcp_recvmsg_locked"],
+    },
+    ProbeProfile {
+        program: "trace_udp_send",
+        symbols: &["udp_sendmsg"],
+    },
+    ProbeProfile {
+        program: "trace_udp_recv",
+        symbols: &["udp_recvmsg"],
+    },
+    ProbeProfile {
+        program: "trace_unix_stream_send",
+        symbols: &["unix_stream_sendmsg"],
+    },
+    ProbeProfile {
+        program: "trace_unix_stream_recv",
+        symbols: &["unix_stream_recvmsg"],
+    },
+    ProbeProfile {
+        program: "trace_unix_dgram_send",
+        symbols: &["unix_dgram_sendmsg"],
+    },
+    ProbeProfile {
+        program: "trace_unix_dgram_recv",
+        symbols: &["unix_dgram_recvmsg"],
+    },
+    ProbeProfile {
+        program: "trace_vfs_read",
+        symbols: &["vfs_read", "ksys_read"],
+    },
+    ProbeProfile {
+        program: "trace_vfs_write",
+        symbols: &["vfs_write", "ksys_write"],
+    },
+    ProbeProfile {
+        program: "trace_listen_start",
+        symbols: &["inet_csk_listen_start"],
+    },
+    ProbeProfile {
+        program: "trace_conn_request",
+        symbols: &["inet_csk_reqsk_queue_hash_add"],
+    },
+    ProbeProfile {
+        program: "trace_syn_cookie_v4",
+        symbols: &["__cookie_v4_init_sequence"],
+    },
+    ProbeProfile {
+        program: "trace_syn_cookie_v6",
+        symbols: &["__cookie_v6_init_sequence"],
+    },
+    ProbeProfile {
+        program: "trace_fd_alloc",
+        symbols: &["get_unused_fd_flags", "alloc_fd", "__alloc_fd"],
+    },
+];
+
+/// Profile for `program`.
+pub fn profile(program: &str) -> Option<&'static ProbeProfile> {
+    let program = program.strip_suffix("_ret").unwrap_or(program);
+    PROFILES.iter().find(|p| p.program == program)
+}
+
+/// Where a kprobe program ended up.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProbeBinding {
+    pub program: &'static str,
+    /// First symbol of the profile
+    pub preferred: &'static str,
+    /// Symbol attached to; `None` when the probe is not attached
+    pub symbol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ProbeBinding {
+    pub fn fallback(&self) -> bool {
+        self.symbol.as_deref().is_some_and(|s| s != self.preferred)
+    }
+}
+
+/// Text symbols from `/proc/kallsyms` that belong to some profile.
+#[derive(Debug, Default)]
+pub struct KernelSymbols {
+    names: HashSet<String>,
+}
+
+/// `tcp_sendmsg.isra.0` -> `tcp_sendmsg`
+fn base_name(symbol: &str) -> &str {
+    symbol.split('.').next().unwrap_or(symbol)
+}
+
+impl KernelSymbols {
+    /// `None` when `/proc/kallsyms` cannot be read.
+    pub fn load() -> Option<Self> {
+        match std::fs::read_to_string("/proc/kallsyms") {
+            Ok(text) => Some(Self::parse(&text)),
+            Err(e) => {
+                debug!("[probes] /proc/kallsyms unreadable: {e}");
+                None
+            }
+        }
+    }
+
+    fn parse(kallsyms: &str) -> Self {
+        let wanted: HashSet<&str> = PROFILES
+            .iter()
+            .flat_map(|p| p.symbols.iter().copied())
+            .collect();
+        let names = kallsyms
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let kind = fields.nth(1)?;
+                let name = fields.next()?;
+                (kind.eq_ignore_ascii_case("t") && wanted.contains(base_name(name)))
+                    .then(|| name.to_string())
+            })
+            .collect();
+        Self { names }
+    }
+
+    /// Attach order for `symbols`: each name the kernel has, or failing that
+    /// its clones (never `.cold` parts, which are not entry points).
+    pub fn candidates(&self, symbols: &[&str]) -> Vec<String> {
+        let mut out = Vec::new();
+        for symbol in symbols {
+            if self.names.contains(*symbol) {
+                out.push(symbol.to_string());
+                continue;
+            }
+            let mut clones: Vec<&String> = self
+                .names
+                .iter()
+                .filter(|name| base_name(name) == *symbol && !name.contains(".cold"))
+                .collect();
+            clones.sort();
+            out.extend(clones.into_iter().cloned());
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_first_present_symbol_then_clones() {
+        let symbols = KernelSymbols::parse(
+            "ffffffff81000000 T vfs_read\n\
+             0000000000000000 t tcp_sendmsg_locked\n\
+             0000000000000000 t tcp_recvmsg.isra.0\n\
+             0000000000000000 t tcp_recvmsg.cold\n\
+             0000000000000000 d udp_sendmsg\n\
+             0000000000000000 t unrelated_fn\n",
+        );
+        let send = profile("trace_tcp_send").unwrap();
+        assert_eq!(symbols.candidates(send.symbols), ["tcp_sendmsg_locked"]);
+        let recv = profile("trace_tcp_recv").unwrap();
+        assert_eq!(symbols.candidates(recv.symbols), ["tcp_recvmsg.isra.0"]);
+        assert_eq!(profile("trace_tcp_recv_ret").unwrap().program, recv.program);
+        let read = profile("trace_vfs_read").unwrap();
+        assert_eq!(symbols.candidates(read.symbols), ["vfs_read"]);
+        // Data symbols are not attachable
+        let udp = profile("trace_udp_send").unwrap();
+        assert!(symbols.candidates(udp.symbols).is_empty());
+
+        let binding = ProbeBinding {
+            program: send.program,
+            preferred: send.symbols[0],
+            symbol: Some("tcp_sendmsg_locked".into()),
+            error: None,
+        };
+        assert!(binding.fallback());
+    }
+}
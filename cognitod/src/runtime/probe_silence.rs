@@ -0,0 +1,235 @@
+//! Silence detection per event type.
+//!
+//! A probe that detaches or stops firing shows up as no events of its type
+//! while the others keep flowing. Every `interval_secs` the check reads the
+//! received-event counter of each type and learns its rate, an EWMA over the
+//! intervals that saw events. A type that has gone `window_secs` without an
+//! event, and whose learned rate predicts at least `min_expected` events in
+//! that window, is silent: `probe_silence` fires once, the type is listed
+//! under `probes.silent_event_types` in `/status`, and it clears when the
+//! type is seen again. Types are only judged in intervals where another type
+//! still flowed, so an idle node does not flag every probe.
+
+use log::{info, warn};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::alerts::{Alert, AlertSender, Severity};
+use crate::config::ProbeSilenceConfig;
+use crate::event_schema::event_type_name;
+use crate::metrics::{Metrics, SilentEventType};
+
+pub const RULE_NAME: &str = "probe_silence";
+
+const RATE_ALPHA: f64 = 0.2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Change {
+    Silent { event_type: u32, rate_per_sec: f64 },
+    Resumed { event_type: u32, silent_secs: u64 },
+}
+
+struct TypeState {
+    count: u64,
+    last_seen: Instant,
+    /// Events per second while flowing; `None` until the type is seen
+    rate_per_sec: Option<f64>,
+    raised: bool,
+}
+
+struct SilenceCheck {
+    window: Duration,
+    min_expected: f64,
+    types: BTreeMap<u32, TypeState>,
+    last_check: Option<Instant>,
+}
+
+impl SilenceCheck {
+    fn new(config: &ProbeSilenceConfig) -> Self {
+        Self {
+            window: Duration::from_secs(config.window_secs.max(1)),
+            min_expected: config.min_expected,
+            types: BTreeMap::new(),
+            last_check: None,
+        }
+    }
+
+    /// Compare `counts` (received events per type id) with the last call.
+    fn check(&mut self, counts: &[(u32, u64)], now: Instant) -> Vec<Change> {
+        let elapsed = self
+            .last_check
+            .replace(now)
+            .map(|last| now.saturating_duration_since(last).as_secs_f64());
+        let mut changes = Vec::new();
+        let mut flowing = false;
+        for &(event_type, count) in counts {
+            let state = self.types.entry(event_type).or_insert(TypeState {
+                count,
+                last_seen: now,
+                rate_per_sec: None,
+                raised: false,
+            });
+            let delta = count.saturating_sub(state.count);
+            state.count = count;
+            let Some(elapsed) = elapsed.filter(|e| *e > 0.0) else {
+                continue;
+            };
+            if delta == 0 {
+                continue;
+            }
+            flowing = true;
+            let sample = delta as f64 / elapsed;
+            state.rate_per_sec = Some(match state.rate_per_sec {
+                Some(rate) => rate + RATE_ALPHA * (sample - rate),
+                None => sample,
+            });
+            if std::mem::take(&mut state.raised) {
+                changes.push(Change::Resumed {
+                    event_type,
+                    silent_secs: now.saturating_duration_since(state.last_seen).as_secs(),
+                });
+            }
+            state.last_seen = now;
+        }
+        if !flowing {
+            return changes;
+        }
+        for (&event_type, state) in &mut self.types {
+            let Some(rate_per_sec) = state.rate_per_sec else {
+                continue;
+            };
+            if state.raised
+                || now.saturating_duration_since(state.last_seen) < self.window
+                || rate_per_sec * self.window.as_secs_f64() < self.min_expected
+            {
+                continue;
+            }
+            state.raised = true;
+            changes.push(Change::Silent {
+                event_type,
+                rate_per_sec,
+            });
+        }
+        changes
+    }
+
+    /// Types currently flagged, for `/status`.
+    fn silent(&self, now: Instant) -> Vec<SilentEventType> {
+        self.types
+            .iter()
+            .filter(|(_, state)| state.raised)
+            .map(|(&event_type, state)| SilentEventType {
+                event_type: event_type_name(event_type),
+                silent_secs: now.saturating_duration_since(state.last_seen).as_secs(),
+                expected_per_min: state.rate_per_sec.unwrap_or_default() * 60.0,
+            })
+            .collect()
+    }
+}
+
+/// Start the periodic silence check.
+pub fn spawn(metrics: Arc<Metrics>, alerts: Option<AlertSender>, config: &ProbeSilenceConfig) {
+    let mut check = SilenceCheck::new(config);
+    let interval = Duration::from_secs(config.interval_secs.max(1));
+    let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown".into());
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(interval);
+        loop {
+            tick.tick().await;
+            let now = Instant::now();
+            for change in check.check(&metrics.received_by_type(), now) {
+                let (event_type, rate_per_sec) = match change {
+                    Change::Resumed {
+                        event_type,
+                        silent_secs,
+                    } => {
+                        info!(
+                            "[probe-silence] {} events resumed after {silent_secs}s",
+                            event_type_name(event_type)
+                        );
+                        continue;
+                    }
+                    Change::Silent {
+                        event_type,
+                        rate_per_sec,
+                    } => (event_type, rate_per_sec),
+                };
+                let message = format!(
+                    "no {} events for {}s while other event types flow \
+                     (usually {:.1}/min); its probe may have detached",
+                    event_type_name(event_type),
+                    check.window.as_secs(),
+                    rate_per_sec * 60.0
+                );
+                warn!("[probe-silence] {message}");
+                if let Some(tx) = &alerts {
+                    tx.send(Alert {
+                        rule: RULE_NAME.to_string(),
+                        severity: Severity::Medium,
+                        message,
+                        host: host.clone(),
+                        detection: "anomaly",
+                        suppressed_by: None,
+                        lineage: Vec::new(),
+                        children: Vec::new(),
+                        pod: None,
+                        evidence: None,
+                        annotations: None,
+                    });
+                }
+            }
+            metrics.set_silent_event_types(check.silent(now));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_a_type_that_stops_while_others_flow() {
+        let config = ProbeSilenceConfig {
+            window_secs: 30,
+            min_expected: 10.0,
+            ..ProbeSilenceConfig::default()
+        };
+        let mut check = SilenceCheck::new(&config);
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        // exec=0 flows at 1/s, listen=8 at one per 10s (never judged)
+        let counts = |exec: u64, net: u64, listen: u64| vec![(0, exec), (3, net), (8, listen)];
+
+        assert!(check.check(&counts(0, 0, 0), at(0)).is_empty());
+        for step in 1..=3 {
+            let t = step * 10;
+            assert!(check.check(&counts(t, t * 5, step), at(t)).is_empty());
+        }
+
+        // net stops; flagged once the window has passed
+        assert!(check.check(&counts(40, 150, 4), at(40)).is_empty());
+        assert!(check.check(&counts(50, 150, 5), at(50)).is_empty());
+        let changes = check.check(&counts(60, 150, 6), at(60));
+        assert!(matches!(
+            changes.as_slice(),
+            [Change::Silent { event_type: 3, rate_per_sec }] if (*rate_per_sec - 5.0).abs() < 1e-9
+        ));
+        assert_eq!(check.silent(at(60))[0].event_type, "net");
+        assert!(check.check(&counts(70, 150, 7), at(70)).is_empty());
+
+        // Nothing flows: not judged, and listen is too rare to flag anyway
+        assert!(check.check(&counts(70, 150, 7), at(200)).is_empty());
+
+        let changes = check.check(&counts(80, 160, 7), at(210));
+        assert_eq!(
+            changes,
+            vec![Change::Resumed {
+                event_type: 3,
+                silent_secs: 180
+            }]
+        );
+        assert!(check.silent(at(210)).is_empty());
+    }
+}
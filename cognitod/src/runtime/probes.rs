@@ -1,3 +1,6 @@
+use super::features::FeatureNegotiation;
+use super::probe_profiles::ProbeBinding;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum RssProbeMode {
     CoreSignal,
@@ -26,10 +29,14 @@ impl RssProbeMode {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub struct ProbeState {
     pub rss_probe: RssProbeMode,
     pub btf_available: bool,
+    /// Features negotiated with the main BPF object, if it was loaded.
+    pub features: Option<FeatureNegotiation>,
+    /// Symbol each kprobe program attached to.
+    pub kprobes: Vec<ProbeBinding>,
 }
 
 impl ProbeState {
@@ -37,6 +44,8 @@ impl ProbeState {
         Self {
             rss_probe: RssProbeMode::Disabled,
             btf_available: false,
+            features: None,
+            kprobes: Vec::new(),
         }
     }
 }
@@ -31,8 +31,11 @@
 //!   avoiding cache ping-pong with kernel producers.
 
 #![allow(dead_code)] // Suppress unused warnings for WIP sequencer
+use std::collections::BTreeMap;
 use std::io;
 use std::os::fd::{BorrowedFd, RawFd};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
 
 use linnix_ai_ebpf_common::{
     ProcessEvent, REAPER_TIMEOUT_NS, SEQUENCER_RING_MASK, SEQUENCER_RING_SIZE, SequencedSlot,
@@ -52,6 +55,9 @@ use memmap2::MmapMut;
 // We use madvise(MADV_HUGEPAGE) to request transparent huge pages. This is
 // a hint - the kernel may or may not use huge pages depending on availability.
 
+/// Unit of work when zeroing the ring at startup: one huge page
+const ZERO_CHUNK: usize = 2 * 1024 * 1024;
+
 /// MADV_HUGEPAGE constant (14 on Linux)
 const MADV_HUGEPAGE: libc::c_int = 14;
 
@@ -77,6 +83,43 @@ fn advise_hugepages(ptr: *mut SequencedSlot, len: usize) {
     }
 }
 
+/// Shortest sleep after an empty poll
+const IDLE_MIN: Duration = Duration::from_micros(50);
+/// Longest sleep while the ring stays empty
+const IDLE_MAX: Duration = Duration::from_millis(5);
+
+/// Sleep between empty polls, doubling from `IDLE_MIN` to `IDLE_MAX` while
+/// the ring stays empty, so an idle consumer does not spin. Producers do
+/// not signal the consumer, so it still has to poll.
+#[derive(Debug)]
+pub struct IdleBackoff {
+    next: Duration,
+}
+
+impl Default for IdleBackoff {
+    fn default() -> Self {
+        Self { next: IDLE_MIN }
+    }
+}
+
+impl IdleBackoff {
+    /// Events arrived: the next empty poll sleeps the least again.
+    pub fn reset(&mut self) {
+        self.next = IDLE_MIN;
+    }
+
+    /// Sleep to take after this empty poll.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.next;
+        self.next = (self.next * 2).min(IDLE_MAX);
+        delay
+    }
+
+    pub fn wait(&mut self) {
+        std::thread::sleep(self.next_delay());
+    }
+}
+
 /// Statistics for the sequencer consumer
 #[derive(Debug, Default, Clone)]
 pub struct SequencerStats {
@@ -92,6 +135,50 @@ pub struct SequencerStats {
     pub max_batch_size: usize,
     /// Number of ordering violations detected (should always be 0)
     pub ordering_violations: u64,
+    /// Tickets skipped when resyncing past a gap (events never consumed)
+    pub events_lost: u64,
+    /// Next ticket the consumer will read
+    pub cursor: u64,
+    /// Ordering diagnostics per producing CPU
+    pub per_cpu: BTreeMap<u16, CpuSequence>,
+}
+
+/// Continuity of one CPU's events, checked against the per-CPU `seq` the
+/// producer stamps after reserving a ticket.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CpuSequence {
+    /// Events read from this CPU
+    pub events: u64,
+    /// Slots this CPU reserved but never committed
+    pub reaped: u64,
+    /// Sequence numbers never seen, reaped slots included
+    pub gaps: u64,
+    /// Events whose `seq` was not above the CPU's previous one: programs
+    /// nested on the CPU reserved tickets out of `seq` order
+    pub inversions: u64,
+    pub last_seq: u64,
+}
+
+impl CpuSequence {
+    /// Account an event with per-CPU sequence number `seq` (0: unstamped).
+    pub fn observe(&mut self, seq: u64) {
+        self.events += 1;
+        if seq == 0 {
+            return;
+        }
+        if self.last_seq != 0 {
+            if seq <= self.last_seq {
+                self.inversions += 1;
+                // A late event fills a gap counted when its successor came
+                if seq < self.last_seq {
+                    self.gaps = self.gaps.saturating_sub(1);
+                }
+                return;
+            }
+            self.gaps += seq - self.last_seq - 1;
+        }
+        self.last_seq = seq;
+    }
 }
 
 /// Validates strict ordering of incoming events
@@ -166,6 +253,23 @@ impl SequencerConsumer {
     /// The map MUST have been created with BPF_F_MMAPABLE flag.
     /// This constructor will mmap the entire ring buffer for zero-copy access.
     pub fn from_fd(fd: BorrowedFd<'_>) -> io::Result<Self> {
+        let mut consumer = Self::map(fd)?;
+
+        // Clear stale slots of a reused map (the caller should reset
+        // SEQUENCER_INDEX first). New maps come zeroed from the kernel and
+        // are only scanned.
+        consumer.zero_ring_buffer();
+
+        Ok(consumer)
+    }
+
+    /// Map a ring that outlived the previous consumer without zeroing it,
+    /// so a cursor restored by `runtime::cursor` can resume in it.
+    pub fn attach_fd(fd: BorrowedFd<'_>) -> io::Result<Self> {
+        Self::map(fd)
+    }
+
+    fn map(fd: BorrowedFd<'_>) -> io::Result<Self> {
         let ring_size_bytes = (SEQUENCER_RING_SIZE as usize) * std::mem::size_of::<SequencedSlot>();
 
         info!(
@@ -204,7 +308,7 @@ impl SequencerConsumer {
             ring_size_bytes / (1024 * 1024)
         );
 
-        let mut consumer = Self {
+        Ok(Self {
             _mmap: mmap,
             ring_ptr,
             cursor: 0, // Will be set by caller if needed
@@ -212,29 +316,43 @@ impl SequencerConsumer {
             validator: OrderingValidator::new(),
             stats: SequencerStats::default(),
             reaper_timeout_ns: REAPER_TIMEOUT_NS,
-        };
-
-        // Zero the ring buffer to clear any uninitialized memory.
-        // This is safe because:
-        // 1. For new maps: memory may be uninitialized
-        // 2. For reused maps: caller should reset SEQUENCER_INDEX first
-        // The memset is fast (~50ms for 256MB)
-        consumer.zero_ring_buffer();
-
-        Ok(consumer)
+        })
     }
 
-    /// Fast zero of entire ring buffer using memset.
-    /// Called once at startup to ensure no garbage data.
+    /// Zero the ring in `ZERO_CHUNK` pieces spread over all cores. A piece
+    /// that is already zero, as all of a freshly created map is, is only
+    /// read, so a new ring costs a scan rather than a 128MB write.
     fn zero_ring_buffer(&mut self) {
         let len = (SEQUENCER_RING_SIZE as usize) * std::mem::size_of::<SequencedSlot>();
         info!("Zeroing ring buffer ({} MB)...", len / (1024 * 1024));
         let start = std::time::Instant::now();
-        unsafe {
-            let ptr = self.ring_ptr as *mut u8;
-            core::ptr::write_bytes(ptr, 0, len);
-        }
-        info!("Ring buffer zeroed in {:?}", start.elapsed());
+        // SAFETY: the mapping is `len` bytes long and the producers are not
+        // enabled yet, so nothing else touches it.
+        let ring = unsafe { std::slice::from_raw_parts_mut(self.ring_ptr as *mut u8, len) };
+        let threads = std::thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .clamp(1, len.div_ceil(ZERO_CHUNK));
+        let written = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for part in ring.chunks_mut(len.div_ceil(threads)) {
+                let written = &written;
+                scope.spawn(move || {
+                    for chunk in part.chunks_mut(ZERO_CHUNK) {
+                        // No early exit, so the scan vectorizes
+                        if chunk.iter().fold(0u8, |acc, &b| acc | b) != 0 {
+                            chunk.fill(0);
+                            written.fetch_add(chunk.len(), Ordering::Relaxed);
+                        }
+                    }
+                });
+            }
+        });
+        info!(
+            "Ring buffer zeroed in {:?} on {} threads ({} MB written)",
+            start.elapsed(),
+            threads,
+            written.load(Ordering::Relaxed) / (1024 * 1024)
+        );
     }
 
     /// Set the consumer cursor position.
@@ -242,6 +360,7 @@ impl SequencerConsumer {
     pub fn set_cursor(&mut self, cursor: u64) {
         info!("Setting consumer cursor to {}", cursor);
         self.cursor = cursor;
+        self.stats.cursor = cursor;
     }
 
     /// Create consumer from raw fd (for backwards compatibility)
@@ -267,6 +386,32 @@ impl SequencerConsumer {
         self.cursor
     }
 
+    /// The event of a ticket behind the cursor, if its slot still holds it.
+    /// For [`super::backlog::Backlog`]; leaves the cursor and stats alone.
+    pub fn read_behind(&self, ticket: u64) -> Option<ProcessEvent> {
+        if ticket >= self.cursor {
+            return None;
+        }
+        let slot = self.get_slot(ticket);
+        let flags = unsafe { core::ptr::read_volatile(&slot.flags) };
+        #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+        std::sync::atomic::fence(std::sync::atomic::Ordering::Acquire);
+        let held = unsafe { core::ptr::read_volatile(&slot.ticket_id) };
+        if flags != slot_flags::READY || held != ticket {
+            return None;
+        }
+        let event = unsafe { core::ptr::read_volatile(&slot.event) };
+        // A producer may have lapped the slot while it was copied
+        let held = unsafe { core::ptr::read_volatile(&slot.ticket_id) };
+        (held == ticket).then_some(event)
+    }
+
+    /// NUMA node of each page of the mapped ring.
+    pub fn page_placement(&self) -> io::Result<super::numa::PagePlacement> {
+        let len = (SEQUENCER_RING_SIZE as usize) * std::mem::size_of::<SequencedSlot>();
+        super::numa::page_placement(self.ring_ptr as *const u8, len)
+    }
+
     /// Get the current boot time in nanoseconds (for reaper timeout checks)
     fn get_boot_time_ns() -> u64 {
         use nix::time::{ClockId, clock_gettime};
@@ -321,6 +466,26 @@ impl SequencerConsumer {
     /// - This keeps cache lines in Shared state, eliminating coherency traffic
     pub fn poll_batch(&mut self, max_batch_size: usize) -> Vec<ProcessEvent> {
         let mut events = Vec::with_capacity(max_batch_size);
+        self.poll_with(max_batch_size, |_, event| events.push(event));
+        events
+    }
+
+    /// Like [`Self::poll_batch`], with the ticket of each event, for
+    /// handlers that keep delivery marks.
+    pub fn poll_batch_tickets(&mut self, max_batch_size: usize) -> Vec<(u64, ProcessEvent)> {
+        let mut events = Vec::with_capacity(max_batch_size);
+        self.poll_with(max_batch_size, |ticket, event| events.push((ticket, event)));
+        events
+    }
+
+    /// Hand up to `max_batch_size` events to `sink`; returns how many.
+    #[inline(always)]
+    fn poll_with(
+        &mut self,
+        max_batch_size: usize,
+        mut sink: impl FnMut(u64, ProcessEvent),
+    ) -> usize {
+        let mut read = 0;
         let now_ns = Self::get_boot_time_ns();
         self.stats.poll_cycles += 1;
 
@@ -334,8 +499,11 @@ impl SequencerConsumer {
             // Read flag with volatile to ensure we see kernel updates
             let flags = unsafe { core::ptr::read_volatile(&(*slot_ptr).flags) };
 
-            // NOTE: On x86, volatile reads have implicit acquire semantics.
-            // We skip the explicit fence for performance.
+            // x86 loads are not reordered with older loads, so the volatile
+            // read already orders the ticket and event reads after it. arm64
+            // and other weakly ordered CPUs need the fence.
+            #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+            std::sync::atomic::fence(std::sync::atomic::Ordering::Acquire);
 
             match flags {
                 x if x == slot_flags::READY => {
@@ -350,8 +518,15 @@ impl SequencerConsumer {
                         if !self.validator.check(ticket) {
                             self.stats.ordering_violations += 1;
                         }
+                        let cpu = unsafe { core::ptr::read_volatile(&(*slot_ptr).cpu) };
+                        self.stats
+                            .per_cpu
+                            .entry(cpu)
+                            .or_default()
+                            .observe(event.seq);
 
-                        events.push(event);
+                        sink(ticket, event);
+                        read += 1;
 
                         // PERFORMANCE OPTIMIZATION:
                         // We DO NOT write EMPTY back to the slot!
@@ -372,6 +547,7 @@ impl SequencerConsumer {
                             self.cursor, ticket
                         );
                         self.stats.ordering_violations += 1;
+                        self.stats.events_lost += ticket - self.cursor;
                         self.cursor = ticket; // Resync to current position
                     }
                 }
@@ -407,6 +583,8 @@ impl SequencerConsumer {
                             // READ-ONLY: We don't write ABANDONED, just advance cursor locally
                             // Since we're single-consumer, this is safe.
                             self.stats.events_reaped += 1;
+                            let cpu = unsafe { core::ptr::read_volatile(&(*slot_ptr).cpu) };
+                            self.stats.per_cpu.entry(cpu).or_default().reaped += 1;
                             self.cursor += 1;
                         } else {
                             // Producer still working, wait
@@ -449,11 +627,12 @@ impl SequencerConsumer {
             }
         }
 
-        if events.len() > self.stats.max_batch_size {
-            self.stats.max_batch_size = events.len();
+        if read > self.stats.max_batch_size {
+            self.stats.max_batch_size = read;
         }
+        self.stats.cursor = self.cursor;
 
-        events
+        read
     }
 
     /// Drain all available events (up to a reasonable limit).
@@ -477,46 +656,6 @@ impl SequencerConsumer {
     }
 }
 
-/// Enable the sequencer in the eBPF program.
-pub fn enable_sequencer(ebpf: &mut aya::Ebpf) -> anyhow::Result<()> {
-    use anyhow::Context;
-    use aya::maps::Array;
-
-    let mut enabled_map: Array<_, u32> = Array::try_from(
-        ebpf.map_mut("SEQUENCER_ENABLED")
-            .context("Failed to find SEQUENCER_ENABLED map")?,
-    )
-    .context("Failed to create Array from SEQUENCER_ENABLED map")?;
-
-    enabled_map
-        .set(0, 1, 0)
-        .context("Failed to set SEQUENCER_ENABLED to 1")?;
-
-    info!("Sequencer ENABLED - eBPF will now use the lock-free ring buffer");
-
-    Ok(())
-}
-
-/// Disable the sequencer, reverting to legacy perf buffer.
-pub fn disable_sequencer(ebpf: &mut aya::Ebpf) -> anyhow::Result<()> {
-    use anyhow::Context;
-    use aya::maps::Array;
-
-    let mut enabled_map: Array<_, u32> = Array::try_from(
-        ebpf.map_mut("SEQUENCER_ENABLED")
-            .context("Failed to find SEQUENCER_ENABLED map")?,
-    )
-    .context("Failed to create Array from SEQUENCER_ENABLED map")?;
-
-    enabled_map
-        .set(0, 0, 0)
-        .context("Failed to set SEQUENCER_ENABLED to 0")?;
-
-    info!("Sequencer DISABLED - eBPF will use legacy perf buffer");
-
-    Ok(())
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -538,6 +677,20 @@ mod tests {
         assert!(validator.check(7));
     }
 
+    #[test]
+    fn cpu_sequence_counts_gaps_and_inversions() {
+        let mut cpu = CpuSequence::default();
+        for seq in [1, 2, 5, 4, 6] {
+            cpu.observe(seq);
+        }
+        // Unstamped events from an older object are counted only
+        cpu.observe(0);
+        assert_eq!(cpu.events, 6);
+        assert_eq!(cpu.gaps, 1, "3 never arrived, 4 did late");
+        assert_eq!(cpu.inversions, 1);
+        assert_eq!(cpu.last_seq, 6);
+    }
+
     #[test]
     fn test_sequenced_slot_alignment() {
         use std::mem::{align_of, size_of};
@@ -547,6 +700,17 @@ mod tests {
         assert_eq!(align_of::<SequencedSlot>(), 128);
     }
 
+    #[test]
+    fn idle_backoff_doubles_up_to_the_cap() {
+        let mut backoff = IdleBackoff::default();
+        let delays: Vec<_> = (0..10).map(|_| backoff.next_delay()).collect();
+        assert_eq!(delays[0], IDLE_MIN);
+        assert_eq!(delays[1], IDLE_MIN * 2);
+        assert_eq!(delays[9], IDLE_MAX);
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), IDLE_MIN);
+    }
+
     #[test]
     fn test_stats_default() {
         let stats = SequencerStats::default();
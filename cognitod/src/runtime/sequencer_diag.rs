@@ -0,0 +1,189 @@
+//! Per-CPU ordering diagnostics for the sequencer.
+//!
+//! Producers record their CPU in each slot and stamp each event with the
+//! CPU's `SEQUENCER_CPU_SEQ` number, bumped with every ticket it reserves.
+//! Consumers check each CPU's stream for gaps and inversions (see
+//! [`CpuSequence`]). Every few seconds the sampler reads the kernel counters,
+//! combines them with the stats of registered consumers and publishes the
+//! result for `/debug/sequencer`: per-CPU production rates, reap counts and
+//! how far the consumers' cursors trail the reserved tickets.
+
+use log::debug;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::bpf_control::BpfControl;
+use super::sequencer::{CpuSequence, SequencerStats};
+use crate::metrics::numa::NumaReport;
+use crate::metrics::{CpuSequenceReport, Metrics, SequencerReport};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Consumers reporting into `/debug/sequencer` and the previous kernel
+/// sample the rates are measured against.
+#[derive(Default)]
+pub struct SequencerDiagnostics {
+    consumers: Mutex<Vec<Arc<Mutex<SequencerStats>>>>,
+    previous: Mutex<Option<(Instant, Vec<u64>)>>,
+    numa: Mutex<Option<NumaReport>>,
+}
+
+impl SequencerDiagnostics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Include a consumer whose polling thread publishes its stats to
+    /// `stats`, as the daemon's sequencer listener and `PartitionedSequencer`
+    /// do.
+    pub fn register(&self, stats: Arc<Mutex<SequencerStats>>) {
+        self.consumers.lock().unwrap().push(stats);
+    }
+
+    /// Latest page placement of the rings, refreshed by the listener.
+    pub fn set_numa(&self, report: NumaReport) {
+        *self.numa.lock().unwrap() = Some(report);
+    }
+
+    /// Combine `tickets` (per CPU, as read at `now`) with the consumers'
+    /// stats.
+    pub fn report(&self, mode: Option<u32>, tickets: Vec<u64>, now: Instant) -> SequencerReport {
+        let consumers: Vec<SequencerStats> = self
+            .consumers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|stats| stats.lock().unwrap().clone())
+            .collect();
+        let previous = self
+            .previous
+            .lock()
+            .unwrap()
+            .replace((now, tickets.clone()));
+        let interval_secs = previous
+            .as_ref()
+            .map_or(0.0, |(at, _)| now.duration_since(*at).as_secs_f64());
+
+        let mut per_cpu: BTreeMap<u16, CpuSequence> = BTreeMap::new();
+        for stats in &consumers {
+            for (cpu, seq) in &stats.per_cpu {
+                let merged = per_cpu.entry(*cpu).or_default();
+                merged.events += seq.events;
+                merged.reaped += seq.reaped;
+                merged.gaps += seq.gaps;
+                merged.inversions += seq.inversions;
+            }
+        }
+        for (cpu, count) in tickets.iter().enumerate() {
+            if *count > 0 {
+                per_cpu.entry(cpu as u16).or_default();
+            }
+        }
+
+        let cpus = per_cpu
+            .into_iter()
+            .map(|(cpu, seq)| {
+                let count = tickets.get(cpu as usize).copied().unwrap_or(0);
+                let before = previous
+                    .as_ref()
+                    .and_then(|(_, t)| t.get(cpu as usize).copied())
+                    .unwrap_or(count);
+                CpuSequenceReport {
+                    cpu,
+                    tickets: count,
+                    tickets_per_sec: if interval_secs > 0.0 {
+                        count.saturating_sub(before) as f64 / interval_secs
+                    } else {
+                        0.0
+                    },
+                    events: seq.events,
+                    reaped: seq.reaped,
+                    gaps: seq.gaps,
+                    inversions: seq.inversions,
+                }
+            })
+            .collect();
+
+        let total: u64 = tickets.iter().sum();
+        let cursor = (!consumers.is_empty()).then(|| consumers.iter().map(|s| s.cursor).sum());
+        SequencerReport {
+            mode,
+            tickets: total,
+            cursor,
+            lag: cursor.map(|c: u64| total.saturating_sub(c)),
+            consumers: consumers.len(),
+            events_reaped: consumers.iter().map(|s| s.events_reaped).sum(),
+            ordering_violations: consumers.iter().map(|s| s.ordering_violations).sum(),
+            interval_secs,
+            cpus,
+            numa: self.numa.lock().unwrap().clone(),
+        }
+    }
+}
+
+/// Start publishing the sequencer report every few seconds.
+pub fn spawn(control: Arc<BpfControl>, metrics: Arc<Metrics>, diag: Arc<SequencerDiagnostics>) {
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(SAMPLE_INTERVAL);
+        loop {
+            tick.tick().await;
+            let mode = control.sequencer_mode().await.ok();
+            let tickets = match control.sequencer_cpu_tickets().await {
+                Ok(tickets) => tickets,
+                Err(e) => {
+                    debug!("[sequencer] per-CPU tickets unavailable: {e:#}");
+                    Vec::new()
+                }
+            };
+            metrics.set_sequencer_report(diag.report(mode, tickets, Instant::now()));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_rates_lag_and_per_cpu_continuity() {
+        let diag = SequencerDiagnostics::new();
+        let start = Instant::now();
+        let first = diag.report(Some(1), vec![100, 0, 50], start);
+        assert_eq!(first.cursor, None);
+        assert_eq!(first.interval_secs, 0.0);
+        assert_eq!(first.cpus.iter().map(|c| c.cpu).collect::<Vec<_>>(), [0, 2]);
+
+        let mut stats = SequencerStats {
+            cursor: 180,
+            events_reaped: 1,
+            ..SequencerStats::default()
+        };
+        let cpu1 = stats.per_cpu.entry(1).or_default();
+        for seq in [1, 2, 4] {
+            cpu1.observe(seq);
+        }
+        cpu1.reaped = 1;
+        diag.register(Arc::new(Mutex::new(stats)));
+
+        let report = diag.report(Some(1), vec![150, 4, 50], start + Duration::from_secs(5));
+        assert_eq!(report.tickets, 204);
+        assert_eq!((report.cursor, report.lag), (Some(180), Some(24)));
+        assert_eq!((report.consumers, report.events_reaped), (1, 1));
+        assert_eq!(report.interval_secs, 5.0);
+        assert_eq!(report.cpus[0].tickets_per_sec, 10.0);
+        assert_eq!(
+            report.cpus[1],
+            CpuSequenceReport {
+                cpu: 1,
+                tickets: 4,
+                tickets_per_sec: 0.8,
+                events: 3,
+                reaped: 1,
+                gaps: 1,
+                inversions: 0,
+            }
+        );
+        assert_eq!(report.cpus[2].tickets_per_sec, 0.0);
+    }
+}
@@ -0,0 +1,188 @@
+//! Staleness alert for the event pipeline.
+//!
+//! Every `window_secs` the watchdog diffs the latency histograms against the
+//! previous read and estimates the window's p99 event age at the last stage
+//! that saw at least `min_samples` events: `deliver` while SSE clients are
+//! connected, otherwise `dispatch`. `event_staleness` fires once when that
+//! p99 exceeds `p99_bound_ms` and re-arms when a window is back under it.
+
+use log::{info, warn};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::alerts::{Alert, AlertSender, Severity};
+use crate::config::StalenessConfig;
+use crate::metrics::Metrics;
+use crate::metrics::latency::{self, LatencyTracker, Stage};
+
+pub const RULE_NAME: &str = "event_staleness";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Breach {
+    stage: Stage,
+    p99_secs: f64,
+    samples: u64,
+}
+
+struct StalenessCheck {
+    bound_secs: f64,
+    min_samples: u64,
+    previous: Vec<Vec<u64>>,
+    raised: bool,
+}
+
+impl StalenessCheck {
+    fn new(config: &StalenessConfig) -> Self {
+        Self {
+            bound_secs: config.p99_bound_ms as f64 / 1000.0,
+            min_samples: config.min_samples.max(1),
+            previous: vec![Vec::new(); Stage::ALL.len()],
+            raised: false,
+        }
+    }
+
+    /// Judge the window since the last call. Returns the breach when the
+    /// alert should fire.
+    fn check(&mut self, tracker: &LatencyTracker) -> Option<Breach> {
+        let mut judged = None;
+        for stage in Stage::ALL {
+            let counts = tracker.counts(stage);
+            let previous = &self.previous[stage as usize];
+            let window: Vec<u64> = counts
+                .iter()
+                .enumerate()
+                .map(|(i, n)| n.saturating_sub(previous.get(i).copied().unwrap_or(0)))
+                .collect();
+            let samples = window.iter().sum::<u64>();
+            if samples >= self.min_samples
+                && let Some(p99_secs) = latency::quantile(&window, 0.99)
+            {
+                judged = Some(Breach {
+                    stage,
+                    p99_secs,
+                    samples,
+                });
+            }
+            self.previous[stage as usize] = counts;
+        }
+
+        let judged = judged?;
+        if judged.p99_secs <= self.bound_secs {
+            if self.raised {
+                info!(
+                    "[staleness] p99 event age at {} back under {:.0}ms",
+                    judged.stage.name(),
+                    self.bound_secs * 1000.0
+                );
+            }
+            self.raised = false;
+            return None;
+        }
+        if self.raised {
+            return None;
+        }
+        self.raised = true;
+        Some(judged)
+    }
+}
+
+fn format_age(secs: f64) -> String {
+    if secs.is_infinite() {
+        format!(
+            ">{}s",
+            latency::BUCKETS_SECS[latency::BUCKETS_SECS.len() - 1]
+        )
+    } else if secs < 0.001 {
+        format!("{:.0}us", secs * 1e6)
+    } else if secs < 1.0 {
+        format!("{:.0}ms", secs * 1000.0)
+    } else {
+        format!("{secs}s")
+    }
+}
+
+/// Start the periodic staleness check.
+pub fn spawn(metrics: Arc<Metrics>, alerts: Option<AlertSender>, config: &StalenessConfig) {
+    let mut check = StalenessCheck::new(config);
+    let window = Duration::from_secs(config.window_secs.max(1));
+    let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown".into());
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(window);
+        loop {
+            tick.tick().await;
+            let Some(breach) = check.check(metrics.latency()) else {
+                continue;
+            };
+            let message = format!(
+                "p99 age of events at the {} stage reached {} over the last {}s \
+                 ({} events), above the {} bound; served data is stale",
+                breach.stage.name(),
+                format_age(breach.p99_secs),
+                window.as_secs(),
+                breach.samples,
+                format_age(check.bound_secs)
+            );
+            warn!("[staleness] {message}");
+            if let Some(tx) = &alerts {
+                tx.send(Alert {
+                    rule: RULE_NAME.to_string(),
+                    severity: Severity::Medium,
+                    message,
+                    host: host.clone(),
+                    detection: "threshold",
+                    suppressed_by: None,
+                    lineage: Vec::new(),
+                    children: Vec::new(),
+                    pod: None,
+                    evidence: None,
+                    annotations: None,
+                });
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fires_once_on_the_last_measured_stage_and_rearms() {
+        let config = StalenessConfig {
+            p99_bound_ms: 100,
+            min_samples: 10,
+            ..StalenessConfig::default()
+        };
+        let mut check = StalenessCheck::new(&config);
+        let tracker = LatencyTracker::new();
+
+        // Fast dispatch, slow delivery: judged on delivery
+        for _ in 0..20 {
+            tracker.observe_ns(Stage::Dispatch, 1_000_000);
+            tracker.observe_ns(Stage::Deliver, 2_000_000_000);
+        }
+        let breach = check.check(&tracker).expect("delivery is stale");
+        assert_eq!(breach.stage, Stage::Deliver);
+        assert_eq!(breach.p99_secs, 5.0);
+        assert_eq!(breach.samples, 20);
+
+        // Still stale: no repeat
+        for _ in 0..20 {
+            tracker.observe_ns(Stage::Deliver, 2_000_000_000);
+        }
+        assert_eq!(check.check(&tracker), None);
+
+        // Too few deliveries to judge; dispatch is fast, which re-arms
+        for _ in 0..20 {
+            tracker.observe_ns(Stage::Dispatch, 1_000_000);
+        }
+        tracker.observe_ns(Stage::Deliver, 2_000_000_000);
+        assert_eq!(check.check(&tracker), None);
+        assert!(!check.raised);
+
+        for _ in 0..20 {
+            tracker.observe_ns(Stage::Deliver, 2_000_000_000);
+        }
+        assert!(check.check(&tracker).is_some());
+    }
+}
@@ -3,13 +3,28 @@ use crate::config::OfflineGuard;
 use crate::context::ContextStore;
 use crate::handler::HandlerList;
 use crate::metrics::Metrics;
+use crate::metrics::cursor::{Decision, Reconciliation};
+use crate::metrics::latency::{Stage, monotonic_ns};
+use crate::runtime::backlog::{Backlog, BacklogConfig};
+use crate::runtime::cursor::Checkpointer;
 use crate::runtime::lineage::LineageCache;
+use crate::runtime::numa::{self, NumaReport, Partition, PartitionedSequencer};
+use crate::runtime::placement::ThreadPlacement;
+use crate::runtime::sequencer::{IdleBackoff, SequencerConsumer, SequencerStats};
+use crate::runtime::sequencer_diag::SequencerDiagnostics;
 use crate::{ProcessEvent, ProcessEventWire};
 use aya::maps::perf::PerfEventArrayBuffer;
 use aya::maps::{MapData, ring_buf::RingBuf};
 use bytes::BytesMut;
-use linnix_ai_ebpf_common::EventType;
-use std::{io, mem, ptr, sync::Arc, thread, time::Duration};
+use linnix_ai_ebpf_common::{EventType, SEQUENCER_RING_SIZE};
+use std::{
+    io, mem,
+    path::Path,
+    ptr,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
 use tokio::io::unix::AsyncFd;
 use tokio::runtime::Handle;
 
@@ -30,6 +45,11 @@ fn event_label(kind: u32) -> &'static str {
         x if x == EventType::Syscall as u32 => "Syscall",
         x if x == EventType::BlockIo as u32 => "BlockIo",
         x if x == EventType::PageFault as u32 => "PageFault",
+        x if x == EventType::Listen as u32 => "Listen",
+        x if x == EventType::Connection as u32 => "Connection",
+        x if x == EventType::FdPressure as u32 => "FdPressure",
+        x if x == EventType::Signal as u32 => "Signal",
+        x if x == EventType::OomKill as u32 => "OomKill",
         _ => "Unknown",
     }
 }
@@ -50,10 +70,14 @@ pub fn start_listener(
         loop {
             if let Some(data) = ringbuf.next() {
                 if let Some(event) = parse_event(data.as_ref()) {
+                    metrics.latency().observe(Stage::Receive, event.ts_ns);
                     let metrics_clone = metrics.clone();
                     if !metrics_clone.record_event(rate_cap, event.event_type) {
                         continue;
                     }
+                    metrics_clone
+                        .ingest()
+                        .record(event.event_type, data.as_ref().len());
                     let comm = std::str::from_utf8(&event.comm)
                         .unwrap_or("invalid")
                         .trim_end_matches('\0')
@@ -61,9 +85,13 @@ pub fn start_listener(
 
                     // Process event asynchronously
                     let context_clone = context.clone();
-                    let event_for_llm = event.clone();
+                    let mut event_for_llm = event.clone();
                     let handlers_clone = handlers.clone();
                     rt_handle.spawn(async move {
+                        let started = Instant::now();
+                        let event_type = event_for_llm.event_type;
+                        crate::script::enrich(&mut event_for_llm, &comm);
+                        crate::exec_path::enrich(&mut event_for_llm);
                         println!(
                             "[event] type={:?} pid={} ppid={} uid={} gid={} comm={}",
                             event_label(event_for_llm.event_type),
@@ -73,8 +101,14 @@ pub fn start_listener(
                             event_for_llm.gid,
                             comm
                         );
+                        metrics_clone
+                            .latency()
+                            .observe(Stage::Dispatch, event_for_llm.ts_ns);
                         handlers_clone.on_event(&event_for_llm).await;
                         context_clone.add(event_for_llm);
+                        metrics_clone
+                            .ingest()
+                            .observe_processing(event_type, started.elapsed());
                     });
                 } else {
                     metrics.inc_rb_overflow();
@@ -95,18 +129,25 @@ pub fn start_perf_listener(
     handlers: Arc<HandlerList>,
     _offline: Arc<OfflineGuard>,
     rate_cap: u64,
+    consumer: Option<Handle>,
 ) {
     println!("[cognitod] Starting listener for BPF perf buffers...");
 
     let lineage_cache: Arc<LineageCache> = Arc::new(LineageCache::default());
+    // Buffers are drained on the consumer runtime when one is configured;
+    // per-event processing stays on the workers.
+    let workers = Handle::current();
+    let consumer = consumer.unwrap_or_else(|| workers.clone());
 
-    for buffer in buffers {
+    for (stream, buffer) in buffers.into_iter().enumerate() {
         let context = Arc::clone(&context);
         let metrics = Arc::clone(&metrics);
         let handlers = Arc::clone(&handlers);
         let lineage = Arc::clone(&lineage_cache);
+        let workers = workers.clone();
+        let loss = metrics.loss().stream(stream);
 
-        tokio::spawn(async move {
+        consumer.spawn(async move {
             let mut async_buffer = match AsyncFd::new(buffer) {
                 Ok(fd) => fd,
                 Err(e) => {
@@ -154,6 +195,7 @@ pub fn start_perf_listener(
 
                 if events.lost > 0 {
                     metrics.inc_rb_overflow();
+                    metrics.loss().record_kernel_lost(events.lost as u64);
                 }
 
                 for buf in scratch.iter_mut().take(events.read) {
@@ -164,71 +206,431 @@ pub fn start_perf_listener(
 
                     let event_wire: ProcessEventWire =
                         unsafe { ptr::read_unaligned(buf.as_ptr() as *const ProcessEventWire) };
+                    let wire_bytes = buf.len();
                     buf.clear();
+                    metrics.latency().observe(Stage::Receive, event_wire.ts_ns);
+
+                    let gap = loss.observe(event_wire.seq, event_wire.event_type);
+                    if gap > 0 {
+                        log::debug!(
+                            "[perf] stream {stream} missed {gap} events before seq {}",
+                            event_wire.seq
+                        );
+                    }
 
                     if !metrics.record_event(rate_cap, event_wire.event_type) {
                         continue;
                     }
+                    metrics.ingest().record(event_wire.event_type, wire_bytes);
 
-                    let mut event_for_llm = ProcessEvent::new(event_wire)
-                        .with_hostname(HOSTNAME.clone());
-                    let comm = std::str::from_utf8(&event_for_llm.comm)
-                        .unwrap_or("invalid")
-                        .trim_end_matches('\0')
-                        .to_string();
+                    let event = ProcessEvent::new(event_wire).with_hostname(HOSTNAME.clone());
+                    dispatch(&workers, event, &context, &metrics, &handlers, &lineage);
+                }
+            }
+        });
+    }
+}
+
+/// Events queued between the sequencer consumer thread and the task that
+/// processes them; a full queue holds the consumer back.
+const ORDERED_QUEUE: usize = 8192;
+
+/// How often the merge thread refreshes the partitions' page placement.
+const NUMA_REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Rings read by [`start_sequencer_listener`], with their maps, which stay
+/// open while the rings are read.
+pub enum SequencerRings {
+    /// `SEQUENCER_RING`, written by every CPU
+    Shared {
+        consumer: SequencerConsumer,
+        map: MapData,
+    },
+    /// One ring per NUMA node (`[sequencer] numa`), each drained by a
+    /// consumer pinned to the node's CPUs
+    Partitioned {
+        partitions: Vec<Partition>,
+        maps: Vec<MapData>,
+    },
+}
+
+impl SequencerRings {
+    /// `SEQUENCER_ENABLED` value that routes producers to these rings.
+    pub fn mode(&self) -> u32 {
+        match self {
+            Self::Shared { .. } => 1,
+            Self::Partitioned { partitions, .. } => partitions.len() as u32,
+        }
+    }
+}
+
+/// Drain the sequencer rings on dedicated threads into the same pipeline as
+/// the perf listener. A single task on the workers takes the events from a
+/// bounded queue and processes them one after the other, so handlers see
+/// them in ticket order, or for partitioned rings in the timestamp order
+/// they are merged in. The consumers' stats are registered with `diag` for
+/// `/debug/sequencer`, along with the rings' page placement on NUMA hosts.
+///
+/// For the shared ring, `checkpoint` is told the next ticket to read and
+/// `resume` is the cursor restored at startup. When the ring still holds
+/// the tickets from there to the kernel's head, live events are read from
+/// the head and that backlog behind them, per `backlog_config`; each poll's
+/// backlogged events are processed ahead of its live ones.
+#[allow(clippy::too_many_arguments)]
+pub fn start_sequencer_listener(
+    rings: SequencerRings,
+    context: Arc<ContextStore>,
+    metrics: Arc<Metrics>,
+    handlers: Arc<HandlerList>,
+    _offline: Arc<OfflineGuard>,
+    rate_cap: u64,
+    batch_size: usize,
+    placement: Option<ThreadPlacement>,
+    diag: Arc<SequencerDiagnostics>,
+    checkpoint: Option<Arc<Checkpointer>>,
+    resume: Option<Reconciliation>,
+    backlog_config: BacklogConfig,
+) -> io::Result<()> {
+    println!("[cognitod] Starting listener for the BPF sequencer ring...");
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<ProcessEvent>(ORDERED_QUEUE);
+    let ingest = Ingest {
+        tx,
+        metrics: Arc::clone(&metrics),
+        rate_cap,
+        lost: 0,
+    };
+    let batch_size = batch_size.max(1);
+    match rings {
+        SequencerRings::Shared { consumer, map } => start_shared_consumer(
+            consumer,
+            map,
+            ingest,
+            batch_size,
+            placement,
+            &diag,
+            checkpoint,
+            resume,
+            backlog_config,
+        )?,
+        SequencerRings::Partitioned { partitions, maps } => {
+            start_partitioned_consumers(partitions, maps, ingest, batch_size, placement, diag)?
+        }
+    }
+
+    let lineage = Arc::new(LineageCache::default());
+    Handle::current().spawn(async move {
+        while let Some(event) = rx.recv().await {
+            process(event, &context, &metrics, &handlers, &lineage).await;
+        }
+    });
+    Ok(())
+}
+
+/// Hands events read from the sequencer to the ordered task.
+struct Ingest {
+    tx: tokio::sync::mpsc::Sender<ProcessEvent>,
+    metrics: Arc<Metrics>,
+    rate_cap: u64,
+    /// Reaped and skipped tickets reported so far
+    lost: u64,
+}
+
+impl Ingest {
+    /// Report reaped and skipped tickets, the sequencer's kernel-side loss,
+    /// beyond those reported before; `total` is the consumers' count.
+    fn record_lost(&mut self, total: u64) {
+        if total > self.lost {
+            self.metrics.inc_rb_overflow();
+            self.metrics.loss().record_kernel_lost(total - self.lost);
+            self.lost = total;
+        }
+    }
 
-                    log::debug!(
-                        "[perf] received event type={:?} pid={} ppid={} comm={}",
-                        event_label(event_for_llm.event_type),
-                        event_for_llm.pid,
-                        event_for_llm.ppid,
-                        comm
+    /// Queue `batch` in order, each event with its ticket when exporters
+    /// track delivery by it; false once the ordered task is gone.
+    fn forward(&self, batch: Vec<(Option<u64>, ProcessEventWire)>) -> bool {
+        let wire_bytes = mem::size_of::<ProcessEventWire>();
+        for (ticket, event_wire) in batch {
+            self.metrics
+                .latency()
+                .observe(Stage::Receive, event_wire.ts_ns);
+            if !self
+                .metrics
+                .record_event(self.rate_cap, event_wire.event_type)
+            {
+                continue;
+            }
+            self.metrics
+                .ingest()
+                .record(event_wire.event_type, wire_bytes);
+
+            let event = ProcessEvent::new(event_wire)
+                .with_hostname(HOSTNAME.clone())
+                .with_ticket(ticket);
+            if self.tx.blocking_send(event).is_err() {
+                log::warn!("[sequencer] event task stopped; consumer exiting");
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn start_shared_consumer(
+    mut consumer: SequencerConsumer,
+    ring: MapData,
+    mut ingest: Ingest,
+    batch_size: usize,
+    placement: Option<ThreadPlacement>,
+    diag: &SequencerDiagnostics,
+    checkpoint: Option<Arc<Checkpointer>>,
+    resume: Option<Reconciliation>,
+    backlog_config: BacklogConfig,
+) -> io::Result<()> {
+    let mut backlog = resume.and_then(|restored| {
+        let kept = matches!(restored.decision, Decision::Resume | Decision::Overrun);
+        let backlog = kept
+            .then(|| {
+                Backlog::new(
+                    restored.cursor,
+                    restored.kernel_head,
+                    backlog_config,
+                    Arc::clone(&ingest.metrics),
+                )
+            })
+            .flatten();
+        consumer.set_cursor(match &backlog {
+            Some(_) => restored.kernel_head,
+            None => restored.cursor,
+        });
+        backlog
+    });
+    if let Some(report) = shared_ring_report(&consumer) {
+        diag.set_numa(report);
+    }
+    let stats = Arc::new(Mutex::new(SequencerStats::default()));
+    diag.register(Arc::clone(&stats));
+
+    thread::Builder::new()
+        .name("seq-consumer".into())
+        .spawn(move || {
+            let _ring = ring;
+            if let Some(placement) = placement {
+                placement.apply();
+            }
+            let mut idle = IdleBackoff::default();
+            loop {
+                let mut batch: Vec<_> = consumer
+                    .poll_batch_tickets(batch_size)
+                    .into_iter()
+                    .map(|(ticket, event)| (Some(ticket), event))
+                    .collect();
+                let mut position = consumer.cursor();
+                if let Some(pending) = &mut backlog {
+                    let oldest = position.saturating_sub(SEQUENCER_RING_SIZE as u64);
+                    let now = monotonic_ns().unwrap_or(0);
+                    let mut behind = Vec::new();
+                    pending.poll(
+                        oldest,
+                        now,
+                        |ticket| consumer.read_behind(ticket),
+                        |ticket, event| behind.push((Some(ticket), event)),
                     );
+                    position = pending.checkpoint(position);
+                    behind.append(&mut batch);
+                    batch = behind;
+                    if pending.done() {
+                        backlog = None;
+                    }
+                }
+                {
+                    let current = consumer.stats();
+                    ingest.record_lost(current.events_lost + current.events_reaped);
+                    *stats.lock().unwrap() = current.clone();
+                }
+                if let Some(checkpoint) = &checkpoint {
+                    checkpoint.set_cursor(position);
+                }
+                if batch.is_empty() {
+                    if backlog.is_none() {
+                        idle.wait();
+                    }
+                    continue;
+                }
+                idle.reset();
+                if !ingest.forward(batch) {
+                    return;
+                }
+            }
+        })?;
+    Ok(())
+}
 
-                    let metrics_for_llm = Arc::clone(&metrics);
-                    let handlers_clone = Arc::clone(&handlers);
-                    let context_clone = Arc::clone(&context);
-                    let lineage_clone = Arc::clone(&lineage);
-
-                    tokio::spawn(async move {
-                        if event_for_llm.event_type == EventType::Fork as u32 {
-                            lineage_clone
-                                .record_fork(event_for_llm.pid, event_for_llm.ppid)
-                                .await;
-                        } else if event_for_llm.ppid == 0 {
-                            match lineage_clone.lookup(event_for_llm.pid).await {
-                                Some(ppid) => {
-                                    event_for_llm.ppid = ppid;
-                                    metrics_for_llm.inc_lineage_hit();
-                                }
-                                None => {
-                                    metrics_for_llm.inc_lineage_miss();
-                                }
-                            }
-                        }
+/// Start one pinned consumer per partition and a thread merging their
+/// streams by timestamp.
+fn start_partitioned_consumers(
+    partitions: Vec<Partition>,
+    maps: Vec<MapData>,
+    mut ingest: Ingest,
+    batch_size: usize,
+    placement: Option<ThreadPlacement>,
+    diag: Arc<SequencerDiagnostics>,
+) -> io::Result<()> {
+    let mut merged = PartitionedSequencer::start(partitions, batch_size);
+    let stats = merged.consumer_stats();
+    for partition in &stats {
+        diag.register(Arc::clone(partition));
+    }
+    diag.set_numa(merged.report());
 
-                        println!(
-                            "[event] type={:?} pid={} ppid={} uid={} gid={} comm={}",
-                            event_label(event_for_llm.event_type),
-                            event_for_llm.pid,
-                            event_for_llm.ppid,
-                            event_for_llm.uid,
-                            event_for_llm.gid,
-                            comm
-                        );
-                        
-                        // Track container activity for warmth keeper (Pro feature)
-                        if let Some(keeper) = crate::runtime::WARMTH_KEEPER.get() {
-                            keeper.record_activity(&comm);
-                        }
-                        
-                        handlers_clone.on_event(&event_for_llm).await;
-                        context_clone.add(event_for_llm);
-                    });
+    thread::Builder::new()
+        .name("seq-merge".into())
+        .spawn(move || {
+            let _maps = maps;
+            if let Some(placement) = placement {
+                placement.apply();
+            }
+            let mut idle = IdleBackoff::default();
+            let mut reported = Instant::now();
+            loop {
+                let batch = merged.poll(batch_size);
+                let lost = stats
+                    .iter()
+                    .map(|partition| {
+                        let partition = partition.lock().unwrap();
+                        partition.events_lost + partition.events_reaped
+                    })
+                    .sum();
+                ingest.record_lost(lost);
+                if reported.elapsed() >= NUMA_REPORT_INTERVAL {
+                    diag.set_numa(merged.report());
+                    reported = Instant::now();
+                }
+                if batch.is_empty() {
+                    idle.wait();
+                    continue;
+                }
+                idle.reset();
+                // Partition tickets do not order the merged stream, so
+                // exporters do not record delivery marks for it
+                if !ingest.forward(batch.into_iter().map(|event| (None, event)).collect()) {
+                    return;
                 }
             }
-        });
+        })?;
+    Ok(())
+}
+
+/// Page placement of the shared ring on a NUMA host, to compare with
+/// `[sequencer] numa`; `None` on a single node.
+fn shared_ring_report(consumer: &SequencerConsumer) -> Option<NumaReport> {
+    let nodes = numa::online_nodes(Path::new(numa::NODE_ROOT)).ok()?;
+    if nodes.len() < 2 {
+        return None;
     }
+    let pages = match consumer.page_placement() {
+        Ok(pages) => pages,
+        Err(e) => {
+            log::warn!("[numa] cannot query ring page placement: {e}");
+            return None;
+        }
+    };
+    Some(NumaReport {
+        nodes: nodes.len(),
+        partitions: Vec::new(),
+        merged: 0,
+        merge_inversions: 0,
+        pending: 0,
+        remote_page_share: numa::shared_ring_remote_share(&pages, &nodes),
+    })
+}
+
+/// Hand a received event to the handlers and the context store on the
+/// workers, after lineage and script enrichment.
+fn dispatch(
+    workers: &Handle,
+    event: ProcessEvent,
+    context: &Arc<ContextStore>,
+    metrics: &Arc<Metrics>,
+    handlers: &Arc<HandlerList>,
+    lineage: &Arc<LineageCache>,
+) {
+    let context = Arc::clone(context);
+    let metrics = Arc::clone(metrics);
+    let handlers = Arc::clone(handlers);
+    let lineage = Arc::clone(lineage);
+    workers.spawn(async move {
+        process(event, &context, &metrics, &handlers, &lineage).await;
+    });
+}
+
+/// Enrich one event and pass it to the handlers and the context store.
+async fn process(
+    mut event_for_llm: ProcessEvent,
+    context: &ContextStore,
+    metrics: &Metrics,
+    handlers: &HandlerList,
+    lineage: &LineageCache,
+) {
+    let comm = std::str::from_utf8(&event_for_llm.comm)
+        .unwrap_or("invalid")
+        .trim_end_matches('\0')
+        .to_string();
+
+    log::debug!(
+        "[event] received type={:?} pid={} ppid={} comm={}",
+        event_label(event_for_llm.event_type),
+        event_for_llm.pid,
+        event_for_llm.ppid,
+        comm
+    );
+
+    let started = Instant::now();
+    let event_type = event_for_llm.event_type;
+    if event_for_llm.event_type == EventType::Fork as u32 {
+        lineage
+            .record_fork(event_for_llm.pid, event_for_llm.ppid)
+            .await;
+    } else if event_for_llm.ppid == 0 {
+        match lineage.lookup(event_for_llm.pid).await {
+            Some(ppid) => {
+                event_for_llm.ppid = ppid;
+                metrics.inc_lineage_hit();
+            }
+            None => {
+                metrics.inc_lineage_miss();
+            }
+        }
+    }
+
+    println!(
+        "[event] type={:?} pid={} ppid={} uid={} gid={} comm={}",
+        event_label(event_for_llm.event_type),
+        event_for_llm.pid,
+        event_for_llm.ppid,
+        event_for_llm.uid,
+        event_for_llm.gid,
+        comm
+    );
+
+    crate::script::enrich(&mut event_for_llm, &comm);
+    crate::exec_path::enrich(&mut event_for_llm);
+
+    // Track container activity for warmth keeper (Pro feature)
+    if let Some(keeper) = crate::runtime::WARMTH_KEEPER.get() {
+        keeper.record_activity(&comm);
+    }
+
+    metrics
+        .latency()
+        .observe(Stage::Dispatch, event_for_llm.ts_ns);
+    handlers.on_event(&event_for_llm).await;
+    context.add(event_for_llm);
+    metrics
+        .ingest()
+        .observe_processing(event_type, started.elapsed());
 }
 
 #[allow(dead_code)]
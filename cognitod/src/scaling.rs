@@ -0,0 +1,599 @@
+//! Per-pod autoscaling signals for KEDA and the HPA
+//!
+//! Every `interval_secs` the sampler groups tracked processes by pod and
+//! computes, per pod:
+//!
+//! - `event_rate` and `fork_rate`: events and forks per second seen since the
+//!   previous pass, counted by the handler and charged to the pod of the pid
+//!   (the parent for forks);
+//! - `cpu_pressure`, `memory_pressure` and `io_pressure`: `some avg10` of the
+//!   pod cgroup's `*.pressure` files, in percent;
+//! - `runqueue_latency`: mean time a thread of the pod waited on a runqueue
+//!   per timeslice since the previous pass, in microseconds, from
+//!   `/proc/<pid>/task/<tid>/schedstat`.
+//!
+//! `/scaling/{signal}` reduces one signal over the selected pods to a single
+//! number for KEDA's `metrics-api` scaler, or lists it per pod as an
+//! `external.metrics.k8s.io` `ExternalMetricValueList` for an external
+//! metrics adapter. Processes without Kubernetes metadata are not counted.
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::ProcessEvent;
+use crate::collectors::cgroup_tree;
+use crate::config::ScalingConfig;
+use crate::context::ContextStore;
+use crate::handler::Handler;
+use crate::types::SystemSnapshot;
+use linnix_ai_ebpf_common::EventType;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Signal {
+    EventRate,
+    ForkRate,
+    CpuPressure,
+    MemoryPressure,
+    IoPressure,
+    RunqueueLatency,
+}
+
+impl Signal {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::EventRate => "event_rate",
+            Self::ForkRate => "fork_rate",
+            Self::CpuPressure => "cpu_pressure",
+            Self::MemoryPressure => "memory_pressure",
+            Self::IoPressure => "io_pressure",
+            Self::RunqueueLatency => "runqueue_latency",
+        }
+    }
+
+    /// Rates add up across replicas; pressure and latency do not.
+    pub fn default_aggregate(&self) -> Aggregate {
+        match self {
+            Self::EventRate | Self::ForkRate => Aggregate::Sum,
+            _ => Aggregate::Max,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Aggregate {
+    Sum,
+    Max,
+    Avg,
+}
+
+/// Signals of one pod at the last pass.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PodSignals {
+    pub namespace: String,
+    pub pod: String,
+    /// `Kind/name` of the controller that owns the pod
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    pub processes: usize,
+    pub event_rate: f64,
+    pub fork_rate: f64,
+    pub cpu_pressure: Option<f32>,
+    pub memory_pressure: Option<f32>,
+    pub io_pressure: Option<f32>,
+    pub runqueue_latency: Option<f64>,
+}
+
+impl PodSignals {
+    pub fn value(&self, signal: Signal) -> Option<f64> {
+        match signal {
+            Signal::EventRate => Some(self.event_rate),
+            Signal::ForkRate => Some(self.fork_rate),
+            Signal::CpuPressure => self.cpu_pressure.map(f64::from),
+            Signal::MemoryPressure => self.memory_pressure.map(f64::from),
+            Signal::IoPressure => self.io_pressure.map(f64::from),
+            Signal::RunqueueLatency => self.runqueue_latency,
+        }
+    }
+}
+
+/// Pods a query applies to; every field is optional.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PodFilter {
+    pub namespace: Option<String>,
+    /// Pod name prefix, e.g. a Deployment name
+    pub pod: Option<String>,
+    /// Owner name, matched with or without its `Kind/`
+    pub owner: Option<String>,
+}
+
+impl PodFilter {
+    fn matches(&self, pod: &PodSignals) -> bool {
+        self.namespace
+            .as_ref()
+            .is_none_or(|ns| *ns == pod.namespace)
+            && self
+                .pod
+                .as_ref()
+                .is_none_or(|p| pod.pod.starts_with(p.as_str()))
+            && self.owner.as_ref().is_none_or(|o| {
+                pod.owner.as_ref().is_some_and(|owner| {
+                    owner == o || owner.split_once('/').is_some_and(|(_, name)| name == o)
+                })
+            })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PodValue {
+    pub namespace: String,
+    pub pod: String,
+    pub value: f64,
+}
+
+/// One signal reduced over the selected pods.
+#[derive(Debug, Clone, Serialize)]
+pub struct SignalValue {
+    pub signal: Signal,
+    pub aggregate: Aggregate,
+    /// 0 when no selected pod has a value
+    pub value: f64,
+    /// Unix seconds of the pass
+    pub timestamp: i64,
+    pub pods: Vec<PodValue>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScalingReport {
+    /// Unix seconds of the pass
+    pub timestamp: i64,
+    pub interval_secs: u64,
+    pub pods: Vec<PodSignals>,
+}
+
+/// Events and forks per pid since the last pass.
+type EventCounts = DashMap<u32, (u64, u64)>;
+
+/// Counts events for the scaling signals.
+pub struct ScalingHandler {
+    counts: Arc<EventCounts>,
+}
+
+#[async_trait]
+impl Handler for ScalingHandler {
+    fn name(&self) -> &'static str {
+        "scaling"
+    }
+
+    async fn on_event(&self, event: &ProcessEvent) {
+        if event.event_type == EventType::Fork as u32 {
+            let mut entry = self.counts.entry(event.ppid).or_insert((0, 0));
+            entry.0 += 1;
+            entry.1 += 1;
+        } else {
+            self.counts.entry(event.pid).or_insert((0, 0)).0 += 1;
+        }
+    }
+
+    async fn on_snapshot(&self, _snapshot: &SystemSnapshot) {}
+}
+
+type PodKey = (String, String);
+
+struct Sampler {
+    root: PathBuf,
+    /// tid -> (runqueue wait ns, timeslices) at the previous pass
+    sched: HashMap<i32, (u64, u64)>,
+    /// pid -> pod at the previous pass, for pids that exited since
+    pods: HashMap<u32, PodKey>,
+    last: Option<Instant>,
+}
+
+impl Sampler {
+    fn sample(&mut self, context: &ContextStore, counts: &EventCounts) -> Vec<PodSignals> {
+        let now = Instant::now();
+        let elapsed = self
+            .last
+            .replace(now)
+            .map(|last| now.duration_since(last).as_secs_f64());
+
+        let mut pods: BTreeMap<PodKey, (PodSignals, Vec<u32>)> = BTreeMap::new();
+        let mut pod_of = HashMap::new();
+        for (pid, (event, meta)) in context.get_live_map().iter() {
+            let Some(meta) = meta else {
+                continue;
+            };
+            let key = (meta.namespace.clone(), meta.pod_name.clone());
+            pod_of.insert(*pid, key.clone());
+            if event.exit_time().is_some() {
+                continue;
+            }
+            let (signals, pids) = pods.entry(key).or_insert_with(|| {
+                let signals = PodSignals {
+                    namespace: meta.namespace.clone(),
+                    pod: meta.pod_name.clone(),
+                    owner: meta.owner_name.as_ref().map(|name| match &meta.owner_kind {
+                        Some(kind) => format!("{kind}/{name}"),
+                        None => name.clone(),
+                    }),
+                    ..PodSignals::default()
+                };
+                (signals, Vec::new())
+            });
+            signals.processes += 1;
+            pids.push(*pid);
+        }
+
+        let pids: Vec<u32> = counts.iter().map(|entry| *entry.key()).collect();
+        for pid in pids {
+            let Some((_, (events, forks))) = counts.remove(&pid) else {
+                continue;
+            };
+            let Some(elapsed) = elapsed.filter(|e| *e > 0.0) else {
+                continue;
+            };
+            let key = pod_of.get(&pid).or_else(|| self.pods.get(&pid));
+            if let Some((signals, _)) = key.and_then(|key| pods.get_mut(key)) {
+                signals.event_rate += events as f64 / elapsed;
+                signals.fork_rate += forks as f64 / elapsed;
+            }
+        }
+
+        let mut sched = HashMap::new();
+        for (signals, pids) in pods.values_mut() {
+            if let Some(dir) = pids
+                .iter()
+                .find_map(|pid| cgroup_tree::process_cgroup_path(*pid))
+                .map(|path| self.root.join(pod_cgroup(&path).trim_start_matches('/')))
+            {
+                let pressure = cgroup_tree::read_pressure(&dir);
+                signals.cpu_pressure = pressure.cpu_some_avg10;
+                signals.memory_pressure = pressure.memory_some_avg10;
+                signals.io_pressure = pressure.io_some_avg10;
+            }
+            let tasks: Vec<(i32, u64, u64)> = pids.iter().flat_map(|pid| tasks(*pid)).collect();
+            signals.runqueue_latency = runqueue_latency(&self.sched, &tasks);
+            sched.extend(
+                tasks
+                    .iter()
+                    .map(|&(tid, wait, slices)| (tid, (wait, slices))),
+            );
+        }
+        self.sched = sched;
+        self.pods = pod_of;
+        pods.into_values().map(|(signals, _)| signals).collect()
+    }
+}
+
+/// (tid, runqueue wait ns, timeslices) of every thread of `pid`.
+fn tasks(pid: u32) -> Vec<(i32, u64, u64)> {
+    let Ok(process) = procfs::process::Process::new(pid as i32) else {
+        return Vec::new();
+    };
+    let Ok(tasks) = process.tasks() else {
+        return Vec::new();
+    };
+    tasks
+        .flatten()
+        .filter_map(|task| {
+            let stat = task.schedstat().ok()?;
+            Some((task.tid, stat.run_delay, stat.pcount))
+        })
+        .collect()
+}
+
+/// Mean runqueue wait per timeslice in microseconds since `prev`. Threads not
+/// in `prev` only set a baseline; `None` when no thread ran in between.
+fn runqueue_latency(prev: &HashMap<i32, (u64, u64)>, tasks: &[(i32, u64, u64)]) -> Option<f64> {
+    let (mut wait, mut slices) = (0u64, 0u64);
+    for (tid, cur_wait, cur_slices) in tasks {
+        if let Some((prev_wait, prev_slices)) = prev.get(tid) {
+            wait += cur_wait.saturating_sub(*prev_wait);
+            slices += cur_slices.saturating_sub(*prev_slices);
+        }
+    }
+    (slices > 0).then(|| wait as f64 / slices as f64 / 1000.0)
+}
+
+/// The pod-level cgroup above a container's: the closest ancestor named after
+/// the pod (`kubepods-burstable-pod<uid>.slice`, `pod<uid>`), else `path`.
+pub fn pod_cgroup(path: &str) -> String {
+    let parts: Vec<&str> = path.split('/').collect();
+    match parts
+        .iter()
+        .rposition(|part| part.starts_with("pod") || part.contains("-pod"))
+    {
+        Some(i) => parts[..=i].join("/"),
+        None => path.to_string(),
+    }
+}
+
+/// `value` as a Kubernetes quantity in milli-units.
+pub fn quantity(value: f64) -> String {
+    format!("{}m", (value * 1000.0).round() as i64)
+}
+
+/// The latest pass, shared with the API.
+pub struct ScalingSignals {
+    report: Mutex<Option<ScalingReport>>,
+    counts: Arc<EventCounts>,
+    interval_secs: u64,
+}
+
+impl ScalingSignals {
+    fn new(interval_secs: u64) -> Self {
+        Self {
+            report: Mutex::new(None),
+            counts: Arc::new(EventCounts::new()),
+            interval_secs,
+        }
+    }
+
+    pub fn spawn(config: &ScalingConfig, context: Arc<ContextStore>) -> Arc<Self> {
+        let signals = Arc::new(Self::new(config.interval_secs.max(1)));
+        let mut sampler = Sampler {
+            root: PathBuf::from(cgroup_tree::cgroup_root()),
+            sched: HashMap::new(),
+            pods: HashMap::new(),
+            last: None,
+        };
+        let shared = Arc::clone(&signals);
+        info!(
+            "[scaling] sampling pod signals every {}s",
+            signals.interval_secs
+        );
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(Duration::from_secs(shared.interval_secs));
+            loop {
+                tick.tick().await;
+                let pods = sampler.sample(&context, &shared.counts);
+                shared.publish(chrono::Utc::now().timestamp(), pods);
+            }
+        });
+        signals
+    }
+
+    pub fn handler(&self) -> ScalingHandler {
+        ScalingHandler {
+            counts: Arc::clone(&self.counts),
+        }
+    }
+
+    fn publish(&self, timestamp: i64, pods: Vec<PodSignals>) {
+        *self.report.lock().unwrap() = Some(ScalingReport {
+            timestamp,
+            interval_secs: self.interval_secs,
+            pods,
+        });
+    }
+
+    /// Every pod of the latest pass; `None` before the first pass.
+    pub fn report(&self, filter: &PodFilter) -> Option<ScalingReport> {
+        let mut report = self.report.lock().unwrap().clone()?;
+        report.pods.retain(|pod| filter.matches(pod));
+        Some(report)
+    }
+
+    /// `signal` over the selected pods, reduced with `aggregate` or the
+    /// signal's default. Pods without a value are left out.
+    pub fn value(
+        &self,
+        signal: Signal,
+        filter: &PodFilter,
+        aggregate: Option<Aggregate>,
+    ) -> Option<SignalValue> {
+        let report = self.report(filter)?;
+        let aggregate = aggregate.unwrap_or_else(|| signal.default_aggregate());
+        let pods: Vec<PodValue> = report
+            .pods
+            .iter()
+            .filter_map(|pod| {
+                Some(PodValue {
+                    namespace: pod.namespace.clone(),
+                    pod: pod.pod.clone(),
+                    value: pod.value(signal)?,
+                })
+            })
+            .collect();
+        let values = pods.iter().map(|pod| pod.value);
+        let value = match aggregate {
+            _ if pods.is_empty() => 0.0,
+            Aggregate::Sum => values.sum(),
+            Aggregate::Max => values.fold(f64::MIN, f64::max),
+            Aggregate::Avg => values.sum::<f64>() / pods.len() as f64,
+        };
+        Some(SignalValue {
+            signal,
+            aggregate,
+            value,
+            timestamp: report.timestamp,
+            pods,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalMetricValue {
+    pub metric_name: String,
+    pub metric_labels: BTreeMap<String, String>,
+    /// RFC 3339
+    pub timestamp: String,
+    pub value: String,
+}
+
+/// `external.metrics.k8s.io/v1beta1` `ExternalMetricValueList`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalMetricValueList {
+    pub kind: &'static str,
+    pub api_version: &'static str,
+    pub metadata: BTreeMap<String, String>,
+    pub items: Vec<ExternalMetricValue>,
+}
+
+impl From<&SignalValue> for ExternalMetricValueList {
+    /// One item per pod, named `linnix_<signal>`.
+    fn from(value: &SignalValue) -> Self {
+        let timestamp = chrono::DateTime::from_timestamp(value.timestamp, 0)
+            .unwrap_or_default()
+            .to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        Self {
+            kind: "ExternalMetricValueList",
+            api_version: "external.metrics.k8s.io/v1beta1",
+            metadata: BTreeMap::new(),
+            items: value
+                .pods
+                .iter()
+                .map(|pod| ExternalMetricValue {
+                    metric_name: format!("linnix_{}", value.signal.as_str()),
+                    metric_labels: BTreeMap::from([
+                        ("namespace".to_string(), pod.namespace.clone()),
+                        ("pod".to_string(), pod.pod.clone()),
+                    ]),
+                    timestamp: timestamp.clone(),
+                    value: quantity(pod.value),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pod(namespace: &str, name: &str, owner: &str, rate: f64, cpu: Option<f32>) -> PodSignals {
+        PodSignals {
+            namespace: namespace.to_string(),
+            pod: name.to_string(),
+            owner: Some(owner.to_string()),
+            processes: 1,
+            event_rate: rate,
+            cpu_pressure: cpu,
+            ..PodSignals::default()
+        }
+    }
+
+    #[test]
+    fn reduces_a_signal_over_the_selected_pods() {
+        let signals = ScalingSignals::new(15);
+        assert!(
+            signals
+                .value(Signal::EventRate, &PodFilter::default(), None)
+                .is_none()
+        );
+        signals.publish(
+            1_700_000_000,
+            vec![
+                pod(
+                    "shop",
+                    "web-7d9f-abcde",
+                    "ReplicaSet/web-7d9f",
+                    10.0,
+                    Some(2.5),
+                ),
+                pod("shop", "web-7d9f-fghij", "ReplicaSet/web-7d9f", 30.0, None),
+                pod("shop", "worker-0", "StatefulSet/worker", 100.0, Some(40.0)),
+                pod("other", "web-1", "ReplicaSet/web-1", 1.0, Some(90.0)),
+            ],
+        );
+
+        let web = PodFilter {
+            namespace: Some("shop".into()),
+            pod: Some("web-".into()),
+            owner: None,
+        };
+        let rate = signals.value(Signal::EventRate, &web, None).unwrap();
+        assert_eq!(rate.aggregate, Aggregate::Sum);
+        assert_eq!(rate.value, 40.0);
+        assert_eq!(rate.pods.len(), 2);
+        let avg = signals
+            .value(Signal::EventRate, &web, Some(Aggregate::Avg))
+            .unwrap();
+        assert_eq!(avg.value, 20.0);
+
+        // Pods without pressure are left out of the reduction
+        let cpu = signals.value(Signal::CpuPressure, &web, None).unwrap();
+        assert_eq!(cpu.aggregate, Aggregate::Max);
+        assert_eq!(cpu.value, 2.5);
+        assert_eq!(cpu.pods.len(), 1);
+
+        let owner = PodFilter {
+            owner: Some("worker".into()),
+            ..PodFilter::default()
+        };
+        let report = signals.report(&owner).unwrap();
+        assert_eq!(report.pods.len(), 1);
+        assert_eq!(report.pods[0].pod, "worker-0");
+
+        let none = PodFilter {
+            namespace: Some("missing".into()),
+            ..PodFilter::default()
+        };
+        let empty = signals.value(Signal::RunqueueLatency, &none, None).unwrap();
+        assert_eq!(empty.value, 0.0);
+        assert!(empty.pods.is_empty());
+    }
+
+    #[test]
+    fn lists_pods_as_external_metrics() {
+        let value = SignalValue {
+            signal: Signal::ForkRate,
+            aggregate: Aggregate::Sum,
+            value: 12.5,
+            timestamp: 1_700_000_000,
+            pods: vec![PodValue {
+                namespace: "shop".into(),
+                pod: "web-1".into(),
+                value: 12.5,
+            }],
+        };
+        let list = serde_json::to_value(ExternalMetricValueList::from(&value)).unwrap();
+        assert_eq!(list["apiVersion"], "external.metrics.k8s.io/v1beta1");
+        assert_eq!(list["kind"], "ExternalMetricValueList");
+        let item = &list["items"][0];
+        assert_eq!(item["metricName"], "linnix_fork_rate");
+        assert_eq!(item["metricLabels"]["pod"], "web-1");
+        assert_eq!(item["timestamp"], "2023-11-14T22:13:20Z");
+        assert_eq!(item["value"], "12500m");
+    }
+
+    #[test]
+    fn finds_the_pod_cgroup() {
+        assert_eq!(
+            pod_cgroup(
+                "/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod1234.slice/cri-containerd-abc.scope"
+            ),
+            "/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod1234.slice"
+        );
+        assert_eq!(
+            pod_cgroup("/kubepods/besteffort/pod1234/abc"),
+            "/kubepods/besteffort/pod1234"
+        );
+        assert_eq!(
+            pod_cgroup("/system.slice/sshd.service"),
+            "/system.slice/sshd.service"
+        );
+    }
+
+    #[test]
+    fn runqueue_latency_is_wait_per_timeslice_since_the_last_pass() {
+        let prev = HashMap::from([(10, (1_000_000, 100)), (11, (0, 0))]);
+        // tid 12 is new and only sets a baseline
+        let tasks = [
+            (10, 3_000_000, 110),
+            (11, 1_000_000, 10),
+            (12, 9_000_000, 5),
+        ];
+        assert_eq!(runqueue_latency(&prev, &tasks), Some(150.0));
+        assert_eq!(runqueue_latency(&prev, &[(10, 1_000_000, 100)]), None);
+    }
+}
@@ -0,0 +1,126 @@
+//! cgroup subtree scoping for multi-tenant nodes
+//!
+//! When `[scope] cgroups` is set, the kernel drops events from tasks outside
+//! the listed subtrees (see `BpfControl::set_cgroup_scope`) and the daemon
+//! refuses to look at or act on anything outside them: enforcement proposals
+//! and the cgroup API check their target against the scope. Subtrees must sit
+//! at the same hierarchy depth because the kernel compares a single ancestor
+//! level.
+
+use anyhow::{Context, bail};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use crate::collectors::cgroup_tree::{normalize, process_cgroup_path};
+
+#[derive(Debug, Clone)]
+pub struct CgroupScope {
+    subtrees: Vec<String>,
+    level: u32,
+    ids: Vec<u64>,
+}
+
+impl CgroupScope {
+    /// Resolve configured subtrees below `root`. `None` when none are set.
+    pub fn resolve(root: &Path, subtrees: &[String]) -> anyhow::Result<Option<Self>> {
+        if subtrees.is_empty() {
+            return Ok(None);
+        }
+        let mut scope = Self {
+            subtrees: Vec::with_capacity(subtrees.len()),
+            level: 0,
+            ids: Vec::with_capacity(subtrees.len()),
+        };
+        for subtree in subtrees {
+            let rel = normalize(subtree);
+            if rel == "/" || rel.split('/').any(|c| c == "..") {
+                bail!("scope cgroup {subtree:?} must name a subtree below the cgroup root");
+            }
+            let level = rel.split('/').filter(|c| !c.is_empty()).count() as u32;
+            if scope.level != 0 && level != scope.level {
+                bail!(
+                    "scope cgroups must share one depth ({} is at depth {level}, expected {})",
+                    rel,
+                    scope.level
+                );
+            }
+            let dir = root.join(rel.trim_start_matches('/'));
+            // cgroup v2 ids are the inode numbers of the cgroup directories.
+            let meta = std::fs::metadata(&dir)
+                .with_context(|| format!("scope cgroup {} not found", dir.display()))?;
+            if !meta.is_dir() {
+                bail!("scope cgroup {} is not a directory", dir.display());
+            }
+            scope.level = level;
+            scope.ids.push(meta.ino());
+            scope.subtrees.push(rel);
+        }
+        Ok(Some(scope))
+    }
+
+    pub fn subtrees(&self) -> &[String] {
+        &self.subtrees
+    }
+
+    /// Hierarchy depth of the subtrees (root is 0).
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    pub fn cgroup_ids(&self) -> &[u64] {
+        &self.ids
+    }
+
+    /// Whether a cgroup path (relative to the root) lies inside the scope.
+    pub fn contains_path(&self, path: &str) -> bool {
+        let path = normalize(path);
+        self.subtrees.iter().any(|subtree| {
+            path == *subtree
+                || path
+                    .strip_prefix(subtree.as_str())
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+    }
+
+    /// Whether a running process is inside the scope. Unknown (exited or
+    /// unreadable) processes are treated as outside.
+    pub fn contains_pid(&self, pid: u32) -> bool {
+        process_cgroup_path(pid).is_some_and(|path| self.contains_path(&path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_subtrees_at_one_depth() {
+        let root = tempfile::tempdir().unwrap();
+        for dir in ["tenants/a/app", "tenants/b", "system.slice"] {
+            std::fs::create_dir_all(root.path().join(dir)).unwrap();
+        }
+        assert!(CgroupScope::resolve(root.path(), &[]).unwrap().is_none());
+
+        let scope = CgroupScope::resolve(root.path(), &["tenants/a".into(), "/tenants/b/".into()])
+            .unwrap()
+            .unwrap();
+        assert_eq!(scope.level(), 2);
+        assert_eq!(scope.subtrees(), ["/tenants/a", "/tenants/b"]);
+        assert_eq!(scope.cgroup_ids().len(), 2);
+        assert_ne!(scope.cgroup_ids()[0], scope.cgroup_ids()[1]);
+
+        assert!(scope.contains_path("/tenants/a"));
+        assert!(scope.contains_path("/tenants/a/app"));
+        assert!(!scope.contains_path("/tenants/ab"));
+        assert!(!scope.contains_path("/system.slice"));
+        assert!(!scope.contains_path("/"));
+
+        for bad in [
+            vec!["/tenants/a".to_string(), "/system.slice".to_string()],
+            vec!["/".to_string()],
+            vec!["/tenants/missing".to_string()],
+        ] {
+            assert!(CgroupScope::resolve(root.path(), &bad).is_err(), "{bad:?}");
+        }
+    }
+}
@@ -0,0 +1,364 @@
+//! Interpreter script resolution
+//!
+//! For interpreted workloads `comm` is just `python3` or `bash`. At exec the
+//! command line is read from `/proc/<pid>/cmdline` and the script is taken
+//! from the first argument that is not an interpreter option (or the module
+//! after `python -m`). A script exec'd directly through an unknown
+//! interpreter keeps its own name as `comm`; it is recognised by checking
+//! the file for a `#!` line. Inline code (`-c`, `-e`) resolves to nothing.
+
+use linnix_ai_ebpf_common::EventType;
+use std::io::Read;
+
+use crate::ProcessEvent;
+use crate::exec_risk;
+
+/// Option conventions of one interpreter family.
+struct Interpreter {
+    /// Options whose value is the next argument.
+    takes_value: &'static [&'static str],
+    /// Options introducing inline code; there is no script.
+    inline: &'static [&'static str],
+    /// Options whose value is the script (`python -m`, `java -jar`).
+    script: &'static [&'static str],
+    /// Short options can be clustered getopt-style (`-uc`).
+    clusters: bool,
+}
+
+const PYTHON: Interpreter = Interpreter {
+    takes_value: &["-W", "-X", "-Q", "--check-hash-based-pycs"],
+    inline: &["-c"],
+    script: &["-m"],
+    clusters: true,
+};
+
+const SHELL: Interpreter = Interpreter {
+    takes_value: &["-o", "+o", "-O", "+O", "--rcfile", "--init-file"],
+    inline: &["-c"],
+    script: &[],
+    clusters: true,
+};
+
+const PERL: Interpreter = Interpreter {
+    takes_value: &["-I", "-M", "-m", "-x"],
+    inline: &["-e", "-E"],
+    script: &[],
+    clusters: true,
+};
+
+const RUBY: Interpreter = Interpreter {
+    takes_value: &["-I", "-r", "-C", "-E"],
+    inline: &["-e"],
+    script: &[],
+    clusters: true,
+};
+
+const NODE: Interpreter = Interpreter {
+    takes_value: &[
+        "-r",
+        "--require",
+        "--import",
+        "--loader",
+        "--experimental-loader",
+    ],
+    inline: &["-e", "--eval", "-p", "--print"],
+    script: &[],
+    clusters: true,
+};
+
+const PHP: Interpreter = Interpreter {
+    takes_value: &["-c", "-d", "-z"],
+    inline: &["-r", "-R", "-B", "-E"],
+    script: &["-f"],
+    clusters: true,
+};
+
+const LUA: Interpreter = Interpreter {
+    takes_value: &["-l"],
+    inline: &["-e"],
+    script: &[],
+    clusters: true,
+};
+
+const RSCRIPT: Interpreter = Interpreter {
+    takes_value: &[],
+    inline: &["-e"],
+    script: &[],
+    clusters: true,
+};
+
+const JAVA: Interpreter = Interpreter {
+    takes_value: &[
+        "-cp",
+        "-classpath",
+        "--class-path",
+        "-p",
+        "--module-path",
+        "--add-modules",
+        "--add-opens",
+        "--add-exports",
+    ],
+    inline: &[],
+    script: &["-jar", "-m", "--module"],
+    clusters: false,
+};
+
+fn interpreter(name: &str) -> Option<&'static Interpreter> {
+    let versioned = |prefix: &str| {
+        name.strip_prefix(prefix)
+            .is_some_and(|v| v.chars().all(|c| c.is_ascii_digit() || c == '.'))
+    };
+    match name {
+        "sh" | "bash" | "dash" | "zsh" | "ksh" | "mksh" | "ash" => Some(&SHELL),
+        "perl" => Some(&PERL),
+        "ruby" => Some(&RUBY),
+        "node" | "nodejs" => Some(&NODE),
+        "Rscript" => Some(&RSCRIPT),
+        "java" => Some(&JAVA),
+        _ if versioned("python") || versioned("pypy") => Some(&PYTHON),
+        _ if versioned("php") => Some(&PHP),
+        _ if versioned("lua") || name == "luajit" => Some(&LUA),
+        _ => None,
+    }
+}
+
+fn basename(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// File name of a resolved script (`/srv/ml/train.py` -> `train.py`).
+pub fn script_name(path: &str) -> &str {
+    basename(path)
+}
+
+/// Script name of `event` when one was resolved, otherwise its `comm`.
+pub fn display_name(event: &ProcessEvent) -> String {
+    match &event.script_path {
+        Some(path) => script_name(path).to_string(),
+        None => String::from_utf8_lossy(&event.comm)
+            .trim_end_matches('\0')
+            .to_string(),
+    }
+}
+
+/// Skip `env`'s options and `NAME=value` assignments, leaving the program it
+/// runs and its arguments.
+fn strip_env(args: &[String]) -> &[String] {
+    let mut i = 1;
+    while let Some(arg) = args.get(i) {
+        match arg.as_str() {
+            "-u" | "--unset" | "-C" | "--chdir" => i += 2,
+            "--" => return &args[(i + 1).min(args.len())..],
+            _ if arg.starts_with('-') || arg.contains('=') => i += 1,
+            _ => break,
+        }
+    }
+    &args[i.min(args.len())..]
+}
+
+/// Script named by `args` (argv, including argv[0]) of a process whose task
+/// name is `comm`. `has_shebang` reports whether a path starts with `#!`.
+pub fn script_from_args(
+    comm: &str,
+    args: &[String],
+    has_shebang: impl Fn(&str) -> bool,
+) -> Option<String> {
+    let mut args = args;
+    if args.first().is_some_and(|a| basename(a) == "env") {
+        args = strip_env(args);
+    }
+    let argv0 = basename(args.first()?);
+    let Some(interp) = interpreter(argv0).or_else(|| interpreter(comm)) else {
+        // `./deploy` with `#!/opt/tool/bin/runner`: the kernel names the task
+        // after the script and passes it as the interpreter's argument.
+        let candidate = args.get(1)?;
+        let name = basename(candidate);
+        let is_script = !comm.is_empty()
+            && name.starts_with(comm)
+            && !candidate.starts_with('-')
+            && has_shebang(candidate);
+        return is_script.then(|| candidate.clone());
+    };
+    let mut iter = args[1..].iter();
+    while let Some(arg) = iter.next() {
+        let flag = arg.as_str();
+        if flag == "--" {
+            return iter.next().cloned();
+        }
+        if flag == "-" {
+            return None;
+        }
+        if !flag.starts_with('-') && !flag.starts_with('+') {
+            return Some(arg.clone());
+        }
+        if flag.starts_with("--") && flag.contains('=') {
+            continue;
+        }
+        let exact = |set: &[&str]| set.contains(&flag);
+        if exact(interp.inline) {
+            return None;
+        }
+        if exact(interp.script) {
+            return iter.next().cloned();
+        }
+        if exact(interp.takes_value) {
+            iter.next();
+            continue;
+        }
+        if !interp.clusters || flag.starts_with("--") {
+            continue;
+        }
+        // getopt cluster (`-uc`, `-Wignore`): an option taking a value ends
+        // it, with the remainder (or the next argument) as the value
+        let prefix = &flag[..1];
+        for (i, c) in flag[1..].char_indices() {
+            let opt = format!("{prefix}{c}");
+            let rest = &flag[1 + i + c.len_utf8()..];
+            let value = |iter: &mut std::slice::Iter<'_, String>| {
+                if rest.is_empty() {
+                    iter.next().cloned()
+                } else {
+                    Some(rest.to_string())
+                }
+            };
+            if interp.inline.contains(&opt.as_str()) {
+                return None;
+            }
+            if interp.script.contains(&opt.as_str()) {
+                return value(&mut iter);
+            }
+            if interp.takes_value.contains(&opt.as_str()) {
+                value(&mut iter);
+                break;
+            }
+        }
+    }
+    None
+}
+
+/// Path of `path` as seen by process `pid` (its root and working directory).
+fn proc_path(pid: u32, path: &str) -> String {
+    if path.starts_with('/') {
+        format!("/proc/{pid}/root{path}")
+    } else {
+        format!("/proc/{pid}/cwd/{path}")
+    }
+}
+
+fn has_shebang(pid: u32, path: &str) -> bool {
+    let mut magic = [0u8; 2];
+    std::fs::File::open(proc_path(pid, path))
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .is_ok_and(|_| &magic == b"#!")
+}
+
+/// Resolve the script of a freshly exec'd process, or `None` when it is not
+/// running one (or has already exited).
+pub fn resolve(pid: u32, comm: &str) -> Option<String> {
+    let args = exec_risk::read_args(pid)?;
+    script_from_args(comm, &args, |path| has_shebang(pid, path))
+}
+
+/// Set `script_path` on exec events. Other events carry no command line;
+/// the context store copies the script onto forked children.
+pub fn enrich(event: &mut ProcessEvent, comm: &str) {
+    if event.event_type == EventType::Exec as u32 {
+        event.script_path = resolve(event.pid, comm);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve_args(comm: &str, cmdline: &str) -> Option<String> {
+        let args: Vec<String> = cmdline.split(' ').map(str::to_string).collect();
+        script_from_args(comm, &args, |path| path.ends_with("deploy"))
+    }
+
+    #[test]
+    fn resolves_scripts_behind_interpreter_flags() {
+        for (comm, cmdline, want) in [
+            (
+                "python3",
+                "/usr/bin/python3 -u -W ignore train.py --epochs 3",
+                Some("train.py"),
+            ),
+            (
+                "python3",
+                "python3 -m http.server 8000",
+                Some("http.server"),
+            ),
+            ("python3", "python3 -uc print(1)", None),
+            (
+                "python3",
+                "/usr/bin/env python3 /srv/ml/train.py",
+                Some("/srv/ml/train.py"),
+            ),
+            (
+                "bash",
+                "bash -o pipefail ./backup.sh nightly",
+                Some("./backup.sh"),
+            ),
+            ("bash", "bash -xc echo", None),
+            ("sh", "/bin/sh", None),
+            (
+                "node",
+                "node --require=dotenv/config -r ts-node/register server.ts",
+                Some("server.ts"),
+            ),
+            (
+                "java",
+                "java -Xmx2g -cp lib/* -jar app.jar",
+                Some("app.jar"),
+            ),
+            (
+                "php8.2",
+                "php8.2 -d memory_limit=1G -f artisan",
+                Some("artisan"),
+            ),
+            ("perl", "perl -e print", None),
+            ("perl", "perl -Mstrict -w tidy.pl", Some("tidy.pl")),
+            (
+                "php",
+                "php -ddisplay_errors=stderr worker.php",
+                Some("worker.php"),
+            ),
+            (
+                "deploy",
+                "/opt/tool/bin/runner /usr/local/bin/deploy --prod",
+                Some("/usr/local/bin/deploy"),
+            ),
+            ("nginx", "nginx -g daemon off;", None),
+        ] {
+            assert_eq!(resolve_args(comm, cmdline).as_deref(), want, "{cmdline}");
+        }
+    }
+
+    #[test]
+    fn display_name_prefers_script() {
+        let mut comm = [0u8; 16];
+        comm[..7].copy_from_slice(b"python3");
+        let mut event = ProcessEvent::new(crate::ProcessEventWire {
+            pid: 1,
+            ppid: 0,
+            uid: 0,
+            gid: 0,
+            event_type: 0,
+            ts_ns: 0,
+            seq: 0,
+            comm,
+            exit_time_ns: 0,
+            cpu_pct_milli: crate::PERCENT_MILLI_UNKNOWN,
+            mem_pct_milli: crate::PERCENT_MILLI_UNKNOWN,
+            data: 0,
+            data2: 0,
+            aux: 0,
+            aux2: 0,
+            cgroup_id: 0,
+        });
+        assert_eq!(display_name(&event), "python3");
+        event.script_path = Some("/srv/ml/train.py".into());
+        assert_eq!(display_name(&event), "train.py");
+    }
+}
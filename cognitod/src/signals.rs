@@ -0,0 +1,139 @@
+//! Signals sent between processes
+//!
+//! The `signal_generate` tracepoint reports a signal from the task that
+//! generated it, so the `pid` and `comm` of a `signal` event are the
+//! sender's and the target pid is in `data`. A signal the kernel raises
+//! itself (a fault, an OOM kill) is reported by whichever task was running,
+//! and flagged as such. Only signals whose default action terminates the
+//! process are reported unless `[probes.signals] fatal_only` is off. The
+//! context store keeps the last fatal signal sent to each live process,
+//! shown as `killed_by` in `/processes` and in alert evidence.
+
+use linnix_ai_ebpf_common::{DEFAULT_SIGNAL_MASK, signal_event};
+use serde::Serialize;
+
+use crate::ProcessEvent;
+
+/// A signal sent to a process, as reported by a `signal` event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SignalRecord {
+    /// `SIGKILL`; `signal 40` for signals without a standard name
+    pub signal: String,
+    pub signo: u32,
+    pub sender_pid: u32,
+    pub sender_comm: String,
+    pub target_pid: u32,
+    /// `si_code`: 0 for kill(2), negative for other senders in userspace
+    pub code: i32,
+    /// Raised by the kernel rather than sent by a process
+    pub kernel: bool,
+    /// Sent to the whole thread group rather than one thread
+    pub group: bool,
+    /// False when the target ignored the signal, already had it pending or
+    /// the kernel dropped it
+    pub delivered: bool,
+}
+
+impl SignalRecord {
+    pub fn from_event(event: &ProcessEvent) -> Self {
+        let result = event.aux2 >> signal_event::RESULT_SHIFT & 0xff;
+        Self {
+            signal: name(event.aux),
+            signo: event.aux,
+            sender_pid: event.pid,
+            sender_comm: String::from_utf8_lossy(&event.comm)
+                .trim_end_matches('\0')
+                .to_string(),
+            target_pid: event.data as u32,
+            code: event.data2 as i64 as i32,
+            kernel: event.aux2 & signal_event::KERNEL != 0,
+            group: event.aux2 & signal_event::GROUP != 0,
+            delivered: result == signal_event::RESULT_DELIVERED,
+        }
+    }
+
+    /// The signal's default action terminates the process.
+    pub fn is_fatal(&self) -> bool {
+        is_fatal(self.signo)
+    }
+
+    /// `SIGKILL from kubelet(812)`, for alert messages.
+    pub fn describe(&self) -> String {
+        if self.kernel {
+            format!("{} from the kernel", self.signal)
+        } else {
+            format!(
+                "{} from {}({})",
+                self.signal, self.sender_comm, self.sender_pid
+            )
+        }
+    }
+
+    /// Whether this signal should replace `previous` as the last one sent:
+    /// a signal that was not delivered does not hide one that was.
+    pub fn supersedes(&self, previous: &SignalRecord) -> bool {
+        self.delivered || !previous.delivered
+    }
+}
+
+/// `SIGTERM`, or `signal 40` for signals without a standard name.
+pub fn name(signo: u32) -> String {
+    signal_event::name(signo).map_or_else(|| format!("signal {signo}"), String::from)
+}
+
+pub fn is_fatal(signo: u32) -> bool {
+    signo < 64 && DEFAULT_SIGNAL_MASK & (1 << signo) != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PERCENT_MILLI_UNKNOWN, ProcessEventWire};
+    use linnix_ai_ebpf_common::EventType;
+
+    fn signal(sender: u32, target: u64, signo: u32, aux2: u32) -> ProcessEvent {
+        let mut comm = [0u8; 16];
+        comm[..7].copy_from_slice(b"kubelet");
+        ProcessEvent::new(ProcessEventWire {
+            pid: sender,
+            ppid: 1,
+            uid: 0,
+            gid: 0,
+            event_type: EventType::Signal as u32,
+            ts_ns: 0,
+            seq: 0,
+            comm,
+            exit_time_ns: 0,
+            cpu_pct_milli: PERCENT_MILLI_UNKNOWN,
+            mem_pct_milli: PERCENT_MILLI_UNKNOWN,
+            data: target,
+            data2: 0,
+            aux: signo,
+            aux2,
+            cgroup_id: 0,
+        })
+    }
+
+    #[test]
+    fn decodes_sender_target_and_result() {
+        let kill = SignalRecord::from_event(&signal(812, 4121, 9, signal_event::GROUP));
+        assert_eq!(kill.signal, "SIGKILL");
+        assert_eq!((kill.sender_pid, kill.target_pid), (812, 4121));
+        assert!(kill.group && kill.delivered && !kill.kernel);
+        assert!(kill.is_fatal());
+        assert_eq!(kill.describe(), "SIGKILL from kubelet(812)");
+
+        let pending =
+            SignalRecord::from_event(&signal(812, 4121, 15, 2 << signal_event::RESULT_SHIFT));
+        assert!(!pending.delivered);
+        assert!(!pending.supersedes(&kill));
+        assert!(kill.supersedes(&pending));
+
+        let segv = SignalRecord::from_event(&signal(4121, 4121, 11, signal_event::KERNEL));
+        assert_eq!(segv.describe(), "SIGSEGV from the kernel");
+
+        let chld = SignalRecord::from_event(&signal(4121, 1, 17, 0));
+        assert!(!chld.is_fatal());
+        assert_eq!(name(40), "signal 40");
+    }
+}
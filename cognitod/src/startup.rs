@@ -0,0 +1,140 @@
+//! Exec-to-listen latency
+//!
+//! A service counts as ready when it first puts a socket into the listening
+//! state after exec. The time between the exec event and that first listen is
+//! kept per live process (for `/processes`) and folded into a histogram for
+//! Prometheus. Later listens by the same image (extra ports, reloads) are
+//! ignored; a new exec starts a new measurement.
+
+use linnix_ai_ebpf_common::EventType;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::ProcessEvent;
+
+/// Histogram bucket upper bounds in seconds.
+pub const BUCKETS_SECS: [f64; 11] = [0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct StartupLatency {
+    pub port: u16,
+    pub exec_to_listen_ns: u64,
+}
+
+impl StartupLatency {
+    pub fn as_millis_f64(&self) -> f64 {
+        self.exec_to_listen_ns as f64 / 1_000_000.0
+    }
+}
+
+/// Latency from `exec` (the live entry of the process) to `listen`. `None`
+/// unless `exec` is an exec event that precedes the listen.
+pub fn measure(exec: &ProcessEvent, listen: &ProcessEvent) -> Option<StartupLatency> {
+    if exec.event_type != EventType::Exec as u32
+        || listen.event_type != EventType::Listen as u32
+        || listen.ts_ns < exec.ts_ns
+    {
+        return None;
+    }
+    Some(StartupLatency {
+        port: listen.data as u16,
+        exec_to_listen_ns: listen.ts_ns - exec.ts_ns,
+    })
+}
+
+#[derive(Default)]
+pub struct StartupHistogram {
+    buckets: [AtomicU64; BUCKETS_SECS.len()],
+    count: AtomicU64,
+    sum_ns: AtomicU64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramSnapshot {
+    /// `(upper bound in seconds, cumulative count)`, excluding `+Inf`.
+    pub buckets: Vec<(f64, u64)>,
+    pub count: u64,
+    pub sum_secs: f64,
+}
+
+impl StartupHistogram {
+    pub fn observe(&self, latency: &StartupLatency) {
+        let secs = latency.exec_to_listen_ns as f64 / 1e9;
+        if let Some(i) = BUCKETS_SECS.iter().position(|le| secs <= *le) {
+            self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_ns
+            .fetch_add(latency.exec_to_listen_ns, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let mut cumulative = 0;
+        let buckets = BUCKETS_SECS
+            .iter()
+            .zip(&self.buckets)
+            .map(|(le, n)| {
+                cumulative += n.load(Ordering::Relaxed);
+                (*le, cumulative)
+            })
+            .collect();
+        HistogramSnapshot {
+            buckets,
+            count: self.count.load(Ordering::Relaxed),
+            sum_secs: self.sum_ns.load(Ordering::Relaxed) as f64 / 1e9,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PERCENT_MILLI_UNKNOWN, ProcessEventWire};
+
+    fn event(event_type: EventType, ts_ns: u64, data: u64) -> ProcessEvent {
+        ProcessEvent::new(ProcessEventWire {
+            pid: 10,
+            ppid: 1,
+            uid: 0,
+            gid: 0,
+            event_type: event_type as u32,
+            ts_ns,
+            seq: 0,
+            comm: [0; 16],
+            exit_time_ns: 0,
+            cpu_pct_milli: PERCENT_MILLI_UNKNOWN,
+            mem_pct_milli: PERCENT_MILLI_UNKNOWN,
+            data,
+            data2: 0,
+            aux: 0,
+            aux2: 0,
+            cgroup_id: 0,
+        })
+    }
+
+    #[test]
+    fn measures_exec_to_listen_and_buckets_it() {
+        let exec = event(EventType::Exec, 1_000_000_000, 0);
+        let listen = event(EventType::Listen, 1_300_000_000, 8080);
+        let latency = measure(&exec, &listen).unwrap();
+        assert_eq!(latency.port, 8080);
+        assert_eq!(latency.as_millis_f64(), 300.0);
+
+        let fork = event(EventType::Fork, 1_000_000_000, 0);
+        assert_eq!(measure(&fork, &listen), None);
+        assert_eq!(measure(&listen, &exec), None);
+
+        let hist = StartupHistogram::default();
+        hist.observe(&latency);
+        hist.observe(&StartupLatency {
+            port: 80,
+            exec_to_listen_ns: 120_000_000_000,
+        });
+        let snap = hist.snapshot();
+        assert_eq!(snap.count, 2);
+        assert_eq!(snap.buckets[3], (0.25, 0));
+        assert_eq!(snap.buckets[4], (0.5, 1));
+        assert_eq!(snap.buckets.last(), Some(&(60.0, 1)));
+        assert!((snap.sum_secs - 120.3).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,309 @@
+//! Versioned operational state, carried across upgrades and re-provisioning
+//!
+//! A bundle is one JSON document (gzipped when the path ends in `.gz`)
+//! holding what a node would otherwise lose: incidents with their analyses
+//! and delivery state, insight feedback labels, usage rollups, API-created
+//! maintenance windows (silences) that have not ended, and the health
+//! score's event-rate baseline. `[state] export_path` writes one on
+//! shutdown and `/state/export` serves one on demand.
+//!
+//! Bundles carry `version`. Import brings an older bundle up to
+//! `BUNDLE_VERSION` by running the `MIGRATIONS` step for each version in
+//! between, each a rewrite of the raw JSON, and refuses bundles from a newer
+//! cognitod. `[state] import_path` imports on startup and renames the file
+//! to `<path>.imported` so a restart does not import it again; importing the
+//! same incidents twice adds nothing anyway.
+
+use anyhow::{Context, Result, anyhow, bail};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::anomaly::Ewma;
+use crate::health_score::HealthScorer;
+use crate::incidents::{FeedbackLabel, Incident, IncidentStore};
+use crate::leaderboard::UsageRollup;
+use crate::maintenance::{AdhocWindow, MaintenanceManager};
+
+/// Version written by this build.
+pub const BUNDLE_VERSION: u32 = 1;
+
+type Migration = fn(&mut Value) -> Result<()>;
+
+/// `MIGRATIONS[i]` rewrites a version `i + 1` bundle into version `i + 2`.
+/// Append a step whenever `BUNDLE_VERSION` is bumped.
+const MIGRATIONS: &[Migration] = &[];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateBundle {
+    pub version: u32,
+    /// Unix seconds
+    pub created_at: i64,
+    pub daemon_version: String,
+    pub host: String,
+    #[serde(default)]
+    pub incidents: Vec<Incident>,
+    #[serde(default)]
+    pub feedback: Vec<FeedbackLabel>,
+    #[serde(default)]
+    pub usage_rollups: Vec<UsageRollup>,
+    #[serde(default)]
+    pub silences: Vec<AdhocWindow>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_baseline: Option<Ewma>,
+}
+
+/// Where state is read from on export and restored to on import; parts
+/// without a source are skipped.
+#[derive(Clone, Copy, Default)]
+pub struct StateSources<'a> {
+    pub incidents: Option<&'a IncidentStore>,
+    pub maintenance: Option<&'a MaintenanceManager>,
+    pub health: Option<&'a HealthScorer>,
+}
+
+/// What an import restored.
+#[derive(Debug, Default, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub from_version: u32,
+    pub incidents: usize,
+    pub feedback: usize,
+    pub usage_rollups: usize,
+    pub silences: usize,
+    pub health_baseline: bool,
+}
+
+/// Snapshot the current state.
+pub async fn collect(sources: StateSources<'_>) -> Result<StateBundle> {
+    let mut bundle = StateBundle {
+        version: BUNDLE_VERSION,
+        created_at: chrono::Utc::now().timestamp(),
+        daemon_version: env!("CARGO_PKG_VERSION").to_string(),
+        host: hostname::get()
+            .ok()
+            .and_then(|h| h.into_string().ok())
+            .unwrap_or_else(|| "unknown".to_string()),
+        incidents: Vec::new(),
+        feedback: Vec::new(),
+        usage_rollups: Vec::new(),
+        silences: Vec::new(),
+        health_baseline: None,
+    };
+    if let Some(store) = sources.incidents {
+        store.flush().await;
+        bundle.incidents = store
+            .since(i64::MIN, None)
+            .await
+            .map_err(|e| anyhow!("reading incidents: {e}"))?;
+        bundle.incidents.reverse();
+        bundle.feedback = store
+            .feedback_labels()
+            .await
+            .map_err(|e| anyhow!("reading feedback: {e}"))?;
+        bundle.usage_rollups = store
+            .usage_rollups_since(i64::MIN)
+            .await
+            .map_err(|e| anyhow!("reading usage rollups: {e}"))?;
+    }
+    if let Some(maintenance) = sources.maintenance {
+        bundle.silences = maintenance.adhoc_windows();
+    }
+    if let Some(health) = sources.health {
+        bundle.health_baseline = Some(health.baseline()).filter(|b| b.samples() > 0);
+    }
+    Ok(bundle)
+}
+
+/// Restore a bundle into the running daemon.
+pub async fn restore(bundle: StateBundle, sources: StateSources<'_>) -> Result<ImportSummary> {
+    let mut summary = ImportSummary {
+        from_version: bundle.version,
+        ..ImportSummary::default()
+    };
+    match sources.incidents {
+        Some(store) => {
+            let counts = store
+                .import(&bundle.incidents, &bundle.feedback, &bundle.usage_rollups)
+                .await
+                .map_err(|e| anyhow!("importing incidents: {e}"))?;
+            summary.incidents = counts.incidents;
+            summary.feedback = counts.feedback;
+            summary.usage_rollups = counts.usage_rollups;
+        }
+        None if !bundle.incidents.is_empty() || !bundle.feedback.is_empty() => {
+            warn!(
+                "[state] no incident store; {} incident(s) and {} feedback label(s) not imported",
+                bundle.incidents.len(),
+                bundle.feedback.len()
+            );
+        }
+        None => {}
+    }
+    if let Some(maintenance) = sources.maintenance {
+        summary.silences = maintenance.restore(bundle.silences);
+    }
+    if let (Some(health), Some(baseline)) = (sources.health, bundle.health_baseline) {
+        summary.health_baseline = health.restore_baseline(baseline);
+    }
+    Ok(summary)
+}
+
+/// Parse a bundle, migrating it to `BUNDLE_VERSION`.
+pub fn parse(json: &[u8]) -> Result<StateBundle> {
+    let mut value: Value = serde_json::from_slice(json).context("bundle is not JSON")?;
+    let version = value
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| anyhow!("bundle has no version"))? as u32;
+    if version == 0 || version > BUNDLE_VERSION {
+        bail!("bundle version {version} is not supported (this build reads 1 to {BUNDLE_VERSION})");
+    }
+    for (step, migrate) in MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+        migrate(&mut value).with_context(|| {
+            format!("migrating bundle from version {} to {}", step + 1, step + 2)
+        })?;
+        value["version"] = Value::from(step as u32 + 2);
+    }
+    let mut bundle: StateBundle = serde_json::from_value(value).context("invalid bundle")?;
+    bundle.version = version;
+    Ok(bundle)
+}
+
+fn gzipped(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "gz")
+}
+
+/// Write a bundle, via a temporary file so a crash never leaves half of one.
+pub fn write(bundle: &StateBundle, path: &Path) -> Result<()> {
+    let json = serde_json::to_vec(bundle)?;
+    let data = if gzipped(path) {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&json)?;
+        encoder.finish()?
+    } else {
+        json
+    };
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, data).with_context(|| format!("writing {}", tmp.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("renaming to {}", path.display()))?;
+    Ok(())
+}
+
+/// Read and migrate a bundle.
+pub fn read(path: &Path) -> Result<StateBundle> {
+    let raw = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    if !gzipped(path) {
+        return parse(&raw);
+    }
+    let mut json = Vec::new();
+    GzDecoder::new(raw.as_slice())
+        .read_to_end(&mut json)
+        .with_context(|| format!("decompressing {}", path.display()))?;
+    parse(&json)
+}
+
+/// Import the bundle at `path` if there is one, then move it aside.
+pub async fn import_on_startup(
+    path: &Path,
+    sources: StateSources<'_>,
+) -> Result<Option<ImportSummary>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let bundle = read(path)?;
+    let summary = restore(bundle, sources).await?;
+    let mut done = PathBuf::from(path);
+    done.as_mut_os_string().push(".imported");
+    std::fs::rename(path, &done).with_context(|| format!("renaming to {}", done.display()))?;
+    info!(
+        "[state] imported {} (v{}): {} incident(s), {} feedback label(s), {} rollup(s), {} silence(s){}",
+        path.display(),
+        summary.from_version,
+        summary.incidents,
+        summary.feedback,
+        summary.usage_rollups,
+        summary.silences,
+        if summary.health_baseline {
+            ", health baseline"
+        } else {
+            ""
+        }
+    );
+    Ok(Some(summary))
+}
+
+/// Export the current state to `path`.
+pub async fn export_to(path: &Path, sources: StateSources<'_>) -> Result<()> {
+    let bundle = collect(sources).await?;
+    write(&bundle, path)?;
+    info!(
+        "[state] exported {} incident(s), {} silence(s) to {}",
+        bundle.incidents.len(),
+        bundle.silences.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MaintenanceScope;
+    use crate::maintenance::CreateWindowRequest;
+
+    #[tokio::test]
+    async fn bundle_round_trips_and_rejects_newer_versions() {
+        let old = MaintenanceManager::default();
+        let window = old
+            .create(CreateWindowRequest {
+                name: "db-upgrade".to_string(),
+                duration_secs: 3600,
+                reason: "schema change".to_string(),
+                created_by: Some("ops".to_string()),
+                scope: MaintenanceScope::default(),
+            })
+            .unwrap();
+        let sources = StateSources {
+            maintenance: Some(&old),
+            ..StateSources::default()
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json.gz");
+        export_to(&path, sources).await.unwrap();
+
+        let new = MaintenanceManager::default();
+        let sources = StateSources {
+            maintenance: Some(&new),
+            ..StateSources::default()
+        };
+        let summary = import_on_startup(&path, sources).await.unwrap().unwrap();
+        assert_eq!(summary.from_version, BUNDLE_VERSION);
+        assert_eq!(summary.silences, 1);
+        assert_eq!(new.adhoc_windows()[0].id, window.id);
+        assert!(!path.exists());
+        assert!(dir.path().join("state.json.gz.imported").exists());
+        assert!(import_on_startup(&path, sources).await.unwrap().is_none());
+
+        // Restored ids are not handed out again
+        let next = new
+            .create(CreateWindowRequest {
+                name: "next".to_string(),
+                duration_secs: 60,
+                reason: String::new(),
+                created_by: None,
+                scope: MaintenanceScope::default(),
+            })
+            .unwrap();
+        assert_ne!(next.id, window.id);
+
+        let err = parse(br#"{"version": 99, "created_at": 0}"#).unwrap_err();
+        assert!(format!("{err:#}").contains("version 99 is not supported"));
+        assert!(parse(br#"{"created_at": 0}"#).is_err());
+    }
+}
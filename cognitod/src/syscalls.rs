@@ -0,0 +1,93 @@
+//! Syscall histograms of each process
+//!
+//! The `raw_syscalls:sys_enter` tracepoint counts calls per process and
+//! syscall number in a per-CPU map, and emits a `syscall` event with the
+//! number (`data`) and the calls counted (`data2`) once a tally reaches the
+//! batch size or has been open for the flush interval (`[probes.syscalls]`),
+//! so calls show up here late and in batches. The context store keeps a
+//! [`SyscallHistogram`] of the live processes, served by
+//! `/processes/{pid}/syscalls` as a baseline of what each one normally asks
+//! of the kernel. Numbers are those of the kernel's native syscall table.
+
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Calls of each syscall made by one process.
+#[derive(Debug, Clone, Default)]
+pub struct SyscallHistogram {
+    counts: HashMap<u32, u64>,
+    total: u64,
+    first_seen_ns: u64,
+    last_seen_ns: u64,
+}
+
+/// Calls of one syscall and their share of the process's calls.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SyscallCount {
+    pub nr: u32,
+    pub count: u64,
+    pub share: f64,
+}
+
+/// Histogram of a process as served by the API, most frequent first.
+#[derive(Debug, Clone, Serialize)]
+pub struct SyscallSummary {
+    pub total: u64,
+    pub first_seen_ns: u64,
+    pub last_seen_ns: u64,
+    pub syscalls: Vec<SyscallCount>,
+}
+
+impl SyscallHistogram {
+    /// Count `calls` calls of syscall `nr` reported at `now`.
+    pub fn record(&mut self, nr: u32, calls: u64, now: u64) {
+        if calls == 0 {
+            return;
+        }
+        *self.counts.entry(nr).or_default() += calls;
+        self.total += calls;
+        if self.first_seen_ns == 0 {
+            self.first_seen_ns = now;
+        }
+        self.last_seen_ns = now;
+    }
+
+    pub fn summary(&self) -> SyscallSummary {
+        let mut syscalls: Vec<SyscallCount> = self
+            .counts
+            .iter()
+            .map(|(&nr, &count)| SyscallCount {
+                nr,
+                count,
+                share: count as f64 / self.total as f64,
+            })
+            .collect();
+        syscalls.sort_by(|a, b| b.count.cmp(&a.count).then(a.nr.cmp(&b.nr)));
+        SyscallSummary {
+            total: self.total,
+            first_seen_ns: self.first_seen_ns,
+            last_seen_ns: self.last_seen_ns,
+            syscalls,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orders_syscalls_by_calls() {
+        let mut hist = SyscallHistogram::default();
+        hist.record(0, 64, 10);
+        hist.record(1, 64, 20);
+        hist.record(0, 64, 30);
+        hist.record(232, 0, 40);
+        let summary = hist.summary();
+        assert_eq!(summary.total, 192);
+        assert_eq!((summary.first_seen_ns, summary.last_seen_ns), (10, 30));
+        let calls: Vec<(u32, u64)> = summary.syscalls.iter().map(|s| (s.nr, s.count)).collect();
+        assert_eq!(calls, vec![(0, 128), (1, 64)]);
+        assert!((summary.syscalls[1].share - 1.0 / 3.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,430 @@
+//! Message templates shared by the notifiers and incident sinks.
+//!
+//! Every notifier renders the text of its messages from a minijinja template
+//! per message kind. Layout stays with the notifier (Slack blocks, Discord
+//! embeds and buttons, PagerDuty fields); the template decides the wording.
+//! Defaults reproduce the built-in messages and can be replaced per notifier
+//! under `[templates.<notifier>]`. Overrides are compiled and rendered
+//! against sample messages at startup, so a broken template stops the daemon
+//! instead of the first page.
+
+use anyhow::{Context, Result, bail};
+use minijinja::Environment;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use crate::alerts::{Alert, Annotations, Severity};
+use crate::enforcement::EnforcementAction;
+use crate::incidents::Incident;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageKind {
+    Alert,
+    Incident,
+    /// Approval request for a proposed enforcement action
+    Enforcement,
+    /// Incident resolved
+    Recovery,
+}
+
+impl MessageKind {
+    pub const ALL: [MessageKind; 4] = [
+        MessageKind::Alert,
+        MessageKind::Incident,
+        MessageKind::Enforcement,
+        MessageKind::Recovery,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MessageKind::Alert => "alert",
+            MessageKind::Incident => "incident",
+            MessageKind::Enforcement => "enforcement",
+            MessageKind::Recovery => "recovery",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.as_str() == name)
+    }
+}
+
+const APPRISE_ALERT: &str = r#"Host: {{ host }}
+
+{{ message }}
+{%- if context %}
+{% for line in context %}
+{{ line.label }}: {{ line.text }}
+{%- endfor %}{% endif %}
+{%- if annotations.description %}
+
+{{ annotations.description }}{% endif %}
+{%- if annotations.owner %}
+Owner: {{ annotations.owner }}{% endif %}
+{%- if annotations.labels %}
+Labels: {% for key, value in annotations.labels|items %}{{ key }}={{ value }}{% if not loop.last %}, {% endif %}{% endfor %}{% endif %}
+{%- if annotations.runbook_url %}
+Runbook: {{ annotations.runbook_url }}{% endif %}"#;
+
+const DISCORD_ALERT: &str = r#"{{ message }}
+{%- if annotations.description %}
+
+{{ annotations.description }}{% endif %}"#;
+
+const DISCORD_RECOVERY: &str = "{% if recovery_time_ms %}Recovered after \
+{{ (recovery_time_ms / 1000)|round(1) }}s{% else %}Pressure cleared{% endif %}";
+
+/// Built-in template of every message a notifier sends. A notifier only
+/// accepts overrides for the kinds listed here.
+const DEFAULTS: &[(&str, MessageKind, &str)] = &[
+    ("slack", MessageKind::Alert, "*Message:*\n{{ message }}"),
+    ("discord", MessageKind::Alert, DISCORD_ALERT),
+    ("discord", MessageKind::Enforcement, "{{ reason }}"),
+    ("discord", MessageKind::Incident, "{{ summary }}"),
+    ("discord", MessageKind::Recovery, DISCORD_RECOVERY),
+    ("apprise", MessageKind::Alert, APPRISE_ALERT),
+    ("webhook", MessageKind::Incident, "{{ summary }}"),
+    ("webhook", MessageKind::Recovery, "{{ summary }}"),
+    ("pagerduty", MessageKind::Incident, "{{ summary }}"),
+    ("opsgenie", MessageKind::Incident, "{{ summary }}"),
+];
+
+static DEFAULT_TEMPLATES: Lazy<Arc<Templates>> = Lazy::new(|| {
+    Arc::new(Templates::from_config(&BTreeMap::new()).expect("built-in templates compile"))
+});
+
+fn template_name(notifier: &str, kind: MessageKind) -> String {
+    format!("{notifier}.{}", kind.as_str())
+}
+
+/// Compiled templates of every notifier.
+pub struct Templates {
+    env: Environment<'static>,
+    /// Source of each template, defaults included, for `/templates/preview`.
+    sources: HashMap<String, String>,
+}
+
+impl Templates {
+    /// The built-in templates.
+    pub fn defaults() -> Arc<Templates> {
+        Arc::clone(&DEFAULT_TEMPLATES)
+    }
+
+    /// Built-in templates with the `[templates]` overrides applied. Fails on
+    /// unknown notifiers or kinds and on templates that do not compile or
+    /// render.
+    pub fn from_config(overrides: &BTreeMap<String, BTreeMap<String, String>>) -> Result<Self> {
+        let mut sources: HashMap<String, String> = DEFAULTS
+            .iter()
+            .map(|(notifier, kind, source)| (template_name(notifier, *kind), source.to_string()))
+            .collect();
+        for (notifier, kinds) in overrides {
+            if !Self::notifiers().any(|known| known == notifier) {
+                bail!(
+                    "unknown notifier '{}' in [templates]; expected one of {}",
+                    notifier,
+                    Self::notifiers().collect::<Vec<_>>().join(", ")
+                );
+            }
+            for (kind_name, source) in kinds {
+                let Some(kind) = MessageKind::parse(kind_name) else {
+                    bail!("unknown message kind '{kind_name}' in [templates.{notifier}]");
+                };
+                let name = template_name(notifier, kind);
+                if !sources.contains_key(&name) {
+                    bail!("{notifier} does not send {} messages", kind.as_str());
+                }
+                sources.insert(name, source.clone());
+            }
+        }
+
+        let mut env = Environment::new();
+        for (name, source) in &sources {
+            env.add_template_owned(name.clone(), source.clone())
+                .with_context(|| format!("template {name}"))?;
+        }
+        let templates = Self { env, sources };
+        for (notifier, kind, _) in DEFAULTS {
+            templates
+                .try_render(notifier, *kind, &sample(*kind))
+                .with_context(|| format!("template {}", template_name(notifier, *kind)))?;
+        }
+        Ok(templates)
+    }
+
+    /// Notifiers with templates, in definition order.
+    pub fn notifiers() -> impl Iterator<Item = &'static str> {
+        let mut seen = Vec::new();
+        DEFAULTS
+            .iter()
+            .map(|(notifier, _, _)| *notifier)
+            .filter(move |notifier| {
+                let first = !seen.contains(notifier);
+                if first {
+                    seen.push(*notifier);
+                }
+                first
+            })
+    }
+
+    /// Whether `notifier` sends `kind` messages.
+    pub fn has(&self, notifier: &str, kind: MessageKind) -> bool {
+        self.sources.contains_key(&template_name(notifier, kind))
+    }
+
+    pub fn source(&self, notifier: &str, kind: MessageKind) -> Option<&str> {
+        self.sources
+            .get(&template_name(notifier, kind))
+            .map(String::as_str)
+    }
+
+    fn try_render<C: Serialize>(
+        &self,
+        notifier: &str,
+        kind: MessageKind,
+        context: &C,
+    ) -> Result<String> {
+        let template = self.env.get_template(&template_name(notifier, kind))?;
+        Ok(template.render(context)?)
+    }
+
+    /// Render the `kind` message of `notifier`. A render error falls back to
+    /// the built-in template rather than dropping the message.
+    pub fn render<C: Serialize>(&self, notifier: &str, kind: MessageKind, context: &C) -> String {
+        match self.try_render(notifier, kind, context) {
+            Ok(text) => text,
+            Err(e) => {
+                log::warn!(
+                    "[templates] {} failed, using the default: {:#}",
+                    template_name(notifier, kind),
+                    e
+                );
+                let defaults = Self::defaults();
+                if std::ptr::eq(self, &*defaults) {
+                    return String::new();
+                }
+                defaults.render(notifier, kind, context)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContextLine {
+    pub label: &'static str,
+    pub text: String,
+}
+
+/// Variables of `alert` templates.
+#[derive(Debug, Serialize)]
+pub struct AlertContext {
+    pub rule: String,
+    /// `info`, `low`, `medium` or `high`
+    pub severity: &'static str,
+    pub message: String,
+    pub host: String,
+    pub detection: &'static str,
+    /// Process, children and pod lines, as `label` and `text`
+    pub context: Vec<ContextLine>,
+    pub annotations: Annotations,
+}
+
+impl AlertContext {
+    pub fn new(alert: &Alert) -> Self {
+        Self {
+            rule: alert.rule.clone(),
+            severity: alert.severity.as_str(),
+            message: alert.message.clone(),
+            host: alert.host.clone(),
+            detection: alert.detection,
+            context: alert
+                .process_context_lines()
+                .into_iter()
+                .map(|(label, text)| ContextLine { label, text })
+                .collect(),
+            annotations: alert.annotations.as_deref().cloned().unwrap_or_default(),
+        }
+    }
+}
+
+/// Variables of `enforcement` templates.
+#[derive(Debug, Serialize)]
+pub struct EnforcementContext {
+    pub id: String,
+    /// Action type, e.g. `kill_process`
+    pub kind: &'static str,
+    pub target: String,
+    pub reason: String,
+    pub source: String,
+    pub rule: Option<String>,
+    pub confidence: Option<f64>,
+    pub expires_at: u64,
+}
+
+impl EnforcementContext {
+    pub fn new(action: &EnforcementAction) -> Self {
+        Self {
+            id: action.id.clone(),
+            kind: action.action.kind(),
+            target: action.action.target(),
+            reason: action.reason.clone(),
+            source: action.source.clone(),
+            rule: action.rule.clone(),
+            confidence: action.confidence,
+            expires_at: action.expires_at,
+        }
+    }
+}
+
+/// Variables of `incident` and `recovery` templates: the incident's fields
+/// plus the ones below.
+#[derive(Debug, Serialize)]
+pub struct IncidentContext<'a> {
+    #[serde(flatten)]
+    pub incident: &'a Incident,
+    /// Stored id; replaces the incident's own, unset before it is written
+    pub id: i64,
+    pub host: &'a str,
+    /// One-line description: event, host, action, target, CPU and PSI
+    pub summary: String,
+    pub dedup_key: String,
+}
+
+fn sample_alert() -> Alert {
+    Alert {
+        rule: "fork_storm".to_string(),
+        severity: Severity::High,
+        message: "pid 4242 (stress) forked 180 children in 1s".to_string(),
+        host: "node-1".to_string(),
+        detection: "threshold",
+        suppressed_by: None,
+        lineage: Vec::new(),
+        children: Vec::new(),
+        pod: None,
+        evidence: None,
+        annotations: Some(Box::new(Annotations {
+            runbook_url: Some("https://runbooks.example.com/fork-storm".to_string()),
+            description: Some(
+                "A process is forking faster than the node can schedule.".to_string(),
+            ),
+            owner: Some("platform-oncall".to_string()),
+            labels: BTreeMap::from([("team".to_string(), "platform".to_string())]),
+        })),
+    }
+}
+
+fn sample_incident() -> Incident {
+    Incident {
+        id: Some(7),
+        timestamp: 1_732_242_135,
+        event_type: "circuit_breaker_cpu".to_string(),
+        psi_cpu: 75.0,
+        psi_memory: 0.0,
+        cpu_percent: 96.5,
+        load_avg: "4.00,3.00,2.00".to_string(),
+        action: "auto_kill".to_string(),
+        target_pid: Some(4242),
+        target_name: Some("stress".to_string()),
+        system_snapshot: None,
+        llm_analysis: None,
+        llm_analyzed_at: None,
+        analysis_source: None,
+        recovery_time_ms: Some(12_500),
+        psi_after: Some(8.0),
+        delivery: Default::default(),
+        dependents: Vec::new(),
+    }
+}
+
+/// Sample variables of a `kind` message, used to validate templates and by
+/// `/templates/preview`.
+pub fn sample(kind: MessageKind) -> serde_json::Value {
+    let value = match kind {
+        MessageKind::Alert => serde_json::to_value(AlertContext::new(&sample_alert())),
+        MessageKind::Enforcement => serde_json::to_value(EnforcementContext {
+            id: "0b6f2c1e".to_string(),
+            kind: "kill_process",
+            target: "pid 4242".to_string(),
+            reason: "fork_storm: pid 4242 (stress) forked 180 children in 1s".to_string(),
+            source: "rules".to_string(),
+            rule: Some("fork_storm".to_string()),
+            confidence: Some(0.92),
+            expires_at: 1_732_242_435,
+        }),
+        MessageKind::Incident | MessageKind::Recovery => {
+            let incident = sample_incident();
+            serde_json::to_value(IncidentContext {
+                incident: &incident,
+                id: 7,
+                host: "node-1",
+                summary:
+                    "circuit_breaker_cpu on node-1: auto_kill stress(4242) (CPU 96.5%, PSI 75.0%)"
+                        .to_string(),
+                dedup_key: "linnix-node-1-7".to_string(),
+            })
+        }
+    };
+    value.expect("sample contexts serialize")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overrides(
+        notifier: &str,
+        kind: &str,
+        source: &str,
+    ) -> BTreeMap<String, BTreeMap<String, String>> {
+        BTreeMap::from([(
+            notifier.to_string(),
+            BTreeMap::from([(kind.to_string(), source.to_string())]),
+        )])
+    }
+
+    #[test]
+    fn defaults_reproduce_builtin_messages() {
+        let templates = Templates::defaults();
+        let alert = AlertContext::new(&sample_alert());
+        assert_eq!(
+            templates.render("apprise", MessageKind::Alert, &alert),
+            "Host: node-1\n\npid 4242 (stress) forked 180 children in 1s\n\n\
+             A process is forking faster than the node can schedule.\n\
+             Owner: platform-oncall\nLabels: team=platform\n\
+             Runbook: https://runbooks.example.com/fork-storm"
+        );
+        assert_eq!(
+            templates.render(
+                "discord",
+                MessageKind::Recovery,
+                &sample(MessageKind::Recovery)
+            ),
+            "Recovered after 12.5s"
+        );
+        assert!(!templates.has("slack", MessageKind::Incident));
+    }
+
+    #[test]
+    fn overrides_are_validated_at_load() {
+        let templates = Templates::from_config(&overrides(
+            "slack",
+            "alert",
+            "{{ severity|upper }}: {{ rule }}",
+        ))
+        .unwrap();
+        assert_eq!(
+            templates.render("slack", MessageKind::Alert, &sample(MessageKind::Alert)),
+            "HIGH: fork_storm"
+        );
+
+        assert!(Templates::from_config(&overrides("slack", "alert", "{% if %}")).is_err());
+        assert!(
+            Templates::from_config(&overrides("slack", "alert", "{{ rule|nosuchfilter }}"))
+                .is_err()
+        );
+        assert!(Templates::from_config(&overrides("teams", "alert", "x")).is_err());
+        assert!(Templates::from_config(&overrides("slack", "incident", "x")).is_err());
+    }
+}
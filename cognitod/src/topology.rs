@@ -0,0 +1,529 @@
+//! Service dependency graph
+//!
+//! Built from the TCP socket table of every network namespace on the node,
+//! sampled every `interval_secs`. Each established connection becomes an edge
+//! from the connecting side to the accepting side. Processes are grouped by
+//! pod, or by comm outside pods; peers off the node are `remote` endpoints.
+//! Edge volume is the change of the sockets' `tcp_info` byte counters
+//! between samples, so connections that open and close within one interval
+//! are not seen. Edges idle for `retention_secs` are dropped.
+
+use log::{debug, info, warn};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::collectors::sock_diag::{self, TcpSocket, TcpState};
+use crate::config::TopologyConfig;
+use crate::context::ContextStore;
+use crate::enrichment::PeerInfo;
+use crate::utils::now_secs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EndpointKind {
+    Pod,
+    Process,
+    Remote,
+}
+
+/// A node of the graph.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub struct Endpoint {
+    /// `pod/<namespace>/<name>`, `process/<comm>` or `remote/<address>`.
+    pub id: String,
+    pub kind: EndpointKind,
+    pub label: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    /// Reverse DNS and GeoIP names of a remote endpoint, when known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peer: Option<PeerInfo>,
+}
+
+impl Endpoint {
+    pub fn pod(namespace: &str, name: &str) -> Self {
+        Self {
+            id: format!("pod/{namespace}/{name}"),
+            kind: EndpointKind::Pod,
+            label: name.to_string(),
+            namespace: Some(namespace.to_string()),
+            peer: None,
+        }
+    }
+
+    pub fn process(comm: &str) -> Self {
+        Self {
+            id: format!("process/{comm}"),
+            kind: EndpointKind::Process,
+            label: comm.to_string(),
+            namespace: None,
+            peer: None,
+        }
+    }
+
+    pub fn remote(address: String) -> Self {
+        Self {
+            id: format!("remote/{address}"),
+            kind: EndpointKind::Remote,
+            label: address,
+            namespace: None,
+            peer: None,
+        }
+    }
+}
+
+/// Sockets of one network namespace.
+#[derive(Debug, Clone)]
+pub struct NamespaceSockets {
+    pub netns: u64,
+    pub sockets: Vec<TcpSocket>,
+}
+
+/// Client-to-server traffic between two endpoints.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Edge {
+    pub source: String,
+    pub target: String,
+    /// Server port, when the target is identified by it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Distinct connections seen.
+    pub connections: u64,
+    /// Connections open at the last sample.
+    pub active: u64,
+    /// Bytes from source to target.
+    pub bytes_sent: u64,
+    /// Bytes from target to source.
+    pub bytes_received: u64,
+    pub last_seen: u64,
+}
+
+/// Response of `/topology`.
+#[derive(Debug, Clone, Serialize)]
+pub struct TopologyReport {
+    pub interval_secs: u64,
+    pub retention_secs: u64,
+    pub nodes: Vec<Endpoint>,
+    pub edges: Vec<Edge>,
+}
+
+/// Byte counters of one tracked socket at the last sample.
+struct TrackedSocket {
+    edge: (String, String),
+    bytes_sent: u64,
+    bytes_received: u64,
+}
+
+#[derive(Default)]
+struct Graph {
+    nodes: HashMap<String, Endpoint>,
+    edges: HashMap<(String, String), Edge>,
+    sockets: HashMap<u64, TrackedSocket>,
+    /// Latest endpoint of each socket-owning pid.
+    pids: HashMap<u32, String>,
+}
+
+/// Where a connection terminates on this node.
+#[derive(Default)]
+struct Listeners {
+    /// Listening `(address, port)`, wildcards expanded to the namespace's
+    /// addresses.
+    by_addr: HashMap<(IpAddr, u16), Endpoint>,
+    /// Loopback listeners per namespace.
+    loopback: HashMap<(u64, u16), Endpoint>,
+    /// Ports accepting connections, per namespace.
+    ports: HashSet<(u64, u16)>,
+    /// Addresses of every namespace on the node.
+    local: HashSet<IpAddr>,
+}
+
+impl Listeners {
+    fn build(namespaces: &[NamespaceSockets], owner: &impl Fn(u64) -> Option<Endpoint>) -> Self {
+        let mut listeners = Self::default();
+        for ns in namespaces {
+            let addrs: HashSet<IpAddr> = ns
+                .sockets
+                .iter()
+                .map(|s| s.local.ip())
+                .filter(|ip| !ip.is_unspecified() && !ip.is_loopback())
+                .collect();
+            for socket in ns.sockets.iter().filter(|s| s.state == TcpState::Listen) {
+                let port = socket.local.port();
+                listeners.ports.insert((ns.netns, port));
+                let Some(endpoint) = owner(socket.inode) else {
+                    continue;
+                };
+                let ip = socket.local.ip();
+                if ip.is_unspecified() || ip.is_loopback() {
+                    listeners
+                        .loopback
+                        .insert((ns.netns, port), endpoint.clone());
+                }
+                if ip.is_unspecified() {
+                    for addr in &addrs {
+                        listeners.by_addr.insert((*addr, port), endpoint.clone());
+                    }
+                } else if !ip.is_loopback() {
+                    listeners.by_addr.insert((ip, port), endpoint);
+                }
+            }
+            listeners.local.extend(addrs);
+        }
+        listeners
+    }
+
+    fn is_local(&self, ip: IpAddr) -> bool {
+        ip.is_loopback() || self.local.contains(&ip)
+    }
+}
+
+pub struct ServiceGraph {
+    interval_secs: u64,
+    retention_secs: u64,
+    graph: Mutex<Graph>,
+}
+
+impl ServiceGraph {
+    pub fn new(config: &TopologyConfig) -> Self {
+        Self {
+            interval_secs: config.interval_secs.max(1),
+            retention_secs: config.retention_secs,
+            graph: Mutex::new(Graph::default()),
+        }
+    }
+
+    /// Fold one sample of the socket tables into the graph. `owners` maps
+    /// socket inodes to pids and `resolve` names the endpoint of a pid.
+    pub fn update(
+        &self,
+        namespaces: &[NamespaceSockets],
+        owners: &HashMap<u64, u32>,
+        resolve: impl Fn(u32) -> Endpoint,
+        now: u64,
+    ) {
+        let mut resolved: HashMap<u32, Endpoint> = HashMap::new();
+        let mut owner = |inode: u64| -> Option<Endpoint> {
+            let pid = *owners.get(&inode)?;
+            Some(resolved.entry(pid).or_insert_with(|| resolve(pid)).clone())
+        };
+        // Listener owners are resolved up front so the closure can be reused
+        let mut listener_owners = HashMap::new();
+        for ns in namespaces {
+            for socket in ns.sockets.iter().filter(|s| s.state == TcpState::Listen) {
+                if let Some(endpoint) = owner(socket.inode) {
+                    listener_owners.insert(socket.inode, endpoint);
+                }
+            }
+        }
+        let listeners = Listeners::build(namespaces, &|inode| listener_owners.get(&inode).cloned());
+
+        let mut graph = self.graph.lock().unwrap();
+        let mut seen = HashSet::new();
+        let mut active: HashMap<(String, String), u64> = HashMap::new();
+        for ns in namespaces {
+            for socket in ns
+                .sockets
+                .iter()
+                .filter(|s| s.state == TcpState::Established)
+            {
+                let accepted = listeners.ports.contains(&(ns.netns, socket.local.port()));
+                let peer_local = listeners.is_local(socket.peer.ip());
+                // (client, server, port, bytes client->server, bytes server->client)
+                let (client, server, port, sent, received) = if accepted {
+                    if peer_local {
+                        // The connecting socket is on this node and seen too
+                        continue;
+                    }
+                    let Some(server) = owner(socket.inode) else {
+                        continue;
+                    };
+                    let client = Endpoint::remote(socket.peer.ip().to_string());
+                    let port = socket.local.port();
+                    (
+                        client,
+                        server,
+                        Some(port),
+                        socket.bytes_received,
+                        socket.bytes_acked,
+                    )
+                } else {
+                    let Some(client) = owner(socket.inode) else {
+                        continue;
+                    };
+                    let peer = socket.peer;
+                    let server = if peer.ip().is_loopback() {
+                        listeners.loopback.get(&(ns.netns, peer.port())).cloned()
+                    } else {
+                        listeners.by_addr.get(&(peer.ip(), peer.port())).cloned()
+                    };
+                    let server = server.unwrap_or_else(|| Endpoint::remote(peer.to_string()));
+                    (
+                        client,
+                        server,
+                        Some(peer.port()),
+                        socket.bytes_acked,
+                        socket.bytes_received,
+                    )
+                };
+                if client.id == server.id {
+                    continue;
+                }
+                let key = (client.id.clone(), server.id.clone());
+                graph.nodes.insert(client.id.clone(), client);
+                graph.nodes.insert(server.id.clone(), server);
+                seen.insert(socket.inode);
+                *active.entry(key.clone()).or_default() += 1;
+
+                let (delta_sent, delta_received, new) = match graph.sockets.get(&socket.inode) {
+                    Some(prev) if prev.edge == key => (
+                        counter_delta(prev.bytes_sent, sent),
+                        counter_delta(prev.bytes_received, received),
+                        false,
+                    ),
+                    _ => (sent, received, true),
+                };
+                graph.sockets.insert(
+                    socket.inode,
+                    TrackedSocket {
+                        edge: key.clone(),
+                        bytes_sent: sent,
+                        bytes_received: received,
+                    },
+                );
+                let edge = graph.edges.entry(key.clone()).or_insert_with(|| Edge {
+                    source: key.0.clone(),
+                    target: key.1.clone(),
+                    port,
+                    ..Edge::default()
+                });
+                if edge.port != port {
+                    // Several server ports behind one endpoint
+                    edge.port = None;
+                }
+                edge.connections += new as u64;
+                edge.bytes_sent += delta_sent;
+                edge.bytes_received += delta_received;
+                edge.last_seen = now;
+            }
+        }
+
+        for (pid, endpoint) in resolved {
+            graph.pids.insert(pid, endpoint.id);
+        }
+        graph.sockets.retain(|inode, _| seen.contains(inode));
+        for (key, edge) in graph.edges.iter_mut() {
+            edge.active = active.get(key).copied().unwrap_or(0);
+        }
+        let retention = self.retention_secs;
+        graph
+            .edges
+            .retain(|_, edge| now.saturating_sub(edge.last_seen) <= retention);
+        let Graph {
+            nodes, edges, pids, ..
+        } = &mut *graph;
+        let linked: HashSet<&String> = edges.keys().flat_map(|(s, t)| [s, t]).collect();
+        nodes.retain(|id, _| linked.contains(id));
+        pids.retain(|_, id| linked.contains(id));
+    }
+
+    pub fn report(&self) -> TopologyReport {
+        let graph = self.graph.lock().unwrap();
+        let mut nodes: Vec<Endpoint> = graph.nodes.values().cloned().collect();
+        nodes.sort();
+        let mut edges: Vec<Edge> = graph.edges.values().cloned().collect();
+        edges.sort_by(|a, b| (&a.source, &a.target).cmp(&(&b.source, &b.target)));
+        TopologyReport {
+            interval_secs: self.interval_secs,
+            retention_secs: self.retention_secs,
+            nodes,
+            edges,
+        }
+    }
+
+    /// Endpoints that connect to `endpoint` and lose their upstream if it
+    /// goes away, busiest first.
+    pub fn dependents(&self, endpoint: &str) -> Vec<String> {
+        let graph = self.graph.lock().unwrap();
+        let mut clients: BTreeMap<&str, u64> = BTreeMap::new();
+        for edge in graph.edges.values().filter(|e| e.target == endpoint) {
+            *clients.entry(&edge.source).or_default() += edge.bytes_sent + edge.bytes_received;
+        }
+        let mut clients: Vec<(&str, u64)> = clients.into_iter().collect();
+        clients.sort_by_key(|&(_, bytes)| std::cmp::Reverse(bytes));
+        clients.into_iter().map(|(id, _)| id.to_string()).collect()
+    }
+
+    /// Dependents of the endpoint `pid` was last seen as.
+    pub fn dependents_of_pid(&self, pid: u32) -> Vec<String> {
+        let endpoint = self.graph.lock().unwrap().pids.get(&pid).cloned();
+        endpoint.map_or_else(Vec::new, |id| self.dependents(&id))
+    }
+
+    fn sample(&self, context: &ContextStore) {
+        let proc_root = Path::new("/proc");
+        let (owners, netns) = sock_diag::scan_proc(proc_root);
+        let mut namespaces = Vec::with_capacity(netns.len());
+        for (ns, pid) in netns {
+            match sock_diag::dump_in(&proc_root.join(format!("{pid}/ns/net"))) {
+                Ok(sockets) => namespaces.push(NamespaceSockets { netns: ns, sockets }),
+                Err(e) => debug!("[topology] socket dump of netns {ns} via pid {pid} failed: {e}"),
+            }
+        }
+        let live = context.get_live_map();
+        let resolve = |pid: u32| match live.get(&pid) {
+            Some((_, Some(meta))) => Endpoint::pod(&meta.namespace, &meta.pod_name),
+            Some((event, None)) => match &event.script_path {
+                Some(path) => Endpoint::process(crate::script::script_name(path)),
+                None => Endpoint::process(
+                    std::str::from_utf8(&event.comm)
+                        .unwrap_or("invalid")
+                        .trim_end_matches('\0'),
+                ),
+            },
+            None => Endpoint::process(
+                &std::fs::read_to_string(proc_root.join(format!("{pid}/comm")))
+                    .map(|c| c.trim().to_string())
+                    .unwrap_or_else(|_| format!("pid-{pid}")),
+            ),
+        };
+        self.update(&namespaces, &owners, resolve, now_secs());
+    }
+
+    async fn run(self: Arc<Self>, context: Arc<ContextStore>) {
+        if let Err(e) = sock_diag::dump() {
+            warn!("[topology] sock_diag unavailable, service graph disabled: {e}");
+            return;
+        }
+        info!(
+            "[topology] sampling connections every {}s",
+            self.interval_secs
+        );
+        let mut tick = tokio::time::interval(Duration::from_secs(self.interval_secs));
+        loop {
+            tick.tick().await;
+            let graph = Arc::clone(&self);
+            let context = Arc::clone(&context);
+            if let Err(e) = tokio::task::spawn_blocking(move || graph.sample(&context)).await {
+                warn!("[topology] sample failed: {e}");
+            }
+        }
+    }
+}
+
+/// Growth of a socket byte counter; a smaller value means the inode was
+/// reused by a new socket.
+fn counter_delta(prev: u64, current: u64) -> u64 {
+    if current >= prev {
+        current - prev
+    } else {
+        current
+    }
+}
+
+pub fn spawn(context: Arc<ContextStore>, config: &TopologyConfig) -> Arc<ServiceGraph> {
+    let graph = Arc::new(ServiceGraph::new(config));
+    tokio::spawn(Arc::clone(&graph).run(context));
+    graph
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn socket(state: TcpState, local: &str, peer: &str, inode: u64, sent: u64) -> TcpSocket {
+        TcpSocket {
+            state,
+            local: local.parse().unwrap(),
+            peer: peer.parse().unwrap(),
+            inode,
+            bytes_acked: sent,
+            bytes_received: sent / 10,
+        }
+    }
+
+    #[test]
+    fn connections_become_client_server_edges() {
+        let graph = ServiceGraph::new(&TopologyConfig::default());
+        let sample = |api_sent: u64| {
+            vec![
+                // Database pod listening on all addresses
+                NamespaceSockets {
+                    netns: 1,
+                    sockets: vec![
+                        socket(TcpState::Listen, "0.0.0.0:5432", "0.0.0.0:0", 10, 0),
+                        socket(
+                            TcpState::Established,
+                            "10.1.0.2:5432",
+                            "10.1.0.3:40000",
+                            11,
+                            0,
+                        ),
+                        socket(
+                            TcpState::Established,
+                            "10.1.0.2:5432",
+                            "192.0.2.7:50000",
+                            12,
+                            80,
+                        ),
+                    ],
+                },
+                // API pod calling the database and an external service
+                NamespaceSockets {
+                    netns: 2,
+                    sockets: vec![
+                        socket(
+                            TcpState::Established,
+                            "10.1.0.3:40000",
+                            "10.1.0.2:5432",
+                            20,
+                            api_sent,
+                        ),
+                        socket(
+                            TcpState::Established,
+                            "10.1.0.3:40001",
+                            "203.0.113.9:443",
+                            21,
+                            50,
+                        ),
+                    ],
+                },
+            ]
+        };
+        let owners = HashMap::from([(10, 100), (11, 100), (12, 100), (20, 200), (21, 200)]);
+        let resolve = |pid: u32| match pid {
+            100 => Endpoint::pod("data", "postgres-0"),
+            _ => Endpoint::pod("shop", "api-1"),
+        };
+        graph.update(&sample(1000), &owners, resolve, 100);
+        graph.update(&sample(1500), &owners, resolve, 115);
+
+        let report = graph.report();
+        let edge = |source: &str, target: &str| {
+            report
+                .edges
+                .iter()
+                .find(|e| e.source == source && e.target == target)
+                .unwrap_or_else(|| panic!("no edge {source} -> {target}: {:?}", report.edges))
+        };
+        let db = edge("pod/shop/api-1", "pod/data/postgres-0");
+        assert_eq!((db.connections, db.active, db.port), (1, 1, Some(5432)));
+        assert_eq!((db.bytes_sent, db.bytes_received), (1500, 150));
+        edge("pod/shop/api-1", "remote/203.0.113.9:443");
+        let inbound = edge("remote/192.0.2.7", "pod/data/postgres-0");
+        assert_eq!(inbound.bytes_sent, 8);
+        assert_eq!(report.edges.len(), 3);
+        assert_eq!(report.nodes.len(), 4);
+
+        let mut dependents = graph.dependents_of_pid(100);
+        dependents.sort();
+        assert_eq!(dependents, ["pod/shop/api-1", "remote/192.0.2.7"]);
+
+        // Edges idle past retention are dropped
+        graph.update(&[], &owners, resolve, 115 + 3601);
+        assert!(graph.report().edges.is_empty());
+    }
+}
@@ -1,13 +1,56 @@
 /// UI Dashboard module for embedded web interface
 ///
 /// Serves a single-page application with real-time process monitoring,
-/// alert visualization, and system metrics.
-use axum::response::{Html, IntoResponse};
+/// alert visualization, and system metrics. The page is rendered per viewer:
+/// only the sections `[ui]` allows for the viewer's token scope are shown,
+/// and approve/reject buttons only to operators.
+use axum::response::Html;
+use serde::Serialize;
+
+use crate::config::{DashboardSection, TokenScope, UiConfig};
 
 /// Embedded dashboard HTML
 const DASHBOARD_HTML: &str = include_str!("dashboard.html");
 
-/// Serve the main dashboard page
-pub async fn dashboard_handler() -> impl IntoResponse {
-    Html(DASHBOARD_HTML)
+/// Replaced with the viewer's [`View`] as a JavaScript object
+const VIEW_PLACEHOLDER: &str = "__LINNIX_VIEW__";
+
+#[derive(Serialize)]
+struct View<'a> {
+    scope: TokenScope,
+    sections: &'a [DashboardSection],
+    /// Whether enforcement actions can be approved or rejected
+    controls: bool,
+}
+
+/// The dashboard page as seen by a viewer with `scope`
+pub fn dashboard(scope: TokenScope, config: &UiConfig) -> Html<String> {
+    let view = View {
+        scope,
+        sections: config.sections(scope),
+        controls: scope == TokenScope::Operator,
+    };
+    let view = serde_json::to_string(&view).unwrap_or_else(|_| "{}".to_string());
+    Html(DASHBOARD_HTML.replacen(VIEW_PLACEHOLDER, &view, 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_only_view_has_no_controls() {
+        let config = UiConfig {
+            read_only_sections: vec![DashboardSection::Overview, DashboardSection::Actions],
+            ..UiConfig::default()
+        };
+        let Html(page) = dashboard(TokenScope::ReadOnly, &config);
+        assert!(!page.contains(VIEW_PLACEHOLDER));
+        assert!(page.contains(
+            r#"{"scope":"read_only","sections":["overview","actions"],"controls":false}"#
+        ));
+
+        let Html(page) = dashboard(TokenScope::Operator, &config);
+        assert!(page.contains(r#""controls":true"#));
+    }
 }
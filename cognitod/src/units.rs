@@ -0,0 +1,136 @@
+//! Typed quantities for API responses
+//!
+//! Sizes, shares and rates used to go out as bare numbers whose unit was
+//! only in the field name (`rss_mb`, `cpu_total_pct`), sometimes truncated
+//! to whole megabytes and sometimes formatted as strings. The wrappers here
+//! serialize every quantity the same way, as its value in the base unit
+//! together with that unit:
+//!
+//! ```json
+//! {"value": 73400320, "unit": "bytes"}
+//! {"value": 12.5, "unit": "percent"}
+//! {"value": 1830.0, "unit": "per_second"}
+//! ```
+
+use serde::Serialize;
+use serde::ser::{SerializeStruct, Serializer};
+
+fn quantity<S: Serializer, V: Serialize>(
+    serializer: S,
+    value: V,
+    unit: &'static str,
+) -> Result<S::Ok, S::Error> {
+    let mut state = serializer.serialize_struct("Quantity", 2)?;
+    state.serialize_field("value", &value)?;
+    state.serialize_field("unit", unit)?;
+    state.end()
+}
+
+/// Rounds away the noise of `f32` samples widened to `f64`.
+fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+/// A size in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Bytes(pub u64);
+
+impl Bytes {
+    pub fn from_kib(kib: u64) -> Self {
+        Self(kib.saturating_mul(1024))
+    }
+}
+
+impl Serialize for Bytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        quantity(serializer, self.0, "bytes")
+    }
+}
+
+/// A share from 0 to 100, or above 100 for CPU time across several cores.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Percent(pub f64);
+
+impl From<f32> for Percent {
+    fn from(value: f32) -> Self {
+        Self(f64::from(value))
+    }
+}
+
+impl Serialize for Percent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        quantity(serializer, round2(self.0), "percent")
+    }
+}
+
+/// What a [`Rate`] counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateUnit {
+    /// Events, requests or other occurrences
+    PerSecond,
+    BytesPerSecond,
+}
+
+impl RateUnit {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RateUnit::PerSecond => "per_second",
+            RateUnit::BytesPerSecond => "bytes_per_second",
+        }
+    }
+}
+
+/// Occurrences or bytes per second.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rate {
+    pub value: f64,
+    pub unit: RateUnit,
+}
+
+impl Rate {
+    pub fn per_second(value: f64) -> Self {
+        Self {
+            value,
+            unit: RateUnit::PerSecond,
+        }
+    }
+
+    pub fn bytes_per_second(value: f64) -> Self {
+        Self {
+            value,
+            unit: RateUnit::BytesPerSecond,
+        }
+    }
+}
+
+impl Serialize for Rate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        quantity(serializer, round2(self.value), self.unit.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn quantities_serialize_with_their_unit() {
+        assert_eq!(
+            serde_json::to_value(Bytes::from_kib(2048)).unwrap(),
+            json!({"value": 2_097_152, "unit": "bytes"})
+        );
+        assert_eq!(
+            serde_json::to_value(Percent::from(12.3_f32)).unwrap(),
+            json!({"value": 12.3, "unit": "percent"})
+        );
+        assert_eq!(
+            serde_json::to_value(Rate::per_second(1830.0)).unwrap(),
+            json!({"value": 1830.0, "unit": "per_second"})
+        );
+        assert_eq!(
+            serde_json::to_value(Rate::bytes_per_second(0.125)).unwrap()["unit"],
+            "bytes_per_second"
+        );
+    }
+}
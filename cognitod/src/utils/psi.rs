@@ -107,7 +107,7 @@ impl PsiMetrics {
 ///
 /// Input: "some avg10=5.23 avg60=3.45 avg300=2.11 total=123456"
 /// Output: Some(5.23)
-fn parse_avg10(content: &str, line_prefix: &str) -> Option<f32> {
+pub(crate) fn parse_avg10(content: &str, line_prefix: &str) -> Option<f32> {
     for line in content.lines() {
         if line.starts_with(line_prefix) {
             // Line format: "some avg10=5.23 avg60=..."
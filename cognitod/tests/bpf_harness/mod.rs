@@ -0,0 +1,226 @@
+//! Helpers for loading the BPF object into the running kernel and driving its
+//! programs. Shared by the root-only `bpf-tests` integration tests.
+
+#![allow(dead_code)]
+
+use std::io;
+use std::os::fd::{AsFd, AsRawFd};
+use std::time::{Duration, Instant};
+
+use aya::maps::{Array, Map, MapData};
+use aya::programs::Program;
+use aya::{Btf, Ebpf, EbpfLoader, Pod};
+use cognitod::bpf_config::derive_telemetry_config;
+use cognitod::runtime::sequencer::SequencerConsumer;
+use linnix_ai_ebpf_common::{ProcessEvent, TelemetryConfig};
+
+/// Where `cargo xtask build-ebpf` leaves the object, relative to this crate.
+pub const DEFAULT_OBJECT: &str = "../target/bpfel-unknown-none/release/linnix-ai-ebpf-ebpf";
+
+/// `BPF_PROG_TEST_RUN` command of the bpf(2) syscall.
+const BPF_PROG_TEST_RUN: libc::c_long = 10;
+/// Kernel-internal "operation not supported", returned by `BPF_PROG_TEST_RUN`
+/// for program types without a test runner.
+const ENOTSUPP: i32 = 524;
+
+#[repr(transparent)]
+#[derive(Copy, Clone)]
+struct TelemetryConfigPod(TelemetryConfig);
+
+unsafe impl Pod for TelemetryConfigPod {}
+
+pub fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// The compiled object from `LINNIX_BPF_PATH`, or the xtask build output.
+pub fn read_object() -> Vec<u8> {
+    let path = std::env::var("LINNIX_BPF_PATH").unwrap_or_else(|_| DEFAULT_OBJECT.to_string());
+    std::fs::read(&path).unwrap_or_else(|e| {
+        panic!("cannot read BPF object {path}: {e}; run `cargo xtask build-ebpf` or set LINNIX_BPF_PATH")
+    })
+}
+
+/// Parse the object with `TELEMETRY_CONFIG` derived from the kernel's BTF,
+/// as the daemon does. Nothing is loaded into the kernel yet.
+pub fn open_object() -> (Ebpf, TelemetryConfig) {
+    let telemetry = derive_telemetry_config()
+        .expect("derive telemetry config from kernel BTF")
+        .config;
+    let pod = TelemetryConfigPod(telemetry);
+    let mut loader = EbpfLoader::new();
+    loader.set_global("TELEMETRY_CONFIG", &pod, true);
+    let ebpf = loader.load(&read_object()).expect("parse BPF object");
+    (ebpf, telemetry)
+}
+
+/// Kernel tracepoint each BTF program hooks, and whether it only exists on
+/// x86 (the page fault tracepoints).
+const BTF_TARGETS: &[(&str, &str, bool)] = &[
+    ("handle_exec_raw", "sched_process_exec", false),
+    ("handle_fork_raw", "sched_process_fork", false),
+    ("handle_exit_raw", "sched_process_exit", false),
+    ("trace_page_fault_user", "page_fault_user", true),
+    ("trace_page_fault_kernel", "page_fault_kernel", true),
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LoadOutcome {
+    Loaded,
+    /// Not loadable on this kernel or architecture, for a known reason.
+    Skipped(&'static str),
+}
+
+/// Load one program into the kernel, running it through the verifier.
+pub fn load_program(
+    name: &str,
+    program: &mut Program,
+    btf: Option<&Btf>,
+) -> anyhow::Result<LoadOutcome> {
+    match program {
+        Program::TracePoint(p) => p.load()?,
+        Program::KProbe(p) => p.load()?,
+        Program::BtfTracePoint(p) => {
+            let Some(&(_, target, x86_only)) = BTF_TARGETS.iter().find(|(n, _, _)| *n == name)
+            else {
+                anyhow::bail!("no tracepoint known for BTF program {name}; add it to BTF_TARGETS");
+            };
+            if x86_only && !cfg!(target_arch = "x86_64") {
+                return Ok(LoadOutcome::Skipped("x86-only tracepoint"));
+            }
+            let Some(btf) = btf else {
+                return Ok(LoadOutcome::Skipped("kernel has no BTF"));
+            };
+            p.load(target, btf)?
+        }
+        other => anyhow::bail!("unexpected program type for {name}: {other:?}"),
+    }
+    Ok(LoadOutcome::Loaded)
+}
+
+/// Whether the kernel can run programs of this type via
+/// `BPF_PROG_TEST_RUN`. Tracepoint and kprobe programs have no test runner,
+/// and the tracing runner only drives fentry/fexit, not `tp_btf`.
+pub fn supports_test_run(program: &Program) -> bool {
+    !matches!(
+        program,
+        Program::TracePoint(_) | Program::KProbe(_) | Program::BtfTracePoint(_)
+    )
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TestRun {
+    Ran {
+        retval: u32,
+        duration_ns: u32,
+    },
+    /// The kernel has no test runner for the program type.
+    Unsupported,
+}
+
+#[repr(C, align(8))]
+#[derive(Default)]
+struct TestRunAttr {
+    prog_fd: u32,
+    retval: u32,
+    data_size_in: u32,
+    data_size_out: u32,
+    data_in: u64,
+    data_out: u64,
+    repeat: u32,
+    duration: u32,
+    ctx_size_in: u32,
+    ctx_size_out: u32,
+    ctx_in: u64,
+    ctx_out: u64,
+    flags: u32,
+    cpu: u32,
+    batch_size: u32,
+    _pad: u32,
+}
+
+/// Run a loaded program once via `BPF_PROG_TEST_RUN` with a synthetic
+/// context and packet data (either may be empty).
+pub fn test_run(program: &Program, ctx: &[u8], data: &[u8]) -> anyhow::Result<TestRun> {
+    let fd = program.fd()?.as_fd().as_raw_fd();
+    let mut attr = TestRunAttr {
+        prog_fd: fd as u32,
+        data_size_in: data.len() as u32,
+        data_in: data.as_ptr() as u64,
+        ctx_size_in: ctx.len() as u32,
+        ctx_in: ctx.as_ptr() as u64,
+        repeat: 1,
+        ..Default::default()
+    };
+    let rc = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            BPF_PROG_TEST_RUN,
+            &mut attr as *mut TestRunAttr,
+            std::mem::size_of::<TestRunAttr>(),
+        )
+    };
+    if rc == 0 {
+        return Ok(TestRun::Ran {
+            retval: attr.retval,
+            duration_ns: attr.duration,
+        });
+    }
+    let err = io::Error::last_os_error();
+    match err.raw_os_error() {
+        Some(ENOTSUPP) | Some(libc::EOPNOTSUPP) => Ok(TestRun::Unsupported),
+        _ => Err(err.into()),
+    }
+}
+
+/// The mmap'd sequencer ring of a loaded object. Keeps the map alive for as
+/// long as the consumer reads it.
+pub struct Sequencer {
+    _map: MapData,
+    consumer: SequencerConsumer,
+}
+
+impl Sequencer {
+    /// Map the ring and switch the programs over to it. The consumer zeroes
+    /// the ring, so it must exist before the sequencer is enabled.
+    pub fn enable(ebpf: &mut Ebpf) -> anyhow::Result<Self> {
+        let map = match ebpf.take_map("SEQUENCER_RING") {
+            Some(Map::Array(data)) => data,
+            other => anyhow::bail!("SEQUENCER_RING missing or not an array: {other:?}"),
+        };
+        let consumer = SequencerConsumer::from_fd(map.fd().as_fd())?;
+        let mut enabled: Array<_, u32> = Array::try_from(
+            ebpf.map_mut("SEQUENCER_ENABLED")
+                .ok_or_else(|| anyhow::anyhow!("SEQUENCER_ENABLED missing"))?,
+        )?;
+        enabled.set(0, 1, 0)?;
+        Ok(Self {
+            _map: map,
+            consumer,
+        })
+    }
+
+    /// Events matching `keep` until `done` holds for them or `timeout`
+    /// passes.
+    pub fn collect(
+        &mut self,
+        timeout: Duration,
+        keep: impl Fn(&ProcessEvent) -> bool,
+        done: impl Fn(&[ProcessEvent]) -> bool,
+    ) -> Vec<ProcessEvent> {
+        let deadline = Instant::now() + timeout;
+        let mut events = Vec::new();
+        while Instant::now() < deadline && !done(&events) {
+            let batch = self.consumer.poll_batch(256);
+            if batch.is_empty() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            events.extend(batch.into_iter().filter(&keep));
+        }
+        events
+    }
+
+    pub fn ordering_violations(&self) -> u64 {
+        self.consumer.stats().ordering_violations
+    }
+}
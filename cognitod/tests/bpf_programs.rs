@@ -0,0 +1,194 @@
+//! In-kernel tests of the BPF object
+//!
+//! Need root, an object built by `cargo xtask build-ebpf` (or
+//! `LINNIX_BPF_PATH`) and the `bpf-tests` feature:
+//!
+//! ```sh
+//! CARGO_TARGET_X86_64_UNKNOWN_LINUX_GNU_RUNNER="sudo -E" \
+//!     cargo test -p cognitod --features bpf-tests --test bpf_programs
+//! ```
+//!
+//! Every program must pass the verifier of the running kernel. Program types
+//! the kernel can test-run are driven through `BPF_PROG_TEST_RUN` with
+//! synthetic contexts. The process lifecycle programs are tracepoints, which
+//! it cannot, so they are attached and fed by spawning real processes.
+
+#![cfg(feature = "bpf-tests")]
+
+mod bpf_harness;
+
+use std::os::unix::process::ExitStatusExt;
+use std::process::Command;
+use std::time::Duration;
+
+use aya::programs::{BtfTracePoint, Program, TracePoint};
+use aya::{Btf, Pod};
+use bpf_harness::{LoadOutcome, Sequencer, TestRun};
+use linnix_ai_ebpf_common::{EventType, ExecDetails, ProcessEvent, exit_status};
+
+fn skip_without_root(test: &str) -> bool {
+    if bpf_harness::is_root() {
+        return false;
+    }
+    eprintln!("skipping {test} (requires root)");
+    true
+}
+
+#[test]
+fn every_program_passes_the_verifier() {
+    if skip_without_root("verifier test") {
+        return;
+    }
+    let (mut ebpf, _) = bpf_harness::open_object();
+    let btf = Btf::from_sys_fs().ok();
+    let mut failures = Vec::new();
+    let mut loaded = 0;
+    for (name, program) in ebpf.programs_mut() {
+        match bpf_harness::load_program(name, program, btf.as_ref()) {
+            Ok(LoadOutcome::Loaded) => loaded += 1,
+            Ok(LoadOutcome::Skipped(reason)) => eprintln!("{name}: skipped ({reason})"),
+            // {:#} keeps the verifier log carried in the error chain
+            Err(e) => failures.push(format!("{name}: {e:#}")),
+        }
+    }
+    assert!(failures.is_empty(), "rejected:\n{}", failures.join("\n"));
+    assert!(loaded > 0, "object contains no programs");
+}
+
+#[test]
+fn programs_run_on_synthetic_contexts_where_supported() {
+    if skip_without_root("test-run test") {
+        return;
+    }
+    let (mut ebpf, _) = bpf_harness::open_object();
+    let btf = Btf::from_sys_fs().ok();
+    let names: Vec<String> = ebpf.programs().map(|(name, _)| name.to_string()).collect();
+    for name in names {
+        let program = ebpf.program_mut(&name).unwrap();
+        if bpf_harness::load_program(&name, program, btf.as_ref()).unwrap() != LoadOutcome::Loaded {
+            continue;
+        }
+        let program = ebpf.program(&name).unwrap();
+        if matches!(program, Program::BtfTracePoint(_)) {
+            // The tracing runner has nothing to drive a tp_btf program with
+            continue;
+        }
+        // Zeroed tracepoint record or packet; handlers bail out on pid 0.
+        let ctx = [0u8; 64];
+        let outcome = bpf_harness::test_run(program, &ctx, &[])
+            .unwrap_or_else(|e| panic!("{name}: BPF_PROG_TEST_RUN failed: {e:#}"));
+        if bpf_harness::supports_test_run(program) {
+            assert!(
+                matches!(outcome, TestRun::Ran { retval: 0, .. }),
+                "{name}: {outcome:?}"
+            );
+        } else {
+            // Confirms the syscall reached the program: a bad fd or attr
+            // fails with EBADF/EINVAL instead
+            assert_eq!(outcome, TestRun::Unsupported, "{name}");
+        }
+    }
+}
+
+/// Load and attach the exec, fork and exit handlers, preferring the BTF
+/// variants as the daemon does.
+fn attach_lifecycle(ebpf: &mut aya::Ebpf) {
+    let btf = Btf::from_sys_fs().ok();
+    for (raw, classic, tracepoint) in [
+        ("handle_exec_raw", "linnix_ai_ebpf", "sched_process_exec"),
+        ("handle_fork_raw", "handle_fork", "sched_process_fork"),
+        ("handle_exit_raw", "handle_exit", "sched_process_exit"),
+    ] {
+        match btf.as_ref() {
+            Some(btf) => {
+                let prog: &mut BtfTracePoint = ebpf.program_mut(raw).unwrap().try_into().unwrap();
+                prog.load(tracepoint, btf).unwrap();
+                prog.attach().unwrap();
+            }
+            None => {
+                let prog: &mut TracePoint = ebpf.program_mut(classic).unwrap().try_into().unwrap();
+                prog.load().unwrap();
+                prog.attach("sched", tracepoint).unwrap();
+            }
+        }
+    }
+}
+
+#[repr(transparent)]
+#[derive(Copy, Clone)]
+struct ExecDetailsPod(ExecDetails);
+
+unsafe impl Pod for ExecDetailsPod {}
+
+fn comm(event: &ProcessEvent) -> String {
+    String::from_utf8_lossy(&event.comm)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+#[test]
+fn lifecycle_events_land_in_the_sequencer_ring() {
+    if skip_without_root("lifecycle test") {
+        return;
+    }
+    let (mut ebpf, telemetry) = bpf_harness::open_object();
+    attach_lifecycle(&mut ebpf);
+    let mut ring = Sequencer::enable(&mut ebpf).unwrap();
+
+    let mut exited = Command::new("/bin/sh")
+        .args(["-c", "exit 3"])
+        .spawn()
+        .unwrap();
+    let exited_pid = exited.id();
+    assert_eq!(exited.wait().unwrap().code(), Some(3));
+    let mut crashed = Command::new("/bin/sh")
+        .args(["-c", "kill -SEGV $$"])
+        .spawn()
+        .unwrap();
+    let crashed_pid = crashed.id();
+    assert_eq!(crashed.wait().unwrap().signal(), Some(libc::SIGSEGV));
+
+    let pids = [exited_pid, crashed_pid];
+    let is_exit = |e: &ProcessEvent| e.event_type == EventType::Exit as u32;
+    let events = ring.collect(
+        Duration::from_secs(2),
+        |e| pids.contains(&e.pid),
+        |events| events.iter().filter(|e| is_exit(e)).count() == pids.len(),
+    );
+    let of = |pid: u32, kind: EventType| {
+        events
+            .iter()
+            .find(|e| e.pid == pid && e.event_type == kind as u32)
+            .unwrap_or_else(|| panic!("no {kind:?} event for pid {pid} in {events:?}"))
+    };
+
+    let fork = of(exited_pid, EventType::Fork);
+    assert_eq!(fork.ppid, std::process::id());
+    assert_ne!(fork.seq, 0, "sequencer events carry a per-CPU seq");
+    let exec = of(exited_pid, EventType::Exec);
+    assert_eq!(comm(exec), "sh");
+    let paths: aya::maps::HashMap<_, u32, ExecDetailsPod> =
+        aya::maps::HashMap::try_from(ebpf.map("EXEC_DETAILS").unwrap()).unwrap();
+    match paths.get(&exited_pid, 0) {
+        Ok(ExecDetailsPod(details)) => {
+            assert_eq!(details.ts_ns, exec.ts_ns);
+            assert_eq!(details.path(), Some("/bin/sh"));
+        }
+        Err(_) if telemetry.bprm_filename_offset == 0 => {
+            eprintln!("kernel BTF has no linux_binprm::filename; exec path not checked");
+        }
+        Err(e) => panic!("no exec path for pid {exited_pid}: {e}"),
+    }
+
+    let exit = of(exited_pid, EventType::Exit);
+    assert_ne!(exit.ts_ns, 0);
+    if telemetry.task_exit_code_offset != 0 {
+        assert_ne!(exit.aux2 & exit_status::VALID, 0);
+        assert_eq!(exit.aux, 3 << 8, "wait status of exit(3)");
+        let crash = of(crashed_pid, EventType::Exit);
+        assert_eq!(crash.aux & 0x7f, libc::SIGSEGV as u32);
+    } else {
+        eprintln!("kernel BTF has no task_struct::exit_code; exit status not checked");
+    }
+    assert_eq!(ring.ordering_violations(), 0);
+}
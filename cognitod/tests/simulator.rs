@@ -0,0 +1,76 @@
+#![cfg(feature = "simulator")]
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use cognitod::Metrics;
+use cognitod::alerts::RuleEngine;
+use cognitod::context::ContextStore;
+use cognitod::handler::HandlerList;
+use cognitod::simulator::{Scenario, Workload, inject};
+
+fn rules_file(yaml: &str) -> tempfile::NamedTempFile {
+    let file = tempfile::Builder::new().suffix(".yaml").tempfile().unwrap();
+    std::fs::write(file.path(), yaml).unwrap();
+    file
+}
+
+#[tokio::test(start_paused = true)]
+async fn fork_storm_trips_fork_burst_rule() {
+    let rules = rules_file(
+        "- name: fork_storm\n  detector: fork_burst\n  threshold: 50\n  window_seconds: 1\n  severity: high\n",
+    );
+    let context = Arc::new(ContextStore::new(Duration::from_secs(60), 4096, None));
+    let engine = RuleEngine::from_path(
+        rules.path().to_str().unwrap(),
+        "/dev/null".into(),
+        false,
+        Arc::new(Metrics::new()),
+    )
+    .unwrap()
+    .with_context_store(Arc::clone(&context));
+    let mut alerts = engine.broadcaster().subscribe();
+    let mut handlers = HandlerList::new();
+    handlers.register(engine);
+
+    // A slow trickle stays under the threshold
+    let calm = Scenario::new(3)
+        .at(Duration::ZERO, Workload::fork_storm(40, 10))
+        .events();
+    inject(&calm, &context, &handlers, true).await;
+    assert!(alerts.try_recv().is_err());
+
+    let storm = Scenario::new(3)
+        .at(Duration::ZERO, Workload::fork_storm(200, 200))
+        .events();
+    inject(&storm, &context, &handlers, true).await;
+    let alert = alerts.try_recv().expect("fork storm alert");
+    assert_eq!(alert.rule, "fork_storm");
+}
+
+#[tokio::test]
+async fn scenarios_populate_context_store() {
+    let context = ContextStore::new(Duration::from_secs(600), 16_384, None);
+    let events = Scenario::new(11)
+        .at(Duration::ZERO, Workload::leaky_process(30))
+        .at(Duration::from_secs(1), Workload::container_churn(10))
+        .at(Duration::from_secs(2), Workload::io_burst(100))
+        .events();
+    inject(&events, &context, &HandlerList::new(), false).await;
+
+    let live = context.live_snapshot();
+    let comm = |e: &cognitod::ProcessEvent| {
+        String::from_utf8_lossy(&e.comm)
+            .trim_end_matches('\0')
+            .to_string()
+    };
+    let leaky = live
+        .iter()
+        .find(|e| comm(e) == "leaky")
+        .expect("leaky live");
+    assert!(leaky.exit_time().is_none());
+    // 10 shims and 10 entrypoints exited; all stay visible for the grace period
+    let exited = live.iter().filter(|e| e.exit_time().is_some()).count();
+    assert_eq!(exited, 20);
+    assert_eq!(context.get_recent().len(), events.len());
+}
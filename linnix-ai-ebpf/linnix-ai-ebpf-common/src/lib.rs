@@ -22,8 +22,8 @@ use bytemuck::{Pod, Zeroable};
 //   [0..8]   flags: u64        - Slot state (EMPTY/WRITING/READY/ABANDONED)
 //   [8..16]  reserved_at_ns    - Timestamp when slot was reserved
 //   [16..24] ticket_id: u64    - Sequence number for ordering validation
-//   [24..120] event: ProcessEvent (96 bytes)
-//   [120..256] _padding        - Cache line alignment padding
+//   [24..128] event: ProcessEvent (104 bytes)
+//   [128..256] _padding        - Cache line alignment padding
 // =============================================================================
 
 /// Ring buffer size: 1 million slots (256MB total RAM)
@@ -33,6 +33,86 @@ pub const SEQUENCER_RING_SIZE: u32 = 1024 * 1024;
 /// Bit mask for wrapping index (RING_SIZE - 1)
 pub const SEQUENCER_RING_MASK: u32 = SEQUENCER_RING_SIZE - 1;
 
+/// Most rings the sequencer can be partitioned into, one per NUMA node.
+///
+/// Element 0 of `SEQUENCER_ENABLED` selects the mode: 0 is off, 1 is the
+/// single shared ring, N > 1 partitions producers by NUMA node across N
+/// rings (node `n` writes ring `n % N`). Partition 0 is `SEQUENCER_RING`,
+/// partition `p` is `SEQUENCER_RING_NODE<p>`. Each partition has its own
+/// ticket counter, so tickets order events within a partition only. The
+/// extra rings are declared with one slot; the loader grows them to
+/// `SEQUENCER_RING_SIZE` before enabling partitioning.
+pub const SEQUENCER_MAX_PARTITIONS: u32 = 4;
+
+/// Number of event types addressable by the userspace control maps
+/// (`SAMPLE_DIVISOR`, `SAMPLE_COUNTER`). Must cover every `EventType`.
+pub const CONTROL_EVENT_TYPES: u32 = 13;
+
+/// Capacity of the `PID_FILTER` map.
+pub const PID_FILTER_MAX_ENTRIES: u32 = 1024;
+
+/// Capacity of the `CGROUP_ALLOW` map.
+pub const CGROUP_ALLOW_MAX_ENTRIES: u32 = 64;
+
+/// Capacity of the per-process `TASK_STATS`, `PAGE_FAULT_THROTTLE`,
+/// `PAGE_FAULT_SUPPRESSED` and `FD_PRESSURE_THROTTLE` maps, and of the
+/// per-thread `NET_INFLIGHT` and `FILE_INFLIGHT`.
+pub const TASK_STATE_MAX_ENTRIES: u32 = 65_536;
+
+/// Capacity of the `EXEC_DETAILS` map. Userspace removes entries as it
+/// handles the exec events; the LRU evicts the ones it never gets to.
+pub const EXEC_DETAILS_MAX_ENTRIES: u32 = 4096;
+
+/// Bytes of the executable path kept in `ExecDetails`, NUL included.
+pub const EXEC_PATH_LEN: usize = 256;
+
+/// Capacity of the `SOCKET_PEERS` map, one entry per connected TCP or UDP
+/// socket seen moving data; the LRU evicts closed ones.
+pub const SOCKET_PEERS_MAX_ENTRIES: u32 = 16_384;
+
+/// Capacity of the per-CPU `SYSCALL_TALLY` map, one entry per process and
+/// syscall number counted since the last flush.
+pub const SYSCALL_TALLY_MAX_ENTRIES: u32 = 16_384;
+
+/// Page-fault spacing per pid when `TelemetryConfig::page_fault_interval_ns`
+/// is 0.
+pub const DEFAULT_PAGE_FAULT_INTERVAL_NS: u64 = 50_000_000;
+
+/// Share of `RLIMIT_NOFILE` at which fd allocations are reported when
+/// `TelemetryConfig::fd_pressure_pct` is 0.
+pub const DEFAULT_FD_PRESSURE_PCT: u32 = 80;
+
+/// Spacing between a process's fd pressure events when
+/// `TelemetryConfig::fd_pressure_interval_ns` is 0. Exhaustion events are
+/// spaced separately.
+pub const DEFAULT_FD_PRESSURE_INTERVAL_NS: u64 = 1_000_000_000;
+
+/// Signals reported when `TelemetryConfig::signal_mask` is 0, bit `n` for
+/// signal `n`: those whose default action terminates the process
+/// (`SIGHUP`, `SIGINT`, `SIGQUIT`, `SIGILL`, `SIGTRAP`, `SIGABRT`, `SIGBUS`,
+/// `SIGFPE`, `SIGKILL`, `SIGSEGV`, `SIGTERM`, `SIGSYS`).
+pub const DEFAULT_SIGNAL_MASK: u64 = (1 << 1)
+    | (1 << 2)
+    | (1 << 3)
+    | (1 << 4)
+    | (1 << 5)
+    | (1 << 6)
+    | (1 << 7)
+    | (1 << 8)
+    | (1 << 9)
+    | (1 << 11)
+    | (1 << 15)
+    | (1 << 31);
+
+/// Calls of one syscall counted on a CPU before a process's tally is
+/// emitted when `TelemetryConfig::syscall_batch` is 0.
+pub const DEFAULT_SYSCALL_BATCH: u32 = 64;
+
+/// Age at which a non-empty syscall tally is emitted regardless of its count
+/// when `TelemetryConfig::syscall_flush_interval_ns` is 0. Checked on the
+/// next call counted into it.
+pub const DEFAULT_SYSCALL_FLUSH_INTERVAL_NS: u64 = 1_000_000_000;
+
 /// Slot state flags (u8 to save space in compacted slot)
 pub mod slot_flags {
     /// Slot is empty and available for reservation
@@ -56,11 +136,12 @@ pub mod slot_flags {
 ///
 /// Layout (128 bytes total):
 ///   [0]      flags: u8         - Slot state
-///   [1..8]   _pad1: [u8; 7]    - Alignment padding
+///   [1]      _pad0: u8
+///   [2..4]   cpu: u16          - CPU that reserved the slot
+///   [4..8]   _pad1: [u8; 4]    - Alignment padding
 ///   [8..16]  ticket_id: u64    - Sequence number
 ///   [16..24] reserved_at_ns: u64 - Timestamp for reaper
-///   [24..120] event: ProcessEvent (96 bytes)
-///   [120..128] _pad2: [u8; 8]  - Final padding to 128
+///   [24..128] event: ProcessEvent (104 bytes)
 ///
 /// The slot uses a simple state machine:
 ///   EMPTY -> WRITING (atomic ticket reservation)
@@ -74,8 +155,14 @@ pub struct SequencedSlot {
     /// Slot state flag (see `slot_flags` module) - u8 to save space
     pub flags: u8,
 
+    pub _pad0: u8,
+
+    /// CPU that reserved the slot, written with the ticket so reaped slots
+    /// are attributed too.
+    pub cpu: u16,
+
     /// Alignment padding to 8-byte boundary
-    pub _pad1: [u8; 7],
+    pub _pad1: [u8; 4],
 
     /// The ticket/sequence number assigned during atomic reservation.
     /// This enables strict ordering validation in userspace.
@@ -85,14 +172,10 @@ pub struct SequencedSlot {
     /// Used by the "Reaper" to detect stalled producers.
     pub reserved_at_ns: u64,
 
-    /// The actual event payload (96 bytes)
+    /// The actual event payload (104 bytes).
+    /// Header: 1 + 1 + 2 + 4 + 8 + 8 = 24 bytes
+    /// Total: 24 + 104 = 128 bytes
     pub event: ProcessEvent,
-
-    /// Final padding to reach exactly 128 bytes.
-    /// Header: 1 + 7 + 8 + 8 = 24 bytes
-    /// ProcessEvent: 96 bytes
-    /// Total: 24 + 96 = 120 bytes, need 8 more
-    pub _pad2: [u8; 8],
 }
 
 // Ensure SequencedSlot is exactly 128 bytes (2 cache lines)
@@ -106,7 +189,9 @@ impl SequencedSlot {
     pub const fn zeroed() -> Self {
         Self {
             flags: slot_flags::EMPTY,
-            _pad1: [0; 7],
+            _pad0: 0,
+            cpu: 0,
+            _pad1: [0; 4],
             ticket_id: 0,
             reserved_at_ns: 0,
             event: ProcessEvent {
@@ -125,8 +210,8 @@ impl SequencedSlot {
                 data2: 0,
                 aux: 0,
                 aux2: 0,
+                cgroup_id: 0,
             },
-            _pad2: [0; 8],
         }
     }
 }
@@ -149,6 +234,9 @@ pub struct ProcessEvent {
 
     pub event_type: u32,
     pub ts_ns: u64,
+    /// Per-CPU sequence number (1-based): `PERF_SEQ` on the perf path,
+    /// `SEQUENCER_CPU_SEQ` on the sequencer path. 0 when the producer did not
+    /// stamp one.
     pub seq: u64,
 
     pub comm: [u8; 16],
@@ -166,10 +254,19 @@ pub struct ProcessEvent {
     pub aux: u32,
     /// Extended auxiliary field for additional flags or identifiers.
     pub aux2: u32,
+    /// cgroup v2 id of the task the event was taken from
+    /// (`bpf_get_current_cgroup_id`), the inode number of its cgroup
+    /// directory. 0 for node-wide events.
+    pub cgroup_id: u64,
 }
 
 pub const PERCENT_MILLI_UNKNOWN: u16 = u16::MAX;
 
+/// Version of the `ProcessEvent` layout and payload semantics as published by
+/// the daemon's event schema. Bump when a field is added, removed or
+/// reinterpreted for any event type.
+pub const WIRE_SCHEMA_VERSION: u32 = 12;
+
 #[repr(u32)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "user", derive(serde::Serialize, serde::Deserialize))]
@@ -184,6 +281,19 @@ pub enum NetOp {
     UnixDgramRecv = 7,
 }
 
+/// Step of an inbound TCP handshake on a listening socket.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "user", derive(serde::Serialize, serde::Deserialize))]
+pub enum ConnOp {
+    /// SYN queued as a request socket.
+    Syn = 0,
+    /// SYN answered with a syncookie because the request queue was full.
+    SynCookie = 1,
+    /// Handshake completed (`SYN_RECV` -> `ESTABLISHED`).
+    Established = 2,
+}
+
 #[repr(u32)]
 #[derive(Copy, Clone, Debug)]
 #[cfg_attr(feature = "user", derive(serde::Serialize, serde::Deserialize))]
@@ -261,10 +371,69 @@ pub struct TelemetryConfig {
     pub rss_file_index: u32,
     pub rss_anon_index: u32,
     pub page_size: u32,
-    pub _reserved: u32,
+    /// Offset of `exit_code` in task_struct; 0 when not discovered
+    pub task_exit_code_offset: u32,
     pub total_memory_bytes: u64,
     pub rss_source: u32,
     pub _pad: u32,
+
+    // Page-fault throttle, applied per pid
+    /// Spacing between page-fault events; 0 uses
+    /// `DEFAULT_PAGE_FAULT_INTERVAL_NS`
+    pub page_fault_interval_ns: u64,
+    /// Faults that may be emitted back to back before the spacing applies;
+    /// 0 and 1 both mean no burst
+    pub page_fault_burst: u32,
+    pub _pad2: u32,
+
+    /// Offset of `rlim[RLIMIT_NOFILE]` in signal_struct; 0 when not
+    /// discovered, which limits fd pressure events to exhaustion
+    pub signal_nofile_rlim_offset: u32,
+    /// Share of the soft limit at which fd allocations are reported; 0 uses
+    /// `DEFAULT_FD_PRESSURE_PCT`
+    pub fd_pressure_pct: u32,
+    /// Spacing between a process's fd pressure events; 0 uses
+    /// `DEFAULT_FD_PRESSURE_INTERVAL_NS`
+    pub fd_pressure_interval_ns: u64,
+
+    /// Signals reported by `signal_generate`, bit `n` for signal `n`; 0 uses
+    /// `DEFAULT_SIGNAL_MASK`
+    pub signal_mask: u64,
+
+    /// Offset of `filename` in `linux_binprm`; 0 when not discovered, which
+    /// leaves exec paths of the BTF exec handler to userspace
+    pub bprm_filename_offset: u32,
+    pub _pad3: u32,
+
+    /// Per-run key mixed into the socket ids of net events, so they do not
+    /// give away kernel addresses
+    pub socket_id_key: u64,
+
+    /// Offsets of `f_inode` in `struct file`, `i_mode`, `i_ino` and `i_sb`
+    /// in `struct inode`, and `s_dev` in `struct super_block`.
+    /// `file_inode_offset` is 0 when they were not discovered, which leaves
+    /// file I/O unreported.
+    pub file_inode_offset: u32,
+    pub inode_mode_offset: u32,
+    pub inode_ino_offset: u32,
+    pub inode_sb_offset: u32,
+    pub sb_dev_offset: u32,
+
+    // Syscall tallies, per process and syscall number on each CPU
+    /// Calls counted before a tally is emitted; 0 uses
+    /// `DEFAULT_SYSCALL_BATCH`
+    pub syscall_batch: u32,
+    /// Age at which a tally is emitted whatever its count; 0 uses
+    /// `DEFAULT_SYSCALL_FLUSH_INTERVAL_NS`
+    pub syscall_flush_interval_ns: u64,
+
+    /// Offsets of `anon_rss`, `file_rss` and `shmem_rss` in the
+    /// `oom:mark_victim` tracepoint, which only newer kernels have; 0 when
+    /// absent, which leaves the victim's RSS out of OOM kill events
+    pub oom_anon_rss_offset: u32,
+    pub oom_file_rss_offset: u32,
+    pub oom_shmem_rss_offset: u32,
+    pub _pad4: u32,
 }
 
 impl TelemetryConfig {
@@ -285,20 +454,436 @@ impl TelemetryConfig {
             rss_file_index: 0,
             rss_anon_index: 0,
             page_size: 0,
-            _reserved: 0,
+            task_exit_code_offset: 0,
             total_memory_bytes: 0,
             rss_source: 0,
             _pad: 0,
+            page_fault_interval_ns: 0,
+            page_fault_burst: 0,
+            _pad2: 0,
+            signal_nofile_rlim_offset: 0,
+            fd_pressure_pct: 0,
+            fd_pressure_interval_ns: 0,
+            signal_mask: 0,
+            bprm_filename_offset: 0,
+            _pad3: 0,
+            socket_id_key: 0,
+            file_inode_offset: 0,
+            inode_mode_offset: 0,
+            inode_ino_offset: 0,
+            inode_sb_offset: 0,
+            sb_dev_offset: 0,
+            syscall_batch: 0,
+            syscall_flush_interval_ns: 0,
+            oom_anon_rss_offset: 0,
+            oom_file_rss_offset: 0,
+            oom_shmem_rss_offset: 0,
+            _pad4: 0,
         }
     }
 }
 
+/// Flags in `aux2` of exit events. `aux` holds the raw wait status
+/// (`task_struct::exit_code`) when `VALID` is set.
+pub mod exit_status {
+    pub const VALID: u32 = 1 << 0;
+}
+
+/// Flags in `aux` of fd pressure events.
+pub mod fd_pressure {
+    /// The allocation failed with `EMFILE`.
+    pub const EXHAUSTED: u32 = 1 << 0;
+}
+
+/// Flags in the low byte of `aux2` of signal events. The byte above holds
+/// the kernel's delivery result (`TRACE_SIGNAL_*`).
+pub mod signal_event {
+    /// Sent to the whole thread group rather than one thread.
+    pub const GROUP: u32 = 1 << 0;
+    /// Raised by the kernel (fault, OOM kill, ...) rather than a syscall.
+    pub const KERNEL: u32 = 1 << 1;
+    pub const RESULT_SHIFT: u32 = 8;
+
+    /// `TRACE_SIGNAL_DELIVERED`; other results mean the signal was ignored,
+    /// already pending or dropped.
+    pub const RESULT_DELIVERED: u32 = 0;
+
+    /// Name of signal `sig` (`SIGKILL`), for the standard signals.
+    pub const fn name(sig: u32) -> Option<&'static str> {
+        Some(match sig {
+            1 => "SIGHUP",
+            2 => "SIGINT",
+            3 => "SIGQUIT",
+            4 => "SIGILL",
+            5 => "SIGTRAP",
+            6 => "SIGABRT",
+            7 => "SIGBUS",
+            8 => "SIGFPE",
+            9 => "SIGKILL",
+            10 => "SIGUSR1",
+            11 => "SIGSEGV",
+            12 => "SIGUSR2",
+            13 => "SIGPIPE",
+            14 => "SIGALRM",
+            15 => "SIGTERM",
+            16 => "SIGSTKFLT",
+            17 => "SIGCHLD",
+            18 => "SIGCONT",
+            19 => "SIGSTOP",
+            20 => "SIGTSTP",
+            21 => "SIGTTIN",
+            22 => "SIGTTOU",
+            23 => "SIGURG",
+            24 => "SIGXCPU",
+            25 => "SIGXFSZ",
+            26 => "SIGVTALRM",
+            27 => "SIGPROF",
+            28 => "SIGWINCH",
+            29 => "SIGIO",
+            30 => "SIGPWR",
+            31 => "SIGSYS",
+            _ => return None,
+        })
+    }
+
+    /// Number of a signal name, with or without the `SIG` prefix and in any
+    /// case; `SIGRTMIN+n` is not supported.
+    #[cfg(feature = "user")]
+    pub fn number(name: &str) -> Option<u32> {
+        let upper = name.trim().to_ascii_uppercase();
+        let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+        (1..32).find(|&sig| self::name(sig).is_some_and(|n| &n[3..] == name))
+    }
+}
+
 pub mod rss_source {
     pub const SIGNAL: u32 = 0;
     pub const MM: u32 = 1;
     pub const DISABLED: u32 = 2;
 }
 
+/// Programs and maps a BPF object provides, one bit per feature. The object
+/// embeds its set in the `LINNIX_FEATURES` global; bits are never reused.
+pub mod features {
+    /// sched exec/fork/exit tracepoints, `EVENTS` and `TASK_STATS`.
+    pub const PROCESS: u64 = 1 << 0;
+    /// tcp_sendmsg/tcp_recvmsg kprobes and kretprobes.
+    pub const NET_TCP: u64 = 1 << 1;
+    /// udp_sendmsg/udp_recvmsg kprobes and kretprobes.
+    pub const NET_UDP: u64 = 1 << 2;
+    /// unix stream and datagram send/recv kprobes and kretprobes.
+    pub const NET_UNIX: u64 = 1 << 3;
+    /// inet_csk_listen_start kprobe.
+    pub const LISTEN: u64 = 1 << 4;
+    /// vfs_read/vfs_write kprobes and kretprobes.
+    pub const FILE_IO: u64 = 1 << 5;
+    /// block bio queue/issue/complete tracepoints.
+    pub const BLOCK_IO: u64 = 1 << 6;
+    /// page_fault_user/page_fault_kernel BTF tracepoints.
+    pub const PAGE_FAULTS: u64 = 1 << 7;
+    /// raw_syscalls:sys_enter tracepoint and the per-CPU `SYSCALL_TALLY`
+    /// map it counts calls in.
+    pub const SYSCALLS: u64 = 1 << 8;
+    /// `SAMPLE_DIVISOR`/`SAMPLE_COUNTER` sampling maps.
+    pub const SAMPLING: u64 = 1 << 9;
+    /// `PID_FILTER` map.
+    pub const PID_FILTER: u64 = 1 << 10;
+    /// `CGROUP_SCOPE`/`CGROUP_ALLOW` maps.
+    pub const CGROUP_SCOPE: u64 = 1 << 11;
+    /// `SEQUENCER_RING` and `SEQUENCER_ENABLED`.
+    pub const SEQUENCER: u64 = 1 << 12;
+    /// Per-CPU `PERF_SEQ` stamped into perf events.
+    pub const PERF_SEQ: u64 = 1 << 13;
+    /// `SEQUENCER_RING_NODE<p>` rings and per-partition tickets.
+    pub const SEQUENCER_NUMA: u64 = 1 << 14;
+    /// Producing CPU in sequencer slots and per-CPU `SEQUENCER_CPU_SEQ`.
+    pub const SEQUENCER_CPU: u64 = 1 << 15;
+    /// Inbound handshake kprobes and the inet_sock_set_state tracepoint.
+    pub const CONNECTIONS: u64 = 1 << 16;
+    /// fd allocation kretprobe and `FD_PRESSURE_THROTTLE`.
+    pub const FD_PRESSURE: u64 = 1 << 17;
+    /// signal:signal_generate tracepoint.
+    pub const SIGNALS: u64 = 1 << 18;
+    /// `EXEC_DETAILS` map filled by the exec tracepoints.
+    pub const EXEC_PATHS: u64 = 1 << 19;
+    /// `SOCKET_PEERS` map filled by the TCP and UDP kretprobes.
+    pub const SOCKET_PEERS: u64 = 1 << 20;
+    /// oom:mark_victim tracepoint.
+    pub const OOM_KILLS: u64 = 1 << 21;
+
+    /// Names as reported by the daemon, in bit order.
+    pub const NAMES: &[(u64, &str)] = &[
+        (PROCESS, "process"),
+        (NET_TCP, "net_tcp"),
+        (NET_UDP, "net_udp"),
+        (NET_UNIX, "net_unix"),
+        (LISTEN, "listen"),
+        (FILE_IO, "file_io"),
+        (BLOCK_IO, "block_io"),
+        (PAGE_FAULTS, "page_faults"),
+        (SYSCALLS, "syscalls"),
+        (SAMPLING, "sampling"),
+        (PID_FILTER, "pid_filter"),
+        (CGROUP_SCOPE, "cgroup_scope"),
+        (SEQUENCER, "sequencer"),
+        (PERF_SEQ, "perf_seq"),
+        (SEQUENCER_NUMA, "sequencer_numa"),
+        (SEQUENCER_CPU, "sequencer_cpu"),
+        (CONNECTIONS, "connections"),
+        (FD_PRESSURE, "fd_pressure"),
+        (SIGNALS, "signals"),
+        (EXEC_PATHS, "exec_paths"),
+        (SOCKET_PEERS, "socket_peers"),
+        (OOM_KILLS, "oom_kills"),
+    ];
+}
+
+/// Marks the feature manifest in the object's `.rodata` so the loader can
+/// find it without resolving symbols.
+pub const FEATURE_MANIFEST_MAGIC: [u8; 8] = *b"LNXFEAT\x01";
+
+/// Layout of the `LINNIX_FEATURES` global: magic followed by the
+/// little-endian bitmap of `features`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct FeatureManifest {
+    pub magic: [u8; 8],
+    pub bits: u64,
+}
+
+impl FeatureManifest {
+    pub const fn new(bits: u64) -> Self {
+        Self {
+            magic: FEATURE_MANIFEST_MAGIC,
+            bits,
+        }
+    }
+}
+
+/// Marks the architecture manifest, next to the feature manifest.
+pub const ARCH_MANIFEST_MAGIC: [u8; 8] = *b"LNXARCH\x01";
+
+/// Layout of the `LINNIX_TARGET_ARCH` global: magic followed by the
+/// NUL-padded architecture (`x86_64`, `aarch64`) the object's kprobe
+/// programs read `pt_regs` for.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct ArchManifest {
+    pub magic: [u8; 8],
+    pub arch: [u8; 16],
+}
+
+impl ArchManifest {
+    pub const fn new(arch: &str) -> Self {
+        let bytes = arch.as_bytes();
+        let mut name = [0u8; 16];
+        let mut i = 0;
+        while i < bytes.len() && i < name.len() {
+            name[i] = bytes[i];
+            i += 1;
+        }
+        Self {
+            magic: ARCH_MANIFEST_MAGIC,
+            arch: name,
+        }
+    }
+}
+
+/// Byte offsets of the fields read from classic tracepoints, as listed in
+/// `events/<category>/<event>/format` under tracefs. The common header is 8
+/// bytes and no field before these is a `long` or pointer whose size depends
+/// on the architecture, except `skaddr`, so they hold on x86_64 and arm64
+/// alike. The daemon checks them against the running kernel.
+pub mod tracepoint_layout {
+    /// A tracepoint and the fields read from it: name, offset and size.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub struct Event {
+        pub category: &'static str,
+        pub event: &'static str,
+        pub fields: &'static [(&'static str, usize, usize)],
+    }
+
+    // sched:sched_process_fork
+    pub const FORK_CHILD_COMM: usize = 28;
+    pub const FORK_CHILD_PID: usize = 44;
+
+    // block:block_bio_queue, block:block_rq_issue, block:block_rq_complete
+    pub const BLOCK_DEV: usize = 8;
+    pub const BLOCK_SECTOR: usize = 16;
+    pub const BLOCK_NR_SECTOR: usize = 24;
+    /// block_rq_issue only
+    pub const BLOCK_RQ_ISSUE_BYTES: usize = 28;
+
+    // mm:rss_stat
+    pub const RSS_STAT_MEMBER: usize = 16;
+    pub const RSS_STAT_SIZE: usize = 24;
+
+    // sock:inet_sock_set_state, after the 8-byte `skaddr` pointer
+    pub const SOCK_STATE_OLDSTATE: usize = 16;
+    pub const SOCK_STATE_NEWSTATE: usize = 20;
+    pub const SOCK_STATE_SPORT: usize = 24;
+    pub const SOCK_STATE_FAMILY: usize = 28;
+    pub const SOCK_STATE_PROTOCOL: usize = 30;
+    pub const SOCK_STATE_DADDR: usize = 36;
+    pub const SOCK_STATE_DADDR_V6: usize = 56;
+
+    // signal:signal_generate; `comm` and `pid` are the target's
+    pub const SIGNAL_SIG: usize = 8;
+    pub const SIGNAL_CODE: usize = 16;
+    pub const SIGNAL_PID: usize = 36;
+    pub const SIGNAL_GROUP: usize = 40;
+    pub const SIGNAL_RESULT: usize = 44;
+
+    // sched:sched_process_exec; `__data_loc`: offset in the low 16 bits,
+    // length in the high 16
+    pub const EXEC_FILENAME: usize = 8;
+
+    // raw_syscalls:sys_enter
+    pub const SYS_ENTER_ID: usize = 8;
+
+    // oom:mark_victim; the RSS fields that newer kernels add after `pid` are
+    // located at load time (`TelemetryConfig::oom_anon_rss_offset`)
+    pub const MARK_VICTIM_PID: usize = 8;
+
+    const BLOCK_FIELDS: &[(&str, usize, usize)] = &[
+        ("dev", BLOCK_DEV, 4),
+        ("sector", BLOCK_SECTOR, 8),
+        ("nr_sector", BLOCK_NR_SECTOR, 4),
+    ];
+
+    /// Every tracepoint read at fixed offsets, for validation.
+    pub const EVENTS: &[Event] = &[
+        Event {
+            category: "sched",
+            event: "sched_process_fork",
+            fields: &[
+                ("child_comm", FORK_CHILD_COMM, 16),
+                ("child_pid", FORK_CHILD_PID, 4),
+            ],
+        },
+        Event {
+            category: "block",
+            event: "block_bio_queue",
+            fields: BLOCK_FIELDS,
+        },
+        Event {
+            category: "block",
+            event: "block_rq_issue",
+            fields: &[
+                ("dev", BLOCK_DEV, 4),
+                ("sector", BLOCK_SECTOR, 8),
+                ("nr_sector", BLOCK_NR_SECTOR, 4),
+                ("bytes", BLOCK_RQ_ISSUE_BYTES, 4),
+            ],
+        },
+        Event {
+            category: "block",
+            event: "block_rq_complete",
+            fields: BLOCK_FIELDS,
+        },
+        Event {
+            category: "mm",
+            event: "rss_stat",
+            fields: &[("member", RSS_STAT_MEMBER, 4), ("size", RSS_STAT_SIZE, 8)],
+        },
+        Event {
+            category: "sock",
+            event: "inet_sock_set_state",
+            fields: &[
+                ("oldstate", SOCK_STATE_OLDSTATE, 4),
+                ("newstate", SOCK_STATE_NEWSTATE, 4),
+                ("sport", SOCK_STATE_SPORT, 2),
+                ("family", SOCK_STATE_FAMILY, 2),
+                ("protocol", SOCK_STATE_PROTOCOL, 2),
+                ("daddr", SOCK_STATE_DADDR, 4),
+                ("daddr_v6", SOCK_STATE_DADDR_V6, 16),
+            ],
+        },
+        Event {
+            category: "signal",
+            event: "signal_generate",
+            fields: &[
+                ("sig", SIGNAL_SIG, 4),
+                ("code", SIGNAL_CODE, 4),
+                ("pid", SIGNAL_PID, 4),
+                ("group", SIGNAL_GROUP, 4),
+                ("result", SIGNAL_RESULT, 4),
+            ],
+        },
+        Event {
+            category: "sched",
+            event: "sched_process_exec",
+            fields: &[("filename", EXEC_FILENAME, 4)],
+        },
+        Event {
+            category: "raw_syscalls",
+            event: "sys_enter",
+            fields: &[("id", SYS_ENTER_ID, 8)],
+        },
+        Event {
+            category: "oom",
+            event: "mark_victim",
+            fields: &[("pid", MARK_VICTIM_PID, 4)],
+        },
+    ];
+}
+
+/// Executable path of an exec, as the kernel resolved it, kept in
+/// `EXEC_DETAILS` under the pid until userspace handles the exec event.
+/// `comm` is the first 15 bytes of the file name and can be set by the
+/// process itself; this is the path that was exec'd (`filename`, before
+/// symlinks are followed).
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+pub struct ExecDetails {
+    /// `ts_ns` of the exec event the path belongs to
+    pub ts_ns: u64,
+    pub pid: u32,
+    /// Bytes of `path` before the NUL; `EXEC_PATH_LEN - 1` may be truncated
+    pub len: u32,
+    pub path: [u8; EXEC_PATH_LEN],
+}
+
+#[cfg(test)]
+const _: () = {
+    assert!(size_of::<ExecDetails>() == 16 + EXEC_PATH_LEN);
+};
+
+impl ExecDetails {
+    /// The path, or `None` when it is empty or not UTF-8.
+    pub fn path(&self) -> Option<&str> {
+        let len = (self.len as usize).min(EXEC_PATH_LEN);
+        let path = self.path[..len].split(|&b| b == 0).next()?;
+        core::str::from_utf8(path).ok().filter(|p| !p.is_empty())
+    }
+
+    /// The path filled the buffer and may have been cut short.
+    pub fn truncated(&self) -> bool {
+        self.len as usize >= EXEC_PATH_LEN - 1
+    }
+}
+
+/// Remote end of a connected TCP or UDP socket, kept in `SOCKET_PEERS` under
+/// the socket id of its net events. Net events carry the family and port in
+/// `aux2`; the address does not fit beside the byte count and socket id.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Pod, Zeroable)]
+pub struct SocketPeer {
+    /// `AF_INET` or `AF_INET6`
+    pub family: u16,
+    /// Remote port, host order
+    pub port: u16,
+    pub _pad: u32,
+    /// IPv4 address in the low 4 bytes of `addr[0]`, or the IPv6 address
+    /// across both, raw bytes in memory order as in connection events
+    pub addr: [u64; 2],
+}
+
+#[cfg(test)]
+const _: () = {
+    assert!(size_of::<SocketPeer>() == 24);
+};
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 #[cfg_attr(feature = "user", derive(serde::Serialize, serde::Deserialize))]
@@ -326,6 +911,16 @@ pub enum EventType {
     Syscall = 5,
     BlockIo = 6,
     PageFault = 7,
+    /// A socket entered the listening state (`inet_csk_listen_start`).
+    Listen = 8,
+    /// Inbound TCP handshake step, see `ConnOp`.
+    Connection = 9,
+    /// A process's fd table nearing or at `RLIMIT_NOFILE`, see `fd_pressure`.
+    FdPressure = 10,
+    /// A signal generated for a process, see `signal_event`.
+    Signal = 11,
+    /// The kernel's OOM killer picked a victim (`oom:mark_victim`).
+    OomKill = 12,
 }
 
 #[cfg(all(feature = "user", not(target_os = "none")))]
@@ -335,6 +930,17 @@ pub struct ProcessEventExt {
     pub base: ProcessEvent,
     #[cfg(all(feature = "user", not(target_os = "none")))]
     pub hostname: Option<String>,
+    /// Script run by an interpreter (`python3 train.py` -> `train.py`),
+    /// resolved from the command line at exec.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script_path: Option<String>,
+    /// Executable path at exec, which `comm` only holds the first 15
+    /// bytes of; inherited by forked children.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exe_path: Option<String>,
+    /// Sequencer ticket the event was read at; `None` for the perf path.
+    #[serde(skip)]
+    pub ticket: Option<u64>,
 }
 
 #[cfg(all(feature = "user", not(target_os = "none")))]
@@ -343,6 +949,9 @@ impl ProcessEventExt {
         Self { 
             base,
             hostname: None,
+            script_path: None,
+            exe_path: None,
+            ticket: None,
         }
     }
 
@@ -351,6 +960,21 @@ impl ProcessEventExt {
         self
     }
 
+    pub fn with_script_path(mut self, script_path: Option<String>) -> Self {
+        self.script_path = script_path;
+        self
+    }
+
+    pub fn with_exe_path(mut self, exe_path: Option<String>) -> Self {
+        self.exe_path = exe_path;
+        self
+    }
+
+    pub fn with_ticket(mut self, ticket: Option<u64>) -> Self {
+        self.ticket = ticket;
+        self
+    }
+
     pub fn exit_time(&self) -> Option<u64> {
         if self.base.exit_time_ns == 0 {
             None
@@ -506,6 +1130,11 @@ mod tests {
             "SequencedSlot must be exactly 128 bytes"
         );
 
+        assert_eq!(core::mem::offset_of!(SequencedSlot, cpu), 2);
+        assert_eq!(core::mem::offset_of!(SequencedSlot, ticket_id), 8);
+        assert_eq!(core::mem::offset_of!(SequencedSlot, event), 24);
+        assert_eq!(size_of::<ProcessEvent>(), 104);
+
         // Must be aligned to 128 bytes (as declared with #[repr(C, align(128))])
         assert_eq!(
             std::mem::align_of::<SequencedSlot>(),
@@ -535,6 +1164,22 @@ mod tests {
         assert!(!flags.contains(PageFaultFlags::INSTRUCTION));
     }
 
+    #[test]
+    fn exec_details_path() {
+        let mut details = ExecDetails::zeroed();
+        assert_eq!(details.path(), None);
+
+        let path = b"/usr/local/bin/inference-server";
+        details.path[..path.len()].copy_from_slice(path);
+        details.len = path.len() as u32;
+        assert_eq!(details.path(), Some("/usr/local/bin/inference-server"));
+        assert!(!details.truncated());
+
+        details.len = EXEC_PATH_LEN as u32 - 1;
+        assert_eq!(details.path(), Some("/usr/local/bin/inference-server"));
+        assert!(details.truncated());
+    }
+
     #[cfg(feature = "user")]
     #[test]
     fn block_io_event_roundtrip() {
@@ -0,0 +1,15 @@
+//! Records the architecture the kprobe programs are built for.
+//!
+//! aya-ebpf lays out `pt_regs` for `--cfg bpf_target_arch`, falling back to
+//! the host's architecture. The same choice is exported as
+//! `LINNIX_BPF_TARGET_ARCH` and embedded in the object, so the daemon can
+//! tell an object built for another architecture.
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=CARGO_CFG_BPF_TARGET_ARCH");
+    let arch = std::env::var("CARGO_CFG_BPF_TARGET_ARCH").unwrap_or_else(|_| {
+        let host = std::env::var("HOST").expect("HOST is set by cargo");
+        host.split('-').next().unwrap_or_default().to_string()
+    });
+    println!("cargo:rustc-env=LINNIX_BPF_TARGET_ARCH={arch}");
+}
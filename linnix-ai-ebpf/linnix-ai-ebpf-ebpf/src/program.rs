@@ -2,30 +2,131 @@ use core::cmp;
 
 use aya_ebpf::{
     helpers::{
-        bpf_get_current_task_btf, bpf_get_current_uid_gid, bpf_ktime_get_ns, bpf_probe_read,
+        bpf_get_current_ancestor_cgroup_id, bpf_get_current_cgroup_id, bpf_get_current_pid_tgid,
+        bpf_get_current_task_btf, bpf_get_current_uid_gid, bpf_get_numa_node_id,
+        bpf_get_smp_processor_id, bpf_ktime_get_ns, bpf_probe_read,
+        bpf_probe_read_kernel_str_bytes,
     },
-    macros::{btf_tracepoint, kprobe, map, tracepoint},
-    maps::{perf::PerfEventArray, Array, HashMap, PerCpuArray},
-    programs::{BtfTracePointContext, ProbeContext, TracePointContext},
+    macros::{btf_tracepoint, kprobe, kretprobe, map, tracepoint},
+    maps::{perf::PerfEventArray, Array, HashMap, LruHashMap, LruPerCpuHashMap, PerCpuArray},
+    programs::{BtfTracePointContext, ProbeContext, RetProbeContext, TracePointContext},
     EbpfContext,
 };
 use aya_log_ebpf::info;
 use linnix_ai_ebpf_common::{
-    rss_source, slot_flags, BlockOp, EventType, PageFaultOrigin, ProcessEvent, SequencedSlot,
-    TelemetryConfig, PERCENT_MILLI_UNKNOWN, SEQUENCER_RING_MASK, SEQUENCER_RING_SIZE,
+    exit_status, fd_pressure, features, rss_source, signal_event, slot_flags, tracepoint_layout,
+    ArchManifest, BlockOp, ConnOp, EventType, ExecDetails, FeatureManifest, FileOp, NetOp,
+    PageFaultOrigin, ProcessEvent, SequencedSlot, SocketPeer, TelemetryConfig,
+    CGROUP_ALLOW_MAX_ENTRIES, CONTROL_EVENT_TYPES, DEFAULT_FD_PRESSURE_INTERVAL_NS,
+    DEFAULT_FD_PRESSURE_PCT, DEFAULT_PAGE_FAULT_INTERVAL_NS, DEFAULT_SIGNAL_MASK,
+    DEFAULT_SYSCALL_BATCH, DEFAULT_SYSCALL_FLUSH_INTERVAL_NS, EXEC_DETAILS_MAX_ENTRIES,
+    PERCENT_MILLI_UNKNOWN, PID_FILTER_MAX_ENTRIES, SEQUENCER_MAX_PARTITIONS, SEQUENCER_RING_MASK,
+    SEQUENCER_RING_SIZE, SOCKET_PEERS_MAX_ENTRIES, SYSCALL_TALLY_MAX_ENTRIES,
+    TASK_STATE_MAX_ENTRIES,
 };
 
 #[map(name = "EVENTS")]
 static mut EVENTS: PerfEventArray<ProcessEvent> = PerfEventArray::new(0);
 
 #[map(name = "TASK_STATS")]
-static mut TASK_STATS: HashMap<u32, TaskStats> = HashMap::with_max_entries(65_536, 0);
+static mut TASK_STATS: HashMap<u32, TaskStats> =
+    HashMap::with_max_entries(TASK_STATE_MAX_ENTRIES, 0);
 
 #[map(name = "EVENT_BUFFER")]
 static mut EVENT_BUFFER: PerCpuArray<ProcessEvent> = PerCpuArray::with_max_entries(1, 0);
 
+/// Per-CPU counter stamped into `ProcessEvent.seq` on the perf path so
+/// userspace can detect gaps in each CPU's perf buffer.
+#[map(name = "PERF_SEQ")]
+static mut PERF_SEQ: PerCpuArray<u64> = PerCpuArray::with_max_entries(1, 0);
+
+/// Per-CPU counter stamped into `ProcessEvent.seq` on the sequencer path,
+/// bumped with every ticket the CPU reserves. Userspace reads it as the
+/// CPU's production count and checks each CPU's stream for gaps and
+/// inversions.
+#[map(name = "SEQUENCER_CPU_SEQ")]
+static mut SEQUENCER_CPU_SEQ: PerCpuArray<u64> = PerCpuArray::with_max_entries(1, 0);
+
+/// Per-pid page-fault schedule: the earliest time the next fault may be
+/// emitted once any burst allowance is spent.
 #[map(name = "PAGE_FAULT_THROTTLE")]
-static mut PAGE_FAULT_THROTTLE: HashMap<u32, u64> = HashMap::with_max_entries(65_536, 0);
+static mut PAGE_FAULT_THROTTLE: HashMap<u32, u64> =
+    HashMap::with_max_entries(TASK_STATE_MAX_ENTRIES, 0);
+
+/// Page faults dropped by the throttle, per pid. Kept past exit so userspace
+/// can account the final count; it deletes entries of exited pids after
+/// reading them, and the LRU evicts any it misses.
+#[map(name = "PAGE_FAULT_SUPPRESSED")]
+static mut PAGE_FAULT_SUPPRESSED: LruHashMap<u32, u64> =
+    LruHashMap::with_max_entries(TASK_STATE_MAX_ENTRIES, 0);
+
+/// Earliest time of a process's next fd pressure event, keyed by
+/// `pid << 1 | exhausted` so running out of fds is never hidden behind an
+/// earlier warning. LRU, so entries of exited pids age out.
+#[map(name = "FD_PRESSURE_THROTTLE")]
+static mut FD_PRESSURE_THROTTLE: LruHashMap<u64, u64> =
+    LruHashMap::with_max_entries(TASK_STATE_MAX_ENTRIES, 0);
+
+/// Userspace-controlled sampling: element N holds the divisor for event type N
+/// (0 or 1 keeps every event, 10 keeps one in ten).
+#[map(name = "SAMPLE_DIVISOR")]
+static mut SAMPLE_DIVISOR: Array<u32> = Array::with_max_entries(CONTROL_EVENT_TYPES, 0);
+
+#[map(name = "SAMPLE_COUNTER")]
+static mut SAMPLE_COUNTER: PerCpuArray<u64> = PerCpuArray::with_max_entries(CONTROL_EVENT_TYPES, 0);
+
+/// PIDs whose events are dropped in-kernel (e.g. the daemon itself).
+#[map(name = "PID_FILTER")]
+static mut PID_FILTER: HashMap<u32, u8> = HashMap::with_max_entries(PID_FILTER_MAX_ENTRIES, 0);
+
+/// Cgroup scoping: element 0 holds the cgroup hierarchy level of the allowed
+/// subtrees (0 disables scoping). An event is kept only when the current
+/// task's ancestor cgroup at that level is in `CGROUP_ALLOW`, so cgroups
+/// created under an allowed subtree are covered without updating the map.
+#[map(name = "CGROUP_SCOPE")]
+static mut CGROUP_SCOPE: Array<u32> = Array::with_max_entries(1, 0);
+
+#[map(name = "CGROUP_ALLOW")]
+static mut CGROUP_ALLOW: HashMap<u64, u8> = HashMap::with_max_entries(CGROUP_ALLOW_MAX_ENTRIES, 0);
+
+/// Socket of each thread's send or receive in progress, keyed by pid/tgid,
+/// from the kprobe on entry to the kretprobe that reports the bytes. LRU, so
+/// entries of calls whose return was missed age out.
+#[map(name = "NET_INFLIGHT")]
+static mut NET_INFLIGHT: LruHashMap<u64, u64> =
+    LruHashMap::with_max_entries(TASK_STATE_MAX_ENTRIES, 0);
+
+/// File of each thread's read or write in progress, keyed by pid/tgid, from
+/// the kprobe on entry to the kretprobe that reports the bytes. Separate
+/// from `NET_INFLIGHT`: a read of a socket goes through `vfs_read` first.
+#[map(name = "FILE_INFLIGHT")]
+static mut FILE_INFLIGHT: LruHashMap<u64, u64> =
+    LruHashMap::with_max_entries(TASK_STATE_MAX_ENTRIES, 0);
+
+/// Remote end of each connected TCP or UDP socket, keyed by socket id,
+/// written by the kretprobes before they report the bytes. Userspace reads
+/// it for sockets it has not seen yet.
+#[map(name = "SOCKET_PEERS")]
+static mut SOCKET_PEERS: LruHashMap<u64, SocketPeer> =
+    LruHashMap::with_max_entries(SOCKET_PEERS_MAX_ENTRIES, 0);
+
+/// Calls of each syscall counted per process on this CPU since the tally
+/// was last emitted, keyed by `(pid << 32) | nr`. Per-CPU so counting takes
+/// no lock; LRU, so tallies of exited processes age out.
+#[map(name = "SYSCALL_TALLY")]
+static mut SYSCALL_TALLY: LruPerCpuHashMap<u64, SyscallTally> =
+    LruPerCpuHashMap::with_max_entries(SYSCALL_TALLY_MAX_ENTRIES, 0);
+
+/// Executable path of the latest exec of each pid. Userspace removes the
+/// entry when it handles the exec event; entries of execs the filters drop
+/// are left to the LRU.
+#[map(name = "EXEC_DETAILS")]
+static mut EXEC_DETAILS: LruHashMap<u32, ExecDetails> =
+    LruHashMap::with_max_entries(EXEC_DETAILS_MAX_ENTRIES, 0);
+
+/// Scratch `ExecDetails`, too large for the stack.
+#[map(name = "EXEC_DETAILS_BUFFER")]
+static mut EXEC_DETAILS_BUFFER: PerCpuArray<ExecDetails> = PerCpuArray::with_max_entries(1, 0);
 
 // =============================================================================
 // SEQUENCED MPSC RING BUFFER - Kernel Producer Maps
@@ -46,17 +147,27 @@ const BPF_F_MMAPABLE: u32 = 1024;
 static mut SEQUENCER_RING: Array<SequencedSlot> =
     Array::with_max_entries(SEQUENCER_RING_SIZE, BPF_F_MMAPABLE);
 
+// Rings of partitions 1..3 when the sequencer is partitioned by NUMA node.
+// Declared with one slot so they cost nothing unless the loader grows them.
+#[map(name = "SEQUENCER_RING_NODE1")]
+static mut SEQUENCER_RING_NODE1: Array<SequencedSlot> = Array::with_max_entries(1, BPF_F_MMAPABLE);
+
+#[map(name = "SEQUENCER_RING_NODE2")]
+static mut SEQUENCER_RING_NODE2: Array<SequencedSlot> = Array::with_max_entries(1, BPF_F_MMAPABLE);
+
+#[map(name = "SEQUENCER_RING_NODE3")]
+static mut SEQUENCER_RING_NODE3: Array<SequencedSlot> = Array::with_max_entries(1, BPF_F_MMAPABLE);
+
 // =============================================================================
-// ISOLATED HOT SEQUENCER - Cache-Line Aligned Global Counter
+// ISOLATED HOT SEQUENCER - Cache-Line Aligned Ticket Counters
 // =============================================================================
 //
-// OPTIMIZATION: We moved the sequencer counter from a BPF Map to a .bss global.
-// This eliminates:
-//   1. bpf_map_lookup_elem helper call overhead
-//   2. TLB misses from separate map memory allocation
-//   3. False sharing with BPF map metadata
+// The ticket counters live in an array map rather than .bss so the daemon can
+// pin them to bpffs next to the ring: a restarted consumer then finds the
+// counter where the producers left it. Array lookups are inlined by the
+// verifier, so reserving a ticket is still a direct LOCK XADD.
 //
-// The counter is aligned to 64 bytes (cache line) to prevent false sharing.
+// Each counter is aligned to 64 bytes (cache line) to prevent false sharing.
 // When Core 1 updates the counter, it won't invalidate Core 2's unrelated data.
 
 /// Cache-line aligned sequencer counter (raw u64, not AtomicU64)
@@ -69,33 +180,61 @@ struct AlignedSequencer {
     _padding: [u8; 56],
 }
 
-/// Global sequencer counter - lives in .bss, initialized to 0 on program load.
-/// This compiles to a direct memory address, not a map lookup.
-#[no_mangle]
-static mut GLOBAL_SEQUENCER: AlignedSequencer = AlignedSequencer {
-    value: 0,
-    _padding: [0; 56],
-};
+/// Ticket counters, zeroed when the map is created. Element 0 serves the
+/// shared ring and partition 0, element `p` partition `p`; each sits on its
+/// own cache line, so CPUs of different sockets never contend on the same
+/// counter.
+#[map(name = "GLOBAL_SEQUENCER")]
+static mut GLOBAL_SEQUENCER: Array<AlignedSequencer> =
+    Array::with_max_entries(SEQUENCER_MAX_PARTITIONS, 0);
 
 // Map 2: Feature flag to enable sequencer (single u32 element)
-// Set element 0 to 1 from userspace to switch from perf buffer to sequencer.
+// Set element 0 to 1 from userspace to switch from perf buffer to sequencer,
+// or to N > 1 to partition it across N per-NUMA-node rings.
 #[map(name = "SEQUENCER_ENABLED")]
 static mut SEQUENCER_ENABLED: Array<u32> = Array::with_max_entries(1, 0);
 
 #[no_mangle]
 static mut TELEMETRY_CONFIG: TelemetryConfig = TelemetryConfig::zeroed();
 
-const BYTES_PER_SECTOR: u64 = 512;
-const PAGE_FAULT_MIN_INTERVAL_NS: u64 = 50_000_000; // 50 ms window per PID
+/// Architecture whose `pt_regs` layout the kprobe programs were built for
+/// (`cargo xtask build-ebpf --arch`). Read by the daemon, which keeps the
+/// kprobes detached on a kernel of another architecture.
+#[no_mangle]
+#[used]
+static LINNIX_TARGET_ARCH: ArchManifest = ArchManifest::new(env!("LINNIX_BPF_TARGET_ARCH"));
 
-const BLOCK_BIO_DEV_OFFSET: usize = 0;
-const BLOCK_BIO_SECTOR_OFFSET: usize = 8;
-const BLOCK_BIO_NR_SECTOR_OFFSET: usize = 16;
+/// Programs and maps this object provides. Read by the daemon from the object
+/// file before loading; update it whenever a probe or map is added.
+#[no_mangle]
+#[used]
+static LINNIX_FEATURES: FeatureManifest = FeatureManifest::new(
+    features::PROCESS
+        | features::NET_TCP
+        | features::NET_UDP
+        | features::NET_UNIX
+        | features::LISTEN
+        | features::FILE_IO
+        | features::BLOCK_IO
+        | features::PAGE_FAULTS
+        | features::SYSCALLS
+        | features::SAMPLING
+        | features::PID_FILTER
+        | features::CGROUP_SCOPE
+        | features::SEQUENCER
+        | features::SEQUENCER_NUMA
+        | features::SEQUENCER_CPU
+        | features::PERF_SEQ
+        | features::CONNECTIONS
+        | features::FD_PRESSURE
+        | features::SIGNALS
+        | features::EXEC_PATHS
+        | features::SOCKET_PEERS
+        | features::OOM_KILLS,
+);
+
+const BYTES_PER_SECTOR: u64 = 512;
 
-const BLOCK_RQ_DEV_OFFSET: usize = 0;
-const BLOCK_RQ_SECTOR_OFFSET: usize = 8;
-const BLOCK_RQ_NR_SECTOR_OFFSET: usize = 16;
-const BLOCK_RQ_ISSUE_BYTES_OFFSET: usize = 20;
 const DEVICE_MAJOR_BITS: u32 = 12;
 const DEVICE_MINOR_BITS: u32 = 20;
 const DEVICE_MAJOR_MASK: u64 = (1u64 << DEVICE_MAJOR_BITS) - 1;
@@ -146,6 +285,13 @@ struct TaskStats {
     last_timestamp_ns: u64,
 }
 
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct SyscallTally {
+    count: u64,
+    since_ns: u64,
+}
+
 #[inline(always)]
 fn encode_block_dev(dev: u64) -> u32 {
     let major = (dev >> DEVICE_MINOR_BITS) & DEVICE_MAJOR_MASK;
@@ -158,22 +304,45 @@ fn block_bytes_from_sectors(sectors: u32) -> u64 {
     (sectors as u64) * BYTES_PER_SECTOR
 }
 
+/// Generic cell rate limiter: each emitted fault pushes the pid's schedule
+/// one interval forward, and faults are dropped while the schedule runs more
+/// than `burst - 1` intervals ahead of now. With no burst this is a plain
+/// minimum spacing between faults.
 #[inline(always)]
 fn throttle_page_fault(pid: u32, now: u64) -> bool {
+    let cfg = load_config();
+    let interval = if cfg.page_fault_interval_ns == 0 {
+        DEFAULT_PAGE_FAULT_INTERVAL_NS
+    } else {
+        cfg.page_fault_interval_ns
+    };
+    let tolerance = interval.saturating_mul(cfg.page_fault_burst.saturating_sub(1) as u64);
     let state = unsafe { &PAGE_FAULT_THROTTLE };
     if let Some(ptr) = state.get_ptr_mut(&pid) {
-        let last = unsafe { &mut *ptr };
-        if now.saturating_sub(*last) < PAGE_FAULT_MIN_INTERVAL_NS {
+        let next = unsafe { &mut *ptr };
+        let start = cmp::max(*next, now);
+        if start - now > tolerance {
+            count_suppressed_fault(pid);
             return false;
         }
-        *last = now;
+        *next = start + interval;
         true
     } else {
-        let _ = state.insert(&pid, &now, 0);
+        let _ = state.insert(&pid, &(now + interval), 0);
         true
     }
 }
 
+#[inline(always)]
+fn count_suppressed_fault(pid: u32) {
+    let counts = unsafe { &PAGE_FAULT_SUPPRESSED };
+    if let Some(ptr) = counts.get_ptr_mut(&pid) {
+        unsafe { atomic_fetch_add_u64(ptr, 1) };
+    } else {
+        let _ = counts.insert(&pid, &1, 0);
+    }
+}
+
 fn tp_read_u64(ctx: &TracePointContext, offset: usize) -> Option<u64> {
     unsafe { ctx.read_at::<u64>(offset).ok() }
 }
@@ -245,6 +414,18 @@ fn parent_tgid(task: *const u8, config: &TelemetryConfig) -> Option<u32> {
     }
 }
 
+/// `(aux, aux2)` of an exit event: the raw wait status of `task` and the
+/// `exit_status::VALID` flag, or zeros when the offset is unknown.
+fn exit_status_of(task: *const u8, config: &TelemetryConfig) -> (u32, u32) {
+    if config.task_exit_code_offset == 0 {
+        return (0, 0);
+    }
+    match read_field::<i32>(task, config.task_exit_code_offset) {
+        Some(code) => (code as u32, exit_status::VALID),
+        None => (0, 0),
+    }
+}
+
 #[cfg(target_arch = "bpf")]
 fn read_sum_exec_runtime(task: *const u8, config: &TelemetryConfig) -> Option<u64> {
     if config.task_se_offset == 0 || config.se_sum_exec_runtime_offset == 0 {
@@ -412,6 +593,9 @@ fn init_event<C: EbpfContext>(
     event.data2 = 0;
     event.aux = 0;
     event.aux2 = 0;
+    // Read with the event rather than from /proc later, which races with
+    // short-lived processes
+    event.cgroup_id = unsafe { bpf_get_current_cgroup_id() };
 
     let mut comm = [0u8; 16];
     if let Ok(name) = ctx.command() {
@@ -434,20 +618,92 @@ fn init_event<C: EbpfContext>(
     }
 }
 
-fn submit_event<C: EbpfContext>(ctx: &C, event: &ProcessEvent) {
-    // Check if sequencer is enabled (read from map)
-    let sequencer_enabled = unsafe {
+/// Next per-CPU perf sequence number (starting at 1; 0 means "unsequenced").
+///
+/// Incremented before `output()` so events the kernel fails to write show up
+/// as gaps in the consumed stream.
+#[inline(always)]
+fn next_perf_seq() -> u64 {
+    match unsafe { PERF_SEQ.get_ptr_mut(0) } {
+        Some(ptr) => unsafe {
+            *ptr += 1;
+            *ptr
+        },
+        None => 0,
+    }
+}
+
+/// Apply the userspace filter and sampling maps. Runs before any sequence
+/// number is assigned so sampled-out events never look like losses.
+#[inline(always)]
+fn should_emit(pid: u32, event_type: u32) -> bool {
+    if unsafe { PID_FILTER.get(&pid) }.is_some() {
+        return false;
+    }
+    if !in_cgroup_scope() {
+        return false;
+    }
+    sampled(event_type)
+}
+
+/// Apply the sampling divisor of `event_type`.
+#[inline(always)]
+fn sampled(event_type: u32) -> bool {
+    let divisor = match unsafe { SAMPLE_DIVISOR.get(event_type) } {
+        Some(d) => *d as u64,
+        None => 0,
+    };
+    if divisor <= 1 {
+        return true;
+    }
+    match unsafe { SAMPLE_COUNTER.get_ptr_mut(event_type) } {
+        Some(ptr) => unsafe {
+            *ptr += 1;
+            *ptr % divisor == 0
+        },
+        None => true,
+    }
+}
+
+#[inline(always)]
+fn in_cgroup_scope() -> bool {
+    let level = match unsafe { CGROUP_SCOPE.get(0) } {
+        Some(level) if *level > 0 => *level,
+        _ => return true,
+    };
+    let id = unsafe { bpf_get_current_ancestor_cgroup_id(level as i32) };
+    unsafe { CGROUP_ALLOW.get(&id) }.is_some()
+}
+
+/// Element 0 of `SEQUENCER_ENABLED`: 0 off, 1 shared ring, N > 1 partitioned.
+#[inline(always)]
+fn sequencer_mode() -> u32 {
+    unsafe {
         match SEQUENCER_ENABLED.get(0) {
             Some(val) => *val,
             None => 0,
         }
-    };
+    }
+}
+
+fn submit_event<C: EbpfContext>(ctx: &C, event: &mut ProcessEvent) {
+    if !should_emit(event.pid, event.event_type) {
+        return;
+    }
+    publish_event(ctx, event);
+}
+
+/// Write a filtered event to the sequencer ring or the perf buffer.
+fn publish_event<C: EbpfContext>(ctx: &C, event: &mut ProcessEvent) {
+    // Check if sequencer is enabled (read from map)
+    let sequencer_enabled = sequencer_mode();
 
     if sequencer_enabled != 0 {
         // Use the new lock-free sequencer
-        let _ = submit_to_sequencer(event);
+        let _ = submit_to_sequencer(sequencer_enabled, event);
     } else {
         // Fall back to legacy perf buffer
+        event.seq = next_perf_seq();
         let events = unsafe { &mut EVENTS };
         events.output(ctx, event, 0);
     }
@@ -474,17 +730,20 @@ fn submit_event_direct<C: EbpfContext>(
     aux: u32,
     aux2: u32,
 ) {
+    if !should_emit(pid, event_type) {
+        return;
+    }
+    // A forked child starts in its parent's cgroup, so the current task's
+    // cgroup is right for both callers
+    let cgroup_id = unsafe { bpf_get_current_cgroup_id() };
+
     // Check if sequencer is enabled
-    let sequencer_enabled = unsafe {
-        match SEQUENCER_ENABLED.get(0) {
-            Some(val) => *val,
-            None => 0,
-        }
-    };
+    let sequencer_enabled = sequencer_mode();
 
     if sequencer_enabled != 0 {
         // ZERO-STACK PATH: Direct write to ring buffer
         let _ = submit_to_sequencer_direct(
+            sequencer_enabled,
             pid,
             ppid,
             uid,
@@ -498,6 +757,7 @@ fn submit_event_direct<C: EbpfContext>(
             data2,
             aux,
             aux2,
+            cgroup_id,
         );
     } else {
         // LEGACY PATH: Build event on stack for perf buffer
@@ -509,7 +769,7 @@ fn submit_event_direct<C: EbpfContext>(
             gid,
             event_type,
             ts_ns,
-            seq: 0,
+            seq: next_perf_seq(),
             comm: *comm,
             exit_time_ns: 0,
             cpu_pct_milli,
@@ -518,6 +778,7 @@ fn submit_event_direct<C: EbpfContext>(
             data2,
             aux,
             aux2,
+            cgroup_id,
         };
         let events = unsafe { &mut EVENTS };
         events.output(ctx, &event, 0);
@@ -558,29 +819,66 @@ unsafe fn atomic_fetch_add_u64(ptr: *mut u64, val: u64) -> u64 {
     core::intrinsics::atomic_xadd_acqrel(ptr, val)
 }
 
+/// Next `SEQUENCER_CPU_SEQ` number of this CPU (starting at 1).
+#[inline(always)]
+fn next_sequencer_cpu_seq() -> u64 {
+    match unsafe { SEQUENCER_CPU_SEQ.get_ptr_mut(0) } {
+        Some(ptr) => unsafe {
+            *ptr += 1;
+            *ptr
+        },
+        None => 0,
+    }
+}
+
+/// Reserve the next ticket and its slot, returning the slot, the ticket and
+/// this CPU's sequence number for the event.
+///
+/// `partitions` is the value of `SEQUENCER_ENABLED`: 1 reserves from the
+/// shared ring, N > 1 from the ring of this CPU's NUMA node (modulo N).
+#[inline(always)]
+fn reserve_slot(partitions: u32) -> Result<(*mut SequencedSlot, u64, u64), i64> {
+    let partition = if partitions > 1 {
+        let node = unsafe { bpf_get_numa_node_id() } as u32;
+        node % cmp::min(partitions, SEQUENCER_MAX_PARTITIONS)
+    } else {
+        0
+    };
+
+    // The lookup is inlined; this compiles to a LOCK XADD on the element.
+    let counter = unsafe { GLOBAL_SEQUENCER.get_ptr_mut(partition) }.ok_or(-2i64)?;
+    let ticket = unsafe { core::intrinsics::atomic_xadd_acqrel(&raw mut (*counter).value, 1) };
+    // Taken right after the ticket: a program nested on this CPU between the
+    // two shows up as an inversion in userspace.
+    let cpu_seq = next_sequencer_cpu_seq();
+
+    // Masked, always in bounds of a full-size ring
+    let slot_idx = (ticket & (SEQUENCER_RING_MASK as u64)) as u32;
+    let slot_ptr = match partition {
+        0 => unsafe { SEQUENCER_RING.get_ptr_mut(slot_idx) },
+        1 => unsafe { SEQUENCER_RING_NODE1.get_ptr_mut(slot_idx) },
+        2 => unsafe { SEQUENCER_RING_NODE2.get_ptr_mut(slot_idx) },
+        _ => unsafe { SEQUENCER_RING_NODE3.get_ptr_mut(slot_idx) },
+    }
+    .ok_or(-2i64)?;
+    Ok((slot_ptr, ticket, cpu_seq))
+}
+
 /// Submit an event to the sequenced ring buffer.
 ///
 /// ULTRA-HOT PATH - every cycle counts!
 ///
 /// Optimizations applied:
-/// 1. ISOLATED SEQUENCER - .bss global instead of BPF map (no lookup overhead)
+/// 1. ISOLATED SEQUENCER - cache-line-aligned counter, lookup inlined by the verifier
 /// 2. Acquire atomic ordering (not seqcst) for ticket reservation
 /// 3. Compacted 128-byte slots (2 cache lines)
 /// 4. u8 flags to reduce write bandwidth
 /// 5. Direct field writes (event passed by reference, written directly)
 #[inline(always)]
-fn submit_to_sequencer(event: &ProcessEvent) -> Result<(), i64> {
-    // 1. ATOMIC RESERVATION (Direct memory access - no map lookup!)
+fn submit_to_sequencer(partitions: u32, event: &ProcessEvent) -> Result<(), i64> {
+    // 1-2. ATOMIC RESERVATION AND SLOT INDEX
     // --------------------------------------------------------
-    // GLOBAL_SEQUENCER is a cache-line-aligned .bss global.
-    // This compiles to a direct LOCK XADD on a constant address.
-    let seq_ptr = unsafe { &raw mut GLOBAL_SEQUENCER.value };
-    let ticket = unsafe { core::intrinsics::atomic_xadd_acqrel(seq_ptr, 1) };
-
-    // 2. CALCULATE SLOT INDEX (masked, always in bounds)
-    // --------------------------------------------------------
-    let slot_idx = (ticket & (SEQUENCER_RING_MASK as u64)) as u32;
-    let slot_ptr = unsafe { SEQUENCER_RING.get_ptr_mut(slot_idx) }.ok_or(-2i64)?;
+    let (slot_ptr, ticket, cpu_seq) = reserve_slot(partitions)?;
 
     // 3. OPTIMISTIC LOCK (Mark as WRITING)
     // --------------------------------------------------------
@@ -590,6 +888,7 @@ fn submit_to_sequencer(event: &ProcessEvent) -> Result<(), i64> {
     // Note: flags is now u8, ticket_id comes before reserved_at in new layout
     unsafe {
         core::ptr::write_volatile(&mut (*slot_ptr).flags, slot_flags::WRITING);
+        core::ptr::write_volatile(&mut (*slot_ptr).cpu, bpf_get_smp_processor_id() as u16);
         core::ptr::write_volatile(&mut (*slot_ptr).ticket_id, ticket);
         core::ptr::write_volatile(&mut (*slot_ptr).reserved_at_ns, now);
     }
@@ -605,9 +904,10 @@ fn submit_to_sequencer(event: &ProcessEvent) -> Result<(), i64> {
     // 4. COPY DATA (Direct write to ring buffer)
     // --------------------------------------------------------
     // The event is passed by reference - we write it directly.
-    // This is a single memcpy of 96 bytes.
+    // This is a single memcpy of 104 bytes.
     unsafe {
         core::ptr::write_volatile(&mut (*slot_ptr).event, *event);
+        core::ptr::write_volatile(&mut (*slot_ptr).event.seq, cpu_seq);
     }
 
     // 5. COMMIT (Mark as READY with u8 flag)
@@ -630,6 +930,7 @@ fn submit_to_sequencer(event: &ProcessEvent) -> Result<(), i64> {
 /// Total memory traffic reduction: ~300 bytes -> ~100 bytes (3x improvement)
 #[inline(always)]
 fn submit_to_sequencer_direct(
+    partitions: u32,
     pid: u32,
     ppid: u32,
     uid: u32,
@@ -643,18 +944,15 @@ fn submit_to_sequencer_direct(
     data2: u64,
     aux: u32,
     aux2: u32,
+    cgroup_id: u64,
 ) -> Result<(), i64> {
-    // 1. ATOMIC RESERVATION (Direct memory access - no map lookup!)
-    let seq_ptr = unsafe { &raw mut GLOBAL_SEQUENCER.value };
-    let ticket = unsafe { core::intrinsics::atomic_xadd_acqrel(seq_ptr, 1) };
-
-    // 2. CALCULATE SLOT INDEX
-    let slot_idx = (ticket & (SEQUENCER_RING_MASK as u64)) as u32;
-    let slot_ptr = unsafe { SEQUENCER_RING.get_ptr_mut(slot_idx) }.ok_or(-2i64)?;
+    // 1-2. ATOMIC RESERVATION AND SLOT INDEX
+    let (slot_ptr, ticket, cpu_seq) = reserve_slot(partitions)?;
 
     // 3. OPTIMISTIC LOCK (Header)
     unsafe {
         core::ptr::write_volatile(&mut (*slot_ptr).flags, slot_flags::WRITING);
+        core::ptr::write_volatile(&mut (*slot_ptr).cpu, bpf_get_smp_processor_id() as u16);
         core::ptr::write_volatile(&mut (*slot_ptr).ticket_id, ticket);
         core::ptr::write_volatile(&mut (*slot_ptr).reserved_at_ns, ts_ns);
     }
@@ -683,7 +981,7 @@ fn submit_to_sequencer_direct(
         // Event metadata
         core::ptr::write_volatile(&mut e.event_type, event_type);
         core::ptr::write_volatile(&mut e.ts_ns, ts_ns);
-        core::ptr::write_volatile(&mut e.seq, 0); // Unused, ticket_id provides ordering
+        core::ptr::write_volatile(&mut e.seq, cpu_seq);
 
         // Command name (16 bytes)
         core::ptr::write_volatile(&mut e.comm, *comm);
@@ -698,6 +996,7 @@ fn submit_to_sequencer_direct(
         core::ptr::write_volatile(&mut e.data2, data2);
         core::ptr::write_volatile(&mut e.aux, aux);
         core::ptr::write_volatile(&mut e.aux2, aux2);
+        core::ptr::write_volatile(&mut e.cgroup_id, cgroup_id);
     }
 
     // 5. COMMIT
@@ -737,10 +1036,37 @@ fn try_handle_exec(ctx: TracePointContext) -> u32 {
         None => return 1,
     };
     init_event(&ctx, EventType::Exec, now, pid, event);
+    // `filename` is a `__data_loc`: its offset in the record, low 16 bits
+    if let Some(loc) = tp_read_u32(&ctx, tracepoint_layout::EXEC_FILENAME) {
+        let filename = unsafe { (ctx.as_ptr() as *const u8).add((loc & 0xffff) as usize) };
+        record_exec_path(pid, now, filename);
+    }
     submit_event(&ctx, event);
     0
 }
 
+/// Store the path at `filename` in `EXEC_DETAILS` for the exec event stamped
+/// `now`. Runs before the event is submitted so the path is there when
+/// userspace handles it.
+#[inline(always)]
+fn record_exec_path(pid: u32, now: u64, filename: *const u8) {
+    if filename.is_null() {
+        return;
+    }
+    let details = match unsafe { EXEC_DETAILS_BUFFER.get_ptr_mut(0) } {
+        Some(ptr) => unsafe { &mut *ptr },
+        None => return,
+    };
+    let len = match unsafe { bpf_probe_read_kernel_str_bytes(filename, &mut details.path) } {
+        Ok(path) => path.len() as u32,
+        Err(_) => return,
+    };
+    details.ts_ns = now;
+    details.pid = pid;
+    details.len = len;
+    let _ = unsafe { EXEC_DETAILS.insert(&pid, details, 0) };
+}
+
 // =============================================================================
 // BTF RAW TRACEPOINT - Zero-overhead exec handler
 // =============================================================================
@@ -778,6 +1104,14 @@ fn try_handle_exec_raw(ctx: &BtfTracePointContext) -> u32 {
     let uid = ids as u32;
     let gid = (ids >> 32) as u32;
 
+    let config = load_config();
+    if config.bprm_filename_offset != 0 {
+        let bprm = unsafe { ctx.arg::<*const u8>(2) };
+        if let Some(filename) = read_ptr(bprm, config.bprm_filename_offset) {
+            record_exec_path(pid, now, filename);
+        }
+    }
+
     // Direct write to ring buffer, bypassing stack allocation
     let _ = submit_event_direct(
         ctx,
@@ -831,8 +1165,14 @@ fn try_handle_fork(ctx: TracePointContext) -> Result<u32, u32> {
     let gid = (ids >> 32) as u32;
 
     // Read child info from tracepoint args (pre-marshalled by kernel)
-    let child_pid: i32 = unsafe { ctx.read_at(44).map_err(|_| 1u32)? };
-    let child_comm_raw: [u8; 16] = unsafe { ctx.read_at(28).map_err(|_| 1u32)? };
+    let child_pid: i32 = unsafe {
+        ctx.read_at(tracepoint_layout::FORK_CHILD_PID)
+            .map_err(|_| 1u32)?
+    };
+    let child_comm_raw: [u8; 16] = unsafe {
+        ctx.read_at(tracepoint_layout::FORK_CHILD_COMM)
+            .map_err(|_| 1u32)?
+    };
 
     let mut comm = [0u8; 16];
     comm.copy_from_slice(&child_comm_raw);
@@ -895,6 +1235,7 @@ fn try_handle_fork_raw(ctx: &BtfTracePointContext) -> i32 {
 
     // Direct write to sequencer ring buffer
     let _ = submit_to_sequencer_direct(
+        sequencer_mode(),
         child_pid,  // pid (child)
         parent_pid, // ppid (parent)
         uid,
@@ -939,6 +1280,8 @@ fn try_handle_exit(ctx: TracePointContext) -> u32 {
         };
         init_event(&ctx, EventType::Exit, now, pid, event);
         event.exit_time_ns = now;
+        let task = unsafe { bpf_get_current_task_btf() } as *const u8;
+        (event.aux, event.aux2) = exit_status_of(task, &load_config());
         submit_event(&ctx, event);
     }
 
@@ -966,6 +1309,7 @@ fn try_handle_exit_raw(ctx: &BtfTracePointContext) -> i32 {
 
     // Read comm from task_struct
     let comm = unsafe { read_task_comm(task) };
+    let (status, status_flags) = exit_status_of(task as *const u8, &load_config());
 
     // Get UID/GID from current context
     let ids = bpf_get_current_uid_gid();
@@ -974,6 +1318,7 @@ fn try_handle_exit_raw(ctx: &BtfTracePointContext) -> i32 {
 
     // Direct write to sequencer ring buffer
     let _ = submit_to_sequencer_direct(
+        sequencer_mode(),
         pid,
         ctx.tgid(), // ppid from context
         uid,
@@ -985,8 +1330,8 @@ fn try_handle_exit_raw(ctx: &BtfTracePointContext) -> i32 {
         PERCENT_MILLI_UNKNOWN, // mem_pct_milli
         now,                   // data = exit_time_ns
         0,                     // data2
-        0,                     // aux
-        0,                     // aux2
+        status,                // aux = wait status
+        status_flags,          // aux2 = exit_status flags
     );
 
     // Clean up per-process state
@@ -1016,10 +1361,7 @@ fn emit_activity_event<C: EbpfContext>(
     aux: u32,
     aux2: u32,
 ) -> u32 {
-    if matches!(
-        event_type,
-        EventType::Net | EventType::FileIo | EventType::Syscall | EventType::BlockIo
-    ) {
+    if matches!(event_type, EventType::BlockIo) {
         return 0;
     }
 
@@ -1050,104 +1392,265 @@ fn emit_activity_event<C: EbpfContext>(
     0
 }
 
+// Socket sends and receives. The kprobe on entry keeps the socket (the
+// first argument: a `struct sock` for TCP and UDP, a `struct socket` for
+// Unix sockets) in `NET_INFLIGHT`; the kretprobe reports the bytes the call
+// returned with it. The kretprobes attach to the same symbols as the kprobes.
+
+/// Remember the socket of a send or receive until it returns.
+#[inline(always)]
+fn stash_net_call(ctx: &ProbeContext) -> u32 {
+    let sock: u64 = match ctx.arg(0) {
+        Some(sock) => sock,
+        None => return 0,
+    };
+    let id = bpf_get_current_pid_tgid();
+    let _ = unsafe { NET_INFLIGHT.insert(&id, &sock, 0) };
+    0
+}
+
+/// Report the bytes a send or receive moved. `data` holds the bytes, `data2`
+/// the socket id, `aux` the `NetOp` and `aux2` the remote end of TCP and UDP
+/// sockets (see `record_socket_peer`); failed calls and calls that moved
+/// nothing are not reported.
+#[inline(always)]
+fn try_trace_net_ret(ctx: RetProbeContext, op: NetOp) -> u32 {
+    let id = bpf_get_current_pid_tgid();
+    let sock = match unsafe { NET_INFLIGHT.get(&id) } {
+        Some(sock) => *sock,
+        None => return 0,
+    };
+    let _ = unsafe { NET_INFLIGHT.remove(&id) };
+    let ret: i32 = match ctx.ret() {
+        Some(ret) => ret,
+        None => return 0,
+    };
+    if ret <= 0 {
+        return 0;
+    }
+    let socket = socket_id(sock);
+    let peer = match op {
+        NetOp::TcpSend | NetOp::TcpRecv | NetOp::UdpSend | NetOp::UdpRecv => {
+            record_socket_peer(sock as *const u8, socket)
+        }
+        _ => 0,
+    };
+    let now = unsafe { bpf_ktime_get_ns() };
+    emit_activity_event(
+        &ctx,
+        EventType::Net,
+        now,
+        ret as u64,
+        socket,
+        op as u32,
+        peer,
+    )
+}
+
+/// Store the remote address of a TCP or UDP socket in `SOCKET_PEERS` under
+/// its id, unless it is there already, and return `family << 16 | remote
+/// port` for `aux2`. 0 for a socket without a remote end: unconnected UDP
+/// sockets name the destination on each call.
+#[inline(always)]
+fn record_socket_peer(sk: *const u8, socket: u64) -> u32 {
+    let family: u16 = read_field(sk, SKC_FAMILY_OFFSET).unwrap_or(0);
+    let port = u16::from_be(read_field(sk, SKC_DPORT_OFFSET).unwrap_or(0));
+    if (family != AF_INET && family != AF_INET6) || port == 0 {
+        return 0;
+    }
+    let peer = SocketPeer {
+        family,
+        port,
+        _pad: 0,
+        addr: sock_remote_addr(sk, family),
+    };
+    let known = unsafe { SOCKET_PEERS.get(&socket) }.is_some_and(|known| *known == peer);
+    if !known {
+        let _ = unsafe { SOCKET_PEERS.insert(&socket, &peer, 0) };
+    }
+    ((family as u32) << 16) | port as u32
+}
+
+/// Opaque id of a socket, the same for every call on it while it is open:
+/// its address mixed with the per-run `socket_id_key`.
+#[inline(always)]
+fn socket_id(sock: u64) -> u64 {
+    (sock ^ load_config().socket_id_key).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+}
+
 #[kprobe(function = "tcp_sendmsg")]
 pub fn trace_tcp_send(ctx: ProbeContext) -> u32 {
-    try_trace_tcp_send(ctx)
+    stash_net_call(&ctx)
 }
 
-fn try_trace_tcp_send(ctx: ProbeContext) -> u32 {
-    let _ = ctx;
-    0
+#[kretprobe(function = "tcp_sendmsg")]
+pub fn trace_tcp_send_ret(ctx: RetProbeContext) -> u32 {
+    try_trace_net_ret(ctx, NetOp::TcpSend)
 }
 
 #[kprobe(function = "tcp_recvmsg")]
 pub fn trace_tcp_recv(ctx: ProbeContext) -> u32 {
-    try_trace_tcp_recv(ctx)
+    stash_net_call(&ctx)
 }
 
-fn try_trace_tcp_recv(ctx: ProbeContext) -> u32 {
-    let _ = ctx;
-    0
+#[kretprobe(function = "tcp_recvmsg")]
+pub fn trace_tcp_recv_ret(ctx: RetProbeContext) -> u32 {
+    try_trace_net_ret(ctx, NetOp::TcpRecv)
 }
 
 #[kprobe(function = "udp_sendmsg")]
 pub fn trace_udp_send(ctx: ProbeContext) -> u32 {
-    try_trace_udp_send(ctx)
+    stash_net_call(&ctx)
 }
 
-fn try_trace_udp_send(ctx: ProbeContext) -> u32 {
-    let _ = ctx;
-    0
+#[kretprobe(function = "udp_sendmsg")]
+pub fn trace_udp_send_ret(ctx: RetProbeContext) -> u32 {
+    try_trace_net_ret(ctx, NetOp::UdpSend)
 }
 
 #[kprobe(function = "udp_recvmsg")]
 pub fn trace_udp_recv(ctx: ProbeContext) -> u32 {
-    try_trace_udp_recv(ctx)
+    stash_net_call(&ctx)
 }
 
-fn try_trace_udp_recv(ctx: ProbeContext) -> u32 {
-    let _ = ctx;
-    0
+#[kretprobe(function = "udp_recvmsg")]
+pub fn trace_udp_recv_ret(ctx: RetProbeContext) -> u32 {
+    try_trace_net_ret(ctx, NetOp::UdpRecv)
 }
 
 #[kprobe(function = "unix_stream_sendmsg")]
 pub fn trace_unix_stream_send(ctx: ProbeContext) -> u32 {
-    try_trace_unix_stream_send(ctx)
+    stash_net_call(&ctx)
 }
 
-fn try_trace_unix_stream_send(ctx: ProbeContext) -> u32 {
-    let _ = ctx;
-    0
+#[kretprobe(function = "unix_stream_sendmsg")]
+pub fn trace_unix_stream_send_ret(ctx: RetProbeContext) -> u32 {
+    try_trace_net_ret(ctx, NetOp::UnixStreamSend)
 }
 
 #[kprobe(function = "unix_stream_recvmsg")]
 pub fn trace_unix_stream_recv(ctx: ProbeContext) -> u32 {
-    try_trace_unix_stream_recv(ctx)
+    stash_net_call(&ctx)
 }
 
-fn try_trace_unix_stream_recv(ctx: ProbeContext) -> u32 {
-    let _ = ctx;
-    0
+#[kretprobe(function = "unix_stream_recvmsg")]
+pub fn trace_unix_stream_recv_ret(ctx: RetProbeContext) -> u32 {
+    try_trace_net_ret(ctx, NetOp::UnixStreamRecv)
 }
 
 #[kprobe(function = "unix_dgram_sendmsg")]
 pub fn trace_unix_dgram_send(ctx: ProbeContext) -> u32 {
-    try_trace_unix_dgram_send(ctx)
+    stash_net_call(&ctx)
 }
 
-fn try_trace_unix_dgram_send(ctx: ProbeContext) -> u32 {
-    let _ = ctx;
-    0
+#[kretprobe(function = "unix_dgram_sendmsg")]
+pub fn trace_unix_dgram_send_ret(ctx: RetProbeContext) -> u32 {
+    try_trace_net_ret(ctx, NetOp::UnixDgramSend)
 }
 
 #[kprobe(function = "unix_dgram_recvmsg")]
 pub fn trace_unix_dgram_recv(ctx: ProbeContext) -> u32 {
-    try_trace_unix_dgram_recv(ctx)
+    stash_net_call(&ctx)
+}
+
+#[kretprobe(function = "unix_dgram_recvmsg")]
+pub fn trace_unix_dgram_recv_ret(ctx: RetProbeContext) -> u32 {
+    try_trace_net_ret(ctx, NetOp::UnixDgramRecv)
 }
 
-fn try_trace_unix_dgram_recv(ctx: ProbeContext) -> u32 {
-    let _ = ctx;
+// File reads and writes, in the same two halves as socket calls: the kprobe
+// keeps the `struct file` in `FILE_INFLIGHT`, the kretprobe reports the bytes
+// with the file's inode number and device. Only regular files are reported;
+// pipes, sockets and terminals also go through `vfs_read`/`vfs_write`. On
+// the `ksys_read`/`ksys_write` fallback the first argument is an fd, the
+// inode cannot be read and nothing is reported.
+
+/// Remember the file of a read or write until it returns.
+#[inline(always)]
+fn stash_file_call(ctx: &ProbeContext) -> u32 {
+    if load_config().file_inode_offset == 0 {
+        return 0;
+    }
+    let file: u64 = match ctx.arg(0) {
+        Some(file) => file,
+        None => return 0,
+    };
+    let id = bpf_get_current_pid_tgid();
+    let _ = unsafe { FILE_INFLIGHT.insert(&id, &file, 0) };
     0
 }
 
+/// Report the bytes a read or write of a regular file moved. `data` holds
+/// the bytes, `data2` the inode number, `aux` the `FileOp` and `aux2` the
+/// device as `(major << 20) | minor`.
+#[inline(always)]
+fn try_trace_file_ret(ctx: RetProbeContext, op: FileOp) -> u32 {
+    let id = bpf_get_current_pid_tgid();
+    let file = match unsafe { FILE_INFLIGHT.get(&id) } {
+        Some(file) => *file as *const u8,
+        None => return 0,
+    };
+    let _ = unsafe { FILE_INFLIGHT.remove(&id) };
+    let ret: i64 = match ctx.ret() {
+        Some(ret) => ret,
+        None => return 0,
+    };
+    if ret <= 0 {
+        return 0;
+    }
+    let (dev, ino) = match regular_file(file) {
+        Some(file) => file,
+        None => return 0,
+    };
+    let now = unsafe { bpf_ktime_get_ns() };
+    emit_activity_event(
+        &ctx,
+        EventType::FileIo,
+        now,
+        ret as u64,
+        ino,
+        op as u32,
+        dev,
+    )
+}
+
+/// `S_IFMT` and `S_IFREG` of `i_mode`.
+const S_IFMT: u16 = 0o170000;
+const S_IFREG: u16 = 0o100000;
+
+/// Device and inode number of `file` when it is a regular file.
+#[inline(always)]
+fn regular_file(file: *const u8) -> Option<(u32, u64)> {
+    let config = load_config();
+    let inode = read_ptr(file, config.file_inode_offset)?;
+    let mode: u16 = read_field(inode, config.inode_mode_offset)?;
+    if mode & S_IFMT != S_IFREG {
+        return None;
+    }
+    let ino: u64 = read_field(inode, config.inode_ino_offset)?;
+    let sb = read_ptr(inode, config.inode_sb_offset)?;
+    let dev: u32 = read_field(sb, config.sb_dev_offset)?;
+    Some((dev, ino))
+}
+
 #[kprobe(function = "vfs_read")]
 pub fn trace_vfs_read(ctx: ProbeContext) -> u32 {
-    try_trace_vfs_read(ctx)
+    stash_file_call(&ctx)
 }
 
-fn try_trace_vfs_read(ctx: ProbeContext) -> u32 {
-    let _ = ctx;
-    0
+#[kretprobe(function = "vfs_read")]
+pub fn trace_vfs_read_ret(ctx: RetProbeContext) -> u32 {
+    try_trace_file_ret(ctx, FileOp::Read)
 }
 
 #[kprobe(function = "vfs_write")]
 pub fn trace_vfs_write(ctx: ProbeContext) -> u32 {
-    try_trace_vfs_write(ctx)
+    stash_file_call(&ctx)
 }
 
-fn try_trace_vfs_write(ctx: ProbeContext) -> u32 {
-    let _ = ctx;
-    0
+#[kretprobe(function = "vfs_write")]
+pub fn trace_vfs_write_ret(ctx: RetProbeContext) -> u32 {
+    try_trace_file_ret(ctx, FileOp::Write)
 }
 
 #[tracepoint(category = "block", name = "block_bio_queue")]
@@ -1156,15 +1659,15 @@ pub fn trace_block_queue(ctx: TracePointContext) -> u32 {
 }
 
 fn try_trace_block_queue(ctx: TracePointContext) -> u32 {
-    let dev = match tp_read_u64(&ctx, BLOCK_BIO_DEV_OFFSET) {
-        Some(value) => value,
+    let dev = match tp_read_u32(&ctx, tracepoint_layout::BLOCK_DEV) {
+        Some(value) => value as u64,
         None => return 0,
     };
-    let sector = match tp_read_u64(&ctx, BLOCK_BIO_SECTOR_OFFSET) {
+    let sector = match tp_read_u64(&ctx, tracepoint_layout::BLOCK_SECTOR) {
         Some(value) => value,
         None => return 0,
     };
-    let sectors = match tp_read_u32(&ctx, BLOCK_BIO_NR_SECTOR_OFFSET) {
+    let sectors = match tp_read_u32(&ctx, tracepoint_layout::BLOCK_NR_SECTOR) {
         Some(value) => value,
         None => return 0,
     };
@@ -1178,19 +1681,19 @@ pub fn trace_block_issue(ctx: TracePointContext) -> u32 {
 }
 
 fn try_trace_block_issue(ctx: TracePointContext) -> u32 {
-    let dev = match tp_read_u64(&ctx, BLOCK_RQ_DEV_OFFSET) {
-        Some(value) => value,
+    let dev = match tp_read_u32(&ctx, tracepoint_layout::BLOCK_DEV) {
+        Some(value) => value as u64,
         None => return 0,
     };
-    let sector = match tp_read_u64(&ctx, BLOCK_RQ_SECTOR_OFFSET) {
+    let sector = match tp_read_u64(&ctx, tracepoint_layout::BLOCK_SECTOR) {
         Some(value) => value,
         None => return 0,
     };
-    let sectors = match tp_read_u32(&ctx, BLOCK_RQ_NR_SECTOR_OFFSET) {
+    let sectors = match tp_read_u32(&ctx, tracepoint_layout::BLOCK_NR_SECTOR) {
         Some(value) => value,
         None => return 0,
     };
-    let bytes = tp_read_u32(&ctx, BLOCK_RQ_ISSUE_BYTES_OFFSET);
+    let bytes = tp_read_u32(&ctx, tracepoint_layout::BLOCK_RQ_ISSUE_BYTES);
     let now = unsafe { bpf_ktime_get_ns() };
     emit_block_event_common(&ctx, now, BlockOp::Issue, dev, sector, sectors, bytes)
 }
@@ -1201,15 +1704,15 @@ pub fn trace_block_complete(ctx: TracePointContext) -> u32 {
 }
 
 fn try_trace_block_complete(ctx: TracePointContext) -> u32 {
-    let dev = match tp_read_u64(&ctx, BLOCK_RQ_DEV_OFFSET) {
-        Some(value) => value,
+    let dev = match tp_read_u32(&ctx, tracepoint_layout::BLOCK_DEV) {
+        Some(value) => value as u64,
         None => return 0,
     };
-    let sector = match tp_read_u64(&ctx, BLOCK_RQ_SECTOR_OFFSET) {
+    let sector = match tp_read_u64(&ctx, tracepoint_layout::BLOCK_SECTOR) {
         Some(value) => value,
         None => return 0,
     };
-    let sectors = match tp_read_u32(&ctx, BLOCK_RQ_NR_SECTOR_OFFSET) {
+    let sectors = match tp_read_u32(&ctx, tracepoint_layout::BLOCK_NR_SECTOR) {
         Some(value) => value,
         None => return 0,
     };
@@ -1217,6 +1720,377 @@ fn try_trace_block_complete(ctx: TracePointContext) -> u32 {
     emit_block_event_common(&ctx, now, BlockOp::Complete, dev, sector, sectors, None)
 }
 
+/// `struct sock_common` offsets of `skc_dport` (remote port, network order),
+/// `skc_num` (local port, host order) and `skc_family`; unchanged since the
+/// struct was introduced.
+const SKC_DPORT_OFFSET: u32 = 12;
+const SKC_NUM_OFFSET: u32 = 14;
+const SKC_FAMILY_OFFSET: u32 = 16;
+
+#[kprobe(function = "inet_csk_listen_start")]
+pub fn trace_listen_start(ctx: ProbeContext) -> u32 {
+    try_trace_listen_start(ctx)
+}
+
+fn try_trace_listen_start(ctx: ProbeContext) -> u32 {
+    let sk: *const u8 = match ctx.arg(0) {
+        Some(sk) => sk,
+        None => return 0,
+    };
+    let port: u16 = read_field(sk, SKC_NUM_OFFSET).unwrap_or(0);
+    let family: u16 = read_field(sk, SKC_FAMILY_OFFSET).unwrap_or(0);
+    let now = unsafe { bpf_ktime_get_ns() };
+    emit_activity_event(
+        &ctx,
+        EventType::Listen,
+        now,
+        port as u64,
+        0,
+        family as u32,
+        0,
+    )
+}
+
+/// `struct sock_common` offsets of `skc_daddr` (remote IPv4 address) and
+/// `skc_v6_daddr`. The latter assumes 64-bit pointers and `CONFIG_NET_NS`,
+/// which every distribution kernel has.
+const SKC_DADDR_OFFSET: u32 = 0;
+const SKC_V6_DADDR_OFFSET: u32 = 56;
+/// Source address offsets in `struct iphdr` and `struct ipv6hdr`, and the
+/// destination port offset in `struct tcphdr` (network order).
+const IPHDR_SADDR_OFFSET: u32 = 12;
+const IPV6HDR_SADDR_OFFSET: u32 = 8;
+const TCPHDR_DEST_OFFSET: u32 = 2;
+const AF_INET: u16 = 2;
+const AF_INET6: u16 = 10;
+
+/// Connection events happen in softirq context, where the current task is
+/// whatever the interrupt landed on. They are node-wide: stamped with pid 0,
+/// cgroup 0 and no comm, and not subject to the pid filter or cgroup scope,
+/// which describe tasks. Sampling still applies.
+///
+/// The remote address goes in `data` (IPv4, or the first half of IPv6) and
+/// `data2` (second half of IPv6) as raw bytes in memory order; `aux` is the
+/// `ConnOp` and `aux2` is `family << 16 | local port`.
+fn emit_connection_event<C: EbpfContext>(
+    ctx: &C,
+    op: ConnOp,
+    family: u16,
+    local_port: u16,
+    addr: [u64; 2],
+) -> u32 {
+    if family != AF_INET && family != AF_INET6 {
+        return 0;
+    }
+    if !sampled(EventType::Connection as u32) {
+        return 0;
+    }
+    let event = match event_buffer_mut() {
+        Some(event) => event,
+        None => return 1,
+    };
+    event.pid = 0;
+    event.ppid = 0;
+    event.uid = 0;
+    event.gid = 0;
+    event.event_type = EventType::Connection as u32;
+    event.ts_ns = unsafe { bpf_ktime_get_ns() };
+    event.seq = 0;
+    event.comm = [0u8; 16];
+    event.exit_time_ns = 0;
+    event.cpu_pct_milli = PERCENT_MILLI_UNKNOWN;
+    event.mem_pct_milli = PERCENT_MILLI_UNKNOWN;
+    event.data = addr[0];
+    event.data2 = addr[1];
+    event.aux = op as u32;
+    event.aux2 = ((family as u32) << 16) | local_port as u32;
+    event.cgroup_id = 0;
+    publish_event(ctx, event);
+    0
+}
+
+/// Remote address of a socket or request socket.
+fn sock_remote_addr(sk: *const u8, family: u16) -> [u64; 2] {
+    if family == AF_INET6 {
+        [
+            read_field::<u64>(sk, SKC_V6_DADDR_OFFSET).unwrap_or(0),
+            read_field::<u64>(sk, SKC_V6_DADDR_OFFSET + 8).unwrap_or(0),
+        ]
+    } else {
+        [
+            read_field::<u32>(sk, SKC_DADDR_OFFSET).unwrap_or(0) as u64,
+            0,
+        ]
+    }
+}
+
+#[kprobe(function = "inet_csk_reqsk_queue_hash_add")]
+pub fn trace_conn_request(ctx: ProbeContext) -> u32 {
+    try_trace_conn_request(ctx)
+}
+
+/// A SYN on a listener became a request socket; its addresses are set by
+/// the time it is hashed into the request queue.
+fn try_trace_conn_request(ctx: ProbeContext) -> u32 {
+    let req: *const u8 = match ctx.arg(1) {
+        Some(req) => req,
+        None => return 0,
+    };
+    let family: u16 = read_field(req, SKC_FAMILY_OFFSET).unwrap_or(0);
+    let port: u16 = read_field(req, SKC_NUM_OFFSET).unwrap_or(0);
+    let addr = sock_remote_addr(req, family);
+    emit_connection_event(&ctx, ConnOp::Syn, family, port, addr)
+}
+
+#[kprobe(function = "__cookie_v4_init_sequence")]
+pub fn trace_syn_cookie_v4(ctx: ProbeContext) -> u32 {
+    try_trace_syn_cookie(ctx, AF_INET)
+}
+
+#[kprobe(function = "__cookie_v6_init_sequence")]
+pub fn trace_syn_cookie_v6(ctx: ProbeContext) -> u32 {
+    try_trace_syn_cookie(ctx, AF_INET6)
+}
+
+/// A SYN answered with a syncookie: the request queue was full, so no
+/// request socket exists and the addresses come from the packet headers.
+fn try_trace_syn_cookie(ctx: ProbeContext, family: u16) -> u32 {
+    let iph: *const u8 = match ctx.arg(0) {
+        Some(iph) => iph,
+        None => return 0,
+    };
+    let th: *const u8 = match ctx.arg(1) {
+        Some(th) => th,
+        None => return 0,
+    };
+    let port = u16::from_be(read_field(th, TCPHDR_DEST_OFFSET).unwrap_or(0));
+    let addr = if family == AF_INET6 {
+        [
+            read_field::<u64>(iph, IPV6HDR_SADDR_OFFSET).unwrap_or(0),
+            read_field::<u64>(iph, IPV6HDR_SADDR_OFFSET + 8).unwrap_or(0),
+        ]
+    } else {
+        [
+            read_field::<u32>(iph, IPHDR_SADDR_OFFSET).unwrap_or(0) as u64,
+            0,
+        ]
+    };
+    emit_connection_event(&ctx, ConnOp::SynCookie, family, port, addr)
+}
+
+const TCP_ESTABLISHED: u32 = 1;
+const TCP_SYN_RECV: u32 = 3;
+const IPPROTO_TCP: u16 = 6;
+
+#[tracepoint(category = "sock", name = "inet_sock_set_state")]
+pub fn trace_sock_state(ctx: TracePointContext) -> u32 {
+    try_trace_sock_state(ctx)
+}
+
+/// Passive opens completing the handshake (`SYN_RECV` -> `ESTABLISHED`).
+fn try_trace_sock_state(ctx: TracePointContext) -> u32 {
+    let protocol =
+        unsafe { ctx.read_at::<u16>(tracepoint_layout::SOCK_STATE_PROTOCOL) }.unwrap_or(0);
+    if protocol != IPPROTO_TCP {
+        return 0;
+    }
+    if tp_read_u32(&ctx, tracepoint_layout::SOCK_STATE_OLDSTATE) != Some(TCP_SYN_RECV)
+        || tp_read_u32(&ctx, tracepoint_layout::SOCK_STATE_NEWSTATE) != Some(TCP_ESTABLISHED)
+    {
+        return 0;
+    }
+    let family = unsafe { ctx.read_at::<u16>(tracepoint_layout::SOCK_STATE_FAMILY) }.unwrap_or(0);
+    let port = unsafe { ctx.read_at::<u16>(tracepoint_layout::SOCK_STATE_SPORT) }.unwrap_or(0);
+    let addr = if family == AF_INET6 {
+        [
+            tp_read_u64(&ctx, tracepoint_layout::SOCK_STATE_DADDR_V6).unwrap_or(0),
+            tp_read_u64(&ctx, tracepoint_layout::SOCK_STATE_DADDR_V6 + 8).unwrap_or(0),
+        ]
+    } else {
+        [
+            tp_read_u32(&ctx, tracepoint_layout::SOCK_STATE_DADDR).unwrap_or(0) as u64,
+            0,
+        ]
+    };
+    emit_connection_event(&ctx, ConnOp::Established, family, port, addr)
+}
+
+/// `EMFILE`, returned negated when the fd table is at `RLIMIT_NOFILE`.
+const EMFILE: i32 = 24;
+const RLIM_INFINITY: u64 = u64::MAX;
+
+/// Soft `RLIMIT_NOFILE` of `task`; `None` when the offsets are unknown or
+/// the limit is unlimited.
+fn nofile_limit(task: *const u8, config: &TelemetryConfig) -> Option<u64> {
+    if config.task_signal_offset == 0 || config.signal_nofile_rlim_offset == 0 {
+        return None;
+    }
+    let signal = read_ptr(task, config.task_signal_offset)?;
+    match read_field::<u64>(signal, config.signal_nofile_rlim_offset)? {
+        0 | RLIM_INFINITY => None,
+        limit => Some(limit),
+    }
+}
+
+/// Minimum spacing between a process's fd pressure events, kept separately
+/// for warnings and exhaustion.
+#[inline(always)]
+fn throttle_fd_pressure(pid: u32, exhausted: bool, now: u64, config: &TelemetryConfig) -> bool {
+    let interval = if config.fd_pressure_interval_ns == 0 {
+        DEFAULT_FD_PRESSURE_INTERVAL_NS
+    } else {
+        config.fd_pressure_interval_ns
+    };
+    let key = ((pid as u64) << 1) | exhausted as u64;
+    let state = unsafe { &FD_PRESSURE_THROTTLE };
+    if let Some(ptr) = state.get_ptr_mut(&key) {
+        let next = unsafe { &mut *ptr };
+        if now < *next {
+            return false;
+        }
+        *next = now + interval;
+    } else {
+        let _ = state.insert(&key, &(now + interval), 0);
+    }
+    true
+}
+
+#[kretprobe(function = "get_unused_fd_flags")]
+pub fn trace_fd_alloc(ctx: RetProbeContext) -> u32 {
+    try_trace_fd_alloc(ctx)
+}
+
+/// Every fd allocation returns the lowest free descriptor, so a process that
+/// got fd `n` has at least `n + 1` open. Reported once that reaches
+/// `fd_pressure_pct` of its soft `RLIMIT_NOFILE`, and on `EMFILE`.
+///
+/// `data` holds the open fds (the limit itself on exhaustion), `data2` the
+/// limit (0 when unknown) and `aux` the `fd_pressure` flags.
+fn try_trace_fd_alloc(ctx: RetProbeContext) -> u32 {
+    let ret: i32 = match ctx.ret() {
+        Some(ret) => ret,
+        None => return 0,
+    };
+    let exhausted = ret == -EMFILE;
+    if ret < 0 && !exhausted {
+        return 0;
+    }
+    let config = load_config();
+    let task = unsafe { bpf_get_current_task_btf() } as *const u8;
+    let limit = nofile_limit(task, &config).unwrap_or(0);
+    let open = if exhausted { limit } else { ret as u64 + 1 };
+    if !exhausted {
+        let pct = if config.fd_pressure_pct == 0 {
+            DEFAULT_FD_PRESSURE_PCT
+        } else {
+            config.fd_pressure_pct
+        };
+        if limit == 0 || open * 100 < limit * pct as u64 {
+            return 0;
+        }
+    }
+    let pid = ctx.pid();
+    let now = unsafe { bpf_ktime_get_ns() };
+    if pid == 0 || !throttle_fd_pressure(pid, exhausted, now, &config) {
+        return 0;
+    }
+    let flags = if exhausted { fd_pressure::EXHAUSTED } else { 0 };
+    emit_activity_event(&ctx, EventType::FdPressure, now, open, limit, flags, 0)
+}
+
+#[tracepoint(category = "signal", name = "signal_generate")]
+pub fn trace_signal_generate(ctx: TracePointContext) -> u32 {
+    try_trace_signal_generate(ctx)
+}
+
+/// Signals in `signal_mask`, reported by the sending task: `current` is the
+/// caller of kill(2) and friends, or the process the kernel raised the
+/// signal on behalf of (faults, OOM kills).
+///
+/// `data` holds the target pid, `data2` the sign-extended `si_code`, `aux`
+/// the signal number and `aux2` the `signal_event` flags and result.
+fn try_trace_signal_generate(ctx: TracePointContext) -> u32 {
+    let sig = match tp_read_u32(&ctx, tracepoint_layout::SIGNAL_SIG) {
+        Some(sig) if (1..64).contains(&sig) => sig,
+        _ => return 0,
+    };
+    let config = load_config();
+    let mask = if config.signal_mask == 0 {
+        DEFAULT_SIGNAL_MASK
+    } else {
+        config.signal_mask
+    };
+    if mask & (1u64 << sig) == 0 {
+        return 0;
+    }
+    let target = match tp_read_u32(&ctx, tracepoint_layout::SIGNAL_PID) {
+        Some(target) if target != 0 => target,
+        _ => return 0,
+    };
+    let code = tp_read_u32(&ctx, tracepoint_layout::SIGNAL_CODE).unwrap_or(0) as i32;
+    let group = tp_read_u32(&ctx, tracepoint_layout::SIGNAL_GROUP).unwrap_or(0);
+    let result = tp_read_u32(&ctx, tracepoint_layout::SIGNAL_RESULT).unwrap_or(0);
+    let mut flags = (result & 0xff) << signal_event::RESULT_SHIFT;
+    if group != 0 {
+        flags |= signal_event::GROUP;
+    }
+    if code > 0 {
+        flags |= signal_event::KERNEL;
+    }
+    let now = unsafe { bpf_ktime_get_ns() };
+    emit_activity_event(
+        &ctx,
+        EventType::Signal,
+        now,
+        target as u64,
+        code as i64 as u64,
+        sig,
+        flags,
+    )
+}
+
+#[tracepoint(category = "oom", name = "mark_victim")]
+pub fn trace_oom_mark_victim(ctx: TracePointContext) -> u32 {
+    try_trace_oom_mark_victim(ctx)
+}
+
+/// OOM kills, reported by the task whose allocation ran out of memory (or
+/// the one that hit its cgroup's limit); the victim is usually another
+/// process.
+///
+/// `data` holds the victim pid and `data2` its RSS in bytes, from the
+/// tracepoint's `anon_rss`, `file_rss` and `shmem_rss` (in KiB) on kernels
+/// that have them, 0 elsewhere.
+fn try_trace_oom_mark_victim(ctx: TracePointContext) -> u32 {
+    let victim = match tp_read_u32(&ctx, tracepoint_layout::MARK_VICTIM_PID) {
+        Some(victim) if victim != 0 => victim,
+        _ => return 0,
+    };
+    let config = load_config();
+    let mut rss_kib: u64 = 0;
+    if config.oom_anon_rss_offset != 0 {
+        for offset in [
+            config.oom_anon_rss_offset,
+            config.oom_file_rss_offset,
+            config.oom_shmem_rss_offset,
+        ] {
+            if offset != 0 {
+                rss_kib += tp_read_u64(&ctx, offset as usize).unwrap_or(0);
+            }
+        }
+    }
+    let now = unsafe { bpf_ktime_get_ns() };
+    emit_activity_event(
+        &ctx,
+        EventType::OomKill,
+        now,
+        victim as u64,
+        rss_kib.saturating_mul(1024),
+        0,
+        0,
+    )
+}
+
 #[btf_tracepoint(function = "page_fault_user")]
 pub fn trace_page_fault_user(ctx: BtfTracePointContext) -> u32 {
     try_trace_page_fault(ctx, PageFaultOrigin::User)
@@ -1255,9 +2129,60 @@ pub fn trace_sys_enter(ctx: TracePointContext) -> u32 {
     try_trace_sys_enter(ctx)
 }
 
+/// Syscall numbers above this are not counted (x32 calls, `-1` for calls
+/// the kernel skipped).
+const MAX_SYSCALL_NR: u64 = 1024;
+
+/// Count the call in the process's tally on this CPU, and emit the tally
+/// once it reaches the batch size or has been open for the flush interval.
+/// `data` holds the syscall number and `data2` the calls in the batch.
 fn try_trace_sys_enter(ctx: TracePointContext) -> u32 {
-    let _ = ctx;
-    0
+    let nr = match tp_read_u64(&ctx, tracepoint_layout::SYS_ENTER_ID) {
+        Some(nr) if nr < MAX_SYSCALL_NR => nr,
+        _ => return 0,
+    };
+    let pid = ctx.pid();
+    if pid == 0 {
+        return 0;
+    }
+    let now = unsafe { bpf_ktime_get_ns() };
+    let key = ((pid as u64) << 32) | nr;
+    let tallies = unsafe { &raw const SYSCALL_TALLY };
+    let ptr = match unsafe { (*tallies).get_ptr_mut(&key) } {
+        Some(ptr) => ptr,
+        None => {
+            let fresh = SyscallTally {
+                count: 0,
+                since_ns: now,
+            };
+            let _ = unsafe { (*tallies).insert(&key, &fresh, 0) };
+            match unsafe { (*tallies).get_ptr_mut(&key) } {
+                Some(ptr) => ptr,
+                None => return 0,
+            }
+        }
+    };
+    let tally = unsafe { &mut *ptr };
+    tally.count += 1;
+
+    let cfg = load_config();
+    let batch = if cfg.syscall_batch == 0 {
+        DEFAULT_SYSCALL_BATCH
+    } else {
+        cfg.syscall_batch
+    };
+    let interval = if cfg.syscall_flush_interval_ns == 0 {
+        DEFAULT_SYSCALL_FLUSH_INTERVAL_NS
+    } else {
+        cfg.syscall_flush_interval_ns
+    };
+    if tally.count < batch as u64 && now.saturating_sub(tally.since_ns) < interval {
+        return 0;
+    }
+    let count = tally.count;
+    tally.count = 0;
+    tally.since_ns = now;
+    emit_activity_event(&ctx, EventType::Syscall, now, nr, count, 0, 0)
 }
 
 #[cfg(all(not(test), target_arch = "bpf"))]
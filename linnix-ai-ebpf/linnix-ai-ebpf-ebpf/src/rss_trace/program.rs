@@ -4,7 +4,7 @@ use aya_ebpf::{
     programs::TracePointContext,
     EbpfContext,
 };
-use linnix_ai_ebpf_common::RssTraceEvent;
+use linnix_ai_ebpf_common::{tracepoint_layout, RssTraceEvent};
 
 #[map(name = "RSS_EVENTS")]
 static mut RSS_EVENTS: PerfEventArray<RssTraceEvent> = PerfEventArray::new(0);
@@ -22,8 +22,10 @@ fn handle_rss_stat(ctx: TracePointContext) -> u32 {
         return 0;
     }
 
-    let member_idx: i32 = unsafe { ctx.read_at::<i32>(20) }.unwrap_or_default();
-    let delta: i64 = unsafe { ctx.read_at::<i64>(24) }.unwrap_or_default();
+    let member_idx: i32 =
+        unsafe { ctx.read_at::<i32>(tracepoint_layout::RSS_STAT_MEMBER) }.unwrap_or_default();
+    let delta: i64 =
+        unsafe { ctx.read_at::<i64>(tracepoint_layout::RSS_STAT_SIZE) }.unwrap_or_default();
 
     let event = RssTraceEvent {
         pid,
@@ -0,0 +1,217 @@
+use serde::Deserialize;
+use std::collections::VecDeque;
+
+/// Number of event-rate samples kept for the sparkline.
+pub const RATE_HISTORY: usize = 120;
+/// Number of alerts kept in the alert pane.
+pub const ALERT_HISTORY: usize = 50;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProcessRow {
+    pub pid: u32,
+    #[serde(default)]
+    pub comm: String,
+    pub cpu_pct: Option<f32>,
+    pub mem_pct: Option<f32>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Status {
+    #[serde(default)]
+    pub cpu_pct: f64,
+    #[serde(default)]
+    pub rss_mb: u64,
+    #[serde(default)]
+    pub events_per_sec: u64,
+    #[serde(default)]
+    pub rb_overflows: u64,
+    #[serde(default)]
+    pub offline: bool,
+    #[serde(default)]
+    pub transport: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AlertRow {
+    pub rule: String,
+    pub severity: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ActionRow {
+    pub id: String,
+    pub action: serde_json::Value,
+    pub reason: String,
+    pub status: String,
+    pub expires_at: u64,
+}
+
+impl ActionRow {
+    /// Short description of the proposed action, e.g. `kill_process pid=42`.
+    pub fn summary(&self) -> String {
+        let kind = self
+            .action
+            .get("type")
+            .and_then(|v| v.as_str())
+            .unwrap_or("unknown");
+        match self.action.get("pid").and_then(|v| v.as_u64()) {
+            Some(pid) => format!("{kind} pid={pid}"),
+            None => match self.action.get("cgroup_path").and_then(|v| v.as_str()) {
+                Some(path) => format!("{kind} {path}"),
+                None => kind.to_string(),
+            },
+        }
+    }
+}
+
+pub enum Update {
+    Processes(Vec<ProcessRow>),
+    Status(Status),
+    Actions(Vec<ActionRow>),
+    Alert(AlertRow),
+    Error(String),
+}
+
+#[derive(Default)]
+pub struct App {
+    pub top_cpu: Vec<ProcessRow>,
+    pub top_mem: Vec<ProcessRow>,
+    pub status: Status,
+    pub rates: VecDeque<u64>,
+    pub alerts: VecDeque<AlertRow>,
+    pub actions: Vec<ActionRow>,
+    pub selected: usize,
+    pub message: Option<String>,
+}
+
+impl App {
+    pub fn apply(&mut self, update: Update) {
+        match update {
+            Update::Processes(rows) => self.set_processes(rows),
+            Update::Status(status) => {
+                if self.rates.len() == RATE_HISTORY {
+                    self.rates.pop_front();
+                }
+                self.rates.push_back(status.events_per_sec);
+                self.status = status;
+            }
+            Update::Actions(actions) => {
+                self.actions = actions
+                    .into_iter()
+                    .filter(|a| a.status == "pending")
+                    .collect();
+                if self.selected >= self.actions.len() {
+                    self.selected = self.actions.len().saturating_sub(1);
+                }
+            }
+            Update::Alert(alert) => {
+                if self.alerts.len() == ALERT_HISTORY {
+                    self.alerts.pop_back();
+                }
+                self.alerts.push_front(alert);
+            }
+            Update::Error(err) => self.message = Some(err),
+        }
+    }
+
+    fn set_processes(&mut self, rows: Vec<ProcessRow>) {
+        let mut by_cpu: Vec<ProcessRow> = rows
+            .iter()
+            .filter(|r| r.cpu_pct.is_some())
+            .cloned()
+            .collect();
+        by_cpu.sort_by(|a, b| {
+            b.cpu_pct
+                .partial_cmp(&a.cpu_pct)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let mut by_mem: Vec<ProcessRow> =
+            rows.into_iter().filter(|r| r.mem_pct.is_some()).collect();
+        by_mem.sort_by(|a, b| {
+            b.mem_pct
+                .partial_cmp(&a.mem_pct)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        self.top_cpu = by_cpu;
+        self.top_mem = by_mem;
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.actions.is_empty() {
+            self.selected = (self.selected + 1) % self.actions.len();
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        if !self.actions.is_empty() {
+            self.selected = (self.selected + self.actions.len() - 1) % self.actions.len();
+        }
+    }
+
+    pub fn selected_action(&self) -> Option<&ActionRow> {
+        self.actions.get(self.selected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(pid: u32, cpu: Option<f32>, mem: Option<f32>) -> ProcessRow {
+        ProcessRow {
+            pid,
+            comm: format!("p{pid}"),
+            cpu_pct: cpu,
+            mem_pct: mem,
+        }
+    }
+
+    #[test]
+    fn processes_are_ranked_by_cpu_and_mem() {
+        let mut app = App::default();
+        app.apply(Update::Processes(vec![
+            row(1, Some(5.0), Some(40.0)),
+            row(2, Some(90.0), None),
+            row(3, None, Some(60.0)),
+        ]));
+        let cpu: Vec<u32> = app.top_cpu.iter().map(|r| r.pid).collect();
+        let mem: Vec<u32> = app.top_mem.iter().map(|r| r.pid).collect();
+        assert_eq!(cpu, vec![2, 1]);
+        assert_eq!(mem, vec![3, 1]);
+    }
+
+    #[test]
+    fn rate_history_is_bounded() {
+        let mut app = App::default();
+        for i in 0..(RATE_HISTORY as u64 + 10) {
+            app.apply(Update::Status(Status {
+                events_per_sec: i,
+                ..Status::default()
+            }));
+        }
+        assert_eq!(app.rates.len(), RATE_HISTORY);
+        assert_eq!(app.rates.front().copied(), Some(10));
+    }
+
+    #[test]
+    fn only_pending_actions_are_selectable() {
+        let mut app = App::default();
+        let action = |id: &str, status: &str| ActionRow {
+            id: id.into(),
+            action: serde_json::json!({"type": "kill_process", "pid": 7, "signal": 9}),
+            reason: "test".into(),
+            status: status.into(),
+            expires_at: 0,
+        };
+        app.selected = 5;
+        app.apply(Update::Actions(vec![
+            action("action-1", "executed"),
+            action("action-2", "pending"),
+        ]));
+        assert_eq!(app.selected, 0);
+        let selected = app.selected_action().expect("pending action");
+        assert_eq!(selected.id, "action-2");
+        assert_eq!(selected.summary(), "kill_process pid=7");
+    }
+}
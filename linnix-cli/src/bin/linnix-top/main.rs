@@ -0,0 +1,192 @@
+//! `linnix-top`: interactive terminal monitor for a running cognitod.
+//!
+//! Polls `/status`, `/processes` and `/actions`, follows `/alerts` over SSE,
+//! and lets an operator approve or reject pending enforcement actions without
+//! opening the web dashboard.
+
+use clap::Parser;
+use crossterm::event::{Event, EventStream, KeyCode, KeyEventKind, KeyModifiers};
+use futures_util::StreamExt;
+use reqwest::Client;
+use std::error::Error;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+mod app;
+#[path = "../../sse.rs"]
+mod sse;
+mod ui;
+
+use app::{ActionRow, AlertRow, App, ProcessRow, Status, Update};
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// Base URL of the Cognitod service
+    #[clap(long, default_value = "http://127.0.0.1:3000")]
+    url: String,
+
+    /// Refresh interval in milliseconds
+    #[clap(long, default_value_t = 1000)]
+    interval_ms: u64,
+
+    /// Name recorded as approver for approve/reject actions
+    #[clap(long)]
+    approver: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    let client = Client::new();
+    let approver = args
+        .approver
+        .clone()
+        .or_else(|| std::env::var("USER").ok())
+        .unwrap_or_else(|| "linnix-top".to_string());
+
+    let (tx, rx) = mpsc::channel::<Update>(256);
+    spawn_poller(
+        client.clone(),
+        args.url.clone(),
+        Duration::from_millis(args.interval_ms.max(100)),
+        tx.clone(),
+    );
+    spawn_alert_stream(client.clone(), args.url.clone(), tx);
+
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, rx, &client, &args.url, &approver).await;
+    ratatui::restore();
+    result
+}
+
+async fn run(
+    terminal: &mut ratatui::DefaultTerminal,
+    mut rx: mpsc::Receiver<Update>,
+    client: &Client,
+    url: &str,
+    approver: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut app = App::default();
+    let mut keys = EventStream::new();
+
+    loop {
+        terminal.draw(|frame| ui::draw(frame, &app))?;
+
+        tokio::select! {
+            Some(update) = rx.recv() => app.apply(update),
+            Some(event) = keys.next() => {
+                let Event::Key(key) = event? else { continue };
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(());
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => app.select_next(),
+                    KeyCode::Up | KeyCode::Char('k') => app.select_prev(),
+                    KeyCode::Char('a') => decide(&mut app, client, url, approver, "approve").await,
+                    KeyCode::Char('r') => decide(&mut app, client, url, approver, "reject").await,
+                    _ => {}
+                }
+            }
+            else => return Ok(()),
+        }
+    }
+}
+
+/// Approve or reject the currently selected pending action.
+async fn decide(app: &mut App, client: &Client, url: &str, approver: &str, verb: &str) {
+    let Some(id) = app.selected_action().map(|a| a.id.clone()) else {
+        return;
+    };
+    let resp = client
+        .post(format!("{url}/actions/{id}/{verb}"))
+        .json(&serde_json::json!({ "approver": approver }))
+        .send()
+        .await;
+    app.message = Some(match resp {
+        Ok(r) if r.status().is_success() => {
+            app.actions.retain(|a| a.id != id);
+            app.select_prev();
+            format!("{verb} {id}: ok")
+        }
+        Ok(r) => format!("{verb} {id}: {}", r.status()),
+        Err(e) => format!("{verb} {id}: {e}"),
+    });
+}
+
+fn spawn_poller(client: Client, url: String, interval: Duration, tx: mpsc::Sender<Update>) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let updates = [
+                fetch::<Status>(&client, &format!("{url}/status"))
+                    .await
+                    .map(Update::Status),
+                fetch::<Vec<ProcessRow>>(&client, &format!("{url}/processes"))
+                    .await
+                    .map(Update::Processes),
+                fetch::<Vec<ActionRow>>(&client, &format!("{url}/actions"))
+                    .await
+                    .map(Update::Actions),
+            ];
+            for update in updates {
+                let update = update.unwrap_or_else(|e| Update::Error(e.to_string()));
+                if tx.send(update).await.is_err() {
+                    return;
+                }
+            }
+        }
+    });
+}
+
+async fn fetch<T: serde::de::DeserializeOwned>(
+    client: &Client,
+    url: &str,
+) -> Result<T, reqwest::Error> {
+    client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+}
+
+fn spawn_alert_stream(client: Client, url: String, tx: mpsc::Sender<Update>) {
+    tokio::spawn(async move {
+        loop {
+            match sse::connect_sse(&client, &format!("{url}/alerts")).await {
+                Ok(mut stream) => {
+                    while let Some(event) = stream.next().await {
+                        match event {
+                            Ok(sse::SseEvent::Message(msg)) => {
+                                let json = msg.strip_prefix("data: ").unwrap_or(&msg);
+                                if let Ok(alert) = serde_json::from_str::<AlertRow>(json) {
+                                    if tx.send(Update::Alert(alert)).await.is_err() {
+                                        return;
+                                    }
+                                }
+                            }
+                            Ok(sse::SseEvent::Heartbeat) => {}
+                            Err(_) => break,
+                        }
+                    }
+                }
+                Err(e) => {
+                    if tx
+                        .send(Update::Error(format!("alert stream: {e}")))
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            }
+            tokio::time::sleep(Duration::from_secs(2)).await;
+        }
+    });
+}
@@ -0,0 +1,179 @@
+use crate::app::{App, ProcessRow};
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{
+    Block, Borders, List, ListItem, ListState, Paragraph, Row, Sparkline, Table,
+};
+use ratatui::Frame;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Length(5),
+            Constraint::Min(8),
+            Constraint::Length(10),
+            Constraint::Length(1),
+        ])
+        .split(frame.area());
+
+    draw_header(frame, rows[0], app);
+    draw_rate(frame, rows[1], app);
+
+    let tables = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(rows[2]);
+    draw_processes(frame, tables[0], "Top CPU", &app.top_cpu, |r| r.cpu_pct);
+    draw_processes(frame, tables[1], "Top RSS (mem%)", &app.top_mem, |r| {
+        r.mem_pct
+    });
+
+    let bottom = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(rows[3]);
+    draw_alerts(frame, bottom[0], app);
+    draw_actions(frame, bottom[1], app);
+
+    let footer = app
+        .message
+        .clone()
+        .unwrap_or_else(|| "q quit  ↑/↓ select action  a approve  r reject".to_string());
+    frame.render_widget(
+        Paragraph::new(footer).style(Style::default().fg(Color::DarkGray)),
+        rows[4],
+    );
+}
+
+fn draw_header(frame: &mut Frame, area: Rect, app: &App) {
+    let status = &app.status;
+    let mode = if status.offline { "offline" } else { "online" };
+    let line = Line::from(vec![
+        Span::styled("linnix ", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(format!(
+            "cpu {:.1}%  rss {} MB  events/s {}  rb_overflows {}  transport {}  {}",
+            status.cpu_pct,
+            status.rss_mb,
+            status.events_per_sec,
+            status.rb_overflows,
+            if status.transport.is_empty() {
+                "-"
+            } else {
+                &status.transport
+            },
+            mode
+        )),
+    ]);
+    frame.render_widget(
+        Paragraph::new(line).block(Block::default().borders(Borders::ALL).title("cognitod")),
+        area,
+    );
+}
+
+fn draw_rate(frame: &mut Frame, area: Rect, app: &App) {
+    let data: Vec<u64> = app.rates.iter().copied().collect();
+    let sparkline = Sparkline::default()
+        .block(Block::default().borders(Borders::ALL).title("Event rate"))
+        .data(&data)
+        .style(Style::default().fg(Color::Cyan));
+    frame.render_widget(sparkline, area);
+}
+
+fn draw_processes(
+    frame: &mut Frame,
+    area: Rect,
+    title: &str,
+    rows: &[ProcessRow],
+    metric: fn(&ProcessRow) -> Option<f32>,
+) {
+    let visible = area.height.saturating_sub(3) as usize;
+    let body = rows.iter().take(visible).map(|r| {
+        Row::new(vec![
+            r.pid.to_string(),
+            metric(r)
+                .map(|v| format!("{v:.1}"))
+                .unwrap_or_else(|| "-".into()),
+            r.comm.clone(),
+        ])
+    });
+    let table = Table::new(
+        body,
+        [
+            Constraint::Length(8),
+            Constraint::Length(7),
+            Constraint::Min(10),
+        ],
+    )
+    .header(Row::new(vec!["PID", "%", "CMD"]).style(Style::default().add_modifier(Modifier::BOLD)))
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(title.to_string()),
+    );
+    frame.render_widget(table, area);
+}
+
+fn severity_color(severity: &str) -> Color {
+    match severity.to_ascii_lowercase().as_str() {
+        "high" => Color::Red,
+        "medium" => Color::Yellow,
+        "low" => Color::Blue,
+        _ => Color::Gray,
+    }
+}
+
+fn draw_alerts(frame: &mut Frame, area: Rect, app: &App) {
+    let items: Vec<ListItem> = app
+        .alerts
+        .iter()
+        .map(|a| {
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("[{}] ", a.severity.to_uppercase()),
+                    Style::default().fg(severity_color(&a.severity)),
+                ),
+                Span::raw(format!("{} - {}", a.rule, a.message)),
+            ]))
+        })
+        .collect();
+    frame.render_widget(
+        List::new(items).block(Block::default().borders(Borders::ALL).title("Alerts")),
+        area,
+    );
+}
+
+fn draw_actions(frame: &mut Frame, area: Rect, app: &App) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let items: Vec<ListItem> = app
+        .actions
+        .iter()
+        .map(|a| {
+            ListItem::new(format!(
+                "{}  {}  expires {}s  {}",
+                a.id,
+                a.summary(),
+                a.expires_at.saturating_sub(now),
+                a.reason
+            ))
+        })
+        .collect();
+    let mut state = ListState::default();
+    if !app.actions.is_empty() {
+        state.select(Some(app.selected));
+    }
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Pending actions"),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, area, &mut state);
+}
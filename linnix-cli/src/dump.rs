@@ -0,0 +1,126 @@
+use clap::ValueEnum;
+use futures_util::StreamExt;
+use reqwest::Client;
+use std::error::Error;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Dataset {
+    Incidents,
+    Actions,
+    Alerts,
+}
+
+impl Dataset {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Incidents => "incidents",
+            Self::Actions => "actions",
+            Self::Alerts => "alerts",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum DumpFormat {
+    Csv,
+    Parquet,
+}
+
+pub struct DumpRequest<'a> {
+    pub dataset: Dataset,
+    pub since: Option<&'a str>,
+    pub start: Option<u64>,
+    pub end: Option<u64>,
+    pub format: DumpFormat,
+    pub columns: Option<&'a str>,
+    pub output: Option<&'a Path>,
+}
+
+/// Parse a window like `90s`, `15m`, `6h` or `7d` into seconds.
+pub fn parse_window(window: &str) -> Option<u64> {
+    let window = window.trim();
+    let (split, _) = window.char_indices().last()?;
+    let (n, unit) = window.split_at(split);
+    let n: u64 = n.parse().ok()?;
+    let scale = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        _ => return None,
+    };
+    n.checked_mul(scale)
+}
+
+/// Stream `/export/{dataset}` to a file, or stdout without one.
+pub async fn run_dump(
+    client: &Client,
+    base: &str,
+    req: DumpRequest<'_>,
+) -> Result<(), Box<dyn Error>> {
+    let mut query: Vec<(&str, String)> = Vec::new();
+    let start = match (req.start, req.since) {
+        (Some(start), _) => Some(start),
+        (None, Some(since)) => {
+            let secs = parse_window(since).ok_or_else(|| format!("invalid --since '{since}'"))?;
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+            Some(now.saturating_sub(secs))
+        }
+        (None, None) => None,
+    };
+    if let Some(start) = start {
+        query.push(("start", start.to_string()));
+    }
+    if let Some(end) = req.end {
+        query.push(("end", end.to_string()));
+    }
+    let format = match req.format {
+        DumpFormat::Csv => "csv",
+        DumpFormat::Parquet => "parquet",
+    };
+    query.push(("format", format.to_string()));
+    if let Some(columns) = req.columns {
+        query.push(("columns", columns.to_string()));
+    }
+
+    let resp = client
+        .get(format!("{}/export/{}", base, req.dataset.as_str()))
+        .query(&query)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+        return Err(format!("export failed ({status}): {body}").into());
+    }
+
+    let mut out: Box<dyn AsyncWrite + Unpin> = match req.output {
+        Some(path) => Box::new(tokio::fs::File::create(path).await?),
+        None => Box::new(tokio::io::stdout()),
+    };
+    let mut body = resp.bytes_stream();
+    while let Some(chunk) = body.next().await {
+        out.write_all(&chunk?).await?;
+    }
+    out.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_windows() {
+        assert_eq!(parse_window("90s"), Some(90));
+        assert_eq!(parse_window("15m"), Some(900));
+        assert_eq!(parse_window("6h"), Some(21_600));
+        assert_eq!(parse_window("7d"), Some(604_800));
+        assert_eq!(parse_window("7"), None);
+        assert_eq!(parse_window("h"), None);
+        assert_eq!(parse_window(""), None);
+    }
+}
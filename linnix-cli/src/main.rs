@@ -8,14 +8,18 @@ use std::error::Error;
 mod alert;
 mod blame;
 mod doctor;
+mod dump;
 mod event;
 mod export;
+mod merge;
 mod pretty;
 mod processes;
 mod sse;
 use alert::Alert;
+use dump::{Dataset, DumpFormat, DumpRequest};
 use event::ProcessEvent;
 use export::{export_incident, Format};
+use merge::MergeFormat;
 use pretty::PrettyEvent;
 
 #[derive(clap::Parser, Debug)]
@@ -55,6 +59,42 @@ enum Command {
         #[clap(long, value_enum, default_value = "txt")]
         format: Format,
     },
+    /// Dump incidents, enforcement actions or alert history as CSV or Parquet
+    Dump {
+        /// Dataset to export
+        #[clap(value_enum)]
+        dataset: Dataset,
+        /// Only rows from this window (e.g. 6h, 7d)
+        #[clap(long, conflicts_with = "start")]
+        since: Option<String>,
+        /// Range start, unix seconds
+        #[clap(long)]
+        start: Option<u64>,
+        /// Range end, unix seconds
+        #[clap(long)]
+        end: Option<u64>,
+        /// Output format (parquet needs a daemon built with it)
+        #[clap(long, value_enum, default_value = "csv")]
+        format: DumpFormat,
+        /// Comma-separated columns to include
+        #[clap(long)]
+        columns: Option<String>,
+        /// Write to this file instead of stdout
+        #[clap(long, short)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Merge JSONL recordings from several nodes into one timeline
+    Merge {
+        /// Recordings (`.jsonl` or `.zst` segments), as `path` or `label=path`
+        #[clap(required = true)]
+        inputs: Vec<String>,
+        /// Output format
+        #[clap(long, value_enum, default_value = "jsonl")]
+        format: MergeFormat,
+        /// Write to this file instead of stdout
+        #[clap(long, short)]
+        output: Option<std::path::PathBuf>,
+    },
     /// Blame a node for performance issues (requires kubectl)
     Blame {
         /// Node name to analyze
@@ -91,6 +131,18 @@ struct Status {
     rb_overflows: u64,
     rate_limited: u64,
     offline: bool,
+    /// Absent from daemons older than the per-type breakdown
+    #[serde(default)]
+    event_types: Vec<EventTypeStatus>,
+}
+
+#[derive(Deserialize, Debug)]
+struct EventTypeStatus {
+    event_type: String,
+    events: u64,
+    bytes: u64,
+    share_pct: f64,
+    avg_processing_us: f64,
 }
 
 #[tokio::main]
@@ -110,6 +162,39 @@ async fn main() -> Result<(), Box<dyn Error>> {
         return Ok(());
     }
 
+    if let Some(Command::Dump {
+        dataset,
+        since,
+        start,
+        end,
+        format,
+        columns,
+        output,
+    }) = args.command.clone()
+    {
+        let req = DumpRequest {
+            dataset,
+            since: since.as_deref(),
+            start,
+            end,
+            format,
+            columns: columns.as_deref(),
+            output: output.as_deref(),
+        };
+        dump::run_dump(&client, &args.url, req).await?;
+        return Ok(());
+    }
+
+    if let Some(Command::Merge {
+        inputs,
+        format,
+        output,
+    }) = args.command.clone()
+    {
+        merge::run_merge(&inputs, format, output.as_deref())?;
+        return Ok(());
+    }
+
     if let Some(Command::Blame { node_name }) = args.command {
         blame::run_blame(&node_name).await?;
         return Ok(());
@@ -162,6 +247,19 @@ async fn main() -> Result<(), Box<dyn Error>> {
             status.rate_limited,
             status.offline
         );
+        if !status.event_types.is_empty() {
+            println!();
+            println!(
+                "{:<12} {:>12} {:>14} {:>7} {:>9}",
+                "event_type", "events", "bytes", "share%", "avg_us"
+            );
+            for row in &status.event_types {
+                println!(
+                    "{:<12} {:>12} {:>14} {:>7.1} {:>9.1}",
+                    row.event_type, row.events, row.bytes, row.share_pct, row.avg_processing_us
+                );
+            }
+        }
         return Ok(());
     }
 
@@ -0,0 +1,496 @@
+//! `linnix-cli merge`: one timeline from the JSONL recordings of several
+//! nodes.
+//!
+//! Event `ts_ns` stamps come from each node's monotonic clock. Recordings
+//! carry `clock_anchor` lines pairing that clock with wall-clock time; every
+//! event is placed by the latest anchor before it (the first one for events
+//! preceding all anchors). Snapshots are stamped in wall-clock seconds
+//! already. The merged records are ordered by wall-clock time, ties keeping
+//! the order of the inputs.
+
+use clap::ValueEnum;
+use linnix_ai_ebpf_common::{signal_event, EventType};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum MergeFormat {
+    /// One merged record per line
+    Jsonl,
+    /// Report with per-node summaries and the timeline
+    Json,
+    /// Self-contained timeline page, one column per node
+    Html,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClockAnchor {
+    host: Option<String>,
+    monotonic_ns: u64,
+    wall_ns: u64,
+}
+
+impl ClockAnchor {
+    fn wall_ns(&self, ts_ns: u64) -> u64 {
+        (self.wall_ns as i128 + ts_ns as i128 - self.monotonic_ns as i128).max(0) as u64
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Kind {
+    Event,
+    Snapshot,
+}
+
+#[derive(Debug, Serialize)]
+struct Entry {
+    wall_ns: u64,
+    node: String,
+    kind: Kind,
+    record: Value,
+}
+
+#[derive(Debug, Serialize)]
+struct NodeSummary {
+    node: String,
+    path: String,
+    events: usize,
+    snapshots: usize,
+    anchors: usize,
+    /// Lines that were neither events, snapshots nor anchors
+    skipped: usize,
+    first_ns: Option<u64>,
+    last_ns: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    start_ns: Option<u64>,
+    end_ns: Option<u64>,
+    nodes: Vec<NodeSummary>,
+    timeline: Vec<Entry>,
+}
+
+/// `path` or `label=path`. A `=` after a `/` belongs to the path.
+fn parse_input(spec: &str) -> (Option<&str>, &Path) {
+    match spec.split_once('=') {
+        Some((label, path)) if !label.is_empty() && !label.contains('/') => {
+            (Some(label), Path::new(path))
+        }
+        _ => (None, Path::new(spec)),
+    }
+}
+
+fn open(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let file = File::open(path)?;
+    let reader: Box<dyn Read> = if path.extension().is_some_and(|ext| ext == "zst") {
+        Box::new(zstd::Decoder::new(file)?)
+    } else {
+        Box::new(file)
+    };
+    Ok(Box::new(BufReader::new(reader)))
+}
+
+/// Put the records of one recording on the wall clock.
+fn read_recording(
+    label: Option<&str>,
+    path: &Path,
+    input: impl BufRead,
+) -> Result<(NodeSummary, Vec<Entry>), Box<dyn Error>> {
+    let mut anchor: Option<ClockAnchor> = None;
+    let mut host = None;
+    // Events read before the first anchor, with their position
+    let mut unanchored: Vec<(usize, u64, Value)> = Vec::new();
+    let mut entries: Vec<(usize, u64, Kind, Value)> = Vec::new();
+    let mut summary = NodeSummary {
+        node: String::new(),
+        path: path.display().to_string(),
+        events: 0,
+        snapshots: 0,
+        anchors: 0,
+        skipped: 0,
+        first_ns: None,
+        last_ns: None,
+    };
+
+    for (index, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Ok(Value::Object(mut record)) = serde_json::from_str::<Value>(&line) else {
+            summary.skipped += 1;
+            continue;
+        };
+        if let Some(value) = record.remove("clock_anchor") {
+            let next: ClockAnchor = serde_json::from_value(value)
+                .map_err(|e| format!("{}:{}: bad clock anchor: {e}", path.display(), index + 1))?;
+            if anchor.is_none() {
+                for (index, ts_ns, record) in unanchored.drain(..) {
+                    entries.push((index, next.wall_ns(ts_ns), Kind::Event, record));
+                }
+            }
+            host = host.or(next.host.clone());
+            summary.anchors += 1;
+            anchor = Some(next);
+            continue;
+        }
+        let ts_ns = record.get("ts_ns").and_then(Value::as_u64);
+        let timestamp = record.get("timestamp").and_then(Value::as_u64);
+        match (ts_ns, timestamp) {
+            (Some(ts_ns), _) if record.contains_key("pid") => {
+                summary.events += 1;
+                match &anchor {
+                    Some(anchor) => entries.push((
+                        index,
+                        anchor.wall_ns(ts_ns),
+                        Kind::Event,
+                        Value::Object(record),
+                    )),
+                    None => unanchored.push((index, ts_ns, Value::Object(record))),
+                }
+            }
+            (_, Some(secs)) => {
+                summary.snapshots += 1;
+                entries.push((
+                    index,
+                    secs.saturating_mul(1_000_000_000),
+                    Kind::Snapshot,
+                    Value::Object(record),
+                ));
+            }
+            _ => summary.skipped += 1,
+        }
+    }
+    if !unanchored.is_empty() {
+        return Err(format!(
+            "{} has events but no clock anchors; it was recorded by a daemon that does not write them",
+            path.display()
+        )
+        .into());
+    }
+
+    summary.node = label.map(str::to_string).or(host).unwrap_or_else(|| {
+        path.file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| summary.path.clone())
+    });
+    entries.sort_by_key(|(index, ..)| *index);
+    let entries: Vec<Entry> = entries
+        .into_iter()
+        .map(|(_, wall_ns, kind, record)| Entry {
+            wall_ns,
+            node: summary.node.clone(),
+            kind,
+            record,
+        })
+        .collect();
+    summary.first_ns = entries.iter().map(|e| e.wall_ns).min();
+    summary.last_ns = entries.iter().map(|e| e.wall_ns).max();
+    Ok((summary, entries))
+}
+
+/// Order the entries of all recordings by wall-clock time.
+fn merge(recordings: Vec<(NodeSummary, Vec<Entry>)>) -> Report {
+    let mut nodes = Vec::with_capacity(recordings.len());
+    let mut timeline = Vec::new();
+    for (summary, entries) in recordings {
+        nodes.push(summary);
+        timeline.extend(entries);
+    }
+    // Stable: equal stamps keep input order
+    timeline.sort_by_key(|e| e.wall_ns);
+    Report {
+        start_ns: timeline.first().map(|e| e.wall_ns),
+        end_ns: timeline.last().map(|e| e.wall_ns),
+        nodes,
+        timeline,
+    }
+}
+
+fn comm(record: &Value) -> String {
+    match record.get("comm") {
+        Some(Value::String(comm)) => comm.clone(),
+        Some(Value::Array(bytes)) => {
+            let bytes: Vec<u8> = bytes
+                .iter()
+                .filter_map(Value::as_u64)
+                .take_while(|b| *b != 0)
+                .map(|b| b as u8)
+                .collect();
+            String::from_utf8_lossy(&bytes).into_owned()
+        }
+        _ => "?".to_string(),
+    }
+}
+
+fn event_type_name(event_type: u64) -> &'static str {
+    const NAMES: [(EventType, &str); 13] = [
+        (EventType::Exec, "exec"),
+        (EventType::Fork, "fork"),
+        (EventType::Exit, "exit"),
+        (EventType::Net, "net"),
+        (EventType::FileIo, "file_io"),
+        (EventType::Syscall, "syscall"),
+        (EventType::BlockIo, "block_io"),
+        (EventType::PageFault, "page_fault"),
+        (EventType::Listen, "listen"),
+        (EventType::Connection, "connection"),
+        (EventType::FdPressure, "fd_pressure"),
+        (EventType::Signal, "signal"),
+        (EventType::OomKill, "oom_kill"),
+    ];
+    NAMES
+        .iter()
+        .find(|(t, _)| *t as u64 == event_type)
+        .map_or("unknown", |(_, name)| name)
+}
+
+/// One-line description of an entry for the HTML timeline.
+fn describe(entry: &Entry) -> String {
+    let record = &entry.record;
+    match entry.kind {
+        Kind::Event => {
+            let event_type = record
+                .get("event_type")
+                .and_then(Value::as_u64)
+                .map_or("unknown", event_type_name);
+            let pid = record.get("pid").and_then(Value::as_u64).unwrap_or(0);
+            let mut text = format!("{event_type} {}({pid})", comm(record));
+            if let Some(script) = record.get("script_path").and_then(Value::as_str) {
+                text.push_str(&format!(" {script}"));
+            }
+            if event_type == "signal" {
+                let field = |key| record.get(key).and_then(Value::as_u64).unwrap_or(0);
+                let sig = field("aux") as u32;
+                let name =
+                    signal_event::name(sig).map_or_else(|| format!("signal {sig}"), String::from);
+                text.push_str(&format!(" sent {name} to {}", field("data")));
+            }
+            if event_type == "oom_kill" {
+                let victim = record.get("data").and_then(Value::as_u64).unwrap_or(0);
+                text.push_str(&format!(" hit OOM, kernel killed {victim}"));
+            }
+            text
+        }
+        Kind::Snapshot => {
+            let pct = |key| record.get(key).and_then(Value::as_f64).unwrap_or(0.0);
+            format!(
+                "snapshot cpu {:.1}% mem {:.1}%",
+                pct("cpu_percent"),
+                pct("mem_percent")
+            )
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn utc(wall_ns: u64) -> String {
+    chrono::DateTime::from_timestamp_nanos(wall_ns as i64)
+        .format("%Y-%m-%d %H:%M:%S%.3f")
+        .to_string()
+}
+
+fn write_html(report: &Report, out: &mut dyn Write) -> io::Result<()> {
+    let start = report.start_ns.unwrap_or(0);
+    writeln!(
+        out,
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>linnix merged timeline</title>\n\
+         <style>body{{font-family:monospace;font-size:13px}}table{{border-collapse:collapse}}\
+         td,th{{border:1px solid #ccc;padding:2px 6px;text-align:left;vertical-align:top}}\
+         th{{background:#eee;position:sticky;top:0}}.snapshot{{color:#777}}</style></head><body>"
+    )?;
+    writeln!(out, "<h1>Merged timeline</h1>\n<table>")?;
+    writeln!(
+        out,
+        "<tr><th>node</th><th>recording</th><th>events</th><th>snapshots</th><th>anchors</th><th>first (UTC)</th><th>last (UTC)</th></tr>"
+    )?;
+    for node in &report.nodes {
+        writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(&node.node),
+            escape(&node.path),
+            node.events,
+            node.snapshots,
+            node.anchors,
+            node.first_ns.map(utc).unwrap_or_default(),
+            node.last_ns.map(utc).unwrap_or_default(),
+        )?;
+    }
+    writeln!(out, "</table>\n<h2>Timeline</h2>\n<table>")?;
+    write!(out, "<tr><th>UTC</th><th>+s</th>")?;
+    for node in &report.nodes {
+        write!(out, "<th>{}</th>", escape(&node.node))?;
+    }
+    writeln!(out, "</tr>")?;
+    for entry in &report.timeline {
+        let class = match entry.kind {
+            Kind::Event => "event",
+            Kind::Snapshot => "snapshot",
+        };
+        write!(
+            out,
+            "<tr class=\"{class}\"><td>{}</td><td>{:.3}</td>",
+            utc(entry.wall_ns),
+            entry.wall_ns.saturating_sub(start) as f64 / 1e9
+        )?;
+        for node in &report.nodes {
+            if node.node == entry.node {
+                write!(out, "<td>{}</td>", escape(&describe(entry)))?;
+            } else {
+                write!(out, "<td></td>")?;
+            }
+        }
+        writeln!(out, "</tr>")?;
+    }
+    writeln!(out, "</table>\n</body></html>")
+}
+
+fn write_report(report: &Report, format: MergeFormat, out: &mut dyn Write) -> io::Result<()> {
+    match format {
+        MergeFormat::Jsonl => {
+            for entry in &report.timeline {
+                serde_json::to_writer(&mut *out, entry)?;
+                out.write_all(b"\n")?;
+            }
+        }
+        MergeFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, report)?;
+            out.write_all(b"\n")?;
+        }
+        MergeFormat::Html => write_html(report, out)?,
+    }
+    out.flush()
+}
+
+/// Merge the recordings named by `inputs` (`path` or `label=path`) into
+/// `output`, or stdout without one.
+pub fn run_merge(
+    inputs: &[String],
+    format: MergeFormat,
+    output: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    let mut recordings = Vec::with_capacity(inputs.len());
+    for spec in inputs {
+        let (label, path) = parse_input(spec);
+        let input = open(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        recordings.push(read_recording(label, path, input)?);
+    }
+    let report = merge(recordings);
+    for node in report.nodes.iter().filter(|n| n.skipped > 0) {
+        eprintln!("{}: skipped {} unrecognized lines", node.path, node.skipped);
+    }
+    let mut out: Box<dyn Write> = match output {
+        Some(path) => Box::new(io::BufWriter::new(File::create(path)?)),
+        None => Box::new(io::BufWriter::new(io::stdout().lock())),
+    };
+    write_report(&report, format, &mut out)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording(label: Option<&str>, text: &str) -> (NodeSummary, Vec<Entry>) {
+        read_recording(label, Path::new("node.jsonl"), text.as_bytes()).unwrap()
+    }
+
+    fn event(pid: u32, ts_ns: u64) -> String {
+        format!(r#"{{"pid":{pid},"ppid":1,"event_type":0,"ts_ns":{ts_ns},"comm":[115,104,0]}}"#)
+    }
+
+    #[test]
+    fn places_events_by_the_latest_anchor_and_interleaves_nodes() {
+        // Node a booted 1000s before wall time 10_000s; its clock then slews by 1ms
+        let a = [
+            event(1, 999_000_000_000),
+            r#"{"clock_anchor":{"host":"a","monotonic_ns":1000000000000,"wall_ns":11000000000000}}"#
+                .to_string(),
+            event(2, 1_002_000_000_000),
+            r#"{"clock_anchor":{"host":"a","monotonic_ns":1003000000000,"wall_ns":11003001000000}}"#
+                .to_string(),
+            event(3, 1_004_000_000_000),
+            r#"{"timestamp":11001,"cpu_percent":50.0,"mem_percent":10.0}"#.to_string(),
+            "not json".to_string(),
+        ]
+        .join("\n");
+        // Node b's clock is 5000s behind a's
+        let b = [
+            r#"{"clock_anchor":{"host":"b","monotonic_ns":5000000000000,"wall_ns":11000000000000}}"#,
+            &event(10, 5_000_500_000_000),
+            &event(11, 5_003_500_000_000),
+        ]
+        .join("\n");
+
+        let (summary, _) = recording(None, &a);
+        assert_eq!(summary.node, "a");
+        assert_eq!((summary.events, summary.snapshots), (3, 1));
+        assert_eq!((summary.anchors, summary.skipped), (2, 1));
+
+        let report = merge(vec![recording(None, &a), recording(Some("web-2"), &b)]);
+        let order: Vec<(String, Option<u64>, u64)> = report
+            .timeline
+            .iter()
+            .map(|e| (e.node.clone(), e.record["pid"].as_u64(), e.wall_ns))
+            .collect();
+        let s = 1_000_000_000;
+        assert_eq!(
+            order,
+            [
+                ("a".to_string(), Some(1), 10_999 * s),
+                ("web-2".to_string(), Some(10), 11_000 * s + s / 2),
+                ("a".to_string(), None, 11_001 * s),
+                ("a".to_string(), Some(2), 11_002 * s),
+                ("web-2".to_string(), Some(11), 11_003 * s + s / 2),
+                ("a".to_string(), Some(3), 11_004 * s + 1_000_000),
+            ]
+        );
+        assert_eq!(describe(&report.timeline[0]), "exec sh(1)");
+        let kill = Entry {
+            node: "a".to_string(),
+            wall_ns: 0,
+            kind: Kind::Event,
+            record: serde_json::from_str(
+                r#"{"pid":7,"event_type":11,"comm":[115,104,0],"data":42,"aux":9}"#,
+            )
+            .unwrap(),
+        };
+        assert_eq!(describe(&kill), "signal sh(7) sent SIGKILL to 42");
+
+        let mut html = Vec::new();
+        write_report(&report, MergeFormat::Html, &mut html).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.contains("<th>a</th><th>web-2</th>"));
+        assert!(html.contains("<td></td><td>exec sh(10)</td>"));
+    }
+
+    #[test]
+    fn events_without_any_anchor_are_an_error() {
+        let err = read_recording(None, Path::new("old.jsonl"), event(1, 5).as_bytes())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("no clock anchors"), "{err}");
+        assert_eq!(parse_input("db=/var/log/a.jsonl").0, Some("db"));
+        assert_eq!(parse_input("/var/log/x=1.jsonl").0, None);
+    }
+}
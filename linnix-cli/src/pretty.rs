@@ -1,6 +1,10 @@
 use crate::event::ProcessEvent;
 use colored::*;
-use linnix_ai_ebpf_common::{BlockOp, EventType, FileOp, NetOp, PageFaultFlags, PageFaultOrigin};
+use linnix_ai_ebpf_common::{
+    fd_pressure, signal_event, BlockOp, ConnOp, EventType, FileOp, NetOp, PageFaultFlags,
+    PageFaultOrigin,
+};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 const DEVICE_MINOR_BITS: u32 = 20;
 const DEVICE_MINOR_MASK: u32 = (1 << DEVICE_MINOR_BITS) - 1;
@@ -26,6 +30,30 @@ fn decode_net_op(op: u32) -> Option<NetOp> {
     }
 }
 
+fn decode_conn_op(op: u32) -> Option<ConnOp> {
+    match op {
+        x if x == ConnOp::Syn as u32 => Some(ConnOp::Syn),
+        x if x == ConnOp::SynCookie as u32 => Some(ConnOp::SynCookie),
+        x if x == ConnOp::Established as u32 => Some(ConnOp::Established),
+        _ => None,
+    }
+}
+
+/// Remote address of a connection event: raw bytes in `data`/`data2`,
+/// family in the high half of `aux2`.
+fn decode_conn_addr(data: u64, data2: u64, aux2: u32) -> Option<IpAddr> {
+    match aux2 >> 16 {
+        2 => Some(IpAddr::V4(Ipv4Addr::from((data as u32).to_ne_bytes()))),
+        10 => {
+            let mut bytes = [0u8; 16];
+            bytes[..8].copy_from_slice(&data.to_ne_bytes());
+            bytes[8..].copy_from_slice(&data2.to_ne_bytes());
+            Some(Ipv6Addr::from(bytes).to_canonical())
+        }
+        _ => None,
+    }
+}
+
 fn decode_file_op(op: u32) -> Option<FileOp> {
     match op {
         x if x == FileOp::Read as u32 => Some(FileOp::Read),
@@ -236,6 +264,86 @@ impl PrettyEvent for ProcessEvent {
                     origin = origin
                 )
             }
+            x if x == EventType::Listen as u32 => {
+                let etype = if color {
+                    "[LISTEN]".bright_blue().bold().to_string()
+                } else {
+                    "[LISTEN]".to_string()
+                };
+                format!(
+                    "{etype} PID {styled_pid:<8} port {port} CMD {styled_comm}{tags}",
+                    port = self.data
+                )
+            }
+            x if x == EventType::Connection as u32 => {
+                let etype = if color {
+                    "[CONN]".bright_magenta().bold().to_string()
+                } else {
+                    "[CONN]".to_string()
+                };
+                let op = match decode_conn_op(self.aux) {
+                    Some(ConnOp::Syn) => "SYN",
+                    Some(ConnOp::SynCookie) => "SYN (cookie)",
+                    Some(ConnOp::Established) => "established",
+                    None => "handshake",
+                };
+                let from = decode_conn_addr(self.data, self.data2, self.aux2)
+                    .map_or_else(|| "?".to_string(), |addr| addr.to_string());
+                format!(
+                    "{etype} {op} from {from} to port {port}{tags}",
+                    port = self.aux2 & 0xffff
+                )
+            }
+            x if x == EventType::FdPressure as u32 => {
+                let etype = if color {
+                    "[FDS]".bright_red().bold().to_string()
+                } else {
+                    "[FDS]".to_string()
+                };
+                let usage = if self.aux & fd_pressure::EXHAUSTED != 0 {
+                    "out of fds".to_string()
+                } else {
+                    format!("{}+ fds open", self.data)
+                };
+                let limit = match self.data2 {
+                    0 => "limit ?".to_string(),
+                    limit => format!("limit {limit}"),
+                };
+                format!("{etype} PID {styled_pid:<8} {usage} ({limit}) CMD {styled_comm}{tags}")
+            }
+            x if x == EventType::Signal as u32 => {
+                let etype = if color {
+                    "[SIG]".red().bold().to_string()
+                } else {
+                    "[SIG]".to_string()
+                };
+                let name = signal_event::name(self.aux)
+                    .map_or_else(|| format!("signal {}", self.aux), String::from);
+                let origin = if self.aux2 & signal_event::KERNEL != 0 {
+                    " (kernel)"
+                } else {
+                    ""
+                };
+                format!(
+                    "{etype} {name} to PID {target} from PID {styled_pid:<8} CMD {styled_comm}{origin}{tags}",
+                    target = self.data
+                )
+            }
+            x if x == EventType::OomKill as u32 => {
+                let etype = if color {
+                    "[OOM]".red().bold().to_string()
+                } else {
+                    "[OOM]".to_string()
+                };
+                let rss = match self.data2 {
+                    0 => String::new(),
+                    bytes => format!(" at {} MiB RSS", bytes >> 20),
+                };
+                format!(
+                    "{etype} killed PID {victim}{rss} on allocation by PID {styled_pid:<8} CMD {styled_comm}{tags}",
+                    victim = self.data
+                )
+            }
             _ => {
                 let etype = if color {
                     "[UNKNOWN]".white().on_red().to_string()
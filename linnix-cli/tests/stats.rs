@@ -21,3 +21,25 @@ async fn stats_mode_fetches_status() {
         .success()
         .stdout(predicates::str::contains("cpu_pct"));
 }
+
+#[tokio::test]
+async fn stats_mode_prints_event_type_breakdown() {
+    let server = MockServer::start_async().await;
+    let _m = server
+        .mock_async(|when, then| {
+            when.method(GET).path("/status");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(
+                    r#"{"cpu_pct":1.2,"rss_mb":3,"events_per_sec":4,"rb_overflows":5,"rate_limited":6,"offline":false,"event_types":[{"event_type":"net","events":30,"bytes":1920,"share_pct":75.0,"avg_processing_us":12.5,"p99_processing_us":25.0}]}"#,
+                );
+        })
+        .await;
+
+    Command::new(assert_cmd::cargo::cargo_bin!("linnix-cli"))
+        .args(["--url", &server.base_url(), "--stats"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("event_type"))
+        .stdout(predicates::str::contains("1920"));
+}
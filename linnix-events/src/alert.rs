@@ -0,0 +1,130 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+
+/// Alert severity, serialized as `Info`, `Low`, `Medium` or `High`.
+/// Deserialization is case-insensitive and maps unknown values to `Info`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[non_exhaustive]
+pub enum Severity {
+    Info,
+    Low,
+    Medium,
+    High,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Severity {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        Ok(match value.to_lowercase().as_str() {
+            "low" => Severity::Low,
+            "medium" => Severity::Medium,
+            "high" => Severity::High,
+            _ => Severity::Info,
+        })
+    }
+}
+
+/// A rule or detector firing, as sent to notifiers and returned by
+/// `GET /timeline`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alert {
+    pub rule: String,
+    pub severity: Severity,
+    pub message: String,
+    pub host: String,
+    /// `threshold` for fixed-limit rules, `anomaly` for learned baselines.
+    pub detection: String,
+    /// Maintenance window that suppressed the alert, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suppressed_by: Option<String>,
+    /// Ancestry of the offending process, self first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lineage: Vec<LineageEntry>,
+    /// Youngest live children of the offending process.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<LineageEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pod: Option<PodInfo>,
+    /// Inputs the rule evaluated when it fired. The shape depends on the
+    /// detector and is not covered by the stability guarantee.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub evidence: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<Annotations>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineageEntry {
+    pub pid: u32,
+    pub comm: String,
+    pub uid: u32,
+    /// Seconds since the process started, when known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub age_secs: Option<u64>,
+}
+
+/// Kubernetes pod a process belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PodInfo {
+    pub pod_name: String,
+    pub namespace: String,
+    pub container_name: String,
+    #[serde(default)]
+    pub owner_kind: Option<String>,
+    #[serde(default)]
+    pub owner_name: Option<String>,
+    /// `critical`, `high`, `medium` or `low`.
+    #[serde(default)]
+    pub priority: String,
+    #[serde(default)]
+    pub slo_tier: Option<String>,
+}
+
+/// Runbook, owner and labels copied from the rule that fired.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Annotations {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runbook_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alert_parses_minimal_payload_and_round_trips() {
+        let alert: Alert = serde_json::from_str(
+            r#"{"rule":"fork_storm","severity":"HIGH","message":"m","host":"h",
+                "detection":"threshold","future_field":1}"#,
+        )
+        .unwrap();
+        assert_eq!(alert.severity, Severity::High);
+        assert!(alert.lineage.is_empty() && alert.pod.is_none());
+
+        let json = serde_json::to_value(&alert).unwrap();
+        assert_eq!(json["severity"], "High");
+        assert!(json.get("lineage").is_none());
+        let back: Alert = serde_json::from_value(json).unwrap();
+        assert_eq!(back, alert);
+    }
+}
@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A circuit breaker action or recorded system event (`GET /incidents`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Incident {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    /// Unix epoch seconds
+    pub timestamp: i64,
+    /// `circuit_breaker`, `manual_kill`, `warning`, ...
+    pub event_type: String,
+
+    pub psi_cpu: f32,
+    pub psi_memory: f32,
+    pub cpu_percent: f32,
+    /// Comma-separated 1, 5 and 15 minute load averages
+    pub load_avg: String,
+
+    /// `kill`, `alert`, `throttle`, ...
+    pub action: String,
+    #[serde(default)]
+    pub target_pid: Option<i32>,
+    #[serde(default)]
+    pub target_name: Option<String>,
+
+    /// `SystemSnapshot` at the time of the incident, as a JSON string
+    #[serde(default)]
+    pub system_snapshot: Option<String>,
+
+    #[serde(default)]
+    pub llm_analysis: Option<String>,
+    /// Unix epoch seconds
+    #[serde(default)]
+    pub llm_analyzed_at: Option<i64>,
+    /// Who wrote `llm_analysis`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub analysis_source: Option<AnalysisSource>,
+
+    #[serde(default)]
+    pub recovery_time_ms: Option<i64>,
+    #[serde(default)]
+    pub psi_after: Option<f32>,
+
+    /// Remote sinks, keyed by sink name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub delivery: BTreeMap<String, SinkDelivery>,
+
+    /// Endpoints connected to the target when it was acted on
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependents: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum AnalysisSource {
+    Llm,
+    /// Rule-based summary written when the LLM missed its deadline; replaced
+    /// once a retry succeeds
+    Fallback,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum DeliveryState {
+    /// Last attempt failed; retrying
+    Pending,
+    Delivered,
+    /// Attempts exhausted or the endpoint rejected the request
+    Failed,
+}
+
+/// Outcome of sending the latest incident event to one sink
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SinkDelivery {
+    /// `trigger` or `resolve`
+    pub event: String,
+    pub state: DeliveryState,
+    pub attempts: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// Unix epoch seconds
+    pub updated_at: i64,
+}
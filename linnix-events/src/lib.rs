@@ -0,0 +1,21 @@
+//! Public data types emitted by Linnix
+//!
+//! These are the JSON shapes served by cognitod's API, SSE stream and
+//! webhooks, published as plain owned structs so integrators can deserialize
+//! them without depending on the daemon or its eBPF wire format. cognitod
+//! converts its internal types into these at the edge.
+//!
+//! Stability follows semver: fields are only added in minor releases, and
+//! new fields are always optional or defaulted so older payloads keep
+//! parsing. Removing or renaming a field, or changing its type, is a major
+//! release. Enums are `#[non_exhaustive]`; match them with a wildcard arm.
+
+mod alert;
+mod incident;
+mod process;
+mod snapshot;
+
+pub use alert::{Alert, Annotations, LineageEntry, PodInfo, Severity};
+pub use incident::{AnalysisSource, DeliveryState, Incident, SinkDelivery};
+pub use process::ProcessEvent;
+pub use snapshot::SystemSnapshot;
@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+/// One process event as sent on `/stream` and to event webhooks.
+///
+/// Payload fields (`data`, `data2`, `aux`, `aux2`) are reused per event type;
+/// `GET /events/schema` describes their meaning for each `event_type`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessEvent {
+    pub pid: u32,
+    pub ppid: u32,
+    pub uid: u32,
+    pub gid: u32,
+    /// cgroup v2 id of the task when the event was taken; 0 for node-wide
+    /// events and from daemons that predate it.
+    #[serde(default)]
+    pub cgroup_id: u64,
+    /// Task command name, at most 15 bytes.
+    pub comm: String,
+    /// Script run by an interpreter, resolved from the command line at exec.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script_path: Option<String>,
+    /// Executable path at exec, of which `comm` holds the first 15 bytes;
+    /// inherited on fork.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exe_path: Option<String>,
+    pub event_type: u32,
+    /// Name of `event_type`, e.g. `exec` or `exit`.
+    #[serde(default)]
+    pub event_type_name: String,
+    /// Kernel monotonic timestamp in nanoseconds.
+    pub ts_ns: u64,
+    #[serde(default)]
+    pub seq: u64,
+    /// Monotonic exit timestamp; 0 until the process exits.
+    #[serde(default)]
+    pub exit_time_ns: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_percent: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mem_percent: Option<f32>,
+    #[serde(default)]
+    pub data: u64,
+    #[serde(default)]
+    pub data2: u64,
+    #[serde(default)]
+    pub aux: u32,
+    #[serde(default)]
+    pub aux2: u32,
+}
+
+impl ProcessEvent {
+    pub fn exit_time(&self) -> Option<u64> {
+        (self.exit_time_ns != 0).then_some(self.exit_time_ns)
+    }
+}
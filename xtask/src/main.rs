@@ -7,12 +7,12 @@ fn main() -> Result<()> {
     if args.len() < 2 {
         eprintln!("Usage: cargo xtask <command>");
         eprintln!("Commands:");
-        eprintln!("  build-ebpf    Build eBPF programs");
+        eprintln!("  build-ebpf [--arch <arch>]    Build eBPF programs");
         std::process::exit(1);
     }
 
     match args[1].as_str() {
-        "build-ebpf" => build_ebpf(),
+        "build-ebpf" => build_ebpf(&args[2..]),
         _ => {
             eprintln!("Unknown command: {}", args[1]);
             std::process::exit(1);
@@ -20,19 +20,46 @@ fn main() -> Result<()> {
     }
 }
 
-fn build_ebpf() -> Result<()> {
-    let status = Command::new("cargo")
-        .args([
-            "build",
-            "--package",
-            "linnix-ai-ebpf-ebpf",
-            "--release",
-            "--target",
-            "bpfel-unknown-none",
-            "-Z",
-            "build-std=core",
-        ])
-        .env("RUSTUP_TOOLCHAIN", "nightly-2024-12-10")
+/// Value of `--arch <arch>` or `--arch=<arch>`, if given.
+fn arch_arg(args: &[String]) -> Result<Option<String>> {
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if let Some(arch) = arg.strip_prefix("--arch=") {
+            return Ok(Some(arch.to_string()));
+        }
+        if arg == "--arch" {
+            let arch = iter.next().context("--arch needs a value, e.g. aarch64")?;
+            return Ok(Some(arch.clone()));
+        }
+    }
+    Ok(None)
+}
+
+fn build_ebpf(args: &[String]) -> Result<()> {
+    let mut cmd = Command::new("cargo");
+    cmd.args([
+        "build",
+        "--package",
+        "linnix-ai-ebpf-ebpf",
+        "--release",
+        "--target",
+        "bpfel-unknown-none",
+        "-Z",
+        "build-std=core",
+    ])
+    .env("RUSTUP_TOOLCHAIN", "nightly-2024-12-10");
+
+    // kprobes read registers through `pt_regs`, whose layout aya-ebpf picks
+    // from `bpf_target_arch` (default: the build host's architecture).
+    if let Some(arch) = arch_arg(args)? {
+        cmd.env(
+            "CARGO_TARGET_BPFEL_UNKNOWN_NONE_RUSTFLAGS",
+            format!("--cfg bpf_target_arch=\"{arch}\""),
+        );
+        println!("Building eBPF programs for {arch}");
+    }
+
+    let status = cmd
         .status()
         .context("Failed to execute cargo build for eBPF")?;
 